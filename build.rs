@@ -8,8 +8,11 @@
 //!
 //! This build script:
 //! 1. Compiles assembly files (context switch)
-//! 2. Embeds files into the kernel as a ramdisk
+//! 2. Embeds files into the kernel as a ramdisk (contents listed in
+//!    ramdisk.toml)
 //! 3. Generates ramdisk.bin at build time
+//! 4. Embeds version information (git hash, build time)
+//! 5. Packs the kernel symbol table from a linker map, if given
 
 use std::env;
 use std::fs;
@@ -19,13 +22,7 @@ use std::io::Write;
 fn main() {
     // Tell cargo to rerun this script if source files change
     println!("cargo:rerun-if-changed=src/arch/amd64/switch.S");
-    println!("cargo:rerun-if-changed=test-userspace/");
-    println!("cargo:rerun-if-changed=test-userspace/shell/");
-    println!("cargo:rerun-if-changed=files/");
-    println!("cargo:rerun-if-changed=target/hello.elf");
-    println!("cargo:rerun-if-changed=target/counter.elf");
-    println!("cargo:rerun-if-changed=target/init.elf");
-    println!("cargo:rerun-if-changed=target/shell.elf");
+    println!("cargo:rerun-if-changed={}", MANIFEST_PATH);
 
     // Get the output directory
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
@@ -63,9 +60,12 @@ fn main() {
         name_offset: u32,
         data_offset: u32,
         size: u32,
-        _pad: u32,
+        flags: u32,
     }
 
+    // Entry flag: the data is a symbolic link's target
+    const RAMDISK_FILE_SYMLINK: u32 = 0x1;
+
     #[repr(C)]
     struct RamdiskSuperblock {
         magic: u32,           // 0x52555458 ("RUTX")
@@ -73,47 +73,49 @@ fn main() {
         files_offset: u32,
     }
 
+    // File contents start on this boundary (see src/fs/filemap.rs)
+    const RAMDISK_PAGE_SIZE: u32 = 4096;
+
     let ramdisk_output = out_dir.join("ramdisk.bin");
     let mut ramdisk = fs::File::create(&ramdisk_output)
         .expect("Failed to create ramdisk.bin");
 
-    // Collect files to embed
-    let mut files_to_embed = vec![
-        // Test files
-        ("files/test.txt", "test.txt"),
-    ];
-
-    // Check for ELF binaries in target directory
-    let potential_elf_files = vec![
-        ("target/hello.elf", "bin/hello"),
-        ("target/counter.elf", "bin/counter"),
-        ("target/init.elf", "bin/init"),
-        ("target/shell.elf", "bin/shell"),
-    ];
-
-    // Add ELF files if they exist
-    for (src_path, name) in potential_elf_files {
-        if PathBuf::from(src_path).exists() {
-            files_to_embed.push((src_path, name));
-            println!("cargo:warning=Embedding ELF: {} -> {}", src_path, name);
-        }
-    }
-
-    // Check which test userspace programs exist
-    let userspace_progs = vec![
-        "test-userspace/hello.c",
-        "test-userspace/counter.c",
-        "test-userspace/spinner.c",
-        "test-userspace/init.c",
-    ];
-
-    // Check for ELF binaries that were compiled
-    let elf_binaries = vec![
-        "test-userspace/hello.elf",
-        "test-userspace/counter.elf",
-        "test-userspace/spinner.elf",
-        "test-userspace/init.elf",
-    ];
+    // Collect files to embed from the manifest: name, contents and flags
+    let mut files_to_embed: Vec<(String, Vec<u8>, u32)> = Vec::new();
+    for entry in read_manifest(MANIFEST_PATH) {
+        if let Some(target) = entry.target {
+            files_to_embed.push((entry.dest, target.into_bytes(), RAMDISK_FILE_SYMLINK));
+            continue;
+        }
+
+        println!("cargo:rerun-if-changed={}", entry.src);
+        if let Some(source) = &entry.source {
+            println!("cargo:rerun-if-changed={}", source);
+        }
+
+        if PathBuf::from(&entry.src).exists() {
+            if entry.binary {
+                println!("cargo:warning=Embedding ELF: {} -> {}", entry.src, entry.dest);
+            }
+            let contents = fs::read(&entry.src)
+                .unwrap_or_else(|_| panic!("Failed to read file: {}", entry.src));
+            files_to_embed.push((entry.dest, contents, 0));
+        } else if entry.binary {
+            println!("cargo:warning=Skipping {}: {} not built", entry.dest, entry.src);
+        } else {
+            panic!("{}: missing file {}", MANIFEST_PATH, entry.src);
+        }
+    }
+
+    // Pack a manifest of the final contents for runtime verification
+    // (see src/fs/manifest.rs)
+    let mut packed_manifest = String::new();
+    for (name, contents, _flags) in &files_to_embed {
+        packed_manifest.push_str(&format!("{} {} {:016x}\n", name, contents.len(), fnv1a64(contents)));
+    }
+    let packed_manifest_path = out_dir.join("ramdisk.manifest");
+    fs::write(&packed_manifest_path, &packed_manifest).expect("Failed to write ramdisk.manifest");
+    files_to_embed.push((".manifest".to_string(), packed_manifest.into_bytes(), 0));
 
     // Calculate offsets
     let superblock_size = std::mem::size_of::<RamdiskSuperblock>() as u32;
@@ -126,23 +128,22 @@ fn main() {
     let mut file_entries = Vec::new();
 
     // First pass: calculate all offsets
-    for (src_path, _name) in &files_to_embed {
-        let contents = fs::read(src_path)
-            .expect(&format!("Failed to read file: {}", src_path));
-
-        let name_bytes = _name.as_bytes();
+    for (name, contents, flags) in &files_to_embed {
+        let name_bytes = name.as_bytes();
 
-        // Calculate offsets
+        // Calculate offsets: the name, then the contents on the next page
+        // boundary so whole pages can be mapped straight from the ramdisk
         let name_len = name_bytes.len() as u32;
+        let contents_offset = (data_offset + name_len + 1 + RAMDISK_PAGE_SIZE - 1) & !(RAMDISK_PAGE_SIZE - 1);
         file_entries.push(RamdiskFile {
             name_offset: data_offset,
-            data_offset: data_offset + name_len + 1, // +1 for null terminator
+            data_offset: contents_offset,
             size: contents.len() as u32,
-            _pad: 0,
+            flags: *flags,
         });
 
-        // Update data offset (after name + null terminator)
-        data_offset = data_offset + name_len + 1;
+        // Update data offset (after name + null terminator + padding + contents)
+        data_offset = contents_offset + contents.len() as u32;
     }
 
     // Second pass: write the ramdisk
@@ -181,15 +182,17 @@ fn main() {
     }
 
     // Write names and data
-    for (src_path, _name) in &files_to_embed {
+    for ((name, contents, _flags), entry) in files_to_embed.iter().zip(&file_entries) {
         // Write name (with null terminator)
-        ramdisk.write_all(_name.as_bytes()).unwrap();
+        ramdisk.write_all(name.as_bytes()).unwrap();
         ramdisk.write_all(&[0u8]).unwrap(); // null terminator
 
+        // Pad up to the page-aligned contents
+        let padding = entry.data_offset - (entry.name_offset + name.len() as u32 + 1);
+        ramdisk.write_all(&vec![0u8; padding as usize]).unwrap();
+
         // Write file contents
-        let contents = fs::read(src_path)
-            .expect(&format!("Failed to read file: {}", src_path));
-        ramdisk.write_all(&contents).unwrap();
+        ramdisk.write_all(contents).unwrap();
     }
 
     // Tell cargo where to find the ramdisk
@@ -202,9 +205,421 @@ fn main() {
         ramdisk.metadata().unwrap().len()
     );
 
+    // ============================================================================
+    // Part 2b: Sign ramdisk and embed the public key
+    // ============================================================================
+    //
+    // RUSTUX_RAMDISK_SIGNING_KEY: path to a 32-byte Ed25519 seed. When set, the
+    //   ramdisk is signed and the derived public key is embedded.
+    // RUSTUX_RAMDISK_PUBLIC_KEY: path to a 32-byte public key to embed instead
+    //   (e.g. when the signature is produced on a separate signing host).
+    // RUSTUX_RAMDISK_SIGNATURE: path to a 64-byte detached Ed25519 signature of
+    //   ramdisk.bin made on that host. Used with RUSTUX_RAMDISK_PUBLIC_KEY, in
+    //   place of RUSTUX_RAMDISK_SIGNING_KEY.
+    //
+    // Without a key, an all-zero key and signature are written and the kernel
+    // treats the build as a development build: it runs the ramdisk without
+    // checking it. A warning says so.
+
+    println!("cargo:rerun-if-env-changed=RUSTUX_RAMDISK_SIGNING_KEY");
+    println!("cargo:rerun-if-env-changed=RUSTUX_RAMDISK_PUBLIC_KEY");
+    println!("cargo:rerun-if-env-changed=RUSTUX_RAMDISK_SIGNATURE");
+
+    drop(ramdisk);
+    let ramdisk_data = fs::read(&ramdisk_output).expect("Failed to re-read ramdisk.bin");
+
+    let mut public_key = [0u8; 32];
+    let mut signature = [0u8; 64];
+
+    if let Ok(key_path) = env::var("RUSTUX_RAMDISK_SIGNING_KEY") {
+        println!("cargo:rerun-if-changed={}", key_path);
+        let seed_bytes = fs::read(&key_path)
+            .unwrap_or_else(|_| panic!("Failed to read signing key: {}", key_path));
+        let seed = ed25519_compact::Seed::from_slice(&seed_bytes)
+            .expect("Signing key must be a 32-byte Ed25519 seed");
+        let key_pair = ed25519_compact::KeyPair::from_seed(seed);
+
+        signature.copy_from_slice(&key_pair.sk.sign(&ramdisk_data, None)[..]);
+        public_key.copy_from_slice(&key_pair.pk[..]);
+        println!("cargo:warning=Ramdisk signed with {}", key_path);
+    }
+
+    if let Ok(pub_path) = env::var("RUSTUX_RAMDISK_PUBLIC_KEY") {
+        println!("cargo:rerun-if-changed={}", pub_path);
+        let pub_bytes = fs::read(&pub_path)
+            .unwrap_or_else(|_| panic!("Failed to read public key: {}", pub_path));
+        if pub_bytes.len() != 32 {
+            panic!("Public key must be 32 bytes: {}", pub_path);
+        }
+        public_key.copy_from_slice(&pub_bytes);
+    }
+
+    if let Ok(sig_path) = env::var("RUSTUX_RAMDISK_SIGNATURE") {
+        if env::var("RUSTUX_RAMDISK_SIGNING_KEY").is_ok() {
+            panic!("Set RUSTUX_RAMDISK_SIGNING_KEY or RUSTUX_RAMDISK_SIGNATURE, not both");
+        }
+        println!("cargo:rerun-if-changed={}", sig_path);
+        let sig_bytes = fs::read(&sig_path)
+            .unwrap_or_else(|_| panic!("Failed to read signature: {}", sig_path));
+        if sig_bytes.len() != 64 {
+            panic!("Signature must be 64 bytes: {}", sig_path);
+        }
+        signature.copy_from_slice(&sig_bytes);
+    }
+
+    if public_key == [0u8; 32] {
+        println!("cargo:warning=No ramdisk public key: the kernel will run the ramdisk unverified");
+    }
+
+    fs::write(out_dir.join("ramdisk.sig"), signature).expect("Failed to write ramdisk.sig");
+    fs::write(out_dir.join("ramdisk.pub"), public_key).expect("Failed to write ramdisk.pub");
+
+    // ============================================================================
+    // Part 2c: Version information
+    // ============================================================================
+    //
+    // RUSTUX_GIT_HASH: `git describe --always --dirty` of the source tree, or
+    //   "unknown" outside a git checkout.
+    // RUSTUX_BUILD_TIME: UTC build time. SOURCE_DATE_EPOCH overrides the clock
+    //   for reproducible builds.
+
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    if PathBuf::from(".git").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs/heads");
+    }
+
+    let git_hash = std::process::Command::new("git")
+        .args(["describe", "--always", "--dirty", "--abbrev=12"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let build_epoch = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=RUSTUX_GIT_HASH={}", git_hash);
+    println!("cargo:rustc-env=RUSTUX_BUILD_TIME={}", format_utc(build_epoch));
+
+    // ============================================================================
+    // Part 2d: Kernel symbol table
+    // ============================================================================
+    //
+    // RUSTUX_SYMBOL_MAP: lld-link map (`-map:`) of an earlier link of the same
+    //   sources. `cargo xtask` links once, then rebuilds with this set so
+    //   panic backtraces can name functions (see src/ksyms.rs for the table
+    //   format). Without it the table is empty.
+
+    println!("cargo:rerun-if-env-changed=RUSTUX_SYMBOL_MAP");
+    let (symbols, anchor) = match env::var("RUSTUX_SYMBOL_MAP") {
+        Ok(map_path) => {
+            println!("cargo:rerun-if-changed={}", map_path);
+            let text = fs::read_to_string(&map_path)
+                .unwrap_or_else(|_| panic!("Failed to read symbol map: {}", map_path));
+            let (symbols, anchor) = read_symbol_map(&text);
+            if anchor.is_none() {
+                println!("cargo:warning={}: no {} symbol, leaving the symbol table empty", map_path, KSYMS_ANCHOR);
+            }
+            (symbols, anchor)
+        }
+        Err(_) => (Vec::new(), None),
+    };
+
+    let ksyms_output = out_dir.join("ksyms.bin");
+    let table = match anchor {
+        Some(anchor) => pack_symbols(&symbols, anchor),
+        None => pack_symbols(&[], 0),
+    };
+    fs::write(&ksyms_output, table).expect("Failed to write ksyms.bin");
+    println!("cargo:rustc-env=RUSTUX_KSYMS_PATH={}", ksyms_output.display());
+
     // ============================================================================
     // Part 3: Link search path
     // ============================================================================
 
     println!("cargo:rustc-link-search={}", out_dir.display());
 }
+
+/// Ramdisk manifest (see the comments in the file for the format)
+const MANIFEST_PATH: &str = "ramdisk.toml";
+
+/// An entry in the ramdisk manifest
+struct ManifestEntry {
+    /// File to pack
+    src: String,
+    /// Path inside the ramdisk
+    dest: String,
+    /// `[[binary]]` entry (optional) rather than `[[file]]` (required)
+    binary: bool,
+    /// Source directory to watch (`crate` key)
+    source: Option<String>,
+    /// `[[symlink]]` entry: the link's target (`target` key)
+    target: Option<String>,
+}
+
+/// Read the ramdisk manifest
+///
+/// Supports the subset of TOML the manifest uses: `[[file]]`,
+/// `[[binary]]` and `[[symlink]]` array-of-table headers,
+/// `key = "string"` pairs and `#` comments.
+fn read_manifest(path: &str) -> Vec<ManifestEntry> {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|_| panic!("Failed to read ramdisk manifest: {}", path));
+
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    for (n, raw) in text.lines().enumerate() {
+        let line = raw.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let kind = match line {
+            "[[file]]" => Some((false, None)),
+            "[[binary]]" => Some((true, None)),
+            "[[symlink]]" => Some((false, Some(String::new()))),
+            _ => None,
+        };
+        if let Some((binary, target)) = kind {
+            entries.push(ManifestEntry { src: String::new(), dest: String::new(), binary, source: None, target });
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .filter(|(_, v)| v.len() >= 2 && v.starts_with('"') && v.ends_with('"'))
+            .unwrap_or_else(|| panic!("{}:{}: expected key = \"value\"", path, n + 1));
+        let value = value[1..value.len() - 1].to_string();
+
+        let entry = entries
+            .last_mut()
+            .unwrap_or_else(|| panic!("{}:{}: key outside of an entry", path, n + 1));
+        match key {
+            "src" => entry.src = value,
+            "dest" => entry.dest = value.trim_start_matches('/').to_string(),
+            "crate" if entry.binary => entry.source = Some(value),
+            "target" if entry.target.is_some() => entry.target = Some(value),
+            _ => panic!("{}:{}: unknown key {}", path, n + 1, key),
+        }
+    }
+
+    for entry in &entries {
+        match &entry.target {
+            Some(target) if target.is_empty() || entry.dest.is_empty() || !entry.src.is_empty() => {
+                panic!("{}: a symlink needs dest and target (and no src)", path);
+            }
+            None if entry.src.is_empty() || entry.dest.is_empty() => {
+                panic!("{}: every entry needs src and dest", path);
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Function whose address tells the kernel which link a symbol table is from
+const KSYMS_ANCHOR: &str = "rx_ksyms_anchor";
+
+/// Read the function symbols of an lld-link map file
+///
+/// Symbol lines look like
+///
+/// ```text
+///  0001:00001230       _ZN6rustux4main17h0123456789abcdefE 0000000140002230 f   rustux.o
+/// ```
+///
+/// and only symbols in code sections (class `CODE` in the section list)
+/// or flagged `f` are kept.
+///
+/// # Returns
+///
+/// `(rva, demangled name)` pairs sorted by RVA, and the RVA of
+/// [`KSYMS_ANCHOR`] if the map has it
+fn read_symbol_map(text: &str) -> (Vec<(u32, String)>, Option<u32>) {
+    let mut base: Option<u64> = None;
+    let mut code_sections: Vec<u16> = Vec::new();
+    let mut symbols: Vec<(u32, String)> = Vec::new();
+    let mut anchor = None;
+
+    for line in text.lines() {
+        if let Some(rest) = line.trim().strip_prefix("Preferred load address is ") {
+            base = u64::from_str_radix(rest.trim(), 16).ok();
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(section) = fields
+            .first()
+            .and_then(|f| f.split_once(':'))
+            .and_then(|(s, _)| u16::from_str_radix(s, 16).ok())
+        else {
+            continue;
+        };
+
+        // Section list: "0001:00000000 00012345H .text CODE"
+        if fields.len() == 4 && fields[1].ends_with('H') {
+            if fields[3] == "CODE" {
+                code_sections.push(section);
+            }
+            continue;
+        }
+
+        let (Some(base), Some(name), Some(va)) = (base, fields.get(1), fields.get(2)) else {
+            continue;
+        };
+        let Some(rva) = u64::from_str_radix(va, 16)
+            .ok()
+            .and_then(|va| va.checked_sub(base))
+            .and_then(|rva| u32::try_from(rva).ok())
+        else {
+            continue;
+        };
+        if *name == KSYMS_ANCHOR {
+            anchor = Some(rva);
+        }
+        if code_sections.contains(&section) || fields.get(3) == Some(&"f") {
+            symbols.push((rva, demangle(name)));
+        }
+    }
+
+    symbols.sort_by_key(|&(rva, _)| rva);
+    symbols.dedup_by_key(|&mut (rva, _)| rva);
+    (symbols, anchor)
+}
+
+/// Demangle a legacy Rust symbol (`_ZN...E`), dropping the hash
+///
+/// Other names (`#[no_mangle]` functions, v0 mangling) are kept as they
+/// are.
+fn demangle(name: &str) -> String {
+    let Some(mut rest) = name.strip_prefix("_ZN").or_else(|| name.strip_prefix("__ZN")) else {
+        return name.to_string();
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    while !rest.starts_with('E') {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let Ok(len) = rest[..digits].parse::<usize>() else {
+            return name.to_string();
+        };
+        if rest.len() < digits + len {
+            return name.to_string();
+        }
+        parts.push(&rest[digits..digits + len]);
+        rest = &rest[digits + len..];
+    }
+
+    // The last path component is the hash: "h" and 16 hex digits
+    if parts
+        .last()
+        .is_some_and(|p| p.len() == 17 && p.starts_with('h') && p[1..].bytes().all(|b| b.is_ascii_hexdigit()))
+    {
+        parts.pop();
+    }
+    parts.iter().map(|p| unescape_symbol(p)).collect::<Vec<_>>().join("::")
+}
+
+/// Decode the `$LT$`-style escapes of a legacy mangled path component
+fn unescape_symbol(part: &str) -> String {
+    // Components starting with an escape get a leading underscore
+    let mut rest = if part.starts_with("_$") { &part[1..] } else { part };
+    let mut out = String::new();
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("..") {
+            out.push_str("::");
+            rest = after;
+            continue;
+        }
+        if c == '$' {
+            let decoded = rest[1..].find('$').and_then(|end| {
+                let ch = match &rest[1..1 + end] {
+                    "SP" => '@',
+                    "BP" => '*',
+                    "RF" => '&',
+                    "LT" => '<',
+                    "GT" => '>',
+                    "LP" => '(',
+                    "RP" => ')',
+                    "C" => ',',
+                    code => code
+                        .strip_prefix('u')
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .and_then(char::from_u32)?,
+                };
+                Some((ch, end + 2))
+            });
+            if let Some((ch, len)) = decoded {
+                out.push(ch);
+                rest = &rest[len..];
+                continue;
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Pack symbols in the `ksyms.bin` format (see src/ksyms.rs)
+fn pack_symbols(symbols: &[(u32, String)], anchor: u32) -> Vec<u8> {
+    let names_len: usize = symbols.iter().map(|(_, name)| name.len()).sum();
+    let mut out = Vec::with_capacity(16 + symbols.len() * 8 + names_len);
+    out.extend_from_slice(b"KSYM");
+    out.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    out.extend_from_slice(&anchor.to_le_bytes());
+    out.extend_from_slice(&(names_len as u32).to_le_bytes());
+
+    let mut name_off = 0u32;
+    for (rva, name) in symbols {
+        out.extend_from_slice(&rva.to_le_bytes());
+        out.extend_from_slice(&name_off.to_le_bytes());
+        name_off += name.len() as u32;
+    }
+    for (_, name) in symbols {
+        out.extend_from_slice(name.as_bytes());
+    }
+    out
+}
+
+/// FNV-1a (64-bit), matching `fs::manifest::fnv1a64` in the kernel
+fn fnv1a64(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS UTC`
+fn format_utc(epoch: u64) -> String {
+    let days = (epoch / 86400) as i64;
+    let secs = epoch % 86400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year, month, day, secs / 3600, (secs / 60) % 60, secs % 60
+    )
+}
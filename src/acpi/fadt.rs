@@ -0,0 +1,284 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! FADT (Fixed ACPI Description Table) parsing and power control
+//!
+//! The FADT locates the PM1 control registers, used to enter a sleep
+//! state, and the reset register. Entering S5 (soft off) also needs the
+//! `SLP_TYPa`/`SLP_TYPb` values of the `\_S5` object in the DSDT. That is
+//! AML, and the kernel has no AML interpreter: [`find_s5`] scans the DSDT
+//! for the object's byte pattern instead. Firmware emits `\_S5` as a plain
+//! name bound to a package of constants, so the scan finds it in practice.
+
+use super::rsdt::SDTHeader;
+
+/// FADT signature
+pub const FADT_SIGNATURE: &[u8; 4] = b"FACP";
+
+/// DSDT signature
+pub const DSDT_SIGNATURE: &[u8; 4] = b"DSDT";
+
+/// PM1 control: ACPI mode is enabled
+const PM1_SCI_EN: u16 = 1 << 0;
+
+/// PM1 control: sleep type field
+const PM1_SLP_TYP_SHIFT: u16 = 10;
+const PM1_SLP_TYP_MASK: u16 = 0b111 << PM1_SLP_TYP_SHIFT;
+
+/// PM1 control: enter the sleep state in `SLP_TYP`
+const PM1_SLP_EN: u16 = 1 << 13;
+
+/// FADT flag: the reset register is supported
+const FLAG_RESET_REG_SUP: u32 = 1 << 10;
+
+/// Generic address structure space ID for system I/O
+const GAS_SYSTEM_IO: u8 = 1;
+
+/// Polls of PM1 control while waiting for the firmware to enter ACPI mode
+const ACPI_ENABLE_POLLS: u32 = 1_000_000;
+
+/// AML opcodes used by [`find_s5`]
+const AML_NAME_OP: u8 = 0x08;
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_ZERO_OP: u8 = 0x00;
+const AML_ONE_OP: u8 = 0x01;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+const AML_ROOT_CHAR: u8 = b'\\';
+
+/// `SLP_TYP` values of one sleep state, for PM1a and PM1b control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepType {
+    pub a: u8,
+    pub b: u8,
+}
+
+/// Fixed hardware used to power off and reset the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerInfo {
+    /// SMI command port (0 if the machine is always in ACPI mode)
+    pub smi_cmd: u32,
+    /// Value written to `smi_cmd` to enter ACPI mode
+    pub acpi_enable: u8,
+    /// PM1a control port
+    pub pm1a_cnt: u16,
+    /// PM1b control port (0 if absent)
+    pub pm1b_cnt: u16,
+    /// Reset register port and the value to write to it, if the reset
+    /// register is supported and lives in I/O space
+    pub reset: Option<(u16, u8)>,
+    /// Physical address of the DSDT
+    pub dsdt: u64,
+}
+
+fn u8_at(bytes: &[u8], offset: usize) -> u8 {
+    bytes[offset]
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from(u32_at(bytes, offset)) | (u64::from(u32_at(bytes, offset + 4)) << 32)
+}
+
+impl PowerInfo {
+    /// Parse a FADT, header included
+    ///
+    /// Returns `None` if the table is too short for the ACPI 1.0 fields or
+    /// has no PM1a control block.
+    pub fn parse(fadt: &[u8]) -> Option<Self> {
+        // ACPI 1.0 FADT ends after the flags at offset 112
+        if fadt.len() < 116 {
+            return None;
+        }
+        let pm1a_cnt = u32_at(fadt, 64) as u16;
+        if pm1a_cnt == 0 {
+            return None;
+        }
+
+        // ACPI 2.0+: reset register (GAS at 116, value at 128), X_DSDT at 140
+        let flags = u32_at(fadt, 112);
+        let reset = if fadt.len() >= 129 && flags & FLAG_RESET_REG_SUP != 0 && u8_at(fadt, 116) == GAS_SYSTEM_IO {
+            Some((u64_at(fadt, 120) as u16, u8_at(fadt, 128)))
+        } else {
+            None
+        };
+        let x_dsdt = if fadt.len() >= 148 { u64_at(fadt, 140) } else { 0 };
+
+        Some(Self {
+            smi_cmd: u32_at(fadt, 48),
+            acpi_enable: u8_at(fadt, 52),
+            pm1a_cnt,
+            pm1b_cnt: u32_at(fadt, 68) as u16,
+            reset,
+            dsdt: if x_dsdt != 0 { x_dsdt } else { u64::from(u32_at(fadt, 40)) },
+        })
+    }
+
+    /// Enter S5 (soft off)
+    ///
+    /// Switches the firmware to ACPI mode first if needed. Returns only if
+    /// the machine is still running afterwards.
+    ///
+    /// # Safety
+    ///
+    /// Powers the machine off: everything worth keeping must be written out.
+    pub unsafe fn enter_s5(&self, s5: SleepType) {
+        use crate::arch::amd64::ioport::{inw, outb, outw};
+
+        if inw(self.pm1a_cnt) & PM1_SCI_EN == 0 && self.smi_cmd != 0 && self.acpi_enable != 0 {
+            outb(self.smi_cmd as u16, self.acpi_enable);
+            for _ in 0..ACPI_ENABLE_POLLS {
+                if inw(self.pm1a_cnt) & PM1_SCI_EN != 0 {
+                    break;
+                }
+                core::hint::spin_loop();
+            }
+        }
+
+        for (port, slp_typ) in [(self.pm1a_cnt, s5.a), (self.pm1b_cnt, s5.b)] {
+            if port != 0 {
+                let value = inw(port) & !PM1_SLP_TYP_MASK;
+                outw(port, value | (u16::from(slp_typ & 0b111) << PM1_SLP_TYP_SHIFT) | PM1_SLP_EN);
+            }
+        }
+    }
+
+    /// Reset the machine through the FADT reset register
+    ///
+    /// Returns if there is no usable reset register, or it did not work.
+    ///
+    /// # Safety
+    ///
+    /// Resets the machine: everything worth keeping must be written out.
+    pub unsafe fn reset(&self) {
+        if let Some((port, value)) = self.reset {
+            crate::arch::amd64::ioport::outb(port, value);
+        }
+    }
+}
+
+/// Read one AML integer constant at `aml[*pos]`, advancing `pos`
+fn aml_byte_const(aml: &[u8], pos: &mut usize) -> Option<u8> {
+    let value = match *aml.get(*pos)? {
+        AML_ZERO_OP => 0,
+        AML_ONE_OP => 1,
+        AML_BYTE_PREFIX => {
+            *pos += 1;
+            *aml.get(*pos)?
+        }
+        _ => return None,
+    };
+    *pos += 1;
+    Some(value)
+}
+
+/// Parse `Package (n) { SLP_TYPa, SLP_TYPb, ... }` at the start of `aml`
+fn parse_s5_package(aml: &[u8]) -> Option<SleepType> {
+    if *aml.first()? != AML_PACKAGE_OP {
+        return None;
+    }
+    // PkgLength: bits 7:6 of the lead byte count the bytes that follow it
+    let pkg_length_bytes = 1 + usize::from(*aml.get(1)? >> 6);
+    // Skip the opcode, PkgLength and NumElements
+    let mut pos = 1 + pkg_length_bytes + 1;
+    let a = aml_byte_const(aml, &mut pos)?;
+    let b = aml_byte_const(aml, &mut pos)?;
+    Some(SleepType { a, b })
+}
+
+/// Find the `SLP_TYP` values of S5 in DSDT (or SSDT) AML
+///
+/// Looks for `Name (\_S5, Package () { ... })` without interpreting the
+/// surrounding code.
+pub fn find_s5(aml: &[u8]) -> Option<SleepType> {
+    (1..aml.len().saturating_sub(3))
+        .filter(|&i| &aml[i..i + 4] == b"_S5_")
+        .filter(|&i| aml[i - 1] == AML_NAME_OP || (i >= 2 && aml[i - 1] == AML_ROOT_CHAR && aml[i - 2] == AML_NAME_OP))
+        .find_map(|i| parse_s5_package(&aml[i + 4..]))
+}
+
+/// Bytes of a table, header included
+///
+/// # Safety
+///
+/// `header` must be the start of a mapped table of `header.length` bytes.
+unsafe fn table_bytes(header: &'static SDTHeader) -> &'static [u8] {
+    let length = header.length as usize;
+    core::slice::from_raw_parts(header as *const SDTHeader as *const u8, length)
+}
+
+/// Find the FADT and the S5 sleep type
+///
+/// # Returns
+/// * `Some((PowerInfo, Some(SleepType)))` if the machine can be powered off
+/// * `Some((PowerInfo, None))` if only the FADT was usable (reset may work)
+/// * `None` if there is no usable FADT
+pub fn find_power_info(rsdp: &super::rsdp::Rsdp) -> Option<(PowerInfo, Option<SleepType>)> {
+    unsafe {
+        let fadt = super::rsdt::find_table_in_rsdt(rsdp, FADT_SIGNATURE)?;
+        let info = PowerInfo::parse(table_bytes(fadt))?;
+
+        let dsdt = (info.dsdt as *const SDTHeader).as_ref();
+        let s5 = match dsdt {
+            Some(dsdt) if &dsdt.signature == DSDT_SIGNATURE => {
+                find_s5(&table_bytes(dsdt)[core::mem::size_of::<SDTHeader>()..])
+            }
+            _ => None,
+        };
+        Some((info, s5))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_s5() {
+        // Name (\_S5, Package (0x04) { 0x05, Zero, Zero, Zero })
+        let aml = [0x10, 0x08, b'\\', b'_', b'S', b'5', b'_', 0x12, 0x08, 0x04, 0x0A, 0x05, 0x00, 0x00, 0x00];
+        assert_eq!(find_s5(&aml), Some(SleepType { a: 5, b: 0 }));
+
+        // Name (_S5, Package (0x02) { One, 0x07 }), after an unrelated use of the name
+        let aml = [b'_', b'S', b'5', b'_', 0x08, b'_', b'S', b'5', b'_', 0x12, 0x06, 0x02, 0x01, 0x0A, 0x07];
+        assert_eq!(find_s5(&aml), Some(SleepType { a: 1, b: 7 }));
+
+        // Method (_S5_) is not a package
+        let aml = [0x14, 0x06, b'_', b'S', b'5', b'_', 0x00];
+        assert_eq!(find_s5(&aml), None);
+        assert_eq!(find_s5(&[]), None);
+    }
+
+    #[test]
+    fn test_parse_fadt() {
+        let mut fadt = [0u8; 244];
+        fadt[40..44].copy_from_slice(&0x7FE0_0000u32.to_le_bytes()); // DSDT
+        fadt[48..52].copy_from_slice(&0xB2u32.to_le_bytes()); // SMI_CMD
+        fadt[52] = 0xF1; // ACPI_ENABLE
+        fadt[64..68].copy_from_slice(&0x604u32.to_le_bytes()); // PM1a_CNT_BLK
+        fadt[112..116].copy_from_slice(&FLAG_RESET_REG_SUP.to_le_bytes());
+        fadt[116] = GAS_SYSTEM_IO;
+        fadt[120..128].copy_from_slice(&0xCF9u64.to_le_bytes());
+        fadt[128] = 0x06;
+
+        let info = PowerInfo::parse(&fadt).unwrap();
+        assert_eq!(info.pm1a_cnt, 0x604);
+        assert_eq!(info.pm1b_cnt, 0);
+        assert_eq!((info.smi_cmd, info.acpi_enable), (0xB2, 0xF1));
+        assert_eq!(info.reset, Some((0xCF9, 0x06)));
+        assert_eq!(info.dsdt, 0x7FE0_0000);
+
+        // X_DSDT wins over DSDT
+        fadt[140..148].copy_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        assert_eq!(PowerInfo::parse(&fadt).unwrap().dsdt, 0x1_0000_0000);
+
+        // ACPI 1.0 table: no reset register
+        assert_eq!(PowerInfo::parse(&fadt[..116]).unwrap().reset, None);
+        assert_eq!(PowerInfo::parse(&fadt[..100]), None);
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! HPET (High Precision Event Timer) table parsing
+//!
+//! The HPET table locates the event timer block and summarizes what it
+//! can do. The driver ([`crate::arch::amd64::hpet`]) reads the block's
+//! own capability register for everything but the address.
+
+use super::rsdt::SDTHeader;
+
+/// HPET table signature
+pub const HPET_SIGNATURE: &[u8; 4] = b"HPET";
+
+/// Generic address structure space ID for system memory
+const GAS_SYSTEM_MEMORY: u8 = 0;
+
+/// Event timer block ID: 64-bit main counter
+const BLOCK_COUNT_SIZE_CAP: u32 = 1 << 13;
+
+/// Event timer block ID: legacy replacement routing supported
+const BLOCK_LEGACY_REPLACEMENT_CAP: u32 = 1 << 15;
+
+/// An HPET block as described by the HPET table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HpetInfo {
+    /// Physical address of the register block
+    pub address: u64,
+    /// HPET sequence number (0 for the first block)
+    pub number: u8,
+    /// Comparators in the block
+    pub comparators: u8,
+    /// Whether the main counter is 64 bits wide
+    pub counter_64bit: bool,
+    /// Whether legacy replacement routing (IRQ0/IRQ8) is supported
+    pub legacy_replacement: bool,
+    /// PCI vendor ID of the block
+    pub vendor_id: u16,
+    /// Least main counter ticks a periodic comparator can be set to
+    pub min_tick: u16,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from(u32_at(bytes, offset)) | (u64::from(u32_at(bytes, offset + 4)) << 32)
+}
+
+impl HpetInfo {
+    /// Parse an HPET table, header included
+    ///
+    /// Returns `None` if the table is too short or the block is not in
+    /// memory space.
+    pub fn parse(hpet: &[u8]) -> Option<Self> {
+        // Block ID at 36, base address GAS at 40, number at 52, min tick at 53
+        if hpet.len() < 56 || hpet[40] != GAS_SYSTEM_MEMORY {
+            return None;
+        }
+        let block_id = u32_at(hpet, 36);
+        let address = u64_at(hpet, 44);
+        if address == 0 {
+            return None;
+        }
+
+        Some(Self {
+            address,
+            number: hpet[52],
+            comparators: ((block_id >> 8) & 0x1F) as u8 + 1,
+            counter_64bit: block_id & BLOCK_COUNT_SIZE_CAP != 0,
+            legacy_replacement: block_id & BLOCK_LEGACY_REPLACEMENT_CAP != 0,
+            vendor_id: (block_id >> 16) as u16,
+            min_tick: u16_at(hpet, 53),
+        })
+    }
+}
+
+/// Find and parse the HPET table
+///
+/// # Returns
+/// * `Some(HpetInfo)` for the first HPET block
+/// * `None` if there is no HPET table or it is not usable
+pub fn find_hpet(rsdp: &super::rsdp::Rsdp) -> Option<HpetInfo> {
+    unsafe {
+        let header = super::rsdt::find_table_in_rsdt(rsdp, HPET_SIGNATURE)?;
+        let bytes = core::slice::from_raw_parts(header as *const SDTHeader as *const u8, header.length as usize);
+        HpetInfo::parse(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hpet() {
+        let mut hpet = [0u8; 56];
+        // QEMU: vendor 0x8086, legacy replacement, 64-bit, 3 comparators
+        hpet[36..40].copy_from_slice(&0x8086_A201u32.to_le_bytes());
+        hpet[44..52].copy_from_slice(&0xFED0_0000u64.to_le_bytes());
+        hpet[53..55].copy_from_slice(&0x80u16.to_le_bytes());
+        let info = HpetInfo::parse(&hpet).unwrap();
+        assert_eq!(info.address, 0xFED0_0000);
+        assert_eq!(info.comparators, 3);
+        assert!(info.counter_64bit && info.legacy_replacement);
+        assert_eq!((info.vendor_id, info.min_tick), (0x8086, 0x80));
+
+        // I/O space, or truncated
+        hpet[40] = 1;
+        assert_eq!(HpetInfo::parse(&hpet), None);
+        assert_eq!(HpetInfo::parse(&hpet[..52]), None);
+    }
+}
@@ -125,11 +125,24 @@ pub struct InterruptSourceOverrideEntry {
     pub flags: u16,
 }
 
+/// Local APIC Address Override entry (Type 5)
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct LocalApicAddressOverrideEntry {
+    pub header: MadtEntryHeader,
+    /// Reserved
+    pub reserved: u16,
+    /// Local APIC address (64-bit physical address)
+    pub address: u64,
+}
+
 /// Parsed MADT table
 #[derive(Debug)]
 pub struct ParsedMadt {
     /// Physical address of local APIC
     pub local_apic_address: u32,
+    /// 64-bit local APIC address, if a Type 5 entry overrides it
+    pub local_apic_address_override: Option<u64>,
     /// Flags
     pub flags: u32,
     /// Number of local APIC entries
@@ -147,6 +160,13 @@ pub struct ParsedMadt {
 }
 
 impl ParsedMadt {
+    /// Physical address of the local APICs
+    ///
+    /// The 64-bit override if there is one, the header field otherwise.
+    pub fn local_apic_base(&self) -> u64 {
+        self.local_apic_address_override.unwrap_or(self.local_apic_address as u64)
+    }
+
     /// Get the first I/O APIC address
     ///
     /// This is a convenience function for systems with a single I/O APIC.
@@ -190,6 +210,7 @@ impl ParsedMadt {
 pub unsafe fn parse_madt(madt: &Madt) -> Option<ParsedMadt> {
     let mut result = ParsedMadt {
         local_apic_address: madt.local_apic_address,
+        local_apic_address_override: None,
         flags: madt.flags,
         local_apic_count: 0,
         local_apics: [LocalApicEntry {
@@ -260,6 +281,11 @@ pub unsafe fn parse_madt(madt: &Madt) -> Option<ParsedMadt> {
                     result.override_count += 1;
                 }
             }
+            5 => {
+                // Local APIC Address Override
+                let entry = &*(entry_ptr as *const LocalApicAddressOverrideEntry);
+                result.local_apic_address_override = Some(entry.address);
+            }
             _ => {
                 // Unknown entry type - skip
             }
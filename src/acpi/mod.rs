@@ -10,6 +10,8 @@
 //! - RSDP (Root System Description Pointer) discovery
 //! - RSDT/XSDT (Root System Description Table) parsing
 //! - MADT (Multiple APIC Description Table) parsing for interrupt controller discovery
+//! - FADT (Fixed ACPI Description Table) parsing for power off and reset
+//! - HPET (High Precision Event Timer) table parsing to locate the HPET
 //!
 //! # Example
 //! ```ignore
@@ -27,6 +29,8 @@
 pub mod rsdp;
 pub mod rsdt;
 pub mod madt;
+pub mod fadt;
+pub mod hpet;
 
 pub use rsdp::{Rsdp, find_rsdp};
 pub use rsdt::{Rsdt, SDTHeader};
@@ -38,3 +42,5 @@ pub use madt::{
     LocalApicEntry,
     InterruptSourceOverrideEntry,
 };
+pub use fadt::{PowerInfo, SleepType, find_power_info};
+pub use hpet::{HpetInfo, find_hpet};
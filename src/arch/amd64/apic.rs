@@ -9,6 +9,13 @@
 //! This module provides the actual APIC implementation for x86_64,
 //! including Local APIC and I/O APIC support.
 
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use crate::object::CachePolicy;
+use crate::arch::amd64::mm::RxStatus;
+use crate::time::clockevents::ClockEvents;
+use crate::time::Duration;
+use crate::{kdebug, kinfo};
+
 /// Local APIC MMIO register offsets
 #[repr(C)]
 pub struct LocalApicRegisters {
@@ -65,48 +72,115 @@ pub struct LocalApicRegisters {
 
 /// Local APIC base address (default from x86_64 CPU)
 ///
-/// NOTE: This should be discovered via ACPI MADT in production.
-/// Using the standard x86 default address for now.
+/// Used until [`set_local_apic_base`] is given the address from the MADT.
+/// MSI messages always target this address.
 pub const LOCAL_APIC_DEFAULT_BASE: u64 = 0xFEE0_0000;
 
 /// I/O APIC base address
 ///
-/// NOTE: This should be discovered via ACPI MADT in production.
-/// Using the standard x86 default address for now.
+/// Used, as the only I/O APIC with GSIs from 0, until [`ioapic_add`]
+/// registers one.
 pub const IOAPIC_DEFAULT_BASE: u64 = 0xFEC0_0000;
 
+/// Most I/O APICs [`ioapic_add`] takes
+pub const MAX_IOAPICS: usize = 8;
+
+/// Virtual address of the Local APIC registers
+///
+/// The firmware's identity mapping of the default base until
+/// [`set_local_apic_base`] maps the real one.
+static LAPIC_BASE: AtomicUsize = AtomicUsize::new(LOCAL_APIC_DEFAULT_BASE as usize);
+
+/// A registered I/O APIC
+///
+/// Atomics, since the affinity balancer reads them from the timer
+/// interrupt.
+struct IoApic {
+    /// Virtual address of the registers
+    base: AtomicUsize,
+    /// First GSI it serves
+    gsi_base: AtomicU32,
+    /// Redirection entries (GSIs served)
+    pins: AtomicU32,
+}
+
+impl IoApic {
+    const fn new() -> Self {
+        Self { base: AtomicUsize::new(0), gsi_base: AtomicU32::new(0), pins: AtomicU32::new(0) }
+    }
+}
+
+static IOAPICS: [IoApic; MAX_IOAPICS] = [const { IoApic::new() }; MAX_IOAPICS];
+
+/// Entries of [`IOAPICS`] in use
+static IOAPIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Trigger mode and polarity of an I/O APIC input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrqMode {
+    /// Level triggered (edge triggered otherwise)
+    pub level: bool,
+    /// Active low (active high otherwise)
+    pub active_low: bool,
+}
+
+impl IrqMode {
+    /// ISA bus interrupts: edge triggered, active high
+    pub const ISA: Self = Self { level: false, active_low: false };
+
+    /// PCI interrupts: level triggered, active low
+    pub const PCI: Self = Self { level: true, active_low: true };
+
+    /// Decode the MPS INTI flags of a MADT entry
+    ///
+    /// Polarity is in bits 0-1 and trigger mode in bits 2-3; a field of
+    /// 0 ("conforms to the bus") is taken from `bus`.
+    pub const fn from_inti(flags: u16, bus: Self) -> Self {
+        let active_low = match flags & 0x3 {
+            1 => false,
+            3 => true,
+            _ => bus.active_low,
+        };
+        let level = match (flags >> 2) & 0x3 {
+            1 => false,
+            3 => true,
+            _ => bus.level,
+        };
+        Self { level, active_low }
+    }
+
+    /// Redirection entry bits: polarity (13) and trigger mode (15)
+    const fn redir_bits(self) -> u32 {
+        ((self.active_low as u32) << 13) | ((self.level as u32) << 15)
+    }
+}
+
+/// Map device registers at `paddr`
+///
+/// Falls back to the firmware's identity mapping if the VMM is not up.
+fn map_mmio(paddr: u64, size: usize) -> usize {
+    crate::mm::vmm::ioremap(paddr, size, CachePolicy::Uncached).unwrap_or(paddr as usize)
+}
+
+/// Use the Local APIC registers at `paddr` (from the MADT)
+///
+/// Call on the BSP before the APs start; they share the mapping.
+pub fn set_local_apic_base(paddr: u64) {
+    LAPIC_BASE.store(map_mmio(paddr, 0x1000), Ordering::Release);
+    kinfo!("[LAPIC] base {:#x}", paddr);
+}
+
 /// Disable the legacy 8259A PIC
 ///
 /// When using APIC mode, the legacy 8259A PIC must be disabled
 /// by masking all IRQs. Otherwise, it will intercept interrupts
 /// before they reach the IOAPIC.
 ///
-/// The 8259A has two chips:
-/// - Master PIC: Command port 0x20, Data port 0x21
-/// - Slave PIC: Command port 0xA0, Data port 0xA1
-///
-/// To disable, we mask all IRQs by writing 0xFF to both data ports.
+/// Masking alone is not enough: the PICs can still raise spurious
+/// IRQ7/IRQ15, which at their reset vectors alias CPU exceptions. They
+/// are remapped first; see [`super::pic`].
 pub fn pic_disable() {
-    const PIC1_CMD: u16 = 0x20;
-    const PIC1_DATA: u16 = 0x21;
-    const PIC2_CMD: u16 = 0xA0;
-    const PIC2_DATA: u16 = 0xA1;
-
-    unsafe {
-        let msg = b"[PIC] Disabling 8259A PIC...\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-
-        // Mask all IRQs on both PICs (write 0xFF to data ports)
-        core::arch::asm!("out dx, al", in("dx") PIC1_DATA, in("al") 0xFFu8, options(nostack));
-        core::arch::asm!("out dx, al", in("dx") PIC2_DATA, in("al") 0xFFu8, options(nostack));
-
-        let msg = b"[PIC] All IRQs masked\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-    }
+    unsafe { super::pic::init() };
 }
 
 /// Initialize the Local APIC
@@ -118,15 +192,18 @@ pub fn apic_local_init() {
     // This must be done before using IOAPIC, otherwise the PIC
     // will intercept interrupts before they reach the IOAPIC
     pic_disable();
+    apic_local_enable();
+}
 
-    unsafe {
-        let apic_base = LOCAL_APIC_DEFAULT_BASE;
-        let svr_offset = 0x70; // Spurious Interrupt Vector Register
-
-        let svr = (apic_base + svr_offset as u64) as *mut u32;
+/// Software-enable this CPU's Local APIC
+///
+/// [`apic_local_init`] without the PIC setup, for application processors.
+pub fn apic_local_enable() {
+    const LAPIC_SVR: u64 = 0xF0; // Spurious Interrupt Vector Register
 
+    unsafe {
         // Enable Local APIC (set bit 8) and set spurious vector to 0xFF
-        *svr = 0x100 | 0xFF;
+        lapic_write(LAPIC_SVR, 0x100 | 0xFF);
     }
 }
 
@@ -135,11 +212,10 @@ pub fn apic_local_init() {
 /// The IRQ number is not used by the Local APIC EOI register,
 /// but we keep it for API compatibility.
 pub fn apic_send_eoi(_irq: u32) {
-    const LAPIC_EOI_OFFSET: u64 = 0x40;
+    const LAPIC_EOI_OFFSET: u64 = 0xB0;
 
     unsafe {
-        let eoi_reg = (LOCAL_APIC_DEFAULT_BASE + LAPIC_EOI_OFFSET) as *mut u32;
-        *eoi_reg = 0;
+        lapic_write(LAPIC_EOI_OFFSET, 0);
     }
 }
 
@@ -148,221 +224,342 @@ pub fn apic_issue_eoi() {
     apic_send_eoi(0); // EOI number doesn't matter for LAPIC
 }
 
-/// Probe the I/O APIC to verify it's accessible
-///
-/// Reads the IOAPIC ID and version registers to verify the IOAPIC
-/// is responding at the expected base address.
-fn ioapic_probe() {
-    const IOAPIC_BASE: u64 = 0xFEC0_0000;
-    const IOAPIC_IOREGSEL: u64 = 0x00;
-    const IOAPIC_IOWIN: u64 = 0x10;
-    const IOAPIC_ID_OFFSET: u32 = 0x00;
-    const IOAPIC_VER_OFFSET: u32 = 0x01;
+/// I/O APIC register select
+const IOAPIC_IOREGSEL: usize = 0x00;
 
-    unsafe {
-        let ioapic_sel = (IOAPIC_BASE + IOAPIC_IOREGSEL) as *mut u32;
-        let ioapic_win = (IOAPIC_BASE + IOAPIC_IOWIN) as *mut u32;
+/// I/O APIC register window
+const IOAPIC_IOWIN: usize = 0x10;
 
-        // Read IOAPIC ID
-        ioapic_sel.write_volatile(IOAPIC_ID_OFFSET);
-        let id = ioapic_win.read_volatile();
+/// I/O APIC version register (redirection entries - 1 in bits 16-23)
+const IOAPIC_VER: u32 = 0x01;
 
-        // Read IOAPIC Version
-        ioapic_sel.write_volatile(IOAPIC_VER_OFFSET);
-        let ver = ioapic_win.read_volatile();
+/// First redirection table register; each entry is 2 dwords (low + high)
+const IOAPIC_REDTBL: u32 = 0x10;
 
-        // Print IOAPIC info
-        let msg = b"[IOAPIC] ID=";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = (id >> 24) & 0x0F;  // IOAPIC ID is in bits 24-27
-        let mut buf = [0u8; 8];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+/// Redirection entry: interrupt masked
+const IOAPIC_MASKED: u32 = 1 << 16;
 
-        let msg = b" VER=";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = ver & 0xFF;  // Version is in low 8 bits
-        let mut buf = [0u8; 8];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+unsafe fn ioapic_read(base: usize, reg: u32) -> u32 {
+    ((base + IOAPIC_IOREGSEL) as *mut u32).write_volatile(reg);
+    ((base + IOAPIC_IOWIN) as *const u32).read_volatile()
+}
 
-        let msg = b" MAX_REDIR=";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = ((ver >> 16) & 0xFF) + 1;  // Max redirection entry
-        let mut buf = [0u8; 8];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+unsafe fn ioapic_write(base: usize, reg: u32, value: u32) {
+    ((base + IOAPIC_IOREGSEL) as *mut u32).write_volatile(reg);
+    ((base + IOAPIC_IOWIN) as *mut u32).write_volatile(value);
+}
 
-        let msg = b"\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+/// Register an I/O APIC from the MADT
+///
+/// Maps its registers and reads how many GSIs, from `gsi_base` on, it
+/// serves. Call on the BSP before any IRQ is routed.
+///
+/// # Returns
+/// `ERR_NO_MEMORY` if [`MAX_IOAPICS`] are registered already
+pub fn ioapic_add(paddr: u64, gsi_base: u32) -> Result<(), RxStatus> {
+    let slot = IOAPIC_COUNT.load(Ordering::Acquire);
+    let ioapic = IOAPICS.get(slot).ok_or(RxStatus::ERR_NO_MEMORY)?;
+    let base = map_mmio(paddr, 0x20);
+
+    let (id, ver) = unsafe { (ioapic_read(base, 0x00), ioapic_read(base, IOAPIC_VER)) };
+    let pins = ((ver >> 16) & 0xFF) + 1;
+    // IOAPIC ID is in bits 24-27
+    kinfo!(
+        "[IOAPIC] ID={} VER={} at {:#x}: GSI {}-{}",
+        (id >> 24) & 0x0F,
+        ver & 0xFF,
+        paddr,
+        gsi_base,
+        gsi_base + pins - 1
+    );
+
+    ioapic.base.store(base, Ordering::Relaxed);
+    ioapic.gsi_base.store(gsi_base, Ordering::Relaxed);
+    ioapic.pins.store(pins, Ordering::Relaxed);
+    IOAPIC_COUNT.store(slot + 1, Ordering::Release);
+    Ok(())
+}
+
+/// Find the I/O APIC serving a GSI
+///
+/// # Returns
+/// Its register base and the GSI's redirection entry. Without any
+/// registered I/O APIC, the default one with GSIs 0-23.
+fn ioapic_pin(gsi: u32) -> Option<(usize, u32)> {
+    let count = IOAPIC_COUNT.load(Ordering::Acquire);
+    if count == 0 {
+        return (gsi < 24).then_some((IOAPIC_DEFAULT_BASE as usize, gsi));
     }
+    IOAPICS[..count].iter().find_map(|ioapic| {
+        let pin = gsi.checked_sub(ioapic.gsi_base.load(Ordering::Relaxed))?;
+        (pin < ioapic.pins.load(Ordering::Relaxed)).then(|| (ioapic.base.load(Ordering::Relaxed), pin))
+    })
 }
 
-/// Initialize I/O APIC for a specific IRQ
+/// Route a GSI to a vector on CPU 0 (the BSP) and unmask it
 ///
 /// # Arguments
-/// * `irq` - The IRQ number to configure (e.g., 1 for keyboard)
+/// * `gsi` - Global System Interrupt (e.g., 1 for the keyboard)
 /// * `vector` - The interrupt vector to route to (e.g., 33 for IRQ1)
+/// * `mode` - Trigger mode and polarity of the input
 ///
-/// # Example
-/// ```ignore
-/// // Route IRQ1 (keyboard) to vector 33
-/// apic_io_init(1, 33);
-/// ```
-pub fn apic_io_init(irq: u8, vector: u8) {
-    // First, probe the IOAPIC to verify it's accessible
-    ioapic_probe();
-    const IOAPIC_BASE: u64 = 0xFEC0_0000;
-    const IOAPIC_IOREGSEL: u64 = 0x00;
-    const IOAPIC_IOWIN: u64 = 0x10;
-    // Redirection table entries start at IOREGSEL 0x12
-    // Each entry is 2 dwords (low + high)
-    let irq_redir_offset: u32 = 0x12 + ((irq as u32 - 1) * 2);
+/// # Returns
+/// `ERR_NOT_FOUND` if no I/O APIC serves the GSI
+pub fn ioapic_route(gsi: u32, vector: u8, mode: IrqMode) -> Result<(), RxStatus> {
+    let (base, pin) = ioapic_pin(gsi).ok_or(RxStatus::ERR_NOT_FOUND)?;
+    let reg = IOAPIC_REDTBL + pin * 2;
+
+    // Fixed delivery, physical destination CPU 0 (BSP), unmasked
+    let low = vector as u32 | mode.redir_bits();
 
     unsafe {
-        // Debug: Print what we're about to write
-        let msg = b"[IOAPIC] irq=";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = irq;
-        let mut buf = [0u8; 8];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+        kdebug!("[IOAPIC] gsi={} vector={} mode={:?}", gsi, vector, mode);
 
-        let msg = b" vector=";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = vector;
-        let mut buf = [0u8; 8];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b"\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        // Destination first, so the entry is complete when it is unmasked
+        ioapic_write(base, reg + 1, 0);
+        ioapic_write(base, reg, low);
 
-        let ioapic_sel = (IOAPIC_BASE + IOAPIC_IOREGSEL) as *mut u32;
-        let ioapic_win = (IOAPIC_BASE + IOAPIC_IOWIN) as *mut u32;
-
-        // Low dword: Vector in bits 0-7, delivery mode = 0 (fixed), mask = 0 (enabled)
-        let low_dword = vector as u32;
-        // High dword: Destination CPU 0 (BSP)
-        let high_dword = 0;
-
-        // Write low dword of redirection entry
-        ioapic_sel.write_volatile(irq_redir_offset);
-        ioapic_win.write_volatile(low_dword);
-        // Write high dword of redirection entry
-        ioapic_sel.write_volatile(irq_redir_offset + 1);
-        ioapic_win.write_volatile(high_dword);
-
-        // Read back and verify
-        ioapic_sel.write_volatile(irq_redir_offset);
-        let read_low = ioapic_win.read_volatile();
-        ioapic_sel.write_volatile(irq_redir_offset + 1);
-        let read_high = ioapic_win.read_volatile();
-
-        let msg = b"[IOAPIC] readback: low=0x";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = read_low;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            let digit = (n & 0xF) as u8;
-            buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-            n >>= 4;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+        kdebug!("[IOAPIC] readback: low={:#x} high={:#x}", ioapic_read(base, reg), ioapic_read(base, reg + 1));
+    }
+    Ok(())
+}
+
+/// Mask or unmask a GSI's redirection entry
+///
+/// # Returns
+/// `ERR_NOT_FOUND` if no I/O APIC serves the GSI
+pub fn ioapic_set_masked(gsi: u32, masked: bool) -> Result<(), RxStatus> {
+    let (base, pin) = ioapic_pin(gsi).ok_or(RxStatus::ERR_NOT_FOUND)?;
+    let reg = IOAPIC_REDTBL + pin * 2;
+
+    unsafe {
+        let low = ioapic_read(base, reg) & !IOAPIC_MASKED;
+        ioapic_write(base, reg, low | if masked { IOAPIC_MASKED } else { 0 });
+    }
+    Ok(())
+}
+
+/// Read the current CPU's Local APIC ID
+pub fn apic_local_id() -> u8 {
+    const LAPIC_ID_OFFSET: u64 = 0x20;
+
+    unsafe { (lapic_read(LAPIC_ID_OFFSET) >> 24) as u8 }
+}
+
+/// Set the destination CPU of an I/O APIC redirection entry
+///
+/// Only the destination field (bits 56-63, physical mode) is changed;
+/// vector, mask and trigger mode are left as configured by
+/// [`ioapic_route`]. Does nothing for a GSI no I/O APIC serves.
+///
+/// # Arguments
+/// * `gsi` - The GSI whose redirection entry to change
+/// * `apic_id` - Local APIC ID of the destination CPU
+pub fn ioapic_set_destination(gsi: u32, apic_id: u8) {
+    let Some((base, pin)) = ioapic_pin(gsi) else {
+        return;
+    };
+    let reg = IOAPIC_REDTBL + pin * 2 + 1;
 
-        let msg = b" high=0x";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
+    unsafe {
+        let high = ioapic_read(base, reg);
+        ioapic_write(base, reg, (high & 0x00FF_FFFF) | ((apic_id as u32) << 24));
+    }
+}
+
+/// MSI message address targeting a CPU
+///
+/// Physical destination mode, no redirection hint.
+pub const fn msi_address(apic_id: u8) -> u32 {
+    (LOCAL_APIC_DEFAULT_BASE as u32) | ((apic_id as u32) << 12)
+}
+
+/// MSI message data for a vector
+///
+/// Fixed delivery mode, edge triggered.
+pub const fn msi_data(vector: u8) -> u32 {
+    vector as u32
+}
+
+/// Start this CPU's Local APIC timer in periodic mode
+///
+/// # Arguments
+/// * `vector` - Vector the timer interrupt is delivered at
+/// * `initial_count` - Bus clocks (divided by 16) between interrupts
+pub fn apic_timer_periodic(vector: u8, initial_count: u32) {
+    unsafe {
+        lapic_write(LAPIC_TIMER_DIVIDE, DIVIDE_BY_16);
+        lapic_write(LAPIC_LVT_TIMER, vector as u32 | LVT_PERIODIC);
+        lapic_write(LAPIC_TIMER_INITIAL, initial_count);
+    }
+}
+
+/// Local APIC timer registers and settings
+const LAPIC_TIMER_DIVIDE: u64 = 0x3E0;
+const LAPIC_LVT_TIMER: u64 = 0x320;
+const LAPIC_TIMER_INITIAL: u64 = 0x380;
+const LAPIC_TIMER_CURRENT: u64 = 0x390;
+const DIVIDE_BY_16: u32 = 0x03;
+const LVT_PERIODIC: u32 = 1 << 17;
+const LVT_MASKED: u32 = 1 << 16;
+
+/// Local APIC timer ticks per second (divided by 16), 0 until calibrated
+static LAPIC_TIMER_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// Measure the Local APIC timer against the TSC and register it as a
+/// clock event device
+///
+/// Counts the timer down, masked, for [`tsc::CALIBRATION_MS`]. All Local
+/// APIC timers share the bus clock, so one measurement serves every CPU.
+///
+/// # Returns
+/// The timer frequency in Hz (after the divide-by-16), or `None` if the
+/// measurement is implausible
+///
+/// # Safety
+///
+/// Boot CPU only, before its timer is started, with the TSC calibrated.
+///
+/// [`tsc::CALIBRATION_MS`]: super::tsc::CALIBRATION_MS
+pub unsafe fn lapic_timer_init() -> Option<u64> {
+    use super::tsc;
+
+    lapic_write(LAPIC_TIMER_DIVIDE, DIVIDE_BY_16);
+    lapic_write(LAPIC_LVT_TIMER, LVT_MASKED);
+    lapic_write(LAPIC_TIMER_INITIAL, u32::MAX);
+    tsc::tsc_delay_ms(tsc::CALIBRATION_MS);
+    let elapsed = u32::MAX - lapic_read(LAPIC_TIMER_CURRENT);
+    lapic_write(LAPIC_TIMER_INITIAL, 0);
+
+    let hz = elapsed as u64 * 1000 / tsc::CALIBRATION_MS;
+    // The divided bus clock is in the MHz range; a timer that did not
+    // count, or ran out, measured nothing
+    if hz < 100_000 || elapsed == u32::MAX {
+        return None;
+    }
+    LAPIC_TIMER_HZ.store(hz, Ordering::Release);
+    kinfo!("[LAPIC] timer {} kHz", hz / 1000);
+    let _ = crate::time::clockevents::register(&LAPIC_TIMER);
+    Some(hz)
+}
+
+/// Local APIC timer count for `d`, clamped to the 32-bit counter
+fn lapic_timer_count(d: Duration) -> u32 {
+    let hz = LAPIC_TIMER_HZ.load(Ordering::Relaxed) as u128;
+    let count = d.as_nanos() as u128 * hz / 1_000_000_000;
+    count.clamp(1, u32::MAX as u128) as u32
+}
+
+/// The Local APIC timer as a clock event device
+///
+/// Each CPU has its own; the calls program the running CPU's.
+pub struct LapicTimer;
+
+impl ClockEvents for LapicTimer {
+    fn name(&self) -> &'static str {
+        "lapic"
+    }
+
+    fn rating(&self) -> u32 {
+        if LAPIC_TIMER_HZ.load(Ordering::Relaxed) != 0 { 100 } else { 0 }
+    }
+
+    fn per_cpu(&self) -> bool {
+        true
+    }
+
+    fn set_periodic(&self, vector: u8, period: Duration) -> Result<(), RxStatus> {
+        if self.rating() == 0 {
+            return Err(RxStatus::ERR_NOT_FOUND);
         }
-        let mut n = read_high;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            let digit = (n & 0xF) as u8;
-            buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-            n >>= 4;
-            i += 1;
-            if n == 0 { break; }
+        apic_timer_periodic(vector, lapic_timer_count(period));
+        Ok(())
+    }
+
+    fn set_oneshot(&self, vector: u8, delta: Duration) -> Result<(), RxStatus> {
+        if self.rating() == 0 {
+            return Err(RxStatus::ERR_NOT_FOUND);
         }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
+        unsafe {
+            lapic_write(LAPIC_TIMER_DIVIDE, DIVIDE_BY_16);
+            lapic_write(LAPIC_LVT_TIMER, vector as u32);
+            lapic_write(LAPIC_TIMER_INITIAL, lapic_timer_count(delta));
         }
+        Ok(())
+    }
 
-        let msg = b"\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
+    fn shutdown(&self) {
+        unsafe {
+            lapic_write(LAPIC_LVT_TIMER, LVT_MASKED);
+            lapic_write(LAPIC_TIMER_INITIAL, 0);
         }
+    }
+}
+
+/// The Local APIC timer clock event device
+pub static LAPIC_TIMER: LapicTimer = LapicTimer;
+
+// ============================================================================
+// Inter-Processor Interrupts
+// ============================================================================
+
+/// Interrupt Command Register, low half (writing it sends the IPI)
+const LAPIC_ICR_LOW: u64 = 0x300;
+
+/// Interrupt Command Register, high half (destination in bits 24-31)
+const LAPIC_ICR_HIGH: u64 = 0x310;
 
-        let msg = b"[IOAPIC] configured\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
+/// ICR delivery mode: fixed vector
+const ICR_FIXED: u32 = 0x000;
+
+/// ICR delivery mode: INIT
+const ICR_INIT: u32 = 0x500;
+
+/// ICR delivery mode: STARTUP (SIPI)
+const ICR_STARTUP: u32 = 0x600;
+
+/// ICR delivery status: the previous IPI is still being sent
+const ICR_SEND_PENDING: u32 = 1 << 12;
+
+/// ICR level: assert
+const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+
+unsafe fn lapic_read(offset: u64) -> u32 {
+    ((LAPIC_BASE.load(Ordering::Relaxed) + offset as usize) as *const u32).read_volatile()
+}
+
+unsafe fn lapic_write(offset: u64, value: u32) {
+    ((LAPIC_BASE.load(Ordering::Relaxed) + offset as usize) as *mut u32).write_volatile(value);
+}
+
+/// Send an IPI to one CPU (physical destination) and wait until the
+/// Local APIC has accepted it
+fn apic_send_icr(apic_id: u8, low: u32) {
+    unsafe {
+        while lapic_read(LAPIC_ICR_LOW) & ICR_SEND_PENDING != 0 {
+            core::hint::spin_loop();
+        }
+        lapic_write(LAPIC_ICR_HIGH, (apic_id as u32) << 24);
+        lapic_write(LAPIC_ICR_LOW, low);
+        while lapic_read(LAPIC_ICR_LOW) & ICR_SEND_PENDING != 0 {
+            core::hint::spin_loop();
         }
     }
 }
+
+/// Send a fixed-vector IPI to another CPU
+pub fn apic_send_ipi(apic_id: u8, vector: u8) {
+    apic_send_icr(apic_id, ICR_FIXED | ICR_LEVEL_ASSERT | vector as u32);
+}
+
+/// Send an INIT IPI, resetting the target CPU into wait-for-SIPI
+pub fn apic_send_init(apic_id: u8) {
+    apic_send_icr(apic_id, ICR_INIT | ICR_LEVEL_ASSERT);
+}
+
+/// Send a STARTUP IPI
+///
+/// The target starts in real mode at `page << 12`.
+pub fn apic_send_startup(apic_id: u8, page: u8) {
+    apic_send_icr(apic_id, ICR_STARTUP | ICR_LEVEL_ASSERT | page as u32);
+}
@@ -0,0 +1,232 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Frame Pointer Backtraces
+//!
+//! The kernel is built with `-C force-frame-pointers=yes` (see
+//! `.cargo/config.toml`), so every function keeps the standard frame
+//! layout:
+//!
+//! ```text
+//! [rbp + 8]  return address
+//! [rbp]      caller's rbp
+//! ```
+//!
+//! [`walk`] follows this chain without any unwinding tables. Every frame
+//! address is checked against the bounds of the stack being walked before
+//! it is read, and frames must move strictly up the stack, so a corrupt
+//! chain ends the walk instead of faulting or looping.
+//!
+//! # Stacks
+//!
+//! [`stack_bounds_for`] knows the boot kernel stack and the kernel stack
+//! of the current process. A frame pointer outside both yields an empty
+//! backtrace.
+
+use core::fmt::Write;
+use crate::klog::{Level, LogWriter};
+use crate::process::table::{KERNEL_STACK_SIZE, PROCESS_TABLE};
+
+/// Maximum number of frames recorded
+pub const MAX_FRAMES: usize = 32;
+
+// ============================================================================
+// Stack Bounds
+// ============================================================================
+
+/// A kernel stack: `[low, high)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackBounds {
+    /// Lowest address (inclusive)
+    pub low: usize,
+    /// Top of the stack (exclusive)
+    pub high: usize,
+}
+
+impl StackBounds {
+    /// Check whether `[addr, addr + len)` lies on this stack
+    pub fn contains(&self, addr: usize, len: usize) -> bool {
+        addr >= self.low && addr.checked_add(len).is_some_and(|end| end <= self.high)
+    }
+}
+
+/// Find the kernel stack containing `addr`
+///
+/// Checks the boot kernel stack and the current process's kernel stack.
+/// The process table is only try-locked, so this is safe to call from
+/// panic and fault paths that may already hold it.
+pub fn stack_bounds_for(addr: usize) -> Option<StackBounds> {
+    if let Some((paddr, size)) = super::init::get_kernel_stack_info() {
+        let low = crate::mm::pmm::paddr_to_vaddr(paddr) as usize;
+        let boot = StackBounds { low, high: low + size };
+        if boot.contains(addr, 1) {
+            return Some(boot);
+        }
+    }
+
+    let table = PROCESS_TABLE.try_lock()?;
+    let top = table.current_thread()?.kernel_stack as usize;
+    let process = StackBounds { low: top.saturating_sub(KERNEL_STACK_SIZE), high: top };
+    process.contains(addr, 1).then_some(process)
+}
+
+// ============================================================================
+// Stack Walking
+// ============================================================================
+
+/// A captured backtrace
+#[derive(Debug, Clone, Copy)]
+pub struct Backtrace {
+    frames: [usize; MAX_FRAMES],
+    len: usize,
+}
+
+impl Backtrace {
+    /// A backtrace with no frames
+    pub const fn empty() -> Self {
+        Self { frames: [0; MAX_FRAMES], len: 0 }
+    }
+
+    /// Return addresses, innermost first
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+
+    /// Write the backtrace, one frame per line
+    ///
+    /// Frames in a moved kernel image also show their link-time address
+    /// (see [`crate::kaslr::link_address`]), and frames are named from the
+    /// kernel symbol table when there is one (see [`crate::ksyms`]).
+    pub fn write_to(&self, out: &mut impl Write) -> core::fmt::Result {
+        for (i, &addr) in self.frames().iter().enumerate() {
+            write!(out, "  #{:<2} {:#018x}", i, addr)?;
+            let link = crate::kaslr::link_address(addr);
+            if link != addr {
+                write!(out, " (link {:#018x})", link)?;
+            }
+            if let Some((name, offset)) = crate::ksyms::symbolize(addr) {
+                write!(out, " {}+{:#x}", name, offset)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Walk a frame-pointer chain
+///
+/// # Arguments
+///
+/// * `rbp` - Frame pointer of the innermost frame
+/// * `bounds` - Stack the chain must stay on
+/// * `out` - Receives the return addresses, innermost first
+///
+/// # Returns
+///
+/// Number of frames written to `out`
+///
+/// # Safety
+///
+/// `bounds` must describe mapped, readable memory.
+pub unsafe fn walk(mut rbp: usize, bounds: StackBounds, out: &mut [usize]) -> usize {
+    let mut n = 0;
+    while n < out.len() {
+        if !rbp.is_multiple_of(8) || !bounds.contains(rbp, 16) {
+            break;
+        }
+        let next = core::ptr::read_volatile(rbp as *const usize);
+        let ret = core::ptr::read_volatile((rbp + 8) as *const usize);
+        if ret == 0 {
+            break;
+        }
+        out[n] = ret;
+        n += 1;
+
+        // Frames live at increasing addresses; anything else is corrupt
+        if next <= rbp {
+            break;
+        }
+        rbp = next;
+    }
+    n
+}
+
+/// Capture a backtrace starting at an interrupted frame
+///
+/// Used by fault handlers, which know the interrupted `rip` and `rbp`.
+pub fn capture_from(rip: usize, rbp: usize) -> Backtrace {
+    let mut bt = Backtrace::empty();
+    bt.frames[0] = rip;
+    bt.len = 1;
+    if let Some(bounds) = stack_bounds_for(rbp) {
+        bt.len += unsafe { walk(rbp, bounds, &mut bt.frames[1..]) };
+    }
+    bt
+}
+
+/// Capture a backtrace of the caller
+#[inline(never)]
+pub fn capture() -> Backtrace {
+    let rbp: usize;
+    unsafe {
+        core::arch::asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack, preserves_flags));
+    }
+    let mut bt = Backtrace::empty();
+    if let Some(bounds) = stack_bounds_for(rbp) {
+        bt.len = unsafe { walk(rbp, bounds, &mut bt.frames) };
+    }
+    bt
+}
+
+/// Log a backtrace
+pub fn print(bt: &Backtrace) {
+    let mut out = LogWriter::new(Level::Info, module_path!());
+    let _ = writeln!(out, "[BACKTRACE] {} frame(s):", bt.frames().len());
+    let _ = bt.write_to(&mut out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_synthetic_chain() {
+        // Three frames: [saved rbp, return address] pairs
+        let mut stack = [0usize; 8];
+        let base = stack.as_ptr() as usize;
+        stack[0] = base + 16;
+        stack[1] = 0x1111;
+        stack[2] = base + 32;
+        stack[3] = 0x2222;
+        stack[4] = 0; // end of chain
+        stack[5] = 0x3333;
+        let bounds = StackBounds { low: base, high: base + 64 };
+
+        let mut out = [0usize; 8];
+        let n = unsafe { walk(base, bounds, &mut out) };
+        assert_eq!(&out[..n], &[0x1111, 0x2222, 0x3333]);
+    }
+
+    #[test]
+    fn test_walk_rejects_out_of_bounds() {
+        let stack = [0usize; 4];
+        let base = stack.as_ptr() as usize;
+        let bounds = StackBounds { low: base, high: base + 32 };
+        let mut out = [0usize; 4];
+        assert_eq!(unsafe { walk(base + 32, bounds, &mut out) }, 0);
+        assert_eq!(unsafe { walk(base + 3, bounds, &mut out) }, 0);
+    }
+
+    #[test]
+    fn test_stack_bounds_contains() {
+        let b = StackBounds { low: 0x1000, high: 0x2000 };
+        assert!(b.contains(0x1000, 16));
+        assert!(b.contains(0x1ff0, 16));
+        assert!(!b.contains(0x1ff8, 16));
+        assert!(!b.contains(0xff8, 8));
+        assert!(!b.contains(usize::MAX, 2));
+    }
+}
@@ -10,25 +10,57 @@
 //!
 //! # Bootstrap Process
 //!
-//! 1. BIOS starts APs at a specific address in low memory (typically 0x7000-0x8000)
-//! 2. APs start in 16-bit real mode with CS:IP pointing to this code
-//! 3. The bootstrap code must:
-//!    - Load a GDT to switch to protected mode
-//!    - Enable PAE (Physical Address Extension)
-//!    - Load page tables (CR3)
-//!    - Enable long mode (EFER.LME)
-//!    - Enable paging (CR0.PG)
-//!    - Load 64-bit segment registers
-//!    - Jump to the 64-bit kernel entry point
+//! 1. The BSP copies the trampoline to [`BOOTSTRAP_START`] and fills in
+//!    the [`BootstrapInfo`] block behind it
+//! 2. INIT and STARTUP IPIs start the AP in 16-bit real mode at
+//!    `BOOTSTRAP_START` (the SIPI vector is its page number)
+//! 3. The trampoline:
+//!    - Loads its own GDT and switches to protected mode
+//!    - Enables PAE and loads the BSP's page tables (CR3)
+//!    - Enables long mode (EFER, copied from the BSP) and paging (CR0.PG)
+//!    - Far jumps to 64-bit code, loads the BSP's CR4 and the AP's stack
+//!    - Calls [`bootstrap16`] with a pointer to the info block
 //!
-//! # Assembly Interface
+//! The trampoline runs from physical memory, so the low megabyte must be
+//! identity mapped in the BSP's page tables (UEFI maps it) and CR3 must
+//! be below 4GB (it is loaded in 32-bit mode).
 //!
-//! The actual 16-bit code must be in assembly (start16.S).
-//! This Rust function is called after the switch to long mode.
+//! # Trampoline GDT
+//!
+//! | Selector | Segment |
+//! |----------|---------|
+//! | 0x08 | 64-bit code (same selector as the kernel's) |
+//! | 0x10 | Data |
+//! | 0x18 | 32-bit code |
+//!
+//! [`bootstrap16`] replaces it with the CPU's own GDT straight away.
 
+use core::sync::atomic::{AtomicU32, Ordering};
 use crate::arch::amd64::mm::page_tables::PAddr;
+use crate::arch::amd64::registers::{self, efer, msr};
+
+/// Bootstrap area in low memory
+///
+/// The bootstrap code is typically placed at 0x7000-0x8000 in physical memory.
+pub const BOOTSTRAP_START: PAddr = 0x7000;
+pub const BOOTSTRAP_SIZE: usize = 0x1000; // 4KB
+
+/// Offset of the [`BootstrapInfo`] block in the bootstrap area
+const INFO_OFFSET: usize = 0xF00;
+
+/// Address of the [`BootstrapInfo`] block
+const INFO_ADDR: usize = BOOTSTRAP_START as usize + INFO_OFFSET;
+
+/// How long to wait for an AP to call in after its STARTUP IPIs
+const CALL_IN_TIMEOUT_MS: u64 = 100;
+
+/// CPU number of the last AP to reach [`bootstrap16`] (0 = none yet)
+static CALLED_IN: AtomicU32 = AtomicU32::new(0);
 
 /// Bootstrap data passed from assembly to Rust
+///
+/// Lives at a fixed address in the bootstrap area; the trampoline reads
+/// the control register values and the stack from it.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct BootstrapInfo {
@@ -42,6 +74,100 @@ pub struct BootstrapInfo {
     pub stack_top: usize,
     /// Entry point for the kernel's per-CPU initialization
     pub entry_point: usize,
+    /// BSP's CR0 (loaded to enable paging)
+    pub cr0: u64,
+    /// BSP's CR4 (loaded once in long mode)
+    pub cr4: u64,
+    /// BSP's EFER, without LMA
+    pub efer: u64,
+}
+
+core::arch::global_asm!(
+    ".pushsection .text.ap_trampoline, \"ax\"",
+    ".balign 16",
+    ".global rx_ap_trampoline_start",
+    "rx_ap_trampoline_start:",
+    ".code16",
+    "cli",
+    "cld",
+    "mov ax, cs",
+    "mov ds, ax",
+    // lgdt [rx_ap_gdtr] (16-bit offset from CS)
+    ".byte 0x0F, 0x01, 0x16",
+    ".word rx_ap_gdtr - rx_ap_trampoline_start",
+    "mov eax, cr0",
+    "or eax, 1",
+    "mov cr0, eax",
+    // ljmp 0x18:rx_ap_protected (32-bit offset)
+    ".byte 0x66, 0xEA",
+    ".long {base} + (rx_ap_protected - rx_ap_trampoline_start)",
+    ".word 0x18",
+
+    ".code32",
+    "rx_ap_protected:",
+    "mov ax, 0x10",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    // PAE only for now: some CR4 bits (PCIDE) need long mode
+    "mov eax, 0x20",
+    "mov cr4, eax",
+    "mov eax, [{info} + {cr3}]",
+    "mov cr3, eax",
+    "mov ecx, {efer_msr}",
+    "mov eax, [{info} + {efer}]",
+    "mov edx, [{info} + {efer} + 4]",
+    "wrmsr",
+    "mov eax, [{info} + {cr0}]",
+    "mov cr0, eax",
+    // ljmp 0x08:rx_ap_long
+    ".byte 0xEA",
+    ".long {base} + (rx_ap_long - rx_ap_trampoline_start)",
+    ".word 0x08",
+
+    ".code64",
+    "rx_ap_long:",
+    "xor eax, eax",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    "mov fs, ax",
+    "mov gs, ax",
+    "mov rax, [{info} + {cr4}]",
+    "mov cr4, rax",
+    "mov rsp, [{info} + {stack_top}]",
+    "mov edi, {info}",
+    "call qword ptr [{info} + {entry}]",
+    "rx_ap_hang:",
+    "hlt",
+    "jmp rx_ap_hang",
+
+    ".balign 8",
+    "rx_ap_gdt:",
+    ".quad 0",
+    ".quad 0x00AF9A000000FFFF",
+    ".quad 0x00CF92000000FFFF",
+    ".quad 0x00CF9A000000FFFF",
+    "rx_ap_gdtr:",
+    ".word 4 * 8 - 1",
+    ".long {base} + (rx_ap_gdt - rx_ap_trampoline_start)",
+    ".global rx_ap_trampoline_end",
+    "rx_ap_trampoline_end:",
+    ".popsection",
+    base = const BOOTSTRAP_START,
+    info = const INFO_ADDR,
+    cr3 = const core::mem::offset_of!(BootstrapInfo, cr3),
+    stack_top = const core::mem::offset_of!(BootstrapInfo, stack_top),
+    entry = const core::mem::offset_of!(BootstrapInfo, entry_point),
+    cr0 = const core::mem::offset_of!(BootstrapInfo, cr0),
+    cr4 = const core::mem::offset_of!(BootstrapInfo, cr4),
+    efer = const core::mem::offset_of!(BootstrapInfo, efer),
+    efer_msr = const msr::IA32_EFER,
+);
+
+extern "C" {
+    static rx_ap_trampoline_start: u8;
+    static rx_ap_trampoline_end: u8;
 }
 
 /// Secondary CPU bootstrap entry (called from 16-bit assembly)
@@ -51,28 +177,34 @@ pub struct BootstrapInfo {
 /// - We're in 64-bit mode
 /// - Paging is enabled
 /// - We have a valid stack
-/// - All segment registers are properly set up
+/// - Data segment registers are null; CS is the trampoline's 0x08
+///
+/// Copies the info block first: the BSP reuses it for the next AP once
+/// this one has called in.
 ///
 /// # Safety
 ///
 /// Must be called with valid bootstrap info
 #[no_mangle]
-pub unsafe extern "C" fn bootstrap16(info: BootstrapInfo) -> ! {
-    // TODO: Call the architecture-specific SMP initialization
-    // This function is defined in the architecture's mp module
-    // For now, halt since we don't have SMP support yet
+pub unsafe extern "C" fn bootstrap16(info: &BootstrapInfo) -> ! {
+    let info = *info;
+    let cpu = info.cpu_num as usize;
 
-    let _ = info;
+    // Per-CPU GDT/TSS (with IST stacks) and GS before anything can trap
+    super::descriptor::cpu_init(cpu);
+    super::entry::init_cpu(cpu);
+    super::tsc::cpu_init(cpu);
+    let idt = super::descriptor::IDT_POINTER;
+    super::descriptor::idt_load(&idt);
 
-    // Halt the CPU
-    loop {
-        core::arch::asm!("hlt", options(nomem, nostack));
-    }
+    CALLED_IN.store(info.cpu_num, Ordering::Release);
+    super::smp::ap_main(cpu, info.apic_id as u8, info.stack_top as u64)
 }
 
 /// Initialize the bootstrap area in low memory
 ///
-/// This sets up the code and data that APs will execute when started.
+/// Copies the trampoline to `bootstrap_code`. Only [`BOOTSTRAP_START`]
+/// works: the trampoline is linked for that address.
 ///
 /// # Arguments
 ///
@@ -81,32 +213,35 @@ pub unsafe extern "C" fn bootstrap16(info: BootstrapInfo) -> ! {
 ///
 /// # Returns
 ///
-/// Physical address of the bootstrap entry point
+/// Physical address of the bootstrap entry point, or 0 if the
+/// trampoline does not fit
 ///
 /// # Safety
 ///
 /// The bootstrap code area must be valid accessible memory
 pub unsafe fn init_bootstrap_area(bootstrap_code: PAddr, code_size: usize) -> PAddr {
-    // In a real implementation, this would:
-    // 1. Copy the 16-bit bootstrap code to the bootstrap area
-    // 2. Set up the bootstrap data structure
-    // 3. Configure the GDT for the bootstrap code
-    // 4. Return the entry point address
-
-    // For now, return the bootstrap code address as the entry point
-    let _ = code_size;
+    let start = &raw const rx_ap_trampoline_start;
+    let len = (&raw const rx_ap_trampoline_end) as usize - start as usize;
+    if bootstrap_code != BOOTSTRAP_START || len > code_size.min(INFO_OFFSET) {
+        return 0;
+    }
+
+    core::ptr::copy_nonoverlapping(start, bootstrap_code as *mut u8, len);
     bootstrap_code
 }
 
 /// Start a secondary CPU
 ///
+/// Sends INIT, then up to two STARTUP IPIs, and waits for the AP to
+/// reach [`bootstrap16`].
+///
 /// # Arguments
 ///
-/// * `_cpu_num` - CPU number to start
-/// * `_apic_id` - APIC ID of the target CPU
-/// * `_entry_point` - 64-bit kernel entry point
-/// * `_stack_top` - Stack pointer for the new CPU
-/// * `_cr3` - Page table physical address
+/// * `cpu_num` - CPU number to start
+/// * `apic_id` - APIC ID of the target CPU
+/// * `entry_point` - 64-bit kernel entry point
+/// * `stack_top` - Stack pointer for the new CPU
+/// * `cr3` - Page table physical address
 ///
 /// # Returns
 ///
@@ -117,27 +252,62 @@ pub unsafe fn init_bootstrap_area(bootstrap_code: PAddr, code_size: usize) -> PA
 /// This function manipulates APIC registers and should only be called
 /// by the BSP during initialization
 pub unsafe fn start_secondary_cpu(
-    _cpu_num: u32,
-    _apic_id: u32,
-    _entry_point: usize,
-    _stack_top: usize,
-    _cr3: PAddr,
+    cpu_num: u32,
+    apic_id: u32,
+    entry_point: usize,
+    stack_top: usize,
+    cr3: PAddr,
 ) -> bool {
-    // TODO: Implement IPI-based CPU startup
-    // 1. Write the bootstrap code to low memory (typically 0x7000)
-    // 2. Set up the bootstrap data with the provided parameters
-    // 3. Send INIT IPI to the target CPU
-    // 4. Wait 10ms
-    // 5. Send STARTUP IPI to the target CPU with the bootstrap address
-    // 6. Wait for the CPU to signal it's ready
-    // 7. Clean up the bootstrap area
-
-    // For now, return false as this is not yet implemented
+    use super::{apic, tsc};
+
+    // Loaded with a 32-bit mov before long mode is on
+    if cr3 >= 1 << 32 || init_bootstrap_area(BOOTSTRAP_START, BOOTSTRAP_SIZE) == 0 {
+        return false;
+    }
+
+    (INFO_ADDR as *mut BootstrapInfo).write_volatile(BootstrapInfo {
+        cpu_num,
+        apic_id,
+        cr3,
+        stack_top,
+        entry_point,
+        cr0: registers::x86_get_cr0(),
+        cr4: registers::x86_get_cr4(),
+        efer: registers::read_msr(msr::IA32_EFER) & !efer::LMA,
+    });
+
+    let apic_id = apic_id as u8;
+    let page = (BOOTSTRAP_START >> 12) as u8;
+    apic::apic_send_init(apic_id);
+    tsc::tsc_delay_ms(10);
+
+    for _ in 0..2 {
+        apic::apic_send_startup(apic_id, page);
+        tsc::tsc_delay_us(200);
+        if CALLED_IN.load(Ordering::Acquire) == cpu_num {
+            return true;
+        }
+    }
+
+    let deadline = tsc::tsc_ticks() + tsc::ns_to_tsc(CALL_IN_TIMEOUT_MS * 1_000_000);
+    while tsc::tsc_ticks() < deadline {
+        if CALLED_IN.load(Ordering::Acquire) == cpu_num {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
     false
 }
 
-/// Bootstrap area in low memory
-///
-/// The bootstrap code is typically placed at 0x7000-0x8000 in physical memory.
-pub const BOOTSTRAP_START: PAddr = 0x7000;
-pub const BOOTSTRAP_SIZE: usize = 0x1000; // 4KB
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info_block_fits() {
+        assert!(INFO_OFFSET + core::mem::size_of::<BootstrapInfo>() <= BOOTSTRAP_SIZE);
+        assert_eq!(core::mem::offset_of!(BootstrapInfo, cr3), 8);
+        assert_eq!(BOOTSTRAP_START % 4096, 0);
+        assert!(BOOTSTRAP_START < 0x10_0000);
+    }
+}
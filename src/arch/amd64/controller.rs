@@ -8,9 +8,24 @@
 //!
 //! This module provides the interrupt controller implementation for x86_64,
 //! implementing the cross-architecture InterruptController trait.
+//!
+//! Built with [`X86_64InterruptController::from_madt`], the controller
+//! uses the Local APIC and I/O APIC addresses the firmware reports and
+//! routes ISA IRQs through the MADT's Interrupt Source Overrides: an
+//! override can move an IRQ to another GSI (IRQ0 → GSI2 is common) and
+//! set its polarity and trigger mode. IRQs from 16 on are GSIs, routed
+//! level triggered and active low as PCI expects.
+//!
+//! [`X86_64InterruptController::new`] keeps the legacy defaults: one
+//! I/O APIC at 0xFEC00000 and ISA IRQ n on GSI n, edge triggered.
 
+use crate::acpi::madt::{InterruptSourceOverrideEntry, ParsedMadt};
 use crate::traits::InterruptController;
-use super::apic;
+use crate::kwarn;
+use super::apic::{self, IrqMode};
+
+/// ISA IRQs, the ones Interrupt Source Overrides can remap
+pub const ISA_IRQS: usize = 16;
 
 /// x86_64 Interrupt Controller (Local APIC + IOAPIC)
 ///
@@ -19,13 +34,56 @@ use super::apic;
 pub struct X86_64InterruptController {
     /// Whether the interrupt controller has been initialized
     pub enabled: bool,
+    /// GSI and mode of each ISA IRQ
+    isa_routes: [(u32, IrqMode); ISA_IRQS],
 }
 
 impl X86_64InterruptController {
-    /// Create a new x86_64 interrupt controller
+    /// Create a new x86_64 interrupt controller with the legacy routing
     pub fn new() -> Self {
+        let mut isa_routes = [(0, IrqMode::ISA); ISA_IRQS];
+        for (irq, route) in isa_routes.iter_mut().enumerate() {
+            route.0 = irq as u32;
+        }
         Self {
             enabled: false,
+            isa_routes,
+        }
+    }
+
+    /// Create the interrupt controller the MADT describes
+    ///
+    /// Maps the Local APIC and every I/O APIC at their reported
+    /// addresses, so call it once, on the BSP, before the APs start.
+    pub fn from_madt(madt: &ParsedMadt) -> Self {
+        apic::set_local_apic_base(madt.local_apic_base());
+        for ioapic in &madt.io_apics[..madt.io_apic_count] {
+            let (address, gsi_base) = (ioapic.address, ioapic.gsi_base);
+            if apic::ioapic_add(address as u64, gsi_base).is_err() {
+                kwarn!("[IOAPIC] too many I/O APICs, ignoring the one at {:#x}", address);
+            }
+        }
+
+        let mut controller = Self::new();
+        controller.apply_overrides(&madt.overrides[..madt.override_count]);
+        controller
+    }
+
+    /// Apply Interrupt Source Overrides for the ISA bus
+    fn apply_overrides(&mut self, overrides: &[InterruptSourceOverrideEntry]) {
+        for entry in overrides {
+            let (bus, irq, gsi, flags) = (entry.bus, entry.source_irq as usize, entry.gsi, entry.flags);
+            if bus == 0 && irq < ISA_IRQS {
+                self.isa_routes[irq] = (gsi, IrqMode::from_inti(flags, IrqMode::ISA));
+            }
+        }
+    }
+
+    /// Get the GSI an IRQ is delivered on and its trigger mode and polarity
+    pub fn route(&self, irq: u64) -> (u32, IrqMode) {
+        match self.isa_routes.get(irq as usize) {
+            Some(&route) => route,
+            None => (irq as u32, IrqMode::PCI),
         }
     }
 }
@@ -39,6 +97,10 @@ impl Default for X86_64InterruptController {
 impl InterruptController for X86_64InterruptController {
     /// Enable IRQ in IOAPIC redirection table
     ///
+    /// ISA IRQs (below 16) go through the Interrupt Source Overrides;
+    /// the vector is then delivered to CPU 0 and registered with the
+    /// affinity balancer.
+    ///
     /// # Arguments
     /// * `irq` - The IRQ number to enable (e.g., 1 for keyboard)
     /// * `vector` - The interrupt vector to route to (e.g., 33 for IRQ1)
@@ -49,15 +111,19 @@ impl InterruptController for X86_64InterruptController {
     /// controller.enable_irq(1, 33); // Route IRQ1 to vector 33
     /// ```
     fn enable_irq(&mut self, irq: u64, vector: u64) {
-        apic::apic_io_init(irq as u8, vector as u8);
+        let (gsi, mode) = self.route(irq);
+        match apic::ioapic_route(gsi, vector as u8, mode) {
+            Ok(()) => crate::interrupt::affinity::register_ioapic(vector as u8, gsi),
+            Err(_) => kwarn!("[IOAPIC] no I/O APIC serves GSI {} (IRQ {})", gsi, irq),
+        }
     }
 
     /// Disable an interrupt
     ///
-    /// TODO: Set mask bit 16 in IOAPIC redirection entry.
-    /// This requires reading current value, OR-ing in the mask bit, writing back.
-    fn disable_irq(&mut self, _irq: u64) {
-        // TODO: Implement by setting mask bit in IOAPIC redirection entry
+    /// Sets the mask bit of the IRQ's I/O APIC redirection entry.
+    fn disable_irq(&mut self, irq: u64) {
+        let (gsi, _) = self.route(irq);
+        let _ = apic::ioapic_set_masked(gsi, true);
     }
 
     /// Send end-of-interrupt signal to the Local APIC
@@ -70,8 +136,9 @@ impl InterruptController for X86_64InterruptController {
 
     /// Initialize the interrupt controller
     ///
-    /// This initializes the Local APIC (which UEFI typically already did)
-    /// and configures the I/O APIC for interrupt routing.
+    /// This disables the legacy PIC and initializes the Local APIC (which
+    /// UEFI typically already did). IRQs are routed with
+    /// [`enable_irq`](InterruptController::enable_irq) afterwards.
     ///
     /// # Returns
     /// * `Ok(())` if initialization succeeded
@@ -80,10 +147,49 @@ impl InterruptController for X86_64InterruptController {
         // Initialize Local APIC (UEFI already did this, but ensure it's enabled)
         apic::apic_local_init();
 
-        // Initialize IOAPIC for IRQ1 (keyboard) - route to vector 33
-        self.enable_irq(1, 33);
-
         self.enabled = true;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acpi::madt::MadtEntryHeader;
+
+    fn source_override(bus: u8, source_irq: u8, gsi: u32, flags: u16) -> InterruptSourceOverrideEntry {
+        InterruptSourceOverrideEntry {
+            header: MadtEntryHeader { entry_type: 2, length: 10 },
+            bus,
+            source_irq,
+            gsi,
+            flags,
+        }
+    }
+
+    #[test]
+    fn test_inti_flags() {
+        assert_eq!(IrqMode::from_inti(0, IrqMode::ISA), IrqMode::ISA);
+        assert_eq!(IrqMode::from_inti(0, IrqMode::PCI), IrqMode::PCI);
+        // Active low, level triggered
+        assert_eq!(IrqMode::from_inti(0xF, IrqMode::ISA), IrqMode::PCI);
+        // Active high, bus trigger mode
+        assert_eq!(IrqMode::from_inti(0x1, IrqMode::PCI), IrqMode { level: true, active_low: false });
+    }
+
+    #[test]
+    fn test_source_overrides() {
+        let mut controller = X86_64InterruptController::new();
+        controller.apply_overrides(&[
+            source_override(0, 0, 2, 0),
+            source_override(0, 9, 9, 0xD),
+            source_override(1, 4, 20, 0),
+        ]);
+
+        assert_eq!(controller.route(0), (2, IrqMode::ISA));
+        assert_eq!(controller.route(1), (1, IrqMode::ISA));
+        assert_eq!(controller.route(4), (4, IrqMode::ISA));
+        assert_eq!(controller.route(9), (9, IrqMode { level: true, active_low: false }));
+        assert_eq!(controller.route(20), (20, IrqMode::PCI));
+    }
+}
@@ -0,0 +1,365 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! CPU Identification (CPUID)
+//!
+//! Reads the CPUID leaves the kernel cares about once and caches the
+//! result. Use [`get`] to obtain the cached [`CpuFeatures`].
+//!
+//! # Leaves
+//!
+//! | Leaf | Used for |
+//! |------|----------|
+//! | 0x0 | Vendor string, maximum basic leaf |
+//! | 0x1 | Family / model / stepping, logical CPUs per package, feature flags |
+//! | 0x4 | Cores per package (Intel) |
+//! | 0x5 | MWAIT extensions and C-state sub-state counts |
+//! | 0x6 | APERF/MPERF present (ECX bit 0) |
+//! | 0x7 | Extended feature flags |
+//! | 0x16 | Base / maximum / bus frequency in MHz |
+//! | 0x8000_0001 | AMD64 feature flags |
+//! | 0x8000_0002..4 | Brand string |
+//! | 0x8000_0008 | Cores per package (AMD) |
+//!
+//! Feature flags use the names Linux reports in `/proc/cpuinfo`.
+
+use core::arch::x86_64::{CpuidResult, __cpuid_count};
+use crate::sync::SpinMutex;
+
+/// Execute CPUID for a leaf and sub-leaf
+#[inline]
+pub fn cpuid(leaf: u32, subleaf: u32) -> CpuidResult {
+    __cpuid_count(leaf, subleaf)
+}
+
+/// Number of MWAIT C-states described by CPUID leaf 5 (C0..C7)
+pub const MWAIT_MAX_CSTATES: usize = 8;
+
+/// MONITOR/MWAIT information (CPUID leaf 5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MwaitInfo {
+    /// Smallest monitor line size in bytes
+    pub min_line: u16,
+    /// Largest monitor line size in bytes
+    pub max_line: u16,
+    /// MWAIT extensions are enumerated (ECX bit 0)
+    pub extensions: bool,
+    /// Interrupts break MWAIT even when masked (ECX bit 1)
+    pub interrupt_break: bool,
+    /// Number of MWAIT sub-states per C-state (EDX nibbles, index = Cn)
+    pub substates: [u8; MWAIT_MAX_CSTATES],
+}
+
+impl MwaitInfo {
+    /// Decode leaf 5 registers
+    pub fn from_leaf5(eax: u32, ebx: u32, ecx: u32, edx: u32) -> Self {
+        let mut substates = [0u8; MWAIT_MAX_CSTATES];
+        for (n, count) in substates.iter_mut().enumerate() {
+            *count = ((edx >> (n * 4)) & 0xF) as u8;
+        }
+        Self {
+            min_line: eax as u16,
+            max_line: ebx as u16,
+            extensions: ecx & 1 != 0,
+            interrupt_break: ecx & 2 != 0,
+            substates,
+        }
+    }
+}
+
+/// Register holding a feature flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureReg {
+    /// Leaf 1 ECX
+    Leaf1Ecx,
+    /// Leaf 1 EDX
+    Leaf1Edx,
+    /// Leaf 7 sub-leaf 0 EBX
+    Leaf7Ebx,
+    /// Leaf 7 sub-leaf 0 ECX
+    Leaf7Ecx,
+    /// Leaf 0x8000_0001 ECX
+    ExtEcx,
+    /// Leaf 0x8000_0001 EDX
+    ExtEdx,
+}
+
+/// Known feature flags: (register, bit, name)
+pub const FEATURE_FLAGS: &[(FeatureReg, u8, &str)] = &[
+    (FeatureReg::Leaf1Edx, 0, "fpu"),
+    (FeatureReg::Leaf1Edx, 1, "vme"),
+    (FeatureReg::Leaf1Edx, 2, "de"),
+    (FeatureReg::Leaf1Edx, 3, "pse"),
+    (FeatureReg::Leaf1Edx, 4, "tsc"),
+    (FeatureReg::Leaf1Edx, 5, "msr"),
+    (FeatureReg::Leaf1Edx, 6, "pae"),
+    (FeatureReg::Leaf1Edx, 7, "mce"),
+    (FeatureReg::Leaf1Edx, 8, "cx8"),
+    (FeatureReg::Leaf1Edx, 9, "apic"),
+    (FeatureReg::Leaf1Edx, 11, "sep"),
+    (FeatureReg::Leaf1Edx, 12, "mtrr"),
+    (FeatureReg::Leaf1Edx, 13, "pge"),
+    (FeatureReg::Leaf1Edx, 14, "mca"),
+    (FeatureReg::Leaf1Edx, 15, "cmov"),
+    (FeatureReg::Leaf1Edx, 16, "pat"),
+    (FeatureReg::Leaf1Edx, 17, "pse36"),
+    (FeatureReg::Leaf1Edx, 19, "clflush"),
+    (FeatureReg::Leaf1Edx, 23, "mmx"),
+    (FeatureReg::Leaf1Edx, 24, "fxsr"),
+    (FeatureReg::Leaf1Edx, 25, "sse"),
+    (FeatureReg::Leaf1Edx, 26, "sse2"),
+    (FeatureReg::Leaf1Edx, 28, "ht"),
+    (FeatureReg::ExtEdx, 11, "syscall"),
+    (FeatureReg::ExtEdx, 20, "nx"),
+    (FeatureReg::ExtEdx, 26, "pdpe1gb"),
+    (FeatureReg::ExtEdx, 27, "rdtscp"),
+    (FeatureReg::ExtEdx, 29, "lm"),
+    (FeatureReg::Leaf1Ecx, 0, "pni"),
+    (FeatureReg::Leaf1Ecx, 1, "pclmulqdq"),
+    (FeatureReg::Leaf1Ecx, 3, "monitor"),
+    (FeatureReg::Leaf1Ecx, 5, "vmx"),
+    (FeatureReg::Leaf1Ecx, 9, "ssse3"),
+    (FeatureReg::Leaf1Ecx, 12, "fma"),
+    (FeatureReg::Leaf1Ecx, 13, "cx16"),
+    (FeatureReg::Leaf1Ecx, 19, "sse4_1"),
+    (FeatureReg::Leaf1Ecx, 20, "sse4_2"),
+    (FeatureReg::Leaf1Ecx, 21, "x2apic"),
+    (FeatureReg::Leaf1Ecx, 22, "movbe"),
+    (FeatureReg::Leaf1Ecx, 23, "popcnt"),
+    (FeatureReg::Leaf1Ecx, 24, "tsc_deadline_timer"),
+    (FeatureReg::Leaf1Ecx, 25, "aes"),
+    (FeatureReg::Leaf1Ecx, 26, "xsave"),
+    (FeatureReg::Leaf1Ecx, 28, "avx"),
+    (FeatureReg::Leaf1Ecx, 29, "f16c"),
+    (FeatureReg::Leaf1Ecx, 30, "rdrand"),
+    (FeatureReg::Leaf1Ecx, 31, "hypervisor"),
+    (FeatureReg::ExtEcx, 0, "lahf_lm"),
+    (FeatureReg::ExtEcx, 2, "svm"),
+    (FeatureReg::ExtEcx, 5, "abm"),
+    (FeatureReg::ExtEcx, 6, "sse4a"),
+    (FeatureReg::ExtEcx, 8, "3dnowprefetch"),
+    (FeatureReg::Leaf7Ebx, 0, "fsgsbase"),
+    (FeatureReg::Leaf7Ebx, 3, "bmi1"),
+    (FeatureReg::Leaf7Ebx, 4, "hle"),
+    (FeatureReg::Leaf7Ebx, 5, "avx2"),
+    (FeatureReg::Leaf7Ebx, 7, "smep"),
+    (FeatureReg::Leaf7Ebx, 8, "bmi2"),
+    (FeatureReg::Leaf7Ebx, 9, "erms"),
+    (FeatureReg::Leaf7Ebx, 10, "invpcid"),
+    (FeatureReg::Leaf7Ebx, 11, "rtm"),
+    (FeatureReg::Leaf7Ebx, 16, "avx512f"),
+    (FeatureReg::Leaf7Ebx, 18, "rdseed"),
+    (FeatureReg::Leaf7Ebx, 19, "adx"),
+    (FeatureReg::Leaf7Ebx, 20, "smap"),
+    (FeatureReg::Leaf7Ebx, 23, "clflushopt"),
+    (FeatureReg::Leaf7Ebx, 24, "clwb"),
+    (FeatureReg::Leaf7Ebx, 29, "sha_ni"),
+    (FeatureReg::Leaf7Ecx, 2, "umip"),
+    (FeatureReg::Leaf7Ecx, 3, "pku"),
+    (FeatureReg::Leaf7Ecx, 16, "la57"),
+    (FeatureReg::Leaf7Ecx, 22, "rdpid"),
+];
+
+/// Decode (family, model, stepping) from leaf 1 EAX
+///
+/// The extended family/model fields are folded in the way Intel and AMD
+/// document for display purposes.
+pub fn decode_signature(eax: u32) -> (u32, u32, u32) {
+    let stepping = eax & 0xF;
+    let base_model = (eax >> 4) & 0xF;
+    let base_family = (eax >> 8) & 0xF;
+    let ext_model = (eax >> 16) & 0xF;
+    let ext_family = (eax >> 20) & 0xFF;
+
+    let family = if base_family == 0xF { base_family + ext_family } else { base_family };
+    let model = if base_family == 0x6 || base_family == 0xF {
+        (ext_model << 4) | base_model
+    } else {
+        base_model
+    };
+    (family, model, stepping)
+}
+
+/// Cached CPU identification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuFeatures {
+    /// Vendor string (e.g. "GenuineIntel")
+    pub vendor: [u8; 12],
+    /// Brand string (e.g. "Intel(R) Core(TM) ..."), null-padded
+    pub brand: [u8; 48],
+    /// Maximum basic CPUID leaf
+    pub max_leaf: u32,
+    /// Maximum extended CPUID leaf
+    pub max_ext_leaf: u32,
+    /// Display family
+    pub family: u32,
+    /// Display model
+    pub model: u32,
+    /// Stepping
+    pub stepping: u32,
+    /// Feature flag registers (indexed by [`FeatureReg`])
+    pub regs: [u32; 6],
+    /// Logical CPUs per package
+    pub threads_per_package: u32,
+    /// Cores per package
+    pub cores_per_package: u32,
+    /// MONITOR/MWAIT support, if present
+    pub mwait: Option<MwaitInfo>,
+    /// IA32_APERF / IA32_MPERF are available
+    pub aperf_mperf: bool,
+    /// Base frequency in MHz (0 if not reported)
+    pub base_mhz: u32,
+    /// Maximum frequency in MHz (0 if not reported)
+    pub max_mhz: u32,
+    /// Bus (reference) frequency in MHz (0 if not reported)
+    pub bus_mhz: u32,
+}
+
+impl CpuFeatures {
+    /// Vendor string as `&str`
+    pub fn vendor_str(&self) -> &str {
+        core::str::from_utf8(&self.vendor).unwrap_or("unknown")
+    }
+
+    /// Brand string as `&str` (empty if not reported)
+    pub fn brand_str(&self) -> &str {
+        let len = self.brand.iter().position(|&b| b == 0).unwrap_or(self.brand.len());
+        core::str::from_utf8(&self.brand[..len]).unwrap_or("").trim()
+    }
+
+    /// Check a feature bit
+    pub fn has(&self, reg: FeatureReg, bit: u8) -> bool {
+        self.regs[reg as usize] & (1 << bit) != 0
+    }
+
+    /// Names of the supported feature flags
+    pub fn flags(&self) -> impl Iterator<Item = &'static str> + '_ {
+        FEATURE_FLAGS
+            .iter()
+            .filter(move |&&(reg, bit, _)| self.has(reg, bit))
+            .map(|&(_, _, name)| name)
+    }
+
+    /// Check a feature flag by name (e.g. "avx2")
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags().any(|f| f == name)
+    }
+
+    /// Read all leaves from the running CPU
+    fn detect() -> Self {
+        let leaf0 = cpuid(0, 0);
+        let max_leaf = leaf0.eax;
+
+        let mut vendor = [0u8; 12];
+        vendor[0..4].copy_from_slice(&leaf0.ebx.to_le_bytes());
+        vendor[4..8].copy_from_slice(&leaf0.edx.to_le_bytes());
+        vendor[8..12].copy_from_slice(&leaf0.ecx.to_le_bytes());
+
+        let leaf1 = if max_leaf >= 1 { cpuid(1, 0) } else { CpuidResult { eax: 0, ebx: 0, ecx: 0, edx: 0 } };
+        let (family, model, stepping) = decode_signature(leaf1.eax);
+        let leaf7 = if max_leaf >= 7 { cpuid(7, 0) } else { CpuidResult { eax: 0, ebx: 0, ecx: 0, edx: 0 } };
+
+        let max_ext_leaf = cpuid(0x8000_0000, 0).eax;
+        let (ext_ecx, ext_edx) = if max_ext_leaf >= 0x8000_0001 {
+            let r = cpuid(0x8000_0001, 0);
+            (r.ecx, r.edx)
+        } else {
+            (0, 0)
+        };
+
+        let mut brand = [0u8; 48];
+        if max_ext_leaf >= 0x8000_0004 {
+            for (i, leaf) in (0x8000_0002u32..=0x8000_0004).enumerate() {
+                let r = cpuid(leaf, 0);
+                for (j, reg) in [r.eax, r.ebx, r.ecx, r.edx].iter().enumerate() {
+                    let at = i * 16 + j * 4;
+                    brand[at..at + 4].copy_from_slice(&reg.to_le_bytes());
+                }
+            }
+        }
+
+        // Leaf 1 EBX[23:16] is only valid with HTT (EDX bit 28)
+        let threads_per_package = if leaf1.edx & (1 << 28) != 0 {
+            core::cmp::max((leaf1.ebx >> 16) & 0xFF, 1)
+        } else {
+            1
+        };
+        let cores_per_package = if &vendor == b"GenuineIntel" && max_leaf >= 4 {
+            (cpuid(4, 0).eax >> 26) + 1
+        } else if max_ext_leaf >= 0x8000_0008 {
+            (cpuid(0x8000_0008, 0).ecx & 0xFF) + 1
+        } else {
+            1
+        };
+
+        let has_monitor = leaf1.ecx & (1 << 3) != 0;
+        let mwait = if has_monitor && max_leaf >= 5 {
+            let r = cpuid(5, 0);
+            Some(MwaitInfo::from_leaf5(r.eax, r.ebx, r.ecx, r.edx))
+        } else {
+            None
+        };
+
+        let aperf_mperf = max_leaf >= 6 && cpuid(6, 0).ecx & 1 != 0;
+
+        let (base_mhz, max_mhz, bus_mhz) = if max_leaf >= 0x16 {
+            let r = cpuid(0x16, 0);
+            (r.eax & 0xFFFF, r.ebx & 0xFFFF, r.ecx & 0xFFFF)
+        } else {
+            (0, 0, 0)
+        };
+
+        Self {
+            vendor,
+            brand,
+            max_leaf,
+            max_ext_leaf,
+            family,
+            model,
+            stepping,
+            regs: [leaf1.ecx, leaf1.edx, leaf7.ebx, leaf7.ecx, ext_ecx, ext_edx],
+            threads_per_package,
+            cores_per_package: core::cmp::min(cores_per_package, threads_per_package),
+            mwait,
+            aperf_mperf,
+            base_mhz,
+            max_mhz,
+            bus_mhz,
+        }
+    }
+}
+
+/// Cached result of [`CpuFeatures::detect`]
+static FEATURES: SpinMutex<Option<CpuFeatures>> = SpinMutex::new(None);
+
+/// Get the CPU features, detecting them on first use
+pub fn get() -> CpuFeatures {
+    let mut features = FEATURES.lock();
+    *features.get_or_insert_with(CpuFeatures::detect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leaf5_decode() {
+        // C0: 0, C1: 2, C2: 1, C3: 0, C4: 1 sub-states
+        let info = MwaitInfo::from_leaf5(64, 64, 0b11, 0x0001_0120);
+        assert_eq!(info.min_line, 64);
+        assert!(info.extensions);
+        assert!(info.interrupt_break);
+        assert_eq!(info.substates, [0, 2, 1, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decode_signature() {
+        // Intel family 6 model 0x9E stepping 10
+        assert_eq!(decode_signature(0x0009_06EA), (6, 0x9E, 10));
+        // AMD family 0x17 (0xF + 0x8) model 0x71 stepping 0
+        assert_eq!(decode_signature(0x0087_0F10), (0x17, 0x71, 0));
+    }
+}
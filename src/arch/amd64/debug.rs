@@ -0,0 +1,452 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Hardware Breakpoints and Watchpoints
+//!
+//! Manages the debug registers (DR0-DR7) on behalf of a debugger. Each
+//! process owns a [`HwDebugState`] with up to [`NUM_SLOTS`] breakpoints
+//! or watchpoints on userspace addresses.
+//!
+//! # Context Switch
+//!
+//! The hardware only ever holds the state of the running process. On a
+//! switch [`switch_state`] loads the next process's slots, or disables
+//! DR7 if the next process has none and the previous one did. DR0-DR3
+//! and DR7 are never changed by the CPU, so nothing needs saving; DR6 is
+//! consumed by the #DB handler.
+//!
+//! # #DB Exceptions
+//!
+//! [`handle_exception`] decodes DR6. A hit on one of the current
+//! process's slots, or a single step in userspace, is recorded as a
+//! [`DebugException`] on the process and the process is suspended; it
+//! stops at its next suspension point (see [`crate::sched::suspend`])
+//! until the debugger resumes it. Anything else is a kernel bug.
+//!
+//! # Constraints
+//!
+//! | Kind | Lengths | Alignment |
+//! |------|---------|-----------|
+//! | Execute | 1 | none |
+//! | Write, ReadWrite | 1, 2, 4, 8 | `len` |
+
+use crate::arch::amd64::mm::RxStatus;
+use crate::arch::amd64::registers::{rflags, X86DebugState};
+use crate::process::table::{Process, PROCESS_TABLE};
+use crate::arch::amd64::syscall::X86Iframe;
+
+/// Number of hardware breakpoint slots (DR0-DR3)
+pub const NUM_SLOTS: usize = 4;
+
+/// Highest address a breakpoint may cover (exclusive)
+const USER_ADDR_END: u64 = crate::arch::amd64::mm::layout::USER_END;
+
+/// DR6 bits
+pub mod dr6 {
+    /// Slot 0-3 condition detected (B0-B3)
+    pub const HIT_MASK: u64 = 0xF;
+    /// Single step (BS)
+    pub const BS: u64 = 1 << 14;
+    /// Value with no conditions pending (reserved bits read as 1)
+    pub const INIT: u64 = 0xFFFF_0FF0;
+}
+
+/// DR7 bits
+pub mod dr7 {
+    /// Local enable bit of a slot (L0-L3)
+    pub const fn local_enable(slot: usize) -> u64 {
+        1 << (slot * 2)
+    }
+    /// Shift of the R/W and LEN fields of a slot
+    pub const fn field_shift(slot: usize) -> u64 {
+        16 + slot as u64 * 4
+    }
+}
+
+/// What a slot triggers on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakKind {
+    /// Instruction fetch
+    Execute,
+    /// Data write
+    Write,
+    /// Data read or write
+    ReadWrite,
+}
+
+impl BreakKind {
+    /// DR7 R/W field encoding
+    const fn rw_bits(self) -> u64 {
+        match self {
+            Self::Execute => 0b00,
+            Self::Write => 0b01,
+            Self::ReadWrite => 0b11,
+        }
+    }
+}
+
+/// A hardware breakpoint or watchpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HwBreakpoint {
+    /// Userspace address
+    pub addr: u64,
+    /// Trigger condition
+    pub kind: BreakKind,
+    /// Watched length in bytes (1 for `Execute`)
+    pub len: u8,
+}
+
+impl HwBreakpoint {
+    /// An instruction breakpoint
+    pub const fn execute(addr: u64) -> Self {
+        Self { addr, kind: BreakKind::Execute, len: 1 }
+    }
+
+    /// A data watchpoint
+    pub const fn watch(addr: u64, kind: BreakKind, len: u8) -> Self {
+        Self { addr, kind, len }
+    }
+
+    /// Check the constraints in the module docs
+    pub fn validate(&self) -> Result<(), RxStatus> {
+        let len_ok = match self.kind {
+            BreakKind::Execute => self.len == 1,
+            BreakKind::Write | BreakKind::ReadWrite => matches!(self.len, 1 | 2 | 4 | 8),
+        };
+        if !len_ok || !self.addr.is_multiple_of(self.len as u64) {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        if self.addr.checked_add(self.len as u64).is_none_or(|end| end > USER_ADDR_END) {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        Ok(())
+    }
+
+    /// DR7 LEN field encoding
+    const fn len_bits(&self) -> u64 {
+        match self.len {
+            2 => 0b01,
+            8 => 0b10,
+            4 => 0b11,
+            _ => 0b00,
+        }
+    }
+}
+
+/// Why a #DB was delivered to a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugStop {
+    /// An `Execute` slot hit
+    Breakpoint { slot: usize },
+    /// A `Write`/`ReadWrite` slot hit
+    Watchpoint { slot: usize },
+    /// Single step (RFLAGS.TF)
+    SingleStep,
+}
+
+/// A #DB recorded for the debugger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugException {
+    /// What triggered
+    pub stop: DebugStop,
+    /// Instruction pointer at the exception
+    pub ip: u64,
+    /// Raw DR6
+    pub dr6: u64,
+}
+
+/// Per-process debug register state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HwDebugState {
+    slots: [Option<HwBreakpoint>; NUM_SLOTS],
+}
+
+impl HwDebugState {
+    /// No slots in use
+    pub const fn new() -> Self {
+        Self { slots: [None; NUM_SLOTS] }
+    }
+
+    /// Install a breakpoint or watchpoint in `slot`, replacing any
+    /// previous one
+    pub fn set(&mut self, slot: usize, bp: HwBreakpoint) -> Result<(), RxStatus> {
+        if slot >= NUM_SLOTS {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        bp.validate()?;
+        self.slots[slot] = Some(bp);
+        Ok(())
+    }
+
+    /// Remove the breakpoint in `slot`
+    ///
+    /// # Returns
+    ///
+    /// `ERR_NOT_FOUND` if the slot was empty
+    pub fn clear(&mut self, slot: usize) -> Result<(), RxStatus> {
+        if slot >= NUM_SLOTS {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        self.slots[slot].take().map(|_| ()).ok_or(RxStatus::ERR_NOT_FOUND)
+    }
+
+    /// Breakpoint in `slot`
+    pub fn get(&self, slot: usize) -> Option<HwBreakpoint> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    /// Whether any slot is in use
+    pub fn is_active(&self) -> bool {
+        self.slots.iter().any(Option::is_some)
+    }
+
+    /// DR7 value enabling the used slots
+    pub fn dr7(&self) -> u64 {
+        self.slots.iter().enumerate().fold(0, |value, (slot, bp)| match bp {
+            Some(bp) => {
+                value | dr7::local_enable(slot)
+                    | (bp.kind.rw_bits() | bp.len_bits() << 2) << dr7::field_shift(slot)
+            }
+            None => value,
+        })
+    }
+
+    /// Register image to load
+    pub fn to_regs(&self) -> X86DebugState {
+        let addr = |slot: usize| self.slots[slot].map_or(0, |bp| bp.addr);
+        X86DebugState {
+            dr0: addr(0),
+            dr1: addr(1),
+            dr2: addr(2),
+            dr3: addr(3),
+            dr6: dr6::INIT,
+            dr7: self.dr7(),
+        }
+    }
+
+    /// Classify a #DB from its DR6 value
+    ///
+    /// # Returns
+    ///
+    /// None if DR6 names no slot of this state and no single step
+    pub fn decode(&self, status: u64) -> Option<DebugStop> {
+        let hit = (0..NUM_SLOTS)
+            .find(|&slot| status & (1 << slot) != 0 && self.slots[slot].is_some());
+        match hit {
+            Some(slot) if self.slots[slot].map(|bp| bp.kind) == Some(BreakKind::Execute) => {
+                Some(DebugStop::Breakpoint { slot })
+            }
+            Some(slot) => Some(DebugStop::Watchpoint { slot }),
+            None if status & dr6::BS != 0 => Some(DebugStop::SingleStep),
+            None => None,
+        }
+    }
+}
+
+// ============================================================================
+// Register Access
+// ============================================================================
+
+/// Write DR0-DR3, DR6 and DR7
+///
+/// # Safety
+///
+/// Must run at CPL 0. The addresses must not cover kernel code the
+/// #DB path itself uses.
+pub unsafe fn load(regs: &X86DebugState) {
+    // Disable first so no half-loaded slot can fire
+    core::arch::asm!("mov dr7, {}", in(reg) 0u64, options(nomem, nostack));
+    core::arch::asm!("mov dr0, {}", in(reg) regs.dr0, options(nomem, nostack));
+    core::arch::asm!("mov dr1, {}", in(reg) regs.dr1, options(nomem, nostack));
+    core::arch::asm!("mov dr2, {}", in(reg) regs.dr2, options(nomem, nostack));
+    core::arch::asm!("mov dr3, {}", in(reg) regs.dr3, options(nomem, nostack));
+    core::arch::asm!("mov dr6, {}", in(reg) regs.dr6, options(nomem, nostack));
+    core::arch::asm!("mov dr7, {}", in(reg) regs.dr7, options(nomem, nostack));
+}
+
+/// Disable every slot (DR7 = 0)
+///
+/// # Safety
+///
+/// Must run at CPL 0.
+pub unsafe fn disable() {
+    core::arch::asm!("mov dr7, {}", in(reg) 0u64, options(nomem, nostack));
+}
+
+/// Read and clear DR6
+///
+/// # Safety
+///
+/// Must run at CPL 0.
+unsafe fn take_dr6() -> u64 {
+    let value: u64;
+    core::arch::asm!("mov {}, dr6", out(reg) value, options(nomem, nostack));
+    core::arch::asm!("mov dr6, {}", in(reg) dr6::INIT, options(nomem, nostack));
+    value
+}
+
+// ============================================================================
+// Process Integration
+// ============================================================================
+
+/// Install a breakpoint or watchpoint on a process
+///
+/// Takes effect immediately if `process` is the one running on this CPU,
+/// otherwise at its next switch in.
+pub fn set(process: &mut Process, slot: usize, bp: HwBreakpoint) -> Result<(), RxStatus> {
+    process.debug_state.set(slot, bp)?;
+    reload_if_current(process);
+    Ok(())
+}
+
+/// Remove a breakpoint or watchpoint from a process
+pub fn clear(process: &mut Process, slot: usize) -> Result<(), RxStatus> {
+    process.debug_state.clear(slot)?;
+    reload_if_current(process);
+    Ok(())
+}
+
+/// Take the last #DB recorded on a process, if any
+pub fn take_exception(process: &mut Process) -> Option<DebugException> {
+    process.debug_exception.take()
+}
+
+/// Load the hardware state of a process that is running right now
+fn reload_if_current(process: &Process) {
+    if process.state != crate::process::table::ProcessState::Running {
+        return;
+    }
+    unsafe {
+        if process.debug_state.is_active() {
+            load(&process.debug_state.to_regs());
+        } else {
+            disable();
+        }
+    }
+}
+
+/// Swap the hardware state on a context switch
+///
+/// # Arguments
+///
+/// * `prev_active` - Whether the outgoing process had any slot in use
+/// * `next` - Debug state of the incoming process
+///
+/// # Safety
+///
+/// Must be called by the scheduler right before switching to `next`.
+pub unsafe fn switch_state(prev_active: bool, next: &HwDebugState) {
+    if next.is_active() {
+        load(&next.to_regs());
+    } else if prev_active {
+        disable();
+    }
+}
+
+/// Handle a #DB exception
+///
+/// # Returns
+///
+/// true if the exception was delivered to the current process; false if
+/// it belongs to nobody and the kernel should die
+pub fn handle_exception(frame: &mut X86Iframe) -> bool {
+    let dr6 = unsafe { take_dr6() };
+    let user_ip = frame.ip < USER_ADDR_END;
+
+    let mut table = match PROCESS_TABLE.try_lock() {
+        Some(table) => table,
+        None => return false,
+    };
+    let process = match table.current_thread_mut() {
+        Some(p) => p,
+        None => return false,
+    };
+
+    let stop = match process.debug_state.decode(dr6) {
+        Some(DebugStop::SingleStep) if !user_ip => return false,
+        Some(stop) => stop,
+        None => return false,
+    };
+
+    process.debug_exception = Some(DebugException { stop, ip: frame.ip, dr6 });
+    if stop == DebugStop::SingleStep {
+        frame.flags &= !rflags::TF;
+    }
+    if let DebugStop::Breakpoint { .. } = stop {
+        // Faulting #DB: let the instruction run once when resumed
+        frame.flags |= rflags::RF;
+    }
+
+    // A watchpoint can trigger on a user copy inside a syscall; either
+    // way the process stops at its next suspension point
+    let _ = crate::sched::suspend::suspend(process);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(HwBreakpoint::execute(0x40_1003).validate().is_ok());
+        assert!(HwBreakpoint::watch(0x1000, BreakKind::Write, 8).validate().is_ok());
+        assert_eq!(
+            HwBreakpoint::watch(0x1004, BreakKind::Write, 8).validate(),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            HwBreakpoint::watch(0x1000, BreakKind::ReadWrite, 3).validate(),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            HwBreakpoint::execute(0xFFFF_8000_0000_0000).validate(),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+    }
+
+    #[test]
+    fn test_dr7_encoding() {
+        let mut state = HwDebugState::new();
+        assert_eq!(state.dr7(), 0);
+
+        state.set(0, HwBreakpoint::execute(0x40_0000)).unwrap();
+        state.set(2, HwBreakpoint::watch(0x60_0000, BreakKind::Write, 4)).unwrap();
+        // L0; L2 with R/W=01, LEN=11 at bits 24-27
+        assert_eq!(state.dr7(), 0b1 | 0b1 << 4 | 0b1101 << 24);
+
+        let regs = state.to_regs();
+        assert_eq!(regs.dr0, 0x40_0000);
+        assert_eq!(regs.dr2, 0x60_0000);
+        assert_eq!(regs.dr1, 0);
+    }
+
+    #[test]
+    fn test_set_clear() {
+        let mut state = HwDebugState::new();
+        assert_eq!(state.set(4, HwBreakpoint::execute(0x1000)), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(state.clear(1), Err(RxStatus::ERR_NOT_FOUND));
+
+        state.set(1, HwBreakpoint::execute(0x1000)).unwrap();
+        assert!(state.is_active());
+        assert_eq!(state.get(1), Some(HwBreakpoint::execute(0x1000)));
+        state.clear(1).unwrap();
+        assert!(!state.is_active());
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut state = HwDebugState::new();
+        state.set(0, HwBreakpoint::execute(0x1000)).unwrap();
+        state.set(3, HwBreakpoint::watch(0x2000, BreakKind::ReadWrite, 1)).unwrap();
+
+        assert_eq!(state.decode(dr6::INIT | 0b0001), Some(DebugStop::Breakpoint { slot: 0 }));
+        assert_eq!(state.decode(dr6::INIT | 0b1000), Some(DebugStop::Watchpoint { slot: 3 }));
+        assert_eq!(state.decode(dr6::INIT | dr6::BS), Some(DebugStop::SingleStep));
+        // B1 set for an unused slot is not ours
+        assert_eq!(state.decode(dr6::INIT | 0b0010), None);
+    }
+}
@@ -7,6 +7,18 @@
 //! x86 Descriptor Tables
 //!
 //! This module provides GDT and IDT setup functions.
+//!
+//! Every CPU gets its own GDT and TSS, built with [`GdtBuilder`] and
+//! [`TssBuilder`] by [`cpu_init`] during its bring-up. The TSS carries
+//! the CPU's IST stacks for NMI, #MC and #DF, so those vectors always
+//! run on a known-good stack. The IDT is shared.
+//!
+//! The TSS is followed by an I/O permission bitmap, which the scheduler
+//! loads with the port ranges of the process it switches to
+//! ([`load_io_ports`]).
+
+use crate::interrupt::affinity::MAX_CPUS;
+use super::ioport::{IoPermissionBitmap, PortGrants, IOPB_SIZE};
 
 // ============================================================================
 // GDT (Global Descriptor Table) Structures
@@ -76,11 +88,6 @@ pub const ACC_DPL3: u8 = 0x60;
 pub const FLAG_GRANULARITY_4K: u8 = 0x80;
 pub const FLAG_SIZE_64BIT: u8 = 0x20;
 
-// Global GDT storage
-static mut GDT: [GdtEntry; GDT_ENTRIES] = [GdtEntry::null(); GDT_ENTRIES];
-static mut GDT_POINTER: GdtPointer = GdtPointer { limit: 0, base: 0 };
-static mut TSS: TaskStateSegment = TaskStateSegment::null();
-
 impl GdtEntry {
     pub const fn null() -> Self {
         Self {
@@ -115,14 +122,15 @@ impl GdtEntry {
         }
     }
 
+    /// Upper half of a 16-byte TSS descriptor: base[63:32], then reserved
     pub fn set_tss_high(base: u64) -> Self {
         Self {
             limit_low: ((base >> 32) & 0xFFFF) as u16,
-            base_low: 0,
+            base_low: ((base >> 48) & 0xFFFF) as u16,
             base_mid: 0,
             access: 0,
-            flags_limit_high: ((base >> 48) as u8) & 0xFF,
-            base_high: ((base >> 56) as u8) & 0xFF,
+            flags_limit_high: 0,
+            base_high: 0,
         }
     }
 }
@@ -149,14 +157,92 @@ impl TaskStateSegment {
     }
 }
 
-/// Setup the GDT (Global Descriptor Table)
-pub fn gdt_setup() {
-    unsafe {
-        // Null descriptor (required)
-        GDT[GDT_NULL] = GdtEntry::null();
+// ============================================================================
+// Builders
+// ============================================================================
+
+/// IST slots of the critical vectors (TSS `ist1`..`ist7`)
+pub mod ist {
+    /// NMI
+    pub const NMI: u8 = 1;
+    /// Machine check (#MC)
+    pub const MACHINE_CHECK: u8 = 2;
+    /// Double fault (#DF)
+    pub const DOUBLE_FAULT: u8 = 3;
+}
+
+/// Selector of the TSS descriptor
+pub const TSS_SELECTOR: u16 = (GDT_TSS_LOW * 8) as u16;
+
+/// Builds a [`TaskStateSegment`]
+///
+/// ```rust
+/// let tss = TssBuilder::new()
+///     .rsp0(kernel_stack_top)
+///     .ist(ist::NMI, nmi_stack_top)
+///     .build();
+/// ```
+pub struct TssBuilder {
+    tss: TaskStateSegment,
+}
+
+impl TssBuilder {
+    /// Empty TSS whose I/O permission bitmap directly follows it
+    pub const fn new() -> Self {
+        let mut tss = TaskStateSegment::null();
+        tss.iomap_base = core::mem::size_of::<TaskStateSegment>() as u16;
+        Self { tss }
+    }
+
+    /// Stack for interrupts arriving from ring 3
+    pub const fn rsp0(mut self, stack_top: u64) -> Self {
+        self.tss.rsp0 = stack_top;
+        self
+    }
+
+    /// Stack for gates using IST `slot` (1-7)
+    ///
+    /// # Panics
+    ///
+    /// If `slot` is not 1-7
+    pub const fn ist(mut self, slot: u8, stack_top: u64) -> Self {
+        match slot {
+            1 => self.tss.ist1 = stack_top,
+            2 => self.tss.ist2 = stack_top,
+            3 => self.tss.ist3 = stack_top,
+            4 => self.tss.ist4 = stack_top,
+            5 => self.tss.ist5 = stack_top,
+            6 => self.tss.ist6 = stack_top,
+            7 => self.tss.ist7 = stack_top,
+            _ => panic!("IST slot out of range"),
+        }
+        self
+    }
+
+    /// Finish
+    pub const fn build(self) -> TaskStateSegment {
+        self.tss
+    }
+}
+
+impl Default for TssBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds a GDT: flat kernel and user segments plus a TSS descriptor
+pub struct GdtBuilder {
+    entries: [GdtEntry; GDT_ENTRIES],
+}
+
+impl GdtBuilder {
+    /// GDT with the kernel and user code/data segments
+    pub fn new() -> Self {
+        let mut entries = [GdtEntry::null(); GDT_ENTRIES];
 
         // Kernel code segment (64-bit)
-        GDT[GDT_KERNEL_CODE] = GdtEntry::set_gate(
+        entries[GDT_KERNEL_CODE] = GdtEntry::set_gate(
             0,                      // Base (ignored in long mode)
             0xFFFFF,                // Limit (ignored in long mode)
             ACC_PRESENT | ACC_CODE_DATA | ACC_CODE | ACC_DPL0, // Present, Code, DPL0
@@ -164,50 +250,124 @@ pub fn gdt_setup() {
         );
 
         // Kernel data segment
-        GDT[GDT_KERNEL_DATA] = GdtEntry::set_gate(
-            0,                      // Base (ignored in long mode)
-            0xFFFFF,                // Limit (ignored in long mode)
+        entries[GDT_KERNEL_DATA] = GdtEntry::set_gate(
+            0,
+            0xFFFFF,
             ACC_PRESENT | ACC_CODE_DATA | ACC_DATA | ACC_DPL0, // Present, Data, DPL0
-            FLAG_GRANULARITY_4K,                                      // 4KB pages
+            FLAG_GRANULARITY_4K,
         );
 
         // User code segment (64-bit)
-        GDT[GDT_USER_CODE] = GdtEntry::set_gate(
-            0,                      // Base (ignored in long mode)
-            0xFFFFF,                // Limit (ignored in long mode)
+        entries[GDT_USER_CODE] = GdtEntry::set_gate(
+            0,
+            0xFFFFF,
             ACC_PRESENT | ACC_CODE_DATA | ACC_CODE | ACC_DPL3, // Present, Code, DPL3
-            FLAG_GRANULARITY_4K | FLAG_SIZE_64BIT,               // 4KB pages, 64-bit
+            FLAG_GRANULARITY_4K | FLAG_SIZE_64BIT,
         );
 
         // User data segment
-        GDT[GDT_USER_DATA] = GdtEntry::set_gate(
-            0,                      // Base (ignored in long mode)
-            0xFFFFF,                // Limit (ignored in long mode)
+        entries[GDT_USER_DATA] = GdtEntry::set_gate(
+            0,
+            0xFFFFF,
             ACC_PRESENT | ACC_CODE_DATA | ACC_DATA | ACC_DPL3, // Present, Data, DPL3
-            FLAG_GRANULARITY_4K,                                      // 4KB pages
+            FLAG_GRANULARITY_4K,
         );
 
-        // TSS entry (needs two entries)
-        let tss_base = &TSS as *const TaskStateSegment as u64;
-        let tss_limit = core::mem::size_of::<TaskStateSegment>() as u32;
-        let tss_access = ACC_PRESENT | 0x09; // Present, TSS, DPL0
+        Self { entries }
+    }
 
-        GDT[GDT_TSS_LOW] = GdtEntry::set_tss_low(tss_base, tss_limit, tss_access);
-        GDT[GDT_TSS_HIGH] = GdtEntry::set_tss_high(tss_base);
+    /// Add the (available, DPL0) descriptor of the TSS at `tss`, which
+    /// must be followed by its I/O permission bitmap
+    pub fn tss(mut self, tss: *const TaskStateSegment) -> Self {
+        let base = tss as u64;
+        let limit = (core::mem::size_of::<TaskStateSegment>() + IOPB_SIZE) as u32 - 1;
+        self.entries[GDT_TSS_LOW] = GdtEntry::set_tss_low(base, limit, ACC_PRESENT | 0x09);
+        self.entries[GDT_TSS_HIGH] = GdtEntry::set_tss_high(base);
+        self
+    }
+
+    /// Finish
+    pub fn build(self) -> [GdtEntry; GDT_ENTRIES] {
+        self.entries
+    }
+}
 
-        // Setup GDT pointer
-        GDT_POINTER.limit = ((core::mem::size_of::<GdtEntry>() * GDT_ENTRIES) - 1) as u16;
-        GDT_POINTER.base = &GDT as *const GdtEntry as u64;
+impl Default for GdtBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        // Load GDT
-        gdt_load(&GDT_POINTER);
+// ============================================================================
+// Per-CPU Tables
+// ============================================================================
 
-        // Load TSS
-        let tss_selector = (GDT_TSS_LOW * 8) as u16;
-        tss_load(tss_selector);
+/// GDT and TSS of one CPU
+///
+/// Lives in a static: the CPU keeps referring to both by address after
+/// `lgdt`/`ltr`.
+#[repr(C, align(16))]
+pub struct CpuTables {
+    gdt: [GdtEntry; GDT_ENTRIES],
+    tss: TaskStateSegment,
+    /// At `iomap_base` from the TSS
+    iopb: IoPermissionBitmap,
+    /// Whether `iopb` allows any port
+    iopb_loaded: bool,
+    pointer: GdtPointer,
+}
+
+impl CpuTables {
+    const fn empty() -> Self {
+        Self {
+            gdt: [GdtEntry::null(); GDT_ENTRIES],
+            tss: TaskStateSegment::null(),
+            iopb: IoPermissionBitmap::new(),
+            iopb_loaded: false,
+            pointer: GdtPointer { limit: 0, base: 0 },
+        }
     }
 }
 
+const _: () = assert!(
+    core::mem::offset_of!(CpuTables, iopb)
+        == core::mem::offset_of!(CpuTables, tss) + core::mem::size_of::<TaskStateSegment>()
+);
+
+static mut CPU_TABLES: [CpuTables; MAX_CPUS] = [const { CpuTables::empty() }; MAX_CPUS];
+
+/// Build and load the GDT and TSS of `cpu`
+///
+/// The TSS gets this CPU's IST stacks for NMI, #MC and #DF (see [`ist`]).
+/// `rsp0` is filled in by the scheduler on each switch
+/// ([`entry::set_kernel_stack`](super::entry::set_kernel_stack)).
+///
+/// # Safety
+///
+/// Must run on `cpu` itself, during its bring-up.
+pub unsafe fn cpu_init(cpu: usize) {
+    let tables = &raw mut CPU_TABLES[cpu % MAX_CPUS];
+
+    (*tables).tss = TssBuilder::new()
+        .ist(ist::NMI, super::nmi::stack_top(cpu))
+        .ist(ist::MACHINE_CHECK, super::mce::stack_top(cpu))
+        .ist(ist::DOUBLE_FAULT, super::faults::double_fault_stack_top(cpu))
+        .build();
+    (*tables).gdt = GdtBuilder::new().tss(&raw const (*tables).tss).build();
+    (*tables).pointer = GdtPointer {
+        limit: (core::mem::size_of::<[GdtEntry; GDT_ENTRIES]>() - 1) as u16,
+        base: &raw const (*tables).gdt as u64,
+    };
+
+    gdt_load(&(*tables).pointer);
+    tss_load(TSS_SELECTOR);
+}
+
+/// Setup the GDT (Global Descriptor Table) of the boot CPU
+pub fn gdt_setup() {
+    unsafe { cpu_init(0) }
+}
+
 /// Setup the IDT (Interrupt Descriptor Table)
 ///
 /// This initializes the IDT with empty entries. Use `idt_set_gate` to install
@@ -389,11 +549,62 @@ pub unsafe fn tss_load(selector: u16) {
     core::arch::asm!("ltr {0:x}", in(reg) selector, options(nostack));
 }
 
-/// Get TSS reference for modification
+/// Get the TSS of `cpu` for modification
 ///
 /// # Safety
 ///
-/// Caller must ensure TSS has been initialized
-pub unsafe fn get_tss() -> &'static mut TaskStateSegment {
-    &mut TSS
+/// [`cpu_init`] must have run for `cpu`, and nothing else may be
+/// modifying the same TSS.
+pub unsafe fn tss(cpu: usize) -> &'static mut TaskStateSegment {
+    &mut CPU_TABLES[cpu % MAX_CPUS].tss
+}
+
+/// Let ring 3 on `cpu` use the ports in `grants` and no others
+///
+/// Called on every switch to a process and when the running process
+/// changes its grants. Does nothing while neither the previous nor the
+/// next process has ports.
+///
+/// # Safety
+///
+/// Must run on `cpu` itself, which must not return to ring 3 until this
+/// is done.
+pub unsafe fn load_io_ports(cpu: usize, grants: &PortGrants) {
+    let tables = &raw mut CPU_TABLES[cpu % MAX_CPUS];
+    if !(*tables).iopb_loaded && grants.is_empty() {
+        return;
+    }
+    (*tables).iopb.load(grants);
+    (*tables).iopb_loaded = !grants.is_empty();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tss_builder() {
+        let tss = TssBuilder::new().rsp0(0x1000).ist(ist::NMI, 0x2000).ist(ist::DOUBLE_FAULT, 0x3000).build();
+        assert_eq!({ tss.rsp0 }, 0x1000);
+        assert_eq!({ tss.ist1 }, 0x2000);
+        assert_eq!({ tss.ist2 }, 0);
+        assert_eq!({ tss.ist3 }, 0x3000);
+        assert_eq!({ tss.iomap_base } as usize, core::mem::size_of::<TaskStateSegment>());
+    }
+
+    #[test]
+    fn test_gdt_builder_tss_descriptor() {
+        let base = 0xFFFF_8000_1234_5678u64;
+        let gdt = GdtBuilder::new().tss(base as *const TaskStateSegment).build();
+
+        let low = gdt[GDT_TSS_LOW];
+        let high = gdt[GDT_TSS_HIGH];
+        assert_eq!({ low.base_low } as u64 | ({ low.base_mid } as u64) << 16 | ({ low.base_high } as u64) << 24, base & 0xFFFF_FFFF);
+        assert_eq!({ high.limit_low } as u64 | ({ high.base_low } as u64) << 16, base >> 32);
+        assert_eq!({ low.access }, ACC_PRESENT | 0x09);
+        // The limit covers the I/O permission bitmap and its final byte
+        assert_eq!({ low.limit_low } as usize, core::mem::size_of::<TaskStateSegment>() + IOPB_SIZE - 1);
+        assert_eq!(TSS_SELECTOR, 0x28);
+        assert_eq!({ gdt[GDT_USER_CODE].access } & ACC_DPL3, ACC_DPL3);
+    }
 }
@@ -0,0 +1,180 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot Entropy
+//!
+//! Early boot (before the PMM, heap or any driver) needs a few random
+//! numbers, mostly for KASLR. [`BootEntropy`] collects what the CPU offers
+//! and stretches it with SplitMix64.
+//!
+//! # Sources
+//!
+//! | Source | Availability |
+//! |--------|--------------|
+//! | `RDSEED` | CPUID.7.0:EBX[18] |
+//! | `RDRAND` | CPUID.1:ECX[30] |
+//! | TSC | Always (weak: mostly boot timing jitter) |
+//!
+//! # Deterministic Mode
+//!
+//! [`BootEntropy::from_seed`] ignores the hardware and derives every value
+//! from the seed, so a boot can be reproduced exactly (the `kaslr.seed=`
+//! command line option). Never use it for anything security relevant.
+
+use super::cpu_features::cpuid;
+
+/// Hardware retries before giving up on RDRAND/RDSEED
+const HW_RETRIES: usize = 10;
+
+/// Read a 64-bit value from RDRAND
+///
+/// # Returns
+///
+/// None if the instruction is unsupported or kept failing
+pub fn rdrand64() -> Option<u64> {
+    if cpuid(1, 0).ecx & (1 << 30) == 0 {
+        return None;
+    }
+    for _ in 0..HW_RETRIES {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdrand {v}",
+                "setc {ok}",
+                v = out(reg) value,
+                ok = out(reg_byte) ok,
+                options(nomem, nostack),
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Read a 64-bit value from RDSEED
+///
+/// # Returns
+///
+/// None if the instruction is unsupported or kept failing
+pub fn rdseed64() -> Option<u64> {
+    if cpuid(0, 0).eax < 7 || cpuid(7, 0).ebx & (1 << 18) == 0 {
+        return None;
+    }
+    for _ in 0..HW_RETRIES {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdseed {v}",
+                "setc {ok}",
+                v = out(reg) value,
+                ok = out(reg_byte) ok,
+                options(nomem, nostack),
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+        core::hint::spin_loop();
+    }
+    None
+}
+
+/// Early boot random number source
+#[derive(Debug, Clone, Copy)]
+pub struct BootEntropy {
+    state: u64,
+    deterministic: bool,
+    hardware: bool,
+}
+
+impl BootEntropy {
+    /// A reproducible stream derived only from `seed`
+    pub const fn from_seed(seed: u64) -> Self {
+        Self { state: seed, deterministic: true, hardware: false }
+    }
+
+    /// Gather entropy from the CPU
+    pub fn gather() -> Self {
+        let tsc = super::tsc::tsc_ticks();
+        let seed = rdseed64();
+        let rand = rdrand64();
+
+        let mut entropy = Self {
+            state: tsc,
+            deterministic: false,
+            hardware: seed.is_some() || rand.is_some(),
+        };
+        for extra in [seed, rand].into_iter().flatten() {
+            entropy.state ^= extra;
+            entropy.next_u64();
+        }
+        entropy
+    }
+
+    /// Whether the stream comes from a fixed seed
+    pub const fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Whether RDSEED or RDRAND contributed
+    pub const fn has_hardware_source(&self) -> bool {
+        self.hardware
+    }
+
+    /// Next value (SplitMix64)
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, bound)` (0 if `bound` is 0)
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return 0;
+        }
+        // Reject the short tail so every value is equally likely
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let v = self.next_u64();
+            if v < zone {
+                return v % bound;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_stream_is_reproducible() {
+        let mut a = BootEntropy::from_seed(42);
+        let mut b = BootEntropy::from_seed(42);
+        for _ in 0..8 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert!(a.is_deterministic());
+        assert_ne!(BootEntropy::from_seed(43).next_u64(), BootEntropy::from_seed(42).next_u64());
+    }
+
+    #[test]
+    fn test_below() {
+        let mut e = BootEntropy::from_seed(7);
+        for _ in 0..64 {
+            assert!(e.below(10) < 10);
+        }
+        assert_eq!(e.below(0), 0);
+        assert_eq!(e.below(1), 0);
+    }
+}
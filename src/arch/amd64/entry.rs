@@ -0,0 +1,375 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Entry/Exit GS Discipline
+//!
+//! The kernel reaches its per-CPU [`EntryArea`] through GS. While a CPU
+//! runs userspace, `IA32_GS_BASE` holds the user value and the kernel
+//! pointer waits in `IA32_KERNEL_GS_BASE`; every entry from userspace
+//! must execute exactly one `swapgs`, and the matching exit exactly one
+//! more. Getting this wrong hands the kernel a user-controlled GS.
+//!
+//! # Rules
+//!
+//! | Path | Swap on entry | Swap on exit |
+//! |------|---------------|--------------|
+//! | `syscall` ([`x86_64_syscall_stub`]) | always | always, right before `sysretq` |
+//! | Interrupt or exception, CS known | iff CS.RPL == 3 ([`GsGuard::from_cs`]) | same as entry |
+//! | NMI, #DB, #MC, or CS unknown | iff `IA32_GS_BASE` is not an entry area ([`GsGuard::paranoid`]) | same as entry |
+//! | First entry to userspace (`uspace_entry`) | - | always |
+//!
+//! The paranoid check reads the MSR instead of trusting CS because an NMI
+//! can land between the `syscall` instruction and its `swapgs` (CS is
+//! kernel, GS is still user) or between the exit `swapgs` and `sysretq`
+//! (CS is kernel, GS is already user).
+//!
+//! # Syscall Return
+//!
+//! `sysretq` to a non-canonical RIP raises #GP in ring 0 on the user
+//! stack on Intel CPUs. The stub refuses such returns and terminates the
+//! process instead. Caller-saved registers are cleared so no kernel
+//! values leak back to userspace.
+//!
+//! The user registers a syscall preserves are saved at the top of the
+//! kernel stack as a [`SyscallFrame`], so `FORK` can start the child
+//! where the parent will return.
+
+use core::arch::naked_asm;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::amd64::registers::{read_msr, write_msr, msr};
+use crate::interrupt::affinity::MAX_CPUS;
+
+/// Per-CPU data reached through GS on kernel entry
+///
+/// The offsets are used by [`x86_64_syscall_stub`]; keep them in sync.
+#[repr(C)]
+#[derive(Debug)]
+pub struct EntryArea {
+    /// Address of this area (`gs:[0]`)
+    pub self_ptr: u64,
+    /// Kernel stack top for `syscall` entries (`gs:[8]`)
+    pub kernel_sp: u64,
+    /// User RSP scratch slot during `syscall` (`gs:[16]`)
+    pub user_sp: u64,
+    /// CPU number (`gs:[24]`)
+    pub cpu: u64,
+}
+
+/// Offset of [`EntryArea::kernel_sp`]
+pub const KERNEL_SP_OFFSET: usize = 8;
+
+/// Offset of [`EntryArea::user_sp`]
+pub const USER_SP_OFFSET: usize = 16;
+
+/// Offset of [`EntryArea::cpu`]
+pub const CPU_OFFSET: usize = 24;
+
+impl EntryArea {
+    const fn new() -> Self {
+        Self { self_ptr: 0, kernel_sp: 0, user_sp: 0, cpu: 0 }
+    }
+}
+
+/// Entry areas, one per CPU
+static mut ENTRY_AREAS: [EntryArea; MAX_CPUS] = [const { EntryArea::new() }; MAX_CPUS];
+
+/// Whether [`init_cpu`] has run (GS checks are no-ops before)
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Point GS at this CPU's entry area
+///
+/// Leaves a zero user GS in `IA32_KERNEL_GS_BASE` for the first
+/// `swapgs` towards userspace.
+///
+/// # Safety
+///
+/// Must run on `cpu` in kernel mode, before any process is started.
+pub unsafe fn init_cpu(cpu: usize) {
+    let area = &raw mut ENTRY_AREAS[cpu % MAX_CPUS];
+    (*area).self_ptr = area as u64;
+    (*area).cpu = cpu as u64;
+    write_msr(msr::IA32_GS_BASE, area as u64);
+    write_msr(msr::IA32_KERNEL_GS_BASE, 0);
+    INITIALIZED.store(true, Ordering::Release);
+}
+
+/// Whether `gs_base` is the address of an entry area
+pub fn is_entry_area(gs_base: u64) -> bool {
+    let first = &raw const ENTRY_AREAS as u64;
+    let end = first + core::mem::size_of::<[EntryArea; MAX_CPUS]>() as u64;
+    gs_base >= first && gs_base < end
+        && (gs_base - first).is_multiple_of(core::mem::size_of::<EntryArea>() as u64)
+}
+
+/// Whether the kernel GS is active on this CPU (MSR check)
+pub fn kernel_gs_active() -> bool {
+    is_entry_area(unsafe { read_msr(msr::IA32_GS_BASE) })
+}
+
+/// Number of the running CPU, from its entry area
+///
+/// CPU 0 before [`init_cpu`]. Falls back to the APIC ID lookup if GS is
+/// not the kernel's, which only a swapgs imbalance can cause.
+pub fn this_cpu() -> usize {
+    if !INITIALIZED.load(Ordering::Acquire) {
+        return 0;
+    }
+    if !kernel_gs_active() {
+        return crate::interrupt::affinity::current_cpu();
+    }
+    let cpu: u64;
+    unsafe {
+        core::arch::asm!(
+            "mov {}, gs:[{off}]",
+            out(reg) cpu,
+            off = const CPU_OFFSET,
+            options(nostack, readonly, preserves_flags),
+        );
+    }
+    cpu as usize
+}
+
+/// Set the kernel stack the next `syscall` or ring 3 interrupt on this
+/// CPU runs on (entry area and TSS `rsp0`)
+///
+/// Called by the scheduler when switching to a process.
+pub fn set_kernel_stack(cpu: usize, stack_top: u64) {
+    unsafe {
+        ENTRY_AREAS[cpu % MAX_CPUS].kernel_sp = stack_top;
+        super::descriptor::tss(cpu).rsp0 = stack_top;
+    }
+}
+
+/// Check that the GS state matches the mode an entry came from
+///
+/// # Returns
+///
+/// false on a swapgs imbalance: user GS with a kernel CS or the other
+/// way round
+pub fn entry_state_ok(from_user: bool) -> bool {
+    !INITIALIZED.load(Ordering::Acquire) || kernel_gs_active() != from_user
+}
+
+#[inline(always)]
+unsafe fn swapgs() {
+    core::arch::asm!("swapgs", options(nomem, nostack, preserves_flags));
+}
+
+/// Swaps to the kernel GS on entry and back on drop, if needed
+///
+/// Take one at the top of every interrupt and exception handler that can
+/// interrupt userspace, before anything touches GS.
+#[must_use]
+pub struct GsGuard {
+    swapped: bool,
+}
+
+impl GsGuard {
+    /// Entry whose previous mode is known from the saved CS
+    ///
+    /// # Panics
+    ///
+    /// On a swapgs imbalance (see [`entry_state_ok`])
+    ///
+    /// # Safety
+    ///
+    /// `cs` must be the CS pushed by the CPU for this entry.
+    pub unsafe fn from_cs(cs: u64) -> Self {
+        let from_user = cs & 3 == 3;
+        if !entry_state_ok(from_user) {
+            panic!("swapgs imbalance on entry (cs={:#x})", cs);
+        }
+        let swapped = from_user && INITIALIZED.load(Ordering::Acquire);
+        if swapped {
+            swapgs();
+        }
+        Self { swapped }
+    }
+
+    /// Entry that may have interrupted any instruction, including the
+    /// swapgs windows of other entry paths (NMI, #DB, #MC)
+    ///
+    /// # Safety
+    ///
+    /// Must be the first thing the handler does with GS.
+    pub unsafe fn paranoid() -> Self {
+        let swapped = INITIALIZED.load(Ordering::Acquire) && !kernel_gs_active();
+        if swapped {
+            swapgs();
+        }
+        Self { swapped }
+    }
+
+    /// Whether the entry swapped GS (it came from userspace GS)
+    pub fn swapped(&self) -> bool {
+        self.swapped
+    }
+}
+
+impl Drop for GsGuard {
+    fn drop(&mut self) {
+        if self.swapped {
+            unsafe { swapgs() };
+        }
+    }
+}
+
+// ============================================================================
+// SYSCALL Entry
+// ============================================================================
+
+/// User registers saved by [`x86_64_syscall_stub`] at the top of the
+/// kernel stack, lowest address first
+///
+/// Holds everything a syscall preserves for userspace: the return state
+/// and the callee-saved registers.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyscallFrame {
+    // Callee-saved registers, in reverse push order
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    /// Return address (`rcx` on entry)
+    pub rip: u64,
+    /// User RFLAGS (`r11` on entry)
+    pub rflags: u64,
+    /// User stack pointer
+    pub rsp: u64,
+}
+
+/// Bytes of [`SyscallFrame`] below the return state
+const CALLEE_SAVED_SIZE: usize = 6 * 8;
+
+/// The frame of the syscall this CPU is running
+///
+/// # Safety
+///
+/// Must be called from a syscall handler, on the kernel stack the stub
+/// switched to.
+pub unsafe fn current_syscall_frame() -> SyscallFrame {
+    let cpu = crate::interrupt::affinity::current_cpu();
+    let top = ENTRY_AREAS[cpu % MAX_CPUS].kernel_sp;
+    core::ptr::read((top as usize - core::mem::size_of::<SyscallFrame>()) as *const SyscallFrame)
+}
+
+/// `syscall` instruction entry point (`IA32_LSTAR`)
+///
+/// Switches to the kernel GS and stack, saves a [`SyscallFrame`], calls
+/// [`x86_64_syscall_entry`](super::syscall::x86_64_syscall_entry) with
+/// the C ABI (`r10` moved to `rcx`, the syscall number as the 7th
+/// argument) and returns with `sysretq`. Interrupts are masked by
+/// `IA32_FMASK` until the kernel stack is in place.
+///
+/// # Safety
+///
+/// Only to be entered by the CPU through `syscall`.
+#[unsafe(naked)]
+#[no_mangle]
+pub unsafe extern "C" fn x86_64_syscall_stub() {
+    naked_asm!(
+        "swapgs",
+        "mov qword ptr gs:[{user_sp}], rsp",
+        "mov rsp, qword ptr gs:[{kernel_sp}]",
+
+        // Return state: user RSP, RFLAGS (r11), RIP (rcx)
+        "push qword ptr gs:[{user_sp}]",
+        "push r11",
+        "push rcx",
+
+        // Callee-saved registers, completing the SyscallFrame
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "sti",
+
+        // C ABI: arg3 in rcx, 7th argument on the (16-byte aligned) stack
+        "mov rcx, r10",
+        "push rax",
+        "call {dispatch}",
+        "add rsp, 8",
+
+        "cli",
+        // The C ABI preserved the callee-saved registers
+        "add rsp, {callee_saved}",
+        "pop rcx",
+        "pop r11",
+
+        // Refuse to sysret to a non-canonical RIP
+        "mov rdx, rcx",
+        "shl rdx, 16",
+        "sar rdx, 16",
+        "cmp rdx, rcx",
+        "jne 2f",
+
+        // Don't leak kernel values in caller-saved registers
+        "xor edi, edi",
+        "xor esi, esi",
+        "xor edx, edx",
+        "xor r8d, r8d",
+        "xor r9d, r9d",
+        "xor r10d, r10d",
+
+        "pop rsp",
+        "swapgs",
+        "sysretq",
+
+        "2:",
+        "call {bad_return}",
+        "ud2",
+
+        user_sp = const USER_SP_OFFSET,
+        kernel_sp = const KERNEL_SP_OFFSET,
+        callee_saved = const CALLEE_SAVED_SIZE,
+        dispatch = sym super::syscall::x86_64_syscall_entry,
+        bad_return = sym x86_64_syscall_bad_return,
+    );
+}
+
+/// The process asked to return to a non-canonical RIP
+///
+/// Terminates it instead of executing `sysretq`.
+extern "C" fn x86_64_syscall_bad_return() -> ! {
+    crate::process::table::kill_current()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_area_offsets() {
+        assert_eq!(core::mem::offset_of!(EntryArea, kernel_sp), KERNEL_SP_OFFSET);
+        assert_eq!(core::mem::offset_of!(EntryArea, user_sp), USER_SP_OFFSET);
+        assert_eq!(core::mem::offset_of!(EntryArea, cpu), CPU_OFFSET);
+    }
+
+    #[test]
+    fn test_syscall_frame_layout() {
+        // Six callee-saved pushes, then rcx, r11 and the user RSP
+        assert_eq!(core::mem::offset_of!(SyscallFrame, rip), CALLEE_SAVED_SIZE);
+        assert_eq!(core::mem::size_of::<SyscallFrame>(), CALLEE_SAVED_SIZE + 3 * 8);
+        // Keeps the stack 16-byte aligned at the dispatch call (with the pushed rax)
+        assert_eq!((core::mem::size_of::<SyscallFrame>() + 8) % 16, 0);
+    }
+
+    #[test]
+    fn test_is_entry_area() {
+        let first = &raw const ENTRY_AREAS as u64;
+        let size = core::mem::size_of::<EntryArea>() as u64;
+        assert!(is_entry_area(first));
+        assert!(is_entry_area(first + size * (MAX_CPUS as u64 - 1)));
+        assert!(!is_entry_area(first + 8));
+        assert!(!is_entry_area(first + size * MAX_CPUS as u64));
+        assert!(!is_entry_area(0));
+    }
+}
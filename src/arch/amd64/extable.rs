@@ -0,0 +1,269 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Exception Fixup Table
+//!
+//! Range checks in [`crate::syscall::uaccess`] cannot make a user access
+//! safe on their own: another thread can unmap the page between the check
+//! and the copy. Every kernel instruction that may fault on user memory is
+//! listed in [`EXCEPTION_TABLE`] together with a recovery address. The
+//! page-fault handler looks up the faulting RIP and, on a match, resumes
+//! at the recovery address instead of halting the kernel.
+//!
+//! # Demand Paging
+//!
+//! Not-present faults on user addresses are first offered to
+//! [`crate::process::vmar::handle_fault`], which commits and maps the page
+//! if the address lies in one of the process's VMO mappings. This applies
+//! both to user code and to the copy routine below; the fixup is only
+//! taken when that fails. A user fault that cannot be resolved is
+//! reported and the process is killed.
+//!
+//! `ERR_BUSY` means another CPU is changing the process's mappings, not
+//! that the address is bad: the copy routine waits for it, and user code
+//! just faults again.
+//!
+//! # Copy Routine
+//!
+//! [`copy_user`] is the only routine with a table entry. It copies with
+//! `rep movsb`, which keeps its progress in RCX, so the fixup can report
+//! how many bytes were left when the fault hit.
+
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::faults::is_user_address;
+use crate::arch::amd64::mm::RxStatus;
+use crate::arch::amd64::registers;
+use crate::process::vmar;
+use crate::kwarn;
+
+// ============================================================================
+// Assembly
+// ============================================================================
+
+// rx_copy_user(dst: rdi, src: rsi, len: rdx) -> bytes not copied (rax)
+//
+// x86_page_fault_entry saves the caller-saved registers, passes a pointer
+// to the error code and hardware frame to x86_page_fault_dispatch and
+// returns to the (possibly rewritten) RIP. The kernel is built without
+// SSE, so no vector state needs saving.
+global_asm!(
+    ".global rx_copy_user",
+    ".global rx_copy_user_fault",
+    ".global rx_copy_user_fixup",
+    ".p2align 4",
+    "rx_copy_user:",
+    "    mov rcx, rdx",
+    "rx_copy_user_fault:",
+    "    rep movsb",
+    "    xor eax, eax",
+    "    ret",
+    "rx_copy_user_fixup:",
+    "    mov rax, rcx",
+    "    ret",
+    "",
+    ".global x86_page_fault_entry",
+    ".p2align 4",
+    "x86_page_fault_entry:",
+    "    push rax",
+    "    push rcx",
+    "    push rdx",
+    "    push rsi",
+    "    push rdi",
+    "    push r8",
+    "    push r9",
+    "    push r10",
+    "    push r11",
+    "    lea rdi, [rsp + 72]",
+    "    sub rsp, 8",
+    "    cld",
+    "    call x86_page_fault_dispatch",
+    "    add rsp, 8",
+    "    pop r11",
+    "    pop r10",
+    "    pop r9",
+    "    pop r8",
+    "    pop rdi",
+    "    pop rsi",
+    "    pop rdx",
+    "    pop rcx",
+    "    pop rax",
+    "    add rsp, 8",
+    "    iretq",
+);
+
+extern "C" {
+    fn rx_copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    fn rx_copy_user_fault();
+    fn rx_copy_user_fixup();
+    fn x86_page_fault_entry();
+}
+
+// ============================================================================
+// Fixup Table
+// ============================================================================
+
+/// A fixup table entry
+#[derive(Clone, Copy)]
+pub struct ExTableEntry {
+    /// Instruction that may fault on user memory
+    pub insn: unsafe extern "C" fn(),
+    /// Address to resume at when it does
+    pub fixup: unsafe extern "C" fn(),
+}
+
+/// Instructions allowed to fault on user memory
+pub static EXCEPTION_TABLE: &[ExTableEntry] = &[
+    ExTableEntry { insn: rx_copy_user_fault, fixup: rx_copy_user_fixup },
+];
+
+/// Number of faults recovered through the table
+static FIXUPS: AtomicU64 = AtomicU64::new(0);
+
+/// Find the recovery address for a faulting instruction
+///
+/// # Returns
+///
+/// The fixup address, or None if `rip` is not in the table
+pub fn search_exception_table(rip: usize) -> Option<usize> {
+    EXCEPTION_TABLE
+        .iter()
+        .find(|e| e.insn as usize == rip)
+        .map(|e| e.fixup as usize)
+}
+
+/// Number of user-copy faults recovered since boot
+pub fn fixup_count() -> u64 {
+    FIXUPS.load(Ordering::Relaxed)
+}
+
+/// Copy bytes, recovering from page faults
+///
+/// # Returns
+///
+/// Number of bytes NOT copied (0 on success)
+///
+/// # Safety
+///
+/// `dst` and `src` must not overlap. Faults are only recovered on user
+/// addresses; kernel addresses must be valid.
+pub unsafe fn copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize {
+    rx_copy_user(dst, src, len)
+}
+
+// ============================================================================
+// Page Fault Handler
+// ============================================================================
+
+/// Error code and hardware frame pushed on a page fault
+#[repr(C)]
+pub struct PageFaultFrame {
+    pub error_code: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+/// Install the page fault handler (vector 14)
+///
+/// # Safety
+///
+/// The IDT must be set up.
+pub unsafe fn install() {
+    super::idt::idt_set_gate(14, x86_page_fault_entry as *const () as u64, 0x08, 0x8E);
+}
+
+/// Page fault dispatch, called from `x86_page_fault_entry`
+///
+/// Kernel-mode faults on user addresses at a listed instruction resume
+/// at its fixup. Other user faults go to demand paging, and other kernel
+/// faults panic (see [`super::panic`]).
+#[no_mangle]
+extern "C" fn x86_page_fault_dispatch(frame: &mut PageFaultFrame) {
+    let cr2 = unsafe { registers::x86_get_cr2() } as usize;
+    let from_kernel = frame.cs & 3 == 0;
+
+    if from_kernel && is_user_address(cr2) {
+        if let Some(fixup) = search_exception_table(frame.rip as usize) {
+            // A user copy touching a page not committed yet
+            let resolved = loop {
+                match vmar::handle_fault(cr2 as u64, frame.error_code) {
+                    Err(RxStatus::ERR_BUSY) => core::hint::spin_loop(),
+                    result => break result.is_ok(),
+                }
+            };
+            if resolved {
+                return;
+            }
+            FIXUPS.fetch_add(1, Ordering::Relaxed);
+            frame.rip = fixup as u64;
+            return;
+        }
+    }
+
+    if !from_kernel {
+        match vmar::handle_fault(cr2 as u64, frame.error_code) {
+            // Restart the instruction once the mappings are unlocked
+            Ok(()) | Err(RxStatus::ERR_BUSY) => {}
+            Err(status) => {
+                report_user_fault(frame, cr2, status);
+                crate::process::table::kill_current();
+            }
+        }
+        return;
+    }
+
+    super::panic::set_frame(super::panic::PanicFrame {
+        rip: frame.rip,
+        rsp: frame.rsp,
+        rflags: frame.rflags,
+        cs: frame.cs,
+        error_code: Some(frame.error_code),
+        cr2: cr2 as u64,
+        ..Default::default()
+    });
+    panic!("kernel page fault at {:#x} (rip={:#x} error={:#x})", cr2, frame.rip, frame.error_code);
+}
+
+/// Report a user page fault that demand paging could not resolve
+fn report_user_fault(frame: &PageFaultFrame, cr2: usize, status: RxStatus) {
+    let pid = crate::process::table::PROCESS_TABLE
+        .try_lock()
+        .and_then(|t| t.current_pid())
+        .unwrap_or(0);
+    let why = match status {
+        RxStatus::ERR_NOT_FOUND => "no mapping",
+        RxStatus::ERR_ACCESS_DENIED => "access denied",
+        RxStatus::ERR_NO_MEMORY => "out of memory",
+        _ => "unresolved",
+    };
+    kwarn!(
+        "[FAULT] pid {}: page fault at rip={:#018x} addr={:#018x} error={:#018x} ({}), killing process",
+        pid, frame.rip, cr2, frame.error_code, why
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_user() {
+        let src = *b"rustux";
+        let mut dst = [0u8; 6];
+        assert_eq!(unsafe { copy_user(dst.as_mut_ptr(), src.as_ptr(), src.len()) }, 0);
+        assert_eq!(&dst, b"rustux");
+    }
+
+    #[test]
+    fn test_search_exception_table() {
+        let insn = rx_copy_user_fault as *const () as usize;
+        assert_eq!(search_exception_table(insn), Some(rx_copy_user_fixup as *const () as usize));
+        assert_eq!(search_exception_table(insn + 1), None);
+    }
+}
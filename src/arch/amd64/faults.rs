@@ -12,6 +12,7 @@
 use crate::arch::amd64::registers;
 use crate::arch::amd64::registers::X86_FLAGS_AC;
 use crate::arch::amd64::syscall::X86Iframe;
+use crate::kwarn;
 
 /// Page fault error code flags
 pub mod pf_error {
@@ -76,7 +77,7 @@ fn is_from_user(frame: &X86Iframe) -> bool {
 /// canonical lower half (0x0000_0000_0000_0000 - 0x0000_7FFF_FFFF_FFFF)
 pub fn is_user_address(addr: usize) -> bool {
     // User addresses are in the lower half
-    addr < 0x0000_8000_0000_0000
+    crate::arch::amd64::mm::layout::is_user(addr as u64)
 }
 
 /// Dump the fault frame for debugging
@@ -116,9 +117,7 @@ pub fn x86_dump_pfe(frame: &X86Iframe, cr2: u64, err_code: u64) {
 /// Fatal page fault handler - halts the system
 pub fn x86_fatal_pfe_handler(frame: &X86Iframe, cr2: u64, err_code: u64) -> ! {
     x86_dump_pfe(frame, cr2, err_code);
-
-    // TODO: Implement proper panic handling
-    exception_die(frame, "fatal page fault, halting\n");
+    exception_die(frame, Some(err_code), "fatal page fault");
 }
 
 /// Page fault handler
@@ -190,72 +189,147 @@ pub fn x86_pfe_handler(frame: &mut X86Iframe, error_code: u64) -> Result<(), ()>
 
 /// Debug exception handler
 pub fn x86_debug_handler(frame: &mut X86Iframe) {
-    // TODO: Implement debug exception handling
-    let _ = frame;
-    exception_die(frame, "unhandled hw breakpoint, halting\n");
+    if super::debug::handle_exception(frame) {
+        return;
+    }
+    exception_die(frame, None, "unhandled hw breakpoint");
 }
 
 /// Breakpoint exception handler (INT 3)
 pub fn x86_breakpoint_handler(frame: &mut X86Iframe) {
     // TODO: Implement breakpoint exception handling
     let _ = frame;
-    exception_die(frame, "unhandled sw breakpoint, halting\n");
+    exception_die(frame, None, "unhandled sw breakpoint");
 }
 
 /// General protection fault handler
 pub fn x86_gpf_handler(frame: &mut X86Iframe) {
     // TODO: Implement GPF handling
     let _ = frame;
-    exception_die(frame, "unhandled gpf, halting\n");
+    exception_die(frame, None, "unhandled gpf");
 }
 
 /// Invalid opcode handler
 pub fn x86_invop_handler(frame: &mut X86Iframe) {
     // TODO: Implement invalid opcode handling
     let _ = frame;
-    exception_die(frame, "invalid opcode, halting\n");
+    exception_die(frame, None, "invalid opcode");
 }
 
 /// Double fault handler
 pub fn x86_df_handler(frame: &X86Iframe) {
     // Do not give the user exception handler the opportunity to handle double faults
     let _ = frame;
-    exception_die(frame, "double fault, halting\n");
+    exception_die(frame, None, "double fault");
+}
+
+/// #DF stack size per CPU
+pub const DOUBLE_FAULT_STACK_SIZE: usize = 16 * 1024;
+
+/// #DF stacks, one per CPU
+#[repr(C, align(16))]
+struct DoubleFaultStack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+static mut DOUBLE_FAULT_STACKS: [DoubleFaultStack; crate::interrupt::affinity::MAX_CPUS] =
+    [const { DoubleFaultStack([0; DOUBLE_FAULT_STACK_SIZE]) }; crate::interrupt::affinity::MAX_CPUS];
+
+/// Top of the #DF stack of `cpu`
+pub fn double_fault_stack_top(cpu: usize) -> u64 {
+    let stack = unsafe { &raw const DOUBLE_FAULT_STACKS[cpu % crate::interrupt::affinity::MAX_CPUS] };
+    stack as u64 + DOUBLE_FAULT_STACK_SIZE as u64
+}
+
+/// Install the #DF gate on its IST stack
+///
+/// A double fault is usually a kernel stack overflow; handling it on the
+/// faulting stack would triple fault instead of reporting it.
+///
+/// # Safety
+///
+/// The IDT must be set up (`idt_setup_readonly`).
+pub unsafe fn install_double_fault() {
+    super::idt::idt_set_gate_ist(
+        exception_vector::DOUBLE_FAULT as u8,
+        double_fault_entry as *const () as u64,
+        0x08,
+        super::idt::IDT_INTERRUPT_GATE,
+        super::descriptor::ist::DOUBLE_FAULT,
+    );
+}
+
+extern "x86-interrupt" fn double_fault_entry(frame: super::nmi::CpuFrame, error_code: u64) -> ! {
+    let _gs = unsafe { super::entry::GsGuard::paranoid() };
+    super::panic::set_frame(super::panic::PanicFrame::from_cpu_frame(&frame, Some(error_code)));
+    // CR2 still holds the address of the page fault that led here
+    let cr2 = unsafe { super::registers::x86_get_cr2() };
+    if crate::mm::vmm::is_stack_guard(cr2) {
+        panic!("kernel stack overflow at rip={:#x} rsp={:#x} (guard page {:#x})", frame.rip, frame.rsp, cr2);
+    }
+    panic!("double fault at rip={:#x} rsp={:#x}", frame.rip, frame.rsp);
+}
+
+/// Install the #GP gate
+///
+/// # Safety
+///
+/// The IDT must be set up (`idt_setup_readonly`).
+pub unsafe fn install_gp_fault() {
+    super::idt::idt_set_gate(
+        exception_vector::GP_FAULT as u8,
+        gp_fault_entry as *const () as u64,
+        0x08,
+        super::idt::IDT_INTERRUPT_GATE,
+    );
+}
+
+/// #GP: kills a user process, panics in the kernel
+///
+/// In userspace this is most often `in`/`out` on a port the process has
+/// not enabled (see [`super::ioport`]), or a privileged instruction.
+extern "x86-interrupt" fn gp_fault_entry(frame: super::nmi::CpuFrame, error_code: u64) {
+    let _gs = unsafe { super::entry::GsGuard::from_cs(frame.cs) };
+    if frame.cs & 3 == 3 {
+        let pid = crate::process::table::PROCESS_TABLE
+            .try_lock()
+            .and_then(|t| t.current_pid())
+            .unwrap_or(0);
+        kwarn!(
+            "[FAULT] pid {}: general protection fault at rip={:#x} error={:#x}, killing process",
+            pid, frame.rip, error_code
+        );
+        crate::process::table::kill_current();
+    }
+    super::panic::set_frame(super::panic::PanicFrame::from_cpu_frame(&frame, Some(error_code)));
+    panic!("general protection fault at rip={:#x} error={:#x}", frame.rip, error_code);
 }
 
 /// NMI handler
-pub fn x86_nmi_handler(_frame: &X86Iframe) {
-    // NMI handler - typically used for watchdog or hardware diagnostics
-    // TODO: Implement proper NMI handling
+pub fn x86_nmi_handler(frame: &X86Iframe) {
+    // The IST gate installed by nmi::install() is the normal path
+    super::nmi::handle(super::nmi::NmiContext {
+        cpu: crate::interrupt::affinity::current_cpu(),
+        rip: frame.ip,
+        cs: 0,
+        rflags: frame.flags,
+        rsp: frame.user_sp,
+    });
 }
 
 /// Unhandled exception handler
 pub fn x86_unhandled_exception(frame: &mut X86Iframe) {
     // TODO: Implement unhandled exception handling
     let _ = frame;
-    exception_die(frame, "unhandled exception, halting\n");
+    exception_die(frame, None, "unhandled exception");
 }
 
-/// Fatal exception handler - prints diagnostic and halts
-fn exception_die(frame: &X86Iframe, msg: &str) -> ! {
-    // TODO: Implement proper panic handling:
-    // - platform_panic_start() to notify other subsystems
-    // - Dump user stack if from user space
-    // - Save crash log to persistent storage
-    // - Call platform-specific halt
-
-    // For user exceptions, try to dump user stack
-    if is_from_user(frame) {
-        // TODO: Implement user-space stack unwinding
-    }
-
-    // Print exception information (if debug output is available)
-    let _ = (frame, msg);
-
-    // Halt the system
-    loop {
-        unsafe { registers::x86_hlt() };
-    }
+/// Fatal exception handler - panics with the frame in the report
+///
+/// The panic report (see [`super::panic`]) prints the registers and a
+/// backtrace from the faulting instruction, and the crash is dumped like
+/// any other panic.
+fn exception_die(frame: &X86Iframe, error_code: Option<u64>, msg: &str) -> ! {
+    super::panic::set_frame(super::panic::PanicFrame::from_iframe(frame, error_code));
+    panic!("{} at rip={:#x}", msg, frame.ip);
 }
 
 /// Main exception dispatch handler
@@ -268,6 +342,9 @@ fn exception_die(frame: &X86Iframe, msg: &str) -> ! {
 /// * `vector` - Exception vector number
 #[no_mangle]
 pub unsafe extern "C" fn x86_exception_handler(frame: *mut X86Iframe, vector: u64) {
+    // The frame carries no CS and this may be an NMI or #DB inside
+    // another entry's swapgs window, so decide from the GS MSR
+    let _gs = super::entry::GsGuard::paranoid();
     let frame = &mut *frame;
 
     match vector {
@@ -280,11 +357,15 @@ pub unsafe extern "C" fn x86_exception_handler(frame: *mut X86Iframe, vector: u6
         exception_vector::BREAKPOINT => {
             x86_breakpoint_handler(frame);
         }
+        exception_vector::MACHINE_CHECK => {
+            // The IST gate installed by mce::init() is the normal path
+            super::mce::handle(frame.ip, 0);
+        }
         exception_vector::INVALID_OP => {
             x86_invop_handler(frame);
         }
         exception_vector::DEVICE_NA => {
-            exception_die(frame, "device na fault\n");
+            exception_die(frame, None, "device not available fault");
         }
         exception_vector::DOUBLE_FAULT => {
             x86_df_handler(frame);
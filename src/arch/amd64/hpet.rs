@@ -0,0 +1,352 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! x86-64 HPET (High Precision Event Timer)
+//!
+//! The HPET is a memory-mapped block with a free-running main counter of
+//! at least 10 MHz and up to 32 comparators, each of which interrupts
+//! when the counter reaches it. The ACPI HPET table locates it
+//! ([`crate::acpi::hpet`]). The kernel uses it:
+//!
+//! - As the reference for TSC calibration ([`super::tsc`]): unlike the
+//!   PIT it can be read at any time, and it exists on machines without
+//!   a PIT.
+//! - As a clock event device ([`HPET_EVENTS`]), on comparator 0, for
+//!   when the Local APIC timer cannot drive the tick
+//!   ([`crate::time::clockevents`]).
+//!
+//! # Interrupts
+//!
+//! Legacy replacement routing is left off. A comparator that supports
+//! FSB delivery sends an MSI; otherwise it is wired to the first I/O APIC
+//! input from GSI 16 on that its routing capability lists (edge
+//! triggered, active high). Either way the vector is registered with the
+//! affinity balancer, starting on CPU 0.
+
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::CachePolicy;
+use crate::time::clockevents::ClockEvents;
+use crate::time::Duration;
+use crate::kinfo;
+use super::apic::{self, IrqMode};
+
+/// General capabilities and ID (counter period in fs in bits 32-63)
+const HPET_GCAP_ID: usize = 0x000;
+
+/// General configuration
+const HPET_GEN_CONF: usize = 0x010;
+
+/// Main counter value
+const HPET_MAIN_CNT: usize = 0x0F0;
+
+/// Comparator `n` configuration and capabilities
+const fn timer_conf(n: usize) -> usize {
+    0x100 + 0x20 * n
+}
+
+/// Comparator `n` value
+const fn timer_cmp(n: usize) -> usize {
+    0x108 + 0x20 * n
+}
+
+/// Comparator `n` FSB interrupt route (data low, address high)
+const fn timer_fsb(n: usize) -> usize {
+    0x110 + 0x20 * n
+}
+
+/// Register block size
+const HPET_MMIO_SIZE: usize = 0x400;
+
+/// Capabilities: 64-bit main counter
+const GCAP_COUNT_SIZE: u64 = 1 << 13;
+
+/// Configuration: main counter runs
+const CONF_ENABLE: u64 = 1 << 0;
+
+/// Configuration: legacy replacement routing
+const CONF_LEGACY: u64 = 1 << 1;
+
+/// Comparator: level triggered interrupt
+const TN_INT_TYPE_LEVEL: u64 = 1 << 1;
+
+/// Comparator: interrupt enabled
+const TN_INT_ENB: u64 = 1 << 2;
+
+/// Comparator: periodic mode
+const TN_TYPE_PERIODIC: u64 = 1 << 3;
+
+/// Comparator: periodic mode supported
+const TN_PER_INT_CAP: u64 = 1 << 4;
+
+/// Comparator: the next comparator write sets the periodic accumulator
+const TN_VAL_SET: u64 = 1 << 6;
+
+/// Comparator: I/O APIC route field (bits 9-13)
+const TN_INT_ROUTE_SHIFT: u32 = 9;
+const TN_INT_ROUTE_MASK: u64 = 0x1F << TN_INT_ROUTE_SHIFT;
+
+/// Comparator: FSB (MSI) delivery enabled
+const TN_FSB_EN: u64 = 1 << 14;
+
+/// Comparator: FSB delivery supported
+const TN_FSB_CAP: u64 = 1 << 15;
+
+/// Longest counter period the specification allows (100 ns, in fs)
+const MAX_PERIOD_FS: u64 = 100_000_000;
+
+/// Femtoseconds per second
+const FS_PER_SEC: u64 = 1_000_000_000_000_000;
+
+/// Comparator used for clock events
+const EVENT_TIMER: usize = 0;
+
+/// Polls of the main counter while checking that it runs
+const START_POLLS: u32 = 100_000;
+
+/// Virtual address of the registers (0 = no HPET)
+static BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// Main counter frequency in Hz
+static FREQUENCY: AtomicU64 = AtomicU64::new(0);
+
+/// Main counter mask (32 or 64 bits)
+static COUNTER_MASK: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Least ticks of a periodic comparator, from the HPET table
+static MIN_TICK: AtomicU64 = AtomicU64::new(0);
+
+/// Vector the event comparator is routed to (0 = not yet)
+static ROUTED_VECTOR: AtomicU8 = AtomicU8::new(0);
+
+unsafe fn read(reg: usize) -> u64 {
+    ((BASE.load(Ordering::Relaxed) + reg) as *const u64).read_volatile()
+}
+
+unsafe fn write(reg: usize, value: u64) {
+    ((BASE.load(Ordering::Relaxed) + reg) as *mut u64).write_volatile(value);
+}
+
+/// Main counter frequency from the capability register's period
+///
+/// # Returns
+/// `None` for a period of 0 or above the specification's 100 ns
+pub const fn frequency_from_period(period_fs: u64) -> Option<u64> {
+    if period_fs == 0 || period_fs > MAX_PERIOD_FS {
+        return None;
+    }
+    Some(FS_PER_SEC / period_fs)
+}
+
+/// First I/O APIC input a comparator may use, from its routing capability
+///
+/// GSIs below 16 belong to ISA devices, so the first one from 16 on is
+/// preferred, the lowest one otherwise.
+pub const fn pick_route(route_cap: u32) -> Option<u32> {
+    if route_cap == 0 {
+        return None;
+    }
+    let high = route_cap & !0xFFFF;
+    if high != 0 {
+        Some(high.trailing_zeros())
+    } else {
+        Some(route_cap.trailing_zeros())
+    }
+}
+
+/// Find the HPET, start its main counter and register it as a clock event
+/// device
+///
+/// Interrupts of all comparators are disabled and legacy replacement
+/// routing is turned off.
+///
+/// # Returns
+/// The main counter frequency in Hz, or `None` if there is no usable HPET
+///
+/// # Safety
+///
+/// Boot CPU only, once.
+pub unsafe fn init() -> Option<u64> {
+    let info = crate::acpi::find_rsdp().and_then(crate::acpi::find_hpet)?;
+    let base = crate::mm::vmm::ioremap(info.address, HPET_MMIO_SIZE, CachePolicy::Uncached)
+        .unwrap_or(info.address as usize);
+    BASE.store(base, Ordering::Relaxed);
+
+    let cap = read(HPET_GCAP_ID);
+    let Some(freq) = frequency_from_period(cap >> 32) else {
+        BASE.store(0, Ordering::Relaxed);
+        return None;
+    };
+    let comparators = ((cap >> 8) & 0x1F) as usize + 1;
+
+    write(HPET_GEN_CONF, read(HPET_GEN_CONF) & !(CONF_ENABLE | CONF_LEGACY));
+    for n in 0..comparators {
+        write(timer_conf(n), read(timer_conf(n)) & !(TN_INT_ENB | TN_FSB_EN));
+    }
+    write(HPET_GEN_CONF, read(HPET_GEN_CONF) | CONF_ENABLE);
+
+    // A block that reports a period but does not count is no use
+    let start = read(HPET_MAIN_CNT);
+    if !(0..START_POLLS).any(|_| read(HPET_MAIN_CNT) != start) {
+        BASE.store(0, Ordering::Relaxed);
+        return None;
+    }
+
+    let counter_64bit = cap & GCAP_COUNT_SIZE != 0;
+    COUNTER_MASK.store(if counter_64bit { u64::MAX } else { u32::MAX as u64 }, Ordering::Relaxed);
+    MIN_TICK.store(info.min_tick as u64, Ordering::Relaxed);
+    FREQUENCY.store(freq, Ordering::Release);
+
+    kinfo!(
+        "[HPET] {}.{:03} MHz, {} comparators, {}-bit counter at {:#x}",
+        freq / 1_000_000,
+        freq / 1_000 % 1_000,
+        comparators,
+        if counter_64bit { 64 } else { 32 },
+        info.address
+    );
+    let _ = crate::time::clockevents::register(&HPET_EVENTS);
+    Some(freq)
+}
+
+/// Main counter frequency in Hz, if there is an HPET
+pub fn frequency() -> Option<u64> {
+    match FREQUENCY.load(Ordering::Acquire) {
+        0 => None,
+        freq => Some(freq),
+    }
+}
+
+/// Read the main counter
+///
+/// Only meaningful once [`init`] found an HPET.
+pub fn counter() -> u64 {
+    unsafe { read(HPET_MAIN_CNT) & COUNTER_MASK.load(Ordering::Relaxed) }
+}
+
+/// Main counter ticks from `earlier` to `later`, across a wrap
+pub fn counter_delta(earlier: u64, later: u64) -> u64 {
+    later.wrapping_sub(earlier) & COUNTER_MASK.load(Ordering::Relaxed)
+}
+
+/// Main counter ticks in `d`
+fn ticks(d: Duration) -> u64 {
+    let freq = FREQUENCY.load(Ordering::Relaxed);
+    ((d.as_nanos() as u128 * freq as u128) / 1_000_000_000) as u64
+}
+
+/// Program the event comparator's FSB message (an MSI from the affinity
+/// balancer)
+fn program_fsb(address: u32, data: u32) {
+    unsafe { write(timer_fsb(EVENT_TIMER), ((address as u64) << 32) | data as u64) };
+}
+
+/// Route the event comparator's interrupt to `vector`
+///
+/// Done once; later calls must use the same vector.
+fn route(vector: u8) -> Result<(), RxStatus> {
+    match ROUTED_VECTOR.load(Ordering::Relaxed) {
+        0 => {}
+        routed if routed == vector => return Ok(()),
+        _ => return Err(RxStatus::ERR_BUSY),
+    }
+
+    unsafe {
+        let conf = read(timer_conf(EVENT_TIMER)) & !(TN_INT_TYPE_LEVEL | TN_INT_ROUTE_MASK);
+        if conf & TN_FSB_CAP != 0 {
+            crate::interrupt::affinity::register_msi(vector, program_fsb);
+            write(timer_conf(EVENT_TIMER), conf | TN_FSB_EN);
+        } else {
+            let gsi = pick_route((conf >> 32) as u32).ok_or(RxStatus::ERR_NOT_SUPPORTED)?;
+            apic::ioapic_route(gsi, vector, IrqMode::ISA)?;
+            crate::interrupt::affinity::register_ioapic(vector, gsi);
+            write(timer_conf(EVENT_TIMER), (conf & !TN_FSB_EN) | ((gsi as u64) << TN_INT_ROUTE_SHIFT));
+        }
+    }
+    ROUTED_VECTOR.store(vector, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Comparator 0 of the HPET as a clock event device
+pub struct HpetEvents;
+
+impl ClockEvents for HpetEvents {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn rating(&self) -> u32 {
+        if frequency().is_some() { 50 } else { 0 }
+    }
+
+    fn per_cpu(&self) -> bool {
+        false
+    }
+
+    fn set_periodic(&self, vector: u8, period: Duration) -> Result<(), RxStatus> {
+        frequency().ok_or(RxStatus::ERR_NOT_FOUND)?;
+        let period = core::cmp::max(ticks(period), core::cmp::max(MIN_TICK.load(Ordering::Relaxed), 1));
+        unsafe {
+            if read(timer_conf(EVENT_TIMER)) & TN_PER_INT_CAP == 0 {
+                return Err(RxStatus::ERR_NOT_SUPPORTED);
+            }
+            route(vector)?;
+
+            // The counter stops while the accumulator is set, so the
+            // first deadline is not already behind it
+            write(HPET_GEN_CONF, read(HPET_GEN_CONF) & !CONF_ENABLE);
+            let now = read(HPET_MAIN_CNT);
+            let conf = read(timer_conf(EVENT_TIMER));
+            write(timer_conf(EVENT_TIMER), conf | TN_INT_ENB | TN_TYPE_PERIODIC | TN_VAL_SET);
+            write(timer_cmp(EVENT_TIMER), now.wrapping_add(period));
+            write(timer_cmp(EVENT_TIMER), period);
+            write(HPET_GEN_CONF, read(HPET_GEN_CONF) | CONF_ENABLE);
+        }
+        Ok(())
+    }
+
+    fn set_oneshot(&self, vector: u8, delta: Duration) -> Result<(), RxStatus> {
+        frequency().ok_or(RxStatus::ERR_NOT_FOUND)?;
+        route(vector)?;
+        unsafe {
+            let conf = read(timer_conf(EVENT_TIMER)) & !TN_TYPE_PERIODIC;
+            write(timer_conf(EVENT_TIMER), conf | TN_INT_ENB);
+            write(timer_cmp(EVENT_TIMER), read(HPET_MAIN_CNT).wrapping_add(core::cmp::max(ticks(delta), 1)));
+        }
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        if frequency().is_some() {
+            unsafe { write(timer_conf(EVENT_TIMER), read(timer_conf(EVENT_TIMER)) & !TN_INT_ENB) };
+        }
+    }
+}
+
+/// The HPET clock event device
+pub static HPET_EVENTS: HpetEvents = HpetEvents;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequency_from_period() {
+        // QEMU and most chipsets: 10 ns, 100 MHz; Intel PCH: 14.318 MHz
+        assert_eq!(frequency_from_period(10_000_000), Some(100_000_000));
+        assert_eq!(frequency_from_period(69_841_279), Some(14_318_180));
+        assert_eq!(frequency_from_period(0), None);
+        assert_eq!(frequency_from_period(MAX_PERIOD_FS + 1), None);
+    }
+
+    #[test]
+    fn test_pick_route() {
+        // QEMU: GSIs 2, 8 and 16-23
+        assert_eq!(pick_route(0x00FF_0104), Some(16));
+        assert_eq!(pick_route(0x0000_0104), Some(2));
+        assert_eq!(pick_route(0), None);
+    }
+}
@@ -118,3 +118,21 @@ pub unsafe fn idt_set_gate(vector: u8, handler: u64, selector: u16, type_attr: u
 
     IDT[vector as usize] = DescriptorIdtEntry::set_gate(handler, selector, type_attr, 0);
 }
+
+/// Set an IDT gate that switches to an Interrupt Stack Table stack
+///
+/// Like [`idt_set_gate`], with `ist` selecting TSS `ist1`..`ist7`
+/// (0 keeps the current stack).
+///
+/// # Safety
+///
+/// The TSS IST slot must point at a valid stack before the vector fires.
+pub unsafe fn idt_set_gate_ist(vector: u8, handler: u64, selector: u16, type_attr: u8, ist: u8) {
+    use super::descriptor::{IDT, IDT_ENTRIES, IdtEntry as DescriptorIdtEntry};
+
+    if vector as usize >= IDT_ENTRIES || ist > 7 {
+        return;
+    }
+
+    IDT[vector as usize] = DescriptorIdtEntry::set_gate(handler, selector, type_attr, ist);
+}
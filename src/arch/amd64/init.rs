@@ -107,6 +107,7 @@ pub unsafe fn x86_write_msr(msr: u32, value: u64) {
 // ============================================================================
 
 use super::mmu;
+use crate::kinfo;
 
 /// Early architecture initialization
 ///
@@ -272,49 +273,7 @@ unsafe fn switch_to_kernel_stack(stack_vaddr: usize, stack_size: usize, continua
     // Calculate new stack top (stacks grow down, so top is highest address)
     let new_stack_top = stack_vaddr + stack_size;
 
-    // Debug: Log stack switch
-    {
-        let msg = b"[STACK] Switching to kernel stack: vaddr=0x";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-        let mut n = stack_vaddr;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            let digit = (n & 0xF) as u8;
-            buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-            n >>= 4;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b" size=0x";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-        let mut n = stack_size;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            let digit = (n & 0xF) as u8;
-            buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-            n >>= 4;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b"\n";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-    }
+    kinfo!("[STACK] Switching to kernel stack: vaddr={:#x} size={:#x}", stack_vaddr, stack_size);
 
     // Disable interrupts before stack switch
     x86_cli();
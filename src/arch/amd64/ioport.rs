@@ -11,6 +11,14 @@
 //! On x86 systems, I/O ports are used to communicate with hardware devices.
 //! The I/O permission bitmap is used by the kernel to control which ports
 //! user-space processes can access.
+//!
+//! Each CPU's TSS is followed by an [`IoPermissionBitmap`] covering the
+//! first [`IOPB_PORTS`] ports. It holds the [`PortGrants`] of the process
+//! running on the CPU (see [`descriptor::load_io_ports`]); `in` and `out`
+//! on any other port raise #GP in ring 3. [`IoBitmap`] has the opposite
+//! bit sense and is not what the CPU reads.
+//!
+//! [`descriptor::load_io_ports`]: super::descriptor::load_io_ports
 
 /// I/O port bitmap size (8K ports = 1KB bitmap)
 pub const IO_BITMAP_SIZE: usize = 0x1000;
@@ -312,6 +320,177 @@ pub mod cmos {
     pub const DATA: u16 = 0x71;
 }
 
+// ============================================================================
+// TSS I/O Permission Bitmap
+// ============================================================================
+
+/// Ports covered by the TSS bitmap; ring 3 can never use higher ones
+pub const IOPB_PORTS: usize = IO_PORT_COUNT;
+
+/// Size of the TSS bitmap, including the all-ones byte the CPU requires
+/// after the last port
+pub const IOPB_SIZE: usize = IOPB_PORTS / 8 + 1;
+
+/// Most port ranges one process can have enabled
+pub const MAX_PORT_GRANTS: usize = 8;
+
+/// `count` ports from `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PortRange {
+    /// First port
+    pub start: u16,
+    /// Number of ports
+    pub count: u16,
+}
+
+impl PortRange {
+    /// A non-empty range inside the TSS bitmap
+    pub const fn new(start: u16, count: u16) -> Option<Self> {
+        if count == 0 || start as usize + count as usize > IOPB_PORTS {
+            return None;
+        }
+        Some(Self { start, count })
+    }
+
+    /// One past the last port
+    pub const fn end(&self) -> u32 {
+        self.start as u32 + self.count as u32
+    }
+
+    /// Check whether `port` is in the range
+    pub const fn contains(&self, port: u16) -> bool {
+        port >= self.start && (port as u32) < self.end()
+    }
+
+    /// Check whether the ranges share a port
+    pub const fn overlaps(&self, other: &PortRange) -> bool {
+        (self.start as u32) < other.end() && (other.start as u32) < self.end()
+    }
+}
+
+/// Ports the kernel drives itself, never granted to userspace
+pub const KERNEL_PORTS: &[PortRange] = &[
+    PortRange { start: pic::PIC1_CMD, count: 2 },
+    PortRange { start: pit::CHANNEL0, count: 4 },
+    // Keyboard data, system control port B (NMI status), keyboard status
+    PortRange { start: keyboard::DATA, count: 5 },
+    PortRange { start: cmos::INDEX, count: 2 },
+    PortRange { start: pic::PIC2_CMD, count: 2 },
+    // QEMU debug console
+    PortRange { start: 0xE9, count: 1 },
+    PortRange { start: com::COM1_BASE, count: 8 },
+    // PCI configuration address/data and the reset control register
+    PortRange { start: 0xCF8, count: 8 },
+];
+
+/// Check whether a range includes a port in [`KERNEL_PORTS`]
+pub fn is_kernel_range(range: &PortRange) -> bool {
+    KERNEL_PORTS.iter().any(|k| k.overlaps(range))
+}
+
+/// The port ranges a process has enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PortGrants {
+    ranges: [PortRange; MAX_PORT_GRANTS],
+    len: usize,
+}
+
+impl PortGrants {
+    /// No ports
+    pub const fn new() -> Self {
+        Self { ranges: [PortRange { start: 0, count: 0 }; MAX_PORT_GRANTS], len: 0 }
+    }
+
+    /// Enabled ranges
+    pub fn ranges(&self) -> &[PortRange] {
+        &self.ranges[..self.len]
+    }
+
+    /// Check whether no ports are enabled
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check whether `port` is in an enabled range
+    pub fn allows(&self, port: u16) -> bool {
+        self.ranges().iter().any(|r| r.contains(port))
+    }
+
+    /// Enable a range
+    ///
+    /// # Returns
+    ///
+    /// false if [`MAX_PORT_GRANTS`] other ranges are enabled already
+    pub fn add(&mut self, range: PortRange) -> bool {
+        if self.ranges().contains(&range) {
+            return true;
+        }
+        if self.len == MAX_PORT_GRANTS {
+            return false;
+        }
+        self.ranges[self.len] = range;
+        self.len += 1;
+        true
+    }
+
+    /// Disable a range enabled with [`add`](Self::add)
+    ///
+    /// Ports in other enabled ranges stay enabled.
+    pub fn remove(&mut self, range: PortRange) {
+        if let Some(i) = self.ranges().iter().position(|r| *r == range) {
+            self.ranges.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+    }
+}
+
+/// The bitmap the CPU checks ring 3 port accesses against
+///
+/// A set bit denies the port.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IoPermissionBitmap {
+    bits: [u8; IOPB_SIZE],
+}
+
+impl IoPermissionBitmap {
+    /// Bitmap denying every port
+    pub const fn new() -> Self {
+        Self { bits: [0xFF; IOPB_SIZE] }
+    }
+
+    /// Deny every port
+    pub fn deny_all(&mut self) {
+        self.bits = [0xFF; IOPB_SIZE];
+    }
+
+    /// Allow the ports in `range`
+    pub fn allow(&mut self, range: PortRange) {
+        for port in range.start as usize..range.end() as usize {
+            self.bits[port / 8] &= !(1 << (port % 8));
+        }
+    }
+
+    /// Allow exactly the ports in `grants`
+    pub fn load(&mut self, grants: &PortGrants) {
+        self.deny_all();
+        for &range in grants.ranges() {
+            self.allow(range);
+        }
+    }
+
+    /// Check whether ring 3 may use `port`
+    pub fn is_allowed(&self, port: u16) -> bool {
+        (port as usize) < IOPB_PORTS && self.bits[port as usize / 8] & (1 << (port % 8)) == 0
+    }
+}
+
+impl Default for IoPermissionBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -349,4 +528,52 @@ mod tests {
         assert!(!bitmap.is_port_enabled(0x60));
         assert!(bitmap.is_port_enabled(0x61));
     }
+
+    #[test]
+    fn test_port_range() {
+        assert_eq!(PortRange::new(0x3C0, 0), None);
+        assert_eq!(PortRange::new(0xFFF, 2), None);
+        assert_eq!(PortRange::new(0xFFFF, 0xFFFF), None);
+        let vga = PortRange::new(0x3C0, 0x20).unwrap();
+        assert!(vga.contains(0x3DF) && !vga.contains(0x3E0));
+        assert!(!is_kernel_range(&vga));
+        assert!(is_kernel_range(&PortRange::new(0x3F8, 1).unwrap()));
+        assert!(is_kernel_range(&PortRange::new(0x3E8, 0x20).unwrap()));
+        assert!(!is_kernel_range(&PortRange::new(com::COM2_BASE, 8).unwrap()));
+        assert!(is_kernel_range(&PortRange::new(0xCF0, 0x10).unwrap()));
+    }
+
+    #[test]
+    fn test_grants() {
+        let mut grants = PortGrants::new();
+        let com2 = PortRange::new(com::COM2_BASE, 8).unwrap();
+        let vga = PortRange::new(0x3C0, 0x20).unwrap();
+        assert!(grants.add(com2) && grants.add(vga) && grants.add(com2));
+        assert_eq!(grants.ranges(), &[com2, vga]);
+        assert!(grants.allows(0x2FF) && grants.allows(0x3D4) && !grants.allows(0x300));
+
+        grants.remove(com2);
+        assert_eq!(grants.ranges(), &[vga]);
+        for port in 0..MAX_PORT_GRANTS as u16 - 1 {
+            assert!(grants.add(PortRange::new(0x100 + port, 1).unwrap()));
+        }
+        assert!(!grants.add(com2));
+    }
+
+    #[test]
+    fn test_permission_bitmap() {
+        let mut iopb = IoPermissionBitmap::new();
+        assert!(!iopb.is_allowed(0x2F8));
+        let mut grants = PortGrants::new();
+        grants.add(PortRange::new(0x2F9, 7).unwrap());
+        iopb.load(&grants);
+        assert!(!iopb.is_allowed(0x2F8));
+        assert!((0x2F9..0x300).all(|port| iopb.is_allowed(port)));
+        assert!(!iopb.is_allowed(0x300));
+        // The byte past the last port must stay all ones
+        assert_eq!(iopb.bits[IOPB_SIZE - 1], 0xFF);
+
+        iopb.load(&PortGrants::new());
+        assert!(iopb.bits.iter().all(|&b| b == 0xFF));
+    }
 }
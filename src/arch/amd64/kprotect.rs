@@ -0,0 +1,350 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Image Protection
+//!
+//! The firmware maps the kernel image read-write and executable, usually
+//! with large pages. Once boot is done, [`init`] walks the image's PE
+//! sections ([`kaslr::pe_sections`]) and tightens the kernel page tables:
+//!
+//! | Pages | Mapping |
+//! |-------|---------|
+//! | Headers, read-only data (`.rdata`, `.pdata`, `.reloc`) | read-only, no-execute |
+//! | Code (`.text`) | read-only, executable |
+//! | Writable data (`.data`, `.bss`) | read-write, no-execute |
+//! | Boot-only code and data (`.inittxt`, `.initdat`) | unmapped, returned to the PMM |
+//!
+//! Large pages covering the image are split into 4 KiB pages first. The
+//! firmware may have made its own page tables read-only, so CR0.WP is
+//! cleared while they are edited.
+//!
+//! # Boot-Only Code
+//!
+//! Functions only called during boot are placed in the init section with
+//! `#[link_section = ".inittxt"]` ([`kaslr::INIT_TEXT_SECTION`]). Nothing
+//! may call them after [`init`]: their pages are unmapped, and a call
+//! page faults.
+//!
+//! # Ordering
+//!
+//! [`init`] runs on the boot CPU before the APs are started and before
+//! any process exists, so no other CPU or address space holds the old
+//! mappings. APs copy the boot CPU's EFER, including NXE.
+
+use alloc::vec::Vec;
+use crate::kaslr::{self, PeSection};
+use crate::mm::pmm;
+use super::registers::{self, cr, efer, msr};
+use crate::{kinfo, kwarn};
+
+/// Size of a small page
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Page table entry bits
+const PTE_P: u64 = 1 << 0;
+const PTE_W: u64 = 1 << 1;
+const PTE_US: u64 = 1 << 2;
+const PTE_PS: u64 = 1 << 7;
+const PTE_PAT_4K: u64 = 1 << 7;
+const PTE_PAT_LARGE: u64 = 1 << 12;
+const PTE_NX: u64 = 1 << 63;
+
+/// Physical address bits of an entry
+const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Entries per page table
+const ENTRIES: u64 = 512;
+
+// ============================================================================
+// Protections
+// ============================================================================
+
+/// Mapping applied to part of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    /// Read-only, executable
+    ReadExecute,
+    /// Read-only, no-execute
+    ReadOnly,
+    /// Read-write, no-execute
+    ReadWrite,
+    /// Left as the firmware mapped it (writable code)
+    Unchanged,
+    /// Unmapped and freed
+    Free,
+}
+
+impl Protection {
+    /// Protection for a section, from its characteristics
+    pub fn for_section(section: &PeSection) -> Self {
+        match (section.is_init(), section.is_executable(), section.is_writable()) {
+            (true, _, _) => Self::Free,
+            (false, true, false) => Self::ReadExecute,
+            (false, true, true) => Self::Unchanged,
+            (false, false, false) => Self::ReadOnly,
+            (false, false, true) => Self::ReadWrite,
+        }
+    }
+
+    /// Apply to a 4 KiB page table entry
+    ///
+    /// `nx` says whether the CPU supports no-execute; without it the NX
+    /// bit is reserved and stays clear.
+    pub fn apply(self, entry: u64, nx: bool) -> u64 {
+        let nx_bit = if nx { PTE_NX } else { 0 };
+        match self {
+            Self::ReadExecute => entry & !(PTE_W | PTE_NX),
+            Self::ReadOnly => (entry & !PTE_W) | nx_bit,
+            Self::ReadWrite => entry | PTE_W | nx_bit,
+            Self::Unchanged => entry,
+            Self::Free => 0,
+        }
+    }
+}
+
+/// What [`protect_kernel_image`] changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtectReport {
+    /// Pages made read-only
+    pub read_only_pages: usize,
+    /// Pages made no-execute
+    pub no_exec_pages: usize,
+    /// Boot-only pages returned to the PMM
+    pub freed_pages: usize,
+    /// Large pages split to 4 KiB pages
+    pub split_pages: usize,
+}
+
+// ============================================================================
+// Page Table Walk
+// ============================================================================
+
+/// Entry `index` of the table a large entry at `level` is split into
+///
+/// `level` is 2 for a 1 GiB PDPT entry and 1 for a 2 MiB PD entry. The
+/// children keep the parent's flags; 4 KiB children carry PAT in bit 7
+/// instead of bit 12.
+fn split_entry(large: u64, level: u32, index: u64) -> u64 {
+    let page_size = 1u64 << (12 + 9 * level);
+    let child_size = page_size / ENTRIES;
+    let base = large & ADDR_MASK & !(page_size - 1);
+    let mut flags = large & !ADDR_MASK;
+    if level == 1 {
+        flags &= !PTE_PS;
+        if large & PTE_PAT_LARGE != 0 {
+            flags |= PTE_PAT_4K;
+        }
+    } else {
+        flags |= large & PTE_PAT_LARGE;
+    }
+    (base + index * child_size) | flags
+}
+
+/// Replace the large entry at `entry` with a table of smaller pages
+unsafe fn split(entry: *mut u64, level: u32) -> Result<(), &'static str> {
+    let table_paddr = pmm::pmm_alloc_kernel_page().map_err(|_| "out of memory splitting a large page")?;
+    let table = pmm::paddr_to_vaddr(table_paddr) as *mut u64;
+    let large = *entry;
+    for i in 0..ENTRIES {
+        *table.add(i as usize) = split_entry(large, level, i);
+    }
+    // The children carry NX and the rest; the parent only grants access
+    *entry = table_paddr | (large & (PTE_P | PTE_W | PTE_US));
+    Ok(())
+}
+
+/// The 4 KiB page table entry mapping `vaddr`, splitting large pages
+unsafe fn leaf_entry(vaddr: u64, report: &mut ProtectReport) -> Result<*mut u64, &'static str> {
+    let mut table = pmm::paddr_to_vaddr(registers::x86_get_cr3() & ADDR_MASK) as *mut u64;
+    // 3: PML4, 2: PDPT, 1: PD
+    for level in (1..=3).rev() {
+        let entry = table.add(((vaddr >> (12 + 9 * level)) & (ENTRIES - 1)) as usize);
+        if *entry & PTE_P == 0 {
+            return Err("kernel image not mapped");
+        }
+        if *entry & PTE_PS != 0 {
+            split(entry, level)?;
+            report.split_pages += 1;
+        }
+        table = pmm::paddr_to_vaddr(*entry & ADDR_MASK) as *mut u64;
+    }
+    Ok(table.add(((vaddr >> 12) & (ENTRIES - 1)) as usize))
+}
+
+/// Apply `protection` to the pages of `[start, start + len)`
+unsafe fn protect_range(
+    start: u64,
+    len: u64,
+    protection: Protection,
+    nx: bool,
+    report: &mut ProtectReport,
+) -> Result<(), &'static str> {
+    if protection == Protection::Unchanged {
+        return Ok(());
+    }
+    let end = start + len.div_ceil(PAGE_SIZE) * PAGE_SIZE;
+    for vaddr in (start..end).step_by(PAGE_SIZE as usize) {
+        let entry = leaf_entry(vaddr, report)?;
+        let old = *entry;
+        let new = protection.apply(old, nx);
+        *entry = new;
+        core::arch::asm!("invlpg [{}]", in(reg) vaddr, options(nostack, preserves_flags));
+
+        if protection == Protection::Free {
+            if old & PTE_P != 0 && pmm::pmm_free_page(old & ADDR_MASK) == crate::arch::amd64::mm::RxStatus::OK {
+                report.freed_pages += 1;
+            }
+            continue;
+        }
+        if old & PTE_W != 0 && new & PTE_W == 0 {
+            report.read_only_pages += 1;
+        }
+        if old & PTE_NX == 0 && new & PTE_NX != 0 {
+            report.no_exec_pages += 1;
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Protection
+// ============================================================================
+
+/// Turn on EFER.NXE if the CPU supports no-execute
+fn enable_nx() -> bool {
+    if !super::cpu_features::get().has_flag("nx") {
+        return false;
+    }
+    unsafe {
+        let value = registers::read_msr(msr::IA32_EFER);
+        if value & efer::NXE == 0 {
+            registers::write_msr(msr::IA32_EFER, value | efer::NXE);
+        }
+    }
+    true
+}
+
+/// Protections for the image, as `(rva, len, protection)` ranges
+fn image_layout(sections: &[PeSection], image_size: u64) -> Result<Vec<(u64, u64, Protection)>, &'static str> {
+    let mut layout = Vec::with_capacity(sections.len() + 1);
+    let first = sections.iter().map(|s| s.rva as u64).min().unwrap_or(image_size);
+    layout.push((0, first, Protection::ReadOnly));
+    for section in sections {
+        let (rva, len) = (section.rva as u64, section.size as u64);
+        if rva % PAGE_SIZE != 0 {
+            return Err("image sections are not page aligned");
+        }
+        if rva + len > image_size {
+            return Err("section outside the image");
+        }
+        layout.push((rva, len, Protection::for_section(section)));
+    }
+    Ok(layout)
+}
+
+/// Apply the final mappings to the kernel image
+///
+/// # Safety
+///
+/// Must run on the boot CPU, before the APs start, and after the last
+/// call into boot-only code.
+pub unsafe fn protect_kernel_image() -> Result<ProtectReport, &'static str> {
+    let image = kaslr::kernel_image().ok_or("kernel image placement not recorded")?;
+    if image.load_base % PAGE_SIZE != 0 {
+        return Err("kernel image not page aligned");
+    }
+    let bytes = core::slice::from_raw_parts(image.load_base as *const u8, image.size as usize);
+    let layout = image_layout(&kaslr::pe_sections(bytes)?, image.size)?;
+
+    let nx = enable_nx();
+    let mut report = ProtectReport::default();
+    let cr0 = registers::x86_get_cr0();
+    registers::x86_set_cr0(cr0 & !cr::CR0_WP);
+    let result = layout
+        .iter()
+        .try_for_each(|&(rva, len, protection)| protect_range(image.load_base + rva, len, protection, nx, &mut report));
+    registers::x86_set_cr0(cr0 | cr::CR0_WP);
+
+    result.map(|_| report)
+}
+
+/// Protect the kernel image and free boot-only code; see the module docs
+///
+/// Failures are logged and leave the remaining pages as they were.
+///
+/// # Safety
+///
+/// As for [`protect_kernel_image`].
+pub unsafe fn init() {
+    match protect_kernel_image() {
+        Ok(report) => {
+            kinfo!("[KPROTECT] {} pages read-only, {} no-execute", report.read_only_pages, report.no_exec_pages);
+            kinfo!("[KPROTECT] Freed {} KiB of boot-only code and data", report.freed_pages * PAGE_SIZE as usize / 1024);
+        }
+        Err(e) => {
+            kwarn!("[KPROTECT] WARNING: kernel image not protected: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(name: &[u8], rva: u32, size: u32, characteristics: u32) -> PeSection {
+        let mut n = [0u8; 8];
+        n[..name.len()].copy_from_slice(name);
+        PeSection { name: n, rva, size, characteristics }
+    }
+
+    #[test]
+    fn test_section_protection() {
+        use kaslr::{IMAGE_SCN_MEM_EXECUTE as X, IMAGE_SCN_MEM_READ as R, IMAGE_SCN_MEM_WRITE as W};
+
+        assert_eq!(Protection::for_section(&section(b".text", 0x1000, 1, R | X)), Protection::ReadExecute);
+        assert_eq!(Protection::for_section(&section(b".rdata", 0x2000, 1, R)), Protection::ReadOnly);
+        assert_eq!(Protection::for_section(&section(b".data", 0x3000, 1, R | W)), Protection::ReadWrite);
+        assert_eq!(Protection::for_section(&section(b".inittxt", 0x4000, 1, R | X)), Protection::Free);
+        assert_eq!(Protection::for_section(&section(b".initdat", 0x5000, 1, R | W)), Protection::Free);
+        assert_eq!(Protection::for_section(&section(b".wx", 0x6000, 1, R | W | X)), Protection::Unchanged);
+    }
+
+    #[test]
+    fn test_apply() {
+        let entry = 0x20_3000 | PTE_P | PTE_W;
+        assert_eq!(Protection::ReadExecute.apply(entry | PTE_NX, true), 0x20_3000 | PTE_P);
+        assert_eq!(Protection::ReadOnly.apply(entry, true), 0x20_3000 | PTE_P | PTE_NX);
+        assert_eq!(Protection::ReadOnly.apply(entry, false), 0x20_3000 | PTE_P);
+        assert_eq!(Protection::ReadWrite.apply(0x20_3000 | PTE_P, true), entry | PTE_NX);
+        assert_eq!(Protection::Free.apply(entry, true), 0);
+    }
+
+    #[test]
+    fn test_split_entry() {
+        // 2 MiB page at 4 MiB with PAT and NX -> 4 KiB pages
+        let large = 0x40_0000 | PTE_P | PTE_W | PTE_PS | PTE_PAT_LARGE | PTE_NX;
+        assert_eq!(split_entry(large, 1, 0), 0x40_0000 | PTE_P | PTE_W | PTE_PAT_4K | PTE_NX);
+        assert_eq!(split_entry(large, 1, 511) & ADDR_MASK, 0x40_0000 + 511 * 0x1000);
+
+        // 1 GiB page -> 2 MiB pages keep PS and PAT in bit 12
+        let huge = 0x4000_0000 | PTE_P | PTE_PS | PTE_PAT_LARGE;
+        assert_eq!(split_entry(huge, 2, 3), (0x4000_0000 + 3 * 0x20_0000) | PTE_P | PTE_PS | PTE_PAT_LARGE);
+    }
+
+    #[test]
+    fn test_image_layout() {
+        use kaslr::{IMAGE_SCN_MEM_EXECUTE as X, IMAGE_SCN_MEM_READ as R};
+
+        let sections = [section(b".text", 0x1000, 0x1800, R | X), section(b".rdata", 0x3000, 0x10, R)];
+        let layout = image_layout(&sections, 0x4000).unwrap();
+        assert_eq!(layout[0], (0, 0x1000, Protection::ReadOnly));
+        assert_eq!(layout[1], (0x1000, 0x1800, Protection::ReadExecute));
+        assert_eq!(layout[2], (0x3000, 0x10, Protection::ReadOnly));
+
+        assert!(image_layout(&[section(b".text", 0x1010, 1, R | X)], 0x4000).is_err());
+        assert!(image_layout(&sections, 0x3000).is_err());
+    }
+}
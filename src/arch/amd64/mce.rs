@@ -0,0 +1,413 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Machine Check Architecture
+//!
+//! [`init`] enables every MCA bank and `CR4.MCE`; without it a machine
+//! check shuts the CPU down (a triple fault). Errors the banks already
+//! hold at boot (left over from before a reset) are reported and
+//! cleared first.
+//!
+//! The #MC handler runs on its own IST stack ([`MCE_IST`]), takes no
+//! locks and prints straight to the debug port. It reads every bank with
+//! `MCi_STATUS.VAL` set and grades it:
+//!
+//! | Severity | Condition | Action |
+//! |----------|-----------|--------|
+//! | [`Severity::Corrected`] | `UC` clear | logged, cleared |
+//! | [`Severity::Recoverable`] | `UC`, address valid, nothing consumed it | page poisoned in the PMM, cleared |
+//! | [`Severity::Fatal`] | `PCC`, overflow of a `UC` error, `AR`, no valid address, no restart IP, or no `MCG_CAP.SER_P` | panic with a report |
+//!
+//! An action-required error (`AR`) means the interrupted code consumed the
+//! bad data. There is no way to unwind that code yet, so it is fatal even
+//! when it came from userspace.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::registers::{read_msr, write_msr};
+use crate::interrupt::affinity::MAX_CPUS;
+use crate::klog::{Level, LogWriter};
+use crate::{kerror, kinfo, kwarn};
+
+/// IST slot used by the #MC gate (TSS `ist2`)
+pub const MCE_IST: u8 = super::descriptor::ist::MACHINE_CHECK;
+
+/// #MC stack size per CPU
+pub const MCE_STACK_SIZE: usize = 16 * 1024;
+
+/// Most banks a report holds (`MCG_CAP.Count` is 8 bits, real CPUs
+/// have up to ~30)
+pub const MAX_BANKS: usize = 32;
+
+/// #MC vector
+const MCE_VECTOR: u8 = 18;
+
+/// MCA MSRs
+pub mod msr {
+    /// `IA32_MCG_CAP`
+    pub const MCG_CAP: u32 = 0x179;
+    /// `IA32_MCG_STATUS`
+    pub const MCG_STATUS: u32 = 0x17A;
+    /// `IA32_MCG_CTL` (present if `MCG_CAP.CTL_P`)
+    pub const MCG_CTL: u32 = 0x17B;
+
+    /// `IA32_MCi_CTL`
+    pub const fn bank_ctl(bank: usize) -> u32 {
+        0x400 + 4 * bank as u32
+    }
+    /// `IA32_MCi_STATUS`
+    pub const fn bank_status(bank: usize) -> u32 {
+        0x401 + 4 * bank as u32
+    }
+    /// `IA32_MCi_ADDR`
+    pub const fn bank_addr(bank: usize) -> u32 {
+        0x402 + 4 * bank as u32
+    }
+    /// `IA32_MCi_MISC`
+    pub const fn bank_misc(bank: usize) -> u32 {
+        0x403 + 4 * bank as u32
+    }
+}
+
+/// `IA32_MCG_CAP` fields
+pub mod mcg_cap {
+    /// Number of banks
+    pub const COUNT_MASK: u64 = 0xFF;
+    /// `IA32_MCG_CTL` is present
+    pub const CTL_P: u64 = 1 << 8;
+    /// Software error recovery: `S` and `AR` are reported
+    pub const SER_P: u64 = 1 << 24;
+}
+
+/// `IA32_MCG_STATUS` fields
+pub mod mcg_status {
+    /// Execution can restart at the saved RIP
+    pub const RIPV: u64 = 1 << 0;
+    /// The saved RIP points at the instruction that caused the error
+    pub const EIPV: u64 = 1 << 1;
+    /// A machine check is in progress (a second one shuts the CPU down)
+    pub const MCIP: u64 = 1 << 2;
+}
+
+/// `IA32_MCi_STATUS` fields
+pub mod mci_status {
+    /// The bank holds an error
+    pub const VAL: u64 = 1 << 63;
+    /// An earlier error was lost
+    pub const OVER: u64 = 1 << 62;
+    /// Uncorrected error
+    pub const UC: u64 = 1 << 61;
+    /// Error reporting enabled
+    pub const EN: u64 = 1 << 60;
+    /// `MCi_MISC` is valid
+    pub const MISCV: u64 = 1 << 59;
+    /// `MCi_ADDR` is valid
+    pub const ADDRV: u64 = 1 << 58;
+    /// Processor context corrupt
+    pub const PCC: u64 = 1 << 57;
+    /// Signaled through #MC (with `SER_P`)
+    pub const S: u64 = 1 << 56;
+    /// Action required: the data was consumed (with `SER_P`)
+    pub const AR: u64 = 1 << 55;
+    /// MCA error code
+    pub const MCA_CODE_MASK: u64 = 0xFFFF;
+}
+
+/// How bad an error is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The hardware fixed it
+    Corrected,
+    /// Uncorrected but contained to a known page
+    Recoverable,
+    /// The system cannot continue
+    Fatal,
+}
+
+/// One bank's error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankError {
+    /// Bank number
+    pub bank: usize,
+    /// `MCi_STATUS`
+    pub status: u64,
+    /// `MCi_ADDR` (0 unless `ADDRV`)
+    pub addr: u64,
+    /// `MCi_MISC` (0 unless `MISCV`)
+    pub misc: u64,
+}
+
+impl BankError {
+    /// Physical address of the error, if the bank reported one
+    pub fn address(&self) -> Option<u64> {
+        (self.status & mci_status::ADDRV != 0).then_some(self.addr)
+    }
+
+    /// MCA error code (`MCi_STATUS[15:0]`)
+    pub fn mca_code(&self) -> u16 {
+        (self.status & mci_status::MCA_CODE_MASK) as u16
+    }
+}
+
+/// Grade one bank
+///
+/// # Arguments
+///
+/// * `status` - `MCi_STATUS`
+/// * `mcg_status` - `IA32_MCG_STATUS` at the time of the #MC
+/// * `ser` - `MCG_CAP.SER_P`
+///
+/// # Returns
+///
+/// None if the bank holds no error
+pub fn classify(status: u64, mcg_status: u64, ser: bool) -> Option<Severity> {
+    use mci_status::*;
+
+    if status & VAL == 0 {
+        return None;
+    }
+    if status & PCC != 0 {
+        return Some(Severity::Fatal);
+    }
+    if status & UC == 0 {
+        return Some(Severity::Corrected);
+    }
+
+    // Uncorrected from here on
+    let fatal = !ser
+        || status & OVER != 0
+        || status & AR != 0
+        || status & ADDRV == 0
+        || mcg_status & mcg_status::RIPV == 0;
+    Some(if fatal { Severity::Fatal } else { Severity::Recoverable })
+}
+
+// ============================================================================
+// Bank Access
+// ============================================================================
+
+/// Number of MCA banks, or 0 without MCE/MCA support
+pub fn bank_count() -> usize {
+    let edx = super::cpu_features::cpuid(1, 0).edx;
+    let has_mce = edx & (1 << 7) != 0;
+    let has_mca = edx & (1 << 14) != 0;
+    if !has_mce || !has_mca {
+        return 0;
+    }
+    core::cmp::min((unsafe { read_msr(msr::MCG_CAP) } & mcg_cap::COUNT_MASK) as usize, MAX_BANKS)
+}
+
+/// Read one bank
+fn read_bank(bank: usize) -> BankError {
+    let status = unsafe { read_msr(msr::bank_status(bank)) };
+    let addr = if status & mci_status::ADDRV != 0 {
+        unsafe { read_msr(msr::bank_addr(bank)) }
+    } else {
+        0
+    };
+    let misc = if status & mci_status::MISCV != 0 {
+        unsafe { read_msr(msr::bank_misc(bank)) }
+    } else {
+        0
+    };
+    BankError { bank, status, addr, misc }
+}
+
+/// Clear one bank's error
+fn clear_bank(bank: usize) {
+    unsafe { write_msr(msr::bank_status(bank), 0) };
+}
+
+/// Errors found and pages poisoned since boot
+static CORRECTED: AtomicU64 = AtomicU64::new(0);
+static POISONED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of corrected errors reported
+pub fn corrected_count() -> u64 {
+    CORRECTED.load(Ordering::Relaxed)
+}
+
+/// Number of pages poisoned after recoverable errors
+pub fn poisoned_count() -> u64 {
+    POISONED.load(Ordering::Relaxed)
+}
+
+// ============================================================================
+// Initialization
+// ============================================================================
+
+/// #MC stacks, one per CPU
+#[repr(C, align(16))]
+struct McStack([u8; MCE_STACK_SIZE]);
+
+static mut MCE_STACKS: [McStack; MAX_CPUS] = [const { McStack([0; MCE_STACK_SIZE]) }; MAX_CPUS];
+
+/// Top of the #MC stack of `cpu`
+pub fn stack_top(cpu: usize) -> u64 {
+    let stack = unsafe { &raw const MCE_STACKS[cpu % MAX_CPUS] };
+    stack as u64 + MCE_STACK_SIZE as u64
+}
+
+/// Enable machine checks on the boot CPU
+///
+/// Reports and clears errors the banks hold from before boot, enables
+/// all banks, installs the #MC gate on its IST stack (set up per CPU by
+/// [`cpu_init`](super::descriptor::cpu_init)) and sets `CR4.MCE`.
+///
+/// # Returns
+///
+/// The number of banks enabled (0 if the CPU has no MCA)
+///
+/// # Safety
+///
+/// The GDT/TSS and IDT must be set up (`gdt_setup`, `idt_setup_readonly`).
+pub unsafe fn init() -> usize {
+    let banks = bank_count();
+    if banks == 0 {
+        kinfo!("[MCE] no machine check architecture");
+        return 0;
+    }
+
+    for bank in 0..banks {
+        let err = read_bank(bank);
+        if err.status & mci_status::VAL != 0 {
+            kwarn!("[MCE] error logged before boot:");
+            dump_bank(&err, classify(err.status, mcg_status::RIPV, true));
+            clear_bank(bank);
+        }
+    }
+
+    if read_msr(msr::MCG_CAP) & mcg_cap::CTL_P != 0 {
+        write_msr(msr::MCG_CTL, u64::MAX);
+    }
+    for bank in 0..banks {
+        write_msr(msr::bank_ctl(bank), u64::MAX);
+    }
+
+    super::idt::idt_set_gate_ist(
+        MCE_VECTOR,
+        mce_entry as *const () as u64,
+        0x08,
+        super::idt::IDT_INTERRUPT_GATE,
+        MCE_IST,
+    );
+
+    super::registers::x86_set_cr4(super::registers::x86_get_cr4() | super::registers::cr::CR4_MCE);
+    banks
+}
+
+// ============================================================================
+// Handler
+// ============================================================================
+
+extern "x86-interrupt" fn mce_entry(frame: super::nmi::CpuFrame) {
+    let _gs = unsafe { super::entry::GsGuard::paranoid() };
+    handle(frame.rip, frame.cs);
+}
+
+/// Handle a machine check
+///
+/// Logs every valid bank, poisons the pages of recoverable errors and
+/// clears the banks. Panics if any error is fatal.
+pub fn handle(rip: u64, cs: u64) -> Severity {
+    let mcg = unsafe { read_msr(msr::MCG_STATUS) };
+    let ser = unsafe { read_msr(msr::MCG_CAP) } & mcg_cap::SER_P != 0;
+    let cpu = crate::interrupt::affinity::current_cpu();
+
+    kerror!("[MCE] machine check on cpu {} rip={:#x} cs={:#x} mcg_status={:#x}", cpu, rip, cs, mcg);
+
+    let mut worst = Severity::Corrected;
+    for bank in 0..bank_count() {
+        let err = read_bank(bank);
+        let Some(severity) = classify(err.status, mcg, ser) else {
+            continue;
+        };
+        dump_bank(&err, Some(severity));
+        worst = core::cmp::max(worst, severity);
+
+        match severity {
+            Severity::Corrected => {
+                CORRECTED.fetch_add(1, Ordering::Relaxed);
+                clear_bank(bank);
+            }
+            Severity::Recoverable => {
+                if let Some(addr) = err.address() {
+                    if crate::mm::pmm::pmm_poison_page(addr & !0xFFF) == crate::arch::amd64::mm::RxStatus::OK {
+                        POISONED.fetch_add(1, Ordering::Relaxed);
+                        kwarn!("[MCE]   page poisoned");
+                    }
+                }
+                clear_bank(bank);
+            }
+            // Leave it in the bank for whoever looks after the reset
+            Severity::Fatal => {}
+        }
+    }
+
+    if worst == Severity::Fatal {
+        panic!("fatal machine check on cpu {} (rip={:#x}, mcg_status={:#x})", cpu, rip, mcg);
+    }
+
+    // A second #MC while MCIP is set shuts the CPU down
+    unsafe { write_msr(msr::MCG_STATUS, 0) };
+    worst
+}
+
+/// Log one bank (no locks, no allocation)
+fn dump_bank(err: &BankError, severity: Option<Severity>) {
+    let (level, what) = match severity {
+        Some(Severity::Corrected) => (Level::Warn, "corrected"),
+        Some(Severity::Recoverable) => (Level::Error, "uncorrected (recoverable)"),
+        Some(Severity::Fatal) => (Level::Error, "uncorrected (FATAL)"),
+        None => (Level::Warn, "no error"),
+    };
+    let mut out = LogWriter::new(level, module_path!());
+    let _ = write!(out, "[MCE]   bank {} {} status={:#018x} code={:#x}", err.bank, what, err.status, err.mca_code());
+    if let Some(addr) = err.address() {
+        let _ = write!(out, " addr={:#x}", addr);
+    }
+    if err.status & mci_status::MISCV != 0 {
+        let _ = write!(out, " misc={:#x}", err.misc);
+    }
+    let _ = writeln!(out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::mci_status::*;
+
+    const RIPV: u64 = mcg_status::RIPV;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(0, RIPV, true), None);
+        assert_eq!(classify(VAL | EN, RIPV, true), Some(Severity::Corrected));
+        assert_eq!(classify(VAL | EN | PCC, RIPV, true), Some(Severity::Fatal));
+        assert_eq!(classify(VAL | UC | ADDRV, RIPV, true), Some(Severity::Recoverable));
+    }
+
+    #[test]
+    fn test_classify_uncorrected_is_fatal_unless_contained() {
+        let uc = VAL | UC | ADDRV;
+        assert_eq!(classify(uc, RIPV, false), Some(Severity::Fatal));
+        assert_eq!(classify(uc | OVER, RIPV, true), Some(Severity::Fatal));
+        assert_eq!(classify(uc | S | AR, RIPV, true), Some(Severity::Fatal));
+        assert_eq!(classify(VAL | UC, RIPV, true), Some(Severity::Fatal));
+        assert_eq!(classify(uc, 0, true), Some(Severity::Fatal));
+        // Overflow of corrected errors only loses a log entry
+        assert_eq!(classify(VAL | OVER, 0, true), Some(Severity::Corrected));
+    }
+
+    #[test]
+    fn test_bank_error_fields() {
+        let err = BankError { bank: 3, status: VAL | UC | ADDRV | 0x0135, addr: 0x1234_5000, misc: 0 };
+        assert_eq!(err.address(), Some(0x1234_5000));
+        assert_eq!(err.mca_code(), 0x0135);
+        assert_eq!(BankError { status: VAL, ..err }.address(), None);
+        assert_eq!(msr::bank_status(3), 0x40D);
+    }
+}
@@ -12,6 +12,8 @@
 
 use core::arch::asm;
 
+use crate::kinfo;
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -231,23 +233,11 @@ pub unsafe fn jump_to_userspace(user_entry: u64, user_stack: u64) -> ! {
 
 /// Test userspace transition with simple debug output
 ///
-/// This is a test function that writes to the debug console (port 0xE9)
-/// before transitioning to userspace. It's used to verify that the kernel
-/// can communicate with the debug console.
+/// This is a test function that logs a message before transitioning to
+/// userspace. It's used to verify that the kernel log reaches the debug
+/// console.
 pub unsafe fn test_userspace_debug() {
-    const DEBUG_PORT: u16 = 0xE9;
-    let msg = b"[KERNEL] Attempting userspace transition...\n";
-
-    for &byte in msg {
-        unsafe {
-            asm!(
-                "out dx, al",
-                in("dx") DEBUG_PORT,
-                in("al") byte,
-                options(nomem, nostack)
-            );
-        }
-    }
+    kinfo!("[KERNEL] Attempting userspace transition...");
 
     // Use a fixed address for testing
     let test_entry = 0x100000u64;
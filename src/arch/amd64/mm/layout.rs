@@ -0,0 +1,193 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! x86_64 virtual address space layout
+//!
+//! Every fixed virtual address the kernel relies on is defined here; the
+//! ELF loader, the stack setup, the vDSO, thread stacks and the user
+//! access checks all derive from these constants. The lower half belongs
+//! to the process, the upper half to the kernel:
+//!
+//! ```text
+//! 0x0000_0000_0000_0000  null page, never mapped
+//! 0x0000_0000_0000_1000  USER_IMAGE     ELF segments
+//! 0x0000_1000_0000_0000  USER_MMAP      VMAR_MAP mappings
+//! 0x0000_7ffd_0000_0000  THREAD_STACKS  kernel-allocated thread stacks
+//! 0x0000_7fff_0000_0000  VDSO           vDSO time page
+//! 0x0000_7fff_ffff_e000  USER_STACK     main thread stack
+//! 0x0000_7fff_ffff_f000                 guard page
+//! 0x0000_8000_0000_0000  USER_END       end of the user half
+//!          (non-canonical hole)
+//! 0xffff_8000_0000_0000  PHYSMAP        direct map of physical memory
+//! 0xffff_c000_0000_0000  VMALLOC        kernel virtual mappings
+//! ```
+//!
+//! Low physical memory (below [`IDENTITY_MAP_LIMIT`]) is also reachable at
+//! its physical address through the firmware's identity map, which every
+//! process page table shares.
+//!
+//! # Changing the Split
+//!
+//! [`USER_VA_BITS`] sets the size of the user half. Lowering it moves
+//! [`USER_END`] and every region at the top of the user half down with
+//! it; the assertions at the end of this file reject a layout whose
+//! regions overlap, are unaligned or leave the user half. With 4-level
+//! paging it can be at most 47.
+
+/// Page size every region is aligned to
+const PAGE: u64 = 4096;
+
+/// An address range `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// Name, for diagnostics
+    pub name: &'static str,
+    /// First address
+    pub start: u64,
+    /// One past the last address
+    pub end: u64,
+}
+
+impl Region {
+    /// Size in bytes
+    pub const fn size(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Check if `addr` is inside the region
+    pub const fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+
+    /// Check if `[start, end)` overlaps the region
+    pub const fn overlaps(&self, start: u64, end: u64) -> bool {
+        start < self.end && end > self.start
+    }
+}
+
+// ============================================================================
+// User Half
+// ============================================================================
+
+/// Bits of user virtual address space
+pub const USER_VA_BITS: u32 = 47;
+
+/// End of the user half of the address space
+pub const USER_END: u64 = 1 << USER_VA_BITS;
+
+/// ELF segments (above the null page)
+pub const USER_IMAGE: Region = Region { name: "image", start: PAGE, end: 0x0000_1000_0000_0000 };
+
+/// Mappings placed by `VMAR_MAP`
+pub const USER_MMAP: Region = Region { name: "mmap", start: USER_IMAGE.end, end: THREAD_STACKS.start };
+
+/// Stacks the kernel allocates for threads (see [`crate::process::thread`])
+pub const THREAD_STACKS: Region =
+    Region { name: "thread stacks", start: USER_END - 0x3_0000_0000, end: USER_END - 0x2_0000_0000 };
+
+/// The vDSO time page (see [`crate::vdso`])
+pub const VDSO: Region = Region { name: "vdso", start: USER_END - 0x1_0000_0000, end: USER_END - 0xffff_0000 };
+
+/// Size of the main thread's stack
+pub const USER_STACK_SIZE: u64 = PAGE;
+
+/// Top of the main thread's stack; the page above it stays unmapped
+pub const USER_STACK_TOP: u64 = USER_END - PAGE;
+
+/// The main thread's stack
+pub const USER_STACK: Region =
+    Region { name: "stack", start: USER_STACK_TOP - USER_STACK_SIZE, end: USER_STACK_TOP };
+
+/// User regions, lowest first
+pub const USER_REGIONS: [Region; 5] = [USER_IMAGE, USER_MMAP, THREAD_STACKS, VDSO, USER_STACK];
+
+// ============================================================================
+// Kernel Half
+// ============================================================================
+
+/// Start of the kernel half (the first canonical address above the hole)
+pub const KERNEL_START: u64 = 0xffff_8000_0000_0000;
+
+/// Largest amount of physical memory in the direct map
+pub const PHYSMAP_SIZE: u64 = 0x4_0000_0000;
+
+/// Direct map: physical address `p` is at `PHYSMAP.start + p`
+pub const PHYSMAP: Region = Region { name: "physmap", start: KERNEL_START, end: KERNEL_START + PHYSMAP_SIZE };
+
+/// Physical memory below this is also identity mapped by the firmware
+pub const IDENTITY_MAP_LIMIT: u64 = 0x8000_0000;
+
+/// Bytes one PML4 entry maps
+pub const PML4_SLOT_SIZE: u64 = 1 << 39;
+
+/// Kernel virtual mappings: `vmalloc`, `ioremap` and kernel stacks (see
+/// [`crate::mm::vmm`])
+///
+/// Exactly one PML4 slot, so every page table shares its PDPT.
+pub const VMALLOC: Region =
+    Region { name: "vmalloc", start: 0xffff_c000_0000_0000, end: 0xffff_c000_0000_0000 + PML4_SLOT_SIZE };
+
+/// Check if an address is in the user half
+pub const fn is_user(addr: u64) -> bool {
+    addr < USER_END
+}
+
+// ============================================================================
+// Layout Checks
+// ============================================================================
+
+/// Regions are page-aligned, non-empty and in ascending order without
+/// overlap
+const fn well_ordered(regions: &[Region]) -> bool {
+    let mut i = 0;
+    while i < regions.len() {
+        let r = regions[i];
+        if !r.start.is_multiple_of(PAGE) || !r.end.is_multiple_of(PAGE) || r.start >= r.end {
+            return false;
+        }
+        if i > 0 && regions[i - 1].end > r.start {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = assert!(USER_VA_BITS <= 47, "4-level paging has a 47-bit user half");
+const _: () = assert!(well_ordered(&USER_REGIONS), "user regions overlap or are unaligned");
+const _: () = assert!(USER_IMAGE.start > 0, "the null page must stay unmapped");
+const _: () = assert!(USER_STACK.end < USER_END, "the stack needs a guard page below USER_END");
+const _: () = assert!(USER_MMAP.size() >= 1 << 40, "the mmap region is too small");
+const _: () = assert!(PHYSMAP.start >= KERNEL_START && PHYSMAP.end > PHYSMAP.start);
+const _: () = assert!(IDENTITY_MAP_LIMIT <= PHYSMAP_SIZE);
+const _: () = assert!(well_ordered(&[PHYSMAP, VMALLOC]), "vmalloc overlaps the physmap");
+const _: () = assert!(
+    VMALLOC.start.is_multiple_of(PML4_SLOT_SIZE) && VMALLOC.size() == PML4_SLOT_SIZE,
+    "vmalloc must be exactly one PML4 slot"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(USER_END, 0x0000_8000_0000_0000);
+        assert_eq!(VDSO.start, 0x7fff_0000_0000);
+        assert_eq!(THREAD_STACKS.end, 0x7ffe_0000_0000);
+        assert_eq!(USER_STACK_TOP, 0x7fff_ffff_f000);
+        assert!(is_user(USER_STACK.start) && !is_user(PHYSMAP.start));
+        assert_eq!((VMALLOC.start >> 39) & 0x1ff, 384);
+    }
+
+    #[test]
+    fn test_region() {
+        assert!(VDSO.contains(VDSO.start) && !VDSO.contains(VDSO.end));
+        assert!(VDSO.overlaps(VDSO.start - PAGE, VDSO.start + 1));
+        assert!(!VDSO.overlaps(VDSO.end, VDSO.end + PAGE));
+        assert!(!well_ordered(&[USER_STACK, VDSO]));
+    }
+}
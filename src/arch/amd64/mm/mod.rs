@@ -7,6 +7,7 @@
 //! x86_64 memory management
 
 pub mod constants;
+pub mod layout;
 pub mod page_tables;
 
 // Re-export all constants and page table types
@@ -130,6 +130,7 @@ pub mod mmu_flags {
 /// Status return type
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
 pub enum RxStatus {
     /// Operation completed successfully
     OK = 0,
@@ -151,6 +152,12 @@ pub enum RxStatus {
     ERR_INTERNAL = 8,
     /// Not supported
     ERR_NOT_SUPPORTED = 9,
+    /// Operation would block (non-blocking file descriptor)
+    ERR_SHOULD_WAIT = 10,
+    /// Resource already exists
+    ERR_ALREADY_EXISTS = 11,
+    /// Output buffer too small; the required size is reported
+    ERR_BUFFER_TOO_SMALL = 12,
 }
 
 /// Result type using RxStatus
@@ -387,3 +394,132 @@ pub unsafe fn zero_page(page_addr: VAddr) {
         *page_ptr.add(i) = 0;
     }
 }
+
+// ============================================================================
+// Accessed / Dirty Bit Harvesting
+// ============================================================================
+
+/// Entries a harvest flushes one `invlpg` at a time before it flushes
+/// the whole TLB instead
+pub const HARVEST_FLUSH_BATCH: usize = 32;
+
+/// What [`harvest_ad_bits`] found, in 4 KB pages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Harvest {
+    /// Pages mapped in the range
+    pub mapped: u64,
+    /// Mapped pages whose accessed bit was set
+    pub accessed: u64,
+    /// Mapped pages whose dirty bit was set
+    pub dirty: u64,
+}
+
+/// Scan the leaf entries mapping `[start, end)` and clear the `clear` bits
+///
+/// `clear` is a mask of [`mmu_flags::X86_MMU_PG_A`] and
+/// [`mmu_flags::X86_MMU_PG_D`] (0 only reads). Bits are cleared
+/// atomically, so an update by the CPU during the scan is either reported
+/// or left set. `visit` gets the address and old value of each present
+/// leaf (4 KB, or a whole 2 MB / 1 GB page if `X86_MMU_PG_PS` is set), in
+/// address order. Unmapped stretches are skipped a table at a time.
+///
+/// If `pml4` is the active page table, entries whose bits were cleared
+/// are flushed when the scan ends: one `invlpg` each for up to
+/// [`HARVEST_FLUSH_BATCH`] entries, a CR3 reload beyond that. Other CPUs
+/// are not flushed; an access through an entry they cache may not set the
+/// accessed bit again until they switch address spaces.
+///
+/// # Safety
+///
+/// `pml4` must be the physical address of a PML4 whose tables are not
+/// freed during the scan.
+pub unsafe fn harvest_ad_bits(
+    pml4: PAddr,
+    start: VAddr,
+    end: VAddr,
+    clear: u64,
+    mut visit: impl FnMut(VAddr, u64),
+) -> Harvest {
+    let active = crate::arch::amd64::mmu::read_cr3() & PT_ADDR_MASK == pml4 & PT_ADDR_MASK;
+    let mut scan = HarvestScan {
+        clear: clear & (mmu_flags::X86_MMU_PG_A | mmu_flags::X86_MMU_PG_D),
+        active,
+        flush: [0; HARVEST_FLUSH_BATCH],
+        pending: 0,
+        harvest: Harvest::default(),
+        visit: &mut visit,
+    };
+    if start < end {
+        scan.table(pml4, 39, 0, start & !(PAGE_SIZE - 1), end);
+    }
+
+    if scan.pending > HARVEST_FLUSH_BATCH {
+        let cr3 = crate::arch::amd64::mmu::read_cr3();
+        crate::arch::amd64::mmu::write_cr3(cr3);
+    } else {
+        for &vaddr in &scan.flush[..scan.pending] {
+            core::arch::asm!("invlpg [{}]", in(reg) vaddr, options(nostack, preserves_flags));
+        }
+    }
+    scan.harvest
+}
+
+/// Address bits of a page table entry
+const PT_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// State of one [`harvest_ad_bits`] scan
+struct HarvestScan<'a> {
+    clear: u64,
+    active: bool,
+    flush: [VAddr; HARVEST_FLUSH_BATCH],
+    pending: usize,
+    harvest: Harvest,
+    visit: &'a mut dyn FnMut(VAddr, u64),
+}
+
+impl HarvestScan<'_> {
+    /// Scan the part of `[start, end)` covered by the table at `table`,
+    /// whose entries each map `1 << shift` bytes from `base`
+    unsafe fn table(&mut self, table: PAddr, shift: u32, base: VAddr, start: VAddr, end: VAddr) {
+        let entries = crate::mm::pmm::paddr_to_vaddr(table & PT_ADDR_MASK) as *const core::sync::atomic::AtomicU64;
+        let size = 1usize << shift;
+        let first = (start - base) >> shift;
+        let last = core::cmp::min((end - 1 - base) >> shift, ENTRIES_PER_PAGE_TABLE - 1);
+
+        for index in first..=last {
+            let entry = &*entries.add(index);
+            let value = entry.load(core::sync::atomic::Ordering::Relaxed);
+            if value & mmu_flags::X86_MMU_PG_P == 0 {
+                continue;
+            }
+            let entry_base = base + (index << shift);
+            if shift > 12 && (shift == 39 || value & mmu_flags::X86_MMU_PG_PS == 0) {
+                let from = core::cmp::max(start, entry_base);
+                let to = core::cmp::min(end, entry_base + size);
+                self.table(value, shift - 9, entry_base, from, to);
+                continue;
+            }
+
+            let old = if self.clear != 0 && value & self.clear != 0 {
+                entry.fetch_and(!self.clear, core::sync::atomic::Ordering::AcqRel)
+            } else {
+                value
+            };
+            let pages = (size / PAGE_SIZE) as u64;
+            self.harvest.mapped += pages;
+            if old & mmu_flags::X86_MMU_PG_A != 0 {
+                self.harvest.accessed += pages;
+            }
+            if old & mmu_flags::X86_MMU_PG_D != 0 {
+                self.harvest.dirty += pages;
+            }
+            if self.active && old & self.clear != 0 {
+                if let Some(slot) = self.flush.get_mut(self.pending) {
+                    *slot = entry_base;
+                }
+                self.pending += 1;
+            }
+            (self.visit)(entry_base, old);
+        }
+    }
+}
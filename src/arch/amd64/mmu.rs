@@ -34,10 +34,10 @@ static mut BOOT_PML4: Option<PAddr> = None;
 /// Physical memory is mapped at this offset in kernel virtual address space.
 /// This allows the kernel to access any physical page by adding this offset.
 /// Using the standard x86_64 kernel direct map offset (same as Linux).
-const KERNEL_PHYS_OFFSET: u64 = 0xffff_8000_0000_0000;
+const KERNEL_PHYS_OFFSET: u64 = super::mm::layout::PHYSMAP.start;
 
 /// Maximum physical memory to map (16GB for now)
-const MAX_PHYS_MEMORY: u64 = 0x4_0000_0000;
+const MAX_PHYS_MEMORY: u64 = super::mm::layout::PHYSMAP_SIZE;
 
 /// Early MMU initialization
 ///
@@ -13,6 +13,9 @@
 pub mod apic;
 pub mod controller;
 
+// Legacy 8259A PIC (remapped and masked)
+pub mod pic;
+
 // Descriptor tables (GDT, IDT)
 pub mod idt;
 pub mod descriptor;
@@ -33,13 +36,24 @@ pub mod test;
 // Low-level CPU operations
 pub mod registers;
 pub mod tsc;
+pub mod hpet;
 pub mod ioport;
 pub mod cache;
 pub mod ops;
+pub mod cpu_features;
+
+// Early boot random numbers (KASLR)
+pub mod entropy;
+
+// Power management (C-state idle, CPU frequency)
+pub mod power;
 
 // System call support
 pub mod syscall;
 
+// Kernel entry/exit GS discipline and the syscall stub
+pub mod entry;
+
 // User space entry
 pub mod uspace_entry;
 
@@ -49,8 +63,35 @@ pub mod mexec;
 // Exception and fault handlers
 pub mod faults;
 
+// NMI handler on its own IST stack
+pub mod nmi;
+
+// Machine check (#MC) handling and MCA bank setup
+pub mod mce;
+
+// Hardware breakpoints and watchpoints (DR0-DR7)
+pub mod debug;
+
+// Page fault recovery for user copies
+pub mod extable;
+
+// Frame-pointer backtraces
+pub mod backtrace;
+
+// Panic reports (registers, backtrace, symbols)
+pub mod panic;
+
 // Bootstrap support for SMP
 pub mod bootstrap16;
 
+// Application processor bring-up
+pub mod smp;
+
+// Cross-CPU TLB flushes
+pub mod tlb;
+
+// Read-only kernel text and freeing of boot-only code
+pub mod kprotect;
+
 // Re-export the interrupt controller
 pub use controller::X86_64InterruptController;
@@ -0,0 +1,403 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Non-Maskable Interrupt Handling
+//!
+//! NMIs arrive at any instruction, including inside code holding a
+//! spinlock or in the middle of a kernel entry. The handler therefore:
+//!
+//! - runs on its own IST stack ([`NMI_IST`]), so a bad or nearly full
+//!   kernel stack can't take it down
+//! - decides whether to `swapgs` from the GS MSR
+//!   ([`GsGuard::paranoid`](super::entry::GsGuard::paranoid))
+//! - never takes a lock: it records into a lock-free ring ([`records`])
+//!   and prints straight to the debug port
+//!
+//! # Sources
+//!
+//! | Reason | How it is recognised |
+//! |--------|----------------------|
+//! | [`NmiReason::Watchdog`] | overflow of the PMC registered with [`set_watchdog_counter`] |
+//! | [`NmiReason::PerfCounter`] | any other overflow in `IA32_PERF_GLOBAL_STATUS` |
+//! | [`NmiReason::HardwareError`] | SERR# or IOCHK# in system control port B (0x61) |
+//! | [`NmiReason::Unknown`] | none of the above |
+//!
+//! Hardware errors and unknown NMIs are dumped; watchdog and perf NMIs
+//! are only counted in the ring.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, AtomicU8, Ordering};
+use crate::arch::amd64::registers::{read_msr, write_msr};
+use crate::interrupt::affinity::MAX_CPUS;
+use crate::kerror;
+
+/// IST slot used by the NMI gate (TSS `ist1`)
+pub const NMI_IST: u8 = super::descriptor::ist::NMI;
+
+/// NMI stack size per CPU
+pub const NMI_STACK_SIZE: usize = 16 * 1024;
+
+/// Entries kept in the NMI ring
+pub const RING_SIZE: usize = 32;
+
+/// NMI vector
+const NMI_VECTOR: u8 = 2;
+
+/// `IA32_PERF_GLOBAL_STATUS`
+const MSR_PERF_GLOBAL_STATUS: u32 = 0x38E;
+
+/// `IA32_PERF_GLOBAL_OVF_CTRL`
+const MSR_PERF_GLOBAL_OVF_CTRL: u32 = 0x390;
+
+/// System control port B
+const PORT_SYSTEM_CONTROL_B: u16 = 0x61;
+
+/// Port B: SERR# asserted (PCI system error / memory parity)
+const PORT_B_SERR: u8 = 1 << 7;
+
+/// Port B: IOCHK# asserted (I/O channel check)
+const PORT_B_IOCHK: u8 = 1 << 6;
+
+/// Why an NMI was raised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NmiReason {
+    /// The watchdog's performance counter overflowed
+    Watchdog,
+    /// Another performance counter overflowed (profiling)
+    PerfCounter,
+    /// Platform hardware error (SERR#/IOCHK#)
+    HardwareError { serr: bool, iochk: bool },
+    /// Nothing claimed it
+    Unknown,
+}
+
+/// Context the NMI interrupted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NmiContext {
+    /// CPU that took the NMI
+    pub cpu: usize,
+    /// Interrupted instruction pointer
+    pub rip: u64,
+    /// Interrupted CS (0 if unknown)
+    pub cs: u64,
+    /// Interrupted RFLAGS
+    pub rflags: u64,
+    /// Interrupted stack pointer (0 if unknown)
+    pub rsp: u64,
+}
+
+/// One NMI as recorded in the ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NmiRecord {
+    /// Sequence number (1-based, in arrival order)
+    pub seq: u64,
+    /// TSC at entry
+    pub tsc: u64,
+    /// Classification
+    pub reason: NmiReason,
+    /// Interrupted context
+    pub context: NmiContext,
+}
+
+/// Register the PMC (0-based index) the NMI watchdog uses
+///
+/// Overflows of that counter are reported as [`NmiReason::Watchdog`].
+pub fn set_watchdog_counter(counter: Option<u8>) {
+    WATCHDOG_COUNTER.store(counter.unwrap_or(NO_COUNTER), Ordering::Relaxed);
+}
+
+/// No watchdog counter registered
+const NO_COUNTER: u8 = u8::MAX;
+
+static WATCHDOG_COUNTER: AtomicU8 = AtomicU8::new(NO_COUNTER);
+
+/// Classify an NMI from its sources
+///
+/// # Arguments
+///
+/// * `perf_status` - `IA32_PERF_GLOBAL_STATUS` (0 without perfmon v2)
+/// * `port_b` - System control port B
+/// * `watchdog_counter` - PMC index of the watchdog, if any
+pub fn classify(perf_status: u64, port_b: u8, watchdog_counter: Option<u8>) -> NmiReason {
+    // Hardware errors first: they must never be mistaken for a tick
+    let serr = port_b & PORT_B_SERR != 0;
+    let iochk = port_b & PORT_B_IOCHK != 0;
+    if serr || iochk {
+        return NmiReason::HardwareError { serr, iochk };
+    }
+
+    let overflow = perf_status & !(1 << 63); // bit 63: condition changed
+    match watchdog_counter {
+        Some(c) if c < 63 && overflow & (1 << c) != 0 => NmiReason::Watchdog,
+        _ if overflow != 0 => NmiReason::PerfCounter,
+        _ => NmiReason::Unknown,
+    }
+}
+
+// ============================================================================
+// Lock-Free Ring
+// ============================================================================
+
+/// A ring slot, guarded by a sequence word
+///
+/// `seq` is odd while the slot is being written and holds the record's
+/// sequence number (times two) once it is complete, so a reader can
+/// detect torn copies without a lock.
+struct Slot {
+    seq: AtomicU64,
+    tsc: AtomicU64,
+    reason: AtomicU64,
+    cpu: AtomicU64,
+    rip: AtomicU64,
+    cs: AtomicU64,
+    rflags: AtomicU64,
+    rsp: AtomicU64,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicU64::new(0),
+            tsc: AtomicU64::new(0),
+            reason: AtomicU64::new(0),
+            cpu: AtomicU64::new(0),
+            rip: AtomicU64::new(0),
+            cs: AtomicU64::new(0),
+            rflags: AtomicU64::new(0),
+            rsp: AtomicU64::new(0),
+        }
+    }
+}
+
+static RING: [Slot; RING_SIZE] = [const { Slot::new() }; RING_SIZE];
+
+/// Next sequence number to hand out
+static NEXT_SEQ: AtomicUsize = AtomicUsize::new(1);
+
+/// Per-CPU NMI counts
+static COUNTS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+fn encode_reason(reason: NmiReason) -> u64 {
+    match reason {
+        NmiReason::Watchdog => 1,
+        NmiReason::PerfCounter => 2,
+        NmiReason::HardwareError { serr, iochk } => 3 | (serr as u64) << 8 | (iochk as u64) << 9,
+        NmiReason::Unknown => 0,
+    }
+}
+
+fn decode_reason(raw: u64) -> NmiReason {
+    match raw & 0xFF {
+        1 => NmiReason::Watchdog,
+        2 => NmiReason::PerfCounter,
+        3 => NmiReason::HardwareError { serr: raw & (1 << 8) != 0, iochk: raw & (1 << 9) != 0 },
+        _ => NmiReason::Unknown,
+    }
+}
+
+/// Append to the ring
+///
+/// Safe from NMI context on any number of CPUs: the slot is claimed with
+/// a single atomic add.
+fn record(tsc: u64, reason: NmiReason, ctx: &NmiContext) -> u64 {
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed) as u64;
+    let slot = &RING[seq as usize % RING_SIZE];
+
+    slot.seq.store(seq * 2 + 1, Ordering::Release);
+    slot.tsc.store(tsc, Ordering::Relaxed);
+    slot.reason.store(encode_reason(reason), Ordering::Relaxed);
+    slot.cpu.store(ctx.cpu as u64, Ordering::Relaxed);
+    slot.rip.store(ctx.rip, Ordering::Relaxed);
+    slot.cs.store(ctx.cs, Ordering::Relaxed);
+    slot.rflags.store(ctx.rflags, Ordering::Relaxed);
+    slot.rsp.store(ctx.rsp, Ordering::Relaxed);
+    slot.seq.store(seq * 2, Ordering::Release);
+
+    COUNTS[ctx.cpu % MAX_CPUS].fetch_add(1, Ordering::Relaxed);
+    seq
+}
+
+/// Copy one slot, or None if it is empty or being written
+fn read_slot(slot: &Slot) -> Option<NmiRecord> {
+    let before = slot.seq.load(Ordering::Acquire);
+    if before == 0 || before & 1 != 0 {
+        return None;
+    }
+    let record = NmiRecord {
+        seq: before / 2,
+        tsc: slot.tsc.load(Ordering::Relaxed),
+        reason: decode_reason(slot.reason.load(Ordering::Relaxed)),
+        context: NmiContext {
+            cpu: slot.cpu.load(Ordering::Relaxed) as usize,
+            rip: slot.rip.load(Ordering::Relaxed),
+            cs: slot.cs.load(Ordering::Relaxed),
+            rflags: slot.rflags.load(Ordering::Relaxed),
+            rsp: slot.rsp.load(Ordering::Relaxed),
+        },
+    };
+    (slot.seq.load(Ordering::Acquire) == before).then_some(record)
+}
+
+/// The most recent NMIs, oldest first
+///
+/// Holds at most [`RING_SIZE`] entries; records being overwritten at the
+/// time of the call are skipped.
+pub fn records() -> alloc::vec::Vec<NmiRecord> {
+    let mut out: alloc::vec::Vec<NmiRecord> = RING.iter().filter_map(read_slot).collect();
+    out.sort_by_key(|r| r.seq);
+    out
+}
+
+/// Number of NMIs taken by `cpu`
+pub fn count(cpu: usize) -> u64 {
+    COUNTS[cpu % MAX_CPUS].load(Ordering::Relaxed)
+}
+
+// ============================================================================
+// Handler
+// ============================================================================
+
+/// Frame pushed by the CPU on interrupt entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CpuFrame {
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+/// NMI stacks, one per CPU
+#[repr(C, align(16))]
+struct NmiStack([u8; NMI_STACK_SIZE]);
+
+static mut NMI_STACKS: [NmiStack; MAX_CPUS] = [const { NmiStack([0; NMI_STACK_SIZE]) }; MAX_CPUS];
+
+/// Top of the NMI stack of `cpu`
+pub fn stack_top(cpu: usize) -> u64 {
+    let stack = unsafe { &raw const NMI_STACKS[cpu % MAX_CPUS] };
+    stack as u64 + NMI_STACK_SIZE as u64
+}
+
+/// Install the NMI gate on its IST stack
+///
+/// Each CPU's TSS points the slot at [`stack_top`] of that CPU
+/// (see [`cpu_init`](super::descriptor::cpu_init)).
+///
+/// # Safety
+///
+/// The IDT must be set up (`idt_setup_readonly`).
+pub unsafe fn install() {
+    super::idt::idt_set_gate_ist(
+        NMI_VECTOR,
+        nmi_entry as *const () as u64,
+        0x08,
+        super::idt::IDT_INTERRUPT_GATE,
+        NMI_IST,
+    );
+}
+
+/// Whether `IA32_PERF_GLOBAL_STATUS` exists (architectural perfmon v2+)
+fn has_perf_global_status() -> bool {
+    super::cpu_features::cpuid(0, 0).eax >= 0xA && super::cpu_features::cpuid(0xA, 0).eax & 0xFF >= 2
+}
+
+extern "x86-interrupt" fn nmi_entry(frame: CpuFrame) {
+    let _gs = unsafe { super::entry::GsGuard::paranoid() };
+    handle(NmiContext {
+        cpu: crate::interrupt::affinity::current_cpu(),
+        rip: frame.rip,
+        cs: frame.cs,
+        rflags: frame.rflags,
+        rsp: frame.rsp,
+    });
+}
+
+/// Handle an NMI
+///
+/// Classifies, acknowledges the perf counters it consumed, records it
+/// and dumps hardware errors and unknown NMIs.
+pub fn handle(ctx: NmiContext) -> NmiReason {
+    let tsc = super::tsc::tsc_ticks();
+    let perf_status = if has_perf_global_status() {
+        unsafe { read_msr(MSR_PERF_GLOBAL_STATUS) }
+    } else {
+        0
+    };
+    let port_b = unsafe { super::ioport::inb(PORT_SYSTEM_CONTROL_B) };
+    let watchdog = match WATCHDOG_COUNTER.load(Ordering::Relaxed) {
+        NO_COUNTER => None,
+        c => Some(c),
+    };
+
+    let reason = classify(perf_status, port_b, watchdog);
+    if matches!(reason, NmiReason::Watchdog | NmiReason::PerfCounter) {
+        unsafe { write_msr(MSR_PERF_GLOBAL_OVF_CTRL, perf_status) };
+    }
+
+    let seq = record(tsc, reason, &ctx);
+    if matches!(reason, NmiReason::HardwareError { .. } | NmiReason::Unknown) {
+        dump(seq, reason, &ctx);
+    }
+    reason
+}
+
+/// Log an NMI (no locks, no allocation)
+fn dump(seq: u64, reason: NmiReason, ctx: &NmiContext) {
+    let what = match reason {
+        NmiReason::HardwareError { serr: true, .. } => "hardware error (SERR#)",
+        NmiReason::HardwareError { .. } => "hardware error (IOCHK#)",
+        NmiReason::Watchdog => "watchdog",
+        NmiReason::PerfCounter => "perf counter",
+        NmiReason::Unknown => "unknown source",
+    };
+    kerror!("[NMI] #{} {} cpu={}", seq, what, ctx.cpu);
+    kerror!("[NMI]   rip={:#018x} cs={:#x} rflags={:#x} rsp={:#018x}", ctx.rip, ctx.cs, ctx.rflags, ctx.rsp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(0, 0, None), NmiReason::Unknown);
+        assert_eq!(classify(1 << 0, 0, None), NmiReason::PerfCounter);
+        assert_eq!(classify(1 << 1, 0, Some(1)), NmiReason::Watchdog);
+        assert_eq!(classify(1 << 0, 0, Some(1)), NmiReason::PerfCounter);
+        assert_eq!(classify(1 << 63, 0, None), NmiReason::Unknown);
+        // A hardware error wins over a simultaneous counter overflow
+        assert_eq!(
+            classify(1 << 1, PORT_B_SERR, Some(1)),
+            NmiReason::HardwareError { serr: true, iochk: false }
+        );
+    }
+
+    #[test]
+    fn test_reason_encoding_round_trips() {
+        for reason in [
+            NmiReason::Watchdog,
+            NmiReason::PerfCounter,
+            NmiReason::HardwareError { serr: false, iochk: true },
+            NmiReason::Unknown,
+        ] {
+            assert_eq!(decode_reason(encode_reason(reason)), reason);
+        }
+    }
+
+    #[test]
+    fn test_ring_records_in_order() {
+        let ctx = NmiContext { cpu: 1, rip: 0x1000, cs: 0x08, rflags: 0x2, rsp: 0x8000 };
+        let a = record(10, NmiReason::PerfCounter, &ctx);
+        let b = record(20, NmiReason::Unknown, &ctx);
+        let recent = records();
+        let ours: alloc::vec::Vec<_> = recent.iter().filter(|r| r.seq == a || r.seq == b).collect();
+        assert_eq!(ours.len(), 2);
+        assert_eq!(ours[0].reason, NmiReason::PerfCounter);
+        assert_eq!(ours[1].context, ctx);
+        assert!(count(1) >= 2);
+    }
+}
@@ -67,6 +67,8 @@ pub unsafe fn read_msr_safe(msr_id: u32, val_out: *mut u64) -> bool {
         0x1B => true,
         // IA32_BIOS_SIGN_ID
         0x8B => true,
+        // IA32_MPERF, IA32_APERF
+        0xE7 | 0xE8 => true,
         // IA32_MTRRCAP
         0xFE => true,
         // IA32_SYSENTER_CS, ESP, EIP
@@ -0,0 +1,187 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Panic Reports
+//!
+//! The panic handler in `main.rs` calls [`report`], which writes:
+//!
+//! 1. The panic message and the CPU that panicked
+//! 2. The interrupted register state, if a fatal exception saved it with
+//!    [`set_frame`] before panicking
+//! 3. A frame-pointer backtrace ([`super::backtrace`]), starting at the
+//!    interrupted instruction when there is a saved frame. Frames are
+//!    named from the kernel symbol table ([`crate::ksyms`]) when the
+//!    kernel was built with one
+//!
+//! Fatal exceptions (unhandled kernel page faults, general protection
+//! faults, invalid opcodes, double faults) save their frame and `panic!`,
+//! so they are reported, logged and dumped like any other panic.
+//!
+//! # Output
+//!
+//! The report goes to the QEMU debug port, to COM1 (polled, after
+//! draining the transmit queue, since interrupts are off for good) and to
+//! the framebuffer console, which is switched to the console VT first. It
+//! is also kept in the log ring ([`crate::klog`]) for the crash dump.
+//!
+//! Nothing here allocates or waits for a lock: a panic can happen with
+//! any of them held. The framebuffer console's locks are bypassed for
+//! good first ([`crate::drivers::display::vt::force_unlock`]).
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use crate::sync::SpinMutex;
+use crate::syscall::X86Iframe;
+use super::backtrace;
+use super::nmi::CpuFrame;
+
+/// Register state at a fatal exception
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PanicFrame {
+    /// Interrupted instruction
+    pub rip: u64,
+    /// Interrupted stack pointer
+    pub rsp: u64,
+    /// Interrupted frame pointer (0 if the entry path did not save it)
+    pub rbp: u64,
+    /// Interrupted flags
+    pub rflags: u64,
+    /// Interrupted code segment (0 if unknown)
+    pub cs: u64,
+    /// Exception error code, if the exception has one
+    pub error_code: Option<u64>,
+    /// CR2 at the time of the exception
+    pub cr2: u64,
+    /// General registers, if the entry path saved them
+    pub regs: Option<X86Iframe>,
+}
+
+impl PanicFrame {
+    /// Frame of an exception with a full register frame
+    pub fn from_iframe(frame: &X86Iframe, error_code: Option<u64>) -> Self {
+        Self {
+            rip: frame.ip,
+            rsp: frame.user_sp,
+            rbp: frame.rbp,
+            rflags: frame.flags,
+            error_code,
+            cr2: unsafe { super::registers::x86_get_cr2() },
+            regs: Some(*frame),
+            ..Self::default()
+        }
+    }
+
+    /// Frame of an exception taken on an IST stack
+    pub fn from_cpu_frame(frame: &CpuFrame, error_code: Option<u64>) -> Self {
+        Self {
+            rip: frame.rip,
+            rsp: frame.rsp,
+            rflags: frame.rflags,
+            cs: frame.cs,
+            error_code,
+            cr2: unsafe { super::registers::x86_get_cr2() },
+            ..Self::default()
+        }
+    }
+
+    /// Write the registers, a few per line
+    pub fn write_to(&self, out: &mut impl Write) -> fmt::Result {
+        writeln!(out, "[PANIC] rip={:#018x} rsp={:#018x} rbp={:#018x}", self.rip, self.rsp, self.rbp)?;
+        write!(out, "[PANIC] rflags={:#x} cs={:#x} cr2={:#x}", self.rflags, self.cs, self.cr2)?;
+        if let Some(error_code) = self.error_code {
+            write!(out, " error={:#x}", error_code)?;
+        }
+        writeln!(out)?;
+        if let Some(r) = &self.regs {
+            writeln!(out, "[PANIC] rax={:#018x} rbx={:#018x} rdi={:#018x} rsi={:#018x}", r.rax, r.rbx, r.rdi, r.rsi)?;
+            writeln!(out, "[PANIC] rdx={:#018x} r8 ={:#018x} r9 ={:#018x} r10={:#018x}", r.rdx, r.r8, r.r9, r.r10)?;
+            writeln!(out, "[PANIC] r12={:#018x} r13={:#018x} r14={:#018x} r15={:#018x}", r.r12, r.r13, r.r14, r.r15)?;
+        }
+        Ok(())
+    }
+}
+
+static FRAME: SpinMutex<Option<PanicFrame>> = SpinMutex::new(None);
+
+/// Save the frame of a fatal exception for the panic report
+///
+/// Call right before `panic!`.
+pub fn set_frame(frame: PanicFrame) {
+    if let Some(mut saved) = FRAME.try_lock() {
+        *saved = Some(frame);
+    }
+}
+
+fn take_frame() -> Option<PanicFrame> {
+    FRAME.try_lock()?.take()
+}
+
+/// `fmt::Write` sink for every panic output
+struct PanicWriter;
+
+impl Write for PanicWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        crate::klog::debugcon_write(s.as_bytes());
+        if let Some(com1) = unsafe { crate::drivers::uart::com1() } {
+            com1.write_str_sync(s);
+        }
+        if crate::drivers::display::console::is_initialized() {
+            crate::drivers::display::console::write_str(s);
+        }
+        crate::klog::write(s);
+        Ok(())
+    }
+}
+
+/// Write the panic report
+pub fn report(info: &PanicInfo) {
+    // Earlier output first, then the report where it will be seen
+    crate::drivers::uart::flush_com1();
+    if crate::drivers::display::console::is_initialized() {
+        crate::drivers::display::vt::force_unlock();
+        crate::drivers::display::vt::reclaim_display();
+        crate::drivers::display::vt::switch_to(crate::drivers::display::vt::CONSOLE_VT);
+    }
+
+    let mut out = PanicWriter;
+    let cpu = crate::interrupt::affinity::current_cpu();
+    let _ = writeln!(out, "\n[PANIC] CPU {}: {}", cpu, info);
+
+    let frame = take_frame();
+    if let Some(frame) = &frame {
+        let _ = frame.write_to(&mut out);
+    }
+    let bt = match frame {
+        Some(frame) if frame.rbp != 0 => backtrace::capture_from(frame.rip as usize, frame.rbp as usize),
+        _ => backtrace::capture(),
+    };
+    let _ = writeln!(out, "[BACKTRACE] {} frame(s):", bt.frames().len());
+    let _ = bt.write_to(&mut out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    #[test]
+    fn test_frame_output() {
+        let frame = PanicFrame { rip: 0x1000, rsp: 0x2000, error_code: Some(2), cr2: 0xdead, ..PanicFrame::default() };
+        let mut out = String::new();
+        frame.write_to(&mut out).unwrap();
+        assert!(out.starts_with("[PANIC] rip=0x0000000000001000 rsp=0x0000000000002000"));
+        assert!(out.contains("cr2=0xdead error=0x2\n"));
+        assert_eq!(out.lines().count(), 2);
+
+        let mut regs = X86Iframe::new();
+        regs.rax = 0x42;
+        let frame = PanicFrame { regs: Some(regs), ..frame };
+        let mut out = String::new();
+        frame.write_to(&mut out).unwrap();
+        assert_eq!(out.lines().count(), 5);
+        assert!(out.contains("rax=0x0000000000000042"));
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Legacy 8259A PIC
+//!
+//! Interrupts are delivered through the Local APIC and I/O APIC, but the
+//! two cascaded 8259As are still present on PC hardware and in QEMU. At
+//! reset their IRQs 0-15 use vectors 0x08-0x0F and 0x70-0x77; the first
+//! range overlaps CPU exceptions, so a stray IRQ0-7 would look like a
+//! double fault or a page fault. [`init`] therefore remaps them to
+//! [`PIC1_VECTOR_BASE`]..0x2F and masks every line, and must run before
+//! the APIC is set up.
+//!
+//! # Spurious IRQs
+//!
+//! Even with every line masked, a PIC can raise IRQ7 (master) or IRQ15
+//! (slave) when a request goes away before it is acknowledged. These
+//! arrive at vectors 0x27 and 0x2F, whose gates [`install`] sets. A
+//! spurious IRQ has no in-service bit and must not be acknowledged, except
+//! that a spurious IRQ15 did take the master's cascade line (IRQ2), so the
+//! master still gets an EOI. Spurious IRQs are counted in
+//! [`spurious_count`].
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use super::ioport::{inb, outb};
+use crate::kinfo;
+
+/// Master PIC command port
+const PIC1_CMD: u16 = 0x20;
+
+/// Master PIC data (mask) port
+const PIC1_DATA: u16 = 0x21;
+
+/// Slave PIC command port
+const PIC2_CMD: u16 = 0xA0;
+
+/// Slave PIC data (mask) port
+const PIC2_DATA: u16 = 0xA1;
+
+/// Vector of master IRQ0 after [`init`]
+pub const PIC1_VECTOR_BASE: u8 = 0x20;
+
+/// Vector of slave IRQ8 after [`init`]
+pub const PIC2_VECTOR_BASE: u8 = 0x28;
+
+/// ICW1: initialise, ICW4 follows
+const ICW1_INIT: u8 = 0x11;
+
+/// ICW3 (master): slave on IRQ2
+const ICW3_MASTER: u8 = 1 << 2;
+
+/// ICW3 (slave): cascade identity 2
+const ICW3_SLAVE: u8 = 2;
+
+/// ICW4: 8086 mode
+const ICW4_8086: u8 = 0x01;
+
+/// OCW2: non-specific EOI
+const OCW2_EOI: u8 = 0x20;
+
+/// OCW3: read the in-service register on the next command port read
+const OCW3_READ_ISR: u8 = 0x0B;
+
+/// IRQ line the slave is cascaded on
+const CASCADE_IRQ: u8 = 2;
+
+/// Spurious IRQs seen on IRQ7 and IRQ15
+static SPURIOUS: AtomicU64 = AtomicU64::new(0);
+
+/// Vector an IRQ is delivered at after [`init`]
+pub const fn irq_vector(irq: u8) -> u8 {
+    if irq < 8 {
+        PIC1_VECTOR_BASE + irq
+    } else {
+        PIC2_VECTOR_BASE + (irq - 8)
+    }
+}
+
+/// Give the PIC time to settle between initialisation words
+///
+/// Port 0x80 (POST codes) is unused; writing it takes about 1us.
+unsafe fn io_wait() {
+    outb(0x80, 0);
+}
+
+/// Remap the PICs to vectors 0x20-0x2F and mask every line
+///
+/// # Safety
+///
+/// Must run with interrupts disabled, before the APIC is set up.
+pub unsafe fn init() {
+    // ICW1-ICW4 on both chips, interleaved so each gets its settling time
+    outb(PIC1_CMD, ICW1_INIT);
+    io_wait();
+    outb(PIC2_CMD, ICW1_INIT);
+    io_wait();
+    outb(PIC1_DATA, PIC1_VECTOR_BASE);
+    io_wait();
+    outb(PIC2_DATA, PIC2_VECTOR_BASE);
+    io_wait();
+    outb(PIC1_DATA, ICW3_MASTER);
+    io_wait();
+    outb(PIC2_DATA, ICW3_SLAVE);
+    io_wait();
+    outb(PIC1_DATA, ICW4_8086);
+    io_wait();
+    outb(PIC2_DATA, ICW4_8086);
+    io_wait();
+
+    // Mask everything; the I/O APIC delivers device interrupts
+    outb(PIC1_DATA, 0xFF);
+    outb(PIC2_DATA, 0xFF);
+
+    kinfo!("[PIC] Remapped to 0x20-0x2F, all IRQs masked");
+}
+
+/// Install the spurious IRQ7 and IRQ15 gates
+///
+/// # Safety
+///
+/// The IDT must be set up (`idt_setup_readonly`).
+pub unsafe fn install() {
+    super::idt::idt_set_gate(irq_vector(7), spurious_irq7 as *const () as u64, 0x08, super::idt::IDT_INTERRUPT_GATE);
+    super::idt::idt_set_gate(irq_vector(15), spurious_irq15 as *const () as u64, 0x08, super::idt::IDT_INTERRUPT_GATE);
+}
+
+/// Mask one IRQ line
+pub fn mask(irq: u8) {
+    let (port, bit) = if irq < 8 { (PIC1_DATA, irq) } else { (PIC2_DATA, irq - 8) };
+    unsafe { outb(port, inb(port) | (1 << bit)) };
+}
+
+/// Unmask one IRQ line (and the cascade, for a slave line)
+pub fn unmask(irq: u8) {
+    let (port, bit) = if irq < 8 { (PIC1_DATA, irq) } else { (PIC2_DATA, irq - 8) };
+    unsafe { outb(port, inb(port) & !(1 << bit)) };
+    if irq >= 8 {
+        unmask(CASCADE_IRQ);
+    }
+}
+
+/// Acknowledge an IRQ taken through the PIC
+pub fn send_eoi(irq: u8) {
+    unsafe {
+        if irq >= 8 {
+            outb(PIC2_CMD, OCW2_EOI);
+        }
+        outb(PIC1_CMD, OCW2_EOI);
+    }
+}
+
+/// In-service register of the PIC at `cmd`
+fn read_isr(cmd: u16) -> u8 {
+    unsafe {
+        outb(cmd, OCW3_READ_ISR);
+        inb(cmd)
+    }
+}
+
+/// Number of spurious IRQ7/IRQ15 interrupts since boot
+pub fn spurious_count() -> u64 {
+    SPURIOUS.load(Ordering::Relaxed)
+}
+
+// Neither handler touches per-CPU data, so GS is left as it is.
+
+extern "x86-interrupt" fn spurious_irq7(_frame: super::idt::X86Iframe) {
+    if read_isr(PIC1_CMD) & (1 << 7) != 0 {
+        send_eoi(7);
+    } else {
+        SPURIOUS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+extern "x86-interrupt" fn spurious_irq15(_frame: super::idt::X86Iframe) {
+    if read_isr(PIC2_CMD) & (1 << 7) != 0 {
+        send_eoi(15);
+    } else {
+        // The master saw a real request on the cascade line
+        SPURIOUS.fetch_add(1, Ordering::Relaxed);
+        send_eoi(CASCADE_IRQ);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_irq_vectors_clear_exceptions() {
+        assert_eq!(irq_vector(0), 0x20);
+        assert_eq!(irq_vector(7), 0x27);
+        assert_eq!(irq_vector(8), 0x28);
+        assert_eq!(irq_vector(15), 0x2F);
+        assert!((0..16).all(|irq| irq_vector(irq) >= 0x20));
+    }
+}
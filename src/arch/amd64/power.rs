@@ -0,0 +1,347 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Power Management: C-State Idle and CPU Frequency
+//!
+//! # Idle
+//!
+//! [`idle`] is called by the idle loop when there is nothing to run. If
+//! the CPU supports MONITOR/MWAIT it enters the deepest C-state that
+//! satisfies two constraints:
+//!
+//! - its exit latency is within the latency limit
+//!   ([`set_latency_limit_us`], unlimited by default)
+//! - its target residency is within the predicted idle time, a moving
+//!   average of recent idle periods
+//!
+//! Otherwise (or if no state qualifies) it falls back to `hlt`.
+//!
+//! # C-State Discovery
+//!
+//! The available C-states come from CPUID leaf 5 (number of MWAIT
+//! sub-states per C-state). ACPI `_CST` is not used: it is an AML method
+//! and the kernel has no AML interpreter. Exit latencies and target
+//! residencies are therefore conservative defaults rather than
+//! firmware-provided values.
+//!
+//! # Frequency
+//!
+//! Base and maximum frequency come from CPUID leaf 0x16, falling back to
+//! the TSC frequency. The current (effective) frequency is derived from
+//! the IA32_APERF / IA32_MPERF ratio since the previous sample.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use crate::arch::amd64::cpu_features::{self, MwaitInfo, MWAIT_MAX_CSTATES};
+use crate::arch::amd64::{ops, tsc};
+use crate::klog::{Level, LogWriter};
+use crate::sync::SpinMutex;
+
+/// IA32_MPERF (counts at a fixed reference frequency)
+pub const MSR_IA32_MPERF: u32 = 0xE7;
+
+/// IA32_APERF (counts at the actual frequency)
+pub const MSR_IA32_APERF: u32 = 0xE8;
+
+/// Latency limit meaning "no constraint"
+pub const LATENCY_UNLIMITED: u32 = u32::MAX;
+
+/// An MWAIT C-state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CState {
+    /// Name (e.g. "C1")
+    pub name: &'static str,
+    /// MWAIT hint (EAX): C-state in bits 7:4, sub-state in bits 3:0
+    pub hint: u32,
+    /// Worst-case exit latency in microseconds
+    pub exit_latency_us: u32,
+    /// Minimum idle time for the state to save power, in microseconds
+    pub target_residency_us: u32,
+}
+
+/// Names of the C-states CPUID leaf 5 can describe
+const CSTATE_NAMES: [&str; MWAIT_MAX_CSTATES] = ["C0", "C1", "C2", "C3", "C4", "C5", "C6", "C7"];
+
+/// Default (exit latency, target residency) in microseconds per C-state
+const DEFAULT_TIMINGS: [(u32, u32); MWAIT_MAX_CSTATES] = [
+    (0, 0),
+    (2, 2),
+    (10, 20),
+    (50, 150),
+    (100, 400),
+    (150, 600),
+    (200, 800),
+    (250, 1000),
+];
+
+/// Available C-states, shallowest first
+#[derive(Debug, Clone, Copy)]
+pub struct CStateTable {
+    states: [CState; MWAIT_MAX_CSTATES],
+    count: usize,
+}
+
+impl CStateTable {
+    /// A table with no states (MWAIT unavailable)
+    pub const fn empty() -> Self {
+        const NONE: CState = CState { name: "", hint: 0, exit_latency_us: 0, target_residency_us: 0 };
+        Self { states: [NONE; MWAIT_MAX_CSTATES], count: 0 }
+    }
+
+    /// Build the table from CPUID leaf 5
+    ///
+    /// Every C-state (C1 and deeper) with at least one sub-state is
+    /// added, using its first sub-state.
+    pub fn from_mwait(info: &MwaitInfo) -> Self {
+        let mut table = Self::empty();
+        for n in 1..MWAIT_MAX_CSTATES {
+            if info.substates[n] == 0 {
+                continue;
+            }
+            let (exit_latency_us, target_residency_us) = DEFAULT_TIMINGS[n];
+            table.states[table.count] = CState {
+                name: CSTATE_NAMES[n],
+                hint: ((n as u32 - 1) & 0xF) << 4,
+                exit_latency_us,
+                target_residency_us,
+            };
+            table.count += 1;
+        }
+        table
+    }
+
+    /// The available states
+    pub fn states(&self) -> &[CState] {
+        &self.states[..self.count]
+    }
+}
+
+/// Select a C-state
+///
+/// Returns the index of the deepest state whose exit latency is at most
+/// `latency_limit_us` and whose target residency is at most
+/// `predicted_idle_us`, or `None` if no state qualifies.
+pub fn select_state(states: &[CState], predicted_idle_us: u64, latency_limit_us: u32) -> Option<usize> {
+    states
+        .iter()
+        .rposition(|s| s.exit_latency_us <= latency_limit_us
+            && s.target_residency_us as u64 <= predicted_idle_us)
+}
+
+// ============================================================================
+// Idle
+// ============================================================================
+
+/// C-states discovered by [`init`]
+static CSTATES: SpinMutex<CStateTable> = SpinMutex::new(CStateTable::empty());
+
+/// Current latency limit in microseconds
+static LATENCY_LIMIT_US: AtomicU32 = AtomicU32::new(LATENCY_UNLIMITED);
+
+/// Predicted idle time in microseconds (moving average)
+static PREDICTED_IDLE_US: AtomicU64 = AtomicU64::new(0);
+
+/// Monitored cache line for MWAIT
+static MONITOR_LINE: AtomicU64 = AtomicU64::new(0);
+
+/// Slot in the statistics arrays for `hlt` idle
+pub const HLT_SLOT: usize = MWAIT_MAX_CSTATES;
+
+/// Times each state was entered (indexed by table index, then `hlt`)
+static USAGE: [AtomicU64; MWAIT_MAX_CSTATES + 1] = [const { AtomicU64::new(0) }; MWAIT_MAX_CSTATES + 1];
+
+/// Time spent in each state in nanoseconds
+static RESIDENCY_NS: [AtomicU64; MWAIT_MAX_CSTATES + 1] = [const { AtomicU64::new(0) }; MWAIT_MAX_CSTATES + 1];
+
+/// Discover C-states and log them
+pub fn init() {
+    let features = cpu_features::get();
+    let table = match features.mwait {
+        Some(info) => CStateTable::from_mwait(&info),
+        None => CStateTable::empty(),
+    };
+    *CSTATES.lock() = table;
+
+    let mut out = LogWriter::new(Level::Info, module_path!());
+    let _ = write!(out, "[POWER] Idle: ");
+    if table.states().is_empty() {
+        let _ = write!(out, "hlt (no MWAIT)");
+    } else {
+        let _ = write!(out, "mwait");
+        for state in table.states() {
+            let _ = write!(out, " {}", state.name);
+        }
+    }
+    let _ = writeln!(out, ", {} MHz base, {} MHz max", base_mhz(), max_mhz());
+}
+
+/// Get the available C-states
+pub fn cstates() -> CStateTable {
+    *CSTATES.lock()
+}
+
+/// Set the idle exit-latency limit in microseconds
+///
+/// States with a longer exit latency are not used. Pass
+/// [`LATENCY_UNLIMITED`] to remove the limit.
+pub fn set_latency_limit_us(limit: u32) {
+    LATENCY_LIMIT_US.store(limit, Ordering::Relaxed);
+}
+
+/// Get the idle exit-latency limit in microseconds
+pub fn latency_limit_us() -> u32 {
+    LATENCY_LIMIT_US.load(Ordering::Relaxed)
+}
+
+/// Times a state was entered (`HLT_SLOT` for `hlt`)
+pub fn usage(slot: usize) -> u64 {
+    USAGE.get(slot).map_or(0, |u| u.load(Ordering::Relaxed))
+}
+
+/// Total time spent in a state in nanoseconds (`HLT_SLOT` for `hlt`)
+pub fn residency_ns(slot: usize) -> u64 {
+    RESIDENCY_NS.get(slot).map_or(0, |r| r.load(Ordering::Relaxed))
+}
+
+/// Idle the CPU until the next interrupt
+///
+/// Must be called with interrupts enabled; returns immediately otherwise.
+pub fn idle() {
+    if ops::x86_get_rflags() & (1 << 9) == 0 {
+        return;
+    }
+
+    let predicted = PREDICTED_IDLE_US.load(Ordering::Relaxed);
+    let limit = LATENCY_LIMIT_US.load(Ordering::Relaxed);
+
+    // Never spin on the lock from the idle path; use hlt if it is busy
+    let selected = CSTATES.try_lock().and_then(|table| {
+        select_state(table.states(), predicted, limit).map(|i| (i, table.states()[i].hint))
+    });
+
+    let start = tsc::tsc_ticks();
+    let slot = match selected {
+        Some((index, hint)) => {
+            unsafe {
+                ops::x86_monitor(&MONITOR_LINE as *const AtomicU64, 0, 0);
+                core::arch::asm!("mwait", in("eax") hint, in("ecx") 0u32, options(nomem, nostack));
+            }
+            index
+        }
+        None => {
+            ops::x86_idle();
+            HLT_SLOT
+        }
+    };
+    let elapsed_ns = tsc::tsc_to_ns(tsc::tsc_ticks().wrapping_sub(start));
+
+    USAGE[slot].fetch_add(1, Ordering::Relaxed);
+    RESIDENCY_NS[slot].fetch_add(elapsed_ns, Ordering::Relaxed);
+
+    // Exponential moving average with weight 1/8
+    let measured = elapsed_ns / 1000;
+    PREDICTED_IDLE_US.store((predicted * 7 + measured) / 8, Ordering::Relaxed);
+}
+
+// ============================================================================
+// Frequency
+// ============================================================================
+
+/// TSC frequency in MHz
+fn tsc_mhz() -> u32 {
+    (tsc::x86_tsc_frequency() / 1_000_000) as u32
+}
+
+/// Base frequency in MHz
+pub fn base_mhz() -> u32 {
+    match cpu_features::get().base_mhz {
+        0 => tsc_mhz(),
+        mhz => mhz,
+    }
+}
+
+/// Maximum (turbo) frequency in MHz
+pub fn max_mhz() -> u32 {
+    match cpu_features::get().max_mhz {
+        0 => base_mhz(),
+        mhz => mhz,
+    }
+}
+
+/// APERF / MPERF at the previous [`current_mhz`] call
+static LAST_PERF: SpinMutex<(u64, u64)> = SpinMutex::new((0, 0));
+
+/// Effective frequency in MHz since the previous call
+///
+/// Returns the base frequency if APERF/MPERF are unavailable or on the
+/// first call.
+pub fn current_mhz() -> u32 {
+    if !cpu_features::get().aperf_mperf {
+        return base_mhz();
+    }
+
+    let mut aperf = 0u64;
+    let mut mperf = 0u64;
+    let ok = unsafe {
+        ops::read_msr_safe(MSR_IA32_APERF, &mut aperf) && ops::read_msr_safe(MSR_IA32_MPERF, &mut mperf)
+    };
+    if !ok {
+        return base_mhz();
+    }
+
+    let mut last = LAST_PERF.lock();
+    let (last_aperf, last_mperf) = core::mem::replace(&mut *last, (aperf, mperf));
+    if last_mperf == 0 {
+        return base_mhz();
+    }
+    effective_mhz(base_mhz(), aperf.wrapping_sub(last_aperf), mperf.wrapping_sub(last_mperf))
+        .unwrap_or_else(base_mhz)
+}
+
+/// Scale `base_mhz` by an APERF / MPERF delta ratio
+pub fn effective_mhz(base_mhz: u32, aperf_delta: u64, mperf_delta: u64) -> Option<u32> {
+    if mperf_delta == 0 {
+        return None;
+    }
+    Some((base_mhz as u128 * aperf_delta as u128 / mperf_delta as u128) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> CStateTable {
+        // C1, C2 and C3 present
+        CStateTable::from_mwait(&MwaitInfo::from_leaf5(64, 64, 3, 0x0000_1120))
+    }
+
+    #[test]
+    fn test_from_mwait() {
+        let table = table();
+        let names: alloc::vec::Vec<_> = table.states().iter().map(|s| s.name).collect();
+        assert_eq!(names, ["C1", "C2", "C3"]);
+        assert_eq!(table.states()[0].hint, 0x00);
+        assert_eq!(table.states()[2].hint, 0x20);
+    }
+
+    #[test]
+    fn test_select_state() {
+        let table = table();
+        let states = table.states();
+        assert_eq!(select_state(states, 0, LATENCY_UNLIMITED), None);
+        assert_eq!(select_state(states, 50, LATENCY_UNLIMITED), Some(1));
+        assert_eq!(select_state(states, 10_000, LATENCY_UNLIMITED), Some(2));
+        // Latency limit caps the depth regardless of the prediction
+        assert_eq!(select_state(states, 10_000, 10), Some(1));
+        assert_eq!(select_state(&[], 10_000, LATENCY_UNLIMITED), None);
+    }
+
+    #[test]
+    fn test_effective_mhz() {
+        assert_eq!(effective_mhz(2000, 150, 100), Some(3000));
+        assert_eq!(effective_mhz(2000, 1, 0), None);
+    }
+}
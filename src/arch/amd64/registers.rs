@@ -34,6 +34,9 @@ pub mod msr {
 
     /// IA32_FMASK - System Call Flag Mask
     pub const IA32_FMASK: u32 = 0xC000_0084;
+
+    /// TSC auxiliary value, returned by RDTSCP and RDPID
+    pub const IA32_TSC_AUX: u32 = 0xC000_0103;
 }
 
 /// Control register definitions
@@ -48,8 +51,10 @@ pub mod cr {
     pub const CR0_PE: u64 = 1 << 0;   // Protected Mode Enable
 
     /// CR4 - Control Register 4
+    pub const CR4_TSD: u64 = 1 << 2;   // Time Stamp Disable (RDTSC/RDTSCP ring 0 only)
     pub const CR4_PSE: u64 = 1 << 4;   // Page Size Extension
     pub const CR4_PAE: u64 = 1 << 5;   // Physical Address Extension
+    pub const CR4_MCE: u64 = 1 << 6;   // Machine Check Enable
     pub const CR4_PGE: u64 = 1 << 7;   // Page Global Enable
     pub const CR4_OSFXSR: u64 = 1 << 9;  // OS FXSAVE/FXRSTOR Support
     pub const CR4_OSXMMEXCPT: u64 = 1 << 10;  // OS Exception Support
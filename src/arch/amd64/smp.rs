@@ -0,0 +1,144 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Application Processor Bring-Up
+//!
+//! The boot CPU (BSP) starts the other CPUs (APs) with [`init`]. It is
+//! off unless `smp` is on the kernel command line: a CPU that fails in
+//! the trampoline triple-faults and resets the machine, so it stays
+//! opt-in until it has seen more hardware.
+//!
+//! # Startup
+//!
+//! Every enabled Local APIC in the MADT other than the BSP's gets the
+//! next CPU number (up to [`MAX_CPUS`]) and a kernel stack, and is
+//! started through [`bootstrap16`](super::bootstrap16). Once in long
+//! mode it loads its own GDT, TSS, GS entry area and the shared IDT, then
+//! [`ap_main`]:
+//!
+//! 1. Enables its Local APIC
+//! 2. Marks itself online ([`affinity::cpu_online`]), so interrupts and
+//!    IPIs can be routed to it
+//! 3. Starts its Local APIC timer at [`IDLE_TICK_VECTOR`]
+//! 4. Enters its idle loop ([`crate::sched::idle`]) on the boot stack,
+//!    which stays its idle stack
+//!
+//! APs start one at a time: the bootstrap area holds a single info block.
+//!
+//! # Idle Tick
+//!
+//! The AP timer only wakes the CPU (the handler just acknowledges it):
+//! an idle AP looks for work whenever it wakes. The same vector is used
+//! as an IPI to wake an idle AP at once ([`crate::sched::idle::kick`]).
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use super::{apic, idt};
+use crate::interrupt::affinity::{self, MAX_CPUS};
+use crate::{kerror, kinfo, kwarn};
+
+/// Vector of the AP timer and of wake-up IPIs
+pub const IDLE_TICK_VECTOR: u8 = 0xF0;
+
+/// AP timer initial count (same period as the BSP's timer)
+const IDLE_TICK_COUNT: u32 = 10_000_000;
+
+/// Page tables the APs start on (the BSP's at [`init`])
+static KERNEL_CR3: AtomicU64 = AtomicU64::new(0);
+
+/// Page tables kernel-only code on an AP runs on
+///
+/// An AP leaving a process for its idle loop switches to these before
+/// the process can be reaped.
+pub fn kernel_cr3() -> u64 {
+    KERNEL_CR3.load(Ordering::Relaxed)
+}
+
+/// Start the application processors
+///
+/// Does nothing without `smp` on the command line. Call on the BSP once
+/// the IDT, Local APIC and timer are set up.
+///
+/// # Returns
+///
+/// The number of APs started
+pub fn init() -> usize {
+    if !crate::cmdline::has_flag("smp") {
+        return 0;
+    }
+
+    let madt = match crate::acpi::find_rsdp().and_then(crate::acpi::find_and_parse_madt) {
+        Some(madt) => madt,
+        None => {
+            kinfo!("[SMP] No MADT, staying on the boot CPU");
+            return 0;
+        }
+    };
+
+    let cr3 = unsafe { super::registers::x86_get_cr3() };
+    KERNEL_CR3.store(cr3, Ordering::Relaxed);
+    unsafe {
+        idt::idt_set_gate(IDLE_TICK_VECTOR, idle_tick as *const () as u64, 0x08, idt::IDT_INTERRUPT_GATE);
+    }
+    super::tlb::init();
+
+    let bsp = apic::apic_local_id();
+    let mut next_cpu = 1;
+    for entry in &madt.local_apics[..madt.local_apic_count] {
+        let (apic_id, flags) = (entry.apic_id, entry.flags);
+        if flags & 1 == 0 || apic_id == bsp {
+            continue;
+        }
+        if next_cpu >= MAX_CPUS {
+            kwarn!("[SMP] More CPUs than MAX_CPUS, ignoring the rest");
+            break;
+        }
+
+        let stack_top = match crate::process::table::alloc_kernel_stack() {
+            Ok(top) => top,
+            Err(_) => break,
+        };
+        let started = unsafe {
+            super::bootstrap16::start_secondary_cpu(
+                next_cpu as u32,
+                apic_id as u32,
+                super::bootstrap16::bootstrap16 as *const () as usize,
+                stack_top as usize,
+                cr3,
+            )
+        };
+        if started {
+            next_cpu += 1;
+        } else {
+            // Its stack is not freed: the CPU may still call in late
+            kerror!("[SMP] An AP did not start");
+        }
+    }
+
+    kinfo!("[SMP] {} CPUs running", next_cpu);
+    next_cpu - 1
+}
+
+/// AP initialisation after the trampoline; see the module docs
+///
+/// # Arguments
+///
+/// * `cpu` - This CPU's number
+/// * `apic_id` - This CPU's Local APIC ID
+/// * `stack_top` - Top of the boot stack, kept as the idle stack
+pub fn ap_main(cpu: usize, apic_id: u8, stack_top: u64) -> ! {
+    apic::apic_local_enable();
+    let _ = affinity::cpu_online(cpu, apic_id);
+    apic::apic_timer_periodic(IDLE_TICK_VECTOR, IDLE_TICK_COUNT);
+    kinfo!("[SMP] CPU {} online", cpu);
+
+    crate::sched::idle::run(cpu, stack_top)
+}
+
+// Only acknowledges the interrupt, so GS is left as it is.
+
+extern "x86-interrupt" fn idle_tick(_frame: idt::X86Iframe) {
+    apic::apic_send_eoi(0);
+}
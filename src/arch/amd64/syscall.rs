@@ -50,17 +50,15 @@ pub unsafe fn x86_syscall_init() {
 
     // IA32_LSTAR - IA32-e Mode System Call Target Address
     // This is the RIP where syscalls enter in 64-bit mode
-    // Set to the architecture-specific syscall entry point
-    extern "C" {
-        fn x86_64_syscall_entry();
-    }
-    registers::write_msr(msr::IA32_LSTAR, x86_64_syscall_entry as u64);
+    // Set to the assembly stub that switches GS and stacks (see entry.rs)
+    registers::write_msr(msr::IA32_LSTAR, super::entry::x86_64_syscall_stub as *const () as u64);
 
     // IA32_FMASK - System Call Flag Mask
     // Masks RFLAGS bits that are cleared on syscall entry
-    // We mask IF (interrupt enable) to disable interrupts during syscall
-    // and other flags to maintain consistent kernel state
-    const FMASK_VALUE: u64 = rflags::IF | rflags::TF | rflags::DF;
+    // We mask IF (interrupt enable) so nothing interrupts the stub before
+    // it is on the kernel stack, TF so single-stepping stops at the
+    // boundary, and DF/AC so user values can't affect kernel code
+    const FMASK_VALUE: u64 = rflags::IF | rflags::TF | rflags::DF | rflags::AC;
     registers::write_msr(msr::IA32_FMASK, FMASK_VALUE);
 
     // IA32_EFER - Enable SCE (System Call Extensions)
@@ -75,8 +73,8 @@ pub unsafe fn x86_syscall_init() {
 
 /// AMD64 syscall entry point
 ///
-/// This function is called from the syscall instruction in user space.
-/// It properly saves/restores registers and calls the syscall dispatcher.
+/// Called by [`x86_64_syscall_stub`](super::entry::x86_64_syscall_stub)
+/// on the kernel stack with the kernel GS active.
 ///
 /// # Safety
 ///
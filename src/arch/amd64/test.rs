@@ -15,6 +15,8 @@
 
 use core::sync::atomic::{AtomicU64, Ordering};
 
+use crate::{kerror, kinfo, kwarn};
+
 /// Test tick counter (incremented by timer interrupt)
 static TIMER_TICKS: AtomicU64 = AtomicU64::new(0);
 
@@ -24,35 +26,6 @@ pub const TIMER_VECTOR: u8 = 32;
 /// Keyboard interrupt vector (typically 33)
 pub const KEYBOARD_VECTOR: u8 = 33;
 
-/// ============================================================================
-/// Serial Output (for debugging without full console)
-/// ============================================================================
-
-/// QEMU x86 debug console port
-const QEMU_DEBUGCON_PORT: u16 = 0xE9;
-
-/// Write a byte to QEMU's debug console
-///
-/// This is the simplest way to output debug messages in QEMU.
-/// Messages appear in the console when using `-debugcon` flag.
-fn qemu_debugcon_write_byte(b: u8) {
-    unsafe {
-        core::arch::asm!(
-            "out dx, al",
-            in("dx") QEMU_DEBUGCON_PORT,
-            in("al") b,
-            options(nostack, nomem)
-        );
-    }
-}
-
-/// Write a string to QEMU's debug console
-pub fn qemu_print(s: &str) {
-    for byte in s.bytes() {
-        qemu_debugcon_write_byte(byte);
-    }
-}
-
 /// ============================================================================
 /// Timer Interrupt Handler
 /// ============================================================================
@@ -66,20 +39,13 @@ extern "x86-interrupt" fn timer_handler(_frame: &mut super::idt::X86Iframe) {
 
     // Print every 10th tick to avoid spam
     if ticks % 10 == 0 {
-        qemu_print("[TICK ");
-        print_digit((ticks / 10) % 10);
-        qemu_print("]\n");
+        kinfo!("[TICK {}]", ticks / 10);
     }
 
     // Send EOI to Local APIC
     super::apic::apic_send_eoi(TIMER_VECTOR as u32);
 }
 
-/// Print a single digit
-fn print_digit(d: u64) {
-    qemu_debugcon_write_byte(b'0' + (d as u8));
-}
-
 /// ============================================================================
 /// Test Functions
 /// ============================================================================
@@ -93,30 +59,31 @@ fn print_digit(d: u64) {
 /// 4. Configure timer interrupt
 /// 5. Enable interrupts and wait for timer ticks
 ///
+/// # Returns
+///
+/// true if at least 10 timer ticks were received
+///
 /// # Safety
 ///
 /// This function modifies critical system state (GDT, IDT, APIC).
 /// It should only be called once during kernel initialization.
-pub fn test_interrupt_system() {
-    qemu_print("=== Rustux Interrupt System Test ===\n");
-    qemu_print("Testing migrated boot infrastructure\n\n");
+pub fn test_interrupt_system() -> bool {
+    kinfo!("=== Rustux Interrupt System Test ===");
+    kinfo!("Testing migrated boot infrastructure");
 
     // Step 1: Setup GDT
-    qemu_print("[1/5] Setting up GDT... ");
     unsafe {
         super::descriptor::gdt_setup();
     }
-    qemu_print("OK\n");
+    kinfo!("[1/5] Setting up GDT... OK");
 
     // Step 2: Setup IDT
-    qemu_print("[2/5] Setting up IDT... ");
     unsafe {
         super::descriptor::idt_setup_readonly();
     }
-    qemu_print("OK\n");
+    kinfo!("[2/5] Setting up IDT... OK");
 
     // Step 3: Install timer handler
-    qemu_print("[3/5] Installing timer handler... ");
     unsafe {
         super::idt::idt_set_gate(
             TIMER_VECTOR,
@@ -125,22 +92,20 @@ pub fn test_interrupt_system() {
             0x8E, // Interrupt gate (present, DPL=0)
         );
     }
-    qemu_print("OK\n");
+    kinfo!("[3/5] Installing timer handler... OK");
 
     // Step 4: Initialize APIC
-    qemu_print("[4/5] Initializing APIC... ");
     super::apic::apic_local_init();
-    qemu_print("OK\n");
+    kinfo!("[4/5] Initializing APIC... OK");
 
     // Step 5: Configure timer
-    qemu_print("[5/5] Configuring timer... ");
     unsafe {
         configure_lapic_timer();
     }
-    qemu_print("OK\n");
+    kinfo!("[5/5] Configuring timer... OK");
 
-    qemu_print("\nInterrupt system configured!\n");
-    qemu_print("Enabling interrupts and waiting for timer ticks...\n\n");
+    kinfo!("Interrupt system configured!");
+    kinfo!("Enabling interrupts and waiting for timer ticks...");
 
     // Enable interrupts
     unsafe {
@@ -148,7 +113,7 @@ pub fn test_interrupt_system() {
     }
 
     // Wait for timer interrupts
-    qemu_print("Waiting for timer interrupts (100 ticks max)...\n");
+    kinfo!("Waiting for timer interrupts (100 ticks max)...");
 
     for i in 0..100 {
         unsafe {
@@ -157,22 +122,19 @@ pub fn test_interrupt_system() {
 
         // Check if we got at least 10 ticks
         if TIMER_TICKS.load(Ordering::Relaxed) >= 10 {
-            qemu_print("\n=== TEST PASSED ===\n");
-            qemu_print("Received ");
-            print_decimal(TIMER_TICKS.load(Ordering::Relaxed));
-            qemu_print(" timer ticks successfully!\n");
-            return;
+            kinfo!("=== TEST PASSED ===");
+            kinfo!("Received {} timer ticks successfully!", TIMER_TICKS.load(Ordering::Relaxed));
+            return true;
         }
     }
 
-    qemu_print("\n=== TEST WARNING ===\n");
-    qemu_print("Only received ");
-    print_decimal(TIMER_TICKS.load(Ordering::Relaxed));
-    qemu_print(" ticks (expected 10+)\n");
-    qemu_print("This could mean:\n");
-    qemu_print("  - Timer not configured correctly\n");
-    qemu_print("  - APIC not enabled\n");
-    qemu_print("  - Running on hardware without APIC\n");
+    kwarn!("=== TEST WARNING ===");
+    kwarn!("Only received {} ticks (expected 10+)", TIMER_TICKS.load(Ordering::Relaxed));
+    kwarn!("This could mean:");
+    kwarn!("  - Timer not configured correctly");
+    kwarn!("  - APIC not enabled");
+    kwarn!("  - Running on hardware without APIC");
+    false
 }
 
 /// Configure the Local APIC timer
@@ -208,26 +170,104 @@ unsafe fn configure_lapic_timer() {
     timer_initial.write_volatile(1_000_000);
 }
 
-/// Print a decimal number to QEMU debug console
-fn print_decimal(mut n: u64) {
-    if n == 0 {
-        qemu_debugcon_write_byte(b'0');
-        return;
+/// ============================================================================
+/// Entry GS Discipline Test
+/// ============================================================================
+
+/// Outer test vector (an interrupt arriving at an entry)
+const GS_OUTER_VECTOR: u8 = 0x81;
+
+/// Nested test vector (an NMI arriving inside the outer handler)
+const GS_NESTED_VECTOR: u8 = 0x82;
+
+/// Bits: 0 = outer saw kernel GS, 1 = outer swapped, 2 = nested saw
+/// kernel GS, 3 = nested swapped, 4 = syscall dispatch returned
+static GS_OBSERVED: AtomicU64 = AtomicU64::new(0);
+
+extern "x86-interrupt" fn gs_outer_handler(_frame: &mut super::idt::X86Iframe) {
+    let gs = unsafe { super::entry::GsGuard::paranoid() };
+    let mut seen = super::entry::kernel_gs_active() as u64 | (gs.swapped() as u64) << 1;
+
+    unsafe {
+        core::arch::asm!("int {}", const GS_NESTED_VECTOR, options(nomem, nostack));
+    }
+
+    // A syscall dispatched from interrupt context must come back with GS
+    // untouched (an unknown number fails without side effects)
+    let ret = crate::syscall::syscall_dispatch(crate::syscall::SyscallArgs::new(0xFFFF, [0; 6]));
+    if ret < 0 && super::entry::kernel_gs_active() {
+        seen |= 1 << 4;
     }
+    GS_OBSERVED.fetch_or(seen, Ordering::Relaxed);
+}
+
+extern "x86-interrupt" fn gs_nested_handler(_frame: &mut super::idt::X86Iframe) {
+    let gs = unsafe { super::entry::GsGuard::paranoid() };
+    let seen = (super::entry::kernel_gs_active() as u64) << 2 | (gs.swapped() as u64) << 3;
+    GS_OBSERVED.fetch_or(seen, Ordering::Relaxed);
+}
+
+/// Raise the outer test vector and return what the handlers saw
+fn gs_round_trip() -> u64 {
+    GS_OBSERVED.store(0, Ordering::Relaxed);
+    unsafe {
+        core::arch::asm!("int {}", const GS_OUTER_VECTOR, options(nomem, nostack));
+    }
+    GS_OBSERVED.load(Ordering::Relaxed)
+}
 
-    let mut buf = [0u8; 20];
-    let mut i = 0;
+/// Test swapgs discipline on nested entries
+///
+/// Raises software interrupts with the kernel GS active and with a user
+/// GS active (as if the interrupt had arrived in the window between
+/// `syscall` and its `swapgs`), each nesting a second entry and a syscall
+/// dispatch. Every handler must run with the kernel GS, only the outer
+/// entry from a user GS may swap, and GS must be restored on exit.
+pub fn test_entry_gs_discipline() -> bool {
+    kinfo!("=== Entry GS Discipline Test ===");
 
-    while n > 0 {
-        buf[i] = b'0' + ((n % 10) as u8);
-        n /= 10;
-        i += 1;
+    unsafe {
+        super::entry::init_cpu(0);
+        super::idt::idt_set_gate(GS_OUTER_VECTOR, gs_outer_handler as *const () as u64, 0x08, 0x8E);
+        super::idt::idt_set_gate(GS_NESTED_VECTOR, gs_nested_handler as *const () as u64, 0x08, 0x8E);
     }
 
-    while i > 0 {
-        i -= 1;
-        qemu_debugcon_write_byte(buf[i]);
+    let mut passed = true;
+    let mut check = |name: &str, ok: bool| {
+        if ok {
+            kinfo!("  PASS {}", name);
+        } else {
+            kerror!("  FAIL {}", name);
+        }
+        passed &= ok;
+    };
+
+    // From kernel GS: nobody swaps
+    let seen = gs_round_trip();
+    check("kernel GS: handlers see kernel GS", seen & 0b10101 == 0b10101);
+    check("kernel GS: no swap", seen & 0b1010 == 0);
+    check("kernel GS: restored", super::entry::kernel_gs_active());
+
+    // From user GS: only the outer entry swaps, and swaps back
+    unsafe { core::arch::asm!("swapgs", options(nomem, nostack)) };
+    let user_gs_before = !super::entry::kernel_gs_active();
+    let seen = gs_round_trip();
+    let user_gs_after = !super::entry::kernel_gs_active();
+    unsafe { core::arch::asm!("swapgs", options(nomem, nostack)) };
+    check("user GS: handlers see kernel GS", seen & 0b10101 == 0b10101);
+    check("user GS: outer swaps, nested doesn't", seen & 0b1010 == 0b0010);
+    check("user GS: restored", user_gs_before && user_gs_after);
+
+    // A kernel GS on a user-mode entry is an imbalance
+    check("imbalance detected", !super::entry::entry_state_ok(true));
+    check("kernel entry accepted", super::entry::entry_state_ok(false));
+
+    if passed {
+        kinfo!("=== TEST PASSED ===");
+    } else {
+        kerror!("=== TEST FAILED ===");
     }
+    passed
 }
 
 /// ============================================================================
@@ -238,7 +278,7 @@ fn print_decimal(mut n: u64) {
 ///
 /// This is called by the IDT entry for vector 33 (KEYBOARD_VECTOR).
 extern "x86-interrupt" fn keyboard_handler(_frame: &mut super::idt::X86Iframe) {
-    qemu_print("[KEYBOARD_PRESS]\n");
+    kinfo!("[KEYBOARD_PRESS]");
 
     // Read from keyboard data port to acknowledge
     unsafe {
@@ -261,8 +301,8 @@ extern "x86-interrupt" fn keyboard_handler(_frame: &mut super::idt::X86Iframe) {
 /// This enables keyboard interrupt (IRQ1) and waits for keypresses.
 /// Requires the IOAPIC to be configured.
 pub fn test_keyboard_interrupt() {
-    qemu_print("=== Keyboard Interrupt Test ===\n");
-    qemu_print("Press any key on the keyboard...\n\n");
+    kinfo!("=== Keyboard Interrupt Test ===");
+    kinfo!("Press any key on the keyboard...");
 
     // Install keyboard handler
     unsafe {
@@ -274,8 +314,8 @@ pub fn test_keyboard_interrupt() {
         );
     }
 
-    qemu_print("Keyboard handler installed.\n");
-    qemu_print("Press Ctrl+C to exit (if running in QEMU)\n");
+    kinfo!("Keyboard handler installed.");
+    kinfo!("Press Ctrl+C to exit (if running in QEMU)");
 
     loop {
         unsafe {
@@ -0,0 +1,121 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TLB Shootdown
+//!
+//! A CPU that unmaps a kernel page flushes its own TLB, but the other
+//! CPUs may still hold the old translation. [`shootdown`] sends every
+//! other online CPU an IPI at [`SHOOTDOWN_VECTOR`], on which it reloads
+//! CR3, and returns a [`Ticket`] that is done once all of them have.
+//! Kernel mappings are not global, so the reload drops them all; a
+//! shootdown is rare enough that flushing more than the unmapped range
+//! costs nothing worth tracking.
+//!
+//! The syscall gate runs with interrupts disabled, so a CPU spinning on a
+//! lock the initiator holds would never take the IPI. The VMM therefore
+//! never waits: it keeps what it unmapped, the pages and the address
+//! range, until [`Ticket::is_done`] (see [`crate::mm::vmm`]). Callers that
+//! must not return before the flush, like the copy-on-write downgrade in
+//! [`crate::process::vmar::write_protect_vmo`], drop every lock and then
+//! [`Ticket::wait`].
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use super::{apic, idt, registers};
+use crate::interrupt::affinity::{self, MAX_CPUS};
+
+/// Vector of the shootdown IPI
+pub const SHOOTDOWN_VECTOR: u8 = 0xF1;
+
+/// Number of the latest shootdown
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Latest shootdown each CPU has flushed for
+static FLUSHED: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+/// A shootdown in progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ticket {
+    generation: u64,
+    /// CPUs that were sent the IPI
+    cpus: u64,
+}
+
+impl Ticket {
+    /// A ticket that needs no other CPU
+    pub const DONE: Self = Self { generation: 0, cpus: 0 };
+
+    /// Check if every CPU sent the IPI has flushed its TLB
+    pub fn is_done(&self) -> bool {
+        (0..MAX_CPUS)
+            .filter(|cpu| self.cpus & (1 << cpu) != 0)
+            .all(|cpu| FLUSHED[cpu].load(Ordering::Acquire) >= self.generation)
+    }
+
+    /// Spin until every CPU sent the IPI has flushed its TLB
+    ///
+    /// Call with no lock held. Interrupts may be disabled: this CPU does
+    /// the flushes asked of it while it waits, so CPUs waiting on each
+    /// other all finish.
+    pub fn wait(&self) {
+        while !self.is_done() {
+            flush_if_asked();
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Install the IPI handler
+///
+/// Call on the BSP before the APs are started.
+pub fn init() {
+    unsafe {
+        idt::idt_set_gate(SHOOTDOWN_VECTOR, shootdown_ipi as *const () as u64, 0x08, idt::IDT_INTERRUPT_GATE);
+    }
+}
+
+/// Have every other online CPU flush its TLB
+///
+/// Call after clearing the page table entries and flushing them locally.
+/// With a single CPU online the returned ticket is already done.
+pub fn shootdown() -> Ticket {
+    let me = affinity::current_cpu();
+    let targets: u64 = (0..MAX_CPUS)
+        .filter(|&cpu| cpu != me && affinity::is_cpu_online(cpu))
+        .fold(0, |mask, cpu| mask | 1 << cpu);
+    if targets == 0 {
+        return Ticket::DONE;
+    }
+
+    // Entries cleared before this are dropped by any flush that sees it
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let mut cpus = 0;
+    for cpu in (0..MAX_CPUS).filter(|cpu| targets & (1 << cpu) != 0) {
+        if let Some(apic_id) = affinity::apic_id(cpu) {
+            apic::apic_send_ipi(apic_id, SHOOTDOWN_VECTOR);
+            cpus |= 1 << cpu;
+        }
+    }
+    Ticket { generation, cpus }
+}
+
+/// Flush this CPU's TLB if a shootdown newer than its last flush was sent
+fn flush_if_asked() {
+    let generation = GENERATION.load(Ordering::SeqCst);
+    let flushed = &FLUSHED[affinity::current_cpu()];
+    if flushed.load(Ordering::Relaxed) < generation {
+        unsafe { registers::x86_set_cr3(registers::x86_get_cr3()) };
+        flushed.fetch_max(generation, Ordering::Release);
+    }
+}
+
+// Touches no per-CPU state through GS, so GS is left as it is.
+
+extern "x86-interrupt" fn shootdown_ipi(_frame: idt::X86Iframe) {
+    let generation = GENERATION.load(Ordering::SeqCst);
+    unsafe { registers::x86_set_cr3(registers::x86_get_cr3()) };
+    FLUSHED[affinity::current_cpu()].fetch_max(generation, Ordering::Release);
+    apic::apic_send_eoi(0);
+}
@@ -10,8 +10,36 @@
 //!
 //! The TSC is a 64-bit register that counts processor cycles since reset.
 //! It provides a high-resolution timestamp for performance measurement.
+//!
+//! # User Access
+//!
+//! [`cpu_init`] sets up each CPU for userspace readers: `IA32_TSC_AUX`
+//! holds the CPU number, so `rdtscp` (and `rdpid`) tell the reader which
+//! CPU the value came from, and `CR4.TSD` follows the
+//! `time.user_counter` policy ([`crate::time::user_counter_allowed`]).
+//! Readings from different CPUs are only comparable when the TSC is
+//! invariant ([`is_invariant`]); the vDSO page reports this to userspace.
+//!
+//! # Calibration
+//!
+//! The TSC rate is not architectural. [`x86_calibrate_tsc`] measures it
+//! at boot against a timer of known rate, [`CALIBRATION_RUNS`] times over
+//! [`CALIBRATION_MS`]:
+//!
+//! - The HPET main counter ([`super::hpet`]), if there is one. Both
+//!   counters are read at the start and end of each run, so a run is only
+//!   off if an SMI or VM exit lands between the two reads; the median run
+//!   is kept.
+//! - Otherwise PIT channel 2, whose input clock is a fixed [`PIT_HZ`]:
+//!   the TSC ticks over a PIT countdown are counted, and the shortest run
+//!   is kept (an SMI or VM exit only ever makes a run longer).
+//!
+//! Without either the frequency stays at a 2 GHz default.
+//! [`TscClockSource`] then serves as the kernel's clock source
+//! ([`crate::time::clocksource`]).
 
 use core::sync::atomic::{AtomicU64, Ordering};
+use crate::time::clocksource::ClockSource;
 
 /// Cached TSC frequency in Hz
 static mut TSC_FREQUENCY: AtomicU64 = AtomicU64::new(0);
@@ -19,6 +47,56 @@ static mut TSC_FREQUENCY: AtomicU64 = AtomicU64::new(0);
 /// Default TSC frequency (2 GHz) when not calibrated
 const DEFAULT_TSC_FREQUENCY: u64 = 2_000_000_000;
 
+/// PIT input clock in Hz
+pub const PIT_HZ: u64 = 1_193_182;
+
+/// Length of one calibration run
+pub const CALIBRATION_MS: u64 = 10;
+
+/// Calibration runs; the shortest wins
+pub const CALIBRATION_RUNS: usize = 3;
+
+/// Plausible TSC frequencies; a measurement outside is discarded
+const PLAUSIBLE_HZ: core::ops::RangeInclusive<u64> = 100_000_000..=20_000_000_000;
+
+/// Polls of the PIT output before giving up on it
+const PIT_MAX_POLLS: u64 = 10_000_000;
+
+/// Polls of the HPET counter before giving up on a run
+const HPET_MAX_POLLS: u64 = 10_000_000;
+
+/// System control port B: PIT channel 2 gate and output
+const PORT_SYSTEM_CONTROL_B: u16 = 0x61;
+
+/// Port B: channel 2 gate
+const PORT_B_GATE2: u8 = 1 << 0;
+
+/// Port B: PC speaker data enable
+const PORT_B_SPEAKER: u8 = 1 << 1;
+
+/// Port B: channel 2 output
+const PORT_B_OUT2: u8 = 1 << 5;
+
+/// The TSC as the kernel's clock source
+pub struct TscClockSource;
+
+impl ClockSource for TscClockSource {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn read(&self) -> u64 {
+        tsc_ticks()
+    }
+
+    fn frequency(&self) -> u64 {
+        x86_tsc_frequency()
+    }
+}
+
+/// The TSC clock source
+pub static TSC_CLOCKSOURCE: TscClockSource = TscClockSource;
+
 /// Read the Time Stamp Counter
 ///
 /// # Safety
@@ -59,6 +137,69 @@ pub unsafe fn rdtsc_serialized() -> u64 {
     ((high as u64) << 32) | (low as u64)
 }
 
+/// Read the Time Stamp Counter and `IA32_TSC_AUX`
+///
+/// Returns the TSC and the number of the CPU it was read on (see
+/// [`cpu_init`]). RDTSCP waits for earlier instructions to complete.
+///
+/// # Safety
+///
+/// RDTSCP must be supported ([`has_rdtscp`]).
+#[inline]
+pub unsafe fn rdtscp() -> (u64, u32) {
+    let mut high: u32;
+    let mut low: u32;
+    let mut aux: u32;
+    core::arch::asm!(
+        "rdtscp",
+        out("eax") low,
+        out("edx") high,
+        out("ecx") aux,
+        options(nomem, nostack, preserves_flags)
+    );
+    (((high as u64) << 32) | (low as u64), aux)
+}
+
+/// Whether the CPU supports RDTSCP
+pub fn has_rdtscp() -> bool {
+    super::cpu_features::get().has_flag("rdtscp")
+}
+
+/// Whether the TSC is invariant
+///
+/// An invariant TSC ticks at a constant rate in every P-, C- and T-state,
+/// so it measures wall time and stays in step across CPUs.
+pub fn is_invariant() -> bool {
+    use super::cpu_features::cpuid;
+
+    // Advanced power management leaf, EDX bit 8
+    cpuid(0x8000_0000, 0).eax >= 0x8000_0007 && cpuid(0x8000_0007, 0).edx & (1 << 8) != 0
+}
+
+/// Set up user access to the TSC on this CPU
+///
+/// Stores `cpu` in `IA32_TSC_AUX` when RDTSCP is supported, and sets
+/// `CR4.TSD` when the user counter policy denies access, clearing it
+/// otherwise.
+///
+/// # Safety
+///
+/// Must run on the CPU numbered `cpu`, in ring 0.
+pub unsafe fn cpu_init(cpu: usize) {
+    use super::registers::{cr::CR4_TSD, msr::IA32_TSC_AUX, write_msr, x86_get_cr4, x86_set_cr4};
+
+    if has_rdtscp() {
+        write_msr(IA32_TSC_AUX, cpu as u64);
+    }
+
+    let cr4 = x86_get_cr4();
+    if crate::time::user_counter_allowed() {
+        x86_set_cr4(cr4 & !CR4_TSD);
+    } else {
+        x86_set_cr4(cr4 | CR4_TSD);
+    }
+}
+
 /// Get the TSC frequency in Hz
 ///
 /// Returns the cached TSC frequency if available, otherwise
@@ -87,23 +228,105 @@ pub unsafe fn x86_set_tsc_frequency(freq: u64) {
     TSC_FREQUENCY.store(freq, Ordering::Release);
 }
 
-/// Calibrate the TSC frequency
+/// Calibrate the TSC frequency against the HPET or, without one, the PIT
 ///
-/// Attempts to calibrate the TSC frequency using a known time source
-/// (such as the PIT or HPET). Returns the calibrated frequency in Hz.
+/// Sets the frequency [`x86_tsc_frequency`] reports. Takes about
+/// `CALIBRATION_RUNS * CALIBRATION_MS` milliseconds.
 ///
 /// # Returns
 ///
-/// The calibrated TSC frequency in Hz, or the default frequency if
-/// calibration fails.
-pub fn x86_calibrate_tsc() -> u64 {
-    // TODO: Implement proper TSC calibration using PIT/HPET/ACPI
-    // For now, return a default frequency
-    let default_freq = DEFAULT_TSC_FREQUENCY;
-    unsafe {
-        x86_set_tsc_frequency(default_freq);
+/// The measured frequency in Hz, or `None` if there is neither a usable
+/// HPET nor PIT (the default frequency is kept)
+///
+/// # Safety
+///
+/// Boot CPU only, with interrupts disabled, after [`super::hpet::init`]:
+/// it may reprogram PIT channel 2, and any delay inflates a PIT
+/// measurement.
+pub unsafe fn x86_calibrate_tsc() -> Option<u64> {
+    let freq = calibrate_hpet()
+        .filter(|freq| PLAUSIBLE_HZ.contains(freq))
+        .or_else(|| calibrate_pit().filter(|freq| PLAUSIBLE_HZ.contains(freq)))?;
+    x86_set_tsc_frequency(freq);
+    Some(freq)
+}
+
+/// Measure the TSC over PIT countdowns, keeping the shortest run
+unsafe fn calibrate_pit() -> Option<u64> {
+    let latch = PIT_HZ * CALIBRATION_MS / 1000;
+    let best = (0..CALIBRATION_RUNS).filter_map(|_| pit_countdown(latch as u16)).min()?;
+    Some(frequency_from_pit(best, latch))
+}
+
+/// Measure the TSC against the HPET main counter, keeping the median run
+unsafe fn calibrate_hpet() -> Option<u64> {
+    use super::hpet;
+
+    let hpet_hz = hpet::frequency()?;
+    let span = hpet_hz * CALIBRATION_MS / 1000;
+    let mut runs = [0u64; CALIBRATION_RUNS];
+    for run in runs.iter_mut() {
+        let tsc_start = rdtsc_serialized();
+        let hpet_start = hpet::counter();
+        let mut hpet_ticks = 0;
+        let mut polls = 0;
+        while hpet_ticks < span && polls < HPET_MAX_POLLS {
+            hpet_ticks = hpet::counter_delta(hpet_start, hpet::counter());
+            polls += 1;
+        }
+        let tsc_ticks = rdtsc_serialized().wrapping_sub(tsc_start);
+        if hpet_ticks < span {
+            return None;
+        }
+        *run = frequency_from_reference(tsc_ticks, hpet_ticks, hpet_hz);
     }
-    default_freq
+    runs.sort_unstable();
+    Some(runs[CALIBRATION_RUNS / 2])
+}
+
+/// TSC frequency from the ticks counted while a `ref_hz` reference
+/// counted `ref_ticks`
+pub const fn frequency_from_reference(tsc_ticks: u64, ref_ticks: u64, ref_hz: u64) -> u64 {
+    if ref_ticks == 0 {
+        return 0;
+    }
+    ((tsc_ticks as u128 * ref_hz as u128) / ref_ticks as u128) as u64
+}
+
+/// TSC frequency from the ticks counted over `latch` PIT periods
+pub const fn frequency_from_pit(tsc_ticks: u64, latch: u64) -> u64 {
+    ((tsc_ticks as u128 * PIT_HZ as u128) / latch as u128) as u64
+}
+
+/// Count TSC ticks while PIT channel 2 counts down from `latch`
+///
+/// Channel 2 runs in mode 0 (interrupt on terminal count) with the
+/// speaker off; its output, read back through port B, rises when the
+/// count reaches zero.
+///
+/// # Returns
+///
+/// The TSC ticks elapsed, or `None` if the output never rose
+unsafe fn pit_countdown(latch: u16) -> Option<u64> {
+    use super::ioport::{inb, outb, pit};
+
+    let saved = inb(PORT_SYSTEM_CONTROL_B);
+    outb(PORT_SYSTEM_CONTROL_B, (saved & !PORT_B_SPEAKER) | PORT_B_GATE2);
+
+    // Channel 2, low then high byte, mode 0, binary
+    outb(pit::MODE, 0b1011_0000);
+    outb(pit::CHANNEL2, latch as u8);
+    outb(pit::CHANNEL2, (latch >> 8) as u8);
+
+    let start = rdtsc_serialized();
+    let mut polls = 0;
+    while inb(PORT_SYSTEM_CONTROL_B) & PORT_B_OUT2 == 0 && polls < PIT_MAX_POLLS {
+        polls += 1;
+    }
+    let end = rdtsc_serialized();
+
+    outb(PORT_SYSTEM_CONTROL_B, saved);
+    (polls < PIT_MAX_POLLS).then(|| end.wrapping_sub(start))
 }
 
 /// Store the TSC adjustment for suspend/resume
@@ -230,6 +453,28 @@ mod tests {
         assert!(freq > 0);
     }
 
+    #[test]
+    fn test_frequency_from_pit() {
+        let latch = PIT_HZ * CALIBRATION_MS / 1000;
+        // 3 GHz over the countdown (latch / PIT_HZ seconds)
+        let ticks = 3_000_000_000 * latch / PIT_HZ;
+        let freq = frequency_from_pit(ticks, latch);
+        assert!(freq.abs_diff(3_000_000_000) < 1_000_000);
+        assert!(PLAUSIBLE_HZ.contains(&freq));
+
+        // No PIT: the output reads high at once
+        assert!(!PLAUSIBLE_HZ.contains(&frequency_from_pit(200, latch)));
+    }
+
+    #[test]
+    fn test_frequency_from_reference() {
+        // 10 ms of a 100 MHz HPET while a 2.5 GHz TSC ran
+        assert_eq!(frequency_from_reference(25_000_000, 1_000_000, 100_000_000), 2_500_000_000);
+        // Slightly past the span: the reference count scales it back
+        assert_eq!(frequency_from_reference(25_002_500, 1_000_100, 100_000_000), 2_500_000_000);
+        assert_eq!(frequency_from_reference(1, 0, 100_000_000), 0);
+    }
+
     #[test]
     fn test_tsc_conversion() {
         // Test round-trip conversion
@@ -13,11 +13,8 @@
 
 use core::arch::asm;
 
-// External framebuffer color functions (defined in main.rs)
-extern "C" {
-    fn fb_blue();
-    fn fb_white();
-}
+use crate::drivers::display::progress;
+use crate::{kerror, kinfo};
 
 /// User code segment selector (RPL=3)
 const USER_CS: u64 = 0x1B;
@@ -52,15 +49,9 @@ const USER_DS: u64 = 0x23;
 /// 3. Uses IRETQ to switch to user mode at the entry point
 pub unsafe fn execute_process(entry: u64, stack_top: u64, cr3: u64) -> ! {
     // Debug: trace execution
-    let msg = b"[USPACE] Starting userspace transition\n";
-    for &byte in msg {
-        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-    }
+    kinfo!("[USPACE] Starting userspace transition");
 
-    let msg = b"[USPACE] About to load CR3\n";
-    for &byte in msg {
-        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-    }
+    kinfo!("[USPACE] About to load CR3");
 
     // CRITICAL: Canary reads BEFORE CR3 load to verify kernel mappings
     // These reads use the process's page tables to verify that kernel
@@ -78,10 +69,7 @@ pub unsafe fn execute_process(entry: u64, stack_top: u64, cr3: u64) -> ! {
         );
 
         if kernel_text_value == 0 {
-            let msg = b"[CANARY] FAIL: Kernel text not mapped!\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
+            kerror!("[CANARY] FAIL: Kernel text not mapped!");
             // Halt instead of loading CR3 - CR3 load would fault
             core::arch::asm!(
                 "2:",
@@ -91,10 +79,7 @@ pub unsafe fn execute_process(entry: u64, stack_top: u64, cr3: u64) -> ! {
             );
         }
 
-        let msg = b"[CANARY] PASS: Kernel text accessible\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[CANARY] PASS: Kernel text accessible");
 
         // Canary 2: Read from current stack (RSP)
         // If this fails, kernel stack is not mapped
@@ -113,15 +98,9 @@ pub unsafe fn execute_process(entry: u64, stack_top: u64, cr3: u64) -> ! {
             options(nostack, readonly)
         );
 
-        let msg = b"[CANARY] PASS: Kernel stack accessible\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[CANARY] PASS: Kernel stack accessible");
 
-        let msg = b"[CANARY] All verified - loading CR3\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[CANARY] All verified - loading CR3");
 
         // Load the new CR3 (page table base)
         // This is the critical switch to the process's address space
@@ -131,13 +110,10 @@ pub unsafe fn execute_process(entry: u64, stack_top: u64, cr3: u64) -> ! {
             options(nostack)
         );
 
-        // PROGRESS MARKER: CR3 loaded successfully (BLUE framebuffer)
-        fb_blue();
+        // PROGRESS MARKER: CR3 loaded successfully (BLUE with boot.progress=markers)
+        progress::marker(progress::MARKER_ADDRESS_SPACE);
 
-        let msg = b"[USPACE] About to load RSP\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[USPACE] About to load RSP");
 
         // Set up user stack
         core::arch::asm!(
@@ -146,10 +122,7 @@ pub unsafe fn execute_process(entry: u64, stack_top: u64, cr3: u64) -> ! {
             options(nostack)
         );
 
-        let msg = b"[USPACE] RSP loaded, about to load segments\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[USPACE] RSP loaded, about to load segments");
 
         // Set up user data segments
         core::arch::asm!(
@@ -162,8 +135,8 @@ pub unsafe fn execute_process(entry: u64, stack_top: u64, cr3: u64) -> ! {
             options(nostack)
         );
 
-        // PROGRESS MARKER: About to IRETQ to userspace (WHITE framebuffer)
-        fb_white();
+        // PROGRESS MARKER: About to IRETQ to userspace (WHITE with boot.progress=markers)
+        progress::marker(progress::MARKER_USER_ENTRY);
 
         // Set up RFLAGS for userspace (interrupts enabled, IOPL 0)
         let rflags: u64 = 0x202; // IF=1 (interrupts enabled), bit 1 always set
@@ -14,6 +14,7 @@
 #![feature(naked_functions)]
 
 use core::arch::naked_asm;
+use crate::process::table::SavedState;
 
 /// User data segment selector (RPL 3, TI=0, index=4)
 const USER_DATA_SELECTOR: u16 = 0x23;
@@ -183,6 +184,72 @@ pub unsafe extern "C" fn x86_uspace_entry_simple(
     );
 }
 
+/// Resume a process in user space from its saved state
+///
+/// Builds the `iretq` frame from the state's RIP, CS, RFLAGS, RSP and SS
+/// and restores every general-purpose register. The caller has already
+/// loaded the process's page tables and kernel stack; the FPU state is
+/// not restored.
+///
+/// # Arguments
+///
+/// * `state` - Saved state to resume (in rdi); must be user mode (CS.RPL 3)
+///
+/// # Safety
+///
+/// This function never returns. `state` must stay readable until the
+/// `iretq`, i.e. be mapped in the process's page tables.
+#[unsafe(naked)]
+pub unsafe extern "C" fn x86_uspace_resume(state: *const SavedState) -> ! {
+    naked_asm!(
+        "push qword ptr [rdi + {ss}]",
+        "push qword ptr [rdi + {rsp}]",
+        "push qword ptr [rdi + {rflags}]",
+        "push qword ptr [rdi + {cs}]",
+        "push qword ptr [rdi + {rip}]",
+
+        "mov rax, [rdi + {rax}]",
+        "mov rbx, [rdi + {rbx}]",
+        "mov rcx, [rdi + {rcx}]",
+        "mov rdx, [rdi + {rdx}]",
+        "mov rsi, [rdi + {rsi}]",
+        "mov rbp, [rdi + {rbp}]",
+        "mov r8, [rdi + {r8}]",
+        "mov r9, [rdi + {r9}]",
+        "mov r10, [rdi + {r10}]",
+        "mov r11, [rdi + {r11}]",
+        "mov r12, [rdi + {r12}]",
+        "mov r13, [rdi + {r13}]",
+        "mov r14, [rdi + {r14}]",
+        "mov r15, [rdi + {r15}]",
+        "mov rdi, [rdi + {rdi}]",
+
+        "swapgs",
+        "iretq",
+
+        ss = const core::mem::offset_of!(SavedState, ss),
+        rsp = const core::mem::offset_of!(SavedState, rsp),
+        rflags = const core::mem::offset_of!(SavedState, rflags),
+        cs = const core::mem::offset_of!(SavedState, cs),
+        rip = const core::mem::offset_of!(SavedState, rip),
+        rax = const core::mem::offset_of!(SavedState, rax),
+        rbx = const core::mem::offset_of!(SavedState, rbx),
+        rcx = const core::mem::offset_of!(SavedState, rcx),
+        rdx = const core::mem::offset_of!(SavedState, rdx),
+        rsi = const core::mem::offset_of!(SavedState, rsi),
+        rdi = const core::mem::offset_of!(SavedState, rdi),
+        rbp = const core::mem::offset_of!(SavedState, rbp),
+        r8 = const core::mem::offset_of!(SavedState, r8),
+        r9 = const core::mem::offset_of!(SavedState, r9),
+        r10 = const core::mem::offset_of!(SavedState, r10),
+        r11 = const core::mem::offset_of!(SavedState, r11),
+        r12 = const core::mem::offset_of!(SavedState, r12),
+        r13 = const core::mem::offset_of!(SavedState, r13),
+        r14 = const core::mem::offset_of!(SavedState, r14),
+        r15 = const core::mem::offset_of!(SavedState, r15),
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
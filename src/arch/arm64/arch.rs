@@ -15,18 +15,20 @@
 //!
 //! The ARM64 support is planned but not yet implemented. This module provides:
 //! - Basic type definitions for ARM64 compatibility
-//! - Placeholder interrupt controller stub
+//! - The interrupt controller, on a GICv2 or GICv3 (see
+//!   [`super::interrupt::gic`])
 //! - Architecture trait implementations (to be completed)
 //!
 //! # Planned Features
 //!
-//! - **GIC (Generic Interrupt Controller)**: GICv2 and GICv3 support
 //! - **MMU**: ARM64 page table management (4KB and 64KB pages)
 //! - **SMP**: Multi-processor support with PSCI
 //! - **Exception handling**: EL1 exception levels
 //! - **Counter-timer**: Generic timer support
 
 use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::traits::InterruptController;
+use super::interrupt::gic::{Gic, GicInfo, GicVersion, DEFAULT_PRIORITY};
 
 /// Maximum number of CPUs for ARM64
 pub const ARM64_MAX_CPUS: usize = 8;
@@ -38,22 +40,38 @@ pub const ARM64_PAGE_SIZE: usize = 4096;
 pub const ARM64_PAGE_SHIFT: usize = 12;
 
 // ============================================================================
-/// ARM64 Interrupt Controller (Placeholder)
+// ARM64 Interrupt Controller
 // ============================================================================
 
-/// ARM64 interrupt controller placeholder
+/// CPU that [`InterruptController::enable_irq`] routes SPIs to
+const BOOT_CPU: u32 = 0;
+
+/// ARM64 interrupt controller on a GIC
 ///
-/// This will be implemented with GIC (Generic Interrupt Controller) support.
+/// The GIC delivers each interrupt with its own ID, so the `vector` of
+/// [`InterruptController::enable_irq`] is unused; use [`Self::enable_irq_on`]
+/// to pick the target CPU and priority.
 pub struct Arm64InterruptController {
     /// Number of IRQ lines
     max_irq: AtomicUsize,
+    /// The GIC behind it
+    gic: Gic,
+    /// Whether the interrupt controller has been initialized
+    pub enabled: bool,
 }
 
 impl Arm64InterruptController {
-    /// Create a new ARM64 interrupt controller
+    /// Create a new ARM64 interrupt controller (QEMU `virt` GICv2)
     pub const fn new() -> Self {
+        Self::with_gic(&GicInfo::qemu_virt(GicVersion::V2))
+    }
+
+    /// Create an interrupt controller for the GIC described by `info`
+    pub const fn with_gic(info: &GicInfo) -> Self {
         Self {
             max_irq: AtomicUsize::new(1024),
+            gic: Gic::from_info(info),
+            enabled: false,
         }
     }
 
@@ -61,6 +79,40 @@ impl Arm64InterruptController {
     pub fn max_irq(&self) -> usize {
         self.max_irq.load(Ordering::Relaxed)
     }
+
+    /// The GIC, for per-CPU setup and interrupt acknowledgement
+    pub fn gic(&self) -> &Gic {
+        &self.gic
+    }
+
+    /// Enable an IRQ with a priority, routed to `cpu` if it is an SPI
+    pub fn enable_irq_on(&mut self, irq: u32, cpu: u32, priority: u8) -> Result<(), &'static str> {
+        self.gic.set_priority(irq, priority)?;
+        self.gic.enable_irq(irq, cpu)
+    }
+}
+
+impl InterruptController for Arm64InterruptController {
+    /// Enable an IRQ at the default priority, routed to the boot CPU
+    fn enable_irq(&mut self, irq: u64, _vector: u64) {
+        let _ = self.enable_irq_on(irq as u32, BOOT_CPU, DEFAULT_PRIORITY);
+    }
+
+    fn disable_irq(&mut self, irq: u64) {
+        let _ = self.gic.disable_irq(irq as u32);
+    }
+
+    fn send_eoi(&self, irq: u64) {
+        self.gic.eoi(irq as u32);
+    }
+
+    /// Initialize the distributor and the boot CPU's interface
+    fn init(&mut self) -> Result<(), &'static str> {
+        self.gic.init()?;
+        self.max_irq.store(self.gic.num_irq(), Ordering::Relaxed);
+        self.enabled = true;
+        Ok(())
+    }
 }
 
 // ============================================================================
@@ -193,6 +245,95 @@ pub unsafe fn get_secondary_sp(cpu_num: usize) -> &'static mut Arm64SpInfo {
     &mut SECONDARY_SP_LIST[cpu_num]
 }
 
+// ============================================================================
+// PSCI (Power State Coordination Interface)
+// ============================================================================
+
+/// PSCI `SYSTEM_OFF` function ID (SMC32 calling convention)
+pub const PSCI_SYSTEM_OFF: u32 = 0x8400_0008;
+
+/// PSCI `SYSTEM_RESET` function ID (SMC32 calling convention)
+pub const PSCI_SYSTEM_RESET: u32 = 0x8400_0009;
+
+/// PSCI `NOT_SUPPORTED` return value
+pub const PSCI_NOT_SUPPORTED: i64 = -1;
+
+/// Make a PSCI call through the hypervisor conduit (`hvc`)
+///
+/// Returns the PSCI status. Off ARM64 every call is `NOT_SUPPORTED`.
+///
+/// # Safety
+///
+/// The call must be valid for the firmware or hypervisor.
+pub unsafe fn psci_call(function: u32, args: [u64; 3]) -> i64 {
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: u64;
+        core::arch::asm!(
+            "hvc #0",
+            inlateout("x0") function as u64 => ret,
+            in("x1") args[0],
+            in("x2") args[1],
+            in("x3") args[2],
+            options(nostack),
+        );
+        ret as i64
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        let _ = (function, args);
+        PSCI_NOT_SUPPORTED
+    }
+}
+
+/// Power the system off
+///
+/// Returns only if PSCI refused the request.
+pub fn psci_system_off() -> i64 {
+    unsafe { psci_call(PSCI_SYSTEM_OFF, [0; 3]) }
+}
+
+/// Reset the system
+///
+/// Returns only if PSCI refused the request.
+pub fn psci_system_reset() -> i64 {
+    unsafe { psci_call(PSCI_SYSTEM_RESET, [0; 3]) }
+}
+
+// ============================================================================
+// Generic Timer Counter
+// ============================================================================
+
+/// `CNTKCTL_EL1.EL0PCTEN`: EL0 may read `CNTPCT_EL0` (and `CNTFRQ_EL0`)
+pub const CNTKCTL_EL0PCTEN: u64 = 1 << 0;
+
+/// `CNTKCTL_EL1.EL0VCTEN`: EL0 may read `CNTVCT_EL0` (and `CNTFRQ_EL0`)
+pub const CNTKCTL_EL0VCTEN: u64 = 1 << 1;
+
+/// Allow or deny EL0 reads of the virtual counter on this CPU
+///
+/// The arm64 counterpart of `CR4.TSD`: userspace reads `CNTVCT_EL0` for
+/// cycle-level timing and `CNTFRQ_EL0` for its frequency. The physical
+/// counter stays kernel-only either way. No-op off ARM64.
+///
+/// # Safety
+///
+/// Must run at EL1 on each CPU, before it enters EL0.
+pub unsafe fn set_user_counter_access(allow: bool) {
+    #[cfg(target_arch = "aarch64")]
+    {
+        let mut cntkctl: u64;
+        core::arch::asm!("mrs {}, cntkctl_el1", out(reg) cntkctl, options(nomem, nostack));
+        cntkctl &= !(CNTKCTL_EL0PCTEN | CNTKCTL_EL0VCTEN);
+        if allow {
+            cntkctl |= CNTKCTL_EL0VCTEN;
+        }
+        core::arch::asm!("msr cntkctl_el1, {}", "isb", in(reg) cntkctl, options(nostack));
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    let _ = allow;
+}
+
 // ============================================================================
 /// ARM64 Feature Detection
 /// ============================================================================
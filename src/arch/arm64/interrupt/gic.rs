@@ -24,12 +24,34 @@
 //! - **CPU Interfaces (GICC)**: Per-CPU interfaces for interrupt handling
 //! - **Redistributors (GICR)**: GICv3 component for MPI support
 //!
+//! # Interrupt IDs
+//!
+//! | Range | Kind | Configured in |
+//! |-------|------|---------------|
+//! | 0-15 | SGI (software, per CPU) | GICv2 distributor / GICv3 redistributor |
+//! | 16-31 | PPI (private peripheral, per CPU) | GICv2 distributor / GICv3 redistributor |
+//! | 32-1019 | SPI (shared peripheral) | Distributor |
+//!
+//! SPIs are routed to one CPU: through `GICD_ITARGETSR` (a CPU mask) on
+//! GICv2 and `GICD_IROUTER` (an MPIDR affinity) on GICv3. SGIs and PPIs
+//! are banked per CPU and go to the CPU that enables them.
+//!
+//! All interrupts are group 1 (IRQ, not FIQ) and start at
+//! [`DEFAULT_PRIORITY`]; the CPU interface accepts every priority above
+//! [`PRIORITY_MASK`].
+//!
+//! # Discovery
+//!
+//! There is no device tree parser yet, so the GIC is described by a board
+//! configuration ([`GicInfo::qemu_virt`]). Registers are accessed at
+//! their physical addresses, which must be identity mapped.
+//!
 //! # Usage
 //!
 //! ```ignore
 //! let gic = GicV2::new(gicd_base, gicc_base);
-//! gic.init();
-//! gic.enable_irq(32, 1)?;
+//! gic.init()?;
+//! gic.enable_irq(32, 0)?;
 //! ```
 
 use core::sync::atomic::{AtomicUsize, Ordering};
@@ -63,11 +85,20 @@ pub mod gicd_offset {
     /// Interrupt Clear-Pending Registers
     pub const ICPENDR: usize = 0x280;
 
+    /// Interrupt Priority Registers (one byte per interrupt)
+    pub const IPRIORITYR: usize = 0x400;
+
+    /// Interrupt Processor Targets Registers (GICv2, one byte per interrupt)
+    pub const ITARGETSR: usize = 0x800;
+
     /// Software Generated Interrupt Register
     pub const SGIR: usize = 0xF00;
 
     /// Interrupt Configuration Registers
     pub const ICFGR: usize = 0xC00;
+
+    /// Interrupt Routing Registers (GICv3, eight bytes per interrupt)
+    pub const IROUTER: usize = 0x6000;
 }
 
 /// GICv2 CPU Interface register offsets
@@ -97,6 +128,139 @@ pub mod gicc_offset {
     pub const EOIR1: usize = 0x0C0;
 }
 
+/// GICv3 Redistributor register offsets
+pub mod gicr_offset {
+    /// Control Register
+    pub const CTLR: usize = 0x0000;
+
+    /// Type Register (64-bit; affinity in bits 63:32)
+    pub const TYPER: usize = 0x0008;
+
+    /// Power management control
+    pub const WAKER: usize = 0x0014;
+
+    /// SGI/PPI frame, 64KB after the control frame
+    pub const SGI_BASE: usize = 0x1_0000;
+
+    /// SGI/PPI Group Register 0
+    pub const IGROUPR0: usize = SGI_BASE + 0x080;
+
+    /// SGI/PPI Set-Enable Register 0
+    pub const ISENABLER0: usize = SGI_BASE + 0x100;
+
+    /// SGI/PPI Clear-Enable Register 0
+    pub const ICENABLER0: usize = SGI_BASE + 0x180;
+
+    /// SGI/PPI Clear-Pending Register 0
+    pub const ICPENDR0: usize = SGI_BASE + 0x280;
+
+    /// SGI/PPI Priority Registers
+    pub const IPRIORITYR: usize = SGI_BASE + 0x400;
+}
+
+/// Distributor control: enable (GICv2), group 0 (GICv3)
+const GICD_CTLR_ENABLE_G0: u32 = 1 << 0;
+
+/// Distributor control: non-secure group 1 (GICv3)
+const GICD_CTLR_ENABLE_G1NS: u32 = 1 << 1;
+
+/// Distributor control: affinity routing (GICv3)
+const GICD_CTLR_ARE: u32 = 1 << 4;
+
+/// Distributor control: register write pending (GICv3)
+const GICD_CTLR_RWP: u32 = 1 << 31;
+
+/// Redistributor type: last redistributor in the region
+const GICR_TYPER_LAST: u64 = 1 << 4;
+
+/// Redistributor type: virtual LPIs supported (two extra 64KB frames)
+const GICR_TYPER_VLPIS: u64 = 1 << 1;
+
+/// Redistributor waker: the CPU interface is asleep
+const GICR_WAKER_PROCESSOR_SLEEP: u32 = 1 << 1;
+
+/// Redistributor waker: the interface has gone quiet
+const GICR_WAKER_CHILDREN_ASLEEP: u32 = 1 << 2;
+
+/// Priority every interrupt starts at (lower is more urgent)
+pub const DEFAULT_PRIORITY: u8 = 0xA0;
+
+/// Priority mask of the CPU interface: priorities below this are taken
+pub const PRIORITY_MASK: u8 = 0xF0;
+
+/// First shared peripheral interrupt
+pub const SPI_START: u32 = 32;
+
+/// Interrupt IDs 1020-1023 are special (1023: nothing pending)
+pub const SPECIAL_IRQ_START: u32 = 1020;
+
+/// Polls of a status bit before giving up on the GIC
+const SPIN_LIMIT: usize = 1_000_000;
+
+// ============================================================================
+// Register Access
+// ============================================================================
+
+unsafe fn read32(base: PAddr, offset: usize) -> u32 {
+    core::ptr::read_volatile((base as usize + offset) as *const u32)
+}
+
+unsafe fn write32(base: PAddr, offset: usize, value: u32) {
+    core::ptr::write_volatile((base as usize + offset) as *mut u32, value)
+}
+
+unsafe fn write8(base: PAddr, offset: usize, value: u8) {
+    core::ptr::write_volatile((base as usize + offset) as *mut u8, value)
+}
+
+unsafe fn read64(base: PAddr, offset: usize) -> u64 {
+    core::ptr::read_volatile((base as usize + offset) as *const u64)
+}
+
+unsafe fn write64(base: PAddr, offset: usize, value: u64) {
+    core::ptr::write_volatile((base as usize + offset) as *mut u64, value)
+}
+
+/// Register offset and bit of `irq` in a one-bit-per-interrupt bank
+pub const fn irq_bit(bank: usize, irq: u32) -> (usize, u32) {
+    (bank + (irq as usize / 32) * 4, 1 << (irq % 32))
+}
+
+/// Number of interrupt IDs from `GICD_TYPER.ITLinesNumber`
+pub const fn irq_count(typer: u32) -> usize {
+    let count = ((typer & 0x1F) as usize + 1) * 32;
+    if count > SPECIAL_IRQ_START as usize {
+        SPECIAL_IRQ_START as usize
+    } else {
+        count
+    }
+}
+
+/// Check that `irq` is an SPI the distributor has
+///
+/// `num_irq` is 0 before `init`, when only the architectural range is
+/// checked.
+pub fn check_spi(irq: u32, num_irq: usize) -> Result<(), &'static str> {
+    if !(SPI_START..SPECIAL_IRQ_START).contains(&irq) {
+        return Err("IRQ out of range");
+    }
+    if num_irq != 0 && irq as usize >= num_irq {
+        return Err("IRQ not implemented by this GIC");
+    }
+    Ok(())
+}
+
+/// Wait until `done` holds, or fail after [`SPIN_LIMIT`] polls
+fn spin_until(mut done: impl FnMut() -> bool, err: &'static str) -> Result<(), &'static str> {
+    for _ in 0..SPIN_LIMIT {
+        if done() {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+    Err(err)
+}
+
 /// ============================================================================
 /// GICv2 Controller
 /// ============================================================================
@@ -136,15 +300,44 @@ impl GicV2 {
 
     /// Initialize the GIC
     ///
-    /// This detects the GIC version and number of IRQ lines.
+    /// Reads the number of IRQ lines and CPUs, disables and clears every
+    /// SPI, gives it [`DEFAULT_PRIORITY`] and CPU 0 as its target, enables
+    /// the distributor and then this CPU's interface.
     pub fn init(&self) -> Result<(), &'static str> {
-        // TODO: Implement GIC initialization
-        // 1. Read TYPER to determine number of IRQs
-        // 2. Configure interrupt groups
-        // 3. Set priority mask
-        // 4. Enable the GIC
+        let d = self.gicd_base;
+        unsafe {
+            write32(d, gicd_offset::CTLR, 0);
+
+            let typer = read32(d, gicd_offset::TYPER);
+            let num_irq = irq_count(typer);
+            self.num_irq.store(num_irq, Ordering::Relaxed);
+            self.num_cpus.store(((typer >> 5) & 0x7) as usize + 1, Ordering::Relaxed);
+
+            for irq in (SPI_START..num_irq as u32).step_by(32) {
+                let (offset, _) = irq_bit(0, irq);
+                write32(d, gicd_offset::ICENABLER + offset, !0);
+                write32(d, gicd_offset::ICPENDR + offset, !0);
+                write32(d, gicd_offset::IGROUPR + offset, 0);
+            }
+            for irq in SPI_START..num_irq as u32 {
+                write8(d, gicd_offset::IPRIORITYR + irq as usize, DEFAULT_PRIORITY);
+                write8(d, gicd_offset::ITARGETSR + irq as usize, 1);
+            }
+
+            write32(d, gicd_offset::CTLR, GICD_CTLR_ENABLE_G0);
+        }
+        self.init_cpu()
+    }
 
-        Err("GIC initialization not yet implemented")
+    /// Enable this CPU's interface; each secondary CPU calls it once
+    pub fn init_cpu(&self) -> Result<(), &'static str> {
+        let c = self.gicc_base;
+        unsafe {
+            write32(c, gicc_offset::PMR, PRIORITY_MASK as u32);
+            write32(c, gicc_offset::BPR, 0);
+            write32(c, gicc_offset::CTLR, 1);
+        }
+        Ok(())
     }
 
     /// Enable an IRQ line
@@ -154,28 +347,45 @@ impl GicV2 {
     /// * `irq` - IRQ number (SPI: 32-1019)
     /// * `cpu` - Target CPU (0-based)
     pub fn enable_irq(&self, irq: u32, cpu: u32) -> Result<(), &'static str> {
-        if irq < 32 || irq >= 1020 {
-            return Err("IRQ out of range");
+        check_spi(irq, self.num_irq())?;
+        if cpu >= 8 {
+            return Err("GICv2 targets CPUs 0-7 only");
         }
 
-        // Calculate register and bit for SPI
-        let reg_offset = gicd_offset::ISENABLER + ((irq as usize / 32) * 4);
-        let bit = irq % 32;
-
-        // TODO: Write to GICD_ISENABLER to enable the IRQ
-        // TODO: Configure target CPU in GICD_ITARGETSR
+        let (offset, bit) = irq_bit(gicd_offset::ISENABLER, irq);
+        unsafe {
+            write8(self.gicd_base, gicd_offset::ITARGETSR + irq as usize, 1 << cpu);
+            write32(self.gicd_base, offset, bit);
+        }
+        Ok(())
+    }
 
+    /// Enable an SGI or PPI (0-31) on this CPU
+    pub fn enable_private(&self, irq: u32) -> Result<(), &'static str> {
+        if irq >= SPI_START {
+            return Err("Not a private IRQ");
+        }
+        let (offset, bit) = irq_bit(gicd_offset::ISENABLER, irq);
+        unsafe { write32(self.gicd_base, offset, bit) };
         Ok(())
     }
 
     /// Disable an IRQ line
     pub fn disable_irq(&self, irq: u32) -> Result<(), &'static str> {
-        if irq < 32 || irq >= 1020 {
-            return Err("IRQ out of range");
+        if irq >= SPI_START {
+            check_spi(irq, self.num_irq())?;
         }
+        let (offset, bit) = irq_bit(gicd_offset::ICENABLER, irq);
+        unsafe { write32(self.gicd_base, offset, bit) };
+        Ok(())
+    }
 
-        // TODO: Write to GICD_ICENABLER to disable the IRQ
-
+    /// Set the priority of an IRQ (lower is more urgent)
+    pub fn set_priority(&self, irq: u32, priority: u8) -> Result<(), &'static str> {
+        if irq >= SPECIAL_IRQ_START {
+            return Err("IRQ out of range");
+        }
+        unsafe { write8(self.gicd_base, gicd_offset::IPRIORITYR + irq as usize, priority) };
         Ok(())
     }
 
@@ -185,15 +395,16 @@ impl GicV2 {
     ///
     /// * `irq` - IRQ number to acknowledge
     pub fn eoi(&self, irq: u32) {
-        // TODO: Write IRQ to GICC_EOIR
+        unsafe { write32(self.gicc_base, gicc_offset::EOIR, irq) };
     }
 
     /// Get the highest priority pending interrupt
     ///
-    /// Returns the IRQ number or None if no interrupt is pending.
+    /// Acknowledges it and returns the IRQ number, or None if no
+    /// interrupt is pending.
     pub fn get_pending(&self) -> Option<u32> {
-        // TODO: Read GICC_IAR and return IRQ number
-        None
+        let irq = unsafe { read32(self.gicc_base, gicc_offset::IAR) } & 0x3FF;
+        (irq < SPECIAL_IRQ_START).then_some(irq)
     }
 
     /// Get number of IRQ lines
@@ -207,13 +418,95 @@ impl GicV2 {
     }
 }
 
+// ============================================================================
+// GICv3 CPU Interface (System Registers)
+// ============================================================================
+
+/// Read a GICv3 CPU interface register (0 off aarch64)
+macro_rules! icc_read {
+    ($reg:literal) => {{
+        #[cfg(target_arch = "aarch64")]
+        {
+            let value: u64;
+            unsafe { core::arch::asm!(concat!("mrs {}, ", $reg), out(reg) value, options(nomem, nostack)) };
+            value
+        }
+        #[cfg(not(target_arch = "aarch64"))]
+        {
+            0u64
+        }
+    }};
+}
+
+/// Write a GICv3 CPU interface register (no-op off aarch64)
+macro_rules! icc_write {
+    ($reg:literal, $value:expr) => {{
+        let value: u64 = $value;
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            core::arch::asm!(concat!("msr ", $reg, ", {}"), "isb", in(reg) value, options(nostack));
+        }
+        #[cfg(not(target_arch = "aarch64"))]
+        let _ = value;
+    }};
+}
+
+// The ICC_* registers are named by their generic encodings below, so no
+// assembler support for the GIC names is needed:
+//
+// | Register | Encoding |
+// |----------|----------|
+// | ICC_SRE_EL1 | S3_0_C12_C12_5 |
+// | ICC_PMR_EL1 | S3_0_C4_C6_0 |
+// | ICC_BPR1_EL1 | S3_0_C12_C12_3 |
+// | ICC_IGRPEN1_EL1 | S3_0_C12_C12_7 |
+// | ICC_IAR1_EL1 | S3_0_C12_C12_0 |
+// | ICC_EOIR1_EL1 | S3_0_C12_C12_1 |
+
+/// This CPU's MPIDR_EL1 (0 off aarch64)
+fn read_mpidr() -> u64 {
+    #[cfg(target_arch = "aarch64")]
+    {
+        let value: u64;
+        unsafe { core::arch::asm!("mrs {}, mpidr_el1", out(reg) value, options(nomem, nostack)) };
+        value
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        0
+    }
+}
+
+/// `GICD_IROUTER` value routing to the CPU with this MPIDR
+///
+/// The affinity fields sit at the same bits in both registers; the
+/// routing mode bit (31) stays clear, for a single target.
+pub const fn mpidr_to_route(mpidr: u64) -> u64 {
+    mpidr & 0xFF_00FF_FFFF
+}
+
+/// Affinity in `GICR_TYPER[63:32]` (Aff3.Aff2.Aff1.Aff0) of an MPIDR
+pub const fn mpidr_to_gicr_affinity(mpidr: u64) -> u32 {
+    (((mpidr >> 8) & 0xFF00_0000) | (mpidr & 0xFF_FFFF)) as u32
+}
+
+/// MPIDR of CPU `cpu`
+///
+/// Board configuration: CPUs are numbered by Aff0 in one cluster, as on
+/// QEMU `virt` with up to 8 CPUs.
+pub const fn cpu_mpidr(cpu: u32) -> u64 {
+    cpu as u64
+}
+
 /// ============================================================================
-/// GICv3 Controller (Placeholder)
+/// GICv3 Controller
 /// ============================================================================
 
 /// GICv3 interrupt controller
 ///
 /// Provides GICv3 support with redistributors and extended IRQ support.
+/// The CPU interface is used through its system registers, so
+/// `gicc_base` is only kept for GICv2 compatibility mode.
 pub struct GicV3 {
     /// Distributor base address
     pub gicd_base: PAddr,
@@ -245,26 +538,253 @@ impl GicV3 {
         }
     }
 
+    /// Wait for a distributor control write to take effect
+    fn wait_rwp(&self) -> Result<(), &'static str> {
+        spin_until(
+            || unsafe { read32(self.gicd_base, gicd_offset::CTLR) } & GICD_CTLR_RWP == 0,
+            "GICv3 distributor write timed out",
+        )
+    }
+
     /// Initialize the GICv3
+    ///
+    /// Sets up the distributor with affinity routing, every SPI disabled,
+    /// in group 1, at [`DEFAULT_PRIORITY`] and routed to CPU 0, then this
+    /// CPU's redistributor and CPU interface.
     pub fn init(&self) -> Result<(), &'static str> {
-        Err("GICv3 initialization not yet implemented")
+        let d = self.gicd_base;
+        unsafe {
+            write32(d, gicd_offset::CTLR, 0);
+            self.wait_rwp()?;
+
+            let num_irq = irq_count(read32(d, gicd_offset::TYPER));
+            self.num_irq.store(num_irq, Ordering::Relaxed);
+
+            for irq in (SPI_START..num_irq as u32).step_by(32) {
+                let (offset, _) = irq_bit(0, irq);
+                write32(d, gicd_offset::ICENABLER + offset, !0);
+                write32(d, gicd_offset::ICPENDR + offset, !0);
+                write32(d, gicd_offset::IGROUPR + offset, !0);
+            }
+            self.wait_rwp()?;
+            for irq in SPI_START..num_irq as u32 {
+                write8(d, gicd_offset::IPRIORITYR + irq as usize, DEFAULT_PRIORITY);
+                write64(d, gicd_offset::IROUTER + irq as usize * 8, mpidr_to_route(cpu_mpidr(0)));
+            }
+
+            write32(d, gicd_offset::CTLR, GICD_CTLR_ARE | GICD_CTLR_ENABLE_G1NS | GICD_CTLR_ENABLE_G0);
+            self.wait_rwp()?;
+        }
+        self.init_cpu()
+    }
+
+    /// Redistributor of the running CPU
+    fn this_redistributor(&self) -> Option<PAddr> {
+        let affinity = mpidr_to_gicr_affinity(read_mpidr());
+        let mut frame = self.gicr_base;
+        loop {
+            let typer = unsafe { read64(frame, gicr_offset::TYPER) };
+            if (typer >> 32) as u32 == affinity {
+                return Some(frame);
+            }
+            if typer & GICR_TYPER_LAST != 0 {
+                return None;
+            }
+            frame += if typer & GICR_TYPER_VLPIS != 0 { 0x4_0000 } else { 0x2_0000 };
+        }
+    }
+
+    /// Wake this CPU's redistributor, set up its SGIs and PPIs (disabled,
+    /// group 1, default priority) and enable its CPU interface; each
+    /// secondary CPU calls it once
+    pub fn init_cpu(&self) -> Result<(), &'static str> {
+        let r = self.this_redistributor().ok_or("No GICv3 redistributor for this CPU")?;
+        unsafe {
+            let waker = read32(r, gicr_offset::WAKER);
+            write32(r, gicr_offset::WAKER, waker & !GICR_WAKER_PROCESSOR_SLEEP);
+            spin_until(
+                || read32(r, gicr_offset::WAKER) & GICR_WAKER_CHILDREN_ASLEEP == 0,
+                "GICv3 redistributor did not wake",
+            )?;
+
+            write32(r, gicr_offset::ICENABLER0, !0);
+            write32(r, gicr_offset::ICPENDR0, !0);
+            write32(r, gicr_offset::IGROUPR0, !0);
+            for irq in 0..SPI_START as usize {
+                write8(r, gicr_offset::IPRIORITYR + irq, DEFAULT_PRIORITY);
+            }
+        }
+
+        icc_write!("S3_0_C12_C12_5", icc_read!("S3_0_C12_C12_5") | 1);
+        icc_write!("S3_0_C4_C6_0", PRIORITY_MASK as u64);
+        icc_write!("S3_0_C12_C12_3", 0);
+        icc_write!("S3_0_C12_C12_7", 1);
+        Ok(())
     }
 
     /// Enable an IRQ line
+    ///
+    /// # Arguments
+    ///
+    /// * `irq` - IRQ number (SPI: 32-1019)
+    /// * `cpu` - Target CPU (0-based, see [`cpu_mpidr`])
     pub fn enable_irq(&self, irq: u32, cpu: u32) -> Result<(), &'static str> {
-        // TODO: Implement GICv3 IRQ enable
-        Err("not implemented")
+        check_spi(irq, self.num_irq.load(Ordering::Relaxed))?;
+
+        let (offset, bit) = irq_bit(gicd_offset::ISENABLER, irq);
+        unsafe {
+            write64(self.gicd_base, gicd_offset::IROUTER + irq as usize * 8, mpidr_to_route(cpu_mpidr(cpu)));
+            write32(self.gicd_base, offset, bit);
+        }
+        Ok(())
+    }
+
+    /// Enable an SGI or PPI (0-31) on this CPU
+    pub fn enable_private(&self, irq: u32) -> Result<(), &'static str> {
+        if irq >= SPI_START {
+            return Err("Not a private IRQ");
+        }
+        let r = self.this_redistributor().ok_or("No GICv3 redistributor for this CPU")?;
+        unsafe { write32(r, gicr_offset::ISENABLER0, 1 << irq) };
+        Ok(())
     }
 
     /// Disable an IRQ line
     pub fn disable_irq(&self, irq: u32) -> Result<(), &'static str> {
-        // TODO: Implement GICv3 IRQ disable
-        Err("not implemented")
+        if irq < SPI_START {
+            let r = self.this_redistributor().ok_or("No GICv3 redistributor for this CPU")?;
+            unsafe { write32(r, gicr_offset::ICENABLER0, 1 << irq) };
+            return Ok(());
+        }
+
+        check_spi(irq, self.num_irq.load(Ordering::Relaxed))?;
+        let (offset, bit) = irq_bit(gicd_offset::ICENABLER, irq);
+        unsafe { write32(self.gicd_base, offset, bit) };
+        self.wait_rwp()
+    }
+
+    /// Set the priority of an IRQ (lower is more urgent)
+    ///
+    /// SGIs and PPIs are set for the running CPU.
+    pub fn set_priority(&self, irq: u32, priority: u8) -> Result<(), &'static str> {
+        if irq < SPI_START {
+            let r = self.this_redistributor().ok_or("No GICv3 redistributor for this CPU")?;
+            unsafe { write8(r, gicr_offset::IPRIORITYR + irq as usize, priority) };
+            return Ok(());
+        }
+
+        check_spi(irq, self.num_irq.load(Ordering::Relaxed))?;
+        unsafe { write8(self.gicd_base, gicd_offset::IPRIORITYR + irq as usize, priority) };
+        Ok(())
     }
 
     /// Send End of Interrupt
     pub fn eoi(&self, irq: u32) {
-        // TODO: Implement GICv3 EOI
+        icc_write!("S3_0_C12_C12_1", irq as u64);
+    }
+
+    /// Get the highest priority pending interrupt
+    ///
+    /// Acknowledges it and returns the IRQ number, or None if no
+    /// interrupt is pending.
+    pub fn get_pending(&self) -> Option<u32> {
+        let irq = (icc_read!("S3_0_C12_C12_0") & 0xFF_FFFF) as u32;
+        (irq < SPECIAL_IRQ_START).then_some(irq)
+    }
+
+    /// Get number of IRQ lines
+    pub fn num_irq(&self) -> usize {
+        self.num_irq.load(Ordering::Relaxed)
+    }
+}
+
+// ============================================================================
+// Either GIC
+// ============================================================================
+
+/// A GICv2 or GICv3, chosen from a [`GicInfo`]
+pub enum Gic {
+    V2(GicV2),
+    V3(GicV3),
+}
+
+impl Gic {
+    /// The controller described by `info`
+    ///
+    /// GICv1 is driven as a GICv2 and GICv4 as a GICv3.
+    pub const fn from_info(info: &GicInfo) -> Self {
+        match (info.version, info.gicr_base) {
+            (GicVersion::V3 | GicVersion::V4, Some(gicr_base)) => {
+                Self::V3(GicV3::new(info.gicd_base, gicr_base, info.gicc_base))
+            }
+            _ => Self::V2(GicV2::new(info.gicd_base, info.gicc_base)),
+        }
+    }
+
+    /// Initialize the distributor and this CPU's interface
+    pub fn init(&self) -> Result<(), &'static str> {
+        match self {
+            Self::V2(gic) => gic.init(),
+            Self::V3(gic) => gic.init(),
+        }
+    }
+
+    /// Set up a secondary CPU's interface
+    pub fn init_cpu(&self) -> Result<(), &'static str> {
+        match self {
+            Self::V2(gic) => gic.init_cpu(),
+            Self::V3(gic) => gic.init_cpu(),
+        }
+    }
+
+    /// Enable an IRQ: an SPI routed to `cpu`, or an SGI/PPI on this CPU
+    pub fn enable_irq(&self, irq: u32, cpu: u32) -> Result<(), &'static str> {
+        match (self, irq < SPI_START) {
+            (Self::V2(gic), true) => gic.enable_private(irq),
+            (Self::V2(gic), false) => gic.enable_irq(irq, cpu),
+            (Self::V3(gic), true) => gic.enable_private(irq),
+            (Self::V3(gic), false) => gic.enable_irq(irq, cpu),
+        }
+    }
+
+    /// Disable an IRQ
+    pub fn disable_irq(&self, irq: u32) -> Result<(), &'static str> {
+        match self {
+            Self::V2(gic) => gic.disable_irq(irq),
+            Self::V3(gic) => gic.disable_irq(irq),
+        }
+    }
+
+    /// Set the priority of an IRQ (lower is more urgent)
+    pub fn set_priority(&self, irq: u32, priority: u8) -> Result<(), &'static str> {
+        match self {
+            Self::V2(gic) => gic.set_priority(irq, priority),
+            Self::V3(gic) => gic.set_priority(irq, priority),
+        }
+    }
+
+    /// Send End of Interrupt
+    pub fn eoi(&self, irq: u32) {
+        match self {
+            Self::V2(gic) => gic.eoi(irq),
+            Self::V3(gic) => gic.eoi(irq),
+        }
+    }
+
+    /// Acknowledge the highest priority pending interrupt
+    pub fn get_pending(&self) -> Option<u32> {
+        match self {
+            Self::V2(gic) => gic.get_pending(),
+            Self::V3(gic) => gic.get_pending(),
+        }
+    }
+
+    /// Number of interrupt IDs (0 before `init`)
+    pub fn num_irq(&self) -> usize {
+        match self {
+            Self::V2(gic) => gic.num_irq(),
+            Self::V3(gic) => gic.num_irq(),
+        }
     }
 }
 
@@ -319,7 +839,7 @@ impl GicVersion {
 
 /// GIC information from ACPI
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct GicInfo {
     /// GIC version
     pub version: GicVersion,
@@ -336,7 +856,7 @@ pub struct GicInfo {
 
 impl GicInfo {
     /// Create new GIC information
-    pub fn new(version: GicVersion, gicd_base: PAddr, gicr_base: Option<PAddr>, gicc_base: PAddr) -> Self {
+    pub const fn new(version: GicVersion, gicd_base: PAddr, gicr_base: Option<PAddr>, gicc_base: PAddr) -> Self {
         Self {
             version,
             gicd_base,
@@ -345,10 +865,22 @@ impl GicInfo {
         }
     }
 
-    /// Get the appropriate GIC controller
+    /// Board configuration of QEMU's `virt` machine
+    ///
+    /// `gic-version=2` or `gic-version=3` on the QEMU command line picks
+    /// the version.
+    pub const fn qemu_virt(version: GicVersion) -> Self {
+        match version {
+            GicVersion::V3 | GicVersion::V4 => Self::new(version, 0x0800_0000, Some(0x080A_0000), 0x0801_0000),
+            _ => Self::new(version, 0x0800_0000, None, 0x0801_0000),
+        }
+    }
+
+    /// Get the appropriate GIC controller, initialized
     pub fn create_controller(&self) -> Result<Box<dyn crate::traits::InterruptController>, &'static str> {
-        // TODO: Implement controller creation
-        Err("GIC controller creation not yet implemented")
+        let mut controller = crate::arch::arm64::Arm64InterruptController::with_gic(self);
+        crate::traits::InterruptController::init(&mut controller)?;
+        Ok(Box::new(controller))
     }
 }
 
@@ -377,17 +909,32 @@ mod tests {
 
     #[test]
     fn test_gic_irq_range() {
-        let gic = GicV2::new(0, 0);
-        assert!(gic.enable_irq(32, 0).is_ok()); // SPI
-        assert!(gic.enable_irq(31, 0).is_err()); // SGI - invalid
-        assert!(gic.enable_irq(1020, 0).is_err()); // Out of range
+        assert!(check_spi(32, 0).is_ok()); // SPI
+        assert!(check_spi(31, 0).is_err()); // SGI - invalid
+        assert!(check_spi(1020, 0).is_err()); // Out of range
+        assert!(check_spi(288, 288).is_err()); // Beyond ITLinesNumber
     }
 
     #[test]
     fn test_gic_v3_create() {
-        let gic = GicV3::new(0x08000000, Some(0x080A0000), 0x08010000);
+        let gic = GicV3::new(0x08000000, 0x080A0000, 0x08010000);
         assert_eq!(gic.gicd_base, 0x08000000);
-        assert_eq!(gic.gicr_base, Some(0x080A0000));
+        assert_eq!(gic.gicr_base, 0x080A0000);
         assert_eq!(gic.gicc_base, 0x08010000);
     }
+
+    #[test]
+    fn test_register_math() {
+        assert_eq!(irq_bit(gicd_offset::ISENABLER, 33), (0x104, 1 << 1));
+        assert_eq!(irq_count(0x8), 288);
+        assert_eq!(irq_count(0x1F), 1020);
+        assert_eq!(mpidr_to_route(0x8000_0000 | (1 << 32) | 0x0203), (1 << 32) | 0x0203);
+        assert_eq!(mpidr_to_gicr_affinity((1 << 32) | 0x03_0201), 0x0103_0201);
+    }
+
+    #[test]
+    fn test_from_info() {
+        assert!(matches!(Gic::from_info(&GicInfo::qemu_virt(GicVersion::V2)), Gic::V2(_)));
+        assert!(matches!(Gic::from_info(&GicInfo::qemu_virt(GicVersion::V3)), Gic::V3(_)));
+    }
 }
@@ -12,4 +12,4 @@
 pub mod gic;
 
 // Re-exports
-pub use gic::{GicV2, GicV3, GicVersion, GicInfo, gicd_offset, gicc_offset};
+pub use gic::{Gic, GicV2, GicV3, GicVersion, GicInfo, gicd_offset, gicc_offset, gicr_offset};
@@ -0,0 +1,34 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! ARM64 virtual address space layout
+//!
+//! TTBR0 translates the user half and TTBR1 the kernel half; each spans
+//! [`USER_VA_BITS`] bits, set by `T0SZ`/`T1SZ`. Only the split is defined
+//! here so far: processes run on x86_64, whose layout
+//! (`arch::amd64::mm::layout`) places the regions inside the user half.
+
+use super::ARM64_VA_BITS;
+
+/// Bits of user virtual address space (`64 - T0SZ`)
+pub const USER_VA_BITS: u32 = ARM64_VA_BITS as u32;
+
+/// End of the user half (TTBR0)
+pub const USER_END: u64 = 1 << USER_VA_BITS;
+
+/// Start of the kernel half (TTBR1)
+pub const KERNEL_START: u64 = !((1u64 << USER_VA_BITS) - 1);
+
+/// `TCR_EL1.T0SZ` and `T1SZ` for this split
+pub const TCR_TXSZ: u64 = 64 - USER_VA_BITS as u64;
+
+/// Check if an address is in the user half
+pub const fn is_user(addr: u64) -> bool {
+    addr < USER_END
+}
+
+const _: () = assert!(USER_VA_BITS >= 25 && USER_VA_BITS <= 48, "T0SZ must be 16..=39");
+const _: () = assert!(USER_END <= KERNEL_START);
@@ -14,6 +14,8 @@
 
 use core::sync::atomic::{AtomicU16, Ordering};
 
+pub mod layout;
+
 /// Physical address type
 pub type PAddr = u64;
 
@@ -20,5 +20,5 @@ pub mod mm;
 
 // Re-exports
 pub use arch::{Arm64ArchInfo, Arm64Features, Arm64SpInfo, Arm64InterruptController, ARM64_MAX_CPUS, ARM64_PAGE_SIZE};
-pub use interrupt::{GicV2, GicV3, GicVersion, GicInfo};
+pub use interrupt::{Gic, GicV2, GicV3, GicVersion, GicInfo};
 pub use mm::{PAddr};
@@ -15,18 +15,20 @@
 //!
 //! The RISC-V support is planned but not yet implemented. This module provides:
 //! - Basic type definitions for RISC-V compatibility
-//! - Placeholder interrupt controller stub
+//! - The interrupt controller: external interrupts from the PLIC (see
+//!   [`super::interrupt::plic`]), timer and software interrupts through
+//!   SBI or the CLINT, taken by the S-mode trap vector in [`super::trap`]
 //! - Architecture trait implementations (to be completed)
 //!
 //! # Planned Features
 //!
-//! - **PLIC (Platform-Level Interrupt Controller)**: Standard RISC-V interrupt controller
 //! - **MMU**: Sv39 and Sv48 page table management
 //! - **SMP**: Hart-based multiprocessing with SBI
-//! - **Exception handling**: Trap handling
-//! - **Clint**: Core-local interruptor
+//! - **Exception handling**: Trap handling beyond interrupts
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::traits::InterruptController;
+use super::interrupt::plic::{Plic, PlicIrq, PlicPriority, PLIC_S_MODE_CONTEXT};
 
 /// Maximum number of harts for RISC-V
 pub const RISCV_MAX_HARTS: usize = 8;
@@ -38,22 +40,83 @@ pub const RISCV_PAGE_SIZE: usize = 4096;
 pub const RISCV_PAGE_SHIFT: usize = 12;
 
 // ============================================================================
-/// RISC-V Interrupt Controller (Placeholder)
+// RISC-V Interrupt Controller
 // ============================================================================
 
-/// RISC-V interrupt controller placeholder
+/// Hart that [`InterruptController::enable_irq`] routes sources to
+const BOOT_HART: usize = 0;
+
+/// Board configuration of the interrupt controllers
 ///
-/// This will be implemented with PLIC (Platform-Level Interrupt Controller) support.
+/// There is no device tree parser, so the PLIC and CLINT are described
+/// here instead.
+#[derive(Debug, Clone, Copy)]
+pub struct RiscvIntcInfo {
+    /// PLIC physical address
+    pub plic_base: usize,
+
+    /// Number of PLIC sources, counting the unused source 0
+    pub num_sources: usize,
+
+    /// Number of harts wired to the PLIC
+    pub num_harts: usize,
+
+    /// CLINT physical address
+    pub clint_base: usize,
+}
+
+impl RiscvIntcInfo {
+    /// Board configuration of QEMU's `virt` machine
+    pub const fn qemu_virt() -> Self {
+        Self {
+            plic_base: 0x0C00_0000,
+            num_sources: 96,
+            num_harts: RISCV_MAX_HARTS,
+            clint_base: 0x0200_0000,
+        }
+    }
+}
+
+/// RISC-V interrupt controller
+///
+/// External interrupts come from the PLIC, through each hart's S-mode
+/// context. Timer and software interrupts are programmed through the SBI
+/// TIME and IPI extensions when the firmware has them, and through the
+/// CLINT directly otherwise (when the kernel owns M-mode).
+///
+/// The PLIC delivers each interrupt with its own source number, so the
+/// `vector` of [`InterruptController::enable_irq`] is unused; use
+/// [`Self::enable_irq_on`] to pick the target hart and priority.
 pub struct RiscvInterruptController {
     /// Number of IRQ lines
     max_irq: AtomicUsize,
+    /// The PLIC, for external interrupts
+    plic: Plic,
+    /// The CLINT, for timer and software interrupts without SBI
+    clint: Clint,
+    /// Whether the firmware has the SBI TIME extension
+    sbi_time: AtomicBool,
+    /// Whether the firmware has the SBI IPI extension
+    sbi_ipi: AtomicBool,
+    /// Whether the interrupt controller has been initialized
+    pub enabled: bool,
 }
 
 impl RiscvInterruptController {
-    /// Create a new RISC-V interrupt controller
+    /// Create a new RISC-V interrupt controller (QEMU `virt`)
     pub const fn new() -> Self {
+        Self::with_info(&RiscvIntcInfo::qemu_virt())
+    }
+
+    /// Create an interrupt controller for the board described by `info`
+    pub const fn with_info(info: &RiscvIntcInfo) -> Self {
         Self {
             max_irq: AtomicUsize::new(1024),
+            plic: Plic::with_config(info.plic_base, info.num_sources, info.num_harts),
+            clint: Clint::from_base(info.clint_base),
+            sbi_time: AtomicBool::new(false),
+            sbi_ipi: AtomicBool::new(false),
+            enabled: false,
         }
     }
 
@@ -61,6 +124,84 @@ impl RiscvInterruptController {
     pub fn max_irq(&self) -> usize {
         self.max_irq.load(Ordering::Relaxed)
     }
+
+    /// The PLIC
+    pub fn plic(&self) -> &Plic {
+        &self.plic
+    }
+
+    /// The CLINT
+    pub fn clint(&self) -> &Clint {
+        &self.clint
+    }
+
+    /// Enable a PLIC source with a priority, delivered to `hart`
+    pub fn enable_irq_on(&mut self, irq: u32, hart: usize, priority: PlicPriority) -> Result<(), &'static str> {
+        let irq = PlicIrq::new(irq);
+        self.plic.check_irq(irq)?;
+        self.plic.set_priority(irq, priority);
+        self.plic.enable_irq(hart, PLIC_S_MODE_CONTEXT, irq)
+    }
+
+    /// Claim the highest priority external interrupt pending on this hart
+    pub fn claim(&self) -> Option<u32> {
+        let context = self.plic.get_context(current_hart(), PLIC_S_MODE_CONTEXT)?;
+        match context.claim() {
+            PlicIrq::NONE => None,
+            irq => Some(irq.into_inner()),
+        }
+    }
+
+    /// Raise a timer interrupt on this hart once `time` reaches `deadline`
+    ///
+    /// Also clears a pending timer interrupt; `u64::MAX` turns it off.
+    pub fn set_timer(&self, deadline: u64) {
+        if self.sbi_time.load(Ordering::Relaxed) {
+            sbi_set_timer(deadline);
+        } else {
+            self.clint.set_timer(current_hart(), deadline);
+        }
+    }
+
+    /// Raise a software interrupt on `hart`
+    pub fn send_ipi(&self, hart: usize) {
+        if self.sbi_ipi.load(Ordering::Relaxed) {
+            sbi_send_ipi(1 << (hart % 64), (hart / 64 * 64) as u64);
+        } else {
+            self.clint.send_ipi(hart);
+        }
+    }
+}
+
+impl InterruptController for RiscvInterruptController {
+    /// Enable an IRQ at the default priority, delivered to the boot hart
+    fn enable_irq(&mut self, irq: u64, _vector: u64) {
+        let _ = self.enable_irq_on(irq as u32, BOOT_HART, PlicPriority::DEFAULT);
+    }
+
+    /// Disable an IRQ on every hart
+    fn disable_irq(&mut self, irq: u64) {
+        for hart in 0..self.plic.num_harts() {
+            let _ = self.plic.disable_irq(hart, PLIC_S_MODE_CONTEXT, PlicIrq::new(irq as u32));
+        }
+    }
+
+    /// Complete an IRQ claimed on this hart
+    fn send_eoi(&self, irq: u64) {
+        if let Some(context) = self.plic.get_context(current_hart(), PLIC_S_MODE_CONTEXT) {
+            context.complete(PlicIrq::new(irq as u32));
+        }
+    }
+
+    /// Initialize the PLIC and pick how timer and software interrupts are sent
+    fn init(&mut self) -> Result<(), &'static str> {
+        self.plic.init()?;
+        self.max_irq.store(self.plic.num_sources(), Ordering::Relaxed);
+        self.sbi_time.store(sbi_probe_extension(SbiExtension::Timer), Ordering::Relaxed);
+        self.sbi_ipi.store(sbi_probe_extension(SbiExtension::Ipi), Ordering::Relaxed);
+        self.enabled = true;
+        Ok(())
+    }
 }
 
 // ============================================================================
@@ -113,6 +254,24 @@ pub unsafe fn set_hart_info(hart_id: usize, info: HartInfo) {
     HART_INFO[hart_id] = info;
 }
 
+/// ID of the hart running this code
+///
+/// Harts keep their ID in `tp` while in the kernel (see
+/// [`super::trap::init_hart`]). Always 0 off riscv64.
+#[inline]
+pub fn current_hart() -> usize {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let hart: usize;
+        unsafe { core::arch::asm!("mv {}, tp", out(reg) hart, options(nomem, nostack)) };
+        hart
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        0
+    }
+}
+
 /// Get bootstrap hart ID
 pub fn get_bootstrap_hart() -> usize {
     unsafe {
@@ -135,16 +294,27 @@ pub fn get_bootstrap_hart() -> usize {
 pub enum SbiExtension {
     /// Base extension
     Base = 0x10,
-    /// Timer extension
-    Timer = 0x54494D4F,
-    /// IPI extension
-    Ipi = 0x7350494E,
-    /// RFENCE extension
-    Rfence = 0x52464E45,
-    /// Hart state management extension
-    HartState = 0x48534D4E,
+    /// Timer extension ("TIME")
+    Timer = 0x54494D45,
+    /// IPI extension ("sPI")
+    Ipi = 0x735049,
+    /// RFENCE extension ("RFNC")
+    Rfence = 0x52464E43,
+    /// Hart state management extension ("HSM")
+    HartState = 0x48534D,
+    /// System reset extension ("SRST")
+    SystemReset = 0x53525354,
 }
 
+/// SRST reset type: power off
+pub const SBI_RESET_SHUTDOWN: u32 = 0;
+
+/// SRST reset type: cold reboot
+pub const SBI_RESET_COLD_REBOOT: u32 = 1;
+
+/// SRST reset reason: no reason
+pub const SBI_RESET_REASON_NONE: u32 = 0;
+
 /// SBI function IDs for Base extension
 #[repr(u64)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -244,13 +414,71 @@ impl SbiRet {
 ///
 /// Must be called from RISC-V code with proper arguments.
 pub unsafe fn sbi_call(extension: SbiExtension, function: SbiFunction, args: [u64; 6]) -> (SbiRet, u64) {
-    // TODO: Implement actual SBI call using ecall instruction
-    // The assembly would be:
-    // ecall
-    // Where a7 = extension, a6 = function, a0-a5 = args
+    sbi_ecall(extension as u64, function.id(), args)
+}
+
+/// Make an SBI call by raw extension and function ID
+///
+/// `a7` carries the extension, `a6` the function and `a0`-`a5` the
+/// arguments; the error comes back in `a0` and the value in `a1`. Off
+/// riscv64 every call is unsupported.
+///
+/// # Safety
+///
+/// The call must be valid for the SBI implementation.
+pub unsafe fn sbi_ecall(extension: u64, function: u64, args: [u64; 6]) -> (SbiRet, u64) {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let error: u64;
+        let value: u64;
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a5") args[5],
+            in("a6") function,
+            in("a7") extension,
+            options(nostack),
+        );
+        (SbiRet::from_raw(error as i64), value)
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        let _ = (extension, function, args);
+        (SbiRet::NotSupported, 0)
+    }
+}
 
-    // For now, return not supported
-    (SbiRet::NotSupported, 0)
+/// Whether the SBI implementation has an extension
+pub fn sbi_probe_extension(extension: SbiExtension) -> bool {
+    let (ret, value) = unsafe {
+        sbi_call(SbiExtension::Base, SbiFunction::ProbeExtension, [extension as u64, 0, 0, 0, 0, 0])
+    };
+    ret == SbiRet::Success && value != 0
+}
+
+/// Program this hart's timer through the SBI TIME extension
+pub fn sbi_set_timer(deadline: u64) {
+    unsafe { sbi_ecall(SbiExtension::Timer as u64, 0, [deadline, 0, 0, 0, 0, 0]) };
+}
+
+/// Send a software interrupt to the harts in `hart_mask`, counted from
+/// `hart_mask_base`, through the SBI IPI extension
+pub fn sbi_send_ipi(hart_mask: u64, hart_mask_base: u64) {
+    unsafe { sbi_ecall(SbiExtension::Ipi as u64, 0, [hart_mask, hart_mask_base, 0, 0, 0, 0]) };
+}
+
+/// Power off or reboot through the SBI SRST extension
+///
+/// Returns only if the SBI implementation refused the request.
+pub fn sbi_system_reset(reset_type: u32, reason: u32) -> SbiRet {
+    let (ret, _) = unsafe {
+        sbi_ecall(SbiExtension::SystemReset as u64, 0, [reset_type as u64, reason as u64, 0, 0, 0, 0])
+    };
+    ret
 }
 
 /// Get SBI version
@@ -269,17 +497,27 @@ pub fn get_sbi_version() -> (u64, u64) {
 /// RISC-V CLINT (Core-Local Interruptor)
 /// ============================================================================
 
+/// CLINT register offsets (SiFive layout)
+pub mod clint_offset {
+    /// msip registers (4 bytes per hart)
+    pub const MSIP: usize = 0x0000;
+
+    /// mtimecmp registers (8 bytes per hart)
+    pub const MTIMECMP: usize = 0x4000;
+
+    /// mtime register
+    pub const MTIME: usize = 0xBFF8;
+}
+
 /// CLINT (Core-Local Interruptor) registers
+///
+/// The CLINT raises M-mode interrupts and firmware usually protects it,
+/// so S-mode goes through SBI instead (see [`RiscvInterruptController`]).
 #[repr(C)]
+#[derive(Debug)]
 pub struct Clint {
-    /// mtime register (memory-mapped)
-    pub mtime: *mut u64,
-
-    /// mtimecmp register (per-hart)
-    pub mtimecmp: *mut u64,
-
-    /// msip register (per-hart)
-    pub msip: *mut u32,
+    /// Base address of the CLINT region
+    pub base: usize,
 }
 
 impl Clint {
@@ -288,55 +526,48 @@ impl Clint {
     /// # Arguments
     ///
     /// * `base` - Base address of CLINT region
-    pub fn from_base(base: usize) -> Self {
-        let mtime = base as *mut u64;
-        let mtimecmp = (base + 0x4000) as *mut u64; // Offset for first hart
-        let msip = (base + 0x8000) as *mut u32; // Offset for first hart
+    pub const fn from_base(base: usize) -> Self {
+        Self { base }
+    }
 
-        Self { mtime, mtimecmp, msip }
+    /// Get the mtime register
+    pub fn mtime(&self) -> *mut u64 {
+        (self.base + clint_offset::MTIME) as *mut u64
     }
 
     /// Get mtimecmp for a specific hart
     pub fn mtimecmp(&self, hart: usize) -> *mut u64 {
-        // Each mtimecmp is 8 bytes apart
-        (self.mtimecmp as usize + hart * 8) as *mut u64
+        (self.base + clint_offset::MTIMECMP + hart * 8) as *mut u64
     }
 
     /// Get msip for a specific hart
     pub fn msip(&self, hart: usize) -> *mut u32 {
-        // Each msip is 4 bytes apart
-        (self.msip as usize + hart * 4) as *mut u32
+        (self.base + clint_offset::MSIP + hart * 4) as *mut u32
     }
 
     /// Get current time from mtime
     pub fn get_mtime(&self) -> u64 {
-        unsafe { *self.mtime }
+        unsafe { core::ptr::read_volatile(self.mtime()) }
     }
 
     /// Set timer for a hart
     pub fn set_timer(&self, hart: usize, value: u64) {
-        unsafe {
-            *self.mtimecmp(hart) = value;
-        }
+        unsafe { core::ptr::write_volatile(self.mtimecmp(hart), value) };
     }
 
     /// Send IPI to a hart
     pub fn send_ipi(&self, hart: usize) {
-        unsafe {
-            *self.msip(hart) = 1;
-        }
+        unsafe { core::ptr::write_volatile(self.msip(hart), 1) };
     }
 
     /// Clear IPI for a hart
     pub fn clear_ipi(&self, hart: usize) {
-        unsafe {
-            *self.msip(hart) = 0;
-        }
+        unsafe { core::ptr::write_volatile(self.msip(hart), 0) };
     }
 
     /// Check if IPI is pending for a hart
     pub fn ipi_pending(&self, hart: usize) -> bool {
-        unsafe { *self.msip(hart) != 0 }
+        unsafe { core::ptr::read_volatile(self.msip(hart)) != 0 }
     }
 }
 
@@ -455,5 +686,15 @@ mod tests {
     fn test_interrupt_controller() {
         let controller = RiscvInterruptController::new();
         assert_eq!(controller.max_irq(), 1024);
+        assert_eq!(controller.plic().base, 0x0C00_0000);
+        assert_eq!(controller.plic().num_sources(), 96);
+    }
+
+    #[test]
+    fn test_clint_layout() {
+        let clint = Clint::from_base(0x0200_0000);
+        assert_eq!(clint.msip(1) as usize, 0x0200_0004);
+        assert_eq!(clint.mtimecmp(1) as usize, 0x0200_4008);
+        assert_eq!(clint.mtime() as usize, 0x0200_BFF8);
     }
 }
@@ -71,6 +71,32 @@ pub const PLIC_MAX_CONTEXT_PER_HART: usize = 2;
 /// Priority value range (0-7, 7 = highest)
 pub const PLIC_MAX_PRIORITY: u32 = 7;
 
+/// Context of a hart's S-mode interface (M-mode is context 0)
+pub const PLIC_S_MODE_CONTEXT: usize = 1;
+
+/// Bytes between the enable banks of consecutive contexts
+const ENABLE_STRIDE: usize = 0x80;
+
+/// Bytes between the threshold/claim blocks of consecutive contexts
+const CONTEXT_STRIDE: usize = 0x1000;
+
+// ============================================================================
+// Register Access
+// ============================================================================
+
+unsafe fn read32(addr: usize) -> u32 {
+    core::ptr::read_volatile(addr as *const u32)
+}
+
+unsafe fn write32(addr: usize, value: u32) {
+    core::ptr::write_volatile(addr as *mut u32, value)
+}
+
+/// Index of a hart context in the PLIC's context numbering
+pub const fn context_index(hart_id: usize, context_id: usize) -> usize {
+    hart_id * PLIC_MAX_CONTEXT_PER_HART + context_id
+}
+
 /// ============================================================================
 /// PLIC IRQ Number
 /// ============================================================================
@@ -166,7 +192,7 @@ impl PlicHartContext {
     pub fn new(hart_id: usize, context_id: usize, plic_base: usize) -> Self {
         // Calculate context base address
         let context_offset = plic_offset::CONTEXT_BASE +
-            (hart_id * PLIC_MAX_CONTEXT_PER_HART + context_id) * 0x1000;
+            context_index(hart_id, context_id) * CONTEXT_STRIDE;
         let base = plic_base + context_offset;
 
         Self {
@@ -192,7 +218,7 @@ impl PlicHartContext {
     /// Interrupts with priority <= threshold will not be delivered.
     pub fn set_threshold(&self, threshold: PlicPriority) {
         self.threshold.store(threshold.into_inner(), Ordering::Release);
-        // TODO: Write to threshold register
+        unsafe { write32(self.threshold_addr(), threshold.into_inner()) };
     }
 
     /// Get the current threshold
@@ -204,18 +230,18 @@ impl PlicHartContext {
     ///
     /// Returns the IRQ number or `PlicIrq::NONE` if no interrupt is pending.
     pub fn claim(&self) -> PlicIrq {
-        // TODO: Read claim register and return IRQ number
-        PlicIrq::NONE
+        PlicIrq(unsafe { read32(self.claim_complete_addr()) })
     }
 
     /// Complete handling of an interrupt
     ///
+    /// The source is not delivered again until it has been completed.
+    ///
     /// # Arguments
     ///
     /// * `irq` - IRQ number to complete
     pub fn complete(&self, irq: PlicIrq) {
-        // TODO: Write IRQ to complete register
-        let _ = irq;
+        unsafe { write32(self.claim_complete_addr(), irq.into_inner()) };
     }
 }
 
@@ -247,27 +273,75 @@ impl Plic {
     ///
     /// * `base` - Base address of PLIC registers
     pub const fn new(base: usize) -> Self {
+        Self::with_config(base, 0, 0)
+    }
+
+    /// Create a PLIC with `num_sources` sources wired to `num_harts` harts
+    ///
+    /// The PLIC has no register reporting either count, so they come from
+    /// the board configuration (see [`super::super::RiscvIntcInfo`]).
+    pub const fn with_config(base: usize, num_sources: usize, num_harts: usize) -> Self {
         const INIT_CONTEXT: Option<PlicHartContext> = None;
         Self {
             base,
-            num_sources: AtomicUsize::new(0),
-            num_harts: AtomicUsize::new(0),
+            num_sources: AtomicUsize::new(num_sources),
+            num_harts: AtomicUsize::new(num_harts),
             contexts: [INIT_CONTEXT; PLIC_MAX_HARTS * PLIC_MAX_CONTEXT_PER_HART],
         }
     }
 
     /// Initialize the PLIC
     ///
-    /// This detects the number of interrupt sources and harts.
-    pub fn init(&self) -> Result<(), &'static str> {
-        // TODO: Implement PLIC initialization
-        // 1. Detect number of interrupt sources
-        // 2. Detect number of harts
-        // 3. Disable all interrupts
-        // 4. Set all priorities to 0
-        // 5. Set all thresholds to 0 (allow all interrupts)
+    /// Masks every source (priority 0), then sets up the S-mode context of
+    /// each hart with nothing enabled and a threshold of 0, so an enabled
+    /// source of any non-zero priority is delivered.
+    pub fn init(&mut self) -> Result<(), &'static str> {
+        let num_sources = self.num_sources();
+        let num_harts = self.num_harts();
+        if num_sources == 0 || num_sources > PLIC_MAX_SOURCES {
+            return Err("PLIC source count out of range");
+        }
+        if num_harts == 0 || num_harts > PLIC_MAX_HARTS {
+            return Err("PLIC hart count out of range");
+        }
+
+        for irq in 1..num_sources as u32 {
+            self.set_priority(PlicIrq(irq), PlicPriority::MIN);
+        }
 
-        Err("PLIC initialization not yet implemented")
+        for hart in 0..num_harts {
+            self.add_context(hart, PLIC_S_MODE_CONTEXT)?;
+            for reg in 0..num_sources.div_ceil(32) {
+                unsafe { write32(self.enable_bank(hart, PLIC_S_MODE_CONTEXT) + reg * 4, 0) };
+            }
+            if let Some(context) = self.get_context(hart, PLIC_S_MODE_CONTEXT) {
+                context.set_threshold(PlicPriority::MIN);
+                // Drain anything claimed before a warm restart
+                loop {
+                    let irq = context.claim();
+                    if irq == PlicIrq::NONE {
+                        break;
+                    }
+                    context.complete(irq);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that `irq` is a source this PLIC has
+    ///
+    /// `num_sources` is 0 until configured, when only the architectural
+    /// range is checked.
+    pub fn check_irq(&self, irq: PlicIrq) -> Result<(), &'static str> {
+        if !irq.is_valid() {
+            return Err("IRQ out of range");
+        }
+        let num_sources = self.num_sources();
+        if num_sources != 0 && irq.into_inner() as usize >= num_sources {
+            return Err("IRQ not implemented by this PLIC");
+        }
+        Ok(())
     }
 
     /// Get the priority register address for an interrupt
@@ -282,15 +356,12 @@ impl Plic {
     /// * `irq` - IRQ number
     /// * `priority` - Priority value (0-7)
     pub fn set_priority(&self, irq: PlicIrq, priority: PlicPriority) {
-        // TODO: Write to priority register
-        let _ = (irq, priority);
+        unsafe { write32(self.priority_addr(irq), priority.into_inner()) };
     }
 
     /// Get the priority for an interrupt
     pub fn get_priority(&self, irq: PlicIrq) -> PlicPriority {
-        // TODO: Read from priority register
-        let _ = irq;
-        PlicPriority::DEFAULT
+        PlicPriority::new(unsafe { read32(self.priority_addr(irq)) })
     }
 
     /// Get the pending register address for an interrupt
@@ -300,9 +371,14 @@ impl Plic {
 
     /// Check if an interrupt is pending
     pub fn is_pending(&self, irq: PlicIrq) -> bool {
-        // TODO: Read pending bit
-        let _ = irq;
-        false
+        let irq = irq.into_inner() as usize;
+        let pending = unsafe { read32(self.pending_addr() + (irq / 32) * 4) };
+        pending & (1 << (irq % 32)) != 0
+    }
+
+    /// First enable register of a hart context
+    pub fn enable_bank(&self, hart_id: usize, context_id: usize) -> usize {
+        self.base + plic_offset::ENABLE_BASE + context_index(hart_id, context_id) * ENABLE_STRIDE
     }
 
     /// Get the enable register address for a hart and interrupt
@@ -313,9 +389,23 @@ impl Plic {
     /// * `context_id` - Context ID (0 = M-mode, 1 = S-mode)
     /// * `irq` - IRQ number
     pub fn enable_addr(&self, hart_id: usize, context_id: usize, irq: PlicIrq) -> usize {
-        let enable_offset = (hart_id * PLIC_MAX_CONTEXT_PER_HART + context_id) * 0x80;
-        let reg_offset = (irq.into_inner() as usize / 32) * 4;
-        self.base + plic_offset::ENABLE_BASE + enable_offset + reg_offset
+        self.enable_bank(hart_id, context_id) + (irq.into_inner() as usize / 32) * 4
+    }
+
+    /// Set or clear the enable bit of `irq` in a hart context
+    fn set_enabled(&self, hart_id: usize, context_id: usize, irq: PlicIrq, enabled: bool) -> Result<(), &'static str> {
+        self.check_irq(irq)?;
+        if context_index(hart_id, context_id) >= self.contexts.len() {
+            return Err("Context index out of range");
+        }
+
+        let addr = self.enable_addr(hart_id, context_id, irq);
+        let bit = 1 << (irq.into_inner() % 32);
+        unsafe {
+            let value = read32(addr);
+            write32(addr, if enabled { value | bit } else { value & !bit });
+        }
+        Ok(())
     }
 
     /// Enable an interrupt for a hart
@@ -326,29 +416,17 @@ impl Plic {
     /// * `context_id` - Context ID (0 = M-mode, 1 = S-mode)
     /// * `irq` - IRQ number
     pub fn enable_irq(&self, hart_id: usize, context_id: usize, irq: PlicIrq) -> Result<(), &'static str> {
-        if !irq.is_valid() {
-            return Err("IRQ out of range");
-        }
-
-        // TODO: Set enable bit
-        let _ = (hart_id, context_id);
-        Ok(())
+        self.set_enabled(hart_id, context_id, irq, true)
     }
 
     /// Disable an interrupt for a hart
     pub fn disable_irq(&self, hart_id: usize, context_id: usize, irq: PlicIrq) -> Result<(), &'static str> {
-        if !irq.is_valid() {
-            return Err("IRQ out of range");
-        }
-
-        // TODO: Clear enable bit
-        let _ = (hart_id, context_id);
-        Ok(())
+        self.set_enabled(hart_id, context_id, irq, false)
     }
 
     /// Get or create a hart context
     pub fn get_context(&self, hart_id: usize, context_id: usize) -> Option<&PlicHartContext> {
-        let idx = hart_id * PLIC_MAX_CONTEXT_PER_HART + context_id;
+        let idx = context_index(hart_id, context_id);
         if idx >= self.contexts.len() {
             return None;
         }
@@ -357,7 +435,7 @@ impl Plic {
 
     /// Add a hart context
     pub fn add_context(&mut self, hart_id: usize, context_id: usize) -> Result<(), &'static str> {
-        let idx = hart_id * PLIC_MAX_CONTEXT_PER_HART + context_id;
+        let idx = context_index(hart_id, context_id);
         if idx >= self.contexts.len() {
             return Err("Context index out of range");
         }
@@ -440,4 +518,19 @@ mod tests {
         assert!(plic.get_context(0, 1).is_some());
         assert!(plic.get_context(1, 0).is_none());
     }
+
+    #[test]
+    fn test_register_math() {
+        let plic = Plic::new(0x0C000000);
+        assert_eq!(plic.priority_addr(PlicIrq::new(10)), 0x0C000028);
+        assert_eq!(plic.enable_addr(1, PLIC_S_MODE_CONTEXT, PlicIrq::new(33)), 0x0C002000 + 3 * 0x80 + 4);
+        assert_eq!(PlicHartContext::new(1, PLIC_S_MODE_CONTEXT, plic.base).claim_complete_addr(), 0x0C203004);
+    }
+
+    #[test]
+    fn test_check_irq_configured() {
+        let plic = Plic::with_config(0, 96, 1);
+        assert!(plic.check_irq(PlicIrq::new(95)).is_ok());
+        assert!(plic.check_irq(PlicIrq::new(96)).is_err());
+    }
 }
@@ -0,0 +1,35 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! RISC-V virtual address space layout
+//!
+//! With Sv39 the user half is the low 2^38 bytes and the kernel half the
+//! high 2^38 (addresses sign-extend bit 38); Sv48 doubles each half's bits
+//! to 47. Only the split is defined here so far: processes run on x86_64,
+//! whose layout (`arch::amd64::mm::layout`) places the regions inside the
+//! user half.
+
+use super::{SV39_VA_BITS, SV48_VA_BITS};
+
+/// Translation mode the layout is built for (Sv39)
+pub const VA_BITS: usize = SV39_VA_BITS;
+
+/// Bits of user virtual address space
+pub const USER_VA_BITS: u32 = VA_BITS as u32 - 1;
+
+/// End of the user half
+pub const USER_END: u64 = 1 << USER_VA_BITS;
+
+/// Start of the kernel half
+pub const KERNEL_START: u64 = !((1u64 << USER_VA_BITS) - 1);
+
+/// Check if an address is in the user half
+pub const fn is_user(addr: u64) -> bool {
+    addr < USER_END
+}
+
+const _: () = assert!(VA_BITS == SV39_VA_BITS || VA_BITS == SV48_VA_BITS);
+const _: () = assert!(USER_END <= KERNEL_START);
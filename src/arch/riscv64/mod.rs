@@ -13,16 +13,18 @@
 //! - [`arch`] - Architecture definitions, CPU features, and SBI interface
 //! - [`interrupt`] - PLIC and CLINT interrupt controller support
 //! - [`mm`] - Memory management unit (MMU) and page tables
+//! - [`trap`] - S-mode trap vector and interrupt dispatch
 
 pub mod arch;
 pub mod interrupt;
 pub mod mm;
+pub mod trap;
 
 // Re-exports
 pub use arch::{
-    HartInfo, RiscvFeatures, RiscvInterruptController,
+    HartInfo, RiscvFeatures, RiscvInterruptController, RiscvIntcInfo,
     SbiExtension, SbiFunction, SbiRet, SbiCall,
-    Clint, get_hart_info, set_hart_info, get_bootstrap_hart,
+    Clint, get_hart_info, set_hart_info, get_bootstrap_hart, current_hart,
     get_sbi_version, get_features,
     fence, fence_i, fence_s,
     RISCV_MAX_HARTS, RISCV_PAGE_SIZE, RISCV_PAGE_SHIFT,
@@ -0,0 +1,328 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! RISC-V S-mode Trap Vector
+//!
+//! [`init_hart`] points `stvec` at `riscv64_trap_entry`, which saves the
+//! interrupted registers in a [`TrapFrame`] on the current stack and calls
+//! [`riscv64_trap_handler`]. Interrupts are dispatched by `scause`:
+//!
+//! | Cause | Interrupt | Goes to |
+//! |-------|-----------|---------|
+//! | 1 | Supervisor software | The hook set by [`set_ipi_hook`] |
+//! | 5 | Supervisor timer | The hook set by [`set_timer_hook`] |
+//! | 9 | Supervisor external | [`InterruptHandler::handle_irq`] of each source claimed from the PLIC |
+//!
+//! The interrupt handler is installed with [`install`]. Traps are only
+//! taken from S-mode for now, on the kernel stack of the interrupted code;
+//! exceptions are fatal.
+//!
+//! [`InterruptHandler::handle_irq`]: crate::interrupt::InterruptHandler::handle_irq
+
+use crate::interrupt::Riscv64InterruptHandler;
+use crate::sync::SpinMutex;
+
+/// `scause` bit set for interrupts (clear for exceptions)
+pub const SCAUSE_INTERRUPT: u64 = 1 << 63;
+
+/// Supervisor software interrupt
+pub const IRQ_S_SOFT: u64 = 1;
+
+/// Supervisor timer interrupt
+pub const IRQ_S_TIMER: u64 = 5;
+
+/// Supervisor external interrupt
+pub const IRQ_S_EXT: u64 = 9;
+
+/// `sstatus.SIE`: interrupts enabled in S-mode
+const SSTATUS_SIE: u64 = 1 << 1;
+
+/// `scounteren.CY`: U-mode may read `cycle`
+pub const SCOUNTEREN_CY: u64 = 1 << 0;
+
+/// `scounteren.TM`: U-mode may read `time`
+pub const SCOUNTEREN_TM: u64 = 1 << 1;
+
+/// `scounteren.IR`: U-mode may read `instret`
+pub const SCOUNTEREN_IR: u64 = 1 << 2;
+
+/// Interrupt handler that external interrupts are dispatched to
+static INTERRUPTS: SpinMutex<Option<Riscv64InterruptHandler>> = SpinMutex::new(None);
+
+/// Run on every supervisor timer interrupt
+static TIMER_HOOK: SpinMutex<Option<fn()>> = SpinMutex::new(None);
+
+/// Run on every supervisor software interrupt
+static IPI_HOOK: SpinMutex<Option<fn()>> = SpinMutex::new(None);
+
+// ============================================================================
+// CSR Access
+// ============================================================================
+
+/// Read a CSR (0 off riscv64)
+macro_rules! csr_read {
+    ($csr:literal) => {{
+        #[cfg(target_arch = "riscv64")]
+        {
+            let value: u64;
+            unsafe { core::arch::asm!(concat!("csrr {}, ", $csr), out(reg) value, options(nomem, nostack)) };
+            value
+        }
+        #[cfg(not(target_arch = "riscv64"))]
+        {
+            0u64
+        }
+    }};
+}
+
+/// Set bits in a CSR (no-op off riscv64)
+macro_rules! csr_set {
+    ($csr:literal, $bits:expr) => {{
+        let bits: u64 = $bits;
+        #[cfg(target_arch = "riscv64")]
+        unsafe {
+            core::arch::asm!(concat!("csrs ", $csr, ", {}"), in(reg) bits, options(nostack));
+        }
+        #[cfg(not(target_arch = "riscv64"))]
+        let _ = bits;
+    }};
+}
+
+/// Clear bits in a CSR (no-op off riscv64)
+macro_rules! csr_clear {
+    ($csr:literal, $bits:expr) => {{
+        let bits: u64 = $bits;
+        #[cfg(target_arch = "riscv64")]
+        unsafe {
+            core::arch::asm!(concat!("csrc ", $csr, ", {}"), in(reg) bits, options(nostack));
+        }
+        #[cfg(not(target_arch = "riscv64"))]
+        let _ = bits;
+    }};
+}
+
+/// Write a CSR (no-op off riscv64)
+macro_rules! csr_write {
+    ($csr:literal, $value:expr) => {{
+        let value: u64 = $value;
+        #[cfg(target_arch = "riscv64")]
+        unsafe {
+            core::arch::asm!(concat!("csrw ", $csr, ", {}"), in(reg) value, options(nostack));
+        }
+        #[cfg(not(target_arch = "riscv64"))]
+        let _ = value;
+    }};
+}
+
+// ============================================================================
+// Trap Frame and Entry
+// ============================================================================
+
+/// Registers saved by `riscv64_trap_entry`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TrapFrame {
+    /// x0-x31, indexed by register number (`regs[2]` is the interrupted sp)
+    pub regs: [u64; 32],
+
+    /// Interrupted pc
+    pub sepc: u64,
+
+    /// `sstatus` at the trap
+    pub sstatus: u64,
+}
+
+/// Size of [`TrapFrame`] on the stack (16-byte aligned)
+pub const TRAP_FRAME_SIZE: usize = core::mem::size_of::<TrapFrame>();
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    ".section .text",
+    ".balign 4",
+    ".global riscv64_trap_entry",
+    "riscv64_trap_entry:",
+    "    addi sp, sp, -{frame}",
+    "    .irp n, 1,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31",
+    "    sd x\\n, (\\n * 8)(sp)",
+    "    .endr",
+    "    addi t0, sp, {frame}",
+    "    sd t0, 16(sp)",
+    "    csrr t0, sepc",
+    "    sd t0, 256(sp)",
+    "    csrr t0, sstatus",
+    "    sd t0, 264(sp)",
+    "    mv a0, sp",
+    "    call {handler}",
+    "    ld t0, 256(sp)",
+    "    csrw sepc, t0",
+    "    ld t0, 264(sp)",
+    "    csrw sstatus, t0",
+    "    .irp n, 1,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31",
+    "    ld x\\n, (\\n * 8)(sp)",
+    "    .endr",
+    "    addi sp, sp, {frame}",
+    "    sret",
+    frame = const TRAP_FRAME_SIZE,
+    handler = sym riscv64_trap_handler,
+);
+
+#[cfg(target_arch = "riscv64")]
+extern "C" {
+    fn riscv64_trap_entry();
+}
+
+/// Address of the trap entry (0 off riscv64)
+fn trap_entry_address() -> u64 {
+    #[cfg(target_arch = "riscv64")]
+    {
+        riscv64_trap_entry as usize as u64
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        0
+    }
+}
+
+// ============================================================================
+// Setup
+// ============================================================================
+
+/// Dispatch external interrupts to `handler`
+///
+/// The handler's controller should already be initialized. Handlers run
+/// with the interrupt handler locked, so they must not call [`install`].
+pub fn install(handler: Riscv64InterruptHandler) {
+    *INTERRUPTS.lock() = Some(handler);
+}
+
+/// Run `hook` on every timer interrupt
+///
+/// The hook must reprogram the timer (`RiscvInterruptController::set_timer`),
+/// or the interrupt stays pending.
+pub fn set_timer_hook(hook: fn()) {
+    *TIMER_HOOK.lock() = Some(hook);
+}
+
+/// Run `hook` on every software interrupt (IPI)
+pub fn set_ipi_hook(hook: fn()) {
+    *IPI_HOOK.lock() = Some(hook);
+}
+
+/// Take traps on this hart
+///
+/// Records `hart_id` in `tp` (see [`super::arch::current_hart`]), points
+/// `stvec` at the trap entry in direct mode, unmasks software, timer and
+/// external interrupts in `sie` and sets `sstatus.SIE`.
+///
+/// # Safety
+///
+/// Must run once per hart in S-mode, after [`install`] on the boot hart,
+/// and nothing else may use `tp`.
+pub unsafe fn init_hart(hart_id: usize) {
+    #[cfg(target_arch = "riscv64")]
+    core::arch::asm!("mv tp, {}", in(reg) hart_id, options(nomem, nostack));
+    #[cfg(not(target_arch = "riscv64"))]
+    let _ = hart_id;
+
+    // Direct mode: the entry is 4-byte aligned, so the MODE bits are 0
+    csr_write!("stvec", trap_entry_address());
+    csr_set!("sie", (1 << IRQ_S_SOFT) | (1 << IRQ_S_TIMER) | (1 << IRQ_S_EXT));
+    csr_set!("sstatus", SSTATUS_SIE);
+}
+
+/// Allow or deny U-mode counter reads on this hart
+///
+/// The riscv64 counterpart of `CR4.TSD`: with `allow`, userspace may use
+/// `rdtime`, `rdcycle` and `rdinstret`; otherwise they trap. The `time`
+/// frequency comes from the devicetree `timebase-frequency`. The SBI
+/// firmware must also enable the counters in `mcounteren`, which
+/// OpenSBI does.
+///
+/// # Safety
+///
+/// Must run in S-mode on each hart, before it enters U-mode.
+pub unsafe fn set_user_counter_access(allow: bool) {
+    let counters = SCOUNTEREN_CY | SCOUNTEREN_TM | SCOUNTEREN_IR;
+    if allow {
+        csr_set!("scounteren", counters);
+    } else {
+        csr_clear!("scounteren", counters);
+    }
+}
+
+// ============================================================================
+// Dispatch
+// ============================================================================
+
+/// Whether `scause` is an interrupt, and its cause code
+pub const fn decode_scause(scause: u64) -> (bool, u64) {
+    (scause & SCAUSE_INTERRUPT != 0, scause & !SCAUSE_INTERRUPT)
+}
+
+/// Claim and handle every external interrupt pending on this hart
+fn handle_external() {
+    let interrupts = INTERRUPTS.lock();
+    let Some(handler) = interrupts.as_ref() else {
+        return;
+    };
+    while let Some(irq) = handler.controller().claim() {
+        handler.handle_irq(irq as u64);
+    }
+}
+
+/// Rust side of the trap vector
+#[no_mangle]
+pub extern "C" fn riscv64_trap_handler(frame: &mut TrapFrame) {
+    let scause = csr_read!("scause");
+    match decode_scause(scause) {
+        (true, IRQ_S_SOFT) => {
+            csr_clear!("sip", 1 << IRQ_S_SOFT);
+            let hook = *IPI_HOOK.lock();
+            if let Some(hook) = hook {
+                hook();
+            }
+        }
+        (true, IRQ_S_TIMER) => {
+            let hook = *TIMER_HOOK.lock();
+            match hook {
+                Some(hook) => hook(),
+                // Nobody wants ticks: stop them rather than trap forever
+                None => csr_clear!("sie", 1 << IRQ_S_TIMER),
+            }
+        }
+        (true, IRQ_S_EXT) => handle_external(),
+        (true, cause) => panic!("unexpected S-mode interrupt {} at {:#x}", cause, frame.sepc),
+        (false, cause) => panic!(
+            "S-mode exception {} at {:#x} (stval {:#x})",
+            cause,
+            frame.sepc,
+            csr_read!("stval"),
+        ),
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trap_frame_layout() {
+        assert_eq!(TRAP_FRAME_SIZE, 272);
+        assert_eq!(TRAP_FRAME_SIZE % 16, 0);
+        assert_eq!(core::mem::offset_of!(TrapFrame, sepc), 256);
+        assert_eq!(core::mem::offset_of!(TrapFrame, sstatus), 264);
+    }
+
+    #[test]
+    fn test_decode_scause() {
+        assert_eq!(decode_scause(SCAUSE_INTERRUPT | IRQ_S_EXT), (true, IRQ_S_EXT));
+        assert_eq!(decode_scause(13), (false, 13));
+    }
+}
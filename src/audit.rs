@@ -0,0 +1,107 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Audit Log
+//!
+//! A fixed-size ring of security- and policy-relevant kernel events
+//! (limit enforcement, denied operations). Each record is also echoed to
+//! the debug port as it is logged. When the ring is full the oldest
+//! record is overwritten.
+//!
+//! Logging never allocates and gives up rather than spin if the log is
+//! busy, so it is safe from interrupt context.
+
+use crate::sync::SpinMutex;
+use crate::kinfo;
+
+/// Number of records kept
+pub const AUDIT_LOG_SIZE: usize = 64;
+
+/// Audit event kinds
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditKind {
+    /// A process exceeded its job's CPU-time limit (soft, SIGXCPU-style)
+    CpuLimitExceeded = 1,
+    /// A process was killed for exceeding its job's CPU-time limit plus grace
+    CpuLimitKilled = 2,
+    /// A process was killed with its job (value: the job that was killed)
+    JobKilled = 3,
+}
+
+impl AuditKind {
+    /// Short name for log output
+    pub const fn name(self) -> &'static str {
+        match self {
+            AuditKind::CpuLimitExceeded => "cpu-limit-exceeded",
+            AuditKind::CpuLimitKilled => "cpu-limit-killed",
+            AuditKind::JobKilled => "job-killed",
+        }
+    }
+}
+
+/// A single audit record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Sequence number (monotonic, starts at 1)
+    pub seq: u64,
+    /// Event kind
+    pub kind: AuditKind,
+    /// Process the event applies to
+    pub pid: u32,
+    /// Job the process belongs to
+    pub job_id: u64,
+    /// Event-specific value (e.g. CPU time used in nanoseconds)
+    pub value: u64,
+}
+
+struct AuditLog {
+    records: [Option<AuditRecord>; AUDIT_LOG_SIZE],
+    next_seq: u64,
+}
+
+static AUDIT_LOG: SpinMutex<AuditLog> = SpinMutex::named(
+    AuditLog { records: [None; AUDIT_LOG_SIZE], next_seq: 1 },
+    &crate::sync::lockstat::AUDIT_LOG,
+);
+
+/// Append a record to the audit log
+///
+/// # Returns
+/// The record's sequence number, or `None` if the log was busy
+pub fn log(kind: AuditKind, pid: u32, job_id: u64, value: u64) -> Option<u64> {
+    let seq = {
+        let mut log = AUDIT_LOG.try_lock()?;
+        let seq = log.next_seq;
+        log.next_seq += 1;
+        log.records[(seq as usize) % AUDIT_LOG_SIZE] = Some(AuditRecord { seq, kind, pid, job_id, value });
+        seq
+    };
+
+    kinfo!("[AUDIT] {} pid={} job={} value={}", kind.name(), pid, job_id, value);
+
+    Some(seq)
+}
+
+/// Copy records with sequence number `>= since` into `out`, oldest first
+///
+/// # Returns
+/// Number of records copied
+pub fn read(since: u64, out: &mut [AuditRecord]) -> usize {
+    let log = AUDIT_LOG.lock();
+    let first = log.next_seq.saturating_sub(AUDIT_LOG_SIZE as u64).max(since).max(1);
+    let mut n = 0;
+    for seq in first..log.next_seq {
+        if n == out.len() {
+            break;
+        }
+        if let Some(rec) = log.records[(seq as usize) % AUDIT_LOG_SIZE] {
+            out[n] = rec;
+            n += 1;
+        }
+    }
+    n
+}
@@ -0,0 +1,170 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Command Line
+//!
+//! This module stores the boot command line passed by the loader and
+//! provides simple lookups for boot-time options.
+//!
+//! # Format
+//!
+//! The command line is a whitespace-separated list of options. Each option
+//! is either a bare flag (`ramdisk.allow_unsigned`) or a `key=value` pair
+//! (`log.level=debug`).
+//!
+//! # Usage
+//!
+//! ```ignore
+//! // During early boot (UEFI load options are UCS-2)
+//! cmdline::init_from_ucs2(load_options);
+//!
+//! if cmdline::has_flag("ramdisk.allow_unsigned") {
+//!     // ...
+//! }
+//! ```
+
+use crate::sync::SpinMutex;
+
+/// Maximum command line length in bytes
+pub const CMDLINE_MAX: usize = 512;
+
+/// Stored command line (ASCII, not null-terminated)
+struct CmdlineBuffer {
+    data: [u8; CMDLINE_MAX],
+    len: usize,
+}
+
+/// Global command line storage
+static CMDLINE: SpinMutex<CmdlineBuffer> = SpinMutex::new(CmdlineBuffer {
+    data: [0; CMDLINE_MAX],
+    len: 0,
+});
+
+/// Set the command line from an ASCII/UTF-8 byte string
+///
+/// Input longer than [`CMDLINE_MAX`] is truncated. A trailing null
+/// terminator, if present, ends the command line.
+pub fn init(bytes: &[u8]) {
+    let mut cmdline = CMDLINE.lock();
+    let mut len = 0;
+    for &b in bytes {
+        if b == 0 || len >= CMDLINE_MAX {
+            break;
+        }
+        cmdline.data[len] = b;
+        len += 1;
+    }
+    cmdline.len = len;
+}
+
+/// Set the command line from UCS-2 little-endian bytes (UEFI load options)
+///
+/// Non-ASCII characters are replaced with `?`.
+pub fn init_from_ucs2(bytes: &[u8]) {
+    let mut cmdline = CMDLINE.lock();
+    let mut len = 0;
+    for pair in bytes.chunks_exact(2) {
+        let c = u16::from_le_bytes([pair[0], pair[1]]);
+        if c == 0 || len >= CMDLINE_MAX {
+            break;
+        }
+        cmdline.data[len] = if c < 0x80 { c as u8 } else { b'?' };
+        len += 1;
+    }
+    cmdline.len = len;
+}
+
+/// Copy the raw command line into `buf`
+///
+/// # Returns
+///
+/// Number of bytes copied
+pub fn copy_to(buf: &mut [u8]) -> usize {
+    let cmdline = CMDLINE.lock();
+    let n = core::cmp::min(buf.len(), cmdline.len);
+    buf[..n].copy_from_slice(&cmdline.data[..n]);
+    n
+}
+
+/// Check whether a bare flag (or a `key=...` option) is present
+pub fn has_flag(name: &str) -> bool {
+    let cmdline = CMDLINE.lock();
+    find_option(&cmdline.data[..cmdline.len], name.as_bytes()).is_some()
+}
+
+/// Look up the value of a `key=value` option and copy it into `buf`
+///
+/// # Returns
+///
+/// The value as a string slice of `buf`, or None if the key is absent
+/// or has no value
+pub fn get<'a>(key: &str, buf: &'a mut [u8]) -> Option<&'a str> {
+    let cmdline = CMDLINE.lock();
+    let value = find_option(&cmdline.data[..cmdline.len], key.as_bytes())??;
+    let n = core::cmp::min(buf.len(), value.len());
+    buf[..n].copy_from_slice(&value[..n]);
+    core::str::from_utf8(&buf[..n]).ok()
+}
+
+/// Check whether a boolean option is enabled
+///
+/// A bare flag, `key=1`, `key=on` and `key=true` are treated as enabled.
+pub fn get_bool(key: &str) -> bool {
+    let cmdline = CMDLINE.lock();
+    match find_option(&cmdline.data[..cmdline.len], key.as_bytes()) {
+        Some(None) => true,
+        Some(Some(v)) => v == b"1" || v == b"on" || v == b"true",
+        None => false,
+    }
+}
+
+/// Find an option in a command line
+///
+/// # Returns
+///
+/// - `None` if the option is absent
+/// - `Some(None)` if it is a bare flag
+/// - `Some(Some(value))` if it is a `key=value` pair
+fn find_option<'a>(cmdline: &'a [u8], key: &[u8]) -> Option<Option<&'a [u8]>> {
+    for token in cmdline.split(|&b| b == b' ' || b == b'\t') {
+        if token.is_empty() {
+            continue;
+        }
+        match token.iter().position(|&b| b == b'=') {
+            Some(eq) if &token[..eq] == key => return Some(Some(&token[eq + 1..])),
+            None if token == key => return Some(None),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_option() {
+        let line = b"quiet ramdisk.allow_unsigned log.level=debug";
+        assert_eq!(find_option(line, b"quiet"), Some(None));
+        assert_eq!(find_option(line, b"log.level"), Some(Some(&b"debug"[..])));
+        assert_eq!(find_option(line, b"log"), None);
+        assert_eq!(find_option(line, b"missing"), None);
+    }
+
+    #[test]
+    fn test_ucs2_init() {
+        let ucs2 = [b'a', 0, b'=', 0, b'1', 0, 0, 0];
+        init_from_ucs2(&ucs2);
+        assert!(get_bool("a"));
+        let mut buf = [0u8; 8];
+        assert_eq!(get("a", &mut buf), Some("1"));
+    }
+}
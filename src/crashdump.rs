@@ -0,0 +1,527 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Crash Dumps
+//!
+//! On real hardware a panic report goes to a debug port nobody listens
+//! to. Booting with `crashkernel=<size>[@<addr>]` sets aside a region of
+//! physical memory ([`reserve`]), and the panic handler writes a dump into
+//! it ([`save`]): a [`DumpHeader`] with a magic number and checksum, the
+//! registers at the time of the panic, the kernel log ring
+//! ([`crate::klog`]) and the most recent trace events ([`crate::trace`]).
+//!
+//! RAM keeps its contents across a warm reboot. On the next boot with the
+//! same region, [`init`] finds the dump, keeps a text rendering of it for
+//! `/proc/last-crash`, and clears the region for this boot's use. A dump
+//! the firmware overwrote while rebooting fails the checksum and is
+//! ignored.
+//!
+//! # Region
+//!
+//! The size takes a `K` or `M` suffix and is rounded up to whole pages,
+//! between [`MIN_REGION_SIZE`] and [`MAX_REGION_SIZE`]. Without `@<addr>`
+//! the region ends at the top of the kernel zone, so it is at the same
+//! address on every boot; an explicit address must keep it inside
+//! `[`[`REGION_MIN_ADDR`]`, `[`REGION_MAX_END`]`)`.
+//!
+//! # Layout
+//!
+//! | Offset | Contents |
+//! |--------|----------|
+//! | 0 | [`DumpHeader`] |
+//! | [`HEADER_SIZE`] | `log_len` bytes of log, oldest first |
+//! | after the log | `trace_len` trace events of [`TRACE_RECORD_SIZE`] bytes, oldest first |
+//!
+//! The newest log output and trace events are kept when they do not all
+//! fit; trace events get at most half of the space.
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::sync::SpinMutex;
+use crate::trace::{TraceEvent, TraceKind};
+
+/// Command-line option that reserves the region
+pub const CRASHKERNEL_OPTION: &str = "crashkernel";
+
+/// First bytes of a valid dump
+pub const DUMP_MAGIC: u64 = u64::from_le_bytes(*b"RXCRASH\0");
+
+/// Dump format version
+pub const DUMP_VERSION: u32 = 1;
+
+/// Smallest region
+pub const MIN_REGION_SIZE: u64 = 16 * 1024;
+
+/// Largest region
+pub const MAX_REGION_SIZE: u64 = 1024 * 1024;
+
+/// Lowest region address (above the boot stack)
+pub const REGION_MIN_ADDR: u64 = 0x0024_0000;
+
+/// End of the kernel zone; default end of the region
+pub const REGION_MAX_END: u64 = 0x0100_0000;
+
+/// Most trace events saved
+pub const MAX_TRACE_EVENTS: usize = 256;
+
+/// Bytes per saved trace event
+pub const TRACE_RECORD_SIZE: usize = 32;
+
+/// Size of [`DumpHeader`]
+pub const HEADER_SIZE: usize = core::mem::size_of::<DumpHeader>();
+
+/// Bytes of the header before the checksummed part (magic and checksum)
+const CHECKSUM_START: usize = 16;
+
+/// Size of a page
+const PAGE_SIZE: u64 = 0x1000;
+
+// ============================================================================
+// Dump Format
+// ============================================================================
+
+/// Registers of the panicking CPU
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Registers {
+    /// Stack pointer in the panic handler
+    pub rsp: u64,
+    /// Frame pointer in the panic handler
+    pub rbp: u64,
+    /// Flags (interrupt state)
+    pub rflags: u64,
+    /// Control register 0
+    pub cr0: u64,
+    /// Last page fault address
+    pub cr2: u64,
+    /// Page table root
+    pub cr3: u64,
+    /// Control register 4
+    pub cr4: u64,
+}
+
+/// Start of a dump
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpHeader {
+    /// [`DUMP_MAGIC`]
+    pub magic: u64,
+    /// FNV-1a of everything after this field, up to the end of the trace
+    pub checksum: u64,
+    /// [`DUMP_VERSION`]
+    pub version: u32,
+    /// CPU that panicked
+    pub cpu: u32,
+    /// Time since boot, in nanoseconds
+    pub uptime_ns: u64,
+    /// Registers at the panic
+    pub regs: Registers,
+    /// Bytes of log
+    pub log_len: u32,
+    /// Number of trace events
+    pub trace_len: u32,
+}
+
+/// A dump read back from a region
+#[derive(Debug, Clone, Copy)]
+pub struct Dump<'a> {
+    /// The header
+    pub header: DumpHeader,
+    /// Log text, oldest first
+    pub log: &'a [u8],
+    trace: &'a [u8],
+}
+
+impl Dump<'_> {
+    /// Saved trace events, oldest first
+    pub fn events(&self) -> impl Iterator<Item = TraceEvent> + '_ {
+        self.trace.chunks_exact(TRACE_RECORD_SIZE).map(decode_event)
+    }
+}
+
+fn fnv1a64(data: &[u8]) -> u64 {
+    crate::fs::manifest::fnv1a64(data)
+}
+
+fn encode_event(event: &TraceEvent, out: &mut [u8]) {
+    out[0..8].copy_from_slice(&event.ts_ns.to_le_bytes());
+    out[8..16].copy_from_slice(&event.id.to_le_bytes());
+    out[16..24].copy_from_slice(&event.id2.to_le_bytes());
+    out[24..26].copy_from_slice(&(event.kind as u16).to_le_bytes());
+    out[26..28].copy_from_slice(&event.cpu.to_le_bytes());
+    out[28..32].copy_from_slice(&event.arg.to_le_bytes());
+}
+
+fn decode_event(raw: &[u8]) -> TraceEvent {
+    let u64_at = |i: usize| u64::from_le_bytes(raw[i..i + 8].try_into().unwrap());
+    TraceEvent {
+        ts_ns: u64_at(0),
+        id: u64_at(8),
+        id2: u64_at(16),
+        kind: TraceKind::from_raw(u16::from_le_bytes([raw[24], raw[25]])),
+        cpu: u16::from_le_bytes([raw[26], raw[27]]),
+        arg: u32::from_le_bytes(raw[28..32].try_into().unwrap()),
+    }
+}
+
+/// Write a dump into `buf`
+///
+/// # Arguments
+///
+/// * `log` - Log text, oldest first, in two parts (as a ring holds it)
+/// * `events` - Trace events, oldest first
+/// * `event_count` - Number of events `events` yields
+///
+/// # Returns
+///
+/// Bytes written, or 0 if `buf` cannot hold the header
+pub fn write_dump(
+    buf: &mut [u8],
+    cpu: u32,
+    uptime_ns: u64,
+    regs: Registers,
+    log: (&[u8], &[u8]),
+    events: impl Iterator<Item = TraceEvent>,
+    event_count: usize,
+) -> usize {
+    let Some(space) = buf.len().checked_sub(HEADER_SIZE) else {
+        return 0;
+    };
+    let trace_len = event_count.min(MAX_TRACE_EVENTS).min(space / 2 / TRACE_RECORD_SIZE);
+    let log_len = (log.0.len() + log.1.len()).min(space - trace_len * TRACE_RECORD_SIZE);
+
+    // The newest `log_len` bytes
+    let mut at = HEADER_SIZE;
+    let mut skip = log.0.len() + log.1.len() - log_len;
+    for part in [log.0, log.1] {
+        let kept = &part[skip.min(part.len())..];
+        skip -= part.len() - kept.len();
+        buf[at..at + kept.len()].copy_from_slice(kept);
+        at += kept.len();
+    }
+    for event in events.skip(event_count - trace_len).take(trace_len) {
+        encode_event(&event, &mut buf[at..at + TRACE_RECORD_SIZE]);
+        at += TRACE_RECORD_SIZE;
+    }
+
+    let mut header = DumpHeader {
+        magic: DUMP_MAGIC,
+        checksum: 0,
+        version: DUMP_VERSION,
+        cpu,
+        uptime_ns,
+        regs,
+        log_len: log_len as u32,
+        trace_len: trace_len as u32,
+    };
+    // SAFETY: `buf` holds at least HEADER_SIZE bytes
+    unsafe { core::ptr::write_unaligned(buf.as_mut_ptr() as *mut DumpHeader, header) };
+    header.checksum = fnv1a64(&buf[CHECKSUM_START..at]);
+    unsafe { core::ptr::write_unaligned(buf.as_mut_ptr() as *mut DumpHeader, header) };
+    at
+}
+
+/// Read a dump from `buf`
+///
+/// # Returns
+///
+/// `None` if `buf` holds no complete dump with a valid checksum
+pub fn parse_dump(buf: &[u8]) -> Option<Dump<'_>> {
+    if buf.len() < HEADER_SIZE {
+        return None;
+    }
+    // SAFETY: `buf` holds at least HEADER_SIZE bytes; every bit pattern is a valid header
+    let header = unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const DumpHeader) };
+    if header.magic != DUMP_MAGIC || header.version != DUMP_VERSION {
+        return None;
+    }
+    let log_end = HEADER_SIZE.checked_add(header.log_len as usize)?;
+    let end = log_end.checked_add((header.trace_len as usize).checked_mul(TRACE_RECORD_SIZE)?)?;
+    if end > buf.len() || fnv1a64(&buf[CHECKSUM_START..end]) != header.checksum {
+        return None;
+    }
+    Some(Dump { header, log: &buf[HEADER_SIZE..log_end], trace: &buf[log_end..end] })
+}
+
+/// Write a dump as text, as `/proc/last-crash` shows it
+pub fn render(dump: &Dump, out: &mut impl Write) -> fmt::Result {
+    let h = &dump.header;
+    let r = &h.regs;
+    writeln!(out, "Crash on CPU {} at {}.{:09} s after boot", h.cpu, h.uptime_ns / 1_000_000_000, h.uptime_ns % 1_000_000_000)?;
+    writeln!(out, "rsp={:#018x} rbp={:#018x} rflags={:#x}", r.rsp, r.rbp, r.rflags)?;
+    writeln!(out, "cr0={:#x} cr2={:#x} cr3={:#x} cr4={:#x}", r.cr0, r.cr2, r.cr3, r.cr4)?;
+
+    writeln!(out, "\nLog ({} bytes):", dump.log.len())?;
+    for chunk in dump.log.utf8_chunks() {
+        out.write_str(chunk.valid())?;
+        if !chunk.invalid().is_empty() {
+            out.write_char(char::REPLACEMENT_CHARACTER)?;
+        }
+    }
+    if !dump.log.ends_with(b"\n") {
+        out.write_char('\n')?;
+    }
+
+    writeln!(out, "\nTrace ({} events):", h.trace_len)?;
+    for e in dump.events() {
+        writeln!(out, "  {:>15} cpu{} {} id={} id2={} arg={}", e.ts_ns, e.cpu, e.kind.name(), e.id, e.id2, e.arg)?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Region
+// ============================================================================
+
+/// A reserved crash dump region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashRegion {
+    /// Physical address of the first byte
+    pub base: u64,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// Parse a size or address: decimal or `0x` hex, with an optional `K` or `M`
+fn parse_amount(s: &str) -> Option<u64> {
+    let (digits, shift) = match s.as_bytes().last()? {
+        b'K' | b'k' => (&s[..s.len() - 1], 10),
+        b'M' | b'm' => (&s[..s.len() - 1], 20),
+        _ => (s, 0),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    value.checked_mul(1 << shift)
+}
+
+/// Parse a `crashkernel=` value: `<size>[@<addr>]`
+///
+/// # Returns
+///
+/// `None` if the value is malformed or the region is out of bounds
+pub fn parse_region(value: &str) -> Option<CrashRegion> {
+    let (size, addr) = match value.split_once('@') {
+        Some((size, addr)) => (size, Some(parse_amount(addr)?)),
+        None => (value, None),
+    };
+    let size = parse_amount(size)?.checked_next_multiple_of(PAGE_SIZE)?;
+    if !(MIN_REGION_SIZE..=MAX_REGION_SIZE).contains(&size) {
+        return None;
+    }
+    let base = addr.unwrap_or(REGION_MAX_END - size);
+    let in_bounds = base % PAGE_SIZE == 0 && base >= REGION_MIN_ADDR && base.checked_add(size)? <= REGION_MAX_END;
+    in_bounds.then_some(CrashRegion { base, size })
+}
+
+// ============================================================================
+// Boot and Panic
+// ============================================================================
+
+/// Region base and size (0 = no region)
+static REGION_BASE: AtomicU64 = AtomicU64::new(0);
+static REGION_SIZE: AtomicU64 = AtomicU64::new(0);
+
+/// Set once the previous dump has been read; the region may be written
+static ARMED: AtomicBool = AtomicBool::new(false);
+
+/// Set by the first panic to save a dump
+static SAVING: AtomicBool = AtomicBool::new(false);
+
+/// Text of the previous boot's dump
+static LAST_CRASH: SpinMutex<Option<String>> = SpinMutex::new(None);
+
+/// The reserved region
+pub fn region() -> Option<CrashRegion> {
+    match REGION_SIZE.load(Ordering::Acquire) {
+        0 => None,
+        size => Some(CrashRegion { base: REGION_BASE.load(Ordering::Relaxed), size }),
+    }
+}
+
+/// The region's memory
+///
+/// # Safety
+///
+/// Nothing else may access the region meanwhile.
+unsafe fn region_bytes(region: CrashRegion) -> &'static mut [u8] {
+    let vaddr = crate::mm::pmm::paddr_to_vaddr(region.base);
+    core::slice::from_raw_parts_mut(vaddr as *mut u8, region.size as usize)
+}
+
+/// Reserve the region requested with `crashkernel=`
+///
+/// Called from `init::pmm_init` once the kernel zone exists, before
+/// anything is allocated from it.
+pub fn reserve() -> Option<CrashRegion> {
+    let mut buf = [0u8; 32];
+    let region = parse_region(crate::cmdline::get(CRASHKERNEL_OPTION, &mut buf)?)?;
+    let pages = (region.size / PAGE_SIZE) as usize;
+    if crate::mm::pmm::pmm_reserve_pages(region.base, pages) != crate::mm::Status::OK {
+        return None;
+    }
+    REGION_BASE.store(region.base, Ordering::Relaxed);
+    REGION_SIZE.store(region.size, Ordering::Release);
+    Some(region)
+}
+
+/// Pick up the previous boot's dump and arm the region for this boot
+///
+/// Called once the heap is up.
+///
+/// # Returns
+///
+/// `true` if a dump from the previous boot was found
+pub fn init() -> bool {
+    let Some(region) = region() else {
+        return false;
+    };
+    // SAFETY: the region is reserved and not armed yet
+    let bytes = unsafe { region_bytes(region) };
+    let found = match parse_dump(bytes) {
+        Some(dump) => {
+            let mut text = String::new();
+            let _ = render(&dump, &mut text);
+            *LAST_CRASH.lock() = Some(text);
+            true
+        }
+        None => false,
+    };
+    bytes[..HEADER_SIZE].fill(0);
+    ARMED.store(true, Ordering::Release);
+    found
+}
+
+/// Text of the previous boot's dump, if there was one
+pub fn last_crash() -> Option<String> {
+    LAST_CRASH.lock().clone()
+}
+
+/// Registers of the calling CPU
+fn capture_registers() -> Registers {
+    use crate::arch::amd64::registers;
+
+    let (rsp, rbp, rflags): (u64, u64, u64);
+    unsafe {
+        core::arch::asm!(
+            "mov {}, rsp",
+            "mov {}, rbp",
+            "pushfq",
+            "pop {}",
+            out(reg) rsp,
+            out(reg) rbp,
+            out(reg) rflags,
+        );
+        Registers {
+            rsp,
+            rbp,
+            rflags,
+            cr0: registers::x86_get_cr0(),
+            cr2: registers::x86_get_cr2(),
+            cr3: registers::x86_get_cr3(),
+            cr4: registers::x86_get_cr4(),
+        }
+    }
+}
+
+/// Save a dump of the current state; called by the panic handler
+///
+/// Does nothing without an armed region, and only the first panic saves.
+/// Never allocates or waits: a log or trace buffer that is locked is left
+/// out.
+pub fn save() {
+    if !ARMED.load(Ordering::Acquire) || SAVING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let Some(region) = region() else {
+        return;
+    };
+    let regs = capture_registers();
+    let cpu = crate::interrupt::affinity::current_cpu() as u32;
+    let uptime_ns = crate::time::Instant::now().as_nanos();
+    // SAFETY: armed, and SAVING keeps other panics out
+    let bytes = unsafe { region_bytes(region) };
+
+    crate::klog::try_with(|log| {
+        let saved = crate::trace::try_with_buffer(|trace| {
+            let count = trace.len();
+            write_dump(bytes, cpu, uptime_ns, regs, log.as_slices(), trace.iter().copied(), count)
+        });
+        if saved.is_none() {
+            write_dump(bytes, cpu, uptime_ns, regs, log.as_slices(), core::iter::empty(), 0);
+        }
+    })
+    .unwrap_or_else(|| {
+        write_dump(bytes, cpu, uptime_ns, regs, (&[], &[]), core::iter::empty(), 0);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn event(ts_ns: u64) -> TraceEvent {
+        TraceEvent { ts_ns, id: 7, kind: TraceKind::Wakeup, id2: 9, cpu: 1, arg: 3 }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut buf = vec![0u8; 4096];
+        let regs = Registers { rsp: 0x1000, cr2: 0xdead, ..Registers::default() };
+        let events = [event(1), event(2)];
+        let n = write_dump(&mut buf, 2, 1_500_000_000, regs, (b"boot ", b"panic\n"), events.iter().copied(), 2);
+        assert_eq!(n, HEADER_SIZE + 11 + 2 * TRACE_RECORD_SIZE);
+
+        let dump = parse_dump(&buf).unwrap();
+        assert_eq!(dump.header.cpu, 2);
+        assert_eq!(dump.header.regs, regs);
+        assert_eq!(dump.log, b"boot panic\n");
+        assert!(dump.events().eq(events.iter().copied()));
+
+        let mut text = String::new();
+        render(&dump, &mut text).unwrap();
+        assert!(text.starts_with("Crash on CPU 2 at 1.500000000 s after boot\n"));
+        assert!(text.contains("cr2=0xdead"));
+        assert!(text.contains("boot panic\n"));
+        assert!(text.contains("cpu1 wakeup id=7 id2=9 arg=3"));
+
+        // Any change breaks the checksum
+        buf[HEADER_SIZE] ^= 1;
+        assert!(parse_dump(&buf).is_none());
+        assert!(parse_dump(&[0u8; 4096]).is_none());
+    }
+
+    #[test]
+    fn test_keeps_newest() {
+        // Room for 136 bytes: 2 events (half at most) and 72 bytes of log
+        let mut buf = vec![0u8; HEADER_SIZE + 136];
+        let (old, new) = ([b'a'; 50], [b'b'; 50]);
+        write_dump(&mut buf, 0, 0, Registers::default(), (&old, &new), (0..10).map(event), 10);
+
+        let dump = parse_dump(&buf).unwrap();
+        let saved: Vec<u64> = dump.events().map(|e| e.ts_ns).collect();
+        assert_eq!(saved, [8, 9]);
+        assert_eq!(dump.log, [&old[..22], &new[..]].concat());
+        assert_eq!(write_dump(&mut [0u8; 8], 0, 0, Registers::default(), (b"", b""), core::iter::empty(), 0), 0);
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(parse_region("64K"), Some(CrashRegion { base: REGION_MAX_END - 0x1_0000, size: 0x1_0000 }));
+        assert_eq!(parse_region("1M@0x300000"), Some(CrashRegion { base: 0x30_0000, size: 0x10_0000 }));
+        assert_eq!(parse_region("20000").map(|r| r.size), Some(0x5000));
+        assert_eq!(parse_region("4K"), None);
+        assert_eq!(parse_region("2M"), None);
+        assert_eq!(parse_region("64K@0x100000"), None);
+        assert_eq!(parse_region("64K@0xFF8000"), None);
+        assert_eq!(parse_region("64K@0x300800"), None);
+        assert_eq!(parse_region("lots"), None);
+    }
+}
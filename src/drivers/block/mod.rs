@@ -0,0 +1,248 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Block Device Layer
+//!
+//! A block device is storage addressed in fixed-size blocks
+//! ([`BlockDevice`]). Drivers [`register`] their devices here; devfs
+//! shows each one as `/dev/<name>`, and filesystem drivers look them up
+//! by name ([`find`]). Devices are never removed, so an index stays
+//! valid for the life of the system.
+//!
+//! A disk added with [`add_disk`] has its partition table read
+//! ([`partition`]), and each partition is registered as a block device
+//! of its own after the disk (`vda`, then `vda1`, `vda2`, ...).
+//!
+//! [`read_at`] and [`write_at`] add byte-granular access on top of the
+//! block interface, for device nodes opened by userspace: whole blocks
+//! are transferred directly, and a partial block at either end is read
+//! (and for writes, modified and written back) through a bounce buffer.
+//!
+//! # Devices
+//!
+//! | Name | Driver |
+//! |------|--------|
+//! | `ram0` | RAM-backed disk for filesystem tests ([`ram`]) |
+//! | `<disk>N`, `<disk>pN` | Partition N of a disk ([`partition`]) |
+
+pub mod partition;
+pub mod ram;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::fs::ramdisk::Errno;
+use crate::sync::SpinMutex;
+use crate::{kinfo, kwarn};
+
+/// Most block devices that can be registered
+pub const MAX_DEVICES: usize = 16;
+
+/// Storage addressed in fixed-size blocks
+pub trait BlockDevice: Send + Sync {
+    /// Device name, as it appears under `/dev`
+    fn name(&self) -> &str;
+
+    /// Block size in bytes (a power of two)
+    fn block_size(&self) -> usize;
+
+    /// Number of blocks
+    fn num_blocks(&self) -> u64;
+
+    /// Read whole blocks starting at `block`
+    ///
+    /// `buf.len()` must be a multiple of the block size, and the range
+    /// must lie within the device (`EINVAL` / `ENXIO` otherwise).
+    fn read_blocks(&self, block: u64, buf: &mut [u8]) -> Result<(), Errno>;
+
+    /// Write whole blocks starting at `block`
+    ///
+    /// Same requirements as [`read_blocks`](Self::read_blocks).
+    fn write_blocks(&self, block: u64, buf: &[u8]) -> Result<(), Errno>;
+
+    /// Make written blocks durable
+    fn flush(&self) -> Result<(), Errno> {
+        Ok(())
+    }
+
+    /// Size in bytes
+    fn size(&self) -> u64 {
+        self.num_blocks() * self.block_size() as u64
+    }
+}
+
+/// Registered devices, by index
+static DEVICES: SpinMutex<Vec<Arc<dyn BlockDevice>>> = SpinMutex::new(Vec::new());
+
+/// Register a block device
+///
+/// # Returns
+///
+/// The device's index, `EEXIST` if a device of the same name exists, or
+/// `ENOSPC` if [`MAX_DEVICES`] are registered
+pub fn register(device: Arc<dyn BlockDevice>) -> Result<usize, Errno> {
+    let mut devices = DEVICES.lock();
+    if devices.iter().any(|d| d.name() == device.name()) {
+        return Err(Errno::EEXIST);
+    }
+    if devices.len() >= MAX_DEVICES {
+        return Err(Errno::ENOSPC);
+    }
+    devices.push(device);
+    Ok(devices.len() - 1)
+}
+
+/// Register a disk and the partitions on it
+///
+/// A partition table that cannot be read leaves just the disk.
+///
+/// # Returns
+///
+/// The disk's index, or the error from [`register`]
+pub fn add_disk(disk: Arc<dyn BlockDevice>) -> Result<usize, Errno> {
+    let index = register(disk.clone())?;
+    let parts = match partition::probe(&disk) {
+        Ok(parts) => parts,
+        Err(e) => {
+            kwarn!("[BLOCK] {}: cannot read partition table: {:?}", disk.name(), e);
+            return Ok(index);
+        }
+    };
+    for part in parts {
+        let name = alloc::string::String::from(part.name());
+        let blocks = part.num_blocks();
+        match register(Arc::new(part)) {
+            Ok(_) => kinfo!("[BLOCK] {}: {} blocks", name, blocks),
+            Err(e) => kwarn!("[BLOCK] {}: not registered: {:?}", name, e),
+        }
+    }
+    Ok(index)
+}
+
+/// Get a device by index
+pub fn get(index: usize) -> Option<Arc<dyn BlockDevice>> {
+    DEVICES.lock().get(index).cloned()
+}
+
+/// Find a device's index by name
+pub fn find(name: &str) -> Option<usize> {
+    DEVICES.lock().iter().position(|d| d.name() == name)
+}
+
+/// Number of registered devices
+pub fn count() -> usize {
+    DEVICES.lock().len()
+}
+
+/// Flush every device
+///
+/// Called on shutdown. Errors are ignored: there is nothing left to do
+/// about them.
+pub fn flush_all() {
+    let devices: Vec<_> = DEVICES.lock().clone();
+    for device in devices {
+        let _ = device.flush();
+    }
+}
+
+/// Read bytes at a byte offset
+///
+/// # Returns
+///
+/// Bytes read: short at the end of the device, 0 at or past it
+pub fn read_at(device: &dyn BlockDevice, offset: u64, buf: &mut [u8]) -> Result<usize, Errno> {
+    let bs = device.block_size();
+    let len = core::cmp::min(buf.len() as u64, device.size().saturating_sub(offset)) as usize;
+    let mut bounce = Vec::new();
+    let mut done = 0;
+    while done < len {
+        let pos = offset + done as u64;
+        let block = pos / bs as u64;
+        let within = (pos % bs as u64) as usize;
+        let rest = len - done;
+        if within == 0 && rest >= bs {
+            let n = rest - rest % bs;
+            device.read_blocks(block, &mut buf[done..done + n])?;
+            done += n;
+        } else {
+            let n = core::cmp::min(bs - within, rest);
+            bounce.resize(bs, 0);
+            device.read_blocks(block, &mut bounce)?;
+            buf[done..done + n].copy_from_slice(&bounce[within..within + n]);
+            done += n;
+        }
+    }
+    Ok(len)
+}
+
+/// Write bytes at a byte offset
+///
+/// # Returns
+///
+/// Bytes written: short at the end of the device, 0 at or past it
+pub fn write_at(device: &dyn BlockDevice, offset: u64, buf: &[u8]) -> Result<usize, Errno> {
+    let bs = device.block_size();
+    let len = core::cmp::min(buf.len() as u64, device.size().saturating_sub(offset)) as usize;
+    let mut bounce = Vec::new();
+    let mut done = 0;
+    while done < len {
+        let pos = offset + done as u64;
+        let block = pos / bs as u64;
+        let within = (pos % bs as u64) as usize;
+        let rest = len - done;
+        if within == 0 && rest >= bs {
+            let n = rest - rest % bs;
+            device.write_blocks(block, &buf[done..done + n])?;
+            done += n;
+        } else {
+            // Partial block: read, modify, write back
+            let n = core::cmp::min(bs - within, rest);
+            bounce.resize(bs, 0);
+            device.read_blocks(block, &mut bounce)?;
+            bounce[within..within + n].copy_from_slice(&buf[done..done + n]);
+            device.write_blocks(block, &bounce)?;
+            done += n;
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::ram::RamDisk;
+    use alloc::vec;
+
+    #[test]
+    fn test_unaligned_read_write() {
+        let disk = RamDisk::new("test", 4 * ram::BLOCK_SIZE).unwrap();
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+
+        // Starts and ends mid-block, spans a whole block
+        assert_eq!(write_at(&disk, 300, &data), Ok(1000));
+        let mut out = vec![0u8; 1000];
+        assert_eq!(read_at(&disk, 300, &mut out), Ok(1000));
+        assert_eq!(out, data);
+
+        // Bytes around the write are untouched
+        let mut edge = [0xFFu8; 1];
+        assert_eq!(read_at(&disk, 299, &mut edge), Ok(1));
+        assert_eq!(edge, [0]);
+        assert_eq!(read_at(&disk, 1300, &mut edge), Ok(1));
+        assert_eq!(edge, [0]);
+    }
+
+    #[test]
+    fn test_end_of_device() {
+        let disk = RamDisk::new("test", 2 * ram::BLOCK_SIZE).unwrap();
+        let size = disk.size();
+        let mut buf = [0u8; 16];
+        assert_eq!(write_at(&disk, size - 4, &[1; 16]), Ok(4));
+        assert_eq!(read_at(&disk, size - 4, &mut buf), Ok(4));
+        assert_eq!(&buf[..4], &[1; 4]);
+        assert_eq!(read_at(&disk, size, &mut buf), Ok(0));
+        assert_eq!(write_at(&disk, size + 1, &buf), Ok(0));
+    }
+}
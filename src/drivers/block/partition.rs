@@ -0,0 +1,415 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Partition Tables
+//!
+//! [`probe`] reads a disk's partition table and returns one
+//! [`Partition`] per entry: a block device that is a window of the disk,
+//! so a filesystem mounts `/dev/vda1` rather than `/dev/vda`. Partitions
+//! are numbered from 1 and named after the disk (`vda1`, or `ram0p1`
+//! when the disk's name ends in a digit).
+//!
+//! # GPT
+//!
+//! A disk whose MBR holds a protective entry (type `0xEE`) is read as
+//! GPT. The primary header (LBA 1) and the backup header (the disk's last
+//! LBA) are both validated: signature, header CRC32, their own LBA, and
+//! the CRC32 of the entry array they point to. The primary is used if it
+//! is valid, otherwise the backup. Partitions are numbered by their slot
+//! in the entry array, so unused slots leave gaps.
+//!
+//! # MBR
+//!
+//! Otherwise the four primary entries of an MBR (signature `0x55AA`) are
+//! used. Extended partitions are skipped; their logical partitions are
+//! not read.
+//!
+//! Entries that do not fit on the disk are skipped with a warning.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::drivers::block::BlockDevice;
+use crate::fs::ramdisk::Errno;
+use crate::kwarn;
+
+/// MBR sector size (MBR addresses are in 512-byte sectors)
+const MBR_SECTOR_SIZE: u64 = 512;
+
+/// Offset of the first MBR partition entry
+const MBR_ENTRIES_OFFSET: usize = 446;
+
+/// MBR boot signature at offset 510
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// MBR partition type: GPT protective entry
+const MBR_TYPE_GPT: u8 = 0xEE;
+
+/// MBR partition types of extended partitions
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+/// GPT header signature
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// Smallest valid GPT header
+const GPT_HEADER_MIN_SIZE: usize = 92;
+
+/// Smallest valid GPT partition entry
+const GPT_ENTRY_MIN_SIZE: usize = 128;
+
+/// Largest GPT entry array read (the usual array is 16 KiB)
+const GPT_ENTRIES_MAX_BYTES: usize = 1024 * 1024;
+
+/// A window of a parent block device
+pub struct Partition {
+    name: String,
+    parent: Arc<dyn BlockDevice>,
+    /// First block on the parent
+    start: u64,
+    /// Length in blocks
+    blocks: u64,
+}
+
+impl Partition {
+    /// Create a partition of `blocks` blocks at `start` on `parent`
+    pub fn new(name: String, parent: Arc<dyn BlockDevice>, start: u64, blocks: u64) -> Self {
+        Self { name, parent, start, blocks }
+    }
+
+    /// First block on the parent device
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Parent block on which `len` bytes at `block` start, if they fit
+    fn map(&self, block: u64, len: usize) -> Result<u64, Errno> {
+        let count = (len / self.block_size()) as u64;
+        match block.checked_add(count) {
+            Some(end) if end <= self.blocks => Ok(self.start + block),
+            _ => Err(Errno::ENXIO),
+        }
+    }
+}
+
+impl BlockDevice for Partition {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn block_size(&self) -> usize {
+        self.parent.block_size()
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    fn read_blocks(&self, block: u64, buf: &mut [u8]) -> Result<(), Errno> {
+        self.parent.read_blocks(self.map(block, buf.len())?, buf)
+    }
+
+    fn write_blocks(&self, block: u64, buf: &[u8]) -> Result<(), Errno> {
+        self.parent.write_blocks(self.map(block, buf.len())?, buf)
+    }
+
+    fn flush(&self) -> Result<(), Errno> {
+        self.parent.flush()
+    }
+}
+
+/// Name of partition `number` of `disk`
+pub fn partition_name(disk: &str, number: usize) -> String {
+    if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", disk, number)
+    } else {
+        format!("{}{}", disk, number)
+    }
+}
+
+/// Read a disk's partition table
+///
+/// # Returns
+///
+/// The partitions, empty if the disk has no (valid) partition table
+pub fn probe(disk: &Arc<dyn BlockDevice>) -> Result<Vec<Partition>, Errno> {
+    let bs = disk.block_size();
+    if bs < MBR_SECTOR_SIZE as usize || disk.num_blocks() < 2 {
+        return Ok(Vec::new());
+    }
+    let mut mbr = vec![0u8; bs];
+    disk.read_blocks(0, &mut mbr)?;
+    if mbr[510..512] != MBR_SIGNATURE {
+        return Ok(Vec::new());
+    }
+
+    let entries: Vec<&[u8]> = mbr[MBR_ENTRIES_OFFSET..510].chunks(16).collect();
+    if entries.iter().any(|e| e[4] == MBR_TYPE_GPT) {
+        return probe_gpt(disk);
+    }
+    Ok(probe_mbr(disk, &entries))
+}
+
+/// Partitions of the MBR's primary entries
+fn probe_mbr(disk: &Arc<dyn BlockDevice>, entries: &[&[u8]]) -> Vec<Partition> {
+    let bs = disk.block_size() as u64;
+    let mut parts = Vec::new();
+    for (i, e) in entries.iter().enumerate() {
+        let kind = e[4];
+        let lba = u32::from_le_bytes([e[8], e[9], e[10], e[11]]) as u64;
+        let sectors = u32::from_le_bytes([e[12], e[13], e[14], e[15]]) as u64;
+        if kind == 0 || sectors == 0 || MBR_TYPES_EXTENDED.contains(&kind) {
+            continue;
+        }
+        let (start, len) = (lba * MBR_SECTOR_SIZE, sectors * MBR_SECTOR_SIZE);
+        if start % bs != 0 || len % bs != 0 {
+            kwarn!("[BLOCK] {}: MBR partition {} is not block aligned, skipped", disk.name(), i + 1);
+            continue;
+        }
+        push_checked(&mut parts, disk, i + 1, start / bs, len / bs);
+    }
+    parts
+}
+
+/// Add a partition if it fits on the disk
+fn push_checked(parts: &mut Vec<Partition>, disk: &Arc<dyn BlockDevice>, number: usize, start: u64, blocks: u64) {
+    match start.checked_add(blocks) {
+        Some(end) if start > 0 && end <= disk.num_blocks() => {
+            parts.push(Partition::new(partition_name(disk.name(), number), disk.clone(), start, blocks));
+        }
+        _ => kwarn!("[BLOCK] {}: partition {} lies outside the disk, skipped", disk.name(), number),
+    }
+}
+
+/// A validated GPT header
+struct GptHeader {
+    first_usable: u64,
+    last_usable: u64,
+    entries_lba: u64,
+    num_entries: usize,
+    entry_size: usize,
+}
+
+/// Partitions of a GPT disk
+fn probe_gpt(disk: &Arc<dyn BlockDevice>) -> Result<Vec<Partition>, Errno> {
+    let last_lba = disk.num_blocks() - 1;
+    let primary = read_gpt(disk, 1);
+    let backup = read_gpt(disk, last_lba);
+
+    let (header, entries) = match (primary, backup) {
+        (Some(primary), backup) => {
+            if backup.is_none() {
+                kwarn!("[BLOCK] {}: GPT backup header is invalid", disk.name());
+            }
+            primary
+        }
+        (None, Some(backup)) => {
+            kwarn!("[BLOCK] {}: GPT primary header is invalid, using the backup", disk.name());
+            backup
+        }
+        (None, None) => {
+            kwarn!("[BLOCK] {}: protective MBR but no valid GPT header", disk.name());
+            return Ok(Vec::new());
+        }
+    };
+
+    let mut parts = Vec::new();
+    for (i, e) in entries.chunks(header.entry_size).take(header.num_entries).enumerate() {
+        if e[..16].iter().all(|&b| b == 0) {
+            continue;
+        }
+        let first = u64_at(e, 32);
+        let last = u64_at(e, 40);
+        if first > last || first < header.first_usable || last > header.last_usable {
+            kwarn!("[BLOCK] {}: GPT partition {} lies outside the usable area, skipped", disk.name(), i + 1);
+            continue;
+        }
+        push_checked(&mut parts, disk, i + 1, first, last - first + 1);
+    }
+    Ok(parts)
+}
+
+/// Read and validate the GPT header at `lba` and its entry array
+fn read_gpt(disk: &Arc<dyn BlockDevice>, lba: u64) -> Option<(GptHeader, Vec<u8>)> {
+    let bs = disk.block_size();
+    let mut block = vec![0u8; bs];
+    disk.read_blocks(lba, &mut block).ok()?;
+    if &block[..8] != GPT_SIGNATURE {
+        return None;
+    }
+
+    let header_size = u32_at(&block, 12) as usize;
+    if !(GPT_HEADER_MIN_SIZE..=bs).contains(&header_size) {
+        return None;
+    }
+    let header_crc = u32_at(&block, 16);
+    block[16..20].fill(0);
+    if crc32(&block[..header_size]) != header_crc || u64_at(&block, 24) != lba {
+        return None;
+    }
+
+    let header = GptHeader {
+        first_usable: u64_at(&block, 40),
+        last_usable: u64_at(&block, 48),
+        entries_lba: u64_at(&block, 72),
+        num_entries: u32_at(&block, 80) as usize,
+        entry_size: u32_at(&block, 84) as usize,
+    };
+    if header.entry_size < GPT_ENTRY_MIN_SIZE || !header.entry_size.is_multiple_of(8) {
+        return None;
+    }
+    let bytes = header.num_entries.checked_mul(header.entry_size).filter(|&n| n <= GPT_ENTRIES_MAX_BYTES)?;
+
+    // Whole blocks of the entry array, checksummed over its exact length
+    let mut entries = vec![0u8; bytes.div_ceil(bs) * bs];
+    disk.read_blocks(header.entries_lba, &mut entries).ok()?;
+    entries.truncate(bytes);
+    if crc32(&entries) != u32_at(&block, 88) {
+        return None;
+    }
+    Some((header, entries))
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// CRC-32 (IEEE 802.3, as used by GPT)
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0u32, |crc, &b| TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use crate::drivers::block::ram::{RamDisk, BLOCK_SIZE};
+
+    /// A 64-block disk with `image` at its start
+    fn disk(image: &[u8]) -> Arc<dyn BlockDevice> {
+        Arc::new(RamDisk::with_image("vda", image, 64 * BLOCK_SIZE).unwrap())
+    }
+
+    fn mbr(entries: &[(u8, u32, u32)]) -> Vec<u8> {
+        let mut mbr = vec![0u8; BLOCK_SIZE];
+        for (i, &(kind, lba, sectors)) in entries.iter().enumerate() {
+            let e = &mut mbr[MBR_ENTRIES_OFFSET + 16 * i..][..16];
+            e[4] = kind;
+            e[8..12].copy_from_slice(&lba.to_le_bytes());
+            e[12..16].copy_from_slice(&sectors.to_le_bytes());
+        }
+        mbr[510..512].copy_from_slice(&MBR_SIGNATURE);
+        mbr
+    }
+
+    /// Write a GPT header at `lba` describing `entries` at `entries_lba`
+    fn write_gpt(disk: &Arc<dyn BlockDevice>, lba: u64, entries_lba: u64, entries: &[u8]) {
+        let mut h = vec![0u8; BLOCK_SIZE];
+        h[..8].copy_from_slice(GPT_SIGNATURE);
+        h[12..16].copy_from_slice(&(GPT_HEADER_MIN_SIZE as u32).to_le_bytes());
+        h[24..32].copy_from_slice(&lba.to_le_bytes());
+        h[40..48].copy_from_slice(&34u64.to_le_bytes());
+        h[48..56].copy_from_slice(&(disk.num_blocks() - 6).to_le_bytes());
+        h[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        h[80..84].copy_from_slice(&((entries.len() / 128) as u32).to_le_bytes());
+        h[84..88].copy_from_slice(&128u32.to_le_bytes());
+        h[88..92].copy_from_slice(&crc32(entries).to_le_bytes());
+        let crc = crc32(&h[..GPT_HEADER_MIN_SIZE]);
+        h[16..20].copy_from_slice(&crc.to_le_bytes());
+        disk.write_blocks(lba, &h).unwrap();
+        let mut padded = entries.to_vec();
+        padded.resize(entries.len().next_multiple_of(BLOCK_SIZE), 0);
+        disk.write_blocks(entries_lba, &padded).unwrap();
+    }
+
+    fn gpt_entries(ranges: &[(u64, u64)]) -> Vec<u8> {
+        let mut entries = vec![0u8; 128 * 4];
+        for (i, &(first, last)) in ranges.iter().enumerate() {
+            if (first, last) == (0, 0) {
+                continue; // unused slot
+            }
+            let e = &mut entries[128 * i..][..128];
+            e[0] = 0xAF; // any non-zero type GUID
+            e[32..40].copy_from_slice(&first.to_le_bytes());
+            e[40..48].copy_from_slice(&last.to_le_bytes());
+        }
+        entries
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_partition_name() {
+        assert_eq!(partition_name("vda", 1), "vda1");
+        assert_eq!(partition_name("ram0", 2), "ram0p2");
+    }
+
+    #[test]
+    fn test_mbr() {
+        // Second entry is extended, fourth runs off the disk
+        let disk = disk(&mbr(&[(0x83, 8, 16), (0x05, 24, 8), (0x0C, 32, 16), (0x83, 60, 16)]));
+        let parts = probe(&disk).unwrap();
+        let found: Vec<_> = parts.iter().map(|p| (p.name().to_string(), p.start(), p.num_blocks())).collect();
+        assert_eq!(found, [("vda1".to_string(), 8, 16), ("vda3".to_string(), 32, 16)]);
+
+        // Reads are offset into the partition and bounded by it
+        disk.write_blocks(9, &[7u8; BLOCK_SIZE]).unwrap();
+        let mut block = [0u8; BLOCK_SIZE];
+        parts[0].read_blocks(1, &mut block).unwrap();
+        assert_eq!(block, [7u8; BLOCK_SIZE]);
+        assert_eq!(parts[0].read_blocks(16, &mut block), Err(Errno::ENXIO));
+    }
+
+    #[test]
+    fn test_no_table() {
+        assert!(probe(&disk(&[0u8; BLOCK_SIZE])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_gpt_primary_and_backup() {
+        let disk = disk(&mbr(&[(MBR_TYPE_GPT, 1, 63)]));
+        let entries = gpt_entries(&[(34, 39), (0, 0), (40, 45)]);
+        write_gpt(&disk, 1, 2, &entries);
+        write_gpt(&disk, 63, 59, &entries);
+
+        let names = |parts: Vec<Partition>| parts.iter().map(|p| p.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(probe(&disk).unwrap()), ["vda1", "vda3"]);
+
+        // Corrupt primary: the backup is used
+        disk.write_blocks(1, &[0xFFu8; BLOCK_SIZE]).unwrap();
+        let parts = probe(&disk).unwrap();
+        assert_eq!((parts[1].start(), parts[1].num_blocks()), (40, 6));
+
+        // Corrupt entry array of the backup too: nothing is trusted
+        disk.write_blocks(59, &[0xFFu8; BLOCK_SIZE]).unwrap();
+        assert!(probe(&disk).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! RAM Block Device
+//!
+//! `/dev/ram0` is a block device backed by kernel heap memory, so
+//! filesystem drivers can be tested before there is a disk driver. It
+//! only exists when the command line asks for it:
+//!
+//! | Option | Effect |
+//! |--------|--------|
+//! | `ram0.size=<KiB>` | Create `ram0` of this size, zero-filled |
+//! | `ram0.image=<path>` | Copy this ramdisk file (e.g. a FAT or ext2 image) to the start of `ram0`; the device grows to fit it |
+//!
+//! With only `ram0.image`, the device is exactly as large as the image
+//! (rounded up to a block). An image with a partition table also gets
+//! its partitions (`/dev/ram0p1`, ...). Contents do not survive a reboot.
+
+use alloc::vec::Vec;
+use crate::drivers::block::{self, BlockDevice};
+use crate::fs::ramdisk::Errno;
+use crate::sync::AdaptiveMutex;
+use crate::{kinfo, kwarn};
+
+/// Block size of RAM devices
+pub const BLOCK_SIZE: usize = 512;
+
+/// Largest RAM device
+pub const MAX_SIZE: usize = 256 * 1024 * 1024;
+
+/// Command line option: size of `ram0` in KiB
+pub const SIZE_OPTION: &str = "ram0.size";
+
+/// Command line option: ramdisk file to load into `ram0`
+pub const IMAGE_OPTION: &str = "ram0.image";
+
+/// A block device backed by a heap buffer
+pub struct RamDisk {
+    name: &'static str,
+    data: AdaptiveMutex<Vec<u8>>,
+}
+
+impl RamDisk {
+    /// Create a zero-filled device of `size` bytes (rounded up to a block)
+    ///
+    /// # Returns
+    ///
+    /// The device, `EINVAL` if `size` is 0 or over [`MAX_SIZE`], or
+    /// `ENOMEM` if the heap cannot hold it
+    pub fn new(name: &'static str, size: usize) -> Result<Self, Errno> {
+        if size == 0 || size > MAX_SIZE {
+            return Err(Errno::EINVAL);
+        }
+        let size = size.next_multiple_of(BLOCK_SIZE);
+        let mut data = Vec::new();
+        data.try_reserve_exact(size).map_err(|_| Errno::ENOMEM)?;
+        data.resize(size, 0);
+        Ok(Self { name, data: AdaptiveMutex::new(data) })
+    }
+
+    /// Create a device holding `image`, at least `size` bytes large
+    pub fn with_image(name: &'static str, image: &[u8], size: usize) -> Result<Self, Errno> {
+        let disk = Self::new(name, size.max(image.len()))?;
+        disk.data.lock()[..image.len()].copy_from_slice(image);
+        Ok(disk)
+    }
+
+    /// Byte range of `len` bytes at `block`, if it is whole blocks on
+    /// the device
+    fn range(&self, block: u64, len: usize, size: usize) -> Result<core::ops::Range<usize>, Errno> {
+        if !len.is_multiple_of(BLOCK_SIZE) {
+            return Err(Errno::EINVAL);
+        }
+        let start = usize::try_from(block).ok().and_then(|b| b.checked_mul(BLOCK_SIZE)).ok_or(Errno::ENXIO)?;
+        match start.checked_add(len) {
+            Some(end) if end <= size => Ok(start..end),
+            _ => Err(Errno::ENXIO),
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn num_blocks(&self) -> u64 {
+        (self.data.lock().len() / BLOCK_SIZE) as u64
+    }
+
+    fn read_blocks(&self, block: u64, buf: &mut [u8]) -> Result<(), Errno> {
+        let data = self.data.lock();
+        let range = self.range(block, buf.len(), data.len())?;
+        buf.copy_from_slice(&data[range]);
+        Ok(())
+    }
+
+    fn write_blocks(&self, block: u64, buf: &[u8]) -> Result<(), Errno> {
+        let mut data = self.data.lock();
+        let range = self.range(block, buf.len(), data.len())?;
+        data[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Create and register `ram0` from the command line options
+///
+/// Runs after the ramdisk is initialized, so an image can be loaded from
+/// it. Does nothing if neither option is given.
+pub fn init() {
+    let mut buf = [0u8; 128];
+    let size_kib = crate::cmdline::get(SIZE_OPTION, &mut buf).and_then(|s| s.parse::<usize>().ok());
+    let size = size_kib.map_or(0, |kib| kib.saturating_mul(1024));
+
+    let disk = match crate::cmdline::get(IMAGE_OPTION, &mut buf) {
+        Some(path) => {
+            let image = crate::fs::ramdisk::get_ramdisk()
+                .ok()
+                .and_then(|rd| rd.find_file(path).map(|f| rd.file_data(&f)));
+            match image {
+                Some(image) => RamDisk::with_image("ram0", image, size),
+                None => {
+                    kwarn!("[BLOCK] ram0: image {} not found in the ramdisk", path);
+                    return;
+                }
+            }
+        }
+        None if size_kib.is_some() => RamDisk::new("ram0", size),
+        None => return,
+    };
+
+    match disk.and_then(|disk| block::add_disk(alloc::sync::Arc::new(disk))) {
+        Ok(index) => kinfo!("[BLOCK] ram0: {} KiB", block::get(index).map_or(0, |d| d.size() / 1024)),
+        Err(e) => kwarn!("[BLOCK] ram0: not created: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(RamDisk::new("ram", 0).err(), Some(Errno::EINVAL));
+        assert_eq!(RamDisk::new("ram", MAX_SIZE + 1).err(), Some(Errno::EINVAL));
+        assert_eq!(RamDisk::new("ram", 1000).unwrap().num_blocks(), 2);
+    }
+
+    #[test]
+    fn test_block_bounds() {
+        let disk = RamDisk::with_image("ram", b"hello", BLOCK_SIZE).unwrap();
+        let mut block = [0u8; BLOCK_SIZE];
+        assert_eq!(disk.read_blocks(0, &mut block), Ok(()));
+        assert_eq!(&block[..5], b"hello");
+        assert_eq!(disk.read_blocks(1, &mut block), Err(Errno::ENXIO));
+        assert_eq!(disk.read_blocks(0, &mut block[..100]), Err(Errno::EINVAL));
+        assert_eq!(disk.write_blocks(u64::MAX, &block), Err(Errno::ENXIO));
+    }
+}
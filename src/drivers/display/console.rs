@@ -8,15 +8,60 @@
 //!
 //! This module provides a text console implementation using the framebuffer
 //! and font rendering.
+//!
+//! The global console functions write to the kernel console VT; see
+//! [`super::vt`] for virtual terminal multiplexing.
+//!
+//! The VTs keep the text; the console only turns cells into pixels. A
+//! cell's colors are indices into the console's palette, so changing the
+//! palette ([`set_palette`]) or the framebuffer ([`set_framebuffer`])
+//! redraws the screen from the VT grid rather than losing it.
+//!
+//! The console is locked with interrupts disabled, inside the VT lock
+//! when both are needed; see [`with_renderer`].
 
 use crate::drivers::display::framebuffer::{Color, Framebuffer};
 use crate::drivers::display::font::SimpleVgaFont;
-use core::sync::atomic::{AtomicBool, Ordering};
+use crate::drivers::display::vt;
+use crate::sync::SpinMutex;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 /// Global text console instance
-static mut CONSOLE: Option<TextConsole> = None;
+static CONSOLE: SpinMutex<Option<TextConsole>> = SpinMutex::new(None);
 static CONSOLE_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+/// Text rows on screen, readable without the lock
+static ROWS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of palette entries
+pub const PALETTE_SIZE: usize = 16;
+
+/// Default palette (the VGA text mode colors)
+pub const DEFAULT_PALETTE: [Color; PALETTE_SIZE] = [
+    Color::new(0, 0, 0),
+    Color::new(0, 0, 170),
+    Color::new(0, 170, 0),
+    Color::new(0, 170, 170),
+    Color::new(170, 0, 0),
+    Color::new(170, 0, 170),
+    Color::new(170, 85, 0),
+    Color::new(170, 170, 170),
+    Color::new(85, 85, 85),
+    Color::new(85, 85, 255),
+    Color::new(85, 255, 85),
+    Color::new(85, 255, 255),
+    Color::new(255, 85, 85),
+    Color::new(255, 85, 255),
+    Color::new(255, 255, 85),
+    Color::new(255, 255, 255),
+];
+
+/// Default foreground palette index (white)
+pub const DEFAULT_FG: u8 = 15;
+
+/// Default background palette index (black)
+pub const DEFAULT_BG: u8 = 0;
+
 /// Text console with framebuffer backing
 pub struct TextConsole {
     framebuffer: Framebuffer,
@@ -26,6 +71,7 @@ pub struct TextConsole {
     bg_color: Color,
     cols: usize,
     rows: usize,
+    palette: [Color; PALETTE_SIZE],
 }
 
 impl TextConsole {
@@ -45,6 +91,7 @@ impl TextConsole {
             bg_color: Color::BLACK,
             cols,
             rows,
+            palette: DEFAULT_PALETTE,
         }
     }
 
@@ -147,6 +194,90 @@ impl TextConsole {
         }
     }
 
+    /// Get the color of a palette entry
+    pub fn palette_color(&self, index: u8) -> Color {
+        self.palette[index as usize % PALETTE_SIZE]
+    }
+
+    /// Change a palette entry
+    ///
+    /// Cells already on screen keep their old pixels until redrawn.
+    ///
+    /// # Returns
+    /// `false` if `index` is out of range
+    pub fn set_palette_color(&mut self, index: usize, color: Color) -> bool {
+        match self.palette.get_mut(index) {
+            Some(entry) => {
+                *entry = color;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Draw a character cell with palette colors
+    ///
+    /// Used by the VT layer, which tracks its own cursor and colors.
+    pub fn draw_cell(&mut self, col: usize, row: usize, ch: u8, fg: u8, bg: u8) {
+        if col >= self.cols || row >= self.rows {
+            return;
+        }
+        let (saved_fg, saved_bg) = (self.fg_color, self.bg_color);
+        self.fg_color = self.palette_color(fg);
+        self.bg_color = self.palette_color(bg);
+        self.render_char(ch, col, row);
+        self.fg_color = saved_fg;
+        self.bg_color = saved_bg;
+    }
+
+    /// Scroll the screen up by one line, clearing the bottom line
+    pub fn scroll_up(&mut self) {
+        self.scroll();
+    }
+
+    /// Scroll the screen up by `lines` lines, clearing the bottom ones
+    pub fn scroll_up_by(&mut self, lines: usize) {
+        let lines = core::cmp::min(lines, self.rows);
+        unsafe {
+            self.framebuffer.scroll(lines, SimpleVgaFont::height());
+        }
+        for row in self.rows - lines..self.rows {
+            for col in 0..self.cols {
+                self.clear_char_at(col, row);
+            }
+        }
+    }
+
+    /// Fill the pixels right of the last column and below the last row
+    ///
+    /// Cells do not cover them when the resolution is not a multiple of
+    /// the font size; a full redraw clears what someone else left there.
+    pub fn clear_margins(&mut self, bg: u8) {
+        let color = self.palette_color(bg);
+        let width = self.cols * SimpleVgaFont::width();
+        let height = self.rows * SimpleVgaFont::height();
+        let fb = &mut self.framebuffer;
+        unsafe {
+            fb.fill_rect(width, 0, fb.width - width, fb.height, color);
+            fb.fill_rect(0, height, width, fb.height - height, color);
+        }
+    }
+
+    /// Switch to another framebuffer (after a mode change)
+    ///
+    /// Recomputes the grid size and clears the new framebuffer. The
+    /// palette is kept.
+    pub fn set_framebuffer(&mut self, framebuffer: Framebuffer) {
+        self.cols = framebuffer.width / SimpleVgaFont::width();
+        self.rows = framebuffer.height / SimpleVgaFont::height();
+        self.framebuffer = framebuffer;
+        self.cursor_x = core::cmp::min(self.cursor_x, self.cols.saturating_sub(1));
+        self.cursor_y = core::cmp::min(self.cursor_y, self.rows.saturating_sub(1));
+        unsafe {
+            self.framebuffer.clear(self.bg_color);
+        }
+    }
+
     /// Render a single character at the given position
     fn render_char(&mut self, ch: u8, col: usize, row: usize) {
         let char_width = SimpleVgaFont::width();
@@ -199,14 +330,7 @@ impl TextConsole {
 
     /// Scroll the console up by one line
     fn scroll(&mut self) {
-        unsafe {
-            self.framebuffer.scroll(1, SimpleVgaFont::height());
-        }
-
-        // Clear the bottom line
-        for col in 0..self.cols {
-            self.clear_char_at(col, self.rows - 1);
-        }
+        self.scroll_up_by(1);
     }
 
     /// Get the number of columns
@@ -236,10 +360,73 @@ impl TextConsole {
 /// This function must be called only once during kernel initialization.
 /// It must be called after the framebuffer has been initialized.
 pub unsafe fn init(framebuffer: Framebuffer) {
-    CONSOLE = Some(TextConsole::new(framebuffer));
+    let console = TextConsole::new(framebuffer);
+    vt::init(console.cols(), console.rows());
+    ROWS.store(console.rows(), Ordering::Release);
+    with_renderer(|con| *con = Some(console));
     CONSOLE_INITIALIZED.store(true, Ordering::Release);
 }
 
+/// Run `f` on the screen renderer with interrupts disabled
+///
+/// The VTs draw through this with their own lock held, so never call
+/// into [`vt`] from `f`. Once a panic report starts, the lock is skipped
+/// (see [`vt::force_unlock`]).
+pub(crate) fn with_renderer<R>(f: impl FnOnce(&mut Option<TextConsole>) -> R) -> R {
+    use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
+
+    if vt::panicking() {
+        // SAFETY: racy if another CPU is drawing; see vt::force_unlock
+        return f(unsafe { &mut *CONSOLE.as_ptr() });
+    }
+
+    let were_disabled = arch_ints_disabled();
+    arch_disable_ints();
+    let result = f(&mut CONSOLE.lock());
+    if !were_disabled {
+        arch_enable_ints();
+    }
+    result
+}
+
+/// Text rows on screen (0 before [`init`])
+///
+/// Does not lock the console, so the keyboard interrupt can use it.
+pub fn rows() -> usize {
+    ROWS.load(Ordering::Acquire)
+}
+
+/// Switch the console to a new framebuffer after a mode change
+///
+/// Every VT is resized to the new grid and the active one is redrawn.
+///
+/// # Safety
+/// `framebuffer` must describe mapped, writable video memory.
+pub unsafe fn set_framebuffer(framebuffer: Framebuffer) {
+    let size = with_renderer(|con| {
+        let con = con.as_mut()?;
+        con.set_framebuffer(framebuffer);
+        Some((con.cols(), con.rows()))
+    });
+    let Some((cols, rows)) = size else {
+        return;
+    };
+    ROWS.store(rows, Ordering::Release);
+    vt::resize(cols, rows);
+}
+
+/// Change a palette entry and redraw the active VT with it
+///
+/// # Returns
+/// `false` if the console is not initialized or `index` is out of range
+pub fn set_palette(index: usize, color: Color) -> bool {
+    let changed = with_renderer(|con| con.as_mut().is_some_and(|con| con.set_palette_color(index, color)));
+    if changed {
+        vt::refresh();
+    }
+    changed
+}
+
 /// Check if the console has been initialized
 pub fn is_initialized() -> bool {
     CONSOLE_INITIALIZED.load(Ordering::Acquire)
@@ -247,49 +434,27 @@ pub fn is_initialized() -> bool {
 
 /// Write a string to the console
 pub fn write_str(s: &str) {
-    unsafe {
-        if let Some(ref mut console) = CONSOLE {
-            console.write_str(s);
-        }
-    }
+    vt::write(vt::CONSOLE_VT, s.as_bytes());
 }
 
 /// Write a single character to the console
 pub fn put_char(ch: u8) {
-    unsafe {
-        if let Some(ref mut console) = CONSOLE {
-            console.put_char(ch);
-        }
-    }
+    vt::put_char(vt::CONSOLE_VT, ch);
 }
 
 /// Clear the console
 pub fn clear() {
-    unsafe {
-        if let Some(ref mut console) = CONSOLE {
-            console.clear();
-        }
-    }
+    vt::clear(vt::CONSOLE_VT);
 }
 
-/// Set the console colors
-pub fn set_color(fg: Color, bg: Color) {
-    unsafe {
-        if let Some(ref mut console) = CONSOLE {
-            console.set_color(fg, bg);
-        }
-    }
+/// Set the console colors (palette indices)
+pub fn set_color(fg: u8, bg: u8) {
+    vt::set_color(vt::CONSOLE_VT, fg, bg);
 }
 
-/// Get the console colors
-pub fn get_color() -> (Color, Color) {
-    unsafe {
-        if let Some(ref console) = CONSOLE {
-            (console.fg_color(), console.bg_color())
-        } else {
-            (Color::WHITE, Color::BLACK)
-        }
-    }
+/// Get the console colors (palette indices)
+pub fn get_color() -> (u8, u8) {
+    vt::get_color(vt::CONSOLE_VT).unwrap_or((DEFAULT_FG, DEFAULT_BG))
 }
 
 #[cfg(test)]
@@ -207,18 +207,14 @@ impl Framebuffer {
             return;
         }
 
-        // Move pixels up
+        // Move pixels up, all rows in one copy
         let fb_ptr = self.base_addr as *mut u8;
         let row_size = self.pitch;
-
-        for y in scroll_pixels..self.height {
-            let src_offset = y * row_size;
-            let dst_offset = (y - scroll_pixels) * row_size;
-
-            for x in 0..row_size {
-                *fb_ptr.add(dst_offset + x) = *fb_ptr.add(src_offset + x);
-            }
-        }
+        core::ptr::copy(
+            fb_ptr.add(scroll_pixels * row_size),
+            fb_ptr,
+            (self.height - scroll_pixels) * row_size,
+        );
 
         // Clear the bottom area
         let clear_start = self.height - scroll_pixels;
@@ -7,13 +7,17 @@
 //! Display Drivers
 //!
 //! This module provides framebuffer and text console support for
-//! displaying graphics and text on the screen.
+//! displaying graphics and text on the screen, multiplexed across
+//! virtual terminals.
 
 pub mod framebuffer;
 pub mod font;
 pub mod console;
+pub mod vt;
+pub mod progress;
 
 // Re-exports
 pub use framebuffer::{Framebuffer, Color, PixelFormat};
 pub use font::{Psf2Font, SimpleVgaFont};
-pub use console::{TextConsole, init, write_str, put_char, clear, set_color, get_color, is_initialized};
+pub use console::{TextConsole, init, write_str, put_char, clear, set_color, get_color, set_palette, set_framebuffer, is_initialized};
+pub use vt::{VirtualTerminal, NUM_VTS, CONSOLE_VT};
@@ -0,0 +1,350 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Boot Progress
+//!
+//! Init phases report where boot has got to with [`begin`]; the screen
+//! shows it in a status bar along the bottom [`BAR_HEIGHT`] pixels: the
+//! current stage's name and one segment per [`Stage`], green once done,
+//! yellow while running and red if the kernel panicked in it. The text
+//! console is set up above the bar ([`reserved_height`]), so the two
+//! never overwrite each other.
+//!
+//! # Modes
+//!
+//! The `boot.progress` command line option selects the display:
+//!
+//! - `bar` (default): the status bar.
+//! - `markers`: fill the whole screen with a stage's color
+//!   ([`Stage::marker`]) as it begins, plus the [`marker`] fills on the
+//!   way into userspace and on syscalls. Crude, but it needs no font and shows how far a
+//!   machine without a debug port got, which is what bring-up wants.
+//! - `off`: leave the screen alone.
+//!
+//! Progress is drawn from the first stage on, before ExitBootServices,
+//! so the framebuffer must be the GOP one, reachable at its physical
+//! address throughout. Reporting stops at [`finish`].
+
+use crate::drivers::display::font::SimpleVgaFont;
+use crate::drivers::display::framebuffer::{Color, Framebuffer};
+use crate::sync::SpinMutex;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Command line option: `bar` (default), `markers` or `off`
+pub const PROGRESS_OPTION: &str = "boot.progress";
+
+/// Height of the status bar at the bottom of the screen
+pub const BAR_HEIGHT: usize = SimpleVgaFont::height() + 8;
+
+/// Space between the edge of the screen and the bar's contents
+const PADDING: usize = 4;
+
+/// Gap between two stage segments
+const SEGMENT_GAP: usize = 2;
+
+/// Bar background
+const BACKGROUND: Color = Color::new(32, 32, 32);
+
+/// Segment of a stage not yet begun
+const PENDING: Color = Color::new(80, 80, 80);
+
+/// Segment of the running stage
+const RUNNING: Color = Color::new(255, 200, 0);
+
+/// Segment of a completed stage
+const DONE: Color = Color::new(0, 180, 0);
+
+/// Segment of the stage the kernel panicked in
+const FAILED: Color = Color::new(220, 0, 0);
+
+/// How progress is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Status bar at the bottom of the screen
+    Bar,
+    /// Full-screen color fills
+    Markers,
+    /// Nothing
+    Off,
+}
+
+impl Mode {
+    /// Mode for a `boot.progress` value; unknown values select the bar
+    pub fn parse(value: Option<&str>) -> Self {
+        match value {
+            Some("markers") => Mode::Markers,
+            Some("off") => Mode::Off,
+            _ => Mode::Bar,
+        }
+    }
+}
+
+/// Boot stages, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// UEFI entry, before ExitBootServices
+    Firmware,
+    /// Physical memory and the kernel stack
+    Memory,
+    /// GDT, IDT and exception handlers
+    Cpu,
+    /// Interrupt handlers
+    Interrupts,
+    /// PCI, APIC, keyboard, serial and the timer
+    Devices,
+    /// Text console
+    Display,
+    /// Ramdisk
+    Filesystem,
+    /// Loading init
+    Userspace,
+}
+
+impl Stage {
+    /// All stages, in order
+    pub const ALL: [Stage; 8] = [
+        Stage::Firmware,
+        Stage::Memory,
+        Stage::Cpu,
+        Stage::Interrupts,
+        Stage::Devices,
+        Stage::Display,
+        Stage::Filesystem,
+        Stage::Userspace,
+    ];
+
+    /// Name shown in the status bar
+    pub const fn label(self) -> &'static str {
+        match self {
+            Stage::Firmware => "Firmware",
+            Stage::Memory => "Memory",
+            Stage::Cpu => "CPU",
+            Stage::Interrupts => "Interrupts",
+            Stage::Devices => "Devices",
+            Stage::Display => "Display",
+            Stage::Filesystem => "Filesystem",
+            Stage::Userspace => "Userspace",
+        }
+    }
+
+    /// Screen color in `markers` mode
+    ///
+    /// The first two are the historic red (EFI entry) and green
+    /// (ExitBootServices done) markers.
+    pub const fn marker(self) -> Color {
+        match self {
+            Stage::Firmware => Color::new(255, 0, 0),
+            Stage::Memory => Color::new(0, 255, 0),
+            Stage::Cpu => Color::new(255, 255, 0),
+            Stage::Interrupts => Color::new(255, 0, 255),
+            Stage::Devices => Color::new(255, 128, 0),
+            Stage::Display => Color::new(128, 128, 128),
+            Stage::Filesystem => Color::new(128, 0, 255),
+            Stage::Userspace => Color::new(0, 128, 128),
+        }
+    }
+}
+
+/// Marker: the process page table is loaded
+pub const MARKER_ADDRESS_SPACE: Color = Color::new(0, 0, 255);
+
+/// Marker: about to enter userspace
+pub const MARKER_USER_ENTRY: Color = Color::WHITE;
+
+/// Marker: a syscall came in
+pub const MARKER_SYSCALL: Color = Color::new(0, 255, 255);
+
+/// Progress display state
+struct Progress {
+    framebuffer: Framebuffer,
+    mode: Mode,
+    current: Option<Stage>,
+    finished: bool,
+}
+
+/// The progress display (`None` until [`init`])
+static PROGRESS: SpinMutex<Option<Progress>> = SpinMutex::new(None);
+
+/// Whether the mode is `markers`, checked before taking the lock on
+/// hot paths ([`marker`])
+static MARKERS: AtomicBool = AtomicBool::new(false);
+
+/// Set up the progress display on `framebuffer`
+///
+/// Reads the mode from the command line, which must be parsed by now.
+///
+/// # Safety
+///
+/// `framebuffer` must describe memory that stays mapped and writable at
+/// its address for as long as the kernel runs.
+pub unsafe fn init(framebuffer: Framebuffer) {
+    let mut buf = [0u8; 8];
+    let mode = Mode::parse(crate::cmdline::get(PROGRESS_OPTION, &mut buf));
+    MARKERS.store(mode == Mode::Markers, Ordering::Relaxed);
+    *PROGRESS.lock() = Some(Progress { framebuffer, mode, current: None, finished: false });
+}
+
+/// Pixel rows at the bottom of the screen the console must leave alone
+pub fn reserved_height() -> usize {
+    match PROGRESS.lock().as_ref() {
+        Some(p) if p.mode == Mode::Bar => BAR_HEIGHT,
+        _ => 0,
+    }
+}
+
+/// Report that `stage` has begun; the previous stage is done
+pub fn begin(stage: Stage) {
+    let mut progress = PROGRESS.lock();
+    let Some(p) = progress.as_mut().filter(|p| !p.finished) else { return };
+    p.current = Some(stage);
+    // SAFETY: see `init`
+    unsafe {
+        match p.mode {
+            Mode::Bar => draw_bar(p, None),
+            Mode::Markers => p.framebuffer.clear(stage.marker()),
+            Mode::Off => {}
+        }
+    }
+}
+
+/// Report that boot is complete; later calls to [`begin`] are ignored
+pub fn finish() {
+    let mut progress = PROGRESS.lock();
+    let Some(p) = progress.as_mut().filter(|p| !p.finished) else { return };
+    p.finished = true;
+    if p.mode == Mode::Bar {
+        // SAFETY: see `init`
+        unsafe { draw_bar(p, None) };
+    }
+}
+
+/// Mark the running stage as failed
+///
+/// For the panic handler: gives up instead of waiting if the display is
+/// busy.
+pub fn fail() {
+    let Some(mut progress) = PROGRESS.try_lock() else { return };
+    let Some(p) = progress.as_mut().filter(|p| !p.finished && p.mode == Mode::Bar) else { return };
+    if let Some(stage) = p.current {
+        // SAFETY: see `init`
+        unsafe { draw_bar(p, Some(stage)) };
+    }
+}
+
+/// Fill the screen with `color` in `markers` mode
+///
+/// Bring-up markers for points that are not stages of their own, such as
+/// the switch into the first process.
+pub fn marker(color: Color) {
+    if !MARKERS.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(p) = PROGRESS.lock().as_mut().filter(|p| p.mode == Mode::Markers) {
+        // SAFETY: see `init`
+        unsafe { p.framebuffer.clear(color) };
+    }
+}
+
+/// Position and width of stage `index`'s segment in a bar of `count`
+/// segments spanning `[x, x + width)`
+pub const fn segment(x: usize, width: usize, index: usize, count: usize) -> (usize, usize) {
+    let start = x + width * index / count;
+    let end = x + width * (index + 1) / count;
+    let w = end - start;
+    (start, if w > SEGMENT_GAP { w - SEGMENT_GAP } else { w })
+}
+
+/// Draw the status bar
+///
+/// # Safety
+///
+/// See [`init`].
+unsafe fn draw_bar(p: &mut Progress, failed: Option<Stage>) {
+    let fb = &mut p.framebuffer;
+    if fb.height < BAR_HEIGHT {
+        return;
+    }
+    let top = fb.height - BAR_HEIGHT;
+    fb.fill_rect(0, top, fb.width, BAR_HEIGHT, BACKGROUND);
+
+    // Stage name on the left half
+    let text_y = top + (BAR_HEIGHT - SimpleVgaFont::height()) / 2;
+    let mut x = PADDING;
+    let words: [&str; 2] = match (p.finished, failed, p.current) {
+        (true, _, _) => ["Boot complete", ""],
+        (_, Some(stage), _) => ["Boot failed: ", stage.label()],
+        (_, None, Some(stage)) => ["Booting: ", stage.label()],
+        (_, None, None) => ["Booting", ""],
+    };
+    for byte in words.iter().flat_map(|w| w.bytes()) {
+        if x + SimpleVgaFont::width() > fb.width / 2 {
+            break;
+        }
+        draw_char(fb, x, text_y, byte, Color::WHITE);
+        x += SimpleVgaFont::width();
+    }
+
+    // One segment per stage on the right half
+    let bar_x = fb.width / 2;
+    let bar_width = fb.width / 2 - PADDING;
+    for (i, &stage) in Stage::ALL.iter().enumerate() {
+        let color = match p.current {
+            _ if p.finished => DONE,
+            _ if failed == Some(stage) => FAILED,
+            Some(current) if stage < current => DONE,
+            Some(current) if stage == current => RUNNING,
+            _ => PENDING,
+        };
+        let (sx, sw) = segment(bar_x, bar_width, i, Stage::ALL.len());
+        fb.fill_rect(sx, top + PADDING, sw, BAR_HEIGHT - 2 * PADDING, color);
+    }
+}
+
+/// Draw one character cell's set pixels
+unsafe fn draw_char(fb: &mut Framebuffer, x: usize, y: usize, ch: u8, color: Color) {
+    for gy in 0..SimpleVgaFont::height() {
+        for gx in 0..SimpleVgaFont::width() {
+            if SimpleVgaFont::glyph_pixel(ch, gx, gy) {
+                fb.put_pixel(x + gx, y + gy, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_parse() {
+        assert_eq!(Mode::parse(None), Mode::Bar);
+        assert_eq!(Mode::parse(Some("bar")), Mode::Bar);
+        assert_eq!(Mode::parse(Some("markers")), Mode::Markers);
+        assert_eq!(Mode::parse(Some("off")), Mode::Off);
+        assert_eq!(Mode::parse(Some("rainbow")), Mode::Bar);
+    }
+
+    #[test]
+    fn test_segments_tile_the_bar() {
+        let count = Stage::ALL.len();
+        let (first, _) = segment(512, 508, 0, count);
+        let (last, last_w) = segment(512, 508, count - 1, count);
+        assert_eq!(first, 512);
+        assert_eq!(last + last_w + SEGMENT_GAP, 512 + 508);
+
+        for i in 1..count {
+            let (prev, prev_w) = segment(512, 508, i - 1, count);
+            let (next, _) = segment(512, 508, i, count);
+            assert_eq!(prev + prev_w + SEGMENT_GAP, next);
+        }
+    }
+
+    #[test]
+    fn test_stages_in_order() {
+        assert!(Stage::ALL.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(Stage::Firmware.marker(), Color::new(255, 0, 0));
+    }
+}
@@ -0,0 +1,863 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Virtual Terminals
+//!
+//! This module multiplexes the framebuffer text console between
+//! [`NUM_VTS`] virtual terminals. Each VT keeps its own character grid,
+//! scrollback history, cursor and colors; only the active VT is drawn.
+//!
+//! Output to an inactive VT only updates its grid. Switching VTs redraws
+//! the screen from the new VT's grid.
+//!
+//! A write to the active VT updates the grid first and draws afterwards
+//! ([`VirtualTerminal::write`]): the lines it scrolled move on screen in
+//! one framebuffer scroll, and only the rows it touched are drawn, so a
+//! burst of output does not scroll the framebuffer line by line.
+//!
+//! Each VT also tracks a line selection ("mark") for the paste buffer:
+//! the marked lines end at the current line (the cursor line, or the
+//! bottom of the view while scrolled back) and are drawn inverted.
+//!
+//! Cells hold palette indices, not pixels, so the screen can always be
+//! rebuilt from the grid ([`refresh`]): after a palette change, after a
+//! mode change ([`resize`]), or when a userspace compositor that took the
+//! display ([`take_display`]) gives it back.
+//!
+//! # Locking
+//!
+//! The VTs are behind one lock, taken with interrupts disabled so the
+//! keyboard interrupt cannot find it held on its own CPU. The keyboard's
+//! requests (switch, scroll, mark) do not wait for it either: they are
+//! recorded and carried out by whoever holds the lock, before it is
+//! released (see [`run_pending`]). The panic report skips the locks
+//! altogether ([`force_unlock`]).
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, AtomicBool, AtomicIsize, AtomicU32, AtomicUsize, Ordering};
+use crate::drivers::display::console::{self, TextConsole, DEFAULT_BG, DEFAULT_FG};
+use crate::sync::SpinMutex;
+
+/// Number of virtual terminals
+pub const NUM_VTS: usize = 4;
+
+/// Lines of scrollback kept per VT (in addition to the visible rows)
+pub const SCROLLBACK_LINES: usize = 200;
+
+/// VT that receives kernel console output
+pub const CONSOLE_VT: usize = 0;
+
+/// A single character cell (colors are palette indices)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    ch: u8,
+    fg: u8,
+    bg: u8,
+}
+
+impl Cell {
+    const fn blank(fg: u8, bg: u8) -> Self {
+        Self { ch: b' ', fg, bg }
+    }
+}
+
+/// Virtual terminal state
+///
+/// Lines are stored in a ring of `rows + SCROLLBACK_LINES` lines. `top`
+/// is the ring index of the first visible row; the `history` lines
+/// before it are scrollback.
+pub struct VirtualTerminal {
+    cols: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+    top: usize,
+    history: usize,
+    cursor_x: usize,
+    cursor_y: usize,
+    fg_color: u8,
+    bg_color: u8,
+    /// Lines scrolled back from the live view (0 = live)
+    view_offset: usize,
+    /// Number of marked lines ending at the current line (0 = no mark)
+    mark: usize,
+    /// Lines scrolled off the top so far (wrapping)
+    scrolls: usize,
+}
+
+impl VirtualTerminal {
+    /// Create a blank virtual terminal
+    pub fn new(cols: usize, rows: usize) -> Self {
+        let capacity = rows + SCROLLBACK_LINES;
+        Self {
+            cols,
+            rows,
+            cells: vec![Cell::blank(DEFAULT_FG, DEFAULT_BG); cols * capacity],
+            top: 0,
+            history: 0,
+            cursor_x: 0,
+            cursor_y: 0,
+            fg_color: DEFAULT_FG,
+            bg_color: DEFAULT_BG,
+            view_offset: 0,
+            mark: 0,
+            scrolls: 0,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.rows + SCROLLBACK_LINES
+    }
+
+    /// Ring index of a logical line (0 = oldest line in the history)
+    fn logical_index(&self, line: usize) -> usize {
+        let cap = self.capacity();
+        (self.top + cap - self.history + line) % cap
+    }
+
+    /// Logical line number of the current line
+    fn current_line(&self) -> usize {
+        if self.view_offset == 0 {
+            self.history + self.cursor_y
+        } else {
+            self.history - self.view_offset + self.rows - 1
+        }
+    }
+
+    /// Logical line range covered by the mark (inclusive)
+    fn mark_range(&self) -> Option<(usize, usize)> {
+        if self.mark == 0 {
+            return None;
+        }
+        let end = self.current_line();
+        Some((end + 1 - core::cmp::min(self.mark, end + 1), end))
+    }
+
+    /// Ring index of a visible row, taking the scrollback view into account
+    fn line_index(&self, row: usize, view_offset: usize) -> usize {
+        let cap = self.capacity();
+        (self.top + cap - view_offset + row) % cap
+    }
+
+    fn cell_mut(&mut self, col: usize, row: usize) -> &mut Cell {
+        let line = self.line_index(row, 0);
+        &mut self.cells[line * self.cols + col]
+    }
+
+    /// Get the cursor position (column, row)
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_x, self.cursor_y)
+    }
+
+    /// Get the current colors (foreground, background palette indices)
+    pub fn colors(&self) -> (u8, u8) {
+        (self.fg_color, self.bg_color)
+    }
+
+    /// Set the foreground and background palette indices for subsequent output
+    pub fn set_color(&mut self, fg: u8, bg: u8) {
+        self.fg_color = fg;
+        self.bg_color = bg;
+    }
+
+    /// Number of scrollback lines currently available
+    pub fn history(&self) -> usize {
+        self.history
+    }
+
+    /// Write a cell and draw it if this VT is on screen
+    fn set_cell(&mut self, col: usize, row: usize, ch: u8, screen: &mut Option<&mut TextConsole>) {
+        let cell = Cell { ch, fg: self.fg_color, bg: self.bg_color };
+        *self.cell_mut(col, row) = cell;
+        if let Some(con) = screen {
+            con.draw_cell(col, row, ch, cell.fg, cell.bg);
+        }
+    }
+
+    /// Advance to a new line, scrolling into the history if needed
+    fn line_feed(&mut self, screen: &mut Option<&mut TextConsole>) {
+        self.cursor_x = 0;
+        self.cursor_y += 1;
+        if self.cursor_y < self.rows {
+            return;
+        }
+
+        self.cursor_y = self.rows - 1;
+        self.top = (self.top + 1) % self.capacity();
+        self.history = core::cmp::min(self.history + 1, SCROLLBACK_LINES);
+        self.scrolls = self.scrolls.wrapping_add(1);
+
+        let blank = Cell::blank(self.fg_color, self.bg_color);
+        let line = self.line_index(self.rows - 1, 0);
+        self.cells[line * self.cols..(line + 1) * self.cols].fill(blank);
+
+        if let Some(con) = screen {
+            con.scroll_up();
+        }
+    }
+
+    /// Put a character, drawing it if `screen` is given
+    pub fn put_char(&mut self, ch: u8, mut screen: Option<&mut TextConsole>) {
+        // New output always snaps back to the live view
+        if self.view_offset != 0 {
+            self.view_offset = 0;
+            if let Some(con) = screen.as_deref_mut() {
+                self.redraw(con);
+            }
+        }
+
+        match ch {
+            b'\n' => self.line_feed(&mut screen),
+            b'\r' => self.cursor_x = 0,
+            b'\t' => {
+                self.cursor_x = (self.cursor_x + 8) & !7;
+                if self.cursor_x >= self.cols {
+                    self.line_feed(&mut screen);
+                }
+            }
+            b'\x08' => {
+                if self.cursor_x > 0 {
+                    self.cursor_x -= 1;
+                } else if self.cursor_y > 0 {
+                    self.cursor_y -= 1;
+                    self.cursor_x = self.cols - 1;
+                } else {
+                    return;
+                }
+                self.set_cell(self.cursor_x, self.cursor_y, b' ', &mut screen);
+            }
+            0x20..=0x7E => {
+                self.set_cell(self.cursor_x, self.cursor_y, ch, &mut screen);
+                self.cursor_x += 1;
+                if self.cursor_x >= self.cols {
+                    self.line_feed(&mut screen);
+                }
+            }
+            _ => {
+                // Other control characters - ignore
+            }
+        }
+    }
+
+    /// Put a string of characters, drawing them if `screen` is given
+    ///
+    /// Same result as [`put_char`](Self::put_char) for each byte, but the
+    /// screen is updated once at the end: scrolled lines move in one
+    /// framebuffer scroll (a redraw if the whole screen scrolled), then the
+    /// rows that changed are drawn.
+    pub fn write(&mut self, bytes: &[u8], screen: Option<&mut TextConsole>) {
+        let Some(con) = screen else {
+            for &b in bytes {
+                self.put_char(b, None);
+            }
+            return;
+        };
+
+        // Leaving the scrollback view redraws everything anyway
+        let scrolled_back = self.view_offset != 0;
+        let start = self.scrolls;
+        // Screen rows touched, as they are placed after the scrolling so far
+        let mut dirty = (self.cursor_y, self.cursor_y);
+        for &b in bytes {
+            let (row, scrolls) = (self.cursor_y, self.scrolls);
+            self.put_char(b, None);
+            let shift = self.scrolls.wrapping_sub(scrolls);
+            dirty = (
+                core::cmp::min(dirty.0, row).saturating_sub(shift),
+                core::cmp::max(dirty.1, row).saturating_sub(shift),
+            );
+            dirty = (core::cmp::min(dirty.0, self.cursor_y), core::cmp::max(dirty.1, self.cursor_y));
+        }
+
+        let scrolled = self.scrolls.wrapping_sub(start);
+        if scrolled_back || scrolled >= self.rows {
+            self.redraw(con);
+            return;
+        }
+        if scrolled > 0 {
+            con.scroll_up_by(scrolled);
+        }
+        for row in dirty.0..=dirty.1 {
+            self.draw_row(con, row);
+        }
+    }
+
+    /// Clear the visible rows and home the cursor
+    pub fn clear(&mut self, screen: Option<&mut TextConsole>) {
+        let blank = Cell::blank(self.fg_color, self.bg_color);
+        for row in 0..self.rows {
+            let line = self.line_index(row, 0);
+            self.cells[line * self.cols..(line + 1) * self.cols].fill(blank);
+        }
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.view_offset = 0;
+        if let Some(con) = screen {
+            self.redraw(con);
+        }
+    }
+
+    /// Scroll the view into the history (positive) or back towards live (negative)
+    pub fn scroll_view(&mut self, delta: isize, screen: Option<&mut TextConsole>) {
+        let target = (self.view_offset as isize + delta).clamp(0, self.history as isize) as usize;
+        if target == self.view_offset {
+            return;
+        }
+        self.view_offset = target;
+        if let Some(con) = screen {
+            self.redraw(con);
+        }
+    }
+
+    /// Grow (positive) or shrink (negative) the mark by `delta` lines
+    pub fn adjust_mark(&mut self, delta: isize, screen: Option<&mut TextConsole>) {
+        let max = self.current_line() + 1;
+        let target = (self.mark as isize + delta).clamp(0, max as isize) as usize;
+        if target == self.mark {
+            return;
+        }
+        self.mark = target;
+        if let Some(con) = screen {
+            self.redraw(con);
+        }
+    }
+
+    /// Copy the marked lines (or the current line if nothing is marked)
+    /// into `out` and clear the mark
+    ///
+    /// Trailing blanks are trimmed and lines are joined with `\n`.
+    ///
+    /// # Returns
+    /// Number of bytes written to `out`
+    pub fn copy_mark(&mut self, out: &mut [u8], screen: Option<&mut TextConsole>) -> usize {
+        let end = self.current_line();
+        let (start, end) = self.mark_range().unwrap_or((end, end));
+        let mut n = 0;
+
+        for line in start..=end {
+            let idx = self.logical_index(line);
+            let cells = &self.cells[idx * self.cols..(idx + 1) * self.cols];
+            let len = cells.iter().rposition(|c| c.ch != b' ').map_or(0, |p| p + 1);
+
+            if line != start {
+                if n == out.len() {
+                    break;
+                }
+                out[n] = b'\n';
+                n += 1;
+            }
+            for cell in &cells[..len] {
+                if n == out.len() {
+                    break;
+                }
+                out[n] = cell.ch;
+                n += 1;
+            }
+        }
+
+        self.mark = 0;
+        if let Some(con) = screen {
+            self.redraw(con);
+        }
+        n
+    }
+
+    /// Resize to `cols` x `rows`, keeping the newest lines
+    ///
+    /// Lines are cut or padded on the right. The cursor keeps its row if
+    /// it still fits; lines above it that no longer do move into the
+    /// history. The view returns to live and the mark is dropped.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        if cols == 0 || rows == 0 || (cols, rows) == (self.cols, self.rows) {
+            return;
+        }
+        let mut resized = Self::new(cols, rows);
+        resized.set_color(self.fg_color, self.bg_color);
+
+        // The history and the screen down to the cursor line, as much of
+        // it as the new history holds
+        resized.cursor_x = core::cmp::min(self.cursor_x, cols - 1);
+        resized.cursor_y = core::cmp::min(self.cursor_y, rows - 1);
+        let used = self.history + self.cursor_y + 1;
+        let kept = core::cmp::min(used, SCROLLBACK_LINES + resized.cursor_y + 1);
+        let width = core::cmp::min(cols, self.cols);
+        for line in 0..kept {
+            let from = self.logical_index(used - kept + line) * self.cols;
+            resized.cells[line * cols..line * cols + width].copy_from_slice(&self.cells[from..from + width]);
+        }
+
+        // Kept lines sit at the start of the ring, the cursor line last
+        resized.history = kept - resized.cursor_y - 1;
+        resized.top = resized.history;
+        *self = resized;
+    }
+
+    /// Redraw the whole screen from this VT's grid
+    pub fn redraw(&self, con: &mut TextConsole) {
+        for row in 0..core::cmp::min(self.rows, con.rows()) {
+            self.draw_row(con, row);
+        }
+    }
+
+    /// Draw one visible row of the current view
+    fn draw_row(&self, con: &mut TextConsole, row: usize) {
+        if row >= core::cmp::min(self.rows, con.rows()) {
+            return;
+        }
+        let cols = core::cmp::min(self.cols, con.cols());
+        let line = self.line_index(row, self.view_offset);
+        let logical = self.history - self.view_offset + row;
+        let inverted = self.mark_range().is_some_and(|(s, e)| logical >= s && logical <= e);
+        for col in 0..cols {
+            let cell = self.cells[line * self.cols + col];
+            if inverted {
+                con.draw_cell(col, row, cell.ch, cell.bg, cell.fg);
+            } else {
+                con.draw_cell(col, row, cell.ch, cell.fg, cell.bg);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Global VT State
+// ============================================================================
+
+static VTS: SpinMutex<Option<Vec<VirtualTerminal>>> = SpinMutex::new(None);
+static ACTIVE_VT: AtomicUsize = AtomicUsize::new(CONSOLE_VT);
+
+/// The active VT needs a full redraw (it was switched to)
+static PENDING_REDRAW: AtomicBool = AtomicBool::new(false);
+
+/// Lines to scroll the active VT's view by
+static PENDING_SCROLL: AtomicIsize = AtomicIsize::new(0);
+
+/// Lines to grow the active VT's mark by
+static PENDING_MARK: AtomicIsize = AtomicIsize::new(0);
+
+/// Set by [`force_unlock`]
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// No process owns the display
+const NO_OWNER: u32 = u32::MAX;
+
+/// PID of the process that took the display, or [`NO_OWNER`]
+static DISPLAY_OWNER: AtomicU32 = AtomicU32::new(NO_OWNER);
+
+/// Create the virtual terminals
+///
+/// # Safety
+/// Must be called once, from [`console::init`], after the heap is up.
+pub unsafe fn init(cols: usize, rows: usize) {
+    let mut vts = Vec::with_capacity(NUM_VTS);
+    for _ in 0..NUM_VTS {
+        vts.push(VirtualTerminal::new(cols, rows));
+    }
+    ACTIVE_VT.store(CONSOLE_VT, Ordering::Release);
+    with_vts(|all| *all = Some(vts));
+}
+
+/// Run `f` on the VTs with interrupts disabled, then the keyboard's
+/// pending requests
+fn with_vts<R>(f: impl FnOnce(&mut Option<Vec<VirtualTerminal>>) -> R) -> R {
+    use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
+
+    if panicking() {
+        // SAFETY: racy if another CPU is drawing; see force_unlock
+        let vts = unsafe { &mut *VTS.as_ptr() };
+        let result = f(vts);
+        run_pending(vts);
+        return result;
+    }
+
+    let were_disabled = arch_ints_disabled();
+    arch_disable_ints();
+    let result = {
+        let mut vts = VTS.lock();
+        let result = f(&mut vts);
+        run_pending(&mut vts);
+        result
+    };
+    flush_pending();
+    if !were_disabled {
+        arch_enable_ints();
+    }
+    result
+}
+
+/// Run `f` on a VT, passing the screen if that VT is on screen
+fn with_vt<R>(vt: usize, f: impl FnOnce(&mut VirtualTerminal, Option<&mut TextConsole>) -> R) -> Option<R> {
+    with_vts(|vts| {
+        let term = vts.as_mut()?.get_mut(vt)?;
+        if !on_screen(vt) {
+            return Some(f(term, None));
+        }
+        Some(console::with_renderer(|con| f(term, con.as_mut())))
+    })
+}
+
+/// Check if `vt` is drawn on the screen
+fn on_screen(vt: usize) -> bool {
+    vt == active() && display_owner().is_none()
+}
+
+/// Carry out the keyboard's requests on the active VT
+///
+/// Called with the VTs locked.
+fn run_pending(vts: &mut Option<Vec<VirtualTerminal>>) {
+    let redraw = PENDING_REDRAW.swap(false, Ordering::AcqRel);
+    let scroll = PENDING_SCROLL.swap(0, Ordering::AcqRel);
+    let mark = PENDING_MARK.swap(0, Ordering::AcqRel);
+    let Some(term) = vts.as_mut().and_then(|vts| vts.get_mut(active())) else {
+        return;
+    };
+    if !redraw && scroll == 0 && mark == 0 {
+        return;
+    }
+    console::with_renderer(|con| {
+        let mut screen = if on_screen(active()) { con.as_mut() } else { None };
+        if redraw {
+            if let Some(con) = screen.as_deref_mut() {
+                term.redraw(con);
+            }
+        }
+        if scroll != 0 {
+            term.scroll_view(scroll, screen.as_deref_mut());
+        }
+        if mark != 0 {
+            term.adjust_mark(mark, screen);
+        }
+    });
+}
+
+/// Check if the keyboard left requests for the lock holder
+fn has_pending() -> bool {
+    PENDING_REDRAW.load(Ordering::Acquire)
+        || PENDING_SCROLL.load(Ordering::Acquire) != 0
+        || PENDING_MARK.load(Ordering::Acquire) != 0
+}
+
+/// Carry out pending requests now, unless another CPU holds the VTs
+///
+/// That CPU then runs them before it unlocks, or here after it: a
+/// request recorded just as it unlocked is seen by one of the two.
+fn flush_pending() {
+    if panicking() {
+        with_vts(|_| ());
+        return;
+    }
+    fence(Ordering::SeqCst);
+    while has_pending() {
+        let Some(mut vts) = VTS.try_lock() else { return };
+        run_pending(&mut vts);
+        drop(vts);
+        fence(Ordering::SeqCst);
+    }
+}
+
+/// Stop locking the VTs and the console, for the panic report
+///
+/// A panic can happen with either lock held, on this CPU or another, and
+/// the report must still reach the screen. Other CPUs may draw at the
+/// same time; that is accepted.
+pub fn force_unlock() {
+    PANICKING.store(true, Ordering::Release);
+}
+
+/// Check if [`force_unlock`] was called
+pub(crate) fn panicking() -> bool {
+    PANICKING.load(Ordering::Acquire)
+}
+
+/// Get the index of the active VT
+pub fn active() -> usize {
+    ACTIVE_VT.load(Ordering::Acquire)
+}
+
+/// Switch the display to another VT
+///
+/// Safe from the keyboard interrupt: the redraw is left to the lock
+/// holder if the VTs are locked.
+///
+/// # Returns
+/// `true` if `vt` is valid (switching to the active VT is a no-op)
+pub fn switch_to(vt: usize) -> bool {
+    if vt >= NUM_VTS {
+        return false;
+    }
+    if vt == ACTIVE_VT.swap(vt, Ordering::AcqRel) {
+        return true;
+    }
+    PENDING_REDRAW.store(true, Ordering::Release);
+    flush_pending();
+    true
+}
+
+/// Redraw the active VT from its grid, margins included
+pub fn refresh() {
+    with_vt(active(), |term, screen| {
+        if let Some(con) = screen {
+            con.clear_margins(term.colors().1);
+            term.redraw(con);
+        }
+    });
+}
+
+/// Resize every VT to a new grid and redraw the active one
+///
+/// Called by [`console::set_framebuffer`] after a mode change.
+pub fn resize(cols: usize, rows: usize) {
+    with_vts(|vts| {
+        for term in vts.iter_mut().flatten() {
+            term.resize(cols, rows);
+        }
+    });
+    refresh();
+}
+
+/// `VT_CONTROL` syscall commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VtCommand {
+    /// Redraw the active VT from its grid
+    Refresh = 0,
+    /// Stop drawing VTs; the caller owns the display
+    TakeDisplay = 1,
+    /// Give the display back and redraw
+    ReleaseDisplay = 2,
+    /// Change a palette entry and redraw
+    SetPalette = 3,
+}
+
+impl VtCommand {
+    /// Convert from the `VT_CONTROL` syscall argument
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Refresh),
+            1 => Some(Self::TakeDisplay),
+            2 => Some(Self::ReleaseDisplay),
+            3 => Some(Self::SetPalette),
+            _ => None,
+        }
+    }
+}
+
+/// Hand the display to a process (a userspace compositor)
+///
+/// The VTs keep updating their grids but stop drawing until
+/// [`release_display`].
+///
+/// # Returns
+/// `false` if another process holds the display
+pub fn take_display(pid: u32) -> bool {
+    match DISPLAY_OWNER.compare_exchange(NO_OWNER, pid, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => true,
+        Err(owner) => owner == pid,
+    }
+}
+
+/// Take the display back from `pid` and redraw the active VT
+///
+/// Also called when the owner exits, so a crashed compositor does not
+/// leave a dead screen.
+///
+/// # Returns
+/// `false` if `pid` did not hold the display
+pub fn release_display(pid: u32) -> bool {
+    if DISPLAY_OWNER.compare_exchange(pid, NO_OWNER, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        return false;
+    }
+    refresh();
+    true
+}
+
+/// Take the display back from whoever holds it (for the panic report)
+pub fn reclaim_display() {
+    if DISPLAY_OWNER.swap(NO_OWNER, Ordering::AcqRel) != NO_OWNER {
+        refresh();
+    }
+}
+
+/// PID of the process holding the display, if any
+pub fn display_owner() -> Option<u32> {
+    match DISPLAY_OWNER.load(Ordering::Acquire) {
+        NO_OWNER => None,
+        pid => Some(pid),
+    }
+}
+
+/// Write bytes to a VT
+pub fn write(vt: usize, bytes: &[u8]) {
+    with_vt(vt, |term, screen| term.write(bytes, screen));
+}
+
+/// Write a single character to a VT
+pub fn put_char(vt: usize, ch: u8) {
+    with_vt(vt, |term, screen| term.put_char(ch, screen));
+}
+
+/// Clear a VT
+pub fn clear(vt: usize) {
+    with_vt(vt, |term, screen| term.clear(screen));
+}
+
+/// Set a VT's colors (palette indices)
+pub fn set_color(vt: usize, fg: u8, bg: u8) {
+    with_vt(vt, |term, _| term.set_color(fg, bg));
+}
+
+/// Get a VT's colors (palette indices)
+pub fn get_color(vt: usize) -> Option<(u8, u8)> {
+    with_vt(vt, |term, _| term.colors())
+}
+
+/// Scroll the active VT's view by `delta` lines (positive = back in history)
+///
+/// Safe from the keyboard interrupt, like [`switch_to`].
+pub fn scroll_active(delta: isize) {
+    PENDING_SCROLL.fetch_add(delta, Ordering::AcqRel);
+    flush_pending();
+}
+
+/// Grow or shrink the active VT's mark by `delta` lines
+///
+/// Safe from the keyboard interrupt, like [`switch_to`].
+pub fn adjust_mark_active(delta: isize) {
+    PENDING_MARK.fetch_add(delta, Ordering::AcqRel);
+    flush_pending();
+}
+
+/// Copy the active VT's marked lines into `out`
+///
+/// For the keyboard interrupt: copies nothing if another CPU holds the
+/// VTs.
+///
+/// # Returns
+/// Number of bytes written to `out`
+pub fn copy_mark_active(out: &mut [u8]) -> usize {
+    let Some(mut vts) = VTS.try_lock() else { return 0 };
+    run_pending(&mut vts);
+    let copied = vts.as_mut().and_then(|vts| vts.get_mut(active())).map(|term| {
+        console::with_renderer(|con| term.copy_mark(out, if on_screen(active()) { con.as_mut() } else { None }))
+    });
+    drop(vts);
+    flush_pending();
+    copied.unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row_text(vt: &VirtualTerminal, row: usize) -> Vec<u8> {
+        let line = vt.line_index(row, vt.view_offset);
+        vt.cells[line * vt.cols..(line + 1) * vt.cols].iter().map(|c| c.ch).collect()
+    }
+
+    #[test]
+    fn test_scrollback() {
+        let mut vt = VirtualTerminal::new(4, 2);
+        for &b in b"a\nb\nc\n" {
+            vt.put_char(b, None);
+        }
+        assert_eq!(vt.history(), 2);
+        assert_eq!(row_text(&vt, 0), b"c   ");
+
+        vt.scroll_view(1, None);
+        assert_eq!(row_text(&vt, 0), b"b   ");
+        vt.scroll_view(10, None);
+        assert_eq!(row_text(&vt, 0), b"a   ");
+
+        vt.put_char(b'x', None);
+        assert_eq!(vt.view_offset, 0);
+    }
+
+    #[test]
+    fn test_copy_mark() {
+        let mut vt = VirtualTerminal::new(8, 3);
+        for &b in b"one\ntwo\nthree" {
+            vt.put_char(b, None);
+        }
+
+        let mut out = [0u8; 32];
+        let n = vt.copy_mark(&mut out, None);
+        assert_eq!(&out[..n], b"three");
+
+        vt.adjust_mark(2, None);
+        let n = vt.copy_mark(&mut out, None);
+        assert_eq!(&out[..n], b"two\nthree");
+        assert_eq!(vt.mark, 0);
+    }
+
+    #[test]
+    fn test_resize_keeps_cursor_line() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        for &b in b"a\nb\nc\nd" {
+            vt.put_char(b, None);
+        }
+        vt.scroll_view(1, None);
+
+        // Shorter and narrower: the lines above the cursor go to history
+        vt.resize(2, 2);
+        assert_eq!(vt.cursor(), (1, 1));
+        assert_eq!(vt.view_offset, 0);
+        assert_eq!(row_text(&vt, 0), b"c ");
+        assert_eq!(row_text(&vt, 1), b"d ");
+        assert_eq!(vt.history(), 2);
+        vt.scroll_view(2, None);
+        assert_eq!(row_text(&vt, 0), b"a ");
+
+        // Taller: the cursor keeps its row, the rows below it are blank
+        vt.resize(6, 4);
+        assert_eq!(vt.cursor(), (1, 1));
+        assert_eq!(row_text(&vt, 0), b"c     ");
+        assert_eq!(row_text(&vt, 2), b"      ");
+        vt.put_char(b'x', None);
+        assert_eq!(row_text(&vt, 1), b"dx    ");
+    }
+
+    #[test]
+    fn test_redraw_uses_palette() {
+        use crate::drivers::display::framebuffer::{Color, Framebuffer, PixelFormat};
+
+        // 1 column by 1 row of 8x16 cells
+        let mut pixels = vec![0u32; 8 * 16];
+        let mut con = TextConsole::new(Framebuffer::new(pixels.as_mut_ptr() as u64, 8, 16, 32, 32, PixelFormat::RGB));
+        let vt = VirtualTerminal::new(1, 1);
+        vt.redraw(&mut con);
+        assert!(pixels.iter().all(|&p| p == Color::BLACK.to_rgba32()));
+
+        let blue = Color::new(0, 0, 200);
+        assert!(con.set_palette_color(DEFAULT_BG as usize, blue));
+        assert!(!con.set_palette_color(console::PALETTE_SIZE, blue));
+        vt.redraw(&mut con);
+        assert!(pixels.iter().all(|&p| p == blue.to_rgba32()));
+    }
+
+    #[test]
+    fn test_batched_write_draws_the_same() {
+        use crate::drivers::display::framebuffer::{Framebuffer, PixelFormat};
+
+        // 8 columns by 4 rows of 8x16 cells
+        let screen = |pixels: &mut Vec<u32>| {
+            TextConsole::new(Framebuffer::new(pixels.as_mut_ptr() as u64, 64, 64, 256, 32, PixelFormat::RGB))
+        };
+        let (mut one, mut batch) = (vec![0u32; 64 * 64], vec![0u32; 64 * 64]);
+        let (mut vt_one, mut vt_batch) = (VirtualTerminal::new(8, 4), VirtualTerminal::new(8, 4));
+        screen(&mut one).clear();
+        screen(&mut batch).clear();
+
+        // A few lines scrolled, then more lines than the screen holds
+        for text in [&b"ab\ncd\tX\x08y\nef\ngh\n12"[..], b"0\n1\n2\n3\n4\n5\n67890123456"] {
+            let mut con = screen(&mut one);
+            for &b in text {
+                vt_one.put_char(b, Some(&mut con));
+            }
+            vt_batch.write(text, Some(&mut screen(&mut batch)));
+            assert!(one == batch);
+            assert_eq!(vt_one.cursor(), vt_batch.cursor());
+        }
+    }
+}
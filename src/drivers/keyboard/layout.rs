@@ -335,6 +335,23 @@ pub fn scancode_to_keyevent(scancode: u8, modifiers: &ModifierState, extended: b
         return KeyEvent::Release(code);
     }
 
+    // Function keys
+    match code {
+        0x3B => return KeyEvent::Special(SpecialKey::F1),
+        0x3C => return KeyEvent::Special(SpecialKey::F2),
+        0x3D => return KeyEvent::Special(SpecialKey::F3),
+        0x3E => return KeyEvent::Special(SpecialKey::F4),
+        0x3F => return KeyEvent::Special(SpecialKey::F5),
+        0x40 => return KeyEvent::Special(SpecialKey::F6),
+        0x41 => return KeyEvent::Special(SpecialKey::F7),
+        0x42 => return KeyEvent::Special(SpecialKey::F8),
+        0x43 => return KeyEvent::Special(SpecialKey::F9),
+        0x44 => return KeyEvent::Special(SpecialKey::F10),
+        0x57 => return KeyEvent::Special(SpecialKey::F11),
+        0x58 => return KeyEvent::Special(SpecialKey::F12),
+        _ => {}
+    }
+
     // Regular ASCII keys - use appropriate table based on shift state
     let shift = modifiers.shift() ^ modifiers.caps_lock;
     let table = if shift {
@@ -386,6 +403,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_function_keys() {
+        let m = ModifierState::new();
+        assert_eq!(scancode_to_keyevent(0x3B, &m, false), KeyEvent::Special(SpecialKey::F1));
+        assert_eq!(scancode_to_keyevent(0x3E, &m, false), KeyEvent::Special(SpecialKey::F4));
+        assert_eq!(scancode_to_keyevent(0x58, &m, false), KeyEvent::Special(SpecialKey::F12));
+    }
+
     #[test]
     fn test_scancode_to_ascii_shifted() {
         let mut m = ModifierState::new();
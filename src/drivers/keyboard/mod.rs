@@ -13,6 +13,11 @@
 //! - Special key support (arrows, home, end, etc.)
 //! - Circular buffer for keyboard events
 //!
+//! Decoded key events are handed to the TTY line discipline
+//! ([`crate::drivers::tty`]), which queues input per virtual terminal.
+//! Every scancode is also published as an [`InputRecord`] to ring buffers
+//! created for [`RingSource::Input`](crate::object::RingSource).
+//!
 //! ## Hardware
 //! - Data port: 0x60
 //! - Command/status port: 0x64
@@ -40,6 +45,8 @@ pub mod ps2;
 pub mod layout;
 
 use core::sync::atomic::{AtomicBool, Ordering};
+use crate::drivers::tty;
+use crate::object::ringbuf::{self, RingSource};
 
 // Re-exports
 pub use layout::{
@@ -52,8 +59,68 @@ pub use ps2::{
     controller_status, read_data_port,
 };
 
-/// Global input buffer for keyboard events
-static mut INPUT_BUFFER: CircularBuffer<u8, INPUT_BUFFER_SIZE> = CircularBuffer::new();
+/// Raw keyboard event streamed to input ring buffers
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputRecord {
+    /// Timestamp in nanoseconds since boot
+    pub ts_ns: u64,
+    /// Scancode (set 1, without the 0xE0 prefix)
+    pub scancode: u8,
+    /// `INPUT_FLAG_*` bits
+    pub flags: u8,
+    /// ASCII character for the key, or 0
+    pub ascii: u8,
+    /// `INPUT_MOD_*` bits, before this event is applied
+    pub modifiers: u8,
+    /// Reserved, zero
+    pub reserved: u32,
+}
+
+/// Key was released
+pub const INPUT_FLAG_RELEASE: u8 = 1 << 0;
+/// Scancode had the 0xE0 prefix
+pub const INPUT_FLAG_EXTENDED: u8 = 1 << 1;
+
+/// A Shift key is held
+pub const INPUT_MOD_SHIFT: u8 = 1 << 0;
+/// A Ctrl key is held
+pub const INPUT_MOD_CTRL: u8 = 1 << 1;
+/// An Alt key is held
+pub const INPUT_MOD_ALT: u8 = 1 << 2;
+/// Caps Lock is on
+pub const INPUT_MOD_CAPS_LOCK: u8 = 1 << 3;
+
+impl InputRecord {
+    /// Build a record for `scancode` with the given modifier state
+    pub fn new(ts_ns: u64, scancode: u8, extended: bool, event: &KeyEvent, modifiers: &ModifierState) -> Self {
+        let mut flags = 0;
+        if scancode & 0x80 != 0 {
+            flags |= INPUT_FLAG_RELEASE;
+        }
+        if extended {
+            flags |= INPUT_FLAG_EXTENDED;
+        }
+        let mut mods = 0;
+        if modifiers.shift() {
+            mods |= INPUT_MOD_SHIFT;
+        }
+        if modifiers.ctrl() {
+            mods |= INPUT_MOD_CTRL;
+        }
+        if modifiers.alt() {
+            mods |= INPUT_MOD_ALT;
+        }
+        if modifiers.caps_lock {
+            mods |= INPUT_MOD_CAPS_LOCK;
+        }
+        let ascii = match event {
+            KeyEvent::Ascii(c) => *c,
+            _ => 0,
+        };
+        Self { ts_ns, scancode: scancode & 0x7F, flags, ascii, modifiers: mods, reserved: 0 }
+    }
+}
 
 /// Current modifier state
 static mut MODIFIER_STATE: ModifierState = ModifierState::new();
@@ -77,7 +144,6 @@ static INITIALIZED: AtomicBool = AtomicBool::new(false);
 /// It should be called before enabling interrupts.
 pub unsafe fn init() {
     // Reset state
-    INPUT_BUFFER = CircularBuffer::new();
     MODIFIER_STATE = ModifierState::new();
     EXTENDED_SCANCODE = false;
 
@@ -140,12 +206,15 @@ pub unsafe fn handle_irq() {
     // Process the scancode
     let keyevent = scancode_to_keyevent(scancode, &MODIFIER_STATE, extended);
 
-    // Update modifier state and write to buffer
+    if ringbuf::has_listeners() {
+        let ts_ns = crate::time::Instant::now().as_nanos();
+        let record = InputRecord::new(ts_ns, scancode, extended, &keyevent, &MODIFIER_STATE);
+        // SAFETY: InputRecord is repr(C) with no padding (8+1+1+1+1+4 bytes)
+        ringbuf::publish(RingSource::Input, ringbuf::record_bytes(&record));
+    }
+
+    // Update modifier state; everything else goes to the line discipline
     match keyevent {
-        KeyEvent::Ascii(ascii) => {
-            // Regular ASCII character - write to buffer
-            INPUT_BUFFER.write(ascii);
-        }
         KeyEvent::Special(special) => {
             match special {
                 // Modifier keys - update state
@@ -158,25 +227,10 @@ pub unsafe fn handle_irq() {
                 SpecialKey::CapsLock => {
                     MODIFIER_STATE.caps_lock = !MODIFIER_STATE.caps_lock;
                 }
-                // Backspace - write as control character
-                SpecialKey::Backspace => {
-                    INPUT_BUFFER.write(0x08);
-                }
-                // Enter - write as newline
-                SpecialKey::Enter => {
-                    INPUT_BUFFER.write(b'\n');
-                }
-                // Tab - write as tab character
-                SpecialKey::Tab => {
-                    INPUT_BUFFER.write(b'\t');
-                }
-                // Other special keys - for future use (arrows, etc.)
-                _ => {
-                    // Arrow keys and other special keys could be handled here
-                    // For now, we ignore them or could write special escape sequences
-                }
+                _ => tty::receive_key(keyevent, &MODIFIER_STATE),
             }
         }
+        KeyEvent::Ascii(_) => tty::receive_key(keyevent, &MODIFIER_STATE),
         KeyEvent::Release(code) => {
             // Key release - update modifier state
             match code {
@@ -204,7 +258,7 @@ pub unsafe fn handle_irq() {
     }
 }
 
-/// Read a single character from the keyboard buffer
+/// Read a single character from the active TTY's input queue
 ///
 /// # Returns
 /// * `Some(char)` - Character if available
@@ -213,20 +267,16 @@ pub unsafe fn handle_irq() {
 /// # Note
 /// This function is non-blocking. Returns immediately if no data is available.
 pub fn read_char() -> Option<char> {
-    unsafe {
-        INPUT_BUFFER.read().map(|b| b as char)
-    }
+    tty::read_byte(tty::active()).map(|b| b as char)
 }
 
-/// Check if keyboard data is available
+/// Check if keyboard data is available on the active TTY
 ///
 /// # Returns
 /// * `true` - At least one character is available
 /// * `false` - Buffer is empty
 pub fn has_data() -> bool {
-    unsafe {
-        INPUT_BUFFER.has_data()
-    }
+    tty::has_input(tty::active())
 }
 
 /// Get the current modifier state
@@ -239,25 +289,19 @@ pub fn get_modifiers() -> ModifierState {
     }
 }
 
-/// Flush the input buffer (discard all pending characters)
+/// Flush the active TTY's input queue (discard all pending characters)
 pub fn flush() {
-    unsafe {
-        while INPUT_BUFFER.read().is_some() {}
-    }
+    tty::flush(tty::active());
 }
 
-/// Get the number of characters available in the buffer
+/// Get the number of characters available on the active TTY
 pub fn available() -> usize {
-    unsafe {
-        INPUT_BUFFER.available()
-    }
+    tty::available(tty::active())
 }
 
-/// Check if the buffer is full
+/// Check if the active TTY's input queue is full
 pub fn is_full() -> bool {
-    unsafe {
-        INPUT_BUFFER.is_full()
-    }
+    tty::is_full(tty::active())
 }
 
 /// Check if keyboard driver has been initialized
@@ -274,6 +318,18 @@ mod tests {
         assert!(!is_initialized());
     }
 
+    #[test]
+    fn test_input_record() {
+        let mut m = ModifierState::new();
+        m.left_shift = true;
+        let r = InputRecord::new(5, 0x9E, true, &KeyEvent::Ascii(b'A'), &m);
+        assert_eq!(core::mem::size_of::<InputRecord>(), 16);
+        assert_eq!(r.scancode, 0x1E);
+        assert_eq!(r.flags, INPUT_FLAG_RELEASE | INPUT_FLAG_EXTENDED);
+        assert_eq!(r.modifiers, INPUT_MOD_SHIFT);
+        assert_eq!(r.ascii, b'A');
+    }
+
     #[test]
     fn test_modifiers_initial_state() {
         unsafe {
@@ -18,7 +18,27 @@ pub mod keyboard;
 /// Display drivers (framebuffer, console)
 pub mod display;
 
+/// TTYs and line discipline (one per virtual terminal)
+pub mod tty;
+
+/// Paste buffer shared between virtual terminals
+pub mod paste;
+
+/// PCI configuration space, enumeration and BAR assignment
+pub mod pci;
+
+/// Block device layer and RAM-backed disks
+pub mod block;
+
 // Re-exports
 pub use uart::{Uart16550, COM1_PORT, COM2_PORT, COM3_PORT, COM4_PORT, init_com1, com1};
 pub use keyboard::{KeyEvent, ModifierState, SpecialKey};
 pub use display::{Framebuffer, Color, PixelFormat, init as display_init, write_str as display_write};
+
+/// Quiesce devices before power off or reset
+///
+/// There are no per-driver remove hooks: the console drivers hold no
+/// state that outlives the machine, so only DMA needs to be stopped.
+pub fn quiesce() {
+    pci::quiesce();
+}
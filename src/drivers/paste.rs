@@ -0,0 +1,94 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Paste Buffer
+//!
+//! A single kernel-wide paste buffer shared by all virtual terminals.
+//! Text is copied into it from VT scrollback (Ctrl+Insert) or by
+//! userspace (`CLIPBOARD_SET`), and pasted into the active TTY's input
+//! queue (Shift+Insert) or read back by userspace (`CLIPBOARD_GET`).
+
+use crate::sync::SpinMutex;
+
+/// Maximum paste buffer size in bytes
+pub const PASTE_BUFFER_SIZE: usize = 4096;
+
+struct PasteBuffer {
+    data: [u8; PASTE_BUFFER_SIZE],
+    len: usize,
+}
+
+static PASTE: SpinMutex<PasteBuffer> = SpinMutex::new(PasteBuffer {
+    data: [0; PASTE_BUFFER_SIZE],
+    len: 0,
+});
+
+/// Replace the paste buffer contents
+///
+/// # Returns
+/// Number of bytes stored (input beyond [`PASTE_BUFFER_SIZE`] is dropped)
+pub fn set(bytes: &[u8]) -> usize {
+    store(&mut PASTE.lock(), bytes)
+}
+
+/// Replace the paste buffer contents from interrupt context
+///
+/// # Returns
+/// Number of bytes stored, or `None` if the buffer is busy
+pub fn try_set(bytes: &[u8]) -> Option<usize> {
+    PASTE.try_lock().map(|mut buf| store(&mut buf, bytes))
+}
+
+fn store(buf: &mut PasteBuffer, bytes: &[u8]) -> usize {
+    let n = core::cmp::min(bytes.len(), PASTE_BUFFER_SIZE);
+    buf.data[..n].copy_from_slice(&bytes[..n]);
+    buf.len = n;
+    n
+}
+
+/// Copy the paste buffer contents into `out`
+///
+/// # Returns
+/// Number of bytes copied
+pub fn get(out: &mut [u8]) -> usize {
+    let buf = PASTE.lock();
+    let n = core::cmp::min(out.len(), buf.len);
+    out[..n].copy_from_slice(&buf.data[..n]);
+    n
+}
+
+/// Current length of the paste buffer contents
+pub fn len() -> usize {
+    PASTE.lock().len
+}
+
+/// Run `f` on each byte of the paste buffer from interrupt context
+///
+/// Does nothing if the buffer is busy.
+pub fn try_for_each(mut f: impl FnMut(u8)) {
+    if let Some(buf) = PASTE.try_lock() {
+        for &b in &buf.data[..buf.len] {
+            f(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_truncates() {
+        let big = [b'x'; PASTE_BUFFER_SIZE + 10];
+        assert_eq!(set(&big), PASTE_BUFFER_SIZE);
+
+        assert_eq!(set(b"hello"), 5);
+        let mut out = [0u8; 3];
+        assert_eq!(get(&mut out), 3);
+        assert_eq!(&out, b"hel");
+        assert_eq!(len(), 5);
+    }
+}
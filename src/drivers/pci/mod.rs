@@ -0,0 +1,525 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! PCI Bus
+//!
+//! Configuration space is reached through the legacy `0xCF8`/`0xCFC` port
+//! pair. [`init`] scans every bus once at boot and records the functions
+//! it finds; drivers probe from [`devices`].
+//!
+//! # BAR Assignment
+//!
+//! Firmware does not always program every BAR (some boards, and QEMU
+//! with devices it does not boot from). [`init`] sizes each BAR and gives
+//! the unassigned ones on the root bus an address from the host bridge's
+//! windows ([`resource`]), largest first, so a driver never sees a zero
+//! BAR:
+//!
+//! 1. BARs and bridge windows the firmware programmed are claimed as-is
+//! 2. Each unassigned BAR gets a naturally aligned range; memory BARs
+//!    get at least a page so they can be mapped on their own
+//! 3. I/O and memory decoding is enabled for the functions that got one
+//!
+//! Functions behind a PCI-to-PCI bridge are left alone: their addresses
+//! must fit the bridge's forwarding windows, which are not reprogrammed.
+
+pub mod resource;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::ioport::{inl, inw, outl, outw};
+use crate::sync::SpinMutex;
+use resource::{HostBridgeWindows, Window};
+use crate::{kinfo, kwarn};
+
+/// Configuration address port
+const CONFIG_ADDRESS: u16 = 0xCF8;
+
+/// Configuration data port
+const CONFIG_DATA: u16 = 0xCFC;
+
+/// Configuration space offsets
+const REG_VENDOR_ID: u8 = 0x00;
+const REG_DEVICE_ID: u8 = 0x02;
+const REG_COMMAND: u8 = 0x04;
+const REG_CLASS: u8 = 0x08;
+const REG_HEADER_TYPE: u8 = 0x0E;
+const REG_BAR0: u8 = 0x10;
+const REG_BRIDGE_IO: u8 = 0x1C;
+const REG_BRIDGE_MEM: u8 = 0x20;
+const REG_BRIDGE_PREF_MEM: u8 = 0x24;
+const REG_BRIDGE_PREF_BASE_HI: u8 = 0x28;
+const REG_BRIDGE_PREF_LIMIT_HI: u8 = 0x2C;
+
+/// Command register bits
+const COMMAND_IO: u16 = 1 << 0;
+const COMMAND_MEM: u16 = 1 << 1;
+const COMMAND_MASTER: u16 = 1 << 2;
+
+/// Header type of a PCI-to-PCI bridge
+const HEADER_BRIDGE: u8 = 0x01;
+
+/// Header type bit set on multi-function devices
+const HEADER_MULTI_FUNCTION: u8 = 0x80;
+
+/// Smallest range given to a memory BAR
+const MIN_MEM_BAR: u64 = 0x1000;
+
+/// Serializes use of the address/data port pair
+static CONFIG_LOCK: SpinMutex<()> = SpinMutex::new(());
+
+/// Functions found by [`init`]
+static DEVICES: SpinMutex<Vec<PciDevice>> = SpinMutex::new(Vec::new());
+
+/// End of RAM below 4 GiB, from the firmware memory map
+static LOW_RAM_TOP: AtomicU64 = AtomicU64::new(0);
+
+// ============================================================================
+// Configuration Space
+// ============================================================================
+
+/// Location of a function on the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PciAddress {
+    /// Bus number
+    pub bus: u8,
+    /// Device number (0-31)
+    pub device: u8,
+    /// Function number (0-7)
+    pub function: u8,
+}
+
+impl PciAddress {
+    /// Create an address (`device` < 32, `function` < 8)
+    pub const fn new(bus: u8, device: u8, function: u8) -> Self {
+        Self { bus, device, function }
+    }
+
+    /// Value written to `CONFIG_ADDRESS` to reach `offset`
+    const fn config_address(self, offset: u8) -> u32 {
+        0x8000_0000
+            | ((self.bus as u32) << 16)
+            | ((self.device as u32 & 0x1F) << 11)
+            | ((self.function as u32 & 0x7) << 8)
+            | (offset as u32 & 0xFC)
+    }
+
+    /// Read a dword of configuration space (`offset` is dword aligned)
+    pub fn read32(self, offset: u8) -> u32 {
+        let _guard = CONFIG_LOCK.lock();
+        unsafe {
+            outl(CONFIG_ADDRESS, self.config_address(offset));
+            inl(CONFIG_DATA)
+        }
+    }
+
+    /// Write a dword of configuration space (`offset` is dword aligned)
+    pub fn write32(self, offset: u8, value: u32) {
+        let _guard = CONFIG_LOCK.lock();
+        unsafe {
+            outl(CONFIG_ADDRESS, self.config_address(offset));
+            outl(CONFIG_DATA, value);
+        }
+    }
+
+    /// Read a word of configuration space (`offset` is word aligned)
+    pub fn read16(self, offset: u8) -> u16 {
+        let _guard = CONFIG_LOCK.lock();
+        unsafe {
+            outl(CONFIG_ADDRESS, self.config_address(offset));
+            inw(CONFIG_DATA + (offset as u16 & 2))
+        }
+    }
+
+    /// Write a word of configuration space (`offset` is word aligned)
+    ///
+    /// Unlike a dword write, this leaves the neighbouring word alone, so
+    /// writing the command register does not clear status bits.
+    pub fn write16(self, offset: u8, value: u16) {
+        let _guard = CONFIG_LOCK.lock();
+        unsafe {
+            outl(CONFIG_ADDRESS, self.config_address(offset));
+            outw(CONFIG_DATA + (offset as u16 & 2), value);
+        }
+    }
+
+    /// Read a byte of configuration space
+    pub fn read8(self, offset: u8) -> u8 {
+        (self.read16(offset & !1) >> ((offset & 1) * 8)) as u8
+    }
+}
+
+// ============================================================================
+// BARs
+// ============================================================================
+
+/// What a BAR decodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarKind {
+    /// I/O ports
+    Io,
+    /// Memory below 4 GiB
+    Mem32,
+    /// Memory anywhere (the BAR spans two registers)
+    Mem64,
+}
+
+/// A sized base address register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bar {
+    /// BAR number (0-5); a 64-bit BAR also uses the next one
+    pub index: u8,
+    /// Address space decoded
+    pub kind: BarKind,
+    /// Memory that may be prefetched
+    pub prefetchable: bool,
+    /// Bus address, 0 if unassigned
+    pub base: u64,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// Decode a BAR's kind, prefetchability and address from its registers
+///
+/// `high` is only used for 64-bit BARs.
+fn decode_bar(low: u32, high: u32) -> (BarKind, bool, u64) {
+    if low & 1 != 0 {
+        return (BarKind::Io, false, (low & !0x3) as u64);
+    }
+    let prefetchable = low & 0x8 != 0;
+    let base = (low & !0xF) as u64;
+    match (low >> 1) & 0x3 {
+        0x2 => (BarKind::Mem64, prefetchable, base | ((high as u64) << 32)),
+        _ => (BarKind::Mem32, prefetchable, base),
+    }
+}
+
+/// Size of a BAR from the value read back after writing all ones
+///
+/// Returns 0 for an unimplemented BAR.
+fn bar_size(kind: BarKind, mask_low: u32, mask_high: u32) -> u64 {
+    match kind {
+        // The upper half of an I/O BAR may read back as zero
+        BarKind::Io => match mask_low & 0xFFFC {
+            0 => 0,
+            m => (!m & 0xFFFF) as u64 + 1,
+        },
+        BarKind::Mem32 => match mask_low & !0xF {
+            0 => 0,
+            m => (!m) as u64 + 1,
+        },
+        BarKind::Mem64 => match ((mask_high as u64) << 32) | (mask_low & !0xF) as u64 {
+            0 => 0,
+            m => (!m).wrapping_add(1),
+        },
+    }
+}
+
+/// Size the first `count` BARs of a function
+///
+/// Decoding is turned off while the BARs hold all ones, then restored.
+fn probe_bars(addr: PciAddress, count: u8) -> Vec<Bar> {
+    let command = addr.read16(REG_COMMAND);
+    addr.write16(REG_COMMAND, command & !(COMMAND_IO | COMMAND_MEM));
+
+    let mut bars = Vec::new();
+    let mut index = 0;
+    while index < count {
+        let reg = REG_BAR0 + index * 4;
+        let low = addr.read32(reg);
+        addr.write32(reg, 0xFFFF_FFFF);
+        let mask_low = addr.read32(reg);
+        addr.write32(reg, low);
+
+        let wide = low & 0x7 == 0x4 && index + 1 < count;
+        let (high, mask_high) = if wide {
+            let high = addr.read32(reg + 4);
+            addr.write32(reg + 4, 0xFFFF_FFFF);
+            let mask_high = addr.read32(reg + 4);
+            addr.write32(reg + 4, high);
+            (high, mask_high)
+        } else {
+            (0, 0)
+        };
+
+        let (kind, prefetchable, base) = decode_bar(low, high);
+        let size = bar_size(kind, mask_low, mask_high);
+        if size != 0 {
+            bars.push(Bar { index, kind, prefetchable, base, size });
+        }
+        index += if wide { 2 } else { 1 };
+    }
+
+    addr.write16(REG_COMMAND, command);
+    bars
+}
+
+/// Program a BAR's address
+fn write_bar(addr: PciAddress, bar: &Bar) {
+    let reg = REG_BAR0 + bar.index * 4;
+    let flags = addr.read32(reg) & if bar.kind == BarKind::Io { 0x3 } else { 0xF };
+    addr.write32(reg, bar.base as u32 | flags);
+    if bar.kind == BarKind::Mem64 {
+        addr.write32(reg + 4, (bar.base >> 32) as u32);
+    }
+}
+
+// ============================================================================
+// Enumeration
+// ============================================================================
+
+/// A function found on the bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PciDevice {
+    /// Location on the bus
+    pub addr: PciAddress,
+    /// Vendor ID
+    pub vendor_id: u16,
+    /// Device ID
+    pub device_id: u16,
+    /// Class code
+    pub class: u8,
+    /// Subclass code
+    pub subclass: u8,
+    /// Programming interface
+    pub prog_if: u8,
+    /// Header layout, without the multi-function bit
+    pub header_type: u8,
+    /// Implemented BARs
+    pub bars: Vec<Bar>,
+}
+
+impl PciDevice {
+    /// Read a function's identity and size its BARs
+    fn probe(addr: PciAddress) -> Option<Self> {
+        let vendor_id = addr.read16(REG_VENDOR_ID);
+        if vendor_id == 0xFFFF {
+            return None;
+        }
+        let class = addr.read32(REG_CLASS);
+        let header_type = addr.read8(REG_HEADER_TYPE) & !HEADER_MULTI_FUNCTION;
+        let bar_count = match header_type {
+            0x00 => 6,
+            HEADER_BRIDGE => 2,
+            _ => 0,
+        };
+        Some(Self {
+            addr,
+            vendor_id,
+            device_id: addr.read16(REG_DEVICE_ID),
+            class: (class >> 24) as u8,
+            subclass: (class >> 16) as u8,
+            prog_if: (class >> 8) as u8,
+            header_type,
+            bars: probe_bars(addr, bar_count),
+        })
+    }
+
+    /// Whether this is a PCI-to-PCI bridge
+    pub fn is_bridge(&self) -> bool {
+        self.header_type == HEADER_BRIDGE
+    }
+}
+
+/// Find every function on every bus
+fn scan() -> Vec<PciDevice> {
+    let mut found = Vec::new();
+    for bus in 0..=255u8 {
+        for device in 0..32u8 {
+            let Some(first) = PciDevice::probe(PciAddress::new(bus, device, 0)) else {
+                continue;
+            };
+            let multi = first.addr.read8(REG_HEADER_TYPE) & HEADER_MULTI_FUNCTION != 0;
+            found.push(first);
+            if multi {
+                found.extend((1..8).filter_map(|f| PciDevice::probe(PciAddress::new(bus, device, f))));
+            }
+        }
+    }
+    found
+}
+
+/// Address range a bridge forwards, as `(base, size)`
+type BridgeRange = (u64, u64);
+
+/// Ranges a bridge forwards to its secondary bus
+///
+/// Returns the I/O range and the memory ranges; a window the firmware
+/// left closed (limit below base) is omitted.
+fn bridge_windows(addr: PciAddress) -> (Option<BridgeRange>, Vec<BridgeRange>) {
+    let range = |base: u64, limit: u64, granule: u64| {
+        (limit >= base).then(|| (base, limit + granule - base))
+    };
+
+    let io = addr.read16(REG_BRIDGE_IO);
+    let io = range(((io & 0xF0) as u64) << 8, ((io >> 8) as u64 & 0xF0) << 8, 0x1000);
+
+    let mem = addr.read32(REG_BRIDGE_MEM);
+    let pref = addr.read32(REG_BRIDGE_PREF_MEM);
+    let pref_hi = (addr.read32(REG_BRIDGE_PREF_BASE_HI) as u64, addr.read32(REG_BRIDGE_PREF_LIMIT_HI) as u64);
+    let mem = [
+        range(((mem & 0xFFF0) as u64) << 16, ((mem >> 16) as u64 & 0xFFF0) << 16, 0x10_0000),
+        range(
+            (((pref & 0xFFF0) as u64) << 16) | (pref_hi.0 << 32),
+            (((pref >> 16) as u64 & 0xFFF0) << 16) | (pref_hi.1 << 32),
+            0x10_0000,
+        ),
+    ];
+    (io, mem.into_iter().flatten().collect())
+}
+
+// ============================================================================
+// Resource Assignment
+// ============================================================================
+
+/// Outcome of [`assign_resources`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AssignReport {
+    /// BARs given an address
+    pub assigned: usize,
+    /// Unassigned BARs that did not fit their window
+    pub failed: usize,
+    /// Unassigned BARs behind a bridge, left alone
+    pub skipped: usize,
+}
+
+/// Give every unassigned BAR on the root bus an address; see the module docs
+///
+/// Updates the `bars` of `devices` to the new addresses.
+fn assign_resources(devices: &mut [PciDevice], windows: &mut HostBridgeWindows) -> AssignReport {
+    let mut report = AssignReport::default();
+
+    // Ranges the firmware set up stay where they are
+    for dev in devices.iter() {
+        for bar in dev.bars.iter().filter(|b| b.base != 0) {
+            window_for(windows, bar.kind).claim(bar.base, bar.size);
+        }
+        if dev.is_bridge() {
+            let (io, mem) = bridge_windows(dev.addr);
+            if let Some((base, size)) = io {
+                windows.io.claim(base, size);
+            }
+            for (base, size) in mem {
+                windows.mem.claim(base, size);
+            }
+        }
+    }
+
+    let mut wanted: Vec<(usize, usize)> = Vec::new();
+    for (d, dev) in devices.iter().enumerate() {
+        for (b, _) in dev.bars.iter().enumerate().filter(|(_, b)| b.base == 0) {
+            if dev.addr.bus == 0 {
+                wanted.push((d, b));
+            } else {
+                report.skipped += 1;
+            }
+        }
+    }
+    // Largest first keeps the naturally aligned ranges packed
+    wanted.sort_by_key(|&(d, b)| core::cmp::Reverse(devices[d].bars[b].size));
+
+    for (d, b) in wanted {
+        let dev = &mut devices[d];
+        let bar = &mut dev.bars[b];
+        let size = match bar.kind {
+            BarKind::Io => bar.size,
+            _ => bar.size.max(MIN_MEM_BAR),
+        };
+        let Some(base) = window_for(windows, bar.kind).allocate(size, size) else {
+            report.failed += 1;
+            continue;
+        };
+        bar.base = base;
+        write_bar(dev.addr, bar);
+        let enable = if bar.kind == BarKind::Io { COMMAND_IO } else { COMMAND_MEM };
+        dev.addr.write16(REG_COMMAND, dev.addr.read16(REG_COMMAND) | enable);
+        report.assigned += 1;
+    }
+    report
+}
+
+/// Window a BAR of `kind` is placed in
+fn window_for(windows: &mut HostBridgeWindows, kind: BarKind) -> &mut Window {
+    match kind {
+        BarKind::Io => &mut windows.io,
+        BarKind::Mem32 | BarKind::Mem64 => &mut windows.mem,
+    }
+}
+
+/// Record where RAM below 4 GiB ends
+///
+/// Called with the firmware memory map before [`init`]; the default
+/// memory window starts above it.
+pub fn set_low_ram_top(addr: u64) {
+    LOW_RAM_TOP.store(addr, Ordering::Relaxed);
+}
+
+/// Functions found at boot, with their final BAR addresses
+pub fn devices() -> Vec<PciDevice> {
+    DEVICES.lock().clone()
+}
+
+/// Stop every function from initiating DMA
+///
+/// Clears bus mastering on all functions except bridges, so no device
+/// writes to memory while the machine powers off or resets. Decoding is
+/// left on: firmware may still need to reach the devices.
+pub fn quiesce() {
+    for device in DEVICES.lock().iter().filter(|d| !d.is_bridge()) {
+        let command = device.addr.read16(REG_COMMAND);
+        if command & COMMAND_MASTER != 0 {
+            device.addr.write16(REG_COMMAND, command & !COMMAND_MASTER);
+        }
+    }
+}
+
+/// Scan the bus and assign unconfigured BARs
+///
+/// Must run once on the boot CPU, before any driver probes.
+#[link_section = ".inittxt"]
+pub fn init() {
+    let mut devices = scan();
+    let mut windows = HostBridgeWindows::defaults(LOW_RAM_TOP.load(Ordering::Relaxed));
+    let report = assign_resources(&mut devices, &mut windows);
+
+    kinfo!("[PCI] {} functions, {} BARs assigned", devices.len(), report.assigned);
+    if report.failed != 0 {
+        kwarn!("[PCI] {} BARs did not fit", report.failed);
+    }
+    if report.skipped != 0 {
+        kwarn!("[PCI] {} BARs behind bridges left unassigned", report.skipped);
+    }
+
+    *DEVICES.lock() = devices;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_address() {
+        assert_eq!(PciAddress::new(0, 0, 0).config_address(0x10), 0x8000_0010);
+        assert_eq!(PciAddress::new(1, 3, 2).config_address(0x3E), 0x8001_1A3C);
+    }
+
+    #[test]
+    fn test_decode_bar() {
+        assert_eq!(decode_bar(0xC001, 0), (BarKind::Io, false, 0xC000));
+        assert_eq!(decode_bar(0xFEB0_0000, 0), (BarKind::Mem32, false, 0xFEB0_0000));
+        assert_eq!(decode_bar(0x0000_000C, 0x8), (BarKind::Mem64, true, 0x8_0000_0000));
+    }
+
+    #[test]
+    fn test_bar_size() {
+        assert_eq!(bar_size(BarKind::Io, 0x0000_FFE1, 0), 0x20);
+        assert_eq!(bar_size(BarKind::Io, 0xFFFF_FFFD, 0), 0x4);
+        assert_eq!(bar_size(BarKind::Mem32, 0xFFF0_0000, 0), 0x10_0000);
+        assert_eq!(bar_size(BarKind::Mem32, 0, 0), 0);
+        assert_eq!(bar_size(BarKind::Mem64, 0xFFFF_C00C, 0xFFFF_FFFF), 0x4000);
+        assert_eq!(bar_size(BarKind::Mem64, 0x0000_000C, 0xFFFF_FFFE), 0x2_0000_0000);
+    }
+}
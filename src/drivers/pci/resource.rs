@@ -0,0 +1,170 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! PCI Resource Windows
+//!
+//! The host bridge forwards a fixed range of I/O ports and a fixed range of
+//! memory addresses to PCI. A [`Window`] tracks which parts of such a range
+//! are taken: BARs and bridge windows the firmware programmed are claimed
+//! first, then unassigned BARs are placed in the gaps.
+//!
+//! The ranges a host bridge decodes are described by its ACPI `_CRS`
+//! method, which needs an AML interpreter. Until there is one,
+//! [`HostBridgeWindows::defaults`] picks ranges that are free on PC
+//! chipsets: the top of the I/O space, and the memory between the end of
+//! low RAM and the IOAPIC.
+
+use alloc::vec::Vec;
+
+/// Start of the default I/O window (the legacy ports sit below)
+pub const DEFAULT_IO_BASE: u64 = 0xC000;
+
+/// End of the I/O space (exclusive)
+pub const IO_END: u64 = 0x1_0000;
+
+/// Lowest start of the default memory window
+///
+/// Stays clear of chipset ranges below, such as the Q35 ECAM at
+/// 0xB000_0000.
+pub const DEFAULT_MEM_MIN_BASE: u64 = 0xC000_0000;
+
+/// End of the default memory window (exclusive): the IOAPIC, HPET and
+/// local APIC live above
+pub const DEFAULT_MEM_END: u64 = 0xFEC0_0000;
+
+/// Alignment of the default memory window's start
+const MEM_WINDOW_ALIGN: u64 = 0x1000_0000;
+
+/// Align `value` up to `align` (a power of two)
+const fn align_up(value: u64, align: u64) -> u64 {
+    (value + align - 1) & !(align - 1)
+}
+
+/// A range of bus addresses and the parts of it already in use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    /// First address
+    base: u64,
+    /// End address (exclusive)
+    end: u64,
+    /// Taken ranges as `(start, end)`, sorted and non-overlapping
+    used: Vec<(u64, u64)>,
+}
+
+impl Window {
+    /// Create an empty window covering `base..end`
+    pub const fn new(base: u64, end: u64) -> Self {
+        Self { base, end, used: Vec::new() }
+    }
+
+    /// First address of the window
+    pub const fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// End address of the window (exclusive)
+    pub const fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Mark `base..base + size` as taken
+    ///
+    /// The part outside the window is ignored. Returns `false` (and takes
+    /// nothing) if the range overlaps one already taken.
+    pub fn claim(&mut self, base: u64, size: u64) -> bool {
+        let start = base.max(self.base);
+        let end = base.saturating_add(size).min(self.end);
+        if start >= end {
+            return true;
+        }
+        let pos = self.used.partition_point(|&(_, e)| e <= start);
+        if self.used.get(pos).is_some_and(|&(s, _)| s < end) {
+            return false;
+        }
+        self.used.insert(pos, (start, end));
+        true
+    }
+
+    /// Take the lowest free range of `size` bytes aligned to `align`
+    ///
+    /// `align` must be a power of two.
+    pub fn allocate(&mut self, size: u64, align: u64) -> Option<u64> {
+        if size == 0 {
+            return None;
+        }
+        let mut cursor = self.base;
+        for i in 0..=self.used.len() {
+            let gap_end = self.used.get(i).map_or(self.end, |&(s, _)| s);
+            let start = align_up(cursor, align);
+            if start.checked_add(size).is_some_and(|end| end <= gap_end) {
+                self.used.insert(i, (start, start + size));
+                return Some(start);
+            }
+            if let Some(&(_, e)) = self.used.get(i) {
+                cursor = cursor.max(e);
+            }
+        }
+        None
+    }
+}
+
+/// Address ranges a host bridge forwards to its root bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostBridgeWindows {
+    /// I/O ports
+    pub io: Window,
+    /// 32-bit memory, used for 64-bit BARs as well
+    pub mem: Window,
+}
+
+impl HostBridgeWindows {
+    /// Windows for a PC whose RAM below 4 GiB ends at `low_ram_top`
+    pub fn defaults(low_ram_top: u64) -> Self {
+        let mem_base = align_up(low_ram_top, MEM_WINDOW_ALIGN).max(DEFAULT_MEM_MIN_BASE);
+        Self {
+            io: Window::new(DEFAULT_IO_BASE, IO_END),
+            mem: Window::new(mem_base, DEFAULT_MEM_END.max(mem_base)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_aligned() {
+        let mut w = Window::new(0x1000, 0x10000);
+        assert_eq!(w.allocate(0x100, 0x100), Some(0x1000));
+        assert_eq!(w.allocate(0x1000, 0x1000), Some(0x2000));
+        // The gap left by the alignment is reused
+        assert_eq!(w.allocate(0x100, 0x100), Some(0x1100));
+        assert_eq!(w.allocate(0x10000, 0x10000), None);
+        assert_eq!(w.allocate(0, 1), None);
+    }
+
+    #[test]
+    fn test_claim_reserves() {
+        let mut w = Window::new(0xC000, 0x1_0000);
+        assert!(w.claim(0xC000, 0x40));
+        assert!(w.claim(0x1F0, 8)); // outside, ignored
+        assert!(!w.claim(0xC020, 0x40));
+        assert_eq!(w.allocate(0x40, 0x40), Some(0xC040));
+        assert!(w.claim(0xFFC0, 0x100)); // clipped to the window
+        assert_eq!(w.allocate(0x40, 0x40), Some(0xC080));
+        assert_eq!(w.allocate(0x4000, 0x4000), None);
+        assert_eq!(w.allocate(0x1000, 0x1000), Some(0xD000));
+    }
+
+    #[test]
+    fn test_default_windows() {
+        let small = HostBridgeWindows::defaults(0x4000_0000);
+        assert_eq!((small.mem.base(), small.mem.end()), (DEFAULT_MEM_MIN_BASE, DEFAULT_MEM_END));
+        let big = HostBridgeWindows::defaults(0xD800_0000);
+        assert_eq!(big.mem.base(), 0xE000_0000);
+        assert_eq!((big.io.base(), big.io.end()), (DEFAULT_IO_BASE, IO_END));
+    }
+}
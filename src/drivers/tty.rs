@@ -0,0 +1,415 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! TTY Layer
+//!
+//! This module provides one TTY per virtual terminal. Each TTY has its
+//! own input queue; output goes to the matching VT.
+//!
+//! ## Line Discipline
+//!
+//! Keyboard events from the PS/2 driver pass through [`receive_key`],
+//! which handles console hotkeys before queuing input for the active TTY:
+//!
+//! - **Alt+F1..F4**: switch to VT 1..4
+//! - **Shift+PageUp / Shift+PageDown**: scroll the active VT's history
+//! - **Shift+Up / Shift+Down**: grow / shrink the line mark
+//! - **Ctrl+Insert**: copy the marked lines to the paste buffer
+//! - **Shift+Insert**: paste the paste buffer into the active TTY
+//!
+//! Everything else is translated to bytes (Enter → `\n`, Backspace →
+//! `0x08`, Tab → `\t`) and queued for the TTY on the active VT.
+//!
+//! ## Serial Console
+//!
+//! Bytes received on COM1 pass through [`receive_serial`] and are queued
+//! for [`CONSOLE_TTY`], whichever VT is on screen, so the kernel can be
+//! driven headless (QEMU `-nographic`, a real serial line). Terminals send
+//! CR for Enter and DEL for Backspace; both are translated to match the
+//! keyboard. Output to the console TTY is copied to COM1.
+//!
+//! ## Output Buffering
+//!
+//! Processes writing to the console at the same time interleave their
+//! bytes mid-line. A process can have its TTY output line buffered
+//! instead: each process has an [`OutputBuffer`] that collects a line and
+//! writes it in one piece when the line ends or the buffer fills. Output
+//! is unbuffered unless [`LINE_BUFFERED_FLAG`] is on the kernel command
+//! line; `TTY_SET_BUFFERING` switches a process either way. Pending output
+//! is also written before the process reads a TTY (so prompts show) and
+//! when it exits.
+
+use crate::drivers::display::vt::{self, NUM_VTS};
+use crate::drivers::keyboard::{CircularBuffer, INPUT_BUFFER_SIZE, KeyEvent, ModifierState, SpecialKey};
+use crate::drivers::paste::{self, PASTE_BUFFER_SIZE};
+use crate::arch::amd64::mm::RxStatus;
+use crate::syscall::uaccess::UserSlice;
+
+/// Number of TTYs (one per VT)
+pub const NUM_TTYS: usize = NUM_VTS;
+
+/// TTY attached to the kernel console and to process stdio
+pub const CONSOLE_TTY: usize = vt::CONSOLE_VT;
+
+/// Per-TTY input queues
+static mut INPUT: [CircularBuffer<u8, INPUT_BUFFER_SIZE>; NUM_TTYS] =
+    [const { CircularBuffer::new() }; NUM_TTYS];
+
+/// Scratch buffer for Ctrl+Insert (keyboard IRQ only)
+static mut COPY_SCRATCH: [u8; PASTE_BUFFER_SIZE] = [0; PASTE_BUFFER_SIZE];
+
+/// Size of a process's output line buffer
+pub const LINE_BUFFER_SIZE: usize = 256;
+
+/// Command-line flag: line-buffer the TTY output of new processes
+pub const LINE_BUFFERED_FLAG: &str = "tty.line_buffered";
+
+// ============================================================================
+// Line Discipline
+// ============================================================================
+
+/// Handle a key event from the keyboard driver
+///
+/// # Safety
+/// Must only be called from the keyboard interrupt handler.
+pub unsafe fn receive_key(event: KeyEvent, modifiers: &ModifierState) {
+    match event {
+        KeyEvent::Ascii(ascii) => queue_input(ascii),
+        KeyEvent::Special(key) => {
+            if modifiers.alt() {
+                if let Some(n) = vt_hotkey(key) {
+                    vt::switch_to(n);
+                    return;
+                }
+            }
+            match key {
+                SpecialKey::PageUp if modifiers.shift() => vt::scroll_active(scroll_step()),
+                SpecialKey::PageDown if modifiers.shift() => vt::scroll_active(-scroll_step()),
+                SpecialKey::ArrowUp if modifiers.shift() => vt::adjust_mark_active(1),
+                SpecialKey::ArrowDown if modifiers.shift() => vt::adjust_mark_active(-1),
+                SpecialKey::Insert if modifiers.ctrl() => copy_selection(),
+                SpecialKey::Insert if modifiers.shift() => paste::try_for_each(|b| queue_input(b)),
+                SpecialKey::Backspace => queue_input(0x08),
+                SpecialKey::Enter => queue_input(b'\n'),
+                SpecialKey::Tab => queue_input(b'\t'),
+                _ => {
+                    // Arrow keys and other special keys are not translated yet
+                }
+            }
+        }
+        KeyEvent::Release(_) => {}
+    }
+}
+
+/// Map Alt+Fn to a VT index
+fn vt_hotkey(key: SpecialKey) -> Option<usize> {
+    let n = match key {
+        SpecialKey::F1 => 0,
+        SpecialKey::F2 => 1,
+        SpecialKey::F3 => 2,
+        SpecialKey::F4 => 3,
+        _ => return None,
+    };
+    if n < NUM_VTS { Some(n) } else { None }
+}
+
+/// Lines to scroll per Shift+PageUp/PageDown
+fn scroll_step() -> isize {
+    (crate::drivers::display::console::rows() / 2).max(1) as isize
+}
+
+/// Copy the active VT's marked lines into the paste buffer
+unsafe fn copy_selection() {
+    let scratch = &mut *core::ptr::addr_of_mut!(COPY_SCRATCH);
+    let n = vt::copy_mark_active(scratch);
+    let _ = paste::try_set(&scratch[..n]);
+}
+
+/// Queue a byte for the TTY on the active VT
+unsafe fn queue_input(byte: u8) {
+    INPUT[vt::active()].write(byte);
+}
+
+/// Handle a byte received on the serial console
+///
+/// # Safety
+/// Must only be called from the COM1 interrupt handler.
+pub unsafe fn receive_serial(byte: u8) {
+    INPUT[CONSOLE_TTY].write(translate_serial(byte));
+}
+
+/// Translate a byte from a serial terminal to keyboard input
+fn translate_serial(byte: u8) -> u8 {
+    match byte {
+        b'\r' => b'\n',
+        0x7F => 0x08,
+        _ => byte,
+    }
+}
+
+// ============================================================================
+// TTY I/O
+// ============================================================================
+
+/// Read one byte of input from a TTY (non-blocking)
+pub fn read_byte(tty: usize) -> Option<u8> {
+    if tty >= NUM_TTYS {
+        return None;
+    }
+    unsafe { INPUT[tty].read() }
+}
+
+/// Check whether a TTY has pending input
+pub fn has_input(tty: usize) -> bool {
+    tty < NUM_TTYS && unsafe { INPUT[tty].has_data() }
+}
+
+/// Number of bytes pending on a TTY
+pub fn available(tty: usize) -> usize {
+    if tty >= NUM_TTYS {
+        return 0;
+    }
+    unsafe { INPUT[tty].available() }
+}
+
+/// Check whether a TTY's input queue is full
+pub fn is_full(tty: usize) -> bool {
+    tty < NUM_TTYS && unsafe { INPUT[tty].is_full() }
+}
+
+/// Discard pending input on a TTY
+pub fn flush(tty: usize) {
+    if tty < NUM_TTYS {
+        unsafe { INPUT[tty].clear() }
+    }
+}
+
+/// Write output to a TTY
+///
+/// Falls back to the debug port when no display console is available.
+/// Console TTY output is also copied to the serial console.
+pub fn write(tty: usize, bytes: &[u8]) {
+    if crate::drivers::display::is_initialized() {
+        vt::write(tty, bytes);
+    } else {
+        crate::klog::debugcon_write(bytes);
+    }
+    if tty == CONSOLE_TTY {
+        crate::drivers::uart::write_com1(bytes);
+    }
+}
+
+/// Get the TTY shown on screen
+pub fn active() -> usize {
+    vt::active()
+}
+
+// ============================================================================
+// Output Buffering
+// ============================================================================
+
+/// How a process's TTY output is written
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferMode {
+    /// Every write goes out at once
+    Unbuffered = 0,
+    /// Output is held until a newline or a full buffer
+    Line = 1,
+}
+
+impl BufferMode {
+    /// Create from the raw `TTY_SET_BUFFERING` argument
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Unbuffered),
+            1 => Some(Self::Line),
+            _ => None,
+        }
+    }
+
+    /// Mode of a new process (see [`LINE_BUFFERED_FLAG`])
+    pub fn initial() -> Self {
+        if crate::cmdline::has_flag(LINE_BUFFERED_FLAG) {
+            Self::Line
+        } else {
+            Self::Unbuffered
+        }
+    }
+}
+
+/// A process's TTY output not yet written
+///
+/// Holds bytes for one TTY at a time; output to another TTY writes the
+/// pending bytes first. Each method takes the function that does the
+/// writing (normally [`write`]).
+#[derive(Clone)]
+pub struct OutputBuffer {
+    mode: BufferMode,
+    tty: usize,
+    data: [u8; LINE_BUFFER_SIZE],
+    len: usize,
+}
+
+impl OutputBuffer {
+    /// Create an empty buffer
+    pub const fn new(mode: BufferMode) -> Self {
+        Self {
+            mode,
+            tty: CONSOLE_TTY,
+            data: [0; LINE_BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Get the buffering mode
+    pub fn mode(&self) -> BufferMode {
+        self.mode
+    }
+
+    /// Number of bytes waiting
+    pub fn pending(&self) -> usize {
+        self.len
+    }
+
+    /// Change the buffering mode; leaving line mode writes what is pending
+    pub fn set_mode(&mut self, mode: BufferMode, out: impl FnMut(usize, &[u8])) {
+        if mode == BufferMode::Unbuffered {
+            self.flush(out);
+        }
+        self.mode = mode;
+    }
+
+    /// Write the pending bytes
+    pub fn flush(&mut self, mut out: impl FnMut(usize, &[u8])) {
+        if self.len > 0 {
+            out(self.tty, &self.data[..self.len]);
+            self.len = 0;
+        }
+    }
+
+    /// Write `bytes` to `tty`, or buffer them in line mode
+    pub fn write(&mut self, tty: usize, bytes: &[u8], mut out: impl FnMut(usize, &[u8])) {
+        if tty != self.tty {
+            self.flush(&mut out);
+            self.tty = tty;
+        }
+        if self.mode == BufferMode::Unbuffered {
+            self.flush(&mut out);
+            out(tty, bytes);
+            return;
+        }
+
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let room = LINE_BUFFER_SIZE - self.len;
+            let line_end = rest.iter().position(|&b| b == b'\n').map(|i| i + 1);
+            let n = line_end.unwrap_or(rest.len()).min(room);
+
+            self.data[self.len..self.len + n].copy_from_slice(&rest[..n]);
+            self.len += n;
+            rest = &rest[n..];
+
+            if self.len == LINE_BUFFER_SIZE || self.data[self.len - 1] == b'\n' {
+                self.flush(&mut out);
+            }
+        }
+    }
+}
+
+/// Write output to a TTY through the current process's [`OutputBuffer`]
+///
+/// Writes directly when there is no current process.
+pub fn write_buffered(tty: usize, bytes: &[u8]) {
+    let buffered = crate::process::table::with_current_process_mut(|p| p.tty_output.write(tty, bytes, write));
+    if buffered.is_none() {
+        write(tty, bytes);
+    }
+}
+
+/// Write a user buffer to a TTY through the current process's [`OutputBuffer`]
+///
+/// Mapped pages are written in place (see
+/// [`UserSlice::for_each_mapped`]) under the process table lock, which
+/// keeps them mapped; that walk goes through the direct map and never
+/// faults. A page that is not mapped yet is copied in only after the lock
+/// is dropped, so the fault handler can map it, and the rest follows in
+/// place again. Bytes written before a fault stay written.
+///
+/// # Returns
+///
+/// `ERR_INVALID_ARGS` if part of the buffer is not readable
+pub fn write_user(tty: usize, buf: UserSlice) -> Result<(), RxStatus> {
+    let mut rest = buf;
+    while !rest.is_empty() {
+        let in_place = crate::process::table::with_current_process_mut(|p| {
+            let page_table = p.page_table;
+            // SAFETY: the process table is locked while `p` is borrowed
+            unsafe { rest.for_each_mapped(page_table, |bytes| p.tty_output.write(tty, bytes, write)) }
+        });
+        rest = rest.skip(in_place.transpose()?.unwrap_or(0));
+
+        let page = rest.first_page();
+        page.for_each_chunk(|bytes| write_buffered(tty, bytes))?;
+        rest = rest.skip(page.len());
+    }
+    Ok(())
+}
+
+/// Write the current process's pending output
+pub fn flush_current() {
+    let _ = crate::process::table::with_current_process_mut(|p| p.tty_output.flush(write));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vt_hotkeys() {
+        assert_eq!(vt_hotkey(SpecialKey::F1), Some(0));
+        assert_eq!(vt_hotkey(SpecialKey::F4), Some(3));
+        assert_eq!(vt_hotkey(SpecialKey::F5), None);
+        assert_eq!(vt_hotkey(SpecialKey::Enter), None);
+    }
+
+    #[test]
+    fn test_translate_serial() {
+        assert_eq!(translate_serial(b'\r'), b'\n');
+        assert_eq!(translate_serial(0x7F), 0x08);
+        assert_eq!(translate_serial(b'a'), b'a');
+        assert_eq!(translate_serial(b'\n'), b'\n');
+    }
+
+    #[test]
+    fn test_line_buffering() {
+        extern crate alloc;
+        use alloc::vec::Vec;
+
+        let mut writes: Vec<(usize, Vec<u8>)> = Vec::new();
+        let mut buf = OutputBuffer::new(BufferMode::Line);
+
+        buf.write(0, b"hel", |tty, b| writes.push((tty, b.to_vec())));
+        buf.write(0, b"lo\nwor", |tty, b| writes.push((tty, b.to_vec())));
+        assert_eq!(writes, [(0, b"hello\n".to_vec())]);
+        assert_eq!(buf.pending(), 3);
+
+        // Another TTY gets the pending bytes written first
+        buf.write(1, b"x\n", |tty, b| writes.push((tty, b.to_vec())));
+        assert_eq!(writes[1..], [(0, b"wor".to_vec()), (1, b"x\n".to_vec())]);
+
+        // A full buffer is written without a newline
+        writes.clear();
+        buf.write(1, &[b'a'; LINE_BUFFER_SIZE + 1], |tty, b| writes.push((tty, b.to_vec())));
+        assert_eq!(writes.len(), 1);
+        assert_eq!(buf.pending(), 1);
+
+        buf.set_mode(BufferMode::Unbuffered, |tty, b| writes.push((tty, b.to_vec())));
+        assert_eq!(writes[1], (1, b"a".to_vec()));
+        buf.write(1, b"y", |tty, b| writes.push((tty, b.to_vec())));
+        assert_eq!(writes[2], (1, b"y".to_vec()));
+    }
+}
@@ -9,6 +9,30 @@
 //! This module provides a driver for the 16550 UART (and compatible variants)
 //! commonly used on x86_64 systems for serial console I/O.
 //!
+//! # Transmit
+//!
+//! Until [`Uart16550::enable_tx_interrupt`] is called, writes spin on the
+//! transmit holding register (THR) one byte at a time, which is what early
+//! boot needs. Afterwards [`Uart16550::write_byte`] only appends to a
+//! [`TX_BUFFER_SIZE`]-byte software FIFO ([`TxRing`]) and returns; the
+//! THR-empty interrupt refills the hardware FIFO from it
+//! ([`Uart16550::handle_interrupt`]), so a burst of log output no longer
+//! stalls the writer. When the software FIFO is full the writer drains
+//! the oldest bytes itself rather than dropping them.
+//!
+//! The panic path cannot rely on interrupts: [`Uart16550::flush`] drains
+//! the FIFO synchronously, and [`Uart16550::write_str_sync`] bypasses it.
+//!
+//! # Receive
+//!
+//! [`Uart16550::enable_rx_interrupt`] turns on the received-data
+//! interrupt. The handler moves every byte in the hardware FIFO into a
+//! [`RX_BUFFER_SIZE`]-byte ring, dropping bytes once it is full, and
+//! [`Uart16550::read_buffered`] takes them out. On COM1 the interrupt
+//! glue ([`handle_com1_irq`]) passes them straight on to the console TTY
+//! ([`crate::drivers::tty::receive_serial`]), so a serial terminal works
+//! as stdin alongside the PS/2 keyboard.
+//!
 //! # Usage
 //!
 //! ```ignore
@@ -22,7 +46,10 @@
 //! uart.write_str("Hello, World!\n");
 //! ```
 
+use core::sync::atomic::{AtomicBool, Ordering};
 use crate::arch::amd64::ioport::{inb, outb};
+use crate::drivers::keyboard::CircularBuffer;
+use crate::sync::SpinMutex;
 
 /// Base I/O port for COM1
 pub const COM1_PORT: u16 = 0x3F8;
@@ -36,6 +63,21 @@ pub const COM3_PORT: u16 = 0x3E8;
 /// Base I/O port for COM4
 pub const COM4_PORT: u16 = 0x2E8;
 
+/// ISA IRQ of COM1
+pub const COM1_IRQ: u8 = 4;
+
+/// Interrupt vector COM1's IRQ is routed to
+pub const COM1_VECTOR: u8 = 32 + COM1_IRQ;
+
+/// Size of the software transmit FIFO
+pub const TX_BUFFER_SIZE: usize = 4096;
+
+/// Size of the receive ring (one slot stays free, see [`CircularBuffer`])
+pub const RX_BUFFER_SIZE: usize = 256;
+
+/// Depth of the 16550's hardware transmit FIFO
+const HW_FIFO_DEPTH: usize = 16;
+
 /// 16550 UART register offsets
 mod reg {
     /// Receive buffer (read) / Transmit hold (write)
@@ -44,9 +86,12 @@ mod reg {
     /// Interrupt enable
     pub const IER: u16 = 1;
 
-    /// FIFO control
+    /// FIFO control (write)
     pub const FCR: u16 = 2;
 
+    /// Interrupt identification (read)
+    pub const IIR: u16 = 2;
+
     /// Line control
     pub const LCR: u16 = 3;
 
@@ -63,6 +108,42 @@ mod reg {
     pub const SCR: u16 = 7;
 }
 
+/// Interrupt Enable Register bits
+mod ier {
+    /// Received data available
+    pub const ERBFI: u8 = 0x01;
+
+    /// Transmitter holding register empty
+    pub const ETBEI: u8 = 0x02;
+}
+
+/// Interrupt Identification Register bits
+mod iir {
+    /// No interrupt pending
+    pub const NO_INT: u8 = 0x01;
+
+    /// Interrupt cause
+    pub const ID_MASK: u8 = 0x0E;
+
+    /// Cause: transmitter holding register empty
+    pub const THRE: u8 = 0x02;
+
+    /// Cause: received data available
+    pub const RDA: u8 = 0x04;
+
+    /// Cause: data has sat in the receive FIFO below its trigger level
+    pub const RX_TIMEOUT: u8 = 0x0C;
+}
+
+/// Modem Control Register bits
+mod mcr {
+    /// Data terminal ready + request to send
+    pub const DTR_RTS: u8 = 0x03;
+
+    /// OUT2: gates the UART's interrupt line on PC hardware
+    pub const OUT2: u8 = 0x08;
+}
+
 /// Line Control Register bits
 mod lcr {
     /// 8 bits per word
@@ -93,11 +174,95 @@ mod fcr {
     pub const CLEAR_TX: u8 = 0x04;
 }
 
+/// Software transmit FIFO
+///
+/// A ring of [`TX_BUFFER_SIZE`] bytes; `head` is the next byte to send,
+/// `len` the number queued.
+pub struct TxRing {
+    buf: [u8; TX_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl TxRing {
+    /// Create an empty ring
+    pub const fn new() -> Self {
+        Self { buf: [0; TX_BUFFER_SIZE], head: 0, len: 0 }
+    }
+
+    /// Queue a byte
+    ///
+    /// # Returns
+    ///
+    /// false if the ring is full
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.buf[(self.head + self.len) % TX_BUFFER_SIZE] = byte;
+        self.len += 1;
+        true
+    }
+
+    /// Take the oldest byte
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % TX_BUFFER_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    /// Number of bytes queued
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if nothing is queued
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check if no byte can be queued
+    pub const fn is_full(&self) -> bool {
+        self.len == TX_BUFFER_SIZE
+    }
+}
+
+impl Default for TxRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// 16550 UART driver
-#[derive(Debug)]
 pub struct Uart16550 {
     /// Base I/O port
     base_port: u16,
+
+    /// Software transmit FIFO, drained by the THR-empty interrupt
+    tx: SpinMutex<TxRing>,
+
+    /// Whether writes go through `tx` (see [`Self::enable_tx_interrupt`])
+    tx_irq: AtomicBool,
+
+    /// Received bytes, filled by the received-data interrupt
+    rx: SpinMutex<CircularBuffer<u8, RX_BUFFER_SIZE>>,
+
+    /// Whether reads come from `rx` (see [`Self::enable_rx_interrupt`])
+    rx_irq: AtomicBool,
+}
+
+impl core::fmt::Debug for Uart16550 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Uart16550")
+            .field("base_port", &self.base_port)
+            .field("tx_irq", &self.tx_irq.load(Ordering::Relaxed))
+            .field("rx_irq", &self.rx_irq.load(Ordering::Relaxed))
+            .finish()
+    }
 }
 
 impl Uart16550 {
@@ -107,7 +272,13 @@ impl Uart16550 {
     ///
     /// The base port must be valid and accessible.
     pub const unsafe fn new(base_port: u16) -> Self {
-        Self { base_port }
+        Self {
+            base_port,
+            tx: SpinMutex::new(TxRing::new()),
+            tx_irq: AtomicBool::new(false),
+            rx: SpinMutex::new(CircularBuffer::new()),
+            rx_irq: AtomicBool::new(false),
+        }
     }
 
     /// Initialize the UART
@@ -148,12 +319,60 @@ impl Uart16550 {
 
         // Set modem control (RTS + DTR)
         unsafe {
-            outb(self.base_port + reg::MCR, 0x03);
+            outb(self.base_port + reg::MCR, mcr::DTR_RTS);
+        }
+    }
+
+    /// Switch transmission to the software FIFO and the THR-empty interrupt
+    ///
+    /// The IRQ must be routed to a handler that calls
+    /// [`handle_interrupt`](Self::handle_interrupt).
+    pub fn enable_tx_interrupt(&self) {
+        unsafe {
+            outb(self.base_port + reg::MCR, mcr::DTR_RTS | mcr::OUT2);
         }
+        self.tx_irq.store(true, Ordering::Release);
+    }
+
+    /// Deliver received bytes through the received-data interrupt
+    ///
+    /// Afterwards input is read with [`read_buffered`](Self::read_buffered).
+    /// The IRQ must be routed to a handler that calls
+    /// [`handle_interrupt`](Self::handle_interrupt).
+    pub fn enable_rx_interrupt(&self) {
+        self.rx_irq.store(true, Ordering::Release);
+        self.with_tx(|tx| unsafe {
+            outb(self.base_port + reg::MCR, mcr::DTR_RTS | mcr::OUT2);
+            outb(self.base_port + reg::IER, self.ier_bits(tx));
+        });
     }
 
     /// Write a single byte
+    ///
+    /// With the transmit interrupt enabled the byte is queued and sent in
+    /// the background; otherwise this waits for the transmitter.
     pub fn write_byte(&self, byte: u8) {
+        if !self.tx_irq.load(Ordering::Acquire) {
+            self.write_byte_sync(byte);
+            return;
+        }
+
+        self.with_tx(|tx| {
+            // Full: make room by sending the oldest bytes ourselves
+            while !tx.push(byte) {
+                if let Some(oldest) = tx.pop() {
+                    self.write_byte_sync(oldest);
+                }
+            }
+            self.fill_fifo(tx);
+        });
+    }
+
+    /// Write a single byte, waiting for the transmitter
+    ///
+    /// Bypasses the software FIFO, so bytes still queued there are sent
+    /// after this one.
+    pub fn write_byte_sync(&self, byte: u8) {
         // Wait for transmitter to be ready
         loop {
             let lsr = unsafe { inb(self.base_port + reg::LSR) };
@@ -168,15 +387,100 @@ impl Uart16550 {
         }
     }
 
-    /// Read a single byte (blocking)
-    pub fn read_byte(&self) -> u8 {
-        // Wait for data to be available
+    /// Send everything in the software FIFO, waiting for the transmitter
+    ///
+    /// For the panic path. Gives up if the FIFO is locked (the panic may
+    /// have interrupted its holder).
+    pub fn flush(&self) {
+        if let Some(mut tx) = self.tx.try_lock() {
+            while let Some(byte) = tx.pop() {
+                self.write_byte_sync(byte);
+            }
+        }
+    }
+
+    /// Handle the UART interrupt
+    ///
+    /// Moves received bytes into the receive ring, refills the hardware
+    /// FIFO from the software one, and stops the THR-empty interrupt once
+    /// there is nothing left to send. Called with interrupts disabled.
+    pub fn handle_interrupt(&self) {
         loop {
-            let lsr = unsafe { inb(self.base_port + reg::LSR) };
-            if lsr & lsr::DR != 0 {
+            let cause = unsafe { inb(self.base_port + reg::IIR) };
+            if cause & iir::NO_INT != 0 {
                 break;
             }
+            match cause & iir::ID_MASK {
+                iir::THRE => self.fill_fifo(&mut self.tx.lock()),
+                iir::RDA | iir::RX_TIMEOUT => self.receive(),
+                // Line or modem status: reading the register clears it
+                _ => unsafe {
+                    inb(self.base_port + reg::LSR);
+                    inb(self.base_port + reg::MSR);
+                },
+            }
+        }
+    }
+
+    /// Move the hardware receive FIFO into the receive ring
+    fn receive(&self) {
+        let mut rx = self.rx.lock();
+        while self.has_data_hw() {
+            let byte = unsafe { inb(self.base_port + reg::RBR_THR) };
+            // Full: drop the byte, like the TTY input queue
+            let _ = rx.write(byte);
+        }
+    }
+
+    /// Take the oldest byte from the receive ring
+    pub fn read_buffered(&self) -> Option<u8> {
+        without_interrupts(|| self.rx.lock().read())
+    }
+
+    /// Move queued bytes into the hardware FIFO if it is empty
+    ///
+    /// Keeps the THR-empty interrupt enabled exactly while bytes remain
+    /// queued.
+    fn fill_fifo(&self, tx: &mut TxRing) {
+        let lsr = unsafe { inb(self.base_port + reg::LSR) };
+        if lsr & lsr::THRE != 0 {
+            for _ in 0..HW_FIFO_DEPTH {
+                let Some(byte) = tx.pop() else { break };
+                unsafe { outb(self.base_port + reg::RBR_THR, byte) };
+            }
         }
+        unsafe { outb(self.base_port + reg::IER, self.ier_bits(tx)) };
+    }
+
+    /// Interrupt enable bits for the current state
+    fn ier_bits(&self, tx: &TxRing) -> u8 {
+        let rx_bits = if self.rx_irq.load(Ordering::Acquire) { ier::ERBFI } else { 0 };
+        let tx_bits = if tx.is_empty() { 0 } else { ier::ETBEI };
+        rx_bits | tx_bits
+    }
+
+    /// Run `f` on the software FIFO with interrupts disabled, so the
+    /// UART interrupt cannot spin on the lock held below it
+    fn with_tx<R>(&self, f: impl FnOnce(&mut TxRing) -> R) -> R {
+        without_interrupts(|| f(&mut self.tx.lock()))
+    }
+
+    /// Read a single byte (blocking)
+    ///
+    /// With the receive interrupt enabled this waits on the receive ring
+    /// instead of the hardware.
+    pub fn read_byte(&self) -> u8 {
+        if self.rx_irq.load(Ordering::Acquire) {
+            loop {
+                if let Some(byte) = self.read_buffered() {
+                    return byte;
+                }
+                core::hint::spin_loop();
+            }
+        }
+
+        // Wait for data to be available
+        while !self.has_data_hw() {}
 
         // Read the byte
         unsafe { inb(self.base_port + reg::RBR_THR) }
@@ -184,6 +488,15 @@ impl Uart16550 {
 
     /// Check if data is available to read
     pub fn has_data(&self) -> bool {
+        if self.rx_irq.load(Ordering::Acquire) {
+            without_interrupts(|| self.rx.lock().has_data())
+        } else {
+            self.has_data_hw()
+        }
+    }
+
+    /// Check if the hardware receive FIFO holds a byte
+    fn has_data_hw(&self) -> bool {
         let lsr = unsafe { inb(self.base_port + reg::LSR) };
         lsr & lsr::DR != 0
     }
@@ -195,12 +508,65 @@ impl Uart16550 {
         }
     }
 
+    /// Write a string, waiting for the transmitter (see
+    /// [`write_byte_sync`](Self::write_byte_sync))
+    pub fn write_str_sync(&self, s: &str) {
+        for byte in s.bytes() {
+            self.write_byte_sync(byte);
+        }
+    }
+
+    /// Write a string without waiting for the software FIFO lock
+    ///
+    /// For the kernel log: a line logged from an NMI or machine check may
+    /// have interrupted the FIFO's holder on this CPU. If the FIFO is
+    /// locked the string is sent directly, possibly ahead of queued bytes.
+    pub fn try_write_str(&self, s: &str) {
+        use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
+
+        if !self.tx_irq.load(Ordering::Acquire) {
+            self.write_str_sync(s);
+            return;
+        }
+        let were_disabled = arch_ints_disabled();
+        arch_disable_ints();
+        match self.tx.try_lock() {
+            Some(mut tx) => {
+                for byte in s.bytes() {
+                    while !tx.push(byte) {
+                        if let Some(oldest) = tx.pop() {
+                            self.write_byte_sync(oldest);
+                        }
+                    }
+                }
+                self.fill_fifo(&mut tx);
+            }
+            None => self.write_str_sync(s),
+        }
+        if !were_disabled {
+            arch_enable_ints();
+        }
+    }
+
     /// Get the base port
     pub const fn base_port(&self) -> u16 {
         self.base_port
     }
 }
 
+/// Run `f` with interrupts disabled, restoring the previous state
+fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
+
+    let were_disabled = arch_ints_disabled();
+    arch_disable_ints();
+    let result = f();
+    if !were_disabled {
+        arch_enable_ints();
+    }
+    result
+}
+
 /// Global COM1 UART instance
 ///
 /// This is initialized during kernel startup and used for console I/O.
@@ -227,6 +593,59 @@ pub unsafe fn com1() -> Option<&'static mut Uart16550> {
     COM1.as_mut()
 }
 
+/// Make COM1 output interrupt-driven
+///
+/// # Safety
+///
+/// Call after [`init_com1`], once IRQ [`COM1_IRQ`] is routed to a handler
+/// that calls [`handle_com1_irq`].
+pub unsafe fn enable_com1_tx_interrupt() {
+    if let Some(uart) = com1() {
+        uart.enable_tx_interrupt();
+    }
+}
+
+/// Take COM1 input through the received-data interrupt
+///
+/// # Safety
+///
+/// Call after [`init_com1`], once IRQ [`COM1_IRQ`] is routed to a handler
+/// that calls [`handle_com1_irq`].
+pub unsafe fn enable_com1_rx_interrupt() {
+    if let Some(uart) = com1() {
+        uart.enable_rx_interrupt();
+    }
+}
+
+/// COM1 interrupt handler body
+///
+/// Received bytes go to the console TTY.
+pub fn handle_com1_irq() {
+    if let Some(uart) = unsafe { com1() } {
+        uart.handle_interrupt();
+        while let Some(byte) = uart.read_buffered() {
+            // SAFETY: called from the COM1 interrupt handler
+            unsafe { crate::drivers::tty::receive_serial(byte) };
+        }
+    }
+}
+
+/// Copy console output to COM1, if it is initialized
+pub fn write_com1(bytes: &[u8]) {
+    if let Some(uart) = unsafe { com1() } {
+        for &byte in bytes {
+            uart.write_byte(byte);
+        }
+    }
+}
+
+/// Send COM1's queued output synchronously (panic path)
+pub fn flush_com1() {
+    if let Some(uart) = unsafe { com1() } {
+        uart.flush();
+    }
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -248,4 +667,39 @@ mod tests {
         let uart = unsafe { Uart16550::new(0x3F8) };
         assert_eq!(uart.base_port(), 0x3F8);
     }
+
+    #[test]
+    fn test_tx_ring_order() {
+        let mut ring = TxRing::new();
+        assert!(ring.is_empty());
+        for byte in b"abc" {
+            assert!(ring.push(*byte));
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.pop(), Some(b'a'));
+        assert!(ring.push(b'd'));
+        assert_eq!(ring.pop(), Some(b'b'));
+        assert_eq!(ring.pop(), Some(b'c'));
+        assert_eq!(ring.pop(), Some(b'd'));
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn test_tx_ring_full_and_wrap() {
+        let mut ring = TxRing::new();
+        for i in 0..TX_BUFFER_SIZE {
+            assert!(ring.push(i as u8));
+        }
+        assert!(ring.is_full());
+        assert!(!ring.push(0xFF));
+
+        // Wrap around the end of the buffer
+        assert_eq!(ring.pop(), Some(0));
+        assert!(ring.push(0xFF));
+        for i in 1..TX_BUFFER_SIZE {
+            assert_eq!(ring.pop(), Some(i as u8));
+        }
+        assert_eq!(ring.pop(), Some(0xFF));
+        assert!(ring.is_empty());
+    }
 }
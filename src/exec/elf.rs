@@ -15,8 +15,11 @@
 extern crate alloc;
 use alloc::vec::Vec;
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 
+use crate::arch::amd64::mm::layout;
 use crate::object::{Vmo, VmoFlags};
+use crate::kdebug;
 
 // ============================================================================
 // ELF Constants
@@ -43,11 +46,45 @@ pub const ET_EXEC: u16 = 2;
 /// Program header type: Load
 pub const PT_LOAD: u32 = 1;
 
+/// Program header type: Dynamic linking information
+pub const PT_DYNAMIC: u32 = 2;
+
+/// Program header type: Program interpreter (dynamic loader) path
+pub const PT_INTERP: u32 = 3;
+
+/// Program header type: Stack permissions (GNU extension)
+pub const PT_GNU_STACK: u32 = 0x6474_e551;
+
 // Segment permissions
 pub const PF_X: u32 = 0x1; // Execute
 pub const PF_W: u32 = 0x2; // Write
 pub const PF_R: u32 = 0x4; // Read
 
+// ============================================================================
+// User Address Space Layout
+// ============================================================================
+
+// See `crate::arch::amd64::mm::layout` for the whole layout.
+
+/// Top of the user stack
+pub const USER_STACK_TOP: u64 = layout::USER_STACK_TOP;
+
+/// Size of the user stack
+pub const USER_STACK_SIZE: u64 = layout::USER_STACK_SIZE;
+
+/// Lowest address a segment may load at (the null page is never mapped)
+pub const USER_IMAGE_BASE: u64 = layout::USER_IMAGE.start;
+
+/// End of the user half of the address space
+pub const USER_SPACE_END: u64 = layout::USER_END;
+
+/// Regions the kernel maps into every process, which segments must avoid
+const RESERVED_RANGES: [(layout::Region, &str); 3] = [
+    (layout::THREAD_STACKS, "Segment overlaps the thread stacks"),
+    (layout::VDSO, "Segment overlaps the vDSO time page"),
+    (layout::USER_STACK, "Segment overlaps the user stack"),
+];
+
 // ============================================================================
 // ELF File Structures
 // ============================================================================
@@ -103,7 +140,7 @@ pub struct ProgramHeader {
 pub struct LoadedSegment {
     pub vaddr: u64,           // Virtual address
     pub size: u64,             // Size in memory
-    pub vmo: Box<Vmo>,         // VMO containing the segment data (boxed for stable address)
+    pub vmo: Arc<Vmo>,         // VMO containing the segment data (shared with the process VMAR)
     pub flags: u32,            // PF_R | PF_W | PF_X
 }
 
@@ -113,6 +150,7 @@ pub struct LoadedElf {
     pub segments: Vec<LoadedSegment>, // Loaded segments
     pub stack_addr: u64,        // Stack top address
     pub stack_size: u64,        // Stack size
+    pub stack_flags: u32,       // PF_R | PF_W, plus PF_X if PT_GNU_STACK asks for it
 }
 
 // ============================================================================
@@ -317,6 +355,56 @@ pub fn validate_elf_header(header: &ElfHeader) -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Check the non-LOAD program headers
+///
+/// Dynamically linked binaries (with `PT_INTERP` or `PT_DYNAMIC`) are
+/// rejected: there is no dynamic loader yet. The stack is readable and
+/// writable, and executable only if a `PT_GNU_STACK` header has `PF_X`.
+///
+/// # Returns
+///
+/// * `Ok(u32)` - Segment flags for the user stack
+/// * `Err(&str)` - The binary needs a dynamic loader
+pub fn check_program_headers(prog_headers: &[ProgramHeader]) -> Result<u32, &'static str> {
+    let mut stack_flags = PF_R | PF_W;
+    for ph in prog_headers {
+        match ph.p_type {
+            PT_INTERP => return Err("Dynamically linked ELF (PT_INTERP) is not supported"),
+            PT_DYNAMIC => return Err("Dynamically linked ELF (PT_DYNAMIC) is not supported"),
+            PT_GNU_STACK if ph.p_flags & PF_X != 0 => stack_flags |= PF_X,
+            _ => {}
+        }
+    }
+    Ok(stack_flags)
+}
+
+/// Check that a segment lies in the part of the user address space
+/// left to the binary
+///
+/// A segment must not cover the null page, reach the kernel half, or
+/// overlap a range the kernel maps into every process (the vDSO time
+/// page and the user stack).
+///
+/// # Arguments
+///
+/// * `vaddr` - Segment start address
+/// * `memsz` - Segment size in memory
+pub fn check_segment_placement(vaddr: u64, memsz: u64) -> Result<(), &'static str> {
+    let end = vaddr.checked_add(memsz).ok_or("Segment address overflows")?;
+    if vaddr < USER_IMAGE_BASE {
+        return Err("Segment maps the null page");
+    }
+    if end > USER_SPACE_END {
+        return Err("Segment outside the user address space");
+    }
+    for &(region, err) in RESERVED_RANGES.iter() {
+        if region.overlaps(vaddr, end) {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
 // ============================================================================
 // ELF Loading
 // ============================================================================
@@ -354,6 +442,7 @@ pub fn load_elf(elf_data: &[u8]) -> Result<Box<LoadedElf>, &'static str> {
     let phnum = header.e_phnum;
 
     let prog_headers = parse_program_headers(elf_data, phoff, phentsize, phnum);
+    let stack_flags = check_program_headers(&prog_headers)?;
 
     // Filter for LOAD segments and clone them to avoid reference issues
     // We need to own the data because heap allocations during VMO creation
@@ -390,35 +479,7 @@ pub fn load_elf(elf_data: &[u8]) -> Result<Box<LoadedElf>, &'static str> {
         let p_vaddr = ph.p_vaddr;
         let p_flags = ph.p_flags;
 
-        // Debug: Log the vaddr from ELF
-        unsafe {
-            let msg = b"[ELF] Segment vaddr from ELF: 0x";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-            let mut n = p_vaddr;
-            let mut buf = [0u8; 16];
-            let mut i = 0;
-            if n == 0 {
-                buf[i] = b'0';
-                i += 1;
-            } else {
-                while n > 0 {
-                    let digit = (n & 0xF) as u8;
-                    buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                    n >>= 4;
-                    i += 1;
-                }
-            }
-            while i > 0 {
-                i -= 1;
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-            }
-            let msg = b"\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-        }
+        kdebug!("[ELF] Segment vaddr={:#x} filesz={:#x} memsz={:#x}", p_vaddr, p_filesz, p_memsz);
 
         // Get segment data from file (using copied values)
         let file_start = p_offset as usize;
@@ -437,6 +498,7 @@ pub fn load_elf(elf_data: &[u8]) -> Result<Box<LoadedElf>, &'static str> {
 
         // Create VMO for this segment
         let mem_size = p_memsz.max(p_filesz); // Handle BSS (filesz < memsz)
+        check_segment_placement(p_vaddr, mem_size)?;
 
         // Skip zero-size segments (alignment segments, etc.)
         if mem_size == 0 {
@@ -453,9 +515,9 @@ pub fn load_elf(elf_data: &[u8]) -> Result<Box<LoadedElf>, &'static str> {
         let vmo = Vmo::create(aligned_size as usize, vmo_flags)
             .map_err(|_| "Failed to create VMO")?;
 
-        // CRITICAL: Immediately box the VMO before any operations
+        // CRITICAL: Immediately move the VMO to the heap before any operations
         // This prevents stack corruption from overwriting the VMO
-        let boxed_vmo = Box::new(vmo);
+        let boxed_vmo = Arc::new(vmo);
 
         // Write segment data to VMO (this allocates physical pages)
         if p_filesz > 0 {
@@ -463,189 +525,31 @@ pub fn load_elf(elf_data: &[u8]) -> Result<Box<LoadedElf>, &'static str> {
                 .map_err(|_| "Failed to write segment data to VMO")?;
         }
 
-        // Zero the BSS portion (if any) - use smaller chunks to avoid stack overflow
-        if p_memsz > p_filesz {
-            let bss_offset = p_filesz as usize;
-            let bss_size = (p_memsz - p_filesz) as usize;
-
-            // Zero in 256-byte chunks to avoid large stack allocations
-            let mut bytes_written = 0;
-            while bytes_written < bss_size {
-                let chunk_size = core::cmp::min(bss_size - bytes_written, 256);
-                let zero_chunk = [0u8; 256]; // Much smaller!
-
-                boxed_vmo.write(bss_offset + bytes_written, &zero_chunk[..chunk_size])
-                    .map_err(|_| "Failed to zero BSS")?;
-                bytes_written += chunk_size;
-            }
-        }
+        // BSS needs nothing: the rest of the last file page is zeroed when
+        // it is allocated, and later pages are committed zero-filled on
+        // first touch (see crate::process::vmar)
 
         // Store segment in Vec
-        unsafe {
-            let msg = b"[ELF] Storing segment with vaddr: 0x";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-            let mut n = p_vaddr;
-            let mut buf = [0u8; 16];
-            let mut i = 0;
-            if n == 0 {
-                buf[i] = b'0';
-                i += 1;
-            } else {
-                while n > 0 {
-                    let digit = (n & 0xF) as u8;
-                    buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                    n >>= 4;
-                    i += 1;
-                }
-            }
-            while i > 0 {
-                i -= 1;
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-            }
-            let msg = b"\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-        }
-
         segments.push(LoadedSegment {
             vaddr: p_vaddr,
             size: mem_size,
             vmo: boxed_vmo,
             flags: p_flags,
         });
-
-        unsafe {
-            let msg = b"[ELF] Segment stored, verifying vaddr: 0x";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-            let mut n = segments.last().unwrap().vaddr;
-            let mut buf = [0u8; 16];
-            let mut i = 0;
-            if n == 0 {
-                buf[i] = b'0';
-                i += 1;
-            } else {
-                while n > 0 {
-                    let digit = (n & 0xF) as u8;
-                    buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                    n >>= 4;
-                    i += 1;
-                }
-            }
-            while i > 0 {
-                i -= 1;
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-            }
-            let msg = b"\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-        }
     }
 
     // Set up user stack
-    let stack_addr = 0x7fff_ffff_f000u64;
-    let stack_size = 4 * 1024; // 4KB stack for testing (was 8MB)
-
-    unsafe {
-        let msg = b"[ELF] About to Box LoadedElf\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-    }
-
-    // Check vaddr BEFORE Box::new
-    unsafe {
-        let msg = b"[ELF] Before Box::new, segments ptr: 0x";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = segments.as_ptr() as usize;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-            n >>= 4;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b"\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-
-        let msg = b"[ELF] Before Box::new, checking segments[2].vaddr: 0x";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = segments[2].vaddr;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        if n == 0 {
-            buf[i] = b'0';
-            i += 1;
-        } else {
-            while n > 0 {
-                let digit = (n & 0xF) as u8;
-                buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                n >>= 4;
-                i += 1;
-            }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b"\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-    }
+    let stack_addr = USER_STACK_TOP;
+    let stack_size = USER_STACK_SIZE;
 
     let boxed = Box::new(LoadedElf {
         entry: header.e_entry,
         segments,
         stack_addr,
         stack_size,
+        stack_flags,
     });
 
-    unsafe {
-        let msg = b"[ELF] Box created, verifying first segment vaddr: 0x";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = boxed.segments[0].vaddr;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        if n == 0 {
-            buf[i] = b'0';
-            i += 1;
-        } else {
-            while n > 0 {
-                let digit = (n & 0xF) as u8;
-                buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                n >>= 4;
-                i += 1;
-            }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b"\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-    }
-
     Ok(boxed)
 }
 
@@ -807,4 +711,43 @@ mod tests {
         let header = parse_elf_header(&bad_data).unwrap();
         assert!(validate_elf_header(&header).is_err());
     }
+
+    fn program_header(p_type: u32, p_flags: u32) -> ProgramHeader {
+        ProgramHeader {
+            p_type,
+            p_flags,
+            p_offset: 0,
+            p_vaddr: 0,
+            p_paddr: 0,
+            p_filesz: 0,
+            p_memsz: 0,
+            p_align: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_program_headers() {
+        let load = program_header(PT_LOAD, PF_R | PF_X);
+        assert_eq!(check_program_headers(&[load]), Ok(PF_R | PF_W));
+
+        let stack = program_header(PT_GNU_STACK, PF_R | PF_W);
+        assert_eq!(check_program_headers(&[load, stack]), Ok(PF_R | PF_W));
+        let stack = program_header(PT_GNU_STACK, PF_R | PF_W | PF_X);
+        assert_eq!(check_program_headers(&[load, stack]), Ok(PF_R | PF_W | PF_X));
+
+        assert!(check_program_headers(&[load, program_header(PT_INTERP, PF_R)]).is_err());
+        assert!(check_program_headers(&[load, program_header(PT_DYNAMIC, PF_R | PF_W)]).is_err());
+    }
+
+    #[test]
+    fn test_check_segment_placement() {
+        assert!(check_segment_placement(0x100000, 0x2000).is_ok());
+        assert!(check_segment_placement(0, 0x2000).is_err());
+        assert!(check_segment_placement(u64::MAX - 0x10, 0x20).is_err());
+        assert!(check_segment_placement(0x7fff_ffff_0000, 0x2_0000).is_err());
+        assert!(check_segment_placement(crate::vdso::VDSO_TIME_VADDR - 0x1000, 0x1000).is_ok());
+        assert!(check_segment_placement(crate::vdso::VDSO_TIME_VADDR - 0x1000, 0x1001).is_err());
+        assert!(check_segment_placement(USER_STACK_TOP - 0x100, 0x10).is_err());
+        assert!(check_segment_placement(layout::THREAD_STACKS.end - 0x1000, 0x1000).is_err());
+    }
 }
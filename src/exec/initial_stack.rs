@@ -0,0 +1,188 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Initial Process Stack
+//!
+//! A new process starts with its arguments, environment and auxiliary
+//! vector on the stack, laid out as the System V x86_64 ABI describes.
+//! At entry `rsp` is 16-byte aligned and points at `argc`:
+//!
+//! ```text
+//! rsp ->  argc
+//!         argv[0] .. argv[argc - 1], NULL
+//!         envp[0] .. envp[envc - 1], NULL
+//!         (AT_PAGESZ, 4096) (AT_ENTRY, entry) (AT_NULL, 0)
+//!         padding
+//!         argv strings, then envp strings (NUL-terminated)
+//! stack top
+//! ```
+//!
+//! [`build`] lays the stack out in a buffer; the loader copies it into the
+//! stack VMO before the process runs.
+//!
+//! # Limits
+//!
+//! The whole block, strings and pointers, must fit in [`ARG_MAX`] bytes,
+//! so at least half of the user stack is left to the program. Each of
+//! `argv` and `envp` holds at most [`MAX_ARGS`] entries.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Auxiliary vector: end of the vector
+pub const AT_NULL: u64 = 0;
+
+/// Auxiliary vector: page size
+pub const AT_PAGESZ: u64 = 6;
+
+/// Auxiliary vector: program entry point
+pub const AT_ENTRY: u64 = 9;
+
+/// Largest argument block (strings, pointers and auxv), in bytes
+pub const ARG_MAX: usize = (crate::exec::elf::USER_STACK_SIZE / 2) as usize;
+
+/// Most entries in `argv`, and in `envp`
+pub const MAX_ARGS: usize = 64;
+
+/// Arguments and environment for a new process
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecArgs {
+    /// Argument strings, without terminators
+    pub argv: Vec<Vec<u8>>,
+    /// `NAME=value` strings, without terminators
+    pub envp: Vec<Vec<u8>>,
+}
+
+impl ExecArgs {
+    /// Arguments and environment, checked against [`MAX_ARGS`]
+    ///
+    /// Fails if either list is too long or a string contains a NUL byte.
+    pub fn new(argv: Vec<Vec<u8>>, envp: Vec<Vec<u8>>) -> Result<Self, &'static str> {
+        if argv.len() > MAX_ARGS || envp.len() > MAX_ARGS {
+            return Err("Too many arguments");
+        }
+        if argv.iter().chain(&envp).any(|s| s.contains(&0)) {
+            return Err("Argument contains a NUL byte");
+        }
+        Ok(Self { argv, envp })
+    }
+
+    /// Just `argv[0]`, with an empty environment
+    pub fn with_name(name: &str) -> Self {
+        Self { argv: vec![name.as_bytes().to_vec()], envp: Vec::new() }
+    }
+}
+
+/// Initial stack contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitialStack {
+    /// Initial stack pointer (the address of `argc`)
+    pub sp: u64,
+    /// Bytes from `sp` up to the stack top
+    pub image: Vec<u8>,
+}
+
+/// Lay out the initial stack below `stack_top`
+///
+/// `auxv` is terminated with `AT_NULL` here; callers leave it out.
+///
+/// # Returns
+///
+/// The stack image, or an error if it would exceed [`ARG_MAX`]
+pub fn build(stack_top: u64, args: &ExecArgs, auxv: &[(u64, u64)]) -> Result<InitialStack, &'static str> {
+    let strings: Vec<&[u8]> = args.argv.iter().chain(&args.envp).map(|s| s.as_slice()).collect();
+    let strings_len: usize = strings.iter().map(|s| s.len() + 1).sum();
+    let word_count = 1 + (args.argv.len() + 1) + (args.envp.len() + 1) + 2 * (auxv.len() + 1);
+
+    let strings_start = (stack_top - strings_len as u64) & !7;
+    let sp = (strings_start - (word_count * 8) as u64) & !15;
+    let size = (stack_top - sp) as usize;
+    if size > ARG_MAX {
+        return Err("Arguments too large for the stack");
+    }
+
+    let mut image = vec![0u8; size];
+
+    // Strings first, remembering where each one lands
+    let mut addrs = Vec::with_capacity(strings.len());
+    let mut at = (strings_start - sp) as usize;
+    for s in &strings {
+        addrs.push(sp + at as u64);
+        image[at..at + s.len()].copy_from_slice(s);
+        at += s.len() + 1;
+    }
+    let (argv_addrs, envp_addrs) = addrs.split_at(args.argv.len());
+
+    // Then the words at sp; NULL terminators are already zero
+    let mut words = Vec::with_capacity(word_count);
+    words.push(args.argv.len() as u64);
+    words.extend_from_slice(argv_addrs);
+    words.push(0);
+    words.extend_from_slice(envp_addrs);
+    words.push(0);
+    for &(key, value) in auxv {
+        words.extend_from_slice(&[key, value]);
+    }
+    words.extend_from_slice(&[AT_NULL, 0]);
+    for (chunk, word) in image.chunks_exact_mut(8).zip(&words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    Ok(InitialStack { sp, image })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOP: u64 = 0x7fff_ffff_f000;
+
+    fn word(stack: &InitialStack, index: usize) -> u64 {
+        let at = index * 8;
+        u64::from_le_bytes(stack.image[at..at + 8].try_into().unwrap())
+    }
+
+    fn string_at(stack: &InitialStack, addr: u64) -> &[u8] {
+        let start = (addr - stack.sp) as usize;
+        let len = stack.image[start..].iter().position(|&b| b == 0).unwrap();
+        &stack.image[start..start + len]
+    }
+
+    #[test]
+    fn test_layout() {
+        let args = ExecArgs::new(vec![b"/bin/ls".to_vec(), b"-l".to_vec()], vec![b"HOME=/".to_vec()]).unwrap();
+        let stack = build(TOP, &args, &[(AT_PAGESZ, 4096), (AT_ENTRY, 0x40_1000)]).unwrap();
+
+        assert_eq!(stack.sp % 16, 0);
+        assert_eq!(stack.sp + stack.image.len() as u64, TOP);
+        assert_eq!(word(&stack, 0), 2);
+        assert_eq!(string_at(&stack, word(&stack, 1)), b"/bin/ls");
+        assert_eq!(string_at(&stack, word(&stack, 2)), b"-l");
+        assert_eq!(word(&stack, 3), 0);
+        assert_eq!(string_at(&stack, word(&stack, 4)), b"HOME=/");
+        assert_eq!(word(&stack, 5), 0);
+        assert_eq!((word(&stack, 6), word(&stack, 7)), (AT_PAGESZ, 4096));
+        assert_eq!((word(&stack, 8), word(&stack, 9)), (AT_ENTRY, 0x40_1000));
+        assert_eq!((word(&stack, 10), word(&stack, 11)), (AT_NULL, 0));
+    }
+
+    #[test]
+    fn test_empty_args() {
+        let stack = build(TOP, &ExecArgs::default(), &[]).unwrap();
+        assert_eq!(stack.sp % 16, 0);
+        assert_eq!(word(&stack, 0), 0);
+        assert_eq!((word(&stack, 1), word(&stack, 2)), (0, 0));
+        assert_eq!((word(&stack, 3), word(&stack, 4)), (AT_NULL, 0));
+    }
+
+    #[test]
+    fn test_limits() {
+        assert!(ExecArgs::new(vec![b"a\0b".to_vec()], Vec::new()).is_err());
+        assert!(ExecArgs::new(vec![Vec::new(); MAX_ARGS + 1], Vec::new()).is_err());
+        let big = ExecArgs::new(vec![vec![b'x'; ARG_MAX]], Vec::new()).unwrap();
+        assert!(build(TOP, &big, &[]).is_err());
+    }
+}
@@ -10,6 +10,7 @@
 //! ELF binaries in userspace.
 
 pub mod elf;
+pub mod initial_stack;
 pub mod process_loader;
 pub mod userspace_exec_test;
 
@@ -29,6 +30,7 @@ pub use elf::{
 
 // Re-export process loader types
 pub use process_loader::{ProcessImage, load_elf_process};
+pub use initial_stack::ExecArgs;
 
 // Re-export userspace test
 pub use userspace_exec_test::test_userspace_execution;
@@ -8,13 +8,24 @@
 //!
 //! This module provides functionality to load ELF binaries into
 //! new process address spaces and prepare them for execution.
+//!
+//! With [`EXEC_DEBUG_FLAG`] on the kernel command line, every mapping
+//! made for a new process is logged with its permissions, e.g.
+//! `[EXEC] segment 0x100000-0x101000 r-x`.
 
 #![allow(dead_code)]
 
-use crate::exec::elf::{load_elf, LoadedElf};
+use crate::exec::elf::{load_elf, PF_R, PF_W, PF_X};
+use crate::exec::initial_stack::{self, ExecArgs, AT_ENTRY, AT_PAGESZ};
 use crate::process::AddressSpace;
+use crate::process::vmar::Vmar;
 use crate::object::{Vmo, VmoFlags};
 use crate::mm::pmm;
+use alloc::sync::Arc;
+use crate::kdebug;
+
+/// Command-line flag: log each mapping and its permissions at spawn
+pub const EXEC_DEBUG_FLAG: &str = "exec.debug";
 
 /// Information needed to start execution of a loaded process
 pub struct ProcessImage {
@@ -22,10 +33,12 @@ pub struct ProcessImage {
     pub entry: u64,
     /// Address space for the process
     pub address_space: AddressSpace,
-    /// Stack top address
+    /// Initial stack pointer, below the argument block
     pub stack_top: u64,
     /// Stack size
     pub stack_size: u64,
+    /// Segment and stack mappings, for demand paging
+    pub vmar: Vmar,
 }
 
 /// Load an ELF binary into a new process
@@ -34,18 +47,22 @@ pub struct ProcessImage {
 /// 1. Parses and loads the ELF binary
 /// 2. Creates a new address space
 /// 3. Maps all ELF segments into the address space
-/// 4. Creates and maps a user stack
-/// 5. Returns information needed to start execution
+/// 4. Creates and maps a user stack (committed on first touch)
+/// 5. Copies `args` and the auxiliary vector onto the stack (see
+///    [`initial_stack`])
+/// 6. Maps the vDSO time page (see [`crate::vdso`])
+/// 7. Returns information needed to start execution
 ///
 /// # Arguments
 ///
 /// * `elf_data` - Raw ELF file contents
+/// * `args` - Arguments and environment for the new process
 ///
 /// # Returns
 ///
 /// * `Ok(ProcessImage)` - Loaded process ready to execute
 /// * `Err(&str)` - Loading failed
-pub fn load_elf_process(elf_data: &[u8]) -> Result<ProcessImage, &'static str> {
+pub fn load_elf_process(elf_data: &[u8], args: &ExecArgs) -> Result<ProcessImage, &'static str> {
     // Load ELF segments into VMOs
     let loaded_elf = load_elf(elf_data)?;
 
@@ -53,35 +70,13 @@ pub fn load_elf_process(elf_data: &[u8]) -> Result<ProcessImage, &'static str> {
     let address_space = AddressSpace::new()
         .map_err(|_| "Failed to create address space")?;
 
+    let mut vmar = Vmar::new();
+
     // Map each segment into the address space
+    let debug = crate::cmdline::has_flag(EXEC_DEBUG_FLAG);
     for segment in loaded_elf.segments.iter() {
-        unsafe {
-            let msg = b"[MAP] About to map segment at vaddr: 0x";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-            let mut n = segment.vaddr;
-            let mut buf = [0u8; 16];
-            let mut i = 0;
-            if n == 0 {
-                buf[i] = b'0';
-                i += 1;
-            } else {
-                while n > 0 {
-                    let digit = (n & 0xF) as u8;
-                    buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                    n >>= 4;
-                    i += 1;
-                }
-            }
-            while i > 0 {
-                i -= 1;
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-            }
-            let msg = b"\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
+        if debug {
+            log_mapping("segment", segment.vaddr, segment.size, segment.flags);
         }
         address_space.map_vmo(
             &segment.vmo,
@@ -89,46 +84,58 @@ pub fn load_elf_process(elf_data: &[u8]) -> Result<ProcessImage, &'static str> {
             segment.size,
             segment.flags,
         )?;
+        vmar.insert(segment.vaddr, segment.size, segment.vmo.clone(), segment.flags)
+            .map_err(|_| "Overlapping segments")?;
     }
 
-    // Create and map the stack
-    let stack_vmo = Vmo::create(loaded_elf.stack_size as usize, VmoFlags::empty)
-        .map_err(|_| "Failed to create stack VMO")?;
-
-    // Pre-allocate stack pages by writing zeros
-    // This allocates physical pages for the stack before mapping
-    let stack_size = loaded_elf.stack_size as usize;
-    let page_size = 4096;
-    let num_pages = (stack_size + page_size - 1) / page_size;
-    let zero_page = [0u8; 4096];
-
-    for page_idx in 0..num_pages {
-        let offset = page_idx * page_size;
-        // Write a zero page to trigger PMM allocation
-        let bytes_to_write = if offset + page_size <= stack_size {
-            &zero_page[..]
-        } else {
-            // Last page might be partial
-            &zero_page[..stack_size - offset]
-        };
-        stack_vmo.write(offset, bytes_to_write)
-            .map_err(|_| "Failed to allocate stack pages")?;
-    }
+    // Create the stack; its pages are committed as the process touches them
+    let stack_vmo = Arc::new(Vmo::create(loaded_elf.stack_size as usize, VmoFlags::empty)
+        .map_err(|_| "Failed to create stack VMO")?);
 
     // Map the stack at the high address
     // Ensure stack_bottom is page-aligned (round down to nearest 4KB)
     let stack_bottom = (loaded_elf.stack_addr - loaded_elf.stack_size) & !0xFFF;
+    // Not executable unless the binary's PT_GNU_STACK asks for it
+    if debug {
+        log_mapping("stack", stack_bottom, loaded_elf.stack_size, loaded_elf.stack_flags);
+    }
     address_space.map_vmo(
         &stack_vmo,
         stack_bottom,
         loaded_elf.stack_size,
-        0x6, // PF_R | PF_W (readable + writable)
+        loaded_elf.stack_flags,
     ).map_err(|_| "Failed to map stack")?;
 
+    // argc, argv, envp and auxv go at the top of the stack
+    let auxv = [(AT_PAGESZ, crate::mm::PAGE_SIZE as u64), (AT_ENTRY, loaded_elf.entry)];
+    let initial = initial_stack::build(loaded_elf.stack_addr, args, &auxv)?;
+    stack_vmo.write((initial.sp - stack_bottom) as usize, &initial.image)
+        .map_err(|_| "Failed to write the initial stack")?;
+
+    vmar.insert(stack_bottom, loaded_elf.stack_size, stack_vmo, loaded_elf.stack_flags)
+        .map_err(|_| "Stack overlaps a segment")?;
+
+    // Map the clock data for syscall-free time reads
+    crate::vdso::map_into(&address_space)?;
+
     Ok(ProcessImage {
         entry: loaded_elf.entry,
         address_space,
-        stack_top: loaded_elf.stack_addr,
+        stack_top: initial.sp,
         stack_size: loaded_elf.stack_size,
+        vmar,
     })
 }
+
+/// Log one mapping as `[EXEC] <what> <start>-<end> rwx`
+fn log_mapping(what: &str, vaddr: u64, size: u64, flags: u32) {
+    kdebug!("[EXEC] {} {:#x}-{:#x} {}", what, vaddr, vaddr + size, permission_string(flags));
+}
+
+/// `PF_*` flags as an `ls`-style `rwx` string
+fn permission_string(flags: u32) -> alloc::string::String {
+    [(PF_R, 'r'), (PF_W, 'w'), (PF_X, 'x')]
+        .iter()
+        .map(|&(bit, c)| if flags & bit != 0 { c } else { '-' })
+        .collect()
+}
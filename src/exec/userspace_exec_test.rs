@@ -9,6 +9,8 @@
 //! This module provides a test for loading and executing
 //! a userspace ELF binary.
 
+use crate::{kerror, kinfo};
+
 /// Embedded userspace test binary
 ///
 /// This is a static x86_64 ELF binary that writes
@@ -17,7 +19,7 @@
 ///
 /// Path: ../../test-userspace/hello.elf (from src/exec/userspace_exec_test.rs)
 /// Resolves to: test-userspace/hello.elf relative to crate root
-static USERSPACE_ELF: &[u8] = include_bytes!("../../test-userspace/hello.elf");
+pub(crate) static USERSPACE_ELF: &[u8] = include_bytes!("../../test-userspace/hello.elf");
 
 /// Build-time verification: Ensure ELF is at least 8KB
 /// If this fails, the hello.elf file may not exist or be wrong size
@@ -47,57 +49,18 @@ pub unsafe fn test_userspace_execution() -> ! {
     // Print heap status BEFORE ELF loading
     allocator::heap_print_summary();
 
-    // Print ELF size for debugging (simple decimal)
-    {
-        let elf_size = USERSPACE_ELF.len();
-        let msg = b"[KERNEL] ELF size: ";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-
-        // Print decimal size
-        let mut n = elf_size;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b'\n', options(nomem, nostack));
-    }
+    kinfo!("[KERNEL] ELF size: {}", USERSPACE_ELF.len());
 
     // Test heap allocation before loading ELF
     extern crate alloc;
     let _test_vec = alloc::vec::Vec::<u8>::new();
-    let msg = b"[KERNEL] Heap test passed\n";
-    for &byte in msg {
-        core::arch::asm!(
-            "out dx, al",
-            in("dx") 0xE9u16,
-            in("al") byte,
-            options(nomem, nostack)
-        );
-    }
+    kinfo!("[KERNEL] Heap test passed");
 
     // Write debug message
-    let msg = b"[KERNEL] Loading userspace ELF binary...\n";
-    for &byte in msg {
-        core::arch::asm!(
-            "out dx, al",
-            in("dx") 0xE9u16,
-            in("al") byte,
-            options(nomem, nostack)
-        );
-    }
+    kinfo!("[KERNEL] Loading userspace ELF binary...");
 
     // Load ELF into process address space
-    let process_image = match process_loader::load_elf_process(USERSPACE_ELF) {
+    let process_image = match process_loader::load_elf_process(USERSPACE_ELF, &crate::exec::ExecArgs::default()) {
         Ok(img) => {
             // Print heap status AFTER ELF loading (SUCCESS)
             allocator::heap_print_summary();
@@ -107,52 +70,25 @@ pub unsafe fn test_userspace_execution() -> ! {
             // Print heap status AFTER ELF loading (FAILURE)
             allocator::heap_print_summary();
 
-            let err_msg = b"[KERNEL] Failed to load ELF: ";
-            for &byte in err_msg {
-                core::arch::asm!(
-                    "out dx, al",
-                    in("dx") 0xE9u16,
-                    in("al") byte,
-                    options(nomem, nostack)
-                );
-            }
-            for &byte in e.as_bytes() {
-                core::arch::asm!(
-                    "out dx, al",
-                    in("dx") 0xE9u16,
-                    in("al") byte,
-                    options(nomem, nostack)
-                );
-            }
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b'\n', options(nomem, nostack));
+            kerror!("[KERNEL] Failed to load ELF: {}", e);
             loop { core::arch::asm!("hlt"); }
         }
     };
 
     // Debug: confirm we got past loading
-    let dbg_msg = b"[KERNEL] ELF load returned\n";
-    for &byte in dbg_msg {
-        core::arch::asm!(
-            "out dx, al",
-            in("dx") 0xE9u16,
-            in("al") byte,
-            options(nomem, nostack)
-        );
-    }
+    kinfo!("[KERNEL] ELF load returned");
 
-    let ok_msg = b"[KERNEL] ELF loaded successfully, jumping to userspace...\n";
-    for &byte in ok_msg {
-        core::arch::asm!(
-            "out dx, al",
-            in("dx") 0xE9u16,
-            in("al") byte,
-            options(nomem, nostack)
-        );
-    }
+    kinfo!("[KERNEL] ELF loaded successfully, jumping to userspace...");
 
     // Get CR3 value from the address space
     let cr3 = process_image.address_space.page_table.phys;
 
+    // No process table entry, so nothing can be demand paged
+    if process_image.vmar.commit_all(cr3).is_err() {
+        kerror!("[KERNEL] Failed to commit process pages");
+        loop { core::arch::asm!("hlt"); }
+    }
+
     // Execute the process
     uspace::execute_process(process_image.entry, process_image.stack_top, cr3);
 }
@@ -0,0 +1,152 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Device Filesystem (devfs)
+//!
+//! This module resolves paths under `/dev` to device nodes. It is a
+//! static namespace: nodes exist for every device the kernel knows about
+//! and cannot be created or removed.
+//!
+//! # Nodes
+//!
+//! | Path | Device |
+//! |------|--------|
+//! | `/dev/tty1` .. `/dev/tty4` | TTY on virtual terminal 1..4 (Alt+F1..F4) |
+//! | `/dev/tty0` | TTY on the currently active virtual terminal |
+//! | `/dev/console` | Kernel console TTY |
+//! | `/dev/ram0`, `/dev/ram0p1`, ... | Block devices and partitions registered with [`crate::drivers::block`] |
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::drivers::block;
+use crate::drivers::tty::{self, NUM_TTYS};
+use crate::fs::ramdisk::Errno;
+use crate::fs::vfs::{DirEntry, Stat, DT_BLK, DT_CHR, FS_DEVFS};
+
+/// devfs mount point
+pub const DEVFS_PREFIX: &str = "/dev/";
+
+/// Inode number of `/dev`
+pub const ROOT_INODE: u64 = 0;
+
+/// Inode number of the first block device (TTYs come before)
+const BLOCK_INODE_BASE: u64 = 0x100;
+
+/// A device node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DevNode {
+    /// TTY by index (0-based, `/dev/tty1` is index 0)
+    Tty(u8),
+    /// Block device by index in the block layer
+    Block(u8),
+}
+
+/// Check whether a path lives in devfs
+pub fn is_devfs_path(path: &str) -> bool {
+    path.starts_with(DEVFS_PREFIX)
+}
+
+/// Look up a device node
+///
+/// # Arguments
+///
+/// * `path` - Absolute path (e.g. `/dev/tty2`)
+///
+/// # Returns
+///
+/// The device node, or `ENOENT` if no such device exists
+pub fn lookup(path: &str) -> Result<DevNode, Errno> {
+    let name = path.strip_prefix(DEVFS_PREFIX).ok_or(Errno::ENOENT)?;
+
+    if name == "console" {
+        return Ok(DevNode::Tty(tty::CONSOLE_TTY as u8));
+    }
+    if let Some(index) = block::find(name) {
+        return Ok(DevNode::Block(index as u8));
+    }
+
+    let num: usize = name
+        .strip_prefix("tty")
+        .and_then(|n| n.parse().ok())
+        .ok_or(Errno::ENOENT)?;
+
+    match num {
+        0 => Ok(DevNode::Tty(tty::active() as u8)),
+        n if n <= NUM_TTYS => Ok(DevNode::Tty((n - 1) as u8)),
+        _ => Err(Errno::ENOENT),
+    }
+}
+
+/// Metadata of a device node
+///
+/// Names for the same device (`/dev/console` and the TTY it is) share
+/// an inode. A block device reports its capacity as its size.
+pub fn stat(node: DevNode) -> Stat {
+    match node {
+        DevNode::Tty(n) => Stat::new(FS_DEVFS, DT_CHR, n as u64 + 1, 0, 0),
+        DevNode::Block(n) => {
+            let size = block::get(n as usize).map_or(0, |dev| dev.size());
+            Stat::new(FS_DEVFS, DT_BLK, BLOCK_INODE_BASE + n as u64, size, 0)
+        }
+    }
+}
+
+/// List the device nodes (the `/dev` directory)
+pub fn list() -> Vec<DirEntry> {
+    let mut entries = vec![DirEntry::device("console"), DirEntry::device("tty0")];
+    for n in 1..=NUM_TTYS {
+        entries.push(DirEntry::device(&format!("tty{}", n)));
+    }
+    for n in 0..block::count() {
+        if let Some(dev) = block::get(n) {
+            entries.push(DirEntry::block_device(dev.name()));
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_tty() {
+        assert_eq!(lookup("/dev/tty1"), Ok(DevNode::Tty(0)));
+        assert_eq!(lookup("/dev/tty4"), Ok(DevNode::Tty(3)));
+        assert_eq!(lookup("/dev/console"), Ok(DevNode::Tty(0)));
+        assert_eq!(lookup("/dev/tty5"), Err(Errno::ENOENT));
+        assert_eq!(lookup("/dev/ttyx"), Err(Errno::ENOENT));
+        assert_eq!(lookup("/bin/init"), Err(Errno::ENOENT));
+    }
+
+    #[test]
+    fn test_stat() {
+        assert_eq!(stat(DevNode::Tty(0)), stat(lookup("/dev/console").unwrap()));
+        assert_ne!(stat(DevNode::Tty(1)).inode, ROOT_INODE);
+    }
+
+    #[test]
+    fn test_block_device() {
+        use alloc::sync::Arc;
+        use crate::drivers::block::ram::{RamDisk, BLOCK_SIZE};
+
+        let _ = block::register(Arc::new(RamDisk::new("devfs-test", BLOCK_SIZE).unwrap()));
+        let node = lookup("/dev/devfs-test").unwrap();
+        assert!(matches!(node, DevNode::Block(_)));
+        let st = stat(node);
+        assert_eq!((st.kind, st.size), (DT_BLK, BLOCK_SIZE as u64));
+        assert_ne!(st.inode, stat(DevNode::Tty(0)).inode);
+        assert!(list().iter().any(|e| e.name == "devfs-test" && e.kind == DT_BLK));
+    }
+
+    #[test]
+    fn test_list() {
+        let names: Vec<_> = list().into_iter().map(|e| e.name).collect();
+        assert_eq!(names.iter().filter(|n| n.starts_with("tty") || *n == "console").count(), NUM_TTYS + 2);
+        assert!(names.iter().all(|n| lookup(&format!("{}{}", DEVFS_PREFIX, n)).is_ok()));
+    }
+}
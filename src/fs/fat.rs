@@ -0,0 +1,1212 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! FAT32 Filesystem
+//!
+//! This module reads and writes FAT32 volumes on a block device, such as
+//! the EFI system partition the kernel was loaded from. A volume is
+//! attached to the file tree by the mount table ([`mount`](crate::fs::mount));
+//! paths given to [`FatVolume`] are relative to the volume's root.
+//!
+//! # Layout
+//!
+//! - The boot sector describes the geometry (BIOS parameter block)
+//! - The FAT has one 32-bit entry per cluster (low 28 bits used): the
+//!   next cluster of the file, 0 if free, or an end-of-chain mark
+//! - Clusters from 2 on hold file data and directories, including the
+//!   root directory
+//! - A directory is an array of 32-byte entries: an 8.3 short name, the
+//!   first cluster and the size, preceded by long-name (VFAT) entries if
+//!   the name needs them
+//!
+//! A volume is recognized as FAT32 by its parameter block (no fixed root
+//! directory, a 32-bit FAT size), not by its cluster count, so small test
+//! images work too.
+//!
+//! # Names
+//!
+//! Names are matched without regard to ASCII case, as on other systems.
+//! A new name that fits 8.3 in one case per part (`log.txt`, `EFI`) gets
+//! only a short entry, with its case kept in the entry's case flags; any
+//! other name gets long-name entries and a short alias like `LONGNA~1.TXT`.
+//!
+//! # Inodes
+//!
+//! A file's inode number is the byte offset of its short entry on the
+//! volume; the root directory is [`ROOT_INODE`]. The entry is read again
+//! on every operation, so all descriptors of a file agree on its size.
+//!
+//! # Limitations
+//!
+//! - There is no calendar clock: new entries are dated 1980-01-01 and
+//!   `mtime_ns` is 0
+//! - Every volume reports `FS_FAT`, and inode numbers are offsets, so two
+//!   volumes can repeat an inode number
+//! - Files are limited to 4 GiB - 1 (`EINVAL` beyond)
+//! - Unlinking frees a file at once: descriptors that have it open then
+//!   fail with `ENOENT`
+//! - The free cluster count in the FSInfo sector is marked unknown on the
+//!   first change rather than kept up to date
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::drivers::block::{self, BlockDevice};
+use crate::fs::mount::Filesystem;
+use crate::fs::ramdisk::Errno;
+use crate::fs::vfs::{DirEntry, Stat, DT_DIR, DT_REG, FS_FAT};
+
+/// Inode number of the root directory
+pub const ROOT_INODE: u64 = 0;
+
+/// Longest name of a single path component
+pub const NAME_MAX: usize = 255;
+
+/// Largest file size
+pub const MAX_FILE_SIZE: u64 = u32::MAX as u64;
+
+/// Size of a directory entry
+const ENTRY_SIZE: usize = 32;
+
+/// Directory entry attributes
+mod attr {
+    pub const READ_ONLY: u8 = 0x01;
+    pub const VOLUME_ID: u8 = 0x08;
+    pub const DIRECTORY: u8 = 0x10;
+    pub const ARCHIVE: u8 = 0x20;
+    /// Long-name entry (read-only, hidden, system and volume ID)
+    pub const LONG_NAME: u8 = 0x0F;
+}
+
+/// First name byte of a deleted entry
+const DELETED: u8 = 0xE5;
+
+/// Bits of a FAT entry that hold the cluster number
+const CLUSTER_MASK: u32 = 0x0FFF_FFFF;
+
+/// FAT entries from here on end a chain
+const END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+
+/// End-of-chain mark written for the last cluster of a file
+const CHAIN_END: u32 = 0x0FFF_FFFF;
+
+/// Case flags of a short entry: base name / extension stored lower case
+const LOWER_BASE: u8 = 0x08;
+const LOWER_EXT: u8 = 0x10;
+
+/// Order-byte flag of the last (first stored) long-name entry
+const LFN_LAST: u8 = 0x40;
+
+/// Characters (UTF-16 units) per long-name entry, at these offsets
+const LFN_CHARS: usize = 13;
+const LFN_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// Date of new entries: 1980-01-01, the FAT epoch
+const EPOCH_DATE: u16 = (1 << 5) | 1;
+
+/// FSInfo sector signatures and the offset of its free cluster count
+const FSINFO_LEAD_SIG: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIG: u32 = 0x6141_7272;
+const FSINFO_FREE_COUNT: u64 = 488;
+const FSINFO_NEXT_FREE: u64 = 492;
+
+/// A file or directory on the volume
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// Inode number (offset of the short entry, [`ROOT_INODE`] for the root)
+    ino: u64,
+    /// Attributes (`attr::*`)
+    attr: u8,
+    /// First cluster (0 for an empty file)
+    cluster: u32,
+    /// Size in bytes (0 for directories)
+    size: u32,
+}
+
+impl Node {
+    /// Node described by the short entry `raw` at offset `ino`
+    fn from_entry(ino: u64, raw: &[u8]) -> Self {
+        Self {
+            ino,
+            attr: raw[11],
+            cluster: ((le16(raw, 20) as u32) << 16) | le16(raw, 26) as u32,
+            size: le32(raw, 28),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.attr & attr::DIRECTORY != 0
+    }
+}
+
+/// A name in a directory
+struct Entry {
+    /// Long name if it has a valid one, else the short name
+    name: String,
+    /// Short name as stored (8 + 3 bytes, space padded)
+    short: [u8; 11],
+    /// The file it names
+    node: Node,
+    /// Offsets of its long-name entries
+    lfn_slots: Vec<u64>,
+}
+
+/// A mounted FAT32 volume
+pub struct FatVolume {
+    device: Arc<dyn BlockDevice>,
+    /// Bytes per cluster
+    cluster_size: u64,
+    /// Offset of the first FAT
+    fat_start: u64,
+    /// Bytes per FAT
+    fat_bytes: u64,
+    /// Number of FAT copies
+    num_fats: u32,
+    /// Offset of cluster 2
+    data_start: u64,
+    /// Highest valid cluster number
+    max_cluster: u32,
+    /// First cluster of the root directory
+    root_cluster: u32,
+    /// Offset of the FSInfo sector, if the volume has a valid one
+    fsinfo: Option<u64>,
+    /// Where the next free cluster search starts
+    next_free: u32,
+    /// Whether the FSInfo free count was already marked unknown
+    fsinfo_stale: bool,
+}
+
+impl FatVolume {
+    /// Read the boot sector of a FAT32 volume on `device`
+    ///
+    /// # Returns
+    ///
+    /// The volume, or `EINVAL` if the device does not hold a FAT32
+    /// filesystem (or one larger than the device)
+    pub fn new(device: Arc<dyn BlockDevice>) -> Result<Self, Errno> {
+        let mut bs = [0u8; 512];
+        if block::read_at(&*device, 0, &mut bs)? < bs.len() || bs[510..512] != [0x55, 0xAA] {
+            return Err(Errno::EINVAL);
+        }
+
+        let sector = le16(&bs, 11) as u64;
+        let per_cluster = bs[13] as u64;
+        let reserved = le16(&bs, 14) as u64;
+        let num_fats = bs[16] as u64;
+        let fat_sectors = le32(&bs, 36) as u64;
+        if !matches!(sector, 512 | 1024 | 2048 | 4096) || !per_cluster.is_power_of_two() || reserved == 0 || num_fats == 0 {
+            return Err(Errno::EINVAL);
+        }
+        // FAT12/16 have a fixed root directory and a 16-bit FAT size
+        if le16(&bs, 17) != 0 || le16(&bs, 22) != 0 || fat_sectors == 0 {
+            return Err(Errno::EINVAL);
+        }
+
+        let total = match le16(&bs, 19) {
+            0 => le32(&bs, 32) as u64,
+            n => n as u64,
+        };
+        let data_sector = reserved + num_fats * fat_sectors;
+        if total <= data_sector || total * sector > device.size() {
+            return Err(Errno::EINVAL);
+        }
+        let clusters = ((total - data_sector) / per_cluster)
+            .min((fat_sectors * sector / 4).saturating_sub(2))
+            // Cluster numbers stop below the bad-cluster and end marks
+            .min(0x0FFF_FFF4);
+        let max_cluster = clusters as u32 + 1;
+        let root_cluster = le32(&bs, 44);
+        if clusters == 0 || !(2..=max_cluster).contains(&root_cluster) {
+            return Err(Errno::EINVAL);
+        }
+
+        let mut volume = Self {
+            device,
+            cluster_size: per_cluster * sector,
+            fat_start: reserved * sector,
+            fat_bytes: fat_sectors * sector,
+            num_fats: num_fats as u32,
+            data_start: data_sector * sector,
+            max_cluster,
+            root_cluster,
+            fsinfo: None,
+            next_free: 2,
+            fsinfo_stale: false,
+        };
+
+        let fsinfo = le16(&bs, 48) as u64;
+        if fsinfo != 0 && fsinfo < reserved {
+            let mut info = vec![0u8; 512];
+            volume.read_bytes(fsinfo * sector, &mut info)?;
+            if le32(&info, 0) == FSINFO_LEAD_SIG && le32(&info, 484) == FSINFO_STRUCT_SIG {
+                volume.fsinfo = Some(fsinfo * sector);
+                volume.next_free = le32(&info, FSINFO_NEXT_FREE as usize);
+            }
+        }
+        Ok(volume)
+    }
+
+    /// The block device holding the volume
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+
+    /// Look up a path
+    ///
+    /// # Returns
+    ///
+    /// The inode number, or `ENOENT` / `ENOTDIR`
+    pub fn lookup(&self, path: &str) -> Result<u64, Errno> {
+        self.resolve(path).map(|node| node.ino)
+    }
+
+    /// Open a file, as for the `OPEN` syscall
+    ///
+    /// `flags` are the `O_*` open flags: `O_CREAT` creates a missing file
+    /// (`EEXIST` with `O_EXCL` if it exists), `O_TRUNC` empties it.
+    ///
+    /// # Returns
+    ///
+    /// The inode number, or `EISDIR` for a directory opened for writing,
+    /// `EACCES` for a read-only file opened for writing, or a lookup error
+    pub fn open(&mut self, path: &str, flags: u32) -> Result<u64, Errno> {
+        use crate::syscall::fd::flags::{O_CREAT, O_EXCL, O_TRUNC, O_RDONLY};
+
+        let writable = flags & 3 != O_RDONLY;
+        let node = match self.resolve(path) {
+            Ok(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => return Err(Errno::EEXIST),
+            Ok(node) => node,
+            Err(Errno::ENOENT) if flags & O_CREAT != 0 => {
+                let (dir, name) = self.parent(path)?;
+                self.create(&dir, name, attr::ARCHIVE, 0)?
+            }
+            Err(e) => return Err(e),
+        };
+
+        if node.is_dir() && (writable || flags & O_TRUNC != 0) {
+            return Err(Errno::EISDIR);
+        }
+        if writable && node.attr & attr::READ_ONLY != 0 {
+            return Err(Errno::EACCES);
+        }
+        if writable && flags & O_TRUNC != 0 {
+            self.truncate(node.ino, 0)?;
+        }
+        Ok(node.ino)
+    }
+
+    /// Size of a file in bytes (0 for a directory)
+    pub fn size(&self, ino: u64) -> Result<u64, Errno> {
+        self.node_at(ino).map(|node| node.size as u64)
+    }
+
+    /// Metadata of a file or directory
+    pub fn stat(&self, ino: u64) -> Result<Stat, Errno> {
+        let node = self.node_at(ino)?;
+        let kind = if node.is_dir() { DT_DIR } else { DT_REG };
+        let size = if node.is_dir() { 0 } else { node.size as u64 };
+        Ok(Stat::new(FS_FAT, kind, ino, size, 0))
+    }
+
+    /// List a directory
+    pub fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno> {
+        let dir = self.resolve(path)?;
+        Ok(self.entries(&dir)?
+            .into_iter()
+            .map(|e| if e.node.is_dir() { DirEntry::dir(&e.name) } else { DirEntry::file(&e.name, e.node.size as u64) })
+            .collect())
+    }
+
+    /// Read from a file at `offset`
+    ///
+    /// # Returns
+    ///
+    /// Bytes read: short at the end of the file, 0 at or past it
+    pub fn read(&self, ino: u64, offset: u64, buf: &mut [u8]) -> Result<usize, Errno> {
+        let node = self.node_at(ino)?;
+        if node.is_dir() {
+            return Err(Errno::EISDIR);
+        }
+        let len = core::cmp::min(buf.len() as u64, (node.size as u64).saturating_sub(offset)) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        let chain = self.chain(node.cluster)?;
+        self.for_each_piece(&chain, offset, len, |fs, at, range| fs.read_bytes(at, &mut buf[range]))?;
+        Ok(len)
+    }
+
+    /// Write to a file at `offset`, growing it if needed
+    ///
+    /// A gap between the old end of the file and `offset` reads as zeros.
+    ///
+    /// # Returns
+    ///
+    /// Bytes written (all of `src`), or `ENOSPC` if the volume is full
+    pub fn write(&mut self, ino: u64, offset: u64, src: &[u8]) -> Result<usize, Errno> {
+        let mut node = self.node_at(ino)?;
+        if node.is_dir() {
+            return Err(Errno::EISDIR);
+        }
+        if src.is_empty() {
+            return Ok(0);
+        }
+        let end = offset.checked_add(src.len() as u64).filter(|&end| end <= MAX_FILE_SIZE).ok_or(Errno::EINVAL)?;
+
+        let chain = self.grow(&mut node, end)?;
+        self.for_each_piece(&chain, offset, src.len(), |fs, at, range| fs.write_bytes(at, &src[range]))?;
+        node.size = node.size.max(end as u32);
+        self.store(&node)?;
+        Ok(src.len())
+    }
+
+    /// Set the size of a file
+    ///
+    /// Growing fills with zeros; shrinking frees the clusters past the
+    /// new end.
+    pub fn truncate(&mut self, ino: u64, size: u64) -> Result<(), Errno> {
+        let mut node = self.node_at(ino)?;
+        if node.is_dir() {
+            return Err(Errno::EISDIR);
+        }
+        if size > MAX_FILE_SIZE {
+            return Err(Errno::EINVAL);
+        }
+
+        if size > node.size as u64 {
+            self.grow(&mut node, size)?;
+        } else {
+            let keep = size.div_ceil(self.cluster_size) as usize;
+            let chain = self.chain(node.cluster)?;
+            if keep == 0 {
+                node.cluster = 0;
+            } else if chain.len() > keep {
+                self.set_fat_entry(chain[keep - 1], CHAIN_END)?;
+            }
+            for &cluster in chain.iter().skip(keep) {
+                self.set_fat_entry(cluster, 0)?;
+            }
+        }
+        node.size = size as u32;
+        self.store(&node)
+    }
+
+    /// Create a directory
+    pub fn mkdir(&mut self, path: &str) -> Result<(), Errno> {
+        if self.resolve(path).is_ok() {
+            return Err(Errno::EEXIST);
+        }
+        let (parent, name) = self.parent(path)?;
+
+        let cluster = self.alloc_cluster(None)?;
+        // ".." of a directory in the root points at cluster 0
+        let up = if parent.ino == ROOT_INODE { 0 } else { parent.cluster };
+        let mut dots = [0u8; 2 * ENTRY_SIZE];
+        dots[..ENTRY_SIZE].copy_from_slice(&short_entry(b".          ", attr::DIRECTORY, 0, cluster));
+        dots[ENTRY_SIZE..].copy_from_slice(&short_entry(b"..         ", attr::DIRECTORY, 0, up));
+        let created = self
+            .write_bytes(self.cluster_offset(cluster), &dots)
+            .and_then(|_| self.create(&parent, name, attr::DIRECTORY, cluster));
+        if let Err(e) = created {
+            self.set_fat_entry(cluster, 0)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Remove a file
+    pub fn unlink(&mut self, path: &str) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path)?;
+        let entry = self.find(&parent, name)?;
+        if entry.node.is_dir() {
+            return Err(Errno::EISDIR);
+        }
+        self.remove(&entry)
+    }
+
+    /// Remove an empty directory
+    pub fn rmdir(&mut self, path: &str) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path)?;
+        let entry = self.find(&parent, name)?;
+        if !entry.node.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        if !self.entries(&entry.node)?.is_empty() {
+            return Err(Errno::ENOTEMPTY);
+        }
+        self.remove(&entry)
+    }
+
+    // ------------------------------------------------------------------
+    // Paths and directories
+    // ------------------------------------------------------------------
+
+    /// The root directory
+    fn root(&self) -> Node {
+        Node { ino: ROOT_INODE, attr: attr::DIRECTORY, cluster: self.root_cluster, size: 0 }
+    }
+
+    /// The node with inode number `ino`
+    fn node_at(&self, ino: u64) -> Result<Node, Errno> {
+        if ino == ROOT_INODE {
+            return Ok(self.root());
+        }
+        let mut raw = [0u8; ENTRY_SIZE];
+        self.read_bytes(ino, &mut raw)?;
+        if raw[0] == 0 || raw[0] == DELETED || raw[11] & 0x3F == attr::LONG_NAME {
+            return Err(Errno::ENOENT);
+        }
+        Ok(Node::from_entry(ino, &raw))
+    }
+
+    /// Walk a path from the root
+    fn resolve(&self, path: &str) -> Result<Node, Errno> {
+        let mut node = self.root();
+        for part in components(path)? {
+            node = self.find(&node, part)?.node;
+        }
+        Ok(node)
+    }
+
+    /// The directory holding the last component of `path`, and that name
+    ///
+    /// The root itself has no parent: `EBUSY`.
+    fn parent<'a>(&self, path: &'a str) -> Result<(Node, &'a str), Errno> {
+        let mut parts = components(path)?;
+        let name = parts.pop().ok_or(Errno::EBUSY)?;
+        let mut dir = self.root();
+        for part in parts {
+            dir = self.find(&dir, part)?.node;
+        }
+        if !dir.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        Ok((dir, name))
+    }
+
+    /// Find a name in a directory
+    fn find(&self, dir: &Node, name: &str) -> Result<Entry, Errno> {
+        self.entries(dir)?
+            .into_iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .ok_or(Errno::ENOENT)
+    }
+
+    /// Entries of a directory, without `.` and `..`
+    fn entries(&self, dir: &Node) -> Result<Vec<Entry>, Errno> {
+        if !dir.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        let mut entries = Vec::new();
+        let mut lfn: Vec<(u64, [u8; ENTRY_SIZE])> = Vec::new();
+        let mut buf = vec![0u8; self.cluster_size as usize];
+
+        'chain: for cluster in self.chain(dir.cluster)? {
+            let base = self.cluster_offset(cluster);
+            self.read_bytes(base, &mut buf)?;
+            for (i, raw) in buf.chunks_exact(ENTRY_SIZE).enumerate() {
+                let pos = base + (i * ENTRY_SIZE) as u64;
+                if raw[0] == 0 {
+                    break 'chain;
+                }
+                if raw[0] == DELETED {
+                    lfn.clear();
+                    continue;
+                }
+                if raw[11] & 0x3F == attr::LONG_NAME {
+                    let mut copy = [0u8; ENTRY_SIZE];
+                    copy.copy_from_slice(raw);
+                    lfn.push((pos, copy));
+                    continue;
+                }
+
+                let slots = core::mem::take(&mut lfn);
+                let mut short = [0u8; 11];
+                short.copy_from_slice(&raw[..11]);
+                if raw[11] & attr::VOLUME_ID != 0 || short[0] == b'.' {
+                    continue;
+                }
+                let raws: Vec<&[u8; ENTRY_SIZE]> = slots.iter().map(|(_, r)| r).collect();
+                let name = long_name(&raws, checksum(&short)).unwrap_or_else(|| short_display(&short, raw[12]));
+                entries.push(Entry {
+                    name,
+                    short,
+                    node: Node::from_entry(pos, raw),
+                    lfn_slots: slots.into_iter().map(|(pos, _)| pos).collect(),
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Add a name to a directory
+    ///
+    /// # Returns
+    ///
+    /// The new node, `EEXIST` if the name is taken, `EINVAL` /
+    /// `ENAMETOOLONG` for a name FAT cannot store, or `ENOSPC`
+    fn create(&mut self, dir: &Node, name: &str, attr: u8, cluster: u32) -> Result<Node, Errno> {
+        let entries = self.entries(dir)?;
+        if entries.iter().any(|e| e.name.eq_ignore_ascii_case(name)) {
+            return Err(Errno::EEXIST);
+        }
+
+        let units: Vec<u16> = name.encode_utf16().collect();
+        let (short, case, lfn_count) = match short_name(name) {
+            Some((short, case)) if !entries.iter().any(|e| e.short == short) => (short, case, 0),
+            _ => {
+                check_long_name(name, units.len())?;
+                (alias(name, &entries)?, 0, units.len().div_ceil(LFN_CHARS))
+            }
+        };
+
+        let slots = self.free_slots(dir, lfn_count + 1)?;
+        let sum = checksum(&short);
+        for (i, &pos) in slots[..lfn_count].iter().enumerate() {
+            // Stored last part first
+            let order = (lfn_count - i) as u8 | if i == 0 { LFN_LAST } else { 0 };
+            self.write_bytes(pos, &lfn_entry(order, sum, &units))?;
+        }
+        let ino = slots[lfn_count];
+        self.write_bytes(ino, &short_entry(&short, attr, case, cluster))?;
+        Ok(Node { ino, attr, cluster, size: 0 })
+    }
+
+    /// Offsets of `count` consecutive free entries in a directory,
+    /// growing it by a cluster at a time if needed
+    fn free_slots(&mut self, dir: &Node, count: usize) -> Result<Vec<u64>, Errno> {
+        let mut chain = self.chain(dir.cluster)?;
+        let mut run = Vec::new();
+        let mut buf = vec![0u8; self.cluster_size as usize];
+
+        for &cluster in &chain {
+            let base = self.cluster_offset(cluster);
+            self.read_bytes(base, &mut buf)?;
+            for (i, raw) in buf.chunks_exact(ENTRY_SIZE).enumerate() {
+                if raw[0] == 0 || raw[0] == DELETED {
+                    run.push(base + (i * ENTRY_SIZE) as u64);
+                    if run.len() == count {
+                        return Ok(run);
+                    }
+                } else {
+                    run.clear();
+                }
+            }
+        }
+
+        // New clusters are zeroed: every entry in them is free
+        while run.len() < count {
+            let cluster = self.alloc_cluster(chain.last().copied())?;
+            chain.push(cluster);
+            let base = self.cluster_offset(cluster);
+            run.extend((0..self.cluster_size / ENTRY_SIZE as u64).map(|i| base + i * ENTRY_SIZE as u64));
+        }
+        run.truncate(count);
+        Ok(run)
+    }
+
+    /// Delete a name and free its clusters
+    fn remove(&mut self, entry: &Entry) -> Result<(), Errno> {
+        for &pos in entry.lfn_slots.iter().chain(core::iter::once(&entry.node.ino)) {
+            self.write_bytes(pos, &[DELETED])?;
+        }
+        for cluster in self.chain(entry.node.cluster)? {
+            self.set_fat_entry(cluster, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Write a node's first cluster and size back to its entry
+    fn store(&self, node: &Node) -> Result<(), Errno> {
+        if node.ino == ROOT_INODE {
+            return Ok(());
+        }
+        let mut raw = [0u8; ENTRY_SIZE];
+        self.read_bytes(node.ino, &mut raw)?;
+        raw[20..22].copy_from_slice(&((node.cluster >> 16) as u16).to_le_bytes());
+        raw[26..28].copy_from_slice(&(node.cluster as u16).to_le_bytes());
+        raw[28..32].copy_from_slice(&node.size.to_le_bytes());
+        self.write_bytes(node.ino, &raw)
+    }
+
+    // ------------------------------------------------------------------
+    // Clusters
+    // ------------------------------------------------------------------
+
+    /// Offset of a cluster on the device
+    fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - 2) as u64 * self.cluster_size
+    }
+
+    /// Clusters of a file or directory, in order
+    ///
+    /// Fails with `EIO` on a chain that leaves the volume or loops.
+    fn chain(&self, first: u32) -> Result<Vec<u32>, Errno> {
+        let mut chain = Vec::new();
+        if first == 0 {
+            return Ok(chain);
+        }
+        let mut cluster = first;
+        loop {
+            if !(2..=self.max_cluster).contains(&cluster) || chain.len() >= self.max_cluster as usize {
+                return Err(Errno::EIO);
+            }
+            chain.push(cluster);
+            cluster = self.fat_entry(cluster)?;
+            if cluster >= END_OF_CHAIN {
+                return Ok(chain);
+            }
+        }
+    }
+
+    /// Run `f` on each contiguous piece of the byte range `offset..offset
+    /// + len` of a file stored in `chain`
+    ///
+    /// `f` gets the piece's device offset and its range within the
+    /// caller's buffer.
+    fn for_each_piece(
+        &self,
+        chain: &[u32],
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(&Self, u64, core::ops::Range<usize>) -> Result<(), Errno>,
+    ) -> Result<(), Errno> {
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let within = pos % self.cluster_size;
+            let n = core::cmp::min((self.cluster_size - within) as usize, len - done);
+            let cluster = *chain.get((pos / self.cluster_size) as usize).ok_or(Errno::EIO)?;
+            f(self, self.cluster_offset(cluster) + within, done..done + n)?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    /// Make a file's clusters cover `end` bytes
+    ///
+    /// The bytes from its current size up to `end` read as zeros
+    /// afterwards. The node's first cluster is stored as soon as it is
+    /// allocated, so a failure part way leaves nothing unreachable.
+    fn grow(&mut self, node: &mut Node, end: u64) -> Result<Vec<u32>, Errno> {
+        let mut chain = self.chain(node.cluster)?;
+        let size = node.size as u64;
+
+        // Old data may remain past the end of file in its last cluster
+        let allocated = chain.len() as u64 * self.cluster_size;
+        if end > size && allocated > size {
+            let zeros = vec![0u8; (end.min(allocated) - size) as usize];
+            self.for_each_piece(&chain, size, zeros.len(), |fs, at, range| fs.write_bytes(at, &zeros[range]))?;
+        }
+
+        let needed = end.div_ceil(self.cluster_size) as usize;
+        while chain.len() < needed {
+            let cluster = self.alloc_cluster(chain.last().copied())?;
+            if chain.is_empty() {
+                node.cluster = cluster;
+                self.store(node)?;
+            }
+            chain.push(cluster);
+        }
+        Ok(chain)
+    }
+
+    /// FAT entry of a cluster
+    fn fat_entry(&self, cluster: u32) -> Result<u32, Errno> {
+        let mut raw = [0u8; 4];
+        self.read_bytes(self.fat_start + cluster as u64 * 4, &mut raw)?;
+        Ok(u32::from_le_bytes(raw) & CLUSTER_MASK)
+    }
+
+    /// Set the FAT entry of a cluster in every copy of the FAT
+    ///
+    /// The top four bits are reserved and kept.
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), Errno> {
+        let offset = self.fat_start + cluster as u64 * 4;
+        let mut raw = [0u8; 4];
+        self.read_bytes(offset, &mut raw)?;
+        let entry = (u32::from_le_bytes(raw) & !CLUSTER_MASK) | (value & CLUSTER_MASK);
+        for i in 0..self.num_fats as u64 {
+            self.write_bytes(offset + i * self.fat_bytes, &entry.to_le_bytes())?;
+        }
+        if value == 0 {
+            self.next_free = self.next_free.min(cluster);
+        }
+        self.invalidate_free_count()
+    }
+
+    /// Allocate a zeroed cluster and append it to the chain ending at
+    /// `prev`
+    ///
+    /// # Returns
+    ///
+    /// The cluster, or `ENOSPC` if the volume is full
+    fn alloc_cluster(&mut self, prev: Option<u32>) -> Result<u32, Errno> {
+        let start = if (2..=self.max_cluster).contains(&self.next_free) { self.next_free } else { 2 };
+        let found = match self.find_free(start, self.max_cluster)? {
+            Some(c) => Some(c),
+            None => self.find_free(2, start - 1)?,
+        };
+        let cluster = found.ok_or(Errno::ENOSPC)?;
+
+        self.write_bytes(self.cluster_offset(cluster), &vec![0u8; self.cluster_size as usize])?;
+        self.set_fat_entry(cluster, CHAIN_END)?;
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, cluster)?;
+        }
+        self.next_free = cluster + 1;
+        Ok(cluster)
+    }
+
+    /// First free cluster in `from..=to`, reading the FAT a sector at a
+    /// time
+    fn find_free(&self, from: u32, to: u32) -> Result<Option<u32>, Errno> {
+        const PER_READ: u32 = 128;
+        let mut buf = [0u8; PER_READ as usize * 4];
+        let mut first = from;
+        while first <= to {
+            let n = core::cmp::min(PER_READ, to - first + 1);
+            let raw = &mut buf[..n as usize * 4];
+            self.read_bytes(self.fat_start + first as u64 * 4, raw)?;
+            if let Some(i) = raw.chunks_exact(4).position(|e| le32(e, 0) & CLUSTER_MASK == 0) {
+                return Ok(Some(first + i as u32));
+            }
+            first += n;
+        }
+        Ok(None)
+    }
+
+    /// Mark the FSInfo free cluster count unknown, once
+    fn invalidate_free_count(&mut self) -> Result<(), Errno> {
+        if let (Some(fsinfo), false) = (self.fsinfo, self.fsinfo_stale) {
+            self.fsinfo_stale = true;
+            self.write_bytes(fsinfo + FSINFO_FREE_COUNT, &u32::MAX.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    // ------------------------------------------------------------------
+    // Device access
+    // ------------------------------------------------------------------
+
+    fn read_bytes(&self, offset: u64, buf: &mut [u8]) -> Result<(), Errno> {
+        match block::read_at(&*self.device, offset, buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(Errno::EIO),
+        }
+    }
+
+    fn write_bytes(&self, offset: u64, buf: &[u8]) -> Result<(), Errno> {
+        match block::write_at(&*self.device, offset, buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(Errno::EIO),
+        }
+    }
+}
+
+impl Filesystem for FatVolume {
+    fn open(&mut self, path: &str, flags: u32) -> Result<u64, Errno> {
+        FatVolume::open(self, path, flags)
+    }
+
+    fn lookup(&self, path: &str) -> Result<u64, Errno> {
+        FatVolume::lookup(self, path)
+    }
+
+    fn size(&self, ino: u64) -> Result<u64, Errno> {
+        FatVolume::size(self, ino)
+    }
+
+    fn stat(&self, ino: u64) -> Result<Stat, Errno> {
+        FatVolume::stat(self, ino)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno> {
+        FatVolume::read_dir(self, path)
+    }
+
+    fn read(&mut self, ino: u64, offset: u64, buf: &mut [u8]) -> Result<usize, Errno> {
+        FatVolume::read(self, ino, offset, buf)
+    }
+
+    fn write(&mut self, ino: u64, offset: u64, src: &[u8]) -> Result<usize, Errno> {
+        FatVolume::write(self, ino, offset, src)
+    }
+
+    fn truncate(&mut self, ino: u64, size: u64) -> Result<(), Errno> {
+        FatVolume::truncate(self, ino, size)
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<(), Errno> {
+        FatVolume::mkdir(self, path)
+    }
+
+    fn unlink(&mut self, path: &str) -> Result<(), Errno> {
+        FatVolume::unlink(self, path)
+    }
+
+    fn rmdir(&mut self, path: &str) -> Result<(), Errno> {
+        FatVolume::rmdir(self, path)
+    }
+
+    fn sync(&mut self) -> Result<(), Errno> {
+        self.device.flush()
+    }
+}
+
+/// Split a path below the volume root into its components
+///
+/// Empty components and `.` are skipped; `..` is rejected.
+fn components(path: &str) -> Result<Vec<&str>, Errno> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Err(Errno::EINVAL),
+            _ if part.len() > NAME_MAX => return Err(Errno::ENAMETOOLONG),
+            _ => parts.push(part),
+        }
+    }
+    Ok(parts)
+}
+
+fn le16(raw: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([raw[at], raw[at + 1]])
+}
+
+fn le32(raw: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([raw[at], raw[at + 1], raw[at + 2], raw[at + 3]])
+}
+
+/// A short directory entry, dated at the FAT epoch
+fn short_entry(short: &[u8; 11], attr: u8, case: u8, cluster: u32) -> [u8; ENTRY_SIZE] {
+    let mut raw = [0u8; ENTRY_SIZE];
+    raw[..11].copy_from_slice(short);
+    raw[11] = attr;
+    raw[12] = case;
+    for at in [16, 18, 24] {
+        raw[at..at + 2].copy_from_slice(&EPOCH_DATE.to_le_bytes());
+    }
+    raw[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    raw[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    raw
+}
+
+/// Checksum of a short name, stored in its long-name entries
+fn checksum(short: &[u8; 11]) -> u8 {
+    short.iter().fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+/// Characters allowed in a short name besides letters and digits
+fn is_short_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'()-@^_`{}~".contains(&b)
+}
+
+/// Short name and case flags of a name that fits 8.3 as it is
+///
+/// Each part must be in one case; `None` if the name needs a long entry.
+fn short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.split_once('.') {
+        Some((base, ext)) if !ext.is_empty() => (base, ext),
+        Some(_) => return None,
+        None => (name, ""),
+    };
+    if base.is_empty() || base.len() > 8 || ext.len() > 3 {
+        return None;
+    }
+
+    let mut short = [b' '; 11];
+    let mut case = 0;
+    for (part, field, flag) in [(base, 0..8, LOWER_BASE), (ext, 8..11, LOWER_EXT)] {
+        if !part.bytes().all(is_short_char) {
+            return None;
+        }
+        let lower = part.bytes().any(|b| b.is_ascii_lowercase());
+        if lower && part.bytes().any(|b| b.is_ascii_uppercase()) {
+            return None;
+        }
+        if lower {
+            case |= flag;
+        }
+        for (dst, b) in short[field].iter_mut().zip(part.bytes()) {
+            *dst = b.to_ascii_uppercase();
+        }
+    }
+    Some((short, case))
+}
+
+/// Check that a name can be stored as a long name of `units` UTF-16 units
+fn check_long_name(name: &str, units: usize) -> Result<(), Errno> {
+    if units > NAME_MAX {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    if name.chars().any(|c| c.is_control() || "\"*/:<>?\\|".contains(c))
+        || name.trim_end_matches(['.', ' ']).is_empty()
+    {
+        return Err(Errno::EINVAL);
+    }
+    Ok(())
+}
+
+/// Short alias (`BASE~N.EXT`) for a long name, unused in `entries`
+fn alias(name: &str, entries: &[Entry]) -> Result<[u8; 11], Errno> {
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) if !base.trim_start_matches('.').is_empty() => (base, ext),
+        _ => (name, ""),
+    };
+    let clean = |part: &str, max: usize| -> Vec<u8> {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| c.to_ascii_uppercase())
+            .map(|c| if c.is_ascii() && is_short_char(c as u8) { c as u8 } else { b'_' })
+            .take(max)
+            .collect()
+    };
+    let base = clean(base, 6);
+    let ext = clean(ext, 3);
+
+    for n in 1..1_000_000u32 {
+        let tail = format!("~{}", n);
+        let keep = core::cmp::min(base.len(), 8 - tail.len());
+        let mut short = [b' '; 11];
+        short[..keep].copy_from_slice(&base[..keep]);
+        short[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+        short[8..8 + ext.len()].copy_from_slice(&ext);
+        if !entries.iter().any(|e| e.short == short) {
+            return Ok(short);
+        }
+    }
+    Err(Errno::EEXIST)
+}
+
+/// Display form of a short name, applying the case flags
+fn short_display(short: &[u8; 11], case: u8) -> String {
+    let part = |bytes: &[u8], lower: bool| -> String {
+        let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        bytes[..len]
+            .iter()
+            .map(|&b| if lower { b.to_ascii_lowercase() } else { b } as char)
+            .collect()
+    };
+    let mut bytes = *short;
+    // 0x05 stands for a leading 0xE5, which marks deleted entries
+    if bytes[0] == 0x05 {
+        bytes[0] = DELETED;
+    }
+    let mut name = part(&bytes[..8], case & LOWER_BASE != 0);
+    let ext = part(&bytes[8..], case & LOWER_EXT != 0);
+    if !ext.is_empty() {
+        name.push('.');
+        name.push_str(&ext);
+    }
+    name
+}
+
+/// Long name from the long-name entries before a short entry, in disk
+/// order, if they are complete and match its checksum
+fn long_name(slots: &[&[u8; ENTRY_SIZE]], sum: u8) -> Option<String> {
+    let n = slots.len();
+    if n == 0 || n * LFN_CHARS > NAME_MAX + LFN_CHARS {
+        return None;
+    }
+    for (i, raw) in slots.iter().enumerate() {
+        let order = (n - i) as u8 | if i == 0 { LFN_LAST } else { 0 };
+        if raw[0] != order || raw[13] != sum {
+            return None;
+        }
+    }
+
+    let units: Vec<u16> = slots
+        .iter()
+        .rev()
+        .flat_map(|raw| LFN_OFFSETS.iter().map(move |&at| le16(&raw[..], at)))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    Some(char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect())
+}
+
+/// Long-name entry number `order` (1-based, possibly with
+/// [`LFN_LAST`]) of the name `units`
+///
+/// The name ends with a NUL and is padded with 0xFFFF.
+fn lfn_entry(order: u8, sum: u8, units: &[u16]) -> [u8; ENTRY_SIZE] {
+    let mut raw = [0u8; ENTRY_SIZE];
+    raw[0] = order;
+    raw[11] = attr::LONG_NAME;
+    raw[13] = sum;
+    let first = ((order & !LFN_LAST) as usize - 1) * LFN_CHARS;
+    for (i, &at) in LFN_OFFSETS.iter().enumerate() {
+        let unit = match (first + i).cmp(&units.len()) {
+            core::cmp::Ordering::Less => units[first + i],
+            core::cmp::Ordering::Equal => 0,
+            core::cmp::Ordering::Greater => 0xFFFF,
+        };
+        raw[at..at + 2].copy_from_slice(&unit.to_le_bytes());
+    }
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::block::ram::RamDisk;
+    use crate::syscall::fd::flags::{O_CREAT, O_EXCL, O_RDONLY, O_RDWR, O_TRUNC};
+
+    /// Format a FAT32 volume of `clusters` 512-byte clusters on a RAM disk
+    fn mkfs(clusters: u32) -> FatVolume {
+        let fat_sectors = (clusters + 2).div_ceil(128);
+        let reserved = 32;
+        let total = reserved + 2 * fat_sectors + clusters;
+        let disk = RamDisk::new("fat", total as usize * 512).unwrap();
+
+        let mut bs = [0u8; 512];
+        bs[11..13].copy_from_slice(&512u16.to_le_bytes());
+        bs[13] = 1;
+        bs[14..16].copy_from_slice(&(reserved as u16).to_le_bytes());
+        bs[16] = 2;
+        bs[32..36].copy_from_slice(&total.to_le_bytes());
+        bs[36..40].copy_from_slice(&fat_sectors.to_le_bytes());
+        bs[44..48].copy_from_slice(&2u32.to_le_bytes());
+        bs[510] = 0x55;
+        bs[511] = 0xAA;
+        block::write_at(&disk, 0, &bs).unwrap();
+        // Media descriptor, reserved entry, root directory
+        for fat in 0..2 {
+            let start = (reserved + fat * fat_sectors) as u64 * 512;
+            for (i, entry) in [0x0FFF_FFF8u32, CHAIN_END, CHAIN_END].iter().enumerate() {
+                block::write_at(&disk, start + i as u64 * 4, &entry.to_le_bytes()).unwrap();
+            }
+        }
+        FatVolume::new(Arc::new(disk)).unwrap()
+    }
+
+    #[test]
+    fn test_not_fat32() {
+        let disk = RamDisk::new("fat", 64 * 1024).unwrap();
+        assert_eq!(FatVolume::new(Arc::new(disk)).err(), Some(Errno::EINVAL));
+    }
+
+    #[test]
+    fn test_create_read_write() {
+        let mut fs = mkfs(64);
+        assert_eq!(fs.open("log.txt", O_RDONLY).err(), Some(Errno::ENOENT));
+
+        let ino = fs.open("log.txt", O_RDWR | O_CREAT).unwrap();
+        let data: Vec<u8> = (0..1500u32).map(|i| i as u8).collect();
+        assert_eq!(fs.write(ino, 0, &data), Ok(1500));
+        assert_eq!(fs.size(ino), Ok(1500));
+
+        let mut out = vec![0u8; 2000];
+        assert_eq!(fs.read(ino, 0, &mut out), Ok(1500));
+        assert_eq!(&out[..1500], &data[..]);
+        assert_eq!(fs.read(ino, 1500, &mut out), Ok(0));
+
+        // A write past the end leaves a gap of zeros
+        assert_eq!(fs.write(ino, 2000, b"end"), Ok(3));
+        assert_eq!(fs.read(ino, 1500, &mut out[..503]), Ok(503));
+        assert!(out[..500].iter().all(|&b| b == 0));
+        assert_eq!(&out[500..503], b"end");
+
+        // Case is kept and ignored in lookups
+        assert_eq!(fs.lookup("LOG.TXT"), Ok(ino));
+        let list = fs.read_dir("").unwrap();
+        assert_eq!(list, vec![DirEntry::file("log.txt", 2003)]);
+        assert_eq!(fs.open("Log.txt", O_RDWR | O_CREAT | O_EXCL).err(), Some(Errno::EEXIST));
+    }
+
+    #[test]
+    fn test_truncate_frees_clusters() {
+        let mut fs = mkfs(8);
+        let ino = fs.open("big", O_RDWR | O_CREAT).unwrap();
+        // The root directory holds one cluster: the rest fit one file
+        assert_eq!(fs.write(ino, 0, &[1u8; 7 * 512]), Ok(7 * 512));
+        assert_eq!(fs.write(ino, 7 * 512, b"x"), Err(Errno::ENOSPC));
+
+        assert_eq!(fs.truncate(ino, 100), Ok(()));
+        assert_eq!(fs.size(ino), Ok(100));
+        assert_eq!(fs.write(ino, 100, &[2u8; 6 * 512]), Ok(6 * 512));
+
+        // Growing reads as zeros, not the old contents
+        assert_eq!(fs.truncate(ino, 0), Ok(()));
+        assert_eq!(fs.truncate(ino, 600), Ok(()));
+        let mut out = [0xFFu8; 600];
+        assert_eq!(fs.read(ino, 0, &mut out), Ok(600));
+        assert!(out.iter().all(|&b| b == 0));
+
+        let reopened = fs.open("big", O_RDWR | O_TRUNC).unwrap();
+        assert_eq!(fs.size(reopened), Ok(0));
+    }
+
+    #[test]
+    fn test_long_names() {
+        let mut fs = mkfs(64);
+        fs.mkdir("EFI").unwrap();
+        fs.mkdir("EFI/Boot").unwrap();
+        let a = fs.open("EFI/Boot/Kernel Log.txt", O_RDWR | O_CREAT).unwrap();
+        let b = fs.open("EFI/Boot/kernel log 2.txt", O_RDWR | O_CREAT).unwrap();
+        assert_ne!(a, b);
+
+        let names: Vec<String> = fs.read_dir("efi/boot").unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["Kernel Log.txt", "kernel log 2.txt"]);
+        let aliases: Vec<[u8; 11]> = fs.entries(&fs.resolve("EFI/Boot").unwrap()).unwrap().iter().map(|e| e.short).collect();
+        assert_eq!(aliases, [*b"KERNEL~1TXT", *b"KERNEL~2TXT"]);
+
+        assert_eq!(fs.open("EFI/Boot/a:b", O_RDWR | O_CREAT).err(), Some(Errno::EINVAL));
+        assert_eq!(fs.open("EFI/Boot/Kernel Log.txt/x", O_RDONLY).err(), Some(Errno::ENOTDIR));
+    }
+
+    #[test]
+    fn test_directory_grows() {
+        let mut fs = mkfs(64);
+        // 16 entries per cluster: this needs several
+        for i in 0..40 {
+            fs.open(&format!("file{}", i), O_RDWR | O_CREAT).unwrap();
+        }
+        assert_eq!(fs.read_dir("").unwrap().len(), 40);
+        assert!(fs.lookup("FILE39").is_ok());
+    }
+
+    #[test]
+    fn test_unlink_rmdir() {
+        let mut fs = mkfs(16);
+        fs.mkdir("dir").unwrap();
+        let ino = fs.open("dir/A long file name", O_RDWR | O_CREAT).unwrap();
+        fs.write(ino, 0, &[7u8; 4 * 512]).unwrap();
+
+        assert_eq!(fs.rmdir("dir"), Err(Errno::ENOTEMPTY));
+        assert_eq!(fs.unlink("dir"), Err(Errno::EISDIR));
+        assert_eq!(fs.unlink("dir/a long file name"), Ok(()));
+        assert_eq!(fs.size(ino), Err(Errno::ENOENT));
+        assert_eq!(fs.read_dir("dir"), Ok(Vec::new()));
+        assert_eq!(fs.rmdir("dir"), Ok(()));
+        assert_eq!(fs.read_dir(""), Ok(Vec::new()));
+        assert_eq!(fs.rmdir(""), Err(Errno::EBUSY));
+
+        // Everything was freed: a file can fill the volume again
+        let ino = fs.open("fill", O_RDWR | O_CREAT).unwrap();
+        assert_eq!(fs.write(ino, 0, &[1u8; 15 * 512]), Ok(15 * 512));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(short_name("log.txt"), Some((*b"LOG     TXT", LOWER_BASE | LOWER_EXT)));
+        assert_eq!(short_name("EFI"), Some((*b"EFI        ", 0)));
+        assert_eq!(short_name("Log.txt"), None);
+        assert_eq!(short_name("a.b.c"), None);
+        assert_eq!(short_name("toolongname"), None);
+        assert_eq!(short_display(b"LOG     TXT", LOWER_EXT), "LOG.txt");
+        assert_eq!(checksum(b"KERNEL~1TXT"), 0xF4);
+
+        let units: Vec<u16> = "Kernel Log.txt".encode_utf16().collect();
+        let second = lfn_entry(2 | LFN_LAST, 0x12, &units);
+        let first = lfn_entry(1, 0x12, &units);
+        assert_eq!(long_name(&[&second, &first], 0x12).as_deref(), Some("Kernel Log.txt"));
+        assert_eq!(long_name(&[&second, &first], 0x13), None);
+        assert_eq!(long_name(&[&first], 0x12), None);
+    }
+}
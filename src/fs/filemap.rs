@@ -0,0 +1,144 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! File-Backed VMOs
+//!
+//! Each ramdisk file gets one read-only [`Vmo`], created the first time
+//! it is asked for and shared by every process that maps the file. The
+//! ramdisk never changes, so the VMO is never invalidated or freed.
+//!
+//! # Pages
+//!
+//! build.rs starts every file's data on a page boundary, and the kernel
+//! image (which embeds the ramdisk) is identity mapped. Each whole page of
+//! a file is therefore a page of ramdisk physical memory, and the VMO
+//! refers to it directly instead of copying it.
+//!
+//! The last page of a file is usually partial; the ramdisk continues with
+//! the next file there. That page is copied into a zero-filled page so
+//! bytes past the end of the file read as zero. Files whose data is not
+//! page-aligned are copied page by page.
+//!
+//! All page entries are read-only: mapping them writable and writing them
+//! through the VMO both fail.
+//!
+//! The VMO is named after the file (truncated to the object name limit).
+//! Handles to it lack `SET_PROPERTY`, since every process shares it.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use crate::arch::amd64::mm::{PAddr, RxStatus};
+use crate::object::vmo::{PageMapEntry, Vmo, VmoFlags};
+use crate::sync::AdaptiveMutex;
+use super::ramdisk;
+
+/// Page size
+const PAGE_SIZE: usize = 4096;
+
+/// File VMOs, by inode (index in the ramdisk file table)
+static FILE_VMOS: AdaptiveMutex<BTreeMap<u32, Arc<Vmo>>> =
+    AdaptiveMutex::named(BTreeMap::new(), &crate::sync::lockstat::FILE_VMOS);
+
+/// Where one page of a file VMO comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageSource {
+    /// The ramdisk page at this physical address
+    Ramdisk(PAddr),
+
+    /// A new page holding this many file bytes, zero-filled after them
+    Copy(usize),
+}
+
+/// Source of the page at `offset` of a file stored at `data_addr`
+fn page_source(data_addr: usize, size: usize, offset: usize) -> PageSource {
+    let len = core::cmp::min(PAGE_SIZE, size - offset);
+    if len == PAGE_SIZE && (data_addr + offset).is_multiple_of(PAGE_SIZE) {
+        PageSource::Ramdisk((data_addr + offset) as PAddr)
+    } else {
+        PageSource::Copy(len)
+    }
+}
+
+/// The shared VMO for a ramdisk file
+///
+/// # Arguments
+///
+/// * `inode` - File index, as stored in `FdKind::File`
+///
+/// # Returns
+///
+/// - `ERR_NOT_FOUND` if there is no ramdisk or no such file
+/// - `ERR_INVALID_ARGS` for an empty file (there is nothing to map)
+/// - `ERR_NO_MEMORY` if copying the last page fails
+pub fn file_vmo(inode: u32) -> Result<Arc<Vmo>, RxStatus> {
+    // Built under the lock so two first users cannot both copy pages
+    let mut cache = FILE_VMOS.lock();
+    if let Some(vmo) = cache.get(&inode) {
+        return Ok(vmo.clone());
+    }
+
+    let disk = ramdisk::get_ramdisk().map_err(|_| RxStatus::ERR_NOT_FOUND)?;
+    let file = disk.file_at(inode).ok_or(RxStatus::ERR_NOT_FOUND)?;
+    let vmo = Arc::new(build(disk.file_data(&file))?);
+    vmo.base().set_name(disk.file_name(&file));
+    cache.insert(inode, vmo.clone());
+    Ok(vmo)
+}
+
+/// Build the VMO for a file's contents
+fn build(data: &'static [u8]) -> Result<Vmo, RxStatus> {
+    use crate::mm::pmm;
+
+    let vmo = Vmo::create(data.len(), VmoFlags::empty).map_err(|_| RxStatus::ERR_INVALID_ARGS)?;
+    let data_addr = data.as_ptr() as usize;
+    let mut pages = vmo.pages.lock();
+
+    for offset in (0..data.len()).step_by(PAGE_SIZE) {
+        let paddr = match page_source(data_addr, data.len(), offset) {
+            PageSource::Ramdisk(paddr) => paddr,
+            PageSource::Copy(len) => {
+                let paddr = pmm::pmm_alloc_user_page().map_err(|_| RxStatus::ERR_NO_MEMORY)?;
+                let dst = pmm::paddr_to_vaddr_user_zone(paddr) as *mut u8;
+                unsafe {
+                    core::ptr::write_bytes(dst, 0, PAGE_SIZE);
+                    core::ptr::copy_nonoverlapping(data.as_ptr().add(offset), dst, len);
+                }
+                paddr
+            }
+        };
+        pages.insert(offset, PageMapEntry::new(paddr, false));
+    }
+
+    drop(pages);
+    Ok(vmo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligned_file_references_whole_pages() {
+        let base = 0x40_0000;
+        let size = 2 * PAGE_SIZE + 100;
+
+        assert_eq!(page_source(base, size, 0), PageSource::Ramdisk(base as PAddr));
+        assert_eq!(
+            page_source(base, size, PAGE_SIZE),
+            PageSource::Ramdisk((base + PAGE_SIZE) as PAddr)
+        );
+        assert_eq!(page_source(base, size, 2 * PAGE_SIZE), PageSource::Copy(100));
+    }
+
+    #[test]
+    fn test_unaligned_file_is_copied() {
+        let base = 0x40_0010;
+        let size = 2 * PAGE_SIZE;
+
+        assert_eq!(page_source(base, size, 0), PageSource::Copy(PAGE_SIZE));
+        assert_eq!(page_source(base, size, PAGE_SIZE), PageSource::Copy(PAGE_SIZE));
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Ramdisk Manifest Verification
+//!
+//! build.rs packs the files listed in `ramdisk.toml` and adds a generated
+//! [`MANIFEST_NAME`] file describing them. At boot, [`verify_manifest`]
+//! checks that every listed file is present with the expected size and
+//! hash.
+//!
+//! # Format
+//!
+//! One line per packed file:
+//!
+//! ```text
+//! <name> <size in bytes> <FNV-1a 64-bit hash, 16 hex digits>
+//! ```
+//!
+//! FNV-1a catches packing mistakes (wrong offsets, truncated files); it
+//! is not a security check. Tampering is detected by the ramdisk
+//! signature (see [`crate::fs::verify`]).
+
+use crate::fs::ramdisk::Ramdisk;
+use crate::kwarn;
+
+/// Name of the manifest inside the ramdisk
+pub const MANIFEST_NAME: &str = ".manifest";
+
+/// FNV-1a (64-bit)
+pub fn fnv1a64(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// A manifest line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestEntry<'a> {
+    /// File name inside the ramdisk
+    pub name: &'a str,
+    /// Size in bytes
+    pub size: usize,
+    /// FNV-1a hash of the contents
+    pub hash: u64,
+}
+
+/// Parse a manifest line
+pub fn parse_line(line: &str) -> Option<ManifestEntry<'_>> {
+    let mut fields = line.split_whitespace();
+    let name = fields.next()?;
+    let size = fields.next()?.parse().ok()?;
+    let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(ManifestEntry { name, size, hash })
+}
+
+/// Result of [`verify_manifest`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ManifestReport {
+    /// Entries that matched
+    pub ok: usize,
+    /// Entries whose file is missing from the ramdisk
+    pub missing: usize,
+    /// Entries whose size or hash differ
+    pub mismatched: usize,
+    /// Lines that could not be parsed
+    pub malformed: usize,
+}
+
+impl ManifestReport {
+    /// Check whether every entry matched
+    pub fn is_clean(&self) -> bool {
+        self.missing == 0 && self.mismatched == 0 && self.malformed == 0
+    }
+}
+
+/// Check the ramdisk contents against its manifest
+///
+/// # Returns
+///
+/// The report, or None if the ramdisk has no manifest
+pub fn verify_manifest(ramdisk: &Ramdisk) -> Option<ManifestReport> {
+    let file = ramdisk.find_file(MANIFEST_NAME)?;
+    let text = file_bytes(ramdisk, file.data_offset as usize, file.size as usize)?;
+    let text = core::str::from_utf8(text).ok()?;
+
+    let mut report = ManifestReport::default();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let entry = match parse_line(line) {
+            Some(e) => e,
+            None => {
+                report.malformed += 1;
+                continue;
+            }
+        };
+
+        let data = ramdisk
+            .find_file(entry.name)
+            .and_then(|f| file_bytes(ramdisk, f.data_offset as usize, f.size as usize));
+        match data {
+            None => {
+                report.missing += 1;
+                kwarn!("[RAMDISK] Manifest: missing {}", entry.name);
+            }
+            Some(data) if data.len() != entry.size || fnv1a64(data) != entry.hash => {
+                report.mismatched += 1;
+                kwarn!("[RAMDISK] Manifest: mismatch {}", entry.name);
+            }
+            Some(_) => report.ok += 1,
+        }
+    }
+    Some(report)
+}
+
+/// Bounds-checked view of a file's contents
+fn file_bytes(ramdisk: &Ramdisk, offset: usize, size: usize) -> Option<&'static [u8]> {
+    ramdisk.data.get(offset..offset.checked_add(size)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a64() {
+        assert_eq!(fnv1a64(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line("bin/init 4096 00000000000000ff"),
+            Some(ManifestEntry { name: "bin/init", size: 4096, hash: 0xff })
+        );
+        assert_eq!(parse_line("bin/init 4096"), None);
+        assert_eq!(parse_line("bin/init x 00"), None);
+        assert_eq!(parse_line("bin/init 1 00 extra"), None);
+    }
+}
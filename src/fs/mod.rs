@@ -9,22 +9,61 @@
 //! This module provides filesystem functionality for the Rustux kernel.
 //! It includes:
 //! - Ramdisk (embedded read-only filesystem)
-//! - VFS (Virtual File System) abstraction
+//! - File-backed VMOs for mapping ramdisk files
+//! - VFS (Virtual File System) abstraction, including directory listing
+//! - Ramdisk signature verification
+//! - Ramdisk manifest verification
+//! - devfs (device nodes under `/dev`)
+//! - procfs (synthesized files under `/proc`)
+//! - tmpfs (writable in-memory files under `/tmp`)
+//! - FAT32 volumes on block devices, and the mount table that places
+//!   them and the filesystems mounted with `MOUNT`
 //! - File operations for reading/writing files
 
 pub mod ramdisk;
+pub mod filemap;
 pub mod vfs;
+pub mod verify;
+pub mod manifest;
+pub mod devfs;
+pub mod procfs;
+pub mod tmpfs;
+pub mod fat;
+pub mod mount;
 
 // Re-export commonly used types
 pub use ramdisk::{
     Ramdisk, RamdiskFile, RamdiskSuperblock,
     RAMDISK, init_ramdisk, get_ramdisk, FileOffset,
     Errno,
-    rxstatus_to_errno, ENOSYS,
+    rxstatus_to_errno, errno_to_rxstatus, ENOSYS,
 };
 
 pub use vfs::{
     FileOps, RamdiskFileOps,
     Whence,
     open_ramdisk_file,
+    DirEntry, Dirent, read_dir,
+    Stat, stat,
+    PATH_MAX, resolve, follow,
 };
+
+pub use devfs::{DevNode, is_devfs_path};
+pub use procfs::{ProcNode, is_procfs_path};
+pub use tmpfs::{Tmpfs, is_tmpfs_path};
+pub use fat::FatVolume;
+
+pub use verify::{
+    RamdiskTrust,
+    verify_ramdisk, ramdisk_trust, spawn_allowed,
+};
+
+/// Write back everything the filesystems have buffered
+///
+/// Called on shutdown. The ramdisk is read-only, tmpfs lives only in
+/// memory, FAT volumes write through to their devices, and there is no
+/// page cache, so only the block devices are flushed. A filesystem that
+/// buffers writes must flush them here first.
+pub fn sync() {
+    crate::drivers::block::flush_all();
+}
@@ -0,0 +1,511 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Mount Table
+//!
+//! Filesystems are mounted on top-level directories (`/boot`, `/esp`);
+//! paths under a mount point are handed to the mounted [`Filesystem`]
+//! with the mount point stripped. devfs, procfs and tmpfs keep their
+//! fixed places (`/dev`, `/proc`, `/tmp`) and are not in the table, and
+//! everything else is the ramdisk.
+//!
+//! Privileged processes mount and unmount with the `MOUNT` and `UMOUNT`
+//! syscalls; volumes can also be mounted at boot from the command line:
+//!
+//! | Option | Effect |
+//! |--------|--------|
+//! | `fat.mount=<dev>:<dir>[,<dev>:<dir>...]` | Mount the FAT32 volume on block device `<dev>` at `<dir>` (e.g. `fat.mount=vda1:/boot`) |
+//!
+//! # Filesystem Types
+//!
+//! A mount names its type, looked up in [`FS_TYPES`]:
+//!
+//! | Type | Device | Mounts |
+//! |------|--------|--------|
+//! | `fat32` (or `vfat`) | Required | The FAT32 volume on the device ([`fat`](crate::fs::fat)) |
+//! | `tmpfs` | None | A new, empty tmpfs, separate from `/tmp` |
+//! | `procfs` | None | Another view of `/proc`: paths under it are rewritten to `/proc` ([`translate`]) |
+//!
+//! # Flags
+//!
+//! [`MS_RDONLY`] refuses every change (`EROFS`). [`MS_NOEXEC`] refuses
+//! to start programs from the mount; programs are only loaded from the
+//! ramdisk for now, so this matters once other sources are added.
+//!
+//! # Unmounting
+//!
+//! A mount is busy while a descriptor has a file on it open: descriptors
+//! are counted as they are opened, copied and closed ([`retain`] /
+//! [`release`]). The syscall also refuses while a process has its
+//! current directory under it. Mounts are identified by an ID that is
+//! never reused, so a stale ID fails with `ENODEV` instead of reaching
+//! a later mount.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::drivers::block::BlockDevice;
+use crate::fs::fat::FatVolume;
+use crate::fs::ramdisk::Errno;
+use crate::fs::tmpfs::Tmpfs;
+use crate::fs::vfs::{DirEntry, Stat};
+use crate::sync::{AdaptiveMutex, SpinMutex};
+use crate::{kinfo, kwarn};
+
+/// Most filesystems mounted at once
+pub const MAX_MOUNTS: usize = 8;
+
+/// Longest filesystem type name
+pub const FS_TYPE_MAX: usize = 16;
+
+/// Command line option: FAT32 volumes to mount
+pub const MOUNT_OPTION: &str = "fat.mount";
+
+/// Mount flag: refuse changes
+pub const MS_RDONLY: u32 = 1 << 0;
+
+/// Mount flag: refuse to start programs
+pub const MS_NOEXEC: u32 = 1 << 1;
+
+/// Every mount flag
+pub const MS_ALL: u32 = MS_RDONLY | MS_NOEXEC;
+
+/// A filesystem that can be mounted
+///
+/// Paths are relative to the filesystem's root (`""` or `/` is the
+/// root), and inode numbers are the filesystem's own. Errors are those
+/// of the same operations on tmpfs.
+pub trait Filesystem: Send {
+    /// Open a file with the `O_*` open flags; this counts as one open
+    /// descriptor until [`release`](Self::release)
+    fn open(&mut self, path: &str, flags: u32) -> Result<u64, Errno>;
+
+    /// Count another descriptor of an open inode
+    fn retain(&mut self, _ino: u64) {}
+
+    /// Drop a descriptor of an open inode
+    fn release(&mut self, _ino: u64) {}
+
+    /// Inode number of a path
+    fn lookup(&self, path: &str) -> Result<u64, Errno>;
+
+    /// Size of a file in bytes
+    fn size(&self, ino: u64) -> Result<u64, Errno>;
+
+    /// Metadata of a file or directory
+    fn stat(&self, ino: u64) -> Result<Stat, Errno>;
+
+    /// List a directory
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno>;
+
+    /// Read from a file at `offset`; short at the end of the file
+    fn read(&mut self, ino: u64, offset: u64, buf: &mut [u8]) -> Result<usize, Errno>;
+
+    /// Write to a file at `offset`, growing it if needed
+    fn write(&mut self, ino: u64, offset: u64, src: &[u8]) -> Result<usize, Errno>;
+
+    /// Set the size of a file
+    fn truncate(&mut self, ino: u64, size: u64) -> Result<(), Errno>;
+
+    /// Create a directory
+    fn mkdir(&mut self, path: &str) -> Result<(), Errno>;
+
+    /// Remove a file
+    fn unlink(&mut self, path: &str) -> Result<(), Errno>;
+
+    /// Remove an empty directory
+    fn rmdir(&mut self, path: &str) -> Result<(), Errno>;
+
+    /// Write back anything buffered (on unmount)
+    fn sync(&mut self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+/// What a mount point leads to
+#[derive(Clone)]
+enum Source {
+    /// A filesystem instance
+    Volume(Arc<AdaptiveMutex<Box<dyn Filesystem>>>),
+    /// Another name for a fixed directory (`/proc`)
+    Alias(&'static str),
+}
+
+/// A filesystem driver in the registry
+pub struct FsType {
+    /// Type name given to `MOUNT`
+    pub name: &'static str,
+    /// Whether a mount needs a block device (and refuses one otherwise)
+    pub needs_device: bool,
+    /// Create the mounted source
+    create: CreateFn,
+}
+
+/// Constructor of a mounted source, given the device if the type needs one
+type CreateFn = fn(Option<Arc<dyn BlockDevice>>) -> Result<Source, Errno>;
+
+/// Filesystem types that can be mounted
+pub static FS_TYPES: [FsType; 4] = [
+    FsType { name: "fat32", needs_device: true, create: create_fat },
+    FsType { name: "vfat", needs_device: true, create: create_fat },
+    FsType { name: "tmpfs", needs_device: false, create: create_tmpfs },
+    FsType { name: "procfs", needs_device: false, create: create_procfs },
+];
+
+fn volume(fs: impl Filesystem + 'static) -> Source {
+    Source::Volume(Arc::new(AdaptiveMutex::new(Box::new(fs))))
+}
+
+fn create_fat(device: Option<Arc<dyn BlockDevice>>) -> Result<Source, Errno> {
+    FatVolume::new(device.ok_or(Errno::EINVAL)?).map(volume)
+}
+
+fn create_tmpfs(_device: Option<Arc<dyn BlockDevice>>) -> Result<Source, Errno> {
+    Ok(volume(Tmpfs::new()))
+}
+
+fn create_procfs(_device: Option<Arc<dyn BlockDevice>>) -> Result<Source, Errno> {
+    Ok(Source::Alias("/proc"))
+}
+
+/// Look up a filesystem type by name
+pub fn fs_type(name: &str) -> Option<&'static FsType> {
+    FS_TYPES.iter().find(|t| t.name == name)
+}
+
+/// A mounted filesystem
+struct Mount {
+    /// Mount ID
+    id: u32,
+    /// Mount point (`/name`)
+    path: String,
+    /// Filesystem type name
+    fs_type: &'static str,
+    /// `MS_*` flags
+    flags: u32,
+    /// Open descriptors of files on it
+    opens: u32,
+    /// The filesystem
+    source: Source,
+}
+
+/// Mount table
+struct MountTable {
+    mounts: Vec<Mount>,
+    next_id: u32,
+}
+
+static MOUNTS: SpinMutex<MountTable> = SpinMutex::new(MountTable { mounts: Vec::new(), next_id: 1 });
+
+/// Mount a filesystem at `path`
+///
+/// # Arguments
+///
+/// * `path` - Mount point: a single directory below the root
+/// * `fs_type` - Type name from [`FS_TYPES`]
+/// * `device` - Block device, for types that need one
+/// * `flags` - `MS_*` flags
+///
+/// # Returns
+///
+/// The mount ID; `ENODEV` for an unknown type, `EINVAL` for bad flags, a
+/// missing or unexpected device, or a mount point that is not a single
+/// directory below the root; `EBUSY` if the mount point is taken (by
+/// another mount, devfs, procfs or tmpfs); `ENOSPC` if [`MAX_MOUNTS`]
+/// are mounted; or the driver's error
+pub fn mount(path: &str, fs_type: &str, device: Option<Arc<dyn BlockDevice>>, flags: u32) -> Result<u32, Errno> {
+    let fs_type = self::fs_type(fs_type).ok_or(Errno::ENODEV)?;
+    if flags & !MS_ALL != 0 || fs_type.needs_device != device.is_some() {
+        return Err(Errno::EINVAL);
+    }
+    let name = mount_name(path)?;
+    if matches!(name, "dev" | "proc" | "tmp") || is_mounted(name) {
+        return Err(Errno::EBUSY);
+    }
+
+    // Create outside the table lock: a driver reads its device
+    let source = (fs_type.create)(device)?;
+
+    let mut table = MOUNTS.lock();
+    if table.mounts.iter().any(|m| m.path[1..] == *name) {
+        return Err(Errno::EBUSY);
+    }
+    if table.mounts.len() >= MAX_MOUNTS {
+        return Err(Errno::ENOSPC);
+    }
+    let id = table.next_id;
+    table.next_id += 1;
+    table.mounts.push(Mount {
+        id,
+        path: alloc::format!("/{}", name),
+        fs_type: fs_type.name,
+        flags,
+        opens: 0,
+        source,
+    });
+    Ok(id)
+}
+
+/// Unmount the filesystem at `path`
+///
+/// The caller checks that no process has its current directory under
+/// it, holding the process table lock across this call.
+///
+/// # Returns
+///
+/// `EINVAL` if nothing is mounted there, `EBUSY` if a descriptor has a
+/// file on it open, or the error from syncing it (it stays mounted)
+pub fn umount(path: &str) -> Result<(), Errno> {
+    let name = mount_name(path)?;
+    let source = {
+        let table = MOUNTS.lock();
+        let mount = table.mounts.iter().find(|m| m.path[1..] == *name).ok_or(Errno::EINVAL)?;
+        if mount.opens > 0 {
+            return Err(Errno::EBUSY);
+        }
+        mount.source.clone()
+    };
+    if let Source::Volume(volume) = &source {
+        volume.lock().sync()?;
+    }
+
+    // Nothing can have opened it in between: opens go through the table
+    let mut table = MOUNTS.lock();
+    let index = table.mounts.iter().position(|m| m.path[1..] == *name).ok_or(Errno::EINVAL)?;
+    if table.mounts[index].opens > 0 {
+        return Err(Errno::EBUSY);
+    }
+    table.mounts.remove(index);
+    Ok(())
+}
+
+/// The directory name of a mount point (`/boot` -> `boot`)
+fn mount_name(path: &str) -> Result<&str, Errno> {
+    let name = path.strip_prefix('/').ok_or(Errno::EINVAL)?.trim_end_matches('/');
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(Errno::EINVAL);
+    }
+    Ok(name)
+}
+
+fn is_mounted(name: &str) -> bool {
+    MOUNTS.lock().mounts.iter().any(|m| m.path[1..] == *name)
+}
+
+/// The rest of `path` below `point`, if `path` is `point` or under it
+pub fn below<'a>(path: &'a str, point: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(point)?;
+    (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+}
+
+/// Find the filesystem holding a resolved path
+///
+/// # Returns
+///
+/// The mount ID and the rest of the path below the mount point (empty
+/// for the mount point itself), or `None` outside every mounted
+/// filesystem (procfs views are rewritten by [`translate`] instead)
+pub fn find(path: &str) -> Option<(u32, &str)> {
+    let table = MOUNTS.lock();
+    table.mounts.iter().find_map(|m| match m.source {
+        Source::Volume(_) => below(path, &m.path).map(|rest| (m.id, rest)),
+        Source::Alias(_) => None,
+    })
+}
+
+/// Rewrite a resolved path under a procfs view to the same path under
+/// `/proc`
+pub fn translate(path: String) -> String {
+    let table = MOUNTS.lock();
+    for m in table.mounts.iter() {
+        if let Source::Alias(target) = m.source {
+            if let Some(rest) = below(&path, &m.path) {
+                return alloc::format!("{}{}", target, rest);
+            }
+        }
+    }
+    path
+}
+
+/// `MS_*` flags of the mount holding a resolved path, if any
+pub fn flags_at(path: &str) -> Option<u32> {
+    let table = MOUNTS.lock();
+    table.mounts.iter().find(|m| below(path, &m.path).is_some()).map(|m| m.flags)
+}
+
+/// The volume of mount `id`, checking it is writable if `write` is set
+fn volume_of(id: u32, write: bool) -> Result<Arc<AdaptiveMutex<Box<dyn Filesystem>>>, Errno> {
+    let table = MOUNTS.lock();
+    let mount = table.mounts.iter().find(|m| m.id == id).ok_or(Errno::ENODEV)?;
+    if write && mount.flags & MS_RDONLY != 0 {
+        return Err(Errno::EROFS);
+    }
+    match &mount.source {
+        Source::Volume(volume) => Ok(volume.clone()),
+        Source::Alias(_) => Err(Errno::ENODEV),
+    }
+}
+
+/// Run `f` on mount `id`'s filesystem
+///
+/// The filesystem is locked for the duration; the mount table is not.
+/// Lock order as for tmpfs: the process table lock may be held, never
+/// taken inside.
+///
+/// # Returns
+///
+/// The result of `f`, or `ENODEV` if there is no such mount
+pub fn with<R>(id: u32, f: impl FnOnce(&mut dyn Filesystem) -> Result<R, Errno>) -> Result<R, Errno> {
+    let volume = volume_of(id, false)?;
+    let mut guard = volume.lock();
+    f(&mut **guard)
+}
+
+/// [`with`], for a change: fails with `EROFS` on a read-only mount
+pub fn with_writable<R>(id: u32, f: impl FnOnce(&mut dyn Filesystem) -> Result<R, Errno>) -> Result<R, Errno> {
+    let volume = volume_of(id, true)?;
+    let mut guard = volume.lock();
+    f(&mut **guard)
+}
+
+/// Open a file on mount `id`, counting the descriptor
+///
+/// Opening for writing, or with `O_CREAT` or `O_TRUNC`, fails with
+/// `EROFS` on a read-only mount. The caller wraps the inode in a
+/// descriptor, whose drop calls [`release`].
+pub fn open(id: u32, path: &str, flags: u32) -> Result<u64, Errno> {
+    use crate::syscall::fd::flags::{O_CREAT, O_RDONLY, O_TRUNC};
+
+    let write = flags & 3 != O_RDONLY || flags & (O_CREAT | O_TRUNC) != 0;
+    let volume = volume_of(id, write)?;
+    count_open(id, 1);
+    let opened = volume.lock().open(path, flags);
+    if opened.is_err() {
+        count_open(id, -1);
+    }
+    opened
+}
+
+/// Count another descriptor of an open inode (fd copied)
+pub fn retain(id: u32, ino: u64) {
+    if let Ok(volume) = volume_of(id, false) {
+        count_open(id, 1);
+        volume.lock().retain(ino);
+    }
+}
+
+/// Drop a descriptor of an open inode (fd closed)
+pub fn release(id: u32, ino: u64) {
+    if let Ok(volume) = volume_of(id, false) {
+        volume.lock().release(ino);
+        count_open(id, -1);
+    }
+}
+
+fn count_open(id: u32, delta: i32) {
+    if let Some(mount) = MOUNTS.lock().mounts.iter_mut().find(|m| m.id == id) {
+        mount.opens = mount.opens.saturating_add_signed(delta);
+    }
+}
+
+/// Names of the mount points, without the leading `/`
+pub fn names() -> Vec<String> {
+    MOUNTS.lock().mounts.iter().map(|m| m.path[1..].to_string()).collect()
+}
+
+/// Write the mount table, one line per mount: mount point, type, flags
+/// and open descriptors
+pub fn write_table(out: &mut impl core::fmt::Write) -> core::fmt::Result {
+    for m in MOUNTS.lock().mounts.iter() {
+        let ro = if m.flags & MS_RDONLY != 0 { "ro" } else { "rw" };
+        let noexec = if m.flags & MS_NOEXEC != 0 { ",noexec" } else { "" };
+        writeln!(out, "{} {} {}{} opens={}", m.path, m.fs_type, ro, noexec, m.opens)?;
+    }
+    Ok(())
+}
+
+/// Mount the volumes named by the `fat.mount` command line option
+///
+/// Runs after the block devices are registered. A volume that cannot be
+/// mounted is logged and skipped.
+pub fn init() {
+    let mut buf = [0u8; 256];
+    let Some(option) = crate::cmdline::get(MOUNT_OPTION, &mut buf) else { return };
+
+    for spec in option.split(',').filter(|s| !s.is_empty()) {
+        let Some((dev, path)) = spec.split_once(':') else {
+            kwarn!("[MOUNT] {}: expected <dev>:<dir>", spec);
+            continue;
+        };
+        let Some(device) = crate::drivers::block::find(dev).and_then(crate::drivers::block::get) else {
+            kwarn!("[MOUNT] {}: no such block device", dev);
+            continue;
+        };
+        match mount(path, "fat32", Some(device), 0) {
+            Ok(_) => kinfo!("[MOUNT] {} on {} (fat32)", dev, path),
+            Err(e) => kwarn!("[MOUNT] {} on {}: {:?}", dev, path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::fd::flags::{O_CREAT, O_RDONLY, O_RDWR};
+
+    #[test]
+    fn test_mount_checks() {
+        assert_eq!(mount("/a", "ext2", None, 0), Err(Errno::ENODEV));
+        assert_eq!(mount("/a", "fat32", None, 0), Err(Errno::EINVAL));
+        assert_eq!(mount("/a", "tmpfs", None, 1 << 7), Err(Errno::EINVAL));
+        assert_eq!(mount("/a/b", "tmpfs", None, 0), Err(Errno::EINVAL));
+        assert_eq!(mount("/", "tmpfs", None, 0), Err(Errno::EINVAL));
+        assert_eq!(mount("/tmp", "tmpfs", None, 0), Err(Errno::EBUSY));
+        assert_eq!(umount("/a"), Err(Errno::EINVAL));
+    }
+
+    #[test]
+    fn test_tmpfs_mount() {
+        let id = mount("/scratch/", "tmpfs", None, 0).unwrap();
+        assert_eq!(mount("/scratch", "tmpfs", None, 0), Err(Errno::EBUSY));
+        assert_eq!(find("/scratch/x"), Some((id, "/x")));
+        assert_eq!(find("/scratchy"), None);
+
+        let ino = open(id, "/x", O_RDWR | O_CREAT).unwrap();
+        assert_eq!(with(id, |fs| fs.write(ino, 0, b"hi")), Ok(2));
+        assert_eq!(umount("/scratch"), Err(Errno::EBUSY));
+        release(id, ino);
+        assert_eq!(umount("/scratch"), Ok(()));
+
+        // The ID is not reused by the next mount there
+        assert_eq!(with(id, |fs| fs.size(ino)), Err(Errno::ENODEV));
+        let again = mount("/scratch", "tmpfs", None, 0).unwrap();
+        assert_ne!(again, id);
+        assert_eq!(with(again, |fs| fs.lookup("/x")), Err(Errno::ENOENT));
+        assert_eq!(umount("/scratch"), Ok(()));
+    }
+
+    #[test]
+    fn test_read_only() {
+        let id = mount("/ro", "tmpfs", None, MS_RDONLY | MS_NOEXEC).unwrap();
+        assert_eq!(open(id, "/x", O_RDWR | O_CREAT), Err(Errno::EROFS));
+        assert_eq!(with_writable(id, |fs| fs.mkdir("/d")), Err(Errno::EROFS));
+        assert_eq!(open(id, "", O_RDONLY).map(|_| ()), Ok(()));
+        assert_eq!(flags_at("/ro/x"), Some(MS_RDONLY | MS_NOEXEC));
+        assert_eq!(umount("/ro"), Err(Errno::EBUSY));
+    }
+
+    #[test]
+    fn test_procfs_view() {
+        mount("/p", "procfs", None, 0).unwrap();
+        assert_eq!(find("/p/cpuinfo"), None);
+        assert_eq!(translate("/p/cpuinfo".into()), "/proc/cpuinfo");
+        assert_eq!(translate("/p".into()), "/proc");
+        assert_eq!(translate("/pp/x".into()), "/pp/x");
+        assert_eq!(umount("/p"), Ok(()));
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Process Filesystem (procfs)
+//!
+//! This module resolves paths under `/proc` to synthesized, read-only
+//! files. Like devfs it is a static namespace. File contents are
+//! generated on every read, so a reader always sees current values.
+//!
+//! # Files
+//!
+//! | Path | Contents |
+//! |------|----------|
+//! | `/proc/cpuinfo` | Per-CPU identification, topology, features, frequency and idle states |
+//! | `/proc/version` | Kernel version, git hash and build time |
+//! | `/proc/cmdline` | Boot command line |
+//! | `/proc/lockstat` | Lock contention statistics (`lockstat` feature) |
+//! | `/proc/meminfo` | Current and peak heap / physical memory usage, reclaim counters |
+//! | `/proc/kobjects` | Live, created and destroyed kernel objects by type, with lifetimes |
+//! | `/proc/last-crash` | The previous boot's crash dump (`crashkernel=`), empty if there was none |
+//! | `/proc/klog` | The kernel log ring, oldest line first |
+//! | `/proc/syscalls` | Per-syscall call counts and latency histograms |
+//! | `/proc/mounts` | Mounted filesystems: mount point, type, flags, open descriptors |
+//! | `/proc/self/handles` | The reading process's handles: value, type, rights, name |
+//! | `/proc/self/wss` | The reading process's working set as of the last accessed-bit scan |
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use crate::arch::amd64::{cpu_features, power};
+use crate::fs::ramdisk::Errno;
+use crate::fs::vfs::{DirEntry, Stat, DT_REG, FS_PROCFS};
+use crate::interrupt::affinity;
+use crate::process::table::Process;
+
+/// procfs mount point
+pub const PROCFS_PREFIX: &str = "/proc/";
+
+/// Inode number of `/proc`
+pub const ROOT_INODE: u64 = 0;
+
+/// Inode number of `/proc/self`
+pub const SELF_INODE: u64 = 1;
+
+/// A procfs file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcNode {
+    /// `/proc/cpuinfo`
+    CpuInfo,
+    /// `/proc/version`
+    Version,
+    /// `/proc/cmdline`
+    Cmdline,
+    /// `/proc/lockstat`
+    LockStat,
+    /// `/proc/meminfo`
+    MemInfo,
+    /// `/proc/kobjects`
+    KObjects,
+    /// `/proc/last-crash`
+    LastCrash,
+    /// `/proc/klog`
+    KLog,
+    /// `/proc/syscalls`
+    Syscalls,
+    /// `/proc/mounts`
+    Mounts,
+    /// `/proc/self/handles`
+    Handles,
+    /// `/proc/self/wss`
+    WorkingSet,
+}
+
+/// Check whether a path lives in procfs
+pub fn is_procfs_path(path: &str) -> bool {
+    path.starts_with(PROCFS_PREFIX)
+}
+
+/// Look up a procfs file
+///
+/// # Arguments
+///
+/// * `path` - Absolute path (e.g. `/proc/cpuinfo`)
+///
+/// # Returns
+///
+/// The file, or `ENOENT` if no such file exists
+pub fn lookup(path: &str) -> Result<ProcNode, Errno> {
+    match path.strip_prefix(PROCFS_PREFIX).ok_or(Errno::ENOENT)? {
+        "cpuinfo" => Ok(ProcNode::CpuInfo),
+        "version" => Ok(ProcNode::Version),
+        "cmdline" => Ok(ProcNode::Cmdline),
+        "lockstat" => Ok(ProcNode::LockStat),
+        "meminfo" => Ok(ProcNode::MemInfo),
+        "kobjects" => Ok(ProcNode::KObjects),
+        "last-crash" => Ok(ProcNode::LastCrash),
+        "klog" => Ok(ProcNode::KLog),
+        "syscalls" => Ok(ProcNode::Syscalls),
+        "mounts" => Ok(ProcNode::Mounts),
+        "self/handles" => Ok(ProcNode::Handles),
+        "self/wss" => Ok(ProcNode::WorkingSet),
+        _ => Err(Errno::ENOENT),
+    }
+}
+
+/// Metadata of a procfs file
+///
+/// Sizes are reported as 0, as in [`list`].
+pub fn stat(node: ProcNode) -> Stat {
+    let inode = match node {
+        ProcNode::CpuInfo => 2,
+        ProcNode::Version => 3,
+        ProcNode::Cmdline => 4,
+        ProcNode::LockStat => 5,
+        ProcNode::MemInfo => 6,
+        ProcNode::Handles => 7,
+        ProcNode::KObjects => 8,
+        ProcNode::LastCrash => 9,
+        ProcNode::KLog => 10,
+        ProcNode::Syscalls => 11,
+        ProcNode::Mounts => 12,
+        ProcNode::WorkingSet => 13,
+    };
+    Stat::new(FS_PROCFS, DT_REG, inode, 0, 0)
+}
+
+/// List a procfs directory (`/proc` or `/proc/self`)
+///
+/// Sizes are reported as 0: contents are only generated when read.
+pub fn list(path: &str) -> Result<Vec<DirEntry>, Errno> {
+    match path.trim_end_matches('/') {
+        "/proc" => Ok(vec![
+            DirEntry::file("cpuinfo", 0),
+            DirEntry::file("version", 0),
+            DirEntry::file("cmdline", 0),
+            DirEntry::file("lockstat", 0),
+            DirEntry::file("meminfo", 0),
+            DirEntry::file("kobjects", 0),
+            DirEntry::file("last-crash", 0),
+            DirEntry::file("klog", 0),
+            DirEntry::file("syscalls", 0),
+            DirEntry::file("mounts", 0),
+            DirEntry::dir("self"),
+        ]),
+        "/proc/self" => Ok(vec![DirEntry::file("handles", 0), DirEntry::file("wss", 0)]),
+        _ => Err(Errno::ENOENT),
+    }
+}
+
+/// Generate the contents of a file as seen by a process
+///
+/// Per-process files (`/proc/self/...`) describe `process`; the caller
+/// holds the process table lock.
+pub fn generate_for(node: ProcNode, process: &Process) -> String {
+    match node {
+        ProcNode::Handles => {
+            let mut out = String::new();
+            let _ = process.handles.write_list(&mut out);
+            out
+        }
+        ProcNode::WorkingSet => {
+            let mut out = String::new();
+            let _ = process.wss.write_report(&mut out);
+            out
+        }
+        _ => generate(node),
+    }
+}
+
+/// Generate the contents of a file
+///
+/// Per-process files are empty here; see [`generate_for`].
+pub fn generate(node: ProcNode) -> String {
+    let mut out = String::new();
+    match node {
+        ProcNode::CpuInfo => cpuinfo(&mut out),
+        ProcNode::Version => {
+            let _ = crate::version::write_banner(&mut out);
+            out.push('\n');
+        }
+        ProcNode::Cmdline => {
+            let mut buf = [0u8; crate::cmdline::CMDLINE_MAX];
+            let n = crate::cmdline::copy_to(&mut buf);
+            out.push_str(core::str::from_utf8(&buf[..n]).unwrap_or(""));
+            out.push('\n');
+        }
+        ProcNode::LockStat => {
+            let _ = crate::sync::lockstat::write_report(&mut out);
+        }
+        ProcNode::MemInfo => {
+            use crate::mm::watermark::{HEAP, PMM};
+            let _ = HEAP.write_summary(&mut out);
+            let _ = PMM.write_summary(&mut out);
+            let _ = crate::mm::reclaim::write_summary(&mut out);
+        }
+        ProcNode::KObjects => {
+            let _ = crate::object::metrics::write_report(&mut out);
+        }
+        ProcNode::LastCrash => {
+            if let Some(text) = crate::crashdump::last_crash() {
+                out = text;
+            }
+        }
+        ProcNode::KLog => {
+            // Skipped if a line is being logged right now
+            crate::klog::try_with(|log| {
+                let (old, new) = log.as_slices();
+                out.push_str(&String::from_utf8_lossy(old));
+                out.push_str(&String::from_utf8_lossy(new));
+            });
+        }
+        ProcNode::Syscalls => {
+            let _ = crate::syscall::latency::write_report(&mut out);
+        }
+        ProcNode::Mounts => {
+            let _ = crate::fs::mount::write_table(&mut out);
+        }
+        ProcNode::Handles | ProcNode::WorkingSet => {}
+    }
+    out
+}
+
+/// Read from a file at an offset
+///
+/// # Returns
+///
+/// Number of bytes copied into `buf` (0 at end of file)
+pub fn read(node: ProcNode, offset: u64, buf: &mut [u8]) -> usize {
+    let content = generate(node);
+    let bytes = content.as_bytes();
+    let start = core::cmp::min(offset, bytes.len() as u64) as usize;
+    let n = core::cmp::min(buf.len(), bytes.len() - start);
+    buf[..n].copy_from_slice(&bytes[start..start + n]);
+    n
+}
+
+/// `/proc/cpuinfo`
+///
+/// One block per online CPU. All CPUs are assumed identical, so the
+/// identification is read once from the boot CPU.
+fn cpuinfo(out: &mut String) {
+    let features = cpu_features::get();
+    let mhz = power::current_mhz();
+    let table = power::cstates();
+
+    for cpu in (0..affinity::MAX_CPUS).filter(|&cpu| affinity::is_cpu_online(cpu)) {
+        let _ = writeln!(out, "processor\t: {}", cpu);
+        let _ = writeln!(out, "vendor_id\t: {}", features.vendor_str());
+        let _ = writeln!(out, "cpu family\t: {}", features.family);
+        let _ = writeln!(out, "model\t\t: {}", features.model);
+        let _ = writeln!(out, "model name\t: {}", features.brand_str());
+        let _ = writeln!(out, "stepping\t: {}", features.stepping);
+        let _ = writeln!(out, "cpu MHz\t\t: {}", mhz);
+        let _ = writeln!(out, "base MHz\t: {}", power::base_mhz());
+        let _ = writeln!(out, "max MHz\t\t: {}", power::max_mhz());
+        let _ = writeln!(out, "siblings\t: {}", features.threads_per_package);
+        let _ = writeln!(out, "cpu cores\t: {}", features.cores_per_package);
+        let _ = writeln!(out, "mwait\t\t: {}", if features.mwait.is_some() { "yes" } else { "no" });
+
+        let _ = write!(out, "cstates\t\t:");
+        for (i, state) in table.states().iter().enumerate() {
+            let _ = write!(out, " {}({}us,{})", state.name, state.exit_latency_us, power::usage(i));
+        }
+        let _ = writeln!(out, " hlt({})", power::usage(power::HLT_SLOT));
+
+        let _ = write!(out, "flags\t\t:");
+        for flag in features.flags() {
+            let _ = write!(out, " {}", flag);
+        }
+        let _ = writeln!(out, "\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("/proc/cpuinfo"), Ok(ProcNode::CpuInfo));
+        assert_eq!(lookup("/proc/version"), Ok(ProcNode::Version));
+        assert_eq!(lookup("/proc/cmdline"), Ok(ProcNode::Cmdline));
+        assert_eq!(lookup("/proc/lockstat"), Ok(ProcNode::LockStat));
+        assert_eq!(lookup("/proc/meminfo"), Ok(ProcNode::MemInfo));
+        assert_eq!(lookup("/proc/self/handles"), Ok(ProcNode::Handles));
+        assert_eq!(lookup("/proc/self/wss"), Ok(ProcNode::WorkingSet));
+        assert_eq!(lookup("/proc/kobjects"), Ok(ProcNode::KObjects));
+        assert_eq!(lookup("/proc/last-crash"), Ok(ProcNode::LastCrash));
+        assert_eq!(lookup("/proc/klog"), Ok(ProcNode::KLog));
+        assert_eq!(lookup("/proc/syscalls"), Ok(ProcNode::Syscalls));
+        assert_eq!(lookup("/proc/nope"), Err(Errno::ENOENT));
+        assert_eq!(lookup("/dev/tty1"), Err(Errno::ENOENT));
+    }
+
+    #[test]
+    fn test_list() {
+        for entry in list("/proc").unwrap().iter().filter(|e| e.name != "self") {
+            assert!(lookup(&format!("/proc/{}", entry.name)).is_ok());
+        }
+        assert_eq!(list("/proc/self/").unwrap()[0].name, "handles");
+        assert_eq!(list("/proc/nope"), Err(Errno::ENOENT));
+    }
+
+    #[test]
+    fn test_stat_inodes_unique() {
+        let nodes = ["cpuinfo", "version", "cmdline", "lockstat", "meminfo", "kobjects", "last-crash", "klog", "syscalls", "mounts", "self/handles", "self/wss"];
+        let mut inodes: Vec<u64> = nodes.iter().map(|n| stat(lookup(&format!("/proc/{}", n)).unwrap()).inode).collect();
+        inodes.extend([ROOT_INODE, SELF_INODE]);
+        inodes.sort();
+        inodes.dedup();
+        assert_eq!(inodes.len(), nodes.len() + 2);
+    }
+
+    #[test]
+    fn test_read_offset() {
+        crate::cmdline::init(b"trace quiet");
+        let mut buf = [0u8; 4];
+        assert_eq!(read(ProcNode::Cmdline, 6, &mut buf), 4);
+        assert_eq!(&buf, b"quie");
+        assert_eq!(read(ProcNode::Cmdline, 12, &mut buf), 0);
+    }
+}
@@ -23,10 +23,19 @@
 //! ```text
 //! Offset 0x00: Superblock (16 bytes)
 //! Offset 0x10: File headers (16 bytes each, num_files entries)
-//! Offset 0x10 + (num_files * 16): File names (null-terminated)
-//! After names: File data (contiguous)
+//! Offset 0x10 + (num_files * 16): Per file: name (null-terminated),
+//!                                  zero padding, data
 //! ```
 //!
+//! Each file's data starts on a 4 KiB boundary (relative to the image,
+//! which is embedded page-aligned), so whole pages of a file can be mapped
+//! straight from the ramdisk (see [`filemap`](super::filemap)).
+//!
+//! The last word of a file header holds entry flags (it was reserved and
+//! zero in the first version of the layout). An entry with
+//! [`RAMDISK_FILE_SYMLINK`] is a symbolic link whose data is its target
+//! path.
+//!
 //! # Usage
 //!
 //! ```ignore
@@ -44,7 +53,7 @@
 //! let bytes_read = ramdisk.read_file(&file, &mut buffer);
 //! ```
 
-use crate::sync::SpinMutex;
+use crate::sync::AdaptiveMutex;
 use alloc::vec::Vec;
 
 /// ============================================================================
@@ -72,19 +81,24 @@ pub enum Errno {
     EFAULT = 14,    // Bad address
     EBUSY = 16,     // Device busy
     EEXIST = 17,    // File exists
+    EXDEV = 18,     // Cross-device link
     ENODEV = 19,    // No such device
     ENOTDIR = 20,   // Not a directory
     EISDIR = 21,    // Is a directory
     EINVAL = 22,   // Invalid argument
     ENFILE = 23,    // File table overflow
     EMFILE = 24,    // Too many open files
+    ENOSPC = 28,    // No space left on device
     ESPIPE = 29,    // Illegal seek
     EROFS = 30,     // Read-only filesystem
     EMLINK = 31,    // Too many links
     EPIPE = 32,     // Broken pipe
     EDOM = 33,      // Numerical argument out of domain
     ERANGE = 34,    // Result too large
+    ENAMETOOLONG = 36, // File name too long
     ENOSYS = 38,    // Function not implemented
+    ENOTEMPTY = 39, // Directory not empty
+    ELOOP = 40,     // Too many symbolic links
 }
 
 /// Convert RxStatus to Errno
@@ -96,10 +110,30 @@ pub fn rxstatus_to_errno(status: crate::arch::amd64::mm::RxStatus) -> Errno {
         RxStatus::ERR_NO_MEMORY => Errno::ENOMEM,
         RxStatus::ERR_NOT_FOUND => Errno::ENOENT,
         RxStatus::ERR_NOT_SUPPORTED => Errno::ENOSYS,
+        RxStatus::ERR_SHOULD_WAIT => Errno::EAGAIN,
+        RxStatus::ERR_ALREADY_EXISTS => Errno::EEXIST,
+        RxStatus::ERR_BUFFER_TOO_SMALL => Errno::ERANGE,
         _ => Errno::EIO,
     }
 }
 
+/// Convert Errno to RxStatus (for filesystem errors returned by syscalls)
+pub fn errno_to_rxstatus(errno: Errno) -> crate::arch::amd64::mm::RxStatus {
+    use crate::arch::amd64::mm::RxStatus;
+    match errno {
+        Errno::Success => RxStatus::OK,
+        Errno::ENOENT | Errno::ENODEV => RxStatus::ERR_NOT_FOUND,
+        Errno::ENOMEM | Errno::ENOSPC | Errno::EMFILE | Errno::ENFILE => RxStatus::ERR_NO_MEMORY,
+        Errno::EEXIST => RxStatus::ERR_ALREADY_EXISTS,
+        Errno::EACCES | Errno::EPERM | Errno::EROFS => RxStatus::ERR_ACCESS_DENIED,
+        Errno::EBUSY | Errno::ENOTEMPTY => RxStatus::ERR_BUSY,
+        Errno::EAGAIN => RxStatus::ERR_SHOULD_WAIT,
+        Errno::ENOSYS => RxStatus::ERR_NOT_SUPPORTED,
+        Errno::EIO => RxStatus::ERR_IO,
+        _ => RxStatus::ERR_INVALID_ARGS,
+    }
+}
+
 /// Additional error for ENOSYS
 pub const ENOSYS: Errno = Errno::EPERM; // Temporary: use EPERM for "not supported"
 
@@ -117,8 +151,18 @@ pub struct RamdiskFile {
     pub data_offset: u32,
     /// File size in bytes
     pub size: u32,
-    /// Reserved (for alignment)
-    pub _pad: u32,
+    /// Entry flags (`RAMDISK_FILE_*`)
+    pub flags: u32,
+}
+
+/// Ramdisk entry flag: the entry is a symbolic link to the path in its data
+pub const RAMDISK_FILE_SYMLINK: u32 = 0x1;
+
+impl RamdiskFile {
+    /// Check if the entry is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.flags & RAMDISK_FILE_SYMLINK != 0
+    }
 }
 
 /// Ramdisk superblock (at offset 0)
@@ -255,6 +299,7 @@ impl Ramdisk {
             return 0;
         }
 
+        crate::mm::kasan::check_write(buf.as_ptr() as usize, to_copy);
         unsafe {
             core::ptr::copy_nonoverlapping(data_ptr, buf.as_mut_ptr(), to_copy);
         }
@@ -262,6 +307,38 @@ impl Ramdisk {
         to_copy
     }
 
+    /// Get a file by index
+    ///
+    /// The index is the inode number open file descriptors carry.
+    pub fn file_at(&self, index: u32) -> Option<RamdiskFile> {
+        if index >= self.superblock.num_files {
+            return None;
+        }
+        let files = unsafe {
+            let base = self.data.as_ptr().add(self.superblock.files_offset as usize);
+            core::slice::from_raw_parts(base as *const RamdiskFile, self.superblock.num_files as usize)
+        };
+        Some(files[index as usize])
+    }
+
+    /// Get a file's contents
+    ///
+    /// Empty if the header points outside the image.
+    pub fn file_data(&self, file: &RamdiskFile) -> &'static [u8] {
+        let start = file.data_offset as usize;
+        let end = start.saturating_add(file.size as usize);
+        self.data.get(start..end).unwrap_or(&[])
+    }
+
+    /// Get a file's name
+    ///
+    /// Empty if the header points outside the image or the name is not UTF-8.
+    pub fn file_name(&self, file: &RamdiskFile) -> &'static str {
+        let rest = self.data.get(file.name_offset as usize..).unwrap_or(&[]);
+        let len = rest.iter().take(256).position(|&b| b == 0).unwrap_or(0);
+        core::str::from_utf8(&rest[..len]).unwrap_or("")
+    }
+
     /// Get file size
     ///
     /// # Arguments
@@ -322,11 +399,18 @@ impl Ramdisk {
 /// Global Ramdisk Instance
 /// ============================================================================
 
+/// Page-aligned storage for the embedded ramdisk image
+///
+/// File contents are page-aligned relative to the image, so the image
+/// itself must be too for file pages to be mapped in place.
+#[repr(C, align(4096))]
+pub struct PageAligned<T: ?Sized>(pub T);
+
 /// Global ramdisk instance
 ///
 /// This is initialized during kernel startup with the embedded
 /// ramdisk data that was generated by build.rs
-pub static RAMDISK: SpinMutex<Option<Ramdisk>> = SpinMutex::new(None);
+pub static RAMDISK: AdaptiveMutex<Option<Ramdisk>> = AdaptiveMutex::named(None, &crate::sync::lockstat::RAMDISK);
 
 /// Initialize the ramdisk from embedded data
 ///
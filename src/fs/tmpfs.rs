@@ -0,0 +1,717 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Temporary Filesystem (tmpfs)
+//!
+//! This module provides a writable in-memory filesystem mounted at
+//! `/tmp`. Files and directories can be created, written, truncated and
+//! removed; their contents live on the kernel heap and last until they
+//! are removed or the system reboots.
+//!
+//! # Design
+//!
+//! - Inodes are kept in a map keyed by inode number; inode 0 is `/tmp`
+//! - A directory maps names to inode numbers
+//! - File data is a heap buffer, grown on write (gaps read as zeros)
+//! - A symbolic link stores its target path; the VFS follows it (see
+//!   [`vfs::follow`](crate::fs::vfs::follow)), tmpfs only stores it
+//! - A file or link may have several names ([`link`](Tmpfs::link)); each
+//!   counts in the inode's `links`
+//! - The total size of all files is capped at [`TMPFS_MAX_BYTES`]
+//! - Each inode records when it was last modified (a directory changes
+//!   when an entry is added or removed)
+//!
+//! An unlinked file stays readable and writable through descriptors that
+//! already have it open; its inode is freed when the last one is closed.
+//! Open descriptors are counted with [`retain`] / [`release`], which the
+//! fd table calls as `FdKind::Tmp` descriptors are copied and dropped.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use crate::fs::mount::Filesystem;
+use crate::fs::ramdisk::Errno;
+use crate::fs::vfs::{DirEntry, Stat, DT_DIR, DT_LNK, DT_REG, FS_TMPFS, PATH_MAX};
+use crate::sync::AdaptiveMutex;
+
+/// tmpfs mount point
+pub const TMPFS_ROOT: &str = "/tmp";
+
+/// Inode number of the root directory (`/tmp`)
+pub const ROOT_INODE: u32 = 0;
+
+/// Longest name of a single path component
+pub const NAME_MAX: usize = 255;
+
+/// Total bytes of file data tmpfs may hold
+pub const TMPFS_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// Contents of an inode
+#[derive(Debug)]
+enum Node {
+    /// Regular file
+    File(Vec<u8>),
+    /// Directory: name to inode number
+    Dir(BTreeMap<String, u32>),
+    /// Symbolic link: target path
+    Symlink(String),
+}
+
+/// A tmpfs inode
+#[derive(Debug)]
+struct Inode {
+    /// File data or directory entries
+    node: Node,
+    /// Number of directory entries naming this inode (0 once unlinked)
+    links: u32,
+    /// Number of open file descriptors
+    opens: u32,
+    /// Last modification in nanoseconds on the monotonic clock
+    mtime_ns: u64,
+}
+
+/// The tmpfs instance
+#[derive(Debug)]
+pub struct Tmpfs {
+    /// Inodes by number
+    inodes: BTreeMap<u32, Inode>,
+    /// Next inode number to hand out
+    next_inode: u32,
+    /// Bytes of file data currently held
+    used: usize,
+}
+
+/// Check whether a path lives in tmpfs
+pub fn is_tmpfs_path(path: &str) -> bool {
+    match path.strip_prefix(TMPFS_ROOT) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Split a tmpfs path into its components below `/tmp`
+///
+/// Empty components and `.` are skipped; `..` is rejected.
+fn components(path: &str) -> Result<Vec<&str>, Errno> {
+    let rest = path.strip_prefix(TMPFS_ROOT).ok_or(Errno::ENOENT)?;
+    let mut parts = Vec::new();
+    for part in rest.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Err(Errno::EINVAL),
+            _ if part.len() > NAME_MAX => return Err(Errno::ENAMETOOLONG),
+            _ => parts.push(part),
+        }
+    }
+    Ok(parts)
+}
+
+impl Tmpfs {
+    /// Create an empty filesystem holding only the root directory
+    pub fn new() -> Self {
+        let mut inodes = BTreeMap::new();
+        inodes.insert(ROOT_INODE, Inode {
+            node: Node::Dir(BTreeMap::new()),
+            links: 1,
+            opens: 0,
+            mtime_ns: 0,
+        });
+        Self {
+            inodes,
+            next_inode: ROOT_INODE + 1,
+            used: 0,
+        }
+    }
+
+    /// Bytes of file data currently held
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Resolve a path to an inode number
+    pub fn lookup(&self, path: &str) -> Result<u32, Errno> {
+        let mut ino = ROOT_INODE;
+        for name in components(path)? {
+            ino = self.child(ino, name)?.ok_or(Errno::ENOENT)?;
+        }
+        Ok(ino)
+    }
+
+    /// Resolve the parent directory of a path and the final name
+    ///
+    /// Fails with `EINVAL` for `/tmp` itself, which has no parent.
+    fn parent<'a>(&self, path: &'a str) -> Result<(u32, &'a str), Errno> {
+        let mut parts = components(path)?;
+        let name = parts.pop().ok_or(Errno::EINVAL)?;
+        let mut ino = ROOT_INODE;
+        for dir in parts {
+            ino = self.child(ino, dir)?.ok_or(Errno::ENOENT)?;
+        }
+        // Make sure the parent is a directory
+        self.child(ino, name)?;
+        Ok((ino, name))
+    }
+
+    /// Look up `name` in directory `dir`
+    fn child(&self, dir: u32, name: &str) -> Result<Option<u32>, Errno> {
+        match &self.inodes.get(&dir).ok_or(Errno::ENOENT)?.node {
+            Node::Dir(entries) => Ok(entries.get(name).copied()),
+            Node::File(_) | Node::Symlink(_) => Err(Errno::ENOTDIR),
+        }
+    }
+
+    /// Add an inode and link it into directory `dir` as `name`
+    fn link_new(&mut self, dir: u32, name: &str, node: Node) -> Result<u32, Errno> {
+        let ino = self.next_inode;
+        self.next_inode = self.next_inode.checked_add(1).ok_or(Errno::ENOMEM)?;
+        self.inodes.insert(ino, Inode { node, links: 1, opens: 0, mtime_ns: now() });
+        if let Some(Inode { node: Node::Dir(entries), .. }) = self.inodes.get_mut(&dir) {
+            entries.insert(name.to_string(), ino);
+        }
+        self.touch(dir);
+        Ok(ino)
+    }
+
+    /// Record a modification of an inode
+    fn touch(&mut self, ino: u32) {
+        if let Some(inode) = self.inodes.get_mut(&ino) {
+            inode.mtime_ns = now();
+        }
+    }
+
+    /// Open a path, counting the open
+    ///
+    /// `O_CREAT` creates a missing file (`O_EXCL` fails with `EEXIST` if
+    /// it exists) and `O_TRUNC` empties a file opened for writing.
+    /// Directories can only be opened read-only, and a symbolic link
+    /// (which the VFS did not follow) not at all (`ELOOP`).
+    ///
+    /// # Returns
+    ///
+    /// The inode number; release it with [`close`](Self::close)
+    pub fn open(&mut self, path: &str, flags: u32) -> Result<u32, Errno> {
+        use crate::syscall::fd::flags::{O_CREAT, O_EXCL, O_TRUNC, O_RDONLY};
+
+        let writable = flags & 3 != O_RDONLY;
+        let ino = match self.lookup(path) {
+            Ok(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => return Err(Errno::EEXIST),
+            Ok(ino) => ino,
+            Err(Errno::ENOENT) if flags & O_CREAT != 0 => {
+                let (dir, name) = self.parent(path)?;
+                self.link_new(dir, name, Node::File(Vec::new()))?
+            }
+            Err(e) => return Err(e),
+        };
+
+        if matches!(self.inodes[&ino].node, Node::Symlink(_)) {
+            return Err(Errno::ELOOP);
+        }
+        let is_dir = matches!(self.inodes[&ino].node, Node::Dir(_));
+        if is_dir && (writable || flags & O_TRUNC != 0) {
+            return Err(Errno::EISDIR);
+        }
+        if writable && flags & O_TRUNC != 0 {
+            self.truncate(ino, 0)?;
+        }
+
+        self.retain(ino);
+        Ok(ino)
+    }
+
+    /// Count another open of an inode
+    pub fn retain(&mut self, ino: u32) {
+        if let Some(inode) = self.inodes.get_mut(&ino) {
+            inode.opens += 1;
+        }
+    }
+
+    /// Drop an open of an inode, freeing it if it was unlinked
+    pub fn close(&mut self, ino: u32) {
+        if let Some(inode) = self.inodes.get_mut(&ino) {
+            inode.opens = inode.opens.saturating_sub(1);
+            if inode.opens == 0 && inode.links == 0 {
+                self.free(ino);
+            }
+        }
+    }
+
+    /// Remove an inode and return its data to the budget
+    fn free(&mut self, ino: u32) {
+        if let Some(Inode { node: Node::File(data), .. }) = self.inodes.remove(&ino) {
+            self.used -= data.len();
+        }
+    }
+
+    /// The file data of an inode
+    fn file(&mut self, ino: u32) -> Result<&mut Vec<u8>, Errno> {
+        match &mut self.inodes.get_mut(&ino).ok_or(Errno::ENOENT)?.node {
+            Node::File(data) => Ok(data),
+            Node::Dir(_) => Err(Errno::EISDIR),
+            Node::Symlink(_) => Err(Errno::ELOOP),
+        }
+    }
+
+    /// Size of a file in bytes (0 for a directory, the target's length for
+    /// a symbolic link)
+    pub fn size(&self, ino: u32) -> Result<u64, Errno> {
+        match &self.inodes.get(&ino).ok_or(Errno::ENOENT)?.node {
+            Node::File(data) => Ok(data.len() as u64),
+            Node::Dir(_) => Ok(0),
+            Node::Symlink(target) => Ok(target.len() as u64),
+        }
+    }
+
+    /// Metadata of an inode
+    pub fn stat(&self, ino: u32) -> Result<Stat, Errno> {
+        let inode = self.inodes.get(&ino).ok_or(Errno::ENOENT)?;
+        let (kind, size) = match &inode.node {
+            Node::File(data) => (DT_REG, data.len() as u64),
+            Node::Dir(_) => (DT_DIR, 0),
+            Node::Symlink(target) => (DT_LNK, target.len() as u64),
+        };
+        Ok(Stat::new(FS_TMPFS, kind, ino as u64, size, inode.mtime_ns))
+    }
+
+    /// Whether an inode is a directory
+    pub fn is_dir(&self, ino: u32) -> bool {
+        matches!(self.inodes.get(&ino), Some(Inode { node: Node::Dir(_), .. }))
+    }
+
+    /// List a directory
+    pub fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno> {
+        let ino = self.lookup(path)?;
+        let entries = match &self.inodes[&ino].node {
+            Node::Dir(entries) => entries,
+            Node::File(_) | Node::Symlink(_) => return Err(Errno::ENOTDIR),
+        };
+        Ok(entries
+            .iter()
+            .map(|(name, child)| match &self.inodes[child].node {
+                Node::Dir(_) => DirEntry::dir(name),
+                Node::File(data) => DirEntry::file(name, data.len() as u64),
+                Node::Symlink(target) => DirEntry::symlink(name, target.len() as u64),
+            })
+            .collect())
+    }
+
+    /// Read from a file at an offset
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes copied into `buf` (0 at end of file)
+    pub fn read(&mut self, ino: u32, offset: u64, buf: &mut [u8]) -> Result<usize, Errno> {
+        let data = self.file(ino)?;
+        let start = core::cmp::min(offset, data.len() as u64) as usize;
+        let n = core::cmp::min(buf.len(), data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        Ok(n)
+    }
+
+    /// Write to a file at an offset, growing it as needed
+    ///
+    /// Writing past the end fills the gap with zeros. Fails with `ENOSPC`
+    /// if the file would grow beyond the tmpfs budget.
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes written
+    pub fn write(&mut self, ino: u32, offset: u64, src: &[u8]) -> Result<usize, Errno> {
+        let end = offset.checked_add(src.len() as u64).ok_or(Errno::EINVAL)?;
+        let size = self.size(ino)?;
+        if end > size {
+            self.truncate(ino, end)?;
+        }
+        let data = self.file(ino)?;
+        data[offset as usize..end as usize].copy_from_slice(src);
+        self.touch(ino);
+        Ok(src.len())
+    }
+
+    /// Set the size of a file, zero-filling if it grows
+    pub fn truncate(&mut self, ino: u32, size: u64) -> Result<(), Errno> {
+        let old = self.file(ino)?.len();
+        let new = usize::try_from(size).map_err(|_| Errno::ENOSPC)?;
+        if new > old && new - old > TMPFS_MAX_BYTES - self.used {
+            return Err(Errno::ENOSPC);
+        }
+
+        let data = self.file(ino)?;
+        if new > old {
+            data.try_reserve_exact(new - old).map_err(|_| Errno::ENOMEM)?;
+        }
+        data.resize(new, 0);
+        if new < old {
+            data.shrink_to_fit();
+        }
+        self.used = self.used + new - old;
+        self.touch(ino);
+        Ok(())
+    }
+
+    /// Create a directory
+    pub fn mkdir(&mut self, path: &str) -> Result<(), Errno> {
+        let (dir, name) = self.parent(path)?;
+        if self.child(dir, name)?.is_some() {
+            return Err(Errno::EEXIST);
+        }
+        self.link_new(dir, name, Node::Dir(BTreeMap::new()))?;
+        Ok(())
+    }
+
+    /// Create a symbolic link at `path` pointing to `target`
+    ///
+    /// The target is stored as given and need not exist.
+    pub fn symlink(&mut self, target: &str, path: &str) -> Result<(), Errno> {
+        if target.is_empty() {
+            return Err(Errno::ENOENT);
+        }
+        if target.len() > PATH_MAX {
+            return Err(Errno::ENAMETOOLONG);
+        }
+        let (dir, name) = self.parent(path)?;
+        if self.child(dir, name)?.is_some() {
+            return Err(Errno::EEXIST);
+        }
+        self.link_new(dir, name, Node::Symlink(target.to_string()))?;
+        Ok(())
+    }
+
+    /// Target of the symbolic link at `path`
+    ///
+    /// Fails with `EINVAL` if `path` is not a symbolic link.
+    pub fn readlink(&self, path: &str) -> Result<String, Errno> {
+        match &self.inodes[&self.lookup(path)?].node {
+            Node::Symlink(target) => Ok(target.clone()),
+            _ => Err(Errno::EINVAL),
+        }
+    }
+
+    /// Give the file or symbolic link at `old` another name, `new`
+    ///
+    /// Both names refer to the same inode, which lasts until every name is
+    /// removed and every descriptor closed. Directories cannot be linked
+    /// (`EPERM`).
+    pub fn link(&mut self, old: &str, new: &str) -> Result<(), Errno> {
+        let ino = self.lookup(old)?;
+        if self.is_dir(ino) {
+            return Err(Errno::EPERM);
+        }
+        let (dir, name) = self.parent(new)?;
+        if self.child(dir, name)?.is_some() {
+            return Err(Errno::EEXIST);
+        }
+
+        let inode = self.inodes.get_mut(&ino).ok_or(Errno::ENOENT)?;
+        inode.links = inode.links.checked_add(1).ok_or(Errno::EMLINK)?;
+        if let Some(Inode { node: Node::Dir(entries), .. }) = self.inodes.get_mut(&dir) {
+            entries.insert(name.to_string(), ino);
+        }
+        self.touch(dir);
+        Ok(())
+    }
+
+    /// Number of names an inode has
+    pub fn links(&self, ino: u32) -> Result<u32, Errno> {
+        Ok(self.inodes.get(&ino).ok_or(Errno::ENOENT)?.links)
+    }
+
+    /// Remove a file
+    ///
+    /// The data is freed once no descriptor has the file open and no other
+    /// name refers to it.
+    pub fn unlink(&mut self, path: &str) -> Result<(), Errno> {
+        let (dir, name) = self.parent(path)?;
+        let ino = self.child(dir, name)?.ok_or(Errno::ENOENT)?;
+        if self.is_dir(ino) {
+            return Err(Errno::EISDIR);
+        }
+        self.remove_entry(dir, name, ino);
+        Ok(())
+    }
+
+    /// Remove an empty directory
+    pub fn rmdir(&mut self, path: &str) -> Result<(), Errno> {
+        let (dir, name) = self.parent(path)?;
+        let ino = self.child(dir, name)?.ok_or(Errno::ENOENT)?;
+        match &self.inodes[&ino].node {
+            Node::Dir(entries) if !entries.is_empty() => return Err(Errno::ENOTEMPTY),
+            Node::Dir(_) => {}
+            Node::File(_) | Node::Symlink(_) => return Err(Errno::ENOTDIR),
+        }
+        self.remove_entry(dir, name, ino);
+        Ok(())
+    }
+
+    /// Drop the directory entry `name` in `dir`, which names `ino`
+    fn remove_entry(&mut self, dir: u32, name: &str, ino: u32) {
+        if let Some(Inode { node: Node::Dir(entries), .. }) = self.inodes.get_mut(&dir) {
+            entries.remove(name);
+        }
+        self.touch(dir);
+        if let Some(inode) = self.inodes.get_mut(&ino) {
+            inode.links -= 1;
+            if inode.links == 0 && inode.opens == 0 {
+                self.free(ino);
+            }
+        }
+    }
+}
+
+/// Current time for modification stamps
+fn now() -> u64 {
+    crate::time::Instant::now().as_nanos()
+}
+
+impl Default for Tmpfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A tmpfs mounted elsewhere than `/tmp` ([`mount`](crate::fs::mount)):
+/// paths relative to its root are taken as paths under `/tmp`, and inode
+/// numbers widened
+impl Filesystem for Tmpfs {
+    fn open(&mut self, path: &str, flags: u32) -> Result<u64, Errno> {
+        Tmpfs::open(self, &format!("{}{}", TMPFS_ROOT, path), flags).map(u64::from)
+    }
+
+    fn retain(&mut self, ino: u64) {
+        if let Ok(ino) = u32::try_from(ino) {
+            Tmpfs::retain(self, ino);
+        }
+    }
+
+    fn release(&mut self, ino: u64) {
+        if let Ok(ino) = u32::try_from(ino) {
+            self.close(ino);
+        }
+    }
+
+    fn lookup(&self, path: &str) -> Result<u64, Errno> {
+        Tmpfs::lookup(self, &format!("{}{}", TMPFS_ROOT, path)).map(u64::from)
+    }
+
+    fn size(&self, ino: u64) -> Result<u64, Errno> {
+        Tmpfs::size(self, inode(ino)?)
+    }
+
+    fn stat(&self, ino: u64) -> Result<Stat, Errno> {
+        Tmpfs::stat(self, inode(ino)?)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno> {
+        Tmpfs::read_dir(self, &format!("{}{}", TMPFS_ROOT, path))
+    }
+
+    fn read(&mut self, ino: u64, offset: u64, buf: &mut [u8]) -> Result<usize, Errno> {
+        Tmpfs::read(self, inode(ino)?, offset, buf)
+    }
+
+    fn write(&mut self, ino: u64, offset: u64, src: &[u8]) -> Result<usize, Errno> {
+        Tmpfs::write(self, inode(ino)?, offset, src)
+    }
+
+    fn truncate(&mut self, ino: u64, size: u64) -> Result<(), Errno> {
+        Tmpfs::truncate(self, inode(ino)?, size)
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<(), Errno> {
+        Tmpfs::mkdir(self, &format!("{}{}", TMPFS_ROOT, path))
+    }
+
+    fn unlink(&mut self, path: &str) -> Result<(), Errno> {
+        Tmpfs::unlink(self, &format!("{}{}", TMPFS_ROOT, path))
+    }
+
+    fn rmdir(&mut self, path: &str) -> Result<(), Errno> {
+        Tmpfs::rmdir(self, &format!("{}{}", TMPFS_ROOT, path))
+    }
+}
+
+/// Narrow a mounted tmpfs inode number
+fn inode(ino: u64) -> Result<u32, Errno> {
+    u32::try_from(ino).map_err(|_| Errno::ENOENT)
+}
+
+// ============================================================================
+// Global tmpfs Instance
+// ============================================================================
+
+/// The filesystem mounted at `/tmp`, created on first use
+static TMPFS: AdaptiveMutex<Option<Tmpfs>> = AdaptiveMutex::named(None, &crate::sync::lockstat::TMPFS);
+
+/// Run `f` on the global tmpfs
+///
+/// Lock order: the process table lock may be held (a waiter then spins
+/// instead of blocking), never taken inside.
+pub fn with<R>(f: impl FnOnce(&mut Tmpfs) -> R) -> R {
+    let mut guard = TMPFS.lock();
+    f(guard.get_or_insert_with(Tmpfs::new))
+}
+
+/// Count another open descriptor of an inode (fd copied by `FORK`)
+pub fn retain(ino: u32) {
+    with(|fs| fs.retain(ino));
+}
+
+/// Drop an open descriptor of an inode (fd closed or its process reaped)
+pub fn release(ino: u32) {
+    with(|fs| fs.close(ino));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::fd::flags::{O_CREAT, O_EXCL, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY};
+
+    #[test]
+    fn test_is_tmpfs_path() {
+        assert!(is_tmpfs_path("/tmp"));
+        assert!(is_tmpfs_path("/tmp/a/b"));
+        assert!(!is_tmpfs_path("/tmpfoo"));
+        assert!(!is_tmpfs_path("/bin/init"));
+    }
+
+    #[test]
+    fn test_create_write_read() {
+        let mut fs = Tmpfs::new();
+        assert_eq!(fs.open("/tmp/a", O_RDONLY), Err(Errno::ENOENT));
+
+        let ino = fs.open("/tmp/a", O_RDWR | O_CREAT).unwrap();
+        assert_eq!(fs.write(ino, 0, b"hello"), Ok(5));
+        assert_eq!(fs.write(ino, 7, b"!"), Ok(1));
+        assert_eq!(fs.size(ino), Ok(8));
+        assert_eq!(fs.used(), 8);
+
+        let mut buf = [0xFFu8; 16];
+        assert_eq!(fs.read(ino, 0, &mut buf), Ok(8));
+        assert_eq!(&buf[..8], b"hello\0\0!");
+        assert_eq!(fs.read(ino, 8, &mut buf), Ok(0));
+
+        assert_eq!(fs.open("/tmp/a", O_WRONLY | O_CREAT | O_EXCL), Err(Errno::EEXIST));
+        let again = fs.open("/tmp/a", O_WRONLY | O_TRUNC).unwrap();
+        assert_eq!(again, ino);
+        assert_eq!(fs.size(ino), Ok(0));
+        assert_eq!(fs.used(), 0);
+    }
+
+    #[test]
+    fn test_directories() {
+        let mut fs = Tmpfs::new();
+        assert_eq!(fs.mkdir("/tmp/d"), Ok(()));
+        assert_eq!(fs.mkdir("/tmp/d"), Err(Errno::EEXIST));
+        assert_eq!(fs.mkdir("/tmp/x/y"), Err(Errno::ENOENT));
+
+        let ino = fs.open("/tmp/d/f", O_WRONLY | O_CREAT).unwrap();
+        fs.close(ino);
+        assert_eq!(fs.open("/tmp/d/f/g", O_WRONLY | O_CREAT), Err(Errno::ENOTDIR));
+        assert_eq!(fs.open("/tmp/d", O_WRONLY), Err(Errno::EISDIR));
+        assert!(fs.open("/tmp/d", O_RDONLY).is_ok());
+
+        assert_eq!(fs.read_dir("/tmp").unwrap(), [DirEntry::dir("d")]);
+        assert_eq!(fs.read_dir("/tmp/d/").unwrap(), [DirEntry::file("f", 0)]);
+        assert_eq!(fs.read_dir("/tmp/d/f"), Err(Errno::ENOTDIR));
+
+        assert_eq!(fs.rmdir("/tmp/d"), Err(Errno::ENOTEMPTY));
+        assert_eq!(fs.unlink("/tmp/d"), Err(Errno::EISDIR));
+        assert_eq!(fs.rmdir("/tmp/d/f"), Err(Errno::ENOTDIR));
+        assert_eq!(fs.unlink("/tmp/d/f"), Ok(()));
+        assert_eq!(fs.rmdir("/tmp/d"), Ok(()));
+        assert_eq!(fs.lookup("/tmp/d"), Err(Errno::ENOENT));
+        assert_eq!(fs.rmdir("/tmp"), Err(Errno::EINVAL));
+    }
+
+    #[test]
+    fn test_unlink_while_open() {
+        let mut fs = Tmpfs::new();
+        let ino = fs.open("/tmp/f", O_RDWR | O_CREAT).unwrap();
+        fs.write(ino, 0, b"data").unwrap();
+
+        fs.unlink("/tmp/f").unwrap();
+        assert_eq!(fs.lookup("/tmp/f"), Err(Errno::ENOENT));
+        let mut buf = [0u8; 4];
+        assert_eq!(fs.read(ino, 0, &mut buf), Ok(4));
+        assert_eq!(fs.used(), 4);
+
+        fs.close(ino);
+        assert_eq!(fs.size(ino), Err(Errno::ENOENT));
+        assert_eq!(fs.used(), 0);
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut fs = Tmpfs::new();
+        let ino = fs.open("/tmp/big", O_WRONLY | O_CREAT).unwrap();
+        assert_eq!(fs.truncate(ino, TMPFS_MAX_BYTES as u64 + 1), Err(Errno::ENOSPC));
+        assert_eq!(fs.write(ino, TMPFS_MAX_BYTES as u64, b"x"), Err(Errno::ENOSPC));
+        assert_eq!(fs.used(), 0);
+    }
+
+    #[test]
+    fn test_stat() {
+        let mut fs = Tmpfs::new();
+        let root = fs.stat(ROOT_INODE).unwrap();
+        assert_eq!((root.dev, root.kind, root.size), (FS_TMPFS, DT_DIR, 0));
+
+        let ino = fs.open("/tmp/f", O_RDWR | O_CREAT).unwrap();
+        fs.write(ino, 0, b"abc").unwrap();
+        let st = fs.stat(ino).unwrap();
+        assert_eq!((st.kind, st.inode, st.size), (DT_REG, ino as u64, 3));
+
+        fs.close(ino);
+        fs.unlink("/tmp/f").unwrap();
+        assert_eq!(fs.stat(ino), Err(Errno::ENOENT));
+    }
+
+    #[test]
+    fn test_hard_links() {
+        let mut fs = Tmpfs::new();
+        let ino = fs.open("/tmp/a", O_RDWR | O_CREAT).unwrap();
+        fs.write(ino, 0, b"data").unwrap();
+        fs.close(ino);
+        fs.mkdir("/tmp/d").unwrap();
+
+        assert_eq!(fs.link("/tmp/a", "/tmp/d/b"), Ok(()));
+        assert_eq!(fs.lookup("/tmp/d/b"), Ok(ino));
+        assert_eq!(fs.links(ino), Ok(2));
+        assert_eq!(fs.link("/tmp/a", "/tmp/d/b"), Err(Errno::EEXIST));
+        assert_eq!(fs.link("/tmp/d", "/tmp/e"), Err(Errno::EPERM));
+        assert_eq!(fs.link("/tmp/missing", "/tmp/e"), Err(Errno::ENOENT));
+
+        // The data lives until the last name is removed
+        fs.unlink("/tmp/a").unwrap();
+        assert_eq!(fs.links(ino), Ok(1));
+        let mut buf = [0u8; 4];
+        assert_eq!(fs.read(ino, 0, &mut buf), Ok(4));
+        fs.unlink("/tmp/d/b").unwrap();
+        assert_eq!(fs.stat(ino), Err(Errno::ENOENT));
+        assert_eq!(fs.used(), 0);
+    }
+
+    #[test]
+    fn test_symlinks() {
+        let mut fs = Tmpfs::new();
+        assert_eq!(fs.symlink("/bin/hello", "/tmp/l"), Ok(()));
+        assert_eq!(fs.symlink("x", "/tmp/l"), Err(Errno::EEXIST));
+        assert_eq!(fs.symlink("", "/tmp/m"), Err(Errno::ENOENT));
+        assert_eq!(fs.readlink("/tmp/l").unwrap(), "/bin/hello");
+        assert_eq!(fs.readlink("/tmp"), Err(Errno::EINVAL));
+
+        let ino = fs.lookup("/tmp/l").unwrap();
+        let st = fs.stat(ino).unwrap();
+        assert_eq!((st.kind, st.size), (DT_LNK, 10));
+        assert_eq!(fs.read_dir("/tmp").unwrap(), [DirEntry::symlink("l", 10)]);
+
+        // tmpfs itself never follows a link
+        assert_eq!(fs.open("/tmp/l", O_RDONLY), Err(Errno::ELOOP));
+        assert_eq!(fs.open("/tmp/l/x", O_RDONLY | O_CREAT), Err(Errno::ENOTDIR));
+        assert_eq!(fs.rmdir("/tmp/l"), Err(Errno::ENOTDIR));
+        assert_eq!(fs.unlink("/tmp/l"), Ok(()));
+        assert_eq!(fs.lookup("/tmp/l"), Err(Errno::ENOENT));
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Ramdisk Signature Verification
+//!
+//! This module verifies the embedded ramdisk against an Ed25519 public key
+//! that is compiled into the kernel image. build.rs signs `ramdisk.bin`
+//! when a signing key is provided and embeds the matching public key.
+//!
+//! # Policy
+//!
+//! - **Key embedded, signature valid**: the ramdisk is trusted
+//! - **Key embedded, signature missing or invalid**: spawning from the
+//!   ramdisk is refused unless `ramdisk.allow_unsigned` is on the command line
+//! - **No key embedded** (development build): verification is skipped and
+//!   a warning is printed, at build time and at boot
+//!
+//! The last case fails open: a kernel built without a key runs whatever
+//! ramdisk it carries. Release builds must provision a key.
+//!
+//! # Build Configuration
+//!
+//! - `RUSTUX_RAMDISK_SIGNING_KEY`: path to a 32-byte Ed25519 seed used to
+//!   sign the ramdisk (the public key is derived from it)
+//! - `RUSTUX_RAMDISK_PUBLIC_KEY`: path to a 32-byte public key to embed
+//!   instead of the derived one (for externally signed ramdisks)
+//! - `RUSTUX_RAMDISK_SIGNATURE`: path to the 64-byte detached signature of
+//!   an externally signed `ramdisk.bin`; set instead of
+//!   `RUSTUX_RAMDISK_SIGNING_KEY`
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use ed25519_compact::{PublicKey, Signature};
+use crate::{kerror, kinfo, kwarn};
+
+/// Ed25519 public key length
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+/// Ed25519 signature length
+pub const SIGNATURE_LEN: usize = 64;
+
+/// Command line flag that permits spawning from an unverified ramdisk
+pub const ALLOW_UNSIGNED_FLAG: &str = "ramdisk.allow_unsigned";
+
+/// Public key embedded at build time (all zeros if none was provisioned)
+pub static RAMDISK_PUBLIC_KEY: [u8; PUBLIC_KEY_LEN] =
+    *include_bytes!(concat!(env!("OUT_DIR"), "/ramdisk.pub"));
+
+/// Ramdisk signature produced at build time (all zeros if unsigned)
+pub static RAMDISK_SIGNATURE: [u8; SIGNATURE_LEN] =
+    *include_bytes!(concat!(env!("OUT_DIR"), "/ramdisk.sig"));
+
+/// Result of ramdisk verification
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RamdiskTrust {
+    /// Verification has not run yet
+    Unchecked = 0,
+    /// Signature verified against the embedded key
+    Verified = 1,
+    /// No public key was embedded (development build)
+    NoKey = 2,
+    /// A key is embedded but the ramdisk carries no signature
+    Unsigned = 3,
+    /// The signature does not match the ramdisk contents
+    BadSignature = 4,
+}
+
+impl RamdiskTrust {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => Self::Verified,
+            2 => Self::NoKey,
+            3 => Self::Unsigned,
+            4 => Self::BadSignature,
+            _ => Self::Unchecked,
+        }
+    }
+
+    /// Human-readable description
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unchecked => "unchecked",
+            Self::Verified => "verified",
+            Self::NoKey => "no key embedded",
+            Self::Unsigned => "unsigned",
+            Self::BadSignature => "bad signature",
+        }
+    }
+}
+
+/// Verification result for the global ramdisk
+static RAMDISK_TRUST: AtomicU8 = AtomicU8::new(RamdiskTrust::Unchecked as u8);
+
+/// Verify `data` against `signature` using `public_key`
+///
+/// An all-zero key means no key was provisioned, and an all-zero
+/// signature means the image was not signed.
+pub fn verify_image(
+    data: &[u8],
+    signature: &[u8; SIGNATURE_LEN],
+    public_key: &[u8; PUBLIC_KEY_LEN],
+) -> RamdiskTrust {
+    if public_key.iter().all(|&b| b == 0) {
+        return RamdiskTrust::NoKey;
+    }
+    if signature.iter().all(|&b| b == 0) {
+        return RamdiskTrust::Unsigned;
+    }
+
+    let pk = match PublicKey::from_slice(public_key) {
+        Ok(pk) => pk,
+        Err(_) => return RamdiskTrust::BadSignature,
+    };
+    let sig = match Signature::from_slice(signature) {
+        Ok(sig) => sig,
+        Err(_) => return RamdiskTrust::BadSignature,
+    };
+
+    match pk.verify(data, &sig) {
+        Ok(()) => RamdiskTrust::Verified,
+        Err(_) => RamdiskTrust::BadSignature,
+    }
+}
+
+/// Verify the embedded ramdisk and record the result
+///
+/// Must be called once the ramdisk data is available and before any
+/// process is spawned from it.
+pub fn verify_ramdisk(data: &[u8]) -> RamdiskTrust {
+    let trust = verify_image(data, &RAMDISK_SIGNATURE, &RAMDISK_PUBLIC_KEY);
+    RAMDISK_TRUST.store(trust as u8, Ordering::Release);
+
+    kinfo!("[RAMDISK] Signature check: {}", trust.as_str());
+    match trust {
+        RamdiskTrust::NoKey => {
+            kwarn!("[RAMDISK] WARNING: no public key embedded, ramdisk is not verified");
+        }
+        RamdiskTrust::Unsigned | RamdiskTrust::BadSignature => {
+            if crate::cmdline::has_flag(ALLOW_UNSIGNED_FLAG) {
+                kwarn!("[RAMDISK] WARNING: running unverified ramdisk (override on cmdline)");
+            } else {
+                kerror!("[RAMDISK] Refusing to execute from unverified ramdisk");
+            }
+        }
+        _ => {}
+    }
+
+    trust
+}
+
+/// Get the recorded verification result
+pub fn ramdisk_trust() -> RamdiskTrust {
+    RamdiskTrust::from_u8(RAMDISK_TRUST.load(Ordering::Acquire))
+}
+
+/// Check whether executables may be loaded from the ramdisk
+///
+/// Allowed without a check when no key was embedded (see the module
+/// documentation).
+pub fn spawn_allowed() -> bool {
+    match ramdisk_trust() {
+        RamdiskTrust::Verified => true,
+        // Development build: fail open
+        RamdiskTrust::NoKey => true,
+        RamdiskTrust::Unsigned | RamdiskTrust::BadSignature => {
+            crate::cmdline::has_flag(ALLOW_UNSIGNED_FLAG)
+        }
+        RamdiskTrust::Unchecked => false,
+    }
+}
+
+/// ============================================================================
+/// Tests
+/// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_compact::{KeyPair, Seed};
+
+    fn test_keypair() -> KeyPair {
+        KeyPair::from_seed(Seed::new([7u8; 32]))
+    }
+
+    #[test]
+    fn test_verify_valid_signature() {
+        let kp = test_keypair();
+        let data = b"ramdisk contents";
+        let sig = kp.sk.sign(data, None);
+        assert_eq!(verify_image(data, &*sig, &*kp.pk), RamdiskTrust::Verified);
+    }
+
+    #[test]
+    fn test_verify_tampered_data() {
+        let kp = test_keypair();
+        let sig = kp.sk.sign(b"ramdisk contents", None);
+        assert_eq!(verify_image(b"ramdisk c0ntents", &*sig, &*kp.pk), RamdiskTrust::BadSignature);
+    }
+
+    #[test]
+    fn test_verify_missing_key_or_signature() {
+        let kp = test_keypair();
+        assert_eq!(verify_image(b"x", &[0; SIGNATURE_LEN], &[0; PUBLIC_KEY_LEN]), RamdiskTrust::NoKey);
+        assert_eq!(verify_image(b"x", &[0; SIGNATURE_LEN], &*kp.pk), RamdiskTrust::Unsigned);
+    }
+}
@@ -8,8 +8,16 @@
 //!
 //! This module provides the VFS abstraction for file I/O operations.
 //! It defines the FileOps trait that must be implemented by different
-//! file types (ramdisk files, pipes, etc.).
+//! file types (ramdisk files, pipes, etc.), and lists directories and
+//! looks up file metadata across the ramdisk, devfs, procfs, tmpfs and
+//! mounted FAT volumes.
+//!
+//! Symbolic links live in tmpfs and the ramdisk. Syscalls follow them
+//! with [`follow`] before handing a path to a filesystem, which never
+//! follows links itself.
 
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use crate::fs::ramdisk::{Ramdisk, RamdiskFile, Errno};
 
 /// ============================================================================
@@ -130,6 +138,7 @@ impl FileOps for RamdiskFileOps {
             ramdisk.data.as_ptr().add(data_offset)
         };
 
+        crate::mm::kasan::check_write(buf.as_ptr() as usize, to_read);
         unsafe {
             core::ptr::copy_nonoverlapping(data_ptr, buf.as_mut_ptr(), to_read);
         }
@@ -208,6 +217,486 @@ pub fn open_ramdisk_file(path: &str) -> Result<RamdiskFileOps, Errno> {
     Ok(RamdiskFileOps::new(file))
 }
 
+// ============================================================================
+// Directories
+// ============================================================================
+
+/// Directory entry type: device node
+pub const DT_CHR: u8 = 2;
+
+/// Directory entry type: block device node
+pub const DT_BLK: u8 = 6;
+
+/// Directory entry type: directory
+pub const DT_DIR: u8 = 4;
+
+/// Directory entry type: regular file
+pub const DT_REG: u8 = 8;
+
+/// Directory entry type: symbolic link
+pub const DT_LNK: u8 = 10;
+
+/// Alignment of `READDIR` records
+pub const DIRENT_ALIGN: usize = 8;
+
+/// A directory entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// Name within the directory
+    pub name: String,
+    /// Entry type (`DT_REG`, `DT_DIR`, `DT_CHR`, `DT_BLK` or `DT_LNK`)
+    pub kind: u8,
+    /// Size in bytes (0 for directories, devices and procfs files; the
+    /// target's length for symbolic links)
+    pub size: u64,
+}
+
+impl DirEntry {
+    /// A regular file
+    pub fn file(name: &str, size: u64) -> Self {
+        Self { name: name.to_string(), kind: DT_REG, size }
+    }
+
+    /// A directory
+    pub fn dir(name: &str) -> Self {
+        Self { name: name.to_string(), kind: DT_DIR, size: 0 }
+    }
+
+    /// A device node
+    pub fn device(name: &str) -> Self {
+        Self { name: name.to_string(), kind: DT_CHR, size: 0 }
+    }
+
+    /// A block device node
+    pub fn block_device(name: &str) -> Self {
+        Self { name: name.to_string(), kind: DT_BLK, size: 0 }
+    }
+
+    /// A symbolic link whose target is `size` bytes long
+    pub fn symlink(name: &str, size: u64) -> Self {
+        Self { name: name.to_string(), kind: DT_LNK, size }
+    }
+}
+
+/// Header of a `READDIR` record
+///
+/// Followed by the NUL-terminated name; the record is padded to
+/// [`DIRENT_ALIGN`] bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Dirent {
+    /// Cookie to pass to `READDIR` to continue after this entry
+    pub next_cookie: u64,
+    /// Size in bytes
+    pub size: u64,
+    /// Length of the whole record, including the name and padding
+    pub reclen: u16,
+    /// Entry type (`DT_*`)
+    pub kind: u8,
+    /// Length of the name, excluding the NUL
+    pub name_len: u8,
+    /// Reserved (zero)
+    pub _pad: u32,
+}
+
+/// List a directory
+///
+/// Directories are found by path:
+/// - `/` lists the top level of the ramdisk plus `dev`, `proc`, `tmp` and
+///   the mount points
+/// - `/dev`, `/proc` and `/proc/self` list devfs and procfs
+/// - paths under `/tmp` list tmpfs directories
+/// - paths under a mount point list directories of the mounted volume
+/// - any other path lists the ramdisk files under it (the ramdisk has no
+///   directories of its own; `bin/hello` makes `/bin` one)
+///
+/// # Returns
+///
+/// The entries sorted by name, or `ENOENT` / `ENOTDIR`
+pub fn read_dir(path: &str) -> Result<Vec<DirEntry>, Errno> {
+    let path = path.trim_end_matches('/');
+    let mut entries = match path {
+        "" => {
+            let mounts = crate::fs::mount::names();
+            let mut entries = ramdisk_dir("").unwrap_or_default();
+            entries.retain(|e| !matches!(e.name.as_str(), "dev" | "proc" | "tmp") && !mounts.contains(&e.name));
+            entries.push(DirEntry::dir("dev"));
+            entries.push(DirEntry::dir("proc"));
+            entries.push(DirEntry::dir("tmp"));
+            entries.extend(mounts.iter().map(|name| DirEntry::dir(name)));
+            entries
+        }
+        "/dev" => crate::fs::devfs::list(),
+        "/proc" | "/proc/self" => crate::fs::procfs::list(path)?,
+        _ if crate::fs::tmpfs::is_tmpfs_path(path) => {
+            crate::fs::tmpfs::with(|fs| fs.read_dir(path))?
+        }
+        _ => match crate::fs::mount::find(path) {
+            Some((index, rest)) => crate::fs::mount::with(index, |fs| fs.read_dir(rest))?,
+            None => ramdisk_dir(path)?,
+        },
+    };
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// List a directory of the ramdisk
+fn ramdisk_dir(path: &str) -> Result<Vec<DirEntry>, Errno> {
+    let ramdisk = crate::fs::ramdisk::get_ramdisk()?;
+    let files = (0..ramdisk.file_count() as u32)
+        .filter_map(|i| ramdisk.file_at(i))
+        .map(|f| (ramdisk.file_name(&f), f.size as u64, if f.is_symlink() { DT_LNK } else { DT_REG }));
+    list_prefix(files, path.trim_start_matches('/'))
+}
+
+/// Entries directly under `dir` in a flat list of file paths, sizes and
+/// types (`DT_REG` or `DT_LNK`)
+///
+/// A path with more components below `dir` adds a directory entry for its
+/// first component. Fails with `ENOTDIR` if `dir` is itself a file and with
+/// `ENOENT` if nothing lives under it (`""`, the root, may be empty).
+fn list_prefix<'a>(
+    files: impl Iterator<Item = (&'a str, u64, u8)>,
+    dir: &str,
+) -> Result<Vec<DirEntry>, Errno> {
+    let mut entries: Vec<DirEntry> = Vec::new();
+    let mut found = dir.is_empty();
+
+    for (name, size, kind) in files {
+        if name == dir {
+            return Err(Errno::ENOTDIR);
+        }
+        let rest = if dir.is_empty() {
+            name
+        } else {
+            match name.strip_prefix(dir).and_then(|r| r.strip_prefix('/')) {
+                Some(rest) => rest,
+                None => continue,
+            }
+        };
+        found = true;
+
+        match rest.split_once('/') {
+            Some((sub, _)) => {
+                if !entries.iter().any(|e| e.name == sub) {
+                    entries.push(DirEntry::dir(sub));
+                }
+            }
+            None => entries.push(DirEntry { name: rest.to_string(), kind, size }),
+        }
+    }
+
+    if found {
+        Ok(entries)
+    } else {
+        Err(Errno::ENOENT)
+    }
+}
+
+/// Encode directory entries as `READDIR` records
+///
+/// Starts at entry number `cookie` and stops before the first record that
+/// does not fit in `max` bytes.
+pub fn encode_dirents(entries: &[DirEntry], cookie: u64, max: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    let start = usize::try_from(cookie).unwrap_or(usize::MAX);
+
+    for (i, entry) in entries.iter().enumerate().skip(start) {
+        let name = &entry.name.as_bytes()[..core::cmp::min(entry.name.len(), u8::MAX as usize)];
+        let unpadded = core::mem::size_of::<Dirent>() + name.len() + 1;
+        let reclen = (unpadded + DIRENT_ALIGN - 1) & !(DIRENT_ALIGN - 1);
+        if out.len() + reclen > max {
+            break;
+        }
+
+        let record = out.len();
+        out.extend_from_slice(&(i as u64 + 1).to_ne_bytes());
+        out.extend_from_slice(&entry.size.to_ne_bytes());
+        out.extend_from_slice(&(reclen as u16).to_ne_bytes());
+        out.push(entry.kind);
+        out.push(name.len() as u8);
+        out.extend_from_slice(&0u32.to_ne_bytes());
+        out.extend_from_slice(name);
+        out.resize(record + reclen, 0);
+    }
+    out
+}
+
+// ============================================================================
+// Metadata
+// ============================================================================
+
+/// Filesystem IDs ([`Stat::dev`])
+pub const FS_RAMDISK: u32 = 1;
+pub const FS_DEVFS: u32 = 2;
+pub const FS_PROCFS: u32 = 3;
+pub const FS_TMPFS: u32 = 4;
+pub const FS_FAT: u32 = 5;
+
+/// Modification time of ramdisk files: they are built before boot
+pub const RAMDISK_MTIME: u64 = 0;
+
+/// File metadata returned by `STAT` and `FSTAT`
+///
+/// Same layout on every architecture. `(dev, inode)` identifies a file.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stat {
+    /// Filesystem (`FS_*`)
+    pub dev: u32,
+    /// File type (`DT_REG`, `DT_DIR`, `DT_CHR`, `DT_BLK` or `DT_LNK`)
+    pub kind: u8,
+    /// Reserved (zero)
+    pub _pad: [u8; 3],
+    /// Inode number, unique within the filesystem
+    pub inode: u64,
+    /// Size in bytes (0 for directories, character devices and procfs
+    /// files; the capacity of a block device)
+    pub size: u64,
+    /// Last modification in nanoseconds on the `CLOCK_GET` clock
+    /// (0 for files that never changed since boot)
+    pub mtime_ns: u64,
+}
+
+impl Stat {
+    /// Metadata of a file of type `kind`
+    pub const fn new(dev: u32, kind: u8, inode: u64, size: u64, mtime_ns: u64) -> Self {
+        Self { dev, kind, _pad: [0; 3], inode, size, mtime_ns }
+    }
+}
+
+/// Look up file metadata by path
+///
+/// Paths resolve as for [`read_dir`], and also name files. A symbolic
+/// link is described itself, not its target.
+///
+/// # Returns
+///
+/// The metadata, or `ENOENT` / `ENOTDIR`
+pub fn stat(path: &str) -> Result<Stat, Errno> {
+    use crate::fs::{devfs, mount, procfs, tmpfs};
+
+    let path = path.trim_end_matches('/');
+    match path {
+        "" => Ok(Stat::new(FS_RAMDISK, DT_DIR, ramdisk_dir_inode(""), 0, RAMDISK_MTIME)),
+        "/dev" => Ok(Stat::new(FS_DEVFS, DT_DIR, devfs::ROOT_INODE, 0, 0)),
+        _ if devfs::is_devfs_path(path) => Ok(devfs::stat(devfs::lookup(path)?)),
+        "/proc" => Ok(Stat::new(FS_PROCFS, DT_DIR, procfs::ROOT_INODE, 0, 0)),
+        "/proc/self" => Ok(Stat::new(FS_PROCFS, DT_DIR, procfs::SELF_INODE, 0, 0)),
+        _ if procfs::is_procfs_path(path) => Ok(procfs::stat(procfs::lookup(path)?)),
+        _ if tmpfs::is_tmpfs_path(path) => tmpfs::with(|fs| fs.lookup(path).and_then(|ino| fs.stat(ino))),
+        _ => match mount::find(path) {
+            Some((index, rest)) => mount::with(index, |fs| fs.lookup(rest).and_then(|ino| fs.stat(ino))),
+            None => ramdisk_path_stat(path),
+        },
+    }
+}
+
+/// Metadata of ramdisk file number `index` (its inode number)
+pub fn ramdisk_stat(index: u32) -> Result<Stat, Errno> {
+    let ramdisk = crate::fs::ramdisk::get_ramdisk()?;
+    let file = ramdisk.file_at(index).ok_or(Errno::ENOENT)?;
+    let kind = if file.is_symlink() { DT_LNK } else { DT_REG };
+    Ok(Stat::new(FS_RAMDISK, kind, index as u64, file.size as u64, RAMDISK_MTIME))
+}
+
+/// Metadata of a ramdisk file or (implied) directory by path
+fn ramdisk_path_stat(path: &str) -> Result<Stat, Errno> {
+    let ramdisk = crate::fs::ramdisk::get_ramdisk()?;
+    let name = path.trim_start_matches('/');
+    let index = (0..ramdisk.file_count() as u32)
+        .find(|&i| ramdisk.file_at(i).is_some_and(|f| ramdisk.file_name(&f) == name));
+    match index {
+        Some(index) => ramdisk_stat(index),
+        None => {
+            ramdisk_dir(path)?;
+            Ok(Stat::new(FS_RAMDISK, DT_DIR, ramdisk_dir_inode(name), 0, RAMDISK_MTIME))
+        }
+    }
+}
+
+/// Inode number of a ramdisk directory
+///
+/// The ramdisk only stores files, numbered by index; a directory gets a
+/// hash of its path (FNV-1a) with the top bit set, so it never collides
+/// with a file.
+fn ramdisk_dir_inode(path: &str) -> u64 {
+    let hash = path.bytes().fold(0xCBF2_9CE4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01B3));
+    hash | (1 << 63)
+}
+
+// ============================================================================
+// Paths
+// ============================================================================
+
+/// Longest path, in bytes, a syscall accepts or resolves to
+pub const PATH_MAX: usize = 256;
+
+/// Make `path` absolute against the directory `cwd`
+///
+/// An absolute `path` ignores `cwd`. Empty and `.` components are
+/// dropped and `..` goes up one level (staying at `/`). Resolution is
+/// lexical: nothing is looked up, so the result may not exist.
+///
+/// # Arguments
+///
+/// * `cwd` - Absolute, normalized directory (a process's `cwd`)
+/// * `path` - Path as given by userspace
+///
+/// # Returns
+///
+/// The normalized absolute path (`/` for the root), or `ENOENT` if
+/// `path` is empty, or `ENAMETOOLONG` if the result exceeds [`PATH_MAX`]
+pub fn resolve(cwd: &str, path: &str) -> Result<String, Errno> {
+    if path.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+
+    let base = if path.starts_with('/') { "" } else { cwd };
+    let mut parts: Vec<&str> = Vec::new();
+    for part in base.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    let mut resolved = String::new();
+    for part in &parts {
+        resolved.push('/');
+        resolved.push_str(part);
+    }
+    if resolved.is_empty() {
+        resolved.push('/');
+    }
+    if resolved.len() > PATH_MAX {
+        return Err(Errno::ENAMETOOLONG);
+    }
+    Ok(resolved)
+}
+
+/// Most symbolic links followed while resolving one path
+pub const SYMLOOP_MAX: usize = 8;
+
+/// Replace the symbolic links in a resolved path by their targets
+///
+/// Each component is checked from the root down; a link is replaced by
+/// its target, resolved against the link's directory, and the walk starts
+/// over. The last component is only followed if `follow_last` is set, as
+/// for `STAT` but not `LSTAT`. `..` was already applied by [`resolve`], so
+/// it goes up from the link, not from its target.
+///
+/// # Returns
+///
+/// The path with no links left (other than a last component kept by
+/// `follow_last == false`), or `ELOOP` after [`SYMLOOP_MAX`] links
+pub fn follow(path: &str, follow_last: bool) -> Result<String, Errno> {
+    follow_with(path, follow_last, link_target)
+}
+
+/// [`follow`] with `target` looking up whether a path is a link
+fn follow_with(
+    path: &str,
+    follow_last: bool,
+    target: impl Fn(&str) -> Option<String>,
+) -> Result<String, Errno> {
+    let mut path = path.to_string();
+    let mut links = 0;
+    'walk: loop {
+        // End of each component: before each '/' after the root, and the end
+        let ends: Vec<usize> = path
+            .match_indices('/')
+            .map(|(i, _)| i)
+            .filter(|&i| i > 0)
+            .chain(core::iter::once(path.len()))
+            .collect();
+        for end in ends {
+            let last = end == path.len();
+            if end <= 1 || (last && !follow_last) {
+                continue;
+            }
+            let Some(link) = target(&path[..end]) else { continue };
+
+            links += 1;
+            if links > SYMLOOP_MAX {
+                return Err(Errno::ELOOP);
+            }
+            let dir = &path[..path[..end].rfind('/').unwrap_or(0).max(1)];
+            let mut next = resolve(dir, &link)?;
+            if !last {
+                next = resolve(&next, &path[end + 1..])?;
+            }
+            path = next;
+            continue 'walk;
+        }
+        return Ok(path);
+    }
+}
+
+/// Target of the symbolic link at a resolved path, if it is one
+fn link_target(path: &str) -> Option<String> {
+    use crate::fs::{devfs, mount, procfs, tmpfs};
+
+    if tmpfs::is_tmpfs_path(path) {
+        tmpfs::with(|fs| fs.readlink(path).ok())
+    } else if devfs::is_devfs_path(path) || procfs::is_procfs_path(path) || mount::find(path).is_some() {
+        None
+    } else {
+        let ramdisk = crate::fs::ramdisk::get_ramdisk().ok()?;
+        let file = ramdisk.find_file(path).filter(RamdiskFile::is_symlink)?;
+        core::str::from_utf8(ramdisk.file_data(&file)).ok().map(String::from)
+    }
+}
+
+/// Read the target of a symbolic link
+///
+/// # Returns
+///
+/// The target as stored, or `ENOENT` if nothing is at `path`, or `EINVAL`
+/// if it is not a symbolic link
+pub fn readlink(path: &str) -> Result<String, Errno> {
+    match link_target(path) {
+        Some(target) => Ok(target),
+        None => stat(path).and(Err(Errno::EINVAL)),
+    }
+}
+
+/// Create a symbolic link at `path` pointing to `target`
+///
+/// Only `/tmp` can hold links: this fails with `EPERM` on a mounted
+/// filesystem (FAT has no way to store one), and with `EROFS` elsewhere.
+pub fn symlink(target: &str, path: &str) -> Result<(), Errno> {
+    use crate::fs::{mount, tmpfs};
+
+    if mount::find(path).is_some() {
+        return Err(Errno::EPERM);
+    }
+    if !tmpfs::is_tmpfs_path(path) {
+        return Err(Errno::EROFS);
+    }
+    tmpfs::with(|fs| fs.symlink(target, path))
+}
+
+/// Create a hard link: `new` names the same file as `old`
+///
+/// Both must be in tmpfs: `EXDEV` if only `new` is, `EROFS` if `new` is
+/// not.
+pub fn link(old: &str, new: &str) -> Result<(), Errno> {
+    use crate::fs::tmpfs;
+
+    if !tmpfs::is_tmpfs_path(new) {
+        return Err(Errno::EROFS);
+    }
+    if !tmpfs::is_tmpfs_path(old) {
+        stat(old)?;
+        return Err(Errno::EXDEV);
+    }
+    tmpfs::with(|fs| fs.link(old, new))
+}
+
 /// ============================================================================
 /// Tests
 /// ============================================================================
@@ -229,7 +718,7 @@ mod tests {
             name_offset: 0,
             data_offset: 32,
             size: 100,
-            _pad: 0,
+            flags: 0,
         };
 
         let mut ops = RamdiskFileOps::new(file);
@@ -242,4 +731,97 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 100);
     }
+
+    #[test]
+    fn test_list_prefix() {
+        let files = [
+            ("test.txt", 5, DT_REG),
+            ("bin/hello", 100, DT_REG),
+            ("bin/counter", 200, DT_REG),
+            ("bin/sub/x", 1, DT_REG),
+            ("bin/hi", 5, DT_LNK),
+        ];
+
+        let root = list_prefix(files.iter().copied(), "").unwrap();
+        assert_eq!(root, [DirEntry::file("test.txt", 5), DirEntry::dir("bin")]);
+
+        let bin = list_prefix(files.iter().copied(), "bin").unwrap();
+        assert_eq!(
+            bin,
+            [DirEntry::file("hello", 100), DirEntry::file("counter", 200), DirEntry::dir("sub"), DirEntry::symlink("hi", 5)]
+        );
+
+        assert_eq!(list_prefix(files.iter().copied(), "bi"), Err(Errno::ENOENT));
+        assert_eq!(list_prefix(files.iter().copied(), "test.txt"), Err(Errno::ENOTDIR));
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("/", "test.txt").unwrap(), "/test.txt");
+        assert_eq!(resolve("/bin", "hello").unwrap(), "/bin/hello");
+        assert_eq!(resolve("/bin", "/dev/null").unwrap(), "/dev/null");
+        assert_eq!(resolve("/bin/sub", "../x/./y/").unwrap(), "/bin/x/y");
+        assert_eq!(resolve("/bin", "../../..").unwrap(), "/");
+        assert_eq!(resolve("/tmp", ".").unwrap(), "/tmp");
+        assert_eq!(resolve("/", "//a//b").unwrap(), "/a/b");
+
+        assert_eq!(resolve("/", ""), Err(Errno::ENOENT));
+        let long = "a/".repeat(PATH_MAX / 2 + 1);
+        assert_eq!(resolve("/", &long), Err(Errno::ENAMETOOLONG));
+    }
+
+    #[test]
+    fn test_follow() {
+        let links = |path: &str| match path {
+            "/bin/sh" => Some("shell".to_string()),
+            "/tmp/bin" => Some("/bin".to_string()),
+            "/tmp/up" => Some("../bin/sh".to_string()),
+            "/tmp/loop" => Some("loop".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(follow_with("/bin/sh", true, links).unwrap(), "/bin/shell");
+        assert_eq!(follow_with("/bin/sh", false, links).unwrap(), "/bin/sh");
+        assert_eq!(follow_with("/tmp/bin/sh", false, links).unwrap(), "/bin/sh");
+        assert_eq!(follow_with("/tmp/bin/sh", true, links).unwrap(), "/bin/shell");
+        assert_eq!(follow_with("/tmp/up", true, links).unwrap(), "/bin/shell");
+        assert_eq!(follow_with("/", true, links).unwrap(), "/");
+        assert_eq!(follow_with("/tmp/loop", true, links), Err(Errno::ELOOP));
+        assert_eq!(follow_with("/tmp/loop/x", false, links), Err(Errno::ELOOP));
+        assert_eq!(follow_with("/tmp/loop", false, links).unwrap(), "/tmp/loop");
+    }
+
+    #[test]
+    fn test_stat_layout() {
+        assert_eq!(core::mem::size_of::<Stat>(), 32);
+        let stat = Stat::new(FS_TMPFS, DT_REG, 7, 100, 5);
+        assert_eq!((stat.dev, stat.kind, stat.inode, stat.size, stat.mtime_ns), (FS_TMPFS, DT_REG, 7, 100, 5));
+
+        // Directory inodes are stable and never look like file indices
+        assert_eq!(ramdisk_dir_inode("bin"), ramdisk_dir_inode("bin"));
+        assert_ne!(ramdisk_dir_inode("bin"), ramdisk_dir_inode("bin/sub"));
+        assert!(ramdisk_dir_inode("") > u32::MAX as u64);
+    }
+
+    #[test]
+    fn test_encode_dirents() {
+        assert_eq!(core::mem::size_of::<Dirent>(), 24);
+
+        let entries = [DirEntry::file("hello", 100), DirEntry::dir("sub")];
+        let out = encode_dirents(&entries, 0, 4096);
+        assert_eq!(out.len(), 32 + 32);
+        assert_eq!(u64::from_ne_bytes(out[0..8].try_into().unwrap()), 1);
+        assert_eq!(u64::from_ne_bytes(out[8..16].try_into().unwrap()), 100);
+        assert_eq!(u16::from_ne_bytes(out[16..18].try_into().unwrap()), 32);
+        assert_eq!(out[18], DT_REG);
+        assert_eq!(out[19], 5);
+        assert_eq!(&out[24..30], b"hello\0");
+        assert_eq!(out[32 + 18], DT_DIR);
+
+        // Only whole records, resuming from a cookie
+        assert_eq!(encode_dirents(&entries, 0, 40).len(), 32);
+        let rest = encode_dirents(&entries, 1, 40);
+        assert_eq!(u64::from_ne_bytes(rest[0..8].try_into().unwrap()), 2);
+        assert!(encode_dirents(&entries, 2, 4096).is_empty());
+    }
 }
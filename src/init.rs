@@ -31,39 +31,13 @@
 
 use core::sync::atomic::{AtomicUsize, Ordering};
 use crate::arch::amd64::mmu::PAddr;
+use crate::{kdebug, kinfo, kwarn};
 
-const QEMU_DEBUGCON_PORT: u16 = 0xE9;
-
-fn qemu_debugcon_write_byte(b: u8) {
-    unsafe {
-        core::arch::asm!("out dx, al", in("dx") QEMU_DEBUGCON_PORT, in("al") b, options(nostack, nomem));
-    }
-}
-
-fn debug_print(s: &str) {
-    for byte in s.bytes() {
-        qemu_debugcon_write_byte(byte);
-    }
-}
-
-fn print_hex(mut n: u64) {
-    if n == 0 {
-        qemu_debugcon_write_byte(b'0');
-        return;
-    }
-    let mut buf = [0u8; 16];
-    let mut i = 0;
-    while n > 0 {
-        let digit = (n % 16) as u8;
-        buf[i] = if digit < 10 { b'0' + digit } else { b'A' + digit - 10 };
-        n /= 16;
-        i += 1;
-    }
-    while i > 0 {
-        i -= 1;
-        qemu_debugcon_write_byte(buf[i]);
-    }
-}
+/// Physical base of the kernel heap, right after the kernel zone
+///
+/// The heap's size is chosen at boot (`mm::boot_heap_size()`); the user
+/// zone starts where it ends.
+const HEAP_PADDR: u64 = 0x0100_0000;
 
 /// Boot allocator - simple bump allocator for early boot
 ///
@@ -263,19 +237,13 @@ fn init_early() {
         BOOT_ALLOCATOR.init(BOOT_ALLOC_BUFFER.as_ptr() as usize);
 
         // Debug print
-        let msg = b"[INIT] Boot allocator initialized\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[INIT] Boot allocator initialized");
 
         // Set up the boot allocator for PMM
         pmm::set_boot_allocator(boot_alloc_callback);
 
         // Debug print
-        let msg = b"[INIT] Calling pmm_init_early...\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[INIT] Calling pmm_init_early...");
 
         // Initialize PMM - we need memory allocation before anything else
         //
@@ -288,18 +256,19 @@ fn init_early() {
         //   - Page tables
         //   - Kernel metadata structures
         //
-        // Heap Zone: 0x01000000 - 0x01FFFFFF (16 MB)
+        // Heap Zone: 0x01000000 - heap end (16-48 MB, sized from RAM)
         //   - Kernel heap (metadata, allocations)
         //
-        // User Zone: 0x02000000 - 0x07FE0000 (96 MB)
+        // User Zones: every usable region of the firmware memory map
+        // above the heap (heap end - +96 MB if no map was recorded)
         //   - VMO backing pages
         //   - User data
         //   - Clone destinations
         //
         const KERNEL_ZONE_BASE: u64 = 0x0020_0000;   // 2MB (after kernel image)
         const KERNEL_ZONE_SIZE: usize = 14 * 1024 * 1024;  // 14MB
-        const USER_ZONE_BASE: u64 = 0x0200_0000;    // 32MB (AFTER heap zone)
         const USER_ZONE_SIZE: usize = 96 * 1024 * 1024;   // 96MB (reduced to make room for heap)
+        let user_zone_base = HEAP_PADDR + crate::mm::boot_heap_size() as u64;
 
         // Add kernel zone arena
         let kernel_info = pmm::ArenaInfo::new(
@@ -311,15 +280,20 @@ fn init_early() {
         );
         let _ = pmm::pmm_add_arena(kernel_info);
 
-        // Add user zone arena
-        let user_info = pmm::ArenaInfo::new(
-            b"user\0\0\0\0\0\0\0\0\0\0\0\0",
-            pmm::ARENA_FLAG_LOW_MEM | pmm::ARENA_FLAG_USER,
-            1, // lower priority
-            USER_ZONE_BASE,
-            USER_ZONE_SIZE,
-        );
-        let _ = pmm::pmm_add_arena(user_info);
+        // Add user zone arenas
+        let map_arenas = pmm::pmm_add_memory_map(user_zone_base);
+        if map_arenas == 0 {
+            let user_info = pmm::ArenaInfo::new(
+                b"user\0\0\0\0\0\0\0\0\0\0\0\0",
+                pmm::ARENA_FLAG_LOW_MEM | pmm::ARENA_FLAG_USER,
+                1, // lower priority
+                user_zone_base,
+                USER_ZONE_SIZE,
+            );
+            let _ = pmm::pmm_add_arena(user_info);
+        }
+
+        kinfo!("[INIT] Memory map arenas: {:x}", map_arenas);
 
         // CRITICAL: Reserve kernel stack pages in the PMM
         // The kernel stack is at 0x200000 with size 0x40000 (256KB = 64 pages)
@@ -328,20 +302,17 @@ fn init_early() {
         const KERNEL_STACK_PAGES: usize = 64;          // 256KB
         let _ = pmm::pmm_reserve_pages(KERNEL_STACK_BASE, KERNEL_STACK_PAGES);
 
-        // Debug print
-        let msg = b"[INIT] PMM init complete, free pages: \n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
+        // The crash dump region must be at the same address on every boot
+        if let Some(region) = crate::crashdump::reserve() {
+            kinfo!("[INIT] Crash dump region at {:#x}", region.base);
         }
 
-        // Print number of free pages
-        let free_pages = pmm::pmm_count_free_pages();
-        print_hex(free_pages);
+        crate::mm::watermark::init_thresholds();
+        crate::mm::reclaim::init_options();
+        crate::mm::wss::init_options();
+        pmm::pmm_track_usage();
 
-        let msg = b"\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+        kinfo!("[INIT] PMM init complete, free pages: {:x}", pmm::pmm_count_free_pages());
 
         INIT_STATE = InitState::Early;
     }
@@ -386,15 +357,7 @@ fn init_memory() {
 
         unsafe {
             // Debug print before heap init
-            let msg = b"[INIT] Starting heap initialization...\n";
-            for &byte in msg {
-                core::arch::asm!(
-                    "out dx, al",
-                    in("dx") 0xE9u16,
-                    in("al") byte,
-                    options(nomem, nostack)
-                );
-            }
+            kinfo!("[INIT] Starting heap initialization...");
 
             // TODO: Get actual memory map from UEFI
             // WORKAROUND: PMM has a bug where it only allocates 1 page
@@ -407,33 +370,28 @@ fn init_memory() {
             //
             // FIX: The heap was consuming most of the kernel zone, leaving no pages
             // for page tables. Moved heap to start AFTER kernel zone ends.
-            // The heap (kernel metadata) is now in a separate "heap zone" at 0x01000000.
-            const HEAP_PADDR: u64 = 0x0100_0000;  // 16MB physical address (AFTER kernel zone)
-            const HEAP_SIZE: usize = 16 * 1024 * 1024; // 16MB heap (reduced to avoid consuming too much memory)
+            // The heap (kernel metadata) is now in a separate "heap zone" at HEAP_PADDR,
+            // an eighth of RAM between 16MB and 48MB unless `heap.mb=` overrides it.
+            let heap_size = crate::mm::boot_heap_size();
 
             let heap_start_vaddr = pmm::paddr_to_vaddr(HEAP_PADDR);
 
-            let msg = b"[INIT] Using hardcoded heap at 0x";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-            print_hex(heap_start_vaddr as u64);
-            let msg = b", size: 0x4000000 (64MB)\n[INIT] Initializing heap...\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
+            kinfo!("[INIT] Using heap at {:#x}, size: {:#x}", heap_start_vaddr, heap_size);
+            kinfo!("[INIT] Initializing heap...");
 
             // Initialize the heap
-            crate::mm::heap_init_aligned(heap_start_vaddr as usize, HEAP_SIZE);
+            crate::mm::heap_init_aligned(heap_start_vaddr as usize, heap_size);
 
             // Reserve the heap pages in the PMM so they won't be allocated for other uses
-            // Heap size: 16MB = 4096 pages
-            const HEAP_PAGES: usize = 4096;
-            let _ = pmm::pmm_reserve_pages(HEAP_PADDR, HEAP_PAGES);
+            let _ = pmm::pmm_reserve_pages(HEAP_PADDR, heap_size / crate::mm::PAGE_SIZE);
 
-            let msg = b"[INIT] Heap initialized successfully (16MB)\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
+            kinfo!("[INIT] Heap initialized successfully");
+
+            // Kernel virtual mappings; must precede the first address space
+            if crate::mm::vmm::init().is_ok() {
+                kinfo!("[INIT] VMM ready, vmalloc at {:#x}", crate::arch::amd64::mm::layout::VMALLOC.start);
+            } else {
+                kwarn!("[INIT] WARNING: VMM not initialized, kernel stacks have no guard pages");
             }
 
             INIT_STATE = InitState::VM;
@@ -476,58 +434,26 @@ fn init_late() {
     //}
 
     // DEBUG: Prove we reached init_late
-    unsafe {
-        let msg = b"[INIT] Reached init_late()\n";
-        for &b in msg {
-            core::arch::asm!("out dx, al",
-                in("dx") 0xE9u16,
-                in("al") b,
-                options(nomem, nostack));
-        }
-    }
+    kdebug!("[INIT] Reached init_late()");
 
     // Test userspace execution (Phase 4A)
     #[cfg(feature = "userspace_test")]
     {
         // DEBUG: Before userspace_exec_test call
-        unsafe {
-            let msg = b"[INIT] BEFORE userspace_exec_test call\n";
-            for &b in msg {
-                core::arch::asm!("out dx, al",
-                    in("dx") 0xE9u16,
-                    in("al") b,
-                    options(nomem, nostack));
-            }
-        }
+        kdebug!("[INIT] BEFORE userspace_exec_test call");
 
         unsafe {
             crate::exec::userspace_exec_test::test_userspace_execution();
         }
 
         // DEBUG: After userspace_exec_test call (should never reach here)
-        unsafe {
-            let msg = b"[INIT] AFTER userspace_exec_test call (UNREACHABLE)\n";
-            for &b in msg {
-                core::arch::asm!("out dx, al",
-                    in("dx") 0xE9u16,
-                    in("al") b,
-                    options(nomem, nostack));
-            }
-        }
+        kwarn!("[INIT] AFTER userspace_exec_test call (UNREACHABLE)");
     }
 
     #[cfg(not(feature = "userspace_test"))]
     {
         // DEBUG: Feature gate not enabled
-        unsafe {
-            let msg = b"[INIT] userspace_test feature NOT enabled - skipping test\n";
-            for &b in msg {
-                core::arch::asm!("out dx, al",
-                    in("dx") 0xE9u16,
-                    in("al") b,
-                    options(nomem, nostack));
-            }
-        }
+        kinfo!("[INIT] userspace_test feature NOT enabled - skipping test");
     }
 
     unsafe {
@@ -555,14 +481,11 @@ pub fn kernel_running() {
 /// This is the entry point for idle threads.
 /// When there's no work to do, the idle thread runs.
 pub extern "C" fn idle_thread_entry(_cpu_id: usize) -> ! {
-    // TODO: Implement proper idle loop
     loop {
         // TODO: Check for pending work
-        // If no work, halt the CPU until interrupt
-        // Repeat
 
-        // For now, just spin
-        core::hint::spin_loop();
+        // Enter a C-state (or halt) until the next interrupt
+        crate::arch::amd64::power::idle();
     }
 }
 
@@ -0,0 +1,364 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Interrupt Affinity and Balancing
+//!
+//! Device interrupts are routed to a single CPU. This module keeps the
+//! routing for every registered vector, lets it be changed at runtime
+//! ([`set_affinity`]) and spreads busy vectors across CPUs ([`balance`]).
+//!
+//! # Routing
+//!
+//! - **I/O APIC** vectors ([`register_ioapic`]) are moved by rewriting
+//!   the destination field of the GSI's redirection entry.
+//! - **MSI** vectors ([`register_msi`]) are moved by handing a new
+//!   message address/data pair to a driver-supplied function, which
+//!   writes it into the device's MSI capability.
+//!
+//! # Statistics
+//!
+//! Every interrupt is counted per CPU and per vector ([`account`], called
+//! via `kcounters::record_irq`). [`irq_count`] and [`cpu_irq_total`]
+//! expose the counts; the balancer works from their change since the
+//! previous pass.
+//!
+//! # Balancing Policy
+//!
+//! Every [`BALANCE_INTERVAL_TICKS`] timer ticks, vectors that fired at
+//! least [`HIGH_RATE_THRESHOLD`] times in the interval are handed out,
+//! busiest first, to the online CPU with the least interrupt load. Quiet
+//! vectors are never moved.
+//!
+//! CPUs are numbered 0..[`MAX_CPUS`]; CPU 0 is the boot CPU. Other CPUs
+//! are added with [`cpu_online`] as they are brought up.
+
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use crate::arch::amd64::apic;
+use crate::arch::amd64::mm::RxStatus;
+use crate::sync::SpinMutex;
+
+/// Maximum number of CPUs tracked
+pub const MAX_CPUS: usize = 8;
+
+/// Number of interrupt vectors
+pub const NUM_VECTORS: usize = 256;
+
+/// Timer ticks between balancing passes
+pub const BALANCE_INTERVAL_TICKS: u64 = 1000;
+
+/// Interrupts per interval above which a vector is balanced
+pub const HIGH_RATE_THRESHOLD: u64 = 100;
+
+/// Function that programs a device's MSI address/data registers
+pub type MsiProgramFn = fn(address: u32, data: u32);
+
+/// Where a vector comes from (and how to re-target it)
+#[derive(Clone, Copy)]
+pub enum IrqSource {
+    /// I/O APIC redirection entry for a GSI
+    IoApic { gsi: u32 },
+    /// Message-signalled interrupt
+    Msi { program: MsiProgramFn },
+}
+
+/// Routing state for one vector
+#[derive(Clone, Copy)]
+struct IrqRoute {
+    source: IrqSource,
+    /// CPU the vector is delivered to
+    cpu: u8,
+    /// Total count at the previous balancing pass
+    last_total: u64,
+}
+
+/// Per-CPU, per-vector interrupt counts
+static IRQ_COUNTS: [[AtomicU64; NUM_VECTORS]; MAX_CPUS] =
+    [const { [const { AtomicU64::new(0) }; NUM_VECTORS] }; MAX_CPUS];
+
+/// Routing table, indexed by vector
+static ROUTES: SpinMutex<[Option<IrqRoute>; NUM_VECTORS]> = SpinMutex::new([None; NUM_VECTORS]);
+
+/// Bitmask of online CPUs (the boot CPU is always online)
+static ONLINE_CPUS: AtomicU64 = AtomicU64::new(1);
+
+/// Local APIC ID of each CPU
+static APIC_IDS: [AtomicU8; MAX_CPUS] = [const { AtomicU8::new(0) }; MAX_CPUS];
+
+/// Timer ticks since the last balancing pass
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+// ============================================================================
+// CPUs
+// ============================================================================
+
+/// Record the boot CPU's APIC ID
+///
+/// Must be called after the Local APIC is initialized.
+pub fn init() {
+    APIC_IDS[0].store(apic::apic_local_id(), Ordering::Relaxed);
+}
+
+/// Mark a CPU online so interrupts may be routed to it
+pub fn cpu_online(cpu: usize, apic_id: u8) -> Result<(), RxStatus> {
+    if cpu >= MAX_CPUS {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    APIC_IDS[cpu].store(apic_id, Ordering::Relaxed);
+    ONLINE_CPUS.fetch_or(1 << cpu, Ordering::AcqRel);
+    Ok(())
+}
+
+/// Check whether a CPU is online
+pub fn is_cpu_online(cpu: usize) -> bool {
+    cpu < MAX_CPUS && ONLINE_CPUS.load(Ordering::Acquire) & (1 << cpu) != 0
+}
+
+/// Local APIC ID of an online CPU (the IPI destination)
+pub fn apic_id(cpu: usize) -> Option<u8> {
+    is_cpu_online(cpu).then(|| APIC_IDS[cpu].load(Ordering::Relaxed))
+}
+
+/// Get the CPU number of the running CPU
+pub fn current_cpu() -> usize {
+    let online = ONLINE_CPUS.load(Ordering::Relaxed);
+    if online == 1 {
+        return 0;
+    }
+    let id = apic::apic_local_id();
+    (0..MAX_CPUS)
+        .find(|&cpu| online & (1 << cpu) != 0 && APIC_IDS[cpu].load(Ordering::Relaxed) == id)
+        .unwrap_or(0)
+}
+
+// ============================================================================
+// Routing
+// ============================================================================
+
+/// Register an I/O APIC-routed vector (initially delivered to CPU 0)
+pub fn register_ioapic(vector: u8, gsi: u32) {
+    register(vector, IrqSource::IoApic { gsi });
+}
+
+/// Register an MSI vector (initially delivered to CPU 0)
+///
+/// `program` is called immediately with the CPU 0 message and again
+/// whenever the vector moves.
+pub fn register_msi(vector: u8, program: MsiProgramFn) {
+    program(apic::msi_address(APIC_IDS[0].load(Ordering::Relaxed)), apic::msi_data(vector));
+    register(vector, IrqSource::Msi { program });
+}
+
+fn register(vector: u8, source: IrqSource) {
+    let last_total = vector_total(vector);
+    ROUTES.lock()[vector as usize] = Some(IrqRoute { source, cpu: 0, last_total });
+}
+
+/// Forget a vector's routing (when its driver is unloaded)
+pub fn unregister(vector: u8) {
+    ROUTES.lock()[vector as usize] = None;
+}
+
+/// Get the CPU a vector is delivered to
+pub fn affinity(vector: u8) -> Option<usize> {
+    ROUTES.lock()[vector as usize].map(|r| r.cpu as usize)
+}
+
+/// Deliver a vector to `cpu`
+///
+/// # Returns
+/// `ERR_NOT_FOUND` if the vector is not registered, `ERR_INVALID_ARGS`
+/// if the CPU is not online
+pub fn set_affinity(vector: u8, cpu: usize) -> Result<(), RxStatus> {
+    let mut routes = ROUTES.lock();
+    let route = routes[vector as usize].as_mut().ok_or(RxStatus::ERR_NOT_FOUND)?;
+    retarget(vector, route, cpu)
+}
+
+fn retarget(vector: u8, route: &mut IrqRoute, cpu: usize) -> Result<(), RxStatus> {
+    if !is_cpu_online(cpu) {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+
+    let apic_id = APIC_IDS[cpu].load(Ordering::Relaxed);
+    match route.source {
+        IrqSource::IoApic { gsi } => apic::ioapic_set_destination(gsi, apic_id),
+        IrqSource::Msi { program } => program(apic::msi_address(apic_id), apic::msi_data(vector)),
+    }
+    route.cpu = cpu as u8;
+    Ok(())
+}
+
+// ============================================================================
+// Statistics
+// ============================================================================
+
+/// Count an interrupt on `vector` for the running CPU
+#[inline]
+pub fn account(vector: u8) {
+    IRQ_COUNTS[current_cpu()][vector as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of interrupts on `vector` handled by `cpu`
+pub fn irq_count(cpu: usize, vector: u8) -> u64 {
+    if cpu >= MAX_CPUS {
+        return 0;
+    }
+    IRQ_COUNTS[cpu][vector as usize].load(Ordering::Relaxed)
+}
+
+/// Total interrupts handled by `cpu`
+pub fn cpu_irq_total(cpu: usize) -> u64 {
+    if cpu >= MAX_CPUS {
+        return 0;
+    }
+    IRQ_COUNTS[cpu].iter().map(|c| c.load(Ordering::Relaxed)).sum()
+}
+
+/// Total interrupts on `vector` across all CPUs
+pub fn vector_total(vector: u8) -> u64 {
+    (0..MAX_CPUS).map(|cpu| irq_count(cpu, vector)).sum()
+}
+
+// ============================================================================
+// Balancing
+// ============================================================================
+
+/// Count a timer tick and balance every [`BALANCE_INTERVAL_TICKS`]
+///
+/// Called from the timer interrupt. Skips the pass if the routing table
+/// is busy.
+pub fn balance_tick() {
+    if TICKS.fetch_add(1, Ordering::Relaxed) + 1 < BALANCE_INTERVAL_TICKS {
+        return;
+    }
+    TICKS.store(0, Ordering::Relaxed);
+    if let Some(mut routes) = ROUTES.try_lock() {
+        balance_locked(&mut routes);
+    }
+}
+
+/// Run a balancing pass now
+///
+/// # Returns
+/// Number of vectors moved
+pub fn balance() -> usize {
+    balance_locked(&mut ROUTES.lock())
+}
+
+fn balance_locked(routes: &mut [Option<IrqRoute>; NUM_VECTORS]) -> usize {
+    let online = ONLINE_CPUS.load(Ordering::Acquire);
+
+    // Interrupts per vector since the previous pass
+    let mut rates = [VectorRate { vector: 0, rate: 0, cpu: 0 }; NUM_VECTORS];
+    let mut n = 0;
+    for (vector, slot) in routes.iter_mut().enumerate() {
+        if let Some(route) = slot {
+            let total = vector_total(vector as u8);
+            rates[n] = VectorRate { vector: vector as u8, rate: total - route.last_total, cpu: route.cpu };
+            route.last_total = total;
+            n += 1;
+        }
+    }
+
+    let mut moves = [(0u8, 0u8); NUM_VECTORS];
+    let count = plan(&mut rates[..n], online, &mut moves);
+
+    let mut moved = 0;
+    for &(vector, cpu) in &moves[..count] {
+        if let Some(route) = routes[vector as usize].as_mut() {
+            if retarget(vector, route, cpu as usize).is_ok() {
+                moved += 1;
+            }
+        }
+    }
+    moved
+}
+
+/// Interrupt rate of one vector over a balancing interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VectorRate {
+    vector: u8,
+    rate: u64,
+    cpu: u8,
+}
+
+/// Decide which vectors to move
+///
+/// Quiet vectors stay put and count towards their CPU's load. Busy
+/// vectors are placed, busiest first, on the least-loaded online CPU,
+/// preferring their current CPU on ties. Does not allocate, so it can
+/// run from the timer interrupt.
+///
+/// # Returns
+/// Number of `(vector, new_cpu)` pairs written to `moves`
+fn plan(rates: &mut [VectorRate], online: u64, moves: &mut [(u8, u8)]) -> usize {
+    let mut load = [0u64; MAX_CPUS];
+    for r in rates.iter() {
+        if r.rate < HIGH_RATE_THRESHOLD && (r.cpu as usize) < MAX_CPUS {
+            load[r.cpu as usize] += r.rate;
+        }
+    }
+    rates.sort_unstable_by_key(|r| core::cmp::Reverse(r.rate));
+
+    let mut count = 0;
+    for r in rates.iter().take_while(|r| r.rate >= HIGH_RATE_THRESHOLD) {
+        let mut best = r.cpu as usize;
+        if best >= MAX_CPUS || online & (1 << best) == 0 {
+            best = 0;
+        }
+        for cpu in 0..MAX_CPUS {
+            if online & (1 << cpu) != 0 && load[cpu] < load[best] {
+                best = cpu;
+            }
+        }
+        load[best] += r.rate;
+        if best != r.cpu as usize && count < moves.len() {
+            moves[count] = (r.vector, best as u8);
+            count += 1;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(vector: u8, rate: u64, cpu: u8) -> VectorRate {
+        VectorRate { vector, rate, cpu }
+    }
+
+    #[test]
+    fn test_plan_spreads_busy_vectors() {
+        let mut rates = [rate(42, 2000, 0), rate(40, 5000, 0), rate(33, 10, 0), rate(41, 3000, 0)];
+        let mut moves = [(0, 0); 4];
+        let n = plan(&mut rates, 0b11, &mut moves);
+        // 40 stays on CPU 0, 41 goes to CPU 1, 42 joins it (3000 < 5010)
+        assert_eq!(&moves[..n], &[(41, 1), (42, 1)]);
+    }
+
+    #[test]
+    fn test_plan_single_cpu() {
+        let mut rates = [rate(40, 5000, 0), rate(41, 3000, 0)];
+        let mut moves = [(0, 0); 2];
+        assert_eq!(plan(&mut rates, 0b1, &mut moves), 0);
+    }
+
+    #[test]
+    fn test_plan_leaves_quiet_vectors() {
+        let mut rates = [rate(33, 50, 1), rate(40, 500, 1)];
+        let mut moves = [(0, 0); 2];
+        // CPU 1 carries 50 from the quiet vector, so the busy one moves to CPU 0
+        let n = plan(&mut rates, 0b11, &mut moves);
+        assert_eq!(&moves[..n], &[(40, 0)]);
+    }
+
+    #[test]
+    fn test_msi_message() {
+        assert_eq!(apic::msi_address(3), 0xFEE0_3000);
+        assert_eq!(apic::msi_data(0x40), 0x40);
+    }
+}
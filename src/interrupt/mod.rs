@@ -9,11 +9,21 @@
 //! This module provides architecture-independent interrupt handling,
 //! using the architecture-specific InterruptController implementations.
 
+pub mod affinity;
+
 use crate::traits::InterruptController;
 
+/// Number of IRQ lines that can have a registered handler
+pub const MAX_HANDLED_IRQS: usize = 1024;
+
+/// Function run for an IRQ, given its number
+pub type IrqHandlerFn = fn(irq: u64);
+
 /// Generic interrupt handler that can use any InterruptController implementation
 pub struct InterruptHandler<C: InterruptController> {
     controller: C,
+    /// Handler per IRQ line, run by [`Self::handle_irq`]
+    handlers: [Option<IrqHandlerFn>; MAX_HANDLED_IRQS],
 }
 
 impl<C: InterruptController> InterruptHandler<C> {
@@ -21,9 +31,47 @@ impl<C: InterruptController> InterruptHandler<C> {
     pub fn new(controller: C) -> Self {
         Self {
             controller,
+            handlers: [None; MAX_HANDLED_IRQS],
+        }
+    }
+
+    /// The interrupt controller
+    pub fn controller(&self) -> &C {
+        &self.controller
+    }
+
+    /// The interrupt controller, for controller-specific setup
+    pub fn controller_mut(&mut self) -> &mut C {
+        &mut self.controller
+    }
+
+    /// Set the function run when `irq` fires
+    pub fn register_handler(&mut self, irq: u64, handler: IrqHandlerFn) -> Result<(), &'static str> {
+        let slot = self.handlers.get_mut(irq as usize).ok_or("IRQ out of range")?;
+        *slot = Some(handler);
+        Ok(())
+    }
+
+    /// Remove the function run when `irq` fires
+    pub fn unregister_handler(&mut self, irq: u64) {
+        if let Some(slot) = self.handlers.get_mut(irq as usize) {
+            *slot = None;
         }
     }
 
+    /// Run the handler of an IRQ the controller delivered, then EOI it
+    ///
+    /// Returns whether a handler was registered. The IRQ is EOI'd either
+    /// way, so an unclaimed line does not stay in service.
+    pub fn handle_irq(&self, irq: u64) -> bool {
+        let handler = self.handlers.get(irq as usize).copied().flatten();
+        if let Some(handler) = handler {
+            handler(irq);
+        }
+        self.controller.send_eoi(irq);
+        handler.is_some()
+    }
+
     /// Initialize the interrupt controller
     pub fn init(&mut self) -> Result<(), &'static str> {
         self.controller.init()
@@ -0,0 +1,469 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Image Randomization (KASLR)
+//!
+//! The firmware loads the kernel PE image wherever its allocator puts it,
+//! which is the same address on every boot of the same machine. With
+//! `kaslr` on the command line, the UEFI stage moves the image to a random
+//! address before leaving boot services:
+//!
+//! 1. Draw a 2 MiB aligned candidate in the placement window from
+//!    [`boot_entropy`]
+//! 2. Ask the firmware for pages at exactly that address (retry a few
+//!    candidates if they are taken)
+//! 3. Copy the loaded image and apply its base relocations for the new
+//!    address ([`apply_relocations`])
+//! 4. Jump to the continuation in the copy and never return
+//!
+//! UEFI identity maps memory, so the virtual and physical slides are the
+//! same. If no candidate can be allocated the kernel keeps running where
+//! the firmware put it.
+//!
+//! # Deterministic Boots
+//!
+//! `kaslr.seed=<n>` (decimal or `0x` hex) replaces hardware entropy with a
+//! fixed seed, so a layout can be reproduced while debugging.
+//!
+//! # Symbolization
+//!
+//! The placement is recorded with [`record`] whether or not it was
+//! randomized. Backtraces print [`link_address`]es, which match the
+//! addresses in the unrelocated binary and can be fed to `addr2line`.
+//!
+//! # Sections
+//!
+//! [`pe_sections`] lists the image's sections, which
+//! [`kprotect`](crate::arch::amd64::kprotect) uses to set page
+//! permissions once boot is done. Code and data only needed during boot
+//! go in [`INIT_TEXT_SECTION`] / [`INIT_DATA_SECTION`] and are freed then.
+
+use crate::arch::amd64::entropy::BootEntropy;
+use crate::sync::SpinMutex;
+
+/// Command line flag enabling KASLR
+pub const KASLR_OPTION: &str = "kaslr";
+
+/// Command line option fixing the KASLR seed
+pub const SEED_OPTION: &str = "kaslr.seed";
+
+/// Alignment of the randomized image base
+pub const SLIDE_ALIGN: u64 = 0x20_0000;
+
+/// Lowest base the image is moved to (64 MiB)
+pub const PLACEMENT_START: u64 = 0x0400_0000;
+
+/// End of the placement window (1 GiB)
+pub const PLACEMENT_END: u64 = 0x4000_0000;
+
+/// Candidates tried before giving up
+pub const PLACEMENT_ATTEMPTS: usize = 16;
+
+/// Section for code only run during boot (`#[link_section]` value)
+pub const INIT_TEXT_SECTION: &str = ".inittxt";
+
+/// Section for data only used during boot (`#[link_section]` value)
+pub const INIT_DATA_SECTION: &str = ".initdat";
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Check whether KASLR was requested
+pub fn enabled() -> bool {
+    crate::cmdline::get_bool(KASLR_OPTION)
+}
+
+/// Entropy for the placement: seeded from `kaslr.seed=` if given
+pub fn boot_entropy() -> BootEntropy {
+    let mut buf = [0u8; 24];
+    match crate::cmdline::get(SEED_OPTION, &mut buf).and_then(parse_u64) {
+        Some(seed) => BootEntropy::from_seed(seed),
+        None => BootEntropy::gather(),
+    }
+}
+
+/// Parse a decimal or `0x` hex number
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Draw a candidate image base
+///
+/// # Returns
+///
+/// A [`SLIDE_ALIGN`] aligned base such that the whole image fits in the
+/// placement window, or None if it can't fit
+#[link_section = ".inittxt"]
+pub fn candidate(entropy: &mut BootEntropy, image_size: u64) -> Option<u64> {
+    let room = PLACEMENT_END.checked_sub(PLACEMENT_START)?.checked_sub(image_size)?;
+    let slots = room / SLIDE_ALIGN + 1;
+    Some(PLACEMENT_START + entropy.below(slots) * SLIDE_ALIGN)
+}
+
+// ============================================================================
+// PE Relocations
+// ============================================================================
+
+/// Optional header magic for PE32+
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+
+/// Data directory index of the base relocation table
+const DIR_BASERELOC: usize = 5;
+
+/// Relocation types
+const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+const IMAGE_REL_BASED_DIR64: u16 = 10;
+
+/// Section characteristics
+pub const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+pub const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+pub const IMAGE_SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+/// Size of a section table entry
+const SECTION_HEADER_SIZE: usize = 40;
+
+/// Fields of a PE32+ image needed to relocate it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeInfo {
+    /// Link-time image base
+    pub image_base: u64,
+    /// Size of the loaded image
+    pub size_of_image: u64,
+    /// Base relocation table (RVA, size); size 0 if absent
+    pub reloc: (u32, u32),
+}
+
+fn read_u16(image: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(image.get(off..off + 2)?.try_into().ok()?))
+}
+
+fn read_u32(image: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(image.get(off..off + 4)?.try_into().ok()?))
+}
+
+fn read_u64(image: &[u8], off: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(image.get(off..off + 8)?.try_into().ok()?))
+}
+
+/// Parse the headers of a loaded PE32+ image
+pub fn parse_pe(image: &[u8]) -> Result<PeInfo, &'static str> {
+    if image.get(0..2) != Some(b"MZ") {
+        return Err("missing MZ header");
+    }
+    let pe = read_u32(image, 0x3C).ok_or("truncated DOS header")? as usize;
+    if image.get(pe..pe + 4) != Some(b"PE\0\0") {
+        return Err("missing PE signature");
+    }
+    let opt = pe + 24;
+    if read_u16(image, opt) != Some(PE32_PLUS_MAGIC) {
+        return Err("not a PE32+ image");
+    }
+
+    let image_base = read_u64(image, opt + 24).ok_or("truncated optional header")?;
+    let size_of_image = read_u32(image, opt + 56).ok_or("truncated optional header")? as u64;
+    let num_dirs = read_u32(image, opt + 108).ok_or("truncated optional header")? as usize;
+
+    let reloc = if num_dirs > DIR_BASERELOC {
+        let dir = opt + 112 + DIR_BASERELOC * 8;
+        (
+            read_u32(image, dir).ok_or("truncated data directory")?,
+            read_u32(image, dir + 4).ok_or("truncated data directory")?,
+        )
+    } else {
+        (0, 0)
+    };
+
+    Ok(PeInfo { image_base, size_of_image, reloc })
+}
+
+/// A section of a loaded PE32+ image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeSection {
+    /// Name, NUL padded
+    pub name: [u8; 8],
+    /// Offset from the image base
+    pub rva: u32,
+    /// Size in memory
+    pub size: u32,
+    /// `IMAGE_SCN_*` flags
+    pub characteristics: u32,
+}
+
+impl PeSection {
+    /// Name as a string (empty if not UTF-8)
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+
+    /// Whether the section is executable
+    pub const fn is_executable(&self) -> bool {
+        self.characteristics & IMAGE_SCN_MEM_EXECUTE != 0
+    }
+
+    /// Whether the section is writable
+    pub const fn is_writable(&self) -> bool {
+        self.characteristics & IMAGE_SCN_MEM_WRITE != 0
+    }
+
+    /// Whether the section holds boot-only code or data
+    pub fn is_init(&self) -> bool {
+        self.name() == INIT_TEXT_SECTION || self.name() == INIT_DATA_SECTION
+    }
+}
+
+/// List the sections of a loaded PE32+ image
+pub fn pe_sections(image: &[u8]) -> Result<alloc::vec::Vec<PeSection>, &'static str> {
+    parse_pe(image)?;
+    let pe = read_u32(image, 0x3C).ok_or("truncated DOS header")? as usize;
+    let count = read_u16(image, pe + 6).ok_or("truncated file header")? as usize;
+    let opt_size = read_u16(image, pe + 20).ok_or("truncated file header")? as usize;
+    let table = pe + 24 + opt_size;
+
+    (0..count)
+        .map(|i| {
+            let hdr = table + i * SECTION_HEADER_SIZE;
+            let name = image.get(hdr..hdr + 8).ok_or("truncated section table")?;
+            Ok(PeSection {
+                name: name.try_into().map_err(|_| "truncated section table")?,
+                size: read_u32(image, hdr + 8).ok_or("truncated section table")?,
+                rva: read_u32(image, hdr + 12).ok_or("truncated section table")?,
+                characteristics: read_u32(image, hdr + 36).ok_or("truncated section table")?,
+            })
+        })
+        .collect()
+}
+
+/// Apply base relocations to an image that moved by `delta` bytes
+///
+/// `image` is the loaded image (RVA-indexed), already relocated for its
+/// old address; `delta` is `new_base - old_base` (wrapping).
+///
+/// # Returns
+///
+/// Number of fixups applied
+#[link_section = ".inittxt"]
+pub fn apply_relocations(image: &mut [u8], delta: u64) -> Result<usize, &'static str> {
+    let info = parse_pe(image)?;
+    let (rva, size) = (info.reloc.0 as usize, info.reloc.1 as usize);
+    if size == 0 {
+        return Err("image has no relocations");
+    }
+    let end = rva.checked_add(size).filter(|&e| e <= image.len()).ok_or("relocation table out of range")?;
+
+    let mut block = rva;
+    let mut fixups = 0;
+    while block + 8 <= end {
+        let page = read_u32(image, block).ok_or("truncated relocation block")? as usize;
+        let block_size = read_u32(image, block + 4).ok_or("truncated relocation block")? as usize;
+        if block_size < 8 || block + block_size > end {
+            return Err("bad relocation block size");
+        }
+
+        for entry_off in (block + 8..block + block_size).step_by(2) {
+            let entry = read_u16(image, entry_off).ok_or("truncated relocation entry")?;
+            let target = page + (entry & 0xFFF) as usize;
+            match entry >> 12 {
+                IMAGE_REL_BASED_ABSOLUTE => {}
+                IMAGE_REL_BASED_DIR64 => {
+                    let value = read_u64(image, target).ok_or("relocation target out of range")?;
+                    image[target..target + 8].copy_from_slice(&value.wrapping_add(delta).to_le_bytes());
+                    fixups += 1;
+                }
+                _ => return Err("unsupported relocation type"),
+            }
+        }
+        block += block_size;
+    }
+    Ok(fixups)
+}
+
+// ============================================================================
+// Kernel Image Placement
+// ============================================================================
+
+/// Where the kernel image ended up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelImage {
+    /// Runtime base (virtual == physical)
+    pub load_base: u64,
+    /// Link-time base from the PE header
+    pub link_base: u64,
+    /// Size of the loaded image in bytes
+    pub size: u64,
+    /// Whether the base was chosen by KASLR (false: firmware placement)
+    pub randomized: bool,
+}
+
+impl KernelImage {
+    /// Runtime base minus link-time base
+    pub const fn slide(&self) -> u64 {
+        self.load_base.wrapping_sub(self.link_base)
+    }
+
+    /// Check whether `addr` lies in the loaded image
+    pub const fn contains(&self, addr: u64) -> bool {
+        addr >= self.load_base && addr - self.load_base < self.size
+    }
+}
+
+static KERNEL_IMAGE: SpinMutex<Option<KernelImage>> = SpinMutex::new(None);
+
+/// Record the final kernel image placement
+pub fn record(image: KernelImage) {
+    *KERNEL_IMAGE.lock() = Some(image);
+}
+
+/// The recorded kernel image placement
+pub fn kernel_image() -> Option<KernelImage> {
+    *KERNEL_IMAGE.lock()
+}
+
+/// Translate a runtime address to its link-time address
+///
+/// Addresses outside the image are returned unchanged. Only try-locks, so
+/// it is safe on panic and fault paths.
+pub fn link_address(addr: usize) -> usize {
+    match KERNEL_IMAGE.try_lock().and_then(|image| *image) {
+        Some(image) if image.contains(addr as u64) => addr.wrapping_sub(image.slide() as usize),
+        _ => addr,
+    }
+}
+
+/// Offset of a runtime address from the image base (its RVA)
+///
+/// `None` outside the image or before [`record`]. Only try-locks, like
+/// [`link_address`].
+pub fn image_offset(addr: usize) -> Option<u64> {
+    let image = (*KERNEL_IMAGE.try_lock()?)?;
+    image.contains(addr as u64).then(|| addr as u64 - image.load_base)
+}
+
+/// Keep the PMM from handing out the pages holding the kernel image
+///
+/// Call after the PMM is initialized. Images outside the PMM arenas need
+/// no reservation.
+pub fn reserve_image() {
+    use crate::mm::pmm;
+
+    if let Some(image) = kernel_image() {
+        let first = image.load_base & !0xFFF;
+        let pages = (image.load_base + image.size - first).div_ceil(0x1000) as usize;
+        let _ = pmm::pmm_reserve_pages(first, pages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// A minimal PE32+ image: headers at 0, one DIR64 fixup at 0x200,
+    /// relocation table at 0x300
+    fn test_image(base: u64) -> Vec<u8> {
+        let mut image = vec![0u8; 0x400];
+        image[0..2].copy_from_slice(b"MZ");
+        image[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        image[0x80..0x84].copy_from_slice(b"PE\0\0");
+        let opt = 0x80 + 24;
+        image[opt..opt + 2].copy_from_slice(&PE32_PLUS_MAGIC.to_le_bytes());
+        image[opt + 24..opt + 32].copy_from_slice(&base.to_le_bytes());
+        image[opt + 56..opt + 60].copy_from_slice(&0x400u32.to_le_bytes());
+        image[opt + 108..opt + 112].copy_from_slice(&16u32.to_le_bytes());
+        let dir = opt + 112 + DIR_BASERELOC * 8;
+        image[dir..dir + 4].copy_from_slice(&0x300u32.to_le_bytes());
+        image[dir + 4..dir + 8].copy_from_slice(&12u32.to_le_bytes());
+
+        image[0x200..0x208].copy_from_slice(&(base + 0x1234).to_le_bytes());
+        image[0x300..0x304].copy_from_slice(&0x200u32.to_le_bytes());
+        image[0x304..0x308].copy_from_slice(&12u32.to_le_bytes());
+        image[0x308..0x30A].copy_from_slice(&(IMAGE_REL_BASED_DIR64 << 12).to_le_bytes());
+        image[0x30A..0x30C].copy_from_slice(&0u16.to_le_bytes()); // padding
+        image
+    }
+
+    #[test]
+    fn test_parse_pe() {
+        let image = test_image(0x1_4000_0000);
+        let info = parse_pe(&image).unwrap();
+        assert_eq!(info.image_base, 0x1_4000_0000);
+        assert_eq!(info.size_of_image, 0x400);
+        assert_eq!(info.reloc, (0x300, 12));
+        assert_eq!(parse_pe(&image[..0x40]), Err("missing PE signature"));
+    }
+
+    #[test]
+    fn test_pe_sections() {
+        let mut image = test_image(0x1_4000_0000);
+        let pe = 0x80;
+        image[pe + 6..pe + 8].copy_from_slice(&2u16.to_le_bytes());
+        image[pe + 20..pe + 22].copy_from_slice(&240u16.to_le_bytes());
+        let table = pe + 24 + 240;
+        for (i, (name, rva, flags)) in [
+            (&b".text\0\0\0"[..], 0x1000u32, IMAGE_SCN_MEM_EXECUTE | IMAGE_SCN_MEM_READ),
+            (&b".inittxt"[..], 0x2000, IMAGE_SCN_MEM_EXECUTE | IMAGE_SCN_MEM_READ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let hdr = table + i * SECTION_HEADER_SIZE;
+            image[hdr..hdr + 8].copy_from_slice(name);
+            image[hdr + 8..hdr + 12].copy_from_slice(&0x800u32.to_le_bytes());
+            image[hdr + 12..hdr + 16].copy_from_slice(&rva.to_le_bytes());
+            image[hdr + 36..hdr + 40].copy_from_slice(&flags.to_le_bytes());
+        }
+
+        let sections = pe_sections(&image).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].name(), ".text");
+        assert_eq!((sections[0].rva, sections[0].size), (0x1000, 0x800));
+        assert!(sections[0].is_executable() && !sections[0].is_writable());
+        assert!(!sections[0].is_init());
+        assert!(sections[1].is_init());
+    }
+
+    #[test]
+    fn test_apply_relocations() {
+        let mut image = test_image(0x1_4000_0000);
+        assert_eq!(apply_relocations(&mut image, 0x20_0000), Ok(1));
+        assert_eq!(read_u64(&image, 0x200), Some(0x1_4020_1234));
+
+        // Moving back down wraps
+        assert_eq!(apply_relocations(&mut image, 0u64.wrapping_sub(0x20_0000)), Ok(1));
+        assert_eq!(read_u64(&image, 0x200), Some(0x1_4000_1234));
+    }
+
+    #[test]
+    fn test_candidate_in_window() {
+        let mut entropy = BootEntropy::from_seed(1);
+        for _ in 0..64 {
+            let base = candidate(&mut entropy, 0x30_0000).unwrap();
+            assert_eq!(base % SLIDE_ALIGN, 0);
+            assert!(base >= PLACEMENT_START && base + 0x30_0000 <= PLACEMENT_END);
+        }
+        assert_eq!(candidate(&mut entropy, PLACEMENT_END), None);
+    }
+
+    #[test]
+    fn test_parse_u64() {
+        assert_eq!(parse_u64("1234"), Some(1234));
+        assert_eq!(parse_u64("0xff"), Some(255));
+        assert_eq!(parse_u64("seed"), None);
+    }
+
+    #[test]
+    fn test_kernel_image_slide() {
+        let image = KernelImage { load_base: 0x600_0000, link_base: 0x1_4000_0000, size: 0x10_0000, randomized: true };
+        assert!(image.contains(0x600_0000));
+        assert!(!image.contains(0x610_0000));
+        assert_eq!(0x600_1000u64.wrapping_sub(image.slide()), 0x1_4000_1000);
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Counters Page
+//!
+//! This module exports a single page of kernel counters (syscall counts,
+//! context switches, IRQ counts, free pages) that privileged processes
+//! can map read-only with `sys_kcounters_map`. The kernel updates the
+//! counters in place, so monitoring tools read them without a syscall.
+//!
+//! # Layout
+//!
+//! The page starts with [`KernelCounters`]. Its layout is part of the
+//! stable ABI: fields are only ever appended, and `version` is bumped
+//! when that happens. Readers must check `magic` and must not read past
+//! `size`. `syscall_counts` grows with the syscall table, so the fields
+//! after it are found from `num_syscalls`, not at fixed offsets.
+//!
+//! All counters are 64-bit and naturally aligned, so userspace can read
+//! each one atomically. There is no snapshot consistency across fields.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use crate::arch::amd64::mm::page_tables::PAddr;
+use crate::arch::amd64::mm::RxStatus;
+use crate::exec::elf::PF_R;
+use crate::mm::pmm;
+use crate::object::{Vmo, VmoFlags};
+use crate::object::vmo::PageMapEntry;
+use crate::process::vmar::Vmar;
+use crate::syscall::number;
+
+/// Magic value at the start of the page ("KCNT")
+pub const KCOUNTERS_MAGIC: u32 = 0x544E_434B;
+
+/// Current layout version
+pub const KCOUNTERS_VERSION: u32 = 3;
+
+/// Number of syscall counter slots (every syscall number up to `MAX_SYSCALL`)
+pub const NUM_SYSCALL_SLOTS: usize = number::MAX_SYSCALL as usize + 1;
+
+/// Number of IRQ counter slots (one per interrupt vector)
+pub const NUM_IRQ_SLOTS: usize = 256;
+
+/// Size of the counters mapping
+pub const KCOUNTERS_SIZE: usize = 4096;
+
+/// Kernel counters page layout (version 3)
+#[repr(C)]
+pub struct KernelCounters {
+    /// [`KCOUNTERS_MAGIC`]
+    pub magic: u32,
+    /// [`KCOUNTERS_VERSION`]
+    pub version: u32,
+    /// Size of this structure in bytes
+    pub size: u32,
+    /// Number of entries in `syscall_counts`
+    pub num_syscalls: u32,
+    /// Number of entries in `irq_counts`
+    pub num_irqs: u32,
+    /// Reserved, zero
+    pub _reserved: u32,
+    /// Total context switches
+    pub context_switches: AtomicU64,
+    /// Free physical pages
+    pub free_pages: AtomicU64,
+    /// Total physical pages at the time the page was created
+    pub total_pages: AtomicU64,
+    /// Invocation count per syscall number (version 3: one per syscall,
+    /// was 128)
+    pub syscall_counts: [AtomicU64; NUM_SYSCALL_SLOTS],
+    /// Interrupt count per vector
+    pub irq_counts: [AtomicU64; NUM_IRQ_SLOTS],
+    /// Memory reclaim passes (version 2)
+    pub reclaim_scans: AtomicU64,
+    /// Pages given back to the PMM by reclaim (version 2)
+    pub pages_reclaimed: AtomicU64,
+    /// Reclaimed pages touched again (version 2)
+    pub reclaim_refaults: AtomicU64,
+}
+
+const _: () = assert!(core::mem::size_of::<KernelCounters>() <= KCOUNTERS_SIZE);
+const _: () = assert!(NUM_SYSCALL_SLOTS > number::MAX_SYSCALL as usize, "every syscall needs a counter");
+
+/// Kernel mapping of the counters page (null until [`init`])
+static COUNTERS: AtomicPtr<KernelCounters> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Physical address of the counters page (0 until [`init`])
+static COUNTERS_PADDR: AtomicU64 = AtomicU64::new(0);
+
+/// Get the counters page, if initialized
+fn counters() -> Option<&'static KernelCounters> {
+    let ptr = COUNTERS.load(Ordering::Acquire);
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { &*ptr })
+    }
+}
+
+/// Allocate and initialize the counters page
+///
+/// Must be called once after the PMM is up. Events recorded before this
+/// call are not counted.
+pub fn init() -> Result<(), &'static str> {
+    if !COUNTERS.load(Ordering::Acquire).is_null() {
+        return Err("kernel counters already initialized");
+    }
+
+    let paddr = pmm::pmm_alloc_kernel_page()
+        .map_err(|_| "Failed to allocate kernel counters page")?;
+    let vaddr = pmm::paddr_to_vaddr(paddr);
+
+    unsafe {
+        core::ptr::write_bytes(vaddr as *mut u8, 0, KCOUNTERS_SIZE);
+
+        let page = &mut *(vaddr as *mut KernelCounters);
+        page.magic = KCOUNTERS_MAGIC;
+        page.version = KCOUNTERS_VERSION;
+        page.size = core::mem::size_of::<KernelCounters>() as u32;
+        page.num_syscalls = NUM_SYSCALL_SLOTS as u32;
+        page.num_irqs = NUM_IRQ_SLOTS as u32;
+
+        let free = pmm::pmm_count_free_pages();
+        page.free_pages.store(free, Ordering::Relaxed);
+        page.total_pages.store(free + 1, Ordering::Relaxed);
+    }
+
+    COUNTERS_PADDR.store(paddr as u64, Ordering::Relaxed);
+    COUNTERS.store(vaddr as *mut KernelCounters, Ordering::Release);
+    Ok(())
+}
+
+// ============================================================================
+// Update Hooks
+// ============================================================================
+
+/// Count a syscall invocation
+#[inline]
+pub fn record_syscall(num: u32) {
+    if let Some(c) = counters() {
+        if let Some(slot) = c.syscall_counts.get(num as usize) {
+            slot.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Count an interrupt on `vector`
+///
+/// Also updates the per-CPU counts used by the IRQ balancer; see
+/// [`crate::interrupt::affinity`].
+#[inline]
+pub fn record_irq(vector: u8) {
+    crate::interrupt::affinity::account(vector);
+    if let Some(c) = counters() {
+        c.irq_counts[vector as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Count a context switch
+#[inline]
+pub fn record_context_switch() {
+    if let Some(c) = counters() {
+        c.context_switches.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Account for `count` pages leaving the free pool
+#[inline]
+pub fn pages_allocated(count: usize) {
+    if let Some(c) = counters() {
+        c.free_pages.fetch_sub(count as u64, Ordering::Relaxed);
+    }
+}
+
+/// Account for `count` pages returning to the free pool
+#[inline]
+pub fn pages_freed(count: usize) {
+    if let Some(c) = counters() {
+        c.free_pages.fetch_add(count as u64, Ordering::Relaxed);
+    }
+}
+
+/// Count a memory reclaim pass that gave back `pages` pages
+#[inline]
+pub fn record_reclaim(pages: usize) {
+    if let Some(c) = counters() {
+        c.reclaim_scans.fetch_add(1, Ordering::Relaxed);
+        c.pages_reclaimed.fetch_add(pages as u64, Ordering::Relaxed);
+    }
+}
+
+/// Count a reclaimed page being committed again
+#[inline]
+pub fn record_refault() {
+    if let Some(c) = counters() {
+        c.reclaim_refaults.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// ============================================================================
+// Userspace Mapping
+// ============================================================================
+
+/// Build a VMO backed by the counters page
+///
+/// The page entry is marked read-only. The VMO does not own the page.
+pub fn vmo() -> Result<Vmo, RxStatus> {
+    let paddr = COUNTERS_PADDR.load(Ordering::Relaxed);
+    if paddr == 0 {
+        return Err(RxStatus::ERR_NOT_FOUND);
+    }
+
+    let vmo = Vmo::create(KCOUNTERS_SIZE, VmoFlags::empty)
+        .map_err(|_| RxStatus::ERR_NO_MEMORY)?;
+    vmo.pages.lock().insert(0, PageMapEntry::new(paddr as PAddr, false));
+    Ok(vmo)
+}
+
+/// Map the counters page read-only into a process
+///
+/// The mapping is recorded in `vmar` like any other, so it cannot land on
+/// top of an existing one and is torn down with the process.
+///
+/// # Arguments
+///
+/// * `page_table` - Physical address of the target PML4
+/// * `vmar` - Mappings of the target process
+/// * `vaddr` - Page-aligned userspace address
+///
+/// # Returns
+/// `ERR_BUSY` if the page overlaps an existing mapping
+pub fn map_into(page_table: PAddr, vmar: &mut Vmar, vaddr: usize) -> Result<(), RxStatus> {
+    if !vaddr.is_multiple_of(KCOUNTERS_SIZE) {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+
+    let vmo = Arc::new(vmo()?);
+    crate::syscall::vmo::map_into(page_table, vmar, vmo, vaddr, KCOUNTERS_SIZE, PF_R)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(core::mem::offset_of!(KernelCounters, context_switches), 24);
+        assert_eq!(core::mem::offset_of!(KernelCounters, syscall_counts), 48);
+        assert_eq!(
+            core::mem::offset_of!(KernelCounters, irq_counts),
+            48 + 8 * NUM_SYSCALL_SLOTS
+        );
+        assert_eq!(
+            core::mem::offset_of!(KernelCounters, reclaim_scans),
+            48 + 8 * (NUM_SYSCALL_SLOTS + NUM_IRQ_SLOTS)
+        );
+    }
+}
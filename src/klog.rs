@@ -0,0 +1,618 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Log
+//!
+//! Leveled logging for the whole kernel. Code logs one line per call with
+//! [`kerror!`], [`kwarn!`], [`kinfo!`], [`kdebug!`] or [`ktrace!`] (or
+//! [`klog!`] with a [`Level`]); the line is tagged with the caller's
+//! module path as its target. A line whose level passes the [`Filter`]
+//! for its target goes to:
+//!
+//! - The log ring: the last [`KLOG_SIZE`] bytes of output, shown by
+//!   `/proc/klog` and saved by a crash dump ([`crate::crashdump`]), since
+//!   on real hardware the debug port goes nowhere
+//! - Every enabled sink in [`SINKS`] whose own level allows it: the QEMU
+//!   debug port, COM1 and the framebuffer console
+//!
+//! Sinks start disabled. The boot path enables each one once its device
+//! is usable ([`enable_sink`]); until then lines only reach the ring.
+//! Messages keep their own `[TAG]` prefixes; the logger only adds the
+//! newline.
+//!
+//! Multi-line reports (backtraces, leak reports) write through a
+//! [`LogWriter`] instead, which passes text on unchanged.
+//!
+//! # Command Line
+//!
+//! - `log.level=<spec>`: comma-separated; a bare level sets the default
+//!   (`info` if not given), `target=level` sets the level of a module
+//!   and everything under it, e.g. `log.level=warn,mm=debug,exec::elf=trace`.
+//!   Targets are module paths without the crate name; the longest
+//!   matching one wins. Levels are `off`, `error`, `warn`, `info`,
+//!   `debug` and `trace`.
+//! - `log.console=<level>`: the framebuffer console's level (`warn`)
+//!
+//! Logging never allocates. The ring drops text rather than spin if it is
+//! busy, so logging is safe from interrupt and panic context.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use crate::sync::SpinMutex;
+
+// ============================================================================
+// Levels and Filters
+// ============================================================================
+
+/// Log level, most severe first
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    /// Lower-case name, as on the command line
+    pub const fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+/// Most verbose level a filter lets through; 0 lets nothing through
+fn parse_level(s: &str) -> Option<u8> {
+    let level = match s {
+        "off" => 0,
+        "error" => Level::Error as u8,
+        "warn" => Level::Warn as u8,
+        "info" => Level::Info as u8,
+        "debug" => Level::Debug as u8,
+        "trace" => Level::Trace as u8,
+        _ => return None,
+    };
+    Some(level)
+}
+
+/// Per-target levels a [`Filter`] can hold
+pub const MAX_TARGET_FILTERS: usize = 8;
+
+/// Longest target a [`Filter`] can hold
+pub const MAX_TARGET_LEN: usize = 32;
+
+/// Command line option with the filter spec
+pub const LEVEL_OPTION: &str = "log.level";
+
+/// Command line option with the console sink's level
+pub const CONSOLE_OPTION: &str = "log.console";
+
+/// Crate name, stripped from module paths
+const CRATE_PREFIX: &str = "rustux::";
+
+#[derive(Clone, Copy)]
+struct TargetFilter {
+    target: [u8; MAX_TARGET_LEN],
+    len: usize,
+    level: u8,
+}
+
+impl TargetFilter {
+    const EMPTY: Self = Self { target: [0; MAX_TARGET_LEN], len: 0, level: 0 };
+
+    fn target(&self) -> &[u8] {
+        &self.target[..self.len]
+    }
+}
+
+/// Which levels are logged, by target
+#[derive(Clone, Copy)]
+pub struct Filter {
+    default: u8,
+    targets: [TargetFilter; MAX_TARGET_FILTERS],
+    count: usize,
+}
+
+impl Filter {
+    /// Log `Info` and above everywhere
+    pub const fn new() -> Self {
+        Self { default: Level::Info as u8, targets: [TargetFilter::EMPTY; MAX_TARGET_FILTERS], count: 0 }
+    }
+
+    /// Parse a `log.level` spec
+    ///
+    /// Unknown levels, targets longer than [`MAX_TARGET_LEN`] and targets
+    /// past [`MAX_TARGET_FILTERS`] are ignored.
+    pub fn parse(spec: &str) -> Self {
+        let mut filter = Self::new();
+        for item in spec.split(',') {
+            match item.split_once('=') {
+                None => {
+                    if let Some(level) = parse_level(item) {
+                        filter.default = level;
+                    }
+                }
+                Some((target, level)) => {
+                    let target = target.strip_prefix(CRATE_PREFIX).unwrap_or(target).as_bytes();
+                    let Some(level) = parse_level(level) else { continue };
+                    if target.is_empty() || target.len() > MAX_TARGET_LEN || filter.count == MAX_TARGET_FILTERS {
+                        continue;
+                    }
+                    let entry = &mut filter.targets[filter.count];
+                    entry.target[..target.len()].copy_from_slice(target);
+                    entry.len = target.len();
+                    entry.level = level;
+                    filter.count += 1;
+                }
+            }
+        }
+        filter
+    }
+
+    /// Check whether a line at `level` from `target` (a module path) is
+    /// logged
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        let target = target.strip_prefix(CRATE_PREFIX).unwrap_or(target).as_bytes();
+        let mut best: Option<&TargetFilter> = None;
+        for entry in &self.targets[..self.count] {
+            let t = entry.target();
+            let matches = target.starts_with(t) && (target.len() == t.len() || target[t.len()..].starts_with(b"::"));
+            if matches && best.is_none_or(|b| t.len() > b.len) {
+                best = Some(entry);
+            }
+        }
+        level as u8 <= best.map_or(self.default, |b| b.level)
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The filter from the command line, written once by [`init`] before
+/// [`FILTER_READY`] is set and never again
+static mut FILTER: Filter = Filter::new();
+static FILTER_READY: AtomicBool = AtomicBool::new(false);
+static FILTER_INIT: AtomicBool = AtomicBool::new(false);
+
+/// Apply the `log.level` and `log.console` command line options
+///
+/// Until this runs, `Info` and above are logged everywhere.
+pub fn init() {
+    if FILTER_INIT.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let mut buf = [0u8; 128];
+    if let Some(spec) = crate::cmdline::get(LEVEL_OPTION, &mut buf) {
+        // SAFETY: only this call writes FILTER, and readers wait for
+        // FILTER_READY
+        unsafe { FILTER = Filter::parse(spec) };
+        FILTER_READY.store(true, Ordering::Release);
+    }
+    if let Some(level) = crate::cmdline::get(CONSOLE_OPTION, &mut buf).and_then(parse_level) {
+        SINKS[SinkId::Console as usize].default_level.store(level, Ordering::Relaxed);
+    }
+}
+
+/// Check whether a line at `level` from `target` would be logged
+pub fn enabled(level: Level, target: &str) -> bool {
+    if FILTER_READY.load(Ordering::Acquire) {
+        let filter = &raw const FILTER;
+        // SAFETY: FILTER is not written once FILTER_READY is set
+        unsafe { (*filter).enabled(level, target) }
+    } else {
+        level <= Level::Info
+    }
+}
+
+// ============================================================================
+// Sinks
+// ============================================================================
+
+/// An output for log lines
+///
+/// Called from any context, including interrupt handlers: a sink must
+/// not allocate or wait for a lock another CPU may hold for long.
+pub trait Sink: Sync {
+    /// Write text
+    fn write_str(&self, s: &str);
+}
+
+/// A sink and the most verbose level it takes (0 while disabled)
+pub struct SinkSlot {
+    sink: &'static dyn Sink,
+    level: AtomicU8,
+    /// Level [`enable_sink`] uses when not given one
+    default_level: AtomicU8,
+}
+
+impl SinkSlot {
+    const fn new(sink: &'static dyn Sink, default_level: Level) -> Self {
+        Self { sink, level: AtomicU8::new(0), default_level: AtomicU8::new(default_level as u8) }
+    }
+
+    fn accepts(&self, level: Level) -> bool {
+        level as u8 <= self.level.load(Ordering::Relaxed)
+    }
+}
+
+/// Index of a sink in [`SINKS`]
+#[repr(usize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkId {
+    /// QEMU debug console (port 0xE9)
+    Debugcon = 0,
+    /// COM1, through [`crate::drivers::uart`]
+    Uart = 1,
+    /// Framebuffer console ([`crate::drivers::display::console`])
+    Console = 2,
+}
+
+/// Every sink; add one by implementing [`Sink`] and giving it a slot
+pub static SINKS: [SinkSlot; 3] = [
+    SinkSlot::new(&Debugcon, Level::Trace),
+    SinkSlot::new(&Uart, Level::Trace),
+    SinkSlot::new(&Console, Level::Warn),
+];
+
+/// Start sending lines to a sink, at its default level
+pub fn enable_sink(id: SinkId) {
+    let slot = &SINKS[id as usize];
+    slot.level.store(slot.default_level.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Stop sending lines to a sink
+pub fn disable_sink(id: SinkId) {
+    SINKS[id as usize].level.store(0, Ordering::Relaxed);
+}
+
+/// Check whether a sink is enabled
+pub fn sink_enabled(id: SinkId) -> bool {
+    SINKS[id as usize].level.load(Ordering::Relaxed) != 0
+}
+
+/// QEMU debug console
+pub struct Debugcon;
+
+impl Sink for Debugcon {
+    fn write_str(&self, s: &str) {
+        debugcon_write(s.as_bytes());
+    }
+}
+
+/// COM1, queued while its transmit interrupt is enabled
+pub struct Uart;
+
+impl Sink for Uart {
+    fn write_str(&self, s: &str) {
+        if let Some(com1) = unsafe { crate::drivers::uart::com1() } {
+            com1.try_write_str(s);
+        }
+    }
+}
+
+/// Framebuffer console
+pub struct Console;
+
+impl Sink for Console {
+    fn write_str(&self, s: &str) {
+        if crate::drivers::display::console::is_initialized() {
+            crate::drivers::display::console::write_str(s);
+        }
+    }
+}
+
+/// Write bytes to the QEMU debug console (port 0xE9)
+///
+/// The raw port, for output that is not a log line: process output,
+/// `DEBUG_WRITE` and data dumps. Log lines go through the macros.
+pub fn debugcon_write(bytes: &[u8]) {
+    for &b in bytes {
+        unsafe {
+            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
+        }
+    }
+}
+
+/// `fmt::Write` sink for the raw debug port ([`debugcon_write`])
+pub struct DebugconWriter;
+
+impl Write for DebugconWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        debugcon_write(s.as_bytes());
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Log Ring
+// ============================================================================
+
+/// Bytes of output kept
+pub const KLOG_SIZE: usize = 16 * 1024;
+
+/// Ring of output bytes
+pub struct LogRing {
+    data: [u8; KLOG_SIZE],
+    /// Total bytes ever written (the next byte goes at `head % SIZE`)
+    head: u64,
+}
+
+impl LogRing {
+    /// Create an empty ring
+    pub const fn new() -> Self {
+        Self { data: [0; KLOG_SIZE], head: 0 }
+    }
+
+    /// Append bytes, overwriting the oldest when full
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.data[(self.head as usize) % KLOG_SIZE] = b;
+            self.head += 1;
+        }
+    }
+
+    /// Number of bytes held
+    pub fn len(&self) -> usize {
+        (self.head as usize).min(KLOG_SIZE)
+    }
+
+    /// Check whether the ring is empty
+    pub fn is_empty(&self) -> bool {
+        self.head == 0
+    }
+
+    /// Position just past the newest byte (total bytes ever written)
+    pub fn head(&self) -> u64 {
+        self.head
+    }
+
+    /// Copy held bytes starting at stream position `cursor` into `out`
+    ///
+    /// A cursor older than the oldest held byte starts at the oldest; one
+    /// past [`head`](Self::head) copies nothing.
+    ///
+    /// # Returns
+    /// The position copying started at, and the number of bytes copied
+    pub fn read_at(&self, cursor: u64, out: &mut [u8]) -> (u64, usize) {
+        let start = cursor.clamp(self.head - self.len() as u64, self.head);
+        let n = core::cmp::min(out.len() as u64, self.head - start) as usize;
+        for (i, byte) in out[..n].iter_mut().enumerate() {
+            *byte = self.data[((start + i as u64) % KLOG_SIZE as u64) as usize];
+        }
+        (start, n)
+    }
+
+    /// Held bytes, oldest first, as two slices
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        if (self.head as usize) < KLOG_SIZE {
+            return (&self.data[..self.head as usize], &[]);
+        }
+        let split = (self.head as usize) % KLOG_SIZE;
+        (&self.data[split..], &self.data[..split])
+    }
+}
+
+impl Default for LogRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for LogRing {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+static KLOG: SpinMutex<LogRing> = SpinMutex::new(LogRing::new());
+
+/// Append text to the ring only
+///
+/// For the panic report, which writes to its devices itself.
+pub fn write(s: &str) {
+    if let Some(mut log) = KLOG.try_lock() {
+        log.write(s.as_bytes());
+    }
+}
+
+/// Copy log text from stream position `cursor` (see [`LogRing::read_at`])
+///
+/// Waits for the ring, so not for interrupt context; lines logged
+/// meanwhile are dropped rather than block.
+pub fn read(cursor: u64, out: &mut [u8]) -> (u64, usize) {
+    KLOG.lock().read_at(cursor, out)
+}
+
+/// Run `f` on the log, or return `None` if it is busy
+pub fn try_with<R>(f: impl FnOnce(&LogRing) -> R) -> Option<R> {
+    KLOG.try_lock().map(|log| f(&log))
+}
+
+// ============================================================================
+// Logging
+// ============================================================================
+
+/// `fmt::Write` adapter for a sink
+struct SinkWriter(&'static dyn Sink);
+
+impl Write for SinkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s);
+        Ok(())
+    }
+}
+
+/// Send text to the ring and to the sinks taking `level`
+fn emit(level: Level, args: fmt::Arguments, newline: bool) {
+    if let Some(mut log) = KLOG.try_lock() {
+        let _ = log.write_fmt(args);
+        if newline {
+            log.write(b"\n");
+        }
+    }
+    for slot in SINKS.iter().filter(|slot| slot.accepts(level)) {
+        let _ = SinkWriter(slot.sink).write_fmt(args);
+        if newline {
+            slot.sink.write_str("\n");
+        }
+    }
+}
+
+/// Log one line (use the macros)
+pub fn log(level: Level, target: &str, args: fmt::Arguments) {
+    if enabled(level, target) {
+        emit(level, args, true);
+    }
+}
+
+/// `fmt::Write` sink that logs text as is, for multi-line reports
+///
+/// Filtered like a line from `target`, once, when created.
+pub struct LogWriter {
+    level: Level,
+    enabled: bool,
+}
+
+impl LogWriter {
+    /// Writer for text at `level` from `target` (`module_path!()`)
+    pub fn new(level: Level, target: &str) -> Self {
+        Self { level, enabled: enabled(level, target) }
+    }
+}
+
+impl Write for LogWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.enabled {
+            emit(self.level, format_args!("{}", s), false);
+        }
+        Ok(())
+    }
+}
+
+/// Log a line at a [`Level`]
+#[macro_export]
+macro_rules! klog {
+    ($level:expr, $($arg:tt)+) => {
+        $crate::klog::log($level, module_path!(), format_args!($($arg)+))
+    };
+}
+
+/// Log a line at `Error`
+#[macro_export]
+macro_rules! kerror {
+    ($($arg:tt)+) => { $crate::klog!($crate::klog::Level::Error, $($arg)+) };
+}
+
+/// Log a line at `Warn`
+#[macro_export]
+macro_rules! kwarn {
+    ($($arg:tt)+) => { $crate::klog!($crate::klog::Level::Warn, $($arg)+) };
+}
+
+/// Log a line at `Info`
+#[macro_export]
+macro_rules! kinfo {
+    ($($arg:tt)+) => { $crate::klog!($crate::klog::Level::Info, $($arg)+) };
+}
+
+/// Log a line at `Debug`
+#[macro_export]
+macro_rules! kdebug {
+    ($($arg:tt)+) => { $crate::klog!($crate::klog::Level::Debug, $($arg)+) };
+}
+
+/// Log a line at `Trace`
+#[macro_export]
+macro_rules! ktrace {
+    ($($arg:tt)+) => { $crate::klog!($crate::klog::Level::Trace, $($arg)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_wraps() {
+        let mut ring = alloc::boxed::Box::new(LogRing::new());
+        assert!(ring.is_empty());
+        ring.write(b"boot\n");
+        assert_eq!(ring.as_slices(), (&b"boot\n"[..], &b""[..]));
+
+        // Overwrites all but the newline of "boot\n"
+        ring.write(&[b'x'; KLOG_SIZE - 6]);
+        ring.write(b"panic");
+        assert_eq!(ring.len(), KLOG_SIZE);
+        let (old, new) = ring.as_slices();
+        let held = [old, new].concat();
+        assert!(held.starts_with(b"\nxx"));
+        assert!(held.ends_with(b"xpanic"));
+        assert_eq!(held.len(), KLOG_SIZE);
+    }
+
+    #[test]
+    fn test_ring_read_at() {
+        let mut ring = alloc::boxed::Box::new(LogRing::new());
+        ring.write(b"one\ntwo\n");
+        let mut out = [0u8; 6];
+        assert_eq!(ring.read_at(0, &mut out), (0, 6));
+        assert_eq!(&out, b"one\ntw");
+        assert_eq!(ring.read_at(6, &mut out), (6, 2));
+        assert_eq!(ring.read_at(8, &mut out), (8, 0));
+        assert_eq!(ring.read_at(100, &mut out), (8, 0));
+
+        // A cursor into overwritten text skips to the oldest byte held
+        ring.write(&[b'x'; KLOG_SIZE]);
+        assert_eq!(ring.read_at(2, &mut out), (8, 6));
+        assert_eq!(&out, b"xxxxxx");
+        assert_eq!(ring.read_at(ring.head() - 1, &mut out), (ring.head() - 1, 1));
+    }
+
+    #[test]
+    fn test_filter_default() {
+        let filter = Filter::new();
+        assert!(filter.enabled(Level::Info, "rustux::mm::pmm"));
+        assert!(!filter.enabled(Level::Debug, "rustux::mm::pmm"));
+
+        let filter = Filter::parse("warn");
+        assert!(filter.enabled(Level::Error, "rustux"));
+        assert!(!filter.enabled(Level::Info, "rustux"));
+        assert!(!Filter::parse("off").enabled(Level::Error, "rustux"));
+        // Unknown levels leave the default alone
+        assert!(Filter::parse("loud").enabled(Level::Info, "rustux"));
+    }
+
+    #[test]
+    fn test_filter_targets() {
+        let filter = Filter::parse("warn,mm=debug,mm::pmm=trace,rustux::exec::elf=off,syscall=bogus");
+        assert!(filter.enabled(Level::Debug, "rustux::mm::allocator"));
+        assert!(!filter.enabled(Level::Trace, "rustux::mm::allocator"));
+        assert!(filter.enabled(Level::Trace, "rustux::mm::pmm"));
+        assert!(!filter.enabled(Level::Error, "rustux::exec::elf"));
+        assert!(filter.enabled(Level::Warn, "rustux::exec::process_loader"));
+        assert!(!filter.enabled(Level::Info, "rustux::syscall"));
+        // Targets match whole path components
+        assert!(!filter.enabled(Level::Info, "rustux::mmio"));
+    }
+
+    #[test]
+    fn test_filter_limits() {
+        let long = alloc::format!("{}=trace", "x".repeat(MAX_TARGET_LEN + 1));
+        assert_eq!(Filter::parse(&long).count, 0);
+        let many = "a=trace,".repeat(MAX_TARGET_FILTERS + 2);
+        assert_eq!(Filter::parse(&many).count, MAX_TARGET_FILTERS);
+    }
+}
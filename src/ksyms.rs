@@ -0,0 +1,214 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Symbol Table
+//!
+//! Function names for backtraces. The table is built from the linker map
+//! of an earlier link of the same sources: `cargo xtask` links the kernel
+//! with `-map:`, then links it again with `RUSTUX_SYMBOL_MAP` pointing at
+//! the map, and build.rs packs the function symbols into `ksyms.bin`,
+//! which is embedded here in its own `.ksyms` section. A kernel built with
+//! plain `cargo build` has an empty table and backtraces show addresses
+//! only.
+//!
+//! The table records where [`rx_ksyms_anchor`] was in the map it came
+//! from. If embedding the table moved code, the anchor does not match the
+//! running kernel and the table is ignored rather than trusted; `cargo
+//! xtask` links until the map settles, so this only happens to kernels
+//! built by hand.
+//!
+//! # Format
+//!
+//! All fields are little-endian `u32`s:
+//!
+//! | Offset | Field |
+//! |--------|-------|
+//! | 0 | Magic `KSYM` |
+//! | 4 | Number of symbols |
+//! | 8 | RVA of `rx_ksyms_anchor` |
+//! | 12 | Size of the name area |
+//! | 16 | `(rva, name offset)` per symbol, sorted by RVA |
+//! | After the entries | Demangled UTF-8 names, back to back |
+//!
+//! A name ends where the next symbol's name starts.
+
+/// Table magic
+pub const KSYMS_MAGIC: [u8; 4] = *b"KSYM";
+
+/// Size of the table header
+const HEADER_SIZE: usize = 16;
+
+/// Size of a symbol entry
+const ENTRY_SIZE: usize = 8;
+
+/// A parsed symbol table
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolTable<'a> {
+    entries: &'a [u8],
+    names: &'a [u8],
+    anchor: u32,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+impl<'a> SymbolTable<'a> {
+    /// Parse a table, checking its sizes
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < HEADER_SIZE || data[..4] != KSYMS_MAGIC {
+            return None;
+        }
+        let count = read_u32(data, 4) as usize;
+        let names_len = read_u32(data, 12) as usize;
+        let names_start = count.checked_mul(ENTRY_SIZE)?.checked_add(HEADER_SIZE)?;
+        if names_start.checked_add(names_len)? != data.len() {
+            return None;
+        }
+        Some(Self {
+            entries: &data[HEADER_SIZE..names_start],
+            names: &data[names_start..],
+            anchor: read_u32(data, 8),
+        })
+    }
+
+    /// Number of symbols
+    pub fn len(&self) -> usize {
+        self.entries.len() / ENTRY_SIZE
+    }
+
+    /// Check whether the table has no symbols
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// RVA of `rx_ksyms_anchor` in the link the table came from
+    pub fn anchor(&self) -> u32 {
+        self.anchor
+    }
+
+    fn rva(&self, i: usize) -> u32 {
+        read_u32(self.entries, i * ENTRY_SIZE)
+    }
+
+    fn name(&self, i: usize) -> &'a str {
+        let start = read_u32(self.entries, i * ENTRY_SIZE + 4) as usize;
+        let end = if i + 1 < self.len() {
+            read_u32(self.entries, (i + 1) * ENTRY_SIZE + 4) as usize
+        } else {
+            self.names.len()
+        };
+        self.names
+            .get(start..end)
+            .and_then(|name| core::str::from_utf8(name).ok())
+            .unwrap_or("?")
+    }
+
+    /// The symbol at or before `rva`: its name and the offset into it
+    pub fn lookup(&self, rva: u32) -> Option<(&'a str, u32)> {
+        // First symbol past `rva`; the one before it contains `rva`
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.rva(mid) <= rva {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let i = lo.checked_sub(1)?;
+        Some((self.name(i), rva - self.rva(i)))
+    }
+}
+
+/// The table generated by build.rs
+#[link_section = ".ksyms"]
+static KSYMS_DATA: [u8; include_bytes!(env!("RUSTUX_KSYMS_PATH")).len()] =
+    *include_bytes!(env!("RUSTUX_KSYMS_PATH"));
+
+/// Slice over the table, read volatile by [`table`] so the table's size
+/// never becomes an immediate in code, which would move code between links
+static KSYMS: &[u8] = &KSYMS_DATA;
+
+/// Reference point for checking that the table matches this kernel
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn rx_ksyms_anchor() -> u32 {
+    u32::from_le_bytes(KSYMS_MAGIC)
+}
+
+/// The embedded table, if it has symbols and matches the running kernel
+pub fn table() -> Option<SymbolTable<'static>> {
+    let data = unsafe { core::ptr::read_volatile(&KSYMS) };
+    let table = SymbolTable::parse(data).filter(|t| !t.is_empty())?;
+    let anchor = crate::kaslr::image_offset(rx_ksyms_anchor as *const () as usize)?;
+    (anchor == table.anchor() as u64).then_some(table)
+}
+
+/// Name the function containing a kernel address
+///
+/// # Returns
+///
+/// The symbol name and the offset of `addr` into it, or `None` outside the
+/// kernel image or without a usable table. Safe on panic paths.
+pub fn symbolize(addr: usize) -> Option<(&'static str, u32)> {
+    let rva = u32::try_from(crate::kaslr::image_offset(addr)?).ok()?;
+    table()?.lookup(rva)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn pack(symbols: &[(u32, &str)], anchor: u32) -> Vec<u8> {
+        let names_len: usize = symbols.iter().map(|(_, name)| name.len()).sum();
+        let mut out = Vec::new();
+        out.extend_from_slice(&KSYMS_MAGIC);
+        out.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+        out.extend_from_slice(&anchor.to_le_bytes());
+        out.extend_from_slice(&(names_len as u32).to_le_bytes());
+        let mut off = 0u32;
+        for (rva, name) in symbols {
+            out.extend_from_slice(&rva.to_le_bytes());
+            out.extend_from_slice(&off.to_le_bytes());
+            off += name.len() as u32;
+        }
+        for (_, name) in symbols {
+            out.extend_from_slice(name.as_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn test_lookup() {
+        let data = pack(&[(0x1000, "rustux::main"), (0x1040, "rx_ksyms_anchor"), (0x2000, "panic")], 0x1040);
+        let table = SymbolTable::parse(&data).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.anchor(), 0x1040);
+        assert_eq!(table.lookup(0xfff), None);
+        assert_eq!(table.lookup(0x1000), Some(("rustux::main", 0)));
+        assert_eq!(table.lookup(0x103f), Some(("rustux::main", 0x3f)));
+        assert_eq!(table.lookup(0x1040), Some(("rx_ksyms_anchor", 0)));
+        assert_eq!(table.lookup(0x2345), Some(("panic", 0x345)));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_tables() {
+        let data = pack(&[(0x1000, "main")], 0);
+        assert!(SymbolTable::parse(&data[..data.len() - 1]).is_none());
+        assert!(SymbolTable::parse(&data[..8]).is_none());
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        assert!(SymbolTable::parse(&bad).is_none());
+
+        // The table build.rs writes without a map
+        let data = pack(&[], 0);
+        let empty = SymbolTable::parse(&data).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.lookup(0x1000), None);
+    }
+}
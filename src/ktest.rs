@@ -0,0 +1,371 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! In-Kernel Tests
+//!
+//! Host `#[test]`s run against mocks: they cannot touch the real heap,
+//! page tables or devices. Tests declared with [`ktest!`] are compiled
+//! into the kernel instead and run in QEMU by `cargo xtask test`, after
+//! the kernel has finished booting.
+//!
+//! # Declaring Tests
+//!
+//! ```ignore
+//! crate::ktest! {
+//!     fn heap_box_roundtrip() {
+//!         let b = alloc::boxed::Box::new(7u64);
+//!         assert_eq!(*b, 7);
+//!     }
+//! }
+//!
+//! crate::ktest! {
+//!     #[should_panic]
+//!     fn overflow_is_checked() {
+//!         let _ = Semaphore::new(2, 1).unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! Tests can live in any module. Without the `kernel_test` feature the
+//! macro expands to nothing, so they cost nothing in normal builds.
+//!
+//! # Registration
+//!
+//! Each test adds a pointer to its [`KTest`] to the `.ktest` section. The
+//! kernel is a PE image, so the linker merges `.ktest$a`, `.ktest$m` and
+//! `.ktest$z` in that order: the runner walks the entries between the
+//! start marker (`$a`) and the end marker (`$z`). Entries are
+//! `Option<&KTest>` so that alignment padding the linker may insert
+//! reads as `None` and is skipped.
+//!
+//! # Running
+//!
+//! Each test runs on the boot CPU, in order:
+//!
+//! - A panic is caught by [`recover_panic`] (called from the kernel panic
+//!   handler), which abandons the test's stack and resumes the runner.
+//!   Destructors do not run and locks the test held stay held.
+//! - Kernel heap usage ([`crate::mm::watermark::HEAP`]) is compared before
+//!   and after a test that returned; a test that leaves heap allocated
+//!   fails. Heap leaked by a panicking test is not reported.
+//! - The interrupt flag is restored to its state before the test.
+//!
+//! # Filtering
+//!
+//! `ktest.filter=<names>` on the kernel command line runs only the tests
+//! whose full name (`module::path::test`) contains one of the
+//! comma-separated `<names>`.
+
+/// Command line option: comma-separated substrings of test names to run
+pub const FILTER_OPTION: &str = "ktest.filter";
+
+/// A registered in-kernel test
+pub struct KTest {
+    /// Full name: module path and function name
+    pub name: &'static str,
+
+    /// Test body
+    pub func: fn(),
+
+    /// Passes only if the body panics
+    pub should_panic: bool,
+}
+
+/// Declare an in-kernel test
+///
+/// See the [module documentation](crate::ktest) for the syntax.
+#[macro_export]
+macro_rules! ktest {
+    (#[should_panic] fn $name:ident() $body:block) => {
+        $crate::ktest!(@register $name, true, $body);
+    };
+    (fn $name:ident() $body:block) => {
+        $crate::ktest!(@register $name, false, $body);
+    };
+    (@register $name:ident, $should_panic:expr, $body:block) => {
+        #[cfg(feature = "kernel_test")]
+        fn $name() $body
+
+        #[cfg(feature = "kernel_test")]
+        const _: () = {
+            #[used]
+            #[link_section = ".ktest$m"]
+            static ENTRY: Option<&$crate::ktest::KTest> = Some(&$crate::ktest::KTest {
+                name: concat!(module_path!(), "::", stringify!($name)),
+                func: $name,
+                should_panic: $should_panic,
+            });
+        };
+    };
+}
+
+/// Whether test `name` is selected by a `ktest.filter` value
+///
+/// An empty filter selects every test.
+pub fn matches_filter(name: &str, filter: &str) -> bool {
+    filter.is_empty() || filter.split(',').any(|f| !f.is_empty() && name.contains(f))
+}
+
+/// Outcome of a test run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Tests that passed
+    pub passed: usize,
+
+    /// Tests that failed
+    pub failed: usize,
+
+    /// Tests skipped by the filter
+    pub filtered: usize,
+}
+
+impl Summary {
+    /// Whether no test failed
+    pub fn ok(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+#[cfg(feature = "kernel_test")]
+pub use runner::{recover_panic, run_all};
+
+// ============================================================================
+// Runner
+// ============================================================================
+
+#[cfg(feature = "kernel_test")]
+mod runner {
+    use super::{matches_filter, KTest, Summary, FILTER_OPTION};
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+    use crate::kerror;
+    use crate::klog::{Level, LogWriter};
+    use crate::mm::watermark::HEAP;
+
+    /// Start marker: sorts before every entry
+    #[used]
+    #[link_section = ".ktest$a"]
+    static KTEST_START: Option<&KTest> = None;
+
+    /// End marker: sorts after every entry
+    #[used]
+    #[link_section = ".ktest$z"]
+    static KTEST_END: Option<&KTest> = None;
+
+    /// Callee-saved registers and stack pointer at the start of a test
+    #[repr(C)]
+    #[derive(Default)]
+    struct JumpBuf {
+        /// rbx, rbp, r12-r15, rsp
+        regs: [u64; 7],
+    }
+
+    /// The test in progress, shared with the panic handler
+    struct Running {
+        /// Where [`recover_panic`] resumes
+        jump: JumpBuf,
+
+        /// CPU running the test
+        cpu: usize,
+
+        /// Set by [`recover_panic`]
+        panicked: AtomicBool,
+    }
+
+    /// Test in progress (null outside a test)
+    static RUNNING: AtomicPtr<Running> = AtomicPtr::new(core::ptr::null_mut());
+
+    // ktest_call(trampoline: rdi, jump: rsi, test: rdx) -> 0 when the test
+    // returns, 1 when resumed by ktest_resume(jump: rdi) after a panic.
+    //
+    // The registers restored are the ones ktest_call's caller expects to
+    // survive the call, so resuming looks like an ordinary return to it.
+    core::arch::global_asm!(
+        ".global ktest_call",
+        ".p2align 4",
+        "ktest_call:",
+        "    mov [rsi + 0], rbx",
+        "    mov [rsi + 8], rbp",
+        "    mov [rsi + 16], r12",
+        "    mov [rsi + 24], r13",
+        "    mov [rsi + 32], r14",
+        "    mov [rsi + 40], r15",
+        "    mov [rsi + 48], rsp",
+        "    mov rax, rdi",
+        "    mov rdi, rdx",
+        "    sub rsp, 8",
+        "    call rax",
+        "    add rsp, 8",
+        "    xor eax, eax",
+        "    ret",
+        "",
+        ".global ktest_resume",
+        ".p2align 4",
+        "ktest_resume:",
+        "    mov rbx, [rdi + 0]",
+        "    mov rbp, [rdi + 8]",
+        "    mov r12, [rdi + 16]",
+        "    mov r13, [rdi + 24]",
+        "    mov r14, [rdi + 32]",
+        "    mov r15, [rdi + 40]",
+        "    mov rsp, [rdi + 48]",
+        "    mov eax, 1",
+        "    ret",
+    );
+
+    extern "C" {
+        fn ktest_call(trampoline: extern "C" fn(*const ()), jump: *mut JumpBuf, test: *const ()) -> u64;
+        fn ktest_resume(jump: *const JumpBuf) -> !;
+    }
+
+    /// Called by `ktest_call` on a fresh frame
+    extern "C" fn trampoline(test: *const ()) {
+        let test = unsafe { &*(test as *const KTest) };
+        (test.func)()
+    }
+
+    /// Registered tests
+    fn entries() -> impl Iterator<Item = &'static KTest> {
+        let start = core::ptr::addr_of!(KTEST_START);
+        let end = core::ptr::addr_of!(KTEST_END);
+        let count = (end as usize - start as usize) / core::mem::size_of::<Option<&KTest>>();
+        // The markers themselves are None and are skipped like padding
+        (0..count).filter_map(move |i| unsafe { core::ptr::read_volatile(start.add(i)) })
+    }
+
+    /// Run `test`, catching a panic
+    ///
+    /// # Returns
+    ///
+    /// True if the test panicked
+    fn run_caught(test: &KTest) -> bool {
+        let mut running = Running {
+            jump: JumpBuf::default(),
+            cpu: crate::arch::amd64::entry::this_cpu(),
+            panicked: AtomicBool::new(false),
+        };
+        RUNNING.store(&mut running, Ordering::Release);
+        unsafe { ktest_call(trampoline, &mut running.jump, test as *const KTest as *const ()) };
+        RUNNING.store(core::ptr::null_mut(), Ordering::Release);
+        running.panicked.load(Ordering::Acquire)
+    }
+
+    /// Run one test and report it
+    ///
+    /// # Returns
+    ///
+    /// True if it passed
+    fn run_one(test: &KTest, out: &mut impl Write) -> bool {
+        let interrupts = x86_64::instructions::interrupts::are_enabled();
+        let heap_before = HEAP.used();
+
+        let panicked = run_caught(test);
+
+        if interrupts {
+            x86_64::instructions::interrupts::enable();
+        } else {
+            x86_64::instructions::interrupts::disable();
+        }
+        let leaked = HEAP.used().saturating_sub(heap_before);
+
+        let _ = write!(out, "[KTEST] {} ... ", test.name);
+        match (panicked, test.should_panic) {
+            (false, false) if leaked > 0 => {
+                let _ = writeln!(out, "FAILED (leaked {} heap bytes)", leaked);
+                false
+            }
+            (false, false) | (true, true) => {
+                let _ = writeln!(out, "ok");
+                true
+            }
+            (false, true) => {
+                let _ = writeln!(out, "FAILED (did not panic)");
+                false
+            }
+            (true, false) => {
+                let _ = writeln!(out, "FAILED (panicked)");
+                false
+            }
+        }
+    }
+
+    /// Run every registered test selected by `ktest.filter`
+    ///
+    /// Progress and results are logged.
+    pub fn run_all() -> Summary {
+        let mut out = LogWriter::new(Level::Info, module_path!());
+        let mut buf = [0u8; 128];
+        let filter = crate::cmdline::get(FILTER_OPTION, &mut buf).unwrap_or("");
+
+        let mut summary = Summary::default();
+        let selected = entries().filter(|t| matches_filter(t.name, filter)).count();
+        let _ = writeln!(out, "[KTEST] running {} tests", selected);
+        for test in entries() {
+            if !matches_filter(test.name, filter) {
+                summary.filtered += 1;
+            } else if run_one(test, &mut out) {
+                summary.passed += 1;
+            } else {
+                summary.failed += 1;
+            }
+        }
+        let _ = writeln!(
+            out,
+            "[KTEST] result: {}. {} passed, {} failed, {} filtered out",
+            if summary.ok() { "ok" } else { "FAILED" },
+            summary.passed,
+            summary.failed,
+            summary.filtered
+        );
+        summary
+    }
+
+    /// Resume the test runner after a panic in a test
+    ///
+    /// Called first thing by the kernel panic handler. Returns if no test
+    /// is running on this CPU, so the handler can go on to halt.
+    pub fn recover_panic(info: &core::panic::PanicInfo) {
+        let running = RUNNING.load(Ordering::Acquire);
+        if running.is_null() {
+            return;
+        }
+        let running = unsafe { &*running };
+        if running.cpu != crate::arch::amd64::entry::this_cpu() {
+            return;
+        }
+        // A panic while reporting this one halts as usual
+        RUNNING.store(core::ptr::null_mut(), Ordering::Release);
+        running.panicked.store(true, Ordering::Release);
+
+        kerror!("[KTEST] {}", info);
+        unsafe { ktest_resume(&running.jump) }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_filter() {
+        assert!(matches_filter("rustux::mm::heap_box", ""));
+        assert!(matches_filter("rustux::mm::heap_box", "heap"));
+        assert!(matches_filter("rustux::mm::heap_box", "vmo,mm::"));
+        assert!(!matches_filter("rustux::mm::heap_box", "vmo"));
+        assert!(!matches_filter("rustux::mm::heap_box", ","));
+    }
+}
+
+crate::ktest! {
+    #[should_panic]
+    fn ktest_panic_is_caught() {
+        panic!("expected panic");
+    }
+}
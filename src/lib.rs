@@ -22,8 +22,8 @@
 //! src/
 //! ├── arch/              # Architecture-specific code
 //! │   ├── amd64/         # x86_64 APIC implementation
-//! │   ├── arm64/         # ARM GIC implementation (TODO)
-//! │   └── riscv64/       # RISC-V PLIC implementation (TODO)
+//! │   ├── arm64/         # ARM GICv2/GICv3 implementation
+//! │   └── riscv64/       # RISC-V PLIC/CLINT implementation
 //! ├── interrupt/         # Generic interrupt handling
 //! ├── mm/                # Memory management (PMM, heap allocator)
 //! ├── drivers/           # Device drivers
@@ -86,12 +86,51 @@ pub mod testing;
 #[cfg(feature = "kernel_test")]
 pub mod test_entry;
 
+// In-kernel tests (ktest! registration and runner)
+pub mod ktest;
+
 // Scheduler and thread management
 pub mod sched;
 
+// Monotonic time (Instant / Duration)
+pub mod time;
+
 // Kernel initialization
 pub mod init;
 
+// Boot command line
+pub mod cmdline;
+
+// Kernel image placement (KASLR)
+pub mod kaslr;
+
+// Kernel version information (embedded by build.rs)
+pub mod version;
+
+// Kernel counters page (mappable by privileged processes)
+pub mod kcounters;
+
+// vDSO time page (syscall-free clock reads)
+pub mod vdso;
+
+// Audit log (policy enforcement events)
+pub mod audit;
+
+// Kernel trace buffer (scheduler timeline)
+pub mod trace;
+
+// Kernel log ring (recent console output)
+pub mod klog;
+
+// Crash dumps to reserved memory (crashkernel=)
+pub mod crashdump;
+
+// Kernel symbol table (function names for backtraces)
+pub mod ksyms;
+
+// Orderly shutdown (power off and reboot)
+pub mod shutdown;
+
 // System call interface
 pub mod syscall;
 
@@ -240,6 +279,12 @@ pub use object::{
     Job, JobId, JobPolicy, ResourceLimits, JobStats, JOB_ID_ROOT, JOB_ID_INVALID,
     // Event
     Event, EventId, EventFlags,
+    // Semaphore
+    Semaphore, SemaphoreId,
+    // Ring buffer
+    RingBuffer, RingBufferId, RingHeader, RingSource,
+    // Pipe
+    Pipe, PipeId,
     // Timer
     Timer, TimerId, TimerState, SlackPolicy,
     // Channel
@@ -9,29 +9,37 @@ extern crate rustux;
 
 use uefi::prelude::*;
 use core::arch::asm;
-use core::ptr::write_volatile;
 
 use rustux::arch::amd64::{descriptor, idt, apic};
+use rustux::arch::X86_64InterruptController;
+use rustux::traits::InterruptController;
 use rustux::drivers::keyboard;
+use rustux::drivers::display::progress::{self, Stage};
+use rustux::klog::{self, SinkId};
+use rustux::{kerror, kinfo, kwarn};
 
-// Note: Global allocator is now in src/mm/allocator.rs (LinkedListAllocator)
+// Note: Global allocator is now in src/mm/allocator.rs (KernelAllocator)
 // The UEFI allocator is no longer used as the global allocator after exit_boot_services()
 
 // Simple keyboard scancode counter (legacy, for compatibility)
 static mut KEYBOARD_COUNT: u32 = 0;
 
+/// Embedded ramdisk image, page-aligned so file pages can be mapped in place
+static RAMDISK_IMAGE: &rustux::fs::ramdisk::PageAligned<[u8]> =
+    &rustux::fs::ramdisk::PageAligned(*include_bytes!(concat!(env!("OUT_DIR"), "/ramdisk.bin")));
+
 /// Initialize the 8042 Keyboard Controller
 ///
 /// This is now a wrapper around the new keyboard::init() function.
 /// The keyboard controller must be initialized to generate IRQ1 interrupts.
 fn keyboard_controller_init() {
     unsafe {
-        debug_print("[KBD] Initializing PS/2 keyboard driver...\n");
+        kinfo!("[KBD] Initializing PS/2 keyboard driver...");
 
         // Use the new keyboard driver module
         keyboard::init();
 
-        debug_print("[KBD] Keyboard driver initialized\n");
+        kinfo!("[KBD] Keyboard driver initialized");
     }
 }
 
@@ -46,11 +54,28 @@ fn main() -> Status {
         let _ = stdout.output_string(msg);
     });
 
-    // PROGRESS MARKER: Entry point reached (RED framebuffer)
-    fb_red();
+    read_boot_cmdline();
+
+    // Boot progress on screen (a red screen with boot.progress=markers)
+    init_boot_progress();
+    progress::begin(Stage::Firmware);
+
+    // With `kaslr` this continues in a relocated copy and does not return
+    place_kernel_image();
+
+    boot_continue();
+}
+
+/// Rest of the UEFI stage, after the kernel image has been placed
+fn boot_continue() -> ! {
+    use uefi::system;
+    use uefi::cstr16;
 
     let _acpi_rsdp = find_acpi_rsdp();
-    let _memory_map = unsafe { uefi::boot::exit_boot_services(None) };
+    let memory_map = unsafe { uefi::boot::exit_boot_services(None) };
+    record_memory_map(&memory_map);
+    rustux::mm::set_detected_memory(usable_memory(&memory_map));
+    rustux::drivers::pci::set_low_ram_top(low_ram_top(&memory_map));
 
     // PROGRESS MARKER: ExitBootServices succeeded
     // This confirms kernel is fully in control of hardware
@@ -59,22 +84,27 @@ fn main() -> Status {
         let _ = stdout.output_string(msg);
     });
 
-    // PROGRESS MARKER: ExitBootServices succeeded (GREEN framebuffer)
-    fb_green();
-
     // SILENT BOOT PHASE ENDS: Now safe to enable debug output
-    unsafe { DEBUG_ENABLED = true; }
+    klog::init();
+    klog::enable_sink(SinkId::Debugcon);
+    rustux::syscall::latency::init();
 
     kernel_main();
 }
 
 fn kernel_main() -> ! {
-    debug_print("╔══════════════════════════════════════════════════════════╗\n");
-    debug_print("║  KERNEL MODE - Testing Interrupts                       ║\n");
-    debug_print("╚══════════════════════════════════════════════════════════╝\n\n");
+    progress::begin(Stage::Memory);
+
+    kinfo!("╔══════════════════════════════════════════════════════════╗");
+    kinfo!("║  KERNEL MODE - Testing Interrupts                       ║");
+    kinfo!("╚══════════════════════════════════════════════════════════╝\n");
 
     // CRITICAL: Initialize PMM first (needed for stack allocation)
     rustux::init::pmm_init();
+    rustux::kaslr::reserve_image();
+    if let Some(image) = rustux::kaslr::kernel_image() {
+        kinfo!("[KASLR] Kernel image {}", if image.randomized { "randomized" } else { "at firmware address" });
+    }
 
     // CRITICAL: Switch to proper kernel stack BEFORE any deep operations
     // The firmware stack is too small and causes corruption during ELF loading.
@@ -87,186 +117,250 @@ fn kernel_main() -> ! {
 /// Continuation of kernel_main() - runs on the new kernel stack
 /// This function is jumped to directly by init_kernel_stack(), it is never called normally.
 fn kernel_main_on_new_stack() -> ! {
-    debug_print("[STACK] Now running on new kernel stack!\n");
+    kinfo!("[STACK] Now running on new kernel stack!");
 
     // Complete the rest of kernel initialization on the new stack
-    debug_print("[INIT] Calling kernel_init_rest()...\n");
+    kinfo!("[INIT] Calling kernel_init_rest()...");
     rustux::init::kernel_init_rest();
-    debug_print("[INIT] kernel_init_rest() returned!\n");
+    kinfo!("[INIT] kernel_init_rest() returned!");
+
+    // `cargo xtask test`: run the in-kernel tests and exit QEMU
+    #[cfg(feature = "kernel_test")]
+    rustux::test_entry::test_kernel_main();
+
+    if rustux::time::init().is_none() {
+        kwarn!("[INIT] WARNING: TSC calibration failed, assuming 2 GHz");
+    }
+    if rustux::kcounters::init().is_err() {
+        kwarn!("[INIT] WARNING: kernel counters page unavailable");
+    }
+    if rustux::vdso::init().is_err() {
+        kwarn!("[INIT] WARNING: vDSO time page unavailable");
+    }
+    rustux::trace::init();
+    if rustux::crashdump::init() {
+        kinfo!("[INIT] The previous boot crashed; see /proc/last-crash");
+    }
+    rustux::arch::amd64::power::init();
 
     // Setup GDT
-    debug_print("[1/5] Setting up GDT...\n");
+    progress::begin(Stage::Cpu);
+    kinfo!("[1/5] Setting up GDT...");
     unsafe { descriptor::gdt_setup(); }
-    debug_print("      ✓ GDT configured\n");
+    unsafe { rustux::arch::amd64::entry::init_cpu(0); }
+    unsafe { rustux::arch::amd64::tsc::cpu_init(0); }
+    kinfo!("      ✓ GDT configured");
 
     // Setup IDT
-    debug_print("[2/5] Setting up IDT...\n");
+    kinfo!("[2/5] Setting up IDT...");
     unsafe { descriptor::idt_setup_readonly(); }
-    debug_print("      ✓ IDT configured\n");
+    unsafe { rustux::arch::amd64::nmi::install(); }
+    unsafe { rustux::arch::amd64::faults::install_double_fault(); }
+    unsafe { rustux::arch::amd64::faults::install_gp_fault(); }
+    unsafe { rustux::arch::amd64::mce::init(); }
+    unsafe { rustux::arch::amd64::pic::install(); }
+    kinfo!("      ✓ IDT configured");
 
     // Install timer handler
-    debug_print("[3/5] Installing timer handler...\n");
+    progress::begin(Stage::Interrupts);
+    kinfo!("[3/5] Installing timer handler...");
     unsafe { idt::idt_set_gate(32, timer_handler as u64, 0x08, 0x8E); }
-    debug_print("      ✓ Timer handler at vector 32\n");
+    kinfo!("      ✓ Timer handler at vector 32");
 
     // Install keyboard handler
-    debug_print("[3.5/5] Installing keyboard handler...\n");
+    kinfo!("[3.5/5] Installing keyboard handler...");
     unsafe { idt::idt_set_gate(33, keyboard_handler as u64, 0x08, 0x8E); }
-    debug_print("      ✓ Keyboard handler at vector 33\n");
+    kinfo!("      ✓ Keyboard handler at vector 33");
 
     // Install syscall handler (int 0x80)
-    debug_print("[3.6/5] Installing syscall handler...\n");
+    kinfo!("[3.6/5] Installing syscall handler...");
     unsafe { idt::idt_set_gate(0x80, syscall_handler as u64, 0x08, 0x8E); }
-    debug_print("      ✓ Syscall handler at vector 0x80\n");
-
-    // Initialize APIC
-    debug_print("[4/5] Initializing APIC...\n");
-    unsafe { apic::apic_local_init(); }
-    debug_print("      ✓ APIC initialized\n");
+    kinfo!("      ✓ Syscall handler at vector 0x80");
+
+    // Install page fault handler (recovers faulting user copies)
+    kinfo!("[3.7/5] Installing page fault handler...");
+    unsafe { rustux::arch::amd64::extable::install(); }
+    kinfo!("      ✓ Page fault handler at vector 14");
+
+    // Give unconfigured PCI BARs an address before any driver probes
+    progress::begin(Stage::Devices);
+    kinfo!("[3.8/5] Scanning PCI...");
+    rustux::drivers::pci::init();
+
+    // Initialize APIC, at the addresses and with the routing from the MADT
+    kinfo!("[4/5] Initializing APIC...");
+    let mut irqs = match rustux::acpi::find_rsdp().and_then(rustux::acpi::find_and_parse_madt) {
+        Some(madt) => X86_64InterruptController::from_madt(&madt),
+        None => {
+            kwarn!("      MADT not found, using default APIC addresses");
+            X86_64InterruptController::new()
+        }
+    };
+    if let Err(e) = irqs.init() {
+        kerror!("      APIC initialization failed: {}", e);
+    }
+    rustux::interrupt::affinity::init();
+    kinfo!("      ✓ APIC initialized");
 
     // Configure keyboard IRQ
-    debug_print("[4.5/5] Configuring keyboard IRQ...\n");
-    unsafe { apic::apic_io_init(1, 33); }
-    debug_print("      ✓ IRQ1 → Vector 33\n");
+    kinfo!("[4.5/5] Configuring keyboard IRQ...");
+    irqs.enable_irq(1, 33);
+    kinfo!("      ✓ IRQ1 → GSI {} → Vector 33", irqs.route(1).0);
 
     // Initialize keyboard controller
-    debug_print("[4.6/5] Initializing keyboard controller...\n");
+    kinfo!("[4.6/5] Initializing keyboard controller...");
     keyboard_controller_init();
-    debug_print("      ✓ Keyboard controller initialized\n");
+    kinfo!("      ✓ Keyboard controller initialized");
+
+    // Serial output: synchronous until the IRQ is routed
+    kinfo!("[4.7/5] Configuring COM1...");
+    {
+        use rustux::drivers::uart::{self, COM1_IRQ, COM1_VECTOR};
+        unsafe {
+            uart::init_com1();
+            klog::enable_sink(SinkId::Uart);
+            idt::idt_set_gate(COM1_VECTOR, com1_handler as u64, 0x08, 0x8E);
+        }
+        irqs.enable_irq(COM1_IRQ as u64, COM1_VECTOR as u64);
+        unsafe {
+            uart::enable_com1_tx_interrupt();
+            uart::enable_com1_rx_interrupt();
+        }
+    }
+    kinfo!("      ✓ IRQ4 → Vector 36 (COM1 transmit/receive)");
 
-    // Configure timer
-    debug_print("[5/5] Configuring timer...\n");
-    unsafe {
-        let lapic = 0xFEE00000usize;
-        write_volatile((lapic + 0x3E0) as *mut u32, 0x03);
-        write_volatile((lapic + 0x320) as *mut u32, 32 | (1 << 17));
-        write_volatile((lapic + 0x380) as *mut u32, 10_000_000);
+    // Configure timer: the best clock event device drives the tick
+    kinfo!("[5/5] Configuring timer...");
+    if unsafe { apic::lapic_timer_init() }.is_none() {
+        kwarn!("      Local APIC timer calibration failed");
     }
-    debug_print("      ✓ Timer configured\n\n");
+    match rustux::time::clockevents::start_tick(32, rustux::time::clockevents::TICK_PERIOD) {
+        Ok(name) => kinfo!("      ✓ Timer configured ({}, vector 32)", name),
+        Err(_) => kerror!("      No timer available"),
+    }
+
+    // Boot-only code is done; the APs start on the final mappings
+    unsafe { rustux::arch::amd64::kprotect::init(); }
+
+    // Start the other CPUs (only with smp on the command line)
+    if rustux::arch::amd64::smp::init() > 0 {
+        kinfo!("      ✓ Application processors started");
+    }
+    kinfo!("");
 
     // Initialize display console (Phase 6B)
-    debug_print("╔══════════════════════════════════════════════════════════╗\n");
-    debug_print("║  PHASE 6B: Initializing Display Console                   ║\n");
-    debug_print("╚══════════════════════════════════════════════════════════╝\n\n");
+    progress::begin(Stage::Display);
+    kinfo!("╔══════════════════════════════════════════════════════════╗");
+    kinfo!("║  PHASE 6B: Initializing Display Console                   ║");
+    kinfo!("╚══════════════════════════════════════════════════════════╝\n");
     unsafe {
         init_display_console();
     }
-    debug_print("      ✓ Display console initialized\n\n");
+    kinfo!("      ✓ Display console initialized\n");
 
     // Initialize ramdisk (Phase 5C)
-    debug_print("╔══════════════════════════════════════════════════════════╗\n");
-    debug_print("║  PHASE 5C: Initializing Ramdisk                          ║\n");
-    debug_print("╚══════════════════════════════════════════════════════════╝\n\n");
+    progress::begin(Stage::Filesystem);
+    kinfo!("╔══════════════════════════════════════════════════════════╗");
+    kinfo!("║  PHASE 5C: Initializing Ramdisk                          ║");
+    kinfo!("╚══════════════════════════════════════════════════════════╝\n");
     unsafe {
-        rustux::fs::ramdisk::init_ramdisk(include_bytes!(concat!(env!("OUT_DIR"), "/ramdisk.bin")));
+        rustux::fs::ramdisk::init_ramdisk(&RAMDISK_IMAGE.0);
+    }
+    rustux::fs::verify::verify_ramdisk(&RAMDISK_IMAGE.0);
+    if let Ok(ramdisk) = rustux::fs::ramdisk::get_ramdisk() {
+        match rustux::fs::manifest::verify_manifest(ramdisk) {
+            Some(report) if report.is_clean() => kinfo!("      ✓ Ramdisk matches manifest"),
+            Some(_) => kwarn!("[RAMDISK] WARNING: ramdisk does not match its manifest"),
+            None => kwarn!("[RAMDISK] WARNING: ramdisk has no manifest"),
+        }
+    }
+    kinfo!("      ✓ Ramdisk initialized\n");
+
+    // RAM block device (ram0.size= / ram0.image=), for filesystem tests
+    rustux::drivers::block::ram::init();
+
+    // FAT32 volumes named by fat.mount=, once their devices exist
+    rustux::fs::mount::init();
+
+    // Memory self-tests (selftest=mm); boot continues even on failure
+    if let Some(report) = rustux::mm::selftest::run_if_requested() {
+        if report.ok() {
+            kinfo!("      ✓ Memory self-tests passed\n");
+        } else {
+            kwarn!("[SELFTEST] WARNING: memory self-tests failed, continuing boot\n");
+        }
     }
-    debug_print("      ✓ Ramdisk initialized\n\n");
 
     // Try to load and execute init.elf from ramdisk (Phase 5D)
-    debug_print("╔══════════════════════════════════════════════════════════╗\n");
-    debug_print("║  PHASE 5D: Loading Init Process                         ║\n");
-    debug_print("╚══════════════════════════════════════════════════════════╝\n\n");
+    progress::begin(Stage::Userspace);
+    kinfo!("╔══════════════════════════════════════════════════════════╗");
+    kinfo!("║  PHASE 5D: Loading Init Process                         ║");
+    kinfo!("╚══════════════════════════════════════════════════════════╝\n");
 
     let init_loaded = unsafe {
         use rustux::fs::ramdisk;
-        use rustux::exec::load_elf_process;
+        use rustux::exec::{load_elf_process, ExecArgs};
         use rustux::process::table::{Process, PROCESS_TABLE};
 
         // Get the ramdisk
         let ramdisk = match ramdisk::get_ramdisk() {
             Ok(r) => r,
             Err(_) => {
-                debug_print("[INIT] Ramdisk not available, skipping init load\n\n");
+                kinfo!("[INIT] Ramdisk not available, skipping init load\n");
                 false
             }
         };
 
+        // Refuse to execute anything from an unverified ramdisk
+        if !rustux::fs::verify::spawn_allowed() {
+            kerror!("[INIT] Ramdisk not verified, refusing to load init, halting...\n");
+            loop { asm!("hlt"); }
+        }
+
         // Look for init.elf in ramdisk
         let init_file = match ramdisk.find_file("bin/init") {
             Some(f) => f,
             None => {
-                debug_print("[INIT] init.elf not found in ramdisk, skipping\n\n");
+                kinfo!("[INIT] init.elf not found in ramdisk, skipping\n");
                 false
             }
         };
 
-        debug_print("[INIT] Found init.elf in ramdisk\n");
-        debug_print("[INIT] File size: ");
-        print_hex(init_file.size as u64);
-        debug_print(" bytes\n");
+        kinfo!("[INIT] Found init.elf in ramdisk");
+        kinfo!("[INIT] File size: {:x} bytes", init_file.size);
 
         // Read the ELF data from ramdisk
         let elf_data_ptr = ramdisk.data.as_ptr().add(init_file.data_offset as usize);
         let elf_data = core::slice::from_raw_parts(elf_data_ptr, init_file.size as usize);
 
-        debug_print("[INIT] Loading ELF binary...\n");
+        kinfo!("[INIT] Loading ELF binary...");
 
         // Load the ELF binary
-        let process_image = match load_elf_process(elf_data) {
+        let process_image = match load_elf_process(elf_data, &ExecArgs::with_name("/bin/init")) {
             Ok(img) => img,
             Err(e) => {
-                debug_print("[INIT] Failed to load ELF: ");
-                for &b in e.as_bytes() {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-                debug_print("\n");
+                kerror!("[INIT] Failed to load ELF: {}", e);
                 false
             }
         };
 
-        debug_print("[INIT] ELF loaded successfully\n");
-        debug_print("[INIT] Entry point: 0x");
-        print_hex(process_image.entry);
-        debug_print("\n");
+        kinfo!("[INIT] ELF loaded successfully");
+        kinfo!("[INIT] Entry point: {:#x}", process_image.entry);
 
         // Allocate kernel stack (4 pages)
-        let kernel_stack_paddrs = [
-            match rustux::mm::pmm::pmm_alloc_kernel_page() {
-                Ok(p) => p,
-                Err(_) => {
-                    debug_print("[INIT] Failed to allocate kernel stack\n");
-                    false
-                }
-            },
-            match rustux::mm::pmm::pmm_alloc_kernel_page() {
-                Ok(p) => p,
-                Err(_) => {
-                    debug_print("[INIT] Failed to allocate kernel stack\n");
-                    false
-                }
-            },
-            match rustux::mm::pmm::pmm_alloc_kernel_page() {
-                Ok(p) => p,
-                Err(_) => {
-                    debug_print("[INIT] Failed to allocate kernel stack\n");
-                    false
-                }
-            },
-            match rustux::mm::pmm::pmm_alloc_kernel_page() {
-                Ok(p) => p,
-                Err(_) => {
-                    debug_print("[INIT] Failed to allocate kernel stack\n");
-                    false
-                }
-            },
-        ];
-
-        // Get the kernel stack virtual addresses
-        let kernel_stack_vaddrs = [
-            rustux::mm::pmm::paddr_to_vaddr(kernel_stack_paddrs[0]),
-            rustux::mm::pmm::paddr_to_vaddr(kernel_stack_paddrs[1]),
-            rustux::mm::pmm::paddr_to_vaddr(kernel_stack_paddrs[2]),
-            rustux::mm::pmm::paddr_to_vaddr(kernel_stack_paddrs[3]),
-        ];
-
-        // Stack grows down, so top is at the highest address
-        let kernel_stack_top = (kernel_stack_vaddrs[3] + 4096) as u64;
+        let kernel_stack_top = match rustux::process::table::alloc_kernel_stack() {
+            Ok(top) => top,
+            Err(_) => {
+                kerror!("[INIT] Failed to allocate kernel stack");
+                false
+            }
+        };
 
         // Get page table physical address
         let page_table_phys = process_image.address_space.page_table.phys;
 
         // Create process with PID 1
-        let process = Process::new(
+        let mut process = Process::new(
             1,  // PID 1 (init)
             0,  // PPID 0 (kernel)
             page_table_phys,
@@ -279,24 +373,29 @@ fn kernel_main_on_new_stack() -> ! {
         let mut name_owned = alloc::string::String::from("init");
         process.set_name(name_owned);
 
+        // init is trusted to map the kernel counters page
+        process.privileged = true;
+        // and holds the root job (handle 1), the key to the kernel log
+        let _ = process.handles.insert(rustux::process::jobs::root_job_handle());
+        // Counted in the root job like every later process (it has no limits)
+        let _ = rustux::process::jobs::admit(process.job_id);
+        let _ = process_image.vmar.charge_to(process.job_id);
+        rustux::process::vmar::install(page_table_phys, process_image.vmar);
+
         // Add to process table
         PROCESS_TABLE.lock().insert(process);
         PROCESS_TABLE.lock().set_current(1);
 
-        debug_print("[INIT] Process created with PID 1\n");
-        debug_print("[INIT] Kernel stack: 0x");
-        print_hex(kernel_stack_top);
-        debug_print("\n");
-        debug_print("[INIT] User stack: 0x");
-        print_hex(process_image.stack_top);
-        debug_print("\n");
-        debug_print("[INIT] Page table: 0x");
-        print_hex(page_table_phys);
-        debug_print("\n\n");
-
-        debug_print("╔══════════════════════════════════════════════════════════╗\n");
-        debug_print("║  Jumping to Init Process (Userspace)                   ║\n");
-        debug_print("╚══════════════════════════════════════════════════════════╝\n\n");
+        kinfo!("[INIT] Process created with PID 1");
+        kinfo!("[INIT] Kernel stack: {:#x}", kernel_stack_top);
+        kinfo!("[INIT] User stack: {:#x}", process_image.stack_top);
+        kinfo!("[INIT] Page table: {:#x}\n", page_table_phys);
+
+        kinfo!("╔══════════════════════════════════════════════════════════╗");
+        kinfo!("║  Jumping to Init Process (Userspace)                   ║");
+        kinfo!("╚══════════════════════════════════════════════════════════╝\n");
+
+        progress::finish();
 
         // Execute the init process - never returns
         rustux::arch::amd64::uspace::execute_process(
@@ -310,14 +409,14 @@ fn kernel_main_on_new_stack() -> ! {
     };
 
     if !init_loaded {
-        debug_print("[INIT] Failed to load init process, halting...\n");
+        kerror!("[INIT] Failed to load init process, halting...");
         loop { unsafe { asm!("hlt"); } }
     }
 
     // Enable interrupts
-    debug_print("╔══════════════════════════════════════════════════════════╗\n");
-    debug_print("║  PHASE 4A: Testing Userspace Execution                  ║\n");
-    debug_print("╚══════════════════════════════════════════════════════════╝\n\n");
+    kinfo!("╔══════════════════════════════════════════════════════════╗");
+    kinfo!("║  PHASE 4A: Testing Userspace Execution                  ║");
+    kinfo!("╚══════════════════════════════════════════════════════════╝\n");
 
     unsafe { asm!("sti"); }
 
@@ -326,12 +425,12 @@ fn kernel_main_on_new_stack() -> ! {
     // NOTE: This is now done in main() before exiting boot services
     // because the UEFI allocator is needed for heap allocations
 
-    debug_print("╔══════════════════════════════════════════════════════════╗\n");
-    debug_print("║  Userspace test moved to UEFI mode                   ║\n");
-    debug_print("╚══════════════════════════════════════════════════════════╝\n\n");
+    kinfo!("╔══════════════════════════════════════════════════════════╗");
+    kinfo!("║  Userspace test moved to UEFI mode                   ║");
+    kinfo!("╚══════════════════════════════════════════════════════════╝\n");
 
     // Never reached
-    loop { unsafe { asm!("hlt"); } }
+    loop { rustux::arch::amd64::power::idle(); }
 }
 
 // Keyboard handler (IRQ1 = Vector 33)
@@ -339,30 +438,44 @@ fn kernel_main_on_new_stack() -> ! {
 pub extern "x86-interrupt" fn keyboard_handler(_sf: idt::X86Iframe) {
     use rustux::drivers::keyboard;
 
+    let _gs = unsafe { rustux::arch::amd64::entry::GsGuard::paranoid() };
+
+    rustux::kcounters::record_irq(33);
+
     unsafe {
         // Use the new keyboard driver module to handle the IRQ
         keyboard::handle_irq();
 
         // Debug: show we received an interrupt
-        // debug_print("[K]\n");
-
-        // Send EOI to LAPIC (write 0 to EOI register at offset 0x40)
-        let lapic = 0xFEE00000usize;
-        write_volatile((lapic + 0x40) as *mut u32, 0);
+        // kinfo!("[K]");
     }
+
+    apic::apic_send_eoi(1);
+}
+
+// COM1 handler (IRQ4 = Vector 36)
+#[no_mangle]
+pub extern "x86-interrupt" fn com1_handler(_sf: idt::X86Iframe) {
+    let _gs = unsafe { rustux::arch::amd64::entry::GsGuard::paranoid() };
+    rustux::kcounters::record_irq(rustux::drivers::uart::COM1_VECTOR);
+    rustux::drivers::uart::handle_com1_irq();
+
+    apic::apic_send_eoi(rustux::drivers::uart::COM1_IRQ as u32);
 }
 
 // Timer handler (Vector 32)
 #[no_mangle]
 pub extern "x86-interrupt" fn timer_handler(_sf: idt::X86Iframe) {
-    unsafe {
-        let msg = b"[TICK]\n";
-        for &b in msg {
-            asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack, preserves_flags));
-        }
-        let lapic = 0xFEE00000usize;
-        write_volatile((lapic + 0xB0) as *mut u32, 0);
-    }
+    let _gs = unsafe { rustux::arch::amd64::entry::GsGuard::paranoid() };
+    rustux::kcounters::record_irq(32);
+    rustux::vdso::update();
+    rustux::time::timer_queue::tick(rustux::time::Instant::now());
+    rustux::interrupt::affinity::balance_tick();
+
+    // Heartbeat for test-qemu.sh, kept out of the log ring
+    klog::debugcon_write(b"[TICK]\n");
+
+    apic::apic_send_eoi(0);
 }
 
 // Syscall handler (int 0x80 = Vector 0x80)
@@ -374,17 +487,11 @@ pub extern "x86-interrupt" fn timer_handler(_sf: idt::X86Iframe) {
 pub extern "x86-interrupt" fn syscall_handler(sf: idt::X86Iframe) {
     use rustux::syscall::{SyscallArgs, syscall_dispatch};
 
-    // PROOF: Syscall reached - fill top half CYAN to verify
-    unsafe {
-        if FRAMEBUFFER_ADDR != 0 {
-            let fb_ptr = FRAMEBUFFER_ADDR as *mut u16;
-            let pixel_count = FRAMEBUFFER_SIZE as usize / 2;
-            // Fill top half with CYAN (RGB565: 0x07FF)
-            for i in 0..(pixel_count / 2) {
-                *(fb_ptr.add(i)) = 0x07FF;
-            }
-        }
-    }
+    // The frame layout doesn't give us the pushed CS; decide from GS_BASE
+    let _gs = unsafe { rustux::arch::amd64::entry::GsGuard::paranoid() };
+
+    // PROGRESS MARKER: Syscall reached (CYAN with boot.progress=markers)
+    progress::marker(progress::MARKER_SYSCALL);
 
     let syscall_num = sf.rax as u32;
 
@@ -405,64 +512,171 @@ pub extern "x86-interrupt" fn syscall_handler(sf: idt::X86Iframe) {
     let _ret = syscall_dispatch(syscall_args);
 }
 
-fn find_acpi_rsdp() -> Option<u64> {
-    use uefi::table::cfg::ConfigTableEntry;
-    let mut result = None;
-    uefi::system::with_config_table(|slice| {
-        for entry in slice {
-            if entry.guid == ConfigTableEntry::ACPI2_GUID && !entry.address.is_null() {
-                result = Some(entry.address as u64);
-                break;
+/// Copy the UEFI load options into the kernel command line
+///
+/// Must be called before exit_boot_services() while the LoadedImage
+/// protocol is still available.
+#[link_section = ".inittxt"]
+fn read_boot_cmdline() {
+    use uefi::boot;
+    use uefi::proto::loaded_image::LoadedImage;
+
+    if let Ok(loaded_image) = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle()) {
+        if let Some(options) = loaded_image.load_options_as_bytes() {
+            rustux::cmdline::init_from_ucs2(options);
+        }
+    }
+}
+
+/// Record where the kernel image is and, with `kaslr`, move it
+///
+/// See `rustux::kaslr`. When the image is moved this does not return:
+/// execution continues in [`relocated_entry`] inside the copy. On any
+/// failure the kernel stays where the firmware loaded it.
+#[link_section = ".inittxt"]
+fn place_kernel_image() {
+    use uefi::boot::{self, AllocateType, MemoryType};
+    use uefi::proto::loaded_image::LoadedImage;
+    use rustux::kaslr::{self, KernelImage};
+
+    let (base, size) = match boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle()) {
+        Ok(loaded_image) => {
+            let (base, size) = loaded_image.info();
+            (base as u64, size)
+        }
+        Err(_) => return,
+    };
+    let image = unsafe { core::slice::from_raw_parts(base as *const u8, size as usize) };
+    let link_base = kaslr::parse_pe(image).map_or(base, |info| info.image_base);
+    kaslr::record(KernelImage { load_base: base, link_base, size, randomized: false });
+
+    if !kaslr::enabled() {
+        return;
+    }
+
+    let mut entropy = kaslr::boot_entropy();
+    let pages = ((size + 0xFFF) / 0x1000) as usize;
+    for _ in 0..kaslr::PLACEMENT_ATTEMPTS {
+        let new_base = match kaslr::candidate(&mut entropy, size) {
+            Some(new_base) if new_base != base => new_base,
+            Some(_) => continue,
+            None => return,
+        };
+        let copy_ptr = match boot::allocate_pages(AllocateType::Address(new_base), MemoryType::LOADER_CODE, pages) {
+            Ok(ptr) => ptr,
+            Err(_) => continue, // Taken; try another candidate
+        };
+
+        let copy = unsafe { core::slice::from_raw_parts_mut(copy_ptr.as_ptr(), size as usize) };
+        copy.copy_from_slice(image);
+        let delta = new_base.wrapping_sub(base);
+        if kaslr::apply_relocations(copy, delta).is_err() {
+            // Not relocatable; no other candidate will do better
+            unsafe {
+                let _ = boot::free_pages(copy_ptr, pages);
             }
+            return;
         }
-    });
-    result
+
+        // Same function, in the copy
+        let entry = (relocated_entry as *const () as u64).wrapping_add(delta);
+        let entry: extern "C" fn(u64) -> ! = unsafe { core::mem::transmute(entry as usize) };
+        entry(new_base);
+    }
 }
 
-/// Fill the framebuffer with a solid color for progress indication
+/// Entry point inside the relocated image copy
 ///
-/// Color format: RGB565
-/// - Red:   0xF800
-/// - Green: 0x07E0
-/// - Blue:  0x001F
-/// - White: 0xFFFF
-/// - Black: 0x0000
-fn fill_framebuffer_color(color_rgb565: u32) {
-    use uefi::boot;
-    use uefi::proto::console::gop::GraphicsOutput;
-    use core::mem::transmute;
+/// The copy's statics were taken before the move, so its placement record
+/// still describes the old image.
+extern "C" fn relocated_entry(load_base: u64) -> ! {
+    use rustux::kaslr::{self, KernelImage};
 
-    unsafe {
-        // Get GOP handle using the boot services API
-        let gop_handle = boot::get_handle_for_protocol::<GraphicsOutput>()
-            .expect("Failed to get GOP handle");
+    if let Some(old) = kaslr::kernel_image() {
+        kaslr::record(KernelImage { load_base, randomized: true, ..old });
+    }
+    boot_continue();
+}
 
-        // Open GOP protocol
-        let mut gop = boot::open_protocol_exclusive::<GraphicsOutput>(gop_handle)
-            .expect("Failed to open GOP protocol");
+/// Bytes of RAM the kernel may use after ExitBootServices
+///
+/// Boot services and loader memory is free once the firmware is gone.
+fn usable_memory(map: &impl uefi::mem::memory_map::MemoryMap) -> u64 {
+    use uefi::mem::memory_map::MemoryType;
+
+    map.entries()
+        .filter(|d| matches!(
+            d.ty,
+            MemoryType::CONVENTIONAL
+                | MemoryType::BOOT_SERVICES_CODE
+                | MemoryType::BOOT_SERVICES_DATA
+                | MemoryType::LOADER_CODE
+                | MemoryType::LOADER_DATA
+        ))
+        .map(|d| d.page_count * 4096)
+        .sum()
+}
 
-        let mode = gop.current_mode_info();
-        let fb = gop.frame_buffer();
+/// Copy the firmware memory map into the kernel's, for the PMM arenas
+fn record_memory_map(map: &impl uefi::mem::memory_map::MemoryMap) {
+    use rustux::mm::memmap::{self, Region};
 
-        // Use transmute to convert FrameBuffer to a mutable u8 slice
-        // This is unsafe but necessary because the FrameBuffer type doesn't expose the slice directly
-        let fb_slice: &mut [u8] = transmute_copy(&fb);
+    for d in map.entries() {
+        memmap::record(Region::new(d.phys_start, d.page_count * 4096, region_kind(d.ty)));
+    }
+}
 
-        // Fill the framebuffer with the color
-        let pixel_count = mode.resolution().0 * mode.resolution().1;
-        let color_bytes = [
-            (color_rgb565 & 0xFF) as u8,
-            ((color_rgb565 >> 8) & 0xFF) as u8,
-        ];
-
-        for i in 0..pixel_count {
-            let offset = i * 2;
-            if offset + 1 < fb_slice.len() {
-                fb_slice[offset] = color_bytes[0];
-                fb_slice[offset + 1] = color_bytes[1];
+/// What a memory map descriptor's type means to the kernel
+fn region_kind(ty: uefi::mem::memory_map::MemoryType) -> rustux::mm::memmap::RegionKind {
+    use rustux::mm::memmap::RegionKind;
+    use uefi::mem::memory_map::MemoryType;
+
+    match ty {
+        MemoryType::CONVENTIONAL => RegionKind::Usable,
+        MemoryType::BOOT_SERVICES_CODE
+        | MemoryType::BOOT_SERVICES_DATA
+        | MemoryType::LOADER_CODE
+        | MemoryType::LOADER_DATA => RegionKind::Reclaimable,
+        MemoryType::ACPI_RECLAIM | MemoryType::ACPI_NON_VOLATILE => RegionKind::Acpi,
+        MemoryType::MMIO | MemoryType::MMIO_PORT_SPACE => RegionKind::Mmio,
+        _ => RegionKind::Reserved,
+    }
+}
+
+/// End of RAM below 4 GiB
+///
+/// The PCI memory window is placed above it.
+fn low_ram_top(map: &impl uefi::mem::memory_map::MemoryMap) -> u64 {
+    use uefi::mem::memory_map::MemoryType;
+
+    map.entries()
+        .filter(|d| !matches!(
+            d.ty,
+            MemoryType::RESERVED
+                | MemoryType::UNUSABLE
+                | MemoryType::MMIO
+                | MemoryType::MMIO_PORT_SPACE
+                | MemoryType::PAL_CODE
+        ))
+        .map(|d| d.phys_start + d.page_count * 4096)
+        .filter(|&end| end <= 1 << 32)
+        .max()
+        .unwrap_or(0)
+}
+
+#[link_section = ".inittxt"]
+fn find_acpi_rsdp() -> Option<u64> {
+    use uefi::table::cfg::ConfigTableEntry;
+    let mut result = None;
+    uefi::system::with_config_table(|slice| {
+        for entry in slice {
+            if entry.guid == ConfigTableEntry::ACPI2_GUID && !entry.address.is_null() {
+                result = Some(entry.address as u64);
+                break;
             }
         }
-    }
+    });
+    result
 }
 
 // Helper function for transmuting references
@@ -476,85 +690,65 @@ unsafe fn transmute_copy<T, U>(src: &T) -> U {
     dst
 }
 
-/// Fill framebuffer red - EFI entry point reached
-fn fb_red() {
-    fill_framebuffer_color(0xF800);
-}
-
-/// Fill framebuffer green - ExitBootServices succeeded
-/// Also saves framebuffer info for post-ExitBootServices use
-fn fb_green() {
+/// Record the GOP framebuffer and start the boot progress display
+///
+/// Runs while boot services are up; the framebuffer stays at the same
+/// physical address after ExitBootServices.
+fn init_boot_progress() {
     use uefi::boot;
     use uefi::proto::console::gop::GraphicsOutput;
 
     unsafe {
-        let gop_handle = boot::get_handle_for_protocol::<GraphicsOutput>()
-            .expect("Failed to get GOP handle");
-
-        let mut gop = boot::open_protocol_exclusive::<GraphicsOutput>(gop_handle)
-            .expect("Failed to open GOP protocol");
+        let Ok(gop_handle) = boot::get_handle_for_protocol::<GraphicsOutput>() else { return };
+        let Ok(mut gop) = boot::open_protocol_exclusive::<GraphicsOutput>(gop_handle) else { return };
 
         let mode = gop.current_mode_info();
         let fb = gop.frame_buffer();
 
         // Use transmute_copy to convert FrameBuffer to a mutable u8 slice
         let fb_slice: &mut [u8] = transmute_copy(&fb);
-        let fb_addr = fb_slice.as_mut_ptr() as u64;
         let pixel_count = mode.resolution().0 * mode.resolution().1;
 
         // Save framebuffer info for later use
-        FRAMEBUFFER_ADDR = fb_addr;
+        FRAMEBUFFER_ADDR = fb_slice.as_mut_ptr() as u64;
         FRAMEBUFFER_SIZE = (pixel_count * 2) as u64; // 2 bytes per pixel (RGB565)
         FRAMEBUFFER_WIDTH = mode.resolution().0;
         FRAMEBUFFER_HEIGHT = mode.resolution().1;
 
-        // Fill with green (0x07E0 in RGB565)
-        let color_bytes = [0xE0, 0x07]; // Little-endian RGB565
-        for i in 0..pixel_count {
-            let offset = i * 2;
-            if offset + 1 < fb_slice.len() {
-                fb_slice[offset] = color_bytes[0];
-                fb_slice[offset + 1] = color_bytes[1];
-            }
+        if let Some(framebuffer) = boot_framebuffer(0) {
+            rustux::drivers::display::progress::init(framebuffer);
         }
     }
 }
 
+/// The saved framebuffer, less `reserved` pixel rows at the bottom
+unsafe fn boot_framebuffer(reserved: usize) -> Option<rustux::drivers::display::Framebuffer> {
+    use rustux::drivers::display::{Framebuffer, PixelFormat};
+
+    if FRAMEBUFFER_ADDR == 0 {
+        return None;
+    }
+
+    // Calculate pitch (stride) from width and bytes per pixel
+    let bpp = 16; // RGB565
+    let pitch = FRAMEBUFFER_WIDTH * (bpp / 8);
+
+    Some(Framebuffer::new(
+        FRAMEBUFFER_ADDR,
+        FRAMEBUFFER_WIDTH,
+        FRAMEBUFFER_HEIGHT.saturating_sub(reserved),
+        pitch,
+        bpp,
+        PixelFormat::RGB,
+    ))
+}
+
 // Save framebuffer info for use after ExitBootServices
 static mut FRAMEBUFFER_ADDR: u64 = 0;
 static mut FRAMEBUFFER_SIZE: u64 = 0;
 static mut FRAMEBUFFER_WIDTH: usize = 0;
 static mut FRAMEBUFFER_HEIGHT: usize = 0;
 
-/// Fill framebuffer blue - CR3 load succeeded (works after ExitBootServices)
-/// NOTE: Must be called after fb_green() to capture framebuffer address
-pub extern "C" fn fb_blue() {
-    unsafe {
-        if FRAMEBUFFER_ADDR == 0 {
-            return; // No framebuffer available
-        }
-        let fb_ptr = FRAMEBUFFER_ADDR as *mut u16;
-        let pixel_count = FRAMEBUFFER_SIZE as usize / 2;
-        for i in 0..pixel_count {
-            *(fb_ptr.add(i)) = 0x001F; // Blue (RGB565)
-        }
-    }
-}
-
-/// Fill framebuffer white - About to IRETQ to userspace (works after ExitBootServices)
-pub extern "C" fn fb_white() {
-    unsafe {
-        if FRAMEBUFFER_ADDR == 0 {
-            return; // No framebuffer available
-        }
-        let fb_ptr = FRAMEBUFFER_ADDR as *mut u16;
-        let pixel_count = FRAMEBUFFER_SIZE as usize / 2;
-        for i in 0..pixel_count {
-            *(fb_ptr.add(i)) = 0xFFFF; // White (RGB565)
-        }
-    }
-}
-
 /// Get the framebuffer address (for passing to userspace)
 pub fn get_framebuffer_addr() -> u64 {
     unsafe { FRAMEBUFFER_ADDR }
@@ -577,101 +771,37 @@ pub fn get_framebuffer_height() -> usize {
 
 /// Initialize the display console
 ///
-/// This function should be called after fb_green() to initialize
-/// the text console using the framebuffer information.
+/// This function should be called after init_boot_progress() to initialize
+/// the text console using the framebuffer information. The console ends
+/// above the boot progress bar.
 pub unsafe fn init_display_console() {
-    use rustux::drivers::display::{Framebuffer, PixelFormat, init as display_init};
+    use rustux::drivers::display::init as display_init;
 
-    if FRAMEBUFFER_ADDR == 0 {
-        debug_print("[DISPLAY] No framebuffer available, skipping console init\n");
+    let Some(framebuffer) = boot_framebuffer(progress::reserved_height()) else {
+        kinfo!("[DISPLAY] No framebuffer available, skipping console init");
         return;
-    }
-
-    // Calculate pitch (stride) from width and bytes per pixel
-    let bpp = 16; // RGB565
-    let pitch = FRAMEBUFFER_WIDTH * (bpp / 8);
-
-    let framebuffer = Framebuffer::new(
-        FRAMEBUFFER_ADDR,
-        FRAMEBUFFER_WIDTH,
-        FRAMEBUFFER_HEIGHT,
-        pitch,
-        bpp,
-        PixelFormat::RGB,
-    );
+    };
 
     display_init(framebuffer);
+    klog::enable_sink(SinkId::Console);
 
-    debug_print("[DISPLAY] Text console initialized\n");
-    debug_print("[DISPLAY] Resolution: ");
-    print_hex(FRAMEBUFFER_WIDTH as u64);
-    debug_print("x");
-    print_hex(FRAMEBUFFER_HEIGHT as u64);
-    debug_print("\n");
-}
-
-/// Fill framebuffer yellow - Process exited
-/// Works after ExitBootServices
-pub extern "C" fn fb_yellow() {
-    unsafe {
-        if FRAMEBUFFER_ADDR == 0 {
-            return; // No framebuffer available
-        }
-        let fb_ptr = FRAMEBUFFER_ADDR as *mut u16;
-        let pixel_count = FRAMEBUFFER_SIZE as usize / 2;
-        for i in 0..pixel_count {
-            *(fb_ptr.add(i)) = 0xFFE0; // Yellow (RGB565)
-        }
-    }
-}
-
-const QEMU_DEBUGCON_PORT: u16 = 0xE9;
-
-fn qemu_debugcon_write_byte(b: u8) {
-    unsafe {
-        asm!("out dx, al", in("dx") QEMU_DEBUGCON_PORT, in("al") b, options(nostack, nomem));
-    }
-}
-
-// UEFI-safe debug functions (no-op before exit_boot_services)
-static mut DEBUG_ENABLED: bool = false;
-
-#[inline(always)]
-fn debug_print(s: &str) {
-    unsafe {
-        if !DEBUG_ENABLED {
-            return;
-        }
-    }
-    for &b in s.as_bytes() {
-        unsafe { asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack)); }
-    }
-}
-
-#[inline(always)]
-fn print_hex(n: u64) {
-    unsafe {
-        if !DEBUG_ENABLED {
-            return;
-        }
-    }
-    let mut digits = [0u8; 16];
-    let mut i = 0;
-    let mut n = n;
-    loop {
-        let d = (n & 0xF) as u8;
-        digits[i] = if d < 10 { b'0' + d } else { b'a' + d - 10 };
-        n >>= 4;
-        i += 1;
-        if n == 0 { break; }
-    }
-    while i > 0 {
-        i -= 1;
-        unsafe { asm!("out dx, al", in("dx") 0xE9u16, in("al") digits[i], options(nomem, nostack)); }
-    }
+    kinfo!("[DISPLAY] Text console initialized");
+    kinfo!("[DISPLAY] Resolution: {:x}x{:x}", FRAMEBUFFER_WIDTH, FRAMEBUFFER_HEIGHT);
 }
 
 #[panic_handler]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    // A panicking ktest resumes the test runner instead
+    #[cfg(feature = "kernel_test")]
+    rustux::ktest::recover_panic(info);
+
+    // The debug port is only safe to use after ExitBootServices
+    if klog::sink_enabled(SinkId::Debugcon) {
+        rustux::arch::amd64::panic::report(info);
+    }
+    rustux::crashdump::save();
+    progress::fail();
+    // Interrupts are not coming back: send queued serial output now
+    rustux::drivers::uart::flush_com1();
     loop { unsafe { asm!("hlt", options(nostack, nomem)) }; }
 }
\ No newline at end of file
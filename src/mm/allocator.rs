@@ -6,15 +6,39 @@
 
 //! Kernel Heap Allocator
 //!
-//! This module provides a linked list allocator for the kernel heap.
-//! It supports allocation, deallocation, and memory reuse.
+//! This module provides the kernel heap and the global allocator behind
+//! `alloc`.
 //!
 //! # Design
 //!
-//! The allocator uses a simple linked list approach where free blocks
-//! are tracked in a free list. When allocating, it searches for a
-//! suitable free block, potentially splitting it if needed. When freeing,
-//! it merges adjacent free blocks to reduce fragmentation.
+//! The heap region is split into pages managed by a buddy allocator:
+//! free blocks of 2^order pages (order 0 to [`MAX_ORDER`]) sit on one free
+//! list per order, a request takes the smallest block that fits and
+//! splits off the rest, and a freed block merges with its buddy while the
+//! buddy is free. Blocks are aligned to their own size (by physical page
+//! number), which gives large requests their alignment for free.
+//!
+//! Small requests (up to [`MAX_SLAB_SIZE`]) come from slab caches, one per
+//! power-of-two size class in [`SLAB_SIZES`]. A slab is one page cut into
+//! equal objects with a free list threaded through the free ones; a
+//! class keeps the slabs that have free objects on a partial list. A slab
+//! whose last object is freed goes back to the buddy allocator, unless it
+//! is the class's only partial slab.
+//!
+//! Each page has a [`PageInfo`] entry in a table at the start of the
+//! region, so freeing needs only the pointer: the entry says whether the
+//! page is a slab (and of which class) or the head of a large block (and
+//! of which order).
+//!
+//! # Locking
+//!
+//! The heap is a [`Heap`] behind a [`SpinMutex`], held with interrupts
+//! disabled so an interrupt handler on the same CPU cannot deadlock on it.
+//!
+//! # Debug Logging
+//!
+//! With the `heap_debug` feature, initialization, slab creation, large
+//! blocks and failed or bad requests are logged to the debug console.
 //!
 //! # Usage
 //!
@@ -31,7 +55,12 @@
 //! deallocate(ptr, size, align);
 //! ```
 
+use core::fmt::{self, Write};
+use core::ops::{Index, IndexMut};
+use core::ptr::NonNull;
 use crate::arch::amd64::mm::page_tables::PAGE_SIZE;
+use crate::klog::{Level, LogWriter};
+use crate::sync::{lockstat, SpinMutex};
 
 // Align helper function (local to this module)
 fn align_page_up(addr: usize) -> usize {
@@ -40,1024 +69,604 @@ fn align_page_up(addr: usize) -> usize {
 }
 
 /// Default heap size (16 MB)
+///
+/// Used when the amount of RAM is unknown, and the smallest size chosen
+/// automatically.
 pub const DEFAULT_HEAP_SIZE: usize = 16 * 1024 * 1024;
 
-/// Minimum block size - increased to reduce fragmentation
-/// Blocks smaller than this won't be split off during allocation
-const MIN_BLOCK_SIZE: usize = 1024;
+/// Largest heap: it starts at 16 MB and must end below the KASLR
+/// placement window (`kaslr::PLACEMENT_START`, 64 MB)
+pub const MAX_HEAP_SIZE: usize = 48 * 1024 * 1024;
 
-/// Align block size to pointer alignment
-const BLOCK_ALIGN: usize = core::mem::align_of::<BlockHeader>();
+/// Fraction of detected RAM given to the heap (1/8)
+pub const HEAP_RAM_DIVISOR: u64 = 8;
 
-/// Heap block header for free list
-#[repr(C)]
-#[derive(Debug)]
-struct BlockHeader {
-    /// Size of this block (including header)
-    size: usize,
+/// Command line option: heap size in MiB, overriding the automatic size
+pub const HEAP_SIZE_OPTION: &str = "heap.mb";
+
+/// Largest buddy block order (2^14 pages = 64 MB, more than the heap)
+pub const MAX_ORDER: usize = 14;
 
-    /// Whether this block is free
-    free: bool,
+/// Object sizes of the slab caches
+pub const SLAB_SIZES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
 
-    /// Magic value for corruption detection
-    magic: u64,
+/// Largest request served from a slab; bigger ones take whole pages
+pub const MAX_SLAB_SIZE: usize = SLAB_SIZES[SLAB_SIZES.len() - 1];
 
-    /// Previous block in the list (raw pointer for easier manipulation)
-    prev: *mut BlockHeader,
+/// End of a page list
+const NONE: u32 = u32::MAX;
 
-    /// Next block in the list (raw pointer for easier manipulation)
-    next: *mut BlockHeader,
+/// Slab cache serving `size` bytes at `align`, if any
+///
+/// Objects are aligned to their size, so the class is the smallest one
+/// covering both.
+pub fn slab_class(size: usize, align: usize) -> Option<usize> {
+    let need = size.max(align).max(SLAB_SIZES[0]);
+    SLAB_SIZES.iter().position(|&s| s >= need)
 }
 
-/// Magic value to detect heap corruption
-const BLOCK_MAGIC: u64 = 0xDEADBEEFCAFEBABE;
+/// Buddy order of a block holding `size` bytes at `align`
+pub fn block_order(size: usize, align: usize) -> usize {
+    let pages = size.max(align).div_ceil(PAGE_SIZE);
+    pages.next_power_of_two().trailing_zeros() as usize
+}
 
-impl BlockHeader {
-    /// Create a new block header
-    const fn new(size: usize, free: bool) -> Self {
-        Self {
-            size,
-            free,
-            magic: BLOCK_MAGIC,
-            prev: core::ptr::null_mut(),
-            next: core::ptr::null_mut(),
-        }
+/// What a heap page is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageState {
+    /// Holds the page table itself
+    Reserved,
+    /// Inside a block, not its first page
+    Tail,
+    /// First page of a free block
+    Free,
+    /// A slab
+    Slab,
+    /// First page of an allocated block
+    Large,
+}
+
+/// Bookkeeping for one heap page
+#[derive(Debug, Clone, Copy)]
+struct PageInfo {
+    state: PageState,
+    /// Block order (`Free`, `Large`) or size class (`Slab`)
+    order: u8,
+    /// Objects handed out (`Slab`)
+    in_use: u16,
+    /// Free or partial list links (page indices, [`NONE`] at the ends)
+    prev: u32,
+    next: u32,
+    /// First free object, 0 if the slab is full (`Slab`)
+    free: usize,
+}
+
+impl PageInfo {
+    const fn new(state: PageState) -> Self {
+        Self { state, order: 0, in_use: 0, prev: NONE, next: NONE, free: 0 }
     }
+}
 
-    /// Get the end of this block (first byte after the block)
-    fn end(&self) -> *mut u8 {
-        (self as *const BlockHeader as usize + self.size) as *mut u8
+/// The page table at the start of the heap region
+struct PageTable {
+    ptr: NonNull<PageInfo>,
+    len: usize,
+}
+
+impl PageTable {
+    const fn empty() -> Self {
+        Self { ptr: NonNull::dangling(), len: 0 }
     }
 
-    /// Get the payload (data area) of this block
-    fn payload(&self) -> *mut u8 {
-        (self as *const BlockHeader as usize + core::mem::size_of::<BlockHeader>()) as *mut u8
+    fn as_slice(&self) -> &[PageInfo] {
+        // SAFETY: `ptr` points to `len` entries inside the heap region,
+        // which belongs to the heap (see `Heap::init`)
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
     }
 
-    /// Check if this block's magic is valid
-    fn is_valid(&self) -> bool {
-        self.magic == BLOCK_MAGIC
+    fn as_mut_slice(&mut self) -> &mut [PageInfo] {
+        // SAFETY: as in `as_slice`, and `&mut self` makes the access unique
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
     }
+}
 
-    /// Calculate the total size needed for an allocation (including header)
-    fn total_size(alloc_size: usize) -> usize {
-        let size = alloc_size + core::mem::size_of::<BlockHeader>();
-        size.max(MIN_BLOCK_SIZE)
+impl Index<u32> for PageTable {
+    type Output = PageInfo;
+    fn index(&self, i: u32) -> &PageInfo {
+        &self.as_slice()[i as usize]
     }
 }
 
-/// Linked list allocator state
-#[derive(Debug)]
-pub struct LinkedListAllocator {
-    /// First free block in the heap
-    free_list: *mut BlockHeader,
+impl IndexMut<u32> for PageTable {
+    fn index_mut(&mut self, i: u32) -> &mut PageInfo {
+        &mut self.as_mut_slice()[i as usize]
+    }
+}
 
-    /// Start of the heap region
-    heap_start: usize,
+/// Put page `i` at the front of the list starting at `head`
+fn push(pages: &mut PageTable, head: &mut u32, i: u32) {
+    pages[i].prev = NONE;
+    pages[i].next = *head;
+    if *head != NONE {
+        pages[*head].prev = i;
+    }
+    *head = i;
+}
 
-    /// Total size of the heap
-    heap_size: usize,
+/// Take page `i` off the list starting at `head`
+fn unlink(pages: &mut PageTable, head: &mut u32, i: u32) {
+    let PageInfo { prev, next, .. } = pages[i];
+    if prev != NONE {
+        pages[prev].next = next;
+    } else {
+        *head = next;
+    }
+    if next != NONE {
+        pages[next].prev = prev;
+    }
+    pages[i].prev = NONE;
+    pages[i].next = NONE;
+}
 
-    /// Whether the allocator has been initialized
-    initialized: bool,
+/// Slab and buddy heap over one memory region
+pub struct Heap {
+    /// Start of the region (page-aligned)
+    start: usize,
+    /// Size of the region in bytes (whole pages)
+    size: usize,
+    pages: PageTable,
+    /// Free blocks of each order
+    free_lists: [u32; MAX_ORDER + 1],
+    /// Slabs with free objects, per size class
+    partial: [u32; SLAB_SIZES.len()],
+    /// Pages in free blocks
+    free_pages: usize,
+    /// Bytes in free slab objects
+    slab_free: usize,
+    /// Bytes handed out (rounded up to the object or block size)
+    allocated: usize,
 }
 
-impl LinkedListAllocator {
-    /// Create a new unlinked allocator
+// SAFETY: the page table pointer refers to the heap region, which only the
+// `Heap` accesses; moving it to another CPU moves that ownership along
+unsafe impl Send for Heap {}
+
+impl Heap {
+    /// Create a heap with no memory; every request fails until [`init`](Self::init)
     pub const fn new() -> Self {
         Self {
-            free_list: core::ptr::null_mut(),
-            heap_start: 0,
-            heap_size: 0,
-            initialized: false,
+            start: 0,
+            size: 0,
+            pages: PageTable::empty(),
+            free_lists: [NONE; MAX_ORDER + 1],
+            partial: [NONE; SLAB_SIZES.len()],
+            free_pages: 0,
+            slab_free: 0,
+            allocated: 0,
         }
     }
 
-    /// Initialize the allocator with a memory region
-    ///
-    /// # Arguments
+    /// Take over a memory region
     ///
-    /// * `heap_start` - Starting address of the heap region
-    /// * `heap_size` - Size of the heap region in bytes
+    /// The region is trimmed to whole pages. Its first pages hold the page
+    /// table; the rest is carved into the largest aligned blocks that fit.
     ///
     /// # Safety
     ///
-    /// The caller must ensure that:
-    /// - The memory region is valid and accessible
-    /// - The memory region is large enough for at least one block
-    /// - The memory region is properly aligned
+    /// The caller must ensure that the memory region is valid, accessible
+    /// and used for nothing else for as long as the heap exists.
     pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
-        self.heap_start = heap_start;
-        self.heap_size = heap_size;
-        self.initialized = true;
-
-        // Print heap init telemetry once
-        let msg = b"[HEAP] init base=0x";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = heap_start;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-            n >>= 4;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
+        let start = align_page_up(heap_start);
+        let end = (heap_start + heap_size) & !(PAGE_SIZE - 1);
+        let count = end.saturating_sub(start) / PAGE_SIZE;
+        let table_pages = (count * core::mem::size_of::<PageInfo>()).div_ceil(PAGE_SIZE);
+
+        *self = Self::new();
+        if count <= table_pages || count > NONE as usize {
+            debug_log(format_args!("[HEAP] region 0x{:x}+{} too small\n", heap_start, heap_size));
+            return;
         }
+        self.start = start;
+        self.size = count * PAGE_SIZE;
+
+        let table = start as *mut PageInfo;
+        for i in 0..count {
+            let state = if i < table_pages { PageState::Reserved } else { PageState::Tail };
+            table.add(i).write(PageInfo::new(state));
+        }
+        self.pages = PageTable { ptr: NonNull::new_unchecked(table), len: count };
+
+        let mut i = table_pages;
+        while i < count {
+            let pfn = self.pfn(i as u32);
+            let order = (0..=MAX_ORDER)
+                .rev()
+                .find(|&o| pfn.is_multiple_of(1 << o) && i + (1 << o) <= count)
+                .unwrap_or(0);
+            self.free_block(i as u32, order);
+            i += 1 << order;
+        }
+
+        debug_log(format_args!(
+            "[HEAP] init base=0x{:x} size={}MB free_pages={}\n",
+            self.start,
+            self.size / (1024 * 1024),
+            self.free_pages
+        ));
+    }
 
-        let msg = b" size=";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let size_mb = heap_size / (1024 * 1024);
-        let mut n = size_mb;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b"MB\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+    /// Page frame number of page `i` (blocks are aligned to this)
+    fn pfn(&self, i: u32) -> usize {
+        self.start / PAGE_SIZE + i as usize
+    }
+
+    /// Address of page `i`
+    fn page_addr(&self, i: u32) -> usize {
+        self.start + i as usize * PAGE_SIZE
+    }
+
+    /// Page holding `addr`, if it is in the heap
+    fn page_of(&self, addr: usize) -> Option<u32> {
+        (addr >= self.start && addr < self.start + self.size).then(|| ((addr - self.start) / PAGE_SIZE) as u32)
+    }
+
+    /// Buddy of the order-`order` block at page `i`, if it lies in the heap
+    fn buddy(&self, i: u32, order: usize) -> Option<u32> {
+        let base = self.start / PAGE_SIZE;
+        let pfn = self.pfn(i) ^ (1 << order);
+        let j = pfn.checked_sub(base)?;
+        (j + (1 << order) <= self.pages.len).then_some(j as u32)
+    }
+
+    /// Take a free block of `order` pages, splitting a larger one if needed
+    ///
+    /// The block's first page is left `Free`; the caller sets its state.
+    fn alloc_block(&mut self, order: usize) -> Option<u32> {
+        let mut o = (order..=MAX_ORDER).find(|&o| self.free_lists[o] != NONE)?;
+        let i = self.free_lists[o];
+        unlink(&mut self.pages, &mut self.free_lists[o], i);
+        while o > order {
+            o -= 1;
+            let half = i + (1 << o);
+            self.pages[half].state = PageState::Free;
+            self.pages[half].order = o as u8;
+            push(&mut self.pages, &mut self.free_lists[o], half);
+        }
+        self.pages[i].order = order as u8;
+        self.free_pages -= 1 << order;
+        Some(i)
+    }
 
-        // Initialize the heap as a single free block
-        let block = heap_start as *mut BlockHeader;
-        (*block) = BlockHeader::new(heap_size, true);
-        (*block).prev = core::ptr::null_mut();
-        (*block).next = core::ptr::null_mut();
+    /// Return a block, merging it with its free buddies
+    fn free_block(&mut self, i: u32, order: usize) {
+        self.free_pages += 1 << order;
+        let (mut i, mut o) = (i, order);
+        while o < MAX_ORDER {
+            let Some(b) = self.buddy(i, o) else { break };
+            if self.pages[b].state != PageState::Free || self.pages[b].order as usize != o {
+                break;
+            }
+            unlink(&mut self.pages, &mut self.free_lists[o], b);
+            let (head, tail) = if b < i { (b, i) } else { (i, b) };
+            self.pages[tail].state = PageState::Tail;
+            i = head;
+            o += 1;
+        }
+        self.pages[i].state = PageState::Free;
+        self.pages[i].order = o as u8;
+        push(&mut self.pages, &mut self.free_lists[o], i);
+    }
 
-        self.free_list = block;
+    /// Turn a free page into an empty slab of `class`
+    fn grow(&mut self, class: usize) -> Option<u32> {
+        let i = self.alloc_block(0)?;
+        let size = SLAB_SIZES[class];
+        let base = self.page_addr(i);
+        // Thread the free list through the objects, lowest address first
+        for offset in (0..PAGE_SIZE).step_by(size) {
+            let next = if offset + size < PAGE_SIZE { base + offset + size } else { 0 };
+            // SAFETY: the page was free, so it belongs to the heap alone
+            unsafe { ((base + offset) as *mut usize).write(next) };
+        }
+        let page = &mut self.pages[i];
+        page.state = PageState::Slab;
+        page.order = class as u8;
+        page.in_use = 0;
+        page.free = base;
+        push(&mut self.pages, &mut self.partial[class], i);
+        self.slab_free += PAGE_SIZE;
+        debug_log(format_args!("[HEAP] slab {} bytes at 0x{:x}\n", size, base));
+        Some(i)
     }
 
-    /// Allocate memory from the heap
+    /// Allocate memory
     ///
     /// # Arguments
     ///
     /// * `size` - Size of the allocation in bytes
-    /// * `align` - Required alignment in bytes
+    /// * `align` - Required alignment in bytes (a power of two)
     ///
     /// # Returns
     ///
     /// Pointer to the allocated memory, or null if allocation failed
-    ///
-    /// # Safety
-    ///
-    /// This function modifies the heap's internal state.
-    pub unsafe fn allocate(&mut self, size: usize, align: usize) -> *mut u8 {
-        if !self.initialized || size == 0 {
+    pub fn allocate(&mut self, size: usize, align: usize) -> *mut u8 {
+        if size == 0 {
             return core::ptr::null_mut();
         }
-
-        // Log allocation request for first 30 allocations
-        static mut ALLOC_COUNT: u32 = 0;
-        ALLOC_COUNT += 1;
-        if ALLOC_COUNT <= 30 {
-            let msg = b"[HEAP] alloc request size=";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-            let mut n = size;
-            let mut buf = [0u8; 16];
-            let mut i = 0;
-            loop {
-                buf[i] = b'0' + (n % 10) as u8;
-                n /= 10;
-                i += 1;
-                if n == 0 { break; }
-            }
-            while i > 0 {
-                i -= 1;
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-            }
-
-            let msg = b" align=";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-            let mut n = align;
-            let mut buf = [0u8; 16];
-            let mut i = 0;
-            loop {
-                buf[i] = b'0' + (n % 10) as u8;
-                n /= 10;
-                i += 1;
-                if n == 0 { break; }
-            }
-            while i > 0 {
-                i -= 1;
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-            }
-
-            let msg = b"\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-        }
-
-        let block_size = BlockHeader::total_size(size);
-
-        // Ensure minimum alignment
-        let actual_align = align.max(BLOCK_ALIGN);
-
-        // Search for a suitable free block
-        let mut current = self.free_list;
-        let mut prev: *mut BlockHeader = core::ptr::null_mut();
-
-        // Debug: entering while loop
-        static mut LOOP_COUNT: u32 = 0;
-        let my_loop = LOOP_COUNT;
-        LOOP_COUNT += 1;
-
-        if my_loop < 20 {
-            let msg = b"[HEAP] entering while loop\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
+        let ptr = match slab_class(size, align) {
+            Some(class) => self.alloc_object(class),
+            None => self.alloc_large(block_order(size, align)),
+        };
+        if ptr.is_null() {
+            debug_log(format_args!("[HEAP] out of memory: size={} align={}\n", size, align));
         }
+        ptr
+    }
 
-        // Checkpoint: track iterations
-        let mut iteration_count = 0u32;
-
-        while !current.is_null() {
-            iteration_count += 1;
-
-            // Print checkpoint every 100 iterations
-            if iteration_count % 100 == 0 && my_loop < 20 {
-                // Simple checkpoint: print '.'
-                let msg = b".";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-            }
-
-            let block = &*current;
-
-            if my_loop < 20 {
-                let msg = b"[HEAP] loop iteration\n";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-                // Print block size for debugging
-                let msg = b"[HEAP] block size=";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-                let mut n = block.size;
-                let mut buf = [0u8; 16];
-                let mut i = 0;
-                loop {
-                    buf[i] = b'0' + (n % 10) as u8;
-                    n /= 10;
-                    i += 1;
-                    if n == 0 { break; }
-                }
-                while i > 0 {
-                    i -= 1;
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                }
-                let msg = b" free=";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-                let digit = if block.free { b'1' } else { b'0' };
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") digit, options(nomem, nostack));
-                let msg = b"\n";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-            }
-
-            if !block.is_valid() {
-                // Corrupted block, skip
-                if my_loop < 20 {
-                    let msg = b"[HEAP] block invalid, skipping\n";
-                    for &byte in msg {
-                        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                    }
-                }
-                prev = current;
-                current = block.next;
-                continue;
-            }
-
-            if !block.free {
-                // Block is not free, shouldn't happen in free list
-                if my_loop < 20 {
-                    let msg = b"[HEAP] block not free, skipping\n";
-                    for &byte in msg {
-                        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                    }
-                }
-                prev = current;
-                current = block.next;
-                continue;
-            }
-
-            // Check if this block is large enough
-            if block.size >= block_size {
-                if my_loop < 20 {
-                    let msg = b"[HEAP] block large enough, using it\n";
-                    for &byte in msg {
-                        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                    }
-                }
-                // Calculate where the payload would start
-                let payload_start = block.payload() as usize;
-                let aligned_start = (payload_start + actual_align - 1) & !(actual_align - 1);
-
-                // Calculate offset from block start to aligned payload
-                let offset = aligned_start - (current as usize);
-
-                // Check if we have enough space after alignment
-                if block.size >= offset + size {
-                    // MARKER: Confirm we reached this point
-                    if my_loop < 20 {
-                        let msg = b"[HEAP] MARKER: Reached remaining calc\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    // Calculate remaining space after allocation
-                    let remaining = block.size - offset - size;
-
-                    // Debug: print remaining value
-                    if my_loop < 20 {
-                        let msg = b"[HEAP] remaining=";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        let mut n = remaining;
-                        let mut buf = [0u8; 16];
-                        let mut i = 0;
-                        loop {
-                            buf[i] = b'0' + (n % 10) as u8;
-                            n /= 10;
-                            i += 1;
-                            if n == 0 { break; }
-                        }
-                        while i > 0 {
-                            i -= 1;
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                        }
-                        let msg = b" MIN_BLOCK_SIZE=";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        let mut n = MIN_BLOCK_SIZE;
-                        let mut buf = [0u8; 16];
-                        let mut i = 0;
-                        loop {
-                            buf[i] = b'0' + (n % 10) as u8;
-                            n /= 10;
-                            i += 1;
-                            if n == 0 { break; }
-                        }
-                        while i > 0 {
-                            i -= 1;
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                        }
-                        let msg = b" will_split=";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        let digit = if remaining >= MIN_BLOCK_SIZE { b'1' } else { b'0' };
-                        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") digit, options(nomem, nostack));
-                        let msg = b"\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    // Mark block as allocated
-                    (*current).free = false;
-
-                    // CRITICAL: Clear the next/prev pointers to prevent stale pointers
-                    // from corrupting the heap when this block is reallocated
-                    (*current).next = core::ptr::null_mut();
-                    (*current).prev = core::ptr::null_mut();
-
-                    // Debug: Print before updating free_list
-                    if my_loop < 20 {
-                        let msg = b"[HEAP] CRITICAL: About to update free_list\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        // Print current block address
-                        let msg = b"[HEAP] current=";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        let mut n = current as usize;
-                        let mut buf = [0u8; 16];
-                        let mut i = 0;
-                        loop {
-                            buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-                            n >>= 4;
-                            i += 1;
-                            if n == 0 { break; }
-                        }
-                        while i > 0 {
-                            i -= 1;
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                        }
-                        let msg = b"\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    // Debug: Print current.next before dereferencing
-                    if my_loop < 20 {
-                        let msg = b"[HEAP] current.next=";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        let mut n = (*current).next as usize;
-                        let mut buf = [0u8; 16];
-                        let mut i = 0;
-                        loop {
-                            buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-                            n >>= 4;
-                            i += 1;
-                            if n == 0 { break; }
-                        }
-                        while i > 0 {
-                            i -= 1;
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                        }
-                        let msg = b"\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    // Remove from free list
-                    // DEBUG: Track what we're about to write
-                    if my_loop < 20 && !prev.is_null() {
-                        let msg = b"[HEAP] Will write to prev.next at 0x";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        let mut n = &(*prev).next as *const _ as usize;
-                        let mut buf = [0u8; 16];
-                        let mut i = 0;
-                        loop {
-                            buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-                            n >>= 4;
-                            i += 1;
-                            if n == 0 { break; }
-                        }
-                        while i > 0 {
-                            i -= 1;
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                        }
-                        let msg = b"\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    if !prev.is_null() {
-                        if my_loop < 20 {
-                            let msg = b"[HEAP] Writing to prev.next at 0x";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                            let mut n = &(*prev).next as *const _ as usize;
-                            let mut buf = [0u8; 16];
-                            let mut i = 0;
-                            loop {
-                                buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-                                n >>= 4;
-                                i += 1;
-                                if n == 0 { break; }
-                            }
-                            while i > 0 {
-                                i -= 1;
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                            }
-                            let msg = b"\n";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                        }
-                        (*prev).next = (*current).next;
-                    } else {
-                        self.free_list = (*current).next;
-                    }
-                    if !(*current).next.is_null() {
-                        if my_loop < 20 {
-                            let msg = b"[HEAP] Writing to current.next.prev at 0x";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                            let mut n = &(*(*current).next).prev as *const _ as usize;
-                            let mut buf = [0u8; 16];
-                            let mut i = 0;
-                            loop {
-                                buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-                                n >>= 4;
-                                i += 1;
-                                if n == 0 { break; }
-                            }
-                            while i > 0 {
-                                i -= 1;
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                            }
-                            let msg = b"\n";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                        }
-                        (*(*current).next).prev = (*current).prev;
-                    }
-                    (*current).prev = core::ptr::null_mut();
-                    (*current).next = core::ptr::null_mut();
-
-                    // Debug: Print after updating free_list
-                    if my_loop < 20 {
-                        let msg = b"[HEAP] CRITICAL: free_list updated\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    // Split the block if there's enough remaining space for a BlockHeader
-                    // Lower threshold to ensure free_list never becomes empty
-                    if remaining >= MIN_BLOCK_SIZE {
-                        let header_size = core::mem::size_of::<BlockHeader>();
-
-                        // Calculate where the new free block should start (after the allocated portion)
-                        // The allocated portion is: header (40 bytes) + offset (alignment padding) + size (requested)
-                        let alloc_end = current as usize + offset + size;
-
-                        // Round UP to nearest header_size (40 bytes) boundary for the new block
-                        let aligned_new_block = (alloc_end + header_size - 1) & !(header_size - 1);
-
-                        // Calculate the size of the new free block (from aligned_new_block to end of original block)
-                        let new_block_size = (current as usize + block.size) - aligned_new_block;
-
-                        // CRITICAL FIX: Update the original block's size to only include the allocated portion
-                        // This prevents the original block from being found again in future allocations
-                        (*current).size = offset + size;
-
-                        // Debug: check if splitting will happen
-                        if my_loop < 20 {
-                            let msg = b"[HEAP] SPLIT: remaining=";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                            let mut n = remaining;
-                            let mut buf = [0u8; 16];
-                            let mut i = 0;
-                            loop {
-                                buf[i] = b'0' + (n % 10) as u8;
-                                n /= 10;
-                                i += 1;
-                                if n == 0 { break; }
-                            }
-                            while i > 0 {
-                                i -= 1;
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                            }
-                            let msg = b" new_block_size=";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                            let mut n = new_block_size;
-                            let mut buf = [0u8; 16];
-                            let mut i = 0;
-                            loop {
-                                buf[i] = b'0' + (n % 10) as u8;
-                                n /= 10;
-                                i += 1;
-                                if n == 0 { break; }
-                            }
-                            while i > 0 {
-                                i -= 1;
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                            }
-                            let msg = b" MIN_BLOCK_SIZE=";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                            let mut n = MIN_BLOCK_SIZE;
-                            let mut buf = [0u8; 16];
-                            let mut i = 0;
-                            loop {
-                                buf[i] = b'0' + (n % 10) as u8;
-                                n /= 10;
-                                i += 1;
-                                if n == 0 { break; }
-                            }
-                            while i > 0 {
-                                i -= 1;
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                            }
-                            let msg = b" will_split=";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                            let digit = if new_block_size >= MIN_BLOCK_SIZE { b'1' } else { b'0' };
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") digit, options(nomem, nostack));
-                            let msg = b"\n";
-                            for &byte in msg {
-                                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                            }
-                        }
-
-                        // Only split if we have enough space after alignment
-                        if new_block_size >= MIN_BLOCK_SIZE {
-                            // CRITICAL FIX: Update the original block's size to only include the allocated portion
-                            // This prevents the original block from being found again in future allocations
-                            (*current).size = offset + size;
-
-                            let new_block = aligned_new_block as *mut BlockHeader;
-                            (*new_block) = BlockHeader::new(new_block_size, true);
-                            (*new_block).prev = core::ptr::null_mut();
-
-                            // Add new block to free list
-                            (*new_block).next = self.free_list;
-                            if !self.free_list.is_null() {
-                                (*self.free_list).prev = new_block;
-                            }
-                            self.free_list = new_block;
-
-                            if my_loop < 20 {
-                                let msg = b"[HEAP] SPLIT: Created new block at 0x";
-                                for &byte in msg {
-                                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                                }
-                                let mut n = aligned_new_block;
-                                let mut buf = [0u8; 16];
-                                let mut i = 0;
-                                loop {
-                                    buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-                                    n >>= 4;
-                                    i += 1;
-                                    if n == 0 { break; }
-                                }
-                                while i > 0 {
-                                    i -= 1;
-                                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                                }
-                                let msg = b"\n";
-                                for &byte in msg {
-                                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                                }
-                            }
-                        }
-                    }
-
-                    // Return pointer to aligned payload
-                    if my_loop < 20 {
-                        let msg = b"[HEAP] allocation succeeded, returning: 0x";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                        let mut n = aligned_start;
-                        let mut buf = [0u8; 16];
-                        let mut i = 0;
-                        loop {
-                            buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-                            n >>= 4;
-                            i += 1;
-                            if n == 0 { break; }
-                        }
-                        while i > 0 {
-                            i -= 1;
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                        }
-                        let msg = b"\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    return aligned_start as *mut u8;
-                }
-            }
-
-            if my_loop < 20 {
-                let msg = b"[HEAP] block too small, moving to next\n";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-            }
-
-            prev = current;
-            current = block.next;
-        }
+    fn alloc_object(&mut self, class: usize) -> *mut u8 {
+        let i = match self.partial[class] {
+            NONE => match self.grow(class) {
+                Some(i) => i,
+                None => return core::ptr::null_mut(),
+            },
+            i => i,
+        };
+        let object = self.pages[i].free;
+        // SAFETY: free objects hold the address of the next free object
+        let next = unsafe { (object as *const usize).read() };
+        let page = &mut self.pages[i];
+        page.free = next;
+        page.in_use += 1;
+        if next == 0 {
+            unlink(&mut self.pages, &mut self.partial[class], i);
+        }
+        self.slab_free -= SLAB_SIZES[class];
+        self.allocated += SLAB_SIZES[class];
+        object as *mut u8
+    }
 
-        // No suitable free block found
-        if my_loop < 20 {
-            let msg = b"[HEAP] no suitable block found, allocation failed\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
+    fn alloc_large(&mut self, order: usize) -> *mut u8 {
+        if order > MAX_ORDER {
+            return core::ptr::null_mut();
         }
-        core::ptr::null_mut()
+        let Some(i) = self.alloc_block(order) else { return core::ptr::null_mut() };
+        self.pages[i].state = PageState::Large;
+        self.allocated += PAGE_SIZE << order;
+        debug_log(format_args!("[HEAP] {} pages at 0x{:x}\n", 1usize << order, self.page_addr(i)));
+        self.page_addr(i) as *mut u8
     }
 
-    /// Free memory back to the heap
-    ///
-    /// # Arguments
+    /// Free memory
     ///
-    /// * `ptr` - Pointer to the memory to free
-    /// * `_size` - Size of the allocation (for validation, may be 0)
-    /// * `_align` - Alignment of the allocation (may be 0)
+    /// The size class or block is found from the pointer; pointers the
+    /// heap did not hand out are ignored (and logged with `heap_debug`).
     ///
     /// # Safety
     ///
-    /// This function modifies the heap's internal state.
-    /// The pointer must have been returned by a previous call to allocate.
-    pub unsafe fn deallocate(&mut self, ptr: *mut u8, _size: usize, _align: usize) {
-        if !self.initialized || ptr.is_null() {
-            return;
-        }
-
-        // Get the block header (it's before the payload)
-        // Find the block by searching backwards from the payload
-        let mut block = self.heap_start as *mut BlockHeader;
-
-        while (block as usize) < (self.heap_start + self.heap_size) {
-            if !(*block).is_valid() {
-                break; // Corruption detected
+    /// `ptr` must have been returned by [`allocate`](Self::allocate) and
+    /// not freed since.
+    pub unsafe fn deallocate(&mut self, ptr: *mut u8) {
+        let addr = ptr as usize;
+        let Some(i) = self.page_of(addr) else {
+            if !ptr.is_null() {
+                debug_log(format_args!("[HEAP] free of foreign pointer 0x{:x}\n", addr));
             }
-
-            let payload_start = (*block).payload();
-            if payload_start == ptr || (payload_start < ptr && (ptr as usize) < ((*block).end() as usize)) {
-                // Found the block
-                (*block).free = true;
-
-                // Try to merge with next block if it's free
-                let next = (*block).next;
-                if !next.is_null() && (*next).is_valid() && (*next).free {
-                    // Merge with next block
-                    (*block).size += (*next).size;
-                    (*block).next = (*next).next;
-                    let next_next = (*next).next;
-                    if !next_next.is_null() {
-                        (*next_next).prev = block;
-                    }
-                }
-
-                // Try to merge with previous block if it's free
-                // We need to find the previous block by scanning
-                let mut prev_block = self.heap_start as *mut BlockHeader;
-                while (prev_block as usize) < (block as usize) {
-                    if !(*prev_block).is_valid() {
-                        break;
-                    }
-
-                    let prev_end = (*prev_block).end() as usize;
-                    if prev_end == (block as usize) && (*prev_block).free {
-                        // Found adjacent previous free block, merge
-                        (*prev_block).size += (*block).size;
-                        (*prev_block).next = (*block).next;
-                        let next = (*block).next;
-                        if !next.is_null() {
-                            (*next).prev = prev_block;
-                        }
-                        block = prev_block;
-                        break;
-                    }
-
-                    prev_block = (*prev_block).end() as *mut BlockHeader;
-                }
-
-                // Add to free list
-                (*block).next = self.free_list;
-                (*block).prev = core::ptr::null_mut();
-                if !self.free_list.is_null() {
-                    (*self.free_list).prev = block;
-                }
-                self.free_list = block;
-
-                return;
+            return;
+        };
+        match self.pages[i].state {
+            PageState::Slab => self.free_object(i, addr),
+            PageState::Large if addr == self.page_addr(i) => {
+                let order = self.pages[i].order as usize;
+                self.allocated -= PAGE_SIZE << order;
+                self.free_block(i, order);
             }
+            _ => debug_log(format_args!("[HEAP] bad free 0x{:x}\n", addr)),
+        }
+    }
 
-            block = (*block).end() as *mut BlockHeader;
+    unsafe fn free_object(&mut self, i: u32, addr: usize) {
+        let class = self.pages[i].order as usize;
+        let size = SLAB_SIZES[class];
+        let page = &mut self.pages[i];
+        let was_full = page.free == 0;
+        (addr as *mut usize).write(page.free);
+        page.free = addr;
+        page.in_use -= 1;
+        let empty = page.in_use == 0;
+        self.slab_free += size;
+        self.allocated -= size;
+
+        if was_full {
+            push(&mut self.pages, &mut self.partial[class], i);
+        }
+        // Keep one partial slab so a class freeing and allocating a
+        // single object does not rebuild a slab every time
+        let only = self.partial[class] == i && self.pages[i].next == NONE;
+        if empty && !only {
+            unlink(&mut self.pages, &mut self.partial[class], i);
+            self.slab_free -= PAGE_SIZE;
+            self.free_block(i, 0);
         }
     }
 
-    /// Get heap usage statistics
-    ///
-    /// # Returns
-    ///
-    /// Number of bytes currently allocated
+    /// Bytes handed out, rounded up to object or block sizes
     pub fn usage(&self) -> usize {
-        if !self.initialized {
-            return 0;
-        }
+        self.allocated
+    }
 
-        let mut used = 0usize;
-        unsafe {
-            let mut block = self.heap_start as *mut BlockHeader;
+    /// Total size of the heap region in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
 
-            while (block as usize) < (self.heap_start + self.heap_size) {
-                if !(*block).is_valid() {
-                    break;
-                }
+    /// Start and size of the heap region
+    pub fn bounds(&self) -> (usize, usize) {
+        (self.start, self.size)
+    }
 
-                if !(*block).free {
-                    used += (*block).size;
-                }
+    /// Bytes free: free pages plus free slab objects
+    pub fn available(&self) -> usize {
+        self.free_pages * PAGE_SIZE + self.slab_free
+    }
 
-                block = (*block).end() as *mut BlockHeader;
-            }
-        }
+    /// Count the blocks on the buddy free lists
+    pub fn free_block_count(&self) -> usize {
+        self.free_lists.iter().map(|&head| self.list(head).count()).sum()
+    }
 
-        used
+    /// Count the slabs of every size class
+    fn slab_count(&self) -> usize {
+        self.pages.as_slice().iter().filter(|p| p.state == PageState::Slab).count()
     }
 
-    /// Get total heap size
+    /// Pages of the list starting at `head`
     ///
-    /// # Returns
-    ///
-    /// Total size of the heap in bytes
-    pub fn size(&self) -> usize {
-        self.heap_size
+    /// Stops after one more step than there are pages, so a cycle shows
+    /// up as an overlong list.
+    fn list(&self, head: u32) -> impl Iterator<Item = u32> + '_ {
+        let mut next = head;
+        core::iter::from_fn(move || {
+            let i = next;
+            next = self.pages.as_slice().get(i as usize).map_or(NONE, |p| p.next);
+            (i != NONE).then_some(i)
+        })
+        .take(self.pages.len + 1)
     }
 
-    /// Get available (free) heap size
+    /// Check the free lists and slabs for corruption
+    ///
+    /// Every free block must be on the list of its order, aligned to its
+    /// size, inside the heap and linked back by its successor, and the
+    /// lists must add up to the free page count. Every partial slab must
+    /// belong to its class and hold a free list of its own objects whose
+    /// length matches its use count. Walks are bounded, so a cycle is
+    /// reported rather than followed forever.
     ///
     /// # Returns
     ///
-    /// Number of bytes currently available for allocation
-    pub fn available(&self) -> usize {
-        if !self.initialized {
-            return 0;
-        }
-
-        let mut free_bytes = 0usize;
-        unsafe {
-            let mut current = self.free_list;
-
-            while !current.is_null() {
-                let block = &*current;
-                if block.is_valid() && block.free {
-                    free_bytes += block.size;
+    /// The number of free blocks, or a description of the first violation
+    pub fn check_invariants(&self) -> Result<usize, &'static str> {
+        if self.size == 0 {
+            return Err("heap not initialized");
+        }
+
+        let mut blocks = 0usize;
+        let mut free_pages = 0usize;
+        for (order, &head) in self.free_lists.iter().enumerate() {
+            let mut prev = NONE;
+            for i in self.list(head) {
+                blocks += 1;
+                if blocks > self.pages.len {
+                    return Err("free list cycle");
+                }
+                let Some(&page) = self.pages.as_slice().get(i as usize) else {
+                    return Err("free block outside the heap");
+                };
+                if page.state != PageState::Free || page.order as usize != order {
+                    return Err("free list entry is not a free block of its order");
+                }
+                if !self.pfn(i).is_multiple_of(1 << order) || i as usize + (1 << order) > self.pages.len {
+                    return Err("free block misaligned or past the heap");
                 }
-                current = block.next;
+                if page.prev != prev {
+                    return Err("free list back link mismatch");
+                }
+                prev = i;
+                free_pages += 1 << order;
             }
         }
-
-        free_bytes
-    }
-
-    /// Count the number of free blocks
-    pub fn free_block_count(&self) -> usize {
-        if !self.initialized {
-            return 0;
+        if free_pages != self.free_pages {
+            return Err("free lists disagree with the free page count");
         }
 
-        let mut count = 0usize;
-        unsafe {
-            let mut current = self.free_list;
-
-            while !current.is_null() {
-                let block = &*current;
-                if block.is_valid() && block.free {
-                    count += 1;
+        for (class, &head) in self.partial.iter().enumerate() {
+            let size = SLAB_SIZES[class];
+            let mut prev = NONE;
+            for (n, i) in self.list(head).enumerate() {
+                if n == self.pages.len {
+                    return Err("partial list cycle");
+                }
+                let Some(&page) = self.pages.as_slice().get(i as usize) else {
+                    return Err("slab outside the heap");
+                };
+                if page.state != PageState::Slab || page.order as usize != class {
+                    return Err("partial list entry is not a slab of its class");
+                }
+                if page.prev != prev {
+                    return Err("partial list back link mismatch");
+                }
+                let base = self.page_addr(i);
+                let mut free = 0usize;
+                let mut object = page.free;
+                while object != 0 {
+                    if object < base || object >= base + PAGE_SIZE || !(object - base).is_multiple_of(size) {
+                        return Err("slab free list leaves its slab");
+                    }
+                    free += 1;
+                    if free > PAGE_SIZE / size {
+                        return Err("slab free list cycle");
+                    }
+                    // SAFETY: checked to be an object of this slab
+                    object = unsafe { (object as *const usize).read() };
                 }
-                current = block.next;
+                if free == 0 || free + page.in_use as usize != PAGE_SIZE / size {
+                    return Err("slab use count mismatch");
+                }
+                prev = i;
             }
         }
 
-        count
+        Ok(blocks)
     }
 
-    /// Print heap summary
-    pub unsafe fn print_summary(&self) {
-        if !self.initialized {
-            return;
-        }
-
-        let used = self.usage();
-        let avail = self.available();
-        let free_blocks = self.free_block_count();
-
-        // Print in format: [HEAP] base=0x300000 size=16MB used=X avail=Y free_blocks=Z
-        let msg_start = b"[HEAP] base=0x";
-        for &byte in msg_start {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = self.heap_start;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = if (n & 0xF) < 10 { b'0' + (n & 0xF) as u8 } else { b'a' + (n & 0xF) as u8 - 10 };
-            n >>= 4;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+    /// Write a one-line summary of the heap
+    pub fn write_summary(&self, out: &mut impl Write) -> fmt::Result {
+        writeln!(
+            out,
+            "[HEAP] base=0x{:x} size={}MB used={} avail={} free_blocks={} slabs={}",
+            self.start,
+            self.size / (1024 * 1024),
+            self.usage(),
+            self.available(),
+            self.free_block_count(),
+            self.slab_count()
+        )
+    }
+}
 
-        let msg_size = b" size=";
-        for &byte in msg_size {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let size_mb = self.heap_size / (1024 * 1024);
-        let mut n = size_mb;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg_mb = b"MB";
-        for &byte in msg_mb {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let msg_used = b" used=";
-        for &byte in msg_used {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = used;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+/// Log allocator activity (`heap_debug` feature)
+///
+/// Runs inside the allocator; logging does not allocate.
+#[inline(always)]
+fn debug_log(args: fmt::Arguments) {
+    #[cfg(feature = "heap_debug")]
+    let _ = LogWriter::new(Level::Info, module_path!()).write_fmt(args);
+    #[cfg(not(feature = "heap_debug"))]
+    let _ = args;
+}
 
-        let msg_avail = b" avail=";
-        for &byte in msg_avail {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = avail;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+/// The kernel heap
+static KERNEL_HEAP: SpinMutex<Heap> = SpinMutex::named(Heap::new(), &lockstat::HEAP);
 
-        let msg_blocks = b" free_blocks=";
-        for &byte in msg_blocks {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        let mut n = free_blocks;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
+/// Run `f` on the kernel heap with interrupts disabled
+fn with_heap<R>(f: impl FnOnce(&mut Heap) -> R) -> R {
+    use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
 
-        let msg_newline = b"\n";
-        for &byte in msg_newline {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
+    let were_disabled = arch_ints_disabled();
+    arch_disable_ints();
+    let result = f(&mut KERNEL_HEAP.lock());
+    if !were_disabled {
+        arch_enable_ints();
     }
+    result
 }
 
-/// Global allocator instance
-static mut ALLOCATOR: LinkedListAllocator = LinkedListAllocator::new();
-
 /// Initialize the heap allocator
 ///
 /// # Arguments
@@ -1072,7 +681,41 @@ static mut ALLOCATOR: LinkedListAllocator = LinkedListAllocator::new();
 /// - The memory region is not used for any other purpose
 /// - This function is called only once during initialization
 pub unsafe fn init(heap_start: usize, heap_size: usize) {
-    ALLOCATOR.init(heap_start, heap_size);
+    with_heap(|heap| heap.init(heap_start, heap_size));
+    super::watermark::HEAP.set_capacity(heap_size);
+}
+
+/// Heap size for a machine with `memory` bytes of RAM
+///
+/// # Arguments
+///
+/// * `memory` - Usable RAM in bytes, 0 if unknown
+/// * `override_mb` - Size requested on the command line, in MiB
+///
+/// # Returns
+///
+/// `memory / HEAP_RAM_DIVISOR` rounded down to a MiB, at least
+/// [`DEFAULT_HEAP_SIZE`]. An override replaces the automatic size but
+/// may be smaller than the default. Either way the result is at most
+/// [`MAX_HEAP_SIZE`].
+pub fn heap_size_for(memory: u64, override_mb: Option<u64>) -> usize {
+    const MIB: u64 = 1024 * 1024;
+
+    let size = match override_mb {
+        Some(mb) => core::cmp::max(mb, 1) * MIB,
+        None => core::cmp::max((memory / HEAP_RAM_DIVISOR) & !(MIB - 1), DEFAULT_HEAP_SIZE as u64),
+    };
+    core::cmp::min(size, MAX_HEAP_SIZE as u64) as usize
+}
+
+/// Heap size for this boot
+///
+/// From the RAM recorded by [`pmm::set_detected_memory`](super::pmm::set_detected_memory)
+/// and the [`HEAP_SIZE_OPTION`] command line option.
+pub fn boot_heap_size() -> usize {
+    let mut buf = [0u8; 8];
+    let override_mb = crate::cmdline::get(HEAP_SIZE_OPTION, &mut buf).and_then(|s| s.parse().ok());
+    heap_size_for(super::pmm::detected_memory(), override_mb)
 }
 
 /// Initialize the heap allocator with a page-aligned heap
@@ -1115,9 +758,9 @@ pub unsafe fn init_aligned(start_addr: usize, size: usize) -> usize {
 ///
 /// # Safety
 ///
-/// This function modifies the heap's internal state.
+/// The memory must be freed with [`deallocate`].
 pub unsafe fn allocate(size: usize, align: usize) -> *mut u8 {
-    ALLOCATOR.allocate(size, align)
+    with_heap(|heap| heap.allocate(size, align))
 }
 
 /// Free memory back to the heap
@@ -1125,14 +768,16 @@ pub unsafe fn allocate(size: usize, align: usize) -> *mut u8 {
 /// # Arguments
 ///
 /// * `ptr` - Pointer to the memory to free
-/// * `size` - Size of the original allocation
-/// * `align` - Alignment of the original allocation
+/// * `size` - Size of the original allocation (unused: the heap knows it)
+/// * `align` - Alignment of the original allocation (unused)
 ///
 /// # Safety
 ///
-/// This function modifies the heap's internal state.
+/// The pointer must have been returned by a previous call to
+/// [`allocate`] and not freed since.
 pub unsafe fn deallocate(ptr: *mut u8, size: usize, align: usize) {
-    ALLOCATOR.deallocate(ptr, size, align)
+    let _ = (size, align);
+    with_heap(|heap| heap.deallocate(ptr))
 }
 
 /// Get heap usage statistics
@@ -1141,7 +786,7 @@ pub unsafe fn deallocate(ptr: *mut u8, size: usize, align: usize) {
 ///
 /// Number of bytes currently allocated
 pub fn heap_usage() -> usize {
-    unsafe { ALLOCATOR.usage() }
+    with_heap(|heap| heap.usage())
 }
 
 /// Get total heap size
@@ -1150,7 +795,7 @@ pub fn heap_usage() -> usize {
 ///
 /// Total size of the heap in bytes
 pub fn heap_size() -> usize {
-    unsafe { ALLOCATOR.size() }
+    with_heap(|heap| heap.size())
 }
 
 /// Get available heap size
@@ -1159,12 +804,30 @@ pub fn heap_size() -> usize {
 ///
 /// Number of bytes currently available for allocation
 pub fn heap_available() -> usize {
-    unsafe { ALLOCATOR.available() }
+    with_heap(|heap| heap.available())
+}
+
+/// Get the heap region bounds
+///
+/// # Returns
+///
+/// `(heap_start, heap_size)`, or `(0, 0)` before initialization
+pub fn heap_bounds() -> (usize, usize) {
+    with_heap(|heap| heap.bounds())
+}
+
+/// Check the heap for corruption
+///
+/// See [`Heap::check_invariants`].
+pub fn heap_check() -> Result<usize, &'static str> {
+    with_heap(|heap| heap.check_invariants())
 }
 
 /// Print heap summary for debugging
 pub fn heap_print_summary() {
-    unsafe { ALLOCATOR.print_summary() }
+    with_heap(|heap| {
+        let _ = heap.write_summary(&mut LogWriter::new(Level::Info, module_path!()));
+    })
 }
 
 // ============================================================================
@@ -1173,61 +836,186 @@ pub fn heap_print_summary() {
 
 use alloc::alloc::{GlobalAlloc, Layout};
 
-// SAFETY: The allocator is only used in a single-threaded early kernel environment
-// The raw pointers inside are protected by the fact that there's no concurrent access
-unsafe impl Sync for LinkedListAllocator {}
+/// The global allocator: the kernel heap, or the sanitizer on top of it
+pub struct KernelAllocator;
 
-unsafe impl GlobalAlloc for LinkedListAllocator {
+unsafe impl GlobalAlloc for KernelAllocator {
+    #[cfg(not(feature = "kasan"))]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        // Use a mutable reference to self for the allocation
-        // Note: This is a workaround - GlobalAlloc takes &self but we need &mut self
-        // In a single-threaded early kernel environment, this is safe
-        let allocator = &ALLOCATOR as *const LinkedListAllocator as *mut LinkedListAllocator;
-        (*allocator).allocate(layout.size(), layout.align())
+        let ptr = allocate(layout.size(), layout.align());
+        if !ptr.is_null() {
+            super::watermark::HEAP.add(layout.size());
+        }
+        ptr
     }
 
+    #[cfg(not(feature = "kasan"))]
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        let allocator = &ALLOCATOR as *const LinkedListAllocator as *mut LinkedListAllocator;
-        (*allocator).deallocate(ptr, layout.size(), layout.align());
+        deallocate(ptr, layout.size(), layout.align());
+        super::watermark::HEAP.sub(layout.size());
+    }
+
+    // Route through the sanitizer for redzones and quarantine
+    #[cfg(feature = "kasan")]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = super::kasan::alloc(layout);
+        if !ptr.is_null() {
+            super::watermark::HEAP.add(layout.size());
+        }
+        ptr
+    }
+
+    #[cfg(feature = "kasan")]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        super::kasan::dealloc(ptr, layout);
+        super::watermark::HEAP.sub(layout.size());
     }
 }
 
 /// Global heap allocator instance
 ///
-/// This is exported as the global allocator for the Rust standard library.
-#[global_allocator]
-static HEAP_ALLOCATOR: LinkedListAllocator = LinkedListAllocator::new();
+/// This is exported as the global allocator for `alloc`. Host test builds
+/// keep the standard allocator.
+#[cfg_attr(not(test), global_allocator)]
+static HEAP_ALLOCATOR: KernelAllocator = KernelAllocator;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec::Vec;
 
-    const TEST_HEAP_SIZE: usize = 64 * 1024; // 64 KB for testing
+    const TEST_HEAP_SIZE: usize = 1024 * 1024;
+
+    /// A heap over a fresh buffer (kept alive by the returned vector)
+    fn test_heap() -> (Heap, Vec<u8>) {
+        let buf = alloc::vec![0u8; TEST_HEAP_SIZE + PAGE_SIZE];
+        let mut heap = Heap::new();
+        unsafe { heap.init(buf.as_ptr() as usize, buf.len()) };
+        (heap, buf)
+    }
 
     #[test]
     fn test_allocator_init() {
-        unsafe {
-            let heap: [u8; TEST_HEAP_SIZE] = [0; TEST_HEAP_SIZE];
-            init(heap.as_ptr() as usize, TEST_HEAP_SIZE);
-            assert_eq!(heap_size(), TEST_HEAP_SIZE);
-            assert_eq!(heap_usage(), 0);
-            assert!(heap_available() > 0);
-        }
+        let (heap, _buf) = test_heap();
+        assert_eq!(heap.size(), TEST_HEAP_SIZE);
+        assert_eq!(heap.usage(), 0);
+        assert!(heap.available() > TEST_HEAP_SIZE - 4 * PAGE_SIZE);
+        assert!(heap.check_invariants().is_ok());
     }
 
     #[test]
     fn test_allocator_allocate() {
+        let (mut heap, _buf) = test_heap();
+        let free = heap.available();
+
+        let ptr = heap.allocate(1024, 8);
+        assert!(!ptr.is_null());
+        assert!(heap.usage() >= 1024);
+
+        unsafe { heap.deallocate(ptr) };
+        assert_eq!(heap.usage(), 0);
+        assert_eq!(heap.available(), free);
+        assert!(heap.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_size_classes() {
+        assert_eq!(slab_class(1, 1), Some(0));
+        assert_eq!(slab_class(24, 8), Some(1));
+        assert_eq!(slab_class(8, 256), Some(4));
+        assert_eq!(slab_class(MAX_SLAB_SIZE, 8), Some(SLAB_SIZES.len() - 1));
+        assert_eq!(slab_class(MAX_SLAB_SIZE + 1, 8), None);
+
+        assert_eq!(block_order(MAX_SLAB_SIZE + 1, 8), 0);
+        assert_eq!(block_order(3 * PAGE_SIZE, 8), 2);
+        assert_eq!(block_order(100, 2 * PAGE_SIZE), 1);
+    }
+
+    #[test]
+    fn test_slab_objects() {
+        let (mut heap, _buf) = test_heap();
+        let objects: Vec<*mut u8> = (0..PAGE_SIZE / 32 + 1).map(|_| heap.allocate(24, 8)).collect();
+
+        // Distinct, aligned to the class, and spilling into a second slab
+        let mut sorted = objects.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), objects.len());
+        assert!(objects.iter().all(|&p| !p.is_null() && p as usize % 32 == 0));
+        assert_eq!(heap.slab_count(), 2);
+        assert!(heap.check_invariants().is_ok());
+
+        for &p in &objects {
+            unsafe { heap.deallocate(p) };
+        }
+        // One empty slab stays cached
+        assert_eq!(heap.usage(), 0);
+        assert_eq!(heap.slab_count(), 1);
+        assert!(heap.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_large_blocks_merge() {
+        let (mut heap, _buf) = test_heap();
+        let free = heap.available();
+        let blocks = heap.free_block_count();
+
+        let a = heap.allocate(3 * PAGE_SIZE, 8);
+        let b = heap.allocate(100, 2 * PAGE_SIZE);
+        assert_eq!(a as usize % (4 * PAGE_SIZE), 0);
+        assert_eq!(b as usize % (2 * PAGE_SIZE), 0);
+        assert_eq!(heap.usage(), 6 * PAGE_SIZE);
+
+        // Freeing a pointer twice or into the middle of a block is ignored
         unsafe {
-            let heap: [u8; TEST_HEAP_SIZE] = [0; TEST_HEAP_SIZE];
-            init(heap.as_ptr() as usize, TEST_HEAP_SIZE);
+            heap.deallocate(a.add(PAGE_SIZE));
+            heap.deallocate(a);
+            heap.deallocate(a);
+            heap.deallocate(b);
+        }
+        assert_eq!(heap.available(), free);
+        assert_eq!(heap.free_block_count(), blocks);
+        assert!(heap.check_invariants().is_ok());
+    }
 
-            let ptr = allocate(1024, 8);
-            assert!(!ptr.is_null());
-            assert!(heap_usage() >= 1024);
+    #[test]
+    fn test_exhaustion() {
+        let (mut heap, _buf) = test_heap();
+        let free = heap.available();
 
-            deallocate(ptr, 1024, 8);
-            // After deallocation, usage should be back to 0
-            // (but may not be exactly 0 due to block splitting)
+        let mut blocks = Vec::new();
+        loop {
+            let p = heap.allocate(PAGE_SIZE, 8);
+            if p.is_null() {
+                break;
+            }
+            blocks.push(p);
         }
+        assert_eq!(heap.available(), 0);
+        assert!(heap.allocate(16, 8).is_null());
+        assert!(heap.allocate(TEST_HEAP_SIZE * 2, 8).is_null());
+
+        for p in blocks {
+            unsafe { heap.deallocate(p) };
+        }
+        assert_eq!(heap.available(), free);
+        assert!(heap.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_heap_size_for() {
+        const MIB: u64 = 1024 * 1024;
+
+        // Unknown or small machines get the default
+        assert_eq!(heap_size_for(0, None), DEFAULT_HEAP_SIZE);
+        assert_eq!(heap_size_for(128 * MIB, None), DEFAULT_HEAP_SIZE);
+
+        // An eighth of RAM, whole MiB, capped
+        assert_eq!(heap_size_for(250 * MIB, None), 31 * MIB as usize);
+        assert_eq!(heap_size_for(8192 * MIB, None), MAX_HEAP_SIZE);
+
+        // The override ignores RAM but not the cap
+        assert_eq!(heap_size_for(8192 * MIB, Some(8)), 8 * MIB as usize);
+        assert_eq!(heap_size_for(0, Some(1024)), MAX_HEAP_SIZE);
     }
 }
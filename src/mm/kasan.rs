@@ -0,0 +1,375 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Heap Sanitizer (KASAN-lite)
+//!
+//! This module adds redzones and a free quarantine to the kernel heap
+//! when the `kasan` feature is enabled. It is intended for test-suite
+//! runs and catches heap buffer overflows and use-after-free bugs in
+//! kernel code.
+//!
+//! # Design
+//!
+//! - **Redzones**: every allocation is surrounded by [`REDZONE_SIZE`] bytes
+//!   filled with [`REDZONE_BYTE`]. Redzones are checked on free.
+//! - **Poisoning**: freed memory is filled with [`FREED_BYTE`].
+//! - **Quarantine**: freed blocks are not returned to the allocator right
+//!   away. They sit in a FIFO until the quarantine is full, and their
+//!   poison is checked again before the memory is really released.
+//! - **Access checks**: hot paths (copy helpers, VMO read/write) call
+//!   [`check_read`] / [`check_write`] on kernel buffers. Accesses that
+//!   touch a redzone or a quarantined block are reported.
+//!
+//! Without the `kasan` feature all checks compile to nothing.
+//!
+//! Only [`MAX_TRACKED`] live allocations are tracked. Past that, new
+//! allocations still get redzones but are not checked on access; the
+//! first one is reported. Freeing one rebuilds its block from the layout,
+//! so it is checked and quarantined like the others.
+//!
+//! # Layout
+//!
+//! ```text
+//! raw block: [ front redzone | user data (size) | back redzone ]
+//!            ^raw            ^user
+//! ```
+
+#[cfg(feature = "kasan")]
+use crate::sync::SpinMutex;
+use alloc::alloc::Layout;
+#[cfg(feature = "kasan")]
+use crate::kerror;
+
+/// Size of the redzone on each side of an allocation
+pub const REDZONE_SIZE: usize = 32;
+
+/// Fill byte for redzones
+pub const REDZONE_BYTE: u8 = 0xFB;
+
+/// Fill byte for freed (quarantined) memory
+pub const FREED_BYTE: u8 = 0xFD;
+
+/// Maximum number of blocks held in quarantine
+pub const QUARANTINE_SLOTS: usize = 256;
+
+/// Maximum number of bytes held in quarantine
+pub const QUARANTINE_MAX_BYTES: usize = 1024 * 1024;
+
+/// Maximum number of live allocations tracked
+pub const MAX_TRACKED: usize = 4096;
+
+/// Kind of bug detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KasanError {
+    /// Access or corruption in a redzone
+    HeapBufferOverflow,
+    /// Access or write to a freed block in quarantine
+    UseAfterFree,
+    /// Free of a block that is already in quarantine
+    DoubleFree,
+    /// Free of a pointer that was never allocated
+    InvalidFree,
+}
+
+impl KasanError {
+    /// Human-readable description
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HeapBufferOverflow => "heap-buffer-overflow",
+            Self::UseAfterFree => "use-after-free",
+            Self::DoubleFree => "double-free",
+            Self::InvalidFree => "invalid-free",
+        }
+    }
+}
+
+/// Tracked allocation
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "kasan"), allow(dead_code))]
+struct Allocation {
+    /// Start of the raw block (front redzone)
+    raw: usize,
+    /// Size of the raw block
+    raw_size: usize,
+    /// Start of the user data
+    user: usize,
+    /// Size of the user data
+    size: usize,
+    /// Alignment used for the raw block
+    align: usize,
+}
+
+#[cfg_attr(not(feature = "kasan"), allow(dead_code))]
+impl Allocation {
+    const EMPTY: Self = Self { raw: 0, raw_size: 0, user: 0, size: 0, align: 0 };
+
+    /// Alignment of the raw block and size of the front redzone for `layout`
+    fn front(layout: Layout) -> (usize, usize) {
+        let align = layout.align().max(16);
+        (align, (REDZONE_SIZE + align - 1) & !(align - 1))
+    }
+
+    /// The block of an allocation of `layout` whose data starts at `user`
+    fn for_layout(user: usize, layout: Layout) -> Self {
+        let (align, front) = Self::front(layout);
+        Self { raw: user - front, raw_size: front + layout.size() + REDZONE_SIZE, user, size: layout.size(), align }
+    }
+
+    fn overlaps(&self, addr: usize, len: usize) -> bool {
+        addr < self.raw + self.raw_size && addr + len > self.raw
+    }
+
+    fn contains_user(&self, addr: usize, len: usize) -> bool {
+        addr >= self.user && addr + len <= self.user + self.size
+    }
+}
+
+/// Sanitizer state
+#[cfg(feature = "kasan")]
+struct KasanState {
+    /// Live allocations (unordered)
+    live: [Allocation; MAX_TRACKED],
+    live_count: usize,
+    /// Live allocations made while `live` was full
+    untracked: usize,
+    /// Quarantine FIFO (ring buffer)
+    quarantine: [Allocation; QUARANTINE_SLOTS],
+    q_head: usize,
+    q_len: usize,
+    q_bytes: usize,
+    /// Number of reports issued
+    reports: u64,
+}
+
+#[cfg(feature = "kasan")]
+static KASAN: SpinMutex<KasanState> = SpinMutex::new(KasanState {
+    live: [Allocation::EMPTY; MAX_TRACKED],
+    live_count: 0,
+    untracked: 0,
+    quarantine: [Allocation::EMPTY; QUARANTINE_SLOTS],
+    q_head: 0,
+    q_len: 0,
+    q_bytes: 0,
+    reports: 0,
+});
+
+// ============================================================================
+// Allocation Hooks
+// ============================================================================
+
+/// Allocate a block with redzones
+///
+/// # Safety
+///
+/// Same contract as `GlobalAlloc::alloc`.
+#[cfg(feature = "kasan")]
+pub unsafe fn alloc(layout: Layout) -> *mut u8 {
+    let (align, front) = Allocation::front(layout);
+    let raw = super::allocator::allocate(front + layout.size() + REDZONE_SIZE, align) as usize;
+    if raw == 0 {
+        return core::ptr::null_mut();
+    }
+
+    let block = Allocation::for_layout(raw + front, layout);
+    core::ptr::write_bytes(raw as *mut u8, REDZONE_BYTE, front);
+    core::ptr::write_bytes((block.user + block.size) as *mut u8, REDZONE_BYTE, REDZONE_SIZE);
+
+    let mut state = KASAN.lock();
+    if state.live_count < MAX_TRACKED {
+        let idx = state.live_count;
+        state.live[idx] = block;
+        state.live_count += 1;
+    } else {
+        if state.untracked == 0 {
+            kerror!("[KASAN] more than {} live allocations, new ones are not checked", MAX_TRACKED);
+        }
+        state.untracked += 1;
+    }
+
+    block.user as *mut u8
+}
+
+/// Free a block into the quarantine
+///
+/// # Safety
+///
+/// Same contract as `GlobalAlloc::dealloc`.
+#[cfg(feature = "kasan")]
+pub unsafe fn dealloc(ptr: *mut u8, layout: Layout) {
+    let user = ptr as usize;
+    let mut state = KASAN.lock();
+
+    let alloc = match state.live[..state.live_count].iter().position(|a| a.user == user) {
+        Some(idx) => {
+            // Remove from live table (swap-remove)
+            let alloc = state.live[idx];
+            state.live_count -= 1;
+            let last = state.live_count;
+            state.live[idx] = state.live[last];
+            alloc
+        }
+        None => {
+            let in_quarantine = (0..state.q_len)
+                .map(|i| state.quarantine[(state.q_head + i) % QUARANTINE_SLOTS])
+                .any(|a| a.user == user);
+            let (heap_start, heap_size) = super::allocator::heap_bounds();
+            let in_heap = user >= heap_start && user < heap_start + heap_size;
+            if in_quarantine || !in_heap || state.untracked == 0 {
+                let err = if in_quarantine { KasanError::DoubleFree } else { KasanError::InvalidFree };
+                report(&mut state, err, user, 0, None);
+                return;
+            }
+            // Allocated while the live table was full
+            state.untracked -= 1;
+            Allocation::for_layout(user, layout)
+        }
+    };
+
+    // Check both redzones
+    if let Some(bad) = find_mismatch(alloc.raw, alloc.user - alloc.raw, REDZONE_BYTE)
+        .or_else(|| find_mismatch(alloc.user + alloc.size, REDZONE_SIZE, REDZONE_BYTE))
+    {
+        report(&mut state, KasanError::HeapBufferOverflow, bad, 1, Some(alloc));
+    }
+
+    // Poison and quarantine
+    core::ptr::write_bytes(alloc.user as *mut u8, FREED_BYTE, alloc.size);
+
+    if state.q_len == QUARANTINE_SLOTS {
+        evict_oldest(&mut state);
+    }
+    let tail = (state.q_head + state.q_len) % QUARANTINE_SLOTS;
+    state.quarantine[tail] = alloc;
+    state.q_len += 1;
+    state.q_bytes += alloc.raw_size;
+
+    while state.q_bytes > QUARANTINE_MAX_BYTES && state.q_len > 1 {
+        evict_oldest(&mut state);
+    }
+}
+
+/// Release the oldest quarantined block back to the heap
+#[cfg(feature = "kasan")]
+unsafe fn evict_oldest(state: &mut KasanState) {
+    if state.q_len == 0 {
+        return;
+    }
+    let alloc = state.quarantine[state.q_head];
+    state.q_head = (state.q_head + 1) % QUARANTINE_SLOTS;
+    state.q_len -= 1;
+    state.q_bytes -= alloc.raw_size;
+
+    // A changed poison byte means someone wrote to freed memory
+    if let Some(bad) = find_mismatch(alloc.user, alloc.size, FREED_BYTE) {
+        report(state, KasanError::UseAfterFree, bad, 1, Some(alloc));
+    }
+
+    super::allocator::deallocate(alloc.raw as *mut u8, alloc.raw_size, alloc.align);
+}
+
+/// Find the first byte in `[addr, addr + len)` that is not `expected`
+#[cfg(feature = "kasan")]
+unsafe fn find_mismatch(addr: usize, len: usize, expected: u8) -> Option<usize> {
+    let bytes = core::slice::from_raw_parts(addr as *const u8, len);
+    bytes.iter().position(|&b| b != expected).map(|i| addr + i)
+}
+
+// ============================================================================
+// Access Checks
+// ============================================================================
+
+/// Check a kernel read of `len` bytes at `addr`
+#[inline(always)]
+pub fn check_read(addr: usize, len: usize) {
+    #[cfg(feature = "kasan")]
+    check_access(addr, len);
+    #[cfg(not(feature = "kasan"))]
+    let _ = (addr, len);
+}
+
+/// Check a kernel write of `len` bytes at `addr`
+#[inline(always)]
+pub fn check_write(addr: usize, len: usize) {
+    #[cfg(feature = "kasan")]
+    check_access(addr, len);
+    #[cfg(not(feature = "kasan"))]
+    let _ = (addr, len);
+}
+
+#[cfg(feature = "kasan")]
+fn check_access(addr: usize, len: usize) {
+    if len == 0 {
+        return;
+    }
+
+    let (heap_start, heap_size) = super::allocator::heap_bounds();
+    if addr + len <= heap_start || addr >= heap_start + heap_size {
+        return;
+    }
+
+    let mut state = KASAN.lock();
+
+    if let Some(alloc) = state.live[..state.live_count].iter().copied().find(|a| a.overlaps(addr, len)) {
+        if !alloc.contains_user(addr, len) {
+            report(&mut state, KasanError::HeapBufferOverflow, addr, len, Some(alloc));
+        }
+        return;
+    }
+
+    let freed = (0..state.q_len)
+        .map(|i| state.quarantine[(state.q_head + i) % QUARANTINE_SLOTS])
+        .find(|a| a.overlaps(addr, len));
+    if let Some(alloc) = freed {
+        report(&mut state, KasanError::UseAfterFree, addr, len, Some(alloc));
+    }
+}
+
+/// Number of bugs reported so far
+pub fn report_count() -> u64 {
+    #[cfg(feature = "kasan")]
+    return KASAN.lock().reports;
+    #[cfg(not(feature = "kasan"))]
+    0
+}
+
+// ============================================================================
+// Reporting
+// ============================================================================
+
+#[cfg(feature = "kasan")]
+fn report(state: &mut KasanState, err: KasanError, addr: usize, len: usize, alloc: Option<Allocation>) {
+    state.reports += 1;
+
+    match alloc {
+        Some(a) => kerror!("[KASAN] {} at {:#x} len={:x} block={:#x} size={:x}", err.as_str(), addr, len, a.user, a.size),
+        None => kerror!("[KASAN] {} at {:#x} len={:x}", err.as_str(), addr, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocation_bounds() {
+        let a = Allocation { raw: 0x1000, raw_size: 96, user: 0x1020, size: 32, align: 16 };
+        assert!(a.overlaps(0x1000, 1));
+        assert!(!a.overlaps(0x1060, 8));
+        assert!(a.contains_user(0x1020, 32));
+        assert!(!a.contains_user(0x1020, 33));
+        assert!(!a.contains_user(0x101F, 1));
+    }
+
+    #[test]
+    fn test_block_for_layout() {
+        let a = Allocation::for_layout(0x1020, Layout::from_size_align(32, 8).unwrap());
+        assert_eq!((a.raw, a.raw_size, a.size, a.align), (0x1000, 96, 32, 16));
+
+        // A larger alignment widens the front redzone to keep the data aligned
+        let b = Allocation::for_layout(0x2040, Layout::from_size_align(10, 64).unwrap());
+        assert_eq!((b.raw, b.raw_size, b.align), (0x2000, 64 + 10 + REDZONE_SIZE, 64));
+    }
+}
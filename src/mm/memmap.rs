@@ -0,0 +1,246 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Firmware Memory Map
+//!
+//! Physical memory as the UEFI memory map described it when
+//! ExitBootServices returned. The loader classifies each descriptor
+//! ([`RegionKind`]) and [`record`]s it before `init::pmm_init`, which
+//! gives the [`Usable`](RegionKind::Usable) ranges to the PMM as arenas
+//! (see [`pmm_add_memory_map`](super::pmm::pmm_add_memory_map)).
+//!
+//! The map is copied into a fixed table of [`MAX_REGIONS`] entries, since
+//! there is no heap yet when it is recorded. Entries are kept sorted by
+//! address, and adjacent entries of the same kind are merged.
+//!
+//! # Reclaimable Memory
+//!
+//! Boot services and loader memory is free once the firmware is gone, but
+//! at this point it still holds the firmware stack the kernel runs on, the
+//! kernel image and the memory map itself. It is recorded as
+//! [`Reclaimable`](RegionKind::Reclaimable) and not given to the PMM.
+
+use crate::sync::SpinMutex;
+
+/// Entries the recorded map holds
+pub const MAX_REGIONS: usize = 128;
+
+/// What a region of physical memory holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Free RAM
+    Usable,
+    /// RAM still in use by the boot stage (boot services and loader)
+    Reclaimable,
+    /// ACPI tables and ACPI NVS
+    Acpi,
+    /// Memory-mapped I/O
+    Mmio,
+    /// Runtime services, unusable or otherwise reserved memory
+    Reserved,
+}
+
+/// A range of physical memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// First byte
+    pub base: u64,
+    /// Size in bytes
+    pub size: u64,
+    /// What it holds
+    pub kind: RegionKind,
+}
+
+impl Region {
+    /// Create a region
+    pub const fn new(base: u64, size: u64, kind: RegionKind) -> Self {
+        Self { base, size, kind }
+    }
+
+    /// End of the region (exclusive)
+    pub const fn end(&self) -> u64 {
+        self.base.saturating_add(self.size)
+    }
+}
+
+/// Sorted, merged table of regions
+pub struct MemoryMap {
+    regions: [Region; MAX_REGIONS],
+    len: usize,
+    dropped: usize,
+}
+
+impl MemoryMap {
+    /// Create an empty map
+    pub const fn new() -> Self {
+        Self {
+            regions: [Region::new(0, 0, RegionKind::Reserved); MAX_REGIONS],
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Recorded regions, lowest address first
+    pub fn regions(&self) -> &[Region] {
+        &self.regions[..self.len]
+    }
+
+    /// Regions left out because the table was full
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Add a region, merging it with a neighbor of the same kind
+    ///
+    /// # Returns
+    ///
+    /// `false` if the table is full and the region was left out
+    pub fn insert(&mut self, region: Region) -> bool {
+        if region.size == 0 {
+            return true;
+        }
+
+        let at = self.regions().partition_point(|r| r.base < region.base);
+        let joins_prev = at > 0 && {
+            let prev = &self.regions[at - 1];
+            prev.kind == region.kind && prev.end() == region.base
+        };
+        let joins_next = at < self.len && {
+            let next = &self.regions[at];
+            next.kind == region.kind && region.end() == next.base
+        };
+
+        match (joins_prev, joins_next) {
+            (true, true) => {
+                self.regions[at - 1].size += region.size + self.regions[at].size;
+                self.regions.copy_within(at + 1..self.len, at);
+                self.len -= 1;
+            }
+            (true, false) => self.regions[at - 1].size += region.size,
+            (false, true) => {
+                self.regions[at].base = region.base;
+                self.regions[at].size += region.size;
+            }
+            (false, false) => {
+                if self.len == MAX_REGIONS {
+                    self.dropped += 1;
+                    return false;
+                }
+                self.regions.copy_within(at..self.len, at + 1);
+                self.regions[at] = region;
+                self.len += 1;
+            }
+        }
+        true
+    }
+
+    /// Usable memory within `[min, max)`, as page-aligned `(base, size)`
+    /// ranges, lowest first
+    pub fn usable_ranges(&self, min: u64, max: u64) -> impl Iterator<Item = (u64, u64)> + '_ {
+        const PAGE: u64 = 4096;
+
+        self.regions()
+            .iter()
+            .filter(|r| r.kind == RegionKind::Usable)
+            .filter_map(move |r| {
+                let start = r.base.max(min).next_multiple_of(PAGE);
+                let end = r.end().min(max) & !(PAGE - 1);
+                (start < end).then(|| (start, end - start))
+            })
+    }
+
+    /// Total bytes of `kind`
+    pub fn total(&self, kind: RegionKind) -> u64 {
+        self.regions().iter().filter(|r| r.kind == kind).map(|r| r.size).sum()
+    }
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The memory map recorded at boot
+static MEMORY_MAP: SpinMutex<MemoryMap> = SpinMutex::new(MemoryMap::new());
+
+/// Record a region of the firmware memory map
+///
+/// Called by the loader for every descriptor, after ExitBootServices and
+/// before `init::pmm_init()`.
+pub fn record(region: Region) {
+    MEMORY_MAP.lock().insert(region);
+}
+
+/// Run `f` on the recorded memory map
+pub fn with<R>(f: impl FnOnce(&MemoryMap) -> R) -> R {
+    f(&MEMORY_MAP.lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1 << 20;
+
+    #[test]
+    fn test_insert_sorts_and_merges() {
+        let mut map = MemoryMap::new();
+        map.insert(Region::new(4 * MB, 2 * MB, RegionKind::Usable));
+        map.insert(Region::new(0, MB, RegionKind::Usable));
+        map.insert(Region::new(MB, MB, RegionKind::Reclaimable));
+        map.insert(Region::new(2 * MB, 2 * MB, RegionKind::Usable));
+        map.insert(Region::new(8 * MB, MB, RegionKind::Mmio));
+
+        assert_eq!(
+            map.regions(),
+            [
+                Region::new(0, MB, RegionKind::Usable),
+                Region::new(MB, MB, RegionKind::Reclaimable),
+                Region::new(2 * MB, 4 * MB, RegionKind::Usable),
+                Region::new(8 * MB, MB, RegionKind::Mmio),
+            ]
+        );
+
+        // Filling a gap joins both neighbors
+        map.insert(Region::new(6 * MB, 2 * MB, RegionKind::Mmio));
+        map.insert(Region::new(20 * MB, MB, RegionKind::Mmio));
+        map.insert(Region::new(9 * MB, 11 * MB, RegionKind::Mmio));
+        assert_eq!(map.regions()[3], Region::new(6 * MB, 15 * MB, RegionKind::Mmio));
+        assert_eq!(map.regions().len(), 4);
+        assert_eq!(map.total(RegionKind::Usable), 5 * MB);
+    }
+
+    #[test]
+    fn test_full_map_drops() {
+        let mut map = MemoryMap::new();
+        for i in 0..MAX_REGIONS as u64 {
+            assert!(map.insert(Region::new(i * 2 * MB, MB, RegionKind::Usable)));
+        }
+        assert!(!map.insert(Region::new(u64::MAX / 2, MB, RegionKind::Usable)));
+        assert_eq!(map.dropped(), 1);
+        // Merging needs no new entry
+        assert!(map.insert(Region::new(MB, MB, RegionKind::Usable)));
+    }
+
+    #[test]
+    fn test_usable_ranges_clip() {
+        let mut map = MemoryMap::new();
+        map.insert(Region::new(0x800, 0x10_0000, RegionKind::Usable));
+        map.insert(Region::new(0x20_0000, 0x10_0000, RegionKind::Acpi));
+        map.insert(Region::new(0x40_0000, 0x40_0000, RegionKind::Usable));
+
+        let ranges: [(u64, u64); 2] = {
+            let mut it = map.usable_ranges(0, u64::MAX);
+            [it.next().unwrap(), it.next().unwrap()]
+        };
+        assert_eq!(ranges, [(0x1000, 0xF_F000), (0x40_0000, 0x40_0000)]);
+
+        let mut it = map.usable_ranges(0x50_0000, 0x60_0800);
+        assert_eq!(it.next(), Some((0x50_0000, 0x10_0000)));
+        assert_eq!(it.next(), None);
+    }
+}
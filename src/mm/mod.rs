@@ -12,7 +12,14 @@
 //! # Modules
 //!
 //! - [`pmm`] - Physical Memory Manager for allocating physical pages
-//! - [`allocator`] - Heap allocator for dynamic memory allocation
+//! - [`memmap`] - Firmware memory map the PMM arenas are built from
+//! - [`vmm`] - Kernel virtual mappings: `vmalloc`, `ioremap`, guarded kernel stacks
+//! - [`allocator`] - Slab and buddy heap allocator for dynamic memory allocation
+//! - [`kasan`] - Heap redzones and free quarantine (`kasan` feature)
+//! - [`selftest`] - Boot-time memory self-tests (`selftest=mm`)
+//! - [`watermark`] - Current/peak heap and PMM usage, threshold warnings
+//! - [`reclaim`] - Giving unused zero pages of background processes back under pressure
+//! - [`wss`] - Per-process working-set estimates from accessed-bit scans
 //!
 //! # Usage
 //!
@@ -53,7 +60,14 @@
 //! ```
 
 pub mod pmm;
+pub mod memmap;
+pub mod vmm;
 pub mod allocator;
+pub mod kasan;
+pub mod selftest;
+pub mod watermark;
+pub mod reclaim;
+pub mod wss;
 
 // Re-export PAGE_SIZE explicitly from page_tables to avoid ambiguity
 pub use crate::arch::amd64::mm::page_tables::PAGE_SIZE;
@@ -98,8 +112,11 @@ pub use pmm::{
     pmm_count_free_pages,
     pmm_count_total_pages,
     pmm_count_total_bytes,
+    set_detected_memory,
+    detected_memory,
     paddr_to_page,
     pmm_init_early,
+    pmm_add_memory_map,
     // Convenience wrappers
     alloc_page,
     free_page,
@@ -116,6 +133,7 @@ pub use allocator::{
     heap_usage,
     heap_size,
     heap_available,
+    boot_heap_size,
     DEFAULT_HEAP_SIZE,
 };
 
@@ -124,3 +142,14 @@ pub type Result<T> = crate::arch::amd64::mm::RxResult<T>;
 
 /// Memory management status type
 pub type Status = crate::arch::amd64::mm::RxStatus;
+
+/// Run the working-set scan and background reclaim, each if it is due
+///
+/// There are no kernel threads, so this runs wherever a CPU is outside an
+/// interrupt with no locks held: AP idle loops, a CPU waiting for a
+/// blocked thread, and every syscall return (the boot CPU has no idle
+/// loop). Cheap when nothing is due.
+pub fn periodic() {
+    wss::tick();
+    reclaim::background();
+}
@@ -4,26 +4,39 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT
 
-//! Physical Memory Manager (PMM) - Vec-Based Implementation
+//! Physical Memory Manager (PMM) - Buddy Allocator
 //!
-//! This is a simplified PMM using a Vec-based page array with linear scan allocation.
-//! This replaces the bitmap-based PMM which had a bug in the atomic bitmap handling.
+//! Physical memory is split into arenas (the kernel and user zones). Each
+//! arena keeps a [`Page`] for every page it covers and a buddy allocator
+//! over them: free pages are grouped into blocks of 2^order pages, aligned
+//! to their size in physical memory, with one free list per order up to
+//! [`MAX_ORDER`].
 //!
-//! # Architecture Note
+//! # Design
 //!
-//! This represents the "Early Kernel PMM" stage - simple, reliable, proven to work.
+//! - An allocation of order N takes the smallest free block of order N or
+//!   more and splits it, putting the halves it does not need back on their
+//!   free lists
+//! - A freed block merges with its buddy (the other half of the next
+//!   order's block) while the buddy is a free block of the same order
+//! - A contiguous run is rounded up to a block, and the pages past the run
+//!   are freed again; since blocks are aligned to their size, this also
+//!   gives the requested alignment. Runs larger than a [`MAX_ORDER`] block
+//!   fall back to a linear scan
+//! - The free lists are linked through the [`Page`] array itself
+//! - Simple state enum: Free | Allocated | Reserved | Poisoned
 //!
-//! # Design
+//! # Poisoned Pages
 //!
-//! - Uses a Vec of Page structures with state tracking
-//! - Linear scan allocation (O(N) where N = total pages)
-//! - No atomic operations (single-threaded initially)
-//! - Simple state enum: Free | Allocated | Reserved
+//! [`pmm_poison_page`] takes no locks, so a free page it poisons stays on
+//! its free list. An allocation checks the pages of the block it hands
+//! out: pages that are no longer free are left out, and the rest go back
+//! on the free lists.
 //!
-//! # Migration Path
+//! # Locking
 //!
-//! Phase A (NOW): Use this Vec-based PMM to unblock userspace
-//! Phase B (LATER): Reintroduce bitmap PMM with proper testing as optimization
+//! Allocation, freeing and reservation run under one lock with interrupts
+//! disabled.
 //!
 //! # Usage
 //!
@@ -46,29 +59,14 @@ use crate::arch::amd64::mm::{
     page_tables::PAGE_SIZE
 };
 use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::sync::{lockstat, SpinMutex};
+use crate::{kerror, ktrace};
 
 /// Global PMM allocation call counter
 static ALLOC_CALL_COUNT: AtomicUsize = AtomicUsize::new(0);
 
-/// Helper: Print decimal number to debug console
-unsafe fn print_decimal(mut n: usize) {
-    if n == 0 {
-        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b'0', options(nomem, nostack));
-        return;
-    }
-    let mut buf = [0u8; 20];
-    let mut i = 0;
-    while n > 0 {
-        let digit = (n % 10) as u8;
-        buf[i] = b'0' + digit;
-        n /= 10;
-        i += 1;
-    }
-    while i > 0 {
-        i -= 1;
-        core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-    }
-}
+/// Usable RAM reported by the firmware memory map, in bytes (0 = unknown)
+static DETECTED_MEMORY: AtomicU64 = AtomicU64::new(0);
 
 /// Page size shift for quick division/multiplication
 pub const PAGE_SIZE_SHIFT: u8 = 12;
@@ -77,7 +75,19 @@ pub const PAGE_SIZE_SHIFT: u8 = 12;
 pub const PAGE_MASK: usize = PAGE_SIZE - 1;
 
 /// Maximum number of physical memory arenas
-const MAX_ARENAS: usize = 8;
+const MAX_ARENAS: usize = 16;
+
+/// Smallest memory map region worth an arena
+const MIN_MAP_ARENA_SIZE: u64 = 1024 * 1024;
+
+/// Largest buddy block order (2^10 pages = 4 MB)
+pub const MAX_ORDER: usize = 10;
+
+/// [`Page`] free order of a page that does not head a free block
+const NO_ORDER: u8 = u8::MAX;
+
+/// End of a free list
+const NIL: u32 = u32::MAX;
 
 /// Check if an address is page-aligned
 #[inline]
@@ -109,6 +119,12 @@ pub const fn pages_to_bytes(pages: usize) -> usize {
     pages << PAGE_SIZE_SHIFT
 }
 
+/// Smallest buddy order whose blocks hold `count` pages
+#[inline]
+pub const fn order_for(count: usize) -> usize {
+    count.next_power_of_two().trailing_zeros() as usize
+}
+
 /// Page state enumeration
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -121,6 +137,9 @@ pub enum PageState {
 
     /// Page is reserved (cannot be allocated)
     Reserved = 2,
+
+    /// Page had an uncorrected memory error (never allocated again)
+    Poisoned = 3,
 }
 
 impl PageState {
@@ -234,6 +253,15 @@ pub struct Page {
 
     /// Page index within arena
     pub page_index: u32,
+
+    /// Order of the free block this page heads (`NO_ORDER` if none)
+    free_order: u8,
+
+    /// Next block on the same free list (page index in the arena)
+    next: u32,
+
+    /// Previous block on the same free list (page index in the arena)
+    prev: u32,
 }
 
 impl Page {
@@ -245,6 +273,9 @@ impl Page {
             ref_count: 0,
             arena_index,
             page_index,
+            free_order: NO_ORDER,
+            next: NIL,
+            prev: NIL,
         }
     }
 
@@ -265,8 +296,11 @@ struct Arena {
     /// Total number of pages
     total_count: u64,
 
-    /// Lock for arena operations (spinlock for now)
-    locked: core::sync::atomic::AtomicBool,
+    /// Page frame number of the first page
+    base_pfn: u64,
+
+    /// First block on each order's free list
+    free_lists: [u32; MAX_ORDER + 1],
 }
 
 impl Arena {
@@ -276,24 +310,221 @@ impl Arena {
             info,
             pages: alloc::vec::Vec::new(),
             total_count: 0,
-            locked: core::sync::atomic::AtomicBool::new(false),
+            base_pfn: 0,
+            free_lists: [NIL; MAX_ORDER + 1],
         }
     }
 
-    /// Initialize the arena with page structures
+    /// Initialize the arena with page structures, all free
     fn init(&mut self, pages: alloc::vec::Vec<Page>) {
         self.total_count = pages.len() as u64;
         self.pages = pages;
+        self.base_pfn = self.info.base >> PAGE_SIZE_SHIFT;
+        self.free_lists = [NIL; MAX_ORDER + 1];
+        self.release_range(0, self.pages.len());
+    }
+
+    /// Check if allocations with `flags` (PMM_ALLOC_FLAG_*) may use this arena
+    fn matches(&self, flags: u32) -> bool {
+        match flags {
+            PMM_ALLOC_FLAG_LOW_MEM => self.info.flags & ARENA_FLAG_LOW_MEM != 0,
+            PMM_ALLOC_FLAG_KERNEL => self.info.flags & ARENA_FLAG_KERNEL != 0,
+            PMM_ALLOC_FLAG_USER => self.info.flags & ARENA_FLAG_USER != 0,
+            _ => true,
+        }
+    }
+
+    /// Physical address of the page at `index`
+    fn paddr(&self, index: usize) -> PAddr {
+        self.info.base + (index as PAddr) * PAGE_SIZE as PAddr
+    }
+
+    /// Largest order a block starting at page `index` is aligned for
+    fn alignment_order(&self, index: usize) -> usize {
+        let pfn = self.base_pfn + index as u64;
+        core::cmp::min(pfn.trailing_zeros() as usize, MAX_ORDER)
+    }
+
+    /// Index of the buddy of the `order` block at `index`, if it is in the arena
+    fn buddy(&self, index: usize, order: usize) -> Option<usize> {
+        let pfn = (self.base_pfn + index as u64) ^ (1 << order);
+        let buddy = pfn.checked_sub(self.base_pfn)? as usize;
+        (buddy + (1 << order) <= self.pages.len()).then_some(buddy)
+    }
+
+    /// Put the `order` block at `index` on its free list
+    fn push(&mut self, index: usize, order: usize) {
+        let head = self.free_lists[order];
+        let page = &mut self.pages[index];
+        page.free_order = order as u8;
+        page.prev = NIL;
+        page.next = head;
+        if head != NIL {
+            self.pages[head as usize].prev = index as u32;
+        }
+        self.free_lists[order] = index as u32;
+    }
+
+    /// Take the block at `index` off its free list
+    fn unlink(&mut self, index: usize) {
+        let page = &self.pages[index];
+        let (order, prev, next) = (page.free_order as usize, page.prev, page.next);
+        if prev == NIL {
+            self.free_lists[order] = next;
+        } else {
+            self.pages[prev as usize].next = next;
+        }
+        if next != NIL {
+            self.pages[next as usize].prev = prev;
+        }
+        self.pages[index].free_order = NO_ORDER;
+    }
+
+    /// Free the `order` block at `index`, merging it with free buddies
+    ///
+    /// Its pages must already be `Free` and on no free list.
+    fn release_block(&mut self, mut index: usize, mut order: usize) {
+        while order < MAX_ORDER {
+            match self.buddy(index, order) {
+                Some(buddy) if self.pages[buddy].free_order as usize == order => {
+                    self.unlink(buddy);
+                    index = core::cmp::min(index, buddy);
+                    order += 1;
+                }
+                _ => break,
+            }
+        }
+        self.push(index, order);
+    }
+
+    /// Free pages `start..end` in the largest aligned blocks that fit
+    fn release_range(&mut self, mut start: usize, end: usize) {
+        while start < end {
+            let mut order = self.alignment_order(start);
+            while start + (1 << order) > end {
+                order -= 1;
+            }
+            self.release_block(start, order);
+            start += 1 << order;
+        }
+    }
+
+    /// Take a free `order` block off the free lists, splitting a larger
+    /// one if needed
+    ///
+    /// Pages of the block that are no longer free (poisoned while on a
+    /// free list) are left out, the rest are freed again, and the search
+    /// goes on.
+    ///
+    /// # Returns
+    ///
+    /// Index of the block's first page; all its pages are `Free`
+    fn take_block(&mut self, order: usize) -> Option<usize> {
+        loop {
+            let mut k = (order..=MAX_ORDER).find(|&k| self.free_lists[k] != NIL)?;
+            let index = self.free_lists[k] as usize;
+            self.unlink(index);
+            while k > order {
+                k -= 1;
+                self.push(index + (1 << k), k);
+            }
+
+            let block = index..index + (1 << order);
+            if self.pages[block.clone()].iter().all(Page::is_free) {
+                return Some(index);
+            }
+            for i in block {
+                if self.pages[i].is_free() {
+                    self.release_block(i, 0);
+                }
+            }
+        }
+    }
+
+    /// Take the free page at `index` off the free lists, splitting the
+    /// block that holds it
+    ///
+    /// # Returns
+    ///
+    /// False if no free block holds the page
+    fn take_page(&mut self, index: usize) -> bool {
+        let pfn = self.base_pfn + index as u64;
+        for order in 0..=MAX_ORDER {
+            let Some(head) = (pfn & !((1 << order) - 1)).checked_sub(self.base_pfn) else { break };
+            let mut head = head as usize;
+            if self.pages[head].free_order as usize != order {
+                continue;
+            }
+
+            self.unlink(head);
+            for k in (0..order).rev() {
+                let half = head + (1 << k);
+                if index >= half {
+                    self.push(head, k);
+                    head = half;
+                } else {
+                    self.push(half, k);
+                }
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Mark `count` pages from `index` allocated
+    fn mark_allocated(&mut self, index: usize, count: usize) {
+        for page in &mut self.pages[index..index + count] {
+            page.state = PageState::Allocated;
+            page.ref_count = 1;
+        }
     }
 
     /// Allocate a single page from this arena
     fn alloc_page(&mut self) -> Option<PAddr> {
-        // Simple linear scan for free page
-        for page in &mut self.pages {
-            if page.is_free() {
-                page.state = PageState::Allocated;
-                page.ref_count = 1;
-                return Some(page.paddr);
+        let index = self.take_block(0)?;
+        self.mark_allocated(index, 1);
+        Some(self.paddr(index))
+    }
+
+    /// Allocate `count` contiguous pages starting on a 2^`align_order`
+    /// page boundary
+    fn alloc_run(&mut self, count: usize, align_order: usize) -> Option<PAddr> {
+        let order = core::cmp::max(order_for(count), align_order);
+        let index = if order <= MAX_ORDER {
+            let index = self.take_block(order)?;
+            // Only the first `count` pages of the block are needed
+            self.release_range(index + count, index + (1 << order));
+            index
+        } else {
+            self.find_run(count, align_order)?
+        };
+        self.mark_allocated(index, count);
+        Some(self.paddr(index))
+    }
+
+    /// Find `count` free pages in a row by scanning, for runs that do not
+    /// fit in a block
+    ///
+    /// # Returns
+    ///
+    /// Index of the first page; the run is taken off the free lists
+    fn find_run(&mut self, count: usize, align_order: usize) -> Option<usize> {
+        let align = 1u64 << align_order;
+        let mut start = 0;
+        while start + count <= self.pages.len() {
+            let misalign = (self.base_pfn + start as u64) % align;
+            if misalign != 0 {
+                start += (align - misalign) as usize;
+                continue;
+            }
+            match self.pages[start..start + count].iter().position(|p| !p.is_free()) {
+                Some(busy) => start += busy + 1,
+                None => {
+                    for i in start..start + count {
+                        self.take_page(i);
+                    }
+                    return Some(start);
+                }
             }
         }
         None
@@ -311,11 +542,52 @@ impl Arena {
             return RxStatus::ERR_INVALID_ARGS;
         }
 
-        self.pages[index].state = PageState::Free;
+        match self.pages[index].state {
+            // Already free: freeing it again would put it on two free lists
+            PageState::Free => return RxStatus::ERR_INVALID_ARGS,
+            // A poisoned page stays out of circulation when its owner lets go
+            PageState::Poisoned => {}
+            PageState::Allocated | PageState::Reserved => {
+                self.pages[index].state = PageState::Free;
+                self.release_block(index, 0);
+            }
+        }
         self.pages[index].ref_count = 0;
         RxStatus::OK
     }
 
+    /// Reserve the page at `index`, taking it off the free lists if free
+    ///
+    /// A poisoned page stays poisoned: it may still sit on a free list,
+    /// and freeing it as a reserved page would list it twice.
+    ///
+    /// # Returns
+    ///
+    /// Whether the page was free
+    fn reserve_page(&mut self, index: usize) -> bool {
+        match self.pages[index].state {
+            PageState::Poisoned => false,
+            PageState::Free => {
+                self.take_page(index);
+                self.pages[index].state = PageState::Reserved;
+                true
+            }
+            _ => {
+                self.pages[index].state = PageState::Reserved;
+                false
+            }
+        }
+    }
+
+    /// Index of the page containing `paddr`
+    fn page_index(&self, paddr: PAddr) -> Option<usize> {
+        if !self.address_in_arena(paddr) {
+            return None;
+        }
+        Some(((paddr - self.info.base) / PAGE_SIZE as PAddr) as usize)
+            .filter(|&i| i < self.total_count as usize)
+    }
+
     /// Check if a physical address is within this arena
     fn address_in_arena(&self, addr: PAddr) -> bool {
         addr >= self.info.base && addr < (self.info.base + self.info.size as PAddr)
@@ -332,45 +604,37 @@ impl Arena {
     }
 }
 
+/// An unused arena slot
+const EMPTY_ARENA: Arena = Arena::new(ArenaInfo::new(
+    b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    0, 0, 0, 0
+));
+
 /// Global arena array
-static mut ARENAS: [Arena; MAX_ARENAS] = [
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-    Arena::new(ArenaInfo::new(
-        b"empty\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        0, 0, 0, 0
-    )),
-];
+static mut ARENAS: [Arena; MAX_ARENAS] = [EMPTY_ARENA; MAX_ARENAS];
 
 /// Number of arenas currently in use
 static mut NUM_ARENAS: usize = 0;
 
+/// Serializes allocation, freeing and reservation in every arena
+static PMM_LOCK: SpinMutex<()> = SpinMutex::named((), &lockstat::PMM);
+
+/// Run `f` on the arenas under [`PMM_LOCK`] with interrupts disabled
+fn with_arenas<R>(f: impl FnOnce(&mut [Arena]) -> R) -> R {
+    use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
+
+    let were_disabled = arch_ints_disabled();
+    arch_disable_ints();
+    let result = {
+        let _guard = PMM_LOCK.lock();
+        f(unsafe { &mut ARENAS[..NUM_ARENAS] })
+    };
+    if !were_disabled {
+        arch_enable_ints();
+    }
+    result
+}
+
 /// Early PMM initialization
 ///
 /// This function initializes the physical memory manager with memory arenas.
@@ -407,6 +671,56 @@ pub unsafe fn pmm_init_early(
     }
 }
 
+/// Add an arena for every usable region of the firmware memory map
+///
+/// Covers the usable ranges recorded in [`memmap`](super::memmap) from
+/// `min` up to [`IDENTITY_MAP_LIMIT`], the memory `paddr_to_vaddr` can
+/// reach. Each range becomes a user arena that keeps its page structures
+/// in its own first pages, so the boot allocator does not limit how much
+/// RAM is covered. Ranges under [`MIN_MAP_ARENA_SIZE`] and ranges past the
+/// last free arena slot are left out.
+///
+/// # Returns
+///
+/// The number of arenas added (0 if no memory map was recorded)
+///
+/// # Safety
+///
+/// Must be called during boot, before any allocation, with nothing else
+/// living in the usable ranges above `min`.
+pub unsafe fn pmm_add_memory_map(min: PAddr) -> usize {
+    let mut ranges = [(0u64, 0u64); MAX_ARENAS];
+    let count = super::memmap::with(|map| {
+        let mut count = 0;
+        let usable = map.usable_ranges(min, IDENTITY_MAP_LIMIT).filter(|&(_, size)| size >= MIN_MAP_ARENA_SIZE);
+        for (slot, range) in ranges.iter_mut().zip(usable) {
+            *slot = range;
+            count += 1;
+        }
+        count
+    });
+
+    let mut added = 0;
+    for &(base, size) in &ranges[..count] {
+        // Page structures for every page of the range, in its first pages
+        let page_count = size as usize / PAGE_SIZE;
+        let meta_pages = bytes_to_pages(page_count * core::mem::size_of::<Page>());
+        let meta_bytes = pages_to_bytes(meta_pages) as u64;
+        let info = ArenaInfo::new(
+            b"ram\0",
+            ARENA_FLAG_LOW_MEM | ARENA_FLAG_USER,
+            1 + added as u32,
+            base + meta_bytes,
+            (size - meta_bytes) as usize,
+        );
+        if pmm_install_arena(info, paddr_to_vaddr(base) as *mut u8) != RxStatus::OK {
+            break;
+        }
+        added += 1;
+    }
+    added
+}
+
 /// Add a memory arena to the PMM
 ///
 /// # Arguments
@@ -429,8 +743,6 @@ pub unsafe fn pmm_add_arena(info: ArenaInfo) -> RxStatus {
     // Allocate page structures array
     // For now, use the boot allocator (passed via set_boot_allocator)
     // In the future, this should use a proper boot allocator
-    extern crate alloc;
-
     let pages_layout = core::alloc::Layout::array::<Page>(page_count).unwrap();
     let pages_ptr = if let Some(boot_alloc) = BOOT_ALLOC {
         boot_alloc(pages_layout.size(), pages_layout.align())
@@ -445,10 +757,25 @@ pub unsafe fn pmm_add_arena(info: ArenaInfo) -> RxStatus {
         heap_ptr
     };
 
-    let pages = core::ptr::slice_from_raw_parts_mut(
-        pages_ptr as *mut Page,
-        page_count,
-    );
+    pmm_install_arena(info, pages_ptr)
+}
+
+/// Fill in the next arena slot, with its page structures at `pages_ptr`
+///
+/// # Safety
+///
+/// `pages_ptr` must point to writable, suitably aligned memory for
+/// `info.page_count()` [`Page`]s that is never used for anything else.
+unsafe fn pmm_install_arena(info: ArenaInfo, pages_ptr: *mut u8) -> RxStatus {
+    extern crate alloc;
+
+    let page_count = info.page_count();
+    if NUM_ARENAS >= MAX_ARENAS {
+        return RxStatus::ERR_NO_MEMORY;
+    }
+    if page_count == 0 {
+        return RxStatus::ERR_INVALID_ARGS;
+    }
 
     // Initialize page structures using pointer arithmetic
     let pages_slice = unsafe {
@@ -505,102 +832,29 @@ pub fn pmm_alloc_page(flags: u32) -> RxResult<PAddr> {
     // Increment and get call number
     let call_num = ALLOC_CALL_COUNT.fetch_add(1, Ordering::Relaxed);
 
-    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
-
-    // Debug: Log which allocator is being called WITH CALL NUMBER
-    unsafe {
-        let msg = b"[PMM] Call #";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        print_decimal(call_num);
-
-        // Print allocation type separately
-        if flags == PMM_ALLOC_FLAG_KERNEL {
-            let msg = b" alloc_kernel_page\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-        } else if flags == PMM_ALLOC_FLAG_USER {
-            let msg = b" alloc_user_page\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-        } else {
-            let msg = b" alloc_page(GENERIC)\n";
-            for &byte in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-            }
-        }
-    }
+    let kind = match flags {
+        PMM_ALLOC_FLAG_KERNEL => "alloc_kernel_page",
+        PMM_ALLOC_FLAG_USER => "alloc_user_page",
+        _ => "alloc_page(GENERIC)",
+    };
+    ktrace!("[PMM] Call #{} {}", call_num, kind);
 
     // Try to allocate from matching arenas
-    for arena in arenas {
-        // Filter arenas based on requested flags
-        if flags == PMM_ALLOC_FLAG_LOW_MEM && (arena.info.flags & ARENA_FLAG_LOW_MEM) == 0 {
-            continue;
-        }
-        if flags == PMM_ALLOC_FLAG_KERNEL && (arena.info.flags & ARENA_FLAG_KERNEL) == 0 {
-            continue;
-        }
-        if flags == PMM_ALLOC_FLAG_USER && (arena.info.flags & ARENA_FLAG_USER) == 0 {
-            continue;
-        }
-
-        if let Some(paddr) = arena.alloc_page() {
-            // Debug: Log SUCCESS with call number
-            unsafe {
-                let msg = b"[PMM] Call #";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-                print_decimal(call_num);
-                let msg = b" SUCCESS -> 0x";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-                // Print address in hex
-                let mut n = paddr;
-                let mut buf = [0u8; 16];
-                let mut i = 0;
-                loop {
-                    let digit = (n & 0xF) as u8;
-                    buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                    n >>= 4;
-                    i += 1;
-                    if n == 0 { break; }
-                }
-                while i > 0 {
-                    i -= 1;
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                }
-                let msg = b"\n";
-                for &byte in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                }
-            }
-            return Ok(paddr);
-        }
+    let allocated = with_arenas(|arenas| {
+        arenas.iter_mut().filter(|arena| arena.matches(flags)).find_map(|arena| arena.alloc_page())
+    });
+    if let Some(paddr) = allocated {
+        crate::kcounters::pages_allocated(1);
+        super::watermark::PMM.add(PAGE_SIZE);
+
+        ktrace!("[PMM] Call #{} SUCCESS -> {:#x}", call_num, paddr);
+        return Ok(paddr);
     }
 
-    // Debug: Log exhaustion
-    unsafe {
-        let msg = b"[PMM] Call #";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        print_decimal(call_num);
-        let msg = b" FAILED - PMM EXHAUSTED\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        // Halt with distinctive pattern
-        let msg = b"[PMM] EXHAUSTED - HALTING\n";
-        for &byte in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-        }
-        loop {}
-    }
+    kerror!("[PMM] Call #{} FAILED - PMM EXHAUSTED", call_num);
+    // Halt with distinctive pattern
+    kerror!("[PMM] EXHAUSTED - HALTING");
+    loop {}
 
     Err(RxStatus::ERR_NO_MEMORY)
 }
@@ -639,60 +893,37 @@ pub fn pmm_alloc_user_page() -> RxResult<PAddr> {
 ///
 /// * `count` - Number of pages to allocate
 /// * `flags` - Allocation flags
-/// * `align_log2` - Alignment as log2 (0 = page aligned, 12 = 4KB, etc.)
+/// * `align_log2` - Alignment of the first page as log2 of bytes (up to
+///   12 = page aligned, 21 = 2MB, etc.)
 ///
 /// # Returns
 ///
 /// Physical address of the allocated region, or an error
 ///
-/// Note: For the Vec-based PMM, contiguous allocation is implemented
-/// by finding sequential free pages. This is simpler than the bitmap version
-/// but may be slower for large allocations.
-pub fn pmm_alloc_contiguous(count: usize, flags: u32, _align_log2: u8) -> RxResult<PAddr> {
-    if count == 0 {
+/// The run comes from a single buddy block of at least `count` pages,
+/// aligned to its size, and the pages past the run are freed again. Runs
+/// larger than a [`MAX_ORDER`] block are found by scanning.
+pub fn pmm_alloc_contiguous(count: usize, flags: u32, align_log2: u8) -> RxResult<PAddr> {
+    if count == 0 || align_log2 >= 64 {
         return Err(RxStatus::ERR_INVALID_ARGS);
     }
+    let align_order = (align_log2 as usize).saturating_sub(PAGE_SIZE_SHIFT as usize);
 
     // For single pages, use the regular allocator
-    if count == 1 {
+    if count == 1 && align_order == 0 {
         return pmm_alloc_page(flags);
     }
 
-    // For multiple pages, try to find contiguous free pages
-    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
-
-    for arena in arenas {
-        if flags == PMM_ALLOC_FLAG_LOW_MEM && (arena.info.flags & ARENA_FLAG_LOW_MEM) == 0 {
-            continue;
-        }
-
-        // Try to find contiguous free pages
-        let mut start_index = 0;
-        let total_count = arena.total_count as usize;
-
-        while start_index + count <= total_count {
-            // Check if all pages in this range are free
-            let mut all_free = true;
-            for page_idx in start_index..(start_index + count) {
-                if !arena.pages[page_idx].is_free() {
-                    all_free = false;
-                    start_index = page_idx + 1; // Start from next page
-                    break;
-                }
-            }
-
-            if all_free {
-                // Mark all pages as allocated
-                for page_idx in start_index..(start_index + count) {
-                    arena.pages[page_idx].state = PageState::Allocated;
-                    arena.pages[page_idx].ref_count = 1;
-                }
-                return Ok(arena.info.base + (start_index as PAddr) * PAGE_SIZE as PAddr);
-            }
-        }
-    }
-
-    Err(RxStatus::ERR_NO_MEMORY)
+    let allocated = with_arenas(|arenas| {
+        arenas
+            .iter_mut()
+            .filter(|arena| arena.matches(flags))
+            .find_map(|arena| arena.alloc_run(count, align_order))
+    });
+    let paddr = allocated.ok_or(RxStatus::ERR_NO_MEMORY)?;
+    crate::kcounters::pages_allocated(count);
+    super::watermark::PMM.add(count * PAGE_SIZE);
+    Ok(paddr)
 }
 
 /// Free a physical page
@@ -703,18 +934,21 @@ pub fn pmm_alloc_contiguous(count: usize, flags: u32, _align_log2: u8) -> RxResu
 ///
 /// # Returns
 ///
-/// `RxStatus::OK` on success, or an error code
+/// `RxStatus::OK` on success, or `ERR_INVALID_ARGS` if `paddr` is not a
+/// PMM page or is already free
 pub fn pmm_free_page(paddr: PAddr) -> RxStatus {
-    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
-
     // Find the arena containing this page
-    for arena in arenas {
-        if arena.address_in_arena(paddr) {
-            return arena.free_page(paddr);
-        }
+    let status = with_arenas(|arenas| {
+        arenas
+            .iter_mut()
+            .find(|arena| arena.address_in_arena(paddr))
+            .map_or(RxStatus::ERR_INVALID_ARGS, |arena| arena.free_page(paddr))
+    });
+    if status == RxStatus::OK {
+        crate::kcounters::pages_freed(1);
+        super::watermark::PMM.sub(PAGE_SIZE);
     }
-
-    RxStatus::ERR_INVALID_ARGS
+    status
 }
 
 /// Free multiple contiguous physical pages
@@ -732,18 +966,48 @@ pub fn pmm_free_contiguous(paddr: PAddr, count: usize) -> RxStatus {
         return RxStatus::ERR_INVALID_ARGS;
     }
 
-    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
+    let end_addr = paddr + (count as PAddr) * PAGE_SIZE as PAddr;
 
     // Find the arena containing this page range
-    for arena in arenas {
-        let end_addr = paddr + (count as PAddr) * PAGE_SIZE as PAddr;
+    let status = with_arenas(|arenas| {
+        let Some(arena) = arenas
+            .iter_mut()
+            .find(|arena| arena.address_in_arena(paddr) && arena.address_in_arena(end_addr - 1))
+        else {
+            return RxStatus::ERR_INVALID_ARGS;
+        };
+        // Free each page; they merge back into blocks as they go
+        for i in 0..count {
+            let page_paddr = paddr + (i as PAddr) * PAGE_SIZE as PAddr;
+            let _ = arena.free_page(page_paddr);
+        }
+        RxStatus::OK
+    });
+    if status == RxStatus::OK {
+        crate::kcounters::pages_freed(count);
+        super::watermark::PMM.sub(count * PAGE_SIZE);
+    }
+    status
+}
 
-        if arena.address_in_arena(paddr) && arena.address_in_arena(end_addr - 1) {
-            // Free each page
-            for i in 0..count {
-                let page_paddr = paddr + (i as PAddr) * PAGE_SIZE as PAddr;
-                let _ = arena.free_page(page_paddr);
+/// Take another reference to an allocated page
+///
+/// Used when a page is shared copy-on-write: each sharer holds one
+/// reference and releases it with [`pmm_page_unref`].
+///
+/// # Returns
+///
+/// `RxStatus::OK`, or `ERR_INVALID_ARGS` if `paddr` is not an allocated page
+pub fn pmm_page_ref(paddr: PAddr) -> RxStatus {
+    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
+
+    for arena in arenas {
+        if let Some(index) = arena.page_index(paddr) {
+            let page = &mut arena.pages[index];
+            if page.state != PageState::Allocated && page.state != PageState::Poisoned {
+                return RxStatus::ERR_INVALID_ARGS;
             }
+            page.ref_count += 1;
             return RxStatus::OK;
         }
     }
@@ -751,6 +1015,29 @@ pub fn pmm_free_contiguous(paddr: PAddr, count: usize) -> RxStatus {
     RxStatus::ERR_INVALID_ARGS
 }
 
+/// Drop a reference to an allocated page, freeing it with the last one
+///
+/// # Returns
+///
+/// True if the page was freed
+pub fn pmm_page_unref(paddr: PAddr) -> bool {
+    if pmm_page_ref_count(paddr) > 1 {
+        let page = paddr_to_page(paddr);
+        unsafe { (*page).ref_count -= 1 };
+        return false;
+    }
+    pmm_free_page(paddr) == RxStatus::OK
+}
+
+/// References held on the page at `paddr` (0 if free or not PMM memory)
+pub fn pmm_page_ref_count(paddr: PAddr) -> u32 {
+    let page = paddr_to_page(paddr);
+    if page.is_null() {
+        return 0;
+    }
+    unsafe { (*page).ref_count }
+}
+
 /// Get the number of free pages across all arenas
 pub fn pmm_count_free_pages() -> u64 {
     let arenas = unsafe { &ARENAS[..NUM_ARENAS] };
@@ -778,28 +1065,71 @@ pub fn pmm_reserve_pages(paddr: PAddr, count: usize) -> RxStatus {
         return RxStatus::ERR_INVALID_ARGS;
     }
 
-    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
+    let end_addr = paddr + (count as PAddr) * PAGE_SIZE as PAddr;
 
     // Find the arena containing this page range
-    for arena in arenas {
-        let end_addr = paddr + (count as PAddr) * PAGE_SIZE as PAddr;
-
-        if arena.address_in_arena(paddr) && arena.address_in_arena(end_addr - 1) {
-            // Mark each page as reserved
-            for i in 0..count {
-                let page_paddr = paddr + (i as PAddr) * PAGE_SIZE as PAddr;
-                let offset = page_paddr - arena.info.base;
-                let index = (offset / PAGE_SIZE as PAddr) as usize;
-
-                if index < arena.total_count as usize {
-                    arena.pages[index].state = PageState::Reserved;
+    let taken = with_arenas(|arenas| {
+        let arena = arenas
+            .iter_mut()
+            .find(|arena| arena.address_in_arena(paddr) && arena.address_in_arena(end_addr - 1))?;
+        // Mark each page as reserved
+        let mut taken = 0;
+        for i in 0..count {
+            let page_paddr = paddr + (i as PAddr) * PAGE_SIZE as PAddr;
+            if let Some(index) = arena.page_index(page_paddr) {
+                if arena.reserve_page(index) {
+                    taken += 1;
                 }
             }
+        }
+        Some(taken)
+    });
+
+    match taken {
+        Some(taken) => {
+            super::watermark::PMM.add(taken * PAGE_SIZE);
+            RxStatus::OK
+        }
+        None => RxStatus::ERR_INVALID_ARGS,
+    }
+}
+
+/// Mark the page containing `paddr` as poisoned
+///
+/// Called by the machine check handler for an uncorrected memory error.
+/// The page is never handed out again; if it is allocated, it stays
+/// poisoned once freed. Takes no locks, so it is safe in #MC context.
+///
+/// # Returns
+///
+/// `RxStatus::OK`, or `ERR_NOT_FOUND` if no arena covers `paddr`
+pub fn pmm_poison_page(paddr: PAddr) -> RxStatus {
+    let arenas = unsafe { &mut ARENAS[..NUM_ARENAS] };
+
+    for arena in arenas {
+        if let Some(index) = arena.page_index(paddr) {
+            arena.pages[index].state = PageState::Poisoned;
             return RxStatus::OK;
         }
     }
 
-    RxStatus::ERR_INVALID_ARGS
+    RxStatus::ERR_NOT_FOUND
+}
+
+/// Check whether the page containing `paddr` is poisoned
+pub fn pmm_is_poisoned(paddr: PAddr) -> bool {
+    let arenas = unsafe { &ARENAS[..NUM_ARENAS] };
+    arenas.iter().any(|arena| {
+        arena.page_index(paddr).is_some_and(|i| arena.pages[i].state == PageState::Poisoned)
+    })
+}
+
+/// Get the number of poisoned pages across all arenas
+pub fn pmm_count_poisoned_pages() -> u64 {
+    let arenas = unsafe { &ARENAS[..NUM_ARENAS] };
+    arenas.iter()
+        .map(|arena| arena.pages.iter().filter(|p| p.state == PageState::Poisoned).count() as u64)
+        .sum()
 }
 
 /// Get the total number of pages across all arenas
@@ -819,6 +1149,29 @@ pub fn pmm_count_total_bytes() -> u64 {
     pmm_count_total_pages() * PAGE_SIZE as u64
 }
 
+/// Record the usable RAM found in the firmware memory map
+///
+/// Must be called before `init::pmm_init()`, which sizes the heap from it.
+pub fn set_detected_memory(bytes: u64) {
+    DETECTED_MEMORY.store(bytes, Ordering::Relaxed);
+}
+
+/// Usable RAM found in the firmware memory map, 0 if not recorded
+pub fn detected_memory() -> u64 {
+    DETECTED_MEMORY.load(Ordering::Relaxed)
+}
+
+/// Start the PMM usage watermark from the current arena state
+///
+/// Call once the arenas are added; allocations, frees and reservations
+/// after this keep it up to date.
+pub fn pmm_track_usage() {
+    let total = pmm_count_total_bytes() as usize;
+    let free = pmm_count_free_pages() as usize * PAGE_SIZE;
+    super::watermark::PMM.set_capacity(total);
+    super::watermark::PMM.reset(total - free);
+}
+
 /// Convert physical address to page structure
 ///
 /// # Arguments
@@ -853,14 +1206,14 @@ pub fn paddr_to_page(paddr: PAddr) -> *mut Page {
 /// Physical memory is mapped at this offset in kernel virtual address space.
 /// This allows the kernel to access any physical page by adding this offset.
 /// Using the standard x86_64 kernel direct map offset (same as Linux).
-const KERNEL_PHYS_OFFSET: u64 = 0xffff_8000_0000_0000;
+const KERNEL_PHYS_OFFSET: u64 = crate::arch::amd64::mm::layout::PHYSMAP.start;
 
 /// Maximum physical memory that is identity mapped by UEFI
 ///
 /// UEFI typically identity maps the first 2GB of physical memory.
 /// For addresses below this threshold, use identity mapping.
 /// For addresses above, use the direct mapping offset.
-const IDENTITY_MAP_LIMIT: u64 = 0x8000_0000; // 2GB
+const IDENTITY_MAP_LIMIT: u64 = crate::arch::amd64::mm::layout::IDENTITY_MAP_LIMIT;
 
 /// Convert physical address to virtual address (for KERNEL zone only)
 ///
@@ -884,6 +1237,19 @@ pub fn paddr_to_vaddr(paddr: PAddr) -> VAddr {
     }
 }
 
+/// Convert a kernel zone virtual address back to its physical address
+///
+/// Inverse of [`paddr_to_vaddr`], for memory that was allocated from the
+/// PMM and is only known by its kernel mapping (e.g. a kernel stack).
+pub fn vaddr_to_paddr(vaddr: VAddr) -> PAddr {
+    let vaddr = vaddr as u64;
+    if vaddr >= KERNEL_PHYS_OFFSET {
+        vaddr - KERNEL_PHYS_OFFSET
+    } else {
+        vaddr
+    }
+}
+
 /// Convert physical address to virtual address (for USER zone only)
 ///
 /// ALWAYS uses the kernel's direct mapping region (KERNEL_PHYS_OFFSET).
@@ -919,3 +1285,133 @@ pub fn alloc_page() -> RxResult<PAddr> {
 pub fn free_page(paddr: PAddr) {
     let _ = pmm_free_page(paddr);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// An arena of `pages` pages at `base`, all free
+    fn arena(base: PAddr, pages: usize) -> Arena {
+        let mut arena = Arena::new(ArenaInfo::new(b"test", ARENA_FLAG_USER, 0, base, pages * PAGE_SIZE));
+        let page_array = (0..pages)
+            .map(|i| Page::new(base + (i * PAGE_SIZE) as PAddr, 0, i as u32))
+            .collect();
+        arena.init(page_array);
+        arena
+    }
+
+    /// Orders of the blocks on the free lists, by first page index
+    fn free_blocks(arena: &Arena) -> Vec<(usize, usize)> {
+        let mut blocks = Vec::new();
+        for order in 0..=MAX_ORDER {
+            let mut next = arena.free_lists[order];
+            while next != NIL {
+                blocks.push((next as usize, order));
+                next = arena.pages[next as usize].next;
+            }
+        }
+        blocks.sort();
+        blocks
+    }
+
+    #[test]
+    fn test_blocks_follow_physical_alignment() {
+        // Pages 3..13 (pfn) split into 3, 4-7, 8-11, 12
+        let arena = arena(3 * PAGE_SIZE as PAddr, 10);
+        assert_eq!(free_blocks(&arena), [(0, 0), (1, 2), (5, 2), (9, 0)]);
+        assert_eq!(order_for(1), 0);
+        assert_eq!(order_for(5), 3);
+    }
+
+    #[test]
+    fn test_free_merges_buddies() {
+        let mut arena = arena(0, 16);
+        let pages: Vec<PAddr> = (0..16).map(|_| arena.alloc_page().unwrap()).collect();
+        assert!(arena.alloc_page().is_none());
+        assert!(free_blocks(&arena).is_empty());
+
+        for &paddr in pages.iter().rev() {
+            assert_eq!(arena.free_page(paddr), RxStatus::OK);
+        }
+        assert_eq!(free_blocks(&arena), [(0, 4)]);
+        assert_eq!(arena.count_free_pages(), 16);
+        // A second free would put the page on two lists
+        assert_eq!(arena.free_page(pages[0]), RxStatus::ERR_INVALID_ARGS);
+    }
+
+    #[test]
+    fn test_contiguous_run_is_aligned_and_trimmed() {
+        let mut arena = arena(0, 64);
+        let first = arena.alloc_page().unwrap();
+        assert_eq!(first, 0);
+
+        // Five pages come from an 8-page block; the last three go back
+        let run = arena.alloc_run(5, 0).unwrap();
+        assert_eq!(run % (8 * PAGE_SIZE as PAddr), 0);
+        assert_eq!(arena.count_free_pages(), 64 - 6);
+        let index = (run / PAGE_SIZE as PAddr) as usize;
+        assert!(free_blocks(&arena).contains(&(index + 5, 0)));
+        assert!(free_blocks(&arena).contains(&(index + 6, 1)));
+
+        // Alignment beyond the size of the run
+        let aligned = arena.alloc_run(2, 4).unwrap();
+        assert_eq!(aligned % (16 * PAGE_SIZE as PAddr), 0);
+
+        for i in 0..5 {
+            arena.free_page(run + (i * PAGE_SIZE) as PAddr);
+        }
+        for i in 0..2 {
+            arena.free_page(aligned + (i * PAGE_SIZE) as PAddr);
+        }
+        arena.free_page(first);
+        assert_eq!(free_blocks(&arena), [(0, 6)]);
+    }
+
+    #[test]
+    fn test_run_larger_than_a_block() {
+        let pages = (1 << MAX_ORDER) * 2;
+        let mut arena = arena(0, pages);
+        arena.reserve_page(0);
+
+        // No block holds the run, so it is found by scanning
+        let count = (1 << MAX_ORDER) + 1;
+        let run = arena.alloc_run(count, 0).unwrap();
+        assert_eq!(run, PAGE_SIZE as PAddr);
+        assert_eq!(arena.count_free_pages(), (pages - count - 1) as u64);
+        assert!(arena.alloc_run(count, 0).is_none());
+        assert_eq!(arena.alloc_run(pages, 0), None);
+    }
+
+    #[test]
+    fn test_reserve_splits_the_free_block() {
+        let mut arena = arena(0, 8);
+        assert!(arena.reserve_page(5));
+        assert!(!arena.reserve_page(5));
+        assert_eq!(free_blocks(&arena), [(0, 2), (4, 0), (6, 1)]);
+        assert_eq!(arena.pages[5].state, PageState::Reserved);
+
+        // Freeing a reserved page returns it to the free lists
+        assert_eq!(arena.free_page(5 * PAGE_SIZE as PAddr), RxStatus::OK);
+        assert_eq!(free_blocks(&arena), [(0, 3)]);
+    }
+
+    #[test]
+    fn test_poisoned_free_page_is_skipped() {
+        let mut arena = arena(0, 4);
+        // Poisoned while free, as the #MC handler does without the lock
+        arena.pages[1].state = PageState::Poisoned;
+
+        let mut pages: Vec<PAddr> = core::iter::from_fn(|| arena.alloc_page()).collect();
+        pages.sort();
+        assert_eq!(pages, [0, 2 * PAGE_SIZE as PAddr, 3 * PAGE_SIZE as PAddr]);
+        assert!(free_blocks(&arena).is_empty());
+
+        // The run cannot include the poisoned page either
+        for &paddr in &pages {
+            arena.free_page(paddr);
+        }
+        assert_eq!(arena.alloc_run(2, 0), Some(2 * PAGE_SIZE as PAddr));
+        assert_eq!(arena.alloc_run(2, 0), None);
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Memory Reclaim
+//!
+//! When physical memory or the kernel heap runs short, pages that
+//! processes committed but no longer use are given back to the PMM before
+//! allocations start failing. There is no swap, so only pages that hold
+//! nothing but zeros can be given back: the VMO reads them as zeros
+//! anyway, and the next touch commits a fresh zero page (a refault).
+//!
+//! # Aging
+//!
+//! Pages are aged by the working-set scan ([`crate::mm::wss`]), which
+//! each pass runs first: a page no mapping touched since the previous
+//! scan has its `idle` count raised, a used one starts again from 0, and
+//! a page written through a mapping is marked dirty ([`PageMapEntry`]).
+//!
+//! # Decommit
+//!
+//! A page the VMO owns alone that has been idle for enough scans is
+//! unmapped everywhere. If it was touched in the meantime it is kept
+//! (and faults back in on the next access). A clean page is zeros by
+//! construction; a dirty one is read to check. Pages with data are kept
+//! and aged again from 0.
+//!
+//! Only VMOs mapped by processes in the process table are looked at, and
+//! only if no mapping is read-only (those can be shared with a child
+//! still being set up) and no process mapping them is running, since
+//! TLB entries are only flushed on this CPU. The pass holds the process
+//! table lock, so no such process is scheduled and no fault maps a page
+//! meanwhile.
+//!
+//! # Triggers
+//!
+//! | Trigger | Runs | Pages taken |
+//! |---------|------|-------------|
+//! | Background ([`background`]) | From [`crate::mm::periodic`] (idle loops, syscall return), at most every [`BACKGROUND_INTERVAL_NS`], while heap or PMM usage is at or above `mm.reclaim` | Idle for [`COLD_PASSES`] scans |
+//! | Direct ([`direct`]) | When a page fault cannot get a page, before it fails | Idle since the previous scan |
+//!
+//! `mm.reclaim=<percent>` defaults to [`DEFAULT_PERCENT`]; `0` turns
+//! background reclaim off.
+//!
+//! # Counters
+//!
+//! Passes, pages scanned, reclaimed, refaulted and kept for their data
+//! are shown in `/proc/meminfo` ([`write_summary`]); passes, reclaimed
+//! pages and refaults are also in the kernel counters page
+//! ([`crate::kcounters`]). Few refaults per reclaimed page means reclaim
+//! is picking the right pages.
+//!
+//! [`PageMapEntry`]: crate::object::vmo::PageMapEntry
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::arch::amd64::mm::PAddr;
+use crate::exec::elf::PF_W;
+use crate::mm::watermark::{HEAP, PMM};
+use crate::object::vmo::Vmo;
+use crate::process::table::{is_on_cpu, PROCESS_TABLE};
+use crate::process::AddressSpace;
+
+/// Command line option: usage in percent at which background reclaim starts
+pub const RECLAIM_OPTION: &str = "mm.reclaim";
+
+/// Background reclaim threshold used when no option is given
+pub const DEFAULT_PERCENT: usize = 80;
+
+/// Scans a page must go unused before background reclaim takes it
+pub const COLD_PASSES: u8 = 2;
+
+/// Least time between background passes
+pub const BACKGROUND_INTERVAL_NS: u64 = 100_000_000;
+
+/// Most pages one pass unmaps and inspects
+pub const BATCH_PAGES: usize = 256;
+
+/// Page size
+const PAGE_SIZE: usize = 4096;
+
+/// Background reclaim threshold in percent (0 = off)
+static THRESHOLD: AtomicUsize = AtomicUsize::new(DEFAULT_PERCENT);
+
+/// When the last background pass started
+static LAST_PASS_NS: AtomicU64 = AtomicU64::new(0);
+
+static PASSES: AtomicU64 = AtomicU64::new(0);
+static SCANNED: AtomicU64 = AtomicU64::new(0);
+static RECLAIMED: AtomicU64 = AtomicU64::new(0);
+static REFAULTS: AtomicU64 = AtomicU64::new(0);
+static KEPT: AtomicU64 = AtomicU64::new(0);
+
+/// Where a VMO is mapped: page table, base address, size
+type Mapping = (PAddr, u64, u64);
+
+/// A VMO with all its mappings, or None once it is ruled out
+type Candidate = Option<(Arc<Vmo>, Vec<Mapping>)>;
+
+/// Apply the `mm.reclaim` option from the command line
+pub fn init_options() {
+    let mut buf = [0u8; 8];
+    if let Some(percent) = crate::cmdline::get(RECLAIM_OPTION, &mut buf).and_then(|s| s.parse::<usize>().ok()) {
+        THRESHOLD.store(core::cmp::min(percent, 100), Ordering::Relaxed);
+    }
+}
+
+/// Whether usage of `heap` or `pmm` percent is at `threshold` (0 = never)
+fn pressure(heap: usize, pmm: usize, threshold: usize) -> bool {
+    threshold != 0 && core::cmp::max(heap, pmm) >= threshold
+}
+
+/// Run a background pass if memory is short and none ran recently
+///
+/// Called through [`crate::mm::periodic`]; cheap when there is nothing to
+/// do.
+pub fn background() {
+    if !pressure(HEAP.percent_used(), PMM.percent_used(), THRESHOLD.load(Ordering::Relaxed)) {
+        return;
+    }
+    if crate::mm::wss::due(&LAST_PASS_NS, BACKGROUND_INTERVAL_NS) {
+        pass(COLD_PASSES);
+    }
+}
+
+/// Reclaim what can be found right away, for an allocation that failed
+///
+/// Must be called without the process table lock held.
+///
+/// # Returns
+///
+/// Pages given back to the PMM
+pub fn direct() -> usize {
+    pass(1)
+}
+
+/// Count a reclaimed page being committed again
+pub fn record_refault() {
+    REFAULTS.fetch_add(1, Ordering::Relaxed);
+    crate::kcounters::record_refault();
+}
+
+/// Run a working-set scan, then decommit pages idle for `min_age` scans
+///
+/// Gives up at once if the process table is locked: the caller may be
+/// holding it, or a process is being scheduled.
+///
+/// # Returns
+///
+/// Pages given back to the PMM
+fn pass(min_age: u8) -> usize {
+    let Some(mut table) = PROCESS_TABLE.try_lock() else {
+        return 0;
+    };
+    crate::mm::wss::scan_locked(&mut table);
+
+    // Page tables that are live in some CPU's TLB
+    let running: Vec<PAddr> = table.iter().filter(|p| is_on_cpu(p.pid)).map(|p| p.page_table).collect();
+
+    // Every VMO mapped by a process, with all its mappings; None once it
+    // turns out not to be a candidate
+    let mut vmos: BTreeMap<*const Vmo, Candidate> = BTreeMap::new();
+    for (page_table, vmar) in crate::process::vmar::all() {
+        for region in vmar.lock().regions() {
+            let slot = vmos
+                .entry(Arc::as_ptr(&region.vmo))
+                .or_insert_with(|| Some((Arc::clone(&region.vmo), Vec::new())));
+            if region.flags & PF_W == 0 || running.contains(&page_table) {
+                *slot = None;
+            }
+            if let Some((_, mappings)) = slot {
+                mappings.push((page_table, region.base, region.size));
+            }
+        }
+    }
+
+    let mut budget = BATCH_PAGES;
+    let mut reclaimed = 0;
+    for (vmo, mappings) in vmos.into_values().flatten() {
+        reclaimed += reclaim_vmo(&vmo, &mappings, min_age, &mut budget);
+    }
+    drop(table);
+
+    PASSES.fetch_add(1, Ordering::Relaxed);
+    RECLAIMED.fetch_add(reclaimed as u64, Ordering::Relaxed);
+    crate::kcounters::record_reclaim(reclaimed);
+    reclaimed
+}
+
+/// Decommit the cold pages of one VMO, up to `budget`
+fn reclaim_vmo(vmo: &Vmo, mappings: &[Mapping], min_age: u8, budget: &mut usize) -> usize {
+    let mut pages = vmo.pages.lock();
+
+    let mut cold = Vec::new();
+    let mut scanned = 0;
+    for (&key, entry) in pages.iter() {
+        // Pages the VMO does not own, or shares with a clone
+        if !entry.present || !entry.writable || entry.is_shared() {
+            continue;
+        }
+        scanned += 1;
+        if entry.idle >= min_age && cold.len() < *budget {
+            cold.push(key);
+        }
+    }
+    SCANNED.fetch_add(scanned, Ordering::Relaxed);
+    *budget -= cold.len();
+
+    let mut reclaimed = 0;
+    for key in cold {
+        let (accessed, dirty) = unmap_everywhere(mappings, key);
+        let Some(entry) = pages.get_mut(&key) else {
+            continue;
+        };
+        entry.dirty |= dirty;
+        if accessed {
+            // Used after all; faults back in on the next access
+            entry.idle = 0;
+            continue;
+        }
+        if entry.dirty && !is_zero(entry.paddr) {
+            entry.idle = 0;
+            KEPT.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        if vmo.decommit_page(&mut pages, key) {
+            reclaimed += 1;
+        }
+    }
+    reclaimed
+}
+
+/// Unmap the page at VMO offset `key` from every mapping
+///
+/// # Returns
+///
+/// Whether any mapping had the page accessed since the last scan, and
+/// whether any had it dirty
+fn unmap_everywhere(mappings: &[Mapping], key: usize) -> (bool, bool) {
+    let mut result = (false, false);
+    for &(page_table, base, size) in mappings {
+        if key as u64 >= size {
+            continue;
+        }
+        let aspace = unsafe { AddressSpace::from_page_table(page_table) };
+        // Pages never touched through this mapping are not mapped
+        if let Ok((accessed, dirty)) = aspace.unmap_page_harvest(base + key as u64) {
+            result.0 |= accessed;
+            result.1 |= dirty;
+        }
+    }
+    result
+}
+
+/// Check if the user page at `paddr` holds only zeros
+fn is_zero(paddr: PAddr) -> bool {
+    let vaddr = crate::mm::pmm::paddr_to_vaddr_user_zone(paddr);
+    let words = unsafe { core::slice::from_raw_parts(vaddr as *const u64, PAGE_SIZE / 8) };
+    words.iter().all(|&w| w == 0)
+}
+
+/// Write a one-line summary of what reclaim has done
+pub fn write_summary(out: &mut impl Write) -> core::fmt::Result {
+    writeln!(
+        out,
+        "reclaim: {} passes, {} pages scanned, {} reclaimed, {} refaulted, {} kept for data",
+        PASSES.load(Ordering::Relaxed),
+        SCANNED.load(Ordering::Relaxed),
+        RECLAIMED.load(Ordering::Relaxed),
+        REFAULTS.load(Ordering::Relaxed),
+        KEPT.load(Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pressure() {
+        assert!(!pressure(79, 50, 80));
+        assert!(pressure(80, 50, 80));
+        assert!(pressure(10, 95, 80));
+        assert!(!pressure(100, 100, 0));
+    }
+}
@@ -0,0 +1,447 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Memory Management Self-Tests
+//!
+//! Boot with `selftest=mm` to exercise the memory managers right after
+//! init and before the first process is spawned. Memory bugs tend to
+//! surface much later as unrelated corruption; these checks catch them at
+//! the point where the cause is still obvious.
+//!
+//! # Tests
+//!
+//! | Test | Checks |
+//! |------|--------|
+//! | `pmm_pages` | Single-page alloc/free: alignment, uniqueness, free count, data |
+//! | `pmm_contiguous` | Multi-page runs: data across page boundaries, free count |
+//! | `heap_stress` | Mixed-size allocations with interleaved frees keep their data |
+//! | `heap_invariants` | Free list links, magics and bounds (before and after the stress) |
+//! | `page_tables` | map / translate / unmap round-trips in a scratch address space, checked with page table diffs |
+//! | `spawn_unwind` | Spawns refused by a job's process or memory limit give back every page |
+//!
+//! Results are reported on the debug console. Boot continues either way.
+
+use alloc::vec::Vec;
+use crate::arch::amd64::mm::{PAddr, RxStatus};
+use crate::mm::allocator;
+use crate::mm::pmm;
+use crate::process::AddressSpace;
+use crate::process::ptdump::{self, Change, MappedRange};
+use crate::{kerror, kinfo};
+
+/// Command line option selecting self-test suites (comma separated)
+pub const SELFTEST_OPTION: &str = "selftest";
+
+/// Suite name for these tests
+pub const SUITE: &str = "mm";
+
+/// Pages allocated by the single-page test
+const PMM_STRESS_PAGES: usize = 64;
+
+/// Length of the contiguous run
+const PMM_CONTIGUOUS_PAGES: usize = 8;
+
+/// Allocations made by the heap stress test
+const HEAP_STRESS_ALLOCS: usize = 48;
+
+/// Scratch virtual address for the page table test (PML4 slot 128,
+/// unused by the kernel)
+const PT_TEST_VADDR: u64 = 0x0000_4000_0000_0000;
+
+/// Pages mapped by the page table test
+const PT_TEST_PAGES: usize = 4;
+
+/// Refused spawns attempted per limit by the spawn test
+const SPAWN_FAILURES: usize = 16;
+
+/// Summary of a self-test run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SelftestReport {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl SelftestReport {
+    /// Check whether every test passed
+    pub fn ok(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// Check whether `suite` appears in a `selftest=` value
+pub fn suite_listed(value: &str, suite: &str) -> bool {
+    value.split(',').any(|s| s.trim() == suite)
+}
+
+/// Run the suite if the command line asks for it
+///
+/// # Returns
+///
+/// The report, or None if `selftest=mm` was not given
+pub fn run_if_requested() -> Option<SelftestReport> {
+    let mut buf = [0u8; 64];
+    let value = crate::cmdline::get(SELFTEST_OPTION, &mut buf)?;
+    if !suite_listed(value, SUITE) {
+        return None;
+    }
+    Some(run())
+}
+
+/// Run all memory management self-tests
+pub fn run() -> SelftestReport {
+    kinfo!("[SELFTEST] mm: starting");
+    let mut report = SelftestReport::default();
+
+    record(&mut report, "heap_invariants", check_heap());
+    record(&mut report, "pmm_pages", test_pmm_pages());
+    record(&mut report, "pmm_contiguous", test_pmm_contiguous());
+    record(&mut report, "heap_stress", test_heap_stress());
+    record(&mut report, "heap_invariants", check_heap());
+    record(&mut report, "page_tables", test_page_tables());
+    record(&mut report, "spawn_unwind", test_spawn_unwind());
+
+    kinfo!("[SELFTEST] mm: {} passed, {} failed - {}", report.passed, report.failed, if report.ok() { "PASS" } else { "FAIL" });
+    report
+}
+
+fn record(report: &mut SelftestReport, name: &str, result: Result<(), &'static str>) {
+    match result {
+        Ok(()) => {
+            report.passed += 1;
+            kinfo!("[SELFTEST] mm: {} ok", name);
+        }
+        Err(why) => {
+            report.failed += 1;
+            kerror!("[SELFTEST] mm: {} FAILED: {}", name, why);
+        }
+    }
+}
+
+// ============================================================================
+// PMM
+// ============================================================================
+
+/// Pattern stored in a test page
+fn page_pattern(paddr: PAddr, word: usize) -> u64 {
+    paddr ^ (word as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Fill the first and last word of each page with a pattern
+fn fill_pages(base: PAddr, count: usize) {
+    for i in 0..count {
+        let paddr = base + (i * 4096) as PAddr;
+        let words = pmm::paddr_to_vaddr(paddr) as *mut u64;
+        unsafe {
+            core::ptr::write_volatile(words, page_pattern(paddr, 0));
+            core::ptr::write_volatile(words.add(511), page_pattern(paddr, 511));
+        }
+    }
+}
+
+/// Check the pattern written by [`fill_pages`]
+fn check_pages(base: PAddr, count: usize) -> bool {
+    (0..count).all(|i| {
+        let paddr = base + (i * 4096) as PAddr;
+        let words = pmm::paddr_to_vaddr(paddr) as *const u64;
+        unsafe {
+            core::ptr::read_volatile(words) == page_pattern(paddr, 0)
+                && core::ptr::read_volatile(words.add(511)) == page_pattern(paddr, 511)
+        }
+    })
+}
+
+fn test_pmm_pages() -> Result<(), &'static str> {
+    let free_before = pmm::pmm_count_free_pages();
+    let mut pages: [PAddr; PMM_STRESS_PAGES] = [0; PMM_STRESS_PAGES];
+    let mut count = 0;
+    let mut result = Ok(());
+
+    for slot in pages.iter_mut() {
+        match pmm::pmm_alloc_kernel_page() {
+            Ok(paddr) => {
+                *slot = paddr;
+                count += 1;
+            }
+            Err(_) => {
+                result = Err("allocation failed");
+                break;
+            }
+        }
+    }
+    let pages = &pages[..count];
+
+    if result.is_ok() {
+        result = if pages.iter().any(|&p| p & 0xFFF != 0) {
+            Err("page not aligned")
+        } else if (1..pages.len()).any(|i| pages[..i].contains(&pages[i])) {
+            Err("page handed out twice")
+        } else if pmm::pmm_count_free_pages() != free_before - count as u64 {
+            Err("free count did not drop")
+        } else {
+            pages.iter().for_each(|&p| fill_pages(p, 1));
+            if pages.iter().all(|&p| check_pages(p, 1)) {
+                Ok(())
+            } else {
+                Err("page contents overwritten")
+            }
+        };
+    }
+
+    for &paddr in pages {
+        if pmm::pmm_free_page(paddr) != RxStatus::OK && result.is_ok() {
+            result = Err("free failed");
+        }
+    }
+    if result.is_ok() && pmm::pmm_count_free_pages() != free_before {
+        result = Err("free count not restored");
+    }
+    result
+}
+
+fn test_pmm_contiguous() -> Result<(), &'static str> {
+    let free_before = pmm::pmm_count_free_pages();
+    let base = pmm::pmm_alloc_contiguous(PMM_CONTIGUOUS_PAGES, pmm::PMM_ALLOC_FLAG_KERNEL, 0)
+        .map_err(|_| "allocation failed")?;
+
+    let mut result = if base & 0xFFF != 0 {
+        Err("run not aligned")
+    } else {
+        fill_pages(base, PMM_CONTIGUOUS_PAGES);
+        if check_pages(base, PMM_CONTIGUOUS_PAGES) {
+            Ok(())
+        } else {
+            Err("run contents overwritten")
+        }
+    };
+
+    if pmm::pmm_free_contiguous(base, PMM_CONTIGUOUS_PAGES) != RxStatus::OK && result.is_ok() {
+        result = Err("free failed");
+    }
+    if result.is_ok() && pmm::pmm_count_free_pages() != free_before {
+        result = Err("free count not restored");
+    }
+    result
+}
+
+// ============================================================================
+// Heap
+// ============================================================================
+
+fn check_heap() -> Result<(), &'static str> {
+    allocator::heap_check().map(|_| ())
+}
+
+fn test_heap_stress() -> Result<(), &'static str> {
+    const SIZES: [usize; 6] = [8, 24, 200, 1000, 4096, 33];
+
+    let fill = |i: usize| alloc::vec![i as u8 ^ 0x5A; SIZES[i % SIZES.len()]];
+    let intact = |i: usize, v: &Vec<u8>| {
+        v.len() == SIZES[i % SIZES.len()] && v.iter().all(|&b| b == i as u8 ^ 0x5A)
+    };
+
+    let mut blocks: Vec<Option<Vec<u8>>> = (0..HEAP_STRESS_ALLOCS).map(|i| Some(fill(i))).collect();
+
+    // Free every other block, then refill the holes
+    for block in blocks.iter_mut().step_by(2) {
+        *block = None;
+    }
+    check_heap()?;
+    for (i, block) in blocks.iter_mut().enumerate() {
+        if block.is_none() {
+            *block = Some(fill(i));
+        }
+    }
+
+    let ok = blocks.iter().enumerate().all(|(i, b)| b.as_ref().is_some_and(|v| intact(i, v)));
+    drop(blocks);
+    if ok {
+        Ok(())
+    } else {
+        Err("allocation contents overwritten")
+    }
+}
+
+// ============================================================================
+// Page Tables
+// ============================================================================
+
+/// Mappings in the scratch PML4 slot
+fn scratch_snapshot(aspace: &AddressSpace) -> Vec<MappedRange> {
+    let slot = PT_TEST_VADDR..PT_TEST_VADDR + (1 << 39);
+    unsafe { ptdump::snapshot(aspace.page_table.phys(), slot) }
+}
+
+/// Check that mapping the test pages added exactly them, read/write/user
+fn check_map_diff(before: &[MappedRange], after: &[MappedRange]) -> Result<(), &'static str> {
+    const RW_USER: u8 = ptdump::perms::WRITE | ptdump::perms::USER;
+
+    // Frames need not be contiguous, so the pages may come as several ranges
+    let mut next = PT_TEST_VADDR;
+    for d in ptdump::diff(before, after) {
+        let added = d.after.filter(|_| d.change == Change::Added).ok_or("map changed other pages")?;
+        if added.vaddr != next || added.perms & RW_USER != RW_USER {
+            return Err("map added the wrong pages");
+        }
+        next = added.end();
+    }
+    if next != PT_TEST_VADDR + (PT_TEST_PAGES * 4096) as u64 {
+        return Err("map added the wrong pages");
+    }
+    Ok(())
+}
+
+fn test_page_tables() -> Result<(), &'static str> {
+    let free_before = pmm::pmm_count_free_pages();
+    let aspace = AddressSpace::new()?;
+    let empty = scratch_snapshot(&aspace);
+    let mut frames: [PAddr; PT_TEST_PAGES] = [0; PT_TEST_PAGES];
+    let mut result = Ok(());
+
+    for frame in frames.iter_mut() {
+        *frame = pmm::pmm_alloc_kernel_page().map_err(|_| "allocation failed")?;
+    }
+
+    for (i, &paddr) in frames.iter().enumerate() {
+        let vaddr = PT_TEST_VADDR + (i * 4096) as u64;
+        if let Err(e) = aspace.map_page(vaddr, paddr, 0x4 | 0x2) {
+            result = Err(e);
+            break;
+        }
+    }
+
+    if result.is_ok() {
+        result = (|| {
+            check_map_diff(&empty, &scratch_snapshot(&aspace))?;
+            for (i, &paddr) in frames.iter().enumerate() {
+                let vaddr = PT_TEST_VADDR + (i * 4096) as u64;
+                if aspace.translate(vaddr + 0x123) != Some(paddr + 0x123) {
+                    return Err("translate does not match map");
+                }
+            }
+            let past_end = PT_TEST_VADDR + (PT_TEST_PAGES * 4096) as u64;
+            if aspace.translate(past_end).is_some() {
+                return Err("unmapped page translates");
+            }
+            for i in 0..PT_TEST_PAGES {
+                let vaddr = PT_TEST_VADDR + (i * 4096) as u64;
+                aspace.unmap_page(vaddr)?;
+                if aspace.translate(vaddr).is_some() {
+                    return Err("page still mapped after unmap");
+                }
+            }
+            if aspace.unmap_page(PT_TEST_VADDR).is_ok() {
+                return Err("double unmap succeeded");
+            }
+            if !ptdump::diff(&empty, &scratch_snapshot(&aspace)).is_empty() {
+                return Err("unmap left pages mapped");
+            }
+            Ok(())
+        })();
+    }
+
+    for &paddr in &frames {
+        pmm::pmm_free_page(paddr);
+    }
+    free_scratch_tables(&aspace);
+
+    if result.is_ok() && pmm::pmm_count_free_pages() != free_before {
+        result = Err("page tables leaked");
+    }
+    result
+}
+
+/// Free the page tables built for [`PT_TEST_VADDR`] and the PML4
+///
+/// `AddressSpace` has no teardown yet. The tables under the scratch slot
+/// were all allocated by `map_page` for this test.
+fn free_scratch_tables(aspace: &AddressSpace) {
+    const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+    let va = PT_TEST_VADDR as usize;
+    let indices = [(va >> 39) & 0x1FF, (va >> 30) & 0x1FF, (va >> 21) & 0x1FF];
+    let mut tables: [PAddr; 3] = [0; 3];
+    let mut table = aspace.page_table.virt() as *const u64;
+
+    for (level, &index) in indices.iter().enumerate() {
+        let entry = unsafe { *table.add(index) };
+        if entry & 1 == 0 {
+            break;
+        }
+        tables[level] = entry & ADDR_MASK;
+        table = pmm::paddr_to_vaddr(tables[level]) as *const u64;
+    }
+
+    for &paddr in tables.iter().rev().filter(|&&p| p != 0) {
+        pmm::pmm_free_page(paddr);
+    }
+    pmm::pmm_free_page(aspace.page_table.phys());
+}
+
+// ============================================================================
+// Spawn
+// ============================================================================
+
+fn test_spawn_unwind() -> Result<(), &'static str> {
+    use crate::exec::userspace_exec_test::USERSPACE_ELF;
+    use crate::object::{Job, ResourceLimits, JOB_ID_ROOT};
+    use crate::process::jobs;
+
+    let job = Job::new_child_of(JOB_ID_ROOT, 0)?;
+    let spawn = || crate::syscall::create_process(USERSPACE_ELF, &crate::exec::ExecArgs::default(), job.id, |_| {});
+
+    // At the process limit: refused before anything is allocated
+    job.set_limits(ResourceLimits { max_processes: 1, ..ResourceLimits::unlimited() });
+    jobs::admit(job.id).map_err(|_| "could not fill the job")?;
+    let free_before = pmm::pmm_count_free_pages();
+    for _ in 0..SPAWN_FAILURES {
+        if spawn() != Err(RxStatus::ERR_NO_MEMORY) {
+            return Err("spawn over the process limit not refused");
+        }
+    }
+    jobs::leave(job.id);
+    if pmm::pmm_count_free_pages() != free_before {
+        return Err("spawns over the process limit leaked pages");
+    }
+
+    // Over the memory limit: refused once the image is loaded. The first
+    // attempt may grow the heap, so count from the second.
+    job.set_limits(ResourceLimits { max_memory: 1, ..ResourceLimits::unlimited() });
+    let _ = spawn();
+    let free_before = pmm::pmm_count_free_pages();
+    for _ in 0..SPAWN_FAILURES {
+        if spawn() != Err(RxStatus::ERR_NO_MEMORY) {
+            return Err("spawn over the memory limit not refused");
+        }
+    }
+    if pmm::pmm_count_free_pages() != free_before {
+        return Err("spawns over the memory limit leaked pages");
+    }
+
+    let account = jobs::account(job.id);
+    if account.processes != 0 || account.memory != 0 {
+        return Err("refused spawns left a charge on the job");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suite_listed() {
+        assert!(suite_listed("mm", "mm"));
+        assert!(suite_listed("sched,mm", "mm"));
+        assert!(!suite_listed("mmu", "mm"));
+        assert!(!suite_listed("", "mm"));
+    }
+
+    #[test]
+    fn test_page_pattern_differs_per_word() {
+        assert_ne!(page_pattern(0x1000, 0), page_pattern(0x1000, 511));
+        assert_ne!(page_pattern(0x1000, 0), page_pattern(0x2000, 0));
+    }
+}
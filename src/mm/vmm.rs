@@ -0,0 +1,568 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Virtual Memory Manager
+//!
+//! Most kernel memory is reached at a fixed offset from its physical
+//! address (`paddr_to_vaddr`). This module manages the one part of the
+//! kernel half that is not, the [`VMALLOC`] region, for mappings that need
+//! addresses of their own:
+//!
+//! - [`vmalloc`] maps pages that need not be physically contiguous
+//! - [`ioremap`] maps device memory with a [`CachePolicy`]
+//! - [`alloc_stack`] maps a kernel stack with an unmapped guard page below
+//!   it, so an overflow faults instead of running into its neighbour
+//!
+//! # Address Allocation
+//!
+//! [`VmapSpace`] hands out page ranges next-fit from a cursor that wraps
+//! at the end of the region. Every area is preceded by [`GUARD_PAGES`]
+//! unmapped pages, so no two areas touch.
+//!
+//! # Page Tables
+//!
+//! VMALLOC is exactly one PML4 slot. [`init`] installs its PDPT in the
+//! kernel page table before any process exists, and every address space
+//! copies the kernel's PML4 entries when it is created, so all of them see
+//! the same mappings. Lower tables are allocated on demand and never freed.
+//!
+//! # TLB
+//!
+//! Unmapping an area flushes the TLB of the current CPU and starts a
+//! [`tlb::shootdown`] of the others. Until every CPU has flushed, the area
+//! is retired: its pages are not freed and its addresses stay reserved, so
+//! a stale entry on another CPU can only reach memory nobody else uses.
+//! Retired areas are reaped at the next VMM call once their shootdown is
+//! done; with one CPU online that is at once.
+//!
+//! # Lock Order
+//!
+//! The VMM lock is taken before the PMM lock.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use crate::arch::amd64::mm::layout::{Region, VMALLOC};
+use crate::arch::amd64::mm::{PAddr, RxStatus};
+use crate::arch::amd64::registers::{self, cr, efer, msr};
+use crate::arch::amd64::tlb;
+use crate::object::CachePolicy;
+use crate::sync::SpinMutex;
+use super::pmm;
+
+/// Size of a page
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Unmapped pages below every area
+pub const GUARD_PAGES: u64 = 1;
+
+/// Page table entry bits
+const PTE_P: u64 = 1 << 0;
+const PTE_W: u64 = 1 << 1;
+const PTE_PWT: u64 = 1 << 3;
+const PTE_PCD: u64 = 1 << 4;
+const PTE_NX: u64 = 1 << 63;
+
+/// Physical address bits of an entry
+const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// What an area is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaKind {
+    /// Pages from [`vmalloc`], freed with the area
+    Vmalloc,
+    /// Device memory from [`ioremap`], not owned by the area
+    Ioremap,
+    /// A kernel stack from [`alloc_stack`], freed with the area
+    Stack,
+}
+
+/// A mapped range of the VMALLOC region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    /// First mapped byte
+    pub start: u64,
+    /// Mapped pages
+    pub pages: u64,
+    /// What it is used for
+    pub kind: AreaKind,
+}
+
+impl Area {
+    /// End of the mapped range (exclusive)
+    pub const fn end(&self) -> u64 {
+        self.start + self.pages * PAGE_SIZE
+    }
+
+    /// First byte of the guard pages below the area
+    pub const fn guard_start(&self) -> u64 {
+        self.start - GUARD_PAGES * PAGE_SIZE
+    }
+}
+
+/// Areas of a virtual address range, keyed by start address
+pub struct VmapSpace {
+    start: u64,
+    end: u64,
+    cursor: u64,
+    areas: BTreeMap<u64, Area>,
+}
+
+impl VmapSpace {
+    /// Create an empty space covering `region`
+    pub const fn new(region: Region) -> Self {
+        Self { start: region.start, end: region.end, cursor: region.start, areas: BTreeMap::new() }
+    }
+
+    /// Reserve `pages` pages after the guard pages
+    ///
+    /// # Returns
+    ///
+    /// The start of the area, or `None` if no gap is large enough
+    pub fn reserve(&mut self, pages: u64, kind: AreaKind) -> Option<u64> {
+        if pages == 0 {
+            return None;
+        }
+        let span = pages.checked_add(GUARD_PAGES)?.checked_mul(PAGE_SIZE)?;
+        let at = self
+            .find_gap(self.cursor, self.end, span)
+            .or_else(|| self.find_gap(self.start, self.cursor, span))?;
+
+        let start = at + GUARD_PAGES * PAGE_SIZE;
+        self.areas.insert(start, Area { start, pages, kind });
+        self.cursor = at + span;
+        Some(start)
+    }
+
+    /// Lowest address in `[from, limit)` with `span` free bytes after it
+    fn find_gap(&self, from: u64, limit: u64, span: u64) -> Option<u64> {
+        let mut at = from;
+        if let Some(prev) = self.areas.range(..=from).next_back().map(|(_, a)| a) {
+            at = at.max(prev.end());
+        }
+        for area in self.areas.range(at..).map(|(_, a)| a) {
+            if at.checked_add(span).is_some_and(|end| end <= area.guard_start()) {
+                break;
+            }
+            at = area.end();
+        }
+        at.checked_add(span).is_some_and(|end| end <= limit).then_some(at)
+    }
+
+    /// Remove the area starting at `start`
+    pub fn release(&mut self, start: u64) -> Option<Area> {
+        self.areas.remove(&start)
+    }
+
+    /// The area whose mapped range holds `addr`
+    pub fn find(&self, addr: u64) -> Option<&Area> {
+        self.areas.range(..=addr).next_back().map(|(_, a)| a).filter(|a| addr < a.end())
+    }
+
+    /// The area whose guard pages hold `addr`
+    pub fn guarded_by(&self, addr: u64) -> Option<&Area> {
+        let (_, next) = self.areas.range(addr.checked_add(1)?..).next()?;
+        (next.guard_start() <= addr).then_some(next)
+    }
+
+    /// Number of areas
+    pub fn len(&self) -> usize {
+        self.areas.len()
+    }
+
+    /// Check if there are no areas
+    pub fn is_empty(&self) -> bool {
+        self.areas.is_empty()
+    }
+
+    /// Total mapped pages
+    pub fn mapped_pages(&self) -> u64 {
+        self.areas.values().map(|a| a.pages).sum()
+    }
+}
+
+/// Page table bits selecting `policy` in the PAT programmed at boot
+///
+/// `None` for write-through, which the PAT has no entry for.
+pub fn cache_bits(policy: CachePolicy) -> Option<u64> {
+    match policy {
+        // PAT 0: write-back
+        CachePolicy::Default => Some(0),
+        // PAT 1: write-combining
+        CachePolicy::WriteCombining => Some(PTE_PWT),
+        // PAT 3: uncached
+        CachePolicy::Uncached => Some(PTE_PCD | PTE_PWT),
+        CachePolicy::WriteThrough => None,
+    }
+}
+
+/// VMM usage
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VmmStats {
+    /// Mapped areas
+    pub areas: usize,
+    /// Mapped pages, guard pages not included
+    pub mapped_pages: u64,
+    /// Unmapped areas waiting for a TLB shootdown
+    pub retired_areas: usize,
+    /// Page table pages in use
+    pub table_pages: u64,
+}
+
+/// An unmapped area waiting for the other CPUs to flush their TLBs
+struct Retired {
+    area: Area,
+    /// Pages to free once flushed
+    pages: Vec<PAddr>,
+    ticket: tlb::Ticket,
+}
+
+/// The VMALLOC region and its page tables
+struct Vmm {
+    space: VmapSpace,
+    /// Physical address of the region's PDPT, 0 before [`init`]
+    pdpt: PAddr,
+    table_pages: u64,
+    /// Areas still in `space` until their shootdown is done
+    retired: Vec<Retired>,
+}
+
+static VMM: SpinMutex<Vmm> =
+    SpinMutex::new(Vmm { space: VmapSpace::new(VMALLOC), pdpt: 0, table_pages: 0, retired: Vec::new() });
+
+/// Index of `vaddr` in a table at `level` (0 = page table)
+const fn table_index(vaddr: u64, level: u32) -> usize {
+    ((vaddr >> (12 + 9 * level)) & 0x1ff) as usize
+}
+
+/// Allocate a zeroed page table
+unsafe fn new_table() -> Result<PAddr, RxStatus> {
+    let paddr = pmm::pmm_alloc_kernel_page()?;
+    core::ptr::write_bytes(pmm::paddr_to_vaddr(paddr) as *mut u8, 0, PAGE_SIZE as usize);
+    Ok(paddr)
+}
+
+/// No-execute bit for data mappings, if NX is enabled
+fn nx_bit() -> u64 {
+    if unsafe { registers::read_msr(msr::IA32_EFER) } & efer::NXE != 0 {
+        PTE_NX
+    } else {
+        0
+    }
+}
+
+impl Vmm {
+    /// The page table entry for `vaddr`, creating missing tables if `create`
+    unsafe fn entry(&mut self, vaddr: u64, create: bool) -> Result<*mut u64, RxStatus> {
+        let mut table = pmm::paddr_to_vaddr(self.pdpt) as *mut u64;
+        for level in [2, 1] {
+            let entry = table.add(table_index(vaddr, level));
+            if *entry & PTE_P == 0 {
+                if !create {
+                    return Err(RxStatus::ERR_NOT_FOUND);
+                }
+                *entry = new_table()? | PTE_P | PTE_W;
+                self.table_pages += 1;
+            }
+            table = pmm::paddr_to_vaddr(*entry & ADDR_MASK) as *mut u64;
+        }
+        Ok(table.add(table_index(vaddr, 0)))
+    }
+
+    /// Map the page at `vaddr` to `paddr`
+    unsafe fn map(&mut self, vaddr: u64, paddr: PAddr, flags: u64) -> Result<(), RxStatus> {
+        *self.entry(vaddr, true)? = (paddr & ADDR_MASK) | flags;
+        Ok(())
+    }
+
+    /// Unmap the page at `vaddr`, returning the page it mapped
+    unsafe fn unmap(&mut self, vaddr: u64) -> Option<PAddr> {
+        let entry = self.entry(vaddr, false).ok()?;
+        let old = *entry;
+        if old & PTE_P == 0 {
+            return None;
+        }
+        *entry = 0;
+        core::arch::asm!("invlpg [{}]", in(reg) vaddr, options(nostack, preserves_flags));
+        Some(old & ADDR_MASK)
+    }
+
+    /// Unmap the first `pages` pages at `start`, freeing them if `free`
+    ///
+    /// Only for an area that was never handed out, so no other CPU can
+    /// have it in its TLB.
+    unsafe fn unmap_range(&mut self, start: u64, pages: u64, free: bool) {
+        for i in 0..pages {
+            if let Some(paddr) = self.unmap(start + i * PAGE_SIZE) {
+                if free {
+                    let _ = pmm::pmm_free_page(paddr);
+                }
+            }
+        }
+    }
+
+    /// Reserve an area and back it with newly allocated pages
+    fn map_new(&mut self, pages: u64, kind: AreaKind) -> Result<u64, RxStatus> {
+        if self.pdpt == 0 {
+            return Err(RxStatus::ERR_NOT_SUPPORTED);
+        }
+        self.reap();
+        let start = self.space.reserve(pages, kind).ok_or(RxStatus::ERR_NO_MEMORY)?;
+        let flags = PTE_P | PTE_W | nx_bit();
+        for i in 0..pages {
+            let vaddr = start + i * PAGE_SIZE;
+            let mapped = pmm::pmm_alloc_page(pmm::PMM_ALLOC_FLAG_ANY).and_then(|paddr| unsafe {
+                self.map(vaddr, paddr, flags).inspect_err(|_| {
+                    let _ = pmm::pmm_free_page(paddr);
+                })
+            });
+            if let Err(e) = mapped {
+                unsafe { self.unmap_range(start, i, true) };
+                self.space.release(start);
+                return Err(e);
+            }
+        }
+        Ok(start)
+    }
+
+    /// Check if the area starting at `start` is waiting to be reaped
+    fn is_retired(&self, start: u64) -> bool {
+        self.retired.iter().any(|r| r.area.start == start)
+    }
+
+    /// Unmap the area starting at `start`, if it is a `kind`, and retire it
+    fn unmap_area(&mut self, start: u64, kind: AreaKind) -> Result<(), RxStatus> {
+        let area = match self.space.find(start) {
+            Some(area) if area.start == start && area.kind == kind && !self.is_retired(start) => *area,
+            _ => return Err(RxStatus::ERR_INVALID_ARGS),
+        };
+        let mut pages = Vec::new();
+        for i in 0..area.pages {
+            let paddr = unsafe { self.unmap(area.start + i * PAGE_SIZE) };
+            pages.extend(paddr.filter(|_| kind != AreaKind::Ioremap));
+        }
+        self.retired.push(Retired { area, pages, ticket: tlb::shootdown() });
+        self.reap();
+        Ok(())
+    }
+
+    /// Free the pages and addresses of retired areas every CPU has flushed
+    fn reap(&mut self) {
+        let space = &mut self.space;
+        self.retired.retain(|r| {
+            if !r.ticket.is_done() {
+                return true;
+            }
+            for &paddr in &r.pages {
+                let _ = pmm::pmm_free_page(paddr);
+            }
+            space.release(r.area.start);
+            false
+        });
+    }
+}
+
+/// Pages needed for `size` bytes
+fn pages_for(size: usize) -> Result<u64, RxStatus> {
+    match (size as u64).div_ceil(PAGE_SIZE) {
+        0 => Err(RxStatus::ERR_INVALID_ARGS),
+        pages => Ok(pages),
+    }
+}
+
+/// Give the VMALLOC region its PDPT in the kernel page table
+///
+/// # Safety
+///
+/// Called once on the boot CPU, after the PMM and before the APs are
+/// started or any process is created.
+pub unsafe fn init() -> Result<(), RxStatus> {
+    let mut vmm = VMM.lock();
+    let pml4 = pmm::paddr_to_vaddr(registers::x86_get_cr3() & ADDR_MASK) as *mut u64;
+    let slot = pml4.add(table_index(VMALLOC.start, 3));
+    if vmm.pdpt != 0 || *slot & PTE_P != 0 {
+        return Err(RxStatus::ERR_ALREADY_EXISTS);
+    }
+    let pdpt = new_table()?;
+
+    // The firmware may have made its page tables read-only
+    let cr0 = registers::x86_get_cr0();
+    registers::x86_set_cr0(cr0 & !cr::CR0_WP);
+    *slot = pdpt | PTE_P | PTE_W;
+    registers::x86_set_cr0(cr0);
+
+    vmm.pdpt = pdpt;
+    vmm.table_pages = 1;
+    Ok(())
+}
+
+/// Check if [`init`] has run
+pub fn is_ready() -> bool {
+    VMM.lock().pdpt != 0
+}
+
+/// Allocate `size` bytes of zeroed, virtually contiguous memory
+///
+/// The pages come from any PMM arena and need not be physically
+/// contiguous. Free with [`vfree`].
+pub fn vmalloc(size: usize) -> Result<usize, RxStatus> {
+    let pages = pages_for(size)?;
+    let start = VMM.lock().map_new(pages, AreaKind::Vmalloc)?;
+    // The area is ours alone until it is returned
+    unsafe { core::ptr::write_bytes(start as *mut u8, 0, (pages * PAGE_SIZE) as usize) };
+    Ok(start as usize)
+}
+
+/// Free memory from [`vmalloc`]
+pub fn vfree(addr: usize) -> Result<(), RxStatus> {
+    VMM.lock().unmap_area(addr as u64, AreaKind::Vmalloc)
+}
+
+/// Map `size` bytes of device memory at `paddr`
+///
+/// `paddr` need not be page aligned; the returned address has the same
+/// page offset. Unmap with [`iounmap`].
+///
+/// # Errors
+///
+/// `ERR_NOT_SUPPORTED` for [`CachePolicy::WriteThrough`]
+pub fn ioremap(paddr: PAddr, size: usize, policy: CachePolicy) -> Result<usize, RxStatus> {
+    let cache = cache_bits(policy).ok_or(RxStatus::ERR_NOT_SUPPORTED)?;
+    let offset = paddr & (PAGE_SIZE - 1);
+    let base = paddr - offset;
+    let pages = pages_for(size.checked_add(offset as usize).ok_or(RxStatus::ERR_INVALID_ARGS)?)?;
+    base.checked_add(pages * PAGE_SIZE).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+
+    let mut vmm = VMM.lock();
+    if vmm.pdpt == 0 {
+        return Err(RxStatus::ERR_NOT_SUPPORTED);
+    }
+    vmm.reap();
+    let start = vmm.space.reserve(pages, AreaKind::Ioremap).ok_or(RxStatus::ERR_NO_MEMORY)?;
+    let flags = PTE_P | PTE_W | nx_bit() | cache;
+    for i in 0..pages {
+        if let Err(e) = unsafe { vmm.map(start + i * PAGE_SIZE, base + i * PAGE_SIZE, flags) } {
+            unsafe { vmm.unmap_range(start, i, false) };
+            vmm.space.release(start);
+            return Err(e);
+        }
+    }
+    Ok((start + offset) as usize)
+}
+
+/// Unmap device memory from [`ioremap`]
+///
+/// `addr` is the address [`ioremap`] returned.
+pub fn iounmap(addr: usize) -> Result<(), RxStatus> {
+    VMM.lock().unmap_area(addr as u64 & !(PAGE_SIZE - 1), AreaKind::Ioremap)
+}
+
+/// Allocate a kernel stack of `size` bytes with a guard page below it
+///
+/// # Returns
+///
+/// The top of the stack. Free with [`free_stack`].
+pub fn alloc_stack(size: usize) -> Result<u64, RxStatus> {
+    let pages = pages_for(size)?;
+    let start = VMM.lock().map_new(pages, AreaKind::Stack)?;
+    Ok(start + pages * PAGE_SIZE)
+}
+
+/// Free a stack from [`alloc_stack`], given its top
+pub fn free_stack(top: u64) -> Result<(), RxStatus> {
+    let mut vmm = VMM.lock();
+    let start = match vmm.space.find(top.wrapping_sub(1)) {
+        Some(area) if area.end() == top => area.start,
+        _ => return Err(RxStatus::ERR_INVALID_ARGS),
+    };
+    vmm.unmap_area(start, AreaKind::Stack)
+}
+
+/// Check if `addr` is in the guard page of a kernel stack
+///
+/// For fault handlers: does not wait for the VMM lock, and answers `false`
+/// if another CPU holds it.
+pub fn is_stack_guard(addr: u64) -> bool {
+    if !VMALLOC.contains(addr) {
+        return false;
+    }
+    VMM.try_lock()
+        .is_some_and(|vmm| vmm.space.guarded_by(addr).is_some_and(|a| a.kind == AreaKind::Stack))
+}
+
+/// Current VMM usage
+pub fn stats() -> VmmStats {
+    let vmm = VMM.lock();
+    let retired_pages: u64 = vmm.retired.iter().map(|r| r.area.pages).sum();
+    VmmStats {
+        areas: vmm.space.len() - vmm.retired.len(),
+        mapped_pages: vmm.space.mapped_pages() - retired_pages,
+        retired_areas: vmm.retired.len(),
+        table_pages: vmm.table_pages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u64 = 0x1000_0000;
+    const PAGE: u64 = PAGE_SIZE;
+
+    fn space(pages: u64) -> VmapSpace {
+        VmapSpace::new(Region { name: "test", start: BASE, end: BASE + pages * PAGE })
+    }
+
+    #[test]
+    fn test_reserve_leaves_guards() {
+        let mut space = space(16);
+        let a = space.reserve(2, AreaKind::Vmalloc).unwrap();
+        let b = space.reserve(3, AreaKind::Stack).unwrap();
+        assert_eq!(a, BASE + PAGE);
+        assert_eq!(b, BASE + 4 * PAGE);
+
+        assert_eq!(space.find(a + PAGE + 0xfff).map(|a| a.pages), Some(2));
+        assert_eq!(space.find(b - 1), None);
+        assert_eq!(space.guarded_by(b - 1).map(|a| a.kind), Some(AreaKind::Stack));
+        assert_eq!(space.guarded_by(b), None);
+        assert_eq!(space.mapped_pages(), 5);
+
+        // 16 pages: 3 + 4 used, 9 left for at most 8 more mapped pages
+        assert_eq!(space.reserve(9, AreaKind::Vmalloc), None);
+        assert!(space.reserve(8, AreaKind::Vmalloc).is_some());
+        assert_eq!(space.reserve(1, AreaKind::Vmalloc), None);
+        assert_eq!(space.reserve(0, AreaKind::Vmalloc), None);
+    }
+
+    #[test]
+    fn test_next_fit_wraps() {
+        let mut space = space(8);
+        let a = space.reserve(1, AreaKind::Vmalloc).unwrap();
+        let b = space.reserve(1, AreaKind::Vmalloc).unwrap();
+        let c = space.reserve(1, AreaKind::Vmalloc).unwrap();
+        assert_eq!(space.release(a).map(|a| a.start), Some(BASE + PAGE));
+
+        // The freed range is reused only once the end is reached
+        assert_eq!(space.reserve(1, AreaKind::Vmalloc), Some(c + 2 * PAGE));
+        assert_eq!(space.reserve(1, AreaKind::Vmalloc), Some(a));
+        assert_eq!(space.reserve(1, AreaKind::Vmalloc), None);
+
+        // A gap is only used if the area and its guard fit
+        space.release(b);
+        assert_eq!(space.reserve(2, AreaKind::Vmalloc), None);
+        assert_eq!(space.reserve(1, AreaKind::Vmalloc), Some(b));
+        assert_eq!(space.len(), 4);
+        assert_eq!(space.release(b + PAGE), None);
+    }
+
+    #[test]
+    fn test_cache_bits() {
+        assert_eq!(cache_bits(CachePolicy::Default), Some(0));
+        assert_eq!(cache_bits(CachePolicy::WriteCombining), Some(PTE_PWT));
+        assert_eq!(cache_bits(CachePolicy::Uncached), Some(PTE_PCD | PTE_PWT));
+        assert_eq!(cache_bits(CachePolicy::WriteThrough), None);
+        assert_eq!(table_index(VMALLOC.start, 3), 384);
+    }
+}
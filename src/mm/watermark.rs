@@ -0,0 +1,264 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Memory Usage Watermarks
+//!
+//! Tracks current and peak usage of the kernel heap ([`HEAP`]) and of
+//! physical pages ([`PMM`]), and warns on the debug console when usage
+//! crosses a threshold.
+//!
+//! # Thresholds
+//!
+//! | Option | Default | Applies to |
+//! |--------|---------|------------|
+//! | `mm.heap_warn=<percent>` | 90 | Heap bytes allocated |
+//! | `mm.pmm_warn=<percent>` | 90 | Physical pages not free |
+//!
+//! `0` disables the warning. A watermark warns once when usage reaches
+//! its threshold and re-arms only after usage has dropped
+//! [`REARM_PERCENT`] below it, so a workload hovering at the threshold
+//! does not flood the console.
+//!
+//! Updates run inside the global allocator, so nothing here allocates.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::klog::{Level, LogWriter};
+
+/// Command line option: heap warning threshold in percent
+pub const HEAP_WARN_OPTION: &str = "mm.heap_warn";
+
+/// Command line option: physical memory warning threshold in percent
+pub const PMM_WARN_OPTION: &str = "mm.pmm_warn";
+
+/// Threshold used when no option is given
+pub const DEFAULT_WARN_PERCENT: usize = 90;
+
+/// How far below the threshold usage must fall before warning again
+pub const REARM_PERCENT: usize = 10;
+
+/// Kernel heap bytes handed out by the global allocator
+pub static HEAP: Watermark = Watermark::new("heap");
+
+/// Physical memory in use (allocated or reserved), in bytes
+pub static PMM: Watermark = Watermark::new("pmm");
+
+/// Current and peak usage of one memory pool
+pub struct Watermark {
+    /// Name used in warnings
+    name: &'static str,
+
+    /// Bytes in use now
+    used: AtomicUsize,
+
+    /// Highest `used` seen
+    peak: AtomicUsize,
+
+    /// Size of the pool in bytes (0 until known)
+    capacity: AtomicUsize,
+
+    /// Warning threshold in percent of `capacity` (0 = off)
+    threshold: AtomicUsize,
+
+    /// Set once the warning has been printed, until usage drops again
+    warned: AtomicBool,
+}
+
+impl Watermark {
+    /// Create an empty watermark with the default threshold
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            used: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(0),
+            threshold: AtomicUsize::new(DEFAULT_WARN_PERCENT),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Set the pool size
+    pub fn set_capacity(&self, bytes: usize) {
+        self.capacity.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Set the warning threshold (percent, 0 = off)
+    pub fn set_threshold(&self, percent: usize) {
+        self.threshold.store(core::cmp::min(percent, 100), Ordering::Relaxed);
+    }
+
+    /// Start counting from `bytes`, e.g. pages taken before tracking began
+    ///
+    /// The peak restarts at the same value.
+    pub fn reset(&self, bytes: usize) {
+        self.used.store(bytes, Ordering::Relaxed);
+        self.peak.store(bytes, Ordering::Relaxed);
+        self.warned.store(false, Ordering::Relaxed);
+    }
+
+    /// Account for `bytes` more in use
+    #[inline]
+    pub fn add(&self, bytes: usize) {
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak.fetch_max(used, Ordering::Relaxed);
+        if self.crossed(used) {
+            let _ = self.write_warning(used, &mut LogWriter::new(Level::Warn, module_path!()));
+        }
+    }
+
+    /// Account for `bytes` released
+    #[inline]
+    pub fn sub(&self, bytes: usize) {
+        let used = self.used.fetch_sub(bytes, Ordering::Relaxed).saturating_sub(bytes);
+        self.crossed(used);
+    }
+
+    /// Bytes in use now
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Highest usage seen
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Pool size in bytes (0 if not known yet)
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Warning threshold in percent (0 = off)
+    pub fn threshold(&self) -> usize {
+        self.threshold.load(Ordering::Relaxed)
+    }
+
+    /// Current usage in whole percent of the capacity (0 if not known)
+    pub fn percent_used(&self) -> usize {
+        self.percent(self.used())
+    }
+
+    /// Usage in whole percent of the capacity
+    fn percent(&self, used: usize) -> usize {
+        match self.capacity() {
+            0 => 0,
+            capacity => (used as u128 * 100 / capacity as u128) as usize,
+        }
+    }
+
+    /// Update the warning state for usage `used`
+    ///
+    /// # Returns
+    ///
+    /// True if usage just reached the threshold and a warning is due
+    fn crossed(&self, used: usize) -> bool {
+        let threshold = self.threshold();
+        if threshold == 0 || self.capacity() == 0 {
+            return false;
+        }
+        let percent = self.percent(used);
+        if percent >= threshold {
+            return !self.warned.swap(true, Ordering::Relaxed);
+        }
+        if percent + REARM_PERCENT < threshold {
+            self.warned.store(false, Ordering::Relaxed);
+        }
+        false
+    }
+
+    /// Write the threshold warning for usage `used`
+    fn write_warning(&self, used: usize, out: &mut impl Write) -> core::fmt::Result {
+        writeln!(
+            out,
+            "[MM] WARNING: {} usage {} KiB of {} KiB ({}%), threshold {}%",
+            self.name,
+            used / 1024,
+            self.capacity() / 1024,
+            self.percent(used),
+            self.threshold()
+        )
+    }
+
+    /// Write a one-line summary: current, peak and capacity
+    pub fn write_summary(&self, out: &mut impl Write) -> core::fmt::Result {
+        writeln!(
+            out,
+            "{}: used {} KiB, peak {} KiB, total {} KiB",
+            self.name,
+            self.used() / 1024,
+            self.peak() / 1024,
+            self.capacity() / 1024
+        )
+    }
+}
+
+/// Apply the threshold options from the command line
+pub fn init_thresholds() {
+    for (option, watermark) in [(HEAP_WARN_OPTION, &HEAP), (PMM_WARN_OPTION, &PMM)] {
+        let mut buf = [0u8; 8];
+        if let Some(percent) = crate::cmdline::get(option, &mut buf).and_then(|s| s.parse().ok()) {
+            watermark.set_threshold(percent);
+        }
+    }
+}
+
+crate::ktest! {
+    fn heap_watermark_tracks_global_allocator() {
+        let before = HEAP.used();
+        let block = alloc::vec![0u8; 4096];
+        assert!(HEAP.used() >= before + block.len());
+        assert!(HEAP.peak() >= HEAP.used());
+        drop(block);
+        assert_eq!(HEAP.used(), before);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_tracks_high_water() {
+        let w = Watermark::new("test");
+        w.set_threshold(0);
+        w.add(100);
+        w.add(50);
+        w.sub(120);
+        assert_eq!(w.used(), 30);
+        assert_eq!(w.peak(), 150);
+
+        w.reset(10);
+        assert_eq!((w.used(), w.peak()), (10, 10));
+    }
+
+    #[test]
+    fn test_warning_rearms_below_threshold() {
+        let w = Watermark::new("test");
+        w.set_capacity(1000);
+        w.set_threshold(50);
+
+        assert!(!w.crossed(499));
+        assert!(w.crossed(500));
+        assert!(!w.crossed(600));
+
+        // Still within the re-arm margin
+        assert!(!w.crossed(450));
+        assert!(!w.crossed(500));
+
+        assert!(!w.crossed(350));
+        assert!(w.crossed(500));
+    }
+
+    #[test]
+    fn test_warning_text() {
+        let w = Watermark::new("heap");
+        w.set_capacity(16 * 1024 * 1024);
+
+        let mut out = alloc::string::String::new();
+        w.write_warning(15 * 1024 * 1024, &mut out).unwrap();
+        assert_eq!(out, "[MM] WARNING: heap usage 15360 KiB of 16384 KiB (93%), threshold 90%\n");
+    }
+}
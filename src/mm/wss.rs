@@ -0,0 +1,201 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Working-Set Estimation
+//!
+//! A periodic scan harvests the accessed bits of every process's
+//! mappings ([`AddressSpace::harvest_range`]) and records, per process,
+//! how many of its mapped pages were touched since the previous scan
+//! ([`WorkingSet`], read through `/proc/self/wss`).
+//!
+//! The same scan ages VMO pages for [`crate::mm::reclaim`]: a page no
+//! mapping touched has its `idle` count raised, an access resets it, and
+//! a set dirty bit marks the page dirty. Only accessed bits are cleared;
+//! dirty bits stay set until the page is unmapped.
+//!
+//! # Scanner
+//!
+//! There are no kernel threads, so the periodic scanner ([`tick`]) runs
+//! from [`crate::mm::periodic`]: in AP idle loops, while waiting for a
+//! blocked thread and on syscall return, so the boot CPU scans too. It
+//! runs at most once per `mm.wss_interval=<ms>`
+//! ([`DEFAULT_INTERVAL_MS`] by default, `0` turns it off). Reclaim scans
+//! before each of its passes as well, so scans come more often under
+//! memory pressure; [`WorkingSet::window_ns`] is the time the last one
+//! covered.
+//!
+//! A running process's pages may be reported idle: accessed bits are
+//! only flushed from this CPU's TLB, and another CPU can keep using a
+//! cached entry without setting the bit again until it switches address
+//! spaces.
+//!
+//! [`AddressSpace::harvest_range`]: crate::process::AddressSpace::harvest_range
+
+use alloc::collections::BTreeSet;
+use alloc::sync::Arc;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::mm::page_tables::{mmu_flags, Harvest};
+use crate::object::vmo::Vmo;
+use crate::process::table::{ProcessTable, PROCESS_TABLE};
+use crate::process::AddressSpace;
+
+/// Command line option: time between periodic scans in milliseconds
+pub const INTERVAL_OPTION: &str = "mm.wss_interval";
+
+/// Scan interval used when no option is given
+pub const DEFAULT_INTERVAL_MS: u64 = 1000;
+
+/// Time between periodic scans in milliseconds (0 = off)
+static INTERVAL_MS: AtomicU64 = AtomicU64::new(DEFAULT_INTERVAL_MS);
+
+/// When the last periodic scan started
+static LAST_SCAN_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Working-set statistics of one process, as of its last scan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkingSet {
+    /// Pages mapped
+    pub resident: u64,
+    /// Mapped pages accessed since the scan before
+    pub accessed: u64,
+    /// Mapped pages written since they were mapped
+    pub dirty: u64,
+    /// `accessed`, smoothed over scans (each weighs a quarter)
+    pub estimate: u64,
+    /// Scans so far
+    pub scans: u64,
+    /// Time between the last two scans (0 after the first)
+    pub window_ns: u64,
+    /// When the last scan ran
+    last_scan_ns: u64,
+}
+
+impl WorkingSet {
+    /// No scans yet
+    pub const fn new() -> Self {
+        Self { resident: 0, accessed: 0, dirty: 0, estimate: 0, scans: 0, window_ns: 0, last_scan_ns: 0 }
+    }
+
+    /// Record a scan of all the process's mappings at `now_ns`
+    pub fn record(&mut self, harvest: Harvest, now_ns: u64) {
+        if self.scans == 0 {
+            self.estimate = harvest.accessed;
+        } else {
+            self.window_ns = now_ns.saturating_sub(self.last_scan_ns);
+            self.estimate = (self.estimate * 3 + harvest.accessed) / 4;
+        }
+        self.resident = harvest.mapped;
+        self.accessed = harvest.accessed;
+        self.dirty = harvest.dirty;
+        self.last_scan_ns = now_ns;
+        self.scans += 1;
+    }
+
+    /// Write the statistics, one `name: value` per line, sizes in KiB
+    pub fn write_report(&self, out: &mut impl Write) -> core::fmt::Result {
+        writeln!(out, "resident: {} KiB", self.resident * 4)?;
+        writeln!(out, "accessed: {} KiB", self.accessed * 4)?;
+        writeln!(out, "dirty: {} KiB", self.dirty * 4)?;
+        writeln!(out, "estimate: {} KiB", self.estimate * 4)?;
+        writeln!(out, "scans: {}", self.scans)?;
+        writeln!(out, "window: {} ms", self.window_ns / 1_000_000)
+    }
+}
+
+/// Apply the `mm.wss_interval` option from the command line
+pub fn init_options() {
+    let mut buf = [0u8; 12];
+    if let Some(ms) = crate::cmdline::get(INTERVAL_OPTION, &mut buf).and_then(|s| s.parse().ok()) {
+        INTERVAL_MS.store(ms, Ordering::Relaxed);
+    }
+}
+
+/// Claim a periodic job last started at `last` if `interval_ns` has passed
+///
+/// Only one of several CPUs calling this at the same time gets true.
+pub(crate) fn due(last: &AtomicU64, interval_ns: u64) -> bool {
+    let now = crate::time::Instant::now().as_nanos();
+    let prev = last.load(Ordering::Relaxed);
+    now.saturating_sub(prev) >= interval_ns
+        && last.compare_exchange(prev, now, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+}
+
+/// Run the periodic scan if it is due
+///
+/// Called through [`crate::mm::periodic`]. Skipped if the process table
+/// is locked.
+pub fn tick() {
+    let interval_ms = INTERVAL_MS.load(Ordering::Relaxed);
+    if interval_ms == 0 || !due(&LAST_SCAN_NS, interval_ms.saturating_mul(1_000_000)) {
+        return;
+    }
+    if let Some(mut table) = PROCESS_TABLE.try_lock() {
+        scan_locked(&mut table);
+    }
+}
+
+/// Scan every process's mappings: update its [`WorkingSet`] and age the
+/// pages of the VMOs it maps
+pub fn scan_locked(table: &mut ProcessTable) {
+    const ACCESSED: u64 = mmu_flags::X86_MMU_PG_A;
+    const DIRTY: u64 = mmu_flags::X86_MMU_PG_D;
+
+    let now = crate::time::Instant::now().as_nanos();
+
+    // Each VMO ages once per scan, however often it is mapped
+    let mut aged: BTreeSet<*const Vmo> = BTreeSet::new();
+    for process in table.iter_mut() {
+        // Threads use their leader's mappings
+        if process.is_thread() {
+            continue;
+        }
+        let Some(vmar) = crate::process::vmar::of(process.page_table) else {
+            continue;
+        };
+        let aspace = unsafe { AddressSpace::from_page_table(process.page_table) };
+        let mut total = Harvest::default();
+        for region in vmar.lock().regions() {
+            let mut pages = region.vmo.pages.lock();
+            if aged.insert(Arc::as_ptr(&region.vmo)) {
+                for entry in pages.values_mut() {
+                    entry.idle = entry.idle.saturating_add(1);
+                }
+            }
+            let harvest = aspace.harvest_range(region.base, region.size, ACCESSED, |vaddr, old| {
+                let key = (vaddr as u64 - region.base) as usize;
+                if let Some(entry) = pages.get_mut(&key) {
+                    if old & ACCESSED != 0 {
+                        entry.idle = 0;
+                    }
+                    if old & DIRTY != 0 {
+                        entry.dirty = true;
+                    }
+                }
+            });
+            total.mapped += harvest.mapped;
+            total.accessed += harvest.accessed;
+            total.dirty += harvest.dirty;
+        }
+        process.wss.record(total, now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_smooths_accessed() {
+        let mut wss = WorkingSet::new();
+        wss.record(Harvest { mapped: 100, accessed: 40, dirty: 10 }, 1_000);
+        assert_eq!((wss.estimate, wss.window_ns), (40, 0));
+
+        wss.record(Harvest { mapped: 100, accessed: 0, dirty: 10 }, 5_000);
+        assert_eq!((wss.accessed, wss.estimate, wss.window_ns), (0, 30, 4_000));
+        assert_eq!(wss.scans, 2);
+    }
+}
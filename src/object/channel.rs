@@ -17,18 +17,24 @@
 //! - **Handle passing**: Handles can be transferred with rights reduction
 //! - **Peer closure**: One end closed → PEER_CLOSED signal to other
 //!
+//! Each endpoint owns an inbox. Writing to an endpoint queues the message
+//! in its peer's inbox; reading pops from the endpoint's own inbox. A
+//! message is only dequeued when it fits the reader's buffers, so a
+//! reader with a short buffer can retry with a bigger one.
+//!
 //! # Usage
 //!
 //! ```rust
 //! let (channel_a, channel_b) = Channel::create()?;
 //! channel_a.write(&data, &handles)?;
-//! let (msg, handles) = channel_b.read(&mut buf)?;
+//! let result = channel_b.read(&mut buf, &mut handles)?;
 //! ```
 
-use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU64, Ordering};
 use crate::sync::SpinMutex;
-use crate::object::handle::{KernelObjectBase, ObjectType, Handle};
-use crate::object::event::Event;
+use crate::object::handle::{KernelObjectBase, ObjectType};
+use crate::object::kernel_object::ObjectHandle;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use alloc::collections::VecDeque;
 
@@ -63,12 +69,12 @@ pub struct Message {
     pub data: Vec<u8>,
 
     /// Handles being transferred
-    pub handles: Vec<Handle>,
+    pub handles: Vec<ObjectHandle>,
 }
 
 impl Message {
     /// Create a new message
-    pub fn new(data: Vec<u8>, handles: Vec<Handle>) -> Self {
+    pub fn new(data: Vec<u8>, handles: Vec<ObjectHandle>) -> Self {
         Self { data, handles }
     }
 
@@ -122,6 +128,28 @@ impl ChannelState {
     }
 }
 
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// This endpoint was closed
+pub const ERR_CLOSED: &str = "channel closed";
+
+/// The other endpoint was closed (and, for reads, nothing is left)
+pub const ERR_PEER_CLOSED: &str = "peer closed";
+
+/// Nothing to read yet
+pub const ERR_SHOULD_WAIT: &str = "no messages";
+
+/// The peer's inbox is full
+pub const ERR_FULL: &str = "channel full";
+
+/// The message exceeds [`MAX_MSG_SIZE`] or [`MAX_MSG_HANDLES`]
+pub const ERR_TOO_LARGE: &str = "message too large";
+
+/// The next message does not fit the read buffers (nothing was dequeued)
+pub const ERR_BUFFER_TOO_SMALL: &str = "buffer too small";
+
 /// ============================================================================
 /// Channel
 /// ============================================================================
@@ -136,9 +164,34 @@ pub struct ReadResult {
     pub handles_read: usize,
 }
 
+/// Messages waiting to be read by one endpoint
+struct Inbox {
+    /// Queued messages
+    queue: VecDeque<Message>,
+
+    /// Bytes queued
+    bytes: usize,
+
+    /// The owning endpoint was closed
+    closed: bool,
+}
+
+impl Inbox {
+    const fn new() -> Self {
+        Self { queue: VecDeque::new(), bytes: 0, closed: false }
+    }
+}
+
+/// State shared by both endpoints
+struct Shared {
+    /// Inbox of endpoint 0 and endpoint 1
+    inboxes: [SpinMutex<Inbox>; 2],
+}
+
 /// Channel endpoint
 ///
-/// Represents one endpoint of a bidirectional channel.
+/// Represents one endpoint of a bidirectional channel. Dropping an
+/// endpoint closes it.
 pub struct Channel {
     /// Kernel object base
     pub base: KernelObjectBase,
@@ -149,44 +202,26 @@ pub struct Channel {
     /// Peer channel ID
     pub peer: SpinMutex<Option<ChannelId>>,
 
-    /// Message queue
-    pub queue: SpinMutex<VecDeque<Message>>,
-
-    /// Maximum queue depth (in bytes)
+    /// Maximum queue depth of the peer's inbox (in bytes)
     pub max_queue_bytes: usize,
 
-    /// Current queue size (in bytes)
-    pub queue_size: AtomicUsize,
-
-    /// Read event (signaled when messages available)
-    pub read_event: SpinMutex<Event>,
+    /// Inboxes of both endpoints
+    shared: Arc<Shared>,
 
-    /// Write event (signaled when space available)
-    pub write_event: SpinMutex<Event>,
-
-    /// Channel state
-    pub state: SpinMutex<ChannelState>,
+    /// Index of this endpoint's inbox
+    side: usize,
 }
 
 impl Channel {
-    /// Create a new channel
-    fn new(id: ChannelId, max_queue_bytes: usize) -> Self {
+    /// Create a new channel endpoint
+    fn new(id: ChannelId, max_queue_bytes: usize, shared: Arc<Shared>, side: usize) -> Self {
         Self {
             base: KernelObjectBase::new(ObjectType::Channel),
             id,
             peer: SpinMutex::new(None),
-            queue: SpinMutex::new(VecDeque::new()),
             max_queue_bytes,
-            queue_size: AtomicUsize::new(0),
-            read_event: SpinMutex::new(Event::new(
-                false,
-                crate::object::event::EventFlags::empty,
-            )),
-            write_event: SpinMutex::new(Event::new(
-                true, // Initially writable (empty queue)
-                crate::object::event::EventFlags::empty,
-            )),
-            state: SpinMutex::new(ChannelState::Active),
+            shared,
+            side,
         }
     }
 
@@ -201,8 +236,11 @@ impl Channel {
 
         let max_queue_bytes = 256 * 1024; // 256KB default
 
-        let channel_a = Self::new(id_a, max_queue_bytes);
-        let channel_b = Self::new(id_b, max_queue_bytes);
+        let shared = Arc::new(Shared {
+            inboxes: [SpinMutex::new(Inbox::new()), SpinMutex::new(Inbox::new())],
+        });
+        let channel_a = Self::new(id_a, max_queue_bytes, shared.clone(), 0);
+        let channel_b = Self::new(id_b, max_queue_bytes, shared, 1);
 
         // Link peers
         *channel_a.peer.lock() = Some(id_b);
@@ -221,57 +259,124 @@ impl Channel {
         *self.peer.lock()
     }
 
+    /// This endpoint's inbox
+    fn inbox(&self) -> &SpinMutex<Inbox> {
+        &self.shared.inboxes[self.side]
+    }
+
+    /// The peer's inbox
+    fn peer_inbox(&self) -> &SpinMutex<Inbox> {
+        &self.shared.inboxes[1 - self.side]
+    }
+
     /// Get channel state
     pub fn state(&self) -> ChannelState {
-        *self.state.lock()
+        if self.inbox().lock().closed {
+            ChannelState::Closed
+        } else if self.peer_inbox().lock().closed {
+            ChannelState::PeerClosed
+        } else {
+            ChannelState::Active
+        }
+    }
+
+    /// Whether a message is waiting to be read
+    pub fn is_readable(&self) -> bool {
+        !self.inbox().lock().queue.is_empty()
     }
 
     /// Write data and handles to the channel
     ///
+    /// The message is queued for the peer endpoint.
+    ///
     /// # Arguments
     ///
     /// * `data` - Data bytes to write
     /// * `handles` - Handles to transfer
-    pub fn write(&self, data: &[u8], handles: &[Handle]) -> Result<usize, &'static str> {
-        let state = *self.state.lock();
-        if state != ChannelState::Active {
-            return Err("channel not active");
+    pub fn write(&self, data: &[u8], handles: &[ObjectHandle]) -> Result<usize, &'static str> {
+        if self.inbox().lock().closed {
+            return Err(ERR_CLOSED);
         }
 
         // Check message size limits
-        if data.len() > MAX_MSG_SIZE {
-            return Err("message too large");
+        if data.len() > MAX_MSG_SIZE || handles.len() > MAX_MSG_HANDLES {
+            return Err(ERR_TOO_LARGE);
         }
 
-        if handles.len() > MAX_MSG_HANDLES {
-            return Err("too many handles");
+        let mut peer = self.peer_inbox().lock();
+        if peer.closed {
+            return Err(ERR_PEER_CLOSED);
         }
 
         // Check queue space
-        let msg_size = data.len();
-        let current_size = self.queue_size.load(Ordering::Acquire);
-
-        if current_size + msg_size > self.max_queue_bytes {
-            return Err("channel full");
+        if peer.bytes + data.len() > self.max_queue_bytes {
+            return Err(ERR_FULL);
         }
 
-        // Copy data and handles
-        let msg_data = Vec::from(data);
-        let msg_handles = handles.to_vec();
+        peer.queue.push_back(Message::new(Vec::from(data), handles.to_vec()));
+        peer.bytes += data.len();
+
+        Ok(data.len())
+    }
 
-        // Add to queue
-        {
-            let mut queue = self.queue.lock();
-            queue.push_back(Message::new(msg_data, msg_handles));
+    /// Size of the next message as (bytes, handles), if there is one
+    pub fn peek(&self) -> Option<(usize, usize)> {
+        self.inbox().lock().queue.front().map(|m| (m.data_size(), m.handle_count()))
+    }
+
+    /// Dequeue the next message if it fits
+    ///
+    /// # Arguments
+    ///
+    /// * `max_bytes` - Room for message bytes
+    /// * `max_handles` - Room for handles
+    ///
+    /// # Returns
+    ///
+    /// The message, or [`ERR_BUFFER_TOO_SMALL`] (leaving it queued),
+    /// [`ERR_SHOULD_WAIT`] or [`ERR_PEER_CLOSED`] when the inbox is empty
+    pub fn take(&self, max_bytes: usize, max_handles: usize) -> Result<Message, &'static str> {
+        let mut inbox = self.inbox().lock();
+        if inbox.closed {
+            return Err(ERR_CLOSED);
         }
 
-        // Update queue size
-        self.queue_size.fetch_add(msg_size, Ordering::Release);
+        let fits = match inbox.queue.front() {
+            Some(msg) => msg.data_size() <= max_bytes && msg.handle_count() <= max_handles,
+            None => {
+                drop(inbox);
+                if self.peer_inbox().lock().closed {
+                    return Err(ERR_PEER_CLOSED);
+                }
+                return Err(ERR_SHOULD_WAIT);
+            }
+        };
+        if !fits {
+            return Err(ERR_BUFFER_TOO_SMALL);
+        }
 
-        // Signal read event
-        self.read_event.lock().signal();
+        let msg = inbox.queue.pop_front().ok_or(ERR_SHOULD_WAIT)?;
+        inbox.bytes -= msg.data_size();
+        Ok(msg)
+    }
 
-        Ok(data.len())
+    /// Return a message from [`take`](Self::take) to the front of the inbox
+    ///
+    /// For a reader that could not deliver it. The message is dropped if
+    /// the endpoint was closed meanwhile.
+    pub fn put_back(&self, msg: Message) {
+        let rejected = {
+            let mut inbox = self.inbox().lock();
+            if inbox.closed {
+                Some(msg)
+            } else {
+                inbox.bytes += msg.data_size();
+                inbox.queue.push_front(msg);
+                None
+            }
+        };
+        // Its handles may close other channels
+        drop(rejected);
     }
 
     /// Read data and handles from the channel
@@ -279,7 +384,7 @@ impl Channel {
     /// # Arguments
     ///
     /// * `buf` - Buffer to read data into
-    /// * `handle_buf` - Buffer to read handles into
+    /// * `handle_buf` - Receives the transferred handles
     ///
     /// # Returns
     ///
@@ -287,68 +392,50 @@ impl Channel {
     pub fn read(
         &self,
         buf: &mut [u8],
-        handle_buf: &mut [Handle],
+        handle_buf: &mut Vec<ObjectHandle>,
     ) -> Result<ReadResult, &'static str> {
-        // Try to get a message from queue
-        let (data, handles) = {
-            let mut queue = self.queue.lock();
-            match queue.pop_front() {
-                Some(msg) => (msg.data, msg.handles),
-                None => {
-                    // Check if peer closed
-                    if *self.state.lock() == ChannelState::PeerClosed {
-                        // Return peer closed status
-                        return Err("peer closed");
-                    }
-                    return Err("no messages");
-                }
-            }
-        };
-
-        // Update queue size
-        let msg_size = data.len();
-        self.queue_size.fetch_sub(msg_size, Ordering::Release);
-
-        // Copy data to buffer
-        let bytes_to_copy = core::cmp::min(buf.len(), data.len());
-        buf[..bytes_to_copy].copy_from_slice(&data[..bytes_to_copy]);
-
-        // Copy handles to buffer
-        let handles_to_copy = core::cmp::min(handle_buf.len(), handles.len());
-        for (i, handle) in handles.iter().take(handles_to_copy).enumerate() {
-            handle_buf[i] = handle.clone();
-        }
+        let msg = self.take(buf.len(), MAX_MSG_HANDLES)?;
 
-        // Signal write event (space available)
-        self.write_event.lock().signal();
+        buf[..msg.data_size()].copy_from_slice(&msg.data);
+        let result = ReadResult {
+            bytes_read: msg.data_size(),
+            handles_read: msg.handle_count(),
+        };
+        handle_buf.extend(msg.handles);
 
-        Ok(ReadResult {
-            bytes_read: bytes_to_copy,
-            handles_read: handles_to_copy,
-        })
+        Ok(result)
     }
 
     /// Get the number of messages in the queue
     pub fn queue_len(&self) -> usize {
-        self.queue.lock().len()
+        self.inbox().lock().queue.len()
     }
 
     /// Get the current queue size in bytes
     pub fn queue_size(&self) -> usize {
-        self.queue_size.load(Ordering::Acquire)
+        self.inbox().lock().bytes
+    }
+
+    /// Mark this endpoint closed and discard its queued messages
+    ///
+    /// Handles in discarded messages are dropped outside the inbox lock,
+    /// since closing them may close other channels.
+    fn shutdown(&self) {
+        let discarded = {
+            let mut inbox = self.inbox().lock();
+            inbox.closed = true;
+            inbox.bytes = 0;
+            core::mem::take(&mut inbox.queue)
+        };
+        drop(discarded);
     }
 
     /// Close the channel endpoint
     ///
-    /// Returns true if this was the last close.
+    /// The peer sees [`ChannelState::PeerClosed`] once it has drained its
+    /// inbox. Returns true if this was the last close.
     pub fn close(&self) -> bool {
-        *self.state.lock() = ChannelState::Closed;
-
-        // Signal peer (if exists) that we closed
-        // TODO: Implement peer notification
-
-        // Signal read event (to wake readers)
-        self.read_event.lock().signal();
+        self.shutdown();
 
         // Decrement ref count
         self.base.ref_dec()
@@ -377,6 +464,12 @@ impl Channel {
     }
 }
 
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -384,6 +477,9 @@ impl Channel {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
+    use crate::object::kernel_object::KernelObject;
+    use crate::object::Rights;
 
     #[test]
     fn test_channel_create() {
@@ -415,25 +511,84 @@ mod tests {
         let data = vec![1, 2, 3, 4];
         ch_a.write(&data, &[]).unwrap();
 
+        assert_eq!(ch_a.queue_len(), 0);
         assert_eq!(ch_b.queue_len(), 1);
 
         let mut buf = [0u8; 10];
-        let mut handle_buf = [];
+        let mut handle_buf = Vec::new();
 
         let result = ch_b.read(&mut buf, &mut handle_buf).unwrap();
 
         assert_eq!(result.bytes_read, 4);
         assert_eq!(result.handles_read, 0);
         assert_eq!(&buf[..4], &data[..]);
+        assert_eq!(ch_b.read(&mut buf, &mut handle_buf).unwrap_err(), ERR_SHOULD_WAIT);
     }
 
     #[test]
     fn test_channel_queue_full() {
-        // Create a small channel for testing
-        let (ch_a, _) = Channel::create().unwrap();
+        let (ch_a, ch_b) = Channel::create().unwrap();
+        let chunk = vec![0u8; MAX_MSG_SIZE];
+
+        for _ in 0..ch_a.max_queue_bytes / MAX_MSG_SIZE {
+            ch_a.write(&chunk, &[]).unwrap();
+        }
+        assert_eq!(ch_b.queue_size(), ch_a.max_queue_bytes);
+        assert_eq!(ch_a.write(&[1], &[]), Err(ERR_FULL));
+
+        ch_b.take(MAX_MSG_SIZE, 0).unwrap();
+        assert!(ch_a.write(&[1], &[]).is_ok());
+    }
+
+    #[test]
+    fn test_short_buffer_keeps_message() {
+        let (ch_a, ch_b) = Channel::create().unwrap();
+        ch_a.write(&[1, 2, 3, 4], &[]).unwrap();
+
+        assert_eq!(ch_b.take(2, 0).err(), Some(ERR_BUFFER_TOO_SMALL));
+        assert_eq!(ch_b.peek(), Some((4, 0)));
+        assert_eq!(ch_b.take(4, 0).unwrap().data, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_put_back_keeps_order() {
+        let (ch_a, ch_b) = Channel::create().unwrap();
+        ch_a.write(&[1], &[]).unwrap();
+        ch_a.write(&[2, 2], &[]).unwrap();
+
+        let first = ch_b.take(4, 0).unwrap();
+        ch_b.put_back(first);
+        assert_eq!(ch_b.queue_size(), 3);
+        assert_eq!(ch_b.take(4, 0).unwrap().data, vec![1]);
+        assert_eq!(ch_b.take(4, 0).unwrap().data, vec![2, 2]);
+    }
+
+    #[test]
+    fn test_peer_closed() {
+        let (ch_a, ch_b) = Channel::create().unwrap();
+        ch_a.write(&[7], &[]).unwrap();
+        drop(ch_a);
+
+        assert_eq!(ch_b.state(), ChannelState::PeerClosed);
+        assert_eq!(ch_b.write(&[1], &[]), Err(ERR_PEER_CLOSED));
+
+        // Queued messages are still delivered
+        assert_eq!(ch_b.take(1, 0).unwrap().data, vec![7]);
+        assert_eq!(ch_b.take(1, 0).err(), Some(ERR_PEER_CLOSED));
+    }
+
+    #[test]
+    fn test_transfer_handle() {
+        let (ch_a, ch_b) = Channel::create().unwrap();
+        let (ch_c, ch_d) = Channel::create().unwrap();
+        let c = ObjectHandle::new(KernelObject::Channel(Arc::new(ch_c)), Rights::READ | Rights::WRITE);
+
+        ch_a.write(&[], &[c]).unwrap();
+        let msg = ch_b.take(0, 1).unwrap();
+        assert_eq!(msg.handle_count(), 1);
 
-        // Fill the queue (this would require many large messages)
-        // For now, just test that size tracking works
-        assert_eq!(ch_a.queue_size(), 0);
+        let received = msg.handles[0].object.as_channel().unwrap();
+        received.write(&[9], &[]).unwrap();
+        assert_eq!(ch_d.peek(), Some((1, 0)));
     }
 }
@@ -28,7 +28,7 @@
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use crate::sync::SpinMutex;
 use crate::sync::WaitQueue;
-use crate::object::handle::{KernelObjectBase, ObjectType};
+use crate::object::handle::{KernelObjectBase, ObjectType, OBJECT_SIGNALED};
 
 /// ============================================================================
 /// Event ID
@@ -133,12 +133,28 @@ impl Event {
     /// Wakes up all waiting threads.
     pub fn signal(&self) {
         self.signaled.store(true, Ordering::Release);
+        self.base.notify(OBJECT_SIGNALED);
 
         // Wake all waiters (interior mutability through Mutex)
         let waiters = self.waiters.lock();
         waiters.wake_all();
     }
 
+    /// Take the signal if the event is signaled, without blocking
+    ///
+    /// An auto-reset event is cleared by the call that takes its signal,
+    /// so only one of several racing waiters succeeds; a manual-reset
+    /// event stays signaled.
+    pub fn try_acquire(&self) -> bool {
+        if self.flags.is_manual_reset() {
+            self.is_signaled()
+        } else {
+            self.signaled
+                .compare_exchange(true, false, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        }
+    }
+
     /// Unsignal the event
     ///
     /// Clears the signal state (for manual-reset events).
@@ -259,6 +275,17 @@ mod tests {
         assert!(!event.is_signaled());
     }
 
+    #[test]
+    fn test_event_try_acquire() {
+        let event = Event::new(true, EventFlags::empty);
+        assert!(event.try_acquire());
+        assert!(!event.try_acquire());
+
+        let event = Event::new(true, EventFlags::MANUAL_RESET);
+        assert!(event.try_acquire());
+        assert!(event.try_acquire());
+    }
+
     #[test]
     fn test_event_initially_signaled() {
         let event = Event::new(true, EventFlags::empty());
@@ -0,0 +1,121 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! File Objects
+//!
+//! A [`File`] carries an open file descriptor as a kernel object, so it can
+//! be sent in a channel message like any other handle. `FD_TO_HANDLE`
+//! wraps a copy of a descriptor and `HANDLE_TO_FD` installs a copy of the
+//! wrapped one in the receiver's descriptor table. Copies count as open
+//! descriptors: a `/tmp` file or pipe end stays open while a handle to it
+//! exists, even in a message nobody has read yet.
+//!
+//! # Rights
+//!
+//! A file handle has `READ` if the descriptor was opened for reading and
+//! `WRITE` if it was opened for writing. The descriptor installed from a
+//! handle keeps only the access the handle's rights allow: an `O_RDWR`
+//! file passed on with only `READ` comes out `O_RDONLY`.
+//!
+//! `O_CLOEXEC` belongs to a descriptor, not the open file, so it is not
+//! sent along; the receiver chooses it when installing the descriptor.
+
+use crate::object::handle::{KernelObjectBase, ObjectType, Rights};
+use crate::syscall::fd::{flags, FileDescriptor};
+
+/// No access left under the handle's rights
+pub const ERR_NO_ACCESS: &str = "file handle allows no access";
+
+/// An open file descriptor held by a handle
+pub struct File {
+    /// Kernel object base
+    pub base: KernelObjectBase,
+
+    /// The descriptor (without `O_CLOEXEC`)
+    desc: FileDescriptor,
+}
+
+impl File {
+    /// Wrap a copy of a descriptor
+    pub fn new(desc: &FileDescriptor) -> Self {
+        let mut desc = desc.clone();
+        desc.flags &= !flags::O_CLOEXEC;
+        Self { base: KernelObjectBase::new(ObjectType::File), desc }
+    }
+
+    /// Rights a handle to this file starts with
+    pub fn rights(&self) -> Rights {
+        let mut rights = Rights::DUPLICATE | Rights::TRANSFER;
+        if self.desc.is_readable() {
+            rights |= Rights::READ;
+        }
+        if self.desc.is_writable() {
+            rights |= Rights::WRITE;
+        }
+        rights
+    }
+
+    /// A copy of the descriptor, narrowed to the access `rights` allow
+    ///
+    /// # Returns
+    ///
+    /// [`ERR_NO_ACCESS`] if `rights` allow neither reading nor writing it
+    pub fn descriptor(&self, rights: Rights) -> Result<FileDescriptor, &'static str> {
+        let read = self.desc.is_readable() && rights.contains(Rights::READ);
+        let write = self.desc.is_writable() && rights.contains(Rights::WRITE);
+        let mode = match (read, write) {
+            (true, true) => flags::O_RDWR,
+            (true, false) => flags::O_RDONLY,
+            (false, true) => flags::O_WRONLY,
+            (false, false) => return Err(ERR_NO_ACCESS),
+        };
+        let mut desc = self.desc.clone();
+        desc.flags = (desc.flags & !flags::O_ACCMODE) | mode;
+        Ok(desc)
+    }
+
+    /// Get the kernel object base
+    pub fn base(&self) -> &KernelObjectBase {
+        &self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::fd::FdKind;
+
+    #[test]
+    fn test_rights_follow_access_mode() {
+        let tty = FdKind::Tty { tty: 1 };
+        let rdonly = File::new(&FileDescriptor::new(tty, flags::O_RDONLY));
+        assert!(rdonly.rights().contains(Rights::READ | Rights::TRANSFER));
+        assert!(!rdonly.rights().contains(Rights::WRITE));
+
+        let rdwr = File::new(&FileDescriptor::new(tty, flags::O_RDWR | flags::O_CLOEXEC));
+        assert!(rdwr.rights().contains(Rights::READ | Rights::WRITE));
+        assert_eq!(rdwr.base().obj_type, ObjectType::File);
+
+        // The close-on-spawn flag stays with the sender's descriptor
+        let copy = rdwr.descriptor(rdwr.rights()).unwrap();
+        assert_eq!(copy.flags, flags::O_RDWR);
+    }
+
+    #[test]
+    fn test_descriptor_narrowed_by_rights() {
+        let file = File::new(&FileDescriptor::new(FdKind::Tty { tty: 1 }, flags::O_RDWR | flags::O_NONBLOCK));
+
+        let copy = file.descriptor(Rights::READ).unwrap();
+        assert_eq!(copy.flags, flags::O_RDONLY | flags::O_NONBLOCK);
+        assert_eq!(copy.kind, FdKind::Tty { tty: 1 });
+        assert_eq!(file.descriptor(Rights::WRITE).unwrap().flags & flags::O_ACCMODE, flags::O_WRONLY);
+        assert_eq!(file.descriptor(Rights::TRANSFER).err(), Some(ERR_NO_ACCESS));
+
+        // Rights cannot add access the descriptor was not opened with
+        let rdonly = File::new(&FileDescriptor::new(FdKind::Tty { tty: 1 }, flags::O_RDONLY));
+        assert_eq!(rdonly.descriptor(Rights::WRITE).err(), Some(ERR_NO_ACCESS));
+    }
+}
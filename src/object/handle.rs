@@ -26,6 +26,7 @@
 
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use crate::sync::SpinMutex;
+use super::port::Observers;
 
 /// ============================================================================
 /// Handle Rights
@@ -73,6 +74,12 @@ impl Rights {
     /// Apply profile to thread
     pub const APPLY_PROFILE: Self = Self(0x100);
 
+    /// Set object properties (e.g. the name)
+    pub const SET_PROPERTY: Self = Self(0x200);
+
+    /// Read kernel debug state (the kernel log); only on the root job
+    pub const DEBUG: Self = Self(0x400);
+
     /// Basic rights (READ | WRITE)
     pub const BASIC: Self = Self(0x03);
 
@@ -144,15 +151,28 @@ impl Rights {
         match obj_type {
             ObjectType::Process => Self::MANAGE,
             ObjectType::Thread => Self::MANAGE,
-            ObjectType::Vmo => Self::DEFAULT,
+            ObjectType::Vmo => Self::DEFAULT | Self::DUPLICATE | Self::TRANSFER | Self::SET_PROPERTY,
             ObjectType::Vmar => Self::MAP | Self::READ | Self::WRITE,
-            ObjectType::Channel => Self::READ | Self::WRITE,
-            ObjectType::Event => Self::SIGNAL | Self::WAIT,
+            ObjectType::Channel => {
+                Self::READ | Self::WRITE | Self::TRANSFER | Self::DUPLICATE | Self::SET_PROPERTY
+            }
+            ObjectType::Event => {
+                Self::SIGNAL | Self::WAIT | Self::DUPLICATE | Self::TRANSFER | Self::SET_PROPERTY
+            }
             ObjectType::EventPair => Self::SIGNAL | Self::WAIT,
-            ObjectType::Timer => Self::SIGNAL | Self::WRITE,
-            ObjectType::Job => Self::MANAGE,
+            ObjectType::Timer => {
+                Self::SIGNAL | Self::WAIT | Self::WRITE | Self::DUPLICATE | Self::TRANSFER | Self::SET_PROPERTY
+            }
+            ObjectType::Job => Self::MANAGE | Self::DUPLICATE | Self::TRANSFER | Self::SET_PROPERTY,
             ObjectType::Port => Self::READ | Self::WRITE,
             ObjectType::Profile => Self::READ,
+            ObjectType::Semaphore => {
+                Self::SIGNAL | Self::WAIT | Self::DUPLICATE | Self::TRANSFER | Self::SET_PROPERTY
+            }
+            ObjectType::RingBuffer => Self::WAIT | Self::DUPLICATE | Self::TRANSFER,
+            ObjectType::Pipe => Self::READ | Self::WRITE | Self::DUPLICATE | Self::TRANSFER,
+            ObjectType::File => Self::READ | Self::WRITE | Self::DUPLICATE | Self::TRANSFER,
+            ObjectType::IoPort => Self::READ | Self::WRITE | Self::DUPLICATE | Self::TRANSFER,
             ObjectType::Unknown => Self::NONE,
         }
     }
@@ -248,6 +268,21 @@ pub enum ObjectType {
 
     /// Profile object
     Profile = 11,
+
+    /// Semaphore object
+    Semaphore = 12,
+
+    /// Ring buffer object
+    RingBuffer = 13,
+
+    /// Pipe (reached through file descriptors)
+    Pipe = 14,
+
+    /// Open file descriptor held by a handle
+    File = 15,
+
+    /// Range of I/O ports
+    IoPort = 16,
 }
 
 impl ObjectType {
@@ -265,6 +300,11 @@ impl ObjectType {
             9 => Self::Job,
             10 => Self::Port,
             11 => Self::Profile,
+            12 => Self::Semaphore,
+            13 => Self::RingBuffer,
+            14 => Self::Pipe,
+            15 => Self::File,
+            16 => Self::IoPort,
             _ => Self::Unknown,
         }
     }
@@ -289,14 +329,81 @@ impl ObjectType {
             Self::Job => "job",
             Self::Port => "port",
             Self::Profile => "profile",
+            Self::Semaphore => "semaphore",
+            Self::RingBuffer => "ringbuf",
+            Self::Pipe => "pipe",
+            Self::File => "file",
+            Self::IoPort => "ioport",
         }
     }
 }
 
+// ============================================================================
+// Object Names
+// ============================================================================
+
+/// Longest object name in bytes
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Debug name of a kernel object
+///
+/// Fixed size so it can be read and written without allocating. Longer
+/// names are truncated at a UTF-8 character boundary.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ObjectName {
+    bytes: [u8; MAX_NAME_LEN],
+    len: u8,
+}
+
+impl ObjectName {
+    /// The empty name
+    pub const fn empty() -> Self {
+        Self { bytes: [0; MAX_NAME_LEN], len: 0 }
+    }
+
+    /// Make a name from `s`, truncated to [`MAX_NAME_LEN`] bytes
+    pub fn new(s: &str) -> Self {
+        let mut len = core::cmp::min(s.len(), MAX_NAME_LEN);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; MAX_NAME_LEN];
+        bytes[..len].copy_from_slice(&s.as_bytes()[..len]);
+        Self { bytes, len: len as u8 }
+    }
+
+    /// The name
+    pub fn as_str(&self) -> &str {
+        // Only ever built from a &str cut at a char boundary
+        core::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or("")
+    }
+
+    /// Check if no name is set
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The name NUL-padded to [`MAX_NAME_LEN`] bytes, as userspace gets it
+    pub fn to_bytes(&self) -> [u8; MAX_NAME_LEN] {
+        self.bytes
+    }
+}
+
+impl core::fmt::Debug for ObjectName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
 /// ============================================================================
 /// Kernel Object Base
 /// ============================================================================
 
+/// The signal a signaled object raises (`EVENT_SIGNALED` in the syscall
+/// ABI): an event is signaled, a semaphore has units, a timer has fired
+/// or a ring buffer has reached its threshold
+pub const OBJECT_SIGNALED: u32 = 1 << 0;
+
 /// Kernel object base
 ///
 /// All kernel objects share this common structure.
@@ -309,18 +416,62 @@ pub struct KernelObjectBase {
 
     /// Whether object is being destroyed
     pub destroying: AtomicBool,
+
+    /// Debug name (empty unless set)
+    name: SpinMutex<ObjectName>,
+
+    /// Creation time in nanoseconds, for lifetime metrics
+    created_ns: u64,
+
+    /// Ports bound to the object's signals
+    observers: Observers,
 }
 
 impl KernelObjectBase {
     /// Create a new kernel object base
-    pub const fn new(obj_type: ObjectType) -> Self {
+    ///
+    /// The object is counted in [`metrics`](super::metrics) until the
+    /// base is dropped.
+    pub fn new(obj_type: ObjectType) -> Self {
+        let created_ns = super::metrics::now();
+        super::metrics::record_create(obj_type, created_ns);
         Self {
             obj_type,
             ref_count: AtomicUsize::new(1),
             destroying: AtomicBool::new(false),
+            name: SpinMutex::new(ObjectName::empty()),
+            created_ns,
+            observers: Observers::new(),
         }
     }
 
+    /// Ports bound to the object (see [`super::port`])
+    pub fn observers(&self) -> &Observers {
+        &self.observers
+    }
+
+    /// Tell bound ports the object has raised `signals`
+    ///
+    /// Called by each object type when it becomes signaled.
+    pub fn notify(&self, signals: u32) {
+        self.observers.notify(signals);
+    }
+
+    /// Creation time in nanoseconds since boot
+    pub fn created_ns(&self) -> u64 {
+        self.created_ns
+    }
+
+    /// Get the debug name
+    pub fn name(&self) -> ObjectName {
+        *self.name.lock()
+    }
+
+    /// Set the debug name (truncated to [`MAX_NAME_LEN`] bytes)
+    pub fn set_name(&self, name: &str) {
+        *self.name.lock() = ObjectName::new(name);
+    }
+
     /// Increment reference count
     pub fn ref_inc(&self) {
         self.ref_count.fetch_add(1, Ordering::Relaxed);
@@ -349,6 +500,12 @@ impl KernelObjectBase {
     }
 }
 
+impl Drop for KernelObjectBase {
+    fn drop(&mut self) {
+        super::metrics::record_destroy(self.obj_type, self.created_ns);
+    }
+}
+
 /// ============================================================================
 /// Handle
 /// ============================================================================
@@ -785,6 +942,23 @@ mod tests {
         assert!(obj.ref_dec()); // Last reference
     }
 
+    #[test]
+    fn test_object_name() {
+        let obj = KernelObjectBase::new(ObjectType::Vmo);
+        assert!(obj.name().is_empty());
+
+        obj.set_name("heap");
+        assert_eq!(obj.name().as_str(), "heap");
+        assert_eq!(&obj.name().to_bytes()[..5], b"heap\0");
+
+        // Truncated to MAX_NAME_LEN, never inside a character
+        let long = "é".repeat(MAX_NAME_LEN);
+        obj.set_name(&long);
+        assert_eq!(obj.name().as_str().len(), MAX_NAME_LEN);
+        obj.set_name(&alloc::format!("x{}", long));
+        assert_eq!(obj.name().as_str().len(), MAX_NAME_LEN - 1);
+    }
+
     #[test]
     fn test_handle_basic() {
         let base = KernelObjectBase::new(ObjectType::Event);
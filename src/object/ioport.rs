@@ -0,0 +1,85 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! I/O Port Ranges
+//!
+//! An [`IoPortRange`] lets a userspace driver (VGA, a legacy serial port)
+//! use `in` and `out` on a bounded range of ports. Only privileged
+//! processes can create one (`IOPORT_CREATE`); the handle is then passed
+//! to the driver, which enables the range for itself with
+//! `IOPORT_ENABLE`.
+//!
+//! Enabled ranges are loaded into the TSS I/O permission bitmap of the CPU
+//! running the process (see [`crate::arch::amd64::ioport`]). A port
+//! outside them raises #GP, which kills the process. Ports the kernel
+//! drives itself ([`KERNEL_PORTS`]) and ports past the bitmap cannot be
+//! granted.
+//!
+//! # Rights
+//!
+//! `READ | WRITE | DUPLICATE | TRANSFER`. Enabling the range needs both
+//! `READ` and `WRITE`, since the bitmap cannot allow one direction only.
+//!
+//! [`KERNEL_PORTS`]: crate::arch::amd64::ioport::KERNEL_PORTS
+
+use crate::arch::amd64::ioport::{self, PortRange};
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::handle::{KernelObjectBase, ObjectType};
+
+/// A range of I/O ports held by a handle
+pub struct IoPortRange {
+    /// Kernel object base
+    pub base: KernelObjectBase,
+
+    /// The ports
+    range: PortRange,
+}
+
+impl IoPortRange {
+    /// Create a range of `count` ports from `start`
+    ///
+    /// # Returns
+    ///
+    /// `ERR_INVALID_ARGS` if the range is empty or goes past the TSS
+    /// bitmap, `ERR_ACCESS_DENIED` if it includes a kernel port
+    pub fn create(start: u32, count: u32) -> Result<Self, RxStatus> {
+        let start = u16::try_from(start).map_err(|_| RxStatus::ERR_INVALID_ARGS)?;
+        let count = u16::try_from(count).map_err(|_| RxStatus::ERR_INVALID_ARGS)?;
+        let range = PortRange::new(start, count).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+        if ioport::is_kernel_range(&range) {
+            return Err(RxStatus::ERR_ACCESS_DENIED);
+        }
+        Ok(Self { base: KernelObjectBase::new(ObjectType::IoPort), range })
+    }
+
+    /// The ports
+    pub fn range(&self) -> PortRange {
+        self.range
+    }
+
+    /// Get the kernel object base
+    pub fn base(&self) -> &KernelObjectBase {
+        &self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create() {
+        let vga = IoPortRange::create(0x3C0, 0x20).unwrap();
+        assert_eq!(vga.range(), PortRange { start: 0x3C0, count: 0x20 });
+        assert_eq!(vga.base().obj_type, ObjectType::IoPort);
+
+        assert_eq!(IoPortRange::create(0x2F8, 0).err(), Some(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(IoPortRange::create(0xFF8, 0x10).err(), Some(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(IoPortRange::create(0x1_0000, 1).err(), Some(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(IoPortRange::create(0x3F8, 8).err(), Some(RxStatus::ERR_ACCESS_DENIED));
+        assert_eq!(IoPortRange::create(0x20, 0x100).err(), Some(RxStatus::ERR_ACCESS_DENIED));
+    }
+}
@@ -16,6 +16,11 @@
 //! - **Accounting**: Track resource usage across all child processes
 //! - **Lifecycle**: Jobs are created explicitly and destroyed when all children exit
 //!
+//! Process and memory limits are enforced by [`crate::process::jobs`],
+//! CPU time by [`crate::sched::cpu_limit`]. Both key their accounting by
+//! [`JobId`], so a job's processes are limited even after every handle to
+//! it is closed.
+//!
 //! # Usage
 //!
 //! ```rust
@@ -88,6 +93,9 @@ pub enum JobPolicy {
 
     /// Allow debugging
     AllowDebug = 1 << 9,
+
+    /// Allow entering the deadline scheduling class
+    AllowRealtime = 1 << 10,
 }
 
 impl JobPolicy {
@@ -129,7 +137,8 @@ pub struct ResourceLimits {
     /// Maximum memory in bytes (0 = no limit)
     pub max_memory: u64,
 
-    /// Maximum CPU time (0 = no limit)
+    /// Maximum CPU time in nanoseconds, summed over the job's processes
+    /// (0 = no limit)
     pub max_cpu_time: u64,
 
     /// Maximum number of processes (0 = no limit)
@@ -257,10 +266,23 @@ impl Job {
     /// * `parent` - Parent job
     /// * `policy` - Job policy flags
     pub fn new_child(parent: &Job, policy: u32) -> Result<Self, &'static str> {
+        let child = Self::new_child_of(parent.id, policy)?;
+
+        // Add to parent's children
+        parent.children.lock().push(child.id);
+
+        Ok(child)
+    }
+
+    /// Create a new child job of a job known only by ID
+    ///
+    /// For the caller's own job, which it has no handle to. The parent's
+    /// `children` list does not include the new job.
+    pub fn new_child_of(parent_id: JobId, policy: u32) -> Result<Self, &'static str> {
         let child = Self {
             base: KernelObjectBase::new(ObjectType::Job),
             id: alloc_job_id(),
-            parent_id: SpinMutex::new(Some(parent.id)),
+            parent_id: SpinMutex::new(Some(parent_id)),
             children: SpinMutex::new(alloc::vec::Vec::new()),
             processes: SpinMutex::new(alloc::vec::Vec::new()),
             policy: SpinMutex::new(JobPolicy::from_raw(policy)),
@@ -268,8 +290,11 @@ impl Job {
             stats: SpinMutex::new(JobStats::zero()),
         };
 
-        // Add to parent's children
-        parent.children.lock().push(child.id);
+        crate::process::jobs::register(child.id, parent_id);
+        crate::sched::deadline::set_job_allowed(
+            child.id,
+            policy & JobPolicy::AllowRealtime.to_flags() != 0,
+        );
 
         Ok(child)
     }
@@ -292,6 +317,7 @@ impl Job {
     /// Set job policy
     pub fn set_policy(&self, policy: JobPolicy) {
         *self.policy.lock() = policy;
+        crate::sched::deadline::set_job_allowed(self.id, policy.contains(JobPolicy::AllowRealtime));
     }
 
     /// Get resource limits
@@ -300,13 +326,44 @@ impl Job {
     }
 
     /// Set resource limits
+    ///
+    /// The CPU-time limit is enforced by the scheduler; see
+    /// [`crate::sched::cpu_limit`]. The process and memory limits are
+    /// checked when a process is created and when a VMO commits a page;
+    /// see [`crate::process::jobs`].
     pub fn set_limits(&self, limits: ResourceLimits) {
         *self.limits.lock() = limits;
+        crate::process::jobs::set_limits(self.id, &limits);
+        crate::sched::cpu_limit::set_job_limit(
+            self.id,
+            crate::time::Duration::from_nanos(limits.max_cpu_time),
+            crate::sched::cpu_limit::CPU_LIMIT_GRACE,
+        );
     }
 
     /// Get job statistics
+    ///
+    /// Process count and memory usage include the job's child jobs.
     pub fn stats(&self) -> JobStats {
-        *self.stats.lock()
+        let mut stats = *self.stats.lock();
+        if let Some(cpu) = crate::sched::cpu_limit::job_cpu(self.id) {
+            stats.cpu_time = cpu.used.as_nanos();
+        }
+        let account = crate::process::jobs::account(self.id);
+        stats.process_count = account.processes;
+        stats.memory_usage = account.memory;
+        stats
+    }
+
+    /// Kill every process in this job and its child jobs
+    ///
+    /// The jobs admit no new processes afterwards.
+    ///
+    /// # Returns
+    ///
+    /// The number of processes killed
+    pub fn kill(&self) -> usize {
+        crate::process::jobs::kill(self.id)
     }
 
     /// Add a child job
@@ -0,0 +1,201 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Typed Object References
+//!
+//! [`Handle`](super::Handle) only carries an opaque `KernelObjectBase`
+//! pointer, which cannot be turned back into the object it belongs to.
+//! Handles that userspace holds (process handle tables, handles in
+//! channel messages) instead store a [`KernelObject`]: a reference-counted
+//! pointer to the concrete object. The object is destroyed when the last
+//! handle to it goes away.
+//!
+//! Code that needs one particular type looks it up through [`ObjectKind`],
+//! e.g. `handles.object::<Vmo>(value, Rights::READ)`.
+
+use alloc::sync::Arc;
+use super::channel::Channel;
+use super::event::Event;
+use super::file::File;
+use super::ioport::IoPortRange;
+use super::handle::{KernelObjectBase, ObjectName, ObjectType, Rights};
+use super::job::Job;
+use super::port::Port;
+use super::ringbuf::RingBuffer;
+use super::semaphore::Semaphore;
+use super::timer::Timer;
+use super::vmo::Vmo;
+
+/// Reference to a kernel object of any type
+#[derive(Clone)]
+pub enum KernelObject {
+    /// Channel endpoint
+    Channel(Arc<Channel>),
+
+    /// Virtual memory object
+    Vmo(Arc<Vmo>),
+
+    /// Event
+    Event(Arc<Event>),
+
+    /// Timer
+    Timer(Arc<Timer>),
+
+    /// Job
+    Job(Arc<Job>),
+
+    /// Semaphore
+    Semaphore(Arc<Semaphore>),
+
+    /// Ring buffer
+    RingBuffer(Arc<RingBuffer>),
+
+    /// Port
+    Port(Arc<Port>),
+
+    /// Open file descriptor
+    File(Arc<File>),
+
+    /// I/O port range
+    IoPortRange(Arc<IoPortRange>),
+}
+
+impl KernelObject {
+    /// Object type
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            KernelObject::Channel(_) => ObjectType::Channel,
+            KernelObject::Vmo(_) => ObjectType::Vmo,
+            KernelObject::Event(_) => ObjectType::Event,
+            KernelObject::Timer(_) => ObjectType::Timer,
+            KernelObject::Job(_) => ObjectType::Job,
+            KernelObject::Semaphore(_) => ObjectType::Semaphore,
+            KernelObject::RingBuffer(_) => ObjectType::RingBuffer,
+            KernelObject::Port(_) => ObjectType::Port,
+            KernelObject::File(_) => ObjectType::File,
+            KernelObject::IoPortRange(_) => ObjectType::IoPort,
+        }
+    }
+
+    /// Common object state
+    pub fn base(&self) -> &KernelObjectBase {
+        match self {
+            KernelObject::Channel(o) => o.base(),
+            KernelObject::Vmo(o) => o.base(),
+            KernelObject::Event(o) => o.base(),
+            KernelObject::Timer(o) => o.base(),
+            KernelObject::Job(o) => o.base(),
+            KernelObject::Semaphore(o) => o.base(),
+            KernelObject::RingBuffer(o) => o.base(),
+            KernelObject::Port(o) => o.base(),
+            KernelObject::File(o) => o.base(),
+            KernelObject::IoPortRange(o) => o.base(),
+        }
+    }
+
+    /// Debug name (empty unless set)
+    pub fn name(&self) -> ObjectName {
+        self.base().name()
+    }
+
+    /// The channel endpoint, if this is one
+    pub fn as_channel(&self) -> Option<&Arc<Channel>> {
+        Channel::from_object(self)
+    }
+
+    /// The object as type `T`, if it is one
+    pub fn downcast<T: ObjectKind>(&self) -> Option<&Arc<T>> {
+        T::from_object(self)
+    }
+
+    /// Whether both references point to the same object
+    pub fn same_object(&self, other: &KernelObject) -> bool {
+        match (self, other) {
+            (KernelObject::Channel(a), KernelObject::Channel(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::Vmo(a), KernelObject::Vmo(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::Event(a), KernelObject::Event(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::Timer(a), KernelObject::Timer(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::Job(a), KernelObject::Job(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::Semaphore(a), KernelObject::Semaphore(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::RingBuffer(a), KernelObject::RingBuffer(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::Port(a), KernelObject::Port(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::File(a), KernelObject::File(b)) => Arc::ptr_eq(a, b),
+            (KernelObject::IoPortRange(a), KernelObject::IoPortRange(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// An object type a [`KernelObject`] can hold
+pub trait ObjectKind: Sized {
+    /// Wrap a reference to the object
+    fn into_object(this: Arc<Self>) -> KernelObject;
+
+    /// The object, if `object` is of this type
+    fn from_object(object: &KernelObject) -> Option<&Arc<Self>>;
+}
+
+macro_rules! object_kind {
+    ($ty:ident) => {
+        impl ObjectKind for $ty {
+            fn into_object(this: Arc<Self>) -> KernelObject {
+                KernelObject::$ty(this)
+            }
+
+            fn from_object(object: &KernelObject) -> Option<&Arc<Self>> {
+                match object {
+                    KernelObject::$ty(o) => Some(o),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+object_kind!(Channel);
+object_kind!(Vmo);
+object_kind!(Event);
+object_kind!(Timer);
+object_kind!(Job);
+object_kind!(Semaphore);
+object_kind!(RingBuffer);
+object_kind!(Port);
+object_kind!(File);
+object_kind!(IoPortRange);
+
+/// A kernel object together with the rights held on it
+#[derive(Clone)]
+pub struct ObjectHandle {
+    /// Referenced object
+    pub object: KernelObject,
+
+    /// Rights mask
+    pub rights: Rights,
+}
+
+impl ObjectHandle {
+    /// Create a handle
+    pub fn new(object: KernelObject, rights: Rights) -> Self {
+        Self { object, rights }
+    }
+
+    /// Create a handle to a new object with its type's default rights
+    pub fn with_default_rights<T: ObjectKind>(object: T) -> Self {
+        let object = T::into_object(Arc::new(object));
+        let rights = Rights::default_for_type(object.object_type());
+        Self { object, rights }
+    }
+
+    /// Object type
+    pub fn object_type(&self) -> ObjectType {
+        self.object.object_type()
+    }
+
+    /// Check if handle has specific rights
+    pub fn has_right(&self, right: Rights) -> bool {
+        self.rights.contains(right)
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Object Metrics
+//!
+//! Every [`KernelObjectBase`] counts itself here when it is created and
+//! again when it is dropped, so the live count of each [`ObjectType`] is
+//! always `created - destroyed`. Dropped objects also record how long
+//! they lived in a coarse histogram, and the creation times of live
+//! objects are summed so their mean age can be reported without walking
+//! them.
+//!
+//! # Reporting
+//!
+//! - `/proc/kobjects` prints one line per type (see [`write_report`])
+//! - `KOBJECT_STATS` copies one type's [`KobjectStats`] to userspace
+//!
+//! # Leak Checks
+//!
+//! The test kernel takes a [`Baseline`] before running the `ktest!`
+//! tests and [`checks`](Baseline::check) it afterwards: a type with more
+//! live objects than before was leaked by some test.
+//!
+//! [`KernelObjectBase`]: super::handle::KernelObjectBase
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::object::handle::ObjectType;
+use crate::kwarn;
+
+/// Counter slots, indexed by raw [`ObjectType`] value
+const TYPE_SLOTS: usize = 17;
+
+/// Number of lifetime histogram buckets
+pub const LIFETIME_BUCKETS: usize = 7;
+
+/// Upper bounds (exclusive, nanoseconds) of all but the last bucket
+const BUCKET_BOUNDS_NS: [u64; LIFETIME_BUCKETS - 1] = [
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+    10_000_000_000,
+    60_000_000_000,
+];
+
+/// Histogram bucket labels, for reports
+pub const BUCKET_LABELS: [&str; LIFETIME_BUCKETS] =
+    ["<1ms", "<10ms", "<100ms", "<1s", "<10s", "<1m", ">=1m"];
+
+/// Histogram bucket for an object that lived `lifetime_ns`
+fn bucket(lifetime_ns: u64) -> usize {
+    BUCKET_BOUNDS_NS.iter().position(|&bound| lifetime_ns < bound).unwrap_or(LIFETIME_BUCKETS - 1)
+}
+
+/// Counters of one object type
+struct TypeCounters {
+    created: AtomicU64,
+    destroyed: AtomicU64,
+    /// Sum of the creation times of live objects (wrapping)
+    birth_sum: AtomicU64,
+    lifetimes: [AtomicU64; LIFETIME_BUCKETS],
+}
+
+impl TypeCounters {
+    const fn new() -> Self {
+        Self {
+            created: AtomicU64::new(0),
+            destroyed: AtomicU64::new(0),
+            birth_sum: AtomicU64::new(0),
+            lifetimes: [const { AtomicU64::new(0) }; LIFETIME_BUCKETS],
+        }
+    }
+
+    fn record_create(&self, created_ns: u64) {
+        self.birth_sum.fetch_add(created_ns, Ordering::Relaxed);
+        self.created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_destroy(&self, created_ns: u64, now_ns: u64) {
+        self.lifetimes[bucket(now_ns.saturating_sub(created_ns))].fetch_add(1, Ordering::Relaxed);
+        self.birth_sum.fetch_sub(created_ns, Ordering::Relaxed);
+        self.destroyed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, obj_type: ObjectType, now_ns: u64) -> KobjectStats {
+        // Read destroyed first: a concurrent create/destroy pair can then
+        // only overstate the live count, never underflow it
+        let destroyed = self.destroyed.load(Ordering::Relaxed);
+        let created = self.created.load(Ordering::Relaxed);
+        let live = created.saturating_sub(destroyed);
+        let birth_sum = self.birth_sum.load(Ordering::Relaxed);
+        let mean_age_ns = now_ns
+            .wrapping_mul(live)
+            .wrapping_sub(birth_sum)
+            .checked_div(live)
+            .unwrap_or(0);
+        let mut lifetimes = [0u64; LIFETIME_BUCKETS];
+        for (out, counter) in lifetimes.iter_mut().zip(&self.lifetimes) {
+            *out = counter.load(Ordering::Relaxed);
+        }
+        KobjectStats {
+            obj_type: obj_type.into_raw(),
+            _reserved: 0,
+            live,
+            created,
+            destroyed,
+            mean_age_ns,
+            lifetimes,
+        }
+    }
+}
+
+/// Global counters
+static COUNTERS: [TypeCounters; TYPE_SLOTS] = [const { TypeCounters::new() }; TYPE_SLOTS];
+
+fn counters(obj_type: ObjectType) -> &'static TypeCounters {
+    &COUNTERS[(obj_type.into_raw() as usize).min(TYPE_SLOTS - 1)]
+}
+
+/// Count a newly created object
+pub fn record_create(obj_type: ObjectType, created_ns: u64) {
+    counters(obj_type).record_create(created_ns);
+}
+
+/// Count a dropped object created at `created_ns`
+pub fn record_destroy(obj_type: ObjectType, created_ns: u64) {
+    counters(obj_type).record_destroy(created_ns, now());
+}
+
+/// Current time for lifetimes
+pub fn now() -> u64 {
+    crate::time::Instant::now().as_nanos()
+}
+
+/// Statistics of one object type
+///
+/// Also the output struct of `KOBJECT_STATS`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KobjectStats {
+    /// Object type (`ObjectType` value)
+    pub obj_type: u32,
+    /// Reserved, always 0
+    pub _reserved: u32,
+    /// Objects alive now
+    pub live: u64,
+    /// Objects created since boot
+    pub created: u64,
+    /// Objects destroyed since boot
+    pub destroyed: u64,
+    /// Mean age of the live objects in nanoseconds (0 if none)
+    pub mean_age_ns: u64,
+    /// Lifetimes of destroyed objects, bucketed as in [`BUCKET_LABELS`]
+    pub lifetimes: [u64; LIFETIME_BUCKETS],
+}
+
+/// Statistics of one object type, or `None` for `Unknown`
+pub fn stats(obj_type: ObjectType) -> Option<KobjectStats> {
+    if obj_type == ObjectType::Unknown {
+        return None;
+    }
+    Some(counters(obj_type).snapshot(obj_type, now()))
+}
+
+/// Statistics of every known object type, in type order
+pub fn all_stats() -> Vec<KobjectStats> {
+    (1..TYPE_SLOTS as u32).filter_map(|raw| stats(ObjectType::from_raw(raw))).collect()
+}
+
+/// Write the `/proc/kobjects` report
+pub fn write_report(out: &mut impl Write) -> core::fmt::Result {
+    write!(out, "{:<10} {:>8} {:>10} {:>10} {:>12}", "type", "live", "created", "destroyed", "mean_age_ms")?;
+    for label in BUCKET_LABELS {
+        write!(out, " {:>8}", label)?;
+    }
+    writeln!(out)?;
+    for s in all_stats() {
+        write!(
+            out,
+            "{:<10} {:>8} {:>10} {:>10} {:>12}",
+            ObjectType::from_raw(s.obj_type).name(),
+            s.live,
+            s.created,
+            s.destroyed,
+            s.mean_age_ns / 1_000_000
+        )?;
+        for count in s.lifetimes {
+            write!(out, " {:>8}", count)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Live object counts at some point in time
+pub struct Baseline {
+    live: Vec<(ObjectType, u64)>,
+}
+
+impl Baseline {
+    /// Record the current live counts
+    pub fn capture() -> Self {
+        Self { live: all_stats().iter().map(|s| (ObjectType::from_raw(s.obj_type), s.live)).collect() }
+    }
+
+    /// Types with more live objects now than at capture, with the excess
+    pub fn leaks(&self) -> Vec<(ObjectType, u64)> {
+        self.live
+            .iter()
+            .filter_map(|&(ty, before)| {
+                let excess = stats(ty)?.live.saturating_sub(before);
+                (excess > 0).then_some((ty, excess))
+            })
+            .collect()
+    }
+
+    /// Check that no type grew since capture
+    ///
+    /// Each leaking type is logged as a warning.
+    pub fn check(&self) -> bool {
+        let leaks = self.leaks();
+        for (ty, count) in &leaks {
+            kwarn!("[KOBJ] leaked {} {} object(s)", count, ty.name());
+        }
+        leaks.is_empty()
+    }
+}
+
+crate::ktest! {
+    fn kobject_counts_return_to_baseline() {
+        let baseline = Baseline::capture();
+        let event = crate::object::Event::new(false, crate::object::EventFlags::MANUAL_RESET);
+        assert!(baseline.leaks().contains(&(ObjectType::Event, 1)));
+        drop(event);
+        assert!(baseline.leaks().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(999_999), 0);
+        assert_eq!(bucket(1_000_000), 1);
+        assert_eq!(bucket(2_000_000_000), 4);
+        assert_eq!(bucket(u64::MAX), LIFETIME_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_counts_and_mean_age() {
+        let c = TypeCounters::new();
+        c.record_create(1_000);
+        c.record_create(3_000);
+        c.record_create(5_000);
+        c.record_destroy(5_000, 20_000_000);
+
+        let s = c.snapshot(ObjectType::Vmo, 10_000);
+        assert_eq!((s.live, s.created, s.destroyed), (2, 3, 1));
+        // Ages 9000 and 7000
+        assert_eq!(s.mean_age_ns, 8_000);
+        assert_eq!(s.lifetimes[2], 1);
+        assert_eq!(s.obj_type, ObjectType::Vmo.into_raw());
+    }
+
+    #[test]
+    fn test_report_lists_types() {
+        let mut out = alloc::string::String::new();
+        write_report(&mut out).unwrap();
+        assert!(out.starts_with("type"));
+        assert!(out.lines().any(|l| l.starts_with("pipe ")));
+        assert!(!out.contains("unknown"));
+    }
+}
@@ -13,7 +13,8 @@
 //! # Design
 //!
 //! - **Capability-based security**: All operations through handles with rights
-//! - **Object types**: Process, Thread, VMO, VMAR, Channel, Event, Timer, Job, Port
+//! - **Object types**: Process, Thread, VMO, VMAR, Channel, Event, Semaphore, Timer, Job, Port,
+//!   RingBuffer, Pipe, File, IoPortRange
 //! - **Handle passing**: IPC can transfer handles with rights reduction
 //! - **Reference counting**: Automatic cleanup when last handle is closed
 //!
@@ -22,24 +23,48 @@
 //! - [`handle`] - Handle and rights model
 //! - [`vmo`] - Virtual Memory Objects
 //! - [`channel`] - IPC channels
+//! - [`kernel_object`] - Typed object references held by handles
 //! - [`event`] - Event objects
 //! - [`timer`] - Timer objects
 //! - [`job`] - Job objects (resource containers)
+//! - [`semaphore`] - Counting semaphores
+//! - [`ringbuf`] - Shared-memory ring buffers for kernel event streams
+//! - [`port`] - Packet queues that objects signal asynchronously
+//! - [`pipe`] - Byte-stream pipes behind file descriptors
+//! - [`file`] - File descriptors passed as handles
+//! - [`ioport`] - I/O port ranges for userspace drivers
+//! - [`metrics`] - Live counts and lifetimes of objects by type
 
 pub mod handle;
 pub mod vmo;
 pub mod channel;
+pub mod kernel_object;
 pub mod event;
 pub mod timer;
 pub mod job;
+pub mod semaphore;
+pub mod ringbuf;
+pub mod port;
+pub mod pipe;
+pub mod file;
+pub mod ioport;
+pub mod metrics;
 
 // Re-exports
 pub use handle::{
     Handle, HandleId, HandleOwner, HandleTable, KernelObjectBase, Rights, ObjectType,
-    HandleEntry, MAX_HANDLES,
+    HandleEntry, ObjectName, MAX_HANDLES, MAX_NAME_LEN, OBJECT_SIGNALED,
 };
 pub use job::{Job, JobId, JobPolicy, ResourceLimits, JobStats, JOB_ID_ROOT, JOB_ID_INVALID};
 pub use event::{Event, EventId, EventFlags};
 pub use timer::{Timer, TimerId, TimerState, SlackPolicy};
+pub use semaphore::{Semaphore, SemaphoreId};
+pub use ringbuf::{RingBuffer, RingBufferId, RingHeader, RingSource};
+pub use port::{Observer, Port, PortId, PortPacket, PORT_CAPACITY};
+pub use pipe::{Pipe, PipeId, PIPE_BUF, PIPE_CAPACITY};
+pub use file::File;
+pub use ioport::IoPortRange;
+pub use metrics::KobjectStats;
 pub use channel::{Channel, ChannelId, ChannelState, Message, ReadResult, MAX_MSG_SIZE, MAX_MSG_HANDLES};
+pub use kernel_object::{KernelObject, ObjectHandle, ObjectKind};
 pub use vmo::{Vmo, VmoId, VmoFlags, CachePolicy};
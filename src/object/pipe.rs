@@ -0,0 +1,335 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Pipe Objects
+//!
+//! A pipe is a one-way byte stream from a write end to a read end,
+//! buffered in a ring of [`PIPE_CAPACITY`] bytes. Unlike a channel it
+//! keeps no message boundaries: a read returns whatever is buffered, up to
+//! the size asked for.
+//!
+//! # Ends
+//!
+//! Pipes are reached through file descriptors (`FdKind::Pipe`), not
+//! handles, and are found by [`PipeId`] in a global table. Every
+//! descriptor holds one reference to its end; [`retain`] and [`release`]
+//! count them, so the copies `FORK` makes keep the pipe open. Once the
+//! last write end is gone, reads return end of file after the buffer is
+//! drained. Once the last read end is gone, writes fail with
+//! [`ERR_PEER_CLOSED`]. The pipe is freed when both ends are gone.
+//!
+//! # Atomicity
+//!
+//! A write of at most [`PIPE_BUF`] bytes is never split: it fails with
+//! [`ERR_SHOULD_WAIT`] until it fits as a whole, so short writes from
+//! several writers do not interleave. Longer writes take what fits.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let pipe = Pipe::new();
+//! pipe.write(b"hi")?;
+//! let mut buf = [0u8; 8];
+//! assert_eq!(pipe.read(&mut buf)?, 2);
+//! ```
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use crate::object::handle::{KernelObjectBase, ObjectType};
+use crate::sync::SpinMutex;
+
+/// Bytes a pipe buffers
+pub const PIPE_CAPACITY: usize = 16 * 1024;
+
+/// Largest write that is never split
+pub const PIPE_BUF: usize = 4096;
+
+/// Nothing to read yet, or no room for the write
+pub const ERR_SHOULD_WAIT: &str = "pipe would block";
+
+/// Write with no read end left
+pub const ERR_PEER_CLOSED: &str = "no readers";
+
+// ============================================================================
+// Pipe ID
+// ============================================================================
+
+/// Pipe identifier
+pub type PipeId = u32;
+
+/// Next pipe ID counter
+static NEXT_PIPE_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Allocate a new pipe ID
+fn alloc_pipe_id() -> PipeId {
+    NEXT_PIPE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// ============================================================================
+// Byte Ring
+// ============================================================================
+
+/// Fixed-size byte ring
+struct Ring {
+    /// Storage
+    data: Box<[u8]>,
+    /// Index of the oldest byte
+    head: usize,
+    /// Bytes buffered
+    len: usize,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Self { data: alloc::vec![0u8; capacity].into_boxed_slice(), head: 0, len: 0 }
+    }
+
+    fn free(&self) -> usize {
+        self.data.len() - self.len
+    }
+
+    /// Append as much of `src` as fits
+    fn push(&mut self, src: &[u8]) -> usize {
+        let cap = self.data.len();
+        let n = src.len().min(self.free());
+        let tail = (self.head + self.len) % cap;
+        let first = n.min(cap - tail);
+        self.data[tail..tail + first].copy_from_slice(&src[..first]);
+        self.data[..n - first].copy_from_slice(&src[first..n]);
+        self.len += n;
+        n
+    }
+
+    /// Remove up to `dst.len()` of the oldest bytes into `dst`
+    fn pop(&mut self, dst: &mut [u8]) -> usize {
+        let cap = self.data.len();
+        let n = dst.len().min(self.len);
+        let first = n.min(cap - self.head);
+        dst[..first].copy_from_slice(&self.data[self.head..self.head + first]);
+        dst[first..n].copy_from_slice(&self.data[..n - first]);
+        self.head = (self.head + n) % cap;
+        self.len -= n;
+        n
+    }
+}
+
+// ============================================================================
+// Pipe
+// ============================================================================
+
+/// Buffer and open ends, changed together under one lock
+struct PipeState {
+    ring: Ring,
+    readers: u32,
+    writers: u32,
+}
+
+/// Pipe object
+pub struct Pipe {
+    /// Kernel object base
+    pub base: KernelObjectBase,
+
+    /// Pipe ID
+    pub id: PipeId,
+
+    /// Buffered bytes and end counts
+    state: SpinMutex<PipeState>,
+}
+
+impl Pipe {
+    /// Create a pipe with one read end and one write end open
+    pub fn new() -> Self {
+        Self {
+            base: KernelObjectBase::new(ObjectType::Pipe),
+            id: alloc_pipe_id(),
+            state: SpinMutex::new(PipeState { ring: Ring::new(PIPE_CAPACITY), readers: 1, writers: 1 }),
+        }
+    }
+
+    /// Take buffered bytes into `buf` without blocking
+    ///
+    /// # Returns
+    ///
+    /// Bytes read, 0 at end of file (or for an empty `buf`), or
+    /// [`ERR_SHOULD_WAIT`] while the pipe is empty and a write end is open
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        let mut state = self.state.lock();
+        if buf.is_empty() || state.ring.len > 0 || state.writers == 0 {
+            return Ok(state.ring.pop(buf));
+        }
+        Err(ERR_SHOULD_WAIT)
+    }
+
+    /// Buffer bytes from `data` without blocking
+    ///
+    /// # Returns
+    ///
+    /// Bytes written, [`ERR_PEER_CLOSED`] if no read end is open, or
+    /// [`ERR_SHOULD_WAIT`] if nothing fits (or, for a write of at most
+    /// [`PIPE_BUF`] bytes, not all of it)
+    pub fn write(&self, data: &[u8]) -> Result<usize, &'static str> {
+        let mut state = self.state.lock();
+        if state.readers == 0 {
+            return Err(ERR_PEER_CLOSED);
+        }
+        let free = state.ring.free();
+        if !data.is_empty() && (free == 0 || (data.len() <= PIPE_BUF && free < data.len())) {
+            return Err(ERR_SHOULD_WAIT);
+        }
+        Ok(state.ring.push(data))
+    }
+
+    /// Bytes waiting to be read
+    pub fn buffered(&self) -> usize {
+        self.state.lock().ring.len
+    }
+
+    /// Open read ends and write ends
+    pub fn ends(&self) -> (u32, u32) {
+        let state = self.state.lock();
+        (state.readers, state.writers)
+    }
+
+    /// Count another reference to an end
+    fn open_end(&self, read_end: bool) {
+        let mut state = self.state.lock();
+        if read_end {
+            state.readers += 1;
+        } else {
+            state.writers += 1;
+        }
+    }
+
+    /// Drop a reference to an end; returns whether both ends are gone
+    fn close_end(&self, read_end: bool) -> bool {
+        let mut state = self.state.lock();
+        if read_end {
+            state.readers = state.readers.saturating_sub(1);
+        } else {
+            state.writers = state.writers.saturating_sub(1);
+        }
+        state.readers == 0 && state.writers == 0
+    }
+
+    /// Get the kernel object base
+    pub fn base(&self) -> &KernelObjectBase {
+        &self.base
+    }
+}
+
+impl Default for Pipe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Pipe Table
+// ============================================================================
+
+/// Pipes with at least one open end
+static PIPES: SpinMutex<BTreeMap<PipeId, Arc<Pipe>>> = SpinMutex::new(BTreeMap::new());
+
+/// Create a pipe and register it
+///
+/// The caller owns one reference to each end; see the module docs.
+pub fn create() -> PipeId {
+    let pipe = Arc::new(Pipe::new());
+    let id = pipe.id;
+    PIPES.lock().insert(id, pipe);
+    id
+}
+
+/// Look up a pipe by ID
+pub fn get(id: PipeId) -> Option<Arc<Pipe>> {
+    PIPES.lock().get(&id).cloned()
+}
+
+/// Count another reference to an end (a descriptor was copied)
+pub fn retain(id: PipeId, read_end: bool) {
+    if let Some(pipe) = PIPES.lock().get(&id) {
+        pipe.open_end(read_end);
+    }
+}
+
+/// Drop a reference to an end (a descriptor was closed)
+///
+/// Frees the pipe once both ends are gone. Callers still holding the
+/// `Arc` from [`get`] keep it alive until they finish.
+pub fn release(id: PipeId, read_end: bool) {
+    let mut pipes = PIPES.lock();
+    if pipes.get(&id).is_some_and(|pipe| pipe.close_end(read_end)) {
+        pipes.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_wraps() {
+        let mut ring = Ring::new(8);
+        assert_eq!(ring.push(b"abcdef"), 6);
+        let mut buf = [0u8; 4];
+        assert_eq!(ring.pop(&mut buf), 4);
+        assert_eq!(&buf, b"abcd");
+        // Wraps around the end of the storage
+        assert_eq!(ring.push(b"ghijklmn"), 6);
+        let mut buf = [0u8; 16];
+        assert_eq!(ring.pop(&mut buf), 8);
+        assert_eq!(&buf[..8], b"efghijkl");
+        assert_eq!(ring.pop(&mut buf), 0);
+    }
+
+    #[test]
+    fn test_pipe_read_write() {
+        let pipe = Pipe::new();
+        let mut buf = [0u8; 8];
+        assert_eq!(pipe.read(&mut buf), Err(ERR_SHOULD_WAIT));
+        assert_eq!(pipe.write(b"hello"), Ok(5));
+        assert_eq!(pipe.buffered(), 5);
+        assert_eq!(pipe.read(&mut buf[..3]), Ok(3));
+        assert_eq!(pipe.read(&mut buf), Ok(2));
+        assert_eq!(&buf[..2], b"lo");
+        assert_eq!(pipe.base().obj_type, ObjectType::Pipe);
+    }
+
+    #[test]
+    fn test_pipe_full_and_atomic() {
+        let pipe = Pipe::new();
+        assert_eq!(pipe.write(&[1u8; PIPE_CAPACITY - 10]), Ok(PIPE_CAPACITY - 10));
+        // A short write waits until it fits whole; a long one takes what fits
+        assert_eq!(pipe.write(&[2u8; 20]), Err(ERR_SHOULD_WAIT));
+        assert_eq!(pipe.write(&[3u8; PIPE_BUF + 1]), Ok(10));
+        assert_eq!(pipe.write(&[4u8; PIPE_BUF + 1]), Err(ERR_SHOULD_WAIT));
+    }
+
+    #[test]
+    fn test_pipe_close_ends() {
+        let id = create();
+        let pipe = get(id).unwrap();
+        pipe.write(b"x").unwrap();
+
+        retain(id, false);
+        release(id, false);
+        let mut buf = [0u8; 4];
+        assert_eq!(pipe.read(&mut buf), Ok(1));
+        assert_eq!(pipe.read(&mut buf), Err(ERR_SHOULD_WAIT));
+
+        // Last writer gone: end of file
+        release(id, false);
+        assert_eq!(pipe.read(&mut buf), Ok(0));
+        assert_eq!(pipe.ends(), (1, 0));
+
+        // Last reader gone: the pipe is freed
+        release(id, true);
+        assert!(get(id).is_none());
+        assert_eq!(pipe.write(b"y"), Err(ERR_PEER_CLOSED));
+    }
+}
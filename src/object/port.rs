@@ -0,0 +1,451 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Ports
+//!
+//! A port is a queue of packets telling a thread which of its objects
+//! were signaled, so one thread can serve any number of objects without
+//! passing them all to `OBJECT_WAIT_MANY` on every wait.
+//!
+//! # Bindings
+//!
+//! `OBJECT_WAIT_ASYNC` binds an object to a port under a key chosen by
+//! the caller: an [`Observer`] in the object's [`KernelObjectBase`]. When
+//! the object is signaled (an event is signaled, a timer fires, a
+//! semaphore gets units, a ring buffer reaches its threshold), every
+//! observer waiting for that signal queues a [`PortPacket`] with its key
+//! on its port, and `PORT_WAIT` takes packets off the queue.
+//!
+//! - A one-shot binding is removed once it has queued a packet; a
+//!   repeating one stays until it is canceled.
+//! - Binding an object that is already signaled queues a packet at once.
+//! - Binding the same port and key again replaces the earlier binding.
+//!
+//! A packet reports a signal, it does not consume it: after a packet for
+//! an auto-reset event, `OBJECT_WAIT_ONE` with a zero deadline takes the
+//! signal.
+//!
+//! Observers hold their port weakly: the port is destroyed with its last
+//! handle, and its bindings go away the next time their objects are
+//! signaled.
+//!
+//! # Queue
+//!
+//! A port holds up to [`PORT_CAPACITY`] packets. A packet for a key that
+//! is already queued is merged into the queued one, so a port holds at
+//! most one packet per key; a packet that finds the queue full is
+//! dropped and counted ([`Port::dropped`]).
+//!
+//! # Interrupt Context
+//!
+//! Timers fire from the timer interrupt, so observer lists and packet
+//! queues are only locked with interrupts disabled, and queuing a packet
+//! never allocates. Objects with no observers skip all of this
+//! ([`Observers::notify`]).
+//!
+//! # Lock Order
+//!
+//! An object's observer list before a port's queue.
+
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::sync::SpinMutex;
+use crate::object::handle::{KernelObjectBase, ObjectType};
+
+// ============================================================================
+// Port ID
+// ============================================================================
+
+/// Port identifier
+pub type PortId = u64;
+
+/// Next port ID counter
+static NEXT_PORT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate a new port ID
+fn alloc_port_id() -> PortId {
+    NEXT_PORT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Packets a port holds at once
+pub const PORT_CAPACITY: usize = 64;
+
+// ============================================================================
+// Packets
+// ============================================================================
+
+/// A signal delivered to a port (`PORT_WAIT` output)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PortPacket {
+    /// Key the object was bound with
+    pub key: u64,
+    /// Signals of the binding that were raised
+    pub trigger: u32,
+    /// Signals the object had when the packet was queued
+    pub observed: u32,
+}
+
+/// Fixed-capacity FIFO of packets
+struct PacketQueue {
+    packets: [PortPacket; PORT_CAPACITY],
+    head: usize,
+    len: usize,
+}
+
+impl PacketQueue {
+    const fn new() -> Self {
+        const EMPTY: PortPacket = PortPacket { key: 0, trigger: 0, observed: 0 };
+        Self { packets: [EMPTY; PORT_CAPACITY], head: 0, len: 0 }
+    }
+
+    fn slot(&self, i: usize) -> usize {
+        (self.head + i) % PORT_CAPACITY
+    }
+
+    /// Queue `packet`, merging it into a queued packet with the same key
+    ///
+    /// # Returns
+    ///
+    /// `false` if the queue is full
+    fn push(&mut self, packet: PortPacket) -> bool {
+        for i in 0..self.len {
+            let slot = self.slot(i);
+            let queued = &mut self.packets[slot];
+            if queued.key == packet.key {
+                queued.trigger |= packet.trigger;
+                queued.observed |= packet.observed;
+                return true;
+            }
+        }
+        if self.len == PORT_CAPACITY {
+            return false;
+        }
+        let slot = self.slot(self.len);
+        self.packets[slot] = packet;
+        self.len += 1;
+        true
+    }
+
+    /// Take the oldest packet
+    fn pop(&mut self) -> Option<PortPacket> {
+        if self.len == 0 {
+            return None;
+        }
+        let packet = self.packets[self.head];
+        self.head = self.slot(1);
+        self.len -= 1;
+        Some(packet)
+    }
+
+    /// Drop the queued packet with `key`, keeping the others in order
+    fn remove(&mut self, key: u64) -> bool {
+        let Some(found) = (0..self.len).find(|&i| self.packets[self.slot(i)].key == key) else {
+            return false;
+        };
+        for i in found..self.len - 1 {
+            self.packets[self.slot(i)] = self.packets[self.slot(i + 1)];
+        }
+        self.len -= 1;
+        true
+    }
+}
+
+/// Run `f` with interrupts disabled
+fn with_ints_disabled<R>(f: impl FnOnce() -> R) -> R {
+    use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
+
+    let were_disabled = arch_ints_disabled();
+    arch_disable_ints();
+    let result = f();
+    if !were_disabled {
+        arch_enable_ints();
+    }
+    result
+}
+
+// ============================================================================
+// Port
+// ============================================================================
+
+/// Port object
+pub struct Port {
+    /// Kernel object base
+    pub base: KernelObjectBase,
+
+    /// Port ID
+    pub id: PortId,
+
+    /// Queued packets (locked with interrupts disabled)
+    queue: SpinMutex<PacketQueue>,
+
+    /// Packets dropped because the queue was full
+    dropped: AtomicU64,
+}
+
+impl Port {
+    /// Create an empty port
+    pub fn create() -> Self {
+        Self {
+            base: KernelObjectBase::new(ObjectType::Port),
+            id: alloc_port_id(),
+            queue: SpinMutex::new(PacketQueue::new()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Get port ID
+    pub const fn id(&self) -> PortId {
+        self.id
+    }
+
+    /// Queue a packet; the caller has interrupts disabled
+    fn push(&self, packet: PortPacket) {
+        if !self.queue.lock().push(packet) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Take the oldest packet, if any
+    pub fn try_dequeue(&self) -> Option<PortPacket> {
+        with_ints_disabled(|| self.queue.lock().pop())
+    }
+
+    /// Drop the queued packet with `key`
+    ///
+    /// # Returns
+    ///
+    /// Whether one was queued
+    pub fn cancel(&self, key: u64) -> bool {
+        with_ints_disabled(|| self.queue.lock().remove(key))
+    }
+
+    /// Number of queued packets
+    pub fn pending(&self) -> usize {
+        with_ints_disabled(|| self.queue.lock().len)
+    }
+
+    /// Packets dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Get the kernel object base
+    pub fn base(&self) -> &KernelObjectBase {
+        &self.base
+    }
+}
+
+// ============================================================================
+// Observers
+// ============================================================================
+
+/// A binding of an object to a port
+pub struct Observer {
+    port: Weak<Port>,
+    key: u64,
+    signals: u32,
+    repeating: bool,
+}
+
+impl Observer {
+    /// Bind to `port` under `key`, waiting for `signals`
+    ///
+    /// A one-shot observer (`repeating` false) is removed after its first
+    /// packet.
+    pub fn new(port: &Arc<Port>, key: u64, signals: u32, repeating: bool) -> Self {
+        Self { port: Arc::downgrade(port), key, signals, repeating }
+    }
+
+    /// Whether this binds `port` under `key`
+    fn is(&self, port: &Arc<Port>, key: u64) -> bool {
+        self.key == key && self.port.as_ptr() == Arc::as_ptr(port)
+    }
+
+    /// Queue a packet if any of `signals` is one this observer waits for
+    ///
+    /// # Returns
+    ///
+    /// Whether the observer stays bound
+    fn observe(&self, signals: u32) -> bool {
+        let trigger = self.signals & signals;
+        if trigger == 0 {
+            return true;
+        }
+        let Some(port) = self.port.upgrade() else { return false };
+        port.push(PortPacket { key: self.key, trigger, observed: signals });
+        self.repeating
+    }
+}
+
+/// Add `observer` to `list`, given the object's current signals
+///
+/// The caller holds the list lock with interrupts disabled.
+fn add_to(list: &mut Vec<Observer>, observer: Observer, signals: u32) {
+    list.retain(|o| o.port.strong_count() > 0 && !(o.key == observer.key && o.port.ptr_eq(&observer.port)));
+    if observer.observe(signals) {
+        list.push(observer);
+    }
+}
+
+/// The ports bound to one object (see [`KernelObjectBase`])
+pub struct Observers {
+    list: SpinMutex<Vec<Observer>>,
+    /// Length of `list`, so signaling an unbound object takes no lock
+    count: AtomicUsize,
+}
+
+impl Observers {
+    /// Create an empty list
+    pub const fn new() -> Self {
+        Self { list: SpinMutex::new(Vec::new()), count: AtomicUsize::new(0) }
+    }
+
+    /// Bind a port
+    ///
+    /// `signals` reads the object's current signals. It is called under
+    /// the list lock, so a signal raised concurrently is seen either by
+    /// it or by [`notify`](Self::notify).
+    pub fn add(&self, observer: Observer, signals: impl FnOnce() -> u32) {
+        with_ints_disabled(|| {
+            let mut list = self.list.lock();
+            add_to(&mut list, observer, signals());
+            self.count.store(list.len(), Ordering::Release);
+        })
+    }
+
+    /// Unbind `port`'s binding under `key`
+    ///
+    /// # Returns
+    ///
+    /// Whether it was bound
+    pub fn cancel(&self, port: &Arc<Port>, key: u64) -> bool {
+        with_ints_disabled(|| {
+            let mut list = self.list.lock();
+            let before = list.len();
+            list.retain(|o| !o.is(port, key));
+            self.count.store(list.len(), Ordering::Release);
+            list.len() != before
+        })
+    }
+
+    /// Queue packets for the observers waiting for any of `signals`, which
+    /// the object has just raised
+    pub fn notify(&self, signals: u32) {
+        if self.count.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        with_ints_disabled(|| {
+            let mut list = self.list.lock();
+            list.retain(|o| o.observe(signals));
+            self.count.store(list.len(), Ordering::Release);
+        })
+    }
+
+    /// Number of bound ports
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Check if no port is bound
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Observers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNALED: u32 = 1 << 0;
+
+    fn packet(key: u64) -> PortPacket {
+        PortPacket { key, trigger: SIGNALED, observed: SIGNALED }
+    }
+
+    fn drain(port: &Port) -> Vec<u64> {
+        let mut queue = port.queue.lock();
+        core::iter::from_fn(|| queue.pop()).map(|p| p.key).collect()
+    }
+
+    #[test]
+    fn test_queue_merges_keys_and_drops_when_full() {
+        let mut queue = PacketQueue::new();
+        assert!(queue.push(packet(1)));
+        assert!(queue.push(packet(2)));
+        assert!(queue.push(PortPacket { key: 1, trigger: 2, observed: 2 }));
+        assert_eq!(queue.len, 2);
+        assert_eq!(queue.pop(), Some(PortPacket { key: 1, trigger: SIGNALED | 2, observed: SIGNALED | 2 }));
+
+        for key in 3..PORT_CAPACITY as u64 + 2 {
+            assert!(queue.push(packet(key)));
+        }
+        assert!(!queue.push(packet(1000)));
+        // Merging needs no room
+        assert!(queue.push(packet(2)));
+
+        assert!(queue.remove(3));
+        assert!(!queue.remove(3));
+        assert_eq!(queue.pop().map(|p| p.key), Some(2));
+        assert_eq!(queue.pop().map(|p| p.key), Some(4));
+    }
+
+    #[test]
+    fn test_one_shot_and_repeating_observers() {
+        let port = Arc::new(Port::create());
+        let mut list = Vec::new();
+        add_to(&mut list, Observer::new(&port, 1, SIGNALED, false), 0);
+        add_to(&mut list, Observer::new(&port, 2, SIGNALED, true), 0);
+
+        list.retain(|o| o.observe(SIGNALED));
+        assert_eq!(drain(&port), [1, 2]);
+        assert_eq!(list.len(), 1);
+
+        // Signals nobody waits for queue nothing
+        list.retain(|o| o.observe(1 << 5));
+        list.retain(|o| o.observe(SIGNALED));
+        assert_eq!(drain(&port), [2]);
+    }
+
+    #[test]
+    fn test_bind_signaled_object_and_rebind() {
+        let port = Arc::new(Port::create());
+        let mut list = Vec::new();
+
+        // Already signaled: a one-shot binding fires at once and is gone
+        add_to(&mut list, Observer::new(&port, 7, SIGNALED, false), SIGNALED);
+        assert!(list.is_empty());
+        assert_eq!(drain(&port), [7]);
+
+        // Binding the same key again replaces the binding
+        add_to(&mut list, Observer::new(&port, 8, SIGNALED, true), 0);
+        add_to(&mut list, Observer::new(&port, 8, SIGNALED, false), 0);
+        assert_eq!(list.len(), 1);
+        assert!(!list[0].repeating);
+    }
+
+    #[test]
+    fn test_closed_port_unbinds() {
+        let port = Arc::new(Port::create());
+        let mut list = Vec::new();
+        add_to(&mut list, Observer::new(&port, 1, SIGNALED, true), 0);
+        drop(port);
+
+        list.retain(|o| o.observe(SIGNALED));
+        assert!(list.is_empty());
+    }
+}
@@ -0,0 +1,480 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Ring Buffer Objects
+//!
+//! A ring buffer streams fixed-size records from a kernel producer to a
+//! userspace consumer through a VMO both sides map, so neither side makes
+//! a syscall per record. The kernel appends records and advances `head`;
+//! the consumer reads them and advances `tail`. Waiting on the ring
+//! (`OBJECT_WAIT_ONE`) returns once `threshold` unread records are queued;
+//! ports bound to the ring get a packet when that many are reached.
+//!
+//! # Layout
+//!
+//! Page 0 of the VMO holds a [`RingHeader`]; records start at
+//! `data_offset`. Record `n` (counting from 0 since creation) is stored at
+//! `data_offset + (n % capacity) * record_size`. The header is part of the
+//! stable ABI: fields are only ever appended, and `version` is bumped when
+//! that happens.
+//!
+//! # Producers
+//!
+//! A ring created for a [`RingSource`] is attached to that kernel
+//! producer, which calls [`publish`] for every record: the keyboard for
+//! [`RingSource::Input`], the scheduler tracepoints for
+//! [`RingSource::Trace`]. When the ring is full new records are dropped
+//! and counted in `dropped`; unread records are never overwritten.
+//! Publishing never blocks or allocates, so it is safe in interrupt
+//! context.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let ring = Arc::new(RingBuffer::create(RingSource::Input, 64, 1)?);
+//! ringbuf::attach(&ring);
+//! // ... keyboard interrupts call ringbuf::publish(RingSource::Input, ..)
+//! assert!(ring.is_signaled());
+//! ```
+
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::object::handle::{KernelObjectBase, ObjectType, OBJECT_SIGNALED};
+use crate::object::vmo::{Vmo, VmoFlags};
+use crate::sync::SpinMutex;
+
+/// Page size of the backing VMO
+const PAGE_SIZE: usize = 4096;
+
+/// Magic value at the start of the header ("RING")
+pub const RING_MAGIC: u32 = 0x474E_4952;
+
+/// Current header version
+pub const RING_VERSION: u32 = 1;
+
+/// Most records a ring can hold
+pub const RING_MAX_CAPACITY: u32 = 1 << 16;
+
+// ============================================================================
+// Ring Buffer ID
+// ============================================================================
+
+/// Ring buffer identifier
+pub type RingBufferId = u64;
+
+/// Next ring buffer ID counter
+static NEXT_RING_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate a new ring buffer ID
+fn alloc_ring_id() -> RingBufferId {
+    NEXT_RING_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// ============================================================================
+// Sources
+// ============================================================================
+
+/// Kernel producer feeding a ring
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingSource {
+    /// Keyboard events ([`InputRecord`](crate::drivers::keyboard::InputRecord))
+    Input = 1,
+    /// Scheduler tracepoints ([`TraceEvent`](crate::trace::TraceEvent))
+    Trace = 2,
+}
+
+impl RingSource {
+    /// Convert from a raw syscall argument
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Input),
+            2 => Some(Self::Trace),
+            _ => None,
+        }
+    }
+
+    /// Size of this source's records in bytes
+    pub const fn record_size(self) -> u32 {
+        match self {
+            Self::Input => core::mem::size_of::<crate::drivers::keyboard::InputRecord>() as u32,
+            Self::Trace => core::mem::size_of::<crate::trace::TraceEvent>() as u32,
+        }
+    }
+}
+
+// ============================================================================
+// Header
+// ============================================================================
+
+/// Ring buffer header (page 0 of the VMO, version 1)
+#[repr(C)]
+pub struct RingHeader {
+    /// [`RING_MAGIC`]
+    pub magic: u32,
+    /// [`RING_VERSION`]
+    pub version: u32,
+    /// Size of each record in bytes
+    pub record_size: u32,
+    /// Number of record slots (a power of two)
+    pub capacity: u32,
+    /// Offset of the first record slot from the start of the VMO
+    pub data_offset: u32,
+    /// Record format ([`RingSource`] value)
+    pub format: u32,
+    /// Records written since creation (advanced by the kernel)
+    pub head: AtomicU64,
+    /// Records consumed since creation (advanced by userspace)
+    pub tail: AtomicU64,
+    /// Records dropped because the ring was full
+    pub dropped: AtomicU64,
+}
+
+const _: () = assert!(core::mem::size_of::<RingHeader>() <= PAGE_SIZE);
+
+// ============================================================================
+// Ring Buffer
+// ============================================================================
+
+/// Ring buffer object
+pub struct RingBuffer {
+    /// Kernel object base
+    pub base: KernelObjectBase,
+
+    /// Ring buffer ID
+    pub id: RingBufferId,
+
+    /// Producer feeding the ring
+    source: RingSource,
+
+    /// Backing memory, shared with userspace
+    vmo: Arc<Vmo>,
+
+    /// Kernel address of each page of the VMO
+    pages: Vec<usize>,
+
+    /// Unread records at which the ring is signaled
+    threshold: u32,
+
+    /// Held while appending a record
+    writer: SpinMutex<()>,
+}
+
+impl RingBuffer {
+    /// Create a ring buffer for `source`
+    ///
+    /// Every page is committed up front so publishing never allocates.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Producer that will feed the ring (sets the record format)
+    /// * `capacity` - Number of records (a power of two, at most [`RING_MAX_CAPACITY`])
+    /// * `threshold` - Unread records at which the ring is signaled (1..=capacity)
+    pub fn create(source: RingSource, capacity: u32, threshold: u32) -> Result<Self, &'static str> {
+        Self::check_args(capacity, threshold)?;
+
+        let size = PAGE_SIZE + capacity as usize * source.record_size() as usize;
+        let vmo = Vmo::create(size, VmoFlags::empty)?;
+        let mut pages = Vec::new();
+        for offset in (0..vmo.size()).step_by(PAGE_SIZE) {
+            let entry = vmo.commit_page(offset)?;
+            pages.push(crate::mm::pmm::paddr_to_vaddr_user_zone(entry.paddr));
+        }
+
+        Ok(Self::from_pages(source, Arc::new(vmo), pages, capacity, threshold))
+    }
+
+    /// Validate the capacity and threshold
+    fn check_args(capacity: u32, threshold: u32) -> Result<(), &'static str> {
+        if !capacity.is_power_of_two() || capacity > RING_MAX_CAPACITY {
+            return Err("ring capacity must be a power of two");
+        }
+        if threshold == 0 || threshold > capacity {
+            return Err("ring threshold out of range");
+        }
+        Ok(())
+    }
+
+    /// Build a ring on already committed, zeroed pages
+    fn from_pages(source: RingSource, vmo: Arc<Vmo>, pages: Vec<usize>, capacity: u32, threshold: u32) -> Self {
+        let ring = Self {
+            base: KernelObjectBase::new(ObjectType::RingBuffer),
+            id: alloc_ring_id(),
+            source,
+            vmo,
+            pages,
+            threshold,
+            writer: SpinMutex::new(()),
+        };
+
+        // SAFETY: page 0 is a whole page, not yet visible to userspace
+        unsafe {
+            let header = ring.pages[0] as *mut RingHeader;
+            (*header).magic = RING_MAGIC;
+            (*header).version = RING_VERSION;
+            (*header).record_size = source.record_size();
+            (*header).capacity = capacity;
+            (*header).data_offset = PAGE_SIZE as u32;
+            (*header).format = source as u32;
+        }
+        ring
+    }
+
+    /// Get ring buffer ID
+    pub const fn id(&self) -> RingBufferId {
+        self.id
+    }
+
+    /// Producer feeding the ring
+    pub const fn source(&self) -> RingSource {
+        self.source
+    }
+
+    /// The VMO holding the header and records
+    pub fn vmo(&self) -> &Arc<Vmo> {
+        &self.vmo
+    }
+
+    /// Unread records at which the ring is signaled
+    pub const fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The shared header
+    pub fn header(&self) -> &RingHeader {
+        // SAFETY: page 0 holds the header for the ring's lifetime
+        unsafe { &*(self.pages[0] as *const RingHeader) }
+    }
+
+    /// Number of unread records
+    ///
+    /// A `tail` written by userspace beyond `head` counts as a full ring.
+    pub fn pending(&self) -> u64 {
+        let header = self.header();
+        let head = header.head.load(Ordering::Acquire);
+        let tail = header.tail.load(Ordering::Acquire);
+        core::cmp::min(head.wrapping_sub(tail), header.capacity as u64)
+    }
+
+    /// Whether at least `threshold` records are unread
+    pub fn is_signaled(&self) -> bool {
+        self.pending() >= self.threshold as u64
+    }
+
+    /// Append a record
+    ///
+    /// `record` is truncated or zero-padded to the record size. Returns
+    /// false, counting the record in `dropped`, if the ring is full or
+    /// another CPU is appending.
+    pub fn push(&self, record: &[u8]) -> bool {
+        let header = self.header();
+        let _writer = match self.writer.try_lock() {
+            Some(guard) => guard,
+            None => {
+                header.dropped.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+        };
+
+        let head = header.head.load(Ordering::Relaxed);
+        if self.pending() >= header.capacity as u64 {
+            header.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let record_size = header.record_size as usize;
+        let slot = (head % header.capacity as u64) as usize;
+        let offset = header.data_offset as usize + slot * record_size;
+        let len = core::cmp::min(record.len(), record_size);
+        self.copy_in(offset, &record[..len]);
+        self.zero(offset + len, record_size - len);
+
+        header.head.store(head + 1, Ordering::Release);
+        if self.pending() == self.threshold as u64 {
+            self.base.notify(OBJECT_SIGNALED);
+        }
+        true
+    }
+
+    /// Copy `data` to the VMO at `offset`
+    fn copy_in(&self, offset: usize, data: &[u8]) {
+        let mut done = 0;
+        while done < data.len() {
+            let at = offset + done;
+            let n = core::cmp::min(data.len() - done, PAGE_SIZE - at % PAGE_SIZE);
+            // SAFETY: `at` lies within the VMO, whose pages are all committed
+            unsafe {
+                let dst = (self.pages[at / PAGE_SIZE] + at % PAGE_SIZE) as *mut u8;
+                core::ptr::copy_nonoverlapping(data.as_ptr().add(done), dst, n);
+            }
+            done += n;
+        }
+    }
+
+    /// Zero `len` bytes of the VMO at `offset`
+    fn zero(&self, offset: usize, len: usize) {
+        let mut done = 0;
+        while done < len {
+            let at = offset + done;
+            let n = core::cmp::min(len - done, PAGE_SIZE - at % PAGE_SIZE);
+            // SAFETY: as in copy_in
+            unsafe {
+                core::ptr::write_bytes((self.pages[at / PAGE_SIZE] + at % PAGE_SIZE) as *mut u8, 0, n);
+            }
+            done += n;
+        }
+    }
+
+    /// Get the kernel object base
+    pub fn base(&self) -> &KernelObjectBase {
+        &self.base
+    }
+}
+
+// ============================================================================
+// Producers
+// ============================================================================
+
+/// Rings attached to kernel producers
+///
+/// Weak references: a ring is detached once its last handle is closed.
+static ATTACHED: SpinMutex<Vec<Weak<RingBuffer>>> = SpinMutex::new(Vec::new());
+
+/// Number of live entries in [`ATTACHED`], so producers skip the lock
+/// when nobody is listening
+static ATTACHED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Attach a ring to its source's producer
+pub fn attach(ring: &Arc<RingBuffer>) {
+    let mut attached = ATTACHED.lock();
+    attached.retain(|r| r.strong_count() > 0);
+    attached.push(Arc::downgrade(ring));
+    ATTACHED_COUNT.store(attached.len(), Ordering::Release);
+}
+
+/// Whether any ring may be listening (cheap check for hot producers)
+#[inline]
+pub fn has_listeners() -> bool {
+    ATTACHED_COUNT.load(Ordering::Acquire) > 0
+}
+
+/// Append a record to every ring attached to `source`
+///
+/// The record is dropped for every ring if the list is being changed.
+pub fn publish(source: RingSource, record: &[u8]) {
+    if !has_listeners() {
+        return;
+    }
+    let Some(attached) = ATTACHED.try_lock() else {
+        return;
+    };
+    for ring in attached.iter().filter_map(Weak::upgrade) {
+        if ring.source == source {
+            ring.push(record);
+        }
+    }
+}
+
+/// View a plain `repr(C)` record as bytes
+///
+/// # Safety
+///
+/// `T` must have no padding bytes.
+pub unsafe fn record_bytes<T>(record: &T) -> &[u8] {
+    core::slice::from_raw_parts(record as *const T as *const u8, core::mem::size_of::<T>())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+
+    #[repr(C, align(4096))]
+    struct Page([u8; PAGE_SIZE]);
+
+    /// A ring on heap pages instead of PMM pages
+    fn test_ring(capacity: u32, threshold: u32) -> RingBuffer {
+        let vmo = Vmo::create(PAGE_SIZE + capacity as usize * 16, VmoFlags::empty).unwrap();
+        let pages = (0..vmo.size() / PAGE_SIZE)
+            .map(|_| Box::leak(Box::new(Page([0; PAGE_SIZE]))) as *mut Page as usize)
+            .collect();
+        RingBuffer::from_pages(RingSource::Input, Arc::new(vmo), pages, capacity, threshold)
+    }
+
+    fn read_slot(ring: &RingBuffer, n: u64) -> [u8; 16] {
+        let header = ring.header();
+        let offset = header.data_offset as usize + (n % header.capacity as u64) as usize * 16;
+        let mut out = [0u8; 16];
+        for (i, b) in out.iter_mut().enumerate() {
+            let at = offset + i;
+            *b = unsafe { *((ring.pages[at / PAGE_SIZE] + at % PAGE_SIZE) as *const u8) };
+        }
+        out
+    }
+
+    #[test]
+    fn test_ring_args() {
+        assert!(RingBuffer::check_args(0, 1).is_err());
+        assert!(RingBuffer::check_args(3, 1).is_err());
+        assert!(RingBuffer::check_args(RING_MAX_CAPACITY * 2, 1).is_err());
+        assert!(RingBuffer::check_args(4, 0).is_err());
+        assert!(RingBuffer::check_args(4, 5).is_err());
+        assert!(RingBuffer::check_args(4, 4).is_ok());
+        assert_eq!(RingSource::Input.record_size(), 16);
+        assert_eq!(RingSource::Trace.record_size(), 32);
+    }
+
+    #[test]
+    fn test_ring_header() {
+        let ring = test_ring(8, 1);
+        let header = ring.header();
+        assert_eq!(header.magic, RING_MAGIC);
+        assert_eq!(header.capacity, 8);
+        assert_eq!(header.record_size, 16);
+        assert_eq!(header.data_offset, PAGE_SIZE as u32);
+        assert_eq!(header.format, RingSource::Input as u32);
+        assert_eq!(ring.base().obj_type, ObjectType::RingBuffer);
+    }
+
+    #[test]
+    fn test_ring_push_and_threshold() {
+        let ring = test_ring(4, 2);
+        assert!(!ring.is_signaled());
+
+        assert!(ring.push(&[1; 16]));
+        assert!(!ring.is_signaled());
+        assert!(ring.push(&[2; 4]));
+        assert!(ring.is_signaled());
+        assert_eq!(read_slot(&ring, 1), [2, 2, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        // Consumer reads both
+        ring.header().tail.store(2, Ordering::Release);
+        assert_eq!(ring.pending(), 0);
+        assert!(!ring.is_signaled());
+    }
+
+    #[test]
+    fn test_ring_full_drops() {
+        let ring = test_ring(4, 1);
+        for i in 0..4 {
+            assert!(ring.push(&[i; 16]));
+        }
+        assert!(!ring.push(&[9; 16]));
+        assert_eq!(ring.header().dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(read_slot(&ring, 0), [0; 16]);
+
+        // Freeing one slot lets the next record wrap around
+        ring.header().tail.store(1, Ordering::Release);
+        assert!(ring.push(&[7; 16]));
+        assert_eq!(read_slot(&ring, 4), [7; 16]);
+        assert_eq!(ring.header().head.load(Ordering::Relaxed), 5);
+    }
+}
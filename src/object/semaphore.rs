@@ -0,0 +1,169 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Semaphore Objects
+//!
+//! A semaphore holds a count of available units, between 0 and a maximum
+//! fixed at creation. Releasing adds units; waiting takes one, blocking
+//! while the count is 0. It is the userspace counterpart of an
+//! auto-reset event that remembers more than one signal, for simple
+//! producer/consumer pairs.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let sem = Semaphore::new(0, 16)?;
+//! sem.release(1)?;
+//! assert!(sem.try_acquire());
+//! assert!(!sem.try_acquire());
+//! ```
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::object::handle::{KernelObjectBase, ObjectType, OBJECT_SIGNALED};
+
+// ============================================================================
+// Semaphore ID
+// ============================================================================
+
+/// Semaphore identifier
+pub type SemaphoreId = u64;
+
+/// Next semaphore ID counter
+static NEXT_SEMAPHORE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate a new semaphore ID
+fn alloc_semaphore_id() -> SemaphoreId {
+    NEXT_SEMAPHORE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// ============================================================================
+// Semaphore
+// ============================================================================
+
+/// Semaphore object
+pub struct Semaphore {
+    /// Kernel object base
+    pub base: KernelObjectBase,
+
+    /// Semaphore ID
+    pub id: SemaphoreId,
+
+    /// Units available
+    count: AtomicU64,
+
+    /// Most units the semaphore can hold
+    max: u64,
+}
+
+impl Semaphore {
+    /// Create a new semaphore
+    ///
+    /// # Arguments
+    ///
+    /// * `initial` - Units available at first
+    /// * `max` - Most units it can hold (at least 1)
+    pub fn new(initial: u64, max: u64) -> Result<Self, &'static str> {
+        if max == 0 {
+            return Err("semaphore maximum must be at least 1");
+        }
+        if initial > max {
+            return Err("initial count above maximum");
+        }
+        Ok(Self {
+            base: KernelObjectBase::new(ObjectType::Semaphore),
+            id: alloc_semaphore_id(),
+            count: AtomicU64::new(initial),
+            max,
+        })
+    }
+
+    /// Get semaphore ID
+    pub const fn id(&self) -> SemaphoreId {
+        self.id
+    }
+
+    /// Units available
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Most units the semaphore can hold
+    pub const fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Whether a wait would succeed now
+    pub fn is_signaled(&self) -> bool {
+        self.count() > 0
+    }
+
+    /// Add `units` units
+    ///
+    /// Returns the previous count. Fails, changing nothing, if the count
+    /// would go above the maximum.
+    pub fn release(&self, units: u64) -> Result<u64, &'static str> {
+        let result = self.count.fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+            count.checked_add(units).filter(|&new| new <= self.max)
+        });
+        if result.is_ok() && units > 0 {
+            self.base.notify(OBJECT_SIGNALED);
+        }
+        result.map_err(|_| "semaphore count would exceed maximum")
+    }
+
+    /// Take one unit if there is one
+    pub fn try_acquire(&self) -> bool {
+        self.count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| count.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Get the kernel object base
+    pub fn base(&self) -> &KernelObjectBase {
+        &self.base
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semaphore_create() {
+        assert!(Semaphore::new(0, 0).is_err());
+        assert!(Semaphore::new(2, 1).is_err());
+
+        let sem = Semaphore::new(1, 4).unwrap();
+        assert_eq!(sem.count(), 1);
+        assert_eq!(sem.max(), 4);
+        assert_eq!(sem.base().obj_type, ObjectType::Semaphore);
+    }
+
+    #[test]
+    fn test_semaphore_counting() {
+        let sem = Semaphore::new(0, 4).unwrap();
+        assert!(!sem.try_acquire());
+
+        assert_eq!(sem.release(2), Ok(0));
+        assert!(sem.is_signaled());
+        assert!(sem.try_acquire());
+        assert!(sem.try_acquire());
+        assert!(!sem.try_acquire());
+    }
+
+    #[test]
+    fn test_semaphore_max() {
+        let sem = Semaphore::new(3, 4).unwrap();
+        assert!(sem.release(2).is_err());
+        assert_eq!(sem.count(), 3);
+        assert_eq!(sem.release(1), Ok(3));
+        assert!(sem.release(u64::MAX).is_err());
+    }
+}
@@ -16,19 +16,28 @@
 //! - **Periodic**: Fire repeatedly at specified interval
 //! - **Slack**: Allow coalescing for power efficiency
 //!
+//! An armed timer sits in the kernel timer queue
+//! ([`crate::time::timer_queue`]), which fires it from the timer
+//! interrupt: its event is signaled, so `OBJECT_WAIT_ONE` on it returns
+//! and bound ports get a packet.
+//! A one-shot timer stays signaled until it is set again or canceled; a
+//! periodic one re-arms itself and each wait consumes one expiry.
+//!
 //! # Usage
 //!
 //! ```rust
-//! let timer = Timer::create()?;
-//! timer.set(deadline, slack)?;
+//! let timer = Arc::new(Timer::create()?);
+//! timer.set(Instant::after(Duration::from_millis(5)), None)?;
 //! timer.wait()?;
 //! ```
 
+use alloc::sync::Arc;
 use core::num::NonZeroU64;
 use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use crate::sync::SpinMutex;
-use crate::object::handle::{KernelObjectBase, ObjectType};
+use crate::object::handle::{KernelObjectBase, ObjectType, OBJECT_SIGNALED};
 use crate::object::event::Event;
+use crate::time::{timer_queue, Duration, Instant};
 
 /// ============================================================================
 /// Timer ID
@@ -122,13 +131,13 @@ impl SlackPolicy {
         self as u32
     }
 
-    /// Get slack duration in nanoseconds
-    pub const fn duration(self) -> u64 {
+    /// Get slack duration
+    pub const fn duration(self) -> Duration {
         match self {
-            Self::None => 0,
-            Self::Small => 100_000,       // 100us
-            Self::Medium => 1_000_000,    // 1ms
-            Self::Large => 10_000_000,    // 10ms
+            Self::None => Duration::ZERO,
+            Self::Small => Duration::from_micros(100),
+            Self::Medium => Duration::from_millis(1),
+            Self::Large => Duration::from_millis(10),
         }
     }
 }
@@ -137,6 +146,13 @@ impl SlackPolicy {
 /// Timer
 /// ============================================================================
 
+/// First deadline of a timer with `period` after `now`, counting from its
+/// previous deadline
+const fn next_period(deadline: u64, period: u64, now: u64) -> u64 {
+    let periods = now.saturating_sub(deadline) / period + 1;
+    deadline.saturating_add(periods.saturating_mul(period))
+}
+
 /// Timer object
 ///
 /// Provides high-resolution timer functionality.
@@ -153,14 +169,14 @@ pub struct Timer {
     /// Timer slack (in nanoseconds)
     pub slack: AtomicU64,
 
-    /// Timer period (None = one-shot, Some = periodic)
-    pub period: SpinMutex<Option<NonZeroU64>>,
+    /// Timer period in nanoseconds (0 = one-shot)
+    pub period: AtomicU64,
 
     /// Timer state
     pub state: AtomicU8,
 
     /// Event signaled when timer fires
-    pub event: SpinMutex<Event>,
+    pub event: Event,
 
     /// Slack policy
     pub slack_policy: SpinMutex<SlackPolicy>,
@@ -176,9 +192,9 @@ impl Timer {
             id: alloc_timer_id(),
             deadline: AtomicU64::new(0),
             slack: AtomicU64::new(0),
-            period: SpinMutex::new(None),
+            period: AtomicU64::new(0),
             state: AtomicU8::new(TimerState::Disarmed as u8),
-            event: SpinMutex::new(Event::new(false, crate::object::event::EventFlags::empty)),
+            event: Event::new(false, crate::object::event::EventFlags::empty),
             slack_policy: SpinMutex::new(SlackPolicy::Small),
         })
     }
@@ -197,43 +213,111 @@ impl Timer {
     ///
     /// # Arguments
     ///
-    /// * `deadline` - Absolute deadline in nanoseconds
-    /// * `slack` - Optional slack duration in nanoseconds
+    /// * `deadline` - Absolute deadline
+    /// * `slack` - Optional slack duration
     ///
     /// If the timer is already armed, this cancels the previous deadline.
-    pub fn set(&self, deadline: u64, slack: Option<u64>) -> Result<(), &'static str> {
-        // Update deadline and slack
-        self.deadline.store(deadline, Ordering::Release);
-        self.slack.store(slack.unwrap_or(0), Ordering::Release);
+    /// With slack, the deadline may move up to `slack` later to fire
+    /// together with another timer; [`deadline`](Self::deadline) reports
+    /// the one in effect. A deadline that has already passed fires at once.
+    pub fn set(self: &Arc<Self>, deadline: Instant, slack: Option<Duration>) -> Result<(), &'static str> {
+        self.period.store(0, Ordering::Release);
+        self.arm(deadline, slack)
+    }
 
-        // Update state
+    /// Arm the timer in the timer queue
+    fn arm(self: &Arc<Self>, deadline: Instant, slack: Option<Duration>) -> Result<(), &'static str> {
+        let slack = slack.unwrap_or(Duration::ZERO);
+        self.slack.store(slack.as_nanos(), Ordering::Release);
+        self.event.unsignal();
         self.state.store(TimerState::Armed as u8, Ordering::Release);
 
-        // Unsignal event
-        self.event.lock().unsignal();
+        let now = Instant::now();
+        if deadline.has_passed(now) {
+            timer_queue::cancel_timer(self.id);
+            self.deadline.store(deadline.as_nanos(), Ordering::Release);
+            return match self.fire(now) {
+                Some(next) => self.requeue(next, slack),
+                None => Ok(()),
+            };
+        }
+        if deadline.is_infinite() {
+            timer_queue::cancel_timer(self.id);
+            self.deadline.store(deadline.as_nanos(), Ordering::Release);
+            return Ok(());
+        }
+        self.requeue(deadline, slack)
+    }
+
+    /// Put the timer in the timer queue at `deadline`
+    fn requeue(self: &Arc<Self>, deadline: Instant, slack: Duration) -> Result<(), &'static str> {
+        match timer_queue::arm(timer_queue::Target::Timer(self.clone()), deadline, slack) {
+            Ok(deadline) => {
+                self.deadline.store(deadline.as_nanos(), Ordering::Release);
+                Ok(())
+            }
+            Err(_) => {
+                self.state.store(TimerState::Disarmed as u8, Ordering::Release);
+                Err("too many armed timers")
+            }
+        }
+    }
 
-        // TODO: Add to global timer queue
+    /// Fire the timer
+    ///
+    /// Called by the timer queue once the deadline has passed. Signals the
+    /// event; a periodic timer moves its deadline to the next period
+    /// after `now` (expiries missed in between are dropped).
+    ///
+    /// # Returns
+    ///
+    /// The next deadline of a periodic timer, to re-arm at
+    pub fn fire(&self, now: Instant) -> Option<Instant> {
+        if self.state() != TimerState::Armed {
+            return None;
+        }
+        let period = self.period.load(Ordering::Acquire);
+        let next = if period == 0 {
+            self.state.store(TimerState::Fired as u8, Ordering::Release);
+            None
+        } else {
+            let deadline = self.deadline.load(Ordering::Acquire);
+            let next = next_period(deadline, period, now.as_nanos());
+            self.deadline.store(next, Ordering::Release);
+            Some(Instant::from_nanos(next))
+        };
+        self.event.signal();
+        self.base.notify(OBJECT_SIGNALED);
+        next
+    }
 
-        Ok(())
+    /// Take an expiry without blocking
+    ///
+    /// A fired one-shot timer stays signaled; each expiry of a periodic
+    /// timer is taken once.
+    pub fn try_acquire(&self) -> bool {
+        if self.period.load(Ordering::Acquire) == 0 {
+            self.event.is_signaled()
+        } else {
+            self.event.try_acquire()
+        }
     }
 
     /// Set a periodic timer
     ///
     /// # Arguments
     ///
-    /// * `deadline` - First deadline in nanoseconds
-    /// * `period` - Period in nanoseconds
-    /// * `slack` - Optional slack duration in nanoseconds
-    pub fn set_periodic(&self, deadline: u64, period: u64, slack: Option<u64>) -> Result<(), &'static str> {
-        if period == 0 {
-            return Err("period cannot be zero");
-        }
+    /// * `deadline` - First deadline
+    /// * `period` - Time between deadlines
+    /// * `slack` - Optional slack duration
+    pub fn set_periodic(self: &Arc<Self>, deadline: Instant, period: Duration, slack: Option<Duration>) -> Result<(), &'static str> {
+        let period = NonZeroU64::new(period.as_nanos()).ok_or("period cannot be zero")?;
 
         // Set period
-        *self.period.lock() = Some(NonZeroU64::new(period).unwrap());
+        self.period.store(period.get(), Ordering::Release);
 
         // Set timer
-        self.set(deadline, slack)
+        self.arm(deadline, slack)
     }
 
     /// Cancel the timer
@@ -255,11 +339,10 @@ impl Timer {
             TimerState::Armed => {
                 // Cancel timer
                 self.state.store(TimerState::Canceled as u8, Ordering::Release);
-
-                // TODO: Remove from global timer queue
+                timer_queue::cancel_timer(self.id);
 
                 // Unsignal event
-                self.event.lock().unsignal();
+                self.event.unsignal();
 
                 Ok(())
             }
@@ -274,7 +357,7 @@ impl Timer {
     /// - Err("canceled") if timer was canceled
     pub fn wait(&self) -> Result<(), &'static str> {
         // Wait on event
-        self.event.lock().wait()?;
+        self.event.wait()?;
 
         // Check if timer was canceled
         if self.state() == TimerState::Canceled {
@@ -285,13 +368,21 @@ impl Timer {
     }
 
     /// Get current deadline
-    pub fn deadline(&self) -> u64 {
-        self.deadline.load(Ordering::Acquire)
+    pub fn deadline(&self) -> Instant {
+        Instant::from_nanos(self.deadline.load(Ordering::Acquire))
     }
 
     /// Get current slack
-    pub fn slack(&self) -> u64 {
-        self.slack.load(Ordering::Acquire)
+    pub fn slack(&self) -> Duration {
+        Duration::from_nanos(self.slack.load(Ordering::Acquire))
+    }
+
+    /// Get the period of a periodic timer
+    pub fn period(&self) -> Option<Duration> {
+        match self.period.load(Ordering::Acquire) {
+            0 => None,
+            p => Some(Duration::from_nanos(p)),
+        }
     }
 
     /// Get the kernel object base
@@ -342,59 +433,86 @@ mod tests {
         assert_eq!(SlackPolicy::from_raw(2), SlackPolicy::Medium);
         assert_eq!(SlackPolicy::from_raw(3), SlackPolicy::Large);
 
-        assert_eq!(SlackPolicy::Small.duration(), 100_000);
-        assert_eq!(SlackPolicy::Medium.duration(), 1_000_000);
-        assert_eq!(SlackPolicy::Large.duration(), 10_000_000);
+        assert_eq!(SlackPolicy::Small.duration().as_nanos(), 100_000);
+        assert_eq!(SlackPolicy::Medium.duration().as_nanos(), 1_000_000);
+        assert_eq!(SlackPolicy::Large.duration().as_nanos(), 10_000_000);
     }
 
     #[test]
     fn test_timer_create() {
         let timer = Timer::create().unwrap();
         assert_eq!(timer.state(), TimerState::Disarmed);
-        assert_eq!(timer.deadline(), 0);
-        assert_eq!(timer.slack(), 0);
+        assert_eq!(timer.deadline(), Instant::ZERO);
+        assert_eq!(timer.slack(), Duration::ZERO);
+    }
+
+    /// A deadline that does not pass during the test
+    fn later() -> Instant {
+        Instant::after(Duration::from_secs(3600))
     }
 
     #[test]
     fn test_timer_set() {
-        let timer = Timer::create().unwrap();
+        let timer = Arc::new(Timer::create().unwrap());
+        let deadline = later();
 
-        timer.set(1_000_000, Some(100)).unwrap();
+        timer.set(deadline, Some(Duration::from_nanos(100))).unwrap();
         assert_eq!(timer.state(), TimerState::Armed);
-        assert_eq!(timer.deadline(), 1_000_000);
-        assert_eq!(timer.slack(), 100);
+        assert_eq!(timer.deadline(), deadline);
+        assert_eq!(timer.slack().as_nanos(), 100);
+        timer.cancel().unwrap();
     }
 
     #[test]
     fn test_timer_cancel() {
-        let timer = Timer::create().unwrap();
+        let timer = Arc::new(Timer::create().unwrap());
 
         // Cannot cancel when not armed
         assert!(timer.cancel().is_err());
 
-        timer.set(1_000_000, None).unwrap();
+        timer.set(later(), None).unwrap();
         assert_eq!(timer.state(), TimerState::Armed);
 
         timer.cancel().unwrap();
         assert_eq!(timer.state(), TimerState::Canceled);
     }
 
+    #[test]
+    fn test_timer_past_deadline_fires() {
+        let timer = Arc::new(Timer::create().unwrap());
+
+        timer.set(Instant::ZERO, None).unwrap();
+        assert_eq!(timer.state(), TimerState::Fired);
+        // A one-shot timer stays signaled
+        assert!(timer.try_acquire());
+        assert!(timer.try_acquire());
+        assert!(timer.cancel().is_err());
+    }
+
     #[test]
     fn test_timer_periodic() {
-        let timer = Timer::create().unwrap();
+        let timer = Arc::new(Timer::create().unwrap());
 
-        timer.set_periodic(1_000_000, 100_000, None).unwrap();
+        timer.set_periodic(later(), Duration::from_micros(100), None).unwrap();
         assert_eq!(timer.state(), TimerState::Armed);
-
-        let period = *timer.period.lock();
-        assert_eq!(period.map(|p| p.get()), Some(100_000));
+        assert_eq!(timer.period(), Some(Duration::from_nanos(100_000)));
+        timer.cancel().unwrap();
     }
 
     #[test]
     fn test_timer_periodic_zero() {
-        let timer = Timer::create().unwrap();
+        let timer = Arc::new(Timer::create().unwrap());
 
         // Period cannot be zero
-        assert!(timer.set_periodic(1_000_000, 0, None).is_err());
+        assert!(timer.set_periodic(later(), Duration::ZERO, None).is_err());
+    }
+
+    #[test]
+    fn test_next_period() {
+        // On time: one period on
+        assert_eq!(next_period(1_000, 100, 1_000), 1_100);
+        // Late by two and a half periods: the missed expiries are dropped
+        assert_eq!(next_period(1_000, 100, 1_250), 1_300);
+        assert_eq!(next_period(u64::MAX - 10, 100, u64::MAX), u64::MAX);
     }
 }
@@ -23,12 +23,42 @@
 //! vmo.write(0, &data)?;
 //! vmo.read(0, &mut buf)?;
 //! ```
+//!
+//! # Copy-on-Write
+//!
+//! A clone shares every page with its parent. Each VMO holding an owned
+//! (writable) page holds one PMM reference to it, so a page with more
+//! than one reference is shared. Shared pages are mapped read-only
+//! everywhere; the first write through either VMO, whether by
+//! [`Vmo::write`] or by a write fault on a mapping, copies just that page
+//! and drops the reference to the original. The last holder writes the
+//! original in place. Read-only pages the VMO does not own (ramdisk,
+//! counters) are shared without references and never copied.
+//!
+//! # Job Accounting
+//!
+//! A VMO belongs to at most one job ([`Vmo::set_job`]). Every page it
+//! allocates, whether committed by a write, a fault or a copy-on-write
+//! copy, is charged to that job first and refused if the job's memory
+//! limit would be exceeded (see [`crate::process::jobs`]). The charge is
+//! given back when the VMO is dropped. Kernel VMOs have no job.
+//!
+//! # Reclaim
+//!
+//! Under memory pressure, owned pages that hold only zeros and have not
+//! been touched for a while are given back to the PMM
+//! ([`Vmo::decommit_page`], driven by [`crate::mm::reclaim`]). The offset
+//! then reads as zeros again and commits a new zero page on the next
+//! touch, as if it had never been committed. Each entry records how long
+//! it has gone unused and whether it may hold data other than zeros.
 
 use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use crate::sync::SpinMutex;
 use crate::object::handle::{KernelObjectBase, ObjectType};
 use crate::arch::amd64::mm::page_tables::PAddr;
-use alloc::collections::BTreeMap;
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::job::{JobId, JOB_ID_INVALID};
+use alloc::collections::{BTreeMap, BTreeSet};
 
 /// ============================================================================
 /// VMO ID
@@ -136,7 +166,7 @@ impl CachePolicy {
 /// ============================================================================
 
 /// Page map entry
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct PageMapEntry {
     /// Physical page address
     pub paddr: PAddr,
@@ -146,12 +176,48 @@ pub struct PageMapEntry {
 
     /// Whether page is writable
     pub writable: bool,
+
+    /// Reclaim scans in a row that found the page unused
+    pub idle: u8,
+
+    /// Whether the page may hold something other than zeros: written
+    /// through the VMO or a mapping, or copied from another page
+    pub dirty: bool,
+}
+
+impl PageMapEntry {
+    /// Entry for a committed page, not yet aged or written
+    pub const fn new(paddr: PAddr, writable: bool) -> Self {
+        Self { paddr, present: true, writable, idle: 0, dirty: false }
+    }
+
+    /// Whether the page is shared copy-on-write with another VMO
+    pub fn is_shared(&self) -> bool {
+        self.writable && crate::mm::pmm::pmm_page_ref_count(self.paddr) > 1
+    }
+
+    /// Whether the page may be mapped writable as it is
+    pub fn map_writable(&self) -> bool {
+        self.writable && !self.is_shared()
+    }
 }
 
 /// ============================================================================
 /// VMO
 /// ============================================================================
 
+/// Bytes charged to a job per page
+const PAGE_BYTES: u64 = 4096;
+
+/// Memory a VMO has charged to its job
+#[derive(Debug, Clone, Copy)]
+struct Charge {
+    /// Job charged ([`JOB_ID_INVALID`] until [`Vmo::set_job`])
+    job: JobId,
+    /// Bytes of the pages the VMO has allocated
+    bytes: u64,
+}
+
 /// Virtual Memory Object
 ///
 /// Represents a contiguous region of physical memory.
@@ -176,6 +242,13 @@ pub struct Vmo {
 
     /// Parent VMO (for COW clones)
     pub parent: SpinMutex<Option<*const Vmo>>,
+
+    /// Job accounting
+    charge: SpinMutex<Charge>,
+
+    /// Offsets whose pages were reclaimed and not committed again, to
+    /// count refaults (lock order: after `pages`)
+    reclaimed: SpinMutex<BTreeSet<usize>>,
 }
 
 impl Vmo {
@@ -202,9 +275,53 @@ impl Vmo {
             cache_policy: SpinMutex::new(CachePolicy::Default),
             pages: SpinMutex::new(BTreeMap::new()),
             parent: SpinMutex::new(None),
+            charge: SpinMutex::new(Charge { job: JOB_ID_INVALID, bytes: 0 }),
+            reclaimed: SpinMutex::new(BTreeSet::new()),
         })
     }
 
+    /// Job the VMO's pages are charged to ([`JOB_ID_INVALID`] if none)
+    pub fn job(&self) -> JobId {
+        self.charge.lock().job
+    }
+
+    /// Charge the VMO's pages to `job`
+    ///
+    /// Pages allocated so far are charged at once, later ones as they are
+    /// allocated. A VMO that already has a job keeps it: memory is charged
+    /// to the first job that owns it, however many processes map it.
+    ///
+    /// # Returns
+    ///
+    /// `Err(ERR_NO_MEMORY)` if the job's memory limit would be exceeded;
+    /// the VMO is then left without a job
+    pub fn set_job(&self, job: JobId) -> Result<(), RxStatus> {
+        let mut charge = self.charge.lock();
+        if charge.job != JOB_ID_INVALID {
+            return Ok(());
+        }
+        crate::process::jobs::charge_memory(job, charge.bytes)?;
+        charge.job = job;
+        Ok(())
+    }
+
+    /// Charge one page to the VMO's job before allocating it
+    fn charge_page(&self) -> Result<(), &'static str> {
+        let mut charge = self.charge.lock();
+        crate::process::jobs::charge_memory(charge.job, PAGE_BYTES)
+            .map_err(|_| "job memory limit exceeded")?;
+        charge.bytes += PAGE_BYTES;
+        Ok(())
+    }
+
+    /// Give back a page charged by [`charge_page`](Self::charge_page)
+    /// that was not kept
+    fn uncharge_page(&self) {
+        let mut charge = self.charge.lock();
+        crate::process::jobs::uncharge_memory(charge.job, PAGE_BYTES);
+        charge.bytes -= PAGE_BYTES;
+    }
+
     /// Get VMO ID
     pub const fn id(&self) -> VmoId {
         self.id
@@ -251,6 +368,8 @@ impl Vmo {
         let end = core::cmp::min(offset + data.len(), size);
         let to_write = &data[..end - offset];
 
+        crate::mm::kasan::check_read(to_write.as_ptr() as usize, to_write.len());
+
         let page_size = 4096;
 
         // Pre-allocate all pages needed for this write operation
@@ -281,16 +400,28 @@ impl Vmo {
         // Second pass: allocate all pages (without holding lock)
         use crate::mm::pmm;
         for key in &pages_to_allocate {
-            let paddr = pmm::pmm_alloc_user_page()
-                .map_err(|_| "Failed to allocate user page")?;
+            self.charge_page()?;
+            let paddr = pmm::pmm_alloc_user_page().map_err(|_| {
+                self.uncharge_page();
+                "Failed to allocate user page"
+            })?;
+
+            // The rest of the page may be mapped before it is ever written
+            unsafe {
+                core::ptr::write_bytes(pmm::paddr_to_vaddr_user_zone(paddr) as *mut u8, 0, page_size);
+            }
 
             // Insert the page into the map (holding lock briefly)
             let mut pages = self.pages.lock();
-            pages.entry(*key).or_insert(PageMapEntry {
-                paddr,
-                present: true,
-                writable: true,
-            });
+            if pages.contains_key(key) {
+                // Committed by a fault in the meantime
+                drop(pages);
+                let _ = pmm::pmm_free_page(paddr);
+                self.uncharge_page();
+                continue;
+            }
+            self.note_recommit(*key);
+            pages.insert(*key, PageMapEntry::new(paddr, true));
         }
 
         // Third pass: write data to pages
@@ -303,17 +434,35 @@ impl Vmo {
             let page_offset = write_offset % page_size;
             let key = page_index * page_size;
 
-            // Get page entry (holding lock briefly)
-            let (page_paddr, page_present) = {
-                let pages = self.pages.lock();
-                let entry = pages.get(&key).unwrap();
-                (entry.paddr, entry.present)
+            // Get page entry (holding lock briefly); reclaim may have
+            // taken a page committed above
+            let entry = self.pages.lock().get(&key).copied();
+            let entry = match entry {
+                Some(entry) => entry,
+                None => self.commit_page(key)?,
             };
+            let (page_present, page_writable) = (entry.present, entry.writable);
 
             if !page_present {
                 return Err("page not present (allocation failed)");
             }
 
+            // Pages the VMO does not own (ramdisk, counters page)
+            if !page_writable {
+                return Err("page is read-only");
+            }
+
+            // Shared with a clone: write to a private copy
+            let page_paddr = self.commit_page_for_write(key)?.paddr;
+
+            // Copy under the page map lock, so reclaim cannot take the page
+            let mut pages = self.pages.lock();
+            let Some(entry) = pages.get_mut(&key).filter(|e| e.paddr == page_paddr) else {
+                // Reclaimed since it was committed: commit it again
+                continue;
+            };
+            entry.dirty = true;
+
             // Calculate how much to write to this page
             let remaining = to_write.len() - data_offset;
             let space_in_page = page_size - page_offset;
@@ -330,6 +479,7 @@ impl Vmo {
                 core::ptr::copy_nonoverlapping(src, dst, to_copy);
             }
 
+            drop(pages);
             data_offset += to_copy;
             bytes_written += to_copy;
         }
@@ -337,6 +487,125 @@ impl Vmo {
         Ok(bytes_written)
     }
 
+    /// Commit the page at `offset`, allocating a zeroed page if needed
+    ///
+    /// Used by the page fault handler to back a mapping on first touch.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Byte offset within VMO (rounded down to a page)
+    ///
+    /// # Returns
+    ///
+    /// The page's entry, which may be read-only
+    pub fn commit_page(&self, offset: usize) -> Result<PageMapEntry, &'static str> {
+        let page_size = 4096;
+        let key = offset / page_size * page_size;
+
+        if key >= self.size() {
+            return Err("offset out of bounds");
+        }
+
+        if let Some(entry) = self.pages.lock().get(&key) {
+            return if entry.present { Ok(*entry) } else { Err("page not present") };
+        }
+
+        // Allocate without holding the lock, as in write()
+        use crate::mm::pmm;
+        self.charge_page()?;
+        let paddr = pmm::pmm_alloc_user_page().map_err(|_| {
+            self.uncharge_page();
+            "Failed to allocate user page"
+        })?;
+        unsafe {
+            core::ptr::write_bytes(pmm::paddr_to_vaddr_user_zone(paddr) as *mut u8, 0, page_size);
+        }
+
+        let mut pages = self.pages.lock();
+        if let Some(entry) = pages.get(&key) {
+            // Lost a race with another committer
+            let entry = *entry;
+            drop(pages);
+            let _ = pmm::pmm_free_page(paddr);
+            self.uncharge_page();
+            return Ok(entry);
+        }
+        self.note_recommit(key);
+        let entry = PageMapEntry::new(paddr, true);
+        pages.insert(key, entry);
+        Ok(entry)
+    }
+
+    /// Count a refault if the page at `key` was reclaimed before
+    fn note_recommit(&self, key: usize) {
+        if self.reclaimed.lock().remove(&key) {
+            crate::mm::reclaim::record_refault();
+        }
+    }
+
+    /// Give the page at `key` back to the PMM
+    ///
+    /// For [`crate::mm::reclaim`], which holds the page map lock
+    /// (`pages`), has unmapped the page everywhere and checked that it
+    /// holds only zeros. The page's charge goes back to the job; the next
+    /// touch of the offset commits a new zero page.
+    ///
+    /// # Returns
+    ///
+    /// False, doing nothing, unless `key` holds a page the VMO owns alone
+    pub fn decommit_page(&self, pages: &mut BTreeMap<usize, PageMapEntry>, key: usize) -> bool {
+        match pages.get(&key) {
+            Some(entry) if entry.present && entry.writable && !entry.is_shared() => {}
+            _ => return false,
+        }
+        if let Some(entry) = pages.remove(&key) {
+            crate::mm::pmm::pmm_page_unref(entry.paddr);
+            self.uncharge_page();
+            self.reclaimed.lock().insert(key);
+        }
+        true
+    }
+
+    /// Commit the page at `offset` for writing
+    ///
+    /// Like [`commit_page`](Self::commit_page), but a page shared with a
+    /// clone is first replaced by a private copy.
+    ///
+    /// # Returns
+    ///
+    /// The page's entry, or an error if the page is read-only
+    pub fn commit_page_for_write(&self, offset: usize) -> Result<PageMapEntry, &'static str> {
+        let page_size = 4096;
+        let key = offset / page_size * page_size;
+
+        let entry = self.commit_page(key)?;
+        if !entry.writable {
+            return Err("page is read-only");
+        }
+        if !entry.is_shared() {
+            return Ok(entry);
+        }
+
+        use crate::mm::pmm;
+        self.charge_page()?;
+        let paddr = pmm::pmm_alloc_user_page().map_err(|_| {
+            self.uncharge_page();
+            "Failed to allocate user page"
+        })?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                pmm::paddr_to_vaddr_user_zone(entry.paddr) as *const u8,
+                pmm::paddr_to_vaddr_user_zone(paddr) as *mut u8,
+                page_size,
+            );
+        }
+
+        let copy = PageMapEntry { dirty: entry.dirty, ..PageMapEntry::new(paddr, true) };
+        self.pages.lock().insert(key, copy);
+        pmm::pmm_page_unref(entry.paddr);
+        Ok(copy)
+    }
+
     /// Read data from the VMO
     ///
     /// # Arguments
@@ -353,6 +622,8 @@ impl Vmo {
         let end = core::cmp::min(offset + buf.len(), size);
         let to_read = end - offset;
 
+        crate::mm::kasan::check_write(buf.as_ptr() as usize, to_read);
+
         let page_size = 4096;
         let pages = self.pages.lock();
         let mut bytes_read = 0;
@@ -401,73 +672,37 @@ impl Vmo {
 
     /// Clone the VMO (copy-on-write)
     ///
+    /// Committed pages are shared, not copied. The parent's existing
+    /// mappings of them are made read-only, so a write from either side
+    /// faults and gets its own copy. Waits for other CPUs to flush their
+    /// TLBs, so call with no lock held.
+    ///
     /// # Returns
     ///
     /// New VMO that shares pages with parent
     pub fn clone(&self) -> Result<Self, &'static str> {
-        let cloned = Self::create(self.size(), VmoFlags::empty)?;
+        let cloned = Self::create(self.size(), VmoFlags::COW)?;
 
-        // Copy all pages from parent to child
         {
             let parent_pages = self.pages.lock();
             let mut child_pages = cloned.pages.lock();
 
             for (offset, page_entry) in parent_pages.iter() {
-                if page_entry.present {
-                    unsafe {
-                        let msg = b"[VMO] Before PMM alloc for clone\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    // Allocate a new physical page for the child from user zone
-                    use crate::mm::pmm;
-                    let new_paddr = pmm::pmm_alloc_user_page()
-                        .map_err(|_| "Failed to allocate page for clone")?;
-
-                    unsafe {
-                        let msg = b"[VMO] After PMM alloc - checking heap integrity\n";
-                        for &byte in msg {
-                            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") byte, options(nomem, nostack));
-                        }
-                    }
-
-                    // Copy the page data using small chunks to avoid stack overflow
-                    // Use a 256-byte buffer instead of 4KB to fit within kernel stack
-                    let chunk_size = 256usize;
-                    let page_size = 4096usize;
-                    let mut offset_in_page = 0usize;
-
-                    while offset_in_page < page_size {
-                        let mut buffer = [0u8; 256];
-                        let bytes_to_copy = core::cmp::min(chunk_size, page_size - offset_in_page);
-
-                        unsafe {
-                            // Copy from source to buffer
-                            let src_vaddr = pmm::paddr_to_vaddr_user_zone(page_entry.paddr + offset_in_page as u64);
-                            let src_ptr = src_vaddr as *const u8;
-                            core::ptr::copy_nonoverlapping(src_ptr, buffer.as_mut_ptr(), bytes_to_copy);
-
-                            // Copy from buffer to destination
-                            let dst_vaddr = pmm::paddr_to_vaddr_user_zone(new_paddr + offset_in_page as u64);
-                            let dst_ptr = dst_vaddr as *mut u8;
-                            core::ptr::copy_nonoverlapping(buffer.as_ptr(), dst_ptr, bytes_to_copy);
-                        }
-
-                        offset_in_page += bytes_to_copy;
-                    }
-
-                    // Add the page to the child
-                    child_pages.insert(*offset, PageMapEntry {
-                        paddr: new_paddr,
-                        present: true,
-                        writable: true,
-                    });
+                if !page_entry.present {
+                    continue;
+                }
+                // One reference per VMO holding an owned page
+                if page_entry.writable
+                    && crate::mm::pmm::pmm_page_ref(page_entry.paddr) != crate::arch::amd64::mm::RxStatus::OK
+                {
+                    return Err("Failed to share page for clone");
                 }
+                child_pages.insert(*offset, *page_entry);
             }
         } // Locks are released here
 
+        crate::process::vmar::write_protect_vmo(self);
+
         Ok(cloned)
     }
 
@@ -504,6 +739,25 @@ impl Vmo {
     }
 }
 
+impl Drop for Vmo {
+    /// Release the references held on owned pages, and the job's charge
+    fn drop(&mut self) {
+        for entry in self.pages.lock().values() {
+            if entry.present && entry.writable {
+                crate::mm::pmm::pmm_page_unref(entry.paddr);
+            }
+        }
+        let charge = *self.charge.lock();
+        crate::process::jobs::uncharge_memory(charge.job, charge.bytes);
+    }
+}
+
+// The parent pointer is never set (clones share pages by reference count
+// instead) and everything else is behind atomics or locks, so VMOs can be
+// shared through handle tables.
+unsafe impl Send for Vmo {}
+unsafe impl Sync for Vmo {}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -569,6 +823,18 @@ mod tests {
         assert!(vmo.resize(0x2000).is_err());
     }
 
+    #[test]
+    fn test_decommit_needs_owned_page() {
+        let vmo = Vmo::create(0x2000, VmoFlags::empty).unwrap();
+        let mut pages = vmo.pages.lock();
+        assert!(!vmo.decommit_page(&mut pages, 0));
+
+        // Not owned by the VMO: left alone
+        pages.insert(0x1000, PageMapEntry::new(0x5000, false));
+        assert!(!vmo.decommit_page(&mut pages, 0x1000));
+        assert!(pages.contains_key(&0x1000));
+    }
+
     #[test]
     fn test_vmo_write_read() {
         let vmo = Vmo::create(0x1000, VmoFlags::empty()).unwrap();
@@ -599,4 +865,27 @@ mod tests {
         assert!(child.flags.is_cow());
         assert_eq!(child.size(), parent.size());
     }
+
+    #[test]
+    fn test_vmo_job_memory_limit() {
+        use crate::object::{ResourceLimits, JOB_ID_ROOT};
+        use crate::process::jobs;
+
+        let job = 9101;
+        jobs::register(job, JOB_ID_ROOT);
+        jobs::set_limits(job, &ResourceLimits { max_memory: 0x2000, ..ResourceLimits::unlimited() });
+
+        let vmo = Vmo::create(0x3000, VmoFlags::empty).unwrap();
+        vmo.write(0, &[1]).unwrap();
+        // Pages committed before the job was set are charged to it
+        vmo.set_job(job).unwrap();
+        assert_eq!(jobs::account(job).memory, 0x1000);
+
+        vmo.commit_page(0x1000).unwrap();
+        assert!(vmo.commit_page(0x2000).is_err());
+        assert_eq!(jobs::account(job).memory, 0x2000);
+
+        drop(vmo);
+        assert_eq!(jobs::account(job).memory, 0);
+    }
 }
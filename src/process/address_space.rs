@@ -13,6 +13,7 @@
 
 use core::sync::atomic::{AtomicU64, Ordering};
 use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 use crate::sync::SpinMutex;
 use crate::object::{Vmo, VmoId};
 
@@ -20,6 +21,7 @@ use crate::arch::amd64::mm::page_tables::{
     X86PageTableBase, PageTableEntry, PageTableRole, PageTableLevel,
     PAddr, VAddr, pt_entry_t,
 };
+use crate::{kerror, ktrace};
 
 // Page size
 const PAGE_SIZE: usize = 4096;
@@ -41,6 +43,78 @@ fn pt_index(vaddr: VAddr) -> usize {
     (vaddr >> 12) & 0x1FF
 }
 
+/// How a virtual address is mapped, as seen by user mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserAccess {
+    /// Not mapped (yet): a demand-paged region, or nothing at all
+    Unmapped,
+    /// Mapped with the user bit at every level
+    User,
+    /// Mapped for the kernel only
+    Supervisor,
+}
+
+/// Check how `vaddr` is mapped in a page table
+///
+/// Follows 1 GB and 2 MB pages as well as 4 KB pages. User mode can only
+/// reach a page if every entry on the way down has the user bit set.
+///
+/// # Safety
+///
+/// `page_table` must be the physical address of a live PML4.
+pub unsafe fn user_access(page_table: PAddr, vaddr: u64) -> UserAccess {
+    walk_user(page_table, vaddr).0
+}
+
+/// Translate `vaddr` if user mode can reach it
+///
+/// # Returns
+///
+/// The physical address, or None unless [`user_access`] is `User`
+///
+/// # Safety
+///
+/// `page_table` must be the physical address of a live PML4.
+pub unsafe fn user_translate(page_table: PAddr, vaddr: u64) -> Option<PAddr> {
+    match walk_user(page_table, vaddr) {
+        (UserAccess::User, paddr) => Some(paddr),
+        _ => None,
+    }
+}
+
+/// Walk a page table for `vaddr` as user mode would
+///
+/// # Returns
+///
+/// How `vaddr` is mapped, and the physical address if it is (0 otherwise)
+unsafe fn walk_user(page_table: PAddr, vaddr: u64) -> (UserAccess, PAddr) {
+    const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+    const PRESENT: u64 = 1;
+    const USER: u64 = 1 << 2;
+    const LARGE: u64 = 1 << 7;
+    /// Offset bits of a leaf at each level (1 GB, 2 MB, 4 KB pages)
+    const OFFSET_MASK: [u64; 4] = [0, 0x3FFF_FFFF, 0x1F_FFFF, 0xFFF];
+
+    let va = vaddr as usize;
+    let mut table = crate::mm::pmm::paddr_to_vaddr(page_table) as *const pt_entry_t;
+    for (level, index) in [pml4_index(va), pdp_index(va), pd_index(va), pt_index(va)].into_iter().enumerate() {
+        let entry = *table.add(index);
+        if entry & PRESENT == 0 {
+            return (UserAccess::Unmapped, 0);
+        }
+        if entry & USER == 0 {
+            return (UserAccess::Supervisor, 0);
+        }
+        // The PML4 has no large pages; a leaf PTE ends the walk anyway
+        if level == 3 || (level > 0 && entry & LARGE != 0) {
+            let offset = OFFSET_MASK[level];
+            return (UserAccess::User, (entry & ADDR_MASK & !offset) | (vaddr & offset));
+        }
+        table = crate::mm::pmm::paddr_to_vaddr(entry & ADDR_MASK) as *const pt_entry_t;
+    }
+    unreachable!("the walk ends at the PTE level")
+}
+
 /// Mapping information for a VMO in this address space
 struct VmoMapping {
     /// VMO being mapped
@@ -78,6 +152,36 @@ fn alloc_as_id() -> u64 {
     unsafe { NEXT_AS_ID.fetch_add(1, Ordering::Relaxed) }
 }
 
+/// Page-table pages owned by each process PML4
+///
+/// Only PML4s made by [`AddressSpace::new`] are keys. Their lower-level
+/// tables mix pages allocated for the process with tables shared with the
+/// kernel (copied PML4 entries), so the pages a process owns are recorded
+/// as they are allocated rather than found by walking the tables.
+static OWNED_TABLES: SpinMutex<BTreeMap<PAddr, Vec<PAddr>>> = SpinMutex::new(BTreeMap::new());
+
+/// Free a process page table
+///
+/// Frees the PML4 and every table page allocated for it. The pages it
+/// maps belong to VMOs and are left alone. Does nothing for a page table
+/// that [`AddressSpace::new`] did not create.
+///
+/// # Safety
+///
+/// The page table must not be loaded in CR3 on any CPU, and nothing may
+/// map into it afterwards.
+pub unsafe fn destroy(page_table: PAddr) {
+    use crate::mm::pmm;
+
+    let owned = OWNED_TABLES.lock().remove(&page_table);
+    if let Some(tables) = owned {
+        for paddr in tables {
+            let _ = pmm::pmm_free_page(paddr);
+        }
+        let _ = pmm::pmm_free_page(page_table);
+    }
+}
+
 impl AddressSpace {
     /// Create a new address space
     ///
@@ -137,6 +241,8 @@ impl AddressSpace {
             }
         }
 
+        OWNED_TABLES.lock().insert(pml4_paddr, Vec::new());
+
         Ok(Self {
             id: alloc_as_id(),
             page_table,
@@ -145,6 +251,34 @@ impl AddressSpace {
         })
     }
 
+    /// Wrap an existing page table
+    ///
+    /// Used by syscalls that need to add mappings to the calling process,
+    /// whose page table is only known by its CR3 value.
+    ///
+    /// # Safety
+    ///
+    /// `phys` must be the physical address of a live PML4 that outlives
+    /// the returned address space.
+    pub unsafe fn from_page_table(phys: PAddr) -> Self {
+        use crate::mm::pmm;
+
+        let page_table = X86PageTableBase {
+            phys,
+            virt: pmm::paddr_to_vaddr(phys) as *mut pt_entry_t,
+            pages: 1,
+            role: PageTableRole::Independent,
+            num_references: 0,
+        };
+
+        Self {
+            id: alloc_as_id(),
+            page_table,
+            mappings: SpinMutex::new(BTreeMap::new()),
+            ref_count: AtomicU64::new(1),
+        }
+    }
+
     /// Get address space ID
     pub fn id(&self) -> u64 {
         self.id
@@ -152,6 +286,11 @@ impl AddressSpace {
 
     /// Map a VMO into this address space
     ///
+    /// Only pages the VMO has committed are mapped now. The others are
+    /// left unmapped and committed by the page fault handler on first
+    /// touch, provided the caller records the mapping in the process
+    /// [`Vmar`](super::vmar::Vmar).
+    ///
     /// # Arguments
     ///
     /// * `vmo` - VMO to map
@@ -172,30 +311,7 @@ impl AddressSpace {
     ) -> Result<(), &'static str> {
         // Validate alignment
         if vaddr & 0xFFF != 0 {
-            unsafe {
-                let msg = b"[MAP] ALIGN FAIL vaddr=0x";
-                for &b in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-                let mut n = vaddr;
-                let mut buf = [0u8; 16];
-                let mut i = 0;
-                loop {
-                    let digit = (n & 0xF) as u8;
-                    buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                    n >>= 4;
-                    i += 1;
-                    if n == 0 { break; }
-                }
-                while i > 0 {
-                    i -= 1;
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-                }
-                let msg = b"\n";
-                for &b in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-            }
+            kerror!("[MAP] ALIGN FAIL vaddr={:#x}", vaddr);
             return Err("Virtual address not page-aligned");
         }
 
@@ -212,19 +328,20 @@ impl AddressSpace {
             // Get the physical page from the VMO
             let page_entry = vmo_pages.get(&page_offset);
 
-            let paddr = match page_entry {
-                Some(entry) => {
-                    if !entry.present {
-                        return Err("VMO page not present");
+            let entry = match page_entry {
+                Some(entry) if entry.present => {
+                    if !entry.writable && flags & 0x2 != 0 {
+                        return Err("VMO page is read-only");
                     }
-                    entry.paddr
-                }
-                None => {
-                    return Err("VMO page not present");
+                    entry
                 }
+                // Demand paged
+                _ => continue,
             };
 
-            self.map_page(page_vaddr as u64, paddr, flags)?;
+            // Copy-on-write pages are made writable by the fault handler
+            let page_flags = if entry.map_writable() { flags } else { flags & !0x2 };
+            self.map_page(page_vaddr as u64, entry.paddr, page_flags)?;
         }
         // Lock is released here
 
@@ -251,7 +368,7 @@ impl AddressSpace {
     /// * `vaddr` - Virtual address (must be page-aligned)
     /// * `paddr` - Physical address (must be page-aligned)
     /// * `flags` - Page flags (PF_R, PF_W, PF_X)
-    fn map_page(&self, vaddr: u64, paddr: PAddr, flags: u32) -> Result<(), &'static str> {
+    pub(crate) fn map_page(&self, vaddr: u64, paddr: PAddr, flags: u32) -> Result<(), &'static str> {
         // Helper: get virtual address of a page table from a PML4/PDP/PD/PT entry
         // CRITICAL: Always call this AFTER updating the parent entry, never cache and reuse!
         unsafe fn table_from_entry(entry: u64) -> *mut pt_entry_t {
@@ -259,15 +376,8 @@ impl AddressSpace {
             crate::mm::pmm::paddr_to_vaddr(paddr) as *mut pt_entry_t
         }
 
-        // Debug output helper
-        unsafe fn debug_msg(msg: &[u8]) {
-            for &b in msg {
-                core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-            }
-        }
-
         unsafe {
-            debug_msg(b"[MAP-P] Starting map_page\n");
+            ktrace!("[MAP-P] Starting map_page");
 
             let pml4 = self.page_table.virt;
 
@@ -277,7 +387,7 @@ impl AddressSpace {
             let pd_idx = pd_index(vaddr as usize);
             let pt_idx = pt_index(vaddr as usize);
 
-            debug_msg(b"[MAP-P] About to check PML4 entry\n");
+            ktrace!("[MAP-P] About to check PML4 entry");
 
             // CRITICAL: Check if this PML4 entry is from the kernel
             // If so, we MUST NOT reuse it - allocate a new process-specific PDP
@@ -301,7 +411,7 @@ impl AddressSpace {
 
                 if is_kernel_entry {
                     // This is a kernel PML4 entry - allocate new process-specific PDP
-                    debug_msg(b"[MAP-P] Kernel PML4 entry, allocating process-specific PDP\n");
+                    ktrace!("[MAP-P] Kernel PML4 entry, allocating process-specific PDP");
                     let new_pdp = self.alloc_page_table();
                     if new_pdp == 0 { return Err("Failed to allocate page table"); }
                     let new_pdp_vaddr = table_from_entry(new_pdp);
@@ -315,17 +425,17 @@ impl AddressSpace {
 
                     // Update PML4 to point to new process-specific PDP
                     *pml4.add(pml4_idx) = (new_pdp | 7); // Present + Writable + User
-                    debug_msg(b"[MAP-P] Process PDP allocated and installed\n");
+                    ktrace!("[MAP-P] Process PDP allocated and installed");
                 } else if (process_pml4_entry & 1) == 0 {
                     // Empty PML4 entry - allocate new PDP
-                    debug_msg(b"[MAP-P] PML4 entry empty, allocating new PDP\n");
+                    ktrace!("[MAP-P] PML4 entry empty, allocating new PDP");
                     let new_pdp = self.alloc_page_table();
                     if new_pdp == 0 { return Err("Failed to allocate page table"); }
                     let new_pdp_vaddr = table_from_entry(new_pdp);
 
                     // Check if kernel has a PDP at this index to copy
                     if kernel_pml4_entry & 1 != 0 {
-                        debug_msg(b"[MAP-P] Kernel PDP found, copying entries\n");
+                        ktrace!("[MAP-P] Kernel PDP found, copying entries");
                         let kernel_pdp_vaddr = table_from_entry(kernel_pml4_entry);
                         for i in 0..512 {
                             let entry = *kernel_pdp_vaddr.add(i);
@@ -338,17 +448,17 @@ impl AddressSpace {
                         }
                     }
                     *pml4.add(pml4_idx) = (new_pdp | 7);
-                    debug_msg(b"[MAP-P] New PDP allocated and installed\n");
+                    ktrace!("[MAP-P] New PDP allocated and installed");
                 } else {
                     // Process-specific PML4 entry already exists, reuse it
-                    debug_msg(b"[MAP-P] Process PDP exists, reusing\n");
+                    ktrace!("[MAP-P] Process PDP exists, reusing");
                 }
             }
 
             // CRITICAL: Re-read PML4 entry after potential update
             let pdp = table_from_entry(*pml4.add(pml4_idx));
 
-            debug_msg(b"[MAP-P] About to check PDP entry\n");
+            ktrace!("[MAP-P] About to check PDP entry");
 
             // CRITICAL: Check if this PD entry is from the kernel
             // If so, we MUST NOT reuse it - allocate a new process-specific PD
@@ -375,7 +485,7 @@ impl AddressSpace {
 
                 if is_kernel_entry {
                     // This is a kernel PD entry - allocate new process-specific PD
-                    debug_msg(b"[MAP-P] Kernel PD entry, allocating process-specific PD\n");
+                    ktrace!("[MAP-P] Kernel PD entry, allocating process-specific PD");
                     let new_pd = self.alloc_page_table();
                     if new_pd == 0 { return Err("Failed to allocate page table"); }
                     let new_pd_vaddr = table_from_entry(new_pd);
@@ -389,17 +499,17 @@ impl AddressSpace {
 
                     // Update PDP to point to new process-specific PD
                     *pdp.add(pdp_idx) = (new_pd | 7);
-                    debug_msg(b"[MAP-P] Process PD allocated and installed\n");
+                    ktrace!("[MAP-P] Process PD allocated and installed");
                 } else if (process_pd_entry & 1) == 0 {
                     // Empty PD entry - allocate new PD
-                    debug_msg(b"[MAP-P] PD entry empty, allocating new PD\n");
+                    ktrace!("[MAP-P] PD entry empty, allocating new PD");
                     let new_pd = self.alloc_page_table();
                     if new_pd == 0 { return Err("Failed to allocate page table"); }
                     let new_pd_vaddr = table_from_entry(new_pd);
 
                     // Check if kernel has a PD at this index to copy
                     if kernel_pd_entry & 1 != 0 {
-                        debug_msg(b"[MAP-P] Kernel PD found, copying entries\n");
+                        ktrace!("[MAP-P] Kernel PD found, copying entries");
                         let kernel_pd_vaddr = table_from_entry(kernel_pd_entry);
                         for i in 0..512 {
                             let entry = *kernel_pd_vaddr.add(i);
@@ -412,22 +522,22 @@ impl AddressSpace {
                         }
                     }
                     *pdp.add(pdp_idx) = (new_pd | 7);
-                    debug_msg(b"[MAP-P] New PD allocated and installed\n");
+                    ktrace!("[MAP-P] New PD allocated and installed");
                 } else {
                     // Process-specific PD entry already exists, reuse it
-                    debug_msg(b"[MAP-P] Process PD exists, reusing\n");
+                    ktrace!("[MAP-P] Process PD exists, reusing");
                 }
             }
 
             // CRITICAL: Re-read PDP entry after potential update
             let pd = table_from_entry(*pdp.add(pdp_idx));
 
-            debug_msg(b"[MAP-P] About to check PD entry\n");
+            ktrace!("[MAP-P] About to check PD entry");
 
             // Get or create PT entry - allocate if empty, preserve if exists
             if (*pd.add(pd_idx) & 1) == 0 {
                 // Allocate new PT for userspace mapping
-                debug_msg(b"[MAP-P] PD entry empty, allocating new PT\n");
+                ktrace!("[MAP-P] PD entry empty, allocating new PT");
                 let new_pt = self.alloc_page_table();
                 if new_pt == 0 { return Err("Failed to allocate page table"); }
                 let new_pt_vaddr = table_from_entry(new_pt);
@@ -437,13 +547,13 @@ impl AddressSpace {
                 }
                 *pd.add(pd_idx) = (new_pt | 7);
             } else {
-                debug_msg(b"[MAP-P] PD user PT exists, reusing\n");
+                ktrace!("[MAP-P] PD user PT exists, reusing");
             }
 
             // CRITICAL: Re-read PD entry after potential update
             let pt = table_from_entry(*pd.add(pd_idx));
 
-            debug_msg(b"[MAP-P] About to write final PT entry\n");
+            ktrace!("[MAP-P] About to write final PT entry");
 
             // Set the final page table entry
             let mut pt_entry = paddr | 1; // Present
@@ -463,24 +573,191 @@ impl AddressSpace {
 
             *pt.add(pt_idx) = pt_entry;
 
-            debug_msg(b"[MAP-P] map_page complete\n");
+            ktrace!("[MAP-P] map_page complete");
 
             Ok(())
         }
     }
 
+    /// Translate a virtual address
+    ///
+    /// Follows 1 GB and 2 MB pages as well as 4 KB pages.
+    ///
+    /// # Returns
+    ///
+    /// The physical address `vaddr` maps to, or None if it is unmapped
+    pub fn translate(&self, vaddr: u64) -> Option<PAddr> {
+        const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+        const PRESENT: u64 = 1;
+        const LARGE: u64 = 1 << 7;
+
+        let va = vaddr as usize;
+        unsafe {
+            let pml4e = *self.page_table.virt.add(pml4_index(va));
+            if pml4e & PRESENT == 0 {
+                return None;
+            }
+            let pdp = crate::mm::pmm::paddr_to_vaddr(pml4e & ADDR_MASK) as *const pt_entry_t;
+
+            let pdpe = *pdp.add(pdp_index(va));
+            if pdpe & PRESENT == 0 {
+                return None;
+            }
+            if pdpe & LARGE != 0 {
+                return Some((pdpe & ADDR_MASK & !0x3FFF_FFFF) + (vaddr & 0x3FFF_FFFF));
+            }
+            let pd = crate::mm::pmm::paddr_to_vaddr(pdpe & ADDR_MASK) as *const pt_entry_t;
+
+            let pde = *pd.add(pd_index(va));
+            if pde & PRESENT == 0 {
+                return None;
+            }
+            if pde & LARGE != 0 {
+                return Some((pde & ADDR_MASK & !0x1F_FFFF) + (vaddr & 0x1F_FFFF));
+            }
+            let pt = crate::mm::pmm::paddr_to_vaddr(pde & ADDR_MASK) as *const pt_entry_t;
+
+            let pte = *pt.add(pt_index(va));
+            if pte & PRESENT == 0 {
+                return None;
+            }
+            Some((pte & ADDR_MASK) + (vaddr & 0xFFF))
+        }
+    }
+
+    /// Unmap a single 4 KB page
+    ///
+    /// Intermediate page tables are kept. The page itself is not freed.
+    ///
+    /// # Arguments
+    ///
+    /// * `vaddr` - Virtual address (must be page-aligned)
+    pub fn unmap_page(&self, vaddr: u64) -> Result<(), &'static str> {
+        unsafe {
+            let pte = self.leaf_entry(vaddr)?;
+            *pte = 0;
+            core::arch::asm!("invlpg [{}]", in(reg) vaddr, options(nostack, preserves_flags));
+        }
+        Ok(())
+    }
+
+    /// Clear the writable bit of a mapped 4 KB page
+    ///
+    /// Only this CPU's TLB is flushed. If the address space may be live on
+    /// another CPU, follow up with a
+    /// [`tlb::shootdown`](crate::arch::amd64::tlb::shootdown) before relying
+    /// on the page being read-only.
+    ///
+    /// # Arguments
+    ///
+    /// * `vaddr` - Virtual address (must be page-aligned)
+    pub fn write_protect_page(&self, vaddr: u64) -> Result<(), &'static str> {
+        const WRITABLE: u64 = 1 << 1;
+
+        unsafe {
+            let pte = self.leaf_entry(vaddr)?;
+            *pte &= !WRITABLE;
+            core::arch::asm!("invlpg [{}]", in(reg) vaddr, options(nostack, preserves_flags));
+        }
+        Ok(())
+    }
+
+    /// Scan and clear accessed / dirty bits over `size` bytes from `vaddr`
+    ///
+    /// See [`harvest_ad_bits`](crate::arch::amd64::mm::page_tables::harvest_ad_bits)
+    /// for `clear`, `visit` and how the TLB is flushed.
+    pub fn harvest_range(
+        &self,
+        vaddr: u64,
+        size: u64,
+        clear: u64,
+        visit: impl FnMut(VAddr, u64),
+    ) -> crate::arch::amd64::mm::page_tables::Harvest {
+        let end = vaddr.saturating_add(size);
+        unsafe {
+            crate::arch::amd64::mm::page_tables::harvest_ad_bits(
+                self.page_table.phys,
+                vaddr as VAddr,
+                end as VAddr,
+                clear,
+                visit,
+            )
+        }
+    }
+
+    /// Unmap a single 4 KB page, reporting its accessed and dirty bits
+    ///
+    /// Like [`unmap_page`](Self::unmap_page), but the entry is swapped
+    /// out atomically, so an access that raced with a
+    /// [`harvest_range`](Self::harvest_range) is not lost.
+    ///
+    /// # Returns
+    ///
+    /// Whether the page was accessed, and written, since the bits were
+    /// last cleared
+    pub fn unmap_page_harvest(&self, vaddr: u64) -> Result<(bool, bool), &'static str> {
+        const ACCESSED: u64 = 1 << 5;
+        const DIRTY: u64 = 1 << 6;
+
+        unsafe {
+            let pte = &*(self.leaf_entry(vaddr)? as *const AtomicU64);
+            let old = pte.swap(0, Ordering::AcqRel);
+            core::arch::asm!("invlpg [{}]", in(reg) vaddr, options(nostack, preserves_flags));
+            Ok((old & ACCESSED != 0, old & DIRTY != 0))
+        }
+    }
+
+    /// Page table entry mapping the 4 KB page at `vaddr`
+    ///
+    /// Fails if the page is not mapped or is part of a large page.
+    unsafe fn leaf_entry(&self, vaddr: u64) -> Result<*mut pt_entry_t, &'static str> {
+        const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+        const PRESENT: u64 = 1;
+        const LARGE: u64 = 1 << 7;
+
+        if vaddr & 0xFFF != 0 {
+            return Err("Virtual address not page-aligned");
+        }
+
+        let va = vaddr as usize;
+        let mut table = self.page_table.virt;
+        for index in [pml4_index(va), pdp_index(va), pd_index(va)] {
+            let entry = *table.add(index);
+            if entry & PRESENT == 0 {
+                return Err("Page not mapped");
+            }
+            if entry & LARGE != 0 {
+                return Err("Page is part of a large mapping");
+            }
+            table = crate::mm::pmm::paddr_to_vaddr(entry & ADDR_MASK) as *mut pt_entry_t;
+        }
+
+        let pte = table.add(pt_index(va));
+        if *pte & PRESENT == 0 {
+            return Err("Page not mapped");
+        }
+        Ok(pte)
+    }
+
     /// Allocate a new page table
     ///
+    /// The page is recorded against this address space's PML4 so
+    /// [`destroy`] can free it.
+    ///
     /// # Returns
     ///
     /// Physical address of the new page table, or 0 on error
     fn alloc_page_table(&self) -> PAddr {
         use crate::mm::pmm;
 
-        match pmm::pmm_alloc_kernel_page() {
+        let paddr = match pmm::pmm_alloc_kernel_page() {
             Ok(p) => p,
             Err(_) => return 0,
+        };
+        if let Some(tables) = OWNED_TABLES.lock().get_mut(&self.page_table.phys) {
+            tables.push(paddr);
         }
+        paddr
     }
 
     /// Activate this address space
@@ -0,0 +1,216 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Futexes
+//!
+//! `FUTEX` lets userspace build mutexes and condition variables that
+//! sleep instead of spinning. A futex is any aligned 32-bit word in the
+//! process's memory; the kernel keeps a [`WaitQueue`] only for the words
+//! that have waiters, keyed by process and address ([`FutexKey`]).
+//!
+//! - [`wait`]: if the word still holds the expected value, the calling
+//!   thread blocks until it is woken or its deadline passes. The value is
+//!   checked and the thread queued under one lock, so a wake that comes
+//!   between userspace's own check and the syscall is never lost.
+//! - [`wake`]: wakes up to a given number of the word's waiters,
+//!   deadline-class threads first, then in arrival order.
+//!
+//! # Blocking
+//!
+//! A waiting thread is `Blocked`, with its deadline in
+//! [`Process::wake_at`](super::table::Process::wake_at); the scheduler
+//! makes it `Ready` once that passes
+//! ([`ProcessTable::wake_expired`](super::table::ProcessTable::wake_expired)).
+//! A waiter that is still queued when it runs again has timed out.
+//!
+//! Queues belong to the process: they are dropped when it is reaped.
+//!
+//! # Lock Order
+//!
+//! [`FUTEXES`] before [`PROCESS_TABLE`]. The scheduler takes only the
+//! latter.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use crate::arch::amd64::mm::RxStatus;
+use crate::sync::{SpinMutex, WaitQueue, WAIT_OK};
+use crate::syscall::uaccess::UserPtr;
+use crate::time::Instant;
+use super::table::{Process, ProcessState, PROCESS_TABLE};
+
+/// `FUTEX` operation: sleep while the word holds a value
+pub const FUTEX_WAIT: u32 = 0;
+
+/// `FUTEX` operation: wake waiters
+pub const FUTEX_WAKE: u32 = 1;
+
+/// A futex: the process and the address of its word
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FutexKey {
+    /// Process (thread group) ID
+    pub pid: u32,
+    /// User address of the word
+    pub addr: usize,
+}
+
+/// Wait queues of the futexes that have waiters
+static FUTEXES: SpinMutex<BTreeMap<FutexKey, Box<WaitQueue>>> = SpinMutex::new(BTreeMap::new());
+
+/// Wait queue priority of a thread: deadline-class threads go first
+fn wait_priority(thread: &Process) -> u8 {
+    if thread.deadline.is_some() {
+        1
+    } else {
+        0
+    }
+}
+
+/// Sleep on a futex while it holds `expected`
+///
+/// # Arguments
+///
+/// * `word` - The futex word (4-byte aligned)
+/// * `expected` - Value the word must hold for the thread to sleep
+/// * `deadline` - When to give up (`Instant::INFINITE` = never)
+///
+/// # Returns
+///
+/// - `Ok(())` - Woken by [`wake`]
+/// - `Err(ERR_SHOULD_WAIT)` - The word did not hold `expected`
+/// - `Err(ERR_BUSY)` - The deadline passed first
+/// - `Err(ERR_INVALID_ARGS)` - `word` is not an aligned user address
+pub fn wait(word: UserPtr<u32>, expected: u32, deadline: Instant) -> Result<(), RxStatus> {
+    // `wake` refuses such addresses, so a waiter there would never wake
+    if !word.addr().is_multiple_of(core::mem::align_of::<u32>()) {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+
+    let (pid, tid, priority) = {
+        let table = PROCESS_TABLE.lock();
+        let thread = table.current_thread().ok_or(RxStatus::ERR_NOT_FOUND)?;
+        (thread.tgid, thread.pid, wait_priority(thread))
+    };
+    let key = FutexKey { pid, addr: word.addr() };
+
+    {
+        let mut futexes = FUTEXES.lock();
+        if word.read()? != expected {
+            return Err(RxStatus::ERR_SHOULD_WAIT);
+        }
+        let queue = futexes.entry(key).or_insert_with(|| Box::new(WaitQueue::new()));
+        if queue.block(tid as u64, priority, deadline) != WAIT_OK {
+            // Deadline already passed
+            if queue.is_empty() {
+                futexes.remove(&key);
+            }
+            return Err(RxStatus::ERR_BUSY);
+        }
+
+        let mut table = PROCESS_TABLE.lock();
+        if let Some(thread) = table.current_thread_mut() {
+            thread.state = ProcessState::Blocked;
+            thread.wake_at = (!deadline.is_infinite()).then_some(deadline);
+        }
+    }
+
+    crate::sched::round_robin::sleep_while_blocked();
+
+    // Woken threads were dequeued by `wake`
+    let mut futexes = FUTEXES.lock();
+    let timed_out = futexes.get(&key).is_some_and(|queue| queue.remove(tid as u64));
+    if futexes.get(&key).is_some_and(|queue| queue.is_empty()) {
+        futexes.remove(&key);
+    }
+    if timed_out {
+        Err(RxStatus::ERR_BUSY)
+    } else {
+        Ok(())
+    }
+}
+
+/// Wake up to `count` threads sleeping on a futex
+///
+/// # Returns
+///
+/// The number of threads woken, or `ERR_INVALID_ARGS` if `addr` is not
+/// an aligned user address
+pub fn wake(addr: usize, count: u32) -> Result<usize, RxStatus> {
+    if !addr.is_multiple_of(core::mem::align_of::<u32>()) {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    crate::syscall::uaccess::validate_user_range(addr, core::mem::size_of::<u32>())?;
+
+    let (pid, waker) = {
+        let table = PROCESS_TABLE.lock();
+        let thread = table.current_thread().ok_or(RxStatus::ERR_NOT_FOUND)?;
+        (thread.tgid, thread.pid)
+    };
+    let key = FutexKey { pid, addr };
+    let cpu = crate::arch::amd64::entry::this_cpu() as u16;
+
+    let mut futexes = FUTEXES.lock();
+    let Some(queue) = futexes.get(&key) else { return Ok(0) };
+    let mut woken = 0;
+    {
+        let mut table = PROCESS_TABLE.lock();
+        while woken < count as usize {
+            let Some(tid) = queue.wake_one() else { break };
+            let Some(thread) = table.get_mut(tid as u32).filter(|t| t.tgid == pid && t.state.is_alive()) else {
+                continue;
+            };
+            // A thread that just timed out is woken all the same: it is
+            // no longer queued, so its wait succeeds
+            if thread.state == ProcessState::Blocked {
+                thread.state = ProcessState::Ready;
+            }
+            thread.wake_at = None;
+            crate::trace::wakeup(cpu, waker as u64, tid);
+            woken += 1;
+        }
+    }
+    if queue.is_empty() {
+        futexes.remove(&key);
+    }
+    Ok(woken)
+}
+
+/// Drop the futex queues of a reaped process
+///
+/// Its threads are gone, so nothing is left to wake.
+pub fn release_process(pid: u32) {
+    FUTEXES.lock().retain(|key, _| key.pid != pid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_process_drops_its_queues() {
+        let key = |pid, addr| FutexKey { pid, addr };
+        {
+            let mut futexes = FUTEXES.lock();
+            for k in [key(9001, 0x1000), key(9001, 0x2000), key(9002, 0x1000)] {
+                futexes.insert(k, Box::new(WaitQueue::new()));
+                futexes.get(&k).unwrap().block(1, 0, Instant::INFINITE);
+            }
+        }
+
+        release_process(9001);
+        let futexes = FUTEXES.lock();
+        assert!(!futexes.contains_key(&key(9001, 0x1000)));
+        assert!(!futexes.contains_key(&key(9001, 0x2000)));
+        assert_eq!(futexes.get(&key(9002, 0x1000)).map(|q| q.len()), Some(1));
+    }
+
+    #[test]
+    fn test_unaligned_word_is_rejected() {
+        let addr = 0x1000_0002;
+        assert_eq!(wait(UserPtr::new(addr), 0, Instant::INFINITE), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(wake(addr, 1), Err(RxStatus::ERR_INVALID_ARGS));
+        assert!(!FUTEXES.lock().keys().any(|key| key.addr == addr));
+    }
+}
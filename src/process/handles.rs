@@ -0,0 +1,426 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Per-Process Handle Table
+//!
+//! Maps the `u32` handle values userspace passes to syscalls to
+//! [`ObjectHandle`]s. Values are slot index + 1, so 0 is never a valid
+//! handle (`HANDLE_INVALID`). Freed slots are reused lowest first.
+//!
+//! # Errors
+//!
+//! | Condition | Status |
+//! |-----------|--------|
+//! | Value not in the table | `ERR_NOT_FOUND` |
+//! | Handle lacks a required right | `ERR_ACCESS_DENIED` |
+//! | Handle refers to the wrong object type | `ERR_INVALID_ARGS` |
+//! | Duplicate asks for rights the handle lacks | `ERR_INVALID_ARGS` |
+//! | Table full ([`MAX_HANDLES`]) | `ERR_NO_MEMORY` |
+//!
+//! # Leak Reports
+//!
+//! With [`LEAKS_FLAG`] on the kernel command line, handles a process
+//! never closed are listed on the debug console when it exits (see
+//! [`ProcessHandles::close_all`]). Building with the `handle_tracking`
+//! feature adds the kernel backtrace of the syscall that created each
+//! handle (see [`ProcessHandles::set_origin`]). Objects given a name with
+//! `OBJECT_SET_PROPERTY` are listed with it.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use crate::arch::amd64::mm::RxStatus;
+use crate::klog::{Level, LogWriter};
+use crate::object::{ObjectHandle, ObjectKind, Rights, MAX_HANDLES};
+
+#[cfg(feature = "handle_tracking")]
+use crate::arch::amd64::backtrace::Backtrace;
+
+/// The invalid handle value
+pub const HANDLE_INVALID: u32 = 0;
+
+/// Command-line flag: report unclosed handles at process exit
+pub const LEAKS_FLAG: &str = "handle.leaks";
+
+/// Handles owned by one process
+pub struct ProcessHandles {
+    /// Slots, indexed by handle value - 1
+    slots: Vec<Option<ObjectHandle>>,
+
+    /// Number of occupied slots
+    count: usize,
+
+    /// Where each slot's handle was created, parallel to `slots`
+    #[cfg(feature = "handle_tracking")]
+    origins: Vec<Backtrace>,
+
+    /// Origin recorded for handles inserted from now on
+    #[cfg(feature = "handle_tracking")]
+    origin: Option<Backtrace>,
+}
+
+impl ProcessHandles {
+    /// Create an empty table
+    pub const fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            count: 0,
+            #[cfg(feature = "handle_tracking")]
+            origins: Vec::new(),
+            #[cfg(feature = "handle_tracking")]
+            origin: None,
+        }
+    }
+
+    /// Number of handles in the table
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Check if the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Number of handles that can still be added
+    pub fn available(&self) -> usize {
+        MAX_HANDLES - self.count
+    }
+
+    /// Slot index of a handle value
+    fn index(value: u32) -> Option<usize> {
+        (value as usize).checked_sub(1).filter(|&i| i < MAX_HANDLES)
+    }
+
+    /// Add a handle
+    ///
+    /// # Returns
+    ///
+    /// The handle value for userspace
+    pub fn insert(&mut self, handle: ObjectHandle) -> Result<u32, RxStatus> {
+        let index = match self.slots.iter().position(|s| s.is_none()) {
+            Some(i) => i,
+            None if self.slots.len() < MAX_HANDLES => {
+                self.slots.push(None);
+                #[cfg(feature = "handle_tracking")]
+                self.origins.push(Backtrace::empty());
+                self.slots.len() - 1
+            }
+            None => return Err(RxStatus::ERR_NO_MEMORY),
+        };
+        self.slots[index] = Some(handle);
+        #[cfg(feature = "handle_tracking")]
+        {
+            self.origins[index] = self.origin.unwrap_or(Backtrace::empty());
+        }
+        self.count += 1;
+        Ok(index as u32 + 1)
+    }
+
+    /// Set the origin recorded for handles inserted from now on
+    ///
+    /// The backtrace has to be captured before the process table is
+    /// locked: the stack walker cannot find the process's kernel stack
+    /// while the table is held. `None` stops recording.
+    #[cfg(feature = "handle_tracking")]
+    pub fn set_origin(&mut self, origin: Option<Backtrace>) {
+        self.origin = origin;
+    }
+
+    /// Add several handles, all or nothing
+    ///
+    /// # Returns
+    ///
+    /// The handle values, in order
+    pub fn insert_many(&mut self, handles: Vec<ObjectHandle>) -> Result<Vec<u32>, RxStatus> {
+        if handles.len() > self.available() {
+            return Err(RxStatus::ERR_NO_MEMORY);
+        }
+        Ok(handles.into_iter().filter_map(|h| self.insert(h).ok()).collect())
+    }
+
+    /// Look up a handle and check its rights
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Handle value from userspace
+    /// * `required` - Rights the operation needs
+    pub fn get(&self, value: u32, required: Rights) -> Result<&ObjectHandle, RxStatus> {
+        let handle = Self::index(value)
+            .and_then(|i| self.slots.get(i))
+            .and_then(|s| s.as_ref())
+            .ok_or(RxStatus::ERR_NOT_FOUND)?;
+        if !handle.has_right(required) {
+            return Err(RxStatus::ERR_ACCESS_DENIED);
+        }
+        Ok(handle)
+    }
+
+    /// Look up a handle to a `T` and check its rights
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let vmo = handles.object::<Vmo>(value, Rights::READ)?;
+    /// ```
+    pub fn object<T: ObjectKind>(&self, value: u32, required: Rights) -> Result<Arc<T>, RxStatus> {
+        self.get(value, required)?
+            .object
+            .downcast::<T>()
+            .cloned()
+            .ok_or(RxStatus::ERR_INVALID_ARGS)
+    }
+
+    /// Duplicate a handle
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Handle to duplicate (needs DUPLICATE)
+    /// * `rights` - Rights for the new handle; `SAME_RIGHTS` keeps the
+    ///   original's, anything else must be a subset of them
+    ///
+    /// # Returns
+    ///
+    /// The new handle value
+    pub fn duplicate(&mut self, value: u32, rights: Rights) -> Result<u32, RxStatus> {
+        let original = self.get(value, Rights::DUPLICATE)?;
+        let rights = if rights == Rights::SAME_RIGHTS {
+            original.rights
+        } else if original.rights.contains(rights) {
+            rights
+        } else {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        };
+        let copy = ObjectHandle::new(original.object.clone(), rights);
+        self.insert(copy)
+    }
+
+    /// Copy the table for the child of a `FORK`
+    ///
+    /// Every handle is copied with the same value and rights, whatever
+    /// they are: the child inherits the parent's table rather than being
+    /// given handles. Both processes then refer to the same objects.
+    pub fn fork(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            count: self.count,
+            #[cfg(feature = "handle_tracking")]
+            origins: self.origins.clone(),
+            #[cfg(feature = "handle_tracking")]
+            origin: None,
+        }
+    }
+
+    /// Remove a handle from the table
+    ///
+    /// The object is closed when the last handle to it is dropped.
+    pub fn remove(&mut self, value: u32) -> Result<ObjectHandle, RxStatus> {
+        let handle = Self::index(value)
+            .and_then(|i| self.slots.get_mut(i))
+            .and_then(|s| s.take())
+            .ok_or(RxStatus::ERR_NOT_FOUND)?;
+        self.count -= 1;
+        Ok(handle)
+    }
+
+    /// Remove several handles, all or nothing
+    ///
+    /// Every handle must exist, be distinct and hold `required`;
+    /// otherwise nothing is removed.
+    pub fn remove_many(&mut self, values: &[u32], required: Rights) -> Result<Vec<ObjectHandle>, RxStatus> {
+        for (i, &value) in values.iter().enumerate() {
+            self.get(value, required)?;
+            if values[..i].contains(&value) {
+                return Err(RxStatus::ERR_INVALID_ARGS);
+            }
+        }
+        Ok(values.iter().filter_map(|&v| self.remove(v).ok()).collect())
+    }
+
+    /// Empty the table, e.g. when the process exits
+    ///
+    /// Handles still open are reported first if [`LEAKS_FLAG`] is set.
+    ///
+    /// # Returns
+    ///
+    /// The removed handles. Drop them after releasing the process table
+    /// lock: dropping the last handle destroys the object.
+    pub fn close_all(&mut self, pid: u32) -> Vec<ObjectHandle> {
+        if !self.is_empty() && crate::cmdline::has_flag(LEAKS_FLAG) {
+            let _ = self.write_leaks(pid, &mut LogWriter::new(Level::Warn, module_path!()));
+        }
+        self.count = 0;
+        #[cfg(feature = "handle_tracking")]
+        self.origins.clear();
+        core::mem::take(&mut self.slots).into_iter().flatten().collect()
+    }
+
+    /// Write the leak report for the handles still in the table
+    fn write_leaks(&self, pid: u32, out: &mut impl Write) -> core::fmt::Result {
+        writeln!(out, "[HANDLE] pid {} exited with {} open handle(s):", pid, self.count)?;
+        for (i, slot) in self.slots.iter().enumerate() {
+            let handle = match slot {
+                Some(h) => h,
+                None => continue,
+            };
+            write!(out, "  ")?;
+            write_handle(out, i as u32 + 1, handle)?;
+            #[cfg(feature = "handle_tracking")]
+            {
+                writeln!(out, "  created at:")?;
+                self.origins[i].write_to(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write one line per open handle, e.g. for `/proc/self/handles`
+    pub fn write_list(&self, out: &mut impl Write) -> core::fmt::Result {
+        for (i, slot) in self.slots.iter().enumerate() {
+            if let Some(handle) = slot {
+                write_handle(out, i as u32 + 1, handle)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `handle <value> <type> rights <mask>`, plus the object's name if set
+fn write_handle(out: &mut impl Write, value: u32, handle: &ObjectHandle) -> core::fmt::Result {
+    write!(out, "handle {} {:?} rights {:#x}", value, handle.object_type(), handle.rights.into_raw())?;
+    let name = handle.object.name();
+    if !name.is_empty() {
+        write!(out, " name {:?}", name)?;
+    }
+    writeln!(out)
+}
+
+impl Default for ProcessHandles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{Channel, Event, EventFlags, KernelObject};
+
+    fn channel_handle(rights: Rights) -> ObjectHandle {
+        let (a, _) = Channel::create().unwrap();
+        ObjectHandle::new(KernelObject::Channel(Arc::new(a)), rights)
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut table = ProcessHandles::new();
+        let h = table.insert(channel_handle(Rights::READ)).unwrap();
+        assert_ne!(h, HANDLE_INVALID);
+        assert_eq!(table.len(), 1);
+
+        assert!(table.object::<Channel>(h, Rights::READ).is_ok());
+        assert_eq!(table.object::<Channel>(h, Rights::WRITE).err(), Some(RxStatus::ERR_ACCESS_DENIED));
+        assert_eq!(table.object::<Event>(h, Rights::READ).err(), Some(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(table.get(HANDLE_INVALID, Rights::NONE).err(), Some(RxStatus::ERR_NOT_FOUND));
+        assert_eq!(table.get(h + 1, Rights::NONE).err(), Some(RxStatus::ERR_NOT_FOUND));
+
+        assert!(table.remove(h).is_ok());
+        assert_eq!(table.remove(h).err(), Some(RxStatus::ERR_NOT_FOUND));
+        assert!(table.is_empty());
+
+        // Freed slots are reused
+        assert_eq!(table.insert(channel_handle(Rights::READ)).unwrap(), h);
+    }
+
+    #[test]
+    fn test_full_table() {
+        let mut table = ProcessHandles::new();
+        for _ in 0..MAX_HANDLES {
+            table.insert(channel_handle(Rights::READ)).unwrap();
+        }
+        assert_eq!(table.available(), 0);
+        assert_eq!(table.insert(channel_handle(Rights::READ)).err(), Some(RxStatus::ERR_NO_MEMORY));
+        assert_eq!(
+            table.insert_many(alloc::vec![channel_handle(Rights::READ)]).err(),
+            Some(RxStatus::ERR_NO_MEMORY)
+        );
+    }
+
+    #[test]
+    fn test_remove_many_is_all_or_nothing() {
+        let mut table = ProcessHandles::new();
+        let a = table.insert(channel_handle(Rights::TRANSFER)).unwrap();
+        let b = table.insert(channel_handle(Rights::READ)).unwrap();
+
+        assert_eq!(table.remove_many(&[a, b], Rights::TRANSFER).err(), Some(RxStatus::ERR_ACCESS_DENIED));
+        assert_eq!(table.remove_many(&[a, a], Rights::TRANSFER).err(), Some(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(table.len(), 2);
+
+        assert_eq!(table.remove_many(&[a], Rights::TRANSFER).unwrap().len(), 1);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut table = ProcessHandles::new();
+        let event = ObjectHandle::with_default_rights(Event::new(false, EventFlags::empty));
+        let h = table.insert(event).unwrap();
+
+        let same = table.duplicate(h, Rights::SAME_RIGHTS).unwrap();
+        assert_eq!(table.get(same, Rights::NONE).unwrap().rights, table.get(h, Rights::NONE).unwrap().rights);
+        assert!(table.get(h, Rights::NONE).unwrap().object.same_object(&table.get(same, Rights::NONE).unwrap().object));
+
+        // Reduced rights stick: no DUPLICATE means no further copies
+        let reduced = table.duplicate(h, Rights::WAIT).unwrap();
+        assert_eq!(table.duplicate(reduced, Rights::SAME_RIGHTS).err(), Some(RxStatus::ERR_ACCESS_DENIED));
+
+        // Rights cannot grow
+        assert_eq!(table.duplicate(h, Rights::MANAGE).err(), Some(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut table = ProcessHandles::new();
+        let a = table.insert(channel_handle(Rights::READ)).unwrap();
+        let b = table.insert(ObjectHandle::with_default_rights(Event::new(false, EventFlags::empty))).unwrap();
+        table.remove(a).unwrap();
+
+        let mut child = table.fork();
+        assert_eq!(child.len(), 1);
+        assert!(child.get(b, Rights::NONE).unwrap().object.same_object(&table.get(b, Rights::NONE).unwrap().object));
+        assert_eq!(child.get(b, Rights::NONE).unwrap().rights, table.get(b, Rights::NONE).unwrap().rights);
+
+        // The tables are independent afterwards
+        child.remove(b).unwrap();
+        assert!(table.get(b, Rights::NONE).is_ok());
+        assert_eq!(child.insert(channel_handle(Rights::READ)).unwrap(), a);
+    }
+
+    #[test]
+    fn test_leak_report() {
+        let mut table = ProcessHandles::new();
+        let a = table.insert(channel_handle(Rights::READ)).unwrap();
+        table.insert(ObjectHandle::with_default_rights(Event::new(false, EventFlags::empty))).unwrap();
+        table.remove(a).unwrap();
+
+        let mut report = alloc::string::String::new();
+        table.write_leaks(7, &mut report).unwrap();
+        assert!(report.starts_with("[HANDLE] pid 7 exited with 1 open handle(s):\n"));
+        assert!(report.contains("  handle 2 Event rights 0x268\n"));
+        assert!(!report.contains("Channel"));
+
+        // Named objects are listed with their name
+        table.get(2, Rights::NONE).unwrap().object.base().set_name("ready");
+        let mut list = alloc::string::String::new();
+        table.write_list(&mut list).unwrap();
+        assert_eq!(list, "handle 2 Event rights 0x268 name \"ready\"\n");
+
+        assert_eq!(table.close_all(7).len(), 1);
+        assert!(table.is_empty());
+        assert_eq!(table.insert(channel_handle(Rights::READ)).unwrap(), 1);
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Job Membership and Limits
+//!
+//! Every process belongs to a job ([`Process::job_id`](super::table::Process::job_id)).
+//! A spawned or forked process joins its parent's job, or the job whose
+//! handle `SPAWN_FDS` was given. This module keeps what each job uses,
+//! keyed by [`JobId`] like the CPU-time accounting in
+//! [`crate::sched::cpu_limit`], and checks it against the job's
+//! [`ResourceLimits`]:
+//!
+//! - **Processes**: a process is [`admit`]ted before it is inserted in
+//!   the process table and [`leave`]s when it is reaped. Threads do not
+//!   count.
+//! - **Memory**: a VMO charges its job for each page it allocates
+//!   ([`charge_memory`]) and gives the charge back when it is dropped; see
+//!   [`Vmo::set_job`](crate::object::Vmo::set_job).
+//!
+//! A job's usage includes its child jobs', so a limit bounds the whole
+//! subtree. Going over `max_processes` or `max_memory` of the job or any
+//! ancestor fails with `ERR_NO_MEMORY`.
+//!
+//! # Killing
+//!
+//! [`kill`] ends every process in a job and its child jobs with
+//! `EXIT_KILLED`, as the CPU-time limit does for one process. Killed jobs
+//! admit no new processes (`ERR_ACCESS_DENIED`).
+//!
+//! # Lock Order
+//!
+//! [`JOBS`] is taken last: nothing else is locked while it is held.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::arch::amd64::mm::RxStatus;
+use crate::audit::{self, AuditKind};
+use crate::object::{
+    Job, JobId, KernelObject, ObjectHandle, ObjectType, ResourceLimits, Rights, JOB_ID_INVALID, JOB_ID_ROOT,
+};
+use crate::sync::SpinMutex;
+
+/// What a job uses, and its limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JobAccount {
+    /// Parent job (`None` for the root)
+    pub parent: Option<JobId>,
+    /// Process limit (0 = unlimited)
+    pub max_processes: u64,
+    /// Memory limit in bytes (0 = unlimited)
+    pub max_memory: u64,
+    /// Processes in the job and its child jobs
+    pub processes: u64,
+    /// Bytes charged to the job and its child jobs
+    pub memory: u64,
+    /// Killed: no new processes
+    pub killed: bool,
+}
+
+/// Accounts of all jobs that have been created, limited or charged
+pub struct JobTree {
+    jobs: BTreeMap<JobId, JobAccount>,
+}
+
+impl JobTree {
+    /// Create a tree with only the (implicit, unlimited) root job
+    pub const fn new() -> Self {
+        Self { jobs: BTreeMap::new() }
+    }
+
+    /// Record a new job under `parent`
+    pub fn register(&mut self, id: JobId, parent: JobId) {
+        self.jobs.entry(id).or_default().parent = Some(parent);
+    }
+
+    /// Set a job's process and memory limits
+    pub fn set_limits(&mut self, id: JobId, limits: &ResourceLimits) {
+        let account = self.jobs.entry(id).or_default();
+        account.max_processes = limits.max_processes;
+        account.max_memory = limits.max_memory;
+    }
+
+    /// A job's account, if it has one
+    pub fn get(&self, id: JobId) -> Option<&JobAccount> {
+        self.jobs.get(&id)
+    }
+
+    /// `id` and its ancestors, nearest first
+    fn chain(&self, id: JobId) -> Vec<JobId> {
+        let mut chain = Vec::new();
+        let mut next = Some(id);
+        while let Some(job) = next.filter(|job| !chain.contains(job)) {
+            chain.push(job);
+            next = self.jobs.get(&job).and_then(|a| a.parent);
+        }
+        chain
+    }
+
+    /// Add `processes` and `bytes` to `id` and its ancestors, if every
+    /// limit on the way allows it
+    fn charge(&mut self, id: JobId, processes: u64, bytes: u64) -> Result<(), RxStatus> {
+        let chain = self.chain(id);
+        for job in &chain {
+            let Some(a) = self.jobs.get(job) else { continue };
+            if processes > 0 && a.killed {
+                return Err(RxStatus::ERR_ACCESS_DENIED);
+            }
+            let over_processes = a.max_processes != 0 && a.processes + processes > a.max_processes;
+            let over_memory = a.max_memory != 0 && a.memory.saturating_add(bytes) > a.max_memory;
+            if over_processes || over_memory {
+                return Err(RxStatus::ERR_NO_MEMORY);
+            }
+        }
+        for job in chain {
+            let a = self.jobs.entry(job).or_default();
+            a.processes += processes;
+            a.memory = a.memory.saturating_add(bytes);
+        }
+        Ok(())
+    }
+
+    /// Take `processes` and `bytes` back from `id` and its ancestors
+    fn uncharge(&mut self, id: JobId, processes: u64, bytes: u64) {
+        for job in self.chain(id) {
+            if let Some(a) = self.jobs.get_mut(&job) {
+                a.processes = a.processes.saturating_sub(processes);
+                a.memory = a.memory.saturating_sub(bytes);
+            }
+        }
+    }
+
+    /// Count a new process in `id`
+    pub fn admit(&mut self, id: JobId) -> Result<(), RxStatus> {
+        self.charge(id, 1, 0)
+    }
+
+    /// Stop counting a process in `id`
+    pub fn leave(&mut self, id: JobId) {
+        self.uncharge(id, 1, 0);
+    }
+
+    /// Charge `bytes` of memory to `id`
+    pub fn charge_memory(&mut self, id: JobId, bytes: u64) -> Result<(), RxStatus> {
+        self.charge(id, 0, bytes)
+    }
+
+    /// Give back `bytes` of memory charged to `id`
+    pub fn uncharge_memory(&mut self, id: JobId, bytes: u64) {
+        self.uncharge(id, 0, bytes);
+    }
+
+    /// `id` and every job below it
+    pub fn subtree(&self, id: JobId) -> Vec<JobId> {
+        let mut jobs: Vec<JobId> = self
+            .jobs
+            .keys()
+            .copied()
+            .filter(|&job| job != id && self.chain(job).contains(&id))
+            .collect();
+        jobs.push(id);
+        jobs
+    }
+
+    /// Mark `id` and every job below it killed
+    ///
+    /// # Returns
+    ///
+    /// The jobs marked (see [`subtree`](Self::subtree))
+    pub fn mark_killed(&mut self, id: JobId) -> Vec<JobId> {
+        let jobs = self.subtree(id);
+        for &job in &jobs {
+            self.jobs.entry(job).or_default().killed = true;
+        }
+        jobs
+    }
+}
+
+impl Default for JobTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The kernel's job accounts
+static JOBS: SpinMutex<JobTree> = SpinMutex::new(JobTree::new());
+
+/// Record a new job under `parent`
+///
+/// Called from `Job::new_child`.
+pub fn register(id: JobId, parent: JobId) {
+    JOBS.lock().register(id, parent);
+}
+
+/// Set a job's process and memory limits
+///
+/// Called from `Job::set_limits`. Usage already over a new limit is left
+/// alone; only new processes and pages are refused.
+pub fn set_limits(id: JobId, limits: &ResourceLimits) {
+    JOBS.lock().set_limits(id, limits);
+}
+
+/// A job's account (all zero for a job never limited or charged)
+pub fn account(id: JobId) -> JobAccount {
+    JOBS.lock().get(id).copied().unwrap_or_default()
+}
+
+/// The job of the calling process (the root job in kernel context)
+pub fn current() -> JobId {
+    super::table::with_current_process(|p| p.job_id).unwrap_or(JOB_ID_ROOT)
+}
+
+/// Handle value of the root job in init's handle table
+pub const INIT_ROOT_JOB_HANDLE: u32 = 1;
+
+/// A handle to the root job, given to init as its first handle
+///
+/// The only handle created with [`Rights::DEBUG`]. Jobs created from it
+/// do not get the right, so init decides which tools (e.g. `dmesg`)
+/// receive a copy.
+pub fn root_job_handle() -> ObjectHandle {
+    let rights = Rights::default_for_type(ObjectType::Job) | Rights::DEBUG;
+    ObjectHandle::new(KernelObject::Job(Arc::new(Job::new_root())), rights)
+}
+
+/// Count a new process in `id` and its ancestors
+///
+/// # Returns
+///
+/// - `Err(ERR_NO_MEMORY)` - A `max_processes` limit is reached
+/// - `Err(ERR_ACCESS_DENIED)` - The job has been killed
+pub fn admit(id: JobId) -> Result<(), RxStatus> {
+    JOBS.lock().admit(id)
+}
+
+/// Stop counting a reaped process
+pub fn leave(id: JobId) {
+    JOBS.lock().leave(id);
+}
+
+/// Charge `bytes` of memory to `id` and its ancestors
+///
+/// Nothing is charged to [`JOB_ID_INVALID`] (kernel memory).
+///
+/// # Returns
+///
+/// `Err(ERR_NO_MEMORY)` if a `max_memory` limit would be exceeded
+pub fn charge_memory(id: JobId, bytes: u64) -> Result<(), RxStatus> {
+    if id == JOB_ID_INVALID {
+        return Ok(());
+    }
+    JOBS.lock().charge_memory(id, bytes)
+}
+
+/// Give back memory charged with [`charge_memory`]
+pub fn uncharge_memory(id: JobId, bytes: u64) {
+    if id != JOB_ID_INVALID {
+        JOBS.lock().uncharge_memory(id, bytes);
+    }
+}
+
+/// Kill every process in a job and its child jobs
+///
+/// The processes and their threads become zombies holding `EXIT_KILLED`,
+/// with their descriptors closed; each is audited. As with any exit, a
+/// parent collects them with `WAIT_PID` and orphans are reaped here.
+/// Processes on another CPU stop at their next switch or syscall return.
+///
+/// # Returns
+///
+/// The number of processes killed (threads not counted)
+pub fn kill(id: JobId) -> usize {
+    use super::table::{ProcessState, EXIT_KILLED, PROCESS_TABLE};
+
+    let jobs = JOBS.lock().mark_killed(id);
+    let mut killed = 0;
+    {
+        let mut table = PROCESS_TABLE.lock();
+        let victims: Vec<u32> = table
+            .iter()
+            .filter(|p| p.state.is_alive() && jobs.contains(&p.job_id))
+            .map(|p| p.pid)
+            .collect();
+        for pid in victims {
+            let Some(p) = table.get_mut(pid) else { continue };
+            p.state = ProcessState::Zombie;
+            p.exit_code = EXIT_KILLED;
+            crate::sched::deadline::leave(p);
+            if !p.is_thread() {
+                p.fd_table.close_all();
+                p.tty_output.flush(crate::drivers::tty::write);
+                audit::log(AuditKind::JobKilled, p.pid, p.job_id, id);
+                killed += 1;
+            }
+        }
+    }
+    super::table::reap_orphans();
+    killed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_processes: u64, max_memory: u64) -> ResourceLimits {
+        ResourceLimits { max_processes, max_memory, ..ResourceLimits::unlimited() }
+    }
+
+    #[test]
+    fn test_process_limit_covers_subtree() {
+        let mut tree = JobTree::new();
+        tree.register(2, JOB_ID_ROOT);
+        tree.register(3, 2);
+        tree.set_limits(2, &limits(2, 0));
+
+        tree.admit(3).unwrap();
+        tree.admit(2).unwrap();
+        assert_eq!(tree.admit(3), Err(RxStatus::ERR_NO_MEMORY));
+        // A refused process is not counted anywhere
+        assert_eq!(tree.get(3).unwrap().processes, 1);
+        assert_eq!(tree.get(JOB_ID_ROOT).unwrap().processes, 2);
+
+        tree.leave(3);
+        tree.admit(3).unwrap();
+        assert_eq!(tree.get(2).unwrap().processes, 2);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut tree = JobTree::new();
+        tree.register(2, JOB_ID_ROOT);
+        tree.set_limits(2, &limits(0, 8192));
+
+        tree.charge_memory(2, 4096).unwrap();
+        tree.charge_memory(2, 4096).unwrap();
+        assert_eq!(tree.charge_memory(2, 4096), Err(RxStatus::ERR_NO_MEMORY));
+        tree.uncharge_memory(2, 4096);
+        tree.charge_memory(2, 4096).unwrap();
+        assert_eq!(tree.get(JOB_ID_ROOT).unwrap().memory, 8192);
+    }
+
+    #[test]
+    fn test_kill_marks_subtree() {
+        let mut tree = JobTree::new();
+        tree.register(2, JOB_ID_ROOT);
+        tree.register(3, 2);
+        tree.register(4, JOB_ID_ROOT);
+
+        let mut killed = tree.mark_killed(2);
+        killed.sort();
+        assert_eq!(killed, [2, 3]);
+        assert_eq!(tree.admit(3), Err(RxStatus::ERR_ACCESS_DENIED));
+        tree.admit(4).unwrap();
+        // Pages can still be charged while the processes wind down
+        tree.charge_memory(3, 4096).unwrap();
+    }
+}
@@ -25,8 +25,14 @@
 //! ```
 
 pub mod address_space;
+pub mod futex;
+pub mod handles;
+pub mod jobs;
+pub mod ptdump;
 pub mod table;
+pub mod thread;
 pub mod switch;
+pub mod vmar;
 
 use core::sync::atomic::{AtomicU64, Ordering};
 use crate::sync::SpinMutex;
@@ -0,0 +1,495 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Page Table Dumper
+//!
+//! [`snapshot`] walks a page table and returns what it maps as
+//! [`MappedRange`]s: runs of pages that are contiguous in both virtual and
+//! physical memory and have the same effective permissions. [`diff`]
+//! compares two snapshots, so a test can take one before and one after a
+//! VMAR operation and check that exactly the expected pages changed.
+//!
+//! # Report Format
+//!
+//! [`write_ranges`] prints one range per line:
+//!
+//! ```text
+//! 0x0000000000400000-0x0000000000402000       8K r-xu-- -> 0x2f4a000
+//! ```
+//!
+//! The permission columns are `r` (always), `w`rite, e`x`ecute, `u`ser,
+//! `g`lobal and un`c`ached, or `-`. Write, execute and user are the
+//! effective permissions: a bit must be granted at every level of the
+//! walk. [`write_diff`] prefixes added ranges with `+`, removed ones with
+//! `-`, and prints `~` with both sides for ranges whose permissions or
+//! physical pages changed.
+//!
+//! # Reporting
+//!
+//! - `PT_DUMP` copies the report for a process to userspace, or prints it
+//!   on the debug console
+//! - The `page_tables` MM self-test diffs its scratch address space
+//!   around each map and unmap
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+use crate::arch::amd64::mm::page_tables::{pt_entry_t, PAddr};
+
+/// Page table entry bits
+const PRESENT: u64 = 1 << 0;
+const WRITABLE: u64 = 1 << 1;
+const USER: u64 = 1 << 2;
+const CACHE_DISABLE: u64 = 1 << 4;
+const LARGE: u64 = 1 << 7;
+const GLOBAL: u64 = 1 << 8;
+const NO_EXECUTE: u64 = 1 << 63;
+const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Entries per table
+const ENTRIES: usize = 512;
+
+/// Level of the PML4 (level 0 is the page table)
+const TOP_LEVEL: u32 = 3;
+
+/// Lower half of the address space, where user mappings live
+pub const USER_HALF: Range<u64> = 0..crate::arch::amd64::mm::layout::USER_END;
+
+/// Both canonical halves, except the topmost page so that every range
+/// end fits in a `u64`
+pub const ALL: Range<u64> = 0..0xFFFF_FFFF_FFFF_F000;
+
+/// `PT_DUMP` flag: only user-accessible ranges in the lower half
+pub const DUMP_USER_ONLY: usize = 1 << 0;
+
+/// Effective permissions of a [`MappedRange`]
+pub mod perms {
+    /// Writable at every level
+    pub const WRITE: u8 = 1 << 0;
+    /// No level sets NX
+    pub const EXEC: u8 = 1 << 1;
+    /// User-accessible at every level
+    pub const USER: u8 = 1 << 2;
+    /// Global leaf entry
+    pub const GLOBAL: u8 = 1 << 3;
+    /// Cache disabled in the leaf entry
+    pub const UNCACHED: u8 = 1 << 4;
+}
+
+/// Virtually and physically contiguous pages with the same permissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappedRange {
+    /// First virtual address
+    pub vaddr: u64,
+    /// Size in bytes
+    pub size: u64,
+    /// Physical address `vaddr` maps to
+    pub paddr: PAddr,
+    /// `perms::*` bits
+    pub perms: u8,
+}
+
+impl MappedRange {
+    /// Virtual address just past the range
+    pub fn end(&self) -> u64 {
+        self.vaddr + self.size
+    }
+
+    /// Whether the range is user-accessible
+    pub fn is_user(&self) -> bool {
+        self.perms & perms::USER != 0
+    }
+
+    /// Whether `next` continues this range
+    fn extends_to(&self, next: &MappedRange) -> bool {
+        self.end() == next.vaddr && self.paddr + self.size == next.paddr && self.perms == next.perms
+    }
+
+    /// The part of the range within `[start, end)`
+    ///
+    /// Does not compute `self.end()`, so it also clips a leaf that ends at
+    /// the top of the address space.
+    fn clip(&self, start: u64, end: u64) -> MappedRange {
+        let start = start.max(self.vaddr);
+        let last = (end - 1).min(self.vaddr + (self.size - 1));
+        MappedRange {
+            vaddr: start,
+            size: last - start + 1,
+            paddr: self.paddr + (start - self.vaddr),
+            perms: self.perms,
+        }
+    }
+}
+
+/// Add a range to a sorted list, merging it into the last one if possible
+fn push_range(ranges: &mut Vec<MappedRange>, range: MappedRange) {
+    match ranges.last_mut() {
+        Some(last) if last.extends_to(&range) => last.size += range.size,
+        _ => ranges.push(range),
+    }
+}
+
+/// Walk one table, adding its mappings in `range` to `out`
+///
+/// `inherited` holds the `WRITABLE` and `USER` bits granted by every
+/// level above, and `NO_EXECUTE` if any level above set it.
+fn walk_table(
+    read: &impl Fn(PAddr, usize) -> u64,
+    table: PAddr,
+    level: u32,
+    base: u64,
+    inherited: u64,
+    range: &Range<u64>,
+    out: &mut Vec<MappedRange>,
+) {
+    let span = 1u64 << (12 + 9 * level);
+    for index in 0..ENTRIES {
+        let mut vaddr = base + index as u64 * span;
+        // The upper half of the PML4 maps sign-extended addresses
+        if level == TOP_LEVEL && index >= ENTRIES / 2 {
+            vaddr |= 0xFFFF_0000_0000_0000;
+        }
+        let last = vaddr + (span - 1);
+        if last < range.start || vaddr >= range.end {
+            continue;
+        }
+
+        let entry = read(table, index);
+        if entry & PRESENT == 0 {
+            continue;
+        }
+        let bits = (inherited & entry & (WRITABLE | USER)) | ((inherited | entry) & NO_EXECUTE);
+
+        // The PML4 has no large pages
+        if level == 0 || (level < TOP_LEVEL && entry & LARGE != 0) {
+            let mut leaf_perms = 0;
+            if bits & WRITABLE != 0 {
+                leaf_perms |= perms::WRITE;
+            }
+            if bits & NO_EXECUTE == 0 {
+                leaf_perms |= perms::EXEC;
+            }
+            if bits & USER != 0 {
+                leaf_perms |= perms::USER;
+            }
+            if entry & GLOBAL != 0 {
+                leaf_perms |= perms::GLOBAL;
+            }
+            if entry & CACHE_DISABLE != 0 {
+                leaf_perms |= perms::UNCACHED;
+            }
+            let leaf = MappedRange {
+                vaddr,
+                size: span,
+                paddr: entry & ADDR_MASK & !(span - 1),
+                perms: leaf_perms,
+            };
+            push_range(out, leaf.clip(range.start, range.end));
+        } else {
+            walk_table(read, entry & ADDR_MASK, level - 1, vaddr, bits, range, out);
+        }
+    }
+}
+
+/// Walk a page table through `read(table, index)`, which returns entry
+/// `index` of the table at physical address `table`
+fn walk_with(read: &impl Fn(PAddr, usize) -> u64, root: PAddr, range: Range<u64>) -> Vec<MappedRange> {
+    let mut out = Vec::new();
+    walk_table(read, root, TOP_LEVEL, 0, WRITABLE | USER, &range, &mut out);
+    out
+}
+
+/// Mapped ranges of a page table within `range`, in address order
+///
+/// # Safety
+///
+/// `page_table` must be the physical address of a live PML4, and its
+/// tables must not be freed during the walk.
+pub unsafe fn snapshot(page_table: PAddr, range: Range<u64>) -> Vec<MappedRange> {
+    let read = |table: PAddr, index: usize| {
+        let entries = crate::mm::pmm::paddr_to_vaddr(table) as *const pt_entry_t;
+        // SAFETY: the caller guarantees every table reached is live
+        unsafe { core::ptr::read_volatile(entries.add(index)) }
+    };
+    walk_with(&read, page_table, range)
+}
+
+/// How a range differs between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Mapped only after
+    Added,
+    /// Mapped only before
+    Removed,
+    /// Mapped in both, to other pages or with other permissions
+    Changed,
+}
+
+/// One difference between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeDiff {
+    pub change: Change,
+    /// The range before (`None` if added)
+    pub before: Option<MappedRange>,
+    /// The range after (`None` if removed)
+    pub after: Option<MappedRange>,
+}
+
+impl RangeDiff {
+    /// The virtual range that differs
+    pub fn range(&self) -> Range<u64> {
+        let side = self.before.or(self.after).expect("diff without a side");
+        side.vaddr..side.end()
+    }
+}
+
+/// The part of `ranges` covering `[start, end)`, if any
+///
+/// `ranges` is sorted and `[start, end)` lies within a single range or
+/// none, as it does between two consecutive boundaries in [`diff`].
+fn covering(ranges: &[MappedRange], start: u64, end: u64) -> Option<MappedRange> {
+    let i = ranges.partition_point(|r| r.end() <= start);
+    ranges.get(i).filter(|r| r.vaddr <= start).map(|r| r.clip(start, end))
+}
+
+/// Extend `side` with `next`; both must be present or both absent
+fn merge_side(side: &mut Option<MappedRange>, next: Option<MappedRange>) -> bool {
+    match (side.as_mut(), next) {
+        (None, None) => true,
+        (Some(side), Some(next)) if side.extends_to(&next) => {
+            side.size += next.size;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Differences from `before` to `after`, in address order
+///
+/// Both lists must be sorted and non-overlapping, as [`snapshot`] returns
+/// them. A range that is split, for example by changing the permissions
+/// of its middle page, shows up as a change of just that page.
+pub fn diff(before: &[MappedRange], after: &[MappedRange]) -> Vec<RangeDiff> {
+    let mut bounds: Vec<u64> = before.iter().chain(after).flat_map(|r| [r.vaddr, r.end()]).collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut out: Vec<RangeDiff> = Vec::new();
+    for pair in bounds.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let b = covering(before, start, end);
+        let a = covering(after, start, end);
+        let change = match (b, a) {
+            (None, None) => continue,
+            (Some(b), Some(a)) if b == a => continue,
+            (Some(_), Some(_)) => Change::Changed,
+            (None, Some(_)) => Change::Added,
+            (Some(_), None) => Change::Removed,
+        };
+
+        if let Some(last) = out.last_mut() {
+            if last.change == change && last.range().end == start {
+                let (mut before, mut after) = (last.before, last.after);
+                if merge_side(&mut before, b) && merge_side(&mut after, a) {
+                    last.before = before;
+                    last.after = after;
+                    continue;
+                }
+            }
+        }
+        out.push(RangeDiff { change, before: b, after: a });
+    }
+    out
+}
+
+/// Size with the largest unit that divides it exactly
+struct Size(u64);
+
+impl core::fmt::Display for Size {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const UNITS: [(u64, &str); 3] = [(1 << 30, "G"), (1 << 20, "M"), (1 << 10, "K")];
+        let (unit, suffix) = UNITS
+            .iter()
+            .copied()
+            .find(|&(unit, _)| self.0.is_multiple_of(unit) && self.0 != 0)
+            .unwrap_or((1, "B"));
+        f.pad(&alloc::format!("{}{}", self.0 / unit, suffix))
+    }
+}
+
+/// Permission columns, see the module documentation
+struct Perms(u8);
+
+impl core::fmt::Display for Perms {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let columns = [
+            (perms::WRITE, 'w'),
+            (perms::EXEC, 'x'),
+            (perms::USER, 'u'),
+            (perms::GLOBAL, 'g'),
+            (perms::UNCACHED, 'c'),
+        ];
+        f.write_char('r')?;
+        for (bit, c) in columns {
+            f.write_char(if self.0 & bit != 0 { c } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
+/// Write one line for a range, without the newline
+fn write_range(out: &mut impl Write, r: &MappedRange) -> core::fmt::Result {
+    write!(out, "{:#018x}-{:#018x} {:>8} {} -> {:#x}", r.vaddr, r.end(), Size(r.size), Perms(r.perms), r.paddr)
+}
+
+/// Write a snapshot, one range per line
+pub fn write_ranges(out: &mut impl Write, ranges: &[MappedRange]) -> core::fmt::Result {
+    for r in ranges {
+        write_range(out, r)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write a diff, one difference per line
+pub fn write_diff(out: &mut impl Write, diffs: &[RangeDiff]) -> core::fmt::Result {
+    for d in diffs {
+        match (d.change, d.before, d.after) {
+            (Change::Changed, Some(b), Some(a)) => {
+                out.write_str("~ ")?;
+                write_range(out, &b)?;
+                write!(out, " => {} -> {:#x}", Perms(a.perms), a.paddr)?;
+            }
+            (_, Some(r), None) => {
+                out.write_str("- ")?;
+                write_range(out, &r)?;
+            }
+            (_, _, Some(r)) => {
+                out.write_str("+ ")?;
+                write_range(out, &r)?;
+            }
+            (_, None, None) => continue,
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use alloc::string::String;
+
+    /// Page tables in a map from (table, index) to entry
+    struct Tables(BTreeMap<(PAddr, usize), u64>);
+
+    impl Tables {
+        fn read(&self, table: PAddr, index: usize) -> u64 {
+            self.0.get(&(table, index)).copied().unwrap_or(0)
+        }
+    }
+
+    const PML4: PAddr = 0x1000;
+    const PDP: PAddr = 0x2000;
+    const PD: PAddr = 0x3000;
+    const PT: PAddr = 0x4000;
+
+    /// Four user pages at 4 MB (the first two contiguous and writable,
+    /// the last read-only) and a 2 MB kernel page at 2 MB
+    fn tables() -> Tables {
+        let mut t = BTreeMap::new();
+        t.insert((PML4, 0), PDP | 0x7);
+        t.insert((PDP, 0), PD | 0x7);
+        t.insert((PD, 1), 0x20_0000 | LARGE | GLOBAL | 0x3 | NO_EXECUTE);
+        t.insert((PD, 2), PT | 0x7);
+        t.insert((PT, 0), 0x10_0000 | 0x7);
+        t.insert((PT, 1), 0x10_1000 | 0x7);
+        t.insert((PT, 2), 0x50_0000 | 0x7);
+        t.insert((PT, 3), 0x50_1000 | 0x5);
+        Tables(t)
+    }
+
+    fn walk(t: &Tables, range: Range<u64>) -> Vec<MappedRange> {
+        walk_with(&|table, index| t.read(table, index), PML4, range)
+    }
+
+    fn page(vaddr: u64, pages: u64, paddr: PAddr, perms: u8) -> MappedRange {
+        MappedRange { vaddr, size: pages * 4096, paddr, perms }
+    }
+
+    const RWXU: u8 = perms::WRITE | perms::EXEC | perms::USER;
+
+    #[test]
+    fn test_walk_coalesces() {
+        let ranges = walk(&tables(), ALL);
+        assert_eq!(ranges, [
+            MappedRange { vaddr: 0x20_0000, size: 0x20_0000, paddr: 0x20_0000, perms: perms::WRITE | perms::GLOBAL },
+            page(0x40_0000, 2, 0x10_0000, RWXU),
+            page(0x40_2000, 1, 0x50_0000, RWXU),
+            page(0x40_3000, 1, 0x50_1000, perms::EXEC | perms::USER),
+        ]);
+        assert!(!ranges[0].is_user());
+    }
+
+    #[test]
+    fn test_walk_clips_and_inherits() {
+        let mut t = tables();
+        // A read-only PD entry makes everything below it read-only
+        t.0.insert((PD, 2), PT | 0x5);
+        let ranges = walk(&t, 0x30_0000..0x40_2000);
+        assert_eq!(ranges, [
+            MappedRange { vaddr: 0x30_0000, size: 0x10_0000, paddr: 0x30_0000, perms: perms::WRITE | perms::GLOBAL },
+            page(0x40_0000, 2, 0x10_0000, perms::EXEC | perms::USER),
+        ]);
+    }
+
+    #[test]
+    fn test_walk_upper_half() {
+        let mut t = BTreeMap::new();
+        t.insert((PML4, 511), PDP | 0x3);
+        t.insert((PDP, 510), 0x4000_0000 | LARGE | 0x3);
+        let ranges = walk(&Tables(t), ALL);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].vaddr, 0xFFFF_FFFF_8000_0000);
+        assert_eq!(ranges[0].size, 1 << 30);
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = walk(&tables(), USER_HALF);
+        let mut t = tables();
+        // Unmap the first page, write-protect the second, map a new one
+        t.0.remove(&(PT, 0));
+        t.0.insert((PT, 1), 0x10_1000 | 0x5);
+        t.0.insert((PT, 8), 0x60_0000 | 0x7);
+        let after = walk(&t, USER_HALF);
+
+        let diffs = diff(&before, &after);
+        assert_eq!(diffs, [
+            RangeDiff { change: Change::Removed, before: Some(page(0x40_0000, 1, 0x10_0000, RWXU)), after: None },
+            RangeDiff {
+                change: Change::Changed,
+                before: Some(page(0x40_1000, 1, 0x10_1000, RWXU)),
+                after: Some(page(0x40_1000, 1, 0x10_1000, perms::EXEC | perms::USER)),
+            },
+            RangeDiff { change: Change::Added, before: None, after: Some(page(0x40_8000, 1, 0x60_0000, RWXU)) },
+        ]);
+        assert!(diff(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_report() {
+        let mut out = String::new();
+        write_ranges(&mut out, &[page(0x40_0000, 2, 0x10_0000, RWXU)]).unwrap();
+        assert_eq!(out, "0x0000000000400000-0x0000000000402000       8K rwxu-- -> 0x100000\n");
+
+        let mut out = String::new();
+        let removed = RangeDiff { change: Change::Removed, before: Some(page(0, 1, 0, 0)), after: None };
+        write_diff(&mut out, &[removed]).unwrap();
+        assert!(out.starts_with("- 0x0000000000000000-0x0000000000001000       4K r----- -> 0x0"));
+    }
+}
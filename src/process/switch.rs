@@ -92,6 +92,8 @@ pub unsafe fn switch_to(current: &mut Process, next: &Process) {
     // Update process states
     current.state = crate::process::table::ProcessState::Ready;
 
+    crate::kcounters::record_context_switch();
+
     // Perform the context switch
     // The assembly function will save current's state to current.saved_state
     // and restore next's state from next.saved_state
@@ -130,7 +132,7 @@ pub unsafe fn switch_to_pid(next_pid: u32) -> Result<(), &'static str> {
 
     // Get current process
     let current_pid = table
-        .current_pid()
+        .current_tid()
         .ok_or("No current process")?;
 
     // Can't switch to ourselves
@@ -9,8 +9,32 @@
 //! This module provides the global process table for tracking all processes
 //! in the system. It implements the Phase 5B requirements for process
 //! management and context switching.
-
+//!
+//! # CPUs
+//!
+//! Each CPU has its own current process. Every process has a home CPU
+//! ([`Process::cpu`]); the runnable processes homed on a CPU are its run
+//! queue ([`ProcessTable::find_next_runnable_on`]). Processes start on
+//! their parent's CPU and move only when an idle CPU steals one that has
+//! not run yet ([`ProcessTable::steal`]).
+//!
+//! # Threads
+//!
+//! A thread is a table entry of its own, with its own kernel stack and
+//! saved state, in the thread group of the process that created it
+//! ([`Process::tgid`]). The scheduler runs entries, so threads of one
+//! process run (and move between CPUs) independently while sharing the
+//! leader's page table. Everything else a process owns (descriptors,
+//! handles, mappings) lives on the leader's entry: [`ProcessTable::current`]
+//! and [`ProcessTable::current_pid`] resolve to it, while
+//! [`ProcessTable::current_thread`] is the entry actually running. See
+//! [`super::thread`].
+
+use crate::arch::amd64::entry::this_cpu;
 use crate::arch::amd64::mm::page_tables::PAddr;
+use crate::arch::amd64::mm::RxStatus;
+use core::sync::atomic::{AtomicU32, Ordering};
+use crate::interrupt::affinity::MAX_CPUS;
 use crate::syscall::fd::FileDescriptorTable;
 use crate::sync::SpinMutex;
 
@@ -27,6 +51,8 @@ pub enum ProcessState {
     Running,
     /// Process is blocked (waiting for I/O, event, etc.)
     Blocked,
+    /// Process is stopped by a suspend request (see [`crate::sched::suspend`])
+    Suspended,
     /// Process has exited but not yet reaped by parent
     Zombie,
     /// Process is dead (resources freed)
@@ -41,7 +67,7 @@ impl ProcessState {
 
     /// Check if process is alive
     pub const fn is_alive(&self) -> bool {
-        matches!(self, Self::Ready | Self::Running | Self::Blocked)
+        matches!(self, Self::Ready | Self::Running | Self::Blocked | Self::Suspended)
     }
 }
 
@@ -130,6 +156,27 @@ impl SavedState {
         }
     }
 
+    /// Create a SavedState for the child of a `FORK`
+    ///
+    /// The child resumes where the parent's syscall returns, with the
+    /// parent's stack and callee-saved registers and 0 in RAX.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The parent's syscall frame
+    /// * `cr3` - The child's page table physical address
+    pub fn for_fork(frame: &crate::arch::amd64::entry::SyscallFrame, cr3: u64) -> Self {
+        let mut state = Self::for_userspace(frame.rip, frame.rsp, cr3);
+        state.rflags = frame.rflags;
+        state.rbx = frame.rbx;
+        state.rbp = frame.rbp;
+        state.r12 = frame.r12;
+        state.r13 = frame.r13;
+        state.r14 = frame.r14;
+        state.r15 = frame.r15;
+        state
+    }
+
     /// Create a SavedState for returning from a syscall
     ///
     /// This is used when a process makes a syscall and needs to
@@ -152,7 +199,50 @@ impl Default for SavedState {
 /// ============================================================================
 
 /// Maximum number of processes in the system
-const MAX_PROCESSES: usize = 256;
+pub const MAX_PROCESSES: usize = 256;
+
+/// Size of a process's kernel stack (4 pages)
+pub const KERNEL_STACK_SIZE: usize = 4 * 4096;
+
+/// Exit code of a process killed by the kernel (fault, CPU-time limit)
+pub const EXIT_KILLED: i32 = -1;
+
+/// Exit code of a process terminated by a system shutdown
+pub const EXIT_TERMINATED: i32 = -2;
+
+/// Allocate a kernel stack
+///
+/// # Returns
+///
+/// The stack top (virtual address); the stack is [`KERNEL_STACK_SIZE`]
+/// bytes below it. Once the VMM is up the stack is mapped with a guard
+/// page below it (see [`vmm::alloc_stack`](crate::mm::vmm::alloc_stack));
+/// before that it is physically contiguous memory in the physmap.
+pub fn alloc_kernel_stack() -> Result<u64, RxStatus> {
+    use crate::mm::{pmm, vmm};
+
+    if vmm::is_ready() {
+        return vmm::alloc_stack(KERNEL_STACK_SIZE);
+    }
+    let paddr = pmm::pmm_alloc_contiguous(KERNEL_STACK_SIZE / 4096, pmm::PMM_ALLOC_FLAG_KERNEL, 0)?;
+    Ok((pmm::paddr_to_vaddr(paddr) + KERNEL_STACK_SIZE) as u64)
+}
+
+/// Free a stack from [`alloc_kernel_stack`], given its top
+pub(super) fn free_kernel_stack(top: u64) {
+    use crate::arch::amd64::mm::layout::VMALLOC;
+    use crate::mm::{pmm, vmm};
+
+    if top == 0 {
+        return;
+    }
+    if VMALLOC.contains(top - 1) {
+        let _ = vmm::free_stack(top);
+        return;
+    }
+    let paddr = pmm::vaddr_to_paddr(top as usize - KERNEL_STACK_SIZE);
+    let _ = pmm::pmm_free_contiguous(paddr, KERNEL_STACK_SIZE / 4096);
+}
 
 /// Process descriptor (Phase 5B)
 ///
@@ -165,18 +255,33 @@ pub struct Process {
     /// Parent process ID
     pub ppid: u32,
 
+    /// Thread group: the PID of the process this entry is a thread of
+    /// (its own PID for the process itself)
+    pub tgid: u32,
+
     /// Process state
     pub state: ProcessState,
 
+    /// Home CPU: the run queue the process is on (at first, the CPU that
+    /// created it)
+    pub cpu: usize,
+
+    /// Exit code, valid once the process is a zombie
+    pub exit_code: i32,
+
     /// Physical address of page table (CR3 value)
     pub page_table: PAddr,
 
-    /// Kernel stack base (virtual address)
+    /// Kernel stack top (virtual address, [`KERNEL_STACK_SIZE`] bytes)
     pub kernel_stack: u64,
 
     /// User stack top (virtual address)
     pub user_stack: u64,
 
+    /// Base of the user stack the kernel allocated for a thread, unmapped
+    /// when the thread is reaped
+    pub thread_stack: Option<u64>,
+
     /// Saved CPU state
     pub saved_state: SavedState,
 
@@ -186,12 +291,58 @@ pub struct Process {
     /// File descriptor table
     pub fd_table: FileDescriptorTable,
 
+    /// Kernel object handles (channels, ...)
+    pub handles: super::handles::ProcessHandles,
+
+    /// Working-set statistics from the last accessed-bit scan
+    pub wss: crate::mm::wss::WorkingSet,
+
     /// Time accounting
-    pub cpu_time: u64,
-    pub sched_time: u64,
+    ///
+    /// `cpu_time` is the total CPU time charged to the process;
+    /// `sched_time` is when it was last scheduled in (None until the
+    /// first time).
+    pub cpu_time: crate::time::Duration,
+    pub sched_time: Option<crate::time::Instant>,
+
+    /// Job this process belongs to (for resource limits)
+    pub job_id: crate::object::JobId,
+
+    /// SIGXCPU-style notification raised: the job's CPU-time limit was
+    /// exceeded and the process is inside the grace period
+    pub xcpu_pending: bool,
+
+    /// Deadline scheduling class state (`None` = normal class)
+    pub deadline: Option<crate::sched::deadline::DeadlineState>,
 
     /// Process name (for debugging)
     pub name: Option<alloc::string::String>,
+
+    /// Current working directory: an absolute, normalized path that
+    /// relative paths start from (see [`crate::fs::vfs::resolve`])
+    pub cwd: alloc::string::String,
+
+    /// Whether the process may use privileged syscalls (e.g. `KCOUNTERS_MAP`)
+    pub privileged: bool,
+
+    /// Outstanding suspend requests; the process stops while non-zero
+    pub suspend_count: u32,
+
+    /// End of a timed block: the scheduler makes a `Blocked` process
+    /// `Ready` once it passes (see [`ProcessTable::wake_expired`])
+    pub wake_at: Option<crate::time::Instant>,
+
+    /// Hardware breakpoints and watchpoints (loaded while running)
+    pub debug_state: crate::arch::amd64::debug::HwDebugState,
+
+    /// Last #DB delivered to the process, for the debugger
+    pub debug_exception: Option<crate::arch::amd64::debug::DebugException>,
+
+    /// I/O ports enabled with `IOPORT_ENABLE` (loaded while running)
+    pub io_ports: crate::arch::amd64::ioport::PortGrants,
+
+    /// TTY output not yet written (see [`crate::drivers::tty::OutputBuffer`])
+    pub tty_output: crate::drivers::tty::OutputBuffer,
 }
 
 impl Process {
@@ -219,16 +370,33 @@ impl Process {
         Self {
             pid,
             ppid,
+            tgid: pid,
             state: ProcessState::Ready,
+            cpu: this_cpu(),
+            exit_code: 0,
             page_table,
             kernel_stack,
             user_stack,
+            thread_stack: None,
             saved_state: SavedState::for_userspace(entry, user_stack, page_table),
             syscall_ret: 0,
             fd_table,
-            cpu_time: 0,
-            sched_time: 0,
+            handles: super::handles::ProcessHandles::new(),
+            wss: crate::mm::wss::WorkingSet::new(),
+            cpu_time: crate::time::Duration::ZERO,
+            sched_time: None,
+            job_id: crate::object::JOB_ID_ROOT,
+            xcpu_pending: false,
+            deadline: None,
             name: None,
+            cwd: alloc::string::String::from("/"),
+            privileged: false,
+            suspend_count: 0,
+            wake_at: None,
+            debug_state: crate::arch::amd64::debug::HwDebugState::new(),
+            debug_exception: None,
+            io_ports: crate::arch::amd64::ioport::PortGrants::new(),
+            tty_output: crate::drivers::tty::OutputBuffer::new(crate::drivers::tty::BufferMode::initial()),
         }
     }
 
@@ -241,6 +409,11 @@ impl Process {
     pub fn get_name(&self) -> Option<&str> {
         self.name.as_deref()
     }
+
+    /// Check if this entry is a thread of another process
+    pub fn is_thread(&self) -> bool {
+        self.tgid != self.pid
+    }
 }
 
 /// ============================================================================
@@ -255,8 +428,8 @@ pub struct ProcessTable {
     /// Process array (indexed by PID)
     processes: [Option<Process>; MAX_PROCESSES],
 
-    /// Current running process
-    current: Option<u32>,
+    /// Running process of each CPU
+    current: [Option<u32>; MAX_CPUS],
 
     /// Next PID to allocate
     next_pid: u32,
@@ -268,22 +441,44 @@ impl ProcessTable {
         const NONE: Option<Process> = None;
         Self {
             processes: [NONE; MAX_PROCESSES],
-            current: None,
+            current: [None; MAX_CPUS],
             next_pid: 1, // PID 0 is kernel
         }
     }
 
-    /// Get the current process
+    /// Get the current process (of the running CPU)
+    ///
+    /// For a thread, this is the process it belongs to.
     pub fn current(&self) -> Option<&Process> {
-        self.current.and_then(|pid| self.processes.get(pid as usize)?.as_ref())
+        self.current_pid().and_then(|pid| self.processes.get(pid as usize)?.as_ref())
+    }
+
+    /// PID and working directory a process spawned now inherits: the
+    /// current process's, or 0 and `/` when the kernel spawns it
+    pub fn spawn_parent(&self) -> (u32, alloc::string::String) {
+        match self.current() {
+            Some(parent) => (parent.pid, parent.cwd.clone()),
+            None => (0, alloc::string::String::from("/")),
+        }
     }
 
     /// Get the current process (mutable)
     pub fn current_mut(&mut self) -> Option<&mut Process> {
-        let pid = self.current?;
+        let pid = self.current_pid()?;
         self.processes.get_mut(pid as usize)?.as_mut()
     }
 
+    /// Get the entry running on this CPU, thread or process
+    pub fn current_thread(&self) -> Option<&Process> {
+        self.current_tid().and_then(|tid| self.processes.get(tid as usize)?.as_ref())
+    }
+
+    /// Get the entry running on this CPU (mutable)
+    pub fn current_thread_mut(&mut self) -> Option<&mut Process> {
+        let tid = self.current_tid()?;
+        self.processes.get_mut(tid as usize)?.as_mut()
+    }
+
     /// Get a process by PID
     pub fn get(&self, pid: u32) -> Option<&Process> {
         self.processes.get(pid as usize)?.as_ref()
@@ -295,12 +490,13 @@ impl ProcessTable {
     }
 
     /// Allocate a new PID
+    ///
+    /// PIDs of reaped processes are reused once the counter wraps.
     pub fn alloc_pid(&mut self) -> Option<u32> {
-        // Find next free PID
-        let start = self.next_pid;
-        loop {
+        // Find next free PID, skipping PID 0 (kernel)
+        for _ in 1..MAX_PROCESSES {
             if self.next_pid >= MAX_PROCESSES as u32 {
-                return None; // No more PIDs available
+                self.next_pid = 1;
             }
 
             let pid = self.next_pid;
@@ -309,12 +505,8 @@ impl ProcessTable {
             if self.processes[pid as usize].is_none() {
                 return Some(pid);
             }
-
-            // Wrapped around
-            if self.next_pid == start {
-                return None;
-            }
         }
+        None
     }
 
     /// Insert a process into the table
@@ -333,14 +525,51 @@ impl ProcessTable {
         self.processes[pid as usize] = Some(process);
     }
 
-    /// Set the current running process
+    /// Set the current running process (of the running CPU)
     pub fn set_current(&mut self, pid: u32) {
-        self.current = Some(pid);
+        self.set_current_on(this_cpu(), pid);
     }
 
-    /// Get the current PID
+    /// Get the current PID (of the running CPU)
+    ///
+    /// For a thread, this is the PID of the process it belongs to.
     pub fn current_pid(&self) -> Option<u32> {
-        self.current
+        let tid = self.current_tid()?;
+        Some(self.get(tid).map_or(tid, |p| p.tgid))
+    }
+
+    /// Get the ID of the entry running on this CPU, thread or process
+    pub fn current_tid(&self) -> Option<u32> {
+        self.current_on(this_cpu())
+    }
+
+    /// Set the running process of `cpu`
+    pub fn set_current_on(&mut self, cpu: usize, pid: u32) {
+        self.current[cpu % MAX_CPUS] = Some(pid);
+        ON_CPU[cpu % MAX_CPUS].store(pid, Ordering::Release);
+    }
+
+    /// Get the running process of `cpu`
+    pub fn current_on(&self, cpu: usize) -> Option<u32> {
+        self.current[cpu % MAX_CPUS]
+    }
+
+    /// Forget the running process of `cpu` (it went idle)
+    pub fn clear_current_on(&mut self, cpu: usize) {
+        self.current[cpu % MAX_CPUS] = None;
+        ON_CPU[cpu % MAX_CPUS].store(0, Ordering::Release);
+    }
+
+    /// Check if `pid` is the running process of any CPU
+    pub fn is_running(&self, pid: u32) -> bool {
+        self.current.contains(&Some(pid))
+    }
+
+    /// Check if `cpu` may run `pid` now: it is runnable and not running
+    /// on another CPU
+    pub fn can_run_on(&self, pid: u32, cpu: usize) -> bool {
+        self.get(pid).is_some_and(|p| p.state.is_runnable())
+            && self.current.iter().enumerate().all(|(other, &cur)| other == cpu % MAX_CPUS || cur != Some(pid))
     }
 
     /// Remove a process from the table
@@ -349,16 +578,27 @@ impl ProcessTable {
             return None;
         }
 
-        // If this is the current process, clear current
-        if self.current == Some(pid) {
-            self.current = None;
+        // If this is a current process, clear current
+        for current in self.current.iter_mut().filter(|c| **c == Some(pid)) {
+            *current = None;
         }
 
-        self.processes[pid as usize].take()
+        // Give back any deadline-class utilization it reserved
+        let mut process = self.processes[pid as usize].take();
+        if let Some(p) = process.as_mut() {
+            crate::sched::deadline::leave(p);
+        }
+        process
     }
 
-    /// Find the next runnable process
+    /// Find the next runnable process on the running CPU's queue
     pub fn find_next_runnable(&self, current_pid: Option<u32>) -> Option<u32> {
+        self.find_next_runnable_on(this_cpu(), current_pid)
+    }
+
+    /// Find the next runnable process on `cpu`'s queue, round-robin after
+    /// `current_pid`
+    pub fn find_next_runnable_on(&self, cpu: usize, current_pid: Option<u32>) -> Option<u32> {
         // Start from the process after current (or 0 if none)
         let start = current_pid.map_or(0, |p| (p + 1) % MAX_PROCESSES as u32);
 
@@ -366,7 +606,7 @@ impl ProcessTable {
         let mut pid = start;
         loop {
             if let Some(process) = self.get(pid) {
-                if process.state.is_runnable() {
+                if process.cpu == cpu && self.can_run_on(pid, cpu) {
                     return Some(pid);
                 }
             }
@@ -380,6 +620,17 @@ impl ProcessTable {
         }
     }
 
+    /// Make every `Blocked` process whose [`Process::wake_at`] has passed
+    /// `Ready`
+    pub fn wake_expired(&mut self, now: crate::time::Instant) {
+        for p in self.processes.iter_mut().flatten() {
+            if p.state == ProcessState::Blocked && p.wake_at.is_some_and(|at| at.has_passed(now)) {
+                p.state = ProcessState::Ready;
+                p.wake_at = None;
+            }
+        }
+    }
+
     /// Get all runnable PIDs
     pub fn runnable_pids(&self) -> alloc::vec::Vec<u32> {
         let mut pids = alloc::vec::Vec::new();
@@ -393,6 +644,123 @@ impl ProcessTable {
         pids
     }
 
+    /// A process on `cpu`'s queue that has not run yet
+    ///
+    /// Its whole state is in [`Process::saved_state`], so any CPU can
+    /// start it.
+    pub fn find_new_on(&self, cpu: usize) -> Option<u32> {
+        self.iter()
+            .find(|p| p.cpu == cpu && p.state == ProcessState::Ready && p.sched_time.is_none() && !self.is_running(p.pid))
+            .map(|p| p.pid)
+    }
+
+    /// Move a process that has not run yet from the busiest other queue
+    /// to `cpu`'s
+    ///
+    /// A process that has run is never stolen: it may still be on the
+    /// kernel stack of the CPU it ran on.
+    ///
+    /// # Returns
+    ///
+    /// The stolen PID, or `None` if no other queue has a new process
+    pub fn steal(&mut self, cpu: usize) -> Option<u32> {
+        let mut ready = [0usize; MAX_CPUS];
+        for p in self.iter().filter(|p| p.state == ProcessState::Ready) {
+            ready[p.cpu % MAX_CPUS] += 1;
+        }
+
+        let mut victims: [usize; MAX_CPUS] = core::array::from_fn(|i| i);
+        victims.sort_unstable_by_key(|&victim| core::cmp::Reverse(ready[victim]));
+        let pid = victims
+            .iter()
+            .filter(|&&victim| victim != cpu && ready[victim] > 0)
+            .find_map(|&victim| self.find_new_on(victim))?;
+
+        if let Some(process) = self.get_mut(pid) {
+            process.cpu = cpu;
+        }
+        Some(pid)
+    }
+
+    /// Find an exited child of `parent`
+    ///
+    /// `pid` selects one child; `None` accepts any. Threads are not
+    /// children.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Some(pid))` - A zombie child, ready to be reaped
+    /// - `Ok(None)` - Matching children exist but none has exited
+    /// - `Err(ERR_NOT_FOUND)` - No matching child
+    pub fn find_zombie_child(&self, parent: u32, pid: Option<u32>) -> Result<Option<u32>, RxStatus> {
+        let mut found = false;
+        let children = self.iter().filter(|p| p.ppid == parent && !p.is_thread());
+        for p in children.filter(|p| pid.is_none_or(|pid| p.pid == pid)) {
+            if p.state == ProcessState::Zombie {
+                return Ok(Some(p.pid));
+            }
+            found = true;
+        }
+        if found {
+            Ok(None)
+        } else {
+            Err(RxStatus::ERR_NOT_FOUND)
+        }
+    }
+
+    /// Zombies that no live parent will reap, and exited threads
+    ///
+    /// Current processes are skipped: they are still on their kernel stacks.
+    pub fn orphan_zombies(&self) -> alloc::vec::Vec<u32> {
+        self.iter()
+            .filter(|p| p.state == ProcessState::Zombie && !self.is_running(p.pid))
+            .filter(|p| {
+                p.is_thread() || p.ppid == 0 || !self.get(p.ppid).is_some_and(|parent| parent.state.is_alive())
+            })
+            .map(|p| p.pid)
+            .collect()
+    }
+
+    /// Hand the children of `parent` to the kernel (PPID 0)
+    ///
+    /// Their zombies are then reaped by [`reap_orphans`].
+    pub fn reparent_children(&mut self, parent: u32) {
+        for p in self.processes.iter_mut().flatten() {
+            if p.ppid == parent {
+                p.ppid = 0;
+            }
+        }
+    }
+
+    /// Threads of the process `pid`, not counting the process itself
+    pub fn threads_of(&self, pid: u32) -> alloc::vec::Vec<u32> {
+        self.iter().filter(|p| p.tgid == pid && p.pid != pid).map(|p| p.pid).collect()
+    }
+
+    /// Remove a zombie so its resources can be released
+    ///
+    /// Returns `None` if `pid` is not a zombie, is still the current
+    /// process of a CPU (it has not switched off its kernel stack yet), or
+    /// still has threads in the table (they use its page table).
+    pub fn take_zombie(&mut self, pid: u32) -> Option<Process> {
+        let zombie = self.get(pid)?.state == ProcessState::Zombie;
+        if !zombie || self.is_running(pid) || !self.threads_of(pid).is_empty() {
+            return None;
+        }
+        self.reparent_children(pid);
+        self.remove(pid)
+    }
+
+    /// Iterate over all processes
+    pub fn iter(&self) -> impl Iterator<Item = &Process> {
+        self.processes.iter().flatten()
+    }
+
+    /// Iterate over all processes, mutably
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Process> {
+        self.processes.iter_mut().flatten()
+    }
+
     /// Get process count
     pub fn count(&self) -> usize {
         self.processes.iter().filter(|p| p.is_some()).count()
@@ -410,7 +778,29 @@ impl Default for ProcessTable {
 /// ============================================================================
 
 /// Global process table instance
-pub static PROCESS_TABLE: SpinMutex<ProcessTable> = SpinMutex::new(ProcessTable::new());
+pub static PROCESS_TABLE: SpinMutex<ProcessTable> =
+    SpinMutex::named(ProcessTable::new(), &crate::sync::lockstat::PROCESS_TABLE);
+
+/// Running entry of each CPU (0 = none), as set by
+/// [`ProcessTable::set_current_on`], for readers that cannot take the
+/// process table lock
+static ON_CPU: [AtomicU32; MAX_CPUS] = [const { AtomicU32::new(0) }; MAX_CPUS];
+
+/// Get the entry running on `cpu` without locking the process table
+///
+/// May be stale by the time it returns; see [`ProcessTable::current_on`]
+/// for an answer that holds while the table is locked.
+pub fn running_on(cpu: usize) -> Option<u32> {
+    match ON_CPU[cpu % MAX_CPUS].load(Ordering::Acquire) {
+        0 => None,
+        pid => Some(pid),
+    }
+}
+
+/// Check if `pid` is running on any CPU without locking the process table
+pub fn is_on_cpu(pid: u32) -> bool {
+    pid != 0 && ON_CPU.iter().any(|cur| cur.load(Ordering::Acquire) == pid)
+}
 
 /// ============================================================================
 /// Helper type for SpinMutex guard
@@ -442,11 +832,151 @@ where
     F: FnOnce(&mut Process) -> R,
 {
     let mut table = PROCESS_TABLE.lock();
-    let current = table.current?;
+    let current = table.current_pid()?;
     let process = table.get_mut(current)?;
     Some(f(process))
 }
 
+/// Close the current process's handles as it exits
+///
+/// Unclosed handles may be reported as leaks (see
+/// [`close_all`](super::handles::ProcessHandles::close_all)). The objects
+/// are released after the table lock is dropped.
+pub fn close_current_handles() {
+    let closed = with_current_process_mut(|p| p.handles.close_all(p.pid));
+    drop(closed);
+}
+
+/// Terminate the current process
+///
+/// Writes its pending TTY output, closes its handles and file descriptors,
+/// marks it and all its threads zombies holding `code` and switches
+/// away. Threads running on other CPUs stop at their next syscall return
+/// or switch out. Its children are handed to the kernel. The rest of its resources
+/// are freed when it is reaped: by its parent's `WAIT_PID`, or by
+/// [`reap_orphans`] if the parent is gone. Never returns: with no other
+/// process to run, the CPU idles (an AP in its idle loop, see
+/// [`crate::sched::idle`]).
+pub fn exit_current(code: i32) -> ! {
+    crate::drivers::tty::flush_current();
+    close_current_handles();
+    let (pid, exited) = {
+        let mut table = PROCESS_TABLE.lock();
+        let pid = table.current_pid();
+        let exited = pid.and_then(|pid| table.get_mut(pid)).map(|p| {
+            p.state = ProcessState::Zombie;
+            p.exit_code = code;
+            p.fd_table.close_all();
+            crate::sched::deadline::leave(p);
+        });
+        if let Some(pid) = pid {
+            for tid in table.threads_of(pid) {
+                if let Some(thread) = table.get_mut(tid) {
+                    thread.state = ProcessState::Zombie;
+                    thread.exit_code = code;
+                    crate::sched::deadline::leave(thread);
+                }
+            }
+            table.reparent_children(pid);
+        }
+        (pid, exited)
+    };
+    // A compositor that dies gives the display back to the VTs
+    if let Some(pid) = pid {
+        crate::drivers::display::vt::release_display(pid);
+    }
+    reap_orphans();
+    if exited.is_some() {
+        crate::sched::idle::exit_to_idle();
+        let _ = crate::sched::round_robin::yield_cpu();
+    }
+    loop {
+        unsafe { core::arch::asm!("sti", "hlt", options(nomem, nostack)) };
+    }
+}
+
+/// Terminate the current process from a trap it cannot return from
+///
+/// Exits with [`EXIT_KILLED`]; see [`exit_current`].
+pub fn kill_current() -> ! {
+    exit_current(EXIT_KILLED)
+}
+
+/// Free everything a removed process still holds
+///
+/// Called without the table lock: closing handles and dropping VMOs may
+/// wake or free other objects. A thread only holds its kernel stack and
+/// the user stack the kernel gave it; the page table is its process's.
+fn release(mut process: Process) {
+    crate::time::timer_queue::cancel_thread(process.pid);
+    if process.is_thread() {
+        super::thread::release_stack(&process);
+        free_kernel_stack(process.kernel_stack);
+        return;
+    }
+
+    let closed = process.handles.close_all(process.pid);
+    drop(closed);
+    super::futex::release_process(process.pid);
+    super::jobs::leave(process.job_id);
+
+    let page_table = process.page_table;
+    let kernel_stack = process.kernel_stack;
+
+    // Drops the VMAR (and its VMOs' pages) and the file descriptor table
+    drop(super::vmar::remove(page_table));
+    drop(process);
+
+    // SAFETY: a zombie that is not current has switched to another
+    // process's page table and kernel stack for good
+    unsafe { super::address_space::destroy(page_table) };
+    free_kernel_stack(kernel_stack);
+}
+
+/// Free what a spawn set up for a process that never made it into the table
+///
+/// For failures after [`jobs::admit`](super::jobs::admit): stops counting
+/// the process in `job`, drops its mappings (giving back their memory
+/// charge), then frees its page table and kernel stack. Pass 0 for either
+/// if it was not allocated yet.
+pub fn discard_spawn(job: crate::object::JobId, vmar: super::vmar::Vmar, page_table: PAddr, kernel_stack: u64) {
+    super::jobs::leave(job);
+    drop(vmar);
+
+    // SAFETY: the page table was never loaded
+    unsafe { super::address_space::destroy(page_table) };
+    free_kernel_stack(kernel_stack);
+}
+
+/// Reap a zombie, freeing its resources
+///
+/// # Returns
+///
+/// The exit code, or `None` if `pid` is not a reapable zombie (see
+/// [`ProcessTable::take_zombie`])
+pub fn reap(pid: u32) -> Option<i32> {
+    // The process goes last: its threads use its page table
+    let threads = PROCESS_TABLE.lock().threads_of(pid);
+    for tid in threads {
+        if let Some(thread) = PROCESS_TABLE.lock().take_zombie(tid) {
+            release(thread);
+        }
+    }
+
+    let process = PROCESS_TABLE.lock().take_zombie(pid)?;
+    let code = process.exit_code;
+    release(process);
+    Some(code)
+}
+
+/// Reap every zombie whose parent is gone
+pub fn reap_orphans() {
+    let orphans = PROCESS_TABLE.lock().orphan_zombies();
+    for pid in orphans {
+        let _ = reap(pid);
+    }
+}
+
 /// Get a process by PID with manual locking
 pub fn with_process<F, R>(pid: u32, f: F) -> Option<R>
 where
@@ -470,6 +1000,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test]
     fn test_saved_state_new() {
@@ -495,6 +1026,8 @@ mod tests {
         assert!(ProcessState::Ready.is_runnable());
         assert!(ProcessState::Running.is_runnable());
         assert!(!ProcessState::Blocked.is_runnable());
+        assert!(!ProcessState::Suspended.is_runnable());
+        assert!(ProcessState::Suspended.is_alive());
         assert!(!ProcessState::Zombie.is_runnable());
         assert!(!ProcessState::Dead.is_runnable());
     }
@@ -559,4 +1092,186 @@ mod tests {
         assert_eq!(table.find_next_runnable(Some(2)), Some(3));
         assert_eq!(table.find_next_runnable(Some(3)), Some(1)); // Wrap around
     }
+
+    fn zombie(pid: u32, ppid: u32) -> Process {
+        let mut p = Process::new(pid, ppid, 0x1000, 0, 0x7000_0000_0000, 0x4000);
+        p.state = ProcessState::Zombie;
+        p
+    }
+
+    #[test]
+    fn test_find_zombie_child() {
+        let mut table = ProcessTable::new();
+        table.insert(Process::new(1, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+        table.insert(Process::new(2, 1, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+
+        assert_eq!(table.find_zombie_child(1, None), Ok(None));
+        assert_eq!(table.find_zombie_child(1, Some(3)), Err(RxStatus::ERR_NOT_FOUND));
+        assert_eq!(table.find_zombie_child(2, None), Err(RxStatus::ERR_NOT_FOUND));
+
+        table.insert(zombie(3, 1));
+        assert_eq!(table.find_zombie_child(1, None), Ok(Some(3)));
+        assert_eq!(table.find_zombie_child(1, Some(2)), Ok(None));
+    }
+
+    #[test]
+    fn test_take_zombie_skips_current() {
+        let mut table = ProcessTable::new();
+        table.insert(Process::new(1, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+        table.insert(zombie(2, 1));
+        table.insert(Process::new(3, 2, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+
+        assert!(table.take_zombie(1).is_none());
+        table.set_current(2);
+        assert!(table.take_zombie(2).is_none());
+
+        table.set_current(1);
+        assert_eq!(table.take_zombie(2).map(|p| p.pid), Some(2));
+        assert_eq!(table.get(3).unwrap().ppid, 0);
+    }
+
+    #[test]
+    fn test_orphan_zombies() {
+        let mut table = ProcessTable::new();
+        table.insert(Process::new(1, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+        table.insert(zombie(2, 1));
+        table.insert(zombie(3, 0));
+        table.insert(zombie(4, 2));
+
+        // 2 still has a live parent; 4's parent has exited
+        assert_eq!(table.orphan_zombies(), vec![3, 4]);
+    }
+
+    fn thread(tid: u32, pid: u32) -> Process {
+        let mut t = Process::new(tid, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000);
+        t.tgid = pid;
+        t
+    }
+
+    #[test]
+    fn test_threads_resolve_to_their_process() {
+        let mut table = ProcessTable::new();
+        table.insert(Process::new(1, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+        table.insert(Process::new(2, 1, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+        table.insert(thread(3, 2));
+
+        table.set_current(3);
+        assert_eq!(table.current_tid(), Some(3));
+        assert_eq!(table.current_pid(), Some(2));
+        assert_eq!(table.current().unwrap().pid, 2);
+        assert!(table.current_thread().unwrap().is_thread());
+        assert_eq!(table.threads_of(2), vec![3]);
+
+        // A thread is not a child, even once it has exited
+        table.get_mut(3).unwrap().state = ProcessState::Zombie;
+        table.get_mut(3).unwrap().ppid = 1;
+        assert_eq!(table.find_zombie_child(1, None), Ok(None));
+    }
+
+    #[test]
+    fn test_spawn_inherits_cwd() {
+        let mut table = ProcessTable::new();
+        assert_eq!(table.spawn_parent(), (0, alloc::string::String::from("/")));
+
+        let mut parent = Process::new(2, 1, 0x1000, 0, 0x7000_0000_0000, 0x4000);
+        parent.cwd = alloc::string::String::from("/bin");
+        table.insert(parent);
+        table.insert(thread(3, 2));
+
+        // Spawning from a thread gives the child its process's directory
+        table.set_current(3);
+        assert_eq!(table.spawn_parent(), (2, alloc::string::String::from("/bin")));
+    }
+
+    #[test]
+    fn test_process_is_reaped_after_its_threads() {
+        let mut table = ProcessTable::new();
+        table.insert(Process::new(1, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+        table.insert(zombie(2, 1));
+        let mut t = thread(3, 2);
+        t.state = ProcessState::Zombie;
+        table.insert(t);
+
+        // The exited thread is reaped whatever its process does
+        assert_eq!(table.orphan_zombies(), vec![3]);
+        assert!(table.take_zombie(2).is_none());
+        assert_eq!(table.take_zombie(3).map(|p| p.pid), Some(3));
+        assert_eq!(table.take_zombie(2).map(|p| p.pid), Some(2));
+    }
+
+    #[test]
+    fn test_alloc_pid_reuses_freed_pids() {
+        let mut table = ProcessTable::new();
+        table.next_pid = MAX_PROCESSES as u32 - 1;
+        table.insert(Process::new(1, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000));
+
+        assert_eq!(table.alloc_pid(), Some(MAX_PROCESSES as u32 - 1));
+        assert_eq!(table.alloc_pid(), Some(2));
+    }
+
+    fn on_cpu(pid: u32, cpu: usize) -> Process {
+        let mut p = Process::new(pid, 0, 0x1000, 0, 0x7000_0000_0000, 0x4000);
+        p.cpu = cpu;
+        p
+    }
+
+    #[test]
+    fn test_run_queues_are_per_cpu() {
+        let mut table = ProcessTable::new();
+        table.insert(on_cpu(1, 0));
+        table.insert(on_cpu(2, 1));
+        table.insert(on_cpu(3, 1));
+
+        assert_eq!(table.find_next_runnable_on(0, None), Some(1));
+        assert_eq!(table.find_next_runnable_on(1, None), Some(2));
+
+        // Running on CPU 1 keeps it off every other CPU
+        table.set_current_on(1, 2);
+        assert!(table.is_running(2));
+        assert_eq!(running_on(1), Some(2));
+        assert!(!table.can_run_on(2, 0));
+        assert!(table.can_run_on(2, 1));
+        assert_eq!(table.find_next_runnable_on(1, Some(2)), Some(3));
+
+        table.clear_current_on(1);
+        assert!(!table.is_running(2));
+        assert_eq!(running_on(1), None);
+    }
+
+    #[test]
+    fn test_steal_takes_new_process_from_busiest_queue() {
+        let mut table = ProcessTable::new();
+        table.insert(on_cpu(1, 0));
+        table.insert(on_cpu(2, 1));
+        table.insert(on_cpu(3, 1));
+
+        // Already ran: may still be on CPU 1's kernel stack
+        table.get_mut(2).unwrap().sched_time = Some(crate::time::Instant::ZERO);
+
+        assert_eq!(table.steal(2), Some(3));
+        assert_eq!(table.get(3).unwrap().cpu, 2);
+        assert_eq!(table.steal(2), Some(1));
+        assert_eq!(table.steal(2), None);
+        assert_eq!(table.get(2).unwrap().cpu, 1);
+    }
+
+    #[test]
+    fn test_wake_expired() {
+        use crate::time::Instant;
+
+        let mut table = ProcessTable::new();
+        for pid in 1..=3 {
+            table.insert(on_cpu(pid, 0));
+            table.get_mut(pid).unwrap().state = ProcessState::Blocked;
+        }
+        table.get_mut(1).unwrap().wake_at = Some(Instant::from_nanos(100));
+        table.get_mut(2).unwrap().wake_at = Some(Instant::from_nanos(200));
+
+        table.wake_expired(Instant::from_nanos(150));
+        assert_eq!(table.get(1).unwrap().state, ProcessState::Ready);
+        assert_eq!(table.get(1).unwrap().wake_at, None);
+        assert_eq!(table.get(2).unwrap().state, ProcessState::Blocked);
+        // No deadline: blocked until woken
+        assert_eq!(table.get(3).unwrap().state, ProcessState::Blocked);
+    }
 }
@@ -0,0 +1,213 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Threads
+//!
+//! `THREAD_START` adds a thread to the calling process; `THREAD_EXIT`
+//! ends the calling thread. A thread is a process table entry whose
+//! [`tgid`](super::table::Process::tgid) names its process (the thread
+//! group leader). It has its own kernel stack and saved registers and
+//! shares the leader's page table, so the scheduler runs it like any
+//! other entry, on any CPU, with the process's CR3. Descriptors and
+//! handles stay on the leader's entry; mappings are found by the shared
+//! page table (see [`vmar::of`](super::vmar::of)).
+//!
+//! # Stacks
+//!
+//! A thread runs on the user stack its creator passes, or on one the
+//! kernel maps for it: [`THREAD_STACK_SIZE`] bytes of a fresh VMO in the
+//! slot for its thread ID below [`THREAD_STACKS_TOP`] (see
+//! [`stack_slot`]). Slots are spaced [`THREAD_STACK_SLOT`] apart, so an
+//! unmapped gap below each stack catches overflows. A kernel-allocated
+//! stack is unmapped when the thread is reaped.
+//!
+//! # Lifetime
+//!
+//! - `THREAD_EXIT` makes the thread a zombie; it is reaped as soon as it
+//!   has switched off its kernel stack. `THREAD_EXIT` from the leader
+//!   exits the whole process, like `PROCESS_EXIT`.
+//! - `PROCESS_EXIT` (or a kill) from any thread ends every thread of the
+//!   process. The process is reaped only after all its threads.
+//! - Threads are not children: `WAIT_PID` never returns one.
+
+use alloc::sync::Arc;
+use crate::arch::amd64::mm::{layout, RxStatus};
+use crate::exec::elf::{PF_R, PF_W};
+use crate::object::{Vmo, VmoFlags};
+use super::table::{alloc_kernel_stack, Process, ProcessState, SavedState, MAX_PROCESSES, PROCESS_TABLE};
+
+/// Size of a kernel-allocated thread stack
+pub const THREAD_STACK_SIZE: u64 = 64 * 1024;
+
+/// Distance between thread stack slots (the stack plus a guard gap)
+pub const THREAD_STACK_SLOT: u64 = 4 * THREAD_STACK_SIZE;
+
+/// Top of the thread stack area (see [`layout::THREAD_STACKS`])
+pub const THREAD_STACKS_TOP: u64 = layout::THREAD_STACKS.end;
+
+/// Base of the stack slot for thread `tid`
+pub const fn stack_slot(tid: u32) -> u64 {
+    THREAD_STACKS_TOP - (tid as u64 + 1) * THREAD_STACK_SLOT
+}
+
+const _: () = assert!(stack_slot(MAX_PROCESSES as u32 - 1) >= layout::THREAD_STACKS.start);
+
+/// Start a thread in the current process
+///
+/// # Arguments
+///
+/// * `entry` - Userspace address the thread starts at
+/// * `stack_top` - Top of its user stack, or 0 for a kernel-allocated one
+/// * `arg` - Value passed in `rdi`
+///
+/// On a kernel-allocated stack, `rsp` starts 8 bytes below the top, as if
+/// `entry` had been called. The entry function must not return; it ends
+/// with `THREAD_EXIT`.
+///
+/// # Returns
+///
+/// The thread ID
+pub fn start(entry: u64, stack_top: u64, arg: u64) -> Result<u32, RxStatus> {
+    use crate::syscall::uaccess::validate_user_range;
+
+    validate_user_range(entry as usize, 1)?;
+    if stack_top != 0 {
+        validate_user_range(stack_top as usize - 1, 1)?;
+    }
+    let kernel_stack = alloc_kernel_stack()?;
+
+    let tid = {
+        let mut table = PROCESS_TABLE.lock();
+        let result = insert(&mut table, entry, stack_top, arg, kernel_stack);
+        if result.is_err() {
+            drop(table);
+            super::table::free_kernel_stack(kernel_stack);
+        }
+        result?
+    };
+    crate::sched::idle::kick();
+    Ok(tid)
+}
+
+/// Create the thread's table entry and, if needed, its stack
+fn insert(
+    table: &mut super::table::ProcessTable,
+    entry: u64,
+    stack_top: u64,
+    arg: u64,
+    kernel_stack: u64,
+) -> Result<u32, RxStatus> {
+    let pid = table.current_pid().ok_or(RxStatus::ERR_NOT_FOUND)?;
+    let tid = table.alloc_pid().ok_or(RxStatus::ERR_NO_MEMORY)?;
+    let leader = table.get_mut(pid).ok_or(RxStatus::ERR_NOT_FOUND)?;
+
+    let (user_stack, thread_stack) = if stack_top != 0 {
+        (stack_top, None)
+    } else {
+        let base = stack_slot(tid);
+        let vmo = Vmo::create(THREAD_STACK_SIZE as usize, VmoFlags::empty).map_err(|_| RxStatus::ERR_NO_MEMORY)?;
+        vmo.set_job(leader.job_id)?;
+        let vmar = super::vmar::of(leader.page_table).ok_or(RxStatus::ERR_NOT_FOUND)?;
+        crate::syscall::vmo::map_into(
+            leader.page_table,
+            &mut vmar.lock(),
+            Arc::new(vmo),
+            base as usize,
+            THREAD_STACK_SIZE as usize,
+            PF_R | PF_W,
+        )?;
+        (base + THREAD_STACK_SIZE - 8, Some(base))
+    };
+
+    let mut thread = Process::new(tid, leader.ppid, leader.page_table, kernel_stack, user_stack, entry);
+    thread.tgid = pid;
+    thread.thread_stack = thread_stack;
+    thread.saved_state = SavedState::for_userspace(entry, user_stack, leader.page_table);
+    thread.saved_state.rdi = arg;
+    thread.job_id = leader.job_id;
+    thread.privileged = leader.privileged;
+    thread.io_ports = leader.io_ports;
+    thread.name = leader.name.clone();
+    table.insert(thread);
+    Ok(tid)
+}
+
+/// End the current thread
+///
+/// From the leader, exits the whole process with `code` (see
+/// [`exit_current`](super::table::exit_current)). Never returns.
+pub fn exit_current(code: i32) -> ! {
+    let exited = {
+        let mut table = PROCESS_TABLE.lock();
+        match table.current_thread_mut() {
+            Some(thread) if thread.is_thread() => {
+                thread.state = ProcessState::Zombie;
+                thread.exit_code = code;
+                crate::sched::deadline::leave(thread);
+                true
+            }
+            _ => false,
+        }
+    };
+    if !exited {
+        super::table::exit_current(code);
+    }
+
+    super::table::reap_orphans();
+    crate::sched::idle::exit_to_idle();
+    let _ = crate::sched::round_robin::yield_cpu();
+    loop {
+        unsafe { core::arch::asm!("sti", "hlt", options(nomem, nostack)) };
+    }
+}
+
+/// Termination point on syscall return
+///
+/// Ends the current thread if its process exited on another CPU.
+pub fn checkpoint() {
+    let exited = PROCESS_TABLE
+        .lock()
+        .current_thread()
+        .is_some_and(|t| t.is_thread() && t.state == ProcessState::Zombie);
+    if exited {
+        crate::sched::idle::exit_to_idle();
+        let _ = crate::sched::round_robin::yield_cpu();
+        loop {
+            unsafe { core::arch::asm!("sti", "hlt", options(nomem, nostack)) };
+        }
+    }
+}
+
+/// Unmap the user stack the kernel allocated for a reaped thread
+///
+/// Called without the table lock; the stack's VMO is dropped after it is
+/// released.
+pub(super) fn release_stack(thread: &Process) {
+    let Some(base) = thread.thread_stack else { return };
+    let region = {
+        let table = PROCESS_TABLE.lock();
+        // A reaped leader's page table may belong to someone else by now
+        table
+            .get(thread.tgid)
+            .and_then(|leader| super::vmar::of(leader.page_table))
+            .and_then(|vmar| vmar.lock().unmap(thread.page_table, base))
+    };
+    drop(region);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_slots() {
+        assert_eq!(stack_slot(0) + THREAD_STACK_SLOT, THREAD_STACKS_TOP);
+        assert_eq!(stack_slot(1) + THREAD_STACK_SLOT, stack_slot(0));
+        assert_eq!(stack_slot(255) % 4096, 0);
+        // A gap separates each stack from the slot above
+        assert!(stack_slot(2) + THREAD_STACK_SIZE < stack_slot(1));
+    }
+}
@@ -0,0 +1,458 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Process Mappings and Demand Paging
+//!
+//! Each process records the VMO mappings in its address space in a
+//! [`Vmar`]. Mapping a VMO only installs page table entries for pages the
+//! VMO has already committed; the rest are filled in by the page fault
+//! handler the first time they are touched.
+//!
+//! The VMARs live here, by page table, rather than in the process table:
+//! the kernel copies to and from user memory with the table locked, and
+//! those copies fault too. Each VMAR has its own lock, shared by the
+//! process's threads (see [`of`]). Nothing touches user memory while
+//! holding one.
+//!
+//! # Page Faults
+//!
+//! A fault on a user address is resolved by [`handle_fault`]:
+//!
+//! 1. Find the mapping containing the address in the VMAR of the current
+//!    page table (CR3).
+//! 2. Check the access against the mapping's permissions.
+//! 3. Commit the VMO page (a zero-filled page from the PMM if the VMO
+//!    has none yet) and map it.
+//!
+//! Pages a VMO shares copy-on-write with a clone are mapped read-only. A
+//! write to one faults with the page present; step 3 then gives the VMO a
+//! private copy of the page (see [`crate::object::vmo`]) and maps that
+//! writable.
+//!
+//! If step 3 finds no free page, memory reclaim gets a chance to free
+//! some (see [`crate::mm::reclaim`]) and the fault is tried once more.
+//!
+//! The faulting instruction is then restarted. A fault outside any
+//! mapping, or an access the mapping does not allow, is not resolved;
+//! the caller kills the process (or, for a kernel user-copy, takes the
+//! exception table fixup).
+//!
+//! # Fork
+//!
+//! [`fork`] copies a process's mappings for a `FORK` child. Writable
+//! mappings become copy-on-write clones of the parent's VMOs, so the
+//! first write on either side takes the fault path above.
+//!
+//! # Errors
+//!
+//! | Condition | Status |
+//! |-----------|--------|
+//! | Range overlaps an existing mapping | `ERR_BUSY` |
+//! | Mappings locked on another CPU (fault) | `ERR_BUSY` |
+//! | No mapping at the address | `ERR_NOT_FOUND` |
+//! | Write / execute the mapping does not allow | `ERR_ACCESS_DENIED` |
+//! | Write to a read-only VMO page | `ERR_ACCESS_DENIED` |
+//! | No free page to commit or copy | `ERR_NO_MEMORY` |
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use crate::arch::amd64::faults::pf_error;
+use crate::arch::amd64::mm::{PAddr, RxStatus};
+use crate::exec::elf::{PF_W, PF_X};
+use crate::object::vmo::{PageMapEntry, Vmo};
+use crate::sync::SpinMutex;
+
+/// Page size
+const PAGE_SIZE: u64 = 4096;
+
+/// Physical address bits of CR3
+const CR3_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// The mappings of a process, shared by its threads
+pub type SharedVmar = Arc<SpinMutex<Vmar>>;
+
+/// Every process's mappings, by page table
+static VMARS: SpinMutex<BTreeMap<PAddr, SharedVmar>> = SpinMutex::new(BTreeMap::new());
+
+/// Give the address space rooted at `page_table` its mappings
+///
+/// Called when its process enters the process table.
+pub fn install(page_table: PAddr, vmar: Vmar) {
+    VMARS.lock().insert(page_table, Arc::new(SpinMutex::new(vmar)));
+}
+
+/// The mappings of the address space rooted at `page_table`
+///
+/// Lock the result without holding it across user memory accesses: the
+/// page fault handler takes the same lock.
+pub fn of(page_table: PAddr) -> Option<SharedVmar> {
+    VMARS.lock().get(&page_table).cloned()
+}
+
+/// Take the mappings of an address space that is going away
+///
+/// Its VMOs are released when the result is dropped.
+pub fn remove(page_table: PAddr) -> Option<SharedVmar> {
+    VMARS.lock().remove(&page_table)
+}
+
+/// Every address space's mappings, with its page table
+pub fn all() -> alloc::vec::Vec<(PAddr, SharedVmar)> {
+    VMARS.lock().iter().map(|(&pt, vmar)| (pt, Arc::clone(vmar))).collect()
+}
+
+/// A VMO mapped into a process
+#[derive(Clone)]
+pub struct Region {
+    /// First mapped address (page-aligned)
+    pub base: u64,
+
+    /// Mapped bytes (whole pages)
+    pub size: u64,
+
+    /// Mapped object; offset 0 of the VMO is at `base`
+    pub vmo: Arc<Vmo>,
+
+    /// Page flags (`PF_R | PF_W | PF_X`)
+    pub flags: u32,
+}
+
+impl Region {
+    /// Check if `vaddr` is inside the region
+    pub fn contains(&self, vaddr: u64) -> bool {
+        vaddr >= self.base && vaddr - self.base < self.size
+    }
+}
+
+/// The mappings of one address space, by base address
+pub struct Vmar {
+    regions: BTreeMap<u64, Region>,
+}
+
+impl Vmar {
+    /// Create an empty set of mappings
+    pub const fn new() -> Self {
+        Self { regions: BTreeMap::new() }
+    }
+
+    /// Record a mapping
+    ///
+    /// `size` is rounded up to whole pages.
+    pub fn insert(&mut self, base: u64, size: u64, vmo: Arc<Vmo>, flags: u32) -> Result<(), RxStatus> {
+        if !base.is_multiple_of(PAGE_SIZE) || size == 0 {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        let size = size.checked_add(PAGE_SIZE - 1).ok_or(RxStatus::ERR_INVALID_ARGS)? & !(PAGE_SIZE - 1);
+        let end = base.checked_add(size).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+
+        // The region below may run into this one; the one above may start inside it
+        if self.find(base).is_some() || self.regions.range(base..end).next().is_some() {
+            return Err(RxStatus::ERR_BUSY);
+        }
+        self.regions.insert(base, Region { base, size, vmo, flags });
+        Ok(())
+    }
+
+    /// Forget the mapping starting at `base`
+    pub fn remove(&mut self, base: u64) -> Option<Region> {
+        self.regions.remove(&base)
+    }
+
+    /// Remove the mapping starting at `base` and unmap its pages
+    ///
+    /// The VMO keeps its pages; they are freed when the returned region's
+    /// reference to it is dropped.
+    pub fn unmap(&mut self, page_table: PAddr, base: u64) -> Option<Region> {
+        let region = self.regions.remove(&base)?;
+        let aspace = unsafe { super::AddressSpace::from_page_table(page_table) };
+        for offset in (0..region.size).step_by(PAGE_SIZE as usize) {
+            // Pages never touched are not mapped
+            let _ = aspace.unmap_page(region.base + offset);
+        }
+        Some(region)
+    }
+
+    /// The mapping containing `vaddr`
+    pub fn find(&self, vaddr: u64) -> Option<&Region> {
+        self.regions
+            .range(..=vaddr)
+            .next_back()
+            .map(|(_, r)| r)
+            .filter(|r| r.contains(vaddr))
+    }
+
+    /// Commit and map every page of every mapping up front
+    ///
+    /// For images run without a process table entry, where the fault
+    /// handler has no mappings to consult.
+    pub fn commit_all(&self, page_table: PAddr) -> Result<(), RxStatus> {
+        for region in self.regions.values() {
+            for offset in (0..region.size).step_by(PAGE_SIZE as usize) {
+                let page = region.vmo.commit_page(offset as usize).map_err(|_| RxStatus::ERR_NO_MEMORY)?;
+                map_page(page_table, region.base + offset, page.paddr, page_flags(region, &page))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Charge the mapped VMOs that have no job yet to `job`
+    ///
+    /// For a new process's image, whose VMOs were filled before it had a
+    /// job; see [`Vmo::set_job`].
+    ///
+    /// # Returns
+    ///
+    /// `Err(ERR_NO_MEMORY)` if the job's memory limit would be exceeded
+    pub fn charge_to(&self, job: crate::object::JobId) -> Result<(), RxStatus> {
+        self.regions.values().try_for_each(|region| region.vmo.set_job(job))
+    }
+
+    /// The mappings, lowest address first
+    pub fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.regions.values()
+    }
+
+    /// Number of mappings
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Check if nothing is mapped
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+}
+
+impl Default for Vmar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a fault at `vaddr` with `error_code` should map
+///
+/// # Returns
+///
+/// The region and the page's offset within its VMO
+pub fn resolve(vmar: &Vmar, vaddr: u64, error_code: u64) -> Result<(&Region, usize), RxStatus> {
+    let region = vmar.find(vaddr).ok_or(RxStatus::ERR_NOT_FOUND)?;
+    if error_code & pf_error::W != 0 && region.flags & PF_W == 0 {
+        return Err(RxStatus::ERR_ACCESS_DENIED);
+    }
+    if error_code & pf_error::I != 0 && region.flags & PF_X == 0 {
+        return Err(RxStatus::ERR_ACCESS_DENIED);
+    }
+    let offset = (vaddr & !(PAGE_SIZE - 1)) - region.base;
+    Ok((region, offset as usize))
+}
+
+/// Page flags for mapping `page` in `region`
+///
+/// Read-only and copy-on-write VMO pages are mapped read-only whatever
+/// the region allows.
+fn page_flags(region: &Region, page: &PageMapEntry) -> u32 {
+    if page.map_writable() {
+        region.flags
+    } else {
+        region.flags & !PF_W
+    }
+}
+
+/// Resolve a page fault on a user address
+///
+/// Handles not-present faults and writes to copy-on-write pages in the
+/// current address space. Runs in the page fault handler, without the
+/// process table. Gives up with `ERR_BUSY` rather than spin if another
+/// CPU has the mappings locked; the caller retries.
+///
+/// # Arguments
+///
+/// * `vaddr` - Faulting address (CR2)
+/// * `error_code` - Page fault error code
+pub fn handle_fault(vaddr: u64, error_code: u64) -> Result<(), RxStatus> {
+    match fault_in(vaddr, error_code) {
+        // The mappings are unlocked again: reclaim can look at them
+        Err(RxStatus::ERR_NO_MEMORY) if crate::mm::reclaim::direct() > 0 => fault_in(vaddr, error_code),
+        result => result,
+    }
+}
+
+/// Commit and map the page for a fault; see [`handle_fault`]
+fn fault_in(vaddr: u64, error_code: u64) -> Result<(), RxStatus> {
+    let write = error_code & pf_error::W != 0;
+    if error_code & pf_error::P != 0 && !write {
+        // Present page, wrong access: not something paging can fix
+        return Err(RxStatus::ERR_ACCESS_DENIED);
+    }
+
+    let page_table = unsafe { crate::arch::amd64::registers::x86_get_cr3() } & CR3_ADDR_MASK;
+    let vmar = of(page_table).ok_or(RxStatus::ERR_NOT_FOUND)?;
+    let vmar = vmar.try_lock().ok_or(RxStatus::ERR_BUSY)?;
+    let (region, offset) = resolve(&vmar, vaddr, error_code)?;
+
+    let page = if write {
+        region.vmo.commit_page_for_write(offset)
+    } else {
+        region.vmo.commit_page(offset)
+    };
+    let page = page.map_err(|e| match e {
+        "page is read-only" => RxStatus::ERR_ACCESS_DENIED,
+        _ => RxStatus::ERR_NO_MEMORY,
+    })?;
+
+    let page_vaddr = vaddr & !(PAGE_SIZE - 1);
+    map_page(page_table, page_vaddr, page.paddr, page_flags(region, &page))?;
+    if error_code & pf_error::P != 0 {
+        // Replaced a present entry
+        unsafe { core::arch::asm!("invlpg [{}]", in(reg) page_vaddr, options(nostack, preserves_flags)) };
+    }
+    Ok(())
+}
+
+/// Copy a parent's mappings into the address space of a `FORK` child
+///
+/// Writable mappings get a copy-on-write clone of their VMO, so parent
+/// and child each see their own writes; read-only mappings share the
+/// parent's VMO. Pages the parent has committed are mapped right away
+/// (read-only while shared), the rest fault in as usual.
+///
+/// Cloning write-protects the parent's mappings, which locks every VMAR:
+/// call this without holding one.
+///
+/// # Arguments
+///
+/// * `regions` - The parent's mappings (see [`Vmar::regions`])
+/// * `page_table` - The child's page table
+pub fn fork(regions: &[Region], page_table: PAddr) -> Result<Vmar, RxStatus> {
+    let aspace = unsafe { super::AddressSpace::from_page_table(page_table) };
+    let mut vmar = Vmar::new();
+
+    for region in regions {
+        let vmo = if region.flags & PF_W != 0 {
+            Arc::new(Vmo::clone(&region.vmo).map_err(|_| RxStatus::ERR_NO_MEMORY)?)
+        } else {
+            Arc::clone(&region.vmo)
+        };
+        aspace
+            .map_vmo(&vmo, region.base, region.size, region.flags)
+            .map_err(|_| RxStatus::ERR_NO_MEMORY)?;
+        vmar.insert(region.base, region.size, vmo, region.flags)?;
+    }
+    Ok(vmar)
+}
+
+/// Make every existing mapping of `vmo`'s committed pages read-only
+///
+/// Called after `vmo` starts sharing its pages with a clone, so writes
+/// through old mappings fault and copy. Threads of the mapping processes
+/// may be running on other CPUs with the pages still writable in their
+/// TLBs, so this returns only once every other CPU has flushed. Call
+/// with no lock held (see [`tlb`](crate::arch::amd64::tlb)).
+pub fn write_protect_vmo(vmo: &Vmo) {
+    let mut protected = false;
+    {
+        let offsets: alloc::vec::Vec<u64> = vmo.pages.lock().keys().map(|&k| k as u64).collect();
+
+        for (page_table, vmar) in all() {
+            let aspace = unsafe { super::AddressSpace::from_page_table(page_table) };
+            for region in vmar.lock().regions.values() {
+                if !core::ptr::eq(Arc::as_ptr(&region.vmo), vmo) {
+                    continue;
+                }
+                for &offset in offsets.iter().filter(|&&o| o < region.size) {
+                    // Pages never touched are not mapped yet
+                    protected |= aspace.write_protect_page(region.base + offset).is_ok();
+                }
+            }
+        }
+    }
+
+    if protected {
+        crate::arch::amd64::tlb::shootdown().wait();
+    }
+}
+
+/// Map one page into the address space rooted at `page_table`
+fn map_page(page_table: PAddr, vaddr: u64, paddr: PAddr, flags: u32) -> Result<(), RxStatus> {
+    let aspace = unsafe { super::AddressSpace::from_page_table(page_table) };
+    aspace.map_page(vaddr, paddr, flags).map_err(|_| RxStatus::ERR_NO_MEMORY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::elf::PF_R;
+    use crate::object::VmoFlags;
+
+    fn vmo(size: usize) -> Arc<Vmo> {
+        Arc::new(Vmo::create(size, VmoFlags::empty).unwrap())
+    }
+
+    #[test]
+    fn test_insert_and_find() {
+        let mut vmar = Vmar::new();
+        vmar.insert(0x40_0000, 0x1800, vmo(0x2000), PF_R).unwrap();
+
+        assert!(vmar.find(0x3F_FFFF).is_none());
+        assert_eq!(vmar.find(0x40_0000).unwrap().base, 0x40_0000);
+        assert_eq!(vmar.find(0x40_1FFF).unwrap().size, 0x2000);
+        assert!(vmar.find(0x40_2000).is_none());
+    }
+
+    #[test]
+    fn test_overlap_rejected() {
+        let mut vmar = Vmar::new();
+        vmar.insert(0x40_0000, 0x2000, vmo(0x2000), PF_R).unwrap();
+
+        assert_eq!(vmar.insert(0x40_1000, 0x1000, vmo(0x1000), PF_R), Err(RxStatus::ERR_BUSY));
+        assert_eq!(vmar.insert(0x3F_F000, 0x2000, vmo(0x2000), PF_R), Err(RxStatus::ERR_BUSY));
+        assert_eq!(vmar.insert(0x40_0800, 0x1000, vmo(0x1000), PF_R), Err(RxStatus::ERR_INVALID_ARGS));
+
+        // Adjacent is fine
+        vmar.insert(0x40_2000, 0x1000, vmo(0x1000), PF_R).unwrap();
+        vmar.insert(0x3F_F000, 0x1000, vmo(0x1000), PF_R).unwrap();
+        assert_eq!(vmar.len(), 3);
+    }
+
+    #[test]
+    fn test_resolve_checks_access() {
+        let mut vmar = Vmar::new();
+        vmar.insert(0x40_0000, 0x2000, vmo(0x2000), PF_R).unwrap();
+        vmar.insert(0x50_0000, 0x1000, vmo(0x1000), PF_R | PF_W).unwrap();
+
+        let (region, offset) = resolve(&vmar, 0x40_1234, 0).ok().unwrap();
+        assert_eq!((region.base, offset), (0x40_0000, 0x1000));
+
+        assert_eq!(resolve(&vmar, 0x40_0000, pf_error::W).err(), Some(RxStatus::ERR_ACCESS_DENIED));
+        assert_eq!(resolve(&vmar, 0x40_0000, pf_error::I).err(), Some(RxStatus::ERR_ACCESS_DENIED));
+        assert!(resolve(&vmar, 0x50_0010, pf_error::W | pf_error::U).is_ok());
+        assert_eq!(resolve(&vmar, 0x60_0000, 0).err(), Some(RxStatus::ERR_NOT_FOUND));
+    }
+
+    #[test]
+    fn test_vmars_by_page_table() {
+        // Not a real page table: nothing here maps anything
+        const PAGE_TABLE: PAddr = 0xDEAD_0000;
+
+        let mut vmar = Vmar::new();
+        vmar.insert(0x40_0000, 0x1000, vmo(0x1000), PF_R).unwrap();
+        install(PAGE_TABLE, vmar);
+
+        // Threads get the same mappings through the shared page table
+        let a = of(PAGE_TABLE).unwrap();
+        let b = of(PAGE_TABLE).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(a.lock().find(0x40_0000).is_some());
+
+        // Locked on another CPU: the fault path backs off
+        let held = b.lock();
+        assert!(a.try_lock().is_none());
+        drop(held);
+
+        assert!(remove(PAGE_TABLE).is_some());
+        assert!(of(PAGE_TABLE).is_none());
+    }
+}
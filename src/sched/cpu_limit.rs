@@ -0,0 +1,149 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! CPU-Time Accounting and Limits
+//!
+//! The scheduler charges the outgoing process for the time since it was
+//! last scheduled ([`charge`]). The time is added to the process's
+//! `cpu_time` and to its job's total, which is checked against the job's
+//! `ResourceLimits::max_cpu_time`.
+//!
+//! # Enforcement
+//!
+//! - **Soft limit** (`used > max_cpu_time`): the process gets a pending
+//!   SIGXCPU-style notification and an audit record, once.
+//! - **Hard limit** (`used > max_cpu_time + grace`): the process is
+//!   killed (made a zombie) and an audit record is logged.
+//!
+//! Times are [`Duration`]s; job limits arrive from user space in
+//! nanoseconds.
+
+use alloc::collections::BTreeMap;
+use crate::audit::{self, AuditKind};
+use crate::object::JobId;
+use crate::process::table::{Process, ProcessState};
+use crate::sync::SpinMutex;
+use crate::time::Duration;
+
+/// Default grace period past the limit before a process is killed
+pub const CPU_LIMIT_GRACE: Duration = Duration::from_secs(1);
+
+/// Per-job CPU accounting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JobCpu {
+    /// Limit (zero = unlimited)
+    pub limit: Duration,
+    /// Grace past the limit before killing
+    pub grace: Duration,
+    /// CPU time used by all processes in the job
+    pub used: Duration,
+}
+
+/// Result of charging CPU time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuLimitAction {
+    /// Within limits (or already notified)
+    None,
+    /// Soft limit crossed; SIGXCPU-style notification raised
+    Exceeded,
+    /// Hard limit crossed; process killed
+    Killed,
+}
+
+static JOB_CPU: SpinMutex<BTreeMap<JobId, JobCpu>> = SpinMutex::new(BTreeMap::new());
+
+/// Set a job's CPU-time limit
+///
+/// Called from `Job::set_limits`. A zero limit removes the limit but keeps
+/// the usage total.
+pub fn set_job_limit(job_id: JobId, limit: Duration, grace: Duration) {
+    let mut jobs = JOB_CPU.lock();
+    let entry = jobs.entry(job_id).or_default();
+    entry.limit = limit;
+    entry.grace = grace;
+}
+
+/// Get a job's CPU accounting, if the job has been charged or limited
+pub fn job_cpu(job_id: JobId) -> Option<JobCpu> {
+    JOB_CPU.lock().get(&job_id).copied()
+}
+
+/// Forget a job's accounting (when the job is destroyed)
+pub fn remove_job(job_id: JobId) {
+    JOB_CPU.lock().remove(&job_id);
+}
+
+/// Decide the enforcement action for a job's usage
+fn evaluate(cpu: &JobCpu, already_notified: bool) -> CpuLimitAction {
+    if cpu.limit.is_zero() || cpu.used <= cpu.limit {
+        CpuLimitAction::None
+    } else if cpu.used > cpu.limit + cpu.grace {
+        CpuLimitAction::Killed
+    } else if !already_notified {
+        CpuLimitAction::Exceeded
+    } else {
+        CpuLimitAction::None
+    }
+}
+
+/// Charge `ran` of CPU time to a process and enforce its job's limit
+///
+/// Called by the scheduler with the process table locked. Uses
+/// `try_lock` on the job table so it never spins in the timer path; a
+/// missed charge is picked up on the next tick.
+pub fn charge(process: &mut Process, ran: Duration) -> CpuLimitAction {
+    process.cpu_time += ran;
+
+    let cpu = {
+        let mut jobs = match JOB_CPU.try_lock() {
+            Some(j) => j,
+            None => return CpuLimitAction::None,
+        };
+        let entry = jobs.entry(process.job_id).or_default();
+        entry.used += ran;
+        *entry
+    };
+
+    let action = evaluate(&cpu, process.xcpu_pending);
+    match action {
+        CpuLimitAction::Exceeded => {
+            process.xcpu_pending = true;
+            audit::log(AuditKind::CpuLimitExceeded, process.pid, process.job_id, cpu.used.as_nanos());
+        }
+        CpuLimitAction::Killed => {
+            process.state = ProcessState::Zombie;
+            process.exit_code = crate::process::table::EXIT_KILLED;
+            audit::log(AuditKind::CpuLimitKilled, process.pid, process.job_id, cpu.used.as_nanos());
+        }
+        CpuLimitAction::None => {}
+    }
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let mut cpu = JobCpu {
+            limit: Duration::from_nanos(100),
+            grace: Duration::from_nanos(50),
+            used: Duration::from_nanos(90),
+        };
+        assert_eq!(evaluate(&cpu, false), CpuLimitAction::None);
+
+        cpu.used = Duration::from_nanos(120);
+        assert_eq!(evaluate(&cpu, false), CpuLimitAction::Exceeded);
+        assert_eq!(evaluate(&cpu, true), CpuLimitAction::None);
+
+        cpu.used = Duration::from_nanos(151);
+        assert_eq!(evaluate(&cpu, true), CpuLimitAction::Killed);
+
+        cpu.limit = Duration::ZERO;
+        assert_eq!(evaluate(&cpu, false), CpuLimitAction::None);
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Deadline Scheduling Class
+//!
+//! A soft-realtime class that runs above the normal round-robin class.
+//! Each member has a period, a budget and a relative deadline:
+//!
+//! - At the start of every period its budget is replenished and its
+//!   absolute deadline is set to `period_start + deadline`.
+//! - Among members with budget left, the one with the earliest absolute
+//!   deadline runs first (EDF).
+//! - A member that exhausts its budget falls back to the normal class
+//!   until its next period (soft realtime: it is not starved).
+//!
+//! EDF is per CPU: each CPU picks among the members on its own run queue.
+//!
+//! # Admission Control
+//!
+//! A process is only admitted if the total utilization (sum of
+//! `budget / period`) stays at or below [`MAX_UTILIZATION_PPM`].
+//!
+//! # Policy
+//!
+//! Only processes in the root job, or in a job whose policy includes
+//! `JobPolicy::AllowRealtime`, may enter the class ([`job_allowed`]).
+//!
+//! The scheduled entity in this kernel is the process (one thread per
+//! process), so parameters are attached to the process.
+//!
+//! Times are [`Instant`]s and [`Duration`]s on the monotonic clock.
+
+use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::{JobId, JOB_ID_ROOT};
+use crate::process::table::{Process, ProcessTable};
+use crate::sync::SpinMutex;
+use crate::time::{Duration, Instant};
+
+/// Utilization is tracked in parts per million
+pub const UTILIZATION_SCALE: u64 = 1_000_000;
+
+/// Maximum total utilization of the deadline class (90%)
+///
+/// The rest is reserved so the normal class always makes progress.
+pub const MAX_UTILIZATION_PPM: u64 = 900_000;
+
+/// Smallest accepted period
+pub const MIN_PERIOD: Duration = Duration::from_micros(100);
+
+/// Deadline parameters
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineParams {
+    /// Replenishment period
+    pub period: Duration,
+    /// CPU time per period
+    pub budget: Duration,
+    /// Relative deadline (budget <= deadline <= period)
+    pub deadline: Duration,
+}
+
+impl DeadlineParams {
+    /// Validate the parameters
+    pub fn validate(&self) -> Result<(), RxStatus> {
+        if self.period < MIN_PERIOD
+            || self.budget.is_zero()
+            || self.budget > self.deadline
+            || self.deadline > self.period
+        {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        Ok(())
+    }
+
+    /// Utilization in parts per million (rounded up)
+    pub fn utilization_ppm(&self) -> u64 {
+        let period = self.period.as_nanos() as u128;
+        let scaled = self.budget.as_nanos() as u128 * UTILIZATION_SCALE as u128;
+        scaled.div_ceil(period) as u64
+    }
+}
+
+/// Per-process deadline state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineState {
+    /// Parameters the process was admitted with
+    pub params: DeadlineParams,
+    /// Start of the current period
+    pub period_start: Instant,
+    /// Absolute deadline of the current period
+    pub abs_deadline: Instant,
+    /// Budget left in the current period
+    pub remaining: Duration,
+}
+
+impl DeadlineState {
+    /// Create state with the first period starting at `now`
+    pub fn new(params: DeadlineParams, now: Instant) -> Self {
+        Self {
+            params,
+            period_start: now,
+            abs_deadline: now + params.deadline,
+            remaining: params.budget,
+        }
+    }
+
+    /// Start a new period if the current one has ended
+    pub fn replenish(&mut self, now: Instant) {
+        if now < self.period_start + self.params.period {
+            return;
+        }
+        // Skip whole missed periods rather than accumulating budget
+        let missed = (now - self.period_start).as_nanos() / self.params.period.as_nanos();
+        self.period_start += self.params.period.saturating_mul(missed);
+        self.abs_deadline = self.period_start + self.params.deadline;
+        self.remaining = self.params.budget;
+    }
+
+    /// Charge CPU time against the budget
+    pub fn charge(&mut self, ran: Duration) {
+        self.remaining -= ran;
+    }
+
+    /// Check whether the process may run in the deadline class
+    pub fn eligible(&self) -> bool {
+        !self.remaining.is_zero()
+    }
+}
+
+/// Jobs whose policy allows the deadline class (other than the root job)
+static ALLOWED_JOBS: SpinMutex<BTreeMap<JobId, bool>> = SpinMutex::new(BTreeMap::new());
+
+/// Record whether a job's policy allows the deadline class
+///
+/// Called from `Job::new_child` and `Job::set_policy`.
+pub fn set_job_allowed(job_id: JobId, allowed: bool) {
+    let mut jobs = ALLOWED_JOBS.lock();
+    if allowed {
+        jobs.insert(job_id, true);
+    } else {
+        jobs.remove(&job_id);
+    }
+}
+
+/// Check whether processes in a job may enter the deadline class
+pub fn job_allowed(job_id: JobId) -> bool {
+    job_id == JOB_ID_ROOT || ALLOWED_JOBS.lock().contains_key(&job_id)
+}
+
+/// Total admitted utilization (ppm)
+static TOTAL_UTILIZATION: AtomicU64 = AtomicU64::new(0);
+
+/// Reserve utilization for `params`
+fn admit(params: &DeadlineParams) -> Result<(), RxStatus> {
+    let util = params.utilization_ppm();
+    TOTAL_UTILIZATION
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+            let new = total + util;
+            if new <= MAX_UTILIZATION_PPM { Some(new) } else { None }
+        })
+        .map(|_| ())
+        .map_err(|_| RxStatus::ERR_NO_MEMORY)
+}
+
+/// Release utilization reserved for `params`
+fn release(params: &DeadlineParams) {
+    let util = params.utilization_ppm();
+    let _ = TOTAL_UTILIZATION.fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+        Some(total.saturating_sub(util))
+    });
+}
+
+/// Current total utilization of the deadline class (ppm)
+pub fn total_utilization_ppm() -> u64 {
+    TOTAL_UTILIZATION.load(Ordering::Acquire)
+}
+
+/// Move a process into the deadline class (or update its parameters)
+///
+/// # Returns
+/// `ERR_ACCESS_DENIED` if the process's job does not allow it,
+/// `ERR_INVALID_ARGS` for bad parameters, `ERR_NO_MEMORY` if admission
+/// control rejects the utilization
+pub fn enter(process: &mut Process, params: DeadlineParams, now: Instant) -> Result<(), RxStatus> {
+    if !job_allowed(process.job_id) {
+        return Err(RxStatus::ERR_ACCESS_DENIED);
+    }
+    params.validate()?;
+
+    // Release the old reservation first so updates are judged on the delta
+    if let Some(old) = process.deadline {
+        release(&old.params);
+    }
+    if let Err(e) = admit(&params) {
+        if let Some(old) = process.deadline {
+            let _ = admit(&old.params);
+        }
+        return Err(e);
+    }
+
+    process.deadline = Some(DeadlineState::new(params, now));
+    Ok(())
+}
+
+/// Move a process back to the normal class
+pub fn leave(process: &mut Process) {
+    if let Some(state) = process.deadline.take() {
+        release(&state.params);
+    }
+}
+
+/// Pick the eligible deadline process on `cpu`'s run queue with the
+/// earliest absolute deadline
+///
+/// Replenishes budgets of all members as a side effect.
+pub fn pick(process_table: &mut ProcessTable, cpu: usize, now: Instant) -> Option<u32> {
+    let mut best: Option<(Instant, u32)> = None;
+    for pid in process_table.runnable_pids() {
+        if !process_table.can_run_on(pid, cpu) {
+            continue;
+        }
+        if let Some(process) = process_table.get_mut(pid).filter(|p| p.cpu == cpu) {
+            if let Some(state) = process.deadline.as_mut() {
+                state.replenish(now);
+                if state.eligible() && best.is_none_or(|(d, _)| state.abs_deadline < d) {
+                    best = Some((state.abs_deadline, pid));
+                }
+            }
+        }
+    }
+    best.map(|(_, pid)| pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_validate() {
+        let ok = DeadlineParams {
+            period: Duration::from_millis(1),
+            budget: Duration::from_micros(200),
+            deadline: Duration::from_micros(500),
+        };
+        assert!(ok.validate().is_ok());
+        assert_eq!(ok.utilization_ppm(), 200_000);
+
+        let bad = DeadlineParams { budget: Duration::from_micros(600), ..ok };
+        assert_eq!(bad.validate(), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_replenish() {
+        let params = DeadlineParams {
+            period: Duration::from_millis(1),
+            budget: Duration::from_micros(100),
+            deadline: Duration::from_millis(1),
+        };
+        let mut state = DeadlineState::new(params, Instant::ZERO);
+        state.charge(Duration::from_micros(150));
+        assert!(!state.eligible());
+
+        state.replenish(Instant::from_nanos(2_500_000));
+        assert_eq!(state.period_start, Instant::from_nanos(2_000_000));
+        assert_eq!(state.abs_deadline, Instant::from_nanos(3_000_000));
+        assert!(state.eligible());
+    }
+
+    #[test]
+    fn test_job_allowed() {
+        assert!(job_allowed(JOB_ID_ROOT));
+        assert!(!job_allowed(9001));
+        set_job_allowed(9001, true);
+        assert!(job_allowed(9001));
+        set_job_allowed(9001, false);
+        assert!(!job_allowed(9001));
+    }
+}
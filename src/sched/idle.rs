@@ -0,0 +1,172 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Per-CPU Idle Loops
+//!
+//! An application processor (see [`crate::arch::amd64::smp`]) with
+//! nothing to run waits in [`run`] on its idle stack, the stack it was
+//! started on. Each time it wakes, from its idle tick or a [`kick`], it
+//! looks for a process:
+//!
+//! 1. One on its own run queue that has not run yet
+//!    ([`ProcessTable::find_new_on`])
+//! 2. Otherwise one stolen from the busiest other queue
+//!    ([`ProcessTable::steal`])
+//!
+//! and enters it in user mode from its saved state. Only processes that
+//! have not run yet are taken: their whole state is in the process
+//! table. The process then runs on the AP until it exits, when
+//! [`exit_to_idle`] brings the AP back here.
+//!
+//! An AP that finds nothing to run does the periodic working-set scan
+//! and background memory reclaim, when due, before it halts
+//! ([`crate::mm::periodic`]).
+//!
+//! # Kicks
+//!
+//! [`kick`] wakes one idle AP with an IPI when a process is created, so
+//! new work does not wait for an idle tick.
+//!
+//! The boot CPU has no idle loop: it idles in its current process (see
+//! [`exit_current`](crate::process::table::exit_current)).
+//!
+//! [`ProcessTable::find_new_on`]: crate::process::table::ProcessTable::find_new_on
+//! [`ProcessTable::steal`]: crate::process::table::ProcessTable::steal
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::{apic, entry, power, smp};
+use crate::interrupt::affinity::{self, MAX_CPUS};
+use crate::process::table::{ProcessState, SavedState, PROCESS_TABLE};
+use crate::time::Instant;
+use crate::trace::{self, SwitchReason};
+use super::round_robin::SCHEDULER;
+
+/// Idle stack top of each CPU (0: no idle loop)
+static IDLE_STACKS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+/// Bitmask of CPUs waiting in their idle loop
+static IDLE_CPUS: AtomicU64 = AtomicU64::new(0);
+
+/// What a CPU needs to enter a claimed process
+struct Claimed {
+    page_table: u64,
+    kernel_stack: u64,
+    state: SavedState,
+}
+
+/// Run the idle loop of `cpu`; called once, at the end of AP bring-up
+///
+/// # Arguments
+///
+/// * `cpu` - The running CPU
+/// * `stack_top` - Top of the stack it runs on, reused by [`exit_to_idle`]
+pub fn run(cpu: usize, stack_top: u64) -> ! {
+    IDLE_STACKS[cpu % MAX_CPUS].store(stack_top, Ordering::Release);
+    idle_loop(cpu)
+}
+
+fn idle_loop(cpu: usize) -> ! {
+    let bit = 1u64 << (cpu % MAX_CPUS);
+    unsafe { core::arch::asm!("sti", options(nomem, nostack)) };
+    loop {
+        IDLE_CPUS.fetch_or(bit, Ordering::AcqRel);
+        if let Some(claimed) = claim(cpu) {
+            IDLE_CPUS.fetch_and(!bit, Ordering::AcqRel);
+            unsafe { enter(cpu, &claimed) }
+        }
+        crate::mm::periodic();
+        power::idle();
+    }
+}
+
+/// Take a process for `cpu` and make it the CPU's current process
+fn claim(cpu: usize) -> Option<Claimed> {
+    let mut scheduler = SCHEDULER.lock();
+    let mut table = PROCESS_TABLE.lock();
+
+    let pid = table.find_new_on(cpu).or_else(|| table.steal(cpu))?;
+    let process = table.get_mut(pid)?;
+    process.state = ProcessState::Running;
+    process.sched_time = Some(Instant::now());
+    unsafe { crate::arch::amd64::debug::switch_state(false, &process.debug_state) };
+    unsafe { crate::arch::amd64::descriptor::load_io_ports(cpu, &process.io_ports) };
+    let claimed = Claimed {
+        page_table: process.page_table,
+        kernel_stack: process.kernel_stack,
+        state: process.saved_state,
+    };
+
+    table.set_current_on(cpu, pid);
+    scheduler.set_current_on(cpu, pid);
+    crate::kcounters::record_context_switch();
+    trace::switch_in(cpu as u16, pid as u64);
+    Some(claimed)
+}
+
+/// Switch to a claimed process's kernel stack and page tables and
+/// resume it in user mode
+unsafe fn enter(cpu: usize, claimed: &Claimed) -> ! {
+    core::arch::asm!("cli", options(nomem, nostack));
+    entry::set_kernel_stack(cpu, claimed.kernel_stack);
+    crate::arch::amd64::init::x86_write_cr3(claimed.page_table);
+    crate::arch::amd64::uspace_entry::x86_uspace_resume(&claimed.state)
+}
+
+/// Leave the current process for this CPU's idle loop
+///
+/// Called by an exiting process. Returns at once on a CPU without an
+/// idle loop (the boot CPU). Otherwise switches to the idle stack and the
+/// kernel page tables first, and only then stops being the CPU's current
+/// process, after which the process may be reaped.
+pub fn exit_to_idle() {
+    let cpu = entry::this_cpu();
+    let stack_top = IDLE_STACKS[cpu % MAX_CPUS].load(Ordering::Acquire);
+    if stack_top == 0 {
+        return;
+    }
+
+    unsafe {
+        core::arch::asm!(
+            "cli",
+            "mov rsp, {stack}",
+            "call {reenter}",
+            stack = in(reg) stack_top,
+            reenter = sym reenter,
+            in("rdi") cpu,
+            options(noreturn),
+        );
+    }
+}
+
+/// Back in the idle loop after [`exit_to_idle`], on the idle stack
+extern "C" fn reenter(cpu: usize) -> ! {
+    unsafe { crate::arch::amd64::init::x86_write_cr3(smp::kernel_cr3()) };
+    {
+        let mut scheduler = SCHEDULER.lock();
+        let mut table = PROCESS_TABLE.lock();
+        if let Some(pid) = table.current_on(cpu) {
+            trace::switch_out(cpu as u16, pid as u64, SwitchReason::Exit);
+        }
+        table.clear_current_on(cpu);
+        scheduler.clear_current_on(cpu);
+    }
+    crate::process::table::reap_orphans();
+    idle_loop(cpu)
+}
+
+/// Wake one idle AP to look for work
+///
+/// Called when a process is created. Does nothing if no AP is idle.
+pub fn kick() {
+    let idle = IDLE_CPUS.load(Ordering::Acquire);
+    if idle == 0 {
+        return;
+    }
+    let cpu = idle.trailing_zeros() as usize;
+    if let Some(apic_id) = affinity::apic_id(cpu) {
+        apic::apic_send_ipi(apic_id, smp::IDLE_TICK_VECTOR);
+    }
+}
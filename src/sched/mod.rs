@@ -22,6 +22,10 @@ pub mod thread;
 pub mod scheduler;
 pub mod state;
 pub mod round_robin;
+pub mod cpu_limit;
+pub mod deadline;
+pub mod suspend;
+pub mod idle;
 
 pub use thread::{Thread, ThreadId, EntryPoint};
 pub use scheduler::{Scheduler, SchedulingPolicy};
@@ -9,10 +9,19 @@
 //! This module provides a simple round-robin scheduler that works with
 //! the process table to schedule multiple processes. It implements the
 //! Phase 5B requirements for timer-based scheduling and context switching.
+//!
+//! Each CPU schedules from its own run queue (the processes homed on it,
+//! see [`ProcessTable::find_next_runnable_on`]); the scheduler keeps the
+//! current process of every CPU.
 
+use crate::arch::amd64::entry::this_cpu;
+use crate::interrupt::affinity::MAX_CPUS;
 use crate::process::table::{Process, ProcessState, ProcessTable, PROCESS_TABLE};
 use crate::process::switch;
+use crate::sched::{cpu_limit, deadline, suspend};
 use crate::sync::SpinMutex;
+use crate::time::{Duration, Instant};
+use crate::trace::{self, SwitchReason};
 
 /// Default time slice in milliseconds
 pub const DEFAULT_TIME_SLICE_MS: u64 = 10;
@@ -28,34 +37,53 @@ pub const DEFAULT_TIME_SLICE_MS: u64 = 10;
 /// - Timer-based preemption
 /// - Voluntary yielding via sys_yield
 pub struct RoundRobinScheduler {
-    /// Currently running process
-    current: Option<u32>,
+    /// Currently running process of each CPU
+    current: [Option<u32>; MAX_CPUS],
 
     /// Time slice in milliseconds
     time_slice_ms: u64,
 
     /// Preemption enabled
     preemption_enabled: bool,
+
+    /// The next switch-out on each CPU is a voluntary yield (for tracing)
+    yielding: [bool; MAX_CPUS],
 }
 
 impl RoundRobinScheduler {
     /// Create a new round-robin scheduler
     pub const fn new() -> Self {
         Self {
-            current: None,
+            current: [None; MAX_CPUS],
             time_slice_ms: DEFAULT_TIME_SLICE_MS,
             preemption_enabled: true,
+            yielding: [false; MAX_CPUS],
         }
     }
 
-    /// Get the current process PID
+    /// Get the current process PID (of the running CPU)
     pub fn current(&self) -> Option<u32> {
-        self.current
+        self.current_on(this_cpu())
     }
 
-    /// Set the current process PID
+    /// Set the current process PID (of the running CPU)
     pub fn set_current(&mut self, pid: u32) {
-        self.current = Some(pid);
+        self.set_current_on(this_cpu(), pid);
+    }
+
+    /// Get the current process PID of `cpu`
+    pub fn current_on(&self, cpu: usize) -> Option<u32> {
+        self.current[cpu % MAX_CPUS]
+    }
+
+    /// Set the current process PID of `cpu`
+    pub fn set_current_on(&mut self, cpu: usize, pid: u32) {
+        self.current[cpu % MAX_CPUS] = Some(pid);
+    }
+
+    /// Forget the current process of `cpu` (it went idle)
+    pub fn clear_current_on(&mut self, cpu: usize) {
+        self.current[cpu % MAX_CPUS] = None;
     }
 
     /// Get the time slice in milliseconds
@@ -81,10 +109,15 @@ impl RoundRobinScheduler {
     /// Schedule the next process to run
     ///
     /// This function implements the core round-robin scheduling algorithm:
-    /// 1. Mark the current process as Ready (if it was Running)
-    /// 2. Find the next runnable process
-    /// 3. Mark the next process as Running
-    /// 4. Return the next process PID
+    /// 1. Charge the current process for its CPU time (which may kill it,
+    ///    see [`cpu_limit`]) and mark it Ready (if it was Running), or
+    ///    Suspended if a suspend is pending (see [`suspend`])
+    /// 2. Wake blocked processes whose timeout has passed
+    ///    ([`ProcessTable::wake_expired`])
+    /// 3. Find the next runnable process on this CPU's queue: the deadline
+    ///    class (EDF, see [`deadline`]) first, then round-robin
+    /// 4. Mark the next process as Running
+    /// 5. Return the next process PID
     ///
     /// # Arguments
     ///
@@ -94,24 +127,56 @@ impl RoundRobinScheduler {
     ///
     /// The PID of the next process to run, or None if no runnable process
     pub fn schedule(&mut self, process_table: &mut ProcessTable) -> Option<u32> {
-        // Mark current as Ready if it was Running
-        if let Some(current_pid) = self.current {
+        let now = Instant::now();
+        let cpu = this_cpu() % MAX_CPUS;
+
+        // Charge the outgoing process and mark it Ready if it was Running
+        let mut outgoing = None;
+        if let Some(current_pid) = self.current[cpu] {
             if let Some(process) = process_table.get_mut(current_pid) {
-                if process.state == ProcessState::Running {
+                let ran = process.sched_time.map_or(Duration::ZERO, |since| now - since);
+                cpu_limit::charge(process, ran);
+                if let Some(state) = process.deadline.as_mut() {
+                    state.charge(ran);
+                }
+
+                let reason = match process.state {
+                    ProcessState::Running | ProcessState::Ready if self.yielding[cpu] => SwitchReason::Yield,
+                    ProcessState::Running | ProcessState::Ready => SwitchReason::Preempt,
+                    ProcessState::Blocked | ProcessState::Suspended => SwitchReason::Block,
+                    _ => SwitchReason::Exit,
+                };
+                outgoing = Some((current_pid, reason));
+
+                // Preemption is a suspension point; otherwise back to Ready
+                if !suspend::park(process) && process.state == ProcessState::Running {
                     process.state = ProcessState::Ready;
                 }
             }
         }
+        self.yielding[cpu] = false;
+        process_table.wake_expired(now);
 
         // Find next runnable process
-        let next_pid = process_table.find_next_runnable(self.current);
+        let next_pid = deadline::pick(process_table, cpu, now)
+            .or_else(|| process_table.find_next_runnable_on(cpu, self.current[cpu]));
+
+        if next_pid != self.current[cpu] {
+            if let Some((pid, reason)) = outgoing {
+                trace::switch_out(cpu as u16, pid as u64, reason);
+            }
+            if let Some(pid) = next_pid {
+                trace::switch_in(cpu as u16, pid as u64);
+            }
+        }
 
         if let Some(pid) = next_pid {
-            self.current = Some(pid);
-            process_table.set_current(pid);
+            self.current[cpu] = Some(pid);
+            process_table.set_current_on(cpu, pid);
 
             if let Some(process) = process_table.get_mut(pid) {
                 process.state = ProcessState::Running;
+                process.sched_time = Some(now);
             }
         }
 
@@ -136,7 +201,7 @@ impl RoundRobinScheduler {
         let next_pid = self.schedule(process_table);
 
         if let Some(next_pid) = next_pid {
-            if let Some(current_pid) = self.current {
+            if let Some(current_pid) = self.current() {
                 if current_pid != next_pid {
                     // We need to extract the data we need before the mutable borrow
                     // This is a simplified approach - in a real kernel we'd have
@@ -145,9 +210,12 @@ impl RoundRobinScheduler {
                         .map(|p| p.page_table)
                         .unwrap_or(0);
 
-                    // Update current process state before switch
+                    // Update current process state before switch. schedule()
+                    // already did this; don't clobber Blocked/Suspended/Zombie.
                     if let Some(process) = process_table.get_mut(current_pid) {
-                        process.state = crate::process::table::ProcessState::Ready;
+                        if process.state == ProcessState::Running {
+                            process.state = ProcessState::Ready;
+                        }
                     }
 
                     // Get pointers after the mutable borrow ends
@@ -163,6 +231,16 @@ impl RoundRobinScheduler {
 
                     // Perform the context switch using raw pointers
                     if !current_saved_ptr.is_null() && !next_saved_ptr.is_null() {
+                        crate::kcounters::record_context_switch();
+
+                        let prev_debug = process_table.get(current_pid)
+                            .is_some_and(|p| p.debug_state.is_active());
+                        if let Some(next) = process_table.get(next_pid) {
+                            crate::arch::amd64::debug::switch_state(prev_debug, &next.debug_state);
+                            crate::arch::amd64::descriptor::load_io_ports(this_cpu(), &next.io_ports);
+                            crate::arch::amd64::entry::set_kernel_stack(this_cpu(), next.kernel_stack);
+                        }
+
                         // Call the assembly function directly
                         crate::process::switch::context_switch_raw(
                             current_saved_ptr,
@@ -187,7 +265,8 @@ impl Default for RoundRobinScheduler {
 /// ============================================================================
 
 /// Global round-robin scheduler instance
-pub static SCHEDULER: SpinMutex<RoundRobinScheduler> = SpinMutex::new(RoundRobinScheduler::new());
+pub static SCHEDULER: SpinMutex<RoundRobinScheduler> =
+    SpinMutex::named(RoundRobinScheduler::new(), &crate::sync::lockstat::SCHEDULER);
 
 /// ============================================================================
 /// Scheduler API Functions
@@ -234,11 +313,13 @@ pub fn yield_cpu() -> Result<(), &'static str> {
     // Get current process
     let current_pid = scheduler.current().ok_or("No current process")?;
 
-    // Check if there's another runnable process
+    // Check if there's another runnable process, counting timed-out waiters
+    process_table.wake_expired(Instant::now());
     let next_pid = process_table.find_next_runnable(Some(current_pid));
 
     if let Some(next_pid) = next_pid {
         if next_pid != current_pid {
+            scheduler.yielding[this_cpu() % MAX_CPUS] = true;
             unsafe {
                 scheduler.context_switch(&mut process_table);
             }
@@ -248,16 +329,46 @@ pub fn yield_cpu() -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Switch away until the current thread is no longer `Blocked`
+///
+/// For a thread that has just blocked itself. With nothing else to run
+/// the CPU idles here, checking the thread's deadline on every interrupt.
+pub fn sleep_while_blocked() {
+    loop {
+        let _ = yield_cpu();
+
+        let mut table = PROCESS_TABLE.lock();
+        table.wake_expired(Instant::now());
+        match table.current_thread_mut() {
+            Some(thread) if thread.state == ProcessState::Blocked => {}
+            Some(thread) => {
+                if thread.state == ProcessState::Ready {
+                    thread.state = ProcessState::Running;
+                }
+                return;
+            }
+            None => return,
+        }
+        drop(table);
+
+        crate::mm::periodic();
+        unsafe {
+            core::arch::asm!("sti", "hlt", options(nomem, nostack));
+        }
+    }
+}
+
 /// Get the current process PID
 ///
-/// This function returns the PID of the currently running process.
-/// It's used by sys_getpid.
+/// This function returns the PID of the currently running process; for a
+/// thread, the PID of the process it belongs to. It's used by sys_getpid.
 ///
 /// # Returns
 ///
 /// The PID of the current process, or None if no process is running
 pub fn get_current_pid() -> Option<u32> {
-    SCHEDULER.lock().current()
+    let tid = SCHEDULER.lock().current()?;
+    Some(PROCESS_TABLE.lock().get(tid).map_or(tid, |p| p.tgid))
 }
 
 /// Get the parent process PID of the current process
@@ -10,6 +10,7 @@
 
 use super::thread::{Thread, ThreadId, new_thread_id};
 use super::state::{RunQueue, ThreadState};
+use crate::trace::{self, SwitchReason};
 
 /// Default time slice for threads (in CPU cycles)
 const DEFAULT_TIME_SLICE: u64 = 10_000_000;  // ~10ms at 1GHz
@@ -33,6 +34,8 @@ pub struct Scheduler {
     policy: SchedulingPolicy,
     /// Preemption enabled
     preemption_enabled: bool,
+    /// CPU this scheduler runs on (for tracing)
+    cpu: u16,
 }
 
 impl Scheduler {
@@ -45,6 +48,7 @@ impl Scheduler {
             thread_count: 0,
             policy: SchedulingPolicy::RoundRobin,
             preemption_enabled: true,
+            cpu: trace::BOOT_CPU,
         }
     }
 
@@ -131,6 +135,18 @@ impl Scheduler {
         if let Some(entry) = self.run_queue.dequeue() {
             // Mark the current thread as ready (if there is one)
             if let Some(current_id) = self.current_thread {
+                if current_id != entry.thread_id {
+                    let reason = match self.get_thread(current_id).map(|t| t.state) {
+                        Some(ThreadState::Running) => SwitchReason::Preempt,
+                        Some(ThreadState::Ready) => SwitchReason::Yield,
+                        Some(ThreadState::Blocked | ThreadState::BlockedOnMutex | ThreadState::BlockedOnCondvar) => {
+                            SwitchReason::Block
+                        }
+                        _ => SwitchReason::Exit,
+                    };
+                    trace::switch_out(self.cpu, current_id, reason);
+                }
+
                 // First, check if we need to re-queue the current thread
                 let should_requeue = if let Some(current) = self.get_thread(current_id) {
                     current.state == ThreadState::Running
@@ -161,6 +177,10 @@ impl Scheduler {
                 }
             }
 
+            if self.current_thread != Some(entry.thread_id) {
+                trace::switch_in(self.cpu, entry.thread_id);
+            }
+
             // Set the new thread as running
             if let Some(thread) = self.get_thread_mut(entry.thread_id) {
                 thread.set_state(ThreadState::Running);
@@ -242,6 +262,8 @@ impl Scheduler {
             thread.set_state(ThreadState::Ready);
         }
 
+        trace::wakeup(self.cpu, self.current_thread.unwrap_or(0), thread_id);
+
         // Enqueue the thread
         self.enqueue_thread(thread_id);
         Ok(())
@@ -291,10 +313,32 @@ pub struct PerCpuScheduler {
 impl PerCpuScheduler {
     /// Create a new per-CPU scheduler
     pub fn new(cpu_id: u32) -> Self {
+        let mut scheduler = Scheduler::new();
+        scheduler.cpu = cpu_id as u16;
         Self {
             cpu_id,
-            scheduler: Scheduler::new(),
+            scheduler,
             load_balancing: true,
         }
     }
+
+    /// Move a thread from this CPU's scheduler to another CPU's
+    ///
+    /// The thread must not be running on this CPU.
+    pub fn migrate_thread(&mut self, thread_id: ThreadId, dest: &mut PerCpuScheduler) -> Result<(), &'static str> {
+        if self.scheduler.current_thread() == Some(thread_id) {
+            return Err("Thread is running");
+        }
+
+        // Check capacity first so the thread is never dropped
+        if dest.scheduler.thread_count() >= MAX_THREADS {
+            return Err("Maximum number of threads reached");
+        }
+
+        let thread = self.scheduler.remove_thread(thread_id).ok_or("Thread not found")?;
+        dest.scheduler.add_thread(thread)?;
+
+        trace::migrate(self.cpu_id as u16, dest.cpu_id as u16, thread_id);
+        Ok(())
+    }
 }
@@ -0,0 +1,196 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Process Suspension
+//!
+//! Debuggers and job control stop a process with [`suspend`] and let it
+//! continue with [`resume`]. Each process has a suspend count: it stays
+//! stopped while the count is non-zero, so independent suspenders (a
+//! debugger and a job controller) don't undo each other.
+//!
+//! # Suspension Points
+//!
+//! A process is never stopped in the middle of kernel code. Suspending a
+//! `Ready` process takes effect at once. A `Running` or `Blocked` process
+//! keeps going until it next returns to userspace:
+//!
+//! - **Syscall return**: `syscall_dispatch` calls [`checkpoint`]
+//! - **Interrupt return**: the scheduler parks the outgoing process on
+//!   preemption instead of making it `Ready`
+//!
+//! Once stopped the process is `Suspended` and [`signals`] reports
+//! [`TASK_SUSPENDED`].
+//!
+//! # Exit Races
+//!
+//! All transitions happen under the process table lock. A process that
+//! has exited (zombie or dead) cannot be suspended or resumed, and
+//! `resume` never moves an exited process back to `Ready`.
+
+use crate::arch::amd64::mm::RxStatus;
+use crate::process::table::{Process, ProcessState, PROCESS_TABLE};
+
+/// Signal: the process has reached a suspension point and is stopped
+pub const TASK_SUSPENDED: u32 = 1 << 0;
+
+/// Signal: the process has exited
+pub const TASK_TERMINATED: u32 = 1 << 1;
+
+/// Add a suspend request
+///
+/// # Returns
+///
+/// `ERR_NOT_FOUND` if the process has exited, `ERR_BUSY` if the count
+/// would overflow
+pub fn suspend(process: &mut Process) -> Result<(), RxStatus> {
+    if !process.state.is_alive() {
+        return Err(RxStatus::ERR_NOT_FOUND);
+    }
+    process.suspend_count = process.suspend_count.checked_add(1).ok_or(RxStatus::ERR_BUSY)?;
+    if process.state == ProcessState::Ready {
+        process.state = ProcessState::Suspended;
+    }
+    Ok(())
+}
+
+/// Drop a suspend request
+///
+/// The process continues once the last request is dropped. A suspend
+/// that had not taken effect yet is simply cancelled.
+///
+/// # Returns
+///
+/// `ERR_NOT_FOUND` if the process has exited, `ERR_INVALID_ARGS` if it
+/// was not suspended
+pub fn resume(process: &mut Process) -> Result<(), RxStatus> {
+    if !process.state.is_alive() {
+        return Err(RxStatus::ERR_NOT_FOUND);
+    }
+    process.suspend_count = process.suspend_count.checked_sub(1).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+    if process.suspend_count == 0 && process.state == ProcessState::Suspended {
+        process.state = ProcessState::Ready;
+    }
+    Ok(())
+}
+
+/// Stop a process at a suspension point if a suspend is pending
+///
+/// Called with the process on its way back to userspace.
+///
+/// # Returns
+///
+/// true if the process is now `Suspended`
+pub fn park(process: &mut Process) -> bool {
+    if process.suspend_count > 0 && process.state.is_runnable() {
+        process.state = ProcessState::Suspended;
+    }
+    process.state == ProcessState::Suspended
+}
+
+/// Current signal state of a process
+pub fn signals(process: &Process) -> u32 {
+    match process.state {
+        ProcessState::Suspended => TASK_SUSPENDED,
+        ProcessState::Zombie | ProcessState::Dead => TASK_TERMINATED,
+        _ => 0,
+    }
+}
+
+/// Suspension point on syscall return
+///
+/// Parks the current process if a suspend is pending and switches away
+/// until it is resumed. With nothing else to run the CPU idles here.
+pub fn checkpoint() {
+    loop {
+        let parked = crate::process::table::with_current_process_mut(park);
+        if parked != Some(true) {
+            return;
+        }
+
+        let _ = super::round_robin::yield_cpu();
+
+        // Back on this process: either resumed or nothing else was runnable
+        let mut table = PROCESS_TABLE.lock();
+        match table.current_mut() {
+            Some(p) if p.state == ProcessState::Suspended => {}
+            Some(p) => {
+                if p.state == ProcessState::Ready {
+                    p.state = ProcessState::Running;
+                }
+                return;
+            }
+            None => return,
+        }
+        drop(table);
+
+        unsafe {
+            core::arch::asm!("sti", "hlt", options(nomem, nostack));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process() -> Process {
+        Process::new(1, 0, 0x1000, 0x2000, 0x7000_0000_0000, 0x4000)
+    }
+
+    #[test]
+    fn test_suspend_ready_is_immediate() {
+        let mut p = process();
+        suspend(&mut p).unwrap();
+        assert_eq!(p.state, ProcessState::Suspended);
+        assert_eq!(signals(&p), TASK_SUSPENDED);
+        resume(&mut p).unwrap();
+        assert_eq!(p.state, ProcessState::Ready);
+        assert_eq!(signals(&p), 0);
+    }
+
+    #[test]
+    fn test_suspend_running_waits_for_park() {
+        let mut p = process();
+        p.state = ProcessState::Running;
+        suspend(&mut p).unwrap();
+        assert_eq!(p.state, ProcessState::Running);
+        assert!(park(&mut p));
+        assert_eq!(p.state, ProcessState::Suspended);
+    }
+
+    #[test]
+    fn test_suspend_count_nests() {
+        let mut p = process();
+        suspend(&mut p).unwrap();
+        suspend(&mut p).unwrap();
+        resume(&mut p).unwrap();
+        assert_eq!(p.state, ProcessState::Suspended);
+        resume(&mut p).unwrap();
+        assert_eq!(p.state, ProcessState::Ready);
+        assert_eq!(resume(&mut p), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_cancelled_suspend_does_not_park() {
+        let mut p = process();
+        p.state = ProcessState::Running;
+        suspend(&mut p).unwrap();
+        resume(&mut p).unwrap();
+        assert!(!park(&mut p));
+        assert_eq!(p.state, ProcessState::Running);
+    }
+
+    #[test]
+    fn test_exited_process_is_not_revived() {
+        let mut p = process();
+        suspend(&mut p).unwrap();
+        p.state = ProcessState::Zombie;
+        assert_eq!(resume(&mut p), Err(RxStatus::ERR_NOT_FOUND));
+        assert_eq!(suspend(&mut p), Err(RxStatus::ERR_NOT_FOUND));
+        assert_eq!(p.state, ProcessState::Zombie);
+        assert_eq!(signals(&p), TASK_TERMINATED);
+    }
+}
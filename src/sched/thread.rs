@@ -214,10 +214,10 @@ pub fn new_thread_id() -> ThreadId {
 
 /// A simple idle thread entry point
 ///
-/// This is used when no other threads are runnable.
+/// This is used when no other threads are runnable. Each iteration
+/// enters a C-state (or halts) until the next interrupt.
 pub extern "C" fn idle_thread_entry(_arg: usize) -> ! {
     loop {
-        // In a real kernel, this would halt the CPU or enable power saving
-        core::hint::spin_loop();
+        crate::arch::amd64::power::idle();
     }
 }
@@ -0,0 +1,258 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! System Shutdown
+//!
+//! A privileged process powers the machine off or reboots it with the
+//! `POWER` syscall, which runs [`shutdown`] on its behalf. The steps run
+//! in order, each logged on the debug console as `[SHUTDOWN]`:
+//!
+//! 1. **Terminate**: every other process is asked to exit. There are no
+//!    signals: a process exits with [`EXIT_TERMINATED`] at its next
+//!    syscall return ([`checkpoint`]). Suspended processes are resumed
+//!    so they get there.
+//! 2. **Wait**: the initiator yields until the others have exited, for
+//!    at most `shutdown.timeout_ms=` (default 2000) milliseconds.
+//! 3. **Kill**: processes still alive (busy in userspace, or blocked in
+//!    the kernel) are killed with `EXIT_KILLED`. Their descriptors are
+//!    closed and their buffered TTY output is flushed.
+//! 4. **Sync**: filesystems write back what they buffer ([`fs::sync`]).
+//! 5. **Quiesce**: devices stop DMA ([`drivers::quiesce`]).
+//! 6. **Power**: ACPI S5 or the reset register on amd64, PSCI on arm64,
+//!    SBI SRST on riscv64. On amd64, reboot falls back to port `0xCF9`
+//!    and the keyboard controller when there is no reset register.
+//!
+//! If the platform step returns, the CPU halts with interrupts off.
+//!
+//! [`EXIT_TERMINATED`]: crate::process::table::EXIT_TERMINATED
+//! [`fs::sync`]: crate::fs::sync
+//! [`drivers::quiesce`]: crate::drivers::quiesce
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::process::table::{ProcessState, ProcessTable, EXIT_KILLED, EXIT_TERMINATED, PROCESS_TABLE};
+use crate::time::{Duration, Instant};
+use crate::{kerror, kinfo, kwarn};
+
+/// Command line option: how long to wait for processes to exit
+pub const TIMEOUT_OPTION: &str = "shutdown.timeout_ms";
+
+/// Default wait for processes to exit
+const DEFAULT_TIMEOUT_MS: u64 = 2000;
+
+/// No initiating process (kernel-initiated shutdown)
+const NO_INITIATOR: u32 = u32::MAX;
+
+/// What to do once the system is shut down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    /// Power the machine off
+    PowerOff = 1,
+    /// Reset the machine
+    Reboot = 2,
+}
+
+impl PowerAction {
+    /// Convert from the `POWER` syscall argument
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::PowerOff),
+            2 => Some(Self::Reboot),
+            _ => None,
+        }
+    }
+}
+
+/// Set once a shutdown has begun; never cleared
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// PID of the process running the shutdown
+static INITIATOR: AtomicU32 = AtomicU32::new(NO_INITIATOR);
+
+/// Whether a shutdown has begun
+pub fn in_progress() -> bool {
+    SHUTTING_DOWN.load(Ordering::Acquire)
+}
+
+/// Claim the shutdown for `initiator` (`None` for the kernel)
+///
+/// # Returns
+///
+/// false if a shutdown has already begun
+pub fn begin(initiator: Option<u32>) -> bool {
+    if SHUTTING_DOWN.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        return false;
+    }
+    INITIATOR.store(initiator.unwrap_or(NO_INITIATOR), Ordering::Relaxed);
+    true
+}
+
+/// Termination point on syscall return
+///
+/// Exits the current process once a shutdown has begun, unless it is the
+/// one running it.
+pub fn checkpoint() {
+    if !in_progress() {
+        return;
+    }
+    let current = PROCESS_TABLE.lock().current_pid();
+    if current.is_some_and(|pid| pid != INITIATOR.load(Ordering::Relaxed)) {
+        crate::process::table::exit_current(EXIT_TERMINATED);
+    }
+}
+
+/// Shut the system down and power off or reboot
+///
+/// Call after a successful [`begin`], from the initiating process (or
+/// from the kernel with no process current).
+pub fn shutdown(action: PowerAction) -> ! {
+    let initiator = INITIATOR.load(Ordering::Relaxed);
+    kinfo!("[SHUTDOWN] {:?} requested by pid {}", action, initiator);
+
+    let asked = request_exit(initiator);
+    kinfo!("[SHUTDOWN] asked {} process(es) to exit", asked);
+
+    let deadline = Instant::after(Duration::from_millis(timeout_ms()));
+    while alive_others(initiator) > 0 && !deadline.has_passed(Instant::now()) {
+        let _ = crate::sched::round_robin::yield_cpu();
+    }
+
+    let killed = kill_others(initiator);
+    if killed > 0 {
+        kwarn!("[SHUTDOWN] killed {} process(es) that did not exit", killed);
+    }
+    crate::drivers::tty::flush_current();
+
+    crate::fs::sync();
+    kinfo!("[SHUTDOWN] filesystems synced");
+
+    crate::drivers::quiesce();
+    kinfo!("[SHUTDOWN] devices quiesced");
+
+    power(action);
+
+    kerror!("[SHUTDOWN] {:?} failed, halting", action);
+    loop {
+        unsafe { core::arch::asm!("cli", "hlt", options(nomem, nostack)) };
+    }
+}
+
+/// Wait for processes to exit, from the command line or the default
+fn timeout_ms() -> u64 {
+    let mut buf = [0u8; 16];
+    crate::cmdline::get(TIMEOUT_OPTION, &mut buf)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT_MS)
+}
+
+/// Resume every suspended process so it reaches [`checkpoint`]
+///
+/// # Returns
+///
+/// The number of live processes other than the initiator
+fn request_exit(initiator: u32) -> usize {
+    let mut table = PROCESS_TABLE.lock();
+    let pids = others(&table, initiator);
+    for &pid in &pids {
+        if let Some(p) = table.get_mut(pid) {
+            p.suspend_count = 0;
+            if p.state == ProcessState::Suspended {
+                p.state = ProcessState::Ready;
+            }
+        }
+    }
+    pids.len()
+}
+
+/// PIDs of the live processes other than the initiator
+fn others(table: &ProcessTable, initiator: u32) -> Vec<u32> {
+    table.iter().filter(|p| p.pid != initiator && p.state.is_alive()).map(|p| p.pid).collect()
+}
+
+/// Number of live processes other than the initiator
+fn alive_others(initiator: u32) -> usize {
+    others(&PROCESS_TABLE.lock(), initiator).len()
+}
+
+/// Kill every live process other than the initiator
+///
+/// # Returns
+///
+/// The number of processes killed
+fn kill_others(initiator: u32) -> usize {
+    let mut table = PROCESS_TABLE.lock();
+    let pids = others(&table, initiator);
+    for &pid in &pids {
+        if let Some(p) = table.get_mut(pid) {
+            p.state = ProcessState::Zombie;
+            p.exit_code = EXIT_KILLED;
+            p.fd_table.close_all();
+            p.tty_output.flush(crate::drivers::tty::write);
+        }
+    }
+    pids.len()
+}
+
+/// Platform power off or reset; returns only if it failed
+#[cfg(target_arch = "x86_64")]
+fn power(action: PowerAction) {
+    use crate::arch::amd64::ioport::outb;
+
+    let acpi = crate::acpi::find_rsdp().and_then(crate::acpi::find_power_info);
+    unsafe {
+        match (action, acpi) {
+            (PowerAction::PowerOff, Some((info, Some(s5)))) => info.enter_s5(s5),
+            (PowerAction::PowerOff, _) => {}
+            (PowerAction::Reboot, info) => {
+                if let Some((info, _)) = info {
+                    info.reset();
+                }
+                // Reset control register: full reset
+                outb(0xCF9, 0x06);
+                // Keyboard controller: pulse the reset line
+                outb(0x64, 0xFE);
+            }
+        }
+    }
+}
+
+/// Platform power off or reset; returns only if it failed
+#[cfg(target_arch = "aarch64")]
+fn power(action: PowerAction) {
+    use crate::arch::arm64::arch::{psci_system_off, psci_system_reset};
+
+    let _ = match action {
+        PowerAction::PowerOff => psci_system_off(),
+        PowerAction::Reboot => psci_system_reset(),
+    };
+}
+
+/// Platform power off or reset; returns only if it failed
+#[cfg(target_arch = "riscv64")]
+fn power(action: PowerAction) {
+    use crate::arch::riscv64::arch::{
+        sbi_system_reset, SBI_RESET_COLD_REBOOT, SBI_RESET_REASON_NONE, SBI_RESET_SHUTDOWN,
+    };
+
+    let reset_type = match action {
+        PowerAction::PowerOff => SBI_RESET_SHUTDOWN,
+        PowerAction::Reboot => SBI_RESET_COLD_REBOOT,
+    };
+    let _ = sbi_system_reset(reset_type, SBI_RESET_REASON_NONE);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_action_from_raw() {
+        assert_eq!(PowerAction::from_raw(1), Some(PowerAction::PowerOff));
+        assert_eq!(PowerAction::from_raw(2), Some(PowerAction::Reboot));
+        assert_eq!(PowerAction::from_raw(0), None);
+        assert_eq!(PowerAction::from_raw(3), None);
+    }
+}
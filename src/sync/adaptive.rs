@@ -0,0 +1,327 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Adaptive Mutex
+//!
+//! A lock for critical sections that are usually short but can be long,
+//! such as file system operations that copy file data. A waiter spins
+//! like on a [`SpinMutex`] while the owner is running on another CPU, since
+//! it is then likely to release the lock soon. Once the owner is not
+//! running (it was preempted or blocked), or after [`SPIN_LIMIT`] spins,
+//! the waiter blocks on the mutex's [`WaitQueue`] and its CPU runs
+//! something else.
+//!
+//! # Owner Tracking
+//!
+//! The owner is the thread the scheduler is running on the CPU that took
+//! the lock ([`running_on`]), and [`is_on_cpu`] tells whether it still
+//! runs; both read the scheduler's per-CPU current entry without locking
+//! the process table. Kernel context has no thread: waiters there always
+//! spin, and a lock it holds counts as running.
+//!
+//! # Blocking
+//!
+//! A waiter blocks only if it is a thread, has interrupts enabled and
+//! can take the process table; otherwise (an interrupt handler, or a
+//! caller already holding the process table) it keeps spinning. It queues
+//! itself, checks the lock once more and becomes `Blocked`, all under the
+//! mutex's wait lock, and unlocking wakes one waiter under the same lock,
+//! so no wakeup falls in between.
+//!
+//! The unlocking thread may hold the process table, so the wake takes it
+//! only with `try_lock`. A waiter it cannot wake gets up on its own after
+//! [`RECHECK`]. A woken waiter competes for the lock again; the lock is not
+//! handed over.
+//!
+//! # Lock Order
+//!
+//! The wait lock before [`PROCESS_TABLE`].
+//!
+//! [`running_on`]: crate::process::table::running_on
+//! [`is_on_cpu`]: crate::process::table::is_on_cpu
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{fence, AtomicBool, AtomicU32, Ordering};
+use crate::process::table::{self, ProcessState, PROCESS_TABLE};
+use crate::time::{Duration, Instant};
+use super::lockstat::LockClass;
+use super::spinlock::SpinMutex;
+use super::wait_queue::WaitQueue;
+
+/// Spins a waiter makes before blocking while the owner is running
+pub const SPIN_LIMIT: u64 = 4096;
+
+/// Longest a blocked waiter sleeps without being woken
+pub const RECHECK: Duration = Duration::from_millis(10);
+
+/// What a waiter for a held lock does next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Spin and try again
+    Spin,
+    /// Block until the lock is released
+    Block,
+}
+
+/// Choose between spinning and blocking
+///
+/// # Arguments
+///
+/// * `waiter` - The waiting thread (0 in kernel context)
+/// * `owner_running` - Whether the owner is running on another CPU
+/// * `spins` - Spins so far
+pub fn next_step(waiter: u32, owner_running: bool, spins: u64) -> Step {
+    if waiter != 0 && (!owner_running || spins >= SPIN_LIMIT) {
+        Step::Block
+    } else {
+        Step::Spin
+    }
+}
+
+/// The thread running on this CPU (0 in kernel context)
+fn current_thread() -> u32 {
+    table::running_on(crate::arch::amd64::entry::this_cpu()).unwrap_or(0)
+}
+
+/// A mutex that spins while its owner runs and blocks otherwise
+pub struct AdaptiveMutex<T> {
+    locked: AtomicBool,
+    /// Thread holding the lock (0 if none or kernel context)
+    owner: AtomicU32,
+    /// Orders blocking against waking
+    wait_lock: SpinMutex<()>,
+    waiters: WaitQueue,
+    #[cfg(feature = "lockstat")]
+    class: Option<&'static LockClass>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for AdaptiveMutex<T> {}
+unsafe impl<T: Send> Sync for AdaptiveMutex<T> {}
+
+impl<T> AdaptiveMutex<T> {
+    /// Create a new adaptive mutex
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            owner: AtomicU32::new(0),
+            wait_lock: SpinMutex::new(()),
+            waiters: WaitQueue::new(),
+            #[cfg(feature = "lockstat")]
+            class: None,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Create an adaptive mutex whose contention is counted under `class`
+    ///
+    /// Same as [`new`](Self::new) unless the `lockstat` feature is enabled.
+    pub const fn named(data: T, class: &'static LockClass) -> Self {
+        #[cfg(not(feature = "lockstat"))]
+        let _ = class;
+        Self {
+            locked: AtomicBool::new(false),
+            owner: AtomicU32::new(0),
+            wait_lock: SpinMutex::new(()),
+            waiters: WaitQueue::new(),
+            #[cfg(feature = "lockstat")]
+            class: Some(class),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Acquire the lock, spinning or blocking until it becomes available
+    pub fn lock(&self) -> AdaptiveMutexGuard<'_, T> {
+        if self.try_acquire() {
+            #[cfg(feature = "lockstat")]
+            if let Some(class) = self.class {
+                class.record(super::lockstat::cpu(), 0, 0);
+            }
+        } else {
+            self.lock_contended();
+        }
+        self.owner.store(current_thread(), Ordering::Relaxed);
+        AdaptiveMutexGuard { mutex: self }
+    }
+
+    /// Try to acquire the lock without waiting
+    pub fn try_lock(&self) -> Option<AdaptiveMutexGuard<'_, T>> {
+        if self.try_acquire() {
+            self.owner.store(current_thread(), Ordering::Relaxed);
+            Some(AdaptiveMutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    fn try_acquire(&self) -> bool {
+        self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    /// Wait until the lock is free (the lock was held on the first try)
+    #[cold]
+    fn lock_contended(&self) {
+        #[cfg(feature = "lockstat")]
+        let start = super::lockstat::cycles();
+        let waiter = current_thread();
+        let mut spins = 0u64;
+        let mut total = 0u64;
+        while !self.try_acquire() {
+            let owner = self.owner.load(Ordering::Relaxed);
+            let owner_running = owner == 0 || table::is_on_cpu(owner);
+            if next_step(waiter, owner_running, spins) == Step::Block && self.block(waiter) {
+                spins = 0;
+                continue;
+            }
+            core::hint::spin_loop();
+            spins += 1;
+            total += 1;
+        }
+        #[cfg(feature = "lockstat")]
+        if let Some(class) = self.class {
+            let waited = super::lockstat::cycles().wrapping_sub(start);
+            class.record(super::lockstat::cpu(), total.max(1), waited);
+        }
+        let _ = total;
+    }
+
+    /// Block `waiter` until an unlock wakes it or [`RECHECK`] passes
+    ///
+    /// # Returns
+    ///
+    /// `false` if the waiter could not block and should spin instead
+    fn block(&self, waiter: u32) -> bool {
+        if crate::arch::amd64::init::arch_ints_disabled() {
+            return false;
+        }
+        {
+            let _serial = self.wait_lock.lock();
+            self.waiters.block(waiter as u64, 0, Instant::INFINITE);
+            // Pairs with the fence in `unlock`: either it sees this waiter
+            // or this sees the lock free
+            fence(Ordering::SeqCst);
+            if !self.locked.load(Ordering::Relaxed) {
+                self.waiters.remove(waiter as u64);
+                return true;
+            }
+            let Some(mut table) = PROCESS_TABLE.try_lock() else {
+                self.waiters.remove(waiter as u64);
+                return false;
+            };
+            let Some(thread) = table.current_thread_mut() else {
+                self.waiters.remove(waiter as u64);
+                return false;
+            };
+            thread.state = ProcessState::Blocked;
+            thread.wake_at = Some(Instant::now().saturating_add(RECHECK));
+        }
+
+        crate::sched::round_robin::sleep_while_blocked();
+        // Still queued if it got up at its recheck deadline
+        self.waiters.remove(waiter as u64);
+        true
+    }
+
+    /// Release the lock and wake a waiter
+    fn unlock(&self) {
+        self.owner.store(0, Ordering::Relaxed);
+        self.locked.store(false, Ordering::Release);
+        fence(Ordering::SeqCst);
+        if self.waiters.count() == 0 {
+            return;
+        }
+
+        let _serial = self.wait_lock.lock();
+        let Some(tid) = self.waiters.wake_one() else { return };
+        // Table busy: the waiter gets up at its recheck deadline
+        let Some(mut table) = PROCESS_TABLE.try_lock() else { return };
+        if let Some(thread) = table.get_mut(tid as u32) {
+            if thread.state == ProcessState::Blocked {
+                thread.state = ProcessState::Ready;
+                thread.wake_at = None;
+            }
+        }
+    }
+
+    /// Get a raw pointer to the inner data
+    ///
+    /// # Safety
+    ///
+    /// The pointer comes without any synchronization; the caller must
+    /// ensure proper access.
+    pub unsafe fn as_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// Check if the mutex is currently locked
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Thread holding the lock (0 if unlocked or held in kernel context)
+    pub fn owner(&self) -> u32 {
+        self.owner.load(Ordering::Relaxed)
+    }
+}
+
+/// RAII guard for an AdaptiveMutex
+pub struct AdaptiveMutexGuard<'a, T> {
+    mutex: &'a AdaptiveMutex<T>,
+}
+
+impl<'a, T> Drop for AdaptiveMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<'a, T> Deref for AdaptiveMutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for AdaptiveMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_mutex_basic() {
+        let mutex = AdaptiveMutex::new(42);
+        {
+            let mut guard = mutex.lock();
+            assert_eq!(*guard, 42);
+            *guard = 100;
+            assert!(mutex.is_locked());
+            assert!(mutex.try_lock().is_none());
+        }
+        assert!(!mutex.is_locked());
+        assert_eq!(mutex.owner(), 0);
+        assert_eq!(*mutex.try_lock().unwrap(), 100);
+    }
+
+    #[test]
+    fn test_spin_while_owner_runs() {
+        // A running owner is waited out by spinning, up to the limit
+        assert_eq!(next_step(5, true, 0), Step::Spin);
+        assert_eq!(next_step(5, true, SPIN_LIMIT - 1), Step::Spin);
+        assert_eq!(next_step(5, true, SPIN_LIMIT), Step::Block);
+
+        // An owner that is off its CPU will not release the lock soon
+        assert_eq!(next_step(5, false, 0), Step::Block);
+
+        // Kernel context cannot block
+        assert_eq!(next_step(0, false, SPIN_LIMIT), Step::Spin);
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Lock Contention Statistics
+//!
+//! With the `lockstat` feature, every [`SpinMutex`] created with
+//! [`SpinMutex::named`] (or [`AdaptiveMutex::named`]) counts its acquisitions and, when it had to wait,
+//! the spin iterations and TSC cycles spent waiting. Counters are kept
+//! per CPU so taking a lock never bounces a shared statistics line
+//! between CPUs; [`LockClass::stats`] sums them.
+//!
+//! Without the feature the classes still exist (so lock declarations
+//! don't need `cfg`s) but nothing is recorded.
+//!
+//! # Reporting
+//!
+//! `/proc/lockstat` lists the classes by total wait time, hottest first
+//! (see [`write_report`]).
+//!
+//! [`SpinMutex`]: super::SpinMutex
+//! [`SpinMutex::named`]: super::SpinMutex::named
+//! [`AdaptiveMutex::named`]: super::AdaptiveMutex::named
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::interrupt::affinity::MAX_CPUS;
+
+/// Per-CPU counters of one lock class
+struct CpuCounters {
+    acquired: AtomicU64,
+    contended: AtomicU64,
+    spins: AtomicU64,
+    wait_cycles: AtomicU64,
+}
+
+impl CpuCounters {
+    const fn new() -> Self {
+        Self {
+            acquired: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            spins: AtomicU64::new(0),
+            wait_cycles: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Statistics for a lock (or a group of locks sharing a name)
+pub struct LockClass {
+    name: &'static str,
+    cpus: [CpuCounters; MAX_CPUS],
+}
+
+/// Summed statistics of a lock class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockStats {
+    /// Class name
+    pub name: &'static str,
+    /// Successful `lock()` calls
+    pub acquired: u64,
+    /// Acquisitions that found the lock held
+    pub contended: u64,
+    /// Spin iterations while waiting
+    pub spins: u64,
+    /// TSC cycles spent waiting
+    pub wait_cycles: u64,
+}
+
+impl LockClass {
+    /// Create a lock class
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, cpus: [const { CpuCounters::new() }; MAX_CPUS] }
+    }
+
+    /// Class name
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Record an acquisition on `cpu`
+    ///
+    /// `spins` and `wait_cycles` are 0 for an uncontended acquisition.
+    pub fn record(&self, cpu: usize, spins: u64, wait_cycles: u64) {
+        let counters = &self.cpus[cpu % MAX_CPUS];
+        counters.acquired.fetch_add(1, Ordering::Relaxed);
+        if spins > 0 {
+            counters.contended.fetch_add(1, Ordering::Relaxed);
+            counters.spins.fetch_add(spins, Ordering::Relaxed);
+            counters.wait_cycles.fetch_add(wait_cycles, Ordering::Relaxed);
+        }
+    }
+
+    /// Statistics of one CPU
+    pub fn cpu_stats(&self, cpu: usize) -> LockStats {
+        let c = &self.cpus[cpu % MAX_CPUS];
+        LockStats {
+            name: self.name,
+            acquired: c.acquired.load(Ordering::Relaxed),
+            contended: c.contended.load(Ordering::Relaxed),
+            spins: c.spins.load(Ordering::Relaxed),
+            wait_cycles: c.wait_cycles.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Statistics summed over all CPUs
+    pub fn stats(&self) -> LockStats {
+        (0..MAX_CPUS).map(|cpu| self.cpu_stats(cpu)).fold(
+            LockStats { name: self.name, ..LockStats::default() },
+            |sum, s| LockStats {
+                name: sum.name,
+                acquired: sum.acquired + s.acquired,
+                contended: sum.contended + s.contended,
+                spins: sum.spins + s.spins,
+                wait_cycles: sum.wait_cycles + s.wait_cycles,
+            },
+        )
+    }
+
+    /// Clear all counters
+    pub fn reset(&self) {
+        for c in &self.cpus {
+            c.acquired.store(0, Ordering::Relaxed);
+            c.contended.store(0, Ordering::Relaxed);
+            c.spins.store(0, Ordering::Relaxed);
+            c.wait_cycles.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// ============================================================================
+// Lock Classes
+// ============================================================================
+
+/// `process::table::PROCESS_TABLE`
+pub static PROCESS_TABLE: LockClass = LockClass::new("process_table");
+/// `sched::round_robin::SCHEDULER`
+pub static SCHEDULER: LockClass = LockClass::new("scheduler");
+/// `fs::ramdisk::RAMDISK`
+pub static RAMDISK: LockClass = LockClass::new("ramdisk");
+/// `fs::tmpfs::TMPFS`
+pub static TMPFS: LockClass = LockClass::new("tmpfs");
+/// `fs::filemap::FILE_VMOS`
+pub static FILE_VMOS: LockClass = LockClass::new("file_vmos");
+/// `trace` ring buffer
+pub static TRACE_BUFFER: LockClass = LockClass::new("trace_buffer");
+/// `audit` log
+pub static AUDIT_LOG: LockClass = LockClass::new("audit_log");
+/// `mm::allocator` kernel heap
+pub static HEAP: LockClass = LockClass::new("heap");
+/// `mm::pmm` arenas
+pub static PMM: LockClass = LockClass::new("pmm");
+
+/// Every class, for reporting
+static CLASSES: [&LockClass; 9] = [&PROCESS_TABLE, &SCHEDULER, &RAMDISK, &TMPFS, &FILE_VMOS, &TRACE_BUFFER, &AUDIT_LOG, &HEAP, &PMM];
+
+/// Whether statistics are being collected (`lockstat` feature)
+pub const fn enabled() -> bool {
+    cfg!(feature = "lockstat")
+}
+
+/// Current CPU, for [`SpinMutex`](super::SpinMutex) accounting
+#[cfg(feature = "lockstat")]
+pub(super) fn cpu() -> usize {
+    crate::interrupt::affinity::current_cpu()
+}
+
+/// TSC cycles, for [`SpinMutex`](super::SpinMutex) wait timing
+#[cfg(feature = "lockstat")]
+pub(super) fn cycles() -> u64 {
+    crate::arch::amd64::tsc::tsc_ticks()
+}
+
+/// All classes, hottest first (by wait cycles, then contended count)
+pub fn top() -> Vec<LockStats> {
+    let mut stats: Vec<LockStats> = CLASSES.iter().map(|c| c.stats()).collect();
+    stats.sort_by_key(|s| core::cmp::Reverse((s.wait_cycles, s.contended)));
+    stats
+}
+
+/// Clear the counters of every class
+pub fn reset() {
+    CLASSES.iter().for_each(|c| c.reset());
+}
+
+/// Write the `/proc/lockstat` report
+///
+/// One line per class, hottest first, followed by per-CPU contended
+/// counts for the CPUs that saw any contention.
+pub fn write_report(out: &mut impl Write) -> core::fmt::Result {
+    if !enabled() {
+        return writeln!(out, "lockstat: disabled (build with --features lockstat)");
+    }
+
+    writeln!(out, "{:<16} {:>12} {:>12} {:>14} {:>16}  per-cpu contended", "class", "acquired", "contended", "spins", "wait_cycles")?;
+    for s in top() {
+        write!(out, "{:<16} {:>12} {:>12} {:>14} {:>16} ", s.name, s.acquired, s.contended, s.spins, s.wait_cycles)?;
+        let class = CLASSES.iter().find(|c| c.name == s.name);
+        for cpu in 0..MAX_CPUS {
+            let contended = class.map_or(0, |c| c.cpu_stats(cpu).contended);
+            if contended > 0 {
+                write!(out, " cpu{}={}", cpu, contended)?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_sum() {
+        let class = LockClass::new("test");
+        class.record(0, 0, 0);
+        class.record(1, 5, 100);
+        class.record(1, 3, 40);
+
+        let s = class.stats();
+        assert_eq!(s.acquired, 3);
+        assert_eq!(s.contended, 2);
+        assert_eq!(s.spins, 8);
+        assert_eq!(s.wait_cycles, 140);
+        assert_eq!(class.cpu_stats(1).contended, 2);
+        assert_eq!(class.cpu_stats(0).contended, 0);
+
+        class.reset();
+        assert_eq!(class.stats(), LockStats { name: "test", ..LockStats::default() });
+    }
+}
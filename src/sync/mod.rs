@@ -13,8 +13,11 @@
 //! # Primitives
 //!
 //! - **SpinMutex**: Spin-based mutual exclusion lock for short critical sections
+//! - **AdaptiveMutex**: Spins while the owner runs, blocks otherwise; for
+//!   critical sections that may be long
 //! - **Event**: Single-signal synchronization primitive
 //! - **WaitQueue**: Queue for threads waiting on a condition
+//! - **lockstat**: Per-lock contention statistics (`lockstat` feature)
 //!
 //! # Design
 //!
@@ -22,10 +25,13 @@
 //! proper integration for future scheduler integration.
 
 pub mod spinlock;
+pub mod adaptive;
 pub mod event;
 pub mod wait_queue;
+pub mod lockstat;
 
 // Re-exports
 pub use spinlock::{SpinMutex, SpinMutexGuard, SpinLock, SpinLockGuard};
+pub use adaptive::{AdaptiveMutex, AdaptiveMutexGuard};
 pub use event::{Event as SyncEvent, EventFlags as SyncEventFlags};
 pub use wait_queue::{WaitQueue, WaitQueueEntry, WaiterId, WaitStatus, WAIT_OK, WAIT_TIMED_OUT};
@@ -8,14 +8,20 @@
 //!
 //! This module provides a simple spinlock for kernel use.
 //! Spinlocks are used when the expected wait time is very short.
+//!
+//! Locks created with [`SpinMutex::named`] report contention to a
+//! [`LockClass`] when the `lockstat` feature is enabled.
 
 use core::sync::atomic::{AtomicBool, Ordering};
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
+use super::lockstat::LockClass;
 
 /// A simple spinlock
 pub struct SpinMutex<T> {
     locked: AtomicBool,
+    #[cfg(feature = "lockstat")]
+    class: Option<&'static LockClass>,
     data: UnsafeCell<T>,
 }
 
@@ -27,17 +33,56 @@ impl<T> SpinMutex<T> {
     pub const fn new(data: T) -> Self {
         Self {
             locked: AtomicBool::new(false),
+            #[cfg(feature = "lockstat")]
+            class: None,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Create a spinlock whose contention is counted under `class`
+    ///
+    /// Same as [`new`](Self::new) unless the `lockstat` feature is enabled.
+    pub const fn named(data: T, class: &'static LockClass) -> Self {
+        #[cfg(not(feature = "lockstat"))]
+        let _ = class;
+        Self {
+            locked: AtomicBool::new(false),
+            #[cfg(feature = "lockstat")]
+            class: Some(class),
             data: UnsafeCell::new(data),
         }
     }
 
     /// Acquire the lock, spinning until it becomes available
     pub fn lock(&self) -> SpinMutexGuard<'_, T> {
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            self.lock_contended();
+        } else {
+            #[cfg(feature = "lockstat")]
+            if let Some(class) = self.class {
+                class.record(super::lockstat::cpu(), 0, 0);
+            }
+        }
+        SpinMutexGuard { mutex: self }
+    }
+
+    /// Spin until the lock is free (the lock was held on the first try)
+    #[cold]
+    fn lock_contended(&self) {
+        #[cfg(feature = "lockstat")]
+        let start = super::lockstat::cycles();
+        let mut spins = 0u64;
         while self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
             // Spin with pause to reduce bus contention
             core::hint::spin_loop();
+            spins += 1;
         }
-        SpinMutexGuard { mutex: self }
+        #[cfg(feature = "lockstat")]
+        if let Some(class) = self.class {
+            let waited = super::lockstat::cycles().wrapping_sub(start);
+            class.record(super::lockstat::cpu(), spins.max(1), waited);
+        }
+        let _ = spins;
     }
 
     /// Try to acquire the lock without spinning
@@ -22,7 +22,7 @@
 //! let wq = WaitQueue::new();
 //!
 //! // Block current thread on the wait queue
-//! wq.block(u64::MAX); // Infinite timeout
+//! wq.block(waiter_id, priority, Instant::INFINITE);
 //!
 //! // Wake one thread
 //! wq.wake_one();
@@ -33,6 +33,7 @@
 
 use core::sync::atomic::{AtomicUsize, Ordering};
 use crate::sync::spinlock::SpinMutex;
+use crate::time::Instant;
 
 /// ============================================================================
 /// Types
@@ -127,7 +128,7 @@ impl WaitQueueInner {
 
         // Find insertion point (higher priority first)
         let mut insert_pos = self.tail;
-        let mut current = self.tail;
+        let mut current = self.head;
 
         for _ in 0..self.size {
             if let Some(existing) = self.entries[current] {
@@ -167,6 +168,30 @@ impl WaitQueueInner {
         entry
     }
 
+    /// Remove the entry of `waiter_id`, keeping the others in order
+    fn remove(&mut self, waiter_id: WaiterId) -> bool {
+        let mut pos = self.head;
+        for _ in 0..self.size {
+            if self.entries[pos].is_some_and(|e| e.waiter_id == waiter_id) {
+                // Close the gap
+                loop {
+                    let next = (pos + 1) % MAX_QUEUE_DEPTH;
+                    if next == self.tail {
+                        break;
+                    }
+                    self.entries[pos] = self.entries[next];
+                    pos = next;
+                }
+                self.entries[pos] = None;
+                self.tail = pos;
+                self.size -= 1;
+                return true;
+            }
+            pos = (pos + 1) % MAX_QUEUE_DEPTH;
+        }
+        false
+    }
+
     /// Peek at the front entry
     fn peek_front(&self) -> Option<&WaitQueueEntry> {
         if self.size == 0 {
@@ -221,15 +246,19 @@ impl WaitQueue {
     ///
     /// * `waiter_id` - ID of the waiter
     /// * `priority` - Priority of the waiter (higher = more important)
-    /// * `deadline` - Deadline in nanoseconds (u64::MAX = infinite)
+    /// * `deadline` - When to give up (`Instant::INFINITE` = never)
     ///
     /// # Returns
     ///
-    /// - `Ok(())` if woken successfully
-    /// - `Err(WAIT_TIMED_OUT)` if deadline reached
-    pub fn block(&self, waiter_id: WaiterId, priority: u8, _deadline: u64) -> WaitStatus {
+    /// - `WAIT_OK` if woken successfully
+    /// - `WAIT_TIMED_OUT` if the deadline has passed (without queuing)
+    pub fn block(&self, waiter_id: WaiterId, priority: u8, deadline: Instant) -> WaitStatus {
         self.validate();
 
+        if deadline.has_passed(Instant::now()) {
+            return WAIT_TIMED_OUT;
+        }
+
         // Add to queue
         {
             let mut queue = self.queue.lock();
@@ -294,6 +323,21 @@ impl WaitQueue {
         count
     }
 
+    /// Remove a waiter without waking it (e.g. when its wait timed out)
+    ///
+    /// # Returns
+    ///
+    /// true if `waiter_id` was queued
+    pub fn remove(&self, waiter_id: WaiterId) -> bool {
+        self.validate();
+
+        let removed = self.queue.lock().remove(waiter_id);
+        if removed {
+            self.count.fetch_sub(1, Ordering::Release);
+        }
+        removed
+    }
+
     /// Get the number of waiters
     pub fn count(&self) -> usize {
         self.count.load(Ordering::Relaxed)
@@ -332,9 +376,9 @@ mod tests {
         let wq = WaitQueue::new();
 
         // Add some waiters
-        wq.block(1, 10, u64::MAX);
-        wq.block(2, 20, u64::MAX);
-        wq.block(3, 15, u64::MAX);
+        wq.block(1, 10, Instant::INFINITE);
+        wq.block(2, 20, Instant::INFINITE);
+        wq.block(3, 15, Instant::INFINITE);
 
         assert_eq!(wq.len(), 3);
         assert!(!wq.is_empty());
@@ -350,9 +394,9 @@ mod tests {
         let wq = WaitQueue::new();
 
         // Add waiters with different priorities
-        wq.block(1, 10, u64::MAX);
-        wq.block(2, 30, u64::MAX);
-        wq.block(3, 20, u64::MAX);
+        wq.block(1, 10, Instant::INFINITE);
+        wq.block(2, 30, Instant::INFINITE);
+        wq.block(3, 20, Instant::INFINITE);
 
         // Should wake in priority order: 30, 20, 10
         assert_eq!(wq.wake_one(), Some(2)); // priority 30
@@ -365,11 +409,53 @@ mod tests {
     fn test_wait_queue_wake_all() {
         let wq = WaitQueue::new();
 
-        wq.block(1, 10, u64::MAX);
-        wq.block(2, 20, u64::MAX);
-        wq.block(3, 15, u64::MAX);
+        wq.block(1, 10, Instant::INFINITE);
+        wq.block(2, 20, Instant::INFINITE);
+        wq.block(3, 15, Instant::INFINITE);
 
         assert_eq!(wq.wake_all(), 3);
         assert!(wq.is_empty());
     }
+
+    #[test]
+    fn test_wait_queue_fifo_within_priority() {
+        let wq = WaitQueue::new();
+
+        wq.block(1, 10, Instant::INFINITE);
+        wq.block(2, 10, Instant::INFINITE);
+        wq.block(3, 20, Instant::INFINITE);
+        wq.block(4, 10, Instant::INFINITE);
+
+        assert_eq!(wq.wake_one(), Some(3));
+        assert_eq!(wq.wake_one(), Some(1));
+        assert_eq!(wq.wake_one(), Some(2));
+        assert_eq!(wq.wake_one(), Some(4));
+    }
+
+    #[test]
+    fn test_wait_queue_remove() {
+        let wq = WaitQueue::new();
+
+        wq.block(1, 10, Instant::INFINITE);
+        wq.block(2, 10, Instant::INFINITE);
+        wq.block(3, 10, Instant::INFINITE);
+
+        assert!(wq.remove(2));
+        assert!(!wq.remove(2));
+        assert_eq!(wq.len(), 2);
+        assert_eq!(wq.count(), 2);
+        wq.block(4, 10, Instant::INFINITE);
+        assert_eq!(wq.wake_one(), Some(1));
+        assert_eq!(wq.wake_one(), Some(3));
+        assert_eq!(wq.wake_one(), Some(4));
+        assert!(wq.is_empty());
+    }
+
+    #[test]
+    fn test_wait_queue_passed_deadline() {
+        let wq = WaitQueue::new();
+
+        assert_eq!(wq.block(1, 10, Instant::ZERO), WAIT_TIMED_OUT);
+        assert!(wq.is_empty());
+    }
 }
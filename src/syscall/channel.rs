@@ -0,0 +1,251 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Channel Syscalls
+//!
+//! Handle-table side of `sys_channel_create`, `sys_channel_write` and
+//! `sys_channel_read`. Userspace memory is copied by the syscall handlers
+//! before [`write`] and after [`read`], never while the process table is
+//! locked. If copying a read message out faults, [`unread`] takes its
+//! handles back and returns it to the front of the channel.
+//!
+//! # Handle Transfer
+//!
+//! A write may carry up to [`MAX_MSG_HANDLES`] handles. Each needs
+//! `TRANSFER`, must appear once, and must not refer to the channel endpoint
+//! being written (it would end up in its own peer's inbox and never
+//! close). Handles leave the writer's table only once the message is
+//! queued; on any error the writer keeps all of them. On read, the
+//! handles are installed in the reader's table with the rights they were
+//! sent with; a message whose handles do not fit stays queued.
+//!
+//! # Errors
+//!
+//! | Condition | Status |
+//! |-----------|--------|
+//! | Nothing to read yet, or the peer's inbox is full | `ERR_BUSY` |
+//! | Peer closed (for reads: and nothing is left) | `ERR_IO` |
+//! | Message over [`MAX_MSG_SIZE`] / [`MAX_MSG_HANDLES`] | `ERR_INVALID_ARGS` |
+//! | Read buffers too small (message stays queued) | `ERR_BUFFER_TOO_SMALL` |
+//! | Writing an endpoint's own handle | `ERR_NOT_SUPPORTED` |
+//! | Bad handle / missing right / table full | see [`handles`](crate::process::handles) |
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::channel::{self, Channel, Message, MAX_MSG_HANDLES, MAX_MSG_SIZE};
+use crate::object::{KernelObject, ObjectHandle, Rights};
+use crate::process::handles::ProcessHandles;
+use super::{ChannelActual, HandlePair};
+
+/// Map a channel error to a syscall status
+pub fn status(err: &'static str) -> RxStatus {
+    match err {
+        channel::ERR_SHOULD_WAIT | channel::ERR_FULL => RxStatus::ERR_BUSY,
+        channel::ERR_PEER_CLOSED | channel::ERR_CLOSED => RxStatus::ERR_IO,
+        channel::ERR_TOO_LARGE => RxStatus::ERR_INVALID_ARGS,
+        channel::ERR_BUFFER_TOO_SMALL => RxStatus::ERR_BUFFER_TOO_SMALL,
+        _ => RxStatus::ERR_INTERNAL,
+    }
+}
+
+/// Create a channel pair in `handles`
+///
+/// Both endpoints get the default channel rights.
+pub fn create(handles: &mut ProcessHandles) -> Result<HandlePair, RxStatus> {
+    if handles.available() < 2 {
+        return Err(RxStatus::ERR_NO_MEMORY);
+    }
+    let (a, b) = Channel::create().map_err(|_| RxStatus::ERR_NO_MEMORY)?;
+    let values = handles.insert_many(alloc::vec![
+        ObjectHandle::with_default_rights(a),
+        ObjectHandle::with_default_rights(b),
+    ])?;
+    Ok(HandlePair { handle0: values[0], handle1: values[1] })
+}
+
+/// Write a message, moving `transfer` out of `handles`
+///
+/// # Arguments
+///
+/// * `handles` - Writer's handle table
+/// * `handle` - Channel endpoint (needs WRITE)
+/// * `data` - Message bytes
+/// * `transfer` - Handle values to send along (each needs TRANSFER)
+pub fn write(
+    handles: &mut ProcessHandles,
+    handle: u32,
+    data: &[u8],
+    transfer: &[u32],
+) -> Result<usize, RxStatus> {
+    if data.len() > MAX_MSG_SIZE || transfer.len() > MAX_MSG_HANDLES {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    let endpoint = handles.object::<Channel>(handle, Rights::WRITE)?;
+    let own = KernelObject::Channel(endpoint.clone());
+
+    let mut sent = Vec::with_capacity(transfer.len());
+    for (i, &value) in transfer.iter().enumerate() {
+        if transfer[..i].contains(&value) {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        let h = handles.get(value, Rights::TRANSFER)?;
+        if h.object.same_object(&own) {
+            return Err(RxStatus::ERR_NOT_SUPPORTED);
+        }
+        sent.push(h.clone());
+    }
+
+    let written = endpoint.write(data, &sent).map_err(status)?;
+
+    // Queued: the message holds its own references now
+    handles.remove_many(transfer, Rights::TRANSFER)?;
+    Ok(written)
+}
+
+/// A message moved into the reader's handle table
+pub struct Received {
+    /// Message bytes
+    pub data: Vec<u8>,
+
+    /// Handle values in the reader's table
+    pub handles: Vec<u32>,
+
+    /// Channel it was read from, for [`unread`]
+    endpoint: Arc<Channel>,
+}
+
+/// Read the next message, installing its handles in `handles`
+///
+/// # Arguments
+///
+/// * `handles` - Reader's handle table
+/// * `handle` - Channel endpoint (needs READ)
+/// * `max_bytes` - Room for message bytes
+/// * `max_handles` - Room for handle values
+pub fn read(
+    handles: &mut ProcessHandles,
+    handle: u32,
+    max_bytes: usize,
+    max_handles: usize,
+) -> Result<Received, RxStatus> {
+    let endpoint = handles.object::<Channel>(handle, Rights::READ)?;
+    let max_handles = core::cmp::min(max_handles, MAX_MSG_HANDLES);
+
+    let msg = endpoint.take(max_bytes, max_handles).map_err(status)?;
+    // Leave the message queued if its handles would not fit
+    if msg.handle_count() > handles.available() {
+        endpoint.put_back(msg);
+        return Err(RxStatus::ERR_NO_MEMORY);
+    }
+    let values = handles.insert_many(msg.handles)?;
+    Ok(Received { data: msg.data, handles: values, endpoint })
+}
+
+/// Undo a [`read`] whose message could not be copied to userspace
+///
+/// Takes the message's handles back out of `handles` and returns it to
+/// the front of its channel. Handles another thread closed in between
+/// are not sent again.
+pub fn unread(handles: &mut ProcessHandles, received: Received) {
+    let taken = received.handles.iter().filter_map(|&value| handles.remove(value).ok()).collect();
+    received.endpoint.put_back(Message::new(received.data, taken));
+}
+
+/// Size of the next message on `handle`, for reporting short buffers
+pub fn pending(handles: &ProcessHandles, handle: u32) -> Option<ChannelActual> {
+    let (bytes, count) = handles.object::<Channel>(handle, Rights::READ).ok()?.peek()?;
+    Some(ChannelActual { bytes: bytes as u32, handles: count as u32 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_write_read() {
+        let mut table = ProcessHandles::new();
+        let pair = create(&mut table).unwrap();
+        assert_ne!(pair.handle0, pair.handle1);
+
+        assert_eq!(write(&mut table, pair.handle0, b"ping", &[]), Ok(4));
+        let msg = read(&mut table, pair.handle1, 16, 0).ok().unwrap();
+        assert_eq!(msg.data, b"ping");
+        assert!(msg.handles.is_empty());
+
+        assert_eq!(read(&mut table, pair.handle1, 16, 0).err(), Some(RxStatus::ERR_BUSY));
+    }
+
+    #[test]
+    fn test_handle_transfer() {
+        let mut table = ProcessHandles::new();
+        let pair = create(&mut table).unwrap();
+        let other = create(&mut table).unwrap();
+
+        write(&mut table, pair.handle0, b"", &[other.handle1]).unwrap();
+        assert_eq!(table.len(), 3);
+        assert!(table.get(other.handle1, Rights::NONE).is_err());
+
+        // Too little room for the handle: the message stays queued
+        assert_eq!(read(&mut table, pair.handle1, 0, 0).err(), Some(RxStatus::ERR_BUFFER_TOO_SMALL));
+        assert_eq!(pending(&table, pair.handle1), Some(ChannelActual { bytes: 0, handles: 1 }));
+
+        let msg = read(&mut table, pair.handle1, 0, 1).ok().unwrap();
+        assert_eq!(msg.handles.len(), 1);
+
+        // The received endpoint is still connected to its peer
+        write(&mut table, msg.handles[0], b"x", &[]).unwrap();
+        assert_eq!(read(&mut table, other.handle0, 1, 0).ok().unwrap().data, b"x");
+    }
+
+    #[test]
+    fn test_unread_restores_message() {
+        let mut table = ProcessHandles::new();
+        let pair = create(&mut table).unwrap();
+        let other = create(&mut table).unwrap();
+        write(&mut table, pair.handle0, b"hi", &[other.handle1]).unwrap();
+
+        let msg = read(&mut table, pair.handle1, 2, 1).ok().unwrap();
+        assert_eq!(table.len(), 4);
+        unread(&mut table, msg);
+        assert_eq!(table.len(), 3);
+        assert_eq!(pending(&table, pair.handle1), Some(ChannelActual { bytes: 2, handles: 1 }));
+
+        let msg = read(&mut table, pair.handle1, 2, 1).ok().unwrap();
+        assert_eq!((msg.data.as_slice(), msg.handles.len()), (&b"hi"[..], 1));
+    }
+
+    #[test]
+    fn test_transfer_errors_keep_handles() {
+        let mut table = ProcessHandles::new();
+        let pair = create(&mut table).unwrap();
+        let other = create(&mut table).unwrap();
+
+        assert_eq!(
+            write(&mut table, pair.handle0, b"", &[pair.handle0]),
+            Err(RxStatus::ERR_NOT_SUPPORTED)
+        );
+        assert_eq!(
+            write(&mut table, pair.handle0, b"", &[other.handle0, other.handle0]),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            write(&mut table, pair.handle0, b"", &[other.handle0, 999]),
+            Err(RxStatus::ERR_NOT_FOUND)
+        );
+        assert_eq!(table.len(), 4);
+    }
+
+    #[test]
+    fn test_peer_closed() {
+        let mut table = ProcessHandles::new();
+        let pair = create(&mut table).unwrap();
+        table.remove(pair.handle1).unwrap();
+
+        assert_eq!(write(&mut table, pair.handle0, b"x", &[]), Err(RxStatus::ERR_IO));
+        assert_eq!(read(&mut table, pair.handle0, 1, 0).err(), Some(RxStatus::ERR_IO));
+    }
+}
@@ -14,7 +14,34 @@
 //! - fd 0: stdin (keyboard input, future)
 //! - fd 1: stdout (kernel debug console, port 0xE9)
 //! - fd 2: stderr (same as stdout for now)
-//! - fd 3+: files, pipes, etc. (Phase 5C)
+//! - fd 3+: files, TTYs, tmpfs and mounted files, pipes, etc. (Phase 5C)
+//!
+//! These are only the initial assignments: I/O goes by the descriptor's
+//! kind, not its number, so `DUP2` can point fd 1 at a pipe or a file.
+//!
+//! # Duplication and Inheritance
+//!
+//! `DUP` and `DUP2` copy a descriptor within a table
+//! ([`FileDescriptorTable::dup`], [`FileDescriptorTable::dup2`]). A
+//! `SPAWN` child starts with a copy of its parent's table; `SPAWN_FDS`
+//! passes only the descriptors it lists ([`FileDescriptorTable::select`]).
+//! Copies have their own offsets and flags, unlike POSIX, where
+//! duplicates share one open file description.
+//!
+//! # Close on Spawn
+//!
+//! A descriptor with [`flags::O_CLOEXEC`] (set at open, by `HANDLE_TO_FD`
+//! or with `FCNTL(F_SETFD)`) is left out of the table a `SPAWN` child
+//! inherits ([`FileDescriptorTable::inherit`]). `FORK` copies it, and the
+//! copies `DUP`, `DUP2` and `SPAWN_FDS` make start without the flag, as
+//! in POSIX.
+//!
+//! # Non-blocking Mode
+//!
+//! A descriptor opened with [`flags::O_NONBLOCK`], or switched with
+//! `FCNTL(F_SETFL)`, never blocks: a read from a TTY (including stdin)
+//! with no input, or a pipe read or write that would wait, fails with
+//! `ERR_SHOULD_WAIT` instead.
 
 /// File descriptor kinds
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -36,7 +63,47 @@ pub enum FdKind {
         offset: u64,
     },
 
-    /// Pipe descriptor (future)
+    /// TTY device opened through devfs (`/dev/ttyN`)
+    Tty {
+        /// TTY index (0-based)
+        tty: u8,
+    },
+
+    /// Block device opened through devfs (`/dev/ram0`)
+    Block {
+        /// Block device index (see [`crate::drivers::block`])
+        dev: u8,
+        /// Current byte offset
+        offset: u64,
+    },
+
+    /// Synthesized file opened through procfs (`/proc/...`)
+    Proc {
+        /// procfs file
+        node: crate::fs::procfs::ProcNode,
+        /// Current file offset
+        offset: u64,
+    },
+
+    /// File or directory in tmpfs (`/tmp/...`)
+    Tmp {
+        /// tmpfs inode number
+        inode: u32,
+        /// Current file offset
+        offset: u64,
+    },
+
+    /// File or directory on a mounted filesystem
+    Mounted {
+        /// Mount ID (see [`crate::fs::mount`])
+        mount: u32,
+        /// Inode number on the filesystem
+        inode: u64,
+        /// Current file offset
+        offset: u64,
+    },
+
+    /// End of a pipe created by `PIPE`
     Pipe {
         /// True if this is the read end
         read_end: bool,
@@ -46,7 +113,12 @@ pub enum FdKind {
 }
 
 /// File descriptor entry
-#[derive(Debug, Clone)]
+///
+/// A tmpfs descriptor keeps its inode open, and a pipe descriptor its end
+/// of the pipe: copies and drops are counted so an unlinked file is freed
+/// only after its last descriptor goes away, and a pipe sees end of file
+/// (or loses its readers) only when the last copy of an end is closed.
+#[derive(Debug)]
 pub struct FileDescriptor {
     /// Kind of file descriptor
     pub kind: FdKind,
@@ -61,6 +133,41 @@ impl FileDescriptor {
         Self { kind, flags }
     }
 
+    /// Whether operations that would block fail with `ERR_SHOULD_WAIT`
+    pub const fn is_nonblocking(&self) -> bool {
+        self.flags & flags::O_NONBLOCK != 0
+    }
+
+    /// Whether it was opened for reading (`O_RDONLY` or `O_RDWR`)
+    pub const fn is_readable(&self) -> bool {
+        self.flags & flags::O_ACCMODE != flags::O_WRONLY
+    }
+
+    /// Whether it was opened for writing (`O_WRONLY` or `O_RDWR`)
+    pub const fn is_writable(&self) -> bool {
+        self.flags & flags::O_ACCMODE != flags::O_RDONLY
+    }
+
+    /// Whether a `SPAWN` child leaves it out
+    pub const fn is_cloexec(&self) -> bool {
+        self.flags & flags::O_CLOEXEC != 0
+    }
+
+    /// A copy for another fd number, without `O_CLOEXEC`
+    fn dup_copy(&self) -> Self {
+        let mut copy = self.clone();
+        copy.flags &= !flags::O_CLOEXEC;
+        copy
+    }
+
+    /// Replace the status flags ([`flags::STATUS_FLAGS`]) with those in `new`
+    ///
+    /// The access mode and creation flags are kept; other bits in `new`
+    /// are ignored.
+    pub fn set_status_flags(&mut self, new: u32) {
+        self.flags = (self.flags & !flags::STATUS_FLAGS) | (new & flags::STATUS_FLAGS);
+    }
+
     /// Create a stdin file descriptor
     pub const fn stdin() -> Self {
         Self {
@@ -86,16 +193,40 @@ impl FileDescriptor {
     }
 }
 
+impl Clone for FileDescriptor {
+    fn clone(&self) -> Self {
+        match self.kind {
+            FdKind::Tmp { inode, .. } => crate::fs::tmpfs::retain(inode),
+            FdKind::Mounted { mount, inode, .. } => crate::fs::mount::retain(mount, inode),
+            FdKind::Pipe { read_end, pipe_id } => crate::object::pipe::retain(pipe_id, read_end),
+            _ => {}
+        }
+        Self { kind: self.kind, flags: self.flags }
+    }
+}
+
+impl Drop for FileDescriptor {
+    fn drop(&mut self) {
+        match self.kind {
+            FdKind::Tmp { inode, .. } => crate::fs::tmpfs::release(inode),
+            FdKind::Mounted { mount, inode, .. } => crate::fs::mount::release(mount, inode),
+            FdKind::Pipe { read_end, pipe_id } => crate::object::pipe::release(pipe_id, read_end),
+            _ => {}
+        }
+    }
+}
+
 /// Per-process file descriptor table
 ///
 /// Manages file descriptors for a single process.
 /// FD 0, 1, 2 are pre-allocated as stdin, stdout, stderr.
+///
+/// Cloning gives a `FORK` child its own copy: file offsets are not
+/// shared with the parent afterwards.
+#[derive(Clone)]
 pub struct FileDescriptorTable {
     /// File descriptors (indexed by fd number)
     fds: [Option<FileDescriptor>; 256],
-
-    /// Next fd to allocate (starts at 3, after stdin/stdout/stderr)
-    next_fd: u8,
 }
 
 impl FileDescriptorTable {
@@ -109,10 +240,7 @@ impl FileDescriptorTable {
         // Pre-allocate stdin, stdout, stderr (will be set up in init below)
         // For now, we use const fn, so we can't actually set them here
 
-        Self {
-            fds,
-            next_fd: 3,
-        }
+        Self { fds }
     }
 
     /// Initialize the standard file descriptors (0, 1, 2)
@@ -129,21 +257,66 @@ impl FileDescriptorTable {
     ///
     /// Returns the fd number, or None if the table is full.
     pub fn alloc(&mut self, kind: FdKind, flags: u32) -> Option<u8> {
-        // Check if we've wrapped around (table is full)
-        if self.next_fd == 0 {
-            return None;
-        }
+        self.insert(FileDescriptor::new(kind, flags))
+    }
 
-        let fd = self.next_fd;
-        self.fds[fd as usize] = Some(FileDescriptor::new(kind, flags));
+    /// Install an existing descriptor under the lowest free fd number
+    ///
+    /// Returns the fd number, or None (dropping `desc`) if the table is full.
+    pub fn insert(&mut self, desc: FileDescriptor) -> Option<u8> {
+        let fd = self.fds.iter().position(|f| f.is_none())?;
+        self.fds[fd] = Some(desc);
+        Some(fd as u8)
+    }
 
-        // Increment and check for wrap
-        self.next_fd = self.next_fd.wrapping_add(1);
-        if self.next_fd == 0 {
-            // Table is full after this allocation
+    /// Copy `fd` to the lowest free fd number
+    ///
+    /// Returns the new fd number, or None if `fd` is not open or the
+    /// table is full. The copy does not have `O_CLOEXEC`.
+    pub fn dup(&mut self, fd: u8) -> Option<u8> {
+        let desc = self.get(fd)?.dup_copy();
+        self.insert(desc)
+    }
+
+    /// Copy `old_fd` to `new_fd`, closing whatever `new_fd` was
+    ///
+    /// Unlike [`close`](Self::close), this may replace stdin, stdout and
+    /// stderr. Returns `new_fd`, or None if `old_fd` is not open. Copying
+    /// a descriptor onto itself changes nothing; otherwise the copy does
+    /// not have `O_CLOEXEC`.
+    pub fn dup2(&mut self, old_fd: u8, new_fd: u8) -> Option<u8> {
+        let desc = self.get(old_fd)?.dup_copy();
+        if old_fd != new_fd {
+            self.fds[new_fd as usize] = Some(desc);
         }
+        Some(new_fd)
+    }
 
-        Some(fd)
+    /// Build a child's table from the descriptors listed in `map`
+    ///
+    /// Child fd `i` gets a copy of parent fd `map[i]`, without
+    /// `O_CLOEXEC`; a negative entry, and every fd past the end of `map`,
+    /// is left closed. Returns None if an entry names an fd that is not
+    /// open.
+    pub fn select(&self, map: &[i32]) -> Option<Self> {
+        let mut table = Self::new();
+        for (fd, &parent_fd) in map.iter().enumerate().take(table.fds.len()) {
+            if parent_fd >= 0 {
+                let desc = self.get(u8::try_from(parent_fd).ok()?)?;
+                table.fds[fd] = Some(desc.dup_copy());
+            }
+        }
+        Some(table)
+    }
+
+    /// Build a `SPAWN` child's table: every descriptor but those with
+    /// `O_CLOEXEC`, under the same numbers
+    pub fn inherit(&self) -> Self {
+        let mut table = Self::new();
+        for (slot, desc) in table.fds.iter_mut().zip(&self.fds) {
+            *slot = desc.as_ref().filter(|d| !d.is_cloexec()).cloned();
+        }
+        table
     }
 
     /// Get a file descriptor by number
@@ -174,6 +347,14 @@ impl FileDescriptorTable {
         self.fds.get_mut(fd as usize)?.take()
     }
 
+    /// Close every descriptor, including stdin, stdout and stderr
+    ///
+    /// Called when the process exits, so the pipes and unlinked files it
+    /// held are released before it is reaped.
+    pub fn close_all(&mut self) {
+        self.fds.iter_mut().for_each(|f| *f = None);
+    }
+
     /// Get the number of active file descriptors
     pub fn count(&self) -> usize {
         self.fds.iter().filter(|f| f.is_some()).count()
@@ -206,6 +387,36 @@ pub mod flags {
 
     /// Append mode
     pub const O_APPEND: u32 = 1 << 6;
+
+    /// Fail with `ERR_SHOULD_WAIT` instead of blocking
+    pub const O_NONBLOCK: u32 = 1 << 7;
+
+    /// Leave the descriptor out of a `SPAWN` child's table
+    pub const O_CLOEXEC: u32 = 1 << 8;
+
+    /// Bits holding the access mode (O_RDONLY, O_WRONLY, O_RDWR)
+    pub const O_ACCMODE: u32 = 3;
+
+    /// Flags that `FCNTL(F_SETFL)` can change after open
+    pub const STATUS_FLAGS: u32 = O_APPEND | O_NONBLOCK;
+}
+
+/// `FCNTL` commands
+pub mod fcntl {
+    /// Get the descriptor flags ([`FD_CLOEXEC`])
+    pub const F_GETFD: u32 = 1;
+
+    /// Set the descriptor flags ([`FD_CLOEXEC`])
+    pub const F_SETFD: u32 = 2;
+
+    /// Get the descriptor's open flags
+    pub const F_GETFL: u32 = 3;
+
+    /// Set the descriptor's status flags (`O_APPEND`, `O_NONBLOCK`)
+    pub const F_SETFL: u32 = 4;
+
+    /// Descriptor flag: close on spawn (`O_CLOEXEC`)
+    pub const FD_CLOEXEC: u32 = 1;
 }
 
 // ============================================================================
@@ -265,6 +476,76 @@ mod tests {
         assert!(table.get(0).is_some());
     }
 
+    #[test]
+    fn test_fd_alloc_reuses_lowest() {
+        let mut table = FileDescriptorTable::new();
+        table.init();
+
+        let a = table.alloc(FdKind::Tty { tty: 1 }, flags::O_RDWR).unwrap();
+        let b = table.alloc(FdKind::Tty { tty: 2 }, flags::O_RDWR).unwrap();
+        assert_eq!((a, b), (3, 4));
+        table.close(a);
+        assert_eq!(table.alloc(FdKind::Tty { tty: 3 }, flags::O_RDWR), Some(3));
+    }
+
+    #[test]
+    fn test_fd_dup() {
+        let mut table = FileDescriptorTable::new();
+        table.init();
+        let fd = table.alloc(FdKind::Tty { tty: 1 }, flags::O_RDWR | flags::O_NONBLOCK).unwrap();
+
+        let copy = table.dup(fd).unwrap();
+        assert_eq!(copy, 4);
+        assert_eq!(table.get(copy).unwrap().kind, FdKind::Tty { tty: 1 });
+        assert!(table.get(copy).unwrap().is_nonblocking());
+        assert_eq!(table.dup(200), None);
+
+        // dup2 may replace stdout, and leaves the source open
+        assert_eq!(table.dup2(fd, 1), Some(1));
+        assert_eq!(table.get(1).unwrap().kind, FdKind::Tty { tty: 1 });
+        assert!(table.get(fd).is_some());
+        assert_eq!(table.dup2(fd, fd), Some(fd));
+        assert_eq!(table.dup2(200, 1), None);
+    }
+
+    #[test]
+    fn test_fd_select() {
+        let mut parent = FileDescriptorTable::new();
+        parent.init();
+        let tty = parent.alloc(FdKind::Tty { tty: 2 }, flags::O_WRONLY).unwrap();
+
+        // Child stdin from the parent's, stdout to the TTY, no stderr
+        let child = parent.select(&[0, tty as i32, -1]).unwrap();
+        assert!(matches!(child.get(0).unwrap().kind, FdKind::Stdin));
+        assert_eq!(child.get(1).unwrap().kind, FdKind::Tty { tty: 2 });
+        assert!(child.get(2).is_none());
+        assert_eq!(child.count(), 2);
+
+        assert!(parent.select(&[0, 9]).is_none());
+        assert!(parent.select(&[300]).is_none());
+    }
+
+    #[test]
+    fn test_fd_cloexec() {
+        let mut parent = FileDescriptorTable::new();
+        parent.init();
+        let keep = parent.alloc(FdKind::Tty { tty: 1 }, flags::O_RDWR).unwrap();
+        let hide = parent.alloc(FdKind::Tty { tty: 2 }, flags::O_RDWR | flags::O_CLOEXEC).unwrap();
+        assert!(parent.get(hide).unwrap().is_cloexec());
+
+        // A spawned child keeps the numbers but not the close-on-spawn fd
+        let child = parent.inherit();
+        assert_eq!(child.get(keep).unwrap().kind, FdKind::Tty { tty: 1 });
+        assert!(child.get(hide).is_none());
+        assert_eq!(child.count(), 4);
+
+        // Copies start without the flag
+        let copy = parent.dup(hide).unwrap();
+        assert!(!parent.get(copy).unwrap().is_cloexec());
+        assert!(!parent.select(&[hide as i32]).unwrap().get(0).unwrap().is_cloexec());
+        assert!(parent.get(hide).unwrap().is_cloexec());
+    }
+
     #[test]
     fn test_fd_kind() {
         let stdin = FileDescriptor::stdin();
@@ -276,4 +557,18 @@ mod tests {
         let stderr = FileDescriptor::stderr();
         assert!(matches!(stderr.kind, FdKind::Stderr));
     }
+
+    #[test]
+    fn test_fd_status_flags() {
+        let mut fd = FileDescriptor::new(FdKind::Tty { tty: 1 }, flags::O_RDWR | flags::O_NONBLOCK);
+        assert!(fd.is_nonblocking());
+
+        fd.set_status_flags(0);
+        assert!(!fd.is_nonblocking());
+        assert_eq!(fd.flags, flags::O_RDWR);
+
+        // Only status flags change
+        fd.set_status_flags(flags::O_NONBLOCK | flags::O_WRONLY | flags::O_CREAT);
+        assert_eq!(fd.flags, flags::O_RDWR | flags::O_NONBLOCK);
+    }
 }
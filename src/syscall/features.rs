@@ -0,0 +1,151 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Syscall ABI Version and Feature Probe
+//!
+//! `SYSTEM_GET_FEATURES` tells userspace what the running kernel
+//! supports, so a program can use a newer syscall or subsystem when it is
+//! there and fall back when it is not:
+//!
+//! - [`ABI_VERSION`]: bumped when an existing syscall changes in a way old
+//!   programs would notice. Adding a syscall does not bump it.
+//! - A bitmap of the syscalls in [`number`] that are implemented (the
+//!   `syscall_stub!` ones are left out)
+//! - [`feature`] bits for subsystems and compiled-in build features
+//!
+//! A kernel older than the probe fails it with `ERR_NOT_SUPPORTED`, which
+//! userspace treats as ABI version 1 with no optional features.
+
+use super::number;
+
+/// Syscall ABI version
+pub const ABI_VERSION: u32 = 1;
+
+/// Words in the syscall bitmap (syscall numbers 0 to 255)
+pub const SYSCALL_WORDS: usize = 4;
+
+/// Feature bits
+pub mod feature {
+    /// Time page mapped at `VDSO_TIME_VADDR` (see [`crate::vdso`])
+    pub const VDSO_TIME: u64 = 1 << 0;
+    /// Writable `/tmp`
+    pub const TMPFS: u64 = 1 << 1;
+    /// `/proc`
+    pub const PROCFS: u64 = 1 << 2;
+    /// `/proc/lockstat` (`lockstat` build feature)
+    pub const LOCKSTAT: u64 = 1 << 3;
+    /// Handle creation sites recorded (`handle_tracking` build feature)
+    pub const HANDLE_TRACKING: u64 = 1 << 4;
+    /// Heap redzones and quarantine (`kasan` build feature)
+    pub const KASAN: u64 = 1 << 5;
+    /// Test kernel (`kernel_test` build feature)
+    pub const KERNEL_TEST: u64 = 1 << 6;
+}
+
+/// Syscalls in [`number`] whose handler is still a `syscall_stub!`
+const STUBS: &[u32] = &[
+    number::PROCESS_START,
+    number::VMO_CLONE,
+    number::VMAR_UNMAP,
+    number::VMAR_PROTECT,
+    number::EVENTPAIR_CREATE,
+    number::HANDLE_TRANSFER,
+];
+
+/// `SYSTEM_GET_FEATURES` output
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemFeatures {
+    /// [`ABI_VERSION`]
+    pub abi_version: u32,
+    /// Highest syscall number ([`number::MAX_SYSCALL`])
+    pub max_syscall: u32,
+    /// Bit `n % 64` of word `n / 64` is set if syscall `n` is implemented
+    pub syscalls: [u64; SYSCALL_WORDS],
+    /// [`feature`] bits
+    pub features: u64,
+}
+
+impl SystemFeatures {
+    /// Check whether syscall `num` is implemented
+    pub fn has_syscall(&self, num: u32) -> bool {
+        let word = (num / 64) as usize;
+        word < SYSCALL_WORDS && self.syscalls[word] & (1 << (num % 64)) != 0
+    }
+}
+
+/// Feature bits of this kernel build
+const fn feature_bits() -> u64 {
+    let mut bits = feature::VDSO_TIME | feature::TMPFS | feature::PROCFS;
+    if cfg!(feature = "lockstat") {
+        bits |= feature::LOCKSTAT;
+    }
+    if cfg!(feature = "handle_tracking") {
+        bits |= feature::HANDLE_TRACKING;
+    }
+    if cfg!(feature = "kasan") {
+        bits |= feature::KASAN;
+    }
+    if cfg!(feature = "kernel_test") {
+        bits |= feature::KERNEL_TEST;
+    }
+    bits
+}
+
+/// What this kernel supports
+pub const fn current() -> SystemFeatures {
+    let mut syscalls = [0u64; SYSCALL_WORDS];
+    let mut i = 0;
+    while i < number::ALL.len() {
+        let num = number::ALL[i];
+        let mut stub = false;
+        let mut j = 0;
+        while j < STUBS.len() {
+            stub |= STUBS[j] == num;
+            j += 1;
+        }
+        if !stub {
+            syscalls[(num / 64) as usize] |= 1 << (num % 64);
+        }
+        i += 1;
+    }
+    SystemFeatures {
+        abi_version: ABI_VERSION,
+        max_syscall: number::MAX_SYSCALL,
+        syscalls,
+        features: feature_bits(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_list() {
+        // Sorted, unique, and ending at MAX_SYSCALL
+        assert!(number::ALL.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(number::ALL.last(), Some(&number::MAX_SYSCALL));
+        assert!(number::MAX_SYSCALL < (SYSCALL_WORDS * 64) as u32);
+        assert!(STUBS.iter().all(|s| number::ALL.contains(s)));
+    }
+
+    #[test]
+    fn test_bitmap() {
+        let f = current();
+        assert_eq!(f.abi_version, ABI_VERSION);
+        assert!(f.has_syscall(number::WRITE));
+        assert!(f.has_syscall(number::HANDLE_TO_FD));
+        assert!(f.has_syscall(number::SYSTEM_GET_FEATURES));
+        assert!(!f.has_syscall(number::VMAR_PROTECT));
+        assert!(!f.has_syscall(0));
+        assert!(!f.has_syscall(0x8F));
+        assert!(!f.has_syscall(u32::MAX));
+        let count: u32 = f.syscalls.iter().map(|w| w.count_ones()).sum();
+        assert_eq!(count as usize, number::ALL.len() - STUBS.len());
+        assert_ne!(f.features & feature::VDSO_TIME, 0);
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! File Handle Syscalls
+//!
+//! Table side of `sys_fd_to_handle` and `sys_handle_to_fd`, which move
+//! open files between processes: a descriptor is wrapped in a
+//! [`File`](crate::object::File) handle, sent over a channel like any
+//! other handle, and installed as a descriptor again by the receiver.
+//! See [`crate::object::file`] for the rights a file handle carries.
+//!
+//! # Errors
+//!
+//! | Condition | Status |
+//! |-----------|--------|
+//! | Descriptor not open, handle not a file, or flags other than `O_CLOEXEC` | `ERR_INVALID_ARGS` |
+//! | Handle allows neither reading nor writing | `ERR_ACCESS_DENIED` |
+//! | Handle or descriptor table full | `ERR_NO_MEMORY` |
+//! | Bad handle | see [`handles`](crate::process::handles) |
+
+use alloc::sync::Arc;
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::{File, KernelObject, ObjectHandle, Rights};
+use crate::process::handles::ProcessHandles;
+use super::fd::{flags, FileDescriptorTable};
+
+/// Wrap a copy of descriptor `fd` in a new handle
+///
+/// The descriptor stays open. The handle gets `READ` and `WRITE` as the
+/// descriptor's access mode allows, plus `DUPLICATE` and `TRANSFER`.
+pub fn to_handle(fds: &FileDescriptorTable, handles: &mut ProcessHandles, fd: u8) -> Result<u32, RxStatus> {
+    let desc = fds.get(fd).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+    let file = File::new(desc);
+    let rights = file.rights();
+    handles.insert(ObjectHandle::new(KernelObject::File(Arc::new(file)), rights))
+}
+
+/// Install the descriptor a file handle holds, closing the handle
+///
+/// # Arguments
+///
+/// * `handles` - Caller's handle table
+/// * `fds` - Caller's descriptor table
+/// * `value` - File handle (needs `READ` or `WRITE`)
+/// * `fd_flags` - `O_CLOEXEC` or 0
+///
+/// # Returns
+///
+/// The new fd number. On error the handle stays open.
+pub fn from_handle(
+    handles: &mut ProcessHandles,
+    fds: &mut FileDescriptorTable,
+    value: u32,
+    fd_flags: u32,
+) -> Result<u8, RxStatus> {
+    if fd_flags & !flags::O_CLOEXEC != 0 {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    let handle = handles.get(value, Rights::NONE)?;
+    let file = handle.object.downcast::<File>().ok_or(RxStatus::ERR_INVALID_ARGS)?;
+    let mut desc = file.descriptor(handle.rights).map_err(|_| RxStatus::ERR_ACCESS_DENIED)?;
+    desc.flags |= fd_flags;
+
+    let fd = fds.insert(desc).ok_or(RxStatus::ERR_NO_MEMORY)?;
+    handles.remove(value)?;
+    Ok(fd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::channel;
+    use crate::syscall::fd::FdKind;
+
+    fn tables() -> (FileDescriptorTable, ProcessHandles) {
+        let mut fds = FileDescriptorTable::new();
+        fds.init();
+        (fds, ProcessHandles::new())
+    }
+
+    #[test]
+    fn test_pass_over_channel() {
+        let (mut fds, mut handles) = tables();
+        let tty = fds.alloc(FdKind::Tty { tty: 2 }, flags::O_RDWR).unwrap();
+        let pair = channel::create(&mut handles).unwrap();
+
+        let file = to_handle(&fds, &mut handles, tty).unwrap();
+        assert!(fds.get(tty).is_some());
+        channel::write(&mut handles, pair.handle0, b"tty", &[file]).unwrap();
+
+        // Moved to another process's tables, it becomes an fd again
+        let (mut rfds, mut rhandles) = tables();
+        let msg = channel::read(&mut handles, pair.handle1, 16, 1).ok().unwrap();
+        let value = rhandles.insert(handles.remove(msg.handles[0]).unwrap()).unwrap();
+        let fd = from_handle(&mut rhandles, &mut rfds, value, flags::O_CLOEXEC).unwrap();
+        assert_eq!(fd, 3);
+        let desc = rfds.get(fd).unwrap();
+        assert_eq!(desc.kind, FdKind::Tty { tty: 2 });
+        assert_eq!(desc.flags, flags::O_RDWR | flags::O_CLOEXEC);
+        assert!(rhandles.is_empty());
+    }
+
+    #[test]
+    fn test_rights_narrow_access() {
+        let (mut fds, mut handles) = tables();
+        let tty = fds.alloc(FdKind::Tty { tty: 1 }, flags::O_RDWR).unwrap();
+        let file = to_handle(&fds, &mut handles, tty).unwrap();
+        let read_only = handles.duplicate(file, Rights::READ).unwrap();
+        let neither = handles.duplicate(file, Rights::TRANSFER).unwrap();
+
+        let fd = from_handle(&mut handles, &mut fds, read_only, 0).unwrap();
+        assert_eq!(fds.get(fd).unwrap().flags, flags::O_RDONLY);
+        assert_eq!(from_handle(&mut handles, &mut fds, neither, 0), Err(RxStatus::ERR_ACCESS_DENIED));
+        assert!(handles.get(neither, Rights::NONE).is_ok());
+    }
+
+    #[test]
+    fn test_errors_keep_handle() {
+        let (mut fds, mut handles) = tables();
+        assert_eq!(to_handle(&fds, &mut handles, 9), Err(RxStatus::ERR_INVALID_ARGS));
+
+        let pair = channel::create(&mut handles).unwrap();
+        assert_eq!(from_handle(&mut handles, &mut fds, pair.handle0, 0), Err(RxStatus::ERR_INVALID_ARGS));
+
+        let file = to_handle(&fds, &mut handles, 1).unwrap();
+        assert_eq!(
+            from_handle(&mut handles, &mut fds, file, flags::O_NONBLOCK),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+        while fds.alloc(FdKind::Tty { tty: 1 }, flags::O_RDWR).is_some() {}
+        assert_eq!(from_handle(&mut handles, &mut fds, file, 0), Err(RxStatus::ERR_NO_MEMORY));
+        assert_eq!(handles.len(), 3);
+    }
+}
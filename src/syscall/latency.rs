@@ -0,0 +1,187 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Syscall Latency
+//!
+//! [`syscall_dispatch`](super::syscall_dispatch) times every handler on
+//! the monotonic clock and records the duration here: a log2 histogram
+//! per syscall number, plus the total and the worst single call.
+//! Counters are kept per CPU, like lock statistics, so the fast path
+//! never bounces a shared line between CPUs; [`stats`] sums them.
+//!
+//! The time is wall time from entering the handler to leaving it, so a
+//! call that blocks (`OBJECT_WAIT_ONE`, `CHANNEL_READ` on an empty
+//! channel) counts the time it slept. Calls that never return, like
+//! `PROCESS_EXIT`, are not recorded.
+//!
+//! # Histogram
+//!
+//! Bucket 0 counts calls under 2ns; bucket `i` counts calls of
+//! `2^i ..= 2^(i+1) - 1` ns. The last bucket also takes everything
+//! longer.
+//!
+//! # Slow Calls
+//!
+//! `syscall.slow_us=N` on the command line logs every call that takes
+//! at least `N` microseconds, with the calling PID and the arguments, at
+//! warning level. Off by default.
+//!
+//! # Reporting
+//!
+//! `/proc/syscalls` lists the syscalls made at least once (see
+//! [`write_report`]).
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::interrupt::affinity::{self, MAX_CPUS};
+use crate::kwarn;
+use super::{number, SyscallStats};
+
+/// Number of syscall slots (every syscall number up to `MAX_SYSCALL`)
+pub const NUM_SLOTS: usize = number::MAX_SYSCALL as usize + 1;
+
+/// Number of histogram buckets (the last one covers 2^31ns, ~2s, and up)
+pub const NUM_BUCKETS: usize = 32;
+
+/// Command line option: log calls slower than this many microseconds
+pub const SLOW_OPTION: &str = "syscall.slow_us";
+
+/// Slow-call threshold in nanoseconds (0: don't log)
+static SLOW_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Counters of one syscall on one CPU
+struct SlotCounters {
+    buckets: [AtomicU64; NUM_BUCKETS],
+    total_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+impl SlotCounters {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; NUM_BUCKETS],
+            total_ns: AtomicU64::new(0),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Counters of every syscall on one CPU
+struct CpuCounters {
+    slots: [SlotCounters; NUM_SLOTS],
+}
+
+static CPUS: [CpuCounters; MAX_CPUS] = [const { CpuCounters { slots: [const { SlotCounters::new() }; NUM_SLOTS] } }; MAX_CPUS];
+
+/// Histogram bucket of a duration
+pub const fn bucket(ns: u64) -> usize {
+    if ns < 2 {
+        return 0;
+    }
+    let log2 = (63 - ns.leading_zeros()) as usize;
+    if log2 < NUM_BUCKETS { log2 } else { NUM_BUCKETS - 1 }
+}
+
+/// Shortest duration counted in a bucket
+pub const fn bucket_floor_ns(bucket: usize) -> u64 {
+    if bucket == 0 { 0 } else { 1 << bucket }
+}
+
+/// Apply the `syscall.slow_us` command line option
+pub fn init() {
+    let mut buf = [0u8; 16];
+    if let Some(us) = crate::cmdline::get(SLOW_OPTION, &mut buf).and_then(|s| s.parse::<u64>().ok()) {
+        SLOW_NS.store(us.saturating_mul(1_000), Ordering::Relaxed);
+    }
+}
+
+/// Record one call of syscall `num` that took `ns` nanoseconds
+///
+/// `args` are the call's arguments, for the slow-call log.
+pub fn record(num: u32, ns: u64, args: &[usize; 6]) {
+    if let Some(slot) = CPUS[affinity::current_cpu()].slots.get(num as usize) {
+        slot.buckets[bucket(ns)].fetch_add(1, Ordering::Relaxed);
+        slot.total_ns.fetch_add(ns, Ordering::Relaxed);
+        slot.max_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+
+    let slow = SLOW_NS.load(Ordering::Relaxed);
+    if slow != 0 && ns >= slow {
+        let pid = crate::process::table::with_current_process(|p| p.pid).unwrap_or(0);
+        kwarn!(
+            "[SYSCALL] slow: pid={} num={:#x} {}us args={:#x} {:#x} {:#x} {:#x} {:#x} {:#x}",
+            pid, num, ns / 1_000, args[0], args[1], args[2], args[3], args[4], args[5]
+        );
+    }
+}
+
+/// Summed histogram of syscall `num`, or `None` if out of range
+pub fn histogram(num: u32) -> Option<[u64; NUM_BUCKETS]> {
+    let num = num as usize;
+    if num >= NUM_SLOTS {
+        return None;
+    }
+    let mut sum = [0u64; NUM_BUCKETS];
+    for cpu in CPUS.iter() {
+        for (total, count) in sum.iter_mut().zip(cpu.slots[num].buckets.iter()) {
+            *total += count.load(Ordering::Relaxed);
+        }
+    }
+    Some(sum)
+}
+
+/// Summed statistics of syscall `num`, or `None` if out of range
+pub fn stats(num: u32) -> Option<SyscallStats> {
+    let count = histogram(num)?.iter().sum();
+    let mut stats = SyscallStats { count, ..SyscallStats::new() };
+    for cpu in CPUS.iter() {
+        let slot = &cpu.slots[num as usize];
+        stats.total_time += slot.total_ns.load(Ordering::Relaxed);
+        stats.max_time = stats.max_time.max(slot.max_ns.load(Ordering::Relaxed));
+    }
+    Some(stats)
+}
+
+/// Write the `/proc/syscalls` report
+///
+/// One line per syscall made at least once: number, calls, total, mean
+/// and max time in nanoseconds, then `floor:count` for each non-empty
+/// histogram bucket.
+pub fn write_report(out: &mut impl Write) -> core::fmt::Result {
+    writeln!(out, "{:<6} {:>10} {:>14} {:>10} {:>12}  histogram (ns:calls)", "num", "calls", "total_ns", "mean_ns", "max_ns")?;
+    for num in 0..NUM_SLOTS as u32 {
+        let (Some(s), Some(hist)) = (stats(num), histogram(num)) else { continue };
+        if s.count == 0 {
+            continue;
+        }
+        write!(out, "{:<#6x} {:>10} {:>14} {:>10} {:>12} ", num, s.count, s.total_time, s.total_time / s.count, s.max_time)?;
+        for (i, &calls) in hist.iter().enumerate().filter(|(_, calls)| **calls > 0) {
+            write!(out, " {}:{}", bucket_floor_ns(i), calls)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(1), 0);
+        assert_eq!(bucket(2), 1);
+        assert_eq!(bucket(3), 1);
+        assert_eq!(bucket(1023), 9);
+        assert_eq!(bucket(1024), 10);
+        assert_eq!(bucket(u64::MAX), NUM_BUCKETS - 1);
+        for i in 1..NUM_BUCKETS {
+            assert_eq!(bucket(bucket_floor_ns(i)), i);
+            assert_eq!(bucket(bucket_floor_ns(i) - 1), i - 1);
+        }
+    }
+}
@@ -30,10 +30,29 @@
 //! Success: return value in r0/rax/a0 (positive or zero)
 //! Failure: return negative error code
 //! ```
+//!
+//! Handlers that return more than one value write an output struct to
+//! user memory; see [`SyscallResult`] and the [`number`] module.
+//!
+//! # Versioning
+//!
+//! The ABI grows by adding syscalls. Userspace finds out which ones the
+//! running kernel has with `SYSTEM_GET_FEATURES` (see [`features`]).
 
+pub mod channel;
 pub mod fd;
-
+pub mod features;
+pub mod file;
+pub mod latency;
+pub mod pipe;
+pub mod uaccess;
+pub mod vmo;
+
+use alloc::sync::Arc;
 use crate::arch::amd64::mm::RxStatus;
+use crate::object::{ObjectHandle, ObjectKind, Rights};
+use uaccess::{UserPtr, UserSlice};
+use crate::{kdebug, kinfo, kwarn};
 
 // ============================================================================
 // Common Syscall Types
@@ -149,14 +168,16 @@ impl X86SyscallGeneralRegs {
 }
 
 /// Syscall statistics (for debugging/monitoring)
+///
+/// Summed over all CPUs by [`latency::stats`].
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct SyscallStats {
     /// Number of times this syscall was called
     pub count: u64,
-    /// Total time spent in this syscall (TSC ticks)
+    /// Total time spent in this syscall (nanoseconds)
     pub total_time: u64,
-    /// Maximum time spent in a single call (TSC ticks)
+    /// Maximum time spent in a single call (nanoseconds)
     pub max_time: u64,
 }
 
@@ -171,23 +192,9 @@ impl SyscallStats {
     }
 }
 
-/// Per-syscall statistics
-static mut SYSCALL_STATS: [SyscallStats; 1000] = [SyscallStats::new(); 1000];
-
-/// Record a syscall invocation
-fn record_syscall(num: u32) {
-    unsafe {
-        SYSCALL_STATS[num as usize].count += 1;
-    }
-}
-
 /// Get syscall statistics for a syscall
-pub unsafe fn get_syscall_stats(syscall_num: u32) -> Option<&'static SyscallStats> {
-    if (syscall_num as usize) < SYSCALL_STATS.len() {
-        Some(&SYSCALL_STATS[syscall_num as usize])
-    } else {
-        None
-    }
+pub fn get_syscall_stats(syscall_num: u32) -> Option<SyscallStats> {
+    latency::stats(syscall_num)
 }
 
 // Syscall numbers (Stable v1)
@@ -244,6 +251,16 @@ impl SyscallArgs {
     pub const fn arg_i64(&self, index: usize) -> i64 {
         self.arg(index) as i64
     }
+
+    /// Get argument as a userspace pointer
+    pub const fn user_ptr<T: Copy>(&self, index: usize) -> UserPtr<T> {
+        UserPtr::new(self.arg(index))
+    }
+
+    /// Get a userspace buffer from a pointer and a length argument
+    pub const fn user_slice(&self, ptr_index: usize, len_index: usize) -> UserSlice {
+        UserSlice::new(self.arg(ptr_index), self.arg(len_index))
+    }
 }
 
 /// Convert error code to negative return value
@@ -264,6 +281,168 @@ pub const fn ok_to_ret_isize(val: isize) -> SyscallRet {
     val
 }
 
+// ============================================================================
+// Syscall Results
+// ============================================================================
+
+/// Result of a syscall handler
+///
+/// Handlers build a `SyscallResult` and convert it to the raw register
+/// value with [`SyscallResult::into_ret`]. Single values are returned in
+/// the return register. Multiple values are written to a user-provided
+/// output struct (see [`HandlePair`], [`FdPair`]) and the register
+/// returns 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallResult {
+    /// Success with a value in the return register
+    Value(usize),
+    /// Success, outputs were written to user memory (returns 0)
+    Written,
+    /// Failure with an error code (returned negated)
+    Error(RxStatus),
+}
+
+impl SyscallResult {
+    /// Success with no value
+    pub const OK: Self = SyscallResult::Value(0);
+
+    /// Write a multi-value output struct to user memory
+    ///
+    /// # Arguments
+    ///
+    /// * `out_ptr` - Userspace pointer to the output struct
+    /// * `value` - Output struct to write
+    pub fn out<T: Copy>(out_ptr: UserPtr<T>, value: &T) -> Self {
+        match out_ptr.write(value) {
+            Ok(()) => SyscallResult::Written,
+            Err(e) => SyscallResult::Error(e),
+        }
+    }
+
+    /// Pack the result into the raw return register value
+    ///
+    /// Values that do not fit in a non-negative `isize` are reported as
+    /// `ERR_INTERNAL` so they cannot be mistaken for an error code.
+    pub const fn into_ret(self) -> SyscallRet {
+        match self {
+            SyscallResult::Value(v) if v > isize::MAX as usize => err_to_ret(RxStatus::ERR_INTERNAL),
+            SyscallResult::Value(v) => ok_to_ret(v),
+            SyscallResult::Written => 0,
+            SyscallResult::Error(e) => err_to_ret(e),
+        }
+    }
+
+    /// Check whether the result is a success
+    pub const fn is_ok(&self) -> bool {
+        !matches!(self, SyscallResult::Error(_))
+    }
+}
+
+impl From<Result<usize, RxStatus>> for SyscallResult {
+    fn from(result: Result<usize, RxStatus>) -> Self {
+        match result {
+            Ok(v) => SyscallResult::Value(v),
+            Err(e) => SyscallResult::Error(e),
+        }
+    }
+}
+
+impl From<SyscallResult> for SyscallRet {
+    fn from(result: SyscallResult) -> Self {
+        result.into_ret()
+    }
+}
+
+/// Output struct for syscalls that create two handles
+///
+/// Used by `CHANNEL_CREATE`, `EVENTPAIR_CREATE` and `RINGBUF_CREATE`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandlePair {
+    /// First endpoint
+    pub handle0: u32,
+    /// Second endpoint
+    pub handle1: u32,
+}
+
+/// Output struct for `CHANNEL_READ`
+///
+/// Sizes of the message that was read, or of the pending message when
+/// the buffers were too small.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelActual {
+    /// Message bytes
+    pub bytes: u32,
+    /// Handles carried by the message
+    pub handles: u32,
+}
+
+/// `OBJECT_SIGNAL` bit for an event's signaled state
+///
+/// Also the signal `OBJECT_WAIT_ONE` waits for, and the one a semaphore
+/// releases a unit on.
+pub const EVENT_SIGNALED: u32 = crate::object::OBJECT_SIGNALED;
+
+/// `OBJECT_SET_PROPERTY` property: the object's debug name
+pub const PROP_NAME: u32 = 3;
+
+/// `OBJECT_SET_PROPERTY` property: a job's
+/// [`ResourceLimits`](crate::object::ResourceLimits)
+pub const PROP_JOB_LIMITS: u32 = 4;
+
+/// `OBJECT_GET_INFO` topic: [`HandleBasicInfo`]
+pub const INFO_HANDLE_BASIC: u32 = 2;
+
+/// Most items `OBJECT_WAIT_MANY` waits on
+pub const WAIT_MANY_MAX_ITEMS: usize = 16;
+
+/// `OBJECT_WAIT_ASYNC` option: unbind after the first packet
+pub const WAIT_ASYNC_ONCE: u32 = 0;
+
+/// `OBJECT_WAIT_ASYNC` option: queue a packet every time the object is
+/// signaled, until canceled
+pub const WAIT_ASYNC_REPEATING: u32 = 1;
+
+/// `OBJECT_WAIT_ASYNC` option: remove the binding and its queued packet
+pub const WAIT_ASYNC_CANCEL: u32 = 2;
+
+/// One object of an `OBJECT_WAIT_MANY`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WaitItem {
+    /// Handle to wait on (needs WAIT)
+    pub handle: u32,
+    /// Signals to wait for (`EVENT_SIGNALED`)
+    pub signals: u32,
+    /// Written by the kernel: the signals the wait took from the object
+    pub observed: u32,
+}
+
+/// Output struct for `OBJECT_GET_INFO` with [`INFO_HANDLE_BASIC`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandleBasicInfo {
+    /// Object type (`ObjectType` value)
+    pub object_type: u32,
+    /// Rights held through this handle
+    pub rights: u32,
+    /// Object name, NUL-padded (all zero if unnamed)
+    pub name: [u8; crate::object::MAX_NAME_LEN],
+}
+
+/// Output struct for syscalls that create two file descriptors
+///
+/// Used by `PIPE`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FdPair {
+    /// Read end
+    pub read_fd: i32,
+    /// Write end
+    pub write_fd: i32,
+}
+
 /// ============================================================================
 /// Syscall Dispatcher
 /// ============================================================================
@@ -288,11 +467,14 @@ pub const fn ok_to_ret_isize(val: isize) -> SyscallRet {
 pub extern "C" fn syscall_dispatch(args: SyscallArgs) -> SyscallRet {
     let num = args.number;
 
+    crate::kcounters::record_syscall(num);
+    let start = crate::time::clocksource::monotonic_ns();
+
     // Dispatch to handler based on syscall number
     // For now, most syscalls return NOT_IMPLEMENTED
     // We'll implement them incrementally as needed
 
-    match num {
+    let ret = match num {
         // Process & Thread (0x01-0x0F)
         0x01 => sys_process_create(args),
         0x02 => sys_process_start(args),
@@ -301,6 +483,9 @@ pub extern "C" fn syscall_dispatch(args: SyscallArgs) -> SyscallRet {
         0x05 => sys_thread_exit(args),
         0x06 => sys_process_exit(args),
         0x07 => sys_handle_close(args),
+        0x08 => sys_fork(args),
+        0x09 => sys_wait_pid(args),
+        0x0A => sys_spawn_fds(args),
 
         // Memory / VMO (0x10-0x1F)
         0x10 => sys_vmo_create(args),
@@ -310,6 +495,7 @@ pub extern "C" fn syscall_dispatch(args: SyscallArgs) -> SyscallRet {
         0x14 => sys_vmar_map(args),
         0x15 => sys_vmar_unmap(args),
         0x16 => sys_vmar_protect(args),
+        0x17 => sys_vmo_create_from_fd(args),
 
         // IPC & Sync (0x20-0x2F)
         0x20 => sys_channel_create(args),
@@ -320,20 +506,36 @@ pub extern "C" fn syscall_dispatch(args: SyscallArgs) -> SyscallRet {
         0x25 => sys_object_signal(args),
         0x26 => sys_object_wait_one(args),
         0x27 => sys_object_wait_many(args),
+        0x28 => sys_channel_writev(args),
+        0x29 => sys_channel_readv(args),
+        0x2A => sys_semaphore_create(args),
+        0x2B => sys_ringbuf_create(args),
+        0x2C => sys_futex(args),
+        0x2D => sys_port_create(args),
+        0x2E => sys_port_wait(args),
+        0x2F => sys_object_wait_async(args),
 
         // Jobs & Handles (0x30-0x3F)
         0x30 => sys_job_create(args),
         0x31 => sys_handle_duplicate(args),
         0x32 => sys_handle_transfer(args),
+        0x33 => sys_object_set_property(args),
+        0x34 => sys_object_get_info(args),
+        0x35 => sys_job_kill(args),
 
         // Time (0x40-0x4F)
         0x40 => sys_clock_get(args),
         0x41 => sys_timer_create(args),
         0x42 => sys_timer_set(args),
         0x43 => sys_timer_cancel(args),
+        0x44 => sys_nanosleep(args),
 
         // Debug (0x50-0x5F)
         0x50 => sys_debug_write(args),
+        0x51 => sys_kcounters_map(args),
+        0x52 => sys_kobject_stats(args),
+        0x53 => sys_pt_dump(args),
+        0x54 => sys_debug_read(args),
 
         // I/O (0x60-0x6F) - Phase 5A
         0x60 => sys_write(args),
@@ -341,17 +543,68 @@ pub extern "C" fn syscall_dispatch(args: SyscallArgs) -> SyscallRet {
         0x62 => sys_open(args),
         0x63 => sys_close(args),
         0x64 => sys_lseek(args),
+        0x65 => sys_clipboard_get(args),
+        0x66 => sys_clipboard_set(args),
+        0x67 => sys_writev(args),
+        0x68 => sys_readv(args),
+        0x69 => sys_tty_set_buffering(args),
+        0x6A => sys_fcntl(args),
+        0x6B => sys_unlink(args),
+        0x6C => sys_mkdir(args),
+        0x6D => sys_rmdir(args),
+        0x6E => sys_ftruncate(args),
+        0x6F => sys_readdir(args),
 
         // Process Info (0x70-0x7F) - Phase 5A
         0x70 => sys_getpid(args),
         0x71 => sys_getppid(args),
         0x72 => sys_yield(args),
+        0x73 => sys_sched_deadline(args),
+        0x74 => sys_process_suspend(args),
+        0x75 => sys_process_resume(args),
+        0x76 => sys_power(args),
+
+        // I/O, continued (0x80-0x8F)
+        0x80 => sys_stat(args),
+        0x81 => sys_fstat(args),
+        0x82 => sys_pipe(args),
+        0x83 => sys_dup(args),
+        0x84 => sys_dup2(args),
+        0x85 => sys_chdir(args),
+        0x86 => sys_getcwd(args),
+        0x87 => sys_symlink(args),
+        0x88 => sys_readlink(args),
+        0x89 => sys_link(args),
+        0x8A => sys_lstat(args),
+        0x8B => sys_fd_to_handle(args),
+        0x8C => sys_handle_to_fd(args),
+        0x8D => sys_vt_control(args),
+        0x8E => sys_mount(args),
+        0x8F => sys_umount(args),
+
+        // System (0x90-0x9F)
+        0x90 => sys_system_get_features(args),
+        0x91 => sys_ioport_create(args),
+        0x92 => sys_ioport_enable(args),
 
         _ => {
             // Unknown syscall
             err_to_ret(RxStatus::ERR_NOT_SUPPORTED)
         }
-    }
+    };
+
+    let end = crate::time::clocksource::monotonic_ns();
+    latency::record(num, end.saturating_sub(start), &args.args);
+
+    // Returning to userspace: stop here if the process was suspended
+    crate::sched::suspend::checkpoint();
+    // Or exit, if the system is shutting down
+    crate::shutdown::checkpoint();
+    // Or end this thread, if another one exited the process
+    crate::process::thread::checkpoint();
+    // The boot CPU has no idle loop, so it scans and reclaims from here
+    crate::mm::periodic();
+    ret
 }
 
 /// ============================================================================
@@ -371,8 +624,34 @@ macro_rules! syscall_stub {
 
 // Process & Thread syscalls
 syscall_stub!(sys_process_start);
-syscall_stub!(sys_thread_start);
-syscall_stub!(sys_thread_exit);
+
+/// Start a thread in the calling process
+///
+/// Arguments:
+///   arg0: entry point (userspace address)
+///   arg1: top of the thread's user stack, or 0 to have the kernel map one
+///   arg2: argument, passed to the thread in rdi
+///
+/// Returns: thread ID, or negative error code
+///
+/// The thread shares the process's address space, descriptors and
+/// handles, and may run on another CPU right away; see
+/// [`crate::process::thread`].
+fn sys_thread_start(args: SyscallArgs) -> SyscallRet {
+    let result = crate::process::thread::start(args.arg(0) as u64, args.arg(1) as u64, args.arg(2) as u64);
+    SyscallResult::from(result.map(|tid| tid as usize)).into_ret()
+}
+
+/// End the calling thread
+///
+/// Arguments:
+///   arg0: exit code
+///
+/// Does not return. Called from the process's first thread, exits the
+/// whole process like `PROCESS_EXIT`.
+fn sys_thread_exit(args: SyscallArgs) -> SyscallRet {
+    crate::process::thread::exit_current(args.arg_i64(0) as i32)
+}
 
 /// Process create syscall (Phase 5B)
 ///
@@ -386,171 +665,122 @@ syscall_stub!(sys_thread_exit);
 ///   Positive: new process PID
 ///   Negative: error code
 ///
+/// The new process joins the caller's job.
+///
 /// Note: In Phase 5C, this will be replaced by sys_spawn that takes
 /// a path string and looks up the file in the embedded filesystem.
 fn sys_process_create(args: SyscallArgs) -> SyscallRet {
-    use crate::exec::load_elf_process;
-    use crate::process::table::{Process, PROCESS_TABLE};
-    use crate::mm::pmm;
-    use crate::sync::SpinMutex;
+    use crate::process::table::PROCESS_TABLE;
+
+    /// Largest ELF image accepted from userspace
+    const MAX_ELF_SIZE: usize = 16 * 1024 * 1024;
 
-    let elf_ptr = args.arg_u64(0) as *const u8;
-    let elf_size = args.arg(1);
+    let elf = args.user_slice(0, 1);
 
     // Validate arguments
-    if elf_ptr.is_null() || elf_size == 0 {
+    if elf.addr() == 0 || elf.is_empty() || elf.len() > MAX_ELF_SIZE {
         return err_to_ret(RxStatus::ERR_INVALID_ARGS);
     }
 
-    // Get parent PID
-    let parent_pid = {
-        let table = PROCESS_TABLE.lock();
-        table.current_pid().unwrap_or(0)
+    // Read ELF data from userspace
+    let elf_data = match elf.read_to_vec() {
+        Ok(data) => data,
+        Err(e) => return err_to_ret(e),
     };
 
-    // Read ELF data from userspace
-    let elf_data = unsafe {
-        core::slice::from_raw_parts(elf_ptr, elf_size)
+    let job = crate::process::jobs::current();
+    let pid = match create_process(&elf_data, &crate::exec::ExecArgs::default(), job, |_| {}) {
+        Ok(pid) => pid,
+        Err(e) => return err_to_ret(e),
     };
+    PROCESS_TABLE.lock().set_current(pid);
+
+    ok_to_ret(pid as usize)
+}
+
+/// Load an ELF image into a new process in `job` and add it to the table
+///
+/// Shared by the spawn syscalls. The job admits the process before
+/// anything is allocated, so a job at its process limit costs nothing; a
+/// failure after that gives everything back (see
+/// [`discard_spawn`](crate::process::table::discard_spawn)). `setup`
+/// fills in what the caller passes on (name, descriptors, ...) before the
+/// process is visible to the scheduler. The process starts in the
+/// caller's working directory.
+///
+/// # Returns
+///
+/// The new PID. Fails with `ERR_INVALID_ARGS` if the image does not load,
+/// and as [`jobs::admit`](crate::process::jobs::admit) does.
+pub(crate) fn create_process(
+    elf_data: &[u8],
+    exec_args: &crate::exec::ExecArgs,
+    job: crate::object::JobId,
+    setup: impl FnOnce(&mut crate::process::table::Process),
+) -> Result<u32, RxStatus> {
+    use crate::exec::load_elf_process;
+    use crate::process::jobs;
+    use crate::process::table::{alloc_kernel_stack, discard_spawn, Process, PROCESS_TABLE};
+
+    jobs::admit(job)?;
 
     // Load the ELF binary
-    let process_image = match load_elf_process(elf_data) {
+    let process_image = match load_elf_process(elf_data, exec_args) {
         Ok(img) => img,
         Err(e) => {
-            // Debug output for error
-            let msg = b"[SPAWN] Failed to load ELF: ";
-            for &b in msg {
-                unsafe {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-            }
-            for b in e.as_bytes() {
-                unsafe {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") *b, options(nomem, nostack));
-                }
-            }
-            let msg = b"\n";
-            for &b in msg {
-                unsafe {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-            }
-            return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+            kwarn!("[SPAWN] Failed to load ELF: {}", e);
+            jobs::leave(job);
+            return Err(RxStatus::ERR_INVALID_ARGS);
         }
     };
-
-    // Allocate a kernel stack (4 pages)
-    let kernel_stack_paddrs = [
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-    ];
-
-    // Get the kernel stack virtual addresses
-    let kernel_stack_vaddrs = [
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[0]),
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[1]),
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[2]),
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[3]),
-    ];
-
-    // Stack grows down, so top is at the highest address
-    let kernel_stack_top = (kernel_stack_vaddrs[3] + 4096) as u64;
-
-    // Get page table physical address
     let page_table_phys = process_image.address_space.page_table.phys;
 
-    // Allocate PID and create process
-    let (pid, entry, user_stack_top) = {
-        let mut table = PROCESS_TABLE.lock();
-
-        let pid = match table.alloc_pid() {
-            Some(p) => p,
-            None => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        };
-
-        let process = Process::new(
-            pid,
-            parent_pid,
-            page_table_phys,
-            kernel_stack_top,
-            process_image.stack_top,
-            process_image.entry,
-        );
-
-        table.insert(process);
-        table.set_current(pid);
-
-        (pid, process_image.entry, process_image.stack_top)
-    };
-
-    // Debug output
-    unsafe {
-        let msg = b"[SPAWN] Created process PID=";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-        let mut n = pid;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b" entry=0x";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-        let mut n = entry;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        if n == 0 {
-            buf[i] = b'0';
-            i += 1;
-        } else {
-            while n > 0 {
-                let digit = (n & 0xF) as u8;
-                buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                n >>= 4;
-                i += 1;
-            }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b"\n";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
+    // The image's pages count against the job's memory limit
+    let kernel_stack_top = match process_image.vmar.charge_to(job).and_then(|_| alloc_kernel_stack()) {
+        Ok(top) => top,
+        Err(e) => {
+            discard_spawn(job, process_image.vmar, page_table_phys, 0);
+            return Err(e);
         }
-    }
+    };
 
-    ok_to_ret(pid as usize)
+    // Allocate PID and create process
+    let mut table = PROCESS_TABLE.lock();
+    let pid = match table.alloc_pid() {
+        Some(pid) => pid,
+        None => {
+            drop(table);
+            discard_spawn(job, process_image.vmar, page_table_phys, kernel_stack_top);
+            return Err(RxStatus::ERR_NO_MEMORY);
+        }
+    };
+    let (parent_pid, cwd) = table.spawn_parent();
+
+    let mut process = Process::new(
+        pid,
+        parent_pid,
+        page_table_phys,
+        kernel_stack_top,
+        process_image.stack_top,
+        process_image.entry,
+    );
+    crate::process::vmar::install(page_table_phys, process_image.vmar);
+    process.job_id = job;
+    process.cwd = cwd;
+    setup(&mut process);
+    table.insert(process);
+    drop(table);
+
+    kinfo!("[SPAWN] Created process PID={} entry={:#x}", pid, process_image.entry);
+    Ok(pid)
 }
 
 /// Spawn a process from a file in the ramdisk
 ///
 /// Arguments:
 ///   arg0: pointer to path string (null-terminated, userspace)
+///   arg1: pointer to a NULL-terminated `argv` array, or 0
+///   arg2: pointer to a NULL-terminated `envp` array, or 0
 ///
 /// Returns: new process PID, or negative error code
 ///
@@ -558,41 +788,122 @@ fn sys_process_create(args: SyscallArgs) -> SyscallRet {
 /// The path must be a null-terminated string in userspace memory.
 /// This is simpler than sys_process_create because userspace doesn't
 /// need to know the ELF format - just provides the path.
+///
+/// The child starts with a copy of the caller's fd table (less the
+/// descriptors with `O_CLOEXEC`), so a shell
+/// redirects a child's output by pointing its own fd 1 elsewhere (`DUP2`)
+/// around the call. Use `SPAWN_FDS` to pass only some descriptors, or to
+/// start the child in another job; `SPAWN` children join the caller's.
+///
+/// The strings are copied onto the child's stack with an auxiliary
+/// vector (see [`crate::exec::initial_stack`]). A null `argv` starts the
+/// child with just the path as `argv[0]`.
 fn sys_spawn(args: SyscallArgs) -> SyscallRet {
-    use crate::exec::load_elf_process;
-    use crate::fs::ramdisk;
-    use crate::process::table::{Process, PROCESS_TABLE};
-    use crate::mm::pmm;
+    let exec_args = match read_exec_args(args.user_ptr::<u8>(0), args.user_ptr::<usize>(1), args.user_ptr::<usize>(2)) {
+        Ok(exec_args) => exec_args,
+        Err(e) => return err_to_ret(e),
+    };
+    let fds = match crate::process::table::with_current_process_mut(|p| p.fd_table.inherit()) {
+        Some(fds) => fds,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    spawn_from_ramdisk(args.user_ptr::<u8>(0), &exec_args, fds, crate::process::jobs::current())
+}
 
-    let path_ptr = args.arg_u64(0) as *const u8;
+/// Most descriptors `SPAWN_FDS` passes
+pub const SPAWN_MAX_FDS: usize = 16;
 
-    // Validate path pointer
-    if path_ptr.is_null() {
+/// Spawn a process from a file in the ramdisk, choosing its descriptors
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///   arg1: pointer to an array of `i32` parent fds
+///   arg2: number of entries (at most [`SPAWN_MAX_FDS`])
+///   arg3: pointer to a NULL-terminated `argv` array, or 0
+///   arg4: pointer to a NULL-terminated `envp` array, or 0
+///   arg5: job handle (needs MANAGE), or 0 for the caller's job
+///
+/// Returns: new process PID, or negative error code
+///
+/// Child fd `i` is a copy of parent fd `arg1[i]`; a negative entry, and
+/// every fd past the end of the array, starts closed. Fails with
+/// `ERR_INVALID_ARGS` (EBADF) if an entry names an fd that is not open.
+/// Arguments and environment are passed as for `SPAWN`.
+fn sys_spawn_fds(args: SyscallArgs) -> SyscallRet {
+    let count = args.arg(2);
+    if count > SPAWN_MAX_FDS {
         return err_to_ret(RxStatus::ERR_INVALID_ARGS);
     }
-
-    // Read null-terminated path string from userspace (max 256 bytes)
-    let mut path_bytes = alloc::vec::Vec::new();
-    unsafe {
-        let mut i = 0;
-        loop {
-            if i >= 256 {
-                return err_to_ret(RxStatus::ERR_INVALID_ARGS); // Path too long
-            }
-            let c = *path_ptr.add(i);
-            if c == 0 {
-                break;
-            }
-            path_bytes.push(c);
-            i += 1;
+    let array = args.user_ptr::<i32>(1);
+    let mut map = [-1i32; SPAWN_MAX_FDS];
+    for (i, entry) in map[..count].iter_mut().enumerate() {
+        match UserPtr::<i32>::new(array.addr() + i * core::mem::size_of::<i32>()).read() {
+            Ok(fd) => *entry = fd,
+            Err(e) => return err_to_ret(e),
         }
     }
 
-    // Convert to string
-    let path = match core::str::from_utf8(&path_bytes) {
-        Ok(s) => s,
-        Err(_) => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    let fds = match crate::process::table::with_current_process_mut(|p| p.fd_table.select(&map[..count])) {
+        Some(Some(fds)) => fds,
+        Some(None) | None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    let exec_args = match read_exec_args(args.user_ptr::<u8>(0), args.user_ptr::<usize>(3), args.user_ptr::<usize>(4)) {
+        Ok(exec_args) => exec_args,
+        Err(e) => return err_to_ret(e),
+    };
+    let job = match job_arg(args.arg_u32(5)) {
+        Ok(job) => job,
+        Err(e) => return err_to_ret(e),
+    };
+    spawn_from_ramdisk(args.user_ptr::<u8>(0), &exec_args, fds, job)
+}
+
+/// Copy in the `argv` and `envp` arrays of a spawn
+///
+/// A null `argv` becomes just the path.
+fn read_exec_args(
+    path_ptr: UserPtr<u8>,
+    argv_ptr: UserPtr<usize>,
+    envp_ptr: UserPtr<usize>,
+) -> Result<crate::exec::ExecArgs, RxStatus> {
+    use crate::exec::initial_stack::{ARG_MAX, MAX_ARGS};
+
+    let argv = if argv_ptr.is_null() {
+        alloc::vec![path_ptr.read_str(256)?]
+    } else {
+        argv_ptr.read_str_array(MAX_ARGS, ARG_MAX)?
+    };
+    let envp = envp_ptr.read_str_array(MAX_ARGS, ARG_MAX)?;
+    crate::exec::ExecArgs::new(argv, envp).map_err(|_| RxStatus::ERR_INVALID_ARGS)
+}
+
+/// Load `path` from the ramdisk and start it in `job` with `exec_args`
+/// and the descriptors `fds`
+///
+/// Fails with `ERR_NO_MEMORY` if the job, or one above it, is at its
+/// process limit or has no memory left for the image, and with
+/// `ERR_ACCESS_DENIED` if it has been killed.
+fn spawn_from_ramdisk(
+    path_ptr: UserPtr<u8>,
+    exec_args: &crate::exec::ExecArgs,
+    fds: crate::syscall::fd::FileDescriptorTable,
+    job: crate::object::JobId,
+) -> SyscallRet {
+    use crate::fs::ramdisk;
+
+    // Read the path, relative to the caller's current directory
+    let path = match read_user_path(path_ptr) {
+        Ok(path) => path,
+        Err(e) => return err_to_ret(e),
     };
+    let path = path.as_str();
+
+    // Nothing runs from a noexec mount (programs on other mounts are not
+    // found: only the ramdisk holds programs for now)
+    let noexec = crate::fs::mount::flags_at(path).is_some_and(|f| f & crate::fs::mount::MS_NOEXEC != 0);
+    if noexec {
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED);
+    }
 
     // Get the ramdisk
     let ramdisk = match ramdisk::get_ramdisk() {
@@ -600,26 +911,18 @@ fn sys_spawn(args: SyscallArgs) -> SyscallRet {
         Err(_) => return err_to_ret(RxStatus::ERR_NOT_FOUND),
     };
 
+    // Refuse to execute from an unverified ramdisk
+    if !crate::fs::verify::spawn_allowed() {
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED);
+    }
+
     // Look up file in ramdisk
     let ramdisk_file = match ramdisk.find_file(path) {
         Some(f) => f,
         None => return err_to_ret(RxStatus::ERR_NOT_FOUND), // ENOENT
     };
 
-    // Debug output
-    unsafe {
-        let msg = b"[SPAWN] Loading process from ramdisk: ";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-        for b in path_bytes.iter() {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") *b, options(nomem, nostack));
-        }
-        let msg = b"\n";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-    }
+    kinfo!("[SPAWN] Loading process from ramdisk: {}", path);
 
     // Read the ELF data from ramdisk
     let elf_data_ptr = unsafe {
@@ -629,433 +932,1783 @@ fn sys_spawn(args: SyscallArgs) -> SyscallRet {
         core::slice::from_raw_parts(elf_data_ptr, ramdisk_file.size as usize)
     };
 
-    // Load the ELF binary
-    let process_image = match load_elf_process(elf_data) {
-        Ok(img) => img,
-        Err(e) => {
-            // Debug output for error
-            unsafe {
-                let msg = b"[SPAWN] Failed to load ELF: ";
-                for &b in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-                for b in e.as_bytes() {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") *b, options(nomem, nostack));
-                }
-                let msg = b"\n";
-                for &b in msg {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-            }
-            return err_to_ret(RxStatus::ERR_INVALID_ARGS);
-        }
-    };
-
-    // Get parent PID
-    let parent_pid = {
-        let table = PROCESS_TABLE.lock();
-        table.current_pid().unwrap_or(0)
+    // Set process name from path
+    let name = if let Some(last_slash) = path.rfind('/') {
+        alloc::string::String::from(&path[last_slash + 1..])
+    } else {
+        alloc::string::String::from(path)
     };
 
-    // Allocate a kernel stack (4 pages)
-    let kernel_stack_paddrs = [
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-        match pmm::pmm_alloc_kernel_page() {
-            Ok(p) => p,
-            Err(_) => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        },
-    ];
-
-    // Get the kernel stack virtual addresses
-    let kernel_stack_vaddrs = [
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[0]),
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[1]),
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[2]),
-        pmm::paddr_to_vaddr(kernel_stack_paddrs[3]),
-    ];
-
-    // Stack grows down, so top is at the highest address
-    let kernel_stack_top = (kernel_stack_vaddrs[3] + 4096) as u64;
-
-    // Get page table physical address
-    let page_table_phys = process_image.address_space.page_table.phys;
-
-    // Allocate PID and create process
-    let (pid, entry, user_stack_top) = {
-        let mut table = PROCESS_TABLE.lock();
-
-        let pid = match table.alloc_pid() {
-            Some(p) => p,
-            None => return err_to_ret(RxStatus::ERR_NO_MEMORY),
-        };
-
-        let mut process = Process::new(
-            pid,
-            parent_pid,
-            page_table_phys,
-            kernel_stack_top,
-            process_image.stack_top,
-            process_image.entry,
-        );
-
-        // Set process name from path
-        let name = if let Some(last_slash) = path.rfind('/') {
-            alloc::string::String::from(&path[last_slash + 1..])
-        } else {
-            alloc::string::String::from(path)
-        };
+    let pid = match create_process(elf_data, exec_args, job, |process| {
         process.set_name(name);
+        process.fd_table = fds;
+    }) {
+        Ok(pid) => pid,
+        Err(e) => return err_to_ret(e),
+    };
 
-        table.insert(process);
+    crate::sched::idle::kick();
+    ok_to_ret(pid as usize)
+}
 
-        (pid, process_image.entry, process_image.stack_top)
+/// Duplicate the calling process
+///
+/// Arguments: none
+///
+/// Returns: the child's PID in the parent, 0 in the child, or negative
+/// error code
+///
+/// The child starts where the call returns, with the caller's stack and
+/// callee-saved registers. It gets copy-on-write copies of the caller's
+/// mappings, copies of its fd and handle tables, and its job, privilege
+/// and name. Scheduling class, suspend requests, debug registers and CPU
+/// time start afresh. Fails with `ERR_NO_MEMORY` if the job is at its
+/// process limit.
+fn sys_fork(_args: SyscallArgs) -> SyscallRet {
+    use crate::process::jobs;
+    use crate::process::table::{alloc_kernel_stack, discard_spawn, Process, SavedState, PROCESS_TABLE};
+    use crate::process::{vmar, AddressSpace};
+
+    let frame = unsafe { crate::arch::amd64::entry::current_syscall_frame() };
+
+    // Cloning VMOs locks every VMAR, so take a snapshot first
+    let (parent_pid, job, parent_vmar) = {
+        let table = PROCESS_TABLE.lock();
+        match table.current().and_then(|parent| Some((parent.pid, parent.job_id, vmar::of(parent.page_table)?))) {
+            Some(found) => found,
+            None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+        }
     };
+    let regions: alloc::vec::Vec<_> = parent_vmar.lock().regions().cloned().collect();
 
-    // Debug output
-    unsafe {
-        let msg = b"[SPAWN] Created process PID=";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-        let mut n = pid;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        loop {
-            buf[i] = b'0' + (n % 10) as u8;
-            n /= 10;
-            i += 1;
-            if n == 0 { break; }
-        }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
-        }
-        let msg = b" entry=0x";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
+    // Admit first, so a job at its process limit allocates nothing; past
+    // this point every failure goes through discard_spawn
+    if let Err(e) = jobs::admit(job) {
+        return err_to_ret(e);
+    }
+
+    let page_table = match AddressSpace::new() {
+        Ok(aspace) => aspace.page_table.phys,
+        Err(_) => {
+            jobs::leave(job);
+            return err_to_ret(RxStatus::ERR_NO_MEMORY);
         }
-        let mut n = entry;
-        let mut buf = [0u8; 16];
-        let mut i = 0;
-        if n == 0 {
-            buf[i] = b'0';
-            i += 1;
-        } else {
-            while n > 0 {
-                let digit = (n & 0xF) as u8;
-                buf[i] = if digit < 10 { b'0' + digit } else { b'a' + digit - 10 };
-                n >>= 4;
-                i += 1;
-            }
+    };
+    let child_vmar = match vmar::fork(&regions, page_table) {
+        Ok(v) => v,
+        Err(e) => {
+            discard_spawn(job, vmar::Vmar::new(), page_table, 0);
+            return err_to_ret(e);
         }
-        while i > 0 {
-            i -= 1;
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") buf[i], options(nomem, nostack));
+    };
+    // The clones; shared read-only VMOs stay charged to their first job
+    let kernel_stack_top = match child_vmar.charge_to(job).and_then(|_| alloc_kernel_stack()) {
+        Ok(top) => top,
+        Err(e) => {
+            discard_spawn(job, child_vmar, page_table, 0);
+            return err_to_ret(e);
         }
-        let msg = b"\n";
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
+    };
+
+    let mut table = PROCESS_TABLE.lock();
+    let found = match table.alloc_pid() {
+        Some(pid) => table.get(parent_pid).map(|parent| (pid, parent)).ok_or(RxStatus::ERR_NOT_FOUND),
+        None => Err(RxStatus::ERR_NO_MEMORY),
+    };
+    let (pid, parent) = match found {
+        Ok(found) => found,
+        Err(e) => {
+            drop(table);
+            discard_spawn(job, child_vmar, page_table, kernel_stack_top);
+            return err_to_ret(e);
         }
-    }
+    };
 
+    let mut child = Process::new(pid, parent_pid, page_table, kernel_stack_top, parent.user_stack, frame.rip);
+    child.saved_state = SavedState::for_fork(&frame, page_table);
+    child.fd_table = parent.fd_table.clone();
+    child.handles = parent.handles.fork();
+    vmar::install(page_table, child_vmar);
+    child.job_id = parent.job_id;
+    child.privileged = parent.privileged;
+    child.io_ports = parent.io_ports;
+    child.name = parent.name.clone();
+    child.cwd = parent.cwd.clone();
+    child.tty_output = crate::drivers::tty::OutputBuffer::new(parent.tty_output.mode());
+
+    table.insert(child);
+    drop(table);
+    crate::sched::idle::kick();
     ok_to_ret(pid as usize)
 }
 
 /// Process exit syscall
 ///
-/// Terminates the current process. For now, this just halts the CPU.
-/// In a full implementation, this would mark the process as exited
-/// and schedule another process.
+/// Arguments:
+///   arg0: exit code
+///
+/// Does not return. The process becomes a zombie holding the exit code
+/// until its parent collects it with `WAIT_PID`; see
+/// [`exit_current`](crate::process::table::exit_current).
 fn sys_process_exit(args: SyscallArgs) -> SyscallRet {
     let exit_code = args.arg_i64(0) as i32;
-    let _ = exit_code; // TODO: track exit code
 
-    // PROOF: sys_exit called - fill framebuffer YELLOW
-    // We need to access the framebuffer from the library side
-    // For now, we'll use a different approach - write to port 0xE9 to signal exit
-    unsafe {
-        let msg = b"[EXIT]"; // Signal that sys_exit was called
-        for &b in msg {
-            core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-        }
-    }
-
-    // Halt forever - process has exited
-    loop {
-        unsafe { core::arch::asm!("hlt") };
-    }
-}
+    kdebug!("[EXIT] code={}", exit_code);
 
-fn sys_handle_close(args: SyscallArgs) -> SyscallRet {
-    let handle = args.arg_u32(0);
-    // TODO: Implement handle close
-    let _ = handle;
-    ok_to_ret(0)
+    crate::process::table::exit_current(exit_code)
 }
 
-// Memory / VMO syscalls
-syscall_stub!(sys_vmo_create);
-syscall_stub!(sys_vmo_read);
-syscall_stub!(sys_vmo_write);
-syscall_stub!(sys_vmo_clone);
-syscall_stub!(sys_vmar_map);
-syscall_stub!(sys_vmar_unmap);
-syscall_stub!(sys_vmar_protect);
+/// Wait for a child process to exit and reap it
+///
+/// Arguments:
+///   arg0: child PID, or -1 for any child
+///   arg1: pointer to i32 receiving the exit code (may be null)
+///   arg2: options (`WAIT_NOHANG`)
+///
+/// Returns: the reaped child's PID, 0 if `WAIT_NOHANG` is set and no
+/// matching child has exited yet, or negative error code
+///
+/// Blocks until a matching child exits. Reaping frees the child's kernel
+/// stack, page tables and memory. Fails with `ERR_NOT_FOUND` if the
+/// caller has no matching child.
+fn sys_wait_pid(args: SyscallArgs) -> SyscallRet {
+    use crate::process::table::{self as ptable, PROCESS_TABLE};
+
+    /// Return 0 instead of blocking
+    const WAIT_NOHANG: u32 = 1;
+
+    let pid = match args.arg_i64(0) {
+        -1 => None,
+        pid if pid > 0 && pid <= u32::MAX as i64 => Some(pid as u32),
+        _ => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    let code_ptr = args.user_ptr::<i32>(1);
+    let options = args.arg_u32(2);
+    if options & !WAIT_NOHANG != 0 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
 
-// IPC & Sync syscalls
-syscall_stub!(sys_channel_create);
-syscall_stub!(sys_channel_write);
-syscall_stub!(sys_channel_read);
-syscall_stub!(sys_event_create);
-syscall_stub!(sys_eventpair_create);
-syscall_stub!(sys_object_signal);
-syscall_stub!(sys_object_wait_one);
-syscall_stub!(sys_object_wait_many);
+    let (child, code) = loop {
+        let found = {
+            let table = PROCESS_TABLE.lock();
+            match table.current_pid() {
+                Some(parent) => table.find_zombie_child(parent, pid),
+                None => Err(RxStatus::ERR_NOT_FOUND),
+            }
+        };
+        match found {
+            Ok(Some(child)) => {
+                if let Some(code) = ptable::reap(child) {
+                    break (child, code);
+                }
+            }
+            Ok(None) if options & WAIT_NOHANG != 0 => return ok_to_ret(0),
+            Ok(None) => {}
+            Err(e) => return err_to_ret(e),
+        }
 
-// Jobs & Handles syscalls
-syscall_stub!(sys_job_create);
-syscall_stub!(sys_handle_duplicate);
-syscall_stub!(sys_handle_transfer);
+        // Yield to other processes while waiting
+        let _ = crate::sched::round_robin::yield_cpu();
+    };
 
-// Time syscalls
-fn sys_clock_get(args: SyscallArgs) -> SyscallRet {
-    let _clock_id = args.arg_u32(0);
-    // Return current time in nanoseconds (placeholder)
-    // Use the TSC for now
-    use crate::arch::amd64::tsc;
-    let time_ns = tsc::tsc_to_ns(unsafe { tsc::rdtsc() });
-    ok_to_ret_isize(time_ns as isize)
+    if !code_ptr.is_null() {
+        if let Err(e) = code_ptr.write(&code) {
+            return err_to_ret(e);
+        }
+    }
+    ok_to_ret(child as usize)
 }
 
-syscall_stub!(sys_timer_create);
-syscall_stub!(sys_timer_set);
-syscall_stub!(sys_timer_cancel);
-
-// Debug syscalls
-/// Debug write syscall - writes a string to the debug console
+/// Close a handle
 ///
 /// Arguments:
-///   arg0: pointer to string (userspace virtual address)
-///   arg1: length of string
+///   arg0: handle value (`HANDLE_INVALID` is accepted and ignored)
 ///
-/// Returns: number of bytes written, or negative error code
-fn sys_debug_write(args: SyscallArgs) -> SyscallRet {
-    use crate::arch::amd64::uspace;
-    let ptr = args.arg_u64(0) as *const u8;
-    let len = args.arg(1);
+/// Returns: 0, or negative error code
+///
+/// The object is destroyed once its last handle is closed.
+fn sys_handle_close(args: SyscallArgs) -> SyscallRet {
+    let handle = args.arg_u32(0);
+    if handle == crate::process::handles::HANDLE_INVALID {
+        return ok_to_ret(0);
+    }
+    // Drop the handle after the process table lock is released
+    let result = with_handles(|handles| handles.remove(handle));
+    SyscallResult::from(result.map(|_| 0)).into_ret()
+}
 
-    // For now, just write to port 0xE9 (kernel-mediated)
-    // In the future, this could go to a proper logging system
-    unsafe {
-        for i in 0..len {
-            let c = *(ptr.add(i));
-            // Write to debug console
-            core::arch::asm!("out dx, al",
-                in("dx") 0xE9u16,
-                in("al") c,
-                options(nomem, nostack)
-            );
+/// Run `f` on the current process's handle table
+fn with_handles<R>(
+    f: impl FnOnce(&mut crate::process::handles::ProcessHandles) -> Result<R, RxStatus>,
+) -> Result<R, RxStatus> {
+    // Captured before locking; see ProcessHandles::set_origin
+    #[cfg(feature = "handle_tracking")]
+    let origin = crate::arch::amd64::backtrace::capture();
+
+    let mut table = crate::process::table::PROCESS_TABLE.lock();
+    match table.current_mut() {
+        Some(current) => {
+            #[cfg(feature = "handle_tracking")]
+            current.handles.set_origin(Some(origin));
+            let result = f(&mut current.handles);
+            #[cfg(feature = "handle_tracking")]
+            current.handles.set_origin(None);
+            result
         }
+        None => Err(RxStatus::ERR_INVALID_ARGS),
     }
+}
 
-    ok_to_ret_isize(len as isize)
+/// Resolve a handle of the current process to a `T`
+///
+/// Checks `rights` and the object type (`ERR_INVALID_ARGS` on a
+/// mismatch). The returned reference keeps the object alive after the
+/// process table lock is released, even if the handle is closed.
+fn lookup<T: ObjectKind>(handle: u32, rights: Rights) -> Result<Arc<T>, RxStatus> {
+    with_handles(|handles| handles.object::<T>(handle, rights))
 }
 
-// ============================================================================
-// I/O Syscalls (Phase 5A)
-// ============================================================================
+/// Install a new object in the current process's handle table
+///
+/// Returns the new handle value (with the type's default rights), or
+/// negative error code.
+fn create_handle<T: ObjectKind>(object: T) -> SyscallRet {
+    let handle = ObjectHandle::with_default_rights(object);
+    let result = with_handles(|handles| handles.insert(handle));
+    SyscallResult::from(result.map(|value| value as usize)).into_ret()
+}
 
-/// Write to file descriptor
+// Memory / VMO syscalls
+
+/// Create a VMO
 ///
 /// Arguments:
-///   arg0: file descriptor (fd)
-///   arg1: pointer to buffer
-///   arg2: length to write
+///   arg0: size in bytes (rounded up to whole pages)
+///   arg1: options (`VmoFlags::RESIZABLE` or 0)
 ///
-/// Returns: number of bytes written, or negative error code
+/// Returns: handle to the new VMO, or negative error code
 ///
-/// File descriptor mapping:
-///   fd 0: stdin (write not allowed)
-///   fd 1: stdout (kernel debug console, port 0xE9)
-///   fd 2: stderr (same as stdout)
-///   fd 3+: reserved for files (Phase 5C)
-fn sys_write(args: SyscallArgs) -> SyscallRet {
-    let fd = args.arg(0) as u8;
-    let ptr = args.arg_u64(1) as *const u8;
-    let len = args.arg(2);
-
-    use crate::drivers::display;
-
-    // Handle stdout/stderr via display console
-    if fd == 1 || fd == 2 {
-        // Check if display console is initialized
-        if display::is_initialized() {
-            // Write to framebuffer console
-            for i in 0..len {
-                let c = unsafe { *(ptr.add(i)) };
-                display::put_char(c);
-            }
-        } else {
-            // Fallback to debug port if console not initialized
-            unsafe {
-                for i in 0..len {
-                    let c = *(ptr.add(i));
-                    core::arch::asm!("out dx, al",
-                        in("dx") 0xE9u16,
-                        in("al") c,
-                        options(nomem, nostack)
-                    );
-                }
-            }
-        }
-        return ok_to_ret_isize(len as isize);
-    }
+/// The VMO's pages are charged to the caller's job as they are committed.
+fn sys_vmo_create(args: SyscallArgs) -> SyscallRet {
+    use crate::object::{Vmo, VmoFlags};
 
-    // stdin - cannot write
-    if fd == 0 {
+    let size = args.arg(0);
+    let options = args.arg_u32(1);
+    if size > isize::MAX as usize || options & !VmoFlags::RESIZABLE.into_raw() != 0 {
         return err_to_ret(RxStatus::ERR_INVALID_ARGS);
     }
+    match Vmo::create(size, VmoFlags::from_raw(options)) {
+        Ok(vmo) => {
+            // Nothing is committed yet, so there is nothing to refuse
+            let _ = vmo.set_job(crate::process::jobs::current());
+            create_handle(vmo)
+        }
+        Err(_) => err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    }
+}
 
-    // For other file descriptors (fd 3+), return not implemented for now
-    // Future: Write to ramdisk files
-    ok_to_ret_isize(len as isize)
+/// Look up a VMO handle of the current process and run `f` on it
+///
+/// `f` runs without the process table lock held, so it may copy to and
+/// from userspace.
+fn with_vmo<R>(
+    handle: u32,
+    rights: Rights,
+    f: impl FnOnce(&crate::object::Vmo) -> Result<R, RxStatus>,
+) -> Result<R, RxStatus> {
+    let vmo = lookup::<crate::object::Vmo>(handle, rights)?;
+    f(&vmo)
 }
 
-/// Read from file descriptor
+/// Read from a VMO
 ///
 /// Arguments:
-///   arg0: file descriptor (fd)
-///   arg1: pointer to buffer
-///   arg2: length to read
+///   arg0: VMO handle (needs READ)
+///   arg1: pointer to destination buffer
+///   arg2: buffer length
+///   arg3: byte offset within the VMO
 ///
 /// Returns: number of bytes read, or negative error code
 ///
-/// Read from a file descriptor
+/// Short reads happen at the end of the VMO and when the buffer faults
+/// part-way through; see [`vmo`] for the exact rules.
+fn sys_vmo_read(args: SyscallArgs) -> SyscallRet {
+    let buf = args.user_slice(1, 2);
+    let offset = args.arg(3);
+    let result = with_vmo(args.arg_u32(0), crate::object::Rights::READ, |v| {
+        vmo::read_to_user(v, offset, buf)
+    });
+    SyscallResult::from(result).into_ret()
+}
+
+/// Write to a VMO
 ///
-/// For stdin (fd 0): Blocks waiting for keyboard input, returns one character at a time
-/// For files: Reads from ramdisk files
-/// For stdout/stderr: Returns error (not readable)
-fn sys_read(args: SyscallArgs) -> SyscallRet {
-    use crate::syscall::fd::{FdKind, FileDescriptor};
-    use crate::process::table::PROCESS_TABLE;
+/// Arguments:
+///   arg0: VMO handle (needs WRITE)
+///   arg1: pointer to source buffer
+///   arg2: buffer length
+///   arg3: byte offset within the VMO
+///
+/// Returns: number of bytes written, or negative error code
+///
+/// Writes are clamped to the VMO size; a fault on the buffer ends the
+/// write after the bytes already copied. See [`vmo`].
+fn sys_vmo_write(args: SyscallArgs) -> SyscallRet {
+    let buf = args.user_slice(1, 2);
+    let offset = args.arg(3);
+    let result = with_vmo(args.arg_u32(0), crate::object::Rights::WRITE, |v| {
+        vmo::write_from_user(v, offset, buf)
+    });
+    SyscallResult::from(result).into_ret()
+}
 
-    let fd = args.arg(0) as u8;
-    let ptr = args.arg_u64(1) as *mut u8;
-    let len = args.arg(2);
+syscall_stub!(sys_vmo_clone);
 
-    // Get the current process
-    let file_info = {
-        let mut table = PROCESS_TABLE.lock();
-        let current = match table.current_mut() {
+/// Map a VMO into the current process
+///
+/// Arguments:
+///   arg0: VMO handle (needs MAP, plus the rights matching `arg3`)
+///   arg1: page-aligned address (0 = kernel picks, not supported yet)
+///   arg2: size in bytes (at most the VMO size)
+///   arg3: protection (`vmo::PROT_READ | PROT_WRITE | PROT_EXEC`)
+///   arg4: flags (must be 0)
+///
+/// Returns: the mapped address, or negative error code
+///
+/// Uncommitted pages are demand-paged; see [`vmo`].
+fn sys_vmar_map(args: SyscallArgs) -> SyscallRet {
+    let vaddr = args.arg(1);
+    let size = args.arg(2);
+    if args.arg(4) != 0 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    if vaddr == 0 {
+        return err_to_ret(RxStatus::ERR_NOT_SUPPORTED);
+    }
+    let (rights, flags) = match vmo::map_rights(args.arg_u32(3)) {
+        Ok(r) => r,
+        Err(e) => return err_to_ret(e),
+    };
+    let vmo = match lookup::<crate::object::Vmo>(args.arg_u32(0), rights) {
+        Ok(vmo) => vmo,
+        Err(e) => return err_to_ret(e),
+    };
+    let page_table = match crate::process::table::PROCESS_TABLE.lock().current() {
+        Some(process) => process.page_table,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    let vmar = match crate::process::vmar::of(page_table) {
+        Some(vmar) => vmar,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    let result = vmo::map_into(page_table, &mut vmar.lock(), vmo, vaddr, size, flags);
+    SyscallResult::from(result.map(|_| vaddr)).into_ret()
+}
+
+syscall_stub!(sys_vmar_unmap);
+syscall_stub!(sys_vmar_protect);
+
+/// Get a file-backed VMO for an open ramdisk file
+///
+/// Arguments:
+///   arg0: file descriptor
+///
+/// Returns: handle to the file's VMO, or negative error code
+///
+/// Every process gets the same VMO for the same file; see
+/// [`crate::fs::filemap`]. The handle has `READ | MAP | DUPLICATE |
+/// TRANSFER`, so the file can be mapped read-only or read-execute but
+/// never written.
+fn sys_vmo_create_from_fd(args: SyscallArgs) -> SyscallRet {
+    use crate::syscall::fd::FdKind;
+
+    let fd = args.arg(0);
+    if fd > u8::MAX as usize {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let inode = {
+        let table = crate::process::table::PROCESS_TABLE.lock();
+        let current = match table.current() {
             Some(p) => p,
             None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
         };
+        match current.fd_table.get(fd as u8).map(|d| d.kind) {
+            Some(FdKind::File { inode, .. }) => inode,
+            Some(_) => return err_to_ret(RxStatus::ERR_NOT_SUPPORTED),
+            None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+        }
+    };
+    let vmo = match crate::fs::filemap::file_vmo(inode) {
+        Ok(vmo) => vmo,
+        Err(e) => return err_to_ret(e),
+    };
 
-        // Get the file descriptor
-        let file_desc = match current.fd_table.get(fd) {
-            Some(f) => f,
-            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
-        };
+    let rights = Rights::READ | Rights::MAP | Rights::DUPLICATE | Rights::TRANSFER;
+    let handle = ObjectHandle::new(crate::object::KernelObject::Vmo(vmo), rights);
+    let result = with_handles(|handles| handles.insert(handle));
+    SyscallResult::from(result.map(|value| value as usize)).into_ret()
+}
 
-        match file_desc.kind {
-            FdKind::Stdin => {
-                // stdin (fd 0) - Read from keyboard driver
-                // Block until character available
-                if len == 0 {
-                    return ok_to_ret_isize(0);
-                }
+// IPC & Sync syscalls
 
-                // Release process table lock before blocking
-                drop(current);
-                drop(table);
+/// Create a channel
+///
+/// Arguments:
+///   arg0: options (must be 0)
+///   arg1: pointer to a [`HandlePair`] receiving both endpoints
+///
+/// Returns: 0, or negative error code
+fn sys_channel_create(args: SyscallArgs) -> SyscallRet {
+    if args.arg(0) != 0 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let out = args.user_ptr::<HandlePair>(1);
+    match with_handles(channel::create) {
+        Ok(pair) => {
+            let result = SyscallResult::out(out, &pair);
+            if !result.is_ok() {
+                // Nobody learns the values; don't leak the endpoints
+                let _ = with_handles(|h| {
+                    let a = h.remove(pair.handle0);
+                    let b = h.remove(pair.handle1);
+                    Ok((a, b))
+                });
+            }
+            result.into_ret()
+        }
+        Err(e) => err_to_ret(e),
+    }
+}
 
-                // Block until character available from keyboard
-                let ch = loop {
-                    if let Some(ch) = crate::drivers::keyboard::read_char() {
-                        break ch;
-                    }
-                    // Yield to other processes while waiting
-                    let _ = crate::sched::round_robin::yield_cpu();
-                };
+/// Read an array of handle values from userspace
+fn read_handle_values(addr: usize, count: usize) -> Result<alloc::vec::Vec<u32>, RxStatus> {
+    if count > crate::object::MAX_MSG_HANDLES {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    if count == 0 {
+        return Ok(alloc::vec::Vec::new());
+    }
+    let bytes = UserSlice::new(addr, count * 4).read_to_vec()?;
+    Ok(bytes.chunks_exact(4).map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect())
+}
 
-                // Write the character to userspace buffer
-                unsafe {
-                    *ptr = ch as u8;
-                }
+/// Write to a channel
+///
+/// Arguments:
+///   arg0: channel handle (needs WRITE)
+///   arg1: pointer to message bytes
+///   arg2: message size (at most `MAX_MSG_SIZE`)
+///   arg3: pointer to handle values to transfer (each needs TRANSFER)
+///   arg4: handle count (at most `MAX_MSG_HANDLES`)
+///
+/// Returns: number of bytes written, or negative error code
+///
+/// Transferred handles are closed in the caller once the message is
+/// queued. See [`channel`] for the error codes.
+fn sys_channel_write(args: SyscallArgs) -> SyscallRet {
+    let data = args.user_slice(1, 2);
+    if data.len() > crate::object::MAX_MSG_SIZE {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let data = if data.is_empty() {
+        alloc::vec::Vec::new()
+    } else {
+        match data.read_to_vec() {
+            Ok(d) => d,
+            Err(e) => return err_to_ret(e),
+        }
+    };
+    channel_write_message(&args, &data)
+}
 
-                return ok_to_ret_isize(1); // Read one character
-            }
-            FdKind::File { inode, offset } => {
-                // Get the ramdisk file info
-                use crate::fs::ramdisk;
-                let ramdisk = match ramdisk::get_ramdisk() {
-                    Ok(r) => r,
-                    Err(_) => return err_to_ret(RxStatus::ERR_NOT_FOUND),
-                };
+/// Write to a channel from a list of buffers
+///
+/// Arguments:
+///   arg0: channel handle (needs WRITE)
+///   arg1: pointer to an array of [`uaccess::IoVec`]
+///   arg2: number of entries (at most `IOV_MAX`)
+///   arg3: pointer to handle values to transfer (each needs TRANSFER)
+///   arg4: handle count (at most `MAX_MSG_HANDLES`)
+///
+/// Returns: number of bytes written, or negative error code
+///
+/// The buffers are concatenated into one message (at most
+/// `MAX_MSG_SIZE` in total); otherwise as `CHANNEL_WRITE`.
+fn sys_channel_writev(args: SyscallArgs) -> SyscallRet {
+    let data = uaccess::UserIoVec::read(args.user_ptr(1), args.arg(2), crate::object::MAX_MSG_SIZE)
+        .and_then(|iov| iov.read_to_vec());
+    match data {
+        Ok(data) => channel_write_message(&args, &data),
+        Err(e) => err_to_ret(e),
+    }
+}
 
-                // Get file headers array
-                let files = unsafe {
-                    let base = ramdisk.data.as_ptr().add(ramdisk.superblock.files_offset as usize);
-                    let count = ramdisk.superblock.num_files as usize;
-                    core::slice::from_raw_parts(base as *const ramdisk::RamdiskFile, count)
-                };
+/// Queue `data` plus the handles in arg3/arg4 (`CHANNEL_WRITE[V]`)
+fn channel_write_message(args: &SyscallArgs, data: &[u8]) -> SyscallRet {
+    let transfer = match read_handle_values(args.arg(3), args.arg(4)) {
+        Ok(t) => t,
+        Err(e) => return err_to_ret(e),
+    };
 
-                // Find the file by inode (index)
-                let ramdisk_file = match files.get(inode as usize) {
-                    Some(&f) => f,
-                    None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
-                };
+    let result = with_handles(|h| channel::write(h, args.arg_u32(0), data, &transfer));
+    SyscallResult::from(result).into_ret()
+}
 
-                Some((ramdisk_file, offset, len, ptr))
-            }
-            _ => {
-                // Stdout/stderr not readable
-                return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+/// Read from a channel
+///
+/// Arguments:
+///   arg0: channel handle (needs READ)
+///   arg1: pointer to message buffer
+///   arg2: buffer size
+///   arg3: pointer to an array receiving handle values
+///   arg4: array capacity (in handles)
+///   arg5: pointer to a [`ChannelActual`] (optional, 0 to skip)
+///
+/// Returns: number of bytes read, or negative error code
+///
+/// A message that does not fit is left queued and the call fails with
+/// `ERR_BUFFER_TOO_SMALL`; its sizes are written to arg5. If copying the
+/// message out faults, its handles are taken back and it is requeued at
+/// the front of the channel.
+fn sys_channel_read(args: SyscallArgs) -> SyscallRet {
+    let buf = args.user_slice(1, 2);
+    channel_read_message(&args, buf.len(), |data| buf.truncate(data.len()).write(data))
+}
+
+/// Read from a channel into a list of buffers
+///
+/// Arguments:
+///   arg0: channel handle (needs READ)
+///   arg1: pointer to an array of [`uaccess::IoVec`]
+///   arg2: number of entries (at most `IOV_MAX`)
+///   arg3: pointer to an array receiving handle values
+///   arg4: array capacity (in handles)
+///   arg5: pointer to a [`ChannelActual`] (optional, 0 to skip)
+///
+/// Returns: number of bytes read, or negative error code
+///
+/// The message is scattered across the buffers in order; their total
+/// length is the buffer size. Otherwise as `CHANNEL_READ`.
+fn sys_channel_readv(args: SyscallArgs) -> SyscallRet {
+    let iov = match uaccess::UserIoVec::read(args.user_ptr(1), args.arg(2), usize::MAX) {
+        Ok(iov) => iov,
+        Err(e) => return err_to_ret(e),
+    };
+    channel_read_message(&args, iov.len(), |data| iov.write(data))
+}
+
+/// Dequeue a message of at most `capacity` bytes and copy it out with
+/// `write_data`; handles and sizes go to arg3-arg5 (`CHANNEL_READ[V]`)
+fn channel_read_message(
+    args: &SyscallArgs,
+    capacity: usize,
+    write_data: impl FnOnce(&[u8]) -> Result<usize, RxStatus>,
+) -> SyscallRet {
+    let handle = args.arg_u32(0);
+    let handle_cap = core::cmp::min(args.arg(4), crate::object::MAX_MSG_HANDLES);
+    let handle_buf = UserSlice::new(args.arg(3), handle_cap * 4);
+    let actual = args.user_ptr::<ChannelActual>(5);
+
+    let msg = match with_handles(|h| channel::read(h, handle, capacity, handle_cap)) {
+        Ok(msg) => msg,
+        Err(RxStatus::ERR_BUFFER_TOO_SMALL) => {
+            let pending = with_handles(|h| Ok(channel::pending(h, handle))).ok().flatten();
+            if let (Some(sizes), false) = (pending, actual.is_null()) {
+                let _ = actual.write(&sizes);
             }
+            return err_to_ret(RxStatus::ERR_BUFFER_TOO_SMALL);
         }
+        Err(e) => return err_to_ret(e),
     };
 
-    if let Some((ramdisk_file, offset, len, ptr)) = file_info {
-        use crate::fs::ramdisk;
-        let ramdisk = ramdisk::get_ramdisk().unwrap();
+    let copied = copy_out_message(&msg, write_data, handle_buf, actual);
+    if let Err(e) = copied {
+        let _ = with_handles(|h| {
+            channel::unread(h, msg);
+            Ok(())
+        });
+        return err_to_ret(e);
+    }
+    ok_to_ret(msg.data.len())
+}
 
-        // Calculate remaining bytes from current offset
-        let file_size = ramdisk_file.size as u64;
-        let remaining = if offset >= file_size {
-            0
-        } else {
-            file_size - offset
-        };
+/// Copy a read message's bytes, handle values and sizes to userspace
+fn copy_out_message(
+    msg: &channel::Received,
+    write_data: impl FnOnce(&[u8]) -> Result<usize, RxStatus>,
+    handle_buf: UserSlice,
+    actual: UserPtr<ChannelActual>,
+) -> Result<(), RxStatus> {
+    if !msg.data.is_empty() {
+        write_data(&msg.data)?;
+    }
+    if !msg.handles.is_empty() {
+        let values: alloc::vec::Vec<u8> = msg.handles.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        handle_buf.truncate(values.len()).write(&values)?;
+    }
+    if !actual.is_null() {
+        let sizes = ChannelActual { bytes: msg.data.len() as u32, handles: msg.handles.len() as u32 };
+        actual.write(&sizes)?;
+    }
+    Ok(())
+}
 
-        if remaining == 0 {
-            return ok_to_ret_isize(0); // EOF
-        }
+/// Create an event
+///
+/// Arguments:
+///   arg0: options (`EventFlags::MANUAL_RESET` or 0)
+///
+/// Returns: handle to the new (unsignaled) event, or negative error code
+fn sys_event_create(args: SyscallArgs) -> SyscallRet {
+    use crate::object::{Event, EventFlags};
 
-        let to_read = core::cmp::min(len as u64, remaining) as usize;
+    let options = args.arg_u32(0);
+    if options & !EventFlags::MANUAL_RESET.into_raw() != 0 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    create_handle(Event::new(false, EventFlags::from_raw(options)))
+}
 
-        // Read from the file at current offset
-        let data_offset = ramdisk_file.data_offset as usize + offset as usize;
-        let data_ptr = unsafe {
-            ramdisk.data.as_ptr().add(data_offset)
-        };
+syscall_stub!(sys_eventpair_create);
 
-        unsafe {
-            core::ptr::copy_nonoverlapping(data_ptr, ptr, to_read);
-        }
+/// Create a semaphore
+///
+/// Arguments:
+///   arg0: initial count
+///   arg1: maximum count (at least 1, and at least the initial count)
+///
+/// Returns: handle to the new semaphore, or negative error code
+fn sys_semaphore_create(args: SyscallArgs) -> SyscallRet {
+    match crate::object::Semaphore::new(args.arg_u64(0), args.arg_u64(1)) {
+        Ok(semaphore) => create_handle(semaphore),
+        Err(_) => err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    }
+}
 
-        // Update offset in fd_table
-        let mut table = PROCESS_TABLE.lock();
+/// Create a ring buffer fed by a kernel event source (privileged)
+///
+/// Arguments:
+///   arg0: source (`RingSource`: 1 = input, 2 = trace)
+///   arg1: capacity in records (a power of two)
+///   arg2: threshold: unread records at which the ring is signaled
+///   arg3: pointer to a [`HandlePair`] receiving the ring (`handle0`)
+///         and its VMO (`handle1`)
+///
+/// Returns: 0, or negative error code
+///
+/// Map the VMO read-write to read records and advance the header's
+/// `tail`; wait on the ring with `OBJECT_WAIT_ONE` for new records.
+fn sys_ringbuf_create(args: SyscallArgs) -> SyscallRet {
+    use crate::object::ringbuf::{self, RingBuffer, RingSource};
+    use crate::object::KernelObject;
+
+    let source = match RingSource::from_raw(args.arg_u32(0)) {
+        Some(source) => source,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    let privileged = crate::process::table::PROCESS_TABLE.lock().current().map(|p| p.privileged);
+    match privileged {
+        Some(true) => {}
+        Some(false) => return err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    }
+    let out = args.user_ptr::<HandlePair>(3);
+    let ring = match RingBuffer::create(source, args.arg_u32(1), args.arg_u32(2)) {
+        Ok(ring) => Arc::new(ring),
+        Err(_) => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    let vmo = ObjectHandle::new(
+        KernelObject::Vmo(ring.vmo().clone()),
+        Rights::default_for_type(crate::object::ObjectType::Vmo),
+    );
+    let handles = alloc::vec![
+        ObjectHandle::new(KernelObject::RingBuffer(ring.clone()), Rights::default_for_type(ring.base.obj_type)),
+        vmo,
+    ];
+    let pair = match with_handles(|h| h.insert_many(handles)) {
+        Ok(values) => HandlePair { handle0: values[0], handle1: values[1] },
+        Err(e) => return err_to_ret(e),
+    };
+    let result = SyscallResult::out(out, &pair);
+    if result.is_ok() {
+        ringbuf::attach(&ring);
+    } else {
+        // Nobody learns the values; don't leak the handles
+        let _ = with_handles(|h| {
+            let a = h.remove(pair.handle0);
+            let b = h.remove(pair.handle1);
+            Ok((a, b))
+        });
+    }
+    result.into_ret()
+}
+
+/// Sleep on or wake a futex (see [`crate::process::futex`])
+///
+/// Arguments:
+///   arg0: address of the futex word (4-byte aligned)
+///   arg1: operation (`FUTEX_WAIT` = 0, `FUTEX_WAKE` = 1)
+///   arg2: `FUTEX_WAIT`: value the word must hold to sleep;
+///         `FUTEX_WAKE`: most threads to wake (`u32::MAX` = all)
+///   arg3: `FUTEX_WAIT`: deadline in nanoseconds since boot
+///         (`u64::MAX` = none)
+///
+/// Returns: `FUTEX_WAIT`: 0 once woken, `ERR_SHOULD_WAIT` if the word
+/// did not hold the value, `ERR_BUSY` if the deadline passed;
+/// `FUTEX_WAKE`: the number of threads woken. Negative error code on
+/// failure.
+fn sys_futex(args: SyscallArgs) -> SyscallRet {
+    use crate::process::futex::{self, FUTEX_WAIT, FUTEX_WAKE};
+    use crate::time::Instant;
+
+    let result = match args.arg_u32(1) {
+        FUTEX_WAIT => futex::wait(args.user_ptr(0), args.arg_u32(2), Instant::from_nanos(args.arg_u64(3))).map(|()| 0),
+        FUTEX_WAKE => futex::wake(args.arg(0), args.arg_u32(2)),
+        _ => Err(RxStatus::ERR_INVALID_ARGS),
+    };
+    SyscallResult::from(result).into_ret()
+}
+
+/// Clear and set an object's signals
+///
+/// Arguments:
+///   arg0: event or semaphore handle (needs SIGNAL)
+///   arg1: signals to clear
+///   arg2: signals to set
+///
+/// Returns: 0, or negative error code
+///
+/// [`EVENT_SIGNALED`] is the only signal. On an event, clearing happens
+/// before setting. On a semaphore, setting it releases one unit (failing
+/// if the count is at its maximum), and it cannot be cleared.
+fn sys_object_signal(args: SyscallArgs) -> SyscallRet {
+    use crate::object::KernelObject;
+
+    let clear = args.arg_u32(1);
+    let set = args.arg_u32(2);
+    if (clear | set) & !EVENT_SIGNALED != 0 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let handle = args.arg_u32(0);
+    let object = match with_handles(|handles| handles.get(handle, Rights::SIGNAL).map(|h| h.object.clone())) {
+        Ok(object) => object,
+        Err(e) => return err_to_ret(e),
+    };
+    match object {
+        KernelObject::Event(event) => {
+            if clear & EVENT_SIGNALED != 0 {
+                event.unsignal();
+            }
+            if set & EVENT_SIGNALED != 0 {
+                event.signal();
+            }
+        }
+        KernelObject::Semaphore(semaphore) => {
+            if clear != 0 {
+                return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+            }
+            if set & EVENT_SIGNALED != 0 && semaphore.release(1).is_err() {
+                return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+            }
+        }
+        _ => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    }
+    ok_to_ret(0)
+}
+
+/// Wait for an object to be signaled
+///
+/// Arguments:
+///   arg0: event, semaphore or ring buffer handle (needs WAIT)
+///   arg1: signals to wait for (`EVENT_SIGNALED`)
+///   arg2: absolute deadline in nanoseconds (0 polls, `u64::MAX` waits forever)
+///
+/// Returns: 0 once signaled, or negative error code (`ERR_BUSY` if the
+/// deadline passed first)
+///
+/// The wait consumes what it waited for: an auto-reset event is
+/// unsignaled and a semaphore gives up one unit, so each signal wakes one
+/// waiter. A manual-reset event stays signaled. A ring buffer is
+/// signaled while at least its threshold of records is unread; waiting
+/// consumes nothing, the reader advances `tail` itself.
+fn sys_object_wait_one(args: SyscallArgs) -> SyscallRet {
+    use crate::time::Instant;
+
+    if args.arg_u32(1) != EVENT_SIGNALED {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let deadline = Instant::from_nanos(args.arg_u64(2));
+    let handle = args.arg_u32(0);
+    let object = match with_handles(|handles| handles.get(handle, Rights::WAIT).map(|h| h.object.clone())) {
+        Ok(object) => object,
+        Err(e) => return err_to_ret(e),
+    };
+    if !waitable(&object) {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+
+    loop {
+        if try_acquire(&object) {
+            return ok_to_ret(0);
+        }
+        if deadline.has_passed(Instant::now()) {
+            return err_to_ret(RxStatus::ERR_BUSY);
+        }
+        // Yield to the signaling process while waiting
+        let _ = crate::sched::round_robin::yield_cpu();
+    }
+}
+
+/// Check if `OBJECT_WAIT_ONE` and `OBJECT_WAIT_MANY` can wait on an object
+fn waitable(object: &crate::object::KernelObject) -> bool {
+    use crate::object::KernelObject;
+
+    matches!(
+        object,
+        KernelObject::Event(_) | KernelObject::Semaphore(_) | KernelObject::RingBuffer(_) | KernelObject::Timer(_)
+    )
+}
+
+/// Take an object's signal if it is signaled (see `OBJECT_WAIT_ONE`)
+///
+/// Objects that are not [`waitable`] are never signaled.
+fn try_acquire(object: &crate::object::KernelObject) -> bool {
+    use crate::object::KernelObject;
+
+    match object {
+        KernelObject::Event(event) => event.try_acquire(),
+        KernelObject::Semaphore(semaphore) => semaphore.try_acquire(),
+        KernelObject::RingBuffer(ring) => ring.is_signaled(),
+        KernelObject::Timer(timer) => timer.try_acquire(),
+        _ => false,
+    }
+}
+
+/// The signals an object has now, without taking any
+fn signals_of(object: &crate::object::KernelObject) -> u32 {
+    use crate::object::KernelObject;
+
+    let signaled = match object {
+        KernelObject::Event(event) => event.is_signaled(),
+        KernelObject::Semaphore(semaphore) => semaphore.is_signaled(),
+        KernelObject::RingBuffer(ring) => ring.is_signaled(),
+        KernelObject::Timer(timer) => timer.event.is_signaled(),
+        _ => false,
+    };
+    if signaled {
+        EVENT_SIGNALED
+    } else {
+        0
+    }
+}
+
+/// One pass of `OBJECT_WAIT_MANY` over its objects
+///
+/// Takes the signal of every object that is signaled and sets its
+/// item's `observed` to `EVENT_SIGNALED`; the other items get 0.
+///
+/// # Returns
+///
+/// The number of items signaled
+fn poll_items(objects: &[crate::object::KernelObject], items: &mut [WaitItem]) -> usize {
+    let mut signaled = 0;
+    for (object, item) in objects.iter().zip(items.iter_mut()) {
+        item.observed = if try_acquire(object) {
+            signaled += 1;
+            EVENT_SIGNALED
+        } else {
+            0
+        };
+    }
+    signaled
+}
+
+/// Wait for any of several objects to be signaled
+///
+/// Arguments:
+///   arg0: pointer to an array of [`WaitItem`]s
+///   arg1: number of items (1 to [`WAIT_MANY_MAX_ITEMS`])
+///   arg2: absolute deadline in nanoseconds (0 polls, `u64::MAX` waits forever)
+///
+/// Returns: the number of items signaled, or negative error code
+/// (`ERR_BUSY` if the deadline passed first)
+///
+/// Each item takes the objects and signals `OBJECT_WAIT_ONE` does, and
+/// every handle needs WAIT; one bad item fails the whole call before
+/// anything is waited on. On wake, every item whose object is signaled
+/// has its signal taken as `OBJECT_WAIT_ONE` would, so several can be
+/// consumed at once. Every item's `observed` is written back, also when
+/// the deadline passes: `EVENT_SIGNALED` for those taken, 0 for the rest.
+fn sys_object_wait_many(args: SyscallArgs) -> SyscallRet {
+    use crate::time::Instant;
+
+    let count = args.arg(1);
+    if count == 0 || count > WAIT_MANY_MAX_ITEMS {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let item_ptr = |i: usize| UserPtr::<WaitItem>::new(args.arg(0) + i * core::mem::size_of::<WaitItem>());
+    let mut items = [WaitItem::default(); WAIT_MANY_MAX_ITEMS];
+    let items = &mut items[..count];
+    for (i, item) in items.iter_mut().enumerate() {
+        match item_ptr(i).read() {
+            Ok(value) => *item = value,
+            Err(e) => return err_to_ret(e),
+        }
+    }
+    if items.iter().any(|item| item.signals != EVENT_SIGNALED) {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+
+    let objects = with_handles(|handles| {
+        items
+            .iter()
+            .map(|item| handles.get(item.handle, Rights::WAIT).map(|h| h.object.clone()))
+            .collect::<Result<alloc::vec::Vec<_>, _>>()
+    });
+    let objects = match objects {
+        Ok(objects) if objects.iter().all(waitable) => objects,
+        Ok(_) => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        Err(e) => return err_to_ret(e),
+    };
+
+    let deadline = Instant::from_nanos(args.arg_u64(2));
+    let result = loop {
+        let signaled = poll_items(&objects, items);
+        if signaled > 0 {
+            break Ok(signaled);
+        }
+        if deadline.has_passed(Instant::now()) {
+            break Err(RxStatus::ERR_BUSY);
+        }
+        // Yield to the signaling processes while waiting
+        let _ = crate::sched::round_robin::yield_cpu();
+    };
+
+    for (i, item) in items.iter().enumerate() {
+        if let Err(e) = item_ptr(i).write(item) {
+            return err_to_ret(e);
+        }
+    }
+    SyscallResult::from(result).into_ret()
+}
+
+/// Create a port (see [`crate::object::port`])
+///
+/// Arguments:
+///   arg0: options (must be 0)
+///
+/// Returns: handle to the new, empty port, or negative error code
+fn sys_port_create(args: SyscallArgs) -> SyscallRet {
+    if args.arg_u32(0) != 0 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    create_handle(crate::object::Port::create())
+}
+
+/// Take the oldest packet from a port
+///
+/// Arguments:
+///   arg0: port handle (needs READ)
+///   arg1: absolute deadline in nanoseconds (0 polls, `u64::MAX` waits forever)
+///   arg2: pointer to a [`PortPacket`](crate::object::PortPacket)
+///         receiving the packet
+///
+/// Returns: 0, or negative error code (`ERR_BUSY` if the deadline passed
+/// with the port empty)
+fn sys_port_wait(args: SyscallArgs) -> SyscallRet {
+    use crate::object::{Port, PortPacket};
+    use crate::time::Instant;
+
+    let out = args.user_ptr::<PortPacket>(2);
+    // Checked up front: a packet taken off the queue must not be lost
+    if let Err(e) = uaccess::validate_user_range(out.addr(), core::mem::size_of::<PortPacket>()) {
+        return err_to_ret(e);
+    }
+    let port = match lookup::<Port>(args.arg_u32(0), Rights::READ) {
+        Ok(port) => port,
+        Err(e) => return err_to_ret(e),
+    };
+
+    let deadline = Instant::from_nanos(args.arg_u64(1));
+    loop {
+        if let Some(packet) = port.try_dequeue() {
+            return SyscallResult::out(out, &packet).into_ret();
+        }
+        if deadline.has_passed(Instant::now()) {
+            return err_to_ret(RxStatus::ERR_BUSY);
+        }
+        // Yield to the signaling processes while waiting
+        let _ = crate::sched::round_robin::yield_cpu();
+    }
+}
+
+/// Bind an object's signals to a port
+///
+/// Arguments:
+///   arg0: event, semaphore, timer or ring buffer handle (needs WAIT)
+///   arg1: port handle (needs WRITE)
+///   arg2: key, returned in the packets of this binding
+///   arg3: signals to wait for (`EVENT_SIGNALED`; ignored when canceling)
+///   arg4: options ([`WAIT_ASYNC_ONCE`], [`WAIT_ASYNC_REPEATING`] or
+///         [`WAIT_ASYNC_CANCEL`])
+///
+/// Returns: 0, or negative error code (`ERR_NOT_FOUND` if there was
+/// nothing to cancel)
+///
+/// Each time the object is signaled the port gets a packet with the key;
+/// see [`crate::object::port`]. An object that is already signaled
+/// queues one at once. Binding the same port and key again replaces the
+/// binding.
+fn sys_object_wait_async(args: SyscallArgs) -> SyscallRet {
+    use crate::object::{Observer, Port};
+
+    let signals = args.arg_u32(3);
+    let options = args.arg_u32(4);
+    let valid = match options {
+        WAIT_ASYNC_ONCE | WAIT_ASYNC_REPEATING => signals == EVENT_SIGNALED,
+        WAIT_ASYNC_CANCEL => true,
+        _ => false,
+    };
+    if !valid {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let handles = with_handles(|handles| {
+        let object = handles.get(args.arg_u32(0), Rights::WAIT)?.object.clone();
+        let port = handles.object::<Port>(args.arg_u32(1), Rights::WRITE)?;
+        Ok((object, port))
+    });
+    let (object, port) = match handles {
+        Ok((object, _)) if !waitable(&object) => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        Ok(pair) => pair,
+        Err(e) => return err_to_ret(e),
+    };
+
+    let key = args.arg_u64(2);
+    let observers = object.base().observers();
+    if options == WAIT_ASYNC_CANCEL {
+        let unbound = observers.cancel(&port, key);
+        let dequeued = port.cancel(key);
+        return if unbound || dequeued { ok_to_ret(0) } else { err_to_ret(RxStatus::ERR_NOT_FOUND) };
+    }
+    let observer = Observer::new(&port, key, signals, options == WAIT_ASYNC_REPEATING);
+    observers.add(observer, || signals_of(&object));
+    ok_to_ret(0)
+}
+
+// Jobs & Handles syscalls
+
+/// Job a job handle argument names
+///
+/// 0 is the caller's own job; any other value must be a job handle with
+/// MANAGE.
+fn job_arg(handle: u32) -> Result<crate::object::JobId, RxStatus> {
+    match handle {
+        0 => Ok(crate::process::jobs::current()),
+        handle => lookup::<crate::object::Job>(handle, Rights::MANAGE).map(|job| job.id()),
+    }
+}
+
+/// Create a child job
+///
+/// Arguments:
+///   arg0: parent job handle (needs MANAGE), or 0 for the caller's job
+///   arg1: job policy flags
+///
+/// Returns: handle to the new job, or negative error code
+fn sys_job_create(args: SyscallArgs) -> SyscallRet {
+    use crate::object::Job;
+
+    let job = match args.arg_u32(0) {
+        0 => Job::new_child_of(crate::process::jobs::current(), args.arg_u32(1)),
+        handle => match lookup::<Job>(handle, Rights::MANAGE) {
+            Ok(parent) => Job::new_child(&parent, args.arg_u32(1)),
+            Err(e) => return err_to_ret(e),
+        },
+    };
+    match job {
+        Ok(job) => create_handle(job),
+        Err(_) => err_to_ret(RxStatus::ERR_INTERNAL),
+    }
+}
+
+/// Kill every process in a job and its child jobs
+///
+/// Arguments:
+///   arg0: job handle (needs MANAGE)
+///
+/// Returns: the number of processes killed, or negative error code
+///
+/// The processes exit with `EXIT_KILLED`, and the jobs admit no new
+/// processes. Does not return if the caller was in the job.
+fn sys_job_kill(args: SyscallArgs) -> SyscallRet {
+    use crate::process::table::{with_current_process, ProcessState};
+
+    let job = match lookup::<crate::object::Job>(args.arg_u32(0), Rights::MANAGE) {
+        Ok(job) => job,
+        Err(e) => return err_to_ret(e),
+    };
+    let killed = job.kill();
+    drop(job);
+    if with_current_process(|p| p.state == ProcessState::Zombie).unwrap_or(false) {
+        crate::process::table::kill_current();
+    }
+    ok_to_ret(killed)
+}
+
+/// Duplicate a handle
+///
+/// Arguments:
+///   arg0: handle (needs DUPLICATE)
+///   arg1: rights for the copy (0 or `SAME_RIGHTS` to keep them)
+///
+/// Returns: the new handle, or negative error code
+///
+/// The copy's rights must be a subset of the original's.
+fn sys_handle_duplicate(args: SyscallArgs) -> SyscallRet {
+    let rights = match args.arg_u32(1) {
+        0 => Rights::SAME_RIGHTS,
+        raw => Rights::from_raw(raw),
+    };
+    let result = with_handles(|handles| handles.duplicate(args.arg_u32(0), rights));
+    SyscallResult::from(result.map(|value| value as usize)).into_ret()
+}
+
+syscall_stub!(sys_handle_transfer);
+
+/// Set a property of an object
+///
+/// Arguments:
+///   arg0: handle (needs SET_PROPERTY)
+///   arg1: property (`PROP_NAME` or `PROP_JOB_LIMITS`)
+///   arg2: pointer to the value
+///   arg3: value size in bytes
+///
+/// Returns: 0, or negative error code
+///
+/// Names longer than `MAX_NAME_LEN` bytes are truncated; they must be
+/// UTF-8. The name belongs to the object, so every handle to it sees it.
+fn sys_object_set_property(args: SyscallArgs) -> SyscallRet {
+    match args.arg_u32(1) {
+        PROP_NAME => {}
+        PROP_JOB_LIMITS => return set_job_limits(args),
+        _ => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    }
+    let value = match args.user_slice(2, 3).truncate(crate::object::MAX_NAME_LEN).read_to_vec() {
+        Ok(value) => value,
+        Err(e) => return err_to_ret(e),
+    };
+    // A truncated multi-byte character at the end is dropped
+    let name = match core::str::from_utf8(&value) {
+        Ok(name) => name,
+        Err(e) if e.error_len().is_none() => core::str::from_utf8(&value[..e.valid_up_to()]).unwrap_or(""),
+        Err(_) => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+
+    let handle = args.arg_u32(0);
+    match with_handles(|handles| handles.get(handle, Rights::SET_PROPERTY).map(|h| h.object.clone())) {
+        Ok(object) => {
+            object.base().set_name(name);
+            ok_to_ret(0)
+        }
+        Err(e) => err_to_ret(e),
+    }
+}
+
+/// `OBJECT_SET_PROPERTY` with `PROP_JOB_LIMITS`: the value is a
+/// [`ResourceLimits`](crate::object::ResourceLimits)
+///
+/// New limits apply to processes and pages from now on; a job already
+/// over one is not trimmed.
+fn set_job_limits(args: SyscallArgs) -> SyscallRet {
+    use crate::object::{Job, ResourceLimits};
+
+    if args.arg(3) != core::mem::size_of::<ResourceLimits>() {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let limits = match args.user_ptr::<ResourceLimits>(2).read() {
+        Ok(limits) => limits,
+        Err(e) => return err_to_ret(e),
+    };
+    match lookup::<Job>(args.arg_u32(0), Rights::SET_PROPERTY) {
+        Ok(job) => {
+            job.set_limits(limits);
+            ok_to_ret(0)
+        }
+        Err(e) => err_to_ret(e),
+    }
+}
+
+/// Get information about a handle and its object
+///
+/// Arguments:
+///   arg0: handle (no rights needed)
+///   arg1: topic (`INFO_HANDLE_BASIC`)
+///   arg2: pointer to the output buffer
+///   arg3: buffer size in bytes (at least `size_of::<HandleBasicInfo>()`)
+///
+/// Returns: 0, or negative error code
+fn sys_object_get_info(args: SyscallArgs) -> SyscallRet {
+    if args.arg_u32(1) != INFO_HANDLE_BASIC {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    if args.arg(3) < core::mem::size_of::<HandleBasicInfo>() {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+
+    let handle = args.arg_u32(0);
+    let info = with_handles(|handles| {
+        let h = handles.get(handle, Rights::NONE)?;
+        Ok(HandleBasicInfo {
+            object_type: h.object_type().into_raw(),
+            rights: h.rights.into_raw(),
+            name: h.object.name().to_bytes(),
+        })
+    });
+    match info {
+        Ok(info) => SyscallResult::from(args.user_ptr::<HandleBasicInfo>(2).write(&info).map(|_| 0)).into_ret(),
+        Err(e) => err_to_ret(e),
+    }
+}
+
+// Time syscalls
+
+/// Read a clock
+///
+/// Arguments:
+///   arg0: clock ID (CLOCK_MONOTONIC or CLOCK_BOOTTIME)
+///
+/// Returns: the time in nanoseconds, ERR_NOT_SUPPORTED for CLOCK_UTC and
+/// CLOCK_THREAD, ERR_INVALID_ARGS for an unknown clock
+fn sys_clock_get(args: SyscallArgs) -> SyscallRet {
+    use crate::time::{clocksource, CLOCK_BOOTTIME, CLOCK_MONOTONIC, CLOCK_THREAD, CLOCK_UTC};
+
+    let time_ns = match args.arg_u32(0) {
+        CLOCK_MONOTONIC => clocksource::monotonic_ns(),
+        CLOCK_BOOTTIME => clocksource::boottime_ns(),
+        CLOCK_UTC | CLOCK_THREAD => return err_to_ret(RxStatus::ERR_NOT_SUPPORTED),
+        _ => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    ok_to_ret_isize(time_ns as isize)
+}
+
+/// Create a timer
+///
+/// Arguments:
+///   arg0: options (must be 0)
+///
+/// Returns: handle to the new (disarmed) timer, or negative error code
+fn sys_timer_create(args: SyscallArgs) -> SyscallRet {
+    if args.arg(0) != 0 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    match crate::object::Timer::create() {
+        Ok(timer) => create_handle(timer),
+        Err(_) => err_to_ret(RxStatus::ERR_NO_MEMORY),
+    }
+}
+
+/// Arm a timer
+///
+/// Arguments:
+///   arg0: timer handle (needs WRITE)
+///   arg1: absolute deadline in nanoseconds (CLOCK_MONOTONIC)
+///   arg2: slack in nanoseconds (0 for none)
+///   arg3: period in nanoseconds (0 for a one-shot timer)
+///
+/// Returns: 0, or negative error code (ERR_NO_MEMORY if too many timers
+/// are armed)
+///
+/// Re-arming replaces the previous deadline. The timer is signaled for
+/// OBJECT_WAIT_ONE when the deadline passes; a deadline already in the
+/// past fires at once.
+fn sys_timer_set(args: SyscallArgs) -> SyscallRet {
+    use crate::time::{Duration, Instant};
+
+    let deadline = Instant::from_nanos(args.arg_u64(1));
+    let slack = match args.arg_u64(2) {
+        0 => None,
+        slack => Some(Duration::from_nanos(slack)),
+    };
+    let period = args.arg_u64(3);
+    let result = lookup::<crate::object::Timer>(args.arg_u32(0), Rights::WRITE).and_then(|timer| {
+        let armed = match period {
+            0 => timer.set(deadline, slack),
+            period => timer.set_periodic(deadline, Duration::from_nanos(period), slack),
+        };
+        armed.map_err(|_| RxStatus::ERR_NO_MEMORY)
+    });
+    SyscallResult::from(result.map(|_| 0)).into_ret()
+}
+
+/// Cancel a timer
+///
+/// Arguments:
+///   arg0: timer handle (needs WRITE)
+///
+/// Returns: 0, or negative error code
+///
+/// Cancelling a timer that is not armed (or already fired) succeeds and
+/// does nothing.
+fn sys_timer_cancel(args: SyscallArgs) -> SyscallRet {
+    match lookup::<crate::object::Timer>(args.arg_u32(0), Rights::WRITE) {
+        Ok(timer) => {
+            let _ = timer.cancel();
+            ok_to_ret(0)
+        }
+        Err(e) => err_to_ret(e),
+    }
+}
+
+/// Sleep until a deadline
+///
+/// Arguments:
+///   arg0: absolute deadline in nanoseconds (CLOCK_MONOTONIC)
+///
+/// Returns: 0, or negative error code (ERR_INVALID_ARGS for an infinite
+/// deadline)
+///
+/// A deadline in the past returns at once. The thread is woken from the
+/// timer interrupt, so the sleep may run up to one tick long.
+fn sys_nanosleep(args: SyscallArgs) -> SyscallRet {
+    let deadline = crate::time::Instant::from_nanos(args.arg_u64(0));
+    SyscallResult::from(crate::time::timer_queue::sleep_until(deadline).map(|_| 0)).into_ret()
+}
+
+// Debug syscalls
+/// Debug write syscall - writes a string to the debug console
+///
+/// Arguments:
+///   arg0: pointer to string (userspace virtual address)
+///   arg1: length of string
+///
+/// Returns: number of bytes written, or negative error code
+fn sys_debug_write(args: SyscallArgs) -> SyscallRet {
+    let buf = args.user_slice(0, 1);
+
+    // Userspace output goes straight to the debug console, not through
+    // the log filter
+    let written = buf.for_each_chunk(crate::klog::debugcon_write);
+    if let Err(e) = written {
+        return err_to_ret(e);
+    }
+
+    ok_to_ret_isize(buf.len() as isize)
+}
+
+/// Map the kernel counters page
+///
+/// Arguments:
+///   arg0: page-aligned userspace address to map at
+///
+/// Returns: 0 on success, or negative error code (ERR_BUSY if the page
+/// overlaps an existing mapping)
+///
+/// The page is mapped read-only. Only privileged processes may call this;
+/// see [`crate::kcounters`] for the layout.
+fn sys_kcounters_map(args: SyscallArgs) -> SyscallRet {
+    let vaddr = args.arg(0);
+
+    let page_table = match crate::process::table::PROCESS_TABLE.lock().current() {
+        Some(process) if process.privileged => process.page_table,
+        Some(_) => return err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+    let vmar = match crate::process::vmar::of(page_table) {
+        Some(vmar) => vmar,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+
+    let result = crate::kcounters::map_into(page_table, &mut vmar.lock(), vaddr);
+    SyscallResult::from(result.map(|_| 0)).into_ret()
+}
+
+/// Get kernel object statistics for one object type
+///
+/// Arguments:
+///   arg0: object type (`ObjectType` value)
+///   arg1: pointer to a `KobjectStats` output struct
+///
+/// Returns: 0, or negative error code (`ERR_INVALID_ARGS` for an unknown type)
+fn sys_kobject_stats(args: SyscallArgs) -> SyscallRet {
+    let obj_type = crate::object::ObjectType::from_raw(args.arg_u32(0));
+    match crate::object::metrics::stats(obj_type) {
+        Some(stats) => SyscallResult::out(args.user_ptr(1), &stats).into_ret(),
+        None => err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    }
+}
+
+/// Read the kernel log
+///
+/// Arguments:
+///   arg0: root job handle (needs DEBUG)
+///   arg1: pointer to a buffer
+///   arg2: buffer length
+///   arg3: pointer to a u64 cursor: in, the log position to read from (0
+///         for the oldest text held); out, the position after the text read
+///
+/// Returns: number of bytes read (0 once caught up), or negative error code
+///
+/// The log is one byte stream, of which the ring holds the newest
+/// `KLOG_SIZE` bytes. Text at the cursor that was already overwritten is
+/// skipped: reading starts at the oldest byte held. See [`crate::klog`].
+fn sys_debug_read(args: SyscallArgs) -> SyscallRet {
+    let job = match lookup::<crate::object::Job>(args.arg_u32(0), Rights::DEBUG) {
+        Ok(job) => job,
+        Err(e) => return err_to_ret(e),
+    };
+    if job.id() != crate::object::JOB_ID_ROOT {
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED);
+    }
+    let cursor_ptr = args.user_ptr::<u64>(3);
+    let cursor = match cursor_ptr.read() {
+        Ok(cursor) => cursor,
+        Err(e) => return err_to_ret(e),
+    };
+
+    // Copied out of the ring first: the ring lock is not held across a
+    // user copy that may fault
+    let out = args.user_slice(1, 2);
+    let mut buf = alloc::vec![0u8; core::cmp::min(out.len(), crate::klog::KLOG_SIZE)];
+    let (start, n) = crate::klog::read(cursor, &mut buf);
+    if let Err(e) = out.write(&buf[..n]) {
+        return err_to_ret(e);
+    }
+    if let Err(e) = cursor_ptr.write(&(start + n as u64)) {
+        return err_to_ret(e);
+    }
+    ok_to_ret(n)
+}
+
+/// Dump a process's page tables
+///
+/// Arguments:
+///   arg0: target PID
+///   arg1: pointer to a buffer for the report, or null
+///   arg2: buffer length
+///   arg3: flags (`DUMP_USER_ONLY` = 1: user-accessible lower-half ranges only)
+///
+/// Returns: length of the whole report, or negative error code
+///
+/// The report is text, one mapped range per line; see
+/// [`crate::process::ptdump`]. It is truncated to fit the buffer. With a
+/// null buffer it is printed on the debug console instead. The caller may
+/// dump itself, its children, or any process if it is privileged.
+fn sys_pt_dump(args: SyscallArgs) -> SyscallRet {
+    use crate::process::ptdump;
+    use crate::process::table::PROCESS_TABLE;
+    use crate::sched::round_robin;
+
+    let pid = args.arg_u32(0);
+    let user_only = args.arg(3) & ptdump::DUMP_USER_ONLY != 0;
+
+    let caller = match round_robin::get_current_pid() {
+        Some(pid) => pid,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+
+    let ranges = {
+        let table = PROCESS_TABLE.lock();
+        let privileged = table.get(caller).is_some_and(|p| p.privileged);
+        let target = match table.get(pid) {
+            Some(target) => target,
+            None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+        };
+        if pid != caller && target.ppid != caller && !privileged {
+            return err_to_ret(RxStatus::ERR_ACCESS_DENIED);
+        }
+
+        // SAFETY: page tables are freed only after their process leaves
+        // the table, which cannot happen while it is locked
+        let range = if user_only { ptdump::USER_HALF } else { ptdump::ALL };
+        let mut ranges = unsafe { ptdump::snapshot(target.page_table, range) };
+        if user_only {
+            ranges.retain(|r| r.is_user());
+        }
+        ranges
+    };
+
+    let mut report = alloc::string::String::new();
+    let _ = ptdump::write_ranges(&mut report, &ranges);
+
+    if args.user_ptr::<u8>(1).is_null() {
+        crate::klog::debugcon_write(report.as_bytes());
+    } else if let Err(e) = args.user_slice(1, 2).write_partial(report.as_bytes()) {
+        return err_to_ret(e);
+    }
+
+    ok_to_ret(report.len())
+}
+
+// ============================================================================
+// I/O Syscalls (Phase 5A)
+// ============================================================================
+
+/// Write to file descriptor
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///   arg1: pointer to buffer
+///   arg2: length to write
+///
+/// Returns: number of bytes written, or negative error code
+///
+/// Writes go by the descriptor's kind, whatever its number:
+///   stdin: not allowed
+///   stdout, stderr: the console TTY (kernel debug console, port 0xE9)
+///   TTYs opened from /dev, files under /tmp and pipe write ends
+///   other files: reserved (Phase 5C)
+fn sys_write(args: SyscallArgs) -> SyscallRet {
+    fd_write(args.arg(0) as u8, args.user_slice(1, 2))
+}
+
+/// Write one buffer to a file descriptor (`WRITE`, and each `WRITEV` entry)
+fn fd_write(fd: u8, buf: UserSlice) -> SyscallRet {
+    use crate::syscall::fd::FdKind;
+
+    let len = buf.len();
+
+    use crate::drivers::tty;
+
+    let entry = {
+        let table = crate::process::table::PROCESS_TABLE.lock();
+        match table.current() {
+            Some(p) => p.fd_table.get(fd).map(|f| (f.kind, f.flags)),
+            // No process (kernel self-tests): the standard numbers only
+            None => match fd {
+                1 => Some((FdKind::Stdout, 0)),
+                2 => Some((FdKind::Stderr, 0)),
+                _ => None,
+            },
+        }
+    };
+
+    // Handle stdout/stderr via the console TTY (the debug port before the
+    // display is up), line buffered if the process asked for it, straight
+    // from the user pages
+    if let Some((FdKind::Stdout | FdKind::Stderr, _)) = entry {
+        if let Err(e) = tty::write_user(tty::CONSOLE_TTY, buf) {
+            return err_to_ret(e);
+        }
+        return ok_to_ret_isize(len as isize);
+    }
+
+    // stdin - cannot write; closed descriptors
+    if matches!(entry, None | Some((FdKind::Stdin, _))) {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS); // EBADF
+    }
+
+    // TTY devices opened through devfs, files in tmpfs and on mounts
+    if let Some((FdKind::Tmp { inode, offset }, flags)) = entry {
+        return tmpfs_write(fd, inode, offset, flags, buf);
+    }
+    if let Some((FdKind::Mounted { mount, inode, offset }, flags)) = entry {
+        return mounted_write(fd, mount, inode, offset, flags, buf);
+    }
+    if let Some((FdKind::Block { dev, offset }, flags)) = entry {
+        return block_write(fd, dev, offset, flags, buf);
+    }
+    if let Some((FdKind::Pipe { read_end, pipe_id }, flags)) = entry {
+        if read_end {
+            return err_to_ret(RxStatus::ERR_ACCESS_DENIED); // EBADF
+        }
+        let nonblocking = flags & crate::syscall::fd::flags::O_NONBLOCK != 0;
+        return SyscallResult::from(pipe::write(pipe_id, nonblocking, buf)).into_ret();
+    }
+    if let Some((FdKind::Tty { tty }, _)) = entry {
+        if let Err(e) = tty::write_user(tty as usize, buf) {
+            return err_to_ret(e);
+        }
+        return ok_to_ret_isize(len as isize);
+    }
+
+    // For other file descriptors (fd 3+), return not implemented for now
+    // Future: Write to ramdisk files
+    ok_to_ret_isize(len as isize)
+}
+
+/// Read from file descriptor
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///   arg1: pointer to buffer
+///   arg2: length to read
+///
+/// Returns: number of bytes read, or negative error code
+///
+/// Read from a file descriptor
+///
+/// For stdin (fd 0): Blocks waiting for keyboard input, returns one character at a time
+/// (fails with ERR_SHOULD_WAIT instead if the fd is O_NONBLOCK and no input is queued)
+/// For files: Reads from ramdisk, tmpfs and mounted files
+/// For block devices (`/dev/ram0`): Reads at the descriptor's offset; 0 at the end of the device
+/// For pipes: Blocks until bytes are buffered; returns 0 once every write end is closed
+/// For stdout/stderr: Returns error (not readable)
+fn sys_read(args: SyscallArgs) -> SyscallRet {
+    fd_read(args.arg(0) as u8, args.user_slice(1, 2))
+}
+
+/// Read one buffer from a file descriptor (`READ`, and each `READV` entry)
+fn fd_read(fd: u8, buf: UserSlice) -> SyscallRet {
+    use crate::syscall::fd::{FdKind, FileDescriptor};
+    use crate::process::table::PROCESS_TABLE;
+
+    let len = buf.len();
+
+    // Get the current process
+    let file_info = {
+        let mut table = PROCESS_TABLE.lock();
+        let current = match table.current_mut() {
+            Some(p) => p,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        };
+
+        // Get the file descriptor
+        let file_desc = match current.fd_table.get(fd) {
+            Some(f) => f,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
+        };
+
+        let nonblocking = file_desc.is_nonblocking();
+        let tty = match file_desc.kind {
+            FdKind::Stdin => Some(crate::drivers::tty::CONSOLE_TTY),
+            FdKind::Tty { tty } => Some(tty as usize),
+            _ => None,
+        };
+
+        match file_desc.kind {
+            FdKind::Stdin | FdKind::Tty { .. } => {
+                // stdin (fd 0) and /dev/ttyN - read from the TTY's input queue
+                // Block until character available
+                let tty = tty.unwrap_or(crate::drivers::tty::CONSOLE_TTY);
+                if len == 0 {
+                    return ok_to_ret_isize(0);
+                }
+
+                // Release process table lock before blocking
+                drop(current);
+                drop(table);
+
+                // Show a prompt still held in the line buffer
+                crate::drivers::tty::flush_current();
+
+                // Block until character available on the TTY
+                let ch = loop {
+                    if let Some(ch) = crate::drivers::tty::read_byte(tty) {
+                        break ch;
+                    }
+                    if nonblocking {
+                        return err_to_ret(RxStatus::ERR_SHOULD_WAIT);
+                    }
+                    // Yield to other processes while waiting
+                    let _ = crate::sched::round_robin::yield_cpu();
+                };
+
+                // Write the character to userspace buffer
+                if let Err(e) = buf.write(&[ch as u8]) {
+                    return err_to_ret(e);
+                }
+
+                return ok_to_ret_isize(1); // Read one character
+            }
+            FdKind::File { inode, offset } => {
+                // Get the ramdisk file info
+                use crate::fs::ramdisk;
+                let ramdisk = match ramdisk::get_ramdisk() {
+                    Ok(r) => r,
+                    Err(_) => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+                };
+
+                // Get file headers array
+                let files = unsafe {
+                    let base = ramdisk.data.as_ptr().add(ramdisk.superblock.files_offset as usize);
+                    let count = ramdisk.superblock.num_files as usize;
+                    core::slice::from_raw_parts(base as *const ramdisk::RamdiskFile, count)
+                };
+
+                // Find the file by inode (index)
+                let ramdisk_file = match files.get(inode as usize) {
+                    Some(&f) => f,
+                    None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+                };
+
+                Some((ramdisk_file, offset))
+            }
+            FdKind::Tmp { inode, offset } => {
+                // Release process table lock before taking the tmpfs lock
+                drop(table);
+                return tmpfs_read(fd, inode, offset, buf);
+            }
+            FdKind::Mounted { mount, inode, offset } => {
+                drop(table);
+                return mounted_read(fd, mount, inode, offset, buf);
+            }
+            FdKind::Block { dev, offset } => {
+                drop(table);
+                return block_read(fd, dev, offset, buf);
+            }
+            FdKind::Pipe { read_end, pipe_id } => {
+                if !read_end {
+                    return err_to_ret(RxStatus::ERR_ACCESS_DENIED); // EBADF
+                }
+                // Release process table lock before blocking
+                drop(table);
+                return SyscallResult::from(pipe::read(pipe_id, nonblocking, buf)).into_ret();
+            }
+            FdKind::Proc { node, offset } => {
+                // procfs - contents are generated on each read
+                let content = crate::fs::procfs::generate_for(node, current);
+                // Release process table lock before copying out: the copy
+                // may fault, and the fault handler may need to reclaim
+                drop(table);
+
+                let bytes = content.as_bytes();
+                let start = core::cmp::min(offset, bytes.len() as u64) as usize;
+                let n = match buf.write(&bytes[start..]) {
+                    Ok(n) => n,
+                    Err(e) => return err_to_ret(e),
+                };
+
+                set_file_offset(fd, offset + n as u64);
+                return ok_to_ret_isize(n as isize);
+            }
+            _ => {
+                // Stdout/stderr not readable
+                return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+            }
+        }
+    };
+
+    if let Some((ramdisk_file, offset)) = file_info {
+        use crate::fs::ramdisk;
+        let ramdisk = ramdisk::get_ramdisk().unwrap();
+
+        // Calculate remaining bytes from current offset
+        let file_size = ramdisk_file.size as u64;
+        let remaining = if offset >= file_size {
+            0
+        } else {
+            file_size - offset
+        };
+
+        if remaining == 0 {
+            return ok_to_ret_isize(0); // EOF
+        }
+
+        let to_read = core::cmp::min(len as u64, remaining) as usize;
+
+        // Read from the file at current offset
+        let data_offset = ramdisk_file.data_offset as usize + offset as usize;
+        let data_ptr = unsafe {
+            ramdisk.data.as_ptr().add(data_offset)
+        };
+
+        let data = unsafe { core::slice::from_raw_parts(data_ptr, to_read) };
+        if let Err(e) = buf.write(data) {
+            return err_to_ret(e);
+        }
+
+        // Update offset in fd_table
+        let mut table = PROCESS_TABLE.lock();
         if let Some(current) = table.current_mut() {
             if let Some(fd_entry) = current.fd_table.get_mut(fd) {
                 if let FdKind::File { ref mut offset, .. } = fd_entry.kind {
@@ -1064,145 +2717,1309 @@ fn sys_read(args: SyscallArgs) -> SyscallRet {
             }
         }
 
-        ok_to_ret_isize(to_read as isize)
+        ok_to_ret_isize(to_read as isize)
+    } else {
+        ok_to_ret_isize(0)
+    }
+}
+
+/// Read from a tmpfs file at the descriptor's offset and advance it
+fn tmpfs_read(fd: u8, inode: u32, offset: u64, buf: UserSlice) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, tmpfs};
+
+    // Copy out under the tmpfs lock, into userspace after dropping it
+    let data = tmpfs::with(|fs| {
+        let remaining = fs.size(inode)?.saturating_sub(offset);
+        let mut data = alloc::vec![0u8; core::cmp::min(buf.len() as u64, remaining) as usize];
+        fs.read(inode, offset, &mut data).map(|_| data)
+    });
+    let n = match data {
+        Ok(data) => match buf.write(&data) {
+            Ok(n) => n,
+            Err(e) => return err_to_ret(e),
+        },
+        Err(e) => return err_to_ret(errno_to_rxstatus(e)),
+    };
+
+    set_file_offset(fd, offset + n as u64);
+    ok_to_ret(n)
+}
+
+/// Write to a tmpfs file at the descriptor's offset (or its end, with
+/// `O_APPEND`) and advance it
+fn tmpfs_write(fd: u8, inode: u32, offset: u64, flags: u32, buf: UserSlice) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, tmpfs};
+    use crate::syscall::fd::flags::{O_APPEND, O_RDONLY};
+
+    if flags & 3 == O_RDONLY {
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED); // EBADF
+    }
+
+    let data = match buf.read_to_vec() {
+        Ok(d) => d,
+        Err(e) => return err_to_ret(e),
+    };
+    let written = tmpfs::with(|fs| {
+        let offset = if flags & O_APPEND != 0 { fs.size(inode)? } else { offset };
+        fs.write(inode, offset, &data).map(|n| offset + n as u64)
+    });
+    match written {
+        Ok(end) => {
+            set_file_offset(fd, end);
+            ok_to_ret(data.len())
+        }
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Read from a file on a mounted filesystem at the descriptor's offset
+/// and advance it
+fn mounted_read(fd: u8, mount: u32, inode: u64, offset: u64, buf: UserSlice) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, mount as mounts};
+
+    // Copy out under the filesystem lock, into userspace after dropping it
+    let data = mounts::with(mount, |fs| {
+        let remaining = fs.size(inode)?.saturating_sub(offset);
+        let mut data = alloc::vec![0u8; core::cmp::min(buf.len() as u64, remaining) as usize];
+        fs.read(inode, offset, &mut data).map(|n| {
+            data.truncate(n);
+            data
+        })
+    });
+    let n = match data {
+        Ok(data) => match buf.write(&data) {
+            Ok(n) => n,
+            Err(e) => return err_to_ret(e),
+        },
+        Err(e) => return err_to_ret(errno_to_rxstatus(e)),
+    };
+
+    set_file_offset(fd, offset + n as u64);
+    ok_to_ret(n)
+}
+
+/// Write to a file on a mounted filesystem at the descriptor's offset
+/// (or its end, with `O_APPEND`) and advance it
+fn mounted_write(fd: u8, mount: u32, inode: u64, offset: u64, flags: u32, buf: UserSlice) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, mount as mounts};
+    use crate::syscall::fd::flags::{O_APPEND, O_RDONLY};
+
+    if flags & 3 == O_RDONLY {
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED); // EBADF
+    }
+
+    let data = match buf.read_to_vec() {
+        Ok(d) => d,
+        Err(e) => return err_to_ret(e),
+    };
+    let written = mounts::with(mount, |fs| {
+        let offset = if flags & O_APPEND != 0 { fs.size(inode)? } else { offset };
+        fs.write(inode, offset, &data).map(|n| offset + n as u64)
+    });
+    match written {
+        Ok(end) => {
+            set_file_offset(fd, end);
+            ok_to_ret(data.len())
+        }
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Read from a block device at the descriptor's offset and advance it
+fn block_read(fd: u8, dev: u8, offset: u64, buf: UserSlice) -> SyscallRet {
+    use crate::drivers::block;
+    use crate::fs::errno_to_rxstatus;
+
+    let device = match block::get(dev as usize) {
+        Some(d) => d,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+    let remaining = device.size().saturating_sub(offset);
+    let mut data = alloc::vec![0u8; core::cmp::min(buf.len() as u64, remaining) as usize];
+    let n = match block::read_at(&*device, offset, &mut data) {
+        Ok(n) => match buf.write(&data[..n]) {
+            Ok(n) => n,
+            Err(e) => return err_to_ret(e),
+        },
+        Err(e) => return err_to_ret(errno_to_rxstatus(e)),
+    };
+
+    set_file_offset(fd, offset + n as u64);
+    ok_to_ret(n)
+}
+
+/// Write to a block device at the descriptor's offset and advance it
+///
+/// Writing at the end of the device fails with `ERR_NO_MEMORY` (ENOSPC).
+fn block_write(fd: u8, dev: u8, offset: u64, flags: u32, buf: UserSlice) -> SyscallRet {
+    use crate::drivers::block;
+    use crate::fs::errno_to_rxstatus;
+    use crate::syscall::fd::flags::O_RDONLY;
+
+    if flags & 3 == O_RDONLY {
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED); // EBADF
+    }
+    let device = match block::get(dev as usize) {
+        Some(d) => d,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+    let data = match buf.read_to_vec() {
+        Ok(d) => d,
+        Err(e) => return err_to_ret(e),
+    };
+    match block::write_at(&*device, offset, &data) {
+        Ok(0) if !data.is_empty() => err_to_ret(RxStatus::ERR_NO_MEMORY), // ENOSPC
+        Ok(n) => {
+            set_file_offset(fd, offset + n as u64);
+            ok_to_ret(n)
+        }
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Store a tmpfs, mounted file, block device or procfs descriptor's new
+/// offset
+fn set_file_offset(fd: u8, new: u64) {
+    use crate::syscall::fd::FdKind;
+
+    crate::process::table::with_current_process_mut(|p| {
+        if let Some(
+            FdKind::Tmp { offset, .. }
+            | FdKind::Mounted { offset, .. }
+            | FdKind::Block { offset, .. }
+            | FdKind::Proc { offset, .. },
+        ) = p.fd_table.get_mut(fd).map(|f| &mut f.kind)
+        {
+            *offset = new;
+        }
+    });
+}
+
+/// Write a list of buffers to a file descriptor
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///   arg1: pointer to an array of [`uaccess::IoVec`]
+///   arg2: number of entries (at most `IOV_MAX`)
+///
+/// Returns: total bytes written, or negative error code
+///
+/// The buffers are written in order as if by separate `WRITE` calls; a
+/// short write ends the call. An error after some bytes were written
+/// returns the bytes written instead.
+fn sys_writev(args: SyscallArgs) -> SyscallRet {
+    let fd = args.arg(0) as u8;
+    match uaccess::UserIoVec::read(args.user_ptr(1), args.arg(2), isize::MAX as usize) {
+        Ok(iov) => for_each_iovec(&iov, |buf| fd_write(fd, buf)),
+        Err(e) => err_to_ret(e),
+    }
+}
+
+/// Read from a file descriptor into a list of buffers
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///   arg1: pointer to an array of [`uaccess::IoVec`]
+///   arg2: number of entries (at most `IOV_MAX`)
+///
+/// Returns: total bytes read, or negative error code
+///
+/// The buffers are filled in order as if by separate `READ` calls; a
+/// short read (end of file, or a TTY with no more input) ends the call.
+fn sys_readv(args: SyscallArgs) -> SyscallRet {
+    let fd = args.arg(0) as u8;
+    match uaccess::UserIoVec::read(args.user_ptr(1), args.arg(2), isize::MAX as usize) {
+        Ok(iov) => for_each_iovec(&iov, |buf| fd_read(fd, buf)),
+        Err(e) => err_to_ret(e),
+    }
+}
+
+/// Run a single-buffer transfer over each iovec entry
+///
+/// Stops at the first short transfer. An error is returned only if no
+/// bytes were transferred before it.
+fn for_each_iovec(iov: &uaccess::UserIoVec, mut transfer: impl FnMut(UserSlice) -> SyscallRet) -> SyscallRet {
+    let mut done = 0usize;
+    for &buf in iov.slices() {
+        let ret = transfer(buf);
+        if ret < 0 {
+            return if done > 0 { ok_to_ret(done) } else { ret };
+        }
+        done += ret as usize;
+        if (ret as usize) < buf.len() {
+            break;
+        }
+    }
+    ok_to_ret(done)
+}
+
+/// Open a file from the ramdisk
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///   arg1: flags (O_RDONLY, O_WRONLY, O_RDWR, optionally O_CREAT, O_EXCL,
+///         O_TRUNC, O_APPEND, O_NONBLOCK, O_CLOEXEC)
+///
+/// Returns: file descriptor number, or negative error code
+///
+/// Phase 5C: This opens files from the embedded ramdisk filesystem.
+/// Paths under `/dev` are resolved by devfs instead (e.g. `/dev/tty2`),
+/// paths under `/proc` by procfs (e.g. `/proc/cpuinfo`), paths under
+/// `/tmp` by tmpfs, and paths under a mount point by the mounted
+/// filesystem; tmpfs and FAT honour O_CREAT, O_EXCL and O_TRUNC. Opening
+/// for writing, or with O_CREAT or O_TRUNC, fails with EROFS on a
+/// read-only mount.
+/// The path must be a null-terminated string in userspace memory.
+fn sys_open(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::ramdisk::{self, Errno};
+    use crate::syscall::fd::{FdKind, flags};
+    use crate::process::table::PROCESS_TABLE;
+
+    let flags_val = args.arg_u32(1);
+
+    // Read the path, relative to the caller's current directory
+    let path = match read_user_path(args.user_ptr(0)) {
+        Ok(path) => path,
+        Err(e) => return err_to_ret(e),
+    };
+    let path = path.as_str();
+
+    // Device nodes under /dev
+    if crate::fs::devfs::is_devfs_path(path) {
+        let node = match crate::fs::devfs::lookup(path) {
+            Ok(n) => n,
+            Err(_) => return err_to_ret(RxStatus::ERR_NOT_FOUND), // ENOENT
+        };
+        let kind = match node {
+            crate::fs::devfs::DevNode::Tty(tty) => FdKind::Tty { tty },
+            crate::fs::devfs::DevNode::Block(dev) => FdKind::Block { dev, offset: 0 },
+        };
+
+        let mut table = PROCESS_TABLE.lock();
+        let current = match table.current_mut() {
+            Some(p) => p,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        };
+        return match current.fd_table.alloc(kind, flags_val) {
+            Some(fd) => ok_to_ret(fd as usize),
+            None => err_to_ret(RxStatus::ERR_NO_MEMORY), // EMFILE
+        };
+    }
+
+    // Synthesized files under /proc
+    if crate::fs::procfs::is_procfs_path(path) {
+        let node = match crate::fs::procfs::lookup(path) {
+            Ok(n) => n,
+            Err(_) => return err_to_ret(RxStatus::ERR_NOT_FOUND), // ENOENT
+        };
+
+        let mut table = PROCESS_TABLE.lock();
+        let current = match table.current_mut() {
+            Some(p) => p,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        };
+        return match current.fd_table.alloc(FdKind::Proc { node, offset: 0 }, flags_val) {
+            Some(fd) => ok_to_ret(fd as usize),
+            None => err_to_ret(RxStatus::ERR_NO_MEMORY), // EMFILE
+        };
+    }
+
+    // Writable files under /tmp
+    if crate::fs::tmpfs::is_tmpfs_path(path) {
+        let inode = match crate::fs::tmpfs::with(|fs| fs.open(path, flags_val)) {
+            Ok(ino) => ino,
+            Err(e) => return err_to_ret(crate::fs::errno_to_rxstatus(e)),
+        };
+
+        // The descriptor owns the open from here; dropping it releases it
+        let file_desc = crate::syscall::fd::FileDescriptor::new(FdKind::Tmp { inode, offset: 0 }, flags_val);
+        let mut table = PROCESS_TABLE.lock();
+        let current = match table.current_mut() {
+            Some(p) => p,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        };
+        return match current.fd_table.insert(file_desc) {
+            Some(fd) => ok_to_ret(fd as usize),
+            None => err_to_ret(RxStatus::ERR_NO_MEMORY), // EMFILE
+        };
+    }
+
+    // Files on mounted filesystems
+    if let Some((mount, rest)) = crate::fs::mount::find(path) {
+        let inode = match crate::fs::mount::open(mount, rest, flags_val) {
+            Ok(ino) => ino,
+            Err(e) => return err_to_ret(crate::fs::errno_to_rxstatus(e)),
+        };
+
+        // As for tmpfs: dropping the descriptor releases the open
+        let file_desc = crate::syscall::fd::FileDescriptor::new(FdKind::Mounted { mount, inode, offset: 0 }, flags_val);
+        let mut table = PROCESS_TABLE.lock();
+        let current = match table.current_mut() {
+            Some(p) => p,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        };
+        return match current.fd_table.insert(file_desc) {
+            Some(fd) => ok_to_ret(fd as usize),
+            None => err_to_ret(RxStatus::ERR_NO_MEMORY), // EMFILE
+        };
+    }
+
+    // Look up file in ramdisk
+    let ramdisk_file = {
+        let ramdisk = match ramdisk::get_ramdisk() {
+            Ok(r) => r,
+            Err(e) => {
+                // Convert Errno to RxStatus
+                return err_to_ret(match e {
+                    Errno::ENODEV => RxStatus::ERR_NOT_FOUND,
+                    _ => RxStatus::ERR_INVALID_ARGS,
+                });
+            }
+        };
+
+        match ramdisk.find_file(path) {
+            Some(f) => f,
+            None => return err_to_ret(RxStatus::ERR_NOT_FOUND), // ENOENT
+        }
+    };
+
+    // Get the current process and allocate fd
+    let fd_result = {
+        let mut table = PROCESS_TABLE.lock();
+        let current = match table.current_mut() {
+            Some(p) => p,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        };
+
+        // Find the inode (file index) for offset tracking
+        let inode = {
+            let ramdisk = match ramdisk::get_ramdisk() {
+                Ok(r) => r,
+                Err(_) => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+            };
+
+            let files = unsafe {
+                let base = ramdisk.data.as_ptr().add(ramdisk.superblock.files_offset as usize);
+                let count = ramdisk.superblock.num_files as usize;
+                core::slice::from_raw_parts(base as *const ramdisk::RamdiskFile, count)
+            };
+
+            // Find the index of this file
+            files.iter().position(|&f| {
+                f.data_offset == ramdisk_file.data_offset &&
+                f.name_offset == ramdisk_file.name_offset
+            }).unwrap_or(0) as u32
+        };
+
+        // Allocate file descriptor
+        match current.fd_table.alloc(
+            FdKind::File {
+                inode,
+                offset: 0,
+            },
+            flags_val,
+        ) {
+            Some(fd) => fd as usize,
+            None => return err_to_ret(RxStatus::ERR_NO_MEMORY), // EMFILE
+        }
+    };
+
+    ok_to_ret(fd_result)
+}
+
+/// Close a file descriptor
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///
+/// Returns: 0 on success, or negative error code
+///
+/// Phase 5C: This closes files and releases the file descriptor.
+/// stdin/stdout/stderr cannot be closed.
+fn sys_close(args: SyscallArgs) -> SyscallRet {
+    use crate::process::table::PROCESS_TABLE;
+
+    let fd = args.arg(0) as u8;
+
+    let mut table = PROCESS_TABLE.lock();
+    let current = match table.current_mut() {
+        Some(p) => p,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+
+    match current.fd_table.close(fd) {
+        Some(_) => ok_to_ret(0),
+        None => err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
+    }
+}
+
+/// Read the paste buffer
+///
+/// Arguments:
+///   arg0: pointer to output buffer
+///   arg1: buffer length
+///
+/// Returns: number of bytes copied, or negative error code
+///
+/// The paste buffer is shared by all virtual terminals; see
+/// [`crate::drivers::paste`].
+fn sys_clipboard_get(args: SyscallArgs) -> SyscallRet {
+    let out = args.user_slice(0, 1);
+
+    if out.is_empty() {
+        return ok_to_ret(0);
+    }
+    let mut data = alloc::vec![0u8; core::cmp::min(out.len(), crate::drivers::paste::PASTE_BUFFER_SIZE)];
+    let n = crate::drivers::paste::get(&mut data);
+    match out.write(&data[..n]) {
+        Ok(n) => ok_to_ret(n),
+        Err(e) => err_to_ret(e),
+    }
+}
+
+/// Replace the paste buffer
+///
+/// Arguments:
+///   arg0: pointer to data
+///   arg1: data length (at most `PASTE_BUFFER_SIZE` bytes are kept)
+///
+/// Returns: number of bytes stored, or negative error code
+fn sys_clipboard_set(args: SyscallArgs) -> SyscallRet {
+    let input = args.user_slice(0, 1);
+
+    if input.is_empty() {
+        return ok_to_ret(crate::drivers::paste::set(&[]));
+    }
+    // Anything past the buffer size is dropped by `paste::set` anyway
+    let data = match input.truncate(crate::drivers::paste::PASTE_BUFFER_SIZE).read_to_vec() {
+        Ok(data) => data,
+        Err(e) => return err_to_ret(e),
+    };
+    ok_to_ret(crate::drivers::paste::set(&data))
+}
+
+/// Set how the caller's TTY output is buffered
+///
+/// Arguments:
+///   arg0: mode (0 = unbuffered, 1 = line buffered)
+///
+/// Returns: the previous mode, or negative error code
+///
+/// Switching to unbuffered writes any pending output first; see
+/// [`crate::drivers::tty::OutputBuffer`].
+fn sys_tty_set_buffering(args: SyscallArgs) -> SyscallRet {
+    use crate::drivers::tty::{self, BufferMode};
+
+    let mode = match BufferMode::from_raw(args.arg(0) as u32) {
+        Some(mode) => mode,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    let previous = crate::process::table::with_current_process_mut(|p| {
+        let previous = p.tty_output.mode();
+        p.tty_output.set_mode(mode, tty::write);
+        previous
+    });
+    match previous {
+        Some(previous) => ok_to_ret(previous as usize),
+        None => err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    }
+}
+
+/// Control the virtual terminals and the display
+///
+/// Arguments:
+///   arg0: command (0 = refresh, 1 = take display, 2 = release display,
+///         3 = set palette entry)
+///   arg1: palette index (set palette entry)
+///   arg2: color as 0xRRGGBB (set palette entry)
+///
+/// Returns: 0, or negative error code
+///
+/// Refreshing is open to everyone; the other commands are privileged.
+/// See [`crate::drivers::display::vt`].
+fn sys_vt_control(args: SyscallArgs) -> SyscallRet {
+    use crate::drivers::display::{self, vt::{self, VtCommand}, Color};
+
+    let command = match VtCommand::from_raw(args.arg_u32(0)) {
+        Some(command) => command,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+    if !display::is_initialized() {
+        return err_to_ret(RxStatus::ERR_NOT_SUPPORTED);
+    }
+    if command == VtCommand::Refresh {
+        vt::refresh();
+        return ok_to_ret(0);
+    }
+
+    let pid = match crate::process::table::with_current_process_mut(|p| (p.pid, p.privileged)) {
+        Some((pid, true)) => pid,
+        Some((_, false)) => return err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+    match command {
+        VtCommand::TakeDisplay if !vt::take_display(pid) => err_to_ret(RxStatus::ERR_BUSY),
+        VtCommand::ReleaseDisplay if !vt::release_display(pid) => err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        VtCommand::SetPalette => {
+            let rgb = args.arg_u32(2);
+            let color = Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+            if display::set_palette(args.arg(1), color) {
+                ok_to_ret(0)
+            } else {
+                err_to_ret(RxStatus::ERR_INVALID_ARGS)
+            }
+        }
+        _ => ok_to_ret(0),
+    }
+}
+
+/// Mount a filesystem (privileged)
+///
+/// Arguments:
+///   arg0: pointer to the block device path (`/dev/<name>`), or 0 for a
+///         filesystem type without a device (tmpfs, procfs)
+///   arg1: pointer to the mount point path: a single directory below `/`
+///   arg2: pointer to the filesystem type name (`fat32`, `vfat`, `tmpfs`,
+///         `procfs`)
+///   arg3: flags (MS_RDONLY, MS_NOEXEC)
+///
+/// Returns: 0, or negative error code
+///
+/// The mount point does not have to exist; it hides a ramdisk directory
+/// of the same name. `/dev`, `/proc`, `/tmp` and existing mount points
+/// are busy. See [`crate::fs::mount`].
+fn sys_mount(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{devfs, errno_to_rxstatus, mount};
+
+    let privileged = crate::process::table::PROCESS_TABLE.lock().current().map(|p| p.privileged);
+    match privileged {
+        Some(true) => {}
+        Some(false) => return err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    }
+
+    let device = if args.user_ptr::<u8>(0).is_null() {
+        None
+    } else {
+        let path = match read_user_path(args.user_ptr(0)) {
+            Ok(p) => p,
+            Err(e) => return err_to_ret(e),
+        };
+        match devfs::lookup(&path) {
+            Ok(devfs::DevNode::Block(dev)) => match crate::drivers::block::get(dev as usize) {
+                Some(device) => Some(device),
+                None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+            },
+            Ok(_) => return err_to_ret(RxStatus::ERR_INVALID_ARGS), // ENOTBLK
+            Err(e) => return err_to_ret(errno_to_rxstatus(e)),
+        }
+    };
+    let target = match read_user_path(args.user_ptr(1)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+
+    let type_ptr = args.user_ptr::<u8>(2);
+    if type_ptr.is_null() {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    let fs_type = match type_ptr.read_str(mount::FS_TYPE_MAX) {
+        Ok(bytes) => bytes,
+        Err(e) => return err_to_ret(e),
+    };
+    let Ok(fs_type) = core::str::from_utf8(&fs_type) else {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    };
+
+    match mount::mount(&target, fs_type, device, args.arg_u32(3)) {
+        Ok(_) => {
+            kinfo!("[MOUNT] {} on {}", fs_type, target);
+            ok_to_ret(0)
+        }
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Unmount a filesystem (privileged)
+///
+/// Arguments:
+///   arg0: pointer to the mount point path
+///
+/// Returns: 0, or negative error code
+///
+/// Fails with `EBUSY` while a descriptor has a file on it open or a
+/// process has its current directory under it. Anything the filesystem
+/// buffers is written back first.
+fn sys_umount(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, mount};
+
+    let privileged = crate::process::table::PROCESS_TABLE.lock().current().map(|p| p.privileged);
+    match privileged {
+        Some(true) => {}
+        Some(false) => return err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    }
+
+    let path = match read_user_path_nofollow(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+
+    // Lock order: the process table before the mount table
+    let table = crate::process::table::PROCESS_TABLE.lock();
+    if table.iter().any(|p| mount::below(&p.cwd, &path).is_some()) {
+        return err_to_ret(RxStatus::ERR_BUSY);
+    }
+    match mount::umount(&path) {
+        Ok(()) => {
+            drop(table);
+            kinfo!("[MOUNT] unmounted {}", path);
+            ok_to_ret(0)
+        }
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Get or set file descriptor flags
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///   arg1: command (F_GETFD, F_SETFD, F_GETFL or F_SETFL)
+///   arg2: new flags (F_SETFD, F_SETFL)
+///
+/// Returns: the descriptor flags (F_GETFD) or open flags (F_GETFL), 0
+/// (F_SETFD, F_SETFL), or negative error code
+///
+/// The descriptor flags are `FD_CLOEXEC` alone (`O_CLOEXEC` at open).
+/// F_SETFL only changes the status flags (O_APPEND, O_NONBLOCK); other
+/// bits are ignored. The change is private to this process: a `FORK`
+/// child keeps the flags it was created with.
+fn sys_fcntl(args: SyscallArgs) -> SyscallRet {
+    use crate::syscall::fd::{fcntl, flags};
+
+    let fd = args.arg(0) as u8;
+    let cmd = args.arg_u32(1);
+    let value = args.arg_u32(2);
+
+    let ret = crate::process::table::with_current_process_mut(|p| {
+        let file_desc = match p.fd_table.get_mut(fd) {
+            Some(f) => f,
+            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
+        };
+        match cmd {
+            fcntl::F_GETFD => ok_to_ret(if file_desc.is_cloexec() { fcntl::FD_CLOEXEC as usize } else { 0 }),
+            fcntl::F_SETFD => {
+                file_desc.flags &= !flags::O_CLOEXEC;
+                if value & fcntl::FD_CLOEXEC != 0 {
+                    file_desc.flags |= flags::O_CLOEXEC;
+                }
+                ok_to_ret(0)
+            }
+            fcntl::F_GETFL => ok_to_ret((file_desc.flags & !flags::O_CLOEXEC) as usize),
+            fcntl::F_SETFL => {
+                file_desc.set_status_flags(value);
+                ok_to_ret(0)
+            }
+            _ => err_to_ret(RxStatus::ERR_INVALID_ARGS),
+        }
+    });
+    ret.unwrap_or_else(|| err_to_ret(RxStatus::ERR_INVALID_ARGS))
+}
+
+/// Read a null-terminated path (at most 256 bytes) from userspace
+///
+/// A relative path is made absolute against the calling process's current
+/// directory ([`vfs::resolve`](crate::fs::vfs::resolve)), and symbolic
+/// links in it are followed ([`vfs::follow`](crate::fs::vfs::follow)).
+/// A path under a procfs mount is rewritten to `/proc`
+/// ([`translate`](crate::fs::mount::translate)).
+fn read_user_path(ptr: UserPtr<u8>) -> Result<alloc::string::String, RxStatus> {
+    read_user_path_with(ptr, true)
+}
+
+/// [`read_user_path`], but a symbolic link as the last component is kept
+///
+/// For syscalls that act on a link itself (`UNLINK`, `READLINK`, ...).
+fn read_user_path_nofollow(ptr: UserPtr<u8>) -> Result<alloc::string::String, RxStatus> {
+    read_user_path_with(ptr, false)
+}
+
+fn read_user_path_with(ptr: UserPtr<u8>, follow_last: bool) -> Result<alloc::string::String, RxStatus> {
+    use crate::fs::{errno_to_rxstatus, vfs};
+
+    if ptr.is_null() {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    let bytes = ptr.read_str(vfs::PATH_MAX)?;
+    let path = alloc::string::String::from_utf8(bytes).map_err(|_| RxStatus::ERR_INVALID_ARGS)?;
+    crate::process::table::with_current_process(|p| vfs::resolve(&p.cwd, &path))
+        .unwrap_or_else(|| vfs::resolve("/", &path))
+        .and_then(|path| vfs::follow(&path, follow_last))
+        .map(crate::fs::mount::translate)
+        .map_err(errno_to_rxstatus)
+}
+
+/// Run a tmpfs or mounted filesystem operation on the path in arg0
+///
+/// A symbolic link as the last component is not followed. `mount_op`
+/// gets the path below the mount point, and fails with `EROFS` on a
+/// read-only mount. Paths outside `/tmp` and the mounts are read-only
+/// and fail with `ERR_ACCESS_DENIED`.
+fn fs_path_op(
+    args: SyscallArgs,
+    tmpfs_op: impl FnOnce(&mut crate::fs::Tmpfs, &str) -> Result<(), crate::fs::Errno>,
+    mount_op: impl FnOnce(&mut dyn crate::fs::mount::Filesystem, &str) -> Result<(), crate::fs::Errno>,
+) -> SyscallRet {
+    let path = match read_user_path_nofollow(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    let result = if crate::fs::tmpfs::is_tmpfs_path(&path) {
+        crate::fs::tmpfs::with(|fs| tmpfs_op(fs, &path))
+    } else if let Some((mount, rest)) = crate::fs::mount::find(&path) {
+        crate::fs::mount::with_writable(mount, |fs| mount_op(fs, rest))
     } else {
-        ok_to_ret_isize(0)
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED); // EROFS
+    };
+    match result {
+        Ok(()) => ok_to_ret(0),
+        Err(e) => err_to_ret(crate::fs::errno_to_rxstatus(e)),
+    }
+}
+
+/// Remove a file
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///
+/// Returns: 0 on success, or negative error code
+///
+/// Only files under `/tmp` and on mounted filesystems can be removed. In
+/// tmpfs, descriptors that have the file open keep working and its data
+/// is freed when the last is closed; FAT frees it at once.
+fn sys_unlink(args: SyscallArgs) -> SyscallRet {
+    fs_path_op(args, |fs, path| fs.unlink(path), |fs, path| fs.unlink(path))
+}
+
+/// Create a directory
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///
+/// Returns: 0 on success, or negative error code
+///
+/// Directories can only be created under `/tmp` and on mounted
+/// filesystems.
+fn sys_mkdir(args: SyscallArgs) -> SyscallRet {
+    fs_path_op(args, |fs, path| fs.mkdir(path), |fs, path| fs.mkdir(path))
+}
+
+/// Remove an empty directory
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///
+/// Returns: 0 on success, or negative error code
+fn sys_rmdir(args: SyscallArgs) -> SyscallRet {
+    fs_path_op(args, |fs, path| fs.rmdir(path), |fs, path| fs.rmdir(path))
+}
+
+/// Set the size of an open file
+///
+/// Arguments:
+///   arg0: file descriptor (fd), opened for writing
+///   arg1: new size in bytes
+///
+/// Returns: 0 on success, or negative error code
+///
+/// Growing a file fills it with zeros. The descriptor's offset is not
+/// changed. Only tmpfs files and files on mounts can be resized.
+fn sys_ftruncate(args: SyscallArgs) -> SyscallRet {
+    use crate::syscall::fd::{FdKind, flags::O_RDONLY};
+
+    let fd = args.arg(0) as u8;
+    let size = args.arg_u64(1);
+
+    let entry = crate::process::table::with_current_process_mut(|p| {
+        p.fd_table.get(fd).map(|f| (f.kind, f.flags))
+    });
+    let result = match entry.flatten() {
+        Some((FdKind::Tmp { .. } | FdKind::Mounted { .. }, flags)) if flags & 3 == O_RDONLY => {
+            return err_to_ret(RxStatus::ERR_ACCESS_DENIED);
+        }
+        Some((FdKind::Tmp { inode, .. }, _)) => crate::fs::tmpfs::with(|fs| fs.truncate(inode, size)),
+        Some((FdKind::Mounted { mount, inode, .. }, _)) => crate::fs::mount::with(mount, |fs| fs.truncate(inode, size)),
+        Some(_) => return err_to_ret(RxStatus::ERR_NOT_SUPPORTED),
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
+    };
+
+    match result {
+        Ok(()) => ok_to_ret(0),
+        Err(e) => err_to_ret(crate::fs::errno_to_rxstatus(e)),
+    }
+}
+
+/// List a directory
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///   arg1: pointer to output buffer
+///   arg2: buffer length
+///   arg3: cookie (0 for the first call)
+///
+/// Returns: bytes written (0 once the directory is exhausted), or negative
+/// error code
+///
+/// The buffer is filled with [`Dirent`](crate::fs::vfs::Dirent) records,
+/// each followed by its NUL-terminated name and padded to 8 bytes. Pass
+/// the last record's `next_cookie` to continue. Fails with
+/// `ERR_INVALID_ARGS` if the buffer cannot hold the next record.
+fn sys_readdir(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, vfs};
+
+    let path = match read_user_path(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    let buf = args.user_slice(1, 2);
+    let cookie = args.arg_u64(3);
+
+    let entries = match vfs::read_dir(&path) {
+        Ok(e) => e,
+        Err(e) => return err_to_ret(errno_to_rxstatus(e)),
+    };
+    let records = vfs::encode_dirents(&entries, cookie, buf.len());
+    if records.is_empty() && cookie < entries.len() as u64 {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS); // buffer too small
+    }
+
+    match buf.write(&records) {
+        Ok(n) => ok_to_ret(n),
+        Err(e) => err_to_ret(e),
+    }
+}
+
+/// Get metadata of a file or directory by path
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///   arg1: pointer to a [`Stat`](crate::fs::vfs::Stat) receiving the metadata
+///
+/// Returns: 0 on success, or negative error code
+///
+/// A symbolic link is followed; `LSTAT` describes the link itself.
+fn sys_stat(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, vfs};
+
+    let path = match read_user_path(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    match vfs::stat(&path) {
+        Ok(st) => SyscallResult::out(args.user_ptr(1), &st).into_ret(),
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Get metadata of a path without following a final symbolic link
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///   arg1: pointer to a [`Stat`](crate::fs::vfs::Stat) receiving the metadata
+///
+/// Returns: 0 on success, or negative error code
+fn sys_lstat(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, vfs};
+
+    let path = match read_user_path_nofollow(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    match vfs::stat(&path) {
+        Ok(st) => SyscallResult::out(args.user_ptr(1), &st).into_ret(),
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Get metadata of an open file
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///   arg1: pointer to a [`Stat`](crate::fs::vfs::Stat) receiving the metadata
+///
+/// Returns: 0 on success, or negative error code
+///
+/// The standard streams report the console TTY. Pipes have no metadata
+/// and fail with `ERR_NOT_SUPPORTED`.
+fn sys_fstat(args: SyscallArgs) -> SyscallRet {
+    use crate::drivers::tty::CONSOLE_TTY;
+    use crate::fs::{devfs, errno_to_rxstatus, procfs, tmpfs, vfs, DevNode};
+    use crate::syscall::fd::FdKind;
+
+    let fd = args.arg(0) as u8;
+    let kind = crate::process::table::with_current_process_mut(|p| {
+        p.fd_table.get(fd).map(|f| f.kind)
+    });
+    let result = match kind.flatten() {
+        Some(FdKind::Stdin | FdKind::Stdout | FdKind::Stderr) => {
+            Ok(devfs::stat(DevNode::Tty(CONSOLE_TTY as u8)))
+        }
+        Some(FdKind::File { inode, .. }) => vfs::ramdisk_stat(inode),
+        Some(FdKind::Tty { tty }) => Ok(devfs::stat(DevNode::Tty(tty))),
+        Some(FdKind::Proc { node, .. }) => Ok(procfs::stat(node)),
+        Some(FdKind::Block { dev, .. }) => Ok(devfs::stat(DevNode::Block(dev))),
+        Some(FdKind::Tmp { inode, .. }) => tmpfs::with(|fs| fs.stat(inode)),
+        Some(FdKind::Mounted { mount, inode, .. }) => crate::fs::mount::with(mount, |fs| fs.stat(inode)),
+        Some(FdKind::Pipe { .. }) => return err_to_ret(RxStatus::ERR_NOT_SUPPORTED),
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
+    };
+
+    match result {
+        Ok(st) => SyscallResult::out(args.user_ptr(1), &st).into_ret(),
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
+
+/// Create a pipe
+///
+/// Arguments:
+///   arg0: pointer to an [`FdPair`] receiving the read and write ends
+///
+/// Returns: 0, or negative error code
+///
+/// See [`pipe`] for blocking and end-of-file behaviour.
+fn sys_pipe(args: SyscallArgs) -> SyscallRet {
+    let out = args.user_ptr::<FdPair>(0);
+    let pair = match crate::process::table::with_current_process_mut(|p| pipe::create(&mut p.fd_table)) {
+        Some(Ok(pair)) => pair,
+        Some(Err(e)) => return err_to_ret(e),
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+
+    let result = SyscallResult::out(out, &pair);
+    if !result.is_ok() {
+        // Nobody learns the descriptors; don't leak the pipe
+        crate::process::table::with_current_process_mut(|p| {
+            p.fd_table.close(pair.read_fd as u8);
+            p.fd_table.close(pair.write_fd as u8);
+        });
+    }
+    result.into_ret()
+}
+
+/// Duplicate a file descriptor
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///
+/// Returns: the lowest free fd, now a copy of arg0, or negative error code
+///
+/// The copy keeps the original's flags (except `O_CLOEXEC`) and offset,
+/// but they are not shared afterwards (see [`fd`]).
+fn sys_dup(args: SyscallArgs) -> SyscallRet {
+    let fd = args.arg(0);
+    if fd > u8::MAX as usize {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS); // EBADF
+    }
+    let result = crate::process::table::with_current_process_mut(|p| {
+        if p.fd_table.get(fd as u8).is_none() {
+            return Err(RxStatus::ERR_INVALID_ARGS); // EBADF
+        }
+        p.fd_table.dup(fd as u8).map(usize::from).ok_or(RxStatus::ERR_NO_MEMORY)
+    });
+    SyscallResult::from(result.unwrap_or(Err(RxStatus::ERR_INVALID_ARGS))).into_ret()
+}
+
+/// Duplicate a file descriptor onto a chosen number
+///
+/// Arguments:
+///   arg0: file descriptor to copy (old fd)
+///   arg1: file descriptor to replace (new fd, closed first if open)
+///
+/// Returns: the new fd, or negative error code
+///
+/// Unlike `CLOSE`, this may replace fds 0-2: `DUP2(pipe, 1)` sends the
+/// caller's (and its `SPAWN` children's) standard output into a pipe.
+fn sys_dup2(args: SyscallArgs) -> SyscallRet {
+    let (old_fd, new_fd) = (args.arg(0), args.arg(1));
+    if old_fd > u8::MAX as usize || new_fd > u8::MAX as usize {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS); // EBADF
+    }
+    let result = crate::process::table::with_current_process_mut(|p| {
+        p.fd_table.dup2(old_fd as u8, new_fd as u8).map(usize::from)
+    });
+    match result.flatten() {
+        Some(fd) => ok_to_ret(fd),
+        None => err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
+    }
+}
+
+/// Change the current working directory
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///
+/// Returns: 0 on success, or negative error code
+///
+/// The path must name a directory. Threads share their process's
+/// directory, and `SPAWN`/`FORK` children start in it.
+fn sys_chdir(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, vfs};
+    use crate::fs::ramdisk::Errno;
+
+    let path = match read_user_path(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    match vfs::stat(&path) {
+        Ok(st) if st.kind == vfs::DT_DIR => {}
+        Ok(_) => return err_to_ret(errno_to_rxstatus(Errno::ENOTDIR)),
+        Err(e) => return err_to_ret(errno_to_rxstatus(e)),
+    }
+    match crate::process::table::with_current_process_mut(|p| p.cwd = path) {
+        Some(()) => ok_to_ret(0),
+        None => err_to_ret(RxStatus::ERR_INVALID_ARGS),
     }
 }
 
-/// Open a file from the ramdisk
+/// Get the current working directory
 ///
 /// Arguments:
-///   arg0: pointer to path string (null-terminated, userspace)
-///   arg1: flags (O_RDONLY, O_WRONLY, O_RDWR)
+///   arg0: pointer to buffer (userspace)
+///   arg1: buffer length
 ///
-/// Returns: file descriptor number, or negative error code
+/// Returns: length of the path (without the terminating NUL), or negative
+/// error code
 ///
-/// Phase 5C: This opens files from the embedded ramdisk filesystem.
-/// The path must be a null-terminated string in userspace memory.
-fn sys_open(args: SyscallArgs) -> SyscallRet {
-    use crate::fs::ramdisk::{self, Errno};
-    use crate::syscall::fd::{FdKind, flags};
-    use crate::process::table::PROCESS_TABLE;
+/// The path is absolute and null-terminated. A buffer too small for it
+/// fails with `ERR_INVALID_ARGS` and is left untouched.
+fn sys_getcwd(args: SyscallArgs) -> SyscallRet {
+    let buf = args.user_slice(0, 1);
+    let Some(mut path) = crate::process::table::with_current_process(|p| p.cwd.clone()) else {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    };
+    let len = path.len();
+    path.push('\0');
+    if buf.len() < path.len() {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    }
+    match buf.write(path.as_bytes()) {
+        Ok(_) => ok_to_ret(len),
+        Err(e) => err_to_ret(e),
+    }
+}
 
-    let path_ptr = args.arg_u64(0) as *const u8;
-    let flags_val = args.arg_u32(1);
+/// Create a symbolic link
+///
+/// Arguments:
+///   arg0: pointer to the target string (null-terminated, userspace)
+///   arg1: pointer to the link's path (null-terminated, userspace)
+///
+/// Returns: 0 on success, or negative error code
+///
+/// The target is stored as given, relative or not, and need not exist.
+/// Links can only be created under `/tmp`.
+fn sys_symlink(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, vfs};
 
-    // Validate path pointer
-    if path_ptr.is_null() {
+    let target_ptr = args.user_ptr::<u8>(0);
+    if target_ptr.is_null() {
         return err_to_ret(RxStatus::ERR_INVALID_ARGS);
     }
-
-    // Read null-terminated path string from userspace (max 256 bytes)
-    let mut path_bytes = alloc::vec::Vec::new();
-    unsafe {
-        let mut i = 0;
-        loop {
-            if i >= 256 {
-                return err_to_ret(RxStatus::ERR_INVALID_ARGS); // Path too long
-            }
-            let c = *path_ptr.add(i);
-            if c == 0 {
-                break;
-            }
-            path_bytes.push(c);
-            i += 1;
-        }
+    let target = match target_ptr.read_str(vfs::PATH_MAX) {
+        Ok(bytes) => bytes,
+        Err(e) => return err_to_ret(e),
+    };
+    let Ok(target) = core::str::from_utf8(&target) else {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS);
+    };
+    let path = match read_user_path_nofollow(args.user_ptr(1)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    match vfs::symlink(target, &path) {
+        Ok(()) => ok_to_ret(0),
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
     }
+}
 
-    // Convert to string
-    let path = match core::str::from_utf8(&path_bytes) {
-        Ok(s) => s,
-        Err(_) => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+/// Read the target of a symbolic link
+///
+/// Arguments:
+///   arg0: pointer to path string (null-terminated, userspace)
+///   arg1: pointer to buffer (userspace)
+///   arg2: buffer length
+///
+/// Returns: number of bytes copied, or negative error code
+///
+/// As with POSIX `readlink`, no NUL is added and a target longer than the
+/// buffer is cut short.
+fn sys_readlink(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, vfs};
+
+    let path = match read_user_path_nofollow(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
     };
-
-    // Look up file in ramdisk
-    let ramdisk_file = {
-        let ramdisk = match ramdisk::get_ramdisk() {
-            Ok(r) => r,
-            Err(e) => {
-                // Convert Errno to RxStatus
-                return err_to_ret(match e {
-                    Errno::ENODEV => RxStatus::ERR_NOT_FOUND,
-                    _ => RxStatus::ERR_INVALID_ARGS,
-                });
-            }
-        };
-
-        match ramdisk.find_file(path) {
-            Some(f) => f,
-            None => return err_to_ret(RxStatus::ERR_NOT_FOUND), // ENOENT
-        }
+    let target = match vfs::readlink(&path) {
+        Ok(target) => target,
+        Err(e) => return err_to_ret(errno_to_rxstatus(e)),
     };
+    match args.user_slice(1, 2).write(target.as_bytes()) {
+        Ok(n) => ok_to_ret(n),
+        Err(e) => err_to_ret(e),
+    }
+}
 
-    // Get the current process and allocate fd
-    let fd_result = {
-        let mut table = PROCESS_TABLE.lock();
-        let current = match table.current_mut() {
-            Some(p) => p,
-            None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
-        };
-
-        // Find the inode (file index) for offset tracking
-        let inode = {
-            let ramdisk = match ramdisk::get_ramdisk() {
-                Ok(r) => r,
-                Err(_) => return err_to_ret(RxStatus::ERR_NOT_FOUND),
-            };
+/// Create a hard link
+///
+/// Arguments:
+///   arg0: pointer to the existing path (null-terminated, userspace)
+///   arg1: pointer to the new path (null-terminated, userspace)
+///
+/// Returns: 0 on success, or negative error code
+///
+/// Both paths must be under `/tmp`. A symbolic link as the last component
+/// of the existing path is linked itself, not followed.
+fn sys_link(args: SyscallArgs) -> SyscallRet {
+    use crate::fs::{errno_to_rxstatus, vfs};
+
+    let old = match read_user_path_nofollow(args.user_ptr(0)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    let new = match read_user_path_nofollow(args.user_ptr(1)) {
+        Ok(p) => p,
+        Err(e) => return err_to_ret(e),
+    };
+    match vfs::link(&old, &new) {
+        Ok(()) => ok_to_ret(0),
+        Err(e) => err_to_ret(errno_to_rxstatus(e)),
+    }
+}
 
-            let files = unsafe {
-                let base = ramdisk.data.as_ptr().add(ramdisk.superblock.files_offset as usize);
-                let count = ramdisk.superblock.num_files as usize;
-                core::slice::from_raw_parts(base as *const ramdisk::RamdiskFile, count)
-            };
+/// Wrap an open file descriptor in a handle
+///
+/// Arguments:
+///   arg0: file descriptor (fd)
+///
+/// Returns: handle to a file object, or negative error code
+///
+/// The descriptor stays open. The handle can be sent over a channel and
+/// turned back into a descriptor with `HANDLE_TO_FD`; it has READ and
+/// WRITE as the descriptor's access mode allows. See [`file`].
+fn sys_fd_to_handle(args: SyscallArgs) -> SyscallRet {
+    let fd = args.arg(0);
+    if fd > u8::MAX as usize {
+        return err_to_ret(RxStatus::ERR_INVALID_ARGS); // EBADF
+    }
+    let result = crate::process::table::with_current_process_mut(|p| {
+        file::to_handle(&p.fd_table, &mut p.handles, fd as u8)
+    });
+    SyscallResult::from(result.unwrap_or(Err(RxStatus::ERR_INVALID_ARGS)).map(|h| h as usize)).into_ret()
+}
 
-            // Find the index of this file
-            files.iter().position(|&f| {
-                f.data_offset == ramdisk_file.data_offset &&
-                f.name_offset == ramdisk_file.name_offset
-            }).unwrap_or(0) as u32
-        };
+/// Install the descriptor held by a file handle
+///
+/// Arguments:
+///   arg0: file handle (needs READ or WRITE)
+///   arg1: descriptor flags (`O_CLOEXEC` or 0)
+///
+/// Returns: the new fd (lowest free), or negative error code
+///
+/// The handle is closed on success. The descriptor is opened for the
+/// access the handle's rights still allow.
+fn sys_handle_to_fd(args: SyscallArgs) -> SyscallRet {
+    let result = crate::process::table::with_current_process_mut(|p| {
+        file::from_handle(&mut p.handles, &mut p.fd_table, args.arg_u32(0), args.arg_u32(1))
+    });
+    SyscallResult::from(result.unwrap_or(Err(RxStatus::ERR_INVALID_ARGS)).map(usize::from)).into_ret()
+}
 
-        // Allocate file descriptor
-        match current.fd_table.alloc(
-            FdKind::File {
-                inode,
-                offset: 0,
-            },
-            flags_val,
-        ) {
-            Some(fd) => fd as usize,
-            None => return err_to_ret(RxStatus::ERR_NO_MEMORY), // EMFILE
-        }
-    };
+/// Get the syscall ABI version and what this kernel supports
+///
+/// Arguments:
+///   arg0: pointer to a `SystemFeatures` output struct
+///
+/// Returns: 0, or negative error code
+///
+/// The struct holds the ABI version, a bitmap of implemented syscalls and
+/// feature bits; see [`features`].
+fn sys_system_get_features(args: SyscallArgs) -> SyscallRet {
+    SyscallResult::out(args.user_ptr(0), &features::current()).into_ret()
+}
 
-    ok_to_ret(fd_result)
+/// Create an I/O port range (privileged)
+///
+/// Arguments:
+///   arg0: first port
+///   arg1: number of ports
+///
+/// Returns: handle to the range, or negative error code
+///
+/// The range must lie below `IOPB_PORTS` and must not include a port the
+/// kernel drives itself. Hand it to a driver, which enables it with
+/// `IOPORT_ENABLE`. See [`crate::object::ioport`].
+fn sys_ioport_create(args: SyscallArgs) -> SyscallRet {
+    use crate::object::IoPortRange;
+
+    let privileged = crate::process::table::PROCESS_TABLE.lock().current().map(|p| p.privileged);
+    match privileged {
+        Some(true) => {}
+        Some(false) => return err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    }
+    match IoPortRange::create(args.arg_u32(0), args.arg_u32(1)) {
+        Ok(ports) => create_handle(ports),
+        Err(e) => err_to_ret(e),
+    }
 }
 
-/// Close a file descriptor
+/// Enable or disable an I/O port range for the calling process
 ///
 /// Arguments:
-///   arg0: file descriptor (fd)
+///   arg0: I/O port range handle (needs READ and WRITE)
+///   arg1: 1 to enable, 0 to disable
 ///
-/// Returns: 0 on success, or negative error code
+/// Returns: 0, or negative error code
 ///
-/// Phase 5C: This closes files and releases the file descriptor.
-/// stdin/stdout/stderr cannot be closed.
-fn sys_close(args: SyscallArgs) -> SyscallRet {
+/// Applies to every thread of the process: at once for the caller, and
+/// at their next switch for threads running on other CPUs.
+fn sys_ioport_enable(args: SyscallArgs) -> SyscallRet {
     use crate::process::table::PROCESS_TABLE;
 
-    let fd = args.arg(0) as u8;
+    let ports = match lookup::<crate::object::IoPortRange>(args.arg_u32(0), Rights::READ | Rights::WRITE) {
+        Ok(ports) => ports,
+        Err(e) => return err_to_ret(e),
+    };
+    let enable = match args.arg(1) {
+        0 => false,
+        1 => true,
+        _ => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
 
     let mut table = PROCESS_TABLE.lock();
-    let current = match table.current_mut() {
-        Some(p) => p,
-        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    let Some(pid) = table.current_pid() else {
+        return err_to_ret(RxStatus::ERR_NOT_FOUND);
     };
-
-    match current.fd_table.close(fd) {
-        Some(_) => ok_to_ret(0),
-        None => err_to_ret(RxStatus::ERR_INVALID_ARGS), // EBADF
+    let Some(mut grants) = table.get(pid).map(|p| p.io_ports) else {
+        return err_to_ret(RxStatus::ERR_NOT_FOUND);
+    };
+    if enable {
+        if !grants.add(ports.range()) {
+            return err_to_ret(RxStatus::ERR_NO_MEMORY);
+        }
+    } else {
+        grants.remove(ports.range());
     }
+    for tid in core::iter::once(pid).chain(table.threads_of(pid)) {
+        if let Some(thread) = table.get_mut(tid) {
+            thread.io_ports = grants;
+        }
+    }
+    // SAFETY: this CPU, before the return to ring 3
+    unsafe { crate::arch::amd64::descriptor::load_io_ports(crate::arch::amd64::entry::this_cpu(), &grants) };
+    ok_to_ret(0)
 }
 
 /// Seek to a position in a file
@@ -1263,6 +4080,24 @@ fn sys_lseek(args: SyscallArgs) -> SyscallRet {
 
                 (offset, file.size as i64)
             }
+            FdKind::Proc { node, offset } => {
+                (offset, crate::fs::procfs::generate_for(node, current).len() as i64)
+            }
+            FdKind::Block { dev, offset } => {
+                (offset, crate::drivers::block::get(dev as usize).map_or(0, |d| d.size()) as i64)
+            }
+            FdKind::Tmp { inode, offset } => {
+                match crate::fs::tmpfs::with(|fs| fs.size(inode)) {
+                    Ok(size) => (offset, size as i64),
+                    Err(e) => return err_to_ret(crate::fs::errno_to_rxstatus(e)),
+                }
+            }
+            FdKind::Mounted { mount, inode, offset } => {
+                match crate::fs::mount::with(mount, |fs| fs.size(inode)) {
+                    Ok(size) => (offset, size as i64),
+                    Err(e) => return err_to_ret(crate::fs::errno_to_rxstatus(e)),
+                }
+            }
             _ => {
                 // Cannot seek on stdin/stdout/stderr
                 return err_to_ret(RxStatus::ERR_INVALID_ARGS); // ESPIPE
@@ -1315,8 +4150,15 @@ fn sys_lseek(args: SyscallArgs) -> SyscallRet {
         };
 
         if let Some(fd_entry) = current.fd_table.get_mut(fd) {
-            if let FdKind::File { ref mut offset, .. } = fd_entry.kind {
-                *offset = clamped_offset;
+            match fd_entry.kind {
+                FdKind::File { ref mut offset, .. }
+                | FdKind::Proc { ref mut offset, .. }
+                | FdKind::Tmp { ref mut offset, .. }
+                | FdKind::Mounted { ref mut offset, .. }
+                | FdKind::Block { ref mut offset, .. } => {
+                    *offset = clamped_offset;
+                }
+                _ => {}
             }
         }
     }
@@ -1366,6 +4208,135 @@ fn sys_getppid(_args: SyscallArgs) -> SyscallRet {
     }
 }
 
+/// Enter or leave the deadline scheduling class
+///
+/// Arguments:
+///   arg0: period in nanoseconds (0 = leave the class)
+///   arg1: budget (CPU time per period) in nanoseconds
+///   arg2: relative deadline in nanoseconds
+///
+/// Returns: 0 on success, or negative error code
+///
+/// Requires `budget <= deadline <= period`. Fails with
+/// `ERR_ACCESS_DENIED` if the caller's job policy does not allow the
+/// class, and with `ERR_NO_MEMORY` if admission control rejects it; see
+/// [`crate::sched::deadline`].
+fn sys_sched_deadline(args: SyscallArgs) -> SyscallRet {
+    use crate::process::table::PROCESS_TABLE;
+    use crate::sched::deadline::{self, DeadlineParams};
+    use crate::sched::round_robin;
+    use crate::time::{Duration, Instant};
+
+    let pid = match round_robin::get_current_pid() {
+        Some(pid) => pid,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+
+    let mut table = PROCESS_TABLE.lock();
+    let process = match table.get_mut(pid) {
+        Some(process) => process,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+
+    if args.arg(0) == 0 {
+        deadline::leave(process);
+        return ok_to_ret(0);
+    }
+
+    let params = DeadlineParams {
+        period: Duration::from_nanos(args.arg(0) as u64),
+        budget: Duration::from_nanos(args.arg(1) as u64),
+        deadline: Duration::from_nanos(args.arg(2) as u64),
+    };
+    SyscallResult::from(deadline::enter(process, params, Instant::now()).map(|_| 0)).into_ret()
+}
+
+/// Apply a suspend-count change to another process
+///
+/// The caller may target itself, its children, or any process if it is
+/// privileged.
+fn with_suspend_target(
+    pid: u32,
+    f: fn(&mut crate::process::table::Process) -> Result<(), RxStatus>,
+) -> SyscallRet {
+    use crate::process::table::PROCESS_TABLE;
+    use crate::sched::round_robin;
+
+    let caller = match round_robin::get_current_pid() {
+        Some(pid) => pid,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+
+    let mut table = PROCESS_TABLE.lock();
+    let privileged = table.get(caller).is_some_and(|p| p.privileged);
+    let target = match table.get_mut(pid) {
+        Some(target) => target,
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+    if pid != caller && target.ppid != caller && !privileged {
+        return err_to_ret(RxStatus::ERR_ACCESS_DENIED);
+    }
+
+    SyscallResult::from(f(target).map(|_| 0)).into_ret()
+}
+
+/// Suspend a process
+///
+/// Arguments:
+///   arg0: target PID
+///
+/// Returns: 0 on success, or negative error code
+///
+/// Adds a suspend request. The target stops at its next return to
+/// userspace (a self-suspend stops on return from this call) and stays
+/// stopped until every request is dropped with `PROCESS_RESUME`.
+fn sys_process_suspend(args: SyscallArgs) -> SyscallRet {
+    with_suspend_target(args.arg_u32(0), crate::sched::suspend::suspend)
+}
+
+/// Resume a process
+///
+/// Arguments:
+///   arg0: target PID
+///
+/// Returns: 0 on success, or negative error code (`ERR_INVALID_ARGS` if
+/// the target has no suspend request, `ERR_NOT_FOUND` if it exited)
+fn sys_process_resume(args: SyscallArgs) -> SyscallRet {
+    with_suspend_target(args.arg_u32(0), crate::sched::suspend::resume)
+}
+
+/// Power off or reboot the machine
+///
+/// Arguments:
+///   arg0: action (1 = power off, 2 = reboot)
+///
+/// Returns: does not return on success, or negative error code
+///
+/// Only privileged processes may call this. Every other process is
+/// terminated and the filesystems and devices are shut down first; see
+/// [`crate::shutdown`]. Fails with `ERR_BUSY` if a shutdown is already
+/// under way.
+fn sys_power(args: SyscallArgs) -> SyscallRet {
+    use crate::shutdown::{self, PowerAction};
+
+    let action = match PowerAction::from_raw(args.arg_u32(0)) {
+        Some(action) => action,
+        None => return err_to_ret(RxStatus::ERR_INVALID_ARGS),
+    };
+
+    let privileged = crate::process::table::with_current_process_mut(|p| (p.pid, p.privileged));
+    let pid = match privileged {
+        Some((pid, true)) => pid,
+        Some((_, false)) => return err_to_ret(RxStatus::ERR_ACCESS_DENIED),
+        None => return err_to_ret(RxStatus::ERR_NOT_FOUND),
+    };
+
+    if !shutdown::begin(Some(pid)) {
+        return err_to_ret(RxStatus::ERR_BUSY);
+    }
+    shutdown::shutdown(action)
+}
+
 /// Yield CPU to scheduler
 ///
 /// Arguments: none
@@ -1380,24 +4351,7 @@ fn sys_yield(_args: SyscallArgs) -> SyscallRet {
     match round_robin::yield_cpu() {
         Ok(()) => ok_to_ret(0),
         Err(e) => {
-            // Debug output
-            let msg = b"[YIELD] Failed: ";
-            for &b in msg {
-                unsafe {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-            }
-            for b in e.as_bytes() {
-                unsafe {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") *b, options(nomem, nostack));
-                }
-            }
-            let msg = b"\n";
-            for &b in msg {
-                unsafe {
-                    core::arch::asm!("out dx, al", in("dx") 0xE9u16, in("al") b, options(nomem, nostack));
-                }
-            }
+            kwarn!("[YIELD] Failed: {}", e);
             err_to_ret(RxStatus::ERR_INVALID_ARGS)
         }
     }
@@ -1418,6 +4372,33 @@ pub fn init() {
 /// ============================================================================
 
 /// System call numbers (Stable v1)
+///
+/// # Return Values
+///
+/// Every syscall returns a single `isize` in the return register:
+/// non-negative on success, a negated [`RxStatus`] on failure.
+///
+/// - **Register outputs**: syscalls that produce one value (a PID, a
+///   handle, a byte count, a time) return it directly in the register.
+/// - **Memory outputs**: syscalls that produce more than one value take
+///   a pointer to an output struct as their *last* argument. The kernel
+///   writes the struct with the uaccess layer and returns 0. Nothing is
+///   written on failure.
+///
+/// | Syscall | Output | Out-struct argument |
+/// |---------|--------|---------------------|
+/// | `CHANNEL_CREATE` | [`HandlePair`](super::HandlePair) | arg1 (`options` in arg0) |
+/// | `CHANNEL_READ` | [`ChannelActual`](super::ChannelActual), byte count also in the register | arg5 (optional) |
+/// | `CHANNEL_READV` | [`ChannelActual`](super::ChannelActual), byte count also in the register | arg5 (optional) |
+/// | `EVENTPAIR_CREATE` | [`HandlePair`](super::HandlePair) | arg1 (`options` in arg0) |
+/// | `RINGBUF_CREATE` | [`HandlePair`](super::HandlePair) (ring, VMO) | arg3 |
+/// | `PIPE` | [`FdPair`](super::FdPair) | arg0 |
+/// | `KOBJECT_STATS` | [`KobjectStats`](crate::object::KobjectStats) | arg1 |
+/// | `OBJECT_WAIT_MANY` | `observed` of each [`WaitItem`](super::WaitItem), count in the register | arg0 (also the input) |
+/// | `PORT_WAIT` | [`PortPacket`](crate::object::PortPacket) | arg2 |
+/// | `SYSTEM_GET_FEATURES` | [`SystemFeatures`](super::features::SystemFeatures) | arg0 |
+///
+/// [`RxStatus`]: crate::arch::amd64::mm::RxStatus
 pub mod number {
     /// Process & Thread (0x01-0x0F)
     pub const PROCESS_CREATE: u32 = 0x01;
@@ -1427,6 +4408,9 @@ pub mod number {
     pub const THREAD_EXIT: u32 = 0x05;
     pub const PROCESS_EXIT: u32 = 0x06;
     pub const HANDLE_CLOSE: u32 = 0x07;
+    pub const FORK: u32 = 0x08;  // Duplicate the calling process
+    pub const WAIT_PID: u32 = 0x09;  // Wait for a child to exit and reap it
+    pub const SPAWN_FDS: u32 = 0x0A;  // Spawn, passing the listed descriptors
 
     /// Memory / VMO (0x10-0x1F)
     pub const VMO_CREATE: u32 = 0x10;
@@ -1436,6 +4420,7 @@ pub mod number {
     pub const VMAR_MAP: u32 = 0x14;
     pub const VMAR_UNMAP: u32 = 0x15;
     pub const VMAR_PROTECT: u32 = 0x16;
+    pub const VMO_CREATE_FROM_FD: u32 = 0x17;
 
     /// IPC & Sync (0x20-0x2F)
     pub const CHANNEL_CREATE: u32 = 0x20;
@@ -1446,20 +4431,36 @@ pub mod number {
     pub const OBJECT_SIGNAL: u32 = 0x25;
     pub const OBJECT_WAIT_ONE: u32 = 0x26;
     pub const OBJECT_WAIT_MANY: u32 = 0x27;
+    pub const CHANNEL_WRITEV: u32 = 0x28;
+    pub const CHANNEL_READV: u32 = 0x29;
+    pub const SEMAPHORE_CREATE: u32 = 0x2A;
+    pub const RINGBUF_CREATE: u32 = 0x2B;  // Ring buffer fed by a kernel event source
+    pub const FUTEX: u32 = 0x2C;  // Sleep on or wake a userspace word
+    pub const PORT_CREATE: u32 = 0x2D;
+    pub const PORT_WAIT: u32 = 0x2E;
+    pub const OBJECT_WAIT_ASYNC: u32 = 0x2F;  // Bind an object's signals to a port
 
     /// Jobs & Handles (0x30-0x3F)
     pub const JOB_CREATE: u32 = 0x30;
     pub const HANDLE_DUPLICATE: u32 = 0x31;
     pub const HANDLE_TRANSFER: u32 = 0x32;
+    pub const OBJECT_SET_PROPERTY: u32 = 0x33;
+    pub const OBJECT_GET_INFO: u32 = 0x34;
+    pub const JOB_KILL: u32 = 0x35;  // Kill every process in a job
 
     /// Time (0x40-0x4F)
     pub const CLOCK_GET: u32 = 0x40;
     pub const TIMER_CREATE: u32 = 0x41;
     pub const TIMER_SET: u32 = 0x42;
     pub const TIMER_CANCEL: u32 = 0x43;
+    pub const NANOSLEEP: u32 = 0x44;
 
     /// Debug (0x50-0x5F)
     pub const DEBUG_WRITE: u32 = 0x50;
+    pub const KCOUNTERS_MAP: u32 = 0x51;  // Map kernel counters page (privileged)
+    pub const KOBJECT_STATS: u32 = 0x52;
+    pub const PT_DUMP: u32 = 0x53;
+    pub const DEBUG_READ: u32 = 0x54;  // Read the kernel log (root job with DEBUG)
 
     /// I/O (0x60-0x6F) - Phase 5A
     pub const WRITE: u32 = 0x60;
@@ -1467,14 +4468,73 @@ pub mod number {
     pub const OPEN: u32 = 0x62;
     pub const CLOSE: u32 = 0x63;
     pub const LSEEK: u32 = 0x64;
+    pub const CLIPBOARD_GET: u32 = 0x65;  // Read the VT paste buffer
+    pub const CLIPBOARD_SET: u32 = 0x66;  // Replace the VT paste buffer
+    pub const WRITEV: u32 = 0x67;
+    pub const READV: u32 = 0x68;
+    pub const TTY_SET_BUFFERING: u32 = 0x69;  // Line-buffer or unbuffer TTY output
+    pub const FCNTL: u32 = 0x6A;  // Get/set file descriptor flags (O_NONBLOCK)
+    pub const UNLINK: u32 = 0x6B;  // Remove a tmpfs or mounted file
+    pub const MKDIR: u32 = 0x6C;  // Create a tmpfs or mounted directory
+    pub const RMDIR: u32 = 0x6D;  // Remove an empty tmpfs or mounted directory
+    pub const FTRUNCATE: u32 = 0x6E;  // Set the size of a tmpfs or mounted file
+    pub const READDIR: u32 = 0x6F;  // List a directory
 
     /// Process Info (0x70-0x7F) - Phase 5A
     pub const GETPID: u32 = 0x70;
     pub const GETPPID: u32 = 0x71;
     pub const YIELD: u32 = 0x72;
+    pub const SCHED_DEADLINE: u32 = 0x73;  // Enter/leave the deadline class
+    pub const PROCESS_SUSPEND: u32 = 0x74;  // Add a suspend request (by PID)
+    pub const PROCESS_RESUME: u32 = 0x75;   // Drop a suspend request (by PID)
+    pub const POWER: u32 = 0x76;  // Power off or reboot (privileged)
+
+    /// I/O, continued (0x80-0x8F)
+    pub const STAT: u32 = 0x80;  // File metadata by path
+    pub const FSTAT: u32 = 0x81;  // File metadata of an open descriptor
+    pub const PIPE: u32 = 0x82;  // Create a pipe (two descriptors)
+    pub const DUP: u32 = 0x83;  // Copy a descriptor to the lowest free fd
+    pub const DUP2: u32 = 0x84;  // Copy a descriptor onto a chosen fd
+    pub const CHDIR: u32 = 0x85;  // Change the current working directory
+    pub const GETCWD: u32 = 0x86;  // Get the current working directory
+    pub const SYMLINK: u32 = 0x87;  // Create a symbolic link
+    pub const READLINK: u32 = 0x88;  // Read a symbolic link's target
+    pub const LINK: u32 = 0x89;  // Create a hard link
+    pub const LSTAT: u32 = 0x8A;  // File metadata without following a final link
+    pub const FD_TO_HANDLE: u32 = 0x8B;  // Wrap a descriptor in a handle
+    pub const HANDLE_TO_FD: u32 = 0x8C;  // Install a file handle as a descriptor
+    pub const VT_CONTROL: u32 = 0x8D;  // Redraw VTs, hand the display to a compositor, set the palette
+    pub const MOUNT: u32 = 0x8E;  // Mount a filesystem (privileged)
+    pub const UMOUNT: u32 = 0x8F;  // Unmount a filesystem (privileged)
+
+    /// System (0x90-0x9F)
+    pub const SYSTEM_GET_FEATURES: u32 = 0x90;  // ABI version and supported syscalls
+    pub const IOPORT_CREATE: u32 = 0x91;  // Create an I/O port range (privileged)
+    pub const IOPORT_ENABLE: u32 = 0x92;  // Enable/disable a port range for the caller
 
     /// Maximum defined syscall number
-    pub const MAX_SYSCALL: u32 = 0x72;
+    pub const MAX_SYSCALL: u32 = 0x92;
+
+    /// Every syscall number above, in order
+    pub const ALL: &[u32] = &[
+        PROCESS_CREATE, PROCESS_START, SPAWN, THREAD_START, THREAD_EXIT, PROCESS_EXIT, HANDLE_CLOSE,
+        FORK, WAIT_PID, SPAWN_FDS,
+        VMO_CREATE, VMO_READ, VMO_WRITE, VMO_CLONE, VMAR_MAP, VMAR_UNMAP, VMAR_PROTECT,
+        VMO_CREATE_FROM_FD,
+        CHANNEL_CREATE, CHANNEL_WRITE, CHANNEL_READ, EVENT_CREATE, EVENTPAIR_CREATE, OBJECT_SIGNAL,
+        OBJECT_WAIT_ONE, OBJECT_WAIT_MANY, CHANNEL_WRITEV, CHANNEL_READV, SEMAPHORE_CREATE,
+        RINGBUF_CREATE, FUTEX, PORT_CREATE, PORT_WAIT, OBJECT_WAIT_ASYNC,
+        JOB_CREATE, HANDLE_DUPLICATE, HANDLE_TRANSFER, OBJECT_SET_PROPERTY, OBJECT_GET_INFO,
+        JOB_KILL,
+        CLOCK_GET, TIMER_CREATE, TIMER_SET, TIMER_CANCEL, NANOSLEEP,
+        DEBUG_WRITE, KCOUNTERS_MAP, KOBJECT_STATS, PT_DUMP, DEBUG_READ,
+        WRITE, READ, OPEN, CLOSE, LSEEK, CLIPBOARD_GET, CLIPBOARD_SET, WRITEV, READV,
+        TTY_SET_BUFFERING, FCNTL, UNLINK, MKDIR, RMDIR, FTRUNCATE, READDIR,
+        GETPID, GETPPID, YIELD, SCHED_DEADLINE, PROCESS_SUSPEND, PROCESS_RESUME, POWER,
+        STAT, FSTAT, PIPE, DUP, DUP2, CHDIR, GETCWD, SYMLINK, READLINK, LINK, LSTAT, FD_TO_HANDLE,
+        HANDLE_TO_FD, VT_CONTROL, MOUNT, UMOUNT,
+        SYSTEM_GET_FEATURES, IOPORT_CREATE, IOPORT_ENABLE,
+    ];
 }
 
 #[cfg(test)]
@@ -1498,6 +4558,106 @@ mod tests {
         assert_eq!(ok_to_ret_isize(100), 100);
     }
 
+    #[test]
+    fn test_syscall_result_packing() {
+        assert_eq!(SyscallResult::Value(7).into_ret(), 7);
+        assert_eq!(SyscallResult::OK.into_ret(), 0);
+        assert_eq!(SyscallResult::Written.into_ret(), 0);
+        assert_eq!(
+            SyscallResult::Error(RxStatus::ERR_NOT_FOUND).into_ret(),
+            err_to_ret(RxStatus::ERR_NOT_FOUND)
+        );
+        assert_eq!(
+            SyscallResult::Value(usize::MAX).into_ret(),
+            err_to_ret(RxStatus::ERR_INTERNAL)
+        );
+        assert_eq!(
+            SyscallResult::out(UserPtr::new(0), &HandlePair::default()),
+            SyscallResult::Error(RxStatus::ERR_INVALID_ARGS)
+        );
+    }
+
+    #[test]
+    fn test_out_struct_layout() {
+        assert_eq!(core::mem::size_of::<HandlePair>(), 8);
+        assert_eq!(core::mem::size_of::<ChannelActual>(), 8);
+        assert_eq!(core::mem::size_of::<FdPair>(), 8);
+        assert_eq!(core::mem::size_of::<crate::object::KobjectStats>(), 96);
+        assert_eq!(core::mem::size_of::<crate::object::ResourceLimits>(), 40);
+        assert_eq!(core::mem::size_of::<WaitItem>(), 12);
+        assert_eq!(core::mem::size_of::<crate::object::PortPacket>(), 16);
+    }
+
+    fn event(signaled: bool, flags: crate::object::EventFlags) -> crate::object::KernelObject {
+        crate::object::KernelObject::Event(Arc::new(crate::object::Event::new(signaled, flags)))
+    }
+
+    fn wait_items(count: usize) -> alloc::vec::Vec<WaitItem> {
+        (0..count).map(|i| WaitItem { handle: i as u32 + 1, signals: EVENT_SIGNALED, observed: 0 }).collect()
+    }
+
+    #[test]
+    fn test_wait_many_mixed_readiness() {
+        use crate::object::{EventFlags, KernelObject, Semaphore};
+
+        let objects = [
+            event(false, EventFlags::empty),
+            event(true, EventFlags::empty),
+            event(true, EventFlags::MANUAL_RESET),
+            KernelObject::Semaphore(Arc::new(Semaphore::new(1, 4).unwrap())),
+        ];
+        let mut items = wait_items(objects.len());
+
+        // Every ready object is taken in one pass
+        assert_eq!(poll_items(&objects, &mut items), 3);
+        let observed: alloc::vec::Vec<u32> = items.iter().map(|i| i.observed).collect();
+        assert_eq!(observed, [0, EVENT_SIGNALED, EVENT_SIGNALED, EVENT_SIGNALED]);
+
+        // The auto-reset event and the semaphore were consumed; the
+        // manual-reset event stays signaled
+        assert_eq!(poll_items(&objects, &mut items), 1);
+        let observed: alloc::vec::Vec<u32> = items.iter().map(|i| i.observed).collect();
+        assert_eq!(observed, [0, 0, EVENT_SIGNALED, 0]);
+    }
+
+    #[test]
+    fn test_wait_many_none_ready_clears_observed() {
+        use crate::object::EventFlags;
+
+        let objects = [event(false, EventFlags::empty), event(false, EventFlags::MANUAL_RESET)];
+        let mut items = wait_items(objects.len());
+        items[1].observed = EVENT_SIGNALED;
+
+        assert_eq!(poll_items(&objects, &mut items), 0);
+        assert!(items.iter().all(|i| i.observed == 0));
+    }
+
+    #[test]
+    fn test_waitable() {
+        use crate::object::{EventFlags, KernelObject, Vmo, VmoFlags};
+
+        assert!(waitable(&event(false, EventFlags::empty)));
+        let vmo = KernelObject::Vmo(Arc::new(Vmo::create(4096, VmoFlags::empty).unwrap()));
+        assert!(!waitable(&vmo));
+        assert!(!try_acquire(&vmo));
+        assert_eq!(signals_of(&vmo), 0);
+    }
+
+    #[test]
+    fn test_signals_of_takes_nothing() {
+        use crate::object::{EventFlags, KernelObject, Semaphore};
+
+        let auto = event(true, EventFlags::empty);
+        let semaphore = KernelObject::Semaphore(Arc::new(Semaphore::new(1, 1).unwrap()));
+        for object in [&auto, &semaphore] {
+            assert_eq!(signals_of(object), EVENT_SIGNALED);
+            assert_eq!(signals_of(object), EVENT_SIGNALED);
+            assert!(try_acquire(object));
+            assert_eq!(signals_of(object), 0);
+        }
+        assert_eq!(signals_of(&event(false, EventFlags::MANUAL_RESET)), 0);
+    }
+
     #[test]
     fn test_syscall_numbers() {
         assert_eq!(number::PROCESS_CREATE, 0x01);
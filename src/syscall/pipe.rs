@@ -0,0 +1,104 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Pipe Syscalls
+//!
+//! Descriptor side of `sys_pipe`, and of `READ`/`WRITE` on pipe ends.
+//! Userspace memory is copied before [`write`] touches the pipe and after
+//! [`read`] takes bytes from it, never while a lock is held.
+//!
+//! # Blocking
+//!
+//! A read waits while the pipe is empty and a write end is open; a write
+//! waits until there is room. Like TTY reads, a waiting call yields the
+//! CPU and retries. With `O_NONBLOCK` it fails with `ERR_SHOULD_WAIT`
+//! instead, or returns the bytes already written.
+//!
+//! # Errors
+//!
+//! | Condition | Status |
+//! |-----------|--------|
+//! | Would block on an `O_NONBLOCK` descriptor | `ERR_SHOULD_WAIT` |
+//! | Write with no read end left | `ERR_IO` |
+//! | Read from the write end, or write to the read end | `ERR_ACCESS_DENIED` |
+//! | Descriptor table full | `ERR_NO_MEMORY` |
+
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::pipe::{self, PipeId, PIPE_CAPACITY};
+use super::fd::{flags, FdKind, FileDescriptor, FileDescriptorTable};
+use super::uaccess::UserSlice;
+use super::FdPair;
+
+/// Map a pipe error to a syscall status
+pub fn status(err: &'static str) -> RxStatus {
+    match err {
+        pipe::ERR_SHOULD_WAIT => RxStatus::ERR_SHOULD_WAIT,
+        pipe::ERR_PEER_CLOSED => RxStatus::ERR_IO,
+        _ => RxStatus::ERR_INTERNAL,
+    }
+}
+
+/// Create a pipe and install both ends in `fds`
+///
+/// The read end is opened `O_RDONLY`, the write end `O_WRONLY`.
+pub fn create(fds: &mut FileDescriptorTable) -> Result<FdPair, RxStatus> {
+    let pipe_id = pipe::create();
+    // The descriptors own the end references the pipe starts with;
+    // dropping one that does not fit releases its end
+    let read = FileDescriptor::new(FdKind::Pipe { read_end: true, pipe_id }, flags::O_RDONLY);
+    let write = FileDescriptor::new(FdKind::Pipe { read_end: false, pipe_id }, flags::O_WRONLY);
+    let Some(read_fd) = fds.insert(read) else {
+        drop(write);
+        return Err(RxStatus::ERR_NO_MEMORY);
+    };
+    let Some(write_fd) = fds.insert(write) else {
+        fds.close(read_fd);
+        return Err(RxStatus::ERR_NO_MEMORY);
+    };
+    Ok(FdPair { read_fd: read_fd as i32, write_fd: write_fd as i32 })
+}
+
+/// Read from a pipe's read end into `buf`
+///
+/// Returns 0 at end of file.
+pub fn read(pipe_id: PipeId, nonblocking: bool, buf: UserSlice) -> Result<usize, RxStatus> {
+    let pipe = pipe::get(pipe_id).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+    let mut data = alloc::vec![0u8; buf.len().min(PIPE_CAPACITY)];
+    loop {
+        match pipe.read(&mut data) {
+            Ok(n) => return buf.write(&data[..n]),
+            Err(pipe::ERR_SHOULD_WAIT) if !nonblocking => {
+                let _ = crate::sched::round_robin::yield_cpu();
+            }
+            Err(e) => return Err(status(e)),
+        }
+    }
+}
+
+/// Write `buf` to a pipe's write end
+///
+/// A blocking write returns once every byte is buffered. An error after
+/// some bytes were written returns the bytes written instead.
+pub fn write(pipe_id: PipeId, nonblocking: bool, buf: UserSlice) -> Result<usize, RxStatus> {
+    let pipe = pipe::get(pipe_id).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+    let data = buf.read_to_vec()?;
+    let mut done = 0;
+    loop {
+        match pipe.write(&data[done..]) {
+            Ok(n) => {
+                done += n;
+                if done == data.len() {
+                    return Ok(done);
+                }
+            }
+            Err(pipe::ERR_SHOULD_WAIT) if !nonblocking => {
+                let _ = crate::sched::round_robin::yield_cpu();
+            }
+            Err(_) if done > 0 => return Ok(done),
+            Err(e) => return Err(status(e)),
+        }
+    }
+}
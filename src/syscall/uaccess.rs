@@ -0,0 +1,755 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! User Memory Access
+//!
+//! This module provides the kernel's accessors for userspace memory.
+//!
+//! # Pointer Types
+//!
+//! User addresses reach syscall handlers as [`UserPtr<T>`] and
+//! [`UserSlice`] (see [`SyscallArgs::user_ptr`] and
+//! [`SyscallArgs::user_slice`]). Neither can be dereferenced: the only way
+//! to reach the memory behind them is through their `read`/`write`
+//! methods, which validate the range and copy through the fault-safe
+//! path. The raw copy functions are private to this module.
+//!
+//! [`SyscallArgs::user_ptr`]: super::SyscallArgs::user_ptr
+//! [`SyscallArgs::user_slice`]: super::SyscallArgs::user_slice
+//!
+//! # Validation
+//!
+//! Every access checks that:
+//! - The pointer is not null
+//! - The range does not overflow
+//! - The whole range lies in the user half of the address space
+//! - No page of the range is mapped for the kernel only
+//! - The pointer is suitably aligned for typed accesses
+//!
+//! The kernel image and its identity-mapped memory also live in the lower
+//! half, as supervisor pages. The copies run in kernel mode, where those
+//! pages are readable and writable, so each page of the range is looked
+//! up in the active page table (the calling process's) first
+//! ([`check_user_pages`]). Pages that are not mapped yet are allowed
+//! through: they are either demand-paged VMO mappings, committed by the
+//! fault path during the copy, or nothing, which the copy reports as a
+//! fault.
+//!
+//! # Faults
+//!
+//! A valid range can still be unmapped by another thread before the copy
+//! runs. All copies go through [`extable::copy_user`], whose page faults
+//! are recovered by the fault handler and reported as
+//! `ERR_INVALID_ARGS` instead of halting the kernel. Callers that support
+//! short transfers use `read_partial`/`write_partial` on [`UserSlice`],
+//! which return the bytes copied before the fault instead.
+//!
+//! # In-Place Reads
+//!
+//! Output that only passes through the kernel (console writes) can skip
+//! the copy: [`UserSlice::for_each_mapped`] hands out the user pages
+//! themselves, read through the kernel's direct map, as long as they stay
+//! mapped. It stops at the first page that is not mapped yet; the caller
+//! copies that one through the fault-safe path, which faults it in or
+//! fails, and carries on after it.
+//!
+//! # I/O Vectors
+//!
+//! The vectored syscalls (`READV`, `WRITEV`, `CHANNEL_READV`,
+//! `CHANNEL_WRITEV`) take an array of [`IoVec`] entries. [`UserIoVec`]
+//! copies the array in and validates it up front: at most [`IOV_MAX`]
+//! entries, every non-empty buffer inside userspace, and a total length
+//! that neither overflows nor exceeds the caller's limit.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use crate::arch::amd64::extable;
+use crate::arch::amd64::mm::{PAddr, RxStatus};
+
+/// Highest valid userspace address (exclusive upper bound)
+///
+/// This is the end of the canonical lower half on x86_64 (see
+/// [`layout::USER_END`](crate::arch::amd64::mm::layout::USER_END)).
+pub const USER_ADDR_END: usize = crate::arch::amd64::mm::layout::USER_END as usize;
+
+/// Granularity of the kernel-page check and of in-place reads
+const PAGE_SIZE: usize = 4096;
+
+/// Validate a userspace range
+///
+/// # Returns
+///
+/// `Ok(())` if `[addr, addr + len)` is a non-null range within
+/// userspace, `ERR_INVALID_ARGS` otherwise
+pub fn validate_user_range(addr: usize, len: usize) -> Result<(), RxStatus> {
+    if addr == 0 {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    let end = addr.checked_add(len).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+    if end > USER_ADDR_END {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    Ok(())
+}
+
+/// Check that no page of `[addr, addr + len)` is kernel-only
+///
+/// `ERR_INVALID_ARGS` if one is. The range must already have passed
+/// [`validate_user_range`]. User pages are never turned into kernel pages,
+/// so the answer cannot change between the check and the copy.
+pub fn check_user_pages(addr: usize, len: usize) -> Result<(), RxStatus> {
+    use crate::process::address_space::{user_access, UserAccess};
+
+    // Host unit tests pass their own (unmapped-for-user) buffers
+    if cfg!(test) || len == 0 {
+        return Ok(());
+    }
+    let page_table = crate::arch::amd64::mmu::read_cr3() & !0xFFF;
+    let first = addr & !(PAGE_SIZE - 1);
+    let last = (addr + len - 1) & !(PAGE_SIZE - 1);
+    for page in (first..=last).step_by(PAGE_SIZE) {
+        if unsafe { user_access(page_table, page as u64) } == UserAccess::Supervisor {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+    }
+    Ok(())
+}
+
+/// Validate a userspace range and check it holds no kernel pages
+fn check_user_range(addr: usize, len: usize) -> Result<(), RxStatus> {
+    validate_user_range(addr, len)?;
+    check_user_pages(addr, len)
+}
+
+// ============================================================================
+// User Pointers
+// ============================================================================
+
+/// A pointer to a `T` in userspace
+///
+/// Only the address is stored; reads and writes copy through the
+/// uaccess layer. `T` must be valid for any bit pattern (plain integers
+/// and `repr(C)` structs of them).
+#[repr(transparent)]
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+impl<T> core::fmt::Debug for UserPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "UserPtr({:#x})", self.addr)
+    }
+}
+
+impl<T> PartialEq for UserPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr == other.addr
+    }
+}
+
+impl<T> Eq for UserPtr<T> {}
+
+impl<T: Copy> UserPtr<T> {
+    /// Wrap a userspace address
+    pub const fn new(addr: usize) -> Self {
+        Self { addr, _marker: PhantomData }
+    }
+
+    /// The raw address (for logging and mapping, not for access)
+    pub const fn addr(self) -> usize {
+        self.addr
+    }
+
+    /// Check for a null pointer
+    pub const fn is_null(self) -> bool {
+        self.addr == 0
+    }
+
+    /// Copy the value out of userspace
+    pub fn read(self) -> Result<T, RxStatus> {
+        read_user(self.addr)
+    }
+
+    /// Copy a value into userspace
+    pub fn write(self, value: &T) -> Result<(), RxStatus> {
+        write_user(self.addr, value)
+    }
+}
+
+impl UserPtr<u8> {
+    /// Copy a NUL-terminated string out of userspace
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - Maximum length, including the terminator
+    ///
+    /// # Returns
+    ///
+    /// The bytes before the terminator, or `ERR_INVALID_ARGS` if the
+    /// string is longer than `max` or unreadable
+    pub fn read_str(self, max: usize) -> Result<Vec<u8>, RxStatus> {
+        read_user_str(self.addr, max)
+    }
+}
+
+impl UserPtr<usize> {
+    /// Copy a NULL-terminated array of string pointers (`argv`-style)
+    /// out of userspace
+    ///
+    /// A null array is an empty list.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_count` - Maximum number of strings
+    /// * `max_total` - Maximum size of the strings, their terminators and
+    ///   the pointers to them
+    ///
+    /// # Returns
+    ///
+    /// The strings, or `ERR_INVALID_ARGS` if a limit is exceeded or any
+    /// part is unreadable
+    pub fn read_str_array(self, max_count: usize, max_total: usize) -> Result<Vec<Vec<u8>>, RxStatus> {
+        let mut strings = Vec::new();
+        if self.is_null() {
+            return Ok(strings);
+        }
+        let mut total = 0usize;
+        for i in 0..=max_count {
+            let entry = self.addr.checked_add(i * core::mem::size_of::<usize>()).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+            let ptr = UserPtr::<usize>::new(entry).read()?;
+            if ptr == 0 {
+                return Ok(strings);
+            }
+            if i == max_count {
+                break;
+            }
+            let s = UserPtr::<u8>::new(ptr).read_str(max_total - total)?;
+            total += s.len() + 1 + core::mem::size_of::<usize>();
+            if total > max_total {
+                break;
+            }
+            strings.push(s);
+        }
+        Err(RxStatus::ERR_INVALID_ARGS)
+    }
+}
+
+/// A byte buffer in userspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserSlice {
+    addr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    /// Wrap a userspace buffer
+    pub const fn new(addr: usize, len: usize) -> Self {
+        Self { addr, len }
+    }
+
+    /// The raw address (for logging, not for access)
+    pub const fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Length in bytes
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check for an empty buffer
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The first `len` bytes (or the whole buffer if shorter)
+    pub const fn truncate(self, len: usize) -> Self {
+        Self { addr: self.addr, len: if len < self.len { len } else { self.len } }
+    }
+
+    /// The bytes after the first `n` (empty if the buffer is shorter)
+    pub const fn skip(self, n: usize) -> Self {
+        let n = if n < self.len { n } else { self.len };
+        Self { addr: self.addr + n, len: self.len - n }
+    }
+
+    /// The bytes up to the end of the first page
+    pub const fn first_page(self) -> Self {
+        self.truncate(PAGE_SIZE - self.addr % PAGE_SIZE)
+    }
+
+    /// Copy the buffer into `dst`
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes copied: the smaller of both lengths
+    pub fn read(self, dst: &mut [u8]) -> Result<usize, RxStatus> {
+        let n = core::cmp::min(self.len, dst.len());
+        copy_from_user(&mut dst[..n], self.addr)?;
+        Ok(n)
+    }
+
+    /// Copy the buffer into a new vector
+    pub fn read_to_vec(self) -> Result<Vec<u8>, RxStatus> {
+        validate_user_range(self.addr, self.len)?;
+        let mut data = alloc::vec![0u8; self.len];
+        copy_from_user(&mut data, self.addr)?;
+        Ok(data)
+    }
+
+    /// Copy `src` into the buffer
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes copied: the smaller of both lengths
+    pub fn write(self, src: &[u8]) -> Result<usize, RxStatus> {
+        let n = core::cmp::min(self.len, src.len());
+        copy_to_user(self.addr, &src[..n])?;
+        Ok(n)
+    }
+
+    /// Copy the buffer into `dst`, stopping at the first fault
+    ///
+    /// Unlike [`read`](Self::read), a page fault part-way through is not
+    /// an error: the bytes before the faulting page are kept.
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes copied, which may be short of the smaller of both
+    /// lengths, or `ERR_INVALID_ARGS` if the range is not in userspace
+    pub fn read_partial(self, dst: &mut [u8]) -> Result<usize, RxStatus> {
+        let n = core::cmp::min(self.len, dst.len());
+        copy_from_user_partial(&mut dst[..n], self.addr)
+    }
+
+    /// Copy `src` into the buffer, stopping at the first fault
+    ///
+    /// See [`read_partial`](Self::read_partial).
+    pub fn write_partial(self, src: &[u8]) -> Result<usize, RxStatus> {
+        let n = core::cmp::min(self.len, src.len());
+        copy_to_user_partial(self.addr, &src[..n])
+    }
+
+    /// Read the buffer in bounded chunks
+    ///
+    /// The buffer is copied through a small stack buffer and handed to
+    /// `f` piece by piece. Chunks already passed to `f` stay consumed if a
+    /// later chunk faults.
+    pub fn for_each_chunk(self, f: impl FnMut(&[u8])) -> Result<(), RxStatus> {
+        for_each_chunk(self.addr, self.len, f)
+    }
+
+    /// Pass the buffer to `f` in place, up to a page at a time
+    ///
+    /// Nothing is copied: each piece is the user page itself, read through
+    /// the kernel's direct map. The walk stops at the first page that is
+    /// not mapped for user mode ([`user_translate`]).
+    ///
+    /// # Safety
+    ///
+    /// `page_table` must be the calling process's, and its pages must stay
+    /// mapped while `f` runs. Pages are only unmapped under the process
+    /// table lock, so holding it is enough.
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes passed to `f`, or `ERR_INVALID_ARGS` if the range is
+    /// not in userspace
+    ///
+    /// [`user_translate`]: crate::process::address_space::user_translate
+    pub unsafe fn for_each_mapped(self, page_table: PAddr, mut f: impl FnMut(&[u8])) -> Result<usize, RxStatus> {
+        use crate::process::address_space::user_translate;
+
+        validate_user_range(self.addr, self.len)?;
+        // Host unit tests have no user page tables: copy everything
+        if cfg!(test) {
+            return Ok(0);
+        }
+        let mut done = 0;
+        while done < self.len {
+            let page = self.skip(done).first_page();
+            let Some(paddr) = user_translate(page_table, page.addr as u64) else { break };
+            let kernel = crate::mm::pmm::paddr_to_vaddr_user_zone(paddr) as *const u8;
+            f(core::slice::from_raw_parts(kernel, page.len));
+            done += page.len;
+        }
+        Ok(done)
+    }
+}
+
+// ============================================================================
+// I/O Vectors
+// ============================================================================
+
+/// Most entries in one iovec array
+pub const IOV_MAX: usize = 16;
+
+/// One entry of a userspace iovec array
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoVec {
+    /// Buffer address
+    pub base: usize,
+    /// Buffer length in bytes
+    pub len: usize,
+}
+
+/// A validated list of userspace buffers
+///
+/// Empty entries are dropped; the rest keep their order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserIoVec {
+    slices: Vec<UserSlice>,
+    total: usize,
+}
+
+impl UserIoVec {
+    /// Copy in and validate an iovec array
+    ///
+    /// # Arguments
+    ///
+    /// * `array` - Userspace address of the entries
+    /// * `count` - Number of entries (at most [`IOV_MAX`])
+    /// * `max_total` - Largest total length the caller accepts
+    ///
+    /// # Returns
+    ///
+    /// `ERR_INVALID_ARGS` if the array is unreadable or any check fails
+    pub fn read(array: UserPtr<IoVec>, count: usize, max_total: usize) -> Result<Self, RxStatus> {
+        if count > IOV_MAX {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        let mut entries = [IoVec::default(); IOV_MAX];
+        for (i, entry) in entries[..count].iter_mut().enumerate() {
+            let addr = array.addr() + i * core::mem::size_of::<IoVec>();
+            *entry = UserPtr::<IoVec>::new(addr).read()?;
+        }
+        Self::from_entries(&entries[..count], max_total)
+    }
+
+    /// Validate entries already copied in
+    pub fn from_entries(entries: &[IoVec], max_total: usize) -> Result<Self, RxStatus> {
+        if entries.len() > IOV_MAX {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        let mut slices = Vec::with_capacity(entries.len());
+        let mut total = 0usize;
+        for entry in entries.iter().filter(|e| e.len > 0) {
+            validate_user_range(entry.base, entry.len)?;
+            total = total.checked_add(entry.len).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+            slices.push(UserSlice::new(entry.base, entry.len));
+        }
+        if total > max_total {
+            return Err(RxStatus::ERR_INVALID_ARGS);
+        }
+        Ok(Self { slices, total })
+    }
+
+    /// The non-empty buffers, in order
+    pub fn slices(&self) -> &[UserSlice] {
+        &self.slices
+    }
+
+    /// Total length in bytes
+    pub fn len(&self) -> usize {
+        self.total
+    }
+
+    /// Check for no bytes at all
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Gather all buffers into a new vector
+    pub fn read_to_vec(&self) -> Result<Vec<u8>, RxStatus> {
+        let mut data = Vec::with_capacity(self.total);
+        for slice in &self.slices {
+            data.extend_from_slice(&slice.read_to_vec()?);
+        }
+        Ok(data)
+    }
+
+    /// Scatter `src` across the buffers in order
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes copied: the smaller of `src.len()` and [`len`](Self::len)
+    pub fn write(&self, mut src: &[u8]) -> Result<usize, RxStatus> {
+        let mut done = 0;
+        for slice in &self.slices {
+            if src.is_empty() {
+                break;
+            }
+            let n = slice.write(src)?;
+            src = &src[n..];
+            done += n;
+        }
+        Ok(done)
+    }
+}
+
+// ============================================================================
+// Raw Copies
+// ============================================================================
+
+/// Copy bytes out of userspace
+///
+/// # Arguments
+///
+/// * `dst` - Kernel destination buffer
+/// * `src` - Userspace source address (`dst.len()` bytes are read)
+fn copy_from_user(dst: &mut [u8], src: usize) -> Result<(), RxStatus> {
+    if dst.is_empty() {
+        return Ok(());
+    }
+    check_user_range(src, dst.len())?;
+    match unsafe { extable::copy_user(dst.as_mut_ptr(), src as *const u8, dst.len()) } {
+        0 => Ok(()),
+        _ => Err(RxStatus::ERR_INVALID_ARGS),
+    }
+}
+
+/// Copy bytes into userspace
+///
+/// # Arguments
+///
+/// * `dst` - Userspace destination address (`src.len()` bytes are written)
+/// * `src` - Kernel source buffer
+fn copy_to_user(dst: usize, src: &[u8]) -> Result<(), RxStatus> {
+    if src.is_empty() {
+        return Ok(());
+    }
+    check_user_range(dst, src.len())?;
+    match unsafe { extable::copy_user(dst as *mut u8, src.as_ptr(), src.len()) } {
+        0 => Ok(()),
+        _ => Err(RxStatus::ERR_INVALID_ARGS),
+    }
+}
+
+/// Copy bytes out of userspace up to the first fault
+///
+/// # Returns
+///
+/// Number of bytes copied
+fn copy_from_user_partial(dst: &mut [u8], src: usize) -> Result<usize, RxStatus> {
+    if dst.is_empty() {
+        return Ok(0);
+    }
+    check_user_range(src, dst.len())?;
+    let left = unsafe { extable::copy_user(dst.as_mut_ptr(), src as *const u8, dst.len()) };
+    Ok(dst.len() - left)
+}
+
+/// Copy bytes into userspace up to the first fault
+///
+/// # Returns
+///
+/// Number of bytes copied
+fn copy_to_user_partial(dst: usize, src: &[u8]) -> Result<usize, RxStatus> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    check_user_range(dst, src.len())?;
+    let left = unsafe { extable::copy_user(dst as *mut u8, src.as_ptr(), src.len()) };
+    Ok(src.len() - left)
+}
+
+/// Copy a NUL-terminated string out of userspace
+///
+/// # Arguments
+///
+/// * `addr` - Userspace address of the string
+/// * `max` - Maximum length, including the terminator
+///
+/// # Returns
+///
+/// The bytes before the terminator, or `ERR_INVALID_ARGS` if the string
+/// is longer than `max` or unreadable
+fn read_user_str(addr: usize, max: usize) -> Result<Vec<u8>, RxStatus> {
+    let mut bytes = Vec::new();
+    for i in 0..max {
+        let c: u8 = read_user(addr.checked_add(i).ok_or(RxStatus::ERR_INVALID_ARGS)?)?;
+        if c == 0 {
+            return Ok(bytes);
+        }
+        bytes.push(c);
+    }
+    Err(RxStatus::ERR_INVALID_ARGS)
+}
+
+/// Read a userspace buffer in bounded chunks
+///
+/// Large buffers (e.g. `write()` payloads) are copied through a small
+/// stack buffer and handed to `f` piece by piece. Chunks already passed
+/// to `f` stay consumed if a later chunk faults.
+///
+/// # Arguments
+///
+/// * `src` - Userspace source address
+/// * `len` - Total number of bytes
+/// * `f` - Called with each chunk in order
+fn for_each_chunk(src: usize, len: usize, mut f: impl FnMut(&[u8])) -> Result<(), RxStatus> {
+    const CHUNK: usize = 256;
+    let mut buf = [0u8; CHUNK];
+    let mut done = 0;
+    while done < len {
+        let n = core::cmp::min(len - done, CHUNK);
+        copy_from_user(&mut buf[..n], src + done)?;
+        f(&buf[..n]);
+        done += n;
+    }
+    Ok(())
+}
+
+/// Copy a value into userspace
+///
+/// # Arguments
+///
+/// * `addr` - Userspace destination address
+/// * `value` - Value to write
+fn write_user<T: Copy>(addr: usize, value: &T) -> Result<(), RxStatus> {
+    if !addr.is_multiple_of(core::mem::align_of::<T>()) {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    let bytes = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    copy_to_user(addr, bytes)
+}
+
+/// Copy a value out of userspace
+///
+/// # Arguments
+///
+/// * `addr` - Userspace source address
+///
+/// `T` must be valid for any bit pattern (plain integers and `repr(C)`
+/// structs of them).
+fn read_user<T: Copy>(addr: usize) -> Result<T, RxStatus> {
+    if !addr.is_multiple_of(core::mem::align_of::<T>()) {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, core::mem::size_of::<T>())
+    };
+    copy_from_user(bytes, addr)?;
+    Ok(unsafe { value.assume_init() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_user_range() {
+        assert!(validate_user_range(0x1000, 16).is_ok());
+        assert_eq!(validate_user_range(0, 16), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(validate_user_range(USER_ADDR_END - 8, 16), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(validate_user_range(usize::MAX, 2), Err(RxStatus::ERR_INVALID_ARGS));
+        assert!(validate_user_range(USER_ADDR_END - 16, 16).is_ok());
+    }
+
+    #[test]
+    fn test_copy_round_trip() {
+        let src = *b"hello";
+        let mut dst = [0u8; 5];
+        copy_to_user(dst.as_mut_ptr() as usize, &src).unwrap();
+        assert_eq!(&dst, b"hello");
+
+        let mut back = [0u8; 5];
+        copy_from_user(&mut back, dst.as_ptr() as usize).unwrap();
+        assert_eq!(&back, b"hello");
+        assert_eq!(copy_from_user(&mut back, 0), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_user_ptr() {
+        let mut slot = 0u64;
+        let ptr = UserPtr::<u64>::new(&mut slot as *mut u64 as usize);
+        ptr.write(&0xdead_beef).unwrap();
+        assert_eq!(ptr.read(), Ok(0xdead_beef));
+        assert!(UserPtr::<u64>::new(0).is_null());
+        assert_eq!(UserPtr::<u64>::new(ptr.addr() + 1).read(), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_user_slice() {
+        let mut buf = [0u8; 4];
+        let slice = UserSlice::new(buf.as_mut_ptr() as usize, buf.len());
+        assert_eq!(slice.write(b"rustux"), Ok(4));
+        assert_eq!(slice.truncate(2).read_to_vec().unwrap(), b"ru");
+
+        let mut out = [0u8; 8];
+        assert_eq!(slice.read(&mut out), Ok(4));
+        assert_eq!(&out[..4], b"rust");
+    }
+
+    #[test]
+    fn test_user_slice_partial() {
+        let mut buf = [0u8; 4];
+        let slice = UserSlice::new(buf.as_mut_ptr() as usize, buf.len());
+        assert_eq!(slice.write_partial(b"abcdef"), Ok(4));
+        let mut out = [0u8; 2];
+        assert_eq!(slice.read_partial(&mut out), Ok(2));
+        assert_eq!(&out, b"ab");
+        assert_eq!(UserSlice::new(0, 0).read_partial(&mut out), Ok(0));
+        assert_eq!(UserSlice::new(0, 2).read_partial(&mut out), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_iovec_validation() {
+        let ok = [IoVec { base: 0x1000, len: 8 }, IoVec { base: 0, len: 0 }, IoVec { base: 0x3000, len: 4 }];
+        let iov = UserIoVec::from_entries(&ok, 64).unwrap();
+        assert_eq!(iov.len(), 12);
+        assert_eq!(iov.slices().len(), 2);
+
+        assert_eq!(UserIoVec::from_entries(&ok, 11), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(
+            UserIoVec::from_entries(&[IoVec { base: 0, len: 1 }], 64),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            UserIoVec::from_entries(&[IoVec { base: USER_ADDR_END - 4, len: 8 }], 64),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+        assert_eq!(
+            UserIoVec::from_entries(&[IoVec::default(); IOV_MAX + 1], 64),
+            Err(RxStatus::ERR_INVALID_ARGS)
+        );
+    }
+
+    #[test]
+    fn test_iovec_scatter_gather() {
+        let (mut a, mut b) = ([0u8; 3], [0u8; 4]);
+        let entries = [
+            IoVec { base: a.as_mut_ptr() as usize, len: a.len() },
+            IoVec { base: b.as_mut_ptr() as usize, len: b.len() },
+        ];
+        let array = UserPtr::<IoVec>::new(entries.as_ptr() as usize);
+        let iov = UserIoVec::read(array, entries.len(), 64).unwrap();
+
+        assert_eq!(iov.write(b"rustux"), Ok(6));
+        assert_eq!((&a, &b[..3]), (b"rus", &b"tux"[..]));
+        assert_eq!(iov.read_to_vec().unwrap(), b"rustux\0");
+    }
+
+    #[test]
+    fn test_read_user_str() {
+        let s = *b"/bin/sh\0";
+        assert_eq!(read_user_str(s.as_ptr() as usize, 16).unwrap(), b"/bin/sh");
+        assert_eq!(read_user_str(s.as_ptr() as usize, 4), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! VMO Transfers and Mappings
+//!
+//! Copies between a [`Vmo`] and a userspace buffer for `sys_vmo_read` and
+//! `sys_vmo_write`, and the checks behind `sys_vmar_map`.
+//!
+//! # Semantics
+//!
+//! - A transfer covers `min(len, size - offset)` bytes; the number of
+//!   bytes actually moved is returned.
+//! - `offset == size` transfers nothing and returns 0 (end of object).
+//!   `offset > size` fails with `ERR_INVALID_ARGS`.
+//! - A zero-length transfer returns 0 without looking at the buffer.
+//! - The buffer range is validated up front; a range outside userspace
+//!   fails with `ERR_INVALID_ARGS` before anything is copied.
+//! - A page fault on the user buffer part-way through ends the transfer.
+//!   The bytes moved so far are returned; if none were, the call fails
+//!   with `ERR_INVALID_ARGS`.
+//!
+//! # Fault Safety
+//!
+//! Data goes through a small kernel bounce buffer, one chunk at a time,
+//! and a chunk never spans a VMO page. For writes, a chunk is copied out
+//! of userspace first and only the bytes that arrived are written to the
+//! VMO, so a fault never leaves partially copied garbage in the object.
+//! No VMO lock is held while user memory is touched.
+//!
+//! # Mappings
+//!
+//! A mapping needs `MAP` on the handle plus the right matching each
+//! protection bit (`READ`, `WRITE`, `EXECUTE`); every mapping is
+//! readable. Pages the VMO has already committed are mapped right away;
+//! the rest are committed zero-filled by the page fault handler on first
+//! touch (see [`crate::process::vmar`]). Read-only pages (file and
+//! counters VMOs) can never be mapped writable. A mapping may not overlap
+//! an existing one or the vDSO page (`ERR_BUSY`).
+
+use alloc::sync::Arc;
+use crate::arch::amd64::mm::{layout, PAddr, RxStatus};
+use crate::object::{Rights, Vmo};
+use crate::process::vmar::Vmar;
+use super::uaccess::{validate_user_range, UserSlice};
+
+/// Mapping protection: readable
+pub const PROT_READ: u32 = 0x1;
+
+/// Mapping protection: writable
+pub const PROT_WRITE: u32 = 0x2;
+
+/// Mapping protection: executable
+pub const PROT_EXEC: u32 = 0x4;
+
+/// Bounce buffer size (one chunk)
+const CHUNK: usize = 512;
+
+/// VMO page size
+const PAGE_SIZE: usize = 4096;
+
+/// Number of bytes a transfer at `offset` of `len` bytes covers
+///
+/// # Returns
+///
+/// `min(len, size - offset)`, or `ERR_INVALID_ARGS` if `offset` is past
+/// the end of the object
+pub fn transfer_len(size: usize, offset: usize, len: usize) -> Result<usize, RxStatus> {
+    let available = size.checked_sub(offset).ok_or(RxStatus::ERR_INVALID_ARGS)?;
+    Ok(core::cmp::min(len, available))
+}
+
+/// Length of the next chunk: bounded by the bounce buffer and by the end
+/// of the VMO page containing `offset`
+fn chunk_len(offset: usize, remaining: usize) -> usize {
+    let in_page = PAGE_SIZE - offset % PAGE_SIZE;
+    core::cmp::min(remaining, core::cmp::min(in_page, CHUNK))
+}
+
+/// Result of a transfer that stopped after `done` bytes
+fn finish(done: usize, err: RxStatus) -> Result<usize, RxStatus> {
+    if done > 0 {
+        Ok(done)
+    } else {
+        Err(err)
+    }
+}
+
+/// Read from a VMO into a userspace buffer
+///
+/// # Arguments
+///
+/// * `vmo` - Object to read
+/// * `offset` - Byte offset within the VMO
+/// * `buf` - Destination buffer
+///
+/// # Returns
+///
+/// Number of bytes copied (see the module docs for short transfers)
+pub fn read_to_user(vmo: &Vmo, offset: usize, buf: UserSlice) -> Result<usize, RxStatus> {
+    let len = transfer_len(vmo.size(), offset, buf.len())?;
+    if len == 0 {
+        return Ok(0);
+    }
+    validate_user_range(buf.addr(), len)?;
+
+    let mut bounce = [0u8; CHUNK];
+    let mut done = 0;
+    while done < len {
+        let n = chunk_len(offset + done, len - done);
+        if vmo.read(offset + done, &mut bounce[..n]).is_err() {
+            return finish(done, RxStatus::ERR_INTERNAL);
+        }
+        let dst = UserSlice::new(buf.addr() + done, n);
+        let copied = dst.write_partial(&bounce[..n])?;
+        done += copied;
+        if copied < n {
+            return finish(done, RxStatus::ERR_INVALID_ARGS);
+        }
+    }
+    Ok(done)
+}
+
+/// Write a userspace buffer into a VMO
+///
+/// Pages are committed as they are written. If committing fails, the
+/// transfer ends early like a fault, with `ERR_NO_MEMORY` if nothing was
+/// written.
+///
+/// # Arguments
+///
+/// * `vmo` - Object to write
+/// * `offset` - Byte offset within the VMO
+/// * `buf` - Source buffer
+///
+/// # Returns
+///
+/// Number of bytes written (see the module docs for short transfers)
+pub fn write_from_user(vmo: &Vmo, offset: usize, buf: UserSlice) -> Result<usize, RxStatus> {
+    let len = transfer_len(vmo.size(), offset, buf.len())?;
+    if len == 0 {
+        return Ok(0);
+    }
+    validate_user_range(buf.addr(), len)?;
+
+    let mut bounce = [0u8; CHUNK];
+    let mut done = 0;
+    while done < len {
+        let n = chunk_len(offset + done, len - done);
+        let src = UserSlice::new(buf.addr() + done, n);
+        let copied = src.read_partial(&mut bounce[..n])?;
+
+        // Only bytes that made it out of userspace reach the VMO
+        if copied > 0 && vmo.write(offset + done, &bounce[..copied]).is_err() {
+            return finish(done, RxStatus::ERR_NO_MEMORY);
+        }
+        done += copied;
+        if copied < n {
+            return finish(done, RxStatus::ERR_INVALID_ARGS);
+        }
+    }
+    Ok(done)
+}
+
+/// Handle rights and page flags (`PF_*`) for a mapping protection
+///
+/// # Returns
+///
+/// `ERR_INVALID_ARGS` for unknown bits or a mapping that is not readable
+pub fn map_rights(prot: u32) -> Result<(Rights, u32), RxStatus> {
+    use crate::exec::elf::{PF_R, PF_W, PF_X};
+
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 || prot & PROT_READ == 0 {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    let mut rights = Rights::MAP | Rights::READ;
+    let mut flags = PF_R;
+    if prot & PROT_WRITE != 0 {
+        rights |= Rights::WRITE;
+        flags |= PF_W;
+    }
+    if prot & PROT_EXEC != 0 {
+        rights |= Rights::EXECUTE;
+        flags |= PF_X;
+    }
+    Ok((rights, flags))
+}
+
+/// Map the first `size` bytes of a VMO at `vaddr`
+///
+/// # Arguments
+///
+/// * `page_table` - Physical address of the target PML4
+/// * `vmar` - Mappings of the target process; the new one is recorded here
+/// * `vmo` - Object to map
+/// * `vaddr` - Page-aligned userspace address
+/// * `size` - Bytes to map (rounded up to whole pages, at most the VMO size)
+/// * `flags` - Page flags from [`map_rights`]
+pub fn map_into(
+    page_table: PAddr,
+    vmar: &mut Vmar,
+    vmo: Arc<Vmo>,
+    vaddr: usize,
+    size: usize,
+    flags: u32,
+) -> Result<(), RxStatus> {
+    if size == 0 || !vaddr.is_multiple_of(PAGE_SIZE) || size > vmo.size() {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    validate_user_range(vaddr, size)?;
+    // The vDSO is mapped without a VMAR entry for insert() to find
+    if layout::VDSO.overlaps(vaddr as u64, (vaddr + size) as u64) {
+        return Err(RxStatus::ERR_BUSY);
+    }
+    vmar.insert(vaddr as u64, size as u64, vmo.clone(), flags)?;
+
+    let aspace = unsafe { crate::process::AddressSpace::from_page_table(page_table) };
+    aspace.map_vmo(&vmo, vaddr as u64, size as u64, flags).map_err(|e| {
+        // Pages mapped before the failure stay; nothing faults them in again
+        vmar.remove(vaddr as u64);
+        match e {
+            "VMO page is read-only" => RxStatus::ERR_ACCESS_DENIED,
+            _ => RxStatus::ERR_NO_MEMORY,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::VmoFlags;
+
+    #[test]
+    fn test_map_rights() {
+        use crate::exec::elf::{PF_R, PF_W, PF_X};
+
+        assert_eq!(map_rights(PROT_READ), Ok((Rights::MAP | Rights::READ, PF_R)));
+        assert_eq!(
+            map_rights(PROT_READ | PROT_WRITE | PROT_EXEC),
+            Ok((Rights::MAP | Rights::READ | Rights::WRITE | Rights::EXECUTE, PF_R | PF_W | PF_X))
+        );
+        assert_eq!(map_rights(PROT_WRITE), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(map_rights(PROT_READ | 0x8), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+
+    #[test]
+    fn test_transfer_len() {
+        assert_eq!(transfer_len(8192, 0, 100), Ok(100));
+        assert_eq!(transfer_len(8192, 8000, 500), Ok(192));
+        assert_eq!(transfer_len(8192, 8192, 16), Ok(0));
+        assert_eq!(transfer_len(8192, 8193, 16), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(transfer_len(8192, 100, 0), Ok(0));
+    }
+
+    #[test]
+    fn test_chunk_len_stops_at_page_boundary() {
+        assert_eq!(chunk_len(0, 10_000), CHUNK);
+        assert_eq!(chunk_len(4000, 10_000), 96);
+        assert_eq!(chunk_len(4096, 10), 10);
+    }
+
+    #[test]
+    fn test_read_offset_at_end() {
+        let vmo = Vmo::create(4096, VmoFlags::empty).unwrap();
+        let mut buf = [0xAAu8; 16];
+        let slice = UserSlice::new(buf.as_mut_ptr() as usize, buf.len());
+        assert_eq!(read_to_user(&vmo, 4096, slice), Ok(0));
+        assert_eq!(read_to_user(&vmo, 4097, slice), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(buf, [0xAA; 16]);
+    }
+
+    #[test]
+    fn test_zero_length_ignores_buffer() {
+        let vmo = Vmo::create(4096, VmoFlags::empty).unwrap();
+        assert_eq!(read_to_user(&vmo, 0, UserSlice::new(0, 0)), Ok(0));
+        assert_eq!(write_from_user(&vmo, 0, UserSlice::new(0, 0)), Ok(0));
+    }
+
+    #[test]
+    fn test_read_cross_page() {
+        // Uncommitted pages read as zeros
+        let vmo = Vmo::create(8192, VmoFlags::empty).unwrap();
+        let mut buf = [0xAAu8; 200];
+        let slice = UserSlice::new(buf.as_mut_ptr() as usize, buf.len());
+        assert_eq!(read_to_user(&vmo, 4000, slice), Ok(200));
+        assert!(buf.iter().all(|&b| b == 0));
+
+        // Clamped at the end of the object
+        assert_eq!(read_to_user(&vmo, 8100, slice), Ok(92));
+    }
+
+    #[test]
+    fn test_rejects_kernel_buffer() {
+        let vmo = Vmo::create(4096, VmoFlags::empty).unwrap();
+        let kernel = UserSlice::new(0xFFFF_8000_0000_0000, 16);
+        assert_eq!(read_to_user(&vmo, 0, kernel), Err(RxStatus::ERR_INVALID_ARGS));
+        assert_eq!(write_from_user(&vmo, 0, kernel), Err(RxStatus::ERR_INVALID_ARGS));
+    }
+}
@@ -14,14 +14,19 @@
 #![no_std]
 
 use crate::arch::amd64;
+use crate::kinfo;
 
 /// Test kernel entry point
 ///
 /// This is called from the bootloader to test the interrupt system.
 /// It performs the following:
-/// 1. Prints a banner to QEMU debug console
+/// 1. Logs a banner
 /// 2. Tests the interrupt system (GDT, IDT, APIC, Timer)
-/// 3. Halts if test passes, loops if it fails
+/// 3. Runs the `ktest!` tests selected by `ktest.filter`, failing if
+///    they leave more kernel objects alive than before
+/// 4. Dumps the scheduler trace as Chrome trace-event JSON
+/// 5. Exits QEMU with the result (0 = pass, 1 = fail) if the
+///    `isa-debug-exit` device is present, otherwise halts
 ///
 /// # Safety
 ///
@@ -29,18 +34,34 @@ use crate::arch::amd64;
 #[no_mangle]
 pub extern "C" fn test_kernel_main() -> ! {
     // Print banner
-    qemu_print("\n");
-    qemu_print("╔══════════════════════════════════════════════════════════╗\n");
-    qemu_print("║           RUSTUX KERNEL - INTERRUPT TEST                 ║\n");
-    qemu_print("║           Testing Migrated Boot Infrastructure           ║\n");
-    qemu_print("╚══════════════════════════════════════════════════════════╝\n");
-    qemu_print("\n");
+    kinfo!("");
+    kinfo!("╔══════════════════════════════════════════════════════════╗");
+    kinfo!("║           RUSTUX KERNEL - INTERRUPT TEST                 ║");
+    kinfo!("║           Testing Migrated Boot Infrastructure           ║");
+    kinfo!("╚══════════════════════════════════════════════════════════╝");
+    kinfo!("");
+
+    // Record scheduler events for the trace dump below
+    crate::trace::enable();
 
     // Run the interrupt system test
-    crate::arch::amd64::test::test_interrupt_system();
+    let passed = crate::arch::amd64::test::test_interrupt_system()
+        & crate::arch::amd64::test::test_entry_gs_discipline();
+
+    // Run the in-kernel unit tests, then check they freed their objects
+    let objects = crate::object::metrics::Baseline::capture();
+    let passed = crate::ktest::run_all().ok() & passed;
+    let passed = objects.check() & passed;
+
+    // Dump the scheduler timeline (test-qemu.sh extracts it)
+    crate::trace::dump_chrome();
+
+    // Report the result to the host (`cargo xtask test`)
+    qemu_exit(if passed { 0 } else { 1 });
 
     // Test complete - halt
-    qemu_print("\nTest complete. Halting CPU.\n");
+    kinfo!("");
+    kinfo!("Test complete. Halting CPU.");
     loop {
         unsafe {
             core::arch::asm!("hlt", options(nostack));
@@ -48,17 +69,20 @@ pub extern "C" fn test_kernel_main() -> ! {
     }
 }
 
-/// Write a byte to QEMU's debug console
-fn qemu_print(s: &str) {
-    const QEMU_DEBUGCON_PORT: u16 = 0xE9;
-    for byte in s.bytes() {
-        unsafe {
-            core::arch::asm!(
-                "out dx, al",
-                in("dx") QEMU_DEBUGCON_PORT,
-                in("al") byte,
-                options(nostack, nomem)
-            );
-        }
+/// QEMU `isa-debug-exit` device port
+const QEMU_EXIT_PORT: u16 = 0xF4;
+
+/// Exit QEMU
+///
+/// QEMU exits with status `(code << 1) | 1`. The write is ignored when
+/// the `isa-debug-exit` device is not present.
+fn qemu_exit(code: u32) {
+    unsafe {
+        core::arch::asm!(
+            "out dx, eax",
+            in("dx") QEMU_EXIT_PORT,
+            in("eax") code,
+            options(nostack, nomem)
+        );
     }
 }
@@ -0,0 +1,192 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Clock Event Devices
+//!
+//! A clock event device interrupts after a programmed delay, once or
+//! periodically. The kernel tick, which runs the timer queue
+//! ([`super::timer_queue`]), comes from one such device. Drivers
+//! [`register`] theirs; [`start_tick`] then picks the one with the highest
+//! [`ClockEvents::rating`], falling back to the next if it cannot be
+//! programmed. `time.clockevents=<name>` tries a device by name first.
+//!
+//! | Device | Rating | Delivered to |
+//! |--------|--------|--------------|
+//! | `lapic` ([`LapicTimer`]) | 100 once calibrated | The CPU that programs it |
+//! | `hpet` ([`HpetEvents`]) | 50 when present | CPU 0, balanced like device IRQs |
+//!
+//! The Local APIC timer stops in deep C-states on CPUs without ARAT; the
+//! kernel only halts, so it is preferred anyway. The HPET takes over when
+//! it could not be calibrated.
+//!
+//! [`LapicTimer`]: crate::arch::amd64::apic::LapicTimer
+//! [`HpetEvents`]: crate::arch::amd64::hpet::HpetEvents
+
+use alloc::vec::Vec;
+use crate::arch::amd64::mm::RxStatus;
+use crate::sync::SpinMutex;
+use crate::kwarn;
+use super::Duration;
+
+/// Command line option: name of the clock event device to use for the tick
+pub const CLOCKEVENTS_OPTION: &str = "time.clockevents";
+
+/// Time between kernel ticks
+pub const TICK_PERIOD: Duration = Duration::from_millis(10);
+
+/// Most devices that can be registered
+pub const MAX_DEVICES: usize = 4;
+
+/// A programmable timer interrupt
+pub trait ClockEvents: Sync {
+    /// Name, for the boot log and `time.clockevents`
+    fn name(&self) -> &'static str;
+
+    /// Preference among devices, higher is better (0 = unusable)
+    fn rating(&self) -> u32;
+
+    /// Whether each CPU has its own device, programmed from that CPU
+    fn per_cpu(&self) -> bool;
+
+    /// Deliver `vector` every `period`
+    fn set_periodic(&self, vector: u8, period: Duration) -> Result<(), RxStatus>;
+
+    /// Deliver `vector` once, `delta` from now
+    fn set_oneshot(&self, vector: u8, delta: Duration) -> Result<(), RxStatus>;
+
+    /// Stop interrupting
+    fn shutdown(&self);
+}
+
+/// Registered devices
+static DEVICES: SpinMutex<[Option<&'static dyn ClockEvents>; MAX_DEVICES]> = SpinMutex::new([None; MAX_DEVICES]);
+
+/// The device driving the tick
+static ACTIVE: SpinMutex<Option<&'static dyn ClockEvents>> = SpinMutex::new(None);
+
+/// Make a device available to [`start_tick`]
+///
+/// # Returns
+/// `ERR_NO_MEMORY` if [`MAX_DEVICES`] are registered already
+pub fn register(device: &'static dyn ClockEvents) -> Result<(), RxStatus> {
+    let mut devices = DEVICES.lock();
+    let slot = devices.iter_mut().find(|d| d.is_none()).ok_or(RxStatus::ERR_NO_MEMORY)?;
+    *slot = Some(device);
+    Ok(())
+}
+
+/// Usable devices in the order to try them: the one named `preferred`
+/// first, then by rating
+fn candidates<'a>(devices: &[&'a dyn ClockEvents], preferred: Option<&str>) -> Vec<&'a dyn ClockEvents> {
+    let mut order: Vec<_> = devices.iter().copied().filter(|d| d.rating() > 0).collect();
+    order.sort_by_key(|d| (Some(d.name()) != preferred, core::cmp::Reverse(d.rating())));
+    order
+}
+
+/// Program the first device that accepts a periodic `vector` every `period`
+fn start_first<'a>(order: &[&'a dyn ClockEvents], vector: u8, period: Duration) -> Option<&'a dyn ClockEvents> {
+    order.iter().copied().find(|device| match device.set_periodic(vector, period) {
+        Ok(()) => true,
+        Err(e) => {
+            kwarn!("[TIME] clock event device {} failed: {:?}", device.name(), e);
+            false
+        }
+    })
+}
+
+/// Start the kernel tick on the best registered device
+///
+/// A per-CPU device only interrupts the calling CPU, so call on CPU 0.
+///
+/// # Returns
+/// The name of the device, or `ERR_NOT_FOUND` if none could be programmed
+pub fn start_tick(vector: u8, period: Duration) -> Result<&'static str, RxStatus> {
+    let devices: Vec<&'static dyn ClockEvents> = DEVICES.lock().iter().flatten().copied().collect();
+    let mut buf = [0u8; 16];
+    let preferred = crate::cmdline::get(CLOCKEVENTS_OPTION, &mut buf);
+
+    let device = start_first(&candidates(&devices, preferred), vector, period).ok_or(RxStatus::ERR_NOT_FOUND)?;
+    *ACTIVE.lock() = Some(device);
+    Ok(device.name())
+}
+
+/// The device driving the tick, once [`start_tick`] succeeded
+pub fn active() -> Option<&'static dyn ClockEvents> {
+    *ACTIVE.lock()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    struct Fake {
+        name: &'static str,
+        rating: u32,
+        works: bool,
+        vector: AtomicU8,
+    }
+
+    impl Fake {
+        const fn new(name: &'static str, rating: u32, works: bool) -> Self {
+            Self { name, rating, works, vector: AtomicU8::new(0) }
+        }
+    }
+
+    impl ClockEvents for Fake {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn rating(&self) -> u32 {
+            self.rating
+        }
+
+        fn per_cpu(&self) -> bool {
+            false
+        }
+
+        fn set_periodic(&self, vector: u8, _period: Duration) -> Result<(), RxStatus> {
+            if !self.works {
+                return Err(RxStatus::ERR_NOT_SUPPORTED);
+            }
+            self.vector.store(vector, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn set_oneshot(&self, vector: u8, delta: Duration) -> Result<(), RxStatus> {
+            self.set_periodic(vector, delta)
+        }
+
+        fn shutdown(&self) {}
+    }
+
+    fn names(order: &[&dyn ClockEvents]) -> Vec<&'static str> {
+        order.iter().map(|d| d.name()).collect()
+    }
+
+    #[test]
+    fn test_candidates_by_rating() {
+        let (lapic, hpet, dead) = (Fake::new("lapic", 100, true), Fake::new("hpet", 50, true), Fake::new("pit", 0, true));
+        let devices: [&dyn ClockEvents; 3] = [&hpet, &dead, &lapic];
+
+        assert_eq!(names(&candidates(&devices, None)), ["lapic", "hpet"]);
+        assert_eq!(names(&candidates(&devices, Some("hpet"))), ["hpet", "lapic"]);
+        // Unknown or unusable names change nothing
+        assert_eq!(names(&candidates(&devices, Some("pit"))), ["lapic", "hpet"]);
+    }
+
+    #[test]
+    fn test_start_falls_back() {
+        let (lapic, hpet) = (Fake::new("lapic", 100, false), Fake::new("hpet", 50, true));
+        let order: [&dyn ClockEvents; 2] = [&lapic, &hpet];
+
+        let started = start_first(&order, 32, TICK_PERIOD).unwrap();
+        assert_eq!(started.name(), "hpet");
+        assert_eq!(hpet.vector.load(Ordering::Relaxed), 32);
+        assert!(start_first(&order[..1], 32, TICK_PERIOD).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Clock Sources
+//!
+//! The kernel's clocks are derived from one free-running hardware
+//! counter, the clock source. On amd64 it is the TSC
+//! ([`TscClockSource`](crate::arch::amd64::tsc::TscClockSource));
+//! arm64's `CNTVCT_EL0` and riscv64's `time` CSR plug in by implementing
+//! [`ClockSource`] the same way. [`install`] selects the source at boot,
+//! once its frequency is known.
+//!
+//! # Clocks
+//!
+//! - [`monotonic_ns`]: nanoseconds since boot. Never goes backwards and
+//!   stands still while the system is suspended. [`Instant`](super::Instant)
+//!   is a point on this clock.
+//! - [`boottime_ns`]: the monotonic clock plus the time spent suspended
+//!   ([`add_suspended`]).
+//!
+//! # Conversion
+//!
+//! Counter deltas become nanoseconds by fixed-point multiplication,
+//! `ns = (cycles * mult) >> SHIFT` ([`mult_for_frequency`]), with
+//! `mult` computed once per source instead of dividing on every read.
+//! This is the same formula (and, for the TSC, the same `mult`) as the
+//! vDSO page, so userspace and kernel readings agree.
+//!
+//! Installing a source rebases the clock at the current time, so
+//! readings stay monotonic across the switch. Until the first
+//! [`install`] the amd64 TSC is read at its default frequency.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::tsc;
+
+/// Fixed-point shift of the cycles-to-nanoseconds multiplier
+pub const SHIFT: u32 = 32;
+
+/// A free-running hardware counter
+pub trait ClockSource: Sync {
+    /// Name, for the boot log
+    fn name(&self) -> &'static str;
+
+    /// Current counter value
+    fn read(&self) -> u64;
+
+    /// Counter frequency in Hz (non-zero)
+    fn frequency(&self) -> u64;
+}
+
+/// The installed clock source
+///
+/// SAFETY: written only by [`install`], which runs on the boot CPU
+/// before any other CPU or interrupt reads the clock.
+static mut SOURCE: &'static dyn ClockSource = &tsc::TSC_CLOCKSOURCE;
+
+/// `mult` of the installed source (0 until [`install`])
+static MULT: AtomicU64 = AtomicU64::new(0);
+
+/// Counter value when the source was installed
+static BASE_CYCLES: AtomicU64 = AtomicU64::new(0);
+
+/// Monotonic time when the source was installed
+static BASE_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Time spent suspended, added to the boot-time clock
+static SUSPENDED_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Compute `mult` for a counter frequency
+pub const fn mult_for_frequency(freq_hz: u64) -> u64 {
+    ((1_000_000_000u128 << SHIFT) / freq_hz as u128) as u64
+}
+
+/// Nanoseconds in `cycles` counter ticks at `mult`
+pub const fn scale(cycles: u64, mult: u64) -> u64 {
+    ((cycles as u128 * mult as u128) >> SHIFT) as u64
+}
+
+/// The installed clock source
+pub fn current() -> &'static dyn ClockSource {
+    // SAFETY: see SOURCE
+    unsafe { SOURCE }
+}
+
+/// `mult` of the installed source
+fn mult() -> u64 {
+    match MULT.load(Ordering::Acquire) {
+        0 => mult_for_frequency(current().frequency()),
+        mult => mult,
+    }
+}
+
+/// Switch the kernel's clocks to `source`
+///
+/// The monotonic clock continues from its current value.
+///
+/// # Safety
+///
+/// Boot CPU only, before other CPUs start and before interrupts that
+/// read the clock are enabled.
+pub unsafe fn install(source: &'static dyn ClockSource) {
+    let now = monotonic_ns();
+    SOURCE = source;
+    BASE_CYCLES.store(source.read(), Ordering::Relaxed);
+    BASE_NS.store(now, Ordering::Relaxed);
+    MULT.store(mult_for_frequency(source.frequency()), Ordering::Release);
+}
+
+/// Convert a counter value of the installed source to monotonic time
+///
+/// Values from before [`install`] map to the time it ran.
+pub fn cycles_to_ns(cycles: u64) -> u64 {
+    let since = cycles.saturating_sub(BASE_CYCLES.load(Ordering::Relaxed));
+    BASE_NS.load(Ordering::Relaxed).saturating_add(scale(since, mult()))
+}
+
+/// Nanoseconds in a counter delta of the installed source
+pub fn delta_to_ns(cycles: u64) -> u64 {
+    scale(cycles, mult())
+}
+
+/// Counter delta of the installed source in `ns` nanoseconds
+pub fn ns_to_delta(ns: u64) -> u64 {
+    ((ns as u128 * current().frequency() as u128) / 1_000_000_000) as u64
+}
+
+/// Monotonic time in nanoseconds
+pub fn monotonic_ns() -> u64 {
+    cycles_to_ns(current().read())
+}
+
+/// Boot time in nanoseconds: monotonic time plus time spent suspended
+pub fn boottime_ns() -> u64 {
+    monotonic_ns().saturating_add(SUSPENDED_NS.load(Ordering::Relaxed))
+}
+
+/// Account time the system spent suspended
+///
+/// For the resume path: the counter may have stopped or been reset, so
+/// only the boot-time clock counts the gap.
+pub fn add_suspended(ns: u64) {
+    SUSPENDED_NS.fetch_add(ns, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale() {
+        // 1 GHz: one nanosecond per cycle
+        assert_eq!(mult_for_frequency(1_000_000_000), 1 << SHIFT);
+        assert_eq!(scale(12_345, mult_for_frequency(1_000_000_000)), 12_345);
+
+        // 3 GHz: 3e9 cycles are a second, within rounding
+        let mult = mult_for_frequency(3_000_000_000);
+        let second = scale(3_000_000_000, mult);
+        assert!(second <= 1_000_000_000 && second > 1_000_000_000 - 2);
+
+        // An hour at 5 GHz does not overflow; truncating mult costs at
+        // most one nanosecond per 2^SHIFT cycles
+        let hour = scale(5_000_000_000 * 3600, mult_for_frequency(5_000_000_000));
+        assert!(hour.abs_diff(3_600_000_000_000) <= (5_000_000_000 * 3600) >> SHIFT);
+    }
+
+    #[test]
+    fn test_one_second_at_any_frequency() {
+        // Generic timer, a round TSC and an odd calibrated TSC
+        for freq in [19_200_000, 1_000_000_000, 2_994_375_123] {
+            let ns = scale(freq, mult_for_frequency(freq));
+            assert!(ns.abs_diff(1_000_000_000) <= 1, "{} Hz: {} ns", freq, ns);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Time Types
+//!
+//! [`Instant`] is a point on the kernel's monotonic clock and
+//! [`Duration`] a span between two points. Both count nanoseconds; raw
+//! counter ticks of the clock source ([`clocksource`]) are only converted
+//! at the edge ([`Instant::now`], [`Instant::from_ticks`],
+//! [`Duration::from_ticks`]), so the rest of the kernel never mixes ticks
+//! and nanoseconds.
+//!
+//! # Clocks
+//!
+//! `CLOCK_GET` exposes [`CLOCK_MONOTONIC`] (the clock `Instant` lives
+//! on) and [`CLOCK_BOOTTIME`] (which also counts time suspended), both
+//! in nanoseconds. [`init`] calibrates the clock source at boot.
+//!
+//! # Tick
+//!
+//! The timer interrupt that runs the timer queue comes from a clock
+//! event device ([`clockevents`]): the Local APIC timer, or the HPET
+//! where that cannot be used.
+//!
+//! # Deadlines
+//!
+//! A deadline is an `Instant`. [`Instant::INFINITE`] means "never" and
+//! stays infinite under arithmetic. All arithmetic saturates instead of
+//! wrapping: a timeout that would overflow becomes infinite, and the
+//! time between an instant and a later one is zero.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let deadline = Instant::after(Duration::from_millis(10));
+//! if Instant::now() >= deadline {
+//!     // timed out
+//! }
+//! ```
+//!
+//! User space passes times as raw `u64` nanoseconds; syscalls convert
+//! them with `from_nanos` and back with `as_nanos`.
+//!
+//! # User Counter Policy
+//!
+//! Userspace may read the cycle counter directly (`rdtsc`/`rdtscp` on
+//! amd64, `CNTVCT_EL0` on arm64, `rdtime`/`rdcycle` on riscv64) unless
+//! the command line says `time.user_counter=deny`. Denying it makes the
+//! instructions fault and the vDSO fall back to `CLOCK_GET`, at the cost
+//! of a syscall per clock read, in exchange for a coarser timer for code
+//! probing cache or scheduling side channels. Each CPU applies the
+//! policy as it comes up ([`user_counter_allowed`]).
+
+pub mod clockevents;
+pub mod clocksource;
+pub mod timer_queue;
+
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use crate::arch::amd64::tsc;
+
+/// Clock ID: nanoseconds since boot, excluding time suspended
+pub const CLOCK_MONOTONIC: u32 = 0;
+
+/// Clock ID: wall-clock time (not supported)
+pub const CLOCK_UTC: u32 = 1;
+
+/// Clock ID: CPU time of the calling thread (not supported)
+pub const CLOCK_THREAD: u32 = 2;
+
+/// Clock ID: nanoseconds since boot, including time suspended
+pub const CLOCK_BOOTTIME: u32 = 3;
+
+/// Calibrate and install the clock source
+///
+/// Runs on the boot CPU with interrupts disabled, before anything reads
+/// the clock for long-lived state (the vDSO page, deadlines). Brings up
+/// the HPET first, as calibration reference and clock event device.
+///
+/// # Returns
+///
+/// The clock source frequency in Hz, or `None` if calibration failed
+/// and the default frequency is in use
+pub fn init() -> Option<u64> {
+    // SAFETY: boot CPU, interrupts disabled
+    unsafe {
+        crate::arch::amd64::hpet::init();
+        let freq = tsc::x86_calibrate_tsc();
+        clocksource::install(&tsc::TSC_CLOCKSOURCE);
+        freq
+    }
+}
+
+// ============================================================================
+// Duration
+// ============================================================================
+
+/// A span of time in nanoseconds
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Duration(u64);
+
+impl Duration {
+    /// No time at all
+    pub const ZERO: Self = Self(0);
+
+    /// Longest representable span
+    pub const MAX: Self = Self(u64::MAX);
+
+    /// Create from nanoseconds
+    pub const fn from_nanos(ns: u64) -> Self {
+        Self(ns)
+    }
+
+    /// Create from microseconds (saturating)
+    pub const fn from_micros(us: u64) -> Self {
+        Self(us.saturating_mul(1_000))
+    }
+
+    /// Create from milliseconds (saturating)
+    pub const fn from_millis(ms: u64) -> Self {
+        Self(ms.saturating_mul(1_000_000))
+    }
+
+    /// Create from seconds (saturating)
+    pub const fn from_secs(secs: u64) -> Self {
+        Self(secs.saturating_mul(1_000_000_000))
+    }
+
+    /// Create from a number of clock source ticks
+    pub fn from_ticks(ticks: u64) -> Self {
+        Self(clocksource::delta_to_ns(ticks))
+    }
+
+    /// Length in nanoseconds
+    pub const fn as_nanos(self) -> u64 {
+        self.0
+    }
+
+    /// Length in whole microseconds
+    pub const fn as_micros(self) -> u64 {
+        self.0 / 1_000
+    }
+
+    /// Length in whole milliseconds
+    pub const fn as_millis(self) -> u64 {
+        self.0 / 1_000_000
+    }
+
+    /// Length in clock source ticks
+    pub fn as_ticks(self) -> u64 {
+        clocksource::ns_to_delta(self.0)
+    }
+
+    /// Check if the span is empty
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// `self + rhs`, clamped to [`Duration::MAX`]
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+
+    /// `self - rhs`, clamped to [`Duration::ZERO`]
+    pub const fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    /// `self * n`, clamped to [`Duration::MAX`]
+    pub const fn saturating_mul(self, n: u64) -> Self {
+        Self(self.0.saturating_mul(n))
+    }
+}
+
+impl Add for Duration {
+    type Output = Self;
+
+    /// Saturating; see [`Duration::saturating_add`]
+    fn add(self, rhs: Self) -> Self {
+        self.saturating_add(rhs)
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Self;
+
+    /// Saturating; see [`Duration::saturating_sub`]
+    fn sub(self, rhs: Self) -> Self {
+        self.saturating_sub(rhs)
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+// ============================================================================
+// Instant
+// ============================================================================
+
+/// A point in time, in nanoseconds on the monotonic clock
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// The clock's origin
+    pub const ZERO: Self = Self(0);
+
+    /// A deadline that never passes
+    pub const INFINITE: Self = Self(u64::MAX);
+
+    /// Create from nanoseconds on the monotonic clock
+    pub const fn from_nanos(ns: u64) -> Self {
+        Self(ns)
+    }
+
+    /// Create from a clock source reading
+    pub fn from_ticks(ticks: u64) -> Self {
+        Self(clocksource::cycles_to_ns(ticks))
+    }
+
+    /// Current time
+    pub fn now() -> Self {
+        Self(clocksource::monotonic_ns())
+    }
+
+    /// Deadline `timeout` from now
+    ///
+    /// Saturates to [`Instant::INFINITE`].
+    pub fn after(timeout: Duration) -> Self {
+        Self::now() + timeout
+    }
+
+    /// Nanoseconds on the monotonic clock
+    pub const fn as_nanos(self) -> u64 {
+        self.0
+    }
+
+    /// Check if this is [`Instant::INFINITE`]
+    pub const fn is_infinite(self) -> bool {
+        self.0 == u64::MAX
+    }
+
+    /// Check if this deadline has passed at `now`
+    pub fn has_passed(self, now: Instant) -> bool {
+        !self.is_infinite() && now >= self
+    }
+
+    /// `self + d`, clamped to [`Instant::INFINITE`]
+    pub const fn saturating_add(self, d: Duration) -> Self {
+        Self(self.0.saturating_add(d.0))
+    }
+
+    /// `self - d`, clamped to [`Instant::ZERO`]
+    ///
+    /// [`Instant::INFINITE`] stays infinite.
+    pub const fn saturating_sub(self, d: Duration) -> Self {
+        if self.is_infinite() {
+            self
+        } else {
+            Self(self.0.saturating_sub(d.0))
+        }
+    }
+
+    /// Time from `earlier` to `self`, or zero if `earlier` is later
+    pub const fn saturating_duration_since(self, earlier: Instant) -> Duration {
+        Duration(self.0.saturating_sub(earlier.0))
+    }
+
+    /// Time left until this deadline at `now` (zero once passed)
+    ///
+    /// [`Duration::MAX`] for [`Instant::INFINITE`].
+    pub const fn remaining(self, now: Instant) -> Duration {
+        if self.is_infinite() {
+            Duration::MAX
+        } else {
+            self.saturating_duration_since(now)
+        }
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Self;
+
+    /// Saturating; see [`Instant::saturating_add`]
+    fn add(self, rhs: Duration) -> Self {
+        self.saturating_add(rhs)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Self;
+
+    /// Saturating; see [`Instant::saturating_sub`]
+    fn sub(self, rhs: Duration) -> Self {
+        self.saturating_sub(rhs)
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    /// Saturating; see [`Instant::saturating_duration_since`]
+    fn sub(self, rhs: Instant) -> Duration {
+        self.saturating_duration_since(rhs)
+    }
+}
+
+// ============================================================================
+// User Counter Policy
+// ============================================================================
+
+/// Command line option: `allow` (default) or `deny` userspace counter reads
+pub const USER_COUNTER_OPTION: &str = "time.user_counter";
+
+/// Policy for a `time.user_counter` value; anything but `deny` allows
+fn parse_user_counter(value: Option<&str>) -> bool {
+    value != Some("deny")
+}
+
+/// Whether userspace may read the cycle counter directly
+pub fn user_counter_allowed() -> bool {
+    let mut buf = [0u8; 8];
+    parse_user_counter(crate::cmdline::get(USER_COUNTER_OPTION, &mut buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_counter_policy() {
+        assert!(parse_user_counter(None));
+        assert!(parse_user_counter(Some("allow")));
+        assert!(!parse_user_counter(Some("deny")));
+    }
+
+    #[test]
+    fn test_duration_units() {
+        assert_eq!(Duration::from_micros(3).as_nanos(), 3_000);
+        assert_eq!(Duration::from_millis(3).as_micros(), 3_000);
+        assert_eq!(Duration::from_secs(2).as_millis(), 2_000);
+        assert_eq!(Duration::from_secs(u64::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn test_duration_saturates() {
+        let d = Duration::from_nanos(5);
+        assert_eq!(d - Duration::from_nanos(7), Duration::ZERO);
+        assert_eq!(Duration::MAX + d, Duration::MAX);
+        assert_eq!(d.saturating_mul(u64::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn test_instant_arithmetic() {
+        let t = Instant::from_nanos(1_000);
+        let later = t + Duration::from_nanos(500);
+
+        assert_eq!(later.as_nanos(), 1_500);
+        assert_eq!(later - t, Duration::from_nanos(500));
+        assert_eq!(t - later, Duration::ZERO);
+        assert_eq!(t - Duration::from_nanos(2_000), Instant::ZERO);
+    }
+
+    #[test]
+    fn test_infinite_deadline() {
+        let now = Instant::from_nanos(u64::MAX - 10);
+
+        assert_eq!(now + Duration::from_nanos(100), Instant::INFINITE);
+        assert_eq!(Instant::INFINITE - Duration::from_secs(1), Instant::INFINITE);
+        assert!(!Instant::INFINITE.has_passed(now));
+        assert_eq!(Instant::INFINITE.remaining(now), Duration::MAX);
+    }
+
+    #[test]
+    fn test_deadline_remaining() {
+        let deadline = Instant::from_nanos(1_000);
+
+        assert_eq!(deadline.remaining(Instant::from_nanos(400)), Duration::from_nanos(600));
+        assert!(!deadline.has_passed(Instant::from_nanos(999)));
+        assert!(deadline.has_passed(Instant::from_nanos(1_000)));
+        assert_eq!(deadline.remaining(Instant::from_nanos(2_000)), Duration::ZERO);
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Timer Queue
+//!
+//! Every armed kernel deadline, in one global list that the timer
+//! interrupt runs ([`tick`]). An entry's [`Target`] says what happens when
+//! its deadline passes: a timer object is signaled (and re-armed if it is
+//! periodic), or a thread sleeping in `NANOSLEEP` is made runnable.
+//!
+//! # Slack
+//!
+//! A deadline armed with slack may fire up to that much late. [`arm`]
+//! uses the slack to coalesce: if another entry is already due within
+//! `[deadline, deadline + slack]`, the new one takes the earliest such
+//! deadline, so both fire from the same tick.
+//!
+//! # Interrupt Context
+//!
+//! The list has a fixed capacity ([`MAX_ARMED`]) so the interrupt never
+//! allocates. Process context holds its lock with interrupts disabled; the
+//! interrupt takes the process table only with `try_lock` and retries a
+//! thread wakeup on the next tick if the table is busy. [`tick`] returns
+//! at once while nothing is due, so the common tick costs one atomic load.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::arch::amd64::mm::RxStatus;
+use crate::object::{Timer, TimerId};
+use crate::sync::SpinMutex;
+use super::{Duration, Instant};
+
+/// Deadlines the queue holds at once
+pub const MAX_ARMED: usize = 256;
+
+/// What to do when a deadline passes
+#[derive(Clone)]
+pub enum Target {
+    /// Signal a timer object
+    Timer(Arc<Timer>),
+    /// Make a sleeping thread `Ready`
+    Thread(u32),
+}
+
+impl Target {
+    /// Check if two targets are the same timer or thread
+    fn same(&self, other: &Target) -> bool {
+        match (self, other) {
+            (Target::Timer(a), Target::Timer(b)) => a.id() == b.id(),
+            (Target::Thread(a), Target::Thread(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// An armed deadline
+#[derive(Clone)]
+pub struct Entry {
+    /// When to fire
+    pub deadline: Instant,
+    /// What to fire
+    pub target: Target,
+}
+
+/// Fixed-capacity set of armed deadlines
+pub struct TimerQueue {
+    entries: [Option<Entry>; MAX_ARMED],
+}
+
+impl TimerQueue {
+    /// Create an empty queue
+    pub const fn new() -> Self {
+        const EMPTY: Option<Entry> = None;
+        Self { entries: [EMPTY; MAX_ARMED] }
+    }
+
+    /// Number of armed deadlines
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    /// Check if nothing is armed
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(Option::is_none)
+    }
+
+    /// Deadline a new entry fires at: the earliest armed deadline within
+    /// `[deadline, deadline + slack]`, or `deadline` itself
+    pub fn coalesce(&self, deadline: Instant, slack: Duration) -> Instant {
+        let latest = deadline.saturating_add(slack);
+        self.entries
+            .iter()
+            .flatten()
+            .map(|e| e.deadline)
+            .filter(|&d| d >= deadline && d <= latest && !d.is_infinite())
+            .min()
+            .unwrap_or(deadline)
+    }
+
+    /// Arm `target`, replacing its previous deadline
+    ///
+    /// # Returns
+    ///
+    /// The deadline it will fire at (see [`coalesce`](Self::coalesce)),
+    /// or `ERR_NO_MEMORY` if the queue is full
+    pub fn arm(&mut self, target: Target, deadline: Instant, slack: Duration) -> Result<Instant, RxStatus> {
+        self.cancel(&target);
+        let deadline = self.coalesce(deadline, slack);
+        let slot = self.entries.iter_mut().find(|e| e.is_none()).ok_or(RxStatus::ERR_NO_MEMORY)?;
+        *slot = Some(Entry { deadline, target });
+        Ok(deadline)
+    }
+
+    /// Disarm `target`
+    ///
+    /// # Returns
+    ///
+    /// Whether it was armed
+    pub fn cancel(&mut self, target: &Target) -> bool {
+        self.remove(|t| t.same(target))
+    }
+
+    /// Remove the entry whose target matches `f`
+    fn remove(&mut self, f: impl Fn(&Target) -> bool) -> bool {
+        match self.entries.iter_mut().find(|e| e.as_ref().is_some_and(|e| f(&e.target))) {
+            Some(slot) => {
+                *slot = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Earliest armed deadline ([`Instant::INFINITE`] if none)
+    pub fn next_deadline(&self) -> Instant {
+        self.entries.iter().flatten().map(|e| e.deadline).min().unwrap_or(Instant::INFINITE)
+    }
+
+    /// Remove and return the earliest entry due at `now`
+    pub fn pop_expired(&mut self, now: Instant) -> Option<Entry> {
+        let slot = self
+            .entries
+            .iter_mut()
+            .filter(|e| e.as_ref().is_some_and(|e| e.deadline.has_passed(now)))
+            .min_by_key(|e| e.as_ref().map(|e| e.deadline))?;
+        slot.take()
+    }
+}
+
+impl Default for TimerQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The kernel's timer queue
+static QUEUE: SpinMutex<TimerQueue> = SpinMutex::new(TimerQueue::new());
+
+/// [`TimerQueue::next_deadline`] of [`QUEUE`], for the fast path of [`tick`]
+static NEXT_DEADLINE: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Run `f` on the queue with interrupts disabled
+fn with_queue<R>(f: impl FnOnce(&mut TimerQueue) -> R) -> R {
+    use crate::arch::amd64::init::{arch_disable_ints, arch_enable_ints, arch_ints_disabled};
+
+    let were_disabled = arch_ints_disabled();
+    arch_disable_ints();
+    let result = {
+        let mut queue = QUEUE.lock();
+        let result = f(&mut queue);
+        NEXT_DEADLINE.store(queue.next_deadline().as_nanos(), Ordering::Release);
+        result
+    };
+    if !were_disabled {
+        arch_enable_ints();
+    }
+    result
+}
+
+/// Arm `target` to fire at `deadline`, up to `slack` late
+///
+/// # Returns
+///
+/// The deadline it will fire at, or `ERR_NO_MEMORY` if [`MAX_ARMED`]
+/// deadlines are already armed
+pub fn arm(target: Target, deadline: Instant, slack: Duration) -> Result<Instant, RxStatus> {
+    with_queue(|queue| queue.arm(target, deadline, slack))
+}
+
+/// Disarm a timer object
+pub fn cancel_timer(id: TimerId) -> bool {
+    with_queue(|queue| queue.remove(|t| matches!(t, Target::Timer(timer) if timer.id() == id)))
+}
+
+/// Disarm a sleeping thread's wakeup
+pub fn cancel_thread(tid: u32) -> bool {
+    with_queue(|queue| queue.cancel(&Target::Thread(tid)))
+}
+
+/// Fire every deadline that has passed
+///
+/// Called from the timer interrupt on each CPU.
+pub fn tick(now: Instant) {
+    if now.as_nanos() < NEXT_DEADLINE.load(Ordering::Acquire) {
+        return;
+    }
+
+    let mut queue = QUEUE.lock();
+    while let Some(entry) = queue.pop_expired(now) {
+        match &entry.target {
+            Target::Timer(timer) => {
+                if let Some(next) = timer.fire(now) {
+                    // Periodic: the slot just freed takes the next deadline
+                    let _ = queue.arm(entry.target.clone(), next, Duration::ZERO);
+                }
+            }
+            Target::Thread(tid) => {
+                if !wake_thread(*tid) {
+                    // Process table busy: retry on the next tick
+                    let _ = queue.arm(entry.target.clone(), entry.deadline, Duration::ZERO);
+                    break;
+                }
+            }
+        }
+    }
+    NEXT_DEADLINE.store(queue.next_deadline().as_nanos(), Ordering::Release);
+}
+
+/// Block the current thread until `deadline`
+///
+/// # Returns
+///
+/// - `Ok(())` - The deadline has passed
+/// - `Err(ERR_INVALID_ARGS)` - `deadline` is infinite
+/// - `Err(ERR_NO_MEMORY)` - The timer queue is full
+pub fn sleep_until(deadline: Instant) -> Result<(), RxStatus> {
+    use crate::process::table::{ProcessState, PROCESS_TABLE};
+
+    if deadline.is_infinite() {
+        return Err(RxStatus::ERR_INVALID_ARGS);
+    }
+    if deadline.has_passed(Instant::now()) {
+        return Ok(());
+    }
+
+    // Blocked before it is armed, so an early tick cannot miss the thread
+    let tid = {
+        let mut table = PROCESS_TABLE.lock();
+        let thread = table.current_thread_mut().ok_or(RxStatus::ERR_NOT_FOUND)?;
+        thread.state = ProcessState::Blocked;
+        thread.pid
+    };
+    if let Err(e) = arm(Target::Thread(tid), deadline, Duration::ZERO) {
+        if let Some(thread) = PROCESS_TABLE.lock().current_thread_mut() {
+            thread.state = ProcessState::Running;
+        }
+        return Err(e);
+    }
+
+    crate::sched::round_robin::sleep_while_blocked();
+    // Woken some other way first (the thread is being killed)
+    cancel_thread(tid);
+    Ok(())
+}
+
+/// Make a sleeping thread runnable
+///
+/// # Returns
+///
+/// `false` if the process table is locked
+fn wake_thread(tid: u32) -> bool {
+    use crate::process::table::{ProcessState, PROCESS_TABLE};
+
+    let Some(mut table) = PROCESS_TABLE.try_lock() else { return false };
+    if let Some(thread) = table.get_mut(tid) {
+        if thread.state == ProcessState::Blocked {
+            thread.state = ProcessState::Ready;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ns: u64) -> Instant {
+        Instant::from_nanos(ns)
+    }
+
+    #[test]
+    fn test_pop_in_deadline_order() {
+        let mut queue = TimerQueue::new();
+        queue.arm(Target::Thread(1), at(300), Duration::ZERO).unwrap();
+        queue.arm(Target::Thread(2), at(100), Duration::ZERO).unwrap();
+        queue.arm(Target::Thread(3), at(200), Duration::ZERO).unwrap();
+        assert_eq!(queue.next_deadline(), at(100));
+
+        let popped: [u64; 2] = core::array::from_fn(|_| queue.pop_expired(at(250)).unwrap().deadline.as_nanos());
+        assert_eq!(popped, [100, 200]);
+        assert!(queue.pop_expired(at(250)).is_none());
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_rearm_replaces_and_cancel_removes() {
+        let mut queue = TimerQueue::new();
+        queue.arm(Target::Thread(7), at(100), Duration::ZERO).unwrap();
+        queue.arm(Target::Thread(7), at(500), Duration::ZERO).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.next_deadline(), at(500));
+
+        assert!(queue.cancel(&Target::Thread(7)));
+        assert!(!queue.cancel(&Target::Thread(7)));
+        assert!(queue.is_empty());
+        assert_eq!(queue.next_deadline(), Instant::INFINITE);
+    }
+
+    #[test]
+    fn test_slack_coalesces() {
+        let mut queue = TimerQueue::new();
+        queue.arm(Target::Thread(1), at(1_000), Duration::ZERO).unwrap();
+
+        // Within slack of an armed deadline: share it
+        let fired_at = queue.arm(Target::Thread(2), at(900), Duration::from_nanos(200)).unwrap();
+        assert_eq!(fired_at, at(1_000));
+
+        // Out of reach, or no slack: keep the requested deadline
+        assert_eq!(queue.arm(Target::Thread(3), at(700), Duration::from_nanos(200)).unwrap(), at(700));
+        assert_eq!(queue.arm(Target::Thread(4), at(999), Duration::ZERO).unwrap(), at(999));
+    }
+
+    #[test]
+    fn test_full_queue() {
+        let mut queue = TimerQueue::new();
+        for tid in 0..MAX_ARMED as u32 {
+            queue.arm(Target::Thread(tid), at(tid as u64), Duration::ZERO).unwrap();
+        }
+        assert_eq!(queue.arm(Target::Thread(9999), at(1), Duration::ZERO), Err(RxStatus::ERR_NO_MEMORY));
+        // Re-arming an armed target reuses its slot
+        assert!(queue.arm(Target::Thread(0), at(1), Duration::ZERO).is_ok());
+    }
+}
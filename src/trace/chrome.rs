@@ -0,0 +1,105 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Chrome Trace-Event Export
+//!
+//! Converts trace events to the Chrome trace-event JSON format
+//! (`{"traceEvents": [...]}`). Each CPU is shown as a thread of a single
+//! "rustux" process:
+//!
+//! - switch-in / switch-out become `B` / `E` duration events, so every
+//!   run of a task is a slice on its CPU's row, named after the task;
+//!   the switch-out reason is attached as an argument
+//! - wakeups and migrations become instant (`i`) events with the task
+//!   IDs as arguments
+//!
+//! Timestamps are written in microseconds with nanosecond precision.
+
+use core::fmt::{self, Write};
+use super::{SwitchReason, TraceEvent, TraceKind};
+
+/// Highest CPU number given a row name
+const MAX_NAMED_CPUS: u16 = 64;
+
+/// Write `events` as a Chrome trace-event JSON document
+pub fn write_json<I, W>(events: I, out: &mut W) -> fmt::Result
+where
+    I: IntoIterator<Item = TraceEvent>,
+    W: Write,
+{
+    out.write_str("{\"traceEvents\":[\n")?;
+    out.write_str("{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":0,\"args\":{\"name\":\"rustux\"}}")?;
+
+    let mut cpus_named: u64 = 0;
+    for event in events {
+        if event.cpu < MAX_NAMED_CPUS && cpus_named & (1 << event.cpu) == 0 {
+            cpus_named |= 1 << event.cpu;
+            write!(
+                out,
+                ",\n{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{{\"name\":\"CPU {}\"}}}}",
+                event.cpu, event.cpu
+            )?;
+        }
+        write_event(&event, out)?;
+    }
+
+    out.write_str("\n],\"displayTimeUnit\":\"ns\"}")
+}
+
+/// Write one event (preceded by a separator)
+fn write_event<W: Write>(event: &TraceEvent, out: &mut W) -> fmt::Result {
+    let us = event.ts_ns / 1000;
+    let frac = event.ts_ns % 1000;
+
+    match event.kind {
+        TraceKind::SwitchIn => write!(
+            out,
+            ",\n{{\"name\":\"task {}\",\"cat\":\"sched\",\"ph\":\"B\",\"ts\":{}.{:03},\"pid\":0,\"tid\":{}}}",
+            event.id, us, frac, event.cpu
+        ),
+        TraceKind::SwitchOut => write!(
+            out,
+            ",\n{{\"name\":\"task {}\",\"cat\":\"sched\",\"ph\":\"E\",\"ts\":{}.{:03},\"pid\":0,\"tid\":{},\"args\":{{\"reason\":\"{}\"}}}}",
+            event.id, us, frac, event.cpu, SwitchReason::from_raw(event.arg).name()
+        ),
+        TraceKind::Wakeup => write!(
+            out,
+            ",\n{{\"name\":\"wakeup\",\"cat\":\"sched\",\"ph\":\"i\",\"s\":\"t\",\"ts\":{}.{:03},\"pid\":0,\"tid\":{},\"args\":{{\"waker\":{},\"wakee\":{}}}}}",
+            us, frac, event.cpu, event.id, event.id2
+        ),
+        TraceKind::Migrate => write!(
+            out,
+            ",\n{{\"name\":\"migrate\",\"cat\":\"sched\",\"ph\":\"i\",\"s\":\"p\",\"ts\":{}.{:03},\"pid\":0,\"tid\":{},\"args\":{{\"task\":{},\"from\":{},\"to\":{}}}}}",
+            us, frac, event.cpu, event.id, event.cpu, event.arg
+        ),
+        TraceKind::None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    #[test]
+    fn test_write_json() {
+        let events = [
+            TraceEvent { ts_ns: 1_500, id: 3, kind: TraceKind::SwitchIn, ..TraceEvent::EMPTY },
+            TraceEvent { ts_ns: 2_000, id: 1, id2: 4, kind: TraceKind::Wakeup, ..TraceEvent::EMPTY },
+            TraceEvent { ts_ns: 12_345, id: 3, kind: TraceKind::SwitchOut, arg: SwitchReason::Yield as u32, ..TraceEvent::EMPTY },
+        ];
+
+        let mut out = String::new();
+        write_json(events, &mut out).unwrap();
+
+        assert!(out.starts_with("{\"traceEvents\":["));
+        assert!(out.ends_with("],\"displayTimeUnit\":\"ns\"}"));
+        assert!(out.contains("\"args\":{\"name\":\"CPU 0\"}"));
+        assert!(out.contains("\"name\":\"task 3\",\"cat\":\"sched\",\"ph\":\"B\",\"ts\":1.500"));
+        assert!(out.contains("\"ph\":\"E\",\"ts\":12.345,\"pid\":0,\"tid\":0,\"args\":{\"reason\":\"yield\"}"));
+        assert!(out.contains("\"args\":{\"waker\":1,\"wakee\":4}"));
+    }
+}
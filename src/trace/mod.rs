@@ -0,0 +1,368 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Trace Buffer
+//!
+//! A fixed-size ring of timestamped scheduler events used to build a
+//! timeline for scheduler tuning. Tracing is off by default; boot with
+//! `trace` on the command line (or call [`enable`]) to turn it on.
+//!
+//! # Tracepoints
+//!
+//! - [`switch_out`] / [`switch_in`]: a CPU stops / starts running a task;
+//!   switch-out carries a [`SwitchReason`]
+//! - [`wakeup`]: a task (the waker) makes a blocked task (the wakee) ready
+//! - [`migrate`]: a task moves from one CPU's run queue to another's
+//!
+//! Tasks are identified by PID for processes and by thread ID for
+//! kernel threads.
+//!
+//! Recording never allocates and drops the event rather than spin if the
+//! buffer is busy, so tracepoints are safe in interrupt context. When the
+//! ring is full the oldest events are overwritten.
+//!
+//! Events are also published to ring buffers created for
+//! [`RingSource::Trace`](crate::object::RingSource), whether or not the
+//! trace buffer itself is enabled, so a userspace tracer can stream them.
+//!
+//! [`chrome`] converts the buffer to Chrome trace-event JSON, which can be
+//! loaded in `chrome://tracing` or Perfetto.
+
+pub mod chrome;
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::klog::DebugconWriter;
+use crate::sync::SpinMutex;
+
+/// Number of events kept
+pub const TRACE_BUFFER_SIZE: usize = 4096;
+
+/// Command-line flag that enables tracing at boot
+pub const TRACE_FLAG: &str = "trace";
+
+/// CPU that runs the round-robin scheduler (the boot CPU)
+pub const BOOT_CPU: u16 = 0;
+
+/// Trace event kinds
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceKind {
+    /// Unused slot
+    None = 0,
+    /// `id` stopped running on `cpu`; `arg` is a [`SwitchReason`]
+    SwitchOut = 1,
+    /// `id` started running on `cpu`
+    SwitchIn = 2,
+    /// `id` (waker) made `id2` (wakee) ready
+    Wakeup = 3,
+    /// `id` moved from `cpu` to CPU `arg`
+    Migrate = 4,
+}
+
+impl TraceKind {
+    /// Convert from a raw kind ([`TraceKind::None`] if unknown)
+    pub const fn from_raw(raw: u16) -> Self {
+        match raw {
+            1 => Self::SwitchOut,
+            2 => Self::SwitchIn,
+            3 => Self::Wakeup,
+            4 => Self::Migrate,
+            _ => Self::None,
+        }
+    }
+
+    /// Short name for trace output
+    pub const fn name(self) -> &'static str {
+        match self {
+            TraceKind::None => "none",
+            TraceKind::SwitchOut => "switch-out",
+            TraceKind::SwitchIn => "switch-in",
+            TraceKind::Wakeup => "wakeup",
+            TraceKind::Migrate => "migrate",
+        }
+    }
+}
+
+/// Why a task stopped running
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchReason {
+    /// Time slice expired
+    Preempt = 0,
+    /// Gave up the CPU voluntarily
+    Yield = 1,
+    /// Blocked waiting for something
+    Block = 2,
+    /// Exited or was killed
+    Exit = 3,
+}
+
+impl SwitchReason {
+    /// Convert from a raw event argument
+    pub const fn from_raw(raw: u32) -> Self {
+        match raw {
+            1 => Self::Yield,
+            2 => Self::Block,
+            3 => Self::Exit,
+            _ => Self::Preempt,
+        }
+    }
+
+    /// Short name for trace output
+    pub const fn name(self) -> &'static str {
+        match self {
+            SwitchReason::Preempt => "preempt",
+            SwitchReason::Yield => "yield",
+            SwitchReason::Block => "block",
+            SwitchReason::Exit => "exit",
+        }
+    }
+}
+
+/// A single trace event
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Timestamp in nanoseconds since boot
+    pub ts_ns: u64,
+    /// Primary task ID
+    pub id: u64,
+    /// Secondary task ID (wakee for [`TraceKind::Wakeup`])
+    pub id2: u64,
+    /// Event kind
+    pub kind: TraceKind,
+    /// CPU the event happened on
+    pub cpu: u16,
+    /// Kind-specific argument
+    pub arg: u32,
+}
+
+impl TraceEvent {
+    /// An unused slot
+    pub const EMPTY: Self = Self { ts_ns: 0, id: 0, id2: 0, kind: TraceKind::None, cpu: 0, arg: 0 };
+}
+
+/// Ring of trace events
+pub struct TraceBuffer {
+    events: [TraceEvent; TRACE_BUFFER_SIZE],
+    /// Total events ever recorded (the next slot is `head % SIZE`)
+    head: u64,
+}
+
+impl TraceBuffer {
+    /// Create an empty buffer
+    pub const fn new() -> Self {
+        Self { events: [TraceEvent::EMPTY; TRACE_BUFFER_SIZE], head: 0 }
+    }
+
+    /// Append an event, overwriting the oldest when full
+    pub fn push(&mut self, event: TraceEvent) {
+        self.events[(self.head as usize) % TRACE_BUFFER_SIZE] = event;
+        self.head += 1;
+    }
+
+    /// Number of events held
+    pub fn len(&self) -> usize {
+        (self.head as usize).min(TRACE_BUFFER_SIZE)
+    }
+
+    /// Check whether the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.head == 0
+    }
+
+    /// Iterate over held events, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &TraceEvent> + '_ {
+        let first = self.head - self.len() as u64;
+        (first..self.head).map(move |i| &self.events[(i as usize) % TRACE_BUFFER_SIZE])
+    }
+
+    /// Discard all events
+    pub fn clear(&mut self) {
+        self.head = 0;
+    }
+}
+
+impl Default for TraceBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static TRACE_BUFFER: SpinMutex<TraceBuffer> =
+    SpinMutex::named(TraceBuffer::new(), &crate::sync::lockstat::TRACE_BUFFER);
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+// ============================================================================
+// Control
+// ============================================================================
+
+/// Enable tracing if requested on the command line
+pub fn init() {
+    if crate::cmdline::has_flag(TRACE_FLAG) {
+        enable();
+    }
+}
+
+/// Start recording events
+pub fn enable() {
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Stop recording events (the buffer is kept)
+pub fn disable() {
+    ENABLED.store(false, Ordering::Release);
+}
+
+/// Check whether tracing is enabled
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Number of events dropped because the buffer was busy
+pub fn dropped() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Discard all recorded events
+pub fn clear() {
+    TRACE_BUFFER.lock().clear();
+    DROPPED.store(0, Ordering::Relaxed);
+}
+
+/// Run `f` with the trace buffer locked
+///
+/// Events recorded meanwhile are dropped, so keep `f` short or disable
+/// tracing first.
+pub fn with_buffer<F, R>(f: F) -> R
+where
+    F: FnOnce(&TraceBuffer) -> R,
+{
+    f(&TRACE_BUFFER.lock())
+}
+
+/// Run `f` with the trace buffer locked, or return `None` if it is busy
+///
+/// For panic paths, which must not wait for a lock they may hold.
+pub fn try_with_buffer<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce(&TraceBuffer) -> R,
+{
+    TRACE_BUFFER.try_lock().map(|buf| f(&buf))
+}
+
+// ============================================================================
+// Tracepoints
+// ============================================================================
+
+fn record(kind: TraceKind, cpu: u16, id: u64, id2: u64, arg: u32) {
+    use crate::object::ringbuf::{self, RingSource};
+
+    let enabled = is_enabled();
+    if !enabled && !ringbuf::has_listeners() {
+        return;
+    }
+    let event = TraceEvent { ts_ns: crate::time::Instant::now().as_nanos(), id, id2, kind, cpu, arg };
+    // SAFETY: TraceEvent is repr(C) with no padding (8+8+8+2+2+4 bytes)
+    ringbuf::publish(RingSource::Trace, unsafe { ringbuf::record_bytes(&event) });
+    if !enabled {
+        return;
+    }
+    match TRACE_BUFFER.try_lock() {
+        Some(mut buf) => buf.push(event),
+        None => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// `id` stopped running on `cpu`
+#[inline]
+pub fn switch_out(cpu: u16, id: u64, reason: SwitchReason) {
+    record(TraceKind::SwitchOut, cpu, id, 0, reason as u32);
+}
+
+/// `id` started running on `cpu`
+#[inline]
+pub fn switch_in(cpu: u16, id: u64) {
+    record(TraceKind::SwitchIn, cpu, id, 0, 0);
+}
+
+/// `waker` made `wakee` ready
+#[inline]
+pub fn wakeup(cpu: u16, waker: u64, wakee: u64) {
+    record(TraceKind::Wakeup, cpu, waker, wakee, 0);
+}
+
+/// `id` moved from CPU `from` to CPU `to`
+#[inline]
+pub fn migrate(from: u16, to: u16, id: u64) {
+    record(TraceKind::Migrate, from, id, 0, to as u32);
+}
+
+// ============================================================================
+// Dumping
+// ============================================================================
+
+/// Marker printed before the JSON by [`dump_chrome`]
+pub const DUMP_BEGIN: &str = "--- BEGIN CHROME TRACE ---\n";
+
+/// Marker printed after the JSON by [`dump_chrome`]
+pub const DUMP_END: &str = "--- END CHROME TRACE ---\n";
+
+/// Write the trace buffer to the debug port as Chrome trace-event JSON
+///
+/// The JSON is framed by [`DUMP_BEGIN`] / [`DUMP_END`] so it can be cut
+/// out of a QEMU debugcon log. Tracing is disabled while dumping.
+pub fn dump_chrome() {
+    let was_enabled = is_enabled();
+    disable();
+
+    let mut out = DebugconWriter;
+    let _ = core::fmt::Write::write_str(&mut out, DUMP_BEGIN);
+    with_buffer(|buf| {
+        let _ = chrome::write_json(buf.iter().copied(), &mut out);
+    });
+    let _ = core::fmt::Write::write_str(&mut out, "\n");
+    let _ = core::fmt::Write::write_str(&mut out, DUMP_END);
+
+    if was_enabled {
+        enable();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: u64) -> TraceEvent {
+        TraceEvent { id, kind: TraceKind::SwitchIn, ..TraceEvent::EMPTY }
+    }
+
+    #[test]
+    fn test_buffer_wraps() {
+        let mut buf = alloc::boxed::Box::new(TraceBuffer::new());
+        assert!(buf.is_empty());
+
+        for id in 0..(TRACE_BUFFER_SIZE as u64 + 3) {
+            buf.push(event(id));
+        }
+        assert_eq!(buf.len(), TRACE_BUFFER_SIZE);
+
+        let mut iter = buf.iter();
+        assert_eq!(iter.next().map(|e| e.id), Some(3));
+        assert_eq!(buf.iter().last().map(|e| e.id), Some(TRACE_BUFFER_SIZE as u64 + 2));
+    }
+
+    #[test]
+    fn test_switch_reason_roundtrip() {
+        for reason in [SwitchReason::Preempt, SwitchReason::Yield, SwitchReason::Block, SwitchReason::Exit] {
+            assert_eq!(SwitchReason::from_raw(reason as u32), reason);
+        }
+    }
+}
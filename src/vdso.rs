@@ -0,0 +1,243 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! vDSO Time Page
+//!
+//! A page of clock data mapped read-only into every process at
+//! [`VDSO_TIME_VADDR`], so userspace can read the time without a
+//! syscall. The timer interrupt refreshes it ([`update`]).
+//!
+//! # Reading the Time
+//!
+//! Userspace (see `userspace/c-progs/vdso.h`):
+//!
+//! 1. Reads `seq`; if odd, an update is in progress, so retry
+//! 2. Reads `tsc_base`, `ns_base`, `mult` and `max_delta_tsc`
+//! 3. Re-reads `seq`; if it changed, retry
+//! 4. Reads the TSC; if `tsc - tsc_base > max_delta_tsc` the page is
+//!    stale (timer stopped, or not yet running) and it falls back to
+//!    `CLOCK_GET`
+//! 5. Otherwise `ns = ns_base + ((tsc - tsc_base) * mult) >> shift`
+//!
+//! Before step 4, a version 2 reader checks [`VDSO_FLAG_USER_TSC`]: when
+//! the user counter policy denies the TSC, `rdtsc` faults, so the reader
+//! goes straight to `CLOCK_GET`.
+//!
+//! # Cycle Counting
+//!
+//! Since version 2 the page also carries the TSC frequency and
+//! `VDSO_FLAG_*` bits, so benchmarks can turn raw `rdtsc`/`rdtscp`
+//! deltas into time. `rdtscp` returns the CPU number in `ecx`; a delta
+//! between readings on different CPUs is only meaningful when
+//! [`VDSO_FLAG_TSC_INVARIANT`] is set.
+//!
+//! # Layout
+//!
+//! [`VdsoTimeData`] is part of the stable ABI, following the same rules
+//! as the kernel counters page: fields are only appended and `version`
+//! is bumped when that happens.
+
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
+use crate::arch::amd64::mm::page_tables::PAddr;
+use crate::arch::amd64::mm::{layout, RxStatus};
+use crate::arch::amd64::tsc;
+use crate::mm::pmm;
+use crate::object::{Vmo, VmoFlags};
+use crate::object::vmo::PageMapEntry;
+use crate::process::AddressSpace;
+
+/// Magic value at the start of the page ("VDSO")
+pub const VDSO_TIME_MAGIC: u32 = 0x4F53_4456;
+
+/// Current layout version
+pub const VDSO_TIME_VERSION: u32 = 2;
+
+/// Userspace address of the time page in every process
+pub const VDSO_TIME_VADDR: u64 = layout::VDSO.start;
+
+/// Size of the time page mapping
+pub const VDSO_TIME_SIZE: usize = 4096;
+
+const _: () = assert!(VDSO_TIME_VADDR + VDSO_TIME_SIZE as u64 <= layout::VDSO.end);
+
+/// Fixed-point shift of `mult`
+pub const VDSO_MULT_SHIFT: u32 = 32;
+
+/// Age after which userspace must not trust the page (100 ms)
+pub const VDSO_STALE_NS: u64 = 100_000_000;
+
+/// Flag: userspace may execute `rdtsc`/`rdtscp` (`CR4.TSD` clear)
+pub const VDSO_FLAG_USER_TSC: u32 = 1 << 0;
+
+/// Flag: `rdtscp` is supported and returns the CPU number in `ecx`
+pub const VDSO_FLAG_RDTSCP: u32 = 1 << 1;
+
+/// Flag: the TSC is invariant and in step across CPUs
+pub const VDSO_FLAG_TSC_INVARIANT: u32 = 1 << 2;
+
+/// Time page layout (version 2)
+#[repr(C)]
+pub struct VdsoTimeData {
+    /// [`VDSO_TIME_MAGIC`]
+    pub magic: u32,
+    /// [`VDSO_TIME_VERSION`]
+    pub version: u32,
+    /// Sequence count; odd while an update is in progress
+    pub seq: AtomicU32,
+    /// Fixed-point shift of `mult` ([`VDSO_MULT_SHIFT`])
+    pub shift: u32,
+    /// Nanoseconds per TSC tick, scaled by `1 << shift`
+    pub mult: AtomicU64,
+    /// TSC value at the last update
+    pub tsc_base: AtomicU64,
+    /// Monotonic time in nanoseconds at `tsc_base`
+    pub ns_base: AtomicU64,
+    /// TSC ticks after `tsc_base` beyond which the page is stale
+    pub max_delta_tsc: AtomicU64,
+    /// TSC frequency in Hz (version 2)
+    pub tsc_freq_hz: u64,
+    /// `VDSO_FLAG_*` bits (version 2)
+    pub flags: u32,
+    /// Reserved, always 0
+    pub _reserved: u32,
+}
+
+const _: () = assert!(core::mem::size_of::<VdsoTimeData>() <= VDSO_TIME_SIZE);
+
+/// Kernel mapping of the time page (null until [`init`])
+static TIME_DATA: AtomicPtr<VdsoTimeData> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Physical address of the time page (0 until [`init`])
+static TIME_PADDR: AtomicU64 = AtomicU64::new(0);
+
+/// Compute `mult` for a TSC frequency
+pub const fn mult_for_frequency(freq_hz: u64) -> u64 {
+    (((1_000_000_000u128) << VDSO_MULT_SHIFT) / freq_hz as u128) as u64
+}
+
+/// `VDSO_FLAG_*` bits for this machine
+fn tsc_flags() -> u32 {
+    let mut flags = 0;
+    if crate::time::user_counter_allowed() {
+        flags |= VDSO_FLAG_USER_TSC;
+    }
+    if tsc::has_rdtscp() {
+        flags |= VDSO_FLAG_RDTSCP;
+    }
+    if tsc::is_invariant() {
+        flags |= VDSO_FLAG_TSC_INVARIANT;
+    }
+    flags
+}
+
+/// Convert a TSC delta to nanoseconds the way userspace does
+pub const fn delta_to_ns(delta: u64, mult: u64) -> u64 {
+    ((delta as u128 * mult as u128) >> VDSO_MULT_SHIFT) as u64
+}
+
+/// Allocate and initialize the time page
+///
+/// Must be called once after the PMM is up and the TSC frequency is
+/// known. Processes loaded before this call do not get the page.
+pub fn init() -> Result<(), &'static str> {
+    if !TIME_DATA.load(Ordering::Acquire).is_null() {
+        return Err("vDSO time page already initialized");
+    }
+
+    let paddr = pmm::pmm_alloc_kernel_page()
+        .map_err(|_| "Failed to allocate vDSO time page")?;
+    let vaddr = pmm::paddr_to_vaddr(paddr);
+
+    let freq = tsc::x86_tsc_frequency();
+    unsafe {
+        core::ptr::write_bytes(vaddr as *mut u8, 0, VDSO_TIME_SIZE);
+
+        let page = &mut *(vaddr as *mut VdsoTimeData);
+        page.magic = VDSO_TIME_MAGIC;
+        page.version = VDSO_TIME_VERSION;
+        page.shift = VDSO_MULT_SHIFT;
+        page.mult.store(mult_for_frequency(freq), Ordering::Relaxed);
+        page.max_delta_tsc.store(tsc::ns_to_tsc(VDSO_STALE_NS), Ordering::Relaxed);
+        page.tsc_freq_hz = freq;
+        page.flags = tsc_flags();
+    }
+
+    TIME_PADDR.store(paddr as u64, Ordering::Relaxed);
+    TIME_DATA.store(vaddr as *mut VdsoTimeData, Ordering::Release);
+    update();
+    Ok(())
+}
+
+/// Refresh the time page
+///
+/// Called from the timer interrupt. Only one CPU may update at a time.
+#[inline]
+pub fn update() {
+    let ptr = TIME_DATA.load(Ordering::Acquire);
+    if ptr.is_null() {
+        return;
+    }
+    let page = unsafe { &*ptr };
+
+    let now = tsc::tsc_ticks();
+    page.seq.fetch_add(1, Ordering::AcqRel);
+    page.tsc_base.store(now, Ordering::Relaxed);
+    page.ns_base.store(crate::time::clocksource::cycles_to_ns(now), Ordering::Relaxed);
+    page.seq.fetch_add(1, Ordering::Release);
+}
+
+/// Build a VMO backed by the time page
+///
+/// The page entry is marked read-only. The VMO does not own the page.
+fn vmo() -> Result<Vmo, RxStatus> {
+    let paddr = TIME_PADDR.load(Ordering::Relaxed);
+    if paddr == 0 {
+        return Err(RxStatus::ERR_NOT_FOUND);
+    }
+
+    let vmo = Vmo::create(VDSO_TIME_SIZE, VmoFlags::empty)
+        .map_err(|_| RxStatus::ERR_NO_MEMORY)?;
+    vmo.pages.lock().insert(0, PageMapEntry::new(paddr as PAddr, false));
+    Ok(vmo)
+}
+
+/// Map the time page read-only at [`VDSO_TIME_VADDR`]
+///
+/// Does nothing if the page has not been initialized.
+pub fn map_into(address_space: &AddressSpace) -> Result<(), &'static str> {
+    let vmo = match vmo() {
+        Ok(vmo) => vmo,
+        Err(_) => return Ok(()),
+    };
+
+    // PF_R only: map_page leaves the writable bit clear
+    address_space.map_vmo(&vmo, VDSO_TIME_VADDR, VDSO_TIME_SIZE as u64, 0x4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(core::mem::offset_of!(VdsoTimeData, seq), 8);
+        assert_eq!(core::mem::offset_of!(VdsoTimeData, mult), 16);
+        assert_eq!(core::mem::offset_of!(VdsoTimeData, tsc_base), 24);
+        assert_eq!(core::mem::offset_of!(VdsoTimeData, ns_base), 32);
+        assert_eq!(core::mem::offset_of!(VdsoTimeData, max_delta_tsc), 40);
+        assert_eq!(core::mem::offset_of!(VdsoTimeData, tsc_freq_hz), 48);
+        assert_eq!(core::mem::offset_of!(VdsoTimeData, flags), 56);
+        assert_eq!(core::mem::size_of::<VdsoTimeData>(), 64);
+    }
+
+    #[test]
+    fn test_mult() {
+        // 2 GHz: half a nanosecond per tick
+        let mult = mult_for_frequency(2_000_000_000);
+        assert_eq!(delta_to_ns(2_000_000_000, mult), 1_000_000_000);
+        assert_eq!(delta_to_ns(3, mult), 1);
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Kernel Version Information
+//!
+//! Values embedded at build time by `build.rs` (Part 2c).
+
+/// Kernel version (from Cargo.toml)
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `git describe --always --dirty` of the source tree, or "unknown"
+pub const GIT_HASH: &str = env!("RUSTUX_GIT_HASH");
+
+/// Build time (`YYYY-MM-DD HH:MM:SS UTC`)
+pub const BUILD_TIME: &str = env!("RUSTUX_BUILD_TIME");
+
+/// Write the version line reported by `/proc/version`
+pub fn write_banner(out: &mut impl core::fmt::Write) -> core::fmt::Result {
+    write!(out, "Rustux version {} ({}) built {}", VERSION, GIT_HASH, BUILD_TIME)
+}
@@ -88,5 +88,13 @@ else
     echo "❌ ERROR - No debug log created"
 fi
 
+# Extract the scheduler trace (load it in chrome://tracing or Perfetto)
+if [ -f "/tmp/rustux-qemu-debug.log" ] && grep -q "BEGIN CHROME TRACE" /tmp/rustux-qemu-debug.log; then
+    sed -n '/--- BEGIN CHROME TRACE ---/,/--- END CHROME TRACE ---/p' /tmp/rustux-qemu-debug.log \
+        | sed '1d;$d' > /tmp/rustux-trace.json
+    echo ""
+    echo "Scheduler trace saved to: /tmp/rustux-trace.json"
+fi
+
 echo ""
 echo "Full debug log saved to: /tmp/rustux-qemu-debug.log"
@@ -30,8 +30,20 @@ static inline long syscall1(long number, long arg1) {
     return ret;
 }
 
+static inline long syscall3(long number, long arg1, long arg2, long arg3) {
+    long ret;
+    __asm__ volatile (
+        "int $0x80"
+        : "=a" (ret)
+        : "a" (number), "b" (arg1), "c" (arg2), "d" (arg3)
+        : "memory"
+    );
+    return ret;
+}
+
+// No argv: the shell gets its path as argv[0]
 static inline long sys_spawn(const char *path) {
-    return syscall1(SYS_SPAWN, (long)path);
+    return syscall3(SYS_SPAWN, (long)path, 0, 0);
 }
 
 static inline void sys_exit(int code) {
@@ -18,7 +18,7 @@
 #define SYS_GETPPID      0x71  // getppid()
 #define SYS_YIELD        0x72  // yield()
 #define SYS_EXIT         0x06  // exit(code)
-#define SYS_SPAWN        0x03  // spawn(path)
+#define SYS_SPAWN        0x03  // spawn(path, argv, envp)
 
 // File descriptor numbers
 #define STDIN   0
@@ -74,8 +74,8 @@ static inline long sys_read(int fd, char *buf, long len) {
     return syscall3(SYS_READ, fd, (long)buf, len);
 }
 
-static inline long sys_spawn(const char *path) {
-    return syscall1(SYS_SPAWN, (long)path);
+static inline long sys_spawn(const char *path, char **argv) {
+    return syscall3(SYS_SPAWN, (long)path, (long)argv, 0);
 }
 
 static inline void sys_exit(int code) {
@@ -191,7 +191,8 @@ static int parse_command(char *line, int *argc, char **argv) {
     while (*p == ' ' || *p == '\t') p++;
 
     while (*p && *p != '\n') {
-        if (*argc >= MAX_ARGS) break;
+        // Leave room for the NULL that ends argv
+        if (*argc >= MAX_ARGS - 1) break;
 
         // Save argument start
         argv[(*argc)++] = p;
@@ -209,6 +210,7 @@ static int parse_command(char *line, int *argc, char **argv) {
         while (*p == ' ' || *p == '\t') p++;
     }
 
+    argv[*argc] = 0;
     return *argc > 0;
 }
 
@@ -216,7 +218,7 @@ static int parse_command(char *line, int *argc, char **argv) {
 // EXTERNAL COMMAND EXECUTION
 // =============================================================
 
-static int spawn_external(const char *name) {
+static int spawn_external(const char *name, char **argv) {
     char path[128];
     char *p = path;
 
@@ -231,7 +233,7 @@ static int spawn_external(const char *name) {
     *p = '\0';
 
     // Try to spawn the program
-    long pid = sys_spawn(path);
+    long pid = sys_spawn(path, argv);
 
     if (pid < 0) {
         print_color(ANSI_RED, "error: ");
@@ -364,7 +366,7 @@ void _start(void) {
             cmd_exit(argc, argv);
         } else {
             // Try to spawn external program
-            spawn_external(cmd);
+            spawn_external(cmd, argv);
         }
     }
 
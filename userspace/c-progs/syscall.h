@@ -18,7 +18,9 @@
 #define SYS_PROCESS_CREATE  0x01
 #define SYS_PROCESS_EXIT    0x06
 #define SYS_CLOCK_GET       0x40
+#define SYS_NANOSLEEP       0x44
 #define SYS_DEBUG_WRITE     0x50
+#define SYS_DEBUG_READ      0x54
 #define SYS_WRITE           0x60
 #define SYS_READ            0x61
 #define SYS_OPEN            0x62
@@ -27,6 +29,22 @@
 #define SYS_GETPID          0x70
 #define SYS_GETPPID         0x71
 #define SYS_YIELD           0x72
+#define SYS_VT_CONTROL      0x8D
+#define SYS_MOUNT           0x8E
+#define SYS_UMOUNT          0x8F
+#define SYS_SYSTEM_GET_FEATURES 0x90
+#define SYS_IOPORT_CREATE   0x91
+#define SYS_IOPORT_ENABLE   0x92
+
+// VT_CONTROL commands
+#define VT_REFRESH          0
+#define VT_TAKE_DISPLAY     1
+#define VT_RELEASE_DISPLAY  2
+#define VT_SET_PALETTE      3
+
+// MOUNT flags
+#define MS_RDONLY           (1 << 0)
+#define MS_NOEXEC           (1 << 1)
 
 // Open flags
 #define O_RDONLY 0
@@ -43,6 +61,22 @@
 #define STDOUT_FILENO 1
 #define STDERR_FILENO 2
 
+// Feature bits (SYSTEM_GET_FEATURES)
+#define RX_FEATURE_VDSO_TIME        (1ULL << 0)  // time page at VDSO_TIME_VADDR
+#define RX_FEATURE_TMPFS            (1ULL << 1)  // writable /tmp
+#define RX_FEATURE_PROCFS           (1ULL << 2)  // /proc
+#define RX_FEATURE_LOCKSTAT         (1ULL << 3)  // /proc/lockstat
+#define RX_FEATURE_HANDLE_TRACKING  (1ULL << 4)  // handle creation sites recorded
+#define RX_FEATURE_KASAN            (1ULL << 5)  // heap redzones and quarantine
+#define RX_FEATURE_KERNEL_TEST      (1ULL << 6)  // test kernel
+
+struct rx_features {
+    uint32_t abi_version;
+    uint32_t max_syscall;
+    uint64_t syscalls[4];  // bit n % 64 of word n / 64: syscall n implemented
+    uint64_t features;     // RX_FEATURE_* bits
+};
+
 /**
  * Make a syscall with 0 arguments
  */
@@ -113,6 +147,26 @@ static inline int64_t syscall3(int num, int64_t arg1, int64_t arg2, int64_t arg3
     return ret;
 }
 
+/**
+ * Make a syscall with 4 arguments
+ */
+static inline int64_t syscall4(int num, int64_t arg1, int64_t arg2, int64_t arg3, int64_t arg4) {
+    int64_t ret;
+    __asm__ volatile (
+        "mov %1, %%rax\n"
+        "mov %2, %%rdi\n"
+        "mov %3, %%rsi\n"
+        "mov %4, %%rdx\n"
+        "mov %5, %%r10\n"
+        "int $0x80\n"
+        "mov %%rax, %0\n"
+        : "=r" (ret)
+        : "r" ((int64_t)num), "r" (arg1), "r" (arg2), "r" (arg3), "r" (arg4)
+        : "rax", "rdi", "rsi", "rdx", "r10", "rcx", "r11"
+    );
+    return ret;
+}
+
 // Helper functions
 
 /**
@@ -150,6 +204,20 @@ static inline int64_t sys_lseek(int fd, int64_t offset, int whence) {
     return syscall3(SYS_LSEEK, (int64_t)fd, offset, (int64_t)whence);
 }
 
+/**
+ * Read the monotonic clock in nanoseconds (see vdso.h for the fast path)
+ */
+static inline int64_t sys_clock_get(int clock_id) {
+    return syscall1(SYS_CLOCK_GET, (int64_t)clock_id);
+}
+
+/**
+ * Sleep until an absolute monotonic deadline in nanoseconds
+ */
+static inline int64_t sys_nanosleep(uint64_t deadline_ns) {
+    return syscall1(SYS_NANOSLEEP, (int64_t)deadline_ns);
+}
+
 /**
  * Get current process ID
  */
@@ -187,4 +255,92 @@ static inline int64_t sys_debug_write(const void *buf, int64_t len) {
     return syscall2(SYS_DEBUG_WRITE, (int64_t)buf, len);
 }
 
+/**
+ * Get the ABI version and what the kernel supports
+ */
+static inline int64_t sys_system_get_features(struct rx_features *out) {
+    return syscall1(SYS_SYSTEM_GET_FEATURES, (int64_t)out);
+}
+
+/**
+ * What the running kernel supports, probed on first use
+ *
+ * A kernel without SYSTEM_GET_FEATURES reports ABI version 1 with no
+ * syscalls or features set, so only gate syscalls newer than the probe
+ * on rx_has_syscall().
+ */
+static inline const struct rx_features *rx_features(void) {
+    static struct rx_features features;
+    static int probed;
+
+    if (!probed) {
+        if (sys_system_get_features(&features) < 0) {
+            features = (struct rx_features){ .abi_version = 1 };
+        }
+        probed = 1;
+    }
+    return &features;
+}
+
+/**
+ * Whether the kernel implements syscall num
+ */
+static inline int rx_has_syscall(uint32_t num) {
+    return num / 64 < 4 && ((rx_features()->syscalls[num / 64] >> (num % 64)) & 1);
+}
+
+/**
+ * Create an I/O port range (privileged)
+ */
+static inline int64_t sys_ioport_create(uint16_t start, uint16_t count) {
+    return syscall2(SYS_IOPORT_CREATE, start, count);
+}
+
+/**
+ * Enable (1) or disable (0) an I/O port range for this process
+ */
+static inline int64_t sys_ioport_enable(uint32_t handle, int enable) {
+    return syscall2(SYS_IOPORT_ENABLE, handle, enable);
+}
+
+/**
+ * Read kernel log text from *cursor on (0 = oldest held), advancing it
+ *
+ * root_job needs the DEBUG right; init holds it as handle 1.
+ */
+static inline int64_t sys_debug_read(uint32_t root_job, void *buf, int64_t len, uint64_t *cursor) {
+    return syscall4(SYS_DEBUG_READ, root_job, (int64_t)buf, len, (int64_t)cursor);
+}
+
+/**
+ * Redraw the active VT from its text grid
+ */
+static inline int64_t sys_vt_refresh(void) {
+    return syscall1(SYS_VT_CONTROL, VT_REFRESH);
+}
+
+/**
+ * Mount a filesystem of type fs_type ("fat32", "tmpfs", "procfs", ...)
+ * at target (privileged)
+ *
+ * device is a block device path ("/dev/ram0p1"), or 0 for tmpfs and procfs.
+ */
+static inline int64_t sys_mount(const char *device, const char *target, const char *fs_type, uint32_t flags) {
+    return syscall4(SYS_MOUNT, (int64_t)device, (int64_t)target, (int64_t)fs_type, flags);
+}
+
+/**
+ * Unmount the filesystem at target (privileged)
+ */
+static inline int64_t sys_umount(const char *target) {
+    return syscall1(SYS_UMOUNT, (int64_t)target);
+}
+
+/**
+ * Whether the kernel has every RX_FEATURE_* bit in feature
+ */
+static inline int rx_has_feature(uint64_t feature) {
+    return (rx_features()->features & feature) == feature;
+}
+
 #endif // SYSCALL_H
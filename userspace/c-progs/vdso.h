@@ -0,0 +1,155 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Syscall-free clock reads for Rustux userspace programs
+//!
+//! The kernel maps a read-only time page into every process at
+//! VDSO_TIME_VADDR and refreshes it from the timer interrupt.
+//! clock_gettime() computes the time from the page and the TSC, and only
+//! makes the CLOCK_GET syscall when the page is missing or stale. The
+//! page is only read if the kernel reports it (RX_FEATURE_VDSO_TIME);
+//! touching the address on a kernel without it would fault.
+//!
+//! For cycle counting, vdso_tsc_frequency() converts raw TSC deltas to
+//! time and vdso_rdtscp() also returns the CPU the counter was read on.
+//!
+//! The layout must match `VdsoTimeData` in src/vdso.rs.
+
+#ifndef VDSO_H
+#define VDSO_H
+
+#include <stdint.h>
+#include "syscall.h"
+
+#define VDSO_TIME_VADDR   0x7fff00000000ULL
+#define VDSO_TIME_MAGIC   0x4F534456u  // "VDSO"
+
+#define VDSO_FLAG_USER_TSC       (1u << 0)  // rdtsc/rdtscp allowed
+#define VDSO_FLAG_RDTSCP         (1u << 1)  // rdtscp supported
+#define VDSO_FLAG_TSC_INVARIANT  (1u << 2)  // TSC in step across CPUs
+
+#define CLOCK_MONOTONIC   0
+#define CLOCK_BOOTTIME    3  // monotonic plus time suspended; always a syscall
+
+struct vdso_time_data {
+    uint32_t magic;
+    uint32_t version;
+    volatile uint32_t seq;
+    uint32_t shift;
+    volatile uint64_t mult;
+    volatile uint64_t tsc_base;
+    volatile uint64_t ns_base;
+    volatile uint64_t max_delta_tsc;
+    // Version 2
+    uint64_t tsc_freq_hz;
+    uint32_t flags;
+    uint32_t reserved;
+};
+
+struct timespec {
+    int64_t tv_sec;
+    int64_t tv_nsec;
+};
+
+static inline uint64_t vdso_rdtsc(void) {
+    uint32_t lo, hi;
+    __asm__ volatile ("rdtsc" : "=a" (lo), "=d" (hi));
+    return ((uint64_t)hi << 32) | lo;
+}
+
+static inline const struct vdso_time_data *vdso_time_page(void) {
+    const struct vdso_time_data *td = (const struct vdso_time_data *)VDSO_TIME_VADDR;
+
+    if (!rx_has_feature(RX_FEATURE_VDSO_TIME)) {
+        return 0;
+    }
+    return td->magic == VDSO_TIME_MAGIC ? td : 0;
+}
+
+/**
+ * Whether this process may read the TSC
+ *
+ * A version 1 page predates the user counter policy, which then allows it.
+ */
+static inline int vdso_user_tsc(const struct vdso_time_data *td) {
+    return td->version < 2 || (td->flags & VDSO_FLAG_USER_TSC);
+}
+
+/**
+ * TSC frequency in Hz, or 0 if unknown
+ */
+static inline uint64_t vdso_tsc_frequency(void) {
+    const struct vdso_time_data *td = vdso_time_page();
+    return td && td->version >= 2 ? td->tsc_freq_hz : 0;
+}
+
+/**
+ * Read the TSC and the number of the CPU it was read on
+ *
+ * Only valid when VDSO_FLAG_USER_TSC and VDSO_FLAG_RDTSCP are set.
+ */
+static inline uint64_t vdso_rdtscp(uint32_t *cpu) {
+    uint32_t lo, hi, aux;
+    __asm__ volatile ("rdtscp" : "=a" (lo), "=d" (hi), "=c" (aux));
+    *cpu = aux;
+    return ((uint64_t)hi << 32) | lo;
+}
+
+/**
+ * Read the clock from the time page
+ *
+ * Returns 0 and stores nanoseconds in *ns, or -1 if the page is missing
+ * or stale.
+ */
+static inline int vdso_clock_ns(uint64_t *ns) {
+    const struct vdso_time_data *td = vdso_time_page();
+    uint32_t seq;
+    uint64_t mult, tsc_base, ns_base, max_delta, delta;
+
+    if (!td || !vdso_user_tsc(td)) {
+        return -1;
+    }
+
+    do {
+        seq = td->seq;
+        __asm__ volatile ("" ::: "memory");
+        mult = td->mult;
+        tsc_base = td->tsc_base;
+        ns_base = td->ns_base;
+        max_delta = td->max_delta_tsc;
+        __asm__ volatile ("" ::: "memory");
+    } while ((seq & 1) || seq != td->seq);
+
+    delta = vdso_rdtsc() - tsc_base;
+    if (delta > max_delta) {
+        return -1;
+    }
+
+    *ns = ns_base + (uint64_t)(((unsigned __int128)delta * mult) >> td->shift);
+    return 0;
+}
+
+/**
+ * Get the current time (syscall fallback when the page is stale or the
+ * clock is not on the page)
+ */
+static inline int clock_gettime(int clock_id, struct timespec *ts) {
+    uint64_t ns;
+
+    if (clock_id != CLOCK_MONOTONIC || vdso_clock_ns(&ns) != 0) {
+        int64_t ret = sys_clock_get(clock_id);
+        if (ret < 0) {
+            return (int)ret;
+        }
+        ns = (uint64_t)ret;
+    }
+
+    ts->tv_sec = (int64_t)(ns / 1000000000ULL);
+    ts->tv_nsec = (int64_t)(ns % 1000000000ULL);
+    return 0;
+}
+
+#endif // VDSO_H
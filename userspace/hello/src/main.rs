@@ -0,0 +1,87 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Hello World in Rust for Rustux
+//!
+//! The Rust counterpart of `c-progs/hello.c`: prints a greeting and its
+//! PID with `sys_write`, then exits with `sys_exit`.
+
+#![no_std]
+#![no_main]
+
+use core::arch::asm;
+
+// Syscall numbers (see docs/SYSCALL.md)
+const SYS_PROCESS_EXIT: u64 = 0x06;
+const SYS_WRITE: u64 = 0x60;
+const SYS_GETPID: u64 = 0x70;
+
+const STDOUT_FILENO: u64 = 1;
+
+/// Make a syscall with up to 3 arguments
+#[inline(always)]
+unsafe fn syscall3(num: u64, arg1: u64, arg2: u64, arg3: u64) -> i64 {
+    let ret: i64;
+    asm!(
+        "int 0x80",
+        inlateout("rax") num as i64 => ret,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        in("rdx") arg3,
+        lateout("rcx") _,
+        lateout("r11") _,
+        options(nostack)
+    );
+    ret
+}
+
+fn write(s: &[u8]) {
+    unsafe {
+        syscall3(SYS_WRITE, STDOUT_FILENO, s.as_ptr() as u64, s.len() as u64);
+    }
+}
+
+fn exit(code: u64) -> ! {
+    unsafe {
+        syscall3(SYS_PROCESS_EXIT, code, 0, 0);
+    }
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Format `n` in decimal into `buf`, returning the digits
+fn format_decimal(mut n: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    &buf[i..]
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    write(b"Hello from Rust userspace!\n");
+
+    let pid = unsafe { syscall3(SYS_GETPID, 0, 0, 0) };
+    let mut buf = [0u8; 20];
+    write(b"My PID is: ");
+    write(format_decimal(pid.max(0) as u64, &mut buf));
+    write(b"\n");
+
+    exit(0);
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    write(b"hello: panic\n");
+    exit(1);
+}
@@ -4,17 +4,18 @@
 set -e
 
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
-KERNEL_DIR="$(dirname "$SCRIPT_DIR")"
+WORKSPACE_DIR="$(dirname "$SCRIPT_DIR")"
 
 echo "Building userspace test program..."
 
 cd "$SCRIPT_DIR"
 
-# Build the userspace program
-cargo build --release --target x86_64-unknown-none
+# Build the userspace program (target and linker script come from the
+# userspace workspace, see ../.cargo/config.toml)
+cargo build --release -p rustux-userspace-test
 
 # Get the ELF file
-ELF_FILE="target/x86_64-unknown-none/release/rustux-userspace-test"
+ELF_FILE="$WORKSPACE_DIR/target/x86_64-rustux-user/release/rustux-userspace-test"
 
 if [ ! -f "$ELF_FILE" ]; then
     echo "Error: Build failed - ELF file not found"
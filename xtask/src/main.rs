@@ -0,0 +1,443 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Build automation for Rustux
+//!
+//! Run from the kernel directory through the cargo alias:
+//!
+//! ```text
+//! cargo xtask userspace    Build all userspace workspace members and stage
+//!                          their ELFs in target/userspace/ for the ramdisk
+//! cargo xtask run          Build everything and boot it in QEMU
+//! cargo xtask test         Build with `kernel_test`, boot in QEMU and report
+//!                          the result from the isa-debug-exit code
+//! cargo xtask gdb          Like `run`, but QEMU waits for GDB on :1234
+//! ```
+//!
+//! Staged binaries are picked up by build.rs through `ramdisk.toml`.
+//!
+//! # Options
+//!
+//! - `--no-userspace`: skip `cargo xtask userspace` (use staged binaries)
+//! - `--smp <n>`: number of CPUs (default 1)
+//! - `--timeout <secs>`: `test` only, default 60
+//!
+//! # Firmware
+//!
+//! OVMF is searched in the usual distribution locations. Set `OVMF_CODE`
+//! (and optionally `OVMF_VARS`) to use a specific build.
+//!
+//! # Test Exit Codes
+//!
+//! The test kernel writes its result to the isa-debug-exit port (0xF4)
+//! and QEMU exits with `(code << 1) | 1`: 1 is a pass, 3 a failure.
+//! Anything else (crash, triple fault, timeout) is reported as an error.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Userspace workspace (relative to the kernel directory)
+const USERSPACE_DIR: &str = "userspace";
+
+/// Target spec name used by the userspace workspace
+const USERSPACE_TARGET: &str = "x86_64-rustux-user";
+
+/// Where built userspace binaries are staged for the ramdisk
+const STAGING_DIR: &str = "target/userspace";
+
+/// Ramdisk manifest read by build.rs
+const RAMDISK_MANIFEST: &str = "ramdisk.toml";
+
+/// Kernel target and output
+const KERNEL_TARGET: &str = "x86_64-unknown-uefi";
+const KERNEL_EFI: &str = "target/x86_64-unknown-uefi/release/rustux.efi";
+
+/// Linker map of the last kernel link, and the copy build.rs reads the
+/// symbol table from (see src/ksyms.rs)
+const KERNEL_MAP: &str = "target/rustux.map";
+const KERNEL_SYMBOL_MAP: &str = "target/rustux-symbols.map";
+
+/// Give up on a settled symbol table after this many links
+const MAX_KERNEL_LINKS: usize = 3;
+
+/// EFI system partition directory (served to QEMU as a FAT drive)
+const ESP_DIR: &str = "target/esp";
+
+/// Debug console (port 0xE9) log
+const DEBUG_LOG: &str = "target/rustux-debug.log";
+
+/// OVMF locations: (code, vars) pairs. A missing vars file means the
+/// firmware is a combined image and is passed with `-bios`.
+const OVMF_CANDIDATES: &[(&str, Option<&str>)] = &[
+    ("/usr/share/OVMF/OVMF_CODE_4M.fd", Some("/usr/share/OVMF/OVMF_VARS_4M.fd")),
+    ("/usr/share/OVMF/OVMF_CODE.fd", Some("/usr/share/OVMF/OVMF_VARS.fd")),
+    ("/usr/share/edk2/x64/OVMF_CODE.fd", Some("/usr/share/edk2/x64/OVMF_VARS.fd")),
+    ("/usr/share/edk2-ovmf/x64/OVMF_CODE.fd", Some("/usr/share/edk2-ovmf/x64/OVMF_VARS.fd")),
+    ("/usr/share/edk2/ovmf/OVMF_CODE.fd", Some("/usr/share/edk2/ovmf/OVMF_VARS.fd")),
+    ("/usr/share/qemu/OVMF.fd", None),
+    ("/usr/share/ovmf/OVMF.fd", None),
+];
+
+/// QEMU exit status for a passing test (`(0 << 1) | 1`)
+const QEMU_EXIT_PASS: i32 = 1;
+
+/// QEMU exit status for a failing test (`(1 << 1) | 1`)
+const QEMU_EXIT_FAIL: i32 = 3;
+
+type Result<T> = std::result::Result<T, String>;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("userspace") => build_userspace().map(|_| ()),
+        Some(cmd @ ("run" | "test" | "gdb")) => Options::parse(&args[1..]).and_then(|opts| match cmd {
+            "run" => run_qemu(&opts, Mode::Run),
+            "gdb" => run_qemu(&opts, Mode::Gdb),
+            _ => run_tests(&opts),
+        }),
+        Some("help") | Some("--help") | None => {
+            usage();
+            Ok(())
+        }
+        Some(other) => Err(format!("unknown command: {}", other)),
+    };
+
+    if let Err(e) = result {
+        eprintln!("xtask: {}", e);
+        process::exit(1);
+    }
+}
+
+fn usage() {
+    println!("Usage: cargo xtask <command>");
+    println!();
+    println!("Commands:");
+    println!("  userspace   Build all userspace programs and stage them for the ramdisk");
+    println!("  run         Build userspace and kernel, boot in QEMU");
+    println!("  test        Build the test kernel, boot in QEMU, report pass/fail");
+    println!("  gdb         Like run, but wait for GDB on localhost:1234");
+    println!();
+    println!("Options (run/test/gdb):");
+    println!("  --no-userspace    Use already staged userspace binaries");
+    println!("  --smp <n>         Number of CPUs (default 1)");
+    println!("  --timeout <secs>  Test timeout (default 60)");
+}
+
+/// Options for run/test/gdb
+struct Options {
+    userspace: bool,
+    smp: u32,
+    timeout: Duration,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut opts = Options { userspace: true, smp: 1, timeout: Duration::from_secs(60) };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| -> Result<u64> {
+                args.next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| format!("{} needs a number", name))
+            };
+            match arg.as_str() {
+                "--no-userspace" => opts.userspace = false,
+                "--smp" => opts.smp = value("--smp")? as u32,
+                "--timeout" => opts.timeout = Duration::from_secs(value("--timeout")?),
+                other => return Err(format!("unknown option: {}", other)),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// How QEMU is launched
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Interactive, with a display
+    Run,
+    /// Interactive, paused until GDB attaches
+    Gdb,
+    /// Headless test kernel
+    Test,
+}
+
+/// Kernel directory (parent of the xtask crate)
+fn kernel_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf()
+}
+
+/// Run a command, failing on a non-zero exit status
+fn run(cmd: &mut Command) -> Result<()> {
+    let status = cmd.status().map_err(|e| format!("failed to run {:?}: {}", cmd, e))?;
+    if !status.success() {
+        return Err(format!("{:?} failed with {}", cmd, status));
+    }
+    Ok(())
+}
+
+/// Build the kernel EFI binary
+///
+/// The toolchain is inherited (e.g. `cargo +nightly xtask run`). The
+/// symbol table embedded in the kernel comes from the linker map of the
+/// previous link, so the kernel is linked until the map stops changing:
+/// twice, or three times if embedding the table moved code.
+fn build_kernel(features: &str) -> Result<()> {
+    println!("[xtask] Building kernel ({})...", features);
+    let root = kernel_dir();
+    let map = root.join(KERNEL_MAP);
+    let symbols = root.join(KERNEL_SYMBOL_MAP);
+    let _ = fs::remove_file(&symbols);
+
+    for _ in 0..MAX_KERNEL_LINKS {
+        let mut cmd = Command::new("cargo");
+        cmd.current_dir(&root)
+            .args(["rustc", "--release", "--bin", "rustux", "--target", KERNEL_TARGET, "--features", features])
+            .args(["--", "-C"])
+            .arg(format!("link-arg=-map:{}", map.display()));
+        if symbols.exists() {
+            cmd.env("RUSTUX_SYMBOL_MAP", &symbols);
+        } else {
+            cmd.env_remove("RUSTUX_SYMBOL_MAP");
+        }
+        run(&mut cmd)?;
+
+        let anchor = symbol_address(&map, "rx_ksyms_anchor")?;
+        if symbols.exists() && symbol_address(&symbols, "rx_ksyms_anchor")? == anchor {
+            return Ok(());
+        }
+        fs::copy(&map, &symbols).map_err(|e| format!("{}: {}", map.display(), e))?;
+        println!("[xtask] Linking again to embed kernel symbols...");
+    }
+    println!("[xtask] warning: kernel symbols did not settle; backtraces will show addresses only");
+    Ok(())
+}
+
+/// Address of a symbol in an lld-link map, as written in the map
+fn symbol_address(map: &Path, name: &str) -> Result<Option<String>> {
+    let text = fs::read_to_string(map).map_err(|e| format!("{}: {}", map.display(), e))?;
+    Ok(text.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        (fields.get(1) == Some(&name)).then(|| fields.get(2).unwrap_or(&"").to_string())
+    }))
+}
+
+/// Lay out the EFI system partition for QEMU's FAT driver
+fn assemble_esp() -> Result<PathBuf> {
+    let root = kernel_dir();
+    let esp = root.join(ESP_DIR);
+    let boot = esp.join("EFI").join("BOOT");
+    fs::create_dir_all(&boot).map_err(|e| format!("{}: {}", boot.display(), e))?;
+    fs::copy(root.join(KERNEL_EFI), boot.join("BOOTX64.EFI"))
+        .map_err(|e| format!("{}: {}", KERNEL_EFI, e))?;
+    Ok(esp)
+}
+
+/// Locate OVMF firmware
+///
+/// Returns the QEMU arguments that load it. Split images get a private
+/// copy of the variable store so runs do not modify the system file.
+fn firmware_args() -> Result<Vec<String>> {
+    let from_env = env::var("OVMF_CODE").ok().map(|code| (code, env::var("OVMF_VARS").ok()));
+    let found = from_env.or_else(|| {
+        OVMF_CANDIDATES
+            .iter()
+            .find(|(code, _)| Path::new(code).exists())
+            .map(|(code, vars)| (code.to_string(), vars.filter(|v| Path::new(v).exists()).map(String::from)))
+    });
+    let (code, vars) = found.ok_or("OVMF firmware not found (install ovmf or set OVMF_CODE)")?;
+
+    match vars {
+        None => Ok(vec!["-bios".into(), code]),
+        Some(vars) => {
+            let copy = kernel_dir().join("target").join("OVMF_VARS.fd");
+            fs::copy(&vars, &copy).map_err(|e| format!("{}: {}", vars, e))?;
+            Ok(vec![
+                "-drive".into(),
+                format!("if=pflash,format=raw,readonly=on,file={}", code),
+                "-drive".into(),
+                format!("if=pflash,format=raw,file={}", copy.display()),
+            ])
+        }
+    }
+}
+
+/// Build the QEMU command line
+fn qemu_command(opts: &Options, mode: Mode, esp: &Path) -> Result<Command> {
+    let log = kernel_dir().join(DEBUG_LOG);
+    let mut cmd = Command::new("qemu-system-x86_64");
+    cmd.args(["-machine", "q35", "-m", "512M", "-no-reboot"])
+        .args(["-smp", &opts.smp.to_string()])
+        .args(firmware_args()?)
+        .args(["-drive", &format!("format=raw,file=fat:rw:{}", esp.display())])
+        .args(["-device", "isa-debugcon,iobase=0xE9,chardev=debug"])
+        .args(["-chardev", &format!("file,id=debug,path={}", log.display())])
+        .args(["-device", "isa-debug-exit,iobase=0xf4,iosize=0x04"]);
+
+    match mode {
+        Mode::Run => {
+            cmd.args(["-serial", "stdio"]);
+        }
+        Mode::Gdb => {
+            cmd.args(["-serial", "stdio", "-s", "-S"]);
+        }
+        Mode::Test => {
+            cmd.args(["-display", "none", "-serial", "null"]).stdin(Stdio::null());
+        }
+    }
+    Ok(cmd)
+}
+
+/// `run` and `gdb`: build everything and boot interactively
+fn run_qemu(opts: &Options, mode: Mode) -> Result<()> {
+    if opts.userspace {
+        build_userspace()?;
+    }
+    build_kernel("uefi_kernel")?;
+    let esp = assemble_esp()?;
+
+    if mode == Mode::Gdb {
+        println!("[xtask] QEMU is waiting for GDB:");
+        println!("[xtask]   gdb -ex 'target remote localhost:1234'");
+    }
+    println!("[xtask] Debug console: {}", DEBUG_LOG);
+    run(&mut qemu_command(opts, mode, &esp)?)
+}
+
+/// `test`: boot the test kernel and map QEMU's exit status to a result
+fn run_tests(opts: &Options) -> Result<()> {
+    if opts.userspace {
+        build_userspace()?;
+    }
+    build_kernel("uefi_kernel,kernel_test")?;
+    let esp = assemble_esp()?;
+
+    println!("[xtask] Booting test kernel (timeout {}s)...", opts.timeout.as_secs());
+    let mut child = qemu_command(opts, Mode::Test, &esp)?
+        .spawn()
+        .map_err(|e| format!("failed to start qemu-system-x86_64: {}", e))?;
+
+    let deadline = Instant::now() + opts.timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let log = kernel_dir().join(DEBUG_LOG);
+    let result = match status.and_then(|s| s.code()) {
+        Some(QEMU_EXIT_PASS) => Ok(()),
+        Some(QEMU_EXIT_FAIL) => Err("kernel tests failed".to_string()),
+        Some(code) => Err(format!("QEMU exited with unexpected status {} (kernel crash?)", code)),
+        None if status.is_none() => Err(format!("timed out after {}s", opts.timeout.as_secs())),
+        None => Err("QEMU was killed by a signal".to_string()),
+    };
+
+    match &result {
+        Ok(()) => println!("[xtask] Kernel tests passed"),
+        Err(_) => {
+            // Show the end of the debug console to explain the failure
+            let text = fs::read_to_string(&log).unwrap_or_default();
+            let lines: Vec<&str> = text.lines().collect();
+            for line in &lines[lines.len().saturating_sub(30)..] {
+                eprintln!("  | {}", line);
+            }
+        }
+    }
+    println!("[xtask] Debug console: {}", DEBUG_LOG);
+    result
+}
+
+/// A `cargo` command that does not inherit the outer cargo's toolchain
+///
+/// The userspace workspace pins its own toolchain (rust-toolchain.toml).
+fn cargo() -> Command {
+    let mut cmd = Command::new("cargo");
+    for var in ["RUSTUP_TOOLCHAIN", "RUSTC", "RUSTC_WRAPPER", "RUSTFLAGS", "CARGO_TARGET_DIR"] {
+        cmd.env_remove(var);
+    }
+    cmd
+}
+
+/// Extract the string value of `key = "value"` from a TOML line
+fn toml_string<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (k, v) = line.split_once('=')?;
+    if k.trim() != key {
+        return None;
+    }
+    v.trim().strip_prefix('"')?.strip_suffix('"')
+}
+
+/// Binary names of the userspace workspace members
+///
+/// Reads the `members` array of the workspace manifest and the package
+/// name of each member.
+fn userspace_members(workspace: &Path) -> Result<Vec<String>> {
+    let manifest = workspace.join("Cargo.toml");
+    let text = fs::read_to_string(&manifest)
+        .map_err(|e| format!("{}: {}", manifest.display(), e))?;
+
+    let start = text
+        .lines()
+        .position(|l| l.trim_start().starts_with("members"))
+        .ok_or("workspace has no members")?;
+    let list = text.lines().skip(start).collect::<Vec<_>>().join("\n");
+    let list = &list[list.find('[').ok_or("malformed members")? + 1..list.find(']').ok_or("malformed members")?];
+
+    let mut names = Vec::new();
+    for member in list.split(',').map(|m| m.trim().trim_matches('"')).filter(|m| !m.is_empty()) {
+        let member_manifest = workspace.join(member).join("Cargo.toml");
+        let text = fs::read_to_string(&member_manifest)
+            .map_err(|e| format!("{}: {}", member_manifest.display(), e))?;
+        let name = text
+            .lines()
+            .find_map(|l| toml_string(l, "name"))
+            .ok_or_else(|| format!("{}: no package name", member_manifest.display()))?;
+        names.push(name.to_string());
+    }
+    Ok(names)
+}
+
+/// Build the userspace workspace and stage the binaries
+///
+/// Returns the staged paths (relative to the kernel directory).
+fn build_userspace() -> Result<Vec<String>> {
+    let root = kernel_dir();
+    let workspace = root.join(USERSPACE_DIR);
+
+    println!("[xtask] Building userspace workspace...");
+    run(cargo().current_dir(&workspace).args(["build", "--release"]))?;
+
+    let staging = root.join(STAGING_DIR);
+    fs::create_dir_all(&staging).map_err(|e| format!("{}: {}", staging.display(), e))?;
+
+    let manifest = fs::read_to_string(root.join(RAMDISK_MANIFEST)).unwrap_or_default();
+    let out_dir = workspace.join("target").join(USERSPACE_TARGET).join("release");
+
+    let mut staged = Vec::new();
+    for name in userspace_members(&workspace)? {
+        let elf = out_dir.join(&name);
+        let dest = format!("{}/{}.elf", STAGING_DIR, name);
+        fs::copy(&elf, root.join(&dest)).map_err(|e| format!("{}: {}", elf.display(), e))?;
+        println!("[xtask]   {} -> {}", name, dest);
+
+        if !manifest.lines().any(|l| toml_string(l, "src") == Some(dest.as_str())) {
+            println!("[xtask]   note: {} is not listed in {}, it will not be packed", dest, RAMDISK_MANIFEST);
+        }
+        staged.push(dest);
+    }
+    Ok(staged)
+}